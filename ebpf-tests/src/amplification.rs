@@ -0,0 +1,184 @@
+//! Userspace mirror of the UDP amplification heuristics
+//!
+//! `check_amplification_attack` in `ebpf/src/xdp_udp.rs` inspects responses
+//! from well-known reflection ports. The per-protocol decisions are
+//! reproduced here over the UDP payload so they can be tested against the
+//! response builders in `packet_generator`. Keep in sync with the eBPF crate.
+
+use crate::packet_generator::{PORT_DNS, PORT_MEMCACHED, PORT_NTP, PORT_SSDP};
+
+pub const PORT_SNMP: u16 = 161;
+pub const PORT_CHARGEN: u16 = 19;
+pub const PORT_QOTD: u16 = 17;
+pub const PORT_LDAP: u16 = 389;
+pub const PORT_MSSQL: u16 = 1434;
+pub const PORT_RIP: u16 = 520;
+pub const PORT_PORTMAP: u16 = 111;
+pub const PORT_NETBIOS: u16 = 137;
+pub const PORT_CLDAP: u16 = 636;
+pub const PORT_TFTP: u16 = 69;
+
+const DNS_FLAG_RESPONSE: u16 = 0x8000;
+const NTP_MODE_MASK: u8 = 0x07;
+const NTP_MODE_SERVER: u8 = 4;
+const NTP_MODE_BROADCAST: u8 = 5;
+
+/// Outcome of the amplification check for one datagram
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AmpVerdict {
+    /// Counted as amplification (`update_stats_amplification`)
+    pub detected: bool,
+    /// Source recorded in `AMP_SOURCES` (`track_amp_source`)
+    pub tracked: bool,
+    /// Dropped at the given protection level
+    pub drop: bool,
+}
+
+impl AmpVerdict {
+    fn detected(tracked: bool, drop: bool) -> Self {
+        Self {
+            detected: true,
+            tracked,
+            drop,
+        }
+    }
+}
+
+/// Whether `src_port` is a known reflection port
+pub fn is_amp_source(src_port: u16) -> bool {
+    matches!(
+        src_port,
+        PORT_DNS
+            | PORT_NTP
+            | PORT_SSDP
+            | PORT_SNMP
+            | PORT_MEMCACHED
+            | PORT_CHARGEN
+            | PORT_QOTD
+            | PORT_LDAP
+            | PORT_MSSQL
+            | PORT_RIP
+            | PORT_PORTMAP
+            | PORT_NETBIOS
+            | PORT_CLDAP
+            | PORT_TFTP
+    )
+}
+
+/// Run the amplification heuristics on a UDP payload from `src_port`
+pub fn check_amplification(src_port: u16, payload: &[u8], protection_level: u8) -> AmpVerdict {
+    if !is_amp_source(src_port) {
+        return AmpVerdict::default();
+    }
+
+    let payload_len = payload.len().min(u16::MAX as usize) as u16;
+
+    match src_port {
+        PORT_DNS => check_dns(payload, payload_len, protection_level),
+        PORT_NTP => check_ntp(payload, payload_len, protection_level),
+        PORT_SSDP | PORT_SNMP if payload_len > 200 => {
+            AmpVerdict::detected(true, protection_level >= 2)
+        }
+        PORT_MEMCACHED => check_memcached(payload, payload_len, protection_level),
+        PORT_CHARGEN | PORT_QOTD => AmpVerdict::detected(false, protection_level >= 1),
+        PORT_LDAP | PORT_CLDAP if payload_len > 100 => {
+            AmpVerdict::detected(true, protection_level >= 2)
+        }
+        PORT_SSDP | PORT_SNMP | PORT_LDAP | PORT_CLDAP => AmpVerdict::default(),
+        _ if payload_len > 500 => AmpVerdict::detected(true, false),
+        _ => AmpVerdict::default(),
+    }
+}
+
+fn check_dns(payload: &[u8], payload_len: u16, protection_level: u8) -> AmpVerdict {
+    if payload.len() < 12 {
+        return AmpVerdict::default();
+    }
+
+    let flags = u16::from_be_bytes([payload[2], payload[3]]);
+    let qdcount = u16::from_be_bytes([payload[4], payload[5]]);
+    let ancount = u16::from_be_bytes([payload[6], payload[7]]);
+
+    let is_response = (flags & DNS_FLAG_RESPONSE) != 0;
+    let opcode = (flags >> 11) & 0x0f;
+    let valid_opcode = opcode <= 5;
+
+    let amp_ratio_suspicious = ancount > 10 && qdcount <= 2;
+    let is_large = payload_len > 512;
+
+    if !(is_response && valid_opcode) {
+        return AmpVerdict::default();
+    }
+
+    let is_amplification = amp_ratio_suspicious || (is_large && ancount > qdcount.wrapping_mul(5));
+    if !(is_amplification || (is_large && payload_len > 1024)) {
+        return AmpVerdict::default();
+    }
+
+    let drop = (protection_level >= 2 && (amp_ratio_suspicious || payload_len > 1024))
+        || (protection_level >= 3 && is_large);
+    AmpVerdict::detected(true, drop)
+}
+
+fn check_ntp(payload: &[u8], payload_len: u16, protection_level: u8) -> AmpVerdict {
+    let Some(&first_byte) = payload.first() else {
+        return AmpVerdict::default();
+    };
+
+    let mode = first_byte & NTP_MODE_MASK;
+    let version = (first_byte >> 3) & 0x07;
+    let valid_version = (1..=4).contains(&version);
+
+    let mut verdict = AmpVerdict::default();
+
+    if mode == 7 {
+        verdict = AmpVerdict::detected(true, false);
+        if protection_level >= 1 {
+            verdict.drop = true;
+            return verdict;
+        }
+    }
+
+    if mode == 6 && payload_len > 12 {
+        verdict = AmpVerdict::detected(true, false);
+        if protection_level >= 2 {
+            verdict.drop = true;
+            return verdict;
+        }
+    }
+
+    let response_mode = mode == NTP_MODE_SERVER || mode == NTP_MODE_BROADCAST;
+
+    if response_mode && valid_version && payload_len > 48 {
+        verdict = AmpVerdict::detected(true, false);
+        if protection_level >= 2 && payload_len > 200 {
+            verdict.drop = true;
+            return verdict;
+        }
+    }
+
+    if !valid_version && (response_mode || mode == 6 || mode == 7) {
+        verdict.detected = true;
+        if protection_level >= 2 {
+            verdict.drop = true;
+        }
+    }
+
+    verdict
+}
+
+fn check_memcached(payload: &[u8], payload_len: u16, protection_level: u8) -> AmpVerdict {
+    let Some(&magic_byte) = payload.first() else {
+        return AmpVerdict::default();
+    };
+
+    let is_binary_protocol = magic_byte == 0x80 || magic_byte == 0x81;
+    let is_large_response = payload_len > 100;
+
+    if !(is_binary_protocol || is_large_response) {
+        return AmpVerdict::default();
+    }
+
+    let drop = protection_level >= 1 && (is_binary_protocol || payload_len > 500);
+    AmpVerdict::detected(true, drop)
+}
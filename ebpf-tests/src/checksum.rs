@@ -0,0 +1,140 @@
+//! Userspace mirror of the eBPF checksum and rewriting helpers
+//!
+//! The XDP_TX helpers in `ebpf/src/lib.rs` sum header fields exactly as they
+//! sit in the packet (network order read as native words), relying on the
+//! one's complement sum being byte-order independent. These mirrors keep that
+//! shape over byte slices so the tests can check them against big-endian
+//! reference sums. Keep in sync with the eBPF crate.
+
+/// Bytes summed by `csum_add_bytes` before it stops (matches `csum::MAX_BYTES`)
+pub const CSUM_MAX_BYTES: usize = 1500;
+
+/// ICMPv4 protocol number
+pub const IPPROTO_ICMP: u8 = 1;
+
+/// ICMPv4 echo reply type
+pub const ICMP_ECHO_REPLY: u8 = 0;
+
+/// ICMPv4 echo request type
+pub const ICMP_ECHO_REQUEST: u8 = 8;
+
+/// Fold a 32-bit one's complement sum to 16 bits and complement it
+pub fn csum_fold(sum: u32) -> u16 {
+    let sum = (sum & 0xffff) + (sum >> 16);
+    let sum = (sum & 0xffff) + (sum >> 16);
+    !(sum as u16)
+}
+
+/// Add `bytes` to a running (unfolded) one's complement sum
+pub fn csum_add_bytes(sum: u32, bytes: &[u8]) -> u32 {
+    let mut sum = sum;
+    let len = bytes.len();
+    let mut i = 0;
+    while i + 1 < len && i < CSUM_MAX_BYTES {
+        sum += u16::from_ne_bytes([bytes[i], bytes[i + 1]]) as u32;
+        i += 2;
+    }
+    if i < len && i < CSUM_MAX_BYTES {
+        sum += u16::from_ne_bytes([bytes[i], 0]) as u32;
+    }
+    sum
+}
+
+/// Update a checksum after a 16-bit field changed from `old` to `new`
+pub fn csum_replace2(check: u16, old: u16, new: u16) -> u16 {
+    csum_fold(!check as u32 + !old as u32 + new as u32)
+}
+
+/// Update a checksum after a 32-bit field changed from `old` to `new`
+pub fn csum_replace4(check: u16, old: u32, new: u32) -> u16 {
+    let old_hi = (old >> 16) as u16;
+    let old_lo = old as u16;
+    csum_fold(!check as u32 + !old_hi as u32 + !old_lo as u32 + (new >> 16) + (new & 0xffff))
+}
+
+/// Checksum of the 20-byte IPv4 header at the start of `header`, skipping
+/// the checksum field
+pub fn ipv4_csum(header: &[u8]) -> u16 {
+    let sum = csum_add_bytes(0, &header[..10]);
+    csum_fold(csum_add_bytes(sum, &header[12..20]))
+}
+
+/// Unfolded sum of the IPv4 pseudo-header (addresses as read from the packet)
+pub fn ipv4_pseudo_csum(saddr: u32, daddr: u32, protocol: u8, len: u16) -> u32 {
+    (saddr >> 16)
+        + (saddr & 0xffff)
+        + (daddr >> 16)
+        + (daddr & 0xffff)
+        + (protocol as u16).to_be() as u32
+        + len.to_be() as u32
+}
+
+/// Unfolded sum of the IPv6 pseudo-header
+pub fn ipv6_pseudo_csum(saddr: &[u8; 16], daddr: &[u8; 16], nexthdr: u8, len: u32) -> u32 {
+    let sum = csum_add_bytes(0, saddr);
+    let sum = csum_add_bytes(sum, daddr);
+    sum + ((len >> 16) as u16).to_be() as u32
+        + (len as u16).to_be() as u32
+        + (nexthdr as u16).to_be() as u32
+}
+
+/// Read the 16-bit field at `offset` as it sits in the packet
+pub fn raw_u16(packet: &[u8], offset: usize) -> u16 {
+    u16::from_ne_bytes([packet[offset], packet[offset + 1]])
+}
+
+/// Read the 32-bit field at `offset` as it sits in the packet
+pub fn raw_u32(packet: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes([
+        packet[offset],
+        packet[offset + 1],
+        packet[offset + 2],
+        packet[offset + 3],
+    ])
+}
+
+fn write_raw_u16(packet: &mut [u8], offset: usize, value: u16) {
+    packet[offset..offset + 2].copy_from_slice(&value.to_ne_bytes());
+}
+
+/// Set the TTL of the IPv4 header at `l3`, updating its checksum
+pub fn ipv4_set_ttl(packet: &mut [u8], l3: usize, ttl: u8) {
+    let old = raw_u16(packet, l3 + 8);
+    packet[l3 + 8] = ttl;
+    let new = raw_u16(packet, l3 + 8);
+    let check = csum_replace2(raw_u16(packet, l3 + 10), old, new);
+    write_raw_u16(packet, l3 + 10, check);
+}
+
+/// Swap the Ethernet source and destination addresses
+pub fn swap_eth_addrs(frame: &mut [u8]) {
+    let (dst, rest) = frame.split_at_mut(6);
+    dst.swap_with_slice(&mut rest[..6]);
+}
+
+/// Swap the addresses of the IPv4 header at `l3`
+pub fn swap_ipv4_addrs(packet: &mut [u8], l3: usize) {
+    let (src, dst) = packet[l3 + 12..l3 + 20].split_at_mut(4);
+    src.swap_with_slice(dst);
+}
+
+/// Swap the ports of the TCP or UDP header at `l4`
+pub fn swap_ports(packet: &mut [u8], l4: usize) {
+    let (src, dst) = packet[l4..l4 + 4].split_at_mut(2);
+    src.swap_with_slice(dst);
+}
+
+/// Checksum of an ICMPv4 message, skipping its checksum field
+pub fn icmp_csum(message: &[u8]) -> u16 {
+    let sum = csum_add_bytes(0, &message[..2]);
+    csum_fold(csum_add_bytes(sum, &message[4..]))
+}
+
+/// Turn the ICMPv4 echo request at `offset` into the matching reply
+pub fn icmp_echo_to_reply(packet: &mut [u8], offset: usize) {
+    let old = raw_u16(packet, offset);
+    packet[offset] = ICMP_ECHO_REPLY;
+    let new = raw_u16(packet, offset);
+    let check = csum_replace2(raw_u16(packet, offset + 2), old, new);
+    write_raw_u16(packet, offset + 2, check);
+}
@@ -0,0 +1,96 @@
+//! Time source for the userspace reference filters
+//!
+//! The XDP programs read `bpf_ktime_get_ns`, a monotonic nanosecond clock.
+//! The reference filters and their maintenance passes read a [`Clock`]
+//! instead, so tests can drive a [`MockClock`] across rate limit windows,
+//! block expiry and SYN cookie lifetimes without sleeping.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Monotonic nanosecond clock (stands in for `bpf_ktime_get_ns`)
+pub trait Clock {
+    fn now_ns(&self) -> u64;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now_ns(&self) -> u64 {
+        (**self).now_ns()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now_ns(&self) -> u64 {
+        (**self).now_ns()
+    }
+}
+
+/// Real monotonic time, measured from construction
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now_ns(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+}
+
+/// Manually driven clock; clones share the same time
+///
+/// Hand one clone to the filter under test and keep another to advance it.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    now: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new(start_ns: u64) -> Self {
+        Self {
+            now: Arc::new(AtomicU64::new(start_ns)),
+        }
+    }
+
+    /// Move time forward by `by`
+    pub fn advance(&self, by: Duration) {
+        self.advance_ns(by.as_nanos() as u64);
+    }
+
+    /// Move time forward by `ns` nanoseconds
+    pub fn advance_ns(&self, ns: u64) {
+        self.now.fetch_add(ns, Ordering::SeqCst);
+    }
+
+    /// Jump to an absolute time; the clock is monotonic, so it never goes back
+    pub fn set_ns(&self, now_ns: u64) {
+        let previous = self.now.swap(now_ns, Ordering::SeqCst);
+        assert!(
+            now_ns >= previous,
+            "MockClock moved backwards from {} to {}",
+            previous,
+            now_ns
+        );
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ns(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
@@ -3,7 +3,15 @@
 //! This library provides packet generation utilities and test helpers
 //! for testing XDP packet filters in userspace.
 
+pub mod amplification;
+pub mod checksum;
+pub mod clock;
+pub mod minecraft;
 pub mod packet_generator;
+pub mod parser;
+pub mod raknet;
+pub mod scenario;
+pub mod syn_cookie;
 
 // Re-export commonly used items
 pub use packet_generator::*;
@@ -0,0 +1,149 @@
+//! Userspace mirror of the Minecraft Java parsing in `ebpf/src/xdp_minecraft.rs`
+//!
+//! `read_varint_at` and `validate_handshake` are reproduced over plain byte
+//! slices so the encoders in `packet_generator` can be checked against the
+//! exact decoding rules the filter applies. Keep in sync with the eBPF crate.
+
+/// Maximum VarInt bytes (5 for 32-bit values)
+pub const MAX_VARINT_BYTES: usize = 5;
+
+/// Hostname length limit used when no per-backend limit is configured
+pub const DEFAULT_MAX_HOSTNAME_LEN: usize = 255;
+
+/// Protocol version range accepted by default (1.7.2 up to a future-proof bound)
+pub const MIN_VALID_PROTOCOL: u32 = 4;
+pub const MAX_VALID_PROTOCOL: u32 = 1000;
+
+/// Handshake validation result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeResult {
+    pub valid: bool,
+    pub protocol_version: u32,
+    pub next_state: u8,
+}
+
+impl HandshakeResult {
+    fn invalid(protocol_version: u32, next_state: u8) -> Self {
+        Self {
+            valid: false,
+            protocol_version,
+            next_state,
+        }
+    }
+}
+
+/// Read a VarInt from the start of a buffer
+pub fn read_varint(buf: &[u8]) -> Option<(i32, usize)> {
+    read_varint_at(buf, 0)
+}
+
+/// Read a VarInt at a specific offset in a buffer
+/// Returns (value, bytes_consumed) or None if truncated, too long or overflowing
+pub fn read_varint_at(buf: &[u8], offset: usize) -> Option<(i32, usize)> {
+    let mut value: i32 = 0;
+    let mut position = 0;
+
+    for i in 0..MAX_VARINT_BYTES {
+        let byte = *buf.get(offset + i)?;
+        value |= ((byte & 0x7f) as i32) << position;
+
+        if byte & 0x80 == 0 {
+            // Bits 4-6 of the 5th byte would be bits 32-34 of the value
+            if position == 28 && (byte & 0x70) != 0 {
+                return None;
+            }
+            return Some((value, i + 1));
+        }
+
+        position += 7;
+    }
+
+    // 5th byte had the continuation bit set
+    None
+}
+
+/// Validate a Java handshake body
+///
+/// `packet_data` starts at the packet ID (after the length prefix) and
+/// `packet_len` is the value of the length prefix. Returns `None` when the
+/// packet is incomplete.
+pub fn validate_handshake(
+    packet_data: &[u8],
+    id_bytes: usize,
+    packet_len: usize,
+    min_proto: u32,
+    max_proto: u32,
+    max_hostname: usize,
+) -> Option<HandshakeResult> {
+    let data = packet_data.get(id_bytes..).filter(|d| !d.is_empty())?;
+    let mut offset = 0;
+
+    let (proto_version, proto_bytes) = read_varint_at(data, offset)?;
+    if proto_version < 0 {
+        return Some(HandshakeResult::invalid(0, 0));
+    }
+    offset += proto_bytes;
+
+    let proto_u32 = proto_version as u32;
+    if proto_u32 < min_proto || proto_u32 > max_proto {
+        return Some(HandshakeResult::invalid(proto_u32, 0));
+    }
+
+    let (hostname_len, hostname_len_bytes) = read_varint_at(data, offset)?;
+    if hostname_len < 0 {
+        return Some(HandshakeResult::invalid(proto_u32, 0));
+    }
+    offset += hostname_len_bytes;
+
+    let hostname_len = hostname_len as usize;
+    if hostname_len > max_hostname {
+        return Some(HandshakeResult::invalid(proto_u32, 0));
+    }
+
+    let hostname = data.get(offset..offset + hostname_len)?;
+    if hostname
+        .iter()
+        .take(DEFAULT_MAX_HOSTNAME_LEN)
+        .any(|&b| b == 0)
+    {
+        return Some(HandshakeResult::invalid(proto_u32, 0));
+    }
+    offset += hostname_len;
+
+    let port = data.get(offset..offset + 2)?;
+    let claimed_port = u16::from_be_bytes([port[0], port[1]]);
+    offset += 2;
+
+    if claimed_port == 0 {
+        return Some(HandshakeResult::invalid(proto_u32, 0));
+    }
+
+    let (next_state, next_state_bytes) = read_varint_at(data, offset)?;
+    offset += next_state_bytes;
+
+    if !(1..=3).contains(&next_state) {
+        return Some(HandshakeResult::invalid(proto_u32, next_state as u8));
+    }
+
+    // Length prefix must match the parsed content exactly
+    if id_bytes + offset != packet_len {
+        return Some(HandshakeResult::invalid(proto_u32, next_state as u8));
+    }
+
+    Some(HandshakeResult {
+        valid: true,
+        protocol_version: proto_u32,
+        next_state: next_state as u8,
+    })
+}
+
+/// Split a framed packet into (packet_len, packet_data) as the filter does
+///
+/// Returns `None` where the filter drops: an invalid or negative length.
+pub fn split_frame(payload: &[u8]) -> Option<(usize, &[u8])> {
+    let (packet_len, len_bytes) = read_varint(payload)?;
+    if packet_len < 0 {
+        return None;
+    }
+    Some((packet_len as usize, &payload[len_bytes..]))
+}
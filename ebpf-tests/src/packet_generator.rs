@@ -2,14 +2,26 @@
 //!
 //! Provides builders for creating test packets of various protocols.
 
-use std::net::Ipv4Addr;
-// Note: Ipv6Addr will be needed when IPv6 support is added
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 /// Ethernet header constants
 pub const ETH_P_IP: u16 = 0x0800;
 pub const ETH_P_IPV6: u16 = 0x86DD;
+pub const ETH_P_8021Q: u16 = 0x8100;
+pub const ETH_P_8021AD: u16 = 0x88A8;
 pub const IPPROTO_TCP: u8 = 6;
 pub const IPPROTO_UDP: u8 = 17;
+pub const IPPROTO_ICMPV6: u8 = 58;
+
+/// IPv6 extension header next header values
+pub const NEXTHDR_HOP: u8 = 0;
+pub const NEXTHDR_ROUTING: u8 = 43;
+pub const NEXTHDR_FRAGMENT: u8 = 44;
+pub const NEXTHDR_NONE: u8 = 59;
+pub const NEXTHDR_DEST: u8 = 60;
+
+/// IPv4 More Fragments flag (for `Ipv4Packet::with_fragment`)
+pub const IP_MF: u8 = 0x01;
 
 /// TCP flags
 pub const TCP_FIN: u8 = 0x01;
@@ -32,12 +44,25 @@ pub const RAKNET_UNCONNECTED_PING_OPEN: u8 = 0x02;
 pub const RAKNET_OPEN_CONNECTION_REQUEST_1: u8 = 0x05;
 pub const RAKNET_OPEN_CONNECTION_REQUEST_2: u8 = 0x07;
 pub const RAKNET_UNCONNECTED_PONG: u8 = 0x1c;
+pub const RAKNET_FRAME_SET: u8 = 0x84;
+
+/// RakNet frame reliabilities
+pub const RAKNET_UNRELIABLE: u8 = 0;
+pub const RAKNET_UNRELIABLE_SEQUENCED: u8 = 1;
+pub const RAKNET_RELIABLE: u8 = 2;
+pub const RAKNET_RELIABLE_ORDERED: u8 = 3;
+pub const RAKNET_RELIABLE_SEQUENCED: u8 = 4;
+
+/// Split flag of a RakNet frame header
+pub const RAKNET_FRAME_SPLIT: u8 = 0x10;
 
 /// Ethernet frame builder
 #[derive(Debug, Clone)]
 pub struct EthernetFrame {
     pub dst_mac: [u8; 6],
     pub src_mac: [u8; 6],
+    /// VLAN tags as (TPID, TCI), outermost first
+    pub vlan_tags: Vec<(u16, u16)>,
     pub ether_type: u16,
     pub payload: Vec<u8>,
 }
@@ -47,6 +72,7 @@ impl Default for EthernetFrame {
         Self {
             dst_mac: [0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
             src_mac: [0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+            vlan_tags: Vec::new(),
             ether_type: ETH_P_IP,
             payload: Vec::new(),
         }
@@ -73,15 +99,32 @@ impl EthernetFrame {
         self
     }
 
+    /// Add an 802.1Q tag with the given VLAN ID
+    pub fn with_vlan(mut self, vid: u16) -> Self {
+        self.vlan_tags.push((ETH_P_8021Q, vid & 0x0fff));
+        self
+    }
+
+    /// Add 802.1ad (QinQ) outer and 802.1Q inner tags
+    pub fn with_qinq(mut self, outer_vid: u16, inner_vid: u16) -> Self {
+        self.vlan_tags.push((ETH_P_8021AD, outer_vid & 0x0fff));
+        self.vlan_tags.push((ETH_P_8021Q, inner_vid & 0x0fff));
+        self
+    }
+
     pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
         self.payload = payload;
         self
     }
 
     pub fn build(&self) -> Vec<u8> {
-        let mut packet = Vec::with_capacity(14 + self.payload.len());
+        let mut packet = Vec::with_capacity(14 + self.vlan_tags.len() * 4 + self.payload.len());
         packet.extend_from_slice(&self.dst_mac);
         packet.extend_from_slice(&self.src_mac);
+        for &(tpid, tci) in &self.vlan_tags {
+            packet.extend_from_slice(&tpid.to_be_bytes());
+            packet.extend_from_slice(&tci.to_be_bytes());
+        }
         packet.extend_from_slice(&self.ether_type.to_be_bytes());
         packet.extend_from_slice(&self.payload);
         packet
@@ -159,6 +202,11 @@ impl Ipv4Packet {
         self
     }
 
+    pub fn with_identification(mut self, id: u16) -> Self {
+        self.identification = id;
+        self
+    }
+
     pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
         self.payload = payload;
         self
@@ -211,6 +259,227 @@ impl Ipv4Packet {
     }
 }
 
+/// IPv6 extension header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ipv6ExtHeader {
+    /// Hop-by-Hop Options with the given option TLVs (padded with PadN)
+    HopByHop(Vec<u8>),
+    /// Routing header
+    Routing {
+        routing_type: u8,
+        segments_left: u8,
+        /// Type-specific data after the first 4 bytes (padded to 8-byte units)
+        data: Vec<u8>,
+    },
+    /// Fragment header
+    Fragment {
+        /// Offset in 8-byte units
+        offset: u16,
+        more_fragments: bool,
+        identification: u32,
+    },
+    /// Destination Options with the given option TLVs (padded with PadN)
+    DestinationOptions(Vec<u8>),
+}
+
+impl Ipv6ExtHeader {
+    /// Next header value identifying this extension header
+    pub fn nexthdr(&self) -> u8 {
+        match self {
+            Self::HopByHop(_) => NEXTHDR_HOP,
+            Self::Routing { .. } => NEXTHDR_ROUTING,
+            Self::Fragment { .. } => NEXTHDR_FRAGMENT,
+            Self::DestinationOptions(_) => NEXTHDR_DEST,
+        }
+    }
+
+    /// Encode the header with `next` as its next header field
+    pub fn build(&self, next: u8) -> Vec<u8> {
+        match self {
+            Self::HopByHop(options) | Self::DestinationOptions(options) => {
+                let mut header = vec![next, 0];
+                header.extend_from_slice(options);
+                pad_options(&mut header);
+                header[1] = (header.len() / 8 - 1) as u8;
+                header
+            }
+            Self::Routing {
+                routing_type,
+                segments_left,
+                data,
+            } => {
+                let mut header = vec![next, 0, *routing_type, *segments_left];
+                header.extend_from_slice(data);
+                while !header.len().is_multiple_of(8) {
+                    header.push(0);
+                }
+                header[1] = (header.len() / 8 - 1) as u8;
+                header
+            }
+            Self::Fragment {
+                offset,
+                more_fragments,
+                identification,
+            } => {
+                let frag_off_m = (offset << 3) | *more_fragments as u16;
+                let mut header = vec![next, 0];
+                header.extend_from_slice(&frag_off_m.to_be_bytes());
+                header.extend_from_slice(&identification.to_be_bytes());
+                header
+            }
+        }
+    }
+}
+
+/// Pad an options header to a multiple of 8 bytes with Pad1/PadN
+fn pad_options(header: &mut Vec<u8>) {
+    match 8 - header.len() % 8 {
+        8 => {}
+        1 => header.push(0),
+        n => {
+            header.push(1);
+            header.push((n - 2) as u8);
+            header.resize(header.len() + n - 2, 0);
+        }
+    }
+}
+
+/// IPv6 packet builder
+#[derive(Debug, Clone)]
+pub struct Ipv6Packet {
+    pub traffic_class: u8,
+    pub flow_label: u32,
+    pub hop_limit: u8,
+    /// Upper-layer protocol after the extension headers
+    pub protocol: u8,
+    pub src_ip: Ipv6Addr,
+    pub dst_ip: Ipv6Addr,
+    /// Extension headers in wire order
+    pub ext_headers: Vec<Ipv6ExtHeader>,
+    pub payload: Vec<u8>,
+}
+
+impl Default for Ipv6Packet {
+    fn default() -> Self {
+        Self {
+            traffic_class: 0,
+            flow_label: 0,
+            hop_limit: 64,
+            protocol: IPPROTO_TCP,
+            src_ip: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x100),
+            dst_ip: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+            ext_headers: Vec::new(),
+            payload: Vec::new(),
+        }
+    }
+}
+
+impl Ipv6Packet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_src_ip(mut self, ip: Ipv6Addr) -> Self {
+        self.src_ip = ip;
+        self
+    }
+
+    pub fn with_dst_ip(mut self, ip: Ipv6Addr) -> Self {
+        self.dst_ip = ip;
+        self
+    }
+
+    pub fn with_protocol(mut self, protocol: u8) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    pub fn with_hop_limit(mut self, hop_limit: u8) -> Self {
+        self.hop_limit = hop_limit;
+        self
+    }
+
+    pub fn with_flow_label(mut self, flow_label: u32) -> Self {
+        self.flow_label = flow_label & 0xfffff;
+        self
+    }
+
+    /// Append an extension header
+    pub fn with_ext_header(mut self, header: Ipv6ExtHeader) -> Self {
+        self.ext_headers.push(header);
+        self
+    }
+
+    pub fn with_hop_by_hop(self, options: Vec<u8>) -> Self {
+        self.with_ext_header(Ipv6ExtHeader::HopByHop(options))
+    }
+
+    pub fn with_routing(self, routing_type: u8, segments_left: u8, data: Vec<u8>) -> Self {
+        self.with_ext_header(Ipv6ExtHeader::Routing {
+            routing_type,
+            segments_left,
+            data,
+        })
+    }
+
+    pub fn with_fragment(self, offset: u16, more_fragments: bool, identification: u32) -> Self {
+        self.with_ext_header(Ipv6ExtHeader::Fragment {
+            offset,
+            more_fragments,
+            identification,
+        })
+    }
+
+    pub fn with_dest_options(self, options: Vec<u8>) -> Self {
+        self.with_ext_header(Ipv6ExtHeader::DestinationOptions(options))
+    }
+
+    pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut ext = Vec::new();
+        for (i, header) in self.ext_headers.iter().enumerate() {
+            let next = self
+                .ext_headers
+                .get(i + 1)
+                .map(|h| h.nexthdr())
+                .unwrap_or(self.protocol);
+            ext.extend_from_slice(&header.build(next));
+        }
+        let nexthdr = self
+            .ext_headers
+            .first()
+            .map(|h| h.nexthdr())
+            .unwrap_or(self.protocol);
+        let payload_len = ext.len() + self.payload.len();
+
+        let mut packet = Vec::with_capacity(40 + payload_len);
+
+        // Version + Traffic Class + Flow Label
+        let vtf = (6u32 << 28) | ((self.traffic_class as u32) << 20) | self.flow_label;
+        packet.extend_from_slice(&vtf.to_be_bytes());
+        // Payload length
+        packet.extend_from_slice(&(payload_len as u16).to_be_bytes());
+        // Next header
+        packet.push(nexthdr);
+        // Hop limit
+        packet.push(self.hop_limit);
+        // Source IP
+        packet.extend_from_slice(&self.src_ip.octets());
+        // Destination IP
+        packet.extend_from_slice(&self.dst_ip.octets());
+        // Extension headers
+        packet.extend_from_slice(&ext);
+        // Payload
+        packet.extend_from_slice(&self.payload);
+
+        packet
+    }
+}
+
 /// TCP segment builder
 #[derive(Debug, Clone)]
 pub struct TcpSegment {
@@ -444,6 +713,10 @@ pub fn decode_varint(data: &[u8]) -> Option<(i32, usize)> {
         value |= ((byte & 0x7f) as i32) << position;
 
         if byte & 0x80 == 0 {
+            // 5th byte may only carry bits 28-31, as in the filter's read_varint_at
+            if position == 28 && (byte & 0x70) != 0 {
+                return None;
+            }
             return Some((value, bytes_read));
         }
 
@@ -692,6 +965,573 @@ impl RakNetOpenConnReq2 {
     }
 }
 
+/// RakNet frame set holding one fragment of a split packet
+#[derive(Debug, Clone)]
+pub struct RakNetSplitFrame {
+    pub sequence: u32,
+    pub reliability: u8,
+    pub split_count: u32,
+    pub split_id: u16,
+    pub split_index: u32,
+    pub body_len: usize,
+}
+
+impl Default for RakNetSplitFrame {
+    fn default() -> Self {
+        Self {
+            sequence: 0,
+            reliability: RAKNET_RELIABLE_ORDERED,
+            split_count: 2,
+            split_id: 0,
+            split_index: 0,
+            body_len: 1000,
+        }
+    }
+}
+
+impl RakNetSplitFrame {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_sequence(mut self, sequence: u32) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn with_reliability(mut self, reliability: u8) -> Self {
+        self.reliability = reliability;
+        self
+    }
+
+    /// Fragment `index` of the `count` fragments of split packet `id`
+    pub fn with_split(mut self, count: u32, id: u16, index: u32) -> Self {
+        self.split_count = count;
+        self.split_id = id;
+        self.split_index = index;
+        self
+    }
+
+    pub fn with_body_len(mut self, len: usize) -> Self {
+        self.body_len = len;
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(28 + self.body_len);
+
+        // Frame set ID and sequence number (24-bit LE)
+        packet.push(RAKNET_FRAME_SET);
+        packet.extend_from_slice(&self.sequence.to_le_bytes()[..3]);
+        // Frame flags and body length in bits
+        packet.push((self.reliability << 5) | RAKNET_FRAME_SPLIT);
+        packet.extend_from_slice(&((self.body_len * 8) as u16).to_be_bytes());
+        // Message index of reliable frames
+        if matches!(self.reliability, 2 | 3 | 4 | 6 | 7) {
+            packet.extend_from_slice(&[0; 3]);
+        }
+        // Sequence index of sequenced frames
+        if matches!(self.reliability, 1 | 4) {
+            packet.extend_from_slice(&[0; 3]);
+        }
+        // Order index and channel of ordered frames
+        if matches!(self.reliability, 1 | 3 | 4 | 7) {
+            packet.extend_from_slice(&[0; 4]);
+        }
+        // Split count, ID and index
+        packet.extend_from_slice(&self.split_count.to_be_bytes());
+        packet.extend_from_slice(&self.split_id.to_be_bytes());
+        packet.extend_from_slice(&self.split_index.to_be_bytes());
+        // Fragment body
+        packet.resize(packet.len() + self.body_len, 0xab);
+
+        packet
+    }
+}
+
+/// Amplification vector source ports
+pub const PORT_DNS: u16 = 53;
+pub const PORT_NTP: u16 = 123;
+pub const PORT_SSDP: u16 = 1900;
+pub const PORT_MEMCACHED: u16 = 11211;
+
+/// DNS record types
+pub const DNS_TYPE_A: u16 = 1;
+pub const DNS_TYPE_TXT: u16 = 16;
+pub const DNS_TYPE_OPT: u16 = 41;
+pub const DNS_TYPE_ANY: u16 = 255;
+
+/// DNS response builder
+///
+/// Questions repeat `qname`/`qtype`; answers point back at the first
+/// question's name. Answers are A records unless `answer_rdata_len` is not
+/// 4, in which case they are TXT records of that size.
+#[derive(Debug, Clone)]
+pub struct DnsResponse {
+    pub transaction_id: u16,
+    pub flags: u16,
+    pub qdcount: u16,
+    pub ancount: u16,
+    pub qname: String,
+    pub qtype: u16,
+    pub answer_rdata_len: u16,
+    /// Advertised EDNS(0) UDP payload size (adds an OPT record)
+    pub edns_udp_size: Option<u16>,
+}
+
+impl Default for DnsResponse {
+    fn default() -> Self {
+        Self {
+            transaction_id: 0x1234,
+            flags: 0x8180, // Response, RD, RA, NOERROR
+            qdcount: 1,
+            ancount: 1,
+            qname: "example.com".to_string(),
+            qtype: DNS_TYPE_A,
+            answer_rdata_len: 4,
+            edns_udp_size: None,
+        }
+    }
+}
+
+impl DnsResponse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_transaction_id(mut self, id: u16) -> Self {
+        self.transaction_id = id;
+        self
+    }
+
+    pub fn with_flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the opcode (bits 11-14 of the flags)
+    pub fn with_opcode(mut self, opcode: u8) -> Self {
+        self.flags = (self.flags & !0x7800) | (((opcode & 0x0f) as u16) << 11);
+        self
+    }
+
+    pub fn with_qdcount(mut self, qdcount: u16) -> Self {
+        self.qdcount = qdcount;
+        self
+    }
+
+    pub fn with_ancount(mut self, ancount: u16) -> Self {
+        self.ancount = ancount;
+        self
+    }
+
+    pub fn with_qname(mut self, qname: &str) -> Self {
+        self.qname = qname.to_string();
+        self
+    }
+
+    pub fn with_qtype(mut self, qtype: u16) -> Self {
+        self.qtype = qtype;
+        self
+    }
+
+    pub fn with_answer_size(mut self, rdata_len: u16) -> Self {
+        self.answer_rdata_len = rdata_len;
+        self
+    }
+
+    pub fn with_edns(mut self, udp_size: u16) -> Self {
+        self.edns_udp_size = Some(udp_size);
+        self
+    }
+
+    /// Response to an ANY query with `ancount` large TXT answers
+    pub fn any_amplification(ancount: u16) -> Self {
+        Self::new()
+            .with_qtype(DNS_TYPE_ANY)
+            .with_ancount(ancount)
+            .with_answer_size(255)
+            .with_edns(4096)
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut packet = Vec::new();
+
+        // Header
+        packet.extend_from_slice(&self.transaction_id.to_be_bytes());
+        packet.extend_from_slice(&self.flags.to_be_bytes());
+        packet.extend_from_slice(&self.qdcount.to_be_bytes());
+        packet.extend_from_slice(&self.ancount.to_be_bytes());
+        packet.extend_from_slice(&0u16.to_be_bytes()); // NSCOUNT
+        let arcount = self.edns_udp_size.is_some() as u16;
+        packet.extend_from_slice(&arcount.to_be_bytes());
+
+        // Questions
+        for _ in 0..self.qdcount {
+            for label in self.qname.split('.').filter(|l| !l.is_empty()) {
+                packet.push(label.len() as u8);
+                packet.extend_from_slice(label.as_bytes());
+            }
+            packet.push(0);
+            packet.extend_from_slice(&self.qtype.to_be_bytes());
+            packet.extend_from_slice(&1u16.to_be_bytes()); // Class IN
+        }
+
+        // Answers
+        let answer_type = if self.answer_rdata_len == 4 {
+            DNS_TYPE_A
+        } else {
+            DNS_TYPE_TXT
+        };
+        for i in 0..self.ancount {
+            packet.extend_from_slice(&0xc00cu16.to_be_bytes()); // Pointer to qname
+            packet.extend_from_slice(&answer_type.to_be_bytes());
+            packet.extend_from_slice(&1u16.to_be_bytes()); // Class IN
+            packet.extend_from_slice(&300u32.to_be_bytes()); // TTL
+            packet.extend_from_slice(&self.answer_rdata_len.to_be_bytes());
+            if answer_type == DNS_TYPE_A {
+                packet.extend_from_slice(&[192, 0, 2, i as u8]);
+            } else {
+                // TXT: one character-string filling the record
+                let mut rdata = vec![b'v'; self.answer_rdata_len as usize];
+                if let Some(len) = rdata.first_mut() {
+                    *len = (self.answer_rdata_len - 1).min(255) as u8;
+                }
+                packet.extend_from_slice(&rdata);
+            }
+        }
+
+        // EDNS(0) OPT pseudo-record
+        if let Some(udp_size) = self.edns_udp_size {
+            packet.push(0); // Root name
+            packet.extend_from_slice(&DNS_TYPE_OPT.to_be_bytes());
+            packet.extend_from_slice(&udp_size.to_be_bytes());
+            packet.extend_from_slice(&0u32.to_be_bytes()); // Extended RCODE and flags
+            packet.extend_from_slice(&0u16.to_be_bytes()); // RDLEN
+        }
+
+        packet
+    }
+}
+
+/// NTP mode 7 (private) request code of `monlist`
+pub const NTP_MON_GETLIST_1: u8 = 42;
+
+/// Size of one `monlist` entry (`info_monitor_1`)
+pub const NTP_MONLIST_ITEM_SIZE: usize = 72;
+
+/// Most `monlist` entries carried in one response datagram
+pub const NTP_MONLIST_ITEMS_PER_PACKET: u16 = 6;
+
+/// NTP response builder
+///
+/// Builds a standard mode 4 server reply, a mode 6 control reply, or a
+/// mode 7 `monlist` reply, the classic NTP amplification vector.
+#[derive(Debug, Clone)]
+pub struct NtpResponse {
+    pub version: u8,
+    pub mode: u8,
+    /// Mode 6 opcode or mode 7 request code
+    pub request_code: u8,
+    pub sequence: u16,
+    /// Mode 6 data bytes or mode 7 item count
+    pub count: u16,
+    /// More responses follow (mode 6/7 M bit)
+    pub more: bool,
+}
+
+impl Default for NtpResponse {
+    fn default() -> Self {
+        Self {
+            version: 4,
+            mode: 4,
+            request_code: 0,
+            sequence: 1,
+            count: 0,
+            more: false,
+        }
+    }
+}
+
+impl NtpResponse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Standard 48-byte server reply
+    pub fn server() -> Self {
+        Self::new()
+    }
+
+    /// Mode 6 control reply carrying `data_len` bytes (opcode 2 = READVAR)
+    pub fn control(data_len: u16) -> Self {
+        Self {
+            version: 2,
+            mode: 6,
+            request_code: 2,
+            count: data_len,
+            ..Self::default()
+        }
+    }
+
+    /// Mode 7 `monlist` reply carrying `items` entries
+    pub fn monlist(items: u16) -> Self {
+        Self {
+            version: 2,
+            mode: 7,
+            request_code: NTP_MON_GETLIST_1,
+            count: items.min(NTP_MONLIST_ITEMS_PER_PACKET),
+            more: items > NTP_MONLIST_ITEMS_PER_PACKET,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn with_sequence(mut self, sequence: u16) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let vn_mode = ((self.version & 0x07) << 3) | (self.mode & 0x07);
+
+        match self.mode {
+            6 => {
+                let mut packet = Vec::with_capacity(12 + self.count as usize + 3);
+                packet.push(vn_mode);
+                // Response bit, more bit, opcode
+                packet.push(0x80 | ((self.more as u8) << 5) | (self.request_code & 0x1f));
+                packet.extend_from_slice(&self.sequence.to_be_bytes());
+                packet.extend_from_slice(&0x0615u16.to_be_bytes()); // Status
+                packet.extend_from_slice(&0u16.to_be_bytes()); // Association ID
+                packet.extend_from_slice(&0u16.to_be_bytes()); // Offset
+                packet.extend_from_slice(&self.count.to_be_bytes());
+                packet.resize(12 + self.count as usize, b'x');
+                // Data is padded to a 32-bit boundary
+                while packet.len() % 4 != 0 {
+                    packet.push(0);
+                }
+                packet
+            }
+            7 => {
+                let items = self.count as usize;
+                let mut packet = Vec::with_capacity(8 + items * NTP_MONLIST_ITEM_SIZE);
+                // Response bit, more bit, version, mode
+                packet.push(0x80 | ((self.more as u8) << 6) | vn_mode);
+                packet.push((self.sequence & 0x7f) as u8);
+                packet.push(3); // Implementation: XNTPD
+                packet.push(self.request_code);
+                packet.extend_from_slice(&(self.count & 0x0fff).to_be_bytes()); // Error 0 + items
+                packet.extend_from_slice(&(NTP_MONLIST_ITEM_SIZE as u16).to_be_bytes());
+                for i in 0..items {
+                    let mut item = [0u8; NTP_MONLIST_ITEM_SIZE];
+                    // Remote address of the monitored client
+                    item[16..20].copy_from_slice(&[198, 51, 100, i as u8]);
+                    packet.extend_from_slice(&item);
+                }
+                packet
+            }
+            _ => {
+                let mut packet = vec![0u8; 48];
+                packet[0] = vn_mode;
+                packet[1] = 2; // Stratum
+                packet[2] = 6; // Poll
+                packet[3] = 0xec; // Precision
+                packet
+            }
+        }
+    }
+}
+
+/// SSDP M-SEARCH reply builder
+#[derive(Debug, Clone)]
+pub struct SsdpResponse {
+    pub search_target: String,
+    pub location: String,
+    pub server: String,
+    pub usn: String,
+}
+
+impl Default for SsdpResponse {
+    fn default() -> Self {
+        Self {
+            search_target: "upnp:rootdevice".to_string(),
+            location: "http://192.168.1.1:1900/rootDesc.xml".to_string(),
+            server: "Linux/3.14 UPnP/1.0 miniupnpd/2.0".to_string(),
+            usn: "uuid:3ddcd1d3-2380-45f5-b069-b2c1e0e8a1f0".to_string(),
+        }
+    }
+}
+
+impl SsdpResponse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_search_target(mut self, st: &str) -> Self {
+        self.search_target = st.to_string();
+        self
+    }
+
+    pub fn with_location(mut self, location: &str) -> Self {
+        self.location = location.to_string();
+        self
+    }
+
+    pub fn with_server(mut self, server: &str) -> Self {
+        self.server = server.to_string();
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 200 OK\r\n\
+             CACHE-CONTROL: max-age=1800\r\n\
+             DATE: Thu, 01 Jan 2026 00:00:00 GMT\r\n\
+             EXT:\r\n\
+             LOCATION: {}\r\n\
+             SERVER: {}\r\n\
+             ST: {}\r\n\
+             USN: {}::{}\r\n\
+             \r\n",
+            self.location, self.server, self.search_target, self.usn, self.search_target
+        )
+        .into_bytes()
+    }
+}
+
+/// Memcached response encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemcachedFormat {
+    /// Binary protocol GET response (magic 0x81)
+    Binary,
+    /// Text protocol `VALUE ... END` response
+    Text,
+    /// Text protocol `stats` response
+    Stats,
+}
+
+/// Memcached binary protocol response magic
+pub const MEMCACHED_RESPONSE_MAGIC: u8 = 0x81;
+
+/// Memcached response builder
+///
+/// UDP responses start with the 8-byte memcached frame header (request ID,
+/// sequence number, datagram count, reserved) before the protocol data.
+#[derive(Debug, Clone)]
+pub struct MemcachedResponse {
+    pub format: MemcachedFormat,
+    pub request_id: u16,
+    pub key: String,
+    pub value_len: usize,
+    /// Number of `STAT` lines for `MemcachedFormat::Stats`
+    pub stat_count: usize,
+    /// Include the UDP frame header
+    pub udp_frame: bool,
+}
+
+impl Default for MemcachedResponse {
+    fn default() -> Self {
+        Self {
+            format: MemcachedFormat::Text,
+            request_id: 0,
+            key: "key".to_string(),
+            value_len: 16,
+            stat_count: 0,
+            udp_frame: false,
+        }
+    }
+}
+
+impl MemcachedResponse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn binary(mut self) -> Self {
+        self.format = MemcachedFormat::Binary;
+        self
+    }
+
+    pub fn text(mut self) -> Self {
+        self.format = MemcachedFormat::Text;
+        self
+    }
+
+    pub fn stats(mut self, stat_count: usize) -> Self {
+        self.format = MemcachedFormat::Stats;
+        self.stat_count = stat_count;
+        self
+    }
+
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = key.to_string();
+        self
+    }
+
+    pub fn with_value_len(mut self, len: usize) -> Self {
+        self.value_len = len;
+        self
+    }
+
+    /// Prefix the response with the UDP frame header
+    pub fn with_udp_frame(mut self, request_id: u16) -> Self {
+        self.udp_frame = true;
+        self.request_id = request_id;
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut packet = Vec::new();
+
+        if self.udp_frame {
+            packet.extend_from_slice(&self.request_id.to_be_bytes());
+            packet.extend_from_slice(&0u16.to_be_bytes()); // Sequence number
+            packet.extend_from_slice(&1u16.to_be_bytes()); // Datagrams in message
+            packet.extend_from_slice(&0u16.to_be_bytes()); // Reserved
+        }
+
+        match self.format {
+            MemcachedFormat::Binary => {
+                let extras_len = 4u8;
+                let body_len = extras_len as u32 + self.key.len() as u32 + self.value_len as u32;
+                packet.push(MEMCACHED_RESPONSE_MAGIC);
+                packet.push(0x0c); // GETK
+                packet.extend_from_slice(&(self.key.len() as u16).to_be_bytes());
+                packet.push(extras_len);
+                packet.push(0); // Data type
+                packet.extend_from_slice(&0u16.to_be_bytes()); // Status
+                packet.extend_from_slice(&body_len.to_be_bytes());
+                packet.extend_from_slice(&0u32.to_be_bytes()); // Opaque
+                packet.extend_from_slice(&1u64.to_be_bytes()); // CAS
+                packet.extend_from_slice(&0u32.to_be_bytes()); // Flags
+                packet.extend_from_slice(self.key.as_bytes());
+                packet.resize(packet.len() + self.value_len, b'A');
+            }
+            MemcachedFormat::Text => {
+                packet.extend_from_slice(
+                    format!("VALUE {} 0 {}\r\n", self.key, self.value_len).as_bytes(),
+                );
+                packet.resize(packet.len() + self.value_len, b'A');
+                packet.extend_from_slice(b"\r\nEND\r\n");
+            }
+            MemcachedFormat::Stats => {
+                for i in 0..self.stat_count {
+                    packet
+                        .extend_from_slice(format!("STAT stat_{} {}\r\n", i, i * 1000).as_bytes());
+                }
+                packet.extend_from_slice(b"END\r\n");
+            }
+        }
+
+        packet
+    }
+}
+
 /// Compute IP header checksum
 fn compute_ip_checksum(header: &[u8]) -> u16 {
     let mut sum: u32 = 0;
@@ -718,6 +1558,26 @@ fn compute_ip_checksum(header: &[u8]) -> u16 {
     !sum as u16
 }
 
+/// Compute the Internet checksum over `data`
+fn compute_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|word| match word {
+            [hi, lo] => ((*hi as u32) << 8) | *lo as u32,
+            [hi] => (*hi as u32) << 8,
+            _ => 0,
+        })
+        .sum();
+
+    // Fold carries
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    // One's complement
+    !sum as u16
+}
+
 /// Create a complete TCP packet with Ethernet, IP, and TCP headers
 pub fn create_tcp_packet(
     src_ip: Ipv4Addr,
@@ -774,6 +1634,176 @@ pub fn create_udp_packet(
         .build()
 }
 
+/// Create a complete TCP packet with Ethernet, IPv6, and TCP headers
+pub fn create_tcp_packet_v6(
+    src_ip: Ipv6Addr,
+    dst_ip: Ipv6Addr,
+    src_port: u16,
+    dst_port: u16,
+    flags: u8,
+    payload: Vec<u8>,
+) -> Vec<u8> {
+    let tcp = TcpSegment::new()
+        .with_src_port(src_port)
+        .with_dst_port(dst_port)
+        .with_flags(flags)
+        .with_payload(payload)
+        .build();
+
+    let ip = Ipv6Packet::new()
+        .with_src_ip(src_ip)
+        .with_dst_ip(dst_ip)
+        .with_protocol(IPPROTO_TCP)
+        .with_payload(tcp)
+        .build();
+
+    EthernetFrame::new()
+        .with_ether_type(ETH_P_IPV6)
+        .with_payload(ip)
+        .build()
+}
+
+/// Create a complete UDP packet with Ethernet, IPv6, and UDP headers
+///
+/// The UDP checksum is filled in, as IPv6 does not allow it to be zero.
+pub fn create_udp_packet_v6(
+    src_ip: Ipv6Addr,
+    dst_ip: Ipv6Addr,
+    src_port: u16,
+    dst_port: u16,
+    payload: Vec<u8>,
+) -> Vec<u8> {
+    let udp = udp_datagram_v6(src_ip, dst_ip, src_port, dst_port, payload);
+
+    let ip = Ipv6Packet::new()
+        .with_src_ip(src_ip)
+        .with_dst_ip(dst_ip)
+        .with_protocol(IPPROTO_UDP)
+        .with_payload(udp)
+        .build();
+
+    EthernetFrame::new()
+        .with_ether_type(ETH_P_IPV6)
+        .with_payload(ip)
+        .build()
+}
+
+/// Build a UDP datagram with its checksum over the IPv6 pseudo-header
+fn udp_datagram_v6(
+    src_ip: Ipv6Addr,
+    dst_ip: Ipv6Addr,
+    src_port: u16,
+    dst_port: u16,
+    payload: Vec<u8>,
+) -> Vec<u8> {
+    let mut udp = UdpDatagram::new()
+        .with_src_port(src_port)
+        .with_dst_port(dst_port)
+        .with_payload(payload)
+        .build();
+
+    let mut pseudo = Vec::with_capacity(40 + udp.len());
+    pseudo.extend_from_slice(&src_ip.octets());
+    pseudo.extend_from_slice(&dst_ip.octets());
+    pseudo.extend_from_slice(&(udp.len() as u32).to_be_bytes());
+    pseudo.extend_from_slice(&[0, 0, 0, IPPROTO_UDP]);
+    pseudo.extend_from_slice(&udp);
+
+    let checksum = match compute_checksum(&pseudo) {
+        0 => 0xffff,
+        c => c,
+    };
+    udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+    udp
+}
+
+/// Split a transport segment into fragments of at most `fragment_size` bytes
+///
+/// Every fragment but the last is rounded down to a multiple of 8 bytes, as
+/// fragment offsets are in 8-byte units. Returns (offset in 8-byte units,
+/// more fragments, data) for each fragment.
+pub fn split_fragments(segment: &[u8], fragment_size: usize) -> Vec<(u16, bool, Vec<u8>)> {
+    let chunk = (fragment_size / 8).max(1) * 8;
+    let count = segment.chunks(chunk).len();
+    segment
+        .chunks(chunk)
+        .enumerate()
+        .map(|(i, data)| ((i * chunk / 8) as u16, i + 1 < count, data.to_vec()))
+        .collect()
+}
+
+/// Create a UDP datagram fragmented into a series of IPv4 frames
+///
+/// The UDP header travels in the first fragment only.
+pub fn create_fragmented_udp_series(
+    src_ip: Ipv4Addr,
+    dst_ip: Ipv4Addr,
+    src_port: u16,
+    dst_port: u16,
+    payload: Vec<u8>,
+    fragment_size: usize,
+    identification: u16,
+) -> Vec<Vec<u8>> {
+    let udp = UdpDatagram::new()
+        .with_src_port(src_port)
+        .with_dst_port(dst_port)
+        .with_payload(payload)
+        .build();
+
+    split_fragments(&udp, fragment_size)
+        .into_iter()
+        .map(|(offset, more, data)| {
+            let ip = Ipv4Packet::new()
+                .with_src_ip(src_ip)
+                .with_dst_ip(dst_ip)
+                .with_protocol(IPPROTO_UDP)
+                .with_identification(identification)
+                .with_fragment(if more { IP_MF } else { 0 }, offset)
+                .with_payload(data)
+                .build();
+
+            EthernetFrame::new()
+                .with_ether_type(ETH_P_IP)
+                .with_payload(ip)
+                .build()
+        })
+        .collect()
+}
+
+/// Create a UDP datagram fragmented into a series of IPv6 frames
+///
+/// Each frame carries a Fragment extension header; the UDP header travels
+/// in the first fragment only.
+pub fn create_fragmented_udp_series_v6(
+    src_ip: Ipv6Addr,
+    dst_ip: Ipv6Addr,
+    src_port: u16,
+    dst_port: u16,
+    payload: Vec<u8>,
+    fragment_size: usize,
+    identification: u32,
+) -> Vec<Vec<u8>> {
+    let udp = udp_datagram_v6(src_ip, dst_ip, src_port, dst_port, payload);
+
+    split_fragments(&udp, fragment_size)
+        .into_iter()
+        .map(|(offset, more, data)| {
+            let ip = Ipv6Packet::new()
+                .with_src_ip(src_ip)
+                .with_dst_ip(dst_ip)
+                .with_protocol(IPPROTO_UDP)
+                .with_fragment(offset, more, identification)
+                .with_payload(data)
+                .build();
+
+            EthernetFrame::new()
+                .with_ether_type(ETH_P_IPV6)
+                .with_payload(ip)
+                .build()
+        })
+        .collect()
+}
+
 /// Create a Minecraft Java handshake packet
 pub fn create_minecraft_handshake_packet(
     src_ip: Ipv4Addr,
@@ -0,0 +1,157 @@
+//! Userspace mirror of the shared eBPF header parsers
+//!
+//! The XDP programs parse headers through `parse_eth`, `parse_ipv4`, etc. in
+//! `ebpf/src/lib.rs`. Those operate on raw packet pointers and cannot run on
+//! the host, so the same logic is reproduced here over byte slices. Offsets
+//! are relative to the start of the frame. Keep in sync with the eBPF crate.
+
+use crate::packet_generator::{
+    ETH_P_8021AD, ETH_P_8021Q, NEXTHDR_DEST, NEXTHDR_FRAGMENT, NEXTHDR_HOP, NEXTHDR_ROUTING,
+};
+
+/// Ethernet header length without VLAN tags
+pub const ETH_HLEN: usize = 14;
+
+/// Length of one 802.1Q / 802.1ad tag
+pub const VLAN_HLEN: usize = 4;
+
+/// VLAN tags skipped before giving up (matches `protocol::eth::MAX_VLAN_TAGS`)
+pub const MAX_VLAN_TAGS: usize = 2;
+
+/// Minimum IPv4 header length
+pub const IPV4_HLEN: usize = 20;
+
+fn read_u16(packet: &[u8], offset: usize) -> Option<u16> {
+    let bytes = packet.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Parse the Ethernet header, skipping up to two VLAN tags
+///
+/// Returns the inner EtherType and the offset of the L3 header.
+pub fn parse_eth(packet: &[u8]) -> Option<(u16, usize)> {
+    let mut proto = read_u16(packet, 12)?;
+    let mut offset = ETH_HLEN;
+
+    for _ in 0..MAX_VLAN_TAGS {
+        if proto != ETH_P_8021Q && proto != ETH_P_8021AD {
+            break;
+        }
+        proto = read_u16(packet, offset + 2)?;
+        offset += VLAN_HLEN;
+    }
+
+    Some((proto, offset))
+}
+
+/// Parse the IPv4 header at `offset`
+///
+/// Returns the IP protocol and the offset of the transport header (from IHL).
+pub fn parse_ipv4(packet: &[u8], offset: usize) -> Option<(u8, usize)> {
+    let header = packet.get(offset..offset + IPV4_HLEN)?;
+    let ihl = (header[0] & 0x0f) as usize * 4;
+    Some((header[9], offset + ihl))
+}
+
+/// Fixed IPv6 header length
+pub const IPV6_HLEN: usize = 40;
+
+/// Authentication Header next header value
+pub const NEXTHDR_AUTH: u8 = 51;
+
+/// Extension headers walked before giving up (matches `ipv6_ext::MAX_EXT_HEADERS`)
+pub const MAX_EXT_HEADERS: usize = 4;
+
+/// Upper-layer header located by `walk_ipv6_ext_headers`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv6Payload {
+    /// Protocol of the first non-extension header
+    pub protocol: u8,
+    /// Offset of the upper-layer header
+    pub offset: usize,
+    /// A Fragment header was present with MF set or a non-zero offset
+    pub is_fragment: bool,
+    /// Fragment offset is zero, so the upper-layer header is present
+    pub is_first_fragment: bool,
+}
+
+/// Parse the IPv6 header at `offset` and walk its extension headers
+pub fn parse_ipv6(packet: &[u8], offset: usize) -> Option<Ipv6Payload> {
+    let header = packet.get(offset..offset + IPV6_HLEN)?;
+    walk_ipv6_ext_headers(packet, header[6], offset + IPV6_HLEN)
+}
+
+/// Walk the IPv6 extension header chain starting at `offset`
+///
+/// Mirrors `walk_ipv6_ext_headers`: returns `None` if a header is truncated
+/// or the chain is longer than `MAX_EXT_HEADERS`.
+pub fn walk_ipv6_ext_headers(packet: &[u8], nexthdr: u8, offset: usize) -> Option<Ipv6Payload> {
+    let mut payload = Ipv6Payload {
+        protocol: nexthdr,
+        offset,
+        is_fragment: false,
+        is_first_fragment: true,
+    };
+
+    for _ in 0..MAX_EXT_HEADERS {
+        match payload.protocol {
+            NEXTHDR_FRAGMENT => {
+                let frag = packet.get(payload.offset..payload.offset + 8)?;
+                let frag_off_m = u16::from_be_bytes([frag[2], frag[3]]);
+                let frag_offset = frag_off_m & 0xfff8;
+                let more_fragments = (frag_off_m & 0x0001) != 0;
+
+                payload.is_fragment = more_fragments || frag_offset != 0;
+                payload.is_first_fragment = frag_offset == 0;
+                payload.protocol = frag[0];
+                payload.offset += 8;
+            }
+            NEXTHDR_HOP | NEXTHDR_ROUTING | NEXTHDR_DEST | NEXTHDR_AUTH => {
+                let ext = packet.get(payload.offset..payload.offset + 2)?;
+                let ext_len = ext[1] as usize;
+                let total_len = if payload.protocol == NEXTHDR_AUTH {
+                    (ext_len + 2) * 4
+                } else {
+                    (ext_len + 1) * 8
+                };
+
+                payload.protocol = ext[0];
+                payload.offset += total_len;
+            }
+            _ => return Some(payload),
+        }
+    }
+
+    match payload.protocol {
+        NEXTHDR_FRAGMENT | NEXTHDR_HOP | NEXTHDR_ROUTING | NEXTHDR_DEST | NEXTHDR_AUTH => None,
+        _ => Some(payload),
+    }
+}
+
+/// Read the (source, destination) ports of a TCP or UDP header at `offset`
+pub fn transport_ports(packet: &[u8], offset: usize) -> Option<(u16, u16)> {
+    Some((read_u16(packet, offset)?, read_u16(packet, offset + 2)?))
+}
+
+/// Payload bytes copied out of a multi-buffer frame (matches `frags::SCRATCH_BYTES`)
+pub const SCRATCH_BYTES: usize = 512;
+
+/// Borrow the payload at `offset` of a frame whose first buffer is `linear_len` bytes
+///
+/// Mirrors `payload_view`: returns the inspectable bytes and the full payload
+/// length. Bytes past the first buffer are only visible through the copy,
+/// which is capped at `SCRATCH_BYTES`.
+pub fn payload_view(frame: &[u8], linear_len: usize, offset: usize) -> Option<(&[u8], usize)> {
+    if offset >= frame.len() {
+        return None;
+    }
+    let payload_len = frame.len() - offset;
+    let linear = linear_len.min(frame.len()).saturating_sub(offset);
+
+    if linear >= payload_len || linear >= SCRATCH_BYTES {
+        return Some((&frame[offset..offset + linear], payload_len));
+    }
+
+    let copied = payload_len.min(SCRATCH_BYTES);
+    Some((&frame[offset..offset + copied], payload_len))
+}
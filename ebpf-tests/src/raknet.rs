@@ -0,0 +1,170 @@
+//! Userspace mirror of the RakNet split packet limits in `ebpf/src/xdp_minecraft.rs`
+//!
+//! `check_bedrock_split_fragment` bounds how much a Bedrock connection can
+//! make the server buffer for split packet reassembly. It is reproduced here
+//! over the frame set datagram, with the connection's `MC_BEDROCK_SPLITS`
+//! entry as an `Option`, so the limits can be tested against the frames
+//! `RakNetSplitFrame` generates. Keep in sync with the eBPF crate.
+
+/// Max fragments of one split packet
+pub const RAKNET_MAX_SPLIT_COUNT: u32 = 128;
+/// Max split packets reassembled at once
+pub const RAKNET_MAX_OUTSTANDING_SPLITS: usize = 4;
+/// Max fragment bytes buffered
+pub const RAKNET_MAX_REASSEMBLY_BYTES: u32 = 256 * 1024;
+/// Max fragments per second per connection
+pub const RAKNET_FRAGMENT_FLOOD_THRESHOLD: u32 = 1000;
+/// Incomplete split packets expire after 10s
+pub const RAKNET_SPLIT_TIMEOUT_NS: u64 = 10_000_000_000;
+
+/// Split packets being reassembled on a Bedrock connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BedrockSplitState {
+    /// First fragment timestamp per slot
+    pub started: [u64; RAKNET_MAX_OUTSTANDING_SPLITS],
+    /// Fragments still missing per slot (0 = slot free)
+    pub remaining: [u32; RAKNET_MAX_OUTSTANDING_SPLITS],
+    /// Fragment bytes buffered per slot
+    pub bytes: [u32; RAKNET_MAX_OUTSTANDING_SPLITS],
+    /// Split ID per slot
+    pub split_ids: [u16; RAKNET_MAX_OUTSTANDING_SPLITS],
+    /// Fragments in current window
+    pub fragment_count: u32,
+    /// Window start timestamp
+    pub window_start: u64,
+}
+
+impl BedrockSplitState {
+    /// Split packets still being reassembled
+    pub fn outstanding(&self) -> usize {
+        self.remaining.iter().filter(|&&r| r > 0).count()
+    }
+
+    /// Fragment bytes buffered over all split packets
+    pub fn buffered(&self) -> u32 {
+        self.bytes.iter().sum()
+    }
+}
+
+/// Check a split frame against the reassembly limits of its connection
+///
+/// `payload` is a frame set datagram whose first frame has the split flag
+/// and `splits` the connection's entry, created by the first fragment.
+/// Returns false if the fragment should be dropped.
+pub fn check_bedrock_split_fragment(
+    splits: &mut Option<BedrockSplitState>,
+    payload: &[u8],
+    now: u64,
+) -> bool {
+    let frame_header = payload[4];
+    let reliability = (frame_header >> 5) & 0x07;
+
+    let mut offset = 7usize;
+    if matches!(reliability, 2 | 3 | 4 | 6 | 7) {
+        offset += 3;
+    }
+    if matches!(reliability, 1 | 4) {
+        offset += 3;
+    }
+    if matches!(reliability, 1 | 3 | 4 | 7) {
+        offset += 4;
+    }
+    if offset + 10 > payload.len() {
+        return false;
+    }
+
+    let fragment_bytes = (u16::from_be_bytes([payload[5], payload[6]]) as u32).div_ceil(8);
+    let split_count = u32::from_be_bytes([
+        payload[offset],
+        payload[offset + 1],
+        payload[offset + 2],
+        payload[offset + 3],
+    ]);
+    let split_id = u16::from_be_bytes([payload[offset + 4], payload[offset + 5]]);
+    let split_index = u32::from_be_bytes([
+        payload[offset + 6],
+        payload[offset + 7],
+        payload[offset + 8],
+        payload[offset + 9],
+    ]);
+
+    if split_count == 0 || split_count > RAKNET_MAX_SPLIT_COUNT || split_index >= split_count {
+        return false;
+    }
+
+    let Some(splits) = splits else {
+        if fragment_bytes > RAKNET_MAX_REASSEMBLY_BYTES {
+            return false;
+        }
+        let mut state = BedrockSplitState {
+            fragment_count: 1,
+            window_start: now,
+            ..Default::default()
+        };
+        if split_count > 1 {
+            state.started[0] = now;
+            state.remaining[0] = split_count - 1;
+            state.bytes[0] = fragment_bytes;
+            state.split_ids[0] = split_id;
+        }
+        *splits = Some(state);
+        return true;
+    };
+
+    // Fragment rate (1 second window)
+    if now.saturating_sub(splits.window_start) > 1_000_000_000 {
+        splits.window_start = now;
+        splits.fragment_count = 0;
+    }
+    splits.fragment_count += 1;
+    if splits.fragment_count > RAKNET_FRAGMENT_FLOOD_THRESHOLD {
+        return false;
+    }
+
+    // Expire incomplete split packets and find this fragment's slot
+    let mut slot = RAKNET_MAX_OUTSTANDING_SPLITS;
+    let mut free = RAKNET_MAX_OUTSTANDING_SPLITS;
+    let mut buffered = 0u32;
+    for i in 0..RAKNET_MAX_OUTSTANDING_SPLITS {
+        if splits.remaining[i] > 0
+            && now.saturating_sub(splits.started[i]) > RAKNET_SPLIT_TIMEOUT_NS
+        {
+            splits.remaining[i] = 0;
+            splits.bytes[i] = 0;
+        }
+        if splits.remaining[i] == 0 {
+            if free == RAKNET_MAX_OUTSTANDING_SPLITS {
+                free = i;
+            }
+        } else if splits.split_ids[i] == split_id {
+            slot = i;
+        }
+        buffered += splits.bytes[i];
+    }
+
+    if buffered + fragment_bytes > RAKNET_MAX_REASSEMBLY_BYTES {
+        return false;
+    }
+
+    if slot < RAKNET_MAX_OUTSTANDING_SPLITS {
+        splits.remaining[slot] -= 1;
+        splits.bytes[slot] = if splits.remaining[slot] == 0 {
+            // Reassembled - the server releases the buffer
+            0
+        } else {
+            splits.bytes[slot] + fragment_bytes
+        };
+    } else if free < RAKNET_MAX_OUTSTANDING_SPLITS {
+        if split_count > 1 {
+            splits.started[free] = now;
+            splits.remaining[free] = split_count - 1;
+            splits.bytes[free] = fragment_bytes;
+            splits.split_ids[free] = split_id;
+        }
+    } else {
+        // Too many split packets being reassembled
+        return false;
+    }
+
+    true
+}
@@ -0,0 +1,203 @@
+//! Userspace mirror of the SYN cookie logic in `ebpf/src/xdp_tcp.rs`
+//!
+//! `generate_syn_cookie` and `validate_syn_cookie` are reproduced as-is,
+//! and [`SynCookieJar`] stands in for the `SYN_COOKIES` map: it issues
+//! cookies on SYN, validates the echoed value on the first ACK, and drops
+//! entries older than `SYN_COOKIE_TTL_NS` in its maintenance pass. Keep in
+//! sync with the eBPF crate.
+
+use crate::clock::Clock;
+use std::collections::HashMap;
+
+/// Lifetime of a `SYN_COOKIES` entry
+pub const SYN_COOKIE_TTL_NS: u64 = 60_000_000_000;
+
+/// Granularity of the 5-bit time counter in the cookie
+pub const SYN_COOKIE_WINDOW_NS: u64 = 60_000_000_000;
+
+/// Time windows a cookie stays valid after the one it was issued in
+pub const SYN_COOKIE_MAX_WINDOW_DIFF: u32 = 2;
+
+const HASH_MASK: u32 = 0xFFFFFF80;
+const TIME_MASK: u32 = 0x1f;
+
+fn siphash_round(v0: &mut u64, v1: &mut u64, v2: &mut u64, v3: &mut u64) {
+    *v0 = v0.wrapping_add(*v1);
+    *v1 = v1.rotate_left(13);
+    *v1 ^= *v0;
+    *v0 = v0.rotate_left(32);
+
+    *v2 = v2.wrapping_add(*v3);
+    *v3 = v3.rotate_left(16);
+    *v3 ^= *v2;
+
+    *v0 = v0.wrapping_add(*v3);
+    *v3 = v3.rotate_left(21);
+    *v3 ^= *v0;
+
+    *v2 = v2.wrapping_add(*v1);
+    *v1 = v1.rotate_left(17);
+    *v1 ^= *v2;
+    *v2 = v2.rotate_left(32);
+}
+
+/// Generate the cookie for a SYN at `now` with the configured secrets
+pub fn generate_syn_cookie(
+    secrets: (u32, u32),
+    src_ip: u32,
+    src_port: u16,
+    dst_ip: u32,
+    dst_port: u16,
+    seq: u32,
+    now: u64,
+) -> u32 {
+    let (secret1, secret2) = secrets;
+    let time_counter = (now / SYN_COOKIE_WINDOW_NS) as u32;
+
+    let k0 = ((secret1 as u64) << 32) | (secret2 as u64);
+    let k1 = k0.wrapping_mul(0x9e3779b97f4a7c15);
+
+    let mut v0 = k0 ^ 0x736f6d6570736575;
+    let mut v1 = k1 ^ 0x646f72616e646f6d;
+    let mut v2 = k0 ^ 0x6c7967656e657261;
+    let mut v3 = k1 ^ 0x7465646279746573;
+
+    let blocks = [
+        ((src_ip as u64) << 32) | (dst_ip as u64),
+        ((src_port as u64) << 48) | ((dst_port as u64) << 32) | (seq as u64),
+        ((time_counter as u64) << 32) | 0x1800000000000000,
+    ];
+    for m in blocks {
+        v3 ^= m;
+        siphash_round(&mut v0, &mut v1, &mut v2, &mut v3);
+        siphash_round(&mut v0, &mut v1, &mut v2, &mut v3);
+        v0 ^= m;
+    }
+
+    v2 ^= 0xff;
+    for _ in 0..4 {
+        siphash_round(&mut v0, &mut v1, &mut v2, &mut v3);
+    }
+
+    let hash = v0 ^ v1 ^ v2 ^ v3;
+
+    // Upper 25 bits: hash, next 2 bits: MSS index (always 1460), lower 5 bits: time
+    ((hash as u32) & HASH_MASK) | (3 << 5) | (time_counter & TIME_MASK)
+}
+
+/// Check an echoed cookie against the stored one at `now`
+pub fn validate_syn_cookie(cookie: u32, expected: u32, now: u64) -> bool {
+    let time_bits = cookie & TIME_MASK;
+    let current_time = ((now / SYN_COOKIE_WINDOW_NS) as u32) & TIME_MASK;
+
+    let time_diff = if current_time >= time_bits {
+        current_time - time_bits
+    } else {
+        32 - time_bits + current_time
+    };
+
+    if time_diff > SYN_COOKIE_MAX_WINDOW_DIFF {
+        return false;
+    }
+
+    (cookie & HASH_MASK) == (expected & HASH_MASK)
+}
+
+/// Connection 4-tuple of a cookie entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CookieKey {
+    pub src_ip: u32,
+    pub src_port: u16,
+    pub dst_ip: u32,
+    pub dst_port: u16,
+}
+
+/// Stored cookie (mirrors `SynCookieEntry`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CookieEntry {
+    pub cookie: u32,
+    pub created: u64,
+}
+
+/// Outcome of the first ACK of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieCheck {
+    /// No cookie was issued for the connection
+    NotIssued,
+    Valid,
+    Invalid,
+}
+
+/// `SYN_COOKIES` map with the clock that drives it
+#[derive(Debug)]
+pub struct SynCookieJar<C: Clock> {
+    clock: C,
+    secrets: (u32, u32),
+    entries: HashMap<CookieKey, CookieEntry>,
+}
+
+impl<C: Clock> SynCookieJar<C> {
+    pub fn new(clock: C, secrets: (u32, u32)) -> Self {
+        Self {
+            clock,
+            secrets,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Issue a cookie for a SYN with sequence number `seq`
+    pub fn issue(&mut self, key: CookieKey, seq: u32) -> u32 {
+        let now = self.clock.now_ns();
+        let cookie = generate_syn_cookie(
+            self.secrets,
+            key.src_ip,
+            key.src_port,
+            key.dst_ip,
+            key.dst_port,
+            seq,
+            now,
+        );
+        self.entries.insert(
+            key,
+            CookieEntry {
+                cookie,
+                created: now,
+            },
+        );
+        cookie
+    }
+
+    /// Validate the acknowledgment number of the first ACK
+    pub fn check_ack(&self, key: &CookieKey, ack_seq: u32) -> CookieCheck {
+        match self.entries.get(key) {
+            None => CookieCheck::NotIssued,
+            Some(entry) => {
+                let echoed = ack_seq.wrapping_sub(1);
+                if validate_syn_cookie(echoed, entry.cookie, self.clock.now_ns()) {
+                    CookieCheck::Valid
+                } else {
+                    CookieCheck::Invalid
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, key: &CookieKey) -> Option<&CookieEntry> {
+        self.entries.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Maintenance pass: drop entries older than `SYN_COOKIE_TTL_NS`
+    pub fn cleanup_expired(&mut self) {
+        let now = self.clock.now_ns();
+        self.entries
+            .retain(|_, entry| now.saturating_sub(entry.created) < SYN_COOKIE_TTL_NS);
+    }
+}
@@ -0,0 +1,323 @@
+//! UDP Amplification Detection Tests
+//!
+//! Tests the `check_amplification_attack` heuristics against
+//! protocol-accurate DNS, NTP, SSDP and memcached responses, covering both
+//! legitimate replies that must pass and reflection payloads that must drop.
+
+use pistonprotection_ebpf_tests::amplification::*;
+use pistonprotection_ebpf_tests::packet_generator::*;
+
+#[cfg(test)]
+mod dns_amplification_tests {
+    use super::*;
+
+    /// Ordinary A record response passes
+    #[test]
+    fn test_normal_response_passes() {
+        let payload = DnsResponse::new().build();
+
+        assert!(payload.len() < 64);
+        assert_eq!(
+            check_amplification(PORT_DNS, &payload, 3),
+            AmpVerdict::default()
+        );
+    }
+
+    /// Header fields land at their wire offsets
+    #[test]
+    fn test_response_layout() {
+        let payload = DnsResponse::new()
+            .with_transaction_id(0xbeef)
+            .with_qdcount(2)
+            .with_ancount(3)
+            .with_edns(1232)
+            .build();
+
+        assert_eq!(&payload[0..2], &0xbeefu16.to_be_bytes());
+        assert_eq!(payload[2] & 0x80, 0x80);
+        assert_eq!(&payload[4..6], &2u16.to_be_bytes());
+        assert_eq!(&payload[6..8], &3u16.to_be_bytes());
+        assert_eq!(&payload[10..12], &1u16.to_be_bytes());
+
+        // OPT record advertises the EDNS UDP size in its class field
+        let opt = payload.len() - 11;
+        assert_eq!(&payload[opt + 1..opt + 3], &DNS_TYPE_OPT.to_be_bytes());
+        assert_eq!(&payload[opt + 3..opt + 5], &1232u16.to_be_bytes());
+    }
+
+    /// Many answers to one question is flagged even when small
+    #[test]
+    fn test_answer_ratio_detected() {
+        let payload = DnsResponse::new().with_ancount(20).build();
+        assert!(payload.len() <= 512);
+
+        let verdict = check_amplification(PORT_DNS, &payload, 1);
+        assert!(verdict.detected);
+        assert!(!verdict.drop);
+
+        assert!(check_amplification(PORT_DNS, &payload, 2).drop);
+    }
+
+    /// ANY response with EDNS-sized answers drops at moderate protection
+    #[test]
+    fn test_any_amplification_drops() {
+        let payload = DnsResponse::any_amplification(15).build();
+        assert!(payload.len() > 1024);
+
+        let verdict = check_amplification(PORT_DNS, &payload, 2);
+        assert!(verdict.detected);
+        assert!(verdict.tracked);
+        assert!(verdict.drop);
+    }
+
+    /// Large response with few answers only drops at aggressive protection
+    #[test]
+    fn test_large_response_aggressive_only() {
+        let payload = DnsResponse::new()
+            .with_ancount(3)
+            .with_answer_size(200)
+            .with_edns(4096)
+            .build();
+        assert!(payload.len() > 512 && payload.len() <= 1024);
+
+        // 3 answers for 1 question is within the ratio, so it is not flagged
+        assert!(!check_amplification(PORT_DNS, &payload, 3).detected);
+
+        let payload = DnsResponse::new()
+            .with_ancount(8)
+            .with_answer_size(100)
+            .build();
+        assert!(payload.len() > 512 && payload.len() <= 1024);
+
+        assert!(!check_amplification(PORT_DNS, &payload, 2).drop);
+        assert!(check_amplification(PORT_DNS, &payload, 3).drop);
+    }
+
+    /// Queries (QR clear) are never treated as amplification
+    #[test]
+    fn test_query_not_flagged() {
+        let payload = DnsResponse::any_amplification(20)
+            .with_flags(0x0100)
+            .build();
+
+        assert!(!check_amplification(PORT_DNS, &payload, 3).detected);
+    }
+
+    /// Responses with reserved opcodes are not inspected
+    #[test]
+    fn test_reserved_opcode_ignored() {
+        let payload = DnsResponse::any_amplification(20).with_opcode(9).build();
+
+        assert!(!check_amplification(PORT_DNS, &payload, 3).detected);
+    }
+
+    /// Truncated header is ignored
+    #[test]
+    fn test_truncated_header() {
+        let payload = DnsResponse::any_amplification(20).build();
+
+        assert!(!check_amplification(PORT_DNS, &payload[..11], 3).detected);
+    }
+}
+
+#[cfg(test)]
+mod ntp_amplification_tests {
+    use super::*;
+
+    /// Standard 48-byte server reply passes
+    #[test]
+    fn test_server_reply_passes() {
+        let payload = NtpResponse::server().build();
+
+        assert_eq!(payload.len(), 48);
+        assert_eq!(payload[0] & 0x07, 4);
+        assert_eq!(
+            check_amplification(PORT_NTP, &payload, 3),
+            AmpVerdict::default()
+        );
+    }
+
+    /// Mode 7 monlist reply drops at any protection level
+    #[test]
+    fn test_monlist_drops() {
+        let payload = NtpResponse::monlist(6).build();
+
+        assert_eq!(payload.len(), 8 + 6 * NTP_MONLIST_ITEM_SIZE);
+        assert_eq!(payload[0] & 0x07, 7);
+        assert_eq!(payload[3], NTP_MON_GETLIST_1);
+
+        let verdict = check_amplification(PORT_NTP, &payload, 1);
+        assert!(verdict.detected);
+        assert!(verdict.drop);
+        assert!(!check_amplification(PORT_NTP, &payload, 0).drop);
+    }
+
+    /// Monlist replies are capped per datagram and flag further responses
+    #[test]
+    fn test_monlist_more_bit() {
+        let payload = NtpResponse::monlist(600).build();
+
+        assert_eq!(payload[0] & 0x40, 0x40);
+        assert_eq!(
+            u16::from_be_bytes([payload[4], payload[5]]) & 0x0fff,
+            NTP_MONLIST_ITEMS_PER_PACKET
+        );
+    }
+
+    /// Mode 6 control reply with data drops at moderate protection
+    #[test]
+    fn test_control_reply() {
+        let payload = NtpResponse::control(400).build();
+
+        assert_eq!(payload.len() % 4, 0);
+        assert_eq!(u16::from_be_bytes([payload[10], payload[11]]), 400);
+
+        let verdict = check_amplification(PORT_NTP, &payload, 1);
+        assert!(verdict.detected);
+        assert!(!verdict.drop);
+        assert!(check_amplification(PORT_NTP, &payload, 2).drop);
+    }
+
+    /// Empty mode 6 reply (header only) is not flagged
+    #[test]
+    fn test_empty_control_reply() {
+        let payload = NtpResponse::control(0).build();
+
+        assert_eq!(payload.len(), 12);
+        assert!(!check_amplification(PORT_NTP, &payload, 3).detected);
+    }
+
+    /// Server reply with an invalid version is flagged
+    #[test]
+    fn test_invalid_version() {
+        let payload = NtpResponse::server().with_version(0).build();
+
+        let verdict = check_amplification(PORT_NTP, &payload, 2);
+        assert!(verdict.detected);
+        assert!(verdict.drop);
+    }
+}
+
+#[cfg(test)]
+mod ssdp_amplification_tests {
+    use super::*;
+
+    /// M-SEARCH reply is a well-formed HTTP response
+    #[test]
+    fn test_reply_format() {
+        let payload = SsdpResponse::new().build();
+        let text = String::from_utf8(payload).unwrap();
+
+        assert!(text.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(text.contains("ST: upnp:rootdevice\r\n"));
+        assert!(text.ends_with("\r\n\r\n"));
+    }
+
+    /// Typical rootdevice replies exceed the size threshold
+    #[test]
+    fn test_reply_detected() {
+        let payload = SsdpResponse::new().build();
+        assert!(payload.len() > 200);
+
+        assert!(!check_amplification(PORT_SSDP, &payload, 1).drop);
+        assert!(check_amplification(PORT_SSDP, &payload, 2).drop);
+    }
+
+    /// Minimal replies stay under the threshold
+    #[test]
+    fn test_short_reply_passes() {
+        let payload = SsdpResponse::new()
+            .with_location("http://a/")
+            .with_server("x")
+            .with_search_target("ssdp:all")
+            .build();
+        assert!(payload.len() <= 200);
+
+        assert!(!check_amplification(PORT_SSDP, &payload, 3).detected);
+    }
+}
+
+#[cfg(test)]
+mod memcached_amplification_tests {
+    use super::*;
+
+    /// Binary GETK response header layout
+    #[test]
+    fn test_binary_layout() {
+        let payload = MemcachedResponse::new()
+            .binary()
+            .with_key("k")
+            .with_value_len(10)
+            .build();
+
+        assert_eq!(payload[0], MEMCACHED_RESPONSE_MAGIC);
+        assert_eq!(&payload[2..4], &1u16.to_be_bytes());
+        assert_eq!(payload[4], 4);
+        assert_eq!(&payload[8..12], &15u32.to_be_bytes());
+        assert_eq!(payload.len(), 24 + 15);
+    }
+
+    /// Binary responses drop regardless of size
+    #[test]
+    fn test_binary_drops() {
+        let payload = MemcachedResponse::new().binary().with_value_len(1).build();
+
+        assert!(check_amplification(PORT_MEMCACHED, &payload, 1).drop);
+    }
+
+    /// Small text responses pass, large ones drop
+    #[test]
+    fn test_text_sizes() {
+        let small = MemcachedResponse::new().text().with_value_len(16).build();
+        assert!(!check_amplification(PORT_MEMCACHED, &small, 3).detected);
+
+        let medium = MemcachedResponse::new().text().with_value_len(200).build();
+        let verdict = check_amplification(PORT_MEMCACHED, &medium, 3);
+        assert!(verdict.detected);
+        assert!(!verdict.drop);
+
+        let large = MemcachedResponse::new().text().with_value_len(1000).build();
+        assert!(check_amplification(PORT_MEMCACHED, &large, 1).drop);
+    }
+
+    /// Stats responses are large text responses
+    #[test]
+    fn test_stats_drops() {
+        let payload = MemcachedResponse::new().stats(50).build();
+        let text = String::from_utf8(payload.clone()).unwrap();
+
+        assert!(text.starts_with("STAT "));
+        assert!(text.ends_with("END\r\n"));
+        assert!(check_amplification(PORT_MEMCACHED, &payload, 1).drop);
+    }
+
+    /// The UDP frame header hides the binary magic; only size catches it
+    #[test]
+    fn test_udp_frame_hides_magic() {
+        let small = MemcachedResponse::new()
+            .binary()
+            .with_value_len(1)
+            .with_udp_frame(0x0001)
+            .build();
+        assert_eq!(small[8], MEMCACHED_RESPONSE_MAGIC);
+        assert!(!check_amplification(PORT_MEMCACHED, &small, 3).detected);
+
+        let large = MemcachedResponse::new()
+            .binary()
+            .with_value_len(1000)
+            .with_udp_frame(0x0001)
+            .build();
+        assert!(check_amplification(PORT_MEMCACHED, &large, 1).drop);
+    }
+
+    /// Requests to memcached (destination port) are not inspected
+    #[test]
+    fn test_non_amp_port_ignored() {
+        let payload = MemcachedResponse::new().binary().build();
+
+        assert_eq!(
+            check_amplification(40000, &payload, 3),
+            AmpVerdict::default()
+        );
+    }
+}
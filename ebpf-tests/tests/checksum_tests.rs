@@ -0,0 +1,289 @@
+//! Checksum and Packet Rewriting Tests
+//!
+//! Tests the XDP_TX checksum helpers against reference checksums: known
+//! header vectors, a straightforward big-endian RFC 1071 implementation, and
+//! full recomputation after in-place rewrites.
+
+use pistonprotection_ebpf_tests::checksum::*;
+use pistonprotection_ebpf_tests::packet_generator::*;
+use pistonprotection_ebpf_tests::parser::*;
+use std::net::Ipv4Addr;
+
+/// Reference RFC 1071 checksum over big-endian words
+fn reference_csum(bytes: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in bytes.chunks(2) {
+        let word = if chunk.len() == 2 {
+            ((chunk[0] as u32) << 8) | chunk[1] as u32
+        } else {
+            (chunk[0] as u32) << 8
+        };
+        sum += word;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Reference TCP/UDP checksum over an IPv4 pseudo-header and segment
+fn reference_l4_csum(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, segment: &[u8]) -> u16 {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&src.octets());
+    buf.extend_from_slice(&dst.octets());
+    buf.push(0);
+    buf.push(protocol);
+    buf.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+    buf.extend_from_slice(segment);
+    reference_csum(&buf)
+}
+
+/// Big-endian value of a checksum produced by the native-word helpers
+fn as_be(check: u16) -> u16 {
+    u16::from_be_bytes(check.to_ne_bytes())
+}
+
+/// Ethernet/IPv4/TCP SYN from 192.168.1.100:40000 to 10.0.0.1:25565
+fn tcp_frame() -> Vec<u8> {
+    let tcp = TcpSegment::new()
+        .with_src_port(40000)
+        .with_dst_port(25565)
+        .with_seq(0x1234_5678)
+        .syn()
+        .build();
+
+    let ip = Ipv4Packet::new()
+        .with_src_ip(Ipv4Addr::new(192, 168, 1, 100))
+        .with_dst_ip(Ipv4Addr::new(10, 0, 0, 1))
+        .with_protocol(IPPROTO_TCP)
+        .with_payload(tcp)
+        .build();
+
+    EthernetFrame::new().with_payload(ip).build()
+}
+
+/// Fill in the TCP checksum of a frame built by `tcp_frame`
+fn fill_tcp_csum(frame: &mut [u8]) {
+    let l3 = ETH_HLEN;
+    let l4 = l3 + IPV4_HLEN;
+    let sum = ipv4_pseudo_csum(
+        raw_u32(frame, l3 + 12),
+        raw_u32(frame, l3 + 16),
+        IPPROTO_TCP,
+        (frame.len() - l4) as u16,
+    );
+    frame[l4 + 16..l4 + 18].copy_from_slice(&[0, 0]);
+    let check = csum_fold(csum_add_bytes(sum, &frame[l4..]));
+    frame[l4 + 16..l4 + 18].copy_from_slice(&check.to_ne_bytes());
+}
+
+/// A valid checksum makes the sum over the covered bytes fold to zero
+fn verifies(bytes: &[u8]) -> bool {
+    reference_csum(bytes) == 0
+}
+
+#[cfg(test)]
+mod reference_checksum_tests {
+    use super::*;
+
+    /// RFC 1071 section 3 example
+    #[test]
+    fn test_rfc1071_example() {
+        let bytes = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+
+        assert_eq!(as_be(csum_fold(csum_add_bytes(0, &bytes))), 0x220d);
+        assert_eq!(reference_csum(&bytes), 0x220d);
+    }
+
+    /// Well-known IPv4 header with checksum 0xb861
+    #[test]
+    fn test_ipv4_header_vector() {
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0xb8, 0x61, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+
+        assert_eq!(as_be(ipv4_csum(&header)), 0xb861);
+    }
+
+    /// Helper agrees with the packet generator's IPv4 checksum
+    #[test]
+    fn test_ipv4_matches_generator() {
+        let frame = tcp_frame();
+        let header = &frame[ETH_HLEN..ETH_HLEN + IPV4_HLEN];
+
+        assert_eq!(ipv4_csum(header), raw_u16(header, 10));
+        assert!(verifies(header));
+    }
+
+    /// Windows ping echo request (id 1, seq 1) has checksum 0x4d5a
+    #[test]
+    fn test_icmp_echo_vector() {
+        let mut message = vec![ICMP_ECHO_REQUEST, 0, 0, 0, 0, 1, 0, 1];
+        message.extend_from_slice(b"abcdefghijklmnopqrstuvwabcdefghi");
+
+        assert_eq!(as_be(icmp_csum(&message)), 0x4d5a);
+    }
+
+    /// Pseudo-header TCP checksum matches the reference implementation
+    #[test]
+    fn test_tcp_checksum_matches_reference() {
+        let mut frame = tcp_frame();
+        fill_tcp_csum(&mut frame);
+
+        let l4 = ETH_HLEN + IPV4_HLEN;
+        let mut segment = frame[l4..].to_vec();
+        segment[16] = 0;
+        segment[17] = 0;
+        let expected = reference_l4_csum(
+            Ipv4Addr::new(192, 168, 1, 100),
+            Ipv4Addr::new(10, 0, 0, 1),
+            IPPROTO_TCP,
+            &segment,
+        );
+
+        assert_eq!(as_be(raw_u16(&frame, l4 + 16)), expected);
+    }
+
+    /// Odd-length payloads are padded with a zero byte
+    #[test]
+    fn test_odd_length_padding() {
+        let bytes = [0x12, 0x34, 0x56];
+
+        assert_eq!(
+            as_be(csum_fold(csum_add_bytes(0, &bytes))),
+            reference_csum(&bytes)
+        );
+    }
+
+    /// IPv6 pseudo-header matches the reference layout
+    #[test]
+    fn test_ipv6_pseudo_header() {
+        let src = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let dst = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let segment = [
+            0x9c, 0x40, 0x00, 0x35, 0x00, 0x0c, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+
+        let sum = ipv6_pseudo_csum(&src, &dst, IPPROTO_UDP, segment.len() as u32);
+        let check = csum_fold(csum_add_bytes(sum, &segment));
+
+        let mut reference = Vec::new();
+        reference.extend_from_slice(&src);
+        reference.extend_from_slice(&dst);
+        reference.extend_from_slice(&(segment.len() as u32).to_be_bytes());
+        reference.extend_from_slice(&[0, 0, 0, IPPROTO_UDP]);
+        reference.extend_from_slice(&segment);
+
+        assert_eq!(as_be(check), reference_csum(&reference));
+    }
+}
+
+#[cfg(test)]
+mod incremental_update_tests {
+    use super::*;
+
+    /// TTL change updated incrementally matches a full recompute
+    #[test]
+    fn test_set_ttl_incremental() {
+        let mut frame = tcp_frame();
+        ipv4_set_ttl(&mut frame, ETH_HLEN, 64);
+
+        let header = &frame[ETH_HLEN..ETH_HLEN + IPV4_HLEN];
+        assert_eq!(header[8], 64);
+        assert_eq!(raw_u16(header, 10), ipv4_csum(header));
+    }
+
+    /// Replacing the TCP sequence number keeps the checksum valid
+    #[test]
+    fn test_replace4_sequence() {
+        let mut frame = tcp_frame();
+        fill_tcp_csum(&mut frame);
+
+        let l4 = ETH_HLEN + IPV4_HLEN;
+        let old = raw_u32(&frame, l4 + 4);
+        frame[l4 + 4..l4 + 8].copy_from_slice(&0xdead_beefu32.to_be_bytes());
+        let new = raw_u32(&frame, l4 + 4);
+        let check = csum_replace4(raw_u16(&frame, l4 + 16), old, new);
+        frame[l4 + 16..l4 + 18].copy_from_slice(&check.to_ne_bytes());
+
+        let incremental = raw_u16(&frame, l4 + 16);
+        fill_tcp_csum(&mut frame);
+        assert_eq!(incremental, raw_u16(&frame, l4 + 16));
+    }
+
+    /// Replacing a port keeps the checksum valid
+    #[test]
+    fn test_replace2_port() {
+        let mut frame = tcp_frame();
+        fill_tcp_csum(&mut frame);
+
+        let l4 = ETH_HLEN + IPV4_HLEN;
+        let old = raw_u16(&frame, l4 + 2);
+        frame[l4 + 2..l4 + 4].copy_from_slice(&19132u16.to_be_bytes());
+        let new = raw_u16(&frame, l4 + 2);
+        let check = csum_replace2(raw_u16(&frame, l4 + 16), old, new);
+        frame[l4 + 16..l4 + 18].copy_from_slice(&check.to_ne_bytes());
+
+        let incremental = raw_u16(&frame, l4 + 16);
+        fill_tcp_csum(&mut frame);
+        assert_eq!(incremental, raw_u16(&frame, l4 + 16));
+    }
+
+    /// Echo request to reply conversion yields the reference reply checksum
+    #[test]
+    fn test_echo_to_reply() {
+        let mut message = vec![ICMP_ECHO_REQUEST, 0, 0, 0, 0, 1, 0, 1];
+        message.extend_from_slice(b"abcdefghijklmnopqrstuvwabcdefghi");
+        let check = icmp_csum(&message);
+        message[2..4].copy_from_slice(&check.to_ne_bytes());
+
+        icmp_echo_to_reply(&mut message, 0);
+
+        assert_eq!(message[0], ICMP_ECHO_REPLY);
+        assert_eq!(as_be(raw_u16(&message, 2)), 0x555a);
+        assert!(verifies(&message));
+    }
+}
+
+#[cfg(test)]
+mod rewrite_tests {
+    use super::*;
+
+    /// Swapping MACs reverses the frame direction
+    #[test]
+    fn test_swap_eth_addrs() {
+        let mut frame = EthernetFrame::new()
+            .with_src_mac([1, 2, 3, 4, 5, 6])
+            .with_dst_mac([7, 8, 9, 10, 11, 12])
+            .build();
+
+        swap_eth_addrs(&mut frame);
+
+        assert_eq!(&frame[0..6], &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(&frame[6..12], &[7, 8, 9, 10, 11, 12]);
+    }
+
+    /// Swapping addresses and ports leaves both checksums valid
+    #[test]
+    fn test_reflect_keeps_checksums() {
+        let mut frame = tcp_frame();
+        fill_tcp_csum(&mut frame);
+        let l3 = ETH_HLEN;
+        let l4 = l3 + IPV4_HLEN;
+        let ip_check = raw_u16(&frame, l3 + 10);
+        let tcp_check = raw_u16(&frame, l4 + 16);
+
+        swap_eth_addrs(&mut frame);
+        swap_ipv4_addrs(&mut frame, l3);
+        swap_ports(&mut frame, l4);
+
+        assert_eq!(&frame[l3 + 12..l3 + 16], &[10, 0, 0, 1]);
+        assert_eq!(transport_ports(&frame, l4), Some((25565, 40000)));
+        assert_eq!(raw_u16(&frame, l3 + 10), ip_check);
+        assert!(verifies(&frame[l3..l4]));
+
+        fill_tcp_csum(&mut frame);
+        assert_eq!(raw_u16(&frame, l4 + 16), tcp_check);
+    }
+}
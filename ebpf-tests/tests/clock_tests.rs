@@ -0,0 +1,344 @@
+//! Clock-Driven Window and Expiry Tests
+//!
+//! Drives the reference filters with a `MockClock` to check time-dependent
+//! behavior deterministically: token bucket refill and window reset, timed
+//! block expiry, and SYN cookie validity windows and TTL cleanup.
+
+use pistonprotection_ebpf_tests::clock::*;
+use pistonprotection_ebpf_tests::packet_generator::*;
+use pistonprotection_ebpf_tests::scenario::*;
+use pistonprotection_ebpf_tests::syn_cookie::*;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+/// Time for one token to refill
+const TOKEN_NS: u64 = 1 << RATE_LIMIT_REFILL_SHIFT;
+
+fn attacker() -> Ipv4Addr {
+    Ipv4Addr::new(203, 0, 113, 7)
+}
+
+fn udp_from(src: Ipv4Addr) -> Vec<u8> {
+    create_udp_packet(src, Ipv4Addr::new(10, 0, 0, 1), 40000, 19132, vec![0u8; 16])
+}
+
+/// Reference filter with only the blocklist and rate limit enabled
+fn rate_limit_filter(clock: &MockClock) -> ReferenceFilter {
+    let config = ScenarioConfig {
+        amplification: false,
+        ..Default::default()
+    };
+    ReferenceFilter::new(config, clock.clone())
+}
+
+fn cookie_key() -> CookieKey {
+    CookieKey {
+        src_ip: u32::from(attacker()),
+        src_port: 40000,
+        dst_ip: u32::from(Ipv4Addr::new(10, 0, 0, 1)),
+        dst_port: 25565,
+    }
+}
+
+/// Send `count` copies of `frame`, returning how many passed
+fn send(filter: &mut ReferenceFilter, frame: &[u8], count: usize) -> usize {
+    (0..count)
+        .filter(|_| filter.process(frame) == Verdict::Pass)
+        .count()
+}
+
+#[cfg(test)]
+mod mock_clock_tests {
+    use super::*;
+
+    /// Clones share one time source
+    #[test]
+    fn test_clones_share_time() {
+        let clock = MockClock::new(5);
+        let handle = clock.clone();
+
+        handle.advance(Duration::from_millis(2));
+        assert_eq!(clock.now_ns(), 5 + 2_000_000);
+
+        clock.set_ns(10_000_000);
+        assert_eq!(handle.now_ns(), 10_000_000);
+    }
+
+    /// The mock clock is monotonic like `bpf_ktime_get_ns`
+    #[test]
+    #[should_panic(expected = "moved backwards")]
+    fn test_set_backwards_panics() {
+        let clock = MockClock::new(100);
+        clock.set_ns(99);
+    }
+
+    /// The system clock never goes backwards
+    #[test]
+    fn test_system_clock_monotonic() {
+        let clock = SystemClock::new();
+        let first = clock.now_ns();
+        assert!(clock.now_ns() >= first);
+    }
+}
+
+#[cfg(test)]
+mod rate_limit_window_tests {
+    use super::*;
+
+    /// A fresh source gets exactly one bucket of packets
+    #[test]
+    fn test_burst_then_drop() {
+        let clock = MockClock::default();
+        let mut filter = rate_limit_filter(&clock);
+        let frame = udp_from(attacker());
+
+        assert_eq!(send(&mut filter, &frame, 1500), RATE_LIMIT_BURST as usize);
+        assert_eq!(filter.stats().rate_limited, 1500 - RATE_LIMIT_BURST);
+    }
+
+    /// Tokens refill at one per 2^20 ns, not per packet
+    #[test]
+    fn test_partial_refill() {
+        let clock = MockClock::default();
+        let mut filter = rate_limit_filter(&clock);
+        let frame = udp_from(attacker());
+        send(&mut filter, &frame, RATE_LIMIT_BURST as usize);
+
+        clock.advance_ns(TOKEN_NS - 1);
+        assert_eq!(send(&mut filter, &frame, 1), 0);
+
+        // The failed attempt reset last_update, so a full token interval is needed again
+        clock.advance_ns(TOKEN_NS);
+        assert_eq!(send(&mut filter, &frame, 5), 1);
+
+        clock.advance_ns(10 * TOKEN_NS);
+        assert_eq!(send(&mut filter, &frame, 20), 10);
+    }
+
+    /// After a full refill period the source has its whole burst back
+    #[test]
+    fn test_window_reset() {
+        let clock = MockClock::default();
+        let mut filter = rate_limit_filter(&clock);
+        let frame = udp_from(attacker());
+        send(&mut filter, &frame, 2000);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(send(&mut filter, &frame, 2000), RATE_LIMIT_BURST as usize);
+    }
+
+    /// Sources have independent buckets
+    #[test]
+    fn test_buckets_per_source() {
+        let clock = MockClock::default();
+        let mut filter = rate_limit_filter(&clock);
+        send(&mut filter, &udp_from(attacker()), 2000);
+
+        let other = udp_from(Ipv4Addr::new(198, 51, 100, 1));
+        assert_eq!(send(&mut filter, &other, 10), 10);
+    }
+
+    /// Maintenance drops only buckets that have fully refilled
+    #[test]
+    fn test_cleanup_idle_buckets() {
+        let clock = MockClock::default();
+        let mut filter = rate_limit_filter(&clock);
+        let frame = udp_from(attacker());
+        send(&mut filter, &frame, 2000);
+
+        clock.advance_ns((RATE_LIMIT_BURST - 1) * TOKEN_NS);
+        filter.cleanup_expired();
+        assert_eq!(filter.tracked_sources(), 1);
+
+        clock.advance_ns(TOKEN_NS);
+        filter.cleanup_expired();
+        assert_eq!(filter.tracked_sources(), 0);
+
+        // A recreated bucket behaves like the refilled one would have
+        assert_eq!(send(&mut filter, &frame, 2000), RATE_LIMIT_BURST as usize);
+    }
+}
+
+#[cfg(test)]
+mod block_expiry_tests {
+    use super::*;
+
+    /// Timed blocks apply until their expiry and lift exactly at it
+    #[test]
+    fn test_timed_block_expires() {
+        let clock = MockClock::new(1_000);
+        let mut filter = rate_limit_filter(&clock);
+        let frame = udp_from(attacker());
+        filter.block_ip(IpAddr::V4(attacker()), Some(Duration::from_secs(30)));
+
+        assert_eq!(filter.process(&frame), Verdict::Drop);
+
+        clock.advance(Duration::from_secs(30) - Duration::from_nanos(1));
+        assert_eq!(filter.process(&frame), Verdict::Drop);
+
+        // Kernel check is `expires_at > now`
+        clock.advance_ns(1);
+        assert_eq!(filter.process(&frame), Verdict::Pass);
+        assert_eq!(filter.stats().blocked, 2);
+    }
+
+    /// Permanent blocks never expire
+    #[test]
+    fn test_permanent_block() {
+        let clock = MockClock::default();
+        let mut filter = rate_limit_filter(&clock);
+        filter.block_ip(IpAddr::V4(attacker()), None);
+
+        clock.advance(Duration::from_secs(365 * 24 * 3600));
+        filter.cleanup_expired();
+        assert!(filter.is_blocked(&IpAddr::V4(attacker())));
+    }
+
+    /// Config blocklist entries are permanent
+    #[test]
+    fn test_config_blocks_permanent() {
+        let clock = MockClock::default();
+        let config = ScenarioConfig {
+            blocked_ips: vec![IpAddr::V4(attacker())],
+            ..Default::default()
+        };
+        let mut filter = ReferenceFilter::new(config, clock.clone());
+
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(filter.process(&udp_from(attacker())), Verdict::Drop);
+    }
+
+    /// Maintenance removes expired blocks only
+    #[test]
+    fn test_cleanup_expired_blocks() {
+        let clock = MockClock::default();
+        let mut filter = rate_limit_filter(&clock);
+        let short = IpAddr::V4(attacker());
+        let long = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+        filter.block_ip(short, Some(Duration::from_secs(10)));
+        filter.block_ip(long, Some(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(11));
+        filter.cleanup_expired();
+
+        assert!(!filter.is_blocked(&short));
+        assert!(filter.is_blocked(&long));
+    }
+
+    /// Re-blocking extends the expiry from the current time
+    #[test]
+    fn test_reblock_extends() {
+        let clock = MockClock::default();
+        let mut filter = rate_limit_filter(&clock);
+        let ip = IpAddr::V4(attacker());
+        filter.block_ip(ip, Some(Duration::from_secs(10)));
+
+        clock.advance(Duration::from_secs(8));
+        filter.block_ip(ip, Some(Duration::from_secs(10)));
+
+        clock.advance(Duration::from_secs(8));
+        assert!(filter.is_blocked(&ip));
+    }
+}
+
+#[cfg(test)]
+mod syn_cookie_ttl_tests {
+    use super::*;
+
+    const SECRETS: (u32, u32) = (0x1234_5678, 0x9abc_def0);
+
+    /// The echoed cookie validates immediately after issue
+    #[test]
+    fn test_cookie_valid_on_ack() {
+        let clock = MockClock::default();
+        let mut jar = SynCookieJar::new(clock.clone(), SECRETS);
+        let cookie = jar.issue(cookie_key(), 1_000_000);
+
+        assert_eq!(cookie & 0x1f, 0);
+        assert_eq!((cookie >> 5) & 0x03, 3);
+        assert_eq!(
+            jar.check_ack(&cookie_key(), cookie.wrapping_add(1)),
+            CookieCheck::Valid
+        );
+    }
+
+    /// A wrong acknowledgment number fails the hash comparison
+    #[test]
+    fn test_wrong_cookie_rejected() {
+        let clock = MockClock::default();
+        let mut jar = SynCookieJar::new(clock.clone(), SECRETS);
+        let cookie = jar.issue(cookie_key(), 1_000_000);
+
+        let forged = cookie ^ 0x8000_0000;
+        assert_eq!(
+            jar.check_ack(&cookie_key(), forged.wrapping_add(1)),
+            CookieCheck::Invalid
+        );
+    }
+
+    /// Cookies validate for two windows after the issuing one
+    #[test]
+    fn test_validity_windows() {
+        let clock = MockClock::new(SYN_COOKIE_WINDOW_NS / 2);
+        let mut jar = SynCookieJar::new(clock.clone(), SECRETS);
+        let ack = jar.issue(cookie_key(), 42).wrapping_add(1);
+
+        clock.set_ns(3 * SYN_COOKIE_WINDOW_NS - 1);
+        assert_eq!(jar.check_ack(&cookie_key(), ack), CookieCheck::Valid);
+
+        clock.set_ns(3 * SYN_COOKIE_WINDOW_NS);
+        assert_eq!(jar.check_ack(&cookie_key(), ack), CookieCheck::Invalid);
+    }
+
+    /// Entries outlive neither the TTL nor the maintenance pass
+    #[test]
+    fn test_ttl_cleanup() {
+        let clock = MockClock::default();
+        let mut jar = SynCookieJar::new(clock.clone(), SECRETS);
+        jar.issue(cookie_key(), 42);
+
+        clock.advance_ns(SYN_COOKIE_TTL_NS - 1);
+        jar.cleanup_expired();
+        assert_eq!(jar.len(), 1);
+
+        clock.advance_ns(1);
+        jar.cleanup_expired();
+        assert!(jar.is_empty());
+        assert_eq!(jar.check_ack(&cookie_key(), 1), CookieCheck::NotIssued);
+    }
+
+    /// The 5-bit time counter wraps after 32 windows; only cleanup stops reuse
+    #[test]
+    fn test_counter_wrap_needs_cleanup() {
+        let clock = MockClock::default();
+        let mut jar = SynCookieJar::new(clock.clone(), SECRETS);
+        let ack = jar.issue(cookie_key(), 42).wrapping_add(1);
+
+        clock.set_ns(32 * SYN_COOKIE_WINDOW_NS);
+        assert_eq!(jar.check_ack(&cookie_key(), ack), CookieCheck::Valid);
+
+        jar.cleanup_expired();
+        assert_eq!(jar.check_ack(&cookie_key(), ack), CookieCheck::NotIssued);
+    }
+
+    /// Cookies depend on the time window and the client ISN
+    #[test]
+    fn test_cookie_inputs() {
+        let key = cookie_key();
+        let cookie = |seq, now| {
+            generate_syn_cookie(
+                SECRETS,
+                key.src_ip,
+                key.src_port,
+                key.dst_ip,
+                key.dst_port,
+                seq,
+                now,
+            )
+        };
+
+        assert_eq!(cookie(1, 0), cookie(1, SYN_COOKIE_WINDOW_NS - 1));
+        assert_ne!(cookie(1, 0), cookie(1, SYN_COOKIE_WINDOW_NS));
+        assert_ne!(cookie(1, 0) & 0xFFFFFF80, cookie(2, 0) & 0xFFFFFF80);
+    }
+}
@@ -0,0 +1,126 @@
+//! Multi-Buffer (xdp.frags) Payload Tests
+//!
+//! Tests that payload inspection sees the same bytes on jumbo frames split
+//! across buffers as it does on linear frames.
+
+use pistonprotection_ebpf_tests::packet_generator::*;
+use pistonprotection_ebpf_tests::parser::*;
+use std::net::Ipv4Addr;
+
+/// Offset of the UDP payload in an untagged IPv4 frame
+const UDP_PAYLOAD_OFFSET: usize = ETH_HLEN + IPV4_HLEN + 8;
+
+/// Build an Ethernet/IPv4/UDP frame carrying `payload`
+fn udp_frame(src_port: u16, payload: Vec<u8>) -> Vec<u8> {
+    let udp = UdpDatagram::new()
+        .with_src_port(src_port)
+        .with_dst_port(40000)
+        .with_payload(payload)
+        .build();
+
+    let ip = Ipv4Packet::new()
+        .with_src_ip(Ipv4Addr::new(8, 8, 8, 8))
+        .with_dst_ip(Ipv4Addr::new(10, 0, 0, 1))
+        .with_protocol(IPPROTO_UDP)
+        .with_payload(udp)
+        .build();
+
+    EthernetFrame::new().with_payload(ip).build()
+}
+
+/// DNS response header with the given answer count, padded to `len` bytes
+fn dns_response(ancount: u16, len: usize) -> Vec<u8> {
+    let mut payload = vec![0u8; len];
+    payload[0..2].copy_from_slice(&0x1234u16.to_be_bytes());
+    payload[2..4].copy_from_slice(&0x8180u16.to_be_bytes());
+    payload[4..6].copy_from_slice(&1u16.to_be_bytes());
+    payload[6..8].copy_from_slice(&ancount.to_be_bytes());
+    payload
+}
+
+#[cfg(test)]
+mod payload_view_tests {
+    use super::*;
+
+    /// Linear frames are read in place in full
+    #[test]
+    fn test_linear_frame() {
+        let frame = udp_frame(53, dns_response(1, 100));
+
+        let (payload, len) = payload_view(&frame, frame.len(), UDP_PAYLOAD_OFFSET).unwrap();
+        assert_eq!(len, 100);
+        assert_eq!(payload.len(), 100);
+    }
+
+    /// Payload entirely in a fragment is copied out
+    #[test]
+    fn test_payload_in_fragment() {
+        let frame = udp_frame(53, dns_response(1, 100));
+
+        // First buffer ends right after the UDP header
+        let (payload, len) = payload_view(&frame, UDP_PAYLOAD_OFFSET, UDP_PAYLOAD_OFFSET).unwrap();
+        assert_eq!(len, 100);
+        assert_eq!(payload, &frame[UDP_PAYLOAD_OFFSET..]);
+    }
+
+    /// The copy out of fragments is capped at the scratch size
+    #[test]
+    fn test_fragment_copy_capped() {
+        let frame = udp_frame(53, dns_response(1, 8000));
+
+        let (payload, len) = payload_view(&frame, 100, UDP_PAYLOAD_OFFSET).unwrap();
+        assert_eq!(len, 8000);
+        assert_eq!(payload.len(), SCRATCH_BYTES);
+    }
+
+    /// A first buffer holding at least the scratch size is read in place
+    #[test]
+    fn test_large_first_buffer_in_place() {
+        let frame = udp_frame(53, dns_response(1, 8000));
+
+        let (payload, len) = payload_view(&frame, 4096, UDP_PAYLOAD_OFFSET).unwrap();
+        assert_eq!(len, 8000);
+        assert_eq!(payload.len(), 4096 - UDP_PAYLOAD_OFFSET);
+    }
+
+    /// No payload past the headers
+    #[test]
+    fn test_no_payload() {
+        let frame = udp_frame(53, Vec::new());
+
+        assert_eq!(payload_view(&frame, frame.len(), UDP_PAYLOAD_OFFSET), None);
+    }
+}
+
+#[cfg(test)]
+mod jumbo_inspection_tests {
+    use super::*;
+
+    /// DNS header fields are readable when the payload starts in a fragment
+    #[test]
+    fn test_dns_header_in_fragment() {
+        let frame = udp_frame(53, dns_response(40, 6000));
+
+        let (payload, len) =
+            payload_view(&frame, UDP_PAYLOAD_OFFSET + 4, UDP_PAYLOAD_OFFSET).unwrap();
+        assert!(payload.len() >= 12);
+        assert_eq!(len, 6000);
+
+        let flags = u16::from_be_bytes([payload[2], payload[3]]);
+        let ancount = u16::from_be_bytes([payload[6], payload[7]]);
+        assert_ne!(flags & 0x8000, 0);
+        assert_eq!(ancount, 40);
+    }
+
+    /// Linear and multi-buffer copies of a frame expose the same leading bytes
+    #[test]
+    fn test_split_matches_linear() {
+        let frame = udp_frame(53, dns_response(3, 3000));
+
+        let (linear, linear_len) = payload_view(&frame, frame.len(), UDP_PAYLOAD_OFFSET).unwrap();
+        let (split, split_len) = payload_view(&frame, 256, UDP_PAYLOAD_OFFSET).unwrap();
+
+        assert_eq!(linear_len, split_len);
+        assert_eq!(&linear[..split.len()], split);
+    }
+}
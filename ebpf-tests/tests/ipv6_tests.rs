@@ -0,0 +1,317 @@
+//! IPv6 and Extension Header Tests
+//!
+//! Tests the IPv6 packet builders and the extension header walk used by the
+//! v6 paths of every filter: hop-by-hop, routing, fragment and destination
+//! options headers, chain limits, and fragmented UDP series.
+
+use pistonprotection_ebpf_tests::packet_generator::*;
+use pistonprotection_ebpf_tests::parser::*;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+fn src_v6() -> Ipv6Addr {
+    Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x100)
+}
+
+fn dst_v6() -> Ipv6Addr {
+    Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)
+}
+
+/// Build an IPv6 TCP SYN to `dst_port` through the given packet builder
+fn tcp_v6(packet: Ipv6Packet, dst_port: u16) -> Vec<u8> {
+    let tcp = TcpSegment::new()
+        .with_src_port(40000)
+        .with_dst_port(dst_port)
+        .syn()
+        .build();
+
+    let ip = packet
+        .with_src_ip(src_v6())
+        .with_dst_ip(dst_v6())
+        .with_protocol(IPPROTO_TCP)
+        .with_payload(tcp)
+        .build();
+
+    EthernetFrame::new()
+        .with_ether_type(ETH_P_IPV6)
+        .with_payload(ip)
+        .build()
+}
+
+/// Parse an untagged frame down to its IPv6 upper-layer header
+fn parse_frame(frame: &[u8]) -> Option<Ipv6Payload> {
+    let (proto, l3) = parse_eth(frame)?;
+    assert_eq!(proto, ETH_P_IPV6);
+    parse_ipv6(frame, l3)
+}
+
+#[cfg(test)]
+mod ipv6_builder_tests {
+    use super::*;
+
+    /// Fixed header fields land at their wire offsets
+    #[test]
+    fn test_ipv6_header_layout() {
+        let ip = Ipv6Packet::new()
+            .with_src_ip(src_v6())
+            .with_dst_ip(dst_v6())
+            .with_protocol(IPPROTO_UDP)
+            .with_hop_limit(17)
+            .with_flow_label(0x12345)
+            .with_payload(vec![0u8; 10])
+            .build();
+
+        assert_eq!(ip.len(), IPV6_HLEN + 10);
+        assert_eq!(ip[0] >> 4, 6);
+        assert_eq!(
+            u32::from_be_bytes([ip[0], ip[1], ip[2], ip[3]]) & 0xfffff,
+            0x12345
+        );
+        assert_eq!(u16::from_be_bytes([ip[4], ip[5]]), 10);
+        assert_eq!(ip[6], IPPROTO_UDP);
+        assert_eq!(ip[7], 17);
+        assert_eq!(&ip[8..24], &src_v6().octets());
+        assert_eq!(&ip[24..40], &dst_v6().octets());
+    }
+
+    /// Options headers are padded to 8-byte units and chained in order
+    #[test]
+    fn test_extension_header_chain() {
+        let ip = Ipv6Packet::new()
+            .with_protocol(IPPROTO_UDP)
+            .with_hop_by_hop(vec![0x05, 0x02, 0x00, 0x00]) // Router Alert
+            .with_dest_options(vec![])
+            .build();
+
+        assert_eq!(ip[6], NEXTHDR_HOP);
+        // Hop-by-Hop: 2 + 4 bytes of options, padded to 8
+        assert_eq!(ip[IPV6_HLEN], NEXTHDR_DEST);
+        assert_eq!(ip[IPV6_HLEN + 1], 0);
+        assert_eq!(&ip[IPV6_HLEN + 6..IPV6_HLEN + 8], &[0x01, 0x00]);
+        // Destination Options: PadN fills the whole header
+        assert_eq!(ip[IPV6_HLEN + 8], IPPROTO_UDP);
+        assert_eq!(&ip[IPV6_HLEN + 10..IPV6_HLEN + 12], &[0x01, 0x04]);
+        assert_eq!(u16::from_be_bytes([ip[4], ip[5]]), 16);
+    }
+
+    /// Routing header length covers its type-specific data
+    #[test]
+    fn test_routing_header_length() {
+        let ip = Ipv6Packet::new()
+            .with_routing(4, 1, vec![0u8; 4 + 16]) // SRH with one segment
+            .build();
+
+        assert_eq!(ip[IPV6_HLEN + 1], 2);
+        assert_eq!(ip[IPV6_HLEN + 2], 4);
+        assert_eq!(ip[IPV6_HLEN + 3], 1);
+        assert_eq!(ip.len(), IPV6_HLEN + 24);
+    }
+
+    /// Fragment header encodes offset, M flag and identification
+    #[test]
+    fn test_fragment_header_fields() {
+        let header = Ipv6ExtHeader::Fragment {
+            offset: 185,
+            more_fragments: true,
+            identification: 0xdeadbeef,
+        }
+        .build(IPPROTO_UDP);
+
+        assert_eq!(header.len(), 8);
+        assert_eq!(header[0], IPPROTO_UDP);
+        assert_eq!(u16::from_be_bytes([header[2], header[3]]), (185 << 3) | 1);
+        assert_eq!(&header[4..8], &0xdeadbeefu32.to_be_bytes());
+    }
+
+    /// UDP checksum over the IPv6 pseudo-header verifies to zero
+    #[test]
+    fn test_udp_v6_checksum() {
+        let frame = create_udp_packet_v6(src_v6(), dst_v6(), 40000, 53, vec![1, 2, 3]);
+        let l4 = ETH_HLEN + IPV6_HLEN;
+        let udp = &frame[l4..];
+        assert_ne!(&udp[6..8], &[0, 0]);
+
+        let mut sum: u32 = 0;
+        let mut add = |bytes: &[u8]| {
+            for word in bytes.chunks(2) {
+                let hi = word[0] as u32;
+                let lo = word.get(1).copied().unwrap_or(0) as u32;
+                sum += (hi << 8) | lo;
+            }
+        };
+        add(&src_v6().octets());
+        add(&dst_v6().octets());
+        add(&(udp.len() as u32).to_be_bytes());
+        add(&[0, 0, 0, IPPROTO_UDP]);
+        add(udp);
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        assert_eq!(sum, 0xffff);
+    }
+}
+
+#[cfg(test)]
+mod ipv6_ext_walk_tests {
+    use super::*;
+
+    /// No extension headers: transport header right after the fixed header
+    #[test]
+    fn test_plain_ipv6_tcp() {
+        let frame = create_tcp_packet_v6(src_v6(), dst_v6(), 40000, 25565, TCP_SYN, vec![]);
+        let payload = parse_frame(&frame).unwrap();
+
+        assert_eq!(payload.protocol, IPPROTO_TCP);
+        assert_eq!(payload.offset, ETH_HLEN + IPV6_HLEN);
+        assert!(!payload.is_fragment);
+        assert_eq!(
+            transport_ports(&frame, payload.offset),
+            Some((40000, 25565))
+        );
+    }
+
+    /// Each extension header type is skipped to reach the TCP ports
+    #[test]
+    fn test_each_extension_header_skipped() {
+        let packets = [
+            Ipv6Packet::new().with_hop_by_hop(vec![]),
+            Ipv6Packet::new().with_routing(0, 0, vec![0u8; 4]),
+            Ipv6Packet::new().with_fragment(0, false, 1),
+            Ipv6Packet::new().with_dest_options(vec![0x01, 0x02, 0x00, 0x00]),
+        ];
+
+        for packet in packets {
+            let frame = tcp_v6(packet.clone(), 443);
+            let payload = parse_frame(&frame).unwrap();
+
+            assert_eq!(payload.protocol, IPPROTO_TCP, "{:?}", packet.ext_headers);
+            assert_eq!(
+                transport_ports(&frame, payload.offset),
+                Some((40000, 443)),
+                "{:?}",
+                packet.ext_headers
+            );
+        }
+    }
+
+    /// A chain of four extension headers is the longest walked
+    #[test]
+    fn test_max_chain_walked() {
+        let packet = Ipv6Packet::new()
+            .with_hop_by_hop(vec![])
+            .with_dest_options(vec![])
+            .with_routing(0, 0, vec![0u8; 4])
+            .with_dest_options(vec![]);
+        let frame = tcp_v6(packet, 80);
+
+        let payload = parse_frame(&frame).unwrap();
+        assert_eq!(payload.protocol, IPPROTO_TCP);
+        assert_eq!(payload.offset, ETH_HLEN + IPV6_HLEN + 4 * 8);
+    }
+
+    /// Chains longer than the walk limit are not parsed
+    #[test]
+    fn test_chain_too_long() {
+        let mut packet = Ipv6Packet::new();
+        for _ in 0..=MAX_EXT_HEADERS {
+            packet = packet.with_dest_options(vec![]);
+        }
+        let frame = tcp_v6(packet, 80);
+
+        assert_eq!(parse_frame(&frame), None);
+    }
+
+    /// An extension header cut off by the end of the packet fails the bounds check
+    #[test]
+    fn test_truncated_extension_header() {
+        let mut frame = tcp_v6(Ipv6Packet::new().with_fragment(0, false, 1), 80);
+        frame.truncate(ETH_HLEN + IPV6_HLEN + 4);
+
+        assert_eq!(parse_frame(&frame), None);
+    }
+
+    /// Non-first fragments are flagged and carry no transport header
+    #[test]
+    fn test_non_first_fragment_flags() {
+        let frame = tcp_v6(Ipv6Packet::new().with_fragment(100, true, 7), 80);
+        let payload = parse_frame(&frame).unwrap();
+
+        assert!(payload.is_fragment);
+        assert!(!payload.is_first_fragment);
+    }
+}
+
+#[cfg(test)]
+mod fragment_series_tests {
+    use super::*;
+
+    /// Fragments of an IPv6 UDP datagram reassemble to the original
+    #[test]
+    fn test_v6_series_reassembles() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let frames =
+            create_fragmented_udp_series_v6(src_v6(), dst_v6(), 40000, 53, data.clone(), 300, 42);
+        assert_eq!(frames.len(), 4);
+
+        let mut reassembled = Vec::new();
+        for (i, frame) in frames.iter().enumerate() {
+            let payload = parse_frame(frame).unwrap();
+            assert_eq!(payload.protocol, IPPROTO_UDP);
+            assert!(payload.is_fragment);
+            assert_eq!(payload.is_first_fragment, i == 0);
+
+            let frag = ETH_HLEN + IPV6_HLEN;
+            let frag_off_m = u16::from_be_bytes([frame[frag + 2], frame[frag + 3]]);
+            assert_eq!((frag_off_m >> 3) as usize * 8, reassembled.len());
+            assert_eq!(frag_off_m & 1 == 1, i + 1 < frames.len());
+            assert_eq!(&frame[frag + 4..frag + 8], &42u32.to_be_bytes());
+
+            reassembled.extend_from_slice(&frame[payload.offset..]);
+        }
+
+        assert_eq!(&reassembled[8..], &data[..]);
+        assert_eq!(transport_ports(&reassembled, 0), Some((40000, 53)));
+    }
+
+    /// Fragments of an IPv4 UDP datagram carry MF and 8-byte offsets
+    #[test]
+    fn test_v4_series_offsets() {
+        let frames = create_fragmented_udp_series(
+            Ipv4Addr::new(192, 168, 1, 100),
+            Ipv4Addr::new(10, 0, 0, 1),
+            40000,
+            19132,
+            vec![0xab; 500],
+            200,
+            7,
+        );
+        assert_eq!(frames.len(), 3);
+
+        let mut expected_offset = 0;
+        for (i, frame) in frames.iter().enumerate() {
+            let ip = &frame[ETH_HLEN..ETH_HLEN + IPV4_HLEN];
+            let frag_field = u16::from_be_bytes([ip[6], ip[7]]);
+            let more = frag_field & 0x2000 != 0;
+
+            assert_eq!(u16::from_be_bytes([ip[4], ip[5]]), 7);
+            assert_eq!(more, i + 1 < frames.len());
+            assert_eq!((frag_field & 0x1fff) as usize * 8, expected_offset);
+
+            expected_offset += frame.len() - ETH_HLEN - IPV4_HLEN;
+        }
+        assert_eq!(expected_offset, 8 + 500);
+    }
+
+    /// Fragment sizes that are not 8-byte multiples are rounded down
+    #[test]
+    fn test_split_rounds_to_eight() {
+        let fragments = split_fragments(&[0u8; 30], 12);
+
+        assert_eq!(fragments.len(), 4);
+        assert_eq!(fragments[1].0, 1);
+        assert!(fragments[..3]
+            .iter()
+            .all(|(_, more, data)| *more && data.len() == 8));
+        assert!(!fragments[3].1);
+        assert_eq!(fragments[3].2.len(), 6);
+    }
+}
@@ -6,11 +6,19 @@
 // Use the library crate for packet generation
 use pistonprotection_ebpf_tests::packet_generator;
 
+mod amplification_tests;
+mod checksum_tests;
+mod clock_tests;
+mod frags_tests;
 mod http_tests;
+mod ipv6_tests;
 mod minecraft_tests;
+mod raknet_split_tests;
 mod raknet_tests;
+mod scenario_tests;
 mod tcp_tests;
 mod varint_tests;
+mod vlan_tests;
 
 /// Test configuration defaults
 pub const DEFAULT_TEST_TIMEOUT_MS: u64 = 1000;
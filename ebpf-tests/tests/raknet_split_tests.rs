@@ -0,0 +1,287 @@
+//! RakNet Split Packet Tests
+//!
+//! Tests the per-connection reassembly limits of `check_bedrock_split_fragment`
+//! against generated split frames: the split count cap, the outstanding split
+//! cap, the buffered byte cap, the fragment flood threshold and the expiry of
+//! incomplete split packets.
+
+use pistonprotection_ebpf_tests::packet_generator::*;
+use pistonprotection_ebpf_tests::raknet::*;
+
+const SECOND: u64 = 1_000_000_000;
+
+/// Fragment `index` of the `count` fragments of split packet `id`
+fn fragment(count: u32, id: u16, index: u32) -> Vec<u8> {
+    RakNetSplitFrame::new().with_split(count, id, index).build()
+}
+
+#[cfg(test)]
+mod split_frame_tests {
+    use super::*;
+
+    /// Split header follows the reliability dependent frame fields
+    #[test]
+    fn test_split_header_offset() {
+        for (reliability, offset) in [
+            (RAKNET_UNRELIABLE, 7),
+            (RAKNET_UNRELIABLE_SEQUENCED, 14),
+            (RAKNET_RELIABLE, 10),
+            (RAKNET_RELIABLE_ORDERED, 14),
+            (RAKNET_RELIABLE_SEQUENCED, 17),
+        ] {
+            let frame = RakNetSplitFrame::new()
+                .with_reliability(reliability)
+                .with_split(3, 0x1234, 2)
+                .with_body_len(10)
+                .build();
+
+            assert_eq!(frame[0], RAKNET_FRAME_SET);
+            assert_eq!(frame[4], (reliability << 5) | RAKNET_FRAME_SPLIT);
+            assert_eq!(&frame[offset..offset + 4], &3u32.to_be_bytes());
+            assert_eq!(&frame[offset + 4..offset + 6], &0x1234u16.to_be_bytes());
+            assert_eq!(&frame[offset + 6..offset + 10], &2u32.to_be_bytes());
+            assert_eq!(frame.len(), offset + 10 + 10);
+
+            let mut splits = None;
+            assert!(check_bedrock_split_fragment(&mut splits, &frame, 0));
+            assert_eq!(splits.unwrap().remaining[0], 2);
+        }
+    }
+
+    /// Frames cut short before the end of the split header drop
+    #[test]
+    fn test_truncated_split_header_drops() {
+        let frame = fragment(2, 1, 0);
+        let mut splits = None;
+
+        assert!(!check_bedrock_split_fragment(&mut splits, &frame[..20], 0));
+        assert!(splits.is_none());
+        assert!(check_bedrock_split_fragment(&mut splits, &frame[..24], 0));
+    }
+}
+
+#[cfg(test)]
+mod split_count_tests {
+    use super::*;
+
+    /// Split packets of up to 128 fragments are accepted
+    #[test]
+    fn test_split_count_cap() {
+        let mut splits = None;
+        assert!(check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(RAKNET_MAX_SPLIT_COUNT, 1, 0),
+            0
+        ));
+        assert!(!check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(RAKNET_MAX_SPLIT_COUNT + 1, 2, 0),
+            0
+        ));
+        assert!(!check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(u32::MAX, 3, 0),
+            0
+        ));
+        assert_eq!(splits.unwrap().outstanding(), 1);
+    }
+
+    /// Zero counts and indexes past the count drop, even as first fragment
+    #[test]
+    fn test_invalid_split_index() {
+        for frame in [
+            fragment(0, 1, 0),
+            fragment(4, 1, 4),
+            fragment(4, 1, u32::MAX),
+        ] {
+            let mut splits = None;
+            assert!(!check_bedrock_split_fragment(&mut splits, &frame, 0));
+            assert!(splits.is_none());
+        }
+    }
+}
+
+#[cfg(test)]
+mod outstanding_split_tests {
+    use super::*;
+
+    /// A fifth split packet drops until one of the four completes
+    #[test]
+    fn test_outstanding_split_cap() {
+        let mut splits = None;
+        for id in 0..RAKNET_MAX_OUTSTANDING_SPLITS as u16 {
+            assert!(check_bedrock_split_fragment(
+                &mut splits,
+                &fragment(2, id, 0),
+                0
+            ));
+        }
+        assert_eq!(splits.unwrap().outstanding(), RAKNET_MAX_OUTSTANDING_SPLITS);
+        assert!(!check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(2, 100, 0),
+            0
+        ));
+
+        // Fragments of outstanding split packets still pass
+        assert!(check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(2, 0, 1),
+            0
+        ));
+        assert_eq!(
+            splits.unwrap().outstanding(),
+            RAKNET_MAX_OUTSTANDING_SPLITS - 1
+        );
+        assert!(check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(2, 100, 0),
+            0
+        ));
+    }
+
+    /// Unsplit single fragment packets take no slot
+    #[test]
+    fn test_single_fragment_takes_no_slot() {
+        let mut splits = None;
+        for id in 0..10 {
+            assert!(check_bedrock_split_fragment(
+                &mut splits,
+                &fragment(1, id, 0),
+                0
+            ));
+        }
+        assert_eq!(splits.unwrap().outstanding(), 0);
+    }
+}
+
+#[cfg(test)]
+mod reassembly_bytes_tests {
+    use super::*;
+
+    /// Buffered fragment bytes stop at 256 KiB and are released on completion
+    #[test]
+    fn test_reassembly_byte_cap() {
+        let body_len = 8000;
+        let fits = RAKNET_MAX_REASSEMBLY_BYTES / body_len;
+        let frame = |index| {
+            RakNetSplitFrame::new()
+                .with_split(RAKNET_MAX_SPLIT_COUNT, 1, index)
+                .with_body_len(body_len as usize)
+                .build()
+        };
+
+        let mut splits = None;
+        for index in 0..fits {
+            assert!(check_bedrock_split_fragment(&mut splits, &frame(index), 0));
+        }
+        assert_eq!(splits.unwrap().buffered(), fits * body_len);
+        assert!(!check_bedrock_split_fragment(&mut splits, &frame(fits), 0));
+
+        // Other split packets share the budget
+        let other = RakNetSplitFrame::new()
+            .with_split(2, 2, 0)
+            .with_body_len(body_len as usize)
+            .build();
+        assert!(!check_bedrock_split_fragment(&mut splits, &other, 0));
+        let small = RakNetSplitFrame::new()
+            .with_split(2, 2, 0)
+            .with_body_len(100)
+            .build();
+        assert!(check_bedrock_split_fragment(&mut splits, &small, 0));
+        assert_eq!(splits.unwrap().buffered(), fits * body_len + 100);
+
+        // Completing the small split packet frees its bytes
+        let last = RakNetSplitFrame::new()
+            .with_split(2, 2, 1)
+            .with_body_len(100)
+            .build();
+        assert!(check_bedrock_split_fragment(&mut splits, &last, 0));
+        assert_eq!(splits.unwrap().buffered(), fits * body_len);
+    }
+}
+
+#[cfg(test)]
+mod fragment_flood_tests {
+    use super::*;
+
+    /// More than 1000 fragments a second drop until the window rolls over
+    #[test]
+    fn test_fragment_flood() {
+        let frame = fragment(1, 1, 0);
+        let mut splits = None;
+
+        for i in 0..RAKNET_FRAGMENT_FLOOD_THRESHOLD as u64 {
+            assert!(check_bedrock_split_fragment(&mut splits, &frame, i * 1000));
+        }
+        assert!(!check_bedrock_split_fragment(
+            &mut splits,
+            &frame,
+            SECOND / 2
+        ));
+        assert!(!check_bedrock_split_fragment(&mut splits, &frame, SECOND));
+
+        assert!(check_bedrock_split_fragment(
+            &mut splits,
+            &frame,
+            SECOND + 1
+        ));
+        assert_eq!(splits.unwrap().fragment_count, 1);
+    }
+}
+
+#[cfg(test)]
+mod split_timeout_tests {
+    use super::*;
+
+    /// Incomplete split packets free their slot and bytes after 10 seconds
+    #[test]
+    fn test_timeout_expiry() {
+        let mut splits = None;
+        for id in 0..RAKNET_MAX_OUTSTANDING_SPLITS as u16 {
+            assert!(check_bedrock_split_fragment(
+                &mut splits,
+                &fragment(3, id, 0),
+                0
+            ));
+        }
+
+        assert!(!check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(3, 100, 0),
+            RAKNET_SPLIT_TIMEOUT_NS
+        ));
+        assert!(check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(3, 100, 0),
+            RAKNET_SPLIT_TIMEOUT_NS + 1
+        ));
+
+        let state = splits.unwrap();
+        assert_eq!(state.outstanding(), 1);
+        assert_eq!(state.buffered(), 1000);
+        assert_eq!(state.split_ids[0], 100);
+        assert_eq!(state.started[0], RAKNET_SPLIT_TIMEOUT_NS + 1);
+    }
+
+    /// Late fragments of an expired split packet start it over
+    #[test]
+    fn test_late_fragment_restarts_split() {
+        let mut splits = None;
+        assert!(check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(3, 1, 0),
+            0
+        ));
+
+        let late = RAKNET_SPLIT_TIMEOUT_NS + SECOND;
+        assert!(check_bedrock_split_fragment(
+            &mut splits,
+            &fragment(3, 1, 1),
+            late
+        ));
+        let state = splits.unwrap();
+        assert_eq!(state.remaining[0], 2);
+        assert_eq!(state.started[0], late);
+    }
+}
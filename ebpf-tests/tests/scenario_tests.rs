@@ -0,0 +1,159 @@
+//! Scenario Runner Tests
+//!
+//! Runs every declarative scenario in `tests/scenarios` and checks that the
+//! runner itself reports mismatches and rejects malformed files.
+
+use pistonprotection_ebpf_tests::scenario::*;
+use std::path::Path;
+
+fn scenario_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scenarios"))
+}
+
+#[cfg(test)]
+mod scenario_corpus_tests {
+    use super::*;
+
+    /// Every scenario file loads and meets its expectations
+    #[test]
+    fn test_all_scenarios() {
+        let scenarios = load_dir(scenario_dir()).unwrap();
+        assert!(!scenarios.is_empty());
+
+        let failures: Vec<String> = scenarios
+            .iter()
+            .filter_map(|(path, scenario)| {
+                scenario
+                    .run()
+                    .err()
+                    .map(|e| format!("{}: {}", path.display(), e))
+            })
+            .collect();
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    /// Both YAML and RON scenarios are picked up
+    #[test]
+    fn test_both_formats_loaded() {
+        let scenarios = load_dir(scenario_dir()).unwrap();
+        let has = |ext: &str| {
+            scenarios
+                .iter()
+                .any(|(path, _)| path.extension().is_some_and(|e| e == ext))
+        };
+
+        assert!(has("yaml"));
+        assert!(has("ron"));
+    }
+}
+
+#[cfg(test)]
+mod scenario_runner_tests {
+    use super::*;
+
+    const SYN_FLOOD: &str = r#"
+name: mismatch
+config:
+  rate_limit: false
+steps:
+  - at_ms: 0
+    count: 3
+    packet:
+      protocol: tcp
+      src: 192.0.2.1
+      dst: 10.0.0.1
+      dst_port: 80
+      flags: [syn]
+    expect: drop
+expect_stats:
+  passed: 2
+"#;
+
+    /// Unmet verdict and stat expectations are reported, not panicked on
+    #[test]
+    fn test_mismatch_reported() {
+        let scenario = Scenario::from_yaml(SYN_FLOOD).unwrap();
+
+        match scenario.run() {
+            Err(ScenarioError::Failed { name, failures }) => {
+                assert_eq!(name, "mismatch");
+                assert_eq!(failures.len(), 2);
+                assert!(failures[0].contains("expected Drop, got Pass"));
+                assert!(failures[1].contains("stats.passed"));
+            }
+            other => panic!("expected failure, got {:?}", other),
+        }
+    }
+
+    /// Unknown fields are rejected so typos do not silently skip checks
+    #[test]
+    fn test_unknown_field_rejected() {
+        let text = SYN_FLOOD.replace("expect_stats:", "expect_stat:");
+
+        assert!(matches!(
+            Scenario::from_yaml(&text),
+            Err(ScenarioError::Parse(_))
+        ));
+    }
+
+    /// Steps going back in time are invalid
+    #[test]
+    fn test_time_goes_backwards() {
+        let text = r#"
+name: backwards
+steps:
+  - at_ms: 10
+    packet: { protocol: udp, src: 192.0.2.1, dst: 10.0.0.1, dst_port: 53 }
+  - at_ms: 5
+    packet: { protocol: udp, src: 192.0.2.1, dst: 10.0.0.1, dst_port: 53 }
+"#;
+        let scenario = Scenario::from_yaml(text).unwrap();
+
+        assert!(matches!(scenario.run(), Err(ScenarioError::Invalid(_))));
+    }
+
+    /// Mixed address families are invalid
+    #[test]
+    fn test_mixed_families_invalid() {
+        let text = r#"
+name: mixed
+steps:
+  - at_ms: 0
+    packet: { protocol: udp, src: 192.0.2.1, dst: "2001:db8::1", dst_port: 53 }
+"#;
+        let scenario = Scenario::from_yaml(text).unwrap();
+
+        assert!(matches!(scenario.run(), Err(ScenarioError::Invalid(_))));
+    }
+
+    /// Hex payloads are decoded into the datagram
+    #[test]
+    fn test_hex_payload() {
+        let hex = |data: &str| PayloadSpec::Hex {
+            data: data.to_string(),
+        };
+
+        assert_eq!(
+            hex("de ad be ef").build().unwrap(),
+            vec![0xde, 0xad, 0xbe, 0xef]
+        );
+        assert!(hex("abc").build().is_err());
+    }
+
+    /// RON scenarios parse with the same field names
+    #[test]
+    fn test_ron_inline() {
+        let text = r#"(
+            name: "ron",
+            steps: [(
+                at_ms: 0,
+                packet: (protocol: tcp, src: "192.0.2.1", dst: "10.0.0.1", dst_port: 80),
+                expect: Some(pass),
+            )],
+        )"#;
+
+        let stats = Scenario::from_ron(text).unwrap().run().unwrap();
+        assert_eq!(stats.passed, 1);
+    }
+}
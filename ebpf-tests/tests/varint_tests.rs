@@ -1,9 +1,65 @@
 //! VarInt parsing edge case tests
 //!
 //! Tests for the Minecraft VarInt encoding format, focusing on edge cases
-//! that could be exploited for attacks.
+//! that could be exploited for attacks. The golden corpus in
+//! `tests/corpus/varint.txt` and the property tests check that the test
+//! generator and the filter's parsing logic agree on every encoding.
+
+use pistonprotection_ebpf_tests::minecraft::*;
+use pistonprotection_ebpf_tests::packet_generator::{
+    decode_varint, encode_varint, MinecraftHandshake,
+};
+use proptest::prelude::*;
+
+/// One line of the golden corpus
+#[derive(Debug)]
+struct CorpusCase {
+    line: usize,
+    bytes: Vec<u8>,
+    expected: Option<(i32, usize)>,
+}
 
-use pistonprotection_ebpf_tests::packet_generator::{decode_varint, encode_varint};
+/// Parse `tests/corpus/varint.txt`
+fn varint_corpus() -> Vec<CorpusCase> {
+    include_str!("corpus/varint.txt")
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let (input, output) = line.split_once("=>").expect("missing =>");
+            let bytes = match input.trim() {
+                "-" => Vec::new(),
+                hex => hex
+                    .split_whitespace()
+                    .map(|b| u8::from_str_radix(b, 16).expect("bad hex byte"))
+                    .collect(),
+            };
+            let expected = match output.trim() {
+                "invalid" => None,
+                out => {
+                    let (value, len) = out.split_once(' ').expect("missing length");
+                    Some((value.parse().unwrap(), len.parse().unwrap()))
+                }
+            };
+            CorpusCase {
+                line: i + 1,
+                bytes,
+                expected,
+            }
+        })
+        .collect()
+}
+
+/// Encoded length of a VarInt from its unsigned bit width
+fn varint_len(value: i32) -> usize {
+    match value as u32 {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        0x4000..=0x1f_ffff => 3,
+        0x20_0000..=0x0fff_ffff => 4,
+        _ => 5,
+    }
+}
 
 #[cfg(test)]
 mod varint_edge_cases {
@@ -312,3 +368,213 @@ mod varint_security_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod varint_corpus_tests {
+    use super::*;
+
+    /// Test the corpus covers every outcome the decoders can produce
+    #[test]
+    fn test_corpus_loaded() {
+        let corpus = varint_corpus();
+
+        assert!(corpus.len() >= 30);
+        for len in 1..=MAX_VARINT_BYTES {
+            assert!(
+                corpus
+                    .iter()
+                    .any(|c| c.expected.map(|(_, n)| n) == Some(len)),
+                "no accepted case of length {}",
+                len
+            );
+        }
+        assert!(corpus.iter().any(|c| c.expected.is_none()));
+    }
+
+    /// Test the filter's decoder against every golden case
+    #[test]
+    fn test_corpus_filter_decoder() {
+        for case in varint_corpus() {
+            assert_eq!(
+                read_varint(&case.bytes),
+                case.expected,
+                "line {}: {:02x?}",
+                case.line,
+                case.bytes
+            );
+        }
+    }
+
+    /// Test the generator's decoder against every golden case
+    #[test]
+    fn test_corpus_generator_decoder() {
+        for case in varint_corpus() {
+            assert_eq!(
+                decode_varint(&case.bytes),
+                case.expected,
+                "line {}: {:02x?}",
+                case.line,
+                case.bytes
+            );
+        }
+    }
+
+    /// Test canonical cases re-encode byte for byte and overlong ones shrink
+    #[test]
+    fn test_corpus_reencoding() {
+        for case in varint_corpus() {
+            let Some((value, len)) = case.expected else {
+                continue;
+            };
+            let encoded = encode_varint(value);
+
+            if len == varint_len(value) {
+                assert_eq!(&encoded[..], &case.bytes[..len], "line {}", case.line);
+            } else {
+                assert!(encoded.len() < len, "line {} is not overlong", case.line);
+            }
+        }
+    }
+
+    /// Test decoding at an offset matches decoding the sliced buffer
+    #[test]
+    fn test_corpus_at_offset() {
+        for case in varint_corpus() {
+            let mut buf = vec![0xaa, 0xbb, 0xcc];
+            buf.extend_from_slice(&case.bytes);
+
+            assert_eq!(read_varint_at(&buf, 3), case.expected, "line {}", case.line);
+        }
+    }
+
+    /// Test reading past the end of the buffer is rejected
+    #[test]
+    fn test_offset_past_end() {
+        assert_eq!(read_varint_at(&[0x01], 1), None);
+        assert_eq!(read_varint_at(&[0x01], 100), None);
+    }
+}
+
+#[cfg(test)]
+mod varint_property_tests {
+    use super::*;
+
+    proptest! {
+        /// Every i32 round-trips through both decoders with its canonical length
+        #[test]
+        fn prop_roundtrip(value in any::<i32>()) {
+            let encoded = encode_varint(value);
+
+            prop_assert_eq!(encoded.len(), varint_len(value));
+            prop_assert_eq!(decode_varint(&encoded), Some((value, encoded.len())));
+            prop_assert_eq!(read_varint(&encoded), Some((value, encoded.len())));
+        }
+
+        /// Trailing bytes never change the decoded value
+        #[test]
+        fn prop_trailing_data_ignored(value in any::<i32>(), tail in prop::collection::vec(any::<u8>(), 0..8)) {
+            let mut buf = encode_varint(value);
+            let len = buf.len();
+            buf.extend(tail);
+
+            prop_assert_eq!(read_varint(&buf), Some((value, len)));
+        }
+
+        /// Every proper prefix of a multi-byte encoding is rejected
+        #[test]
+        fn prop_truncation_rejected(value in any::<i32>()) {
+            let encoded = encode_varint(value);
+
+            for cut in 0..encoded.len() {
+                prop_assert_eq!(read_varint(&encoded[..cut]), None);
+                prop_assert_eq!(decode_varint(&encoded[..cut]), None);
+            }
+        }
+
+        /// Padding an encoding with zero continuation groups keeps its value
+        #[test]
+        fn prop_overlong_accepted(value in 0i32..0x0fff_ffff) {
+            let mut encoded = encode_varint(value);
+            let last = encoded.len() - 1;
+            encoded[last] |= 0x80;
+            encoded.resize(MAX_VARINT_BYTES, 0x80);
+            encoded[MAX_VARINT_BYTES - 1] = 0x00;
+
+            prop_assert_eq!(read_varint(&encoded), Some((value, MAX_VARINT_BYTES)));
+            prop_assert!(encode_varint(value).len() < MAX_VARINT_BYTES);
+        }
+
+        /// The generator and the filter agree on arbitrary input
+        #[test]
+        fn prop_decoders_agree(bytes in prop::collection::vec(any::<u8>(), 0..8)) {
+            let decoded = read_varint(&bytes);
+
+            prop_assert_eq!(decode_varint(&bytes), decoded);
+            if let Some((_, len)) = decoded {
+                prop_assert!(len <= MAX_VARINT_BYTES);
+                prop_assert_eq!(bytes[len - 1] & 0x80, 0);
+            }
+        }
+
+        /// Generated handshakes pass the filter's handshake validation
+        #[test]
+        fn prop_handshake_roundtrip(
+            protocol in MIN_VALID_PROTOCOL..=MAX_VALID_PROTOCOL,
+            address in "[a-z0-9.-]{0,255}",
+            port in 1u16..,
+            next_state in 1i32..=3,
+        ) {
+            let packet = MinecraftHandshake::new()
+                .with_protocol(protocol as i32)
+                .with_address(&address)
+                .with_port(port)
+                .with_next_state(next_state)
+                .build();
+
+            let (packet_len, packet_data) = split_frame(&packet).unwrap();
+            prop_assert_eq!(packet_data.len(), packet_len);
+
+            let (packet_id, id_bytes) = read_varint(packet_data).unwrap();
+            prop_assert_eq!(packet_id, 0x00);
+
+            let result = validate_handshake(
+                packet_data,
+                id_bytes,
+                packet_len,
+                MIN_VALID_PROTOCOL,
+                MAX_VALID_PROTOCOL,
+                DEFAULT_MAX_HOSTNAME_LEN,
+            );
+            prop_assert_eq!(
+                result,
+                Some(HandshakeResult {
+                    valid: true,
+                    protocol_version: protocol,
+                    next_state: next_state as u8,
+                })
+            );
+        }
+
+        /// Cutting a generated handshake short never validates
+        #[test]
+        fn prop_truncated_handshake_incomplete(
+            address in "[a-z]{1,64}",
+            cut_back in 1usize..8,
+        ) {
+            let packet = MinecraftHandshake::new().with_address(&address).build();
+            let (packet_len, packet_data) = split_frame(&packet).unwrap();
+            let (_, id_bytes) = read_varint(packet_data).unwrap();
+
+            let cut = packet_data.len().saturating_sub(cut_back);
+            let result = validate_handshake(
+                &packet_data[..cut],
+                id_bytes,
+                packet_len,
+                MIN_VALID_PROTOCOL,
+                MAX_VALID_PROTOCOL,
+                DEFAULT_MAX_HOSTNAME_LEN,
+            );
+            prop_assert!(result.is_none_or(|r| !r.valid));
+        }
+    }
+}
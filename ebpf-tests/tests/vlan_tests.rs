@@ -0,0 +1,182 @@
+//! VLAN Tag Handling Tests
+//!
+//! Tests that 802.1Q and QinQ tagged frames are parsed through to the
+//! IP and transport headers, so tagged traffic is filtered like untagged.
+
+use pistonprotection_ebpf_tests::packet_generator::*;
+use pistonprotection_ebpf_tests::parser::*;
+use std::net::Ipv4Addr;
+
+/// Build an IPv4/TCP packet body (no Ethernet header)
+fn tcp_ip_payload(dst_port: u16) -> Vec<u8> {
+    let tcp = TcpSegment::new()
+        .with_src_port(40000)
+        .with_dst_port(dst_port)
+        .syn()
+        .build();
+
+    Ipv4Packet::new()
+        .with_src_ip(Ipv4Addr::new(192, 168, 1, 100))
+        .with_dst_ip(Ipv4Addr::new(10, 0, 0, 1))
+        .with_protocol(IPPROTO_TCP)
+        .with_payload(tcp)
+        .build()
+}
+
+/// Build an IPv4/UDP packet body (no Ethernet header)
+fn udp_ip_payload(dst_port: u16) -> Vec<u8> {
+    let udp = UdpDatagram::new()
+        .with_src_port(40000)
+        .with_dst_port(dst_port)
+        .with_payload(vec![0u8; 16])
+        .build();
+
+    Ipv4Packet::new()
+        .with_src_ip(Ipv4Addr::new(192, 168, 1, 100))
+        .with_dst_ip(Ipv4Addr::new(10, 0, 0, 1))
+        .with_protocol(IPPROTO_UDP)
+        .with_payload(udp)
+        .build()
+}
+
+#[cfg(test)]
+mod vlan_parse_tests {
+    use super::*;
+
+    /// Untagged frames keep the L3 header right after the Ethernet header
+    #[test]
+    fn test_untagged_frame() {
+        let packet = EthernetFrame::new()
+            .with_payload(tcp_ip_payload(80))
+            .build();
+
+        assert_eq!(parse_eth(&packet), Some((ETH_P_IP, ETH_HLEN)));
+    }
+
+    /// Single 802.1Q tag is skipped
+    #[test]
+    fn test_single_vlan_tag() {
+        let packet = EthernetFrame::new()
+            .with_vlan(100)
+            .with_payload(tcp_ip_payload(80))
+            .build();
+
+        assert_eq!(u16::from_be_bytes([packet[12], packet[13]]), ETH_P_8021Q);
+        assert_eq!(parse_eth(&packet), Some((ETH_P_IP, ETH_HLEN + VLAN_HLEN)));
+    }
+
+    /// QinQ (802.1ad outer + 802.1Q inner) tags are both skipped
+    #[test]
+    fn test_qinq_tags() {
+        let packet = EthernetFrame::new()
+            .with_qinq(200, 100)
+            .with_payload(tcp_ip_payload(80))
+            .build();
+
+        assert_eq!(u16::from_be_bytes([packet[12], packet[13]]), ETH_P_8021AD);
+        assert_eq!(
+            parse_eth(&packet),
+            Some((ETH_P_IP, ETH_HLEN + 2 * VLAN_HLEN))
+        );
+    }
+
+    /// Tagged IPv6 frames report the inner EtherType
+    #[test]
+    fn test_vlan_tagged_ipv6() {
+        let packet = EthernetFrame::new()
+            .with_vlan(42)
+            .with_ether_type(ETH_P_IPV6)
+            .with_payload(vec![0x60; 40])
+            .build();
+
+        assert_eq!(parse_eth(&packet), Some((ETH_P_IPV6, ETH_HLEN + VLAN_HLEN)));
+    }
+
+    /// More tags than the parser walks leave a VLAN EtherType (passed as non-IP)
+    #[test]
+    fn test_too_many_tags_not_ip() {
+        let packet = EthernetFrame::new()
+            .with_qinq(200, 100)
+            .with_vlan(300)
+            .with_payload(tcp_ip_payload(80))
+            .build();
+
+        let (proto, _) = parse_eth(&packet).unwrap();
+        assert_eq!(proto, ETH_P_8021Q);
+    }
+
+    /// A tag cut off by the end of the packet fails the bounds check
+    #[test]
+    fn test_truncated_vlan_tag() {
+        let mut packet = EthernetFrame::new().with_vlan(100).build();
+        packet.truncate(ETH_HLEN + 2);
+
+        assert_eq!(parse_eth(&packet), None);
+    }
+
+    /// VLAN ID is masked to 12 bits in the TCI
+    #[test]
+    fn test_vlan_id_masked() {
+        let packet = EthernetFrame::new().with_vlan(0xffff).build();
+
+        let tci = u16::from_be_bytes([packet[14], packet[15]]);
+        assert_eq!(tci, 0x0fff);
+    }
+}
+
+#[cfg(test)]
+mod vlan_filter_tests {
+    use super::*;
+
+    /// Transport ports of a tagged TCP packet are found at the shifted offset
+    #[test]
+    fn test_tagged_tcp_ports() {
+        let packet = EthernetFrame::new()
+            .with_vlan(100)
+            .with_payload(tcp_ip_payload(25565))
+            .build();
+
+        let (proto, l3) = parse_eth(&packet).unwrap();
+        assert_eq!(proto, ETH_P_IP);
+
+        let (ip_proto, l4) = parse_ipv4(&packet, l3).unwrap();
+        assert_eq!(ip_proto, IPPROTO_TCP);
+        assert_eq!(transport_ports(&packet, l4), Some((40000, 25565)));
+    }
+
+    /// Transport ports of a QinQ tagged UDP packet are found at the shifted offset
+    #[test]
+    fn test_qinq_udp_ports() {
+        let packet = EthernetFrame::new()
+            .with_qinq(10, 20)
+            .with_payload(udp_ip_payload(19132))
+            .build();
+
+        let (proto, l3) = parse_eth(&packet).unwrap();
+        assert_eq!(proto, ETH_P_IP);
+
+        let (ip_proto, l4) = parse_ipv4(&packet, l3).unwrap();
+        assert_eq!(ip_proto, IPPROTO_UDP);
+        assert_eq!(transport_ports(&packet, l4), Some((40000, 19132)));
+    }
+
+    /// Tagged and untagged copies of a packet parse to the same headers
+    #[test]
+    fn test_tagged_matches_untagged() {
+        let body = tcp_ip_payload(443);
+        let untagged = EthernetFrame::new().with_payload(body.clone()).build();
+        let tagged = EthernetFrame::new()
+            .with_qinq(1, 2)
+            .with_payload(body)
+            .build();
+
+        let (_, l3_untagged) = parse_eth(&untagged).unwrap();
+        let (_, l3_tagged) = parse_eth(&tagged).unwrap();
+
+        assert_eq!(&untagged[l3_untagged..], &tagged[l3_tagged..]);
+        assert_eq!(
+            parse_ipv4(&untagged, l3_untagged).map(|(p, _)| p),
+            parse_ipv4(&tagged, l3_tagged).map(|(p, _)| p)
+        );
+    }
+}
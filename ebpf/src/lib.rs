@@ -13,6 +13,10 @@
 //! - `xdp_udp` - Generic UDP filtering with amplification detection
 //! - `xdp_tcp` - Enhanced TCP filtering with SYN cookies
 //!
+//! The optional `tc_flowmark` classifier is a TC companion of xdp_tcp: it
+//! marks the packets of flows xdp_tcp validated so the host can skip
+//! conntrack for them.
+//!
 //! # Architecture
 //!
 //! Each XDP program operates independently and can be attached to different
@@ -20,9 +24,39 @@
 //!
 //! The programs share common map structures where appropriate, allowing
 //! userspace to manage blocklists and configuration centrally.
+//!
+//! # Build Variants
+//!
+//! Cargo features select the kernel interfaces the programs use, so the
+//! same release can run on older kernels:
+//!
+//! - `frags` - load as `xdp.frags` and read multi-buffer frames with
+//!   `bpf_xdp_load_bytes` (5.18+)
+//! - `ringbuf` - submit flow samples and drop events through BPF ring
+//!   buffers (5.8+), copying samples with `bpf_xdp_load_bytes` (implies
+//!   `frags`); without it both go through perf event arrays and the kernel
+//!   appends the captured bytes of samples
+//!
+//! The full variant enables both (the default); the compat variant is built
+//! with `--no-default-features` and only sees the first buffer of a frame.
+//! Every variant is built with BTF so the loader can apply CO-RE relocations.
 
 #![no_std]
 
+#[cfg(feature = "frags")]
+use aya_ebpf::helpers::{bpf_xdp_get_buff_len, bpf_xdp_load_bytes};
+#[cfg(not(feature = "ringbuf"))]
+use aya_ebpf::programs::XdpContext;
+use aya_ebpf::{
+    bindings::xdp_md,
+    helpers::{bpf_get_prandom_u32, bpf_ktime_get_ns},
+    maps::{
+        Array, HashMap, LpmTrie, LruHashMap, LruPerCpuHashMap, PerCpuArray, PerCpuHashMap,
+        lpm_trie::Key,
+    },
+};
+use core::{ffi::c_void, mem};
+
 // ============================================================================
 // Common Types
 // ============================================================================
@@ -73,6 +107,20 @@ pub enum BlockReason {
     MinecraftBot = 19,
     /// Generic DDoS detection
     GenericDdos = 20,
+    /// Source IP is on a blocklist
+    BlockedIp = 21,
+    /// Suspicious or disallowed IP fragment
+    Fragmentation = 22,
+    /// Source is listed by a threat intelligence feed
+    ThreatIntel = 23,
+    /// Greylisted source exceeded its reduced rate limit
+    Reputation = 24,
+    /// Source touched a honeypot port
+    Honeypot = 25,
+    /// The backend is switched to block all traffic
+    BackendBlocked = 26,
+    /// Established flow exceeded its per-connection bandwidth
+    FlowRateLimit = 27,
 }
 
 /// Protection levels
@@ -110,66 +158,2395 @@ pub enum XdpProgram {
 }
 
 // ============================================================================
-// Shared Map Key Types
+// Shared Map Key Types
+// ============================================================================
+
+/// IPv4 address key
+pub type Ipv4Key = u32;
+
+/// IPv6 address key
+pub type Ipv6Key = [u8; 16];
+
+/// Connection 4-tuple key (hashed)
+pub type ConnectionKey = u64;
+
+/// Port key
+pub type PortKey = u16;
+
+// ============================================================================
+// Common Configuration Structures
+// ============================================================================
+
+/// Base configuration shared by all programs
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BaseConfig {
+    /// Program enabled flag
+    pub enabled: u32,
+    /// Protection level (1-4)
+    pub protection_level: u32,
+    /// Rate limit window in nanoseconds
+    pub rate_limit_window_ns: u64,
+    /// Block duration in nanoseconds
+    pub block_duration_ns: u64,
+}
+
+impl Default for BaseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: 1,
+            protection_level: 2,
+            rate_limit_window_ns: 1_000_000_000, // 1 second
+            block_duration_ns: 60_000_000_000,   // 60 seconds
+        }
+    }
+}
+
+// ============================================================================
+// Common Statistics Structures
+// ============================================================================
+
+/// Base statistics shared by all programs
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct BaseStats {
+    /// Total packets processed
+    pub total_packets: u64,
+    /// Packets passed through
+    pub passed_packets: u64,
+    /// Packets dropped
+    pub dropped_packets: u64,
+    /// Total bytes processed
+    pub total_bytes: u64,
+}
+
+// ============================================================================
+// Drop Breakdown Statistics
+// ============================================================================
+
+/// Packet and byte counter for a single breakdown bucket
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DropCounter {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Drop attribution for the packet currently being processed
+///
+/// Each program keeps one per CPU in a `DROP_CONTEXT` scratch map. It is
+/// reset when a packet enters the program, filled in as the transport header
+/// is parsed and a drop reason is chosen, and consumed once the verdict is
+/// known to be `XDP_DROP`.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DropContext {
+    /// `BlockReason` of the drop
+    pub reason: u32,
+    /// Destination port (0 if not parsed yet)
+    pub dst_port: u16,
+    /// IP protocol number (0 if not parsed yet)
+    pub protocol: u8,
+    /// `event_budget::PRIORITY_*` of the drop event
+    pub priority: u8,
+    /// Source address, IPv4 as an IPv4-mapped IPv6 address (zero if not
+    /// parsed yet)
+    pub src_addr: [u8; 16],
+}
+
+pub mod breakdown {
+    /// Maximum distinct (protocol, port) pairs tracked per program.
+    /// The map is LRU so rarely targeted ports are evicted first.
+    pub const DST_PORT_MAX_ENTRIES: u32 = 4096;
+
+    /// Number of drop-reason buckets (one per `BlockReason`, with headroom)
+    pub const REASON_BUCKETS: u32 = 32;
+
+    /// Encode the key for a `DROPS_BY_DST_PORT` map
+    #[inline(always)]
+    pub fn dst_port_key(protocol: u8, dst_port: u16) -> u32 {
+        ((protocol as u32) << 16) | dst_port as u32
+    }
+}
+
+/// Reset the per-CPU drop context at the start of a packet
+#[inline(always)]
+pub fn drop_context_reset(scratch: &PerCpuArray<DropContext>, default_reason: BlockReason) {
+    if let Some(ctx) = unsafe { scratch.get_ptr_mut(0) } {
+        unsafe {
+            *ctx = DropContext {
+                reason: default_reason as u32,
+                dst_port: 0,
+                protocol: 0,
+                priority: event_budget::PRIORITY_NORMAL,
+                src_addr: [0; 16],
+            };
+        }
+    }
+}
+
+/// Set the event priority of the current packet's drop
+#[inline(always)]
+pub fn drop_context_set_priority(scratch: &PerCpuArray<DropContext>, priority: u8) {
+    if let Some(ctx) = unsafe { scratch.get_ptr_mut(0) } {
+        unsafe { (*ctx).priority = priority };
+    }
+}
+
+/// Record the transport protocol and destination port of the current packet
+#[inline(always)]
+pub fn drop_context_set_target(scratch: &PerCpuArray<DropContext>, protocol: u8, dst_port: u16) {
+    if let Some(ctx) = unsafe { scratch.get_ptr_mut(0) } {
+        unsafe {
+            (*ctx).protocol = protocol;
+            (*ctx).dst_port = dst_port;
+        }
+    }
+}
+
+/// Record the IPv4 source address (`saddr` as in the header) of the current
+/// packet
+#[inline(always)]
+pub fn drop_context_set_source_v4(scratch: &PerCpuArray<DropContext>, saddr: u32) {
+    if let Some(ctx) = unsafe { scratch.get_ptr_mut(0) } {
+        let octets = saddr.to_ne_bytes();
+        unsafe {
+            (*ctx).src_addr = [
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, octets[0], octets[1], octets[2],
+                octets[3],
+            ];
+        }
+    }
+}
+
+/// Record the IPv6 source address of the current packet
+#[inline(always)]
+pub fn drop_context_set_source_v6(scratch: &PerCpuArray<DropContext>, saddr: &[u8; 16]) {
+    if let Some(ctx) = unsafe { scratch.get_ptr_mut(0) } {
+        unsafe {
+            (*ctx).src_addr = *saddr;
+        }
+    }
+}
+
+/// Record why the current packet is being dropped
+#[inline(always)]
+pub fn drop_context_set_reason(scratch: &PerCpuArray<DropContext>, reason: BlockReason) {
+    if let Some(ctx) = unsafe { scratch.get_ptr_mut(0) } {
+        unsafe {
+            (*ctx).reason = reason as u32;
+        }
+    }
+}
+
+/// Peek at the destination port of a TCP or UDP header starting at `l4`.
+///
+/// Both headers carry the destination port at offset 2, so this works before
+/// the program has fully parsed the transport header. Returns 0 for other
+/// protocols or truncated packets.
+#[inline(always)]
+pub fn peek_dst_port(l4: usize, data_end: usize, ip_protocol: u8) -> u16 {
+    if ip_protocol != protocol::ip::PROTO_TCP && ip_protocol != protocol::ip::PROTO_UDP {
+        return 0;
+    }
+    if l4 + 4 > data_end {
+        return 0;
+    }
+    u16::from_be(unsafe { core::ptr::read_unaligned((l4 + 2) as *const u16) })
+}
+
+/// Account a dropped packet in the per-port and per-reason breakdown maps
+#[inline(always)]
+pub fn record_drop(
+    scratch: &PerCpuArray<DropContext>,
+    by_dst_port: &LruPerCpuHashMap<u32, DropCounter>,
+    by_reason: &PerCpuArray<DropCounter>,
+    bytes: u64,
+) {
+    let ctx = match unsafe { scratch.get(0) } {
+        Some(ctx) => *ctx,
+        None => return,
+    };
+
+    if ctx.reason < breakdown::REASON_BUCKETS {
+        if let Some(counter) = unsafe { by_reason.get_ptr_mut(ctx.reason) } {
+            unsafe {
+                (*counter).packets += 1;
+                (*counter).bytes += bytes;
+            }
+        }
+    }
+
+    let key = breakdown::dst_port_key(ctx.protocol, ctx.dst_port);
+    if let Some(counter) = unsafe { by_dst_port.get_ptr_mut(&key) } {
+        unsafe {
+            (*counter).packets += 1;
+            (*counter).bytes += bytes;
+        }
+    } else {
+        let counter = DropCounter { packets: 1, bytes };
+        let _ = by_dst_port.insert(&key, &counter, 0);
+    }
+}
+
+// ============================================================================
+// Drop Events
+// ============================================================================
+
+/// Per-packet drop events. Every dropped packet is submitted to the
+/// program's `DROP_EVENTS` buffer with its source, reason and size; the
+/// worker coalesces them by source and reason before exporting, so a flood
+/// costs one summary per source rather than one event per packet. Events
+/// are lost when the buffer is full, which only thins out what userspace
+/// sees; the breakdown maps still count every drop.
+pub mod drop_events {
+    /// Size of each program's drop event ring buffer
+    pub const RING_BYTES: u32 = 256 * 1024;
+
+    /// Map the programs submit drop events through (`DROP_EVENTS`)
+    #[cfg(feature = "ringbuf")]
+    pub type EventMap = aya_ebpf::maps::RingBuf;
+
+    /// Map the programs submit drop events through (`DROP_EVENTS`)
+    #[cfg(not(feature = "ringbuf"))]
+    pub type EventMap = aya_ebpf::maps::PerfEventArray<super::DropEvent>;
+
+    #[cfg(feature = "ringbuf")]
+    pub const fn event_map() -> EventMap {
+        EventMap::with_byte_size(RING_BYTES, 0)
+    }
+
+    #[cfg(not(feature = "ringbuf"))]
+    pub const fn event_map() -> EventMap {
+        EventMap::new(0)
+    }
+}
+
+/// Drop of a single packet, submitted through the `DROP_EVENTS` buffer
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DropEvent {
+    /// `bpf_ktime_get_ns` at the time of the drop
+    pub timestamp_ns: u64,
+    /// Source address, IPv4 as an IPv4-mapped IPv6 address
+    pub src_addr: [u8; 16],
+    /// Length of the whole packet
+    pub bytes: u32,
+    /// `BlockReason` of the drop
+    pub reason: u32,
+    pub dst_port: u16,
+    pub protocol: u8,
+    pub _pad: [u8; 5],
+}
+
+/// Submit the drop of the current packet, as attributed in the drop context,
+/// if the event budget admits its priority
+///
+/// Takes the raw context so it can run after the program has consumed its
+/// `XdpContext`.
+#[inline(always)]
+pub fn emit_drop_event(
+    ctx: *mut xdp_md,
+    scratch: &PerCpuArray<DropContext>,
+    events: &drop_events::EventMap,
+    budget_config: &PerCpuArray<EventBudgetConfig>,
+    budget: &PerCpuArray<EventBudget>,
+    bytes: u64,
+) {
+    let drop = match unsafe { scratch.get(0) } {
+        Some(drop) => *drop,
+        None => return,
+    };
+    if !event_admit(budget_config, budget, drop.priority) {
+        return;
+    }
+
+    let event = DropEvent {
+        timestamp_ns: unsafe { bpf_ktime_get_ns() },
+        src_addr: drop.src_addr,
+        bytes: bytes as u32,
+        reason: drop.reason,
+        dst_port: drop.dst_port,
+        protocol: drop.protocol,
+        _pad: [0; 5],
+    };
+
+    #[cfg(feature = "ringbuf")]
+    {
+        let _ = ctx;
+        let _ = events.output(&event, 0);
+    }
+    #[cfg(not(feature = "ringbuf"))]
+    events.output(&XdpContext::new(ctx), &event, 0);
+}
+
+// ============================================================================
+// Event Budget
+// ============================================================================
+
+/// Rate limit on the events the programs submit to userspace. Each CPU has
+/// a token bucket shared by drop events and flow samples; an event takes one
+/// token. Events come in priority classes so the important ones survive a
+/// flood: drops that decided a new block always go through, other drop
+/// events need a token, and samples need more than `low_reserve` tokens, so
+/// they are shed first as the bucket drains. Emitted and suppressed events
+/// are counted per class in `EVENT_BUDGET`.
+pub mod event_budget {
+    /// Flow samples
+    pub const PRIORITY_LOW: u8 = 0;
+    /// Drop events
+    pub const PRIORITY_NORMAL: u8 = 1;
+    /// Drops that decided a new block
+    pub const PRIORITY_HIGH: u8 = 2;
+
+    /// Number of priority classes
+    pub const CLASSES: usize = 3;
+}
+
+/// Event budget of each CPU, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct EventBudgetConfig {
+    /// Tokens added per second (0 = defaults)
+    pub rate: u32,
+    /// Bucket capacity
+    pub burst: u32,
+    /// Tokens kept back from low-priority events
+    pub low_reserve: u32,
+    pub _pad: u32,
+}
+
+impl EventBudgetConfig {
+    /// Budget used when userspace wrote none
+    pub const DEFAULT: Self = Self {
+        rate: 4096,
+        burst: 8192,
+        low_reserve: 4096,
+        _pad: 0,
+    };
+}
+
+/// Token bucket and event counters of one CPU (`EVENT_BUDGET`)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct EventBudget {
+    pub tokens: u64,
+    pub last_refill_ns: u64,
+    /// Events submitted, per priority class
+    pub emitted: [u64; event_budget::CLASSES],
+    /// Events suppressed by the budget, per priority class
+    pub suppressed: [u64; event_budget::CLASSES],
+}
+
+/// Whether an event of `priority` may be submitted; takes a token and
+/// counts the event either way
+#[inline(always)]
+pub fn event_admit(
+    config: &PerCpuArray<EventBudgetConfig>,
+    budget: &PerCpuArray<EventBudget>,
+    priority: u8,
+) -> bool {
+    let config = match unsafe { config.get(0) } {
+        Some(config) if config.rate > 0 => *config,
+        _ => EventBudgetConfig::DEFAULT,
+    };
+    let Some(budget) = (unsafe { budget.get_ptr_mut(0) }) else {
+        return true;
+    };
+    let budget = unsafe { &mut *budget };
+
+    let now = unsafe { bpf_ktime_get_ns() };
+    let burst = config.burst.max(1) as u64;
+    let elapsed = now.saturating_sub(budget.last_refill_ns);
+    if elapsed >= 1_000_000_000 {
+        budget.tokens = burst;
+        budget.last_refill_ns = now;
+    } else {
+        // The clock only advances with whole tokens, so slow rates still refill
+        let added = elapsed * config.rate as u64 / 1_000_000_000;
+        if added > 0 {
+            budget.tokens = (budget.tokens + added).min(burst);
+            budget.last_refill_ns = now;
+        }
+    }
+
+    let (class, admitted) = match priority {
+        event_budget::PRIORITY_LOW => (0, budget.tokens > config.low_reserve as u64),
+        event_budget::PRIORITY_HIGH => (2, true),
+        _ => (1, budget.tokens > 0),
+    };
+    if admitted {
+        budget.tokens = budget.tokens.saturating_sub(1);
+        budget.emitted[class] += 1;
+    } else {
+        budget.suppressed[class] += 1;
+    }
+    admitted
+}
+
+// ============================================================================
+// Observe Mode
+// ============================================================================
+
+/// Worker-wide observe mode. While an operator checks what the filters would
+/// do, programs still account their drops but pass the packets. `OBSERVE`
+/// holds a single flag, pinned by name and shared by every program.
+pub mod observe {
+    /// Drop packets as decided
+    pub const ENFORCE: u32 = 0;
+
+    /// Pass packets the program would drop
+    pub const OBSERVE: u32 = 1;
+}
+
+/// Final verdict of a program, turning drops into passes in observe mode
+///
+/// Called after the drop was recorded, so the breakdown maps show what would
+/// have been dropped. Other verdicts (`XDP_TX` replies, redirects) are kept.
+#[inline(always)]
+pub fn observe_verdict(mode: &Array<u32>, action: u32) -> u32 {
+    if action != aya_ebpf::bindings::xdp_action::XDP_DROP {
+        return action;
+    }
+    match mode.get(0) {
+        Some(&observe::OBSERVE) => aya_ebpf::bindings::xdp_action::XDP_PASS,
+        _ => action,
+    }
+}
+
+// ============================================================================
+// Canary Rule Evaluation
+// ============================================================================
+
+/// Canary verdicts: how a candidate rule set would treat a source compared to
+/// the active one. Canary entries never change the verdict of a packet.
+pub mod canary {
+    /// Candidate rule set would drop traffic the active set passes
+    pub const WOULD_DROP: u32 = 1;
+    /// Candidate rule set would pass traffic the active set drops
+    pub const WOULD_PASS: u32 = 2;
+
+    /// Maximum number of canary entries per address family
+    pub const MAX_ENTRIES: u32 = 65536;
+}
+
+/// Canary entry for a single source address
+///
+/// Inserted by userspace for every source whose verdict differs between the
+/// active and candidate rule sets; the program only bumps the counters.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct CanaryEntry {
+    /// One of the `canary::WOULD_*` verdicts
+    pub verdict: u32,
+    pub _pad: u32,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Count a packet against the canary entry for `key`, if there is one
+#[inline(always)]
+pub fn record_canary<K>(map: &LruPerCpuHashMap<K, CanaryEntry>, key: &K, bytes: u64) {
+    if let Some(entry) = unsafe { map.get_ptr_mut(key) } {
+        unsafe {
+            (*entry).packets += 1;
+            (*entry).bytes += bytes;
+        }
+    }
+}
+
+// ============================================================================
+// Tenant Namespaces
+// ============================================================================
+
+/// Per-organization map namespaces. A worker may protect backends of several
+/// organizations; packets are attributed to a tenant by destination address
+/// and port, and tenant blocklists and rate limits are keyed on
+/// (tenant, source) so one tenant's limits never touch another's traffic.
+pub mod tenant {
+    /// Traffic to destinations of no registered backend (global maps)
+    pub const GLOBAL: u32 = 0;
+
+    /// Maximum number of registered backend destinations per address family
+    pub const MAX_DESTINATIONS: u32 = 65536;
+
+    /// Maximum number of tenants with their own configuration
+    pub const MAX_TENANTS: u32 = 4096;
+
+    /// Maximum number of tenant-scoped entries per address family
+    pub const MAX_ENTRIES: u32 = 1_000_000;
+
+    /// Per-source packets per second when a tenant sets no limit
+    pub const DEFAULT_PPS_LIMIT: u64 = 1000;
+}
+
+/// Backend destination (IPv4 address in host order, port; 0 = any port)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TenantDstV4Key {
+    pub addr: u32,
+    pub port: u16,
+    pub _pad: u16,
+}
+
+/// Backend destination (IPv6 address, port; 0 = any port)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TenantDstV6Key {
+    pub addr: [u8; 16],
+    pub port: u16,
+    pub _pad: u16,
+}
+
+/// Tenant-scoped IPv4 source key
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TenantIpV4Key {
+    pub tenant_id: u32,
+    pub addr: u32,
+}
+
+/// Tenant-scoped IPv6 source key
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TenantIpV6Key {
+    pub tenant_id: u32,
+    pub addr: [u8; 16],
+}
+
+/// Per-tenant configuration, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct TenantConfig {
+    pub protection_level: u32,
+    pub _pad: u32,
+    /// Per-source packets per second (0 = `tenant::DEFAULT_PPS_LIMIT`)
+    pub per_ip_pps_limit: u64,
+    /// Per-source bucket size (0 = one second of `per_ip_pps_limit`)
+    pub per_ip_burst: u64,
+}
+
+/// Tenant owning an IPv4 destination: exact port first, then any port
+#[inline(always)]
+pub fn lookup_tenant_v4(map: &HashMap<TenantDstV4Key, u32>, addr: u32, port: u16) -> u32 {
+    let key = TenantDstV4Key {
+        addr,
+        port,
+        _pad: 0,
+    };
+    if let Some(tenant_id) = unsafe { map.get(&key) } {
+        return *tenant_id;
+    }
+    let key = TenantDstV4Key {
+        addr,
+        port: 0,
+        _pad: 0,
+    };
+    match unsafe { map.get(&key) } {
+        Some(tenant_id) => *tenant_id,
+        None => tenant::GLOBAL,
+    }
+}
+
+/// Tenant owning an IPv6 destination: exact port first, then any port
+#[inline(always)]
+pub fn lookup_tenant_v6(map: &HashMap<TenantDstV6Key, u32>, addr: [u8; 16], port: u16) -> u32 {
+    let key = TenantDstV6Key {
+        addr,
+        port,
+        _pad: 0,
+    };
+    if let Some(tenant_id) = unsafe { map.get(&key) } {
+        return *tenant_id;
+    }
+    let key = TenantDstV6Key {
+        addr,
+        port: 0,
+        _pad: 0,
+    };
+    match unsafe { map.get(&key) } {
+        Some(tenant_id) => *tenant_id,
+        None => tenant::GLOBAL,
+    }
+}
+
+// ============================================================================
+// Threat Intelligence
+// ============================================================================
+
+/// Networks imported from third-party blocklists (Spamhaus DROP, FireHOL,
+/// AbuseIPDB, ...). Userspace normalizes every feed into prefixes and writes
+/// them into per-family LPM tries; each entry names the feed it came from so
+/// feeds can be switched off and their hits counted without rewriting the
+/// tries.
+pub mod threat_intel {
+    /// Maximum number of IPv4 prefixes across all feeds
+    pub const MAX_ENTRIES_V4: u32 = 1_000_000;
+
+    /// Maximum number of IPv6 prefixes across all feeds
+    pub const MAX_ENTRIES_V6: u32 = 250_000;
+
+    /// Maximum number of configured feeds
+    pub const MAX_FEEDS: u32 = 64;
+}
+
+/// Value of the `THREAT_INTEL_V*` tries
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ThreatIntelEntry {
+    /// Index into `THREAT_FEEDS`
+    pub feed_id: u32,
+    /// Category of the listing, as assigned by userspace
+    pub category: u32,
+    /// Confidence that the network is malicious (0-100)
+    pub confidence: u32,
+    pub _pad: u32,
+}
+
+/// Per-feed settings, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct ThreatFeedConfig {
+    /// Drop traffic from the feed's networks (0 = count only)
+    pub enabled: u32,
+    /// Entries below this confidence are ignored
+    pub min_confidence: u32,
+}
+
+/// Look up a source in a threat intelligence trie
+///
+/// Counts the packet against the matching feed and returns whether it should
+/// be dropped. Matches of disabled feeds are counted but never drop, so a feed
+/// can be evaluated before it is enforced; entries below the feed's
+/// confidence threshold are ignored.
+#[inline(always)]
+pub fn check_threat_intel<K>(
+    trie: &LpmTrie<K, ThreatIntelEntry>,
+    feeds: &Array<ThreatFeedConfig>,
+    hits: &PerCpuArray<DropCounter>,
+    key: &Key<K>,
+    bytes: u64,
+) -> bool {
+    let Some(entry) = trie.get(key) else {
+        return false;
+    };
+    let Some(feed) = feeds.get(entry.feed_id) else {
+        return false;
+    };
+    if entry.confidence < feed.min_confidence {
+        return false;
+    }
+    if let Some(counter) = hits.get_ptr_mut(entry.feed_id) {
+        unsafe {
+            (*counter).packets += 1;
+            (*counter).bytes += bytes;
+        }
+    }
+    feed.enabled != 0
+}
+
+// ============================================================================
+// Reputation Greylist
+// ============================================================================
+
+/// Sources with a poor reputation score. Userspace keeps the scores and
+/// writes sources above the greylist threshold here; they are not blocked
+/// but get a much smaller token bucket than regular sources.
+pub mod greylist {
+    /// Maximum number of greylisted sources per address family
+    pub const MAX_ENTRIES: u32 = 65536;
+
+    /// Per-source packets per second when the entry sets no limit
+    pub const DEFAULT_PPS_LIMIT: u64 = 50;
+}
+
+/// Value of the `GREYLIST_V*` maps, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct GreylistEntry {
+    /// Per-source packets per second (0 = `greylist::DEFAULT_PPS_LIMIT`)
+    pub pps_limit: u64,
+    /// Bucket size (0 = one second of `pps_limit`)
+    pub burst: u64,
+}
+
+impl GreylistEntry {
+    /// Effective (rate, bucket size)
+    #[inline(always)]
+    pub fn limits(&self) -> (u64, u64) {
+        let limit = if self.pps_limit > 0 {
+            self.pps_limit
+        } else {
+            greylist::DEFAULT_PPS_LIMIT
+        };
+        let burst = if self.burst > 0 { self.burst } else { limit };
+        (limit, burst)
+    }
+}
+
+// ============================================================================
+// Penalty Ladder
+// ============================================================================
+
+/// Escalating penalties for repeat offenders, shared by every program. A
+/// violation (e.g. a flood threshold exceeded) no longer blocks outright:
+/// the first ones only shrink the source's limits, repeated ones earn a short
+/// block and persistent ones a long block. Violations decay over time. The
+/// `PENALTY` and `PENALTY_CONFIG` maps are pinned by name so a penalty earned
+/// in one program applies in all of them.
+pub mod penalty {
+    /// Maximum number of tracked sources
+    pub const MAX_SOURCES: u32 = 262144;
+
+    /// Entries of `PENALTY_CONFIG`, indexed by protection level
+    pub const LEVELS: u32 = 5;
+
+    /// No penalty
+    pub const STAGE_NONE: u32 = 0;
+    /// Limits divided by `PenaltyConfig::rate_limit_divisor`
+    pub const STAGE_RATE_LIMIT: u32 = 1;
+    /// Blocked for `PenaltyConfig::short_block_ns`
+    pub const STAGE_SHORT_BLOCK: u32 = 2;
+    /// Blocked for `PenaltyConfig::long_block_ns`
+    pub const STAGE_LONG_BLOCK: u32 = 3;
+}
+
+/// Penalty ladder of a protection level, written by userspace
+///
+/// Thresholds count decayed violations, including the current one.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PenaltyConfig {
+    /// Violations before limits are divided
+    pub rate_limit_threshold: u32,
+    /// Divisor applied to the limits of rate-limited sources
+    pub rate_limit_divisor: u32,
+    /// Violations before a short block
+    pub short_block_threshold: u32,
+    /// Violations before a long block
+    pub long_block_threshold: u32,
+    pub short_block_ns: u64,
+    pub long_block_ns: u64,
+    /// Time for one violation to decay (0 = never)
+    pub decay_ns: u64,
+}
+
+impl PenaltyConfig {
+    /// Ladder used when userspace wrote none for the level
+    pub const DEFAULT: Self = Self {
+        rate_limit_threshold: 1,
+        rate_limit_divisor: 4,
+        short_block_threshold: 3,
+        long_block_threshold: 6,
+        short_block_ns: 60_000_000_000,   // 60 seconds
+        long_block_ns: 3_600_000_000_000, // 1 hour
+        decay_ns: 300_000_000_000,        // 5 minutes
+    };
+}
+
+/// Value of the `PENALTY` map
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct PenaltyEntry {
+    /// Violations at `last_violation_ns`, before decay
+    pub violations: u32,
+    /// `penalty::STAGE_*` reached by the last violation
+    pub stage: u32,
+    pub last_violation_ns: u64,
+    /// End of the current block (0 = not blocked)
+    pub blocked_until: u64,
+}
+
+impl PenaltyEntry {
+    /// Violations left after decay
+    #[inline(always)]
+    pub fn decayed(&self, config: &PenaltyConfig, now: u64) -> u32 {
+        if config.decay_ns == 0 {
+            return self.violations;
+        }
+        let decayed = now.saturating_sub(self.last_violation_ns) / config.decay_ns;
+        self.violations
+            .saturating_sub(decayed.min(u32::MAX as u64) as u32)
+    }
+}
+
+/// `PENALTY` key of an IPv4 source (host order), as an IPv4-mapped address
+#[inline(always)]
+pub fn penalty_key_v4(addr: u32) -> [u8; 16] {
+    let octets = addr.to_be_bytes();
+    [
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, octets[0], octets[1], octets[2], octets[3],
+    ]
+}
+
+/// Ladder of a protection level
+#[inline(always)]
+pub fn penalty_config(configs: &Array<PenaltyConfig>, level: u32) -> PenaltyConfig {
+    match configs.get(level) {
+        Some(config) if config.rate_limit_divisor > 0 => *config,
+        _ => PenaltyConfig::DEFAULT,
+    }
+}
+
+/// Record a violation and escalate the source's penalty
+///
+/// A source already penalized since `window_start` is not escalated again,
+/// so a flood counts once per detection window. A violation that earns a
+/// block marks the packet's drop event high priority. Returns the end of the
+/// block the source serves, or 0 if it is only rate-limited.
+#[inline(always)]
+pub fn record_violation(
+    scratch: &PerCpuArray<DropContext>,
+    penalties: &LruHashMap<[u8; 16], PenaltyEntry>,
+    key: &[u8; 16],
+    config: &PenaltyConfig,
+    now: u64,
+    window_start: u64,
+) -> u64 {
+    let previous = match unsafe { penalties.get(key) } {
+        Some(entry) => *entry,
+        None => PenaltyEntry::default(),
+    };
+    if previous.violations > 0 && previous.last_violation_ns >= window_start {
+        return previous.blocked_until;
+    }
+    let violations = previous.decayed(config, now).saturating_add(1);
+
+    let (stage, blocked_until) = if violations >= config.long_block_threshold {
+        (penalty::STAGE_LONG_BLOCK, now + config.long_block_ns)
+    } else if violations >= config.short_block_threshold {
+        (penalty::STAGE_SHORT_BLOCK, now + config.short_block_ns)
+    } else if violations >= config.rate_limit_threshold {
+        (penalty::STAGE_RATE_LIMIT, 0)
+    } else {
+        (penalty::STAGE_NONE, 0)
+    };
+
+    let entry = PenaltyEntry {
+        violations,
+        stage,
+        last_violation_ns: now,
+        blocked_until,
+    };
+    let _ = penalties.insert(key, &entry, 0);
+    if blocked_until > 0 {
+        drop_context_set_priority(scratch, event_budget::PRIORITY_HIGH);
+    }
+    blocked_until
+}
+
+/// Divisor to apply to the limits of a source (1 = no penalty)
+#[inline(always)]
+pub fn penalty_divisor(
+    penalties: &LruHashMap<[u8; 16], PenaltyEntry>,
+    key: &[u8; 16],
+    config: &PenaltyConfig,
+    now: u64,
+) -> u64 {
+    match unsafe { penalties.get(key) } {
+        Some(entry)
+            if entry.violations > 0
+                && entry.decayed(config, now) >= config.rate_limit_threshold =>
+        {
+            config.rate_limit_divisor.max(1) as u64
+        }
+        _ => 1,
+    }
+}
+
+/// Whether a source serves a block earned in any program
+#[inline(always)]
+pub fn penalty_blocked(
+    penalties: &LruHashMap<[u8; 16], PenaltyEntry>,
+    key: &[u8; 16],
+    now: u64,
+) -> bool {
+    match unsafe { penalties.get(key) } {
+        Some(entry) => entry.blocked_until > now,
+        None => false,
+    }
+}
+
+// ============================================================================
+// Known-Good Sources
+// ============================================================================
+
+/// Allowlist learning. While a backend is in its training window, programs
+/// record sources that complete a full, valid protocol handshake in the
+/// `HANDSHAKES` map. Userspace promotes the sources that keep doing so to the
+/// `KNOWN_GOOD` set, whose limits are multiplied so regular clients keep
+/// their service during later attacks. `LEARNING`, `HANDSHAKES` and
+/// `KNOWN_GOOD` are pinned by name and shared by every program.
+pub mod known_good {
+    /// Maximum number of known-good sources
+    pub const MAX_SOURCES: u32 = 65536;
+
+    /// Maximum number of sources with recorded handshakes
+    pub const MAX_HANDSHAKES: u32 = 65536;
+
+    /// TCP connection established and carrying data
+    pub const PROTO_TCP: u32 = 1;
+    /// Minecraft login completed
+    pub const PROTO_MINECRAFT: u32 = 2;
+    /// QUIC handshake completed
+    pub const PROTO_QUIC: u32 = 4;
+}
+
+/// Value of the `HANDSHAKES` map
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct HandshakeRecord {
+    pub first_seen_ns: u64,
+    pub last_seen_ns: u64,
+    /// Handshakes completed since userspace last drained the entry
+    pub completions: u32,
+    /// `known_good::PROTO_*` of the completed handshakes
+    pub protocols: u32,
+    /// Destination of the last handshake (IPv4-mapped for IPv4)
+    pub dst_addr: [u8; 16],
+    pub dst_port: u16,
+    pub _pad: [u8; 6],
+}
+
+/// Record a completed handshake, if some backend is learning
+///
+/// `learning` holds a single flag set by userspace while any backend is in
+/// its training window, so handshakes cost nothing the rest of the time.
+#[inline(always)]
+pub fn record_handshake(
+    learning: &Array<u32>,
+    handshakes: &LruHashMap<[u8; 16], HandshakeRecord>,
+    src: &[u8; 16],
+    dst: &[u8; 16],
+    dst_port: u16,
+    protocol: u32,
+    now: u64,
+) {
+    match learning.get(0) {
+        Some(&enabled) if enabled != 0 => {}
+        _ => return,
+    }
+
+    if let Some(record) = unsafe { handshakes.get_ptr_mut(src) } {
+        let record = unsafe { &mut *record };
+        record.last_seen_ns = now;
+        record.completions = record.completions.saturating_add(1);
+        record.protocols |= protocol;
+        record.dst_addr = *dst;
+        record.dst_port = dst_port;
+        return;
+    }
+
+    let record = HandshakeRecord {
+        first_seen_ns: now,
+        last_seen_ns: now,
+        completions: 1,
+        protocols: protocol,
+        dst_addr: *dst,
+        dst_port,
+        _pad: [0; 6],
+    };
+    let _ = handshakes.insert(src, &record, 0);
+}
+
+/// Multiplier to apply to the limits of a source (1 = not known-good)
+#[inline(always)]
+pub fn known_good_multiplier(known_good: &HashMap<[u8; 16], u32>, key: &[u8; 16]) -> u64 {
+    match unsafe { known_good.get(key) } {
+        Some(&multiplier) if multiplier > 1 => multiplier as u64,
+        _ => 1,
+    }
+}
+
+// ============================================================================
+// Carrier-Grade NAT
+// ============================================================================
+
+/// Carrier-grade NAT. Mobile carriers and some ISPs put thousands of users
+/// behind one address, which then trips every per-IP limit. Userspace writes
+/// the known CGNAT ranges and their limit multiplier to `CGNAT_RANGES`. While
+/// detection is on (`CGNAT_DETECT`), xdp_tcp records in `CGNAT_SIGNALS` a
+/// sketch of the SYN shapes and TCP timestamp clocks seen from each source:
+/// one host has one clock, while a NAT address shows many. All three maps
+/// are pinned by name and shared by every program.
+pub mod cgnat {
+    /// Maximum number of CGNAT ranges
+    pub const MAX_RANGES: u32 = 16384;
+
+    /// Maximum number of sources with recorded signals
+    pub const MAX_SOURCES: u32 = 65536;
+
+    /// TCP options inspected per SYN
+    pub const MAX_OPTIONS: usize = 10;
+
+    /// TCP timestamp clock offsets closer than 2^CLOCK_SHIFT ticks (about a
+    /// minute at 1 kHz) count as the same clock
+    pub const CLOCK_SHIFT: u32 = 16;
+}
+
+/// Value of the `CGNAT_SIGNALS` map
+///
+/// `fingerprints` and `clocks` are 64-bit sketches: each distinct SYN shape
+/// or timestamp clock sets one bit, so userspace can estimate how many were
+/// seen from the number of bits set.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CgnatSignals {
+    pub first_seen_ns: u64,
+    pub last_seen_ns: u64,
+    pub syns: u32,
+    /// SYNs carrying a TCP timestamp
+    pub timestamped: u32,
+    pub fingerprints: u64,
+    pub clocks: u64,
+}
+
+/// Shape of a SYN and its TCP timestamp value
+///
+/// The shape hashes the window and the kind, order and MSS/window scale
+/// values of the TCP options, which are operating system defaults.
+/// `options_end` is the end of the TCP header.
+#[inline(always)]
+pub fn syn_signature(tcp_offset: usize, options_end: usize, data_end: usize) -> (u32, Option<u32>) {
+    let Some(tcp) = header_at::<TcpHdr>(tcp_offset, data_end) else {
+        return (0, None);
+    };
+    let mut fingerprint = u16::from_be(tcp.window) as u32;
+    let mut tsval = None;
+
+    let mut offset = tcp_offset + mem::size_of::<TcpHdr>();
+    for _ in 0..cgnat::MAX_OPTIONS {
+        if offset >= options_end {
+            break;
+        }
+        let Some(&kind) = header_at::<u8>(offset, data_end) else {
+            break;
+        };
+        fingerprint = fingerprint.wrapping_mul(31).wrapping_add(kind as u32);
+        match kind {
+            0 => break,
+            1 => {
+                offset += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let Some(&len) = header_at::<u8>(offset + 1, data_end) else {
+            break;
+        };
+        match (kind, len) {
+            // MSS
+            (2, 4) => {
+                if let Some(mss) = header_at::<[u8; 2]>(offset + 2, data_end) {
+                    fingerprint = fingerprint
+                        .wrapping_mul(31)
+                        .wrapping_add(u16::from_be_bytes(*mss) as u32);
+                }
+            }
+            // Window scale
+            (3, 3) => {
+                if let Some(&shift) = header_at::<u8>(offset + 2, data_end) {
+                    fingerprint = fingerprint.wrapping_mul(31).wrapping_add(shift as u32);
+                }
+            }
+            // Timestamps
+            (8, 10) => {
+                if let Some(value) = header_at::<[u8; 4]>(offset + 2, data_end) {
+                    tsval = Some(u32::from_be_bytes(*value));
+                }
+            }
+            (_, 0 | 1) => break,
+            _ => {}
+        }
+        offset += len as usize;
+    }
+
+    (fingerprint, tsval)
+}
+
+/// Bit of a 64-bit sketch for a value
+#[inline(always)]
+fn sketch_bit(value: u32) -> u64 {
+    1u64 << (value.wrapping_mul(0x9e37_79b1) >> 26)
+}
+
+/// Record the SYN signals of a source, if CGNAT detection is on
+///
+/// The clock of a timestamp is its offset from our own clock: it stays put
+/// for one host, while hosts behind a NAT each have their own.
+#[inline(always)]
+pub fn record_cgnat_signals(
+    detect: &Array<u32>,
+    signals: &LruHashMap<[u8; 16], CgnatSignals>,
+    src: &[u8; 16],
+    fingerprint: u32,
+    tsval: Option<u32>,
+    now: u64,
+) {
+    match detect.get(0) {
+        Some(&enabled) if enabled != 0 => {}
+        _ => return,
+    }
+
+    let fingerprint = sketch_bit(fingerprint);
+    let clock = match tsval {
+        Some(tsval) => {
+            let now_ms = (now / 1_000_000) as u32;
+            sketch_bit(tsval.wrapping_sub(now_ms) >> cgnat::CLOCK_SHIFT)
+        }
+        None => 0,
+    };
+    let timestamped = tsval.is_some() as u32;
+
+    if let Some(entry) = unsafe { signals.get_ptr_mut(src) } {
+        let entry = unsafe { &mut *entry };
+        entry.last_seen_ns = now;
+        entry.syns = entry.syns.saturating_add(1);
+        entry.timestamped = entry.timestamped.saturating_add(timestamped);
+        entry.fingerprints |= fingerprint;
+        entry.clocks |= clock;
+        return;
+    }
+
+    let entry = CgnatSignals {
+        first_seen_ns: now,
+        last_seen_ns: now,
+        syns: 1,
+        timestamped,
+        fingerprints: fingerprint,
+        clocks: clock,
+    };
+    let _ = signals.insert(src, &entry, 0);
+}
+
+/// Multiplier to apply to the per-IP limits of a source: the larger of its
+/// known-good and CGNAT multipliers (1 = neither)
+#[inline(always)]
+pub fn limit_multiplier(
+    known_good: &HashMap<[u8; 16], u32>,
+    cgnat: &LpmTrie<[u8; 16], u32>,
+    key: &[u8; 16],
+) -> u64 {
+    let shared = match cgnat.get(&Key::new(128, *key)) {
+        Some(&multiplier) if multiplier > 1 => multiplier as u64,
+        _ => 1,
+    };
+    known_good_multiplier(known_good, key).max(shared)
+}
+
+// ============================================================================
+// Honeypot Ports
+// ============================================================================
+
+/// Ports of a backend that no real service listens on. Legitimate clients
+/// never send to them, so any source that does is flagged and, if the
+/// backend asks for it, blocked for every backend and program.
+pub mod honeypot {
+    /// Maximum number of honeypot destinations per address family
+    pub const MAX_PORTS: u32 = 65536;
+
+    /// Maximum number of flagged sources awaiting userspace per address family
+    pub const MAX_HITS: u32 = 65536;
+
+    /// `HoneypotConfig::flags`: block sources touching the port
+    pub const FLAG_AUTO_BLOCK: u32 = 1;
+}
+
+/// Honeypot destination (IPv4 address in host order, 0 = any address; port)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct HoneypotV4Key {
+    pub addr: u32,
+    pub port: u16,
+    pub _pad: u16,
+}
+
+/// Honeypot destination (IPv6 address, all zeros = any address; port)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct HoneypotV6Key {
+    pub addr: [u8; 16],
+    pub port: u16,
+    pub _pad: u16,
+}
+
+/// Value of the `HONEYPOT_PORTS_V*` maps, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct HoneypotConfig {
+    /// Tenant of the backend owning the port
+    pub tenant_id: u32,
+    /// `honeypot::FLAG_*`
+    pub flags: u32,
+    /// Block duration in nanoseconds (0 = until unblocked by userspace)
+    pub block_duration_ns: u64,
+}
+
+/// Flagged source, read and removed by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct HoneypotHit {
+    pub first_seen_ns: u64,
+    pub last_seen_ns: u64,
+    pub packets: u64,
+    pub tenant_id: u32,
+    /// Last honeypot port touched
+    pub port: u16,
+    pub protocol: u8,
+    /// The source was blocked
+    pub blocked: u8,
+}
+
+/// Honeypot config of an IPv4 destination: this address first, then any
+/// address
+#[inline(always)]
+pub fn lookup_honeypot_v4(
+    map: &HashMap<HoneypotV4Key, HoneypotConfig>,
+    addr: u32,
+    port: u16,
+) -> Option<HoneypotConfig> {
+    if port == 0 {
+        return None;
+    }
+    let key = HoneypotV4Key {
+        addr,
+        port,
+        _pad: 0,
+    };
+    if let Some(config) = unsafe { map.get(&key) } {
+        return Some(*config);
+    }
+    let key = HoneypotV4Key {
+        addr: 0,
+        port,
+        _pad: 0,
+    };
+    unsafe { map.get(&key) }.copied()
+}
+
+/// Honeypot config of an IPv6 destination: this address first, then any
+/// address
+#[inline(always)]
+pub fn lookup_honeypot_v6(
+    map: &HashMap<HoneypotV6Key, HoneypotConfig>,
+    addr: [u8; 16],
+    port: u16,
+) -> Option<HoneypotConfig> {
+    if port == 0 {
+        return None;
+    }
+    let key = HoneypotV6Key {
+        addr,
+        port,
+        _pad: 0,
+    };
+    if let Some(config) = unsafe { map.get(&key) } {
+        return Some(*config);
+    }
+    let key = HoneypotV6Key {
+        addr: [0; 16],
+        port,
+        _pad: 0,
+    };
+    unsafe { map.get(&key) }.copied()
+}
+
+/// Flag a source that touched a honeypot port
+///
+/// Returns whether the source should be blocked.
+#[inline(always)]
+pub fn record_honeypot_hit<K>(
+    hits: &LruHashMap<K, HoneypotHit>,
+    src: &K,
+    config: &HoneypotConfig,
+    protocol: u8,
+    port: u16,
+) -> bool {
+    let now = unsafe { bpf_ktime_get_ns() };
+    let block = config.flags & honeypot::FLAG_AUTO_BLOCK != 0;
+
+    if let Some(hit) = hits.get_ptr_mut(src) {
+        unsafe {
+            (*hit).last_seen_ns = now;
+            (*hit).packets += 1;
+            (*hit).port = port;
+            (*hit).protocol = protocol;
+            (*hit).blocked |= block as u8;
+        }
+    } else {
+        let hit = HoneypotHit {
+            first_seen_ns: now,
+            last_seen_ns: now,
+            packets: 1,
+            tenant_id: config.tenant_id,
+            port,
+            protocol,
+            blocked: block as u8,
+        };
+        let _ = hits.insert(src, &hit, 0);
+    }
+    block
+}
+
+// ============================================================================
+// Backend Modes
+// ============================================================================
+
+/// Per-backend override of the filtering, set by userspace while an operator
+/// debugs a backend. Checked before any other filter.
+pub mod backend_mode {
+    /// Filter with the configured protection (destinations not listed)
+    pub const PROTECT: u32 = 0;
+
+    /// Pass all traffic without filtering
+    pub const PASSTHROUGH: u32 = 1;
+
+    /// Drop all traffic
+    pub const BLOCK_ALL: u32 = 2;
+
+    /// Maximum number of destinations with a mode per address family
+    pub const MAX_DESTINATIONS: u32 = 65536;
+}
+
+/// Mode of an IPv4 destination: exact port first, then any port
+#[inline(always)]
+pub fn lookup_backend_mode_v4(map: &HashMap<TenantDstV4Key, u32>, addr: u32, port: u16) -> u32 {
+    let key = TenantDstV4Key {
+        addr,
+        port,
+        _pad: 0,
+    };
+    if let Some(mode) = unsafe { map.get(&key) } {
+        return *mode;
+    }
+    let key = TenantDstV4Key {
+        addr,
+        port: 0,
+        _pad: 0,
+    };
+    match unsafe { map.get(&key) } {
+        Some(mode) => *mode,
+        None => backend_mode::PROTECT,
+    }
+}
+
+/// Mode of an IPv6 destination: exact port first, then any port
+#[inline(always)]
+pub fn lookup_backend_mode_v6(
+    map: &HashMap<TenantDstV6Key, u32>,
+    addr: [u8; 16],
+    port: u16,
+) -> u32 {
+    let key = TenantDstV6Key {
+        addr,
+        port,
+        _pad: 0,
+    };
+    if let Some(mode) = unsafe { map.get(&key) } {
+        return *mode;
+    }
+    let key = TenantDstV6Key {
+        addr,
+        port: 0,
+        _pad: 0,
+    };
+    match unsafe { map.get(&key) } {
+        Some(mode) => *mode,
+        None => backend_mode::PROTECT,
+    }
+}
+
+// ============================================================================
+// Per-Connection Bandwidth
+// ============================================================================
+
+/// Per-connection bandwidth limits. A backend can cap the bytes per second of
+/// each established flow, so a single client cannot saturate it without
+/// being blocked: packets beyond the flow's token bucket are dropped and the
+/// flow carries on at the configured rate. Userspace writes the limit of
+/// each backend destination to `FLOW_RATES`, keyed by IPv4-mapped address
+/// and port (0 = any port); the map is pinned by name and shared by xdp_tcp,
+/// which keeps the bucket in its connection state, and xdp_udp, which tracks
+/// flows to limited destinations in `UDP_FLOWS`.
+pub mod flow_rate {
+    /// Maximum number of limited destinations
+    pub const MAX_DESTINATIONS: u32 = 65536;
+
+    /// Maximum number of tracked UDP flows
+    pub const MAX_FLOWS: u32 = 262_144;
+
+    /// Idle time after which a UDP flow is new again
+    pub const UDP_IDLE_NS: u64 = 30_000_000_000;
+}
+
+/// Value of `FLOW_RATES`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FlowRateConfig {
+    pub bytes_per_sec: u64,
+    /// Bucket size (0 = one second of `bytes_per_sec`)
+    pub burst_bytes: u64,
+}
+
+/// Token bucket of one flow; a zeroed bucket is full on first use
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FlowBucket {
+    pub tokens: u64,
+    pub refilled_ns: u64,
+}
+
+/// Key of `UDP_FLOWS` (addresses IPv4-mapped for IPv4)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FlowKey {
+    pub src_addr: [u8; 16],
+    pub dst_addr: [u8; 16],
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+/// Value of `UDP_FLOWS`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct UdpFlowState {
+    pub bucket: FlowBucket,
+    pub packets: u64,
+    pub last_seen_ns: u64,
+}
+
+/// Bandwidth limit of a destination: exact port first, then any port
+#[inline(always)]
+pub fn lookup_flow_rate(
+    map: &HashMap<TenantDstV6Key, FlowRateConfig>,
+    addr: &[u8; 16],
+    port: u16,
+) -> Option<FlowRateConfig> {
+    let key = TenantDstV6Key {
+        addr: *addr,
+        port,
+        _pad: 0,
+    };
+    if let Some(config) = unsafe { map.get(&key) } {
+        return Some(*config);
+    }
+    let key = TenantDstV6Key {
+        addr: *addr,
+        port: 0,
+        _pad: 0,
+    };
+    unsafe { map.get(&key) }.copied()
+}
+
+/// Take `bytes` from a flow's bucket; false means the packet is over the limit
+///
+/// A bucket idle for a second or more is refilled to a full burst.
+#[inline(always)]
+pub fn flow_bucket_take(
+    bucket: &mut FlowBucket,
+    config: &FlowRateConfig,
+    bytes: u64,
+    now: u64,
+) -> bool {
+    let burst = flow_burst(config);
+    let elapsed = now.saturating_sub(bucket.refilled_ns);
+    if elapsed >= 1_000_000_000 {
+        bucket.tokens = burst;
+        bucket.refilled_ns = now;
+    } else {
+        let refill = elapsed.saturating_mul(config.bytes_per_sec) / 1_000_000_000;
+        if refill > 0 {
+            bucket.tokens = (bucket.tokens + refill).min(burst);
+            bucket.refilled_ns = now;
+        }
+    }
+
+    if bucket.tokens < bytes {
+        return false;
+    }
+    bucket.tokens -= bytes;
+    true
+}
+
+#[inline(always)]
+fn flow_burst(config: &FlowRateConfig) -> u64 {
+    if config.burst_bytes != 0 {
+        config.burst_bytes
+    } else {
+        config.bytes_per_sec
+    }
+}
+
+// ============================================================================
+// DNS Query Budgets
+// ============================================================================
+
+/// Per-client query budgets of protected DNS resolvers. xdp_tcp counts the
+/// DNS-over-TCP queries sent to protected TCP ports userspace flags with
+/// `PORT_DNS` in `TCP_PROTECTED_PORTS`, and xdp_http the DNS-over-HTTPS
+/// requests for `DOH_PATH` it can read in plaintext. Each program reads the
+/// budgets from its `DNS_BUDGET` and keeps the per-second query counts of
+/// its clients in `DNS_RATES`, keyed by IPv4-mapped address.
+pub mod dns {
+    /// Maximum number of clients counted per program
+    pub const MAX_CLIENTS: u32 = 262_144;
+
+    /// Flag of protected TCP ports serving DNS (next to `PROTECTED`)
+    pub const PORT_DNS: u32 = 0x2;
+
+    /// Request path of DNS-over-HTTPS (RFC 8484)
+    pub const DOH_PATH: &[u8] = b"/dns-query";
+
+    /// Length-prefixed queries walked per DNS-over-TCP segment
+    pub const MAX_PIPELINED: usize = 8;
+}
+
+/// Value of `DNS_BUDGET` (0 = unlimited)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DnsBudgetConfig {
+    /// DNS-over-TCP queries per second of each client
+    pub tcp_qps: u32,
+    /// DNS-over-HTTPS requests per second of each client
+    pub doh_qps: u32,
+}
+
+/// Value of `DNS_RATES`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DnsRate {
+    pub window_start: u64,
+    pub queries: u64,
+}
+
+/// Count `queries` against a client's budget of `qps`; false means the
+/// client is over it
+#[inline(always)]
+pub fn dns_budget_take(
+    rates: &LruHashMap<[u8; 16], DnsRate>,
+    src_addr: &[u8; 16],
+    qps: u64,
+    queries: u64,
+    now: u64,
+) -> bool {
+    if let Some(rate) = unsafe { rates.get_ptr_mut(src_addr) } {
+        let rate = unsafe { &mut *rate };
+        if now.saturating_sub(rate.window_start) >= 1_000_000_000 {
+            rate.window_start = now;
+            rate.queries = 0;
+        }
+        rate.queries += queries;
+        rate.queries <= qps
+    } else {
+        let rate = DnsRate {
+            window_start: now,
+            queries,
+        };
+        let _ = rates.insert(src_addr, &rate, 0);
+        queries <= qps
+    }
+}
+
+// ============================================================================
+// Destination Traffic Accounting
+// ============================================================================
+
+/// Bandwidth accounting of protected destinations. Userspace inserts a zeroed
+/// `DstTraffic` for every backend destination into `DST_TRAFFIC`, keyed by
+/// IPv4-mapped address and port (0 = any port); xdp_filter only bumps the
+/// counters of existing entries, so unprotected destinations cost nothing.
+/// The destination of a packet is resolved when its headers are parsed and
+/// kept in a per-CPU scratch slot until the verdict is known, the same way
+/// as `DropContext`.
+pub mod traffic {
+    /// Maximum number of accounted destinations
+    pub const MAX_DESTINATIONS: u32 = 65536;
+}
+
+/// Traffic counters of one destination
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DstTraffic {
+    /// Every packet to the destination, dropped or not
+    pub packets: u64,
+    pub bytes: u64,
+    pub dropped_packets: u64,
+    pub dropped_bytes: u64,
+}
+
+/// Per-CPU scratch holding the accounted destination of the current packet
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TrafficContext {
+    pub key: TenantDstV6Key,
+    /// Non-zero once the packet matched an entry of `DST_TRAFFIC`
+    pub matched: u32,
+}
+
+/// Forget the destination of the previous packet
+#[inline(always)]
+pub fn traffic_context_reset(scratch: &PerCpuArray<TrafficContext>) {
+    if let Some(ctx) = unsafe { scratch.get_ptr_mut(0) } {
+        unsafe {
+            (*ctx).matched = 0;
+        }
+    }
+}
+
+/// Resolve the accounted destination of the current packet: exact port
+/// first, then any port
+#[inline(always)]
+pub fn traffic_context_set_destination(
+    scratch: &PerCpuArray<TrafficContext>,
+    map: &PerCpuHashMap<TenantDstV6Key, DstTraffic>,
+    addr: &[u8; 16],
+    port: u16,
+) {
+    let Some(ctx) = (unsafe { scratch.get_ptr_mut(0) }) else {
+        return;
+    };
+    let mut key = TenantDstV6Key {
+        addr: *addr,
+        port,
+        _pad: 0,
+    };
+    if unsafe { map.get(&key) }.is_none() {
+        key.port = 0;
+        if unsafe { map.get(&key) }.is_none() {
+            return;
+        }
+    }
+    unsafe {
+        (*ctx).key = key;
+        (*ctx).matched = 1;
+    }
+}
+
+/// Count the current packet against its destination, if it has one
+#[inline(always)]
+pub fn record_traffic(
+    scratch: &PerCpuArray<TrafficContext>,
+    map: &PerCpuHashMap<TenantDstV6Key, DstTraffic>,
+    bytes: u64,
+    dropped: bool,
+) {
+    let ctx = match unsafe { scratch.get(0) } {
+        Some(ctx) if ctx.matched != 0 => *ctx,
+        _ => return,
+    };
+    if let Some(counters) = unsafe { map.get_ptr_mut(&ctx.key) } {
+        unsafe {
+            (*counters).packets += 1;
+            (*counters).bytes += bytes;
+            if dropped {
+                (*counters).dropped_packets += 1;
+                (*counters).dropped_bytes += bytes;
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Traffic Marking
+// ============================================================================
+
+/// Deprioritization of suspicious traffic. Greylisted sources exceeding their
+/// reduced bucket are normally dropped; a backend with a marking policy in
+/// `MARKING_POLICIES` (keyed by IPv4-mapped address and port, 0 = any port)
+/// instead gets them rewritten to a scavenger DSCP and passed, so QoS further
+/// downstream sheds them first under congestion. ECN-capable packets can also
+/// be marked CE so the sender backs off. Only the IP header changes: the
+/// IPv4 header checksum is fixed up incrementally, IPv6 has none and the
+/// TOS/traffic class is not part of the TCP/UDP pseudo-header.
+pub mod marking {
+    /// Maximum number of destinations with a marking policy
+    pub const MAX_DESTINATIONS: u32 = 65536;
+
+    /// Entries of `MARKING_COUNTERS`, indexed by `MarkingPolicy::policy_id`
+    pub const MAX_POLICIES: u32 = 4096;
+
+    /// Mark and pass greylisted packets beyond their bucket instead of
+    /// dropping them
+    pub const FLAG_GREYLIST_EXCESS: u32 = 1 << 0;
+    /// Also mark greylisted packets within their bucket
+    pub const FLAG_GREYLIST_WITHIN: u32 = 1 << 1;
+    /// Set ECN CE on ECN-capable packets
+    pub const FLAG_ECN_CE: u32 = 1 << 2;
+
+    /// Lower-effort per-hop behavior (RFC 8622)
+    pub const DSCP_LE: u8 = 1;
+    /// Largest DSCP value
+    pub const DSCP_MAX: u8 = 63;
+
+    /// ECN field values (RFC 3168)
+    pub const ECN_NOT_ECT: u8 = 0;
+    pub const ECN_CE: u8 = 3;
+}
+
+/// Value of `MARKING_POLICIES`, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct MarkingPolicy {
+    /// Index into `MARKING_COUNTERS`
+    pub policy_id: u32,
+    /// `marking::FLAG_*`
+    pub flags: u32,
+    /// DSCP written to marked packets
+    pub dscp: u8,
+    pub _pad: [u8; 7],
+}
+
+impl MarkingPolicy {
+    /// Whether a greylisted packet is marked, given whether it was within
+    /// its bucket
+    #[inline(always)]
+    pub fn marks(&self, within_limit: bool) -> bool {
+        let flag = if within_limit {
+            marking::FLAG_GREYLIST_WITHIN
+        } else {
+            marking::FLAG_GREYLIST_EXCESS
+        };
+        self.flags & flag != 0
+    }
+}
+
+/// Counters of one marking policy
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct MarkingCounter {
+    pub packets: u64,
+    pub bytes: u64,
+    /// Part of `packets` marked CE
+    pub ce_marked: u64,
+}
+
+/// Marking policy of a destination: exact port first, then any port
+#[inline(always)]
+pub fn lookup_marking(
+    map: &HashMap<TenantDstV6Key, MarkingPolicy>,
+    addr: &[u8; 16],
+    port: u16,
+) -> Option<MarkingPolicy> {
+    let key = TenantDstV6Key {
+        addr: *addr,
+        port,
+        _pad: 0,
+    };
+    if let Some(policy) = unsafe { map.get(&key) } {
+        return Some(*policy);
+    }
+    let key = TenantDstV6Key {
+        addr: *addr,
+        port: 0,
+        _pad: 0,
+    };
+    unsafe { map.get(&key) }.copied()
+}
+
+/// TOS/traffic class byte with the policy's DSCP, and whether CE was set
+///
+/// CE is only set on ECN-capable packets; a Not-ECT packet marked CE would
+/// be treated as a protocol violation by the receiver.
+#[inline(always)]
+pub fn marked_tos(tos: u8, policy: &MarkingPolicy) -> (u8, bool) {
+    let ecn = tos & 0x03;
+    let ce = policy.flags & marking::FLAG_ECN_CE != 0 && ecn != marking::ECN_NOT_ECT;
+    let ecn = if ce { marking::ECN_CE } else { ecn };
+    (((policy.dscp & marking::DSCP_MAX) << 2) | ecn, ce)
+}
+
+/// Mark an IPv4 packet, updating the header checksum. Returns whether CE was
+/// set.
+#[inline(always)]
+pub fn mark_ipv4(ip: &mut Ipv4Hdr, policy: &MarkingPolicy) -> bool {
+    let (tos, ce) = marked_tos(ip.tos, policy);
+    let old = u16::from_ne_bytes([ip.version_ihl, ip.tos]);
+    let new = u16::from_ne_bytes([ip.version_ihl, tos]);
+    ip.tos = tos;
+    ip.check = csum_replace2(ip.check, old, new);
+    ce
+}
+
+/// Mark an IPv6 packet. Returns whether CE was set.
+#[inline(always)]
+pub fn mark_ipv6(ip6: &mut Ipv6Hdr, policy: &MarkingPolicy) -> bool {
+    let word = u32::from_be(ip6.version_tc_flow);
+    let (tc, ce) = marked_tos((word >> 20) as u8, policy);
+    let word = (word & !(0xff << 20)) | ((tc as u32) << 20);
+    ip6.version_tc_flow = word.to_be();
+    ce
+}
+
+/// Count a marked packet against its policy
+#[inline(always)]
+pub fn record_marking(
+    counters: &PerCpuArray<MarkingCounter>,
+    policy_id: u32,
+    bytes: u64,
+    ce: bool,
+) {
+    if let Some(counter) = unsafe { counters.get_ptr_mut(policy_id) } {
+        unsafe {
+            (*counter).packets += 1;
+            (*counter).bytes += bytes;
+            if ce {
+                (*counter).ce_marked += 1;
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Conntrack Bypass
+// ============================================================================
+
+/// Handoff of validated flows to the host stack. XDP runs before the kernel
+/// allocates an skb, so it cannot set the skb mark itself: once xdp_tcp sees
+/// a connection complete its handshake it records the flow in `FLOW_MARKS`
+/// (keyed client to server, addresses IPv4-mapped), and the tc_flowmark
+/// classifier sets the configured mark bits on the packets of recorded flows
+/// in either direction. A raw table rule matching the mark (`NOTRACK`) then
+/// lets them skip conntrack and the stateful iptables chains. Both maps are
+/// pinned by name; userspace writes `FLOW_MARK_CONFIG`, and while it is
+/// disabled xdp_tcp records nothing.
+pub mod flow_mark {
+    /// Maximum number of recorded flows; the least recently used go first
+    pub const MAX_FLOWS: u32 = 262_144;
+}
+
+/// Value of `FLOW_MARK_CONFIG`, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FlowMarkConfig {
+    /// Record and mark validated flows (0 = off)
+    pub enabled: u32,
+    /// Mark bits set on the packets of recorded flows
+    pub mark: u32,
+    /// Bits of the skb mark owned by the bypass
+    pub mask: u32,
+    pub _pad: u32,
+}
+
+/// Value of `FLOW_MARKS`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FlowMarkEntry {
+    /// When xdp_tcp validated the flow
+    pub validated_ns: u64,
+}
+
+/// Counters of tc_flowmark
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct FlowMarkStats {
+    /// TCP packets looked up
+    pub packets: u64,
+    /// Packets of recorded flows, which were marked
+    pub marked: u64,
+}
+
+/// Record a validated flow, if the bypass is enabled
+#[inline(always)]
+pub fn record_validated_flow(
+    config: &Array<FlowMarkConfig>,
+    flows: &LruHashMap<FlowKey, FlowMarkEntry>,
+    key: &FlowKey,
+    now: u64,
+) {
+    if config.get(0).is_some_and(|config| config.enabled != 0) {
+        let _ = flows.insert(key, &FlowMarkEntry { validated_ns: now }, 0);
+    }
+}
+
+/// Forget a flow in both directions
+#[inline(always)]
+pub fn forget_flow(flows: &LruHashMap<FlowKey, FlowMarkEntry>, key: &FlowKey) {
+    let _ = flows.remove(key);
+    let _ = flows.remove(&flow_key_reversed(key));
+}
+
+/// Key of the other direction of a flow
+#[inline(always)]
+pub fn flow_key_reversed(key: &FlowKey) -> FlowKey {
+    FlowKey {
+        src_addr: key.dst_addr,
+        dst_addr: key.src_addr,
+        src_port: key.dst_port,
+        dst_port: key.src_port,
+    }
+}
+
+/// skb mark with the bypass bits set, other bits kept
+#[inline(always)]
+pub fn flow_marked(mark: u32, config: &FlowMarkConfig) -> u32 {
+    (mark & !config.mask) | (config.mark & config.mask)
+}
+
+// ============================================================================
+// Flow Sampling
+// ============================================================================
+
+pub mod sampling {
+    /// Default sampling rate (1 in N passed packets); 0 disables sampling
+    pub const DEFAULT_RATE: u32 = 1024;
+
+    /// Bytes of each sampled packet copied to userspace
+    pub const CAPTURE_BYTES: usize = 128;
+
+    /// Size of each program's sample ring buffer
+    pub const RING_BYTES: u32 = 256 * 1024;
+
+    /// Map the programs submit samples through (`SAMPLES`)
+    #[cfg(feature = "ringbuf")]
+    pub type SampleMap = aya_ebpf::maps::RingBuf;
+
+    /// Map the programs submit samples through (`SAMPLES`)
+    #[cfg(not(feature = "ringbuf"))]
+    pub type SampleMap = aya_ebpf::maps::PerfEventArray<super::SampleHeader>;
+
+    #[cfg(feature = "ringbuf")]
+    pub const fn sample_map() -> SampleMap {
+        SampleMap::with_byte_size(RING_BYTES, 0)
+    }
+
+    #[cfg(not(feature = "ringbuf"))]
+    pub const fn sample_map() -> SampleMap {
+        SampleMap::new(0)
+    }
+}
+
+/// Sampling configuration, one per program, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SampleConfig {
+    /// Sample 1 in `rate` passed packets (0 disables sampling)
+    pub rate: u32,
+    pub _pad: u32,
+}
+
+/// Headers of a sampled packet, submitted through the `SAMPLES` ring buffer
+#[repr(C)]
+#[cfg(feature = "ringbuf")]
+pub struct PacketSample {
+    /// `bpf_ktime_get_ns` at the time of sampling
+    pub timestamp_ns: u64,
+    /// Length of the whole packet
+    pub packet_len: u32,
+    /// Bytes of `data` that were filled in
+    pub captured_len: u32,
+    /// Sampling rate in effect, for extrapolation in userspace
+    pub rate: u32,
+    pub _pad: u32,
+    pub data: [u8; sampling::CAPTURE_BYTES],
+}
+
+/// Headers of a sampled packet, submitted through the `SAMPLES` perf event
+/// array; the kernel appends the `captured_len` leading bytes of the packet
+#[repr(C)]
+pub struct SampleHeader {
+    pub timestamp_ns: u64,
+    pub packet_len: u32,
+    pub captured_len: u32,
+    pub rate: u32,
+    pub _pad: u32,
+}
+
+/// Sampling rate if this packet should be sampled
+#[inline(always)]
+fn sample_rate(config: &PerCpuArray<SampleConfig>) -> Option<u32> {
+    let rate = match unsafe { config.get(0) } {
+        Some(config) if config.rate > 0 => config.rate,
+        _ => return None,
+    };
+
+    if rate > 1 && unsafe { bpf_get_prandom_u32() } % rate != 0 {
+        return None;
+    }
+    Some(rate)
+}
+
+/// Copy the leading bytes of 1 in `rate` packets into the sample ring buffer
+///
+/// Takes the raw context so it can run after the program has consumed its
+/// `XdpContext`. Samples are dropped silently when the ring buffer is full.
+#[cfg(feature = "ringbuf")]
+#[inline(always)]
+pub fn sample_packet(
+    ctx: *mut xdp_md,
+    packet_len: u64,
+    config: &PerCpuArray<SampleConfig>,
+    ring: &sampling::SampleMap,
+    budget_config: &PerCpuArray<EventBudgetConfig>,
+    budget: &PerCpuArray<EventBudget>,
+) {
+    let Some(rate) = sample_rate(config) else {
+        return;
+    };
+    if !event_admit(budget_config, budget, event_budget::PRIORITY_LOW) {
+        return;
+    }
+
+    let Some(mut entry) = ring.reserve::<PacketSample>(0) else {
+        return;
+    };
+    let sample = entry.as_mut_ptr();
+
+    let mut captured = packet_len as u32;
+    if captured > sampling::CAPTURE_BYTES as u32 {
+        captured = sampling::CAPTURE_BYTES as u32;
+    }
+
+    unsafe {
+        (*sample).timestamp_ns = bpf_ktime_get_ns();
+        (*sample).packet_len = packet_len as u32;
+        (*sample).captured_len = 0;
+        (*sample).rate = rate;
+        (*sample)._pad = 0;
+
+        let data = core::ptr::addr_of_mut!((*sample).data) as *mut c_void;
+        if captured > 0 && bpf_xdp_load_bytes(ctx, 0, data, captured) == 0 {
+            (*sample).captured_len = captured;
+        }
+    }
+
+    entry.submit(0);
+}
+
+/// Send 1 in `rate` packets to the sample perf event array
+///
+/// Used by kernels without ring buffers. The kernel copies the leading bytes
+/// of the packet after the header, so nothing is staged on the stack.
+#[cfg(not(feature = "ringbuf"))]
+#[inline(always)]
+pub fn sample_packet(
+    ctx: *mut xdp_md,
+    packet_len: u64,
+    config: &PerCpuArray<SampleConfig>,
+    samples: &sampling::SampleMap,
+    budget_config: &PerCpuArray<EventBudgetConfig>,
+    budget: &PerCpuArray<EventBudget>,
+) {
+    let Some(rate) = sample_rate(config) else {
+        return;
+    };
+    if !event_admit(budget_config, budget, event_budget::PRIORITY_LOW) {
+        return;
+    }
+
+    let mut captured = packet_len as u32;
+    if captured > sampling::CAPTURE_BYTES as u32 {
+        captured = sampling::CAPTURE_BYTES as u32;
+    }
+
+    let header = SampleHeader {
+        timestamp_ns: unsafe { bpf_ktime_get_ns() },
+        packet_len: packet_len as u32,
+        captured_len: captured,
+        rate,
+        _pad: 0,
+    };
+
+    // The upper flag bits carry the number of packet bytes to append
+    samples.output(&XdpContext::new(ctx), &header, captured);
+}
+
+// ============================================================================
+// Processing Latency
+// ============================================================================
+
+/// Per-packet processing time. 1 in `rate` packets is timed with
+/// `bpf_ktime_get_ns` from entry to verdict and counted in a log2 histogram,
+/// one per protection level, in each program's `LATENCY` map. Off unless
+/// userspace sets a rate, so the measurement itself costs nothing by default.
+pub mod latency {
+    /// Histogram buckets per protection level
+    pub const BUCKETS: u32 = 16;
+
+    /// Bucket `i` counts packets that took less than `2^(MIN_SHIFT + i)` ns;
+    /// the last bucket also counts everything slower
+    pub const MIN_SHIFT: u32 = 6;
+
+    /// Protection levels with their own histogram (0 = program without one)
+    pub const LEVELS: u32 = 5;
+
+    /// Entries of the `LATENCY` map, indexed `level * BUCKETS + bucket`
+    pub const MAX_ENTRIES: u32 = BUCKETS * LEVELS;
+}
+
+/// Latency measurement configuration, one per program, written by userspace
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct LatencyConfig {
+    /// Time 1 in `rate` packets (0 disables measurement)
+    pub rate: u32,
+    pub _pad: u32,
+}
+
+/// One bucket of the `LATENCY` histogram
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct LatencyBucket {
+    pub count: u64,
+    pub sum_ns: u64,
+}
+
+/// Start time of this packet if it should be timed, 0 otherwise
+#[inline(always)]
+pub fn latency_start(config: &PerCpuArray<LatencyConfig>) -> u64 {
+    let rate = match unsafe { config.get(0) } {
+        Some(config) if config.rate > 0 => config.rate,
+        _ => return 0,
+    };
+
+    if rate > 1 && unsafe { bpf_get_prandom_u32() } % rate != 0 {
+        return 0;
+    }
+    unsafe { bpf_ktime_get_ns() }
+}
+
+/// Time elapsed since `latency_start`, if the packet is being timed
+#[inline(always)]
+pub fn latency_elapsed(start: u64) -> Option<u64> {
+    if start == 0 {
+        return None;
+    }
+    Some(unsafe { bpf_ktime_get_ns() }.saturating_sub(start))
+}
+
+/// Count a timed packet in the histogram of `level`
+#[inline(always)]
+pub fn record_latency(histogram: &PerCpuArray<LatencyBucket>, elapsed_ns: u64, level: u32) {
+    let level = if level < latency::LEVELS {
+        level
+    } else {
+        latency::LEVELS - 1
+    };
+    let bits = 64 - elapsed_ns.leading_zeros();
+    let mut bucket = bits.saturating_sub(latency::MIN_SHIFT);
+    if bucket >= latency::BUCKETS {
+        bucket = latency::BUCKETS - 1;
+    }
+
+    if let Some(entry) = unsafe { histogram.get_ptr_mut(level * latency::BUCKETS + bucket) } {
+        unsafe {
+            (*entry).count += 1;
+            (*entry).sum_ns += elapsed_ns;
+        }
+    }
+}
+
+// ============================================================================
+// Per-CPU Source Counters
+// ============================================================================
+
+/// Per-source counters and token leases kept per CPU. Writing a shared
+/// per-IP entry on every packet bounces its cache line between every CPU
+/// that sees the source; counting in `LruPerCpuHashMap`s keeps those writes
+/// local, and userspace sums the CPUs when it polls. Token buckets stay
+/// shared so limits hold across CPUs, but each CPU takes `lease::TOKENS` at
+/// once and spends them locally, writing the bucket once per lease instead
+/// of once per packet. A source can overshoot its bucket by at most
+/// `lease::TOKENS` per CPU.
+pub mod lease {
+    /// Tokens a CPU takes from a shared bucket at once
+    pub const TOKENS: u64 = 8;
+
+    /// Age after which the unspent tokens of a lease are given up
+    pub const TTL_NS: u64 = 10_000_000;
+
+    /// Entries of the `*_LEASES_*` and `SOURCE_COUNTERS_*` maps; values are
+    /// per CPU, so these are kept well below the shared maps
+    pub const MAX_SOURCES: u32 = 131_072;
+}
+
+/// Traffic of one source on one CPU
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SourceCounters {
+    pub packets: u64,
+    pub bytes: u64,
+    /// Packets refused by the rate limit
+    pub dropped: u64,
+    pub last_seen_ns: u64,
+}
+
+/// Tokens a CPU took from a shared bucket
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct TokenLease {
+    pub tokens: u64,
+    pub expires_ns: u64,
+}
+
+/// Count a packet of `key` on this CPU
+#[inline(always)]
+pub fn count_source<K>(
+    counters: &LruPerCpuHashMap<K, SourceCounters>,
+    key: &K,
+    bytes: u64,
+    passed: bool,
+    now: u64,
+) {
+    if let Some(entry) = unsafe { counters.get_ptr_mut(key) } {
+        unsafe {
+            (*entry).packets += 1;
+            (*entry).bytes += bytes;
+            (*entry).dropped += !passed as u64;
+            (*entry).last_seen_ns = now;
+        }
+    } else {
+        let entry = SourceCounters {
+            packets: 1,
+            bytes,
+            dropped: !passed as u64,
+            last_seen_ns: now,
+        };
+        let _ = counters.insert(key, &entry, 0);
+    }
+}
+
+/// Spend a token this CPU leased for `key`, if it has one left
+#[inline(always)]
+pub fn spend_lease<K>(leases: &LruPerCpuHashMap<K, TokenLease>, key: &K, now: u64) -> bool {
+    if let Some(lease) = unsafe { leases.get_ptr_mut(key) } {
+        unsafe {
+            if (*lease).tokens > 0 && (*lease).expires_ns > now {
+                (*lease).tokens -= 1;
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Take up to `lease::TOKENS` from a shared bucket holding `available`
+/// tokens, keeping all but the one spent on this packet for this CPU
+///
+/// Returns the tokens taken; 0 means the packet is over the limit.
+#[inline(always)]
+pub fn take_lease<K>(
+    leases: &LruPerCpuHashMap<K, TokenLease>,
+    key: &K,
+    available: u64,
+    now: u64,
+) -> u64 {
+    let taken = if available < lease::TOKENS {
+        available
+    } else {
+        lease::TOKENS
+    };
+    if taken > 1 {
+        let lease = TokenLease {
+            tokens: taken - 1,
+            expires_ns: now + lease::TTL_NS,
+        };
+        let _ = leases.insert(key, &lease, 0);
+    }
+    taken
+}
+
+// ============================================================================
+// Multi-Buffer Frames
 // ============================================================================
 
-/// IPv4 address key
-pub type Ipv4Key = u32;
+pub mod frags {
+    /// Payload bytes copied out of a multi-buffer frame for inspection
+    pub const SCRATCH_BYTES: usize = 512;
+}
 
-/// IPv6 address key
-pub type Ipv6Key = [u8; 16];
+/// Per-CPU copy of the inspected payload of a multi-buffer frame
+#[repr(C)]
+pub struct PayloadScratch {
+    pub data: [u8; frags::SCRATCH_BYTES],
+}
 
-/// Connection 4-tuple key (hashed)
-pub type ConnectionKey = u64;
+/// Length of the whole frame, including fragments past `data_end`
+///
+/// The programs are loaded as `xdp.frags`, so on jumbo or multi-buffer frames
+/// `data_end - data` only covers the first buffer.
+#[cfg(feature = "frags")]
+#[inline(always)]
+pub fn frame_len(ctx: *mut xdp_md) -> usize {
+    unsafe { bpf_xdp_get_buff_len(ctx) as usize }
+}
 
-/// Port key
-pub type PortKey = u16;
+/// Length of the frame; without `xdp.frags` the first buffer is the frame
+#[cfg(not(feature = "frags"))]
+#[inline(always)]
+pub fn frame_len(ctx: *mut xdp_md) -> usize {
+    unsafe { ((*ctx).data_end - (*ctx).data) as usize }
+}
 
-// ============================================================================
-// Common Configuration Structures
-// ============================================================================
+/// Borrow the L4 payload starting at `offset` for inspection
+///
+/// Returns the inspectable bytes and the full payload length. The payload is
+/// read in place when the first buffer holds all of it (or at least
+/// `frags::SCRATCH_BYTES`); otherwise the leading bytes are copied into
+/// `scratch` with `bpf_xdp_load_bytes`. Returns `None` when there is no payload.
+#[inline(always)]
+pub fn payload_view<'a>(
+    ctx: *mut xdp_md,
+    data: usize,
+    data_end: usize,
+    offset: usize,
+    scratch: &PerCpuArray<PayloadScratch>,
+) -> Option<(&'a [u8], usize)> {
+    if offset < data {
+        return None;
+    }
+    let start = offset - data;
+    let total = frame_len(ctx);
+    if start >= total {
+        return None;
+    }
+    let payload_len = total - start;
+    let linear_len = data_end.saturating_sub(offset);
 
-/// Base configuration shared by all programs
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub struct BaseConfig {
-    /// Program enabled flag
-    pub enabled: u32,
-    /// Protection level (1-4)
-    pub protection_level: u32,
-    /// Rate limit window in nanoseconds
-    pub rate_limit_window_ns: u64,
-    /// Block duration in nanoseconds
-    pub block_duration_ns: u64,
+    if linear_len >= payload_len || linear_len >= frags::SCRATCH_BYTES {
+        let payload = unsafe { core::slice::from_raw_parts(offset as *const u8, linear_len) };
+        return Some((payload, payload_len));
+    }
+
+    copy_payload(ctx, start, payload_len, scratch)
 }
 
-impl Default for BaseConfig {
-    fn default() -> Self {
-        Self {
-            enabled: 1,
-            protection_level: 2,
-            rate_limit_window_ns: 1_000_000_000, // 1 second
-            block_duration_ns: 60_000_000_000,   // 60 seconds
+/// Copy the leading payload bytes of a multi-buffer frame into `scratch`
+#[cfg(feature = "frags")]
+#[inline(always)]
+fn copy_payload<'a>(
+    ctx: *mut xdp_md,
+    start: usize,
+    payload_len: usize,
+    scratch: &PerCpuArray<PayloadScratch>,
+) -> Option<(&'a [u8], usize)> {
+    let buf = unsafe { scratch.get_ptr_mut(0) }?;
+    let mut copied = payload_len;
+    if copied > frags::SCRATCH_BYTES {
+        copied = frags::SCRATCH_BYTES;
+    }
+
+    unsafe {
+        let dst = core::ptr::addr_of_mut!((*buf).data) as *mut c_void;
+        if bpf_xdp_load_bytes(ctx, start as u32, dst, copied as u32) != 0 {
+            return None;
         }
+        Some((
+            core::slice::from_raw_parts(dst as *const u8, copied),
+            payload_len,
+        ))
     }
 }
 
-// ============================================================================
-// Common Statistics Structures
-// ============================================================================
-
-/// Base statistics shared by all programs
-#[repr(C)]
-#[derive(Clone, Copy, Default)]
-pub struct BaseStats {
-    /// Total packets processed
-    pub total_packets: u64,
-    /// Packets passed through
-    pub passed_packets: u64,
-    /// Packets dropped
-    pub dropped_packets: u64,
-    /// Total bytes processed
-    pub total_bytes: u64,
+/// Without `xdp.frags` the first buffer always holds the whole frame
+#[cfg(not(feature = "frags"))]
+#[inline(always)]
+fn copy_payload<'a>(
+    _ctx: *mut xdp_md,
+    _start: usize,
+    _payload_len: usize,
+    _scratch: &PerCpuArray<PayloadScratch>,
+) -> Option<(&'a [u8], usize)> {
+    None
 }
 
 // ============================================================================
@@ -183,6 +2560,10 @@ pub mod protocol {
         pub const P_IPV6: u16 = 0x86DD;
         pub const P_ARP: u16 = 0x0806;
         pub const P_8021Q: u16 = 0x8100;
+        pub const P_8021AD: u16 = 0x88A8;
+
+        /// VLAN tags skipped before giving up (802.1Q, or QinQ outer + inner)
+        pub const MAX_VLAN_TAGS: usize = 2;
     }
 
     /// IP protocol numbers
@@ -218,6 +2599,21 @@ pub mod protocol {
         pub const CWR: u16 = 0x0080;
     }
 
+    /// ICMPv4 types and codes
+    pub mod icmp {
+        pub const ECHO_REPLY: u8 = 0;
+        pub const DEST_UNREACH: u8 = 3;
+        pub const ECHO_REQUEST: u8 = 8;
+        pub const TIME_EXCEEDED: u8 = 11;
+
+        /// `DEST_UNREACH` codes
+        pub const CODE_PORT_UNREACH: u8 = 3;
+        pub const CODE_FRAG_NEEDED: u8 = 4;
+
+        /// Bytes of the offending datagram quoted in an error (IP header + 8)
+        pub const QUOTE_BYTES: usize = 28;
+    }
+
     /// IPv4 fragmentation flags and masks
     ///
     /// IP fragmentation can be used to evade packet inspection:
@@ -281,6 +2677,283 @@ pub mod protocol {
         /// Fragment offset mask (13 bits, in 8-byte units)
         pub const OFFSET_MASK: u16 = 0xFFF8;
     }
+
+    /// IPv6 extension header next header values
+    pub mod ipv6_ext {
+        pub const NEXTHDR_HOP: u8 = 0;
+        pub const NEXTHDR_ROUTING: u8 = 43;
+        pub const NEXTHDR_FRAGMENT: u8 = 44;
+        pub const NEXTHDR_AUTH: u8 = 51;
+        pub const NEXTHDR_DEST: u8 = 60;
+
+        /// Maximum extension headers walked before giving up (verifier bound)
+        pub const MAX_EXT_HEADERS: usize = 4;
+    }
+}
+
+// ============================================================================
+// Network Header Structures
+// ============================================================================
+
+#[repr(C)]
+pub struct EthHdr {
+    pub h_dest: [u8; 6],
+    pub h_source: [u8; 6],
+    pub h_proto: u16,
+}
+
+/// 802.1Q / 802.1ad VLAN tag (follows the outer EtherType)
+#[repr(C)]
+pub struct VlanHdr {
+    pub h_vlan_tci: u16,
+    pub h_vlan_encapsulated_proto: u16,
+}
+
+#[repr(C)]
+pub struct Ipv4Hdr {
+    pub version_ihl: u8,
+    pub tos: u8,
+    pub tot_len: u16,
+    pub id: u16,
+    pub frag_off: u16,
+    pub ttl: u8,
+    pub protocol: u8,
+    pub check: u16,
+    pub saddr: u32,
+    pub daddr: u32,
+}
+
+#[repr(C)]
+pub struct Ipv6Hdr {
+    pub version_tc_flow: u32,
+    pub payload_len: u16,
+    pub nexthdr: u8,
+    pub hop_limit: u8,
+    pub saddr: [u8; 16],
+    pub daddr: [u8; 16],
+}
+
+#[repr(C)]
+pub struct TcpHdr {
+    pub source: u16,
+    pub dest: u16,
+    pub seq: u32,
+    pub ack_seq: u32,
+    pub doff_flags: u16,
+    pub window: u16,
+    pub check: u16,
+    pub urg_ptr: u16,
+}
+
+#[repr(C)]
+pub struct UdpHdr {
+    pub source: u16,
+    pub dest: u16,
+    pub len: u16,
+    pub check: u16,
+}
+
+#[repr(C)]
+pub struct IcmpHdr {
+    pub icmp_type: u8,
+    pub code: u8,
+    pub checksum: u16,
+    /// Echo identifier/sequence, next-hop MTU, or unused, depending on type
+    pub rest: u32,
+}
+
+// ============================================================================
+// Header Parsing
+// ============================================================================
+//
+// Each parser bounds-checks the fixed header against `data_end` before
+// dereferencing it, which is the shape the verifier needs to accept the
+// access. Offsets are absolute packet addresses (`ctx.data()` based).
+// Parsers return the offset of the next header; variable lengths (IHL, TCP
+// data offset) are taken from the packet as-is and not validated here.
+
+/// Bounds-checked view of a `T` at `offset`
+#[inline(always)]
+fn header_at<'a, T>(offset: usize, data_end: usize) -> Option<&'a T> {
+    if offset + mem::size_of::<T>() > data_end {
+        return None;
+    }
+    Some(unsafe { &*(offset as *const T) })
+}
+
+/// Bounds-checked mutable view of a `T` at `offset`, for rewriting headers
+/// in place before `XDP_TX`
+#[inline(always)]
+pub fn header_at_mut<'a, T>(offset: usize, data_end: usize) -> Option<&'a mut T> {
+    if offset + mem::size_of::<T>() > data_end {
+        return None;
+    }
+    Some(unsafe { &mut *(offset as *mut T) })
+}
+
+/// Parse the Ethernet header
+///
+/// Skips up to [`protocol::eth::MAX_VLAN_TAGS`] 802.1Q/802.1ad tags so tagged
+/// frames are filtered like untagged ones. Returns the inner EtherType (host
+/// byte order) and the offset of the L3 header. Frames with more tags return
+/// the VLAN EtherType, which callers pass as non-IP.
+#[inline(always)]
+pub fn parse_eth(data: usize, data_end: usize) -> Option<(u16, usize)> {
+    use protocol::eth::*;
+
+    let eth = header_at::<EthHdr>(data, data_end)?;
+    let mut proto = u16::from_be(eth.h_proto);
+    let mut offset = data + mem::size_of::<EthHdr>();
+
+    for _ in 0..MAX_VLAN_TAGS {
+        if proto != P_8021Q && proto != P_8021AD {
+            break;
+        }
+        let vlan = header_at::<VlanHdr>(offset, data_end)?;
+        proto = u16::from_be(vlan.h_vlan_encapsulated_proto);
+        offset += mem::size_of::<VlanHdr>();
+    }
+
+    Some((proto, offset))
+}
+
+/// Parse the IPv4 header
+///
+/// Returns the header and the offset of the transport header (from IHL).
+#[inline(always)]
+pub fn parse_ipv4<'a>(offset: usize, data_end: usize) -> Option<(&'a Ipv4Hdr, usize)> {
+    let ip = header_at::<Ipv4Hdr>(offset, data_end)?;
+    let ihl = (ip.version_ihl & 0x0f) as usize * 4;
+    Some((ip, offset + ihl))
+}
+
+/// Parse the fixed IPv6 header
+///
+/// Returns the header and the offset just past it. Use
+/// [`parse_ipv6_with_ext`] to reach the transport header.
+#[inline(always)]
+pub fn parse_ipv6<'a>(offset: usize, data_end: usize) -> Option<(&'a Ipv6Hdr, usize)> {
+    let ip6 = header_at::<Ipv6Hdr>(offset, data_end)?;
+    Some((ip6, offset + mem::size_of::<Ipv6Hdr>()))
+}
+
+/// Parse the IPv6 header and walk its extension header chain
+///
+/// Returns `None` if the fixed header is truncated or the chain cannot be
+/// walked (see [`walk_ipv6_ext_headers`]).
+#[inline(always)]
+pub fn parse_ipv6_with_ext<'a>(
+    offset: usize,
+    data_end: usize,
+) -> Option<(&'a Ipv6Hdr, Ipv6Payload)> {
+    let (ip6, ext_start) = parse_ipv6(offset, data_end)?;
+    let payload = walk_ipv6_ext_headers(ip6.nexthdr, ext_start, data_end)?;
+    Some((ip6, payload))
+}
+
+/// Parse the TCP header
+///
+/// Returns the header and the offset of the payload (from the data offset).
+#[inline(always)]
+pub fn parse_tcp<'a>(offset: usize, data_end: usize) -> Option<(&'a TcpHdr, usize)> {
+    let tcp = header_at::<TcpHdr>(offset, data_end)?;
+    let doff = ((u16::from_be(tcp.doff_flags) >> 12) & 0x0f) as usize * 4;
+    Some((tcp, offset + doff))
+}
+
+/// Parse the UDP header
+///
+/// Returns the header and the offset of the payload.
+#[inline(always)]
+pub fn parse_udp<'a>(offset: usize, data_end: usize) -> Option<(&'a UdpHdr, usize)> {
+    let udp = header_at::<UdpHdr>(offset, data_end)?;
+    Some((udp, offset + mem::size_of::<UdpHdr>()))
+}
+
+// ============================================================================
+// IPv6 Extension Headers
+// ============================================================================
+
+/// IPv6 Fragment extension header
+#[repr(C)]
+pub struct Ipv6FragHdr {
+    pub nexthdr: u8,
+    pub reserved: u8,
+    /// Fragment offset (13 bits) + Reserved (2 bits) + M flag (1 bit)
+    pub frag_off_m: u16,
+    pub identification: u32,
+}
+
+/// Upper-layer header located by [`walk_ipv6_ext_headers`]
+#[derive(Clone, Copy)]
+pub struct Ipv6Payload {
+    /// Protocol of the first non-extension header
+    pub protocol: u8,
+    /// Address of the upper-layer header
+    pub offset: usize,
+    /// A Fragment header was present with MF set or a non-zero offset
+    pub is_fragment: bool,
+    /// Fragment offset is zero, so the upper-layer header is present
+    pub is_first_fragment: bool,
+}
+
+/// Walk the IPv6 extension header chain
+///
+/// `nexthdr` and `offset` describe the header following the fixed IPv6
+/// header. Hop-by-Hop, Routing, Destination Options, Authentication and
+/// Fragment headers are skipped. Returns `None` if a header is truncated or
+/// the chain is longer than [`protocol::ipv6_ext::MAX_EXT_HEADERS`]; callers
+/// pass such packets since the transport header cannot be inspected.
+#[inline(always)]
+pub fn walk_ipv6_ext_headers(nexthdr: u8, offset: usize, data_end: usize) -> Option<Ipv6Payload> {
+    use protocol::ipv6_ext::*;
+
+    let mut payload = Ipv6Payload {
+        protocol: nexthdr,
+        offset,
+        is_fragment: false,
+        is_first_fragment: true,
+    };
+
+    for _ in 0..MAX_EXT_HEADERS {
+        match payload.protocol {
+            NEXTHDR_FRAGMENT => {
+                let frag = header_at::<Ipv6FragHdr>(payload.offset, data_end)?;
+                let frag_off_m = u16::from_be(frag.frag_off_m);
+                let frag_offset = frag_off_m & protocol::ipv6_frag::OFFSET_MASK;
+                let more_fragments = (frag_off_m & protocol::ipv6_frag::MF) != 0;
+
+                payload.is_fragment = more_fragments || frag_offset != 0;
+                payload.is_first_fragment = frag_offset == 0;
+                payload.protocol = frag.nexthdr;
+                payload.offset += mem::size_of::<Ipv6FragHdr>();
+            }
+            NEXTHDR_HOP | NEXTHDR_ROUTING | NEXTHDR_DEST | NEXTHDR_AUTH => {
+                // Common format: next header, length, data
+                if payload.offset + 2 > data_end {
+                    return None;
+                }
+                let ext_next = unsafe { *(payload.offset as *const u8) };
+                let ext_len = unsafe { *((payload.offset + 1) as *const u8) } as usize;
+                // AH length is in 4-byte units minus 2; the others are in
+                // 8-byte units not counting the first 8 bytes
+                let total_len = if payload.protocol == NEXTHDR_AUTH {
+                    (ext_len + 2) * 4
+                } else {
+                    (ext_len + 1) * 8
+                };
+
+                payload.protocol = ext_next;
+                payload.offset += total_len;
+            }
+            _ => return Some(payload),
+        }
+    }
+
+    match payload.protocol {
+        NEXTHDR_FRAGMENT | NEXTHDR_HOP | NEXTHDR_ROUTING | NEXTHDR_DEST | NEXTHDR_AUTH => None,
+        _ => Some(payload),
+    }
 }
 
 // ============================================================================
@@ -351,6 +3024,185 @@ pub fn analyze_ipv4_fragment(
     FragmentAction::FirstFragment
 }
 
+// ============================================================================
+// Checksums and Packet Rewriting
+// ============================================================================
+//
+// Helpers for programs that answer with XDP_TX (SYN-ACK, status pong, QUIC
+// Retry). Checksums are one's complement sums (RFC 1071), which come out the
+// same in either byte order, so header fields are passed as they sit in the
+// packet (network order) and results are stored back without conversion.
+// Incremental updates follow RFC 1624.
+
+pub mod csum {
+    /// Bytes summed by `csum_add_bytes` before it stops (bounds the loop)
+    pub const MAX_BYTES: usize = 1500;
+}
+
+/// Fold a 32-bit one's complement sum to 16 bits and complement it
+#[inline(always)]
+pub fn csum_fold(sum: u32) -> u16 {
+    let sum = (sum & 0xffff) + (sum >> 16);
+    let sum = (sum & 0xffff) + (sum >> 16);
+    !(sum as u16)
+}
+
+/// Add `bytes` to a running (unfolded) one's complement sum
+///
+/// An odd trailing byte is padded with zero. At most `csum::MAX_BYTES` are
+/// summed.
+#[inline(always)]
+pub fn csum_add_bytes(sum: u32, bytes: &[u8]) -> u32 {
+    let mut sum = sum;
+    let len = bytes.len();
+    let mut i = 0;
+    while i + 1 < len && i < csum::MAX_BYTES {
+        sum += u16::from_ne_bytes([bytes[i], bytes[i + 1]]) as u32;
+        i += 2;
+    }
+    if i < len && i < csum::MAX_BYTES {
+        sum += u16::from_ne_bytes([bytes[i], 0]) as u32;
+    }
+    sum
+}
+
+/// Update a checksum after a 16-bit field changed from `old` to `new`
+#[inline(always)]
+pub fn csum_replace2(check: u16, old: u16, new: u16) -> u16 {
+    csum_fold(!check as u32 + !old as u32 + new as u32)
+}
+
+/// Update a checksum after a 32-bit field changed from `old` to `new`
+#[inline(always)]
+pub fn csum_replace4(check: u16, old: u32, new: u32) -> u16 {
+    let old_hi = (old >> 16) as u16;
+    let old_lo = old as u16;
+    csum_fold(
+        !check as u32
+            + !old_hi as u32
+            + !old_lo as u32
+            + (new >> 16) as u32
+            + (new & 0xffff) as u32,
+    )
+}
+
+/// Checksum of a 20-byte IPv4 header, ignoring the current `check` value
+///
+/// Options are not covered; responses are built with option-less headers.
+#[inline(always)]
+pub fn ipv4_csum(ip: &Ipv4Hdr) -> u16 {
+    let sum = u16::from_ne_bytes([ip.version_ihl, ip.tos]) as u32
+        + ip.tot_len as u32
+        + ip.id as u32
+        + ip.frag_off as u32
+        + u16::from_ne_bytes([ip.ttl, ip.protocol]) as u32
+        + (ip.saddr >> 16)
+        + (ip.saddr & 0xffff)
+        + (ip.daddr >> 16)
+        + (ip.daddr & 0xffff);
+    csum_fold(sum)
+}
+
+/// Unfolded sum of the IPv4 pseudo-header for a TCP/UDP checksum
+///
+/// `len` is the L4 length (header + payload) in host order.
+#[inline(always)]
+pub fn ipv4_pseudo_csum(saddr: u32, daddr: u32, protocol: u8, len: u16) -> u32 {
+    (saddr >> 16)
+        + (saddr & 0xffff)
+        + (daddr >> 16)
+        + (daddr & 0xffff)
+        + (protocol as u16).to_be() as u32
+        + len.to_be() as u32
+}
+
+/// Unfolded sum of the IPv6 pseudo-header for a TCP/UDP/ICMPv6 checksum
+///
+/// `len` is the upper-layer length in host order.
+#[inline(always)]
+pub fn ipv6_pseudo_csum(saddr: &[u8; 16], daddr: &[u8; 16], nexthdr: u8, len: u32) -> u32 {
+    let sum = csum_add_bytes(0, saddr);
+    let sum = csum_add_bytes(sum, daddr);
+    sum + ((len >> 16) as u16).to_be() as u32
+        + (len as u16).to_be() as u32
+        + (nexthdr as u16).to_be() as u32
+}
+
+/// Set the IPv4 TTL, updating the header checksum
+#[inline(always)]
+pub fn ipv4_set_ttl(ip: &mut Ipv4Hdr, ttl: u8) {
+    let old = u16::from_ne_bytes([ip.ttl, ip.protocol]);
+    let new = u16::from_ne_bytes([ttl, ip.protocol]);
+    ip.ttl = ttl;
+    ip.check = csum_replace2(ip.check, old, new);
+}
+
+/// Swap Ethernet source and destination to send a frame back out
+#[inline(always)]
+pub fn swap_eth_addrs(eth: &mut EthHdr) {
+    mem::swap(&mut eth.h_dest, &mut eth.h_source);
+}
+
+/// Swap IPv4 source and destination
+///
+/// Swapping keeps the same words in the sum, so neither the IP nor the
+/// pseudo-header part of the L4 checksum changes.
+#[inline(always)]
+pub fn swap_ipv4_addrs(ip: &mut Ipv4Hdr) {
+    mem::swap(&mut ip.saddr, &mut ip.daddr);
+}
+
+/// Swap IPv6 source and destination (L4 checksum unchanged)
+#[inline(always)]
+pub fn swap_ipv6_addrs(ip6: &mut Ipv6Hdr) {
+    mem::swap(&mut ip6.saddr, &mut ip6.daddr);
+}
+
+/// Swap TCP source and destination ports (checksum unchanged)
+#[inline(always)]
+pub fn swap_tcp_ports(tcp: &mut TcpHdr) {
+    mem::swap(&mut tcp.source, &mut tcp.dest);
+}
+
+/// Swap UDP source and destination ports (checksum unchanged)
+#[inline(always)]
+pub fn swap_udp_ports(udp: &mut UdpHdr) {
+    mem::swap(&mut udp.source, &mut udp.dest);
+}
+
+/// Checksum of an ICMPv4 message: `icmp` (ignoring its `checksum`) + `body`
+#[inline(always)]
+pub fn icmp_csum(icmp: &IcmpHdr, body: &[u8]) -> u16 {
+    let sum = u16::from_ne_bytes([icmp.icmp_type, icmp.code]) as u32
+        + (icmp.rest >> 16)
+        + (icmp.rest & 0xffff);
+    csum_fold(csum_add_bytes(sum, body))
+}
+
+/// Fill in an ICMPv4 header and its checksum over `body`
+///
+/// `rest` is stored as-is (network order). For errors, `body` is the quoted
+/// datagram (`protocol::icmp::QUOTE_BYTES`).
+#[inline(always)]
+pub fn fill_icmp(icmp: &mut IcmpHdr, icmp_type: u8, code: u8, rest: u32, body: &[u8]) {
+    icmp.icmp_type = icmp_type;
+    icmp.code = code;
+    icmp.rest = rest;
+    icmp.checksum = icmp_csum(icmp, body);
+}
+
+/// Turn an ICMPv4 echo request into the matching reply in place
+///
+/// Identifier, sequence and data are echoed unchanged, so only the type word
+/// of the checksum is updated.
+#[inline(always)]
+pub fn icmp_echo_to_reply(icmp: &mut IcmpHdr) {
+    let old = u16::from_ne_bytes([icmp.icmp_type, icmp.code]);
+    let new = u16::from_ne_bytes([protocol::icmp::ECHO_REPLY, icmp.code]);
+    icmp.icmp_type = protocol::icmp::ECHO_REPLY;
+    icmp.checksum = csum_replace2(icmp.checksum, old, new);
+}
+
 // ============================================================================
 // Utility Functions
 // ============================================================================
@@ -391,6 +3243,22 @@ pub fn hash_connection_symmetric(ip1: u32, ip2: u32, port1: u16, port2: u16) ->
     hash_connection(src_ip, dst_ip, src_port, dst_port)
 }
 
+/// Fold a full IPv6 address into 32 bits (FNV-1a)
+///
+/// Used where a compact address digest is needed (connection keys, SYN
+/// cookies). Per-IP state maps key on the full `[u8; 16]` address instead.
+#[inline(always)]
+pub fn hash_ipv6_addr(addr: &[u8; 16]) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    let mut i = 0;
+    while i < 16 {
+        hash ^= addr[i] as u32;
+        hash = hash.wrapping_mul(0x01000193);
+        i += 1;
+    }
+    hash
+}
+
 // ============================================================================
 // Map Names (for userspace coordination)
 // ============================================================================
@@ -403,6 +3271,20 @@ pub mod map_names {
     pub const RATE_LIMITS_V6: &str = "RATE_LIMITS_V6";
     pub const FILTER_CONFIG: &str = "CONFIG";
     pub const FILTER_STATS: &str = "STATS";
+    pub const TENANT_DESTINATIONS_V4: &str = "TENANT_DESTINATIONS_V4";
+    pub const TENANT_DESTINATIONS_V6: &str = "TENANT_DESTINATIONS_V6";
+    pub const TENANT_CONFIG: &str = "TENANT_CONFIG";
+    pub const TENANT_BLOCKED_V4: &str = "TENANT_BLOCKED_V4";
+    pub const TENANT_BLOCKED_V6: &str = "TENANT_BLOCKED_V6";
+    pub const TENANT_RATE_LIMITS_V4: &str = "TENANT_RATE_LIMITS_V4";
+    pub const TENANT_RATE_LIMITS_V6: &str = "TENANT_RATE_LIMITS_V6";
+    pub const BACKEND_MODES_V4: &str = "BACKEND_MODES_V4";
+    pub const BACKEND_MODES_V6: &str = "BACKEND_MODES_V6";
+    pub const RATE_LEASES_V4: &str = "RATE_LEASES_V4";
+    pub const RATE_LEASES_V6: &str = "RATE_LEASES_V6";
+    pub const DST_TRAFFIC: &str = "DST_TRAFFIC";
+    pub const MARKING_POLICIES: &str = "MARKING_POLICIES";
+    pub const MARKING_COUNTERS: &str = "MARKING_COUNTERS";
 
     // xdp_ratelimit maps
     pub const TOKEN_BUCKETS_V4: &str = "TOKEN_BUCKETS_V4";
@@ -410,6 +3292,9 @@ pub mod map_names {
     pub const SUBNET_BUCKETS: &str = "SUBNET_BUCKETS";
     pub const RATELIMIT_CONFIG: &str = "RATELIMIT_CONFIG";
     pub const RATELIMIT_STATS: &str = "RATELIMIT_STATS";
+    pub const TOKEN_LEASES_V4: &str = "TOKEN_LEASES_V4";
+    pub const TOKEN_LEASES_V6: &str = "TOKEN_LEASES_V6";
+    pub const SUBNET_LEASES: &str = "SUBNET_LEASES";
 
     // xdp_minecraft maps
     pub const MC_JAVA_CONNECTIONS: &str = "MC_JAVA_CONNECTIONS";
@@ -425,6 +3310,7 @@ pub mod map_names {
     pub const BLOCKED_PATHS: &str = "BLOCKED_PATHS";
     pub const BLOCKED_USER_AGENTS: &str = "BLOCKED_USER_AGENTS";
     pub const HTTP_WHITELIST: &str = "HTTP_WHITELIST";
+    pub const HTTP_WHITELIST_V6: &str = "HTTP_WHITELIST_V6";
     pub const HTTP_CONFIG: &str = "HTTP_CONFIG";
     pub const HTTP_STATS: &str = "HTTP_STATS";
 
@@ -436,6 +3322,10 @@ pub mod map_names {
     pub const QUIC_WHITELIST: &str = "QUIC_WHITELIST";
     pub const QUIC_CONFIG: &str = "QUIC_CONFIG";
     pub const QUIC_STATS: &str = "QUIC_STATS";
+    pub const QUIC_AMPLIFICATION: &str = "QUIC_AMPLIFICATION";
+    pub const QUIC_REJECT_0RTT: &str = "QUIC_REJECT_0RTT";
+    pub const QUIC_CID_LIMIT: &str = "QUIC_CID_LIMIT";
+    pub const QUIC_CID_RATES: &str = "QUIC_CID_RATES";
 
     // xdp_udp maps
     pub const UDP_IP_STATE_V4: &str = "UDP_IP_STATE_V4";
@@ -456,6 +3346,57 @@ pub mod map_names {
     pub const GLOBAL_SYN_STATE: &str = "GLOBAL_SYN_STATE";
     pub const TCP_PROTECTED_PORTS: &str = "TCP_PROTECTED_PORTS";
     pub const TCP_WHITELIST: &str = "TCP_WHITELIST";
+    pub const TCP_WHITELIST_V6: &str = "TCP_WHITELIST_V6";
+    pub const INCOMPLETE_HANDSHAKES_V4: &str = "INCOMPLETE_HANDSHAKES_V4";
+    pub const INCOMPLETE_HANDSHAKES_V6: &str = "INCOMPLETE_HANDSHAKES_V6";
     pub const TCP_CONFIG: &str = "TCP_CONFIG";
     pub const TCP_STATS: &str = "TCP_STATS";
+
+    // DNS query budget maps (xdp_tcp and xdp_http)
+    pub const DNS_BUDGET: &str = "DNS_BUDGET";
+    pub const DNS_RATES: &str = "DNS_RATES";
+
+    // Drop breakdown maps (present in every program)
+    pub const DROPS_BY_DST_PORT: &str = "DROPS_BY_DST_PORT";
+    pub const DROPS_BY_REASON: &str = "DROPS_BY_REASON";
+    pub const DROP_CONTEXT: &str = "DROP_CONTEXT";
+    pub const DROP_EVENTS: &str = "DROP_EVENTS";
+
+    // Event budget maps (present in every program)
+    pub const EVENT_BUDGET_CONFIG: &str = "EVENT_BUDGET_CONFIG";
+    pub const EVENT_BUDGET: &str = "EVENT_BUDGET";
+
+    // Canary rule evaluation maps (xdp_filter)
+    pub const CANARY_IPS_V4: &str = "CANARY_IPS_V4";
+    pub const CANARY_IPS_V6: &str = "CANARY_IPS_V6";
+
+    // Flow sampling maps (present in every program)
+    pub const SAMPLE_CONFIG: &str = "SAMPLE_CONFIG";
+    pub const SAMPLES: &str = "SAMPLES";
+
+    // Per-CPU source counters (xdp_filter and xdp_ratelimit)
+    pub const SOURCE_COUNTERS_V4: &str = "SOURCE_COUNTERS_V4";
+    pub const SOURCE_COUNTERS_V6: &str = "SOURCE_COUNTERS_V6";
+
+    // Processing latency maps (present in every program)
+    pub const LATENCY_CONFIG: &str = "LATENCY_CONFIG";
+    pub const LATENCY: &str = "LATENCY";
+
+    // Allowlist learning maps (pinned, shared by xdp_tcp, xdp_minecraft and xdp_quic)
+    pub const LEARNING: &str = "LEARNING";
+    pub const HANDSHAKES: &str = "HANDSHAKES";
+    pub const KNOWN_GOOD: &str = "KNOWN_GOOD";
+
+    // Carrier-grade NAT maps (pinned, shared by xdp_tcp, xdp_minecraft and xdp_quic)
+    pub const CGNAT_RANGES: &str = "CGNAT_RANGES";
+    pub const CGNAT_DETECT: &str = "CGNAT_DETECT";
+    pub const CGNAT_SIGNALS: &str = "CGNAT_SIGNALS";
+
+    // Observe mode flag (pinned, shared by every program)
+    pub const OBSERVE: &str = "OBSERVE";
+
+    // Conntrack bypass maps (pinned, shared by xdp_tcp and tc_flowmark)
+    pub const FLOW_MARKS: &str = "FLOW_MARKS";
+    pub const FLOW_MARK_CONFIG: &str = "FLOW_MARK_CONFIG";
+    pub const FLOW_MARK_STATS: &str = "FLOW_MARK_STATS";
 }
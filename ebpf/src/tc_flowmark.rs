@@ -0,0 +1,144 @@
+//! TC Flow Mark Companion
+//!
+//! Classifier attached to the clsact hooks of the interfaces running xdp_tcp
+//! (ingress, and optionally egress). It sets the configured mark bits on the
+//! packets of flows xdp_tcp validated, in either direction, so a raw table
+//! `NOTRACK` rule matching the mark lets them skip conntrack. Packets of
+//! other flows, and packets it cannot parse, pass unmarked: the classifier
+//! never drops and always hands over to the next filter.
+
+#![no_std]
+#![no_main]
+
+use aya_ebpf::{
+    bindings::TC_ACT_UNSPEC,
+    macros::{classifier, map},
+    maps::{Array, LruHashMap, PerCpuArray},
+    programs::TcContext,
+};
+use pistonprotection_ebpf::{
+    FlowKey, FlowMarkConfig, FlowMarkEntry, FlowMarkStats, flow_key_reversed, flow_mark,
+    flow_marked, parse_eth, parse_ipv4, parse_ipv6_with_ext, parse_tcp, penalty_key_v4,
+};
+
+// ============================================================================
+// eBPF Maps
+// ============================================================================
+
+/// Flows validated by xdp_tcp, shared with it
+#[map(name = "FLOW_MARKS")]
+static FLOW_MARKS: LruHashMap<FlowKey, FlowMarkEntry> = LruHashMap::pinned(flow_mark::MAX_FLOWS, 0);
+
+/// Conntrack bypass configuration, shared with xdp_tcp
+#[map(name = "FLOW_MARK_CONFIG")]
+static FLOW_MARK_CONFIG: Array<FlowMarkConfig> = Array::pinned(1, 0);
+
+/// Statistics
+#[map]
+static FLOW_MARK_STATS: PerCpuArray<FlowMarkStats> = PerCpuArray::with_max_entries(1, 0);
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86DD;
+const IPPROTO_TCP: u8 = 6;
+const IP_OFFSET: u16 = 0x1FFF; // Fragment offset mask
+
+/// Bytes pulled into the linear area so the headers can be read directly
+/// (Ethernet with VLAN tags, IPv6 extension headers and TCP)
+const HEADER_LEN: u32 = 128;
+
+// ============================================================================
+// Main TC Entry Point
+// ============================================================================
+
+#[classifier]
+pub fn tc_flowmark(mut ctx: TcContext) -> i32 {
+    let Some(config) = FLOW_MARK_CONFIG.get(0).copied() else {
+        return TC_ACT_UNSPEC;
+    };
+    if config.enabled == 0 || config.mask == 0 {
+        return TC_ACT_UNSPEC;
+    }
+
+    let len = ctx.len();
+    if ctx.data_end() - ctx.data() < HEADER_LEN.min(len) as usize {
+        let _ = ctx.pull_data(HEADER_LEN.min(len));
+    }
+
+    let Some(key) = flow_key(&ctx) else {
+        return TC_ACT_UNSPEC;
+    };
+    let recorded = unsafe { FLOW_MARKS.get(&key) }.is_some()
+        || unsafe { FLOW_MARKS.get(&flow_key_reversed(&key)) }.is_some();
+    update_stats(recorded);
+
+    if recorded {
+        let mark = unsafe { (*ctx.skb.skb).mark };
+        ctx.set_mark(flow_marked(mark, &config));
+    }
+
+    TC_ACT_UNSPEC
+}
+
+/// Flow of a TCP packet, addresses IPv4-mapped like xdp_tcp records them
+#[inline(always)]
+fn flow_key(ctx: &TcContext) -> Option<FlowKey> {
+    let data = ctx.data();
+    let data_end = ctx.data_end();
+
+    let (eth_proto, l3_offset) = parse_eth(data, data_end)?;
+    let (src_addr, dst_addr, l4_offset) = match eth_proto {
+        ETH_P_IP => {
+            let (ip, l4_offset) = parse_ipv4(l3_offset, data_end)?;
+            // Only the first fragment carries the ports
+            if ip.protocol != IPPROTO_TCP || u16::from_be(ip.frag_off) & IP_OFFSET != 0 {
+                return None;
+            }
+            (
+                penalty_key_v4(u32::from_be(ip.saddr)),
+                penalty_key_v4(u32::from_be(ip.daddr)),
+                l4_offset,
+            )
+        }
+        ETH_P_IPV6 => {
+            let (ip6, l4) = parse_ipv6_with_ext(l3_offset, data_end)?;
+            if l4.protocol != IPPROTO_TCP || (l4.is_fragment && !l4.is_first_fragment) {
+                return None;
+            }
+            (ip6.saddr, ip6.daddr, l4.offset)
+        }
+        _ => return None,
+    };
+
+    let (tcp, _) = parse_tcp(l4_offset, data_end)?;
+    Some(FlowKey {
+        src_addr,
+        dst_addr,
+        src_port: u16::from_be(tcp.source),
+        dst_port: u16::from_be(tcp.dest),
+    })
+}
+
+#[inline(always)]
+fn update_stats(marked: bool) {
+    if let Some(stats) = unsafe { FLOW_MARK_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).packets += 1;
+            if marked {
+                (*stats).marked += 1;
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Panic Handler
+// ============================================================================
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
@@ -6,77 +6,38 @@
 #![no_main]
 
 use aya_ebpf::{
-    bindings::xdp_action,
+    bindings::{BPF_F_NO_PREALLOC, xdp_action},
     macros::{map, xdp},
-    maps::{HashMap, LruHashMap, PerCpuArray},
+    maps::{
+        Array, HashMap, LpmTrie, LruHashMap, LruPerCpuHashMap, PerCpuArray, PerCpuHashMap,
+        lpm_trie::Key,
+    },
     programs::XdpContext,
 };
 use aya_log_ebpf::info;
-use core::mem;
-
-/// IPv4 header structure
-#[repr(C)]
-struct Ipv4Hdr {
-    version_ihl: u8,
-    tos: u8,
-    tot_len: u16,
-    id: u16,
-    frag_off: u16,
-    ttl: u8,
-    protocol: u8,
-    check: u16,
-    saddr: u32,
-    daddr: u32,
-}
-
-/// IPv6 header structure
-#[repr(C)]
-struct Ipv6Hdr {
-    version_tc_flow: u32,
-    payload_len: u16,
-    nexthdr: u8,
-    hop_limit: u8,
-    saddr: [u8; 16],
-    daddr: [u8; 16],
-}
-
-/// TCP header structure
-#[repr(C)]
-struct TcpHdr {
-    source: u16,
-    dest: u16,
-    seq: u32,
-    ack_seq: u32,
-    doff_flags: u16,
-    window: u16,
-    check: u16,
-    urg_ptr: u16,
-}
-
-/// UDP header structure
-#[repr(C)]
-struct UdpHdr {
-    source: u16,
-    dest: u16,
-    len: u16,
-    check: u16,
-}
-
-/// Ethernet header
-#[repr(C)]
-struct EthHdr {
-    h_dest: [u8; 6],
-    h_source: [u8; 6],
-    h_proto: u16,
-}
+use pistonprotection_ebpf::{
+    BlockReason, CanaryEntry, DropContext, DropCounter, DstTraffic, EventBudget, EventBudgetConfig,
+    GreylistEntry, HoneypotConfig, HoneypotHit, HoneypotV4Key, HoneypotV6Key, Ipv4Hdr, Ipv6Hdr,
+    LatencyBucket, LatencyConfig, MarkingCounter, MarkingPolicy, SampleConfig, SourceCounters,
+    TenantConfig, TenantDstV4Key, TenantDstV6Key, TenantIpV4Key, TenantIpV6Key, ThreatFeedConfig,
+    ThreatIntelEntry, TokenLease, TrafficContext, backend_mode,
+    breakdown::{DST_PORT_MAX_ENTRIES, REASON_BUCKETS},
+    canary, check_threat_intel, count_source, drop_context_reset, drop_context_set_reason,
+    drop_context_set_source_v4, drop_context_set_source_v6, drop_context_set_target, drop_events,
+    emit_drop_event, frame_len, greylist, header_at_mut, honeypot, latency, latency_elapsed,
+    latency_start, lease, lookup_backend_mode_v4, lookup_backend_mode_v6, lookup_honeypot_v4,
+    lookup_honeypot_v6, lookup_marking, lookup_tenant_v4, lookup_tenant_v6, mark_ipv4, mark_ipv6,
+    marking, observe_verdict, parse_eth, parse_ipv4, parse_ipv6, parse_tcp, parse_udp,
+    peek_dst_port, penalty_key_v4, record_canary, record_drop, record_honeypot_hit, record_latency,
+    record_marking, record_traffic, sample_packet, sampling, spend_lease, take_lease, tenant,
+    threat_intel, traffic, traffic_context_reset, traffic_context_set_destination,
+};
 
 /// Rate limit entry in map
 #[repr(C)]
 pub struct RateLimitEntry {
     pub tokens: u64,
     pub last_update: u64,
-    pub packets: u64,
-    pub bytes: u64,
 }
 
 /// Blocked IP entry
@@ -129,6 +90,26 @@ static RATE_LIMITS_V4: LruHashMap<u32, RateLimitEntry> = LruHashMap::with_max_en
 static RATE_LIMITS_V6: LruHashMap<[u8; 16], RateLimitEntry> =
     LruHashMap::with_max_entries(500_000, 0);
 
+/// Tokens each CPU leased from `RATE_LIMITS_V4`
+#[map]
+static RATE_LEASES_V4: LruPerCpuHashMap<u32, TokenLease> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
+/// Tokens each CPU leased from `RATE_LIMITS_V6`
+#[map]
+static RATE_LEASES_V6: LruPerCpuHashMap<[u8; 16], TokenLease> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
+/// Per-CPU traffic of each source (IPv4)
+#[map]
+static SOURCE_COUNTERS_V4: LruPerCpuHashMap<u32, SourceCounters> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
+/// Per-CPU traffic of each source (IPv6)
+#[map]
+static SOURCE_COUNTERS_V6: LruPerCpuHashMap<[u8; 16], SourceCounters> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
 /// Global configuration
 #[map]
 static CONFIG: PerCpuArray<FilterConfig> = PerCpuArray::with_max_entries(1, 0);
@@ -137,6 +118,184 @@ static CONFIG: PerCpuArray<FilterConfig> = PerCpuArray::with_max_entries(1, 0);
 #[map]
 static STATS: PerCpuArray<Stats> = PerCpuArray::with_max_entries(1, 0);
 
+/// Drops broken down by (protocol, destination port)
+#[map]
+static DROPS_BY_DST_PORT: LruPerCpuHashMap<u32, DropCounter> =
+    LruPerCpuHashMap::with_max_entries(DST_PORT_MAX_ENTRIES, 0);
+
+/// Drops broken down by BlockReason
+#[map]
+static DROPS_BY_REASON: PerCpuArray<DropCounter> = PerCpuArray::with_max_entries(REASON_BUCKETS, 0);
+
+/// Per-CPU drop attribution for the packet being processed
+#[map]
+static DROP_CONTEXT: PerCpuArray<DropContext> = PerCpuArray::with_max_entries(1, 0);
+
+/// Traffic counters of backend destinations, entries inserted by userspace
+#[map]
+static DST_TRAFFIC: PerCpuHashMap<TenantDstV6Key, DstTraffic> =
+    PerCpuHashMap::with_max_entries(traffic::MAX_DESTINATIONS, 0);
+
+/// Per-CPU accounted destination of the packet being processed
+#[map]
+static TRAFFIC_CONTEXT: PerCpuArray<TrafficContext> = PerCpuArray::with_max_entries(1, 0);
+
+/// Marking policies of backend destinations, written by userspace
+#[map]
+static MARKING_POLICIES: HashMap<TenantDstV6Key, MarkingPolicy> =
+    HashMap::with_max_entries(marking::MAX_DESTINATIONS, 0);
+
+/// Marked packets per policy
+#[map]
+static MARKING_COUNTERS: PerCpuArray<MarkingCounter> =
+    PerCpuArray::with_max_entries(marking::MAX_POLICIES, 0);
+
+/// Set while the worker is in observe mode, shared with the other programs
+#[map(name = "OBSERVE")]
+static OBSERVE: Array<u32> = Array::pinned(1, 0);
+
+/// Flow sampling configuration
+#[map]
+static SAMPLE_CONFIG: PerCpuArray<SampleConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Sampled packet headers for the userspace analyzer
+#[map]
+static SAMPLES: sampling::SampleMap = sampling::sample_map();
+
+/// Dropped packets for the userspace drop event aggregator
+#[map]
+static DROP_EVENTS: drop_events::EventMap = drop_events::event_map();
+
+/// Event budget of each CPU, written by userspace
+#[map]
+static EVENT_BUDGET_CONFIG: PerCpuArray<EventBudgetConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Event token buckets and per-class event counters
+#[map]
+static EVENT_BUDGET: PerCpuArray<EventBudget> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing latency measurement configuration
+#[map]
+static LATENCY_CONFIG: PerCpuArray<LatencyConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing time histograms, per protection level
+#[map]
+static LATENCY: PerCpuArray<LatencyBucket> = PerCpuArray::with_max_entries(latency::MAX_ENTRIES, 0);
+
+/// Canary rule evaluation entries (IPv4), counters only
+#[map]
+static CANARY_IPS_V4: LruPerCpuHashMap<u32, CanaryEntry> =
+    LruPerCpuHashMap::with_max_entries(canary::MAX_ENTRIES, 0);
+
+/// Canary rule evaluation entries (IPv6), counters only
+#[map]
+static CANARY_IPS_V6: LruPerCpuHashMap<[u8; 16], CanaryEntry> =
+    LruPerCpuHashMap::with_max_entries(canary::MAX_ENTRIES, 0);
+
+/// Backend destinations (IPv4) to owning tenant
+#[map]
+static TENANT_DESTINATIONS_V4: HashMap<TenantDstV4Key, u32> =
+    HashMap::with_max_entries(tenant::MAX_DESTINATIONS, 0);
+
+/// Backend destinations (IPv6) to owning tenant
+#[map]
+static TENANT_DESTINATIONS_V6: HashMap<TenantDstV6Key, u32> =
+    HashMap::with_max_entries(tenant::MAX_DESTINATIONS, 0);
+
+/// Per-tenant configuration
+#[map]
+static TENANT_CONFIG: HashMap<u32, TenantConfig> =
+    HashMap::with_max_entries(tenant::MAX_TENANTS, 0);
+
+/// Tenant-scoped blocked sources (IPv4), value is the BlockReason
+#[map]
+static TENANT_BLOCKED_V4: HashMap<TenantIpV4Key, u32> =
+    HashMap::with_max_entries(tenant::MAX_ENTRIES, 0);
+
+/// Tenant-scoped blocked sources (IPv6), value is the BlockReason
+#[map]
+static TENANT_BLOCKED_V6: HashMap<TenantIpV6Key, u32> =
+    HashMap::with_max_entries(tenant::MAX_ENTRIES, 0);
+
+/// Tenant-scoped per-source rate limits (IPv4)
+#[map]
+static TENANT_RATE_LIMITS_V4: LruHashMap<TenantIpV4Key, RateLimitEntry> =
+    LruHashMap::with_max_entries(tenant::MAX_ENTRIES, 0);
+
+/// Tenant-scoped per-source rate limits (IPv6)
+#[map]
+static TENANT_RATE_LIMITS_V6: LruHashMap<TenantIpV6Key, RateLimitEntry> =
+    LruHashMap::with_max_entries(tenant::MAX_ENTRIES, 0);
+
+/// Threat intelligence networks (IPv4, network byte order)
+#[map]
+static THREAT_INTEL_V4: LpmTrie<[u8; 4], ThreatIntelEntry> =
+    LpmTrie::with_max_entries(threat_intel::MAX_ENTRIES_V4, BPF_F_NO_PREALLOC);
+
+/// Threat intelligence networks (IPv6)
+#[map]
+static THREAT_INTEL_V6: LpmTrie<[u8; 16], ThreatIntelEntry> =
+    LpmTrie::with_max_entries(threat_intel::MAX_ENTRIES_V6, BPF_F_NO_PREALLOC);
+
+/// Per-feed settings, indexed by feed id
+#[map]
+static THREAT_FEEDS: Array<ThreatFeedConfig> = Array::with_max_entries(threat_intel::MAX_FEEDS, 0);
+
+/// Per-feed hit counters, indexed by feed id
+#[map]
+static THREAT_FEED_HITS: PerCpuArray<DropCounter> =
+    PerCpuArray::with_max_entries(threat_intel::MAX_FEEDS, 0);
+
+/// Greylisted sources (IPv4) with their reduced limits
+#[map]
+static GREYLIST_V4: HashMap<u32, GreylistEntry> =
+    HashMap::with_max_entries(greylist::MAX_ENTRIES, 0);
+
+/// Greylisted sources (IPv6) with their reduced limits
+#[map]
+static GREYLIST_V6: HashMap<[u8; 16], GreylistEntry> =
+    HashMap::with_max_entries(greylist::MAX_ENTRIES, 0);
+
+/// Token buckets of greylisted sources (IPv4)
+#[map]
+static GREYLIST_RATE_LIMITS_V4: LruHashMap<u32, RateLimitEntry> =
+    LruHashMap::with_max_entries(greylist::MAX_ENTRIES, 0);
+
+/// Token buckets of greylisted sources (IPv6)
+#[map]
+static GREYLIST_RATE_LIMITS_V6: LruHashMap<[u8; 16], RateLimitEntry> =
+    LruHashMap::with_max_entries(greylist::MAX_ENTRIES, 0);
+
+/// Destinations (IPv4) of backends switched to passthrough or block-all
+#[map]
+static BACKEND_MODES_V4: HashMap<TenantDstV4Key, u32> =
+    HashMap::with_max_entries(backend_mode::MAX_DESTINATIONS, 0);
+
+/// Destinations (IPv6) of backends switched to passthrough or block-all
+#[map]
+static BACKEND_MODES_V6: HashMap<TenantDstV6Key, u32> =
+    HashMap::with_max_entries(backend_mode::MAX_DESTINATIONS, 0);
+
+/// Honeypot destinations (IPv4) of registered backends
+#[map]
+static HONEYPOT_PORTS_V4: HashMap<HoneypotV4Key, HoneypotConfig> =
+    HashMap::with_max_entries(honeypot::MAX_PORTS, 0);
+
+/// Honeypot destinations (IPv6) of registered backends
+#[map]
+static HONEYPOT_PORTS_V6: HashMap<HoneypotV6Key, HoneypotConfig> =
+    HashMap::with_max_entries(honeypot::MAX_PORTS, 0);
+
+/// Sources that touched a honeypot port (IPv4), drained by userspace
+#[map]
+static HONEYPOT_HITS_V4: LruHashMap<u32, HoneypotHit> =
+    LruHashMap::with_max_entries(honeypot::MAX_HITS, 0);
+
+/// Sources that touched a honeypot port (IPv6), drained by userspace
+#[map]
+static HONEYPOT_HITS_V6: LruHashMap<[u8; 16], HoneypotHit> =
+    LruHashMap::with_max_entries(honeypot::MAX_HITS, 0);
+
 // Constants
 const ETH_P_IP: u16 = 0x0800;
 const ETH_P_IPV6: u16 = 0x86DD;
@@ -150,12 +309,56 @@ const TCP_ACK: u16 = 0x0010;
 const TCP_RST: u16 = 0x0004;
 
 /// Main XDP filter program
-#[xdp]
+#[cfg_attr(feature = "frags", xdp(frags))]
+#[cfg_attr(not(feature = "frags"), xdp)]
 pub fn xdp_filter(ctx: XdpContext) -> u32 {
-    match try_xdp_filter(ctx) {
+    let started = latency_start(&LATENCY_CONFIG);
+    drop_context_reset(&DROP_CONTEXT, BlockReason::GenericDdos);
+    traffic_context_reset(&TRAFFIC_CONTEXT);
+    let bytes = frame_len(ctx.ctx) as u64;
+    let raw_ctx = ctx.ctx;
+
+    let action = match try_xdp_filter(ctx) {
         Ok(ret) => ret,
         Err(_) => xdp_action::XDP_PASS,
+    };
+
+    record_traffic(
+        &TRAFFIC_CONTEXT,
+        &DST_TRAFFIC,
+        bytes,
+        action == xdp_action::XDP_DROP,
+    );
+    if action == xdp_action::XDP_DROP {
+        record_drop(&DROP_CONTEXT, &DROPS_BY_DST_PORT, &DROPS_BY_REASON, bytes);
+        emit_drop_event(
+            raw_ctx,
+            &DROP_CONTEXT,
+            &DROP_EVENTS,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+            bytes,
+        );
+    } else if action == xdp_action::XDP_PASS {
+        sample_packet(
+            raw_ctx,
+            bytes,
+            &SAMPLE_CONFIG,
+            &SAMPLES,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+        );
+    }
+
+    if let Some(elapsed) = latency_elapsed(started) {
+        record_latency(
+            &LATENCY,
+            elapsed,
+            unsafe { CONFIG.get(0) }.map_or(0, |config| config.protection_level),
+        );
     }
+
+    observe_verdict(&OBSERVE, action)
 }
 
 #[inline(always)]
@@ -163,60 +366,157 @@ fn try_xdp_filter(ctx: XdpContext) -> Result<u32, ()> {
     let data = ctx.data();
     let data_end = ctx.data_end();
 
-    // Check for minimum Ethernet header
-    if data + mem::size_of::<EthHdr>() > data_end {
-        return Ok(xdp_action::XDP_PASS);
-    }
-
     // Parse Ethernet header
-    let eth = unsafe { &*(data as *const EthHdr) };
-    let eth_proto = u16::from_be(eth.h_proto);
+    let Some((eth_proto, l3_offset)) = parse_eth(data, data_end) else {
+        return Ok(xdp_action::XDP_PASS);
+    };
 
     // Update stats
     if let Some(stats) = unsafe { STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).packets_total += 1;
-            (*stats).bytes_total += (data_end - data) as u64;
+            (*stats).bytes_total += frame_len(ctx.ctx) as u64;
         }
     }
 
     match eth_proto {
-        ETH_P_IP => process_ipv4(&ctx, data + mem::size_of::<EthHdr>(), data_end),
-        ETH_P_IPV6 => process_ipv6(&ctx, data + mem::size_of::<EthHdr>(), data_end),
+        ETH_P_IP => process_ipv4(&ctx, l3_offset, data_end),
+        ETH_P_IPV6 => process_ipv6(&ctx, l3_offset, data_end),
         _ => Ok(xdp_action::XDP_PASS),
     }
 }
 
 #[inline(always)]
 fn process_ipv4(ctx: &XdpContext, data: usize, data_end: usize) -> Result<u32, ()> {
-    // Check for IPv4 header
-    if data + mem::size_of::<Ipv4Hdr>() > data_end {
+    let Some((ip, transport_offset)) = parse_ipv4(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
+    };
+    let src_ip = u32::from_be(ip.saddr);
+    let dst_port = peek_dst_port(transport_offset, data_end, ip.protocol);
+
+    drop_context_set_target(&DROP_CONTEXT, ip.protocol, dst_port);
+    drop_context_set_source_v4(&DROP_CONTEXT, ip.saddr);
+    traffic_context_set_destination(
+        &TRAFFIC_CONTEXT,
+        &DST_TRAFFIC,
+        &penalty_key_v4(u32::from_be(ip.daddr)),
+        dst_port,
+    );
+
+    // Backends being debugged skip every other filter
+    match lookup_backend_mode_v4(&BACKEND_MODES_V4, u32::from_be(ip.daddr), dst_port) {
+        backend_mode::PASSTHROUGH => {
+            update_stats_passed();
+            return Ok(xdp_action::XDP_PASS);
+        }
+        backend_mode::BLOCK_ALL => {
+            update_stats_dropped(BlockReason::BackendBlocked);
+            return Ok(xdp_action::XDP_DROP);
+        }
+        _ => {}
     }
 
-    let ip = unsafe { &*(data as *const Ipv4Hdr) };
-    let src_ip = u32::from_be(ip.saddr);
+    // Count against the candidate rule set being evaluated, if any
+    record_canary(&CANARY_IPS_V4, &src_ip, frame_len(ctx.ctx) as u64);
 
     // Check blocked list
     if let Some(blocked) = unsafe { BLOCKED_IPS_V4.get(&src_ip) } {
         // Check expiration
         let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
         if blocked.expires_at == 0 || blocked.expires_at > now {
-            update_stats_dropped();
+            update_stats_dropped(BlockReason::BlockedIp);
             return Ok(xdp_action::XDP_DROP);
         }
     }
 
-    // Check rate limit
-    if !check_rate_limit_v4(src_ip) {
+    // Sources touching a honeypot port are flagged, and blocked for every
+    // backend if the owning backend asks for it
+    if let Some(config) = lookup_honeypot_v4(&HONEYPOT_PORTS_V4, u32::from_be(ip.daddr), dst_port) {
+        if record_honeypot_hit(&HONEYPOT_HITS_V4, &src_ip, &config, ip.protocol, dst_port) {
+            block_source(
+                &BLOCKED_IPS_V4,
+                &src_ip,
+                BlockReason::Honeypot,
+                config.block_duration_ns,
+            );
+        }
+        update_stats_dropped(BlockReason::Honeypot);
+        return Ok(xdp_action::XDP_DROP);
+    }
+
+    // Check threat intelligence feeds
+    let key = Key::new(32, src_ip.to_be_bytes());
+    if check_threat_intel(
+        &THREAT_INTEL_V4,
+        &THREAT_FEEDS,
+        &THREAT_FEED_HITS,
+        &key,
+        frame_len(ctx.ctx) as u64,
+    ) {
+        update_stats_dropped(BlockReason::ThreatIntel);
+        return Ok(xdp_action::XDP_DROP);
+    }
+
+    // Greylisted sources get a reduced bucket on top of the regular limits;
+    // backends with a marking policy get them deprioritized instead
+    if let Some(entry) = unsafe { GREYLIST_V4.get(&src_ip) } {
+        let (limit, burst) = entry.limits();
+        let within = take_token(&GREYLIST_RATE_LIMITS_V4, &src_ip, limit, burst);
+        let policy = lookup_marking(
+            &MARKING_POLICIES,
+            &penalty_key_v4(u32::from_be(ip.daddr)),
+            dst_port,
+        )
+        .filter(|policy| policy.marks(within));
+        match policy {
+            Some(policy) => {
+                if let Some(hdr) = header_at_mut::<Ipv4Hdr>(data, data_end) {
+                    let ce = mark_ipv4(hdr, &policy);
+                    record_marking(
+                        &MARKING_COUNTERS,
+                        policy.policy_id,
+                        frame_len(ctx.ctx) as u64,
+                        ce,
+                    );
+                }
+            }
+            None if !within => {
+                update_stats_greylisted();
+                return Ok(xdp_action::XDP_DROP);
+            }
+            None => {}
+        }
+    }
+
+    // Backends of other tenants never see this tenant's blocks and limits
+    let tenant_id = lookup_tenant_v4(&TENANT_DESTINATIONS_V4, u32::from_be(ip.daddr), dst_port);
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    let passed = if tenant_id != tenant::GLOBAL {
+        let key = TenantIpV4Key {
+            tenant_id,
+            addr: src_ip,
+        };
+        if unsafe { TENANT_BLOCKED_V4.get(&key) }.is_some() {
+            update_stats_dropped(BlockReason::BlockedIp);
+            return Ok(xdp_action::XDP_DROP);
+        }
+        check_tenant_rate_limit(&TENANT_RATE_LIMITS_V4, &key, tenant_id)
+    } else {
+        check_rate_limit_v4(src_ip, now)
+    };
+    count_source(
+        &SOURCE_COUNTERS_V4,
+        &src_ip,
+        frame_len(ctx.ctx) as u64,
+        passed,
+        now,
+    );
+    if !passed {
         update_stats_rate_limited();
         return Ok(xdp_action::XDP_DROP);
     }
 
     // Protocol-specific processing
-    let ihl = (ip.version_ihl & 0x0f) as usize * 4;
-    let transport_offset = data + ihl;
-
     match ip.protocol {
         IPPROTO_TCP => process_tcp(ctx, transport_offset, data_end, src_ip),
         IPPROTO_UDP => process_udp(ctx, transport_offset, data_end, src_ip),
@@ -230,25 +530,120 @@ fn process_ipv4(ctx: &XdpContext, data: usize, data_end: usize) -> Result<u32, (
 
 #[inline(always)]
 fn process_ipv6(ctx: &XdpContext, data: usize, data_end: usize) -> Result<u32, ()> {
-    // Check for IPv6 header
-    if data + mem::size_of::<Ipv6Hdr>() > data_end {
+    let Some((ip6, next_offset)) = parse_ipv6(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
+    };
+    let src_ip = ip6.saddr;
+    let dst_port = peek_dst_port(next_offset, data_end, ip6.nexthdr);
+
+    drop_context_set_target(&DROP_CONTEXT, ip6.nexthdr, dst_port);
+    drop_context_set_source_v6(&DROP_CONTEXT, &ip6.saddr);
+    traffic_context_set_destination(&TRAFFIC_CONTEXT, &DST_TRAFFIC, &ip6.daddr, dst_port);
+
+    // Backends being debugged skip every other filter
+    match lookup_backend_mode_v6(&BACKEND_MODES_V6, ip6.daddr, dst_port) {
+        backend_mode::PASSTHROUGH => {
+            update_stats_passed();
+            return Ok(xdp_action::XDP_PASS);
+        }
+        backend_mode::BLOCK_ALL => {
+            update_stats_dropped(BlockReason::BackendBlocked);
+            return Ok(xdp_action::XDP_DROP);
+        }
+        _ => {}
     }
 
-    let ip6 = unsafe { &*(data as *const Ipv6Hdr) };
-    let src_ip = ip6.saddr;
+    // Count against the candidate rule set being evaluated, if any
+    record_canary(&CANARY_IPS_V6, &src_ip, frame_len(ctx.ctx) as u64);
 
     // Check blocked list
     if let Some(blocked) = unsafe { BLOCKED_IPS_V6.get(&src_ip) } {
         let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
         if blocked.expires_at == 0 || blocked.expires_at > now {
-            update_stats_dropped();
+            update_stats_dropped(BlockReason::BlockedIp);
             return Ok(xdp_action::XDP_DROP);
         }
     }
 
-    // Check rate limit
-    if !check_rate_limit_v6(src_ip) {
+    // Sources touching a honeypot port are flagged, and blocked for every
+    // backend if the owning backend asks for it
+    if let Some(config) = lookup_honeypot_v6(&HONEYPOT_PORTS_V6, ip6.daddr, dst_port) {
+        if record_honeypot_hit(&HONEYPOT_HITS_V6, &src_ip, &config, ip6.nexthdr, dst_port) {
+            block_source(
+                &BLOCKED_IPS_V6,
+                &src_ip,
+                BlockReason::Honeypot,
+                config.block_duration_ns,
+            );
+        }
+        update_stats_dropped(BlockReason::Honeypot);
+        return Ok(xdp_action::XDP_DROP);
+    }
+
+    // Check threat intelligence feeds
+    let key = Key::new(128, src_ip);
+    if check_threat_intel(
+        &THREAT_INTEL_V6,
+        &THREAT_FEEDS,
+        &THREAT_FEED_HITS,
+        &key,
+        frame_len(ctx.ctx) as u64,
+    ) {
+        update_stats_dropped(BlockReason::ThreatIntel);
+        return Ok(xdp_action::XDP_DROP);
+    }
+
+    // Greylisted sources get a reduced bucket on top of the regular limits;
+    // backends with a marking policy get them deprioritized instead
+    if let Some(entry) = unsafe { GREYLIST_V6.get(&src_ip) } {
+        let (limit, burst) = entry.limits();
+        let within = take_token(&GREYLIST_RATE_LIMITS_V6, &src_ip, limit, burst);
+        let policy = lookup_marking(&MARKING_POLICIES, &ip6.daddr, dst_port)
+            .filter(|policy| policy.marks(within));
+        match policy {
+            Some(policy) => {
+                if let Some(hdr) = header_at_mut::<Ipv6Hdr>(data, data_end) {
+                    let ce = mark_ipv6(hdr, &policy);
+                    record_marking(
+                        &MARKING_COUNTERS,
+                        policy.policy_id,
+                        frame_len(ctx.ctx) as u64,
+                        ce,
+                    );
+                }
+            }
+            None if !within => {
+                update_stats_greylisted();
+                return Ok(xdp_action::XDP_DROP);
+            }
+            None => {}
+        }
+    }
+
+    // Backends of other tenants never see this tenant's blocks and limits
+    let tenant_id = lookup_tenant_v6(&TENANT_DESTINATIONS_V6, ip6.daddr, dst_port);
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    let passed = if tenant_id != tenant::GLOBAL {
+        let key = TenantIpV6Key {
+            tenant_id,
+            addr: src_ip,
+        };
+        if unsafe { TENANT_BLOCKED_V6.get(&key) }.is_some() {
+            update_stats_dropped(BlockReason::BlockedIp);
+            return Ok(xdp_action::XDP_DROP);
+        }
+        check_tenant_rate_limit(&TENANT_RATE_LIMITS_V6, &key, tenant_id)
+    } else {
+        check_rate_limit_v6(src_ip, now)
+    };
+    count_source(
+        &SOURCE_COUNTERS_V6,
+        &src_ip,
+        frame_len(ctx.ctx) as u64,
+        passed,
+        now,
+    );
+    if !passed {
         update_stats_rate_limited();
         return Ok(xdp_action::XDP_DROP);
     }
@@ -259,11 +654,9 @@ fn process_ipv6(ctx: &XdpContext, data: usize, data_end: usize) -> Result<u32, (
 
 #[inline(always)]
 fn process_tcp(ctx: &XdpContext, data: usize, data_end: usize, src_ip: u32) -> Result<u32, ()> {
-    if data + mem::size_of::<TcpHdr>() > data_end {
+    let Some((tcp, _)) = parse_tcp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let tcp = unsafe { &*(data as *const TcpHdr) };
+    };
     let flags = u16::from_be(tcp.doff_flags) & 0x003f;
 
     // SYN flood protection
@@ -279,7 +672,7 @@ fn process_tcp(ctx: &XdpContext, data: usize, data_end: usize, src_ip: u32) -> R
 
     // Invalid flag combinations
     if flags == (TCP_SYN | TCP_RST) {
-        update_stats_dropped();
+        update_stats_dropped(BlockReason::InvalidProtocol);
         return Ok(xdp_action::XDP_DROP);
     }
 
@@ -289,11 +682,9 @@ fn process_tcp(ctx: &XdpContext, data: usize, data_end: usize, src_ip: u32) -> R
 
 #[inline(always)]
 fn process_udp(ctx: &XdpContext, data: usize, data_end: usize, src_ip: u32) -> Result<u32, ()> {
-    if data + mem::size_of::<UdpHdr>() > data_end {
+    let Some((udp, _)) = parse_udp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let udp = unsafe { &*(data as *const UdpHdr) };
+    };
     let src_port = u16::from_be(udp.source);
     let dst_port = u16::from_be(udp.dest);
 
@@ -322,53 +713,122 @@ fn process_icmp(ctx: &XdpContext, data: usize, data_end: usize, src_ip: u32) ->
     Ok(xdp_action::XDP_PASS)
 }
 
+/// Per-source limit of 1 token per millisecond, up to 1000
+///
+/// Tokens are spent from this CPU's lease and only taken from the shared
+/// bucket once the lease runs out.
 #[inline(always)]
-fn check_rate_limit_v4(src_ip: u32) -> bool {
-    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+fn check_rate_limit_v4(src_ip: u32, now: u64) -> bool {
+    spend_lease(&RATE_LEASES_V4, &src_ip, now)
+        || lease_tokens(&RATE_LIMITS_V4, &RATE_LEASES_V4, &src_ip, now)
+}
+
+#[inline(always)]
+fn check_rate_limit_v6(src_ip: [u8; 16], now: u64) -> bool {
+    spend_lease(&RATE_LEASES_V6, &src_ip, now)
+        || lease_tokens(&RATE_LIMITS_V6, &RATE_LEASES_V6, &src_ip, now)
+}
 
-    if let Some(entry) = unsafe { RATE_LIMITS_V4.get_ptr_mut(&src_ip) } {
+/// Refill the shared bucket of `key` and lease tokens from it to this CPU
+///
+/// The bucket is only written when it refills or hands out tokens, so a
+/// source over its limit does not keep dirtying it.
+#[inline(always)]
+fn lease_tokens<K>(
+    buckets: &LruHashMap<K, RateLimitEntry>,
+    leases: &LruPerCpuHashMap<K, TokenLease>,
+    key: &K,
+    now: u64,
+) -> bool {
+    if let Some(entry) = unsafe { buckets.get_ptr_mut(key) } {
         let entry = unsafe { &mut *entry };
 
         // Token bucket algorithm
-        let elapsed = now - entry.last_update;
-        let tokens_to_add = (elapsed >> 20); // 1 token per millisecond
-
-        entry.tokens = core::cmp::min(entry.tokens + tokens_to_add, 1000); // Max 1000 tokens
-        entry.last_update = now;
-        entry.packets += 1;
+        let tokens_to_add = now.saturating_sub(entry.last_update) >> 20; // 1 token per millisecond
+        if tokens_to_add > 0 {
+            entry.tokens = core::cmp::min(entry.tokens + tokens_to_add, 1000); // Max 1000 tokens
+            entry.last_update = now;
+        }
 
-        if entry.tokens > 0 {
-            entry.tokens -= 1;
-            true
-        } else {
-            false
+        let taken = take_lease(leases, key, entry.tokens, now);
+        if taken > 0 {
+            entry.tokens -= taken;
         }
+        taken > 0
     } else {
-        // First packet from this IP
+        // First packet from this source: full bucket minus this CPU's lease
+        let taken = take_lease(leases, key, 1000, now);
         let entry = RateLimitEntry {
-            tokens: 999, // Start with full bucket minus this packet
+            tokens: 1000 - taken,
             last_update: now,
-            packets: 1,
-            bytes: 0,
         };
-        let _ = RATE_LIMITS_V4.insert(&src_ip, &entry, 0);
+        let _ = buckets.insert(key, &entry, 0);
         true
     }
 }
 
+/// Token bucket for a tenant-scoped source, sized by the tenant's config
+#[inline(always)]
+fn check_tenant_rate_limit<K>(
+    map: &LruHashMap<K, RateLimitEntry>,
+    key: &K,
+    tenant_id: u32,
+) -> bool {
+    let (limit, burst) = match unsafe { TENANT_CONFIG.get(&tenant_id) } {
+        Some(config) if config.per_ip_pps_limit > 0 => (
+            config.per_ip_pps_limit,
+            if config.per_ip_burst > 0 {
+                config.per_ip_burst
+            } else {
+                config.per_ip_pps_limit
+            },
+        ),
+        _ => (tenant::DEFAULT_PPS_LIMIT, tenant::DEFAULT_PPS_LIMIT),
+    };
+
+    take_token(map, key, limit, burst)
+}
+
+/// Add a source to the global blocklist (`duration_ns` 0 = no expiry)
+#[inline(always)]
+fn block_source<K>(
+    map: &LruHashMap<K, BlockedIpEntry>,
+    key: &K,
+    reason: BlockReason,
+    duration_ns: u64,
+) {
+    let expires_at = if duration_ns > 0 {
+        let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+        now + duration_ns
+    } else {
+        0
+    };
+    let entry = BlockedIpEntry {
+        reason: reason as u32,
+        expires_at,
+        packets_blocked: 0,
+    };
+    let _ = map.insert(key, &entry, 0);
+}
+
+/// Take a token from the bucket of `key`, refilled at `limit` per second up
+/// to `burst`
 #[inline(always)]
-fn check_rate_limit_v6(src_ip: [u8; 16]) -> bool {
+fn take_token<K>(map: &LruHashMap<K, RateLimitEntry>, key: &K, limit: u64, burst: u64) -> bool {
     let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
 
-    if let Some(entry) = unsafe { RATE_LIMITS_V6.get_ptr_mut(&src_ip) } {
+    if let Some(entry) = unsafe { map.get_ptr_mut(key) } {
         let entry = unsafe { &mut *entry };
 
-        let elapsed = now - entry.last_update;
-        let tokens_to_add = (elapsed >> 20);
+        // Cap the refill window so elapsed * limit cannot overflow
+        let elapsed = core::cmp::min(now - entry.last_update, 10_000_000_000);
+        let tokens_to_add = elapsed * limit / 1_000_000_000;
 
-        entry.tokens = core::cmp::min(entry.tokens + tokens_to_add, 1000);
-        entry.last_update = now;
-        entry.packets += 1;
+        // Keep the remainder of partial tokens by only moving the clock on refill
+        if tokens_to_add > 0 {
+            entry.tokens = core::cmp::min(entry.tokens + tokens_to_add, burst);
+            entry.last_update = now;
+        }
 
         if entry.tokens > 0 {
             entry.tokens -= 1;
@@ -378,12 +838,10 @@ fn check_rate_limit_v6(src_ip: [u8; 16]) -> bool {
         }
     } else {
         let entry = RateLimitEntry {
-            tokens: 999,
+            tokens: burst - 1,
             last_update: now,
-            packets: 1,
-            bytes: 0,
         };
-        let _ = RATE_LIMITS_V6.insert(&src_ip, &entry, 0);
+        let _ = map.insert(key, &entry, 0);
         true
     }
 }
@@ -398,7 +856,8 @@ fn update_stats_passed() {
 }
 
 #[inline(always)]
-fn update_stats_dropped() {
+fn update_stats_dropped(reason: BlockReason) {
+    drop_context_set_reason(&DROP_CONTEXT, reason);
     if let Some(stats) = unsafe { STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).packets_dropped += 1;
@@ -408,6 +867,17 @@ fn update_stats_dropped() {
 
 #[inline(always)]
 fn update_stats_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::RateLimit);
+    if let Some(stats) = unsafe { STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).packets_rate_limited += 1;
+        }
+    }
+}
+
+#[inline(always)]
+fn update_stats_greylisted() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::Reputation);
     if let Some(stats) = unsafe { STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).packets_rate_limited += 1;
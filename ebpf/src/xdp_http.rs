@@ -10,6 +10,7 @@
 //! - HTTP/2 frame-level parsing and validation
 //! - HTTP/2 Rapid Reset attack detection (CVE-2023-44487)
 //! - HTTP/2 control frame rate limiting
+//! - Per-client DNS-over-HTTPS request budgets (`/dns-query`)
 
 #![no_std]
 #![no_main]
@@ -17,57 +18,21 @@
 use aya_ebpf::{
     bindings::xdp_action,
     macros::{map, xdp},
-    maps::{HashMap, LruHashMap, PerCpuArray},
+    maps::{Array, HashMap, LruHashMap, LruPerCpuHashMap, PerCpuArray},
     programs::XdpContext,
 };
-use core::mem;
-
-// ============================================================================
-// Network Header Structures
-// ============================================================================
-
-#[repr(C)]
-struct EthHdr {
-    h_dest: [u8; 6],
-    h_source: [u8; 6],
-    h_proto: u16,
-}
-
-#[repr(C)]
-struct Ipv4Hdr {
-    version_ihl: u8,
-    tos: u8,
-    tot_len: u16,
-    id: u16,
-    frag_off: u16,
-    ttl: u8,
-    protocol: u8,
-    check: u16,
-    saddr: u32,
-    daddr: u32,
-}
-
-#[repr(C)]
-struct Ipv6Hdr {
-    version_tc_flow: u32,
-    payload_len: u16,
-    nexthdr: u8,
-    hop_limit: u8,
-    saddr: [u8; 16],
-    daddr: [u8; 16],
-}
-
-#[repr(C)]
-struct TcpHdr {
-    source: u16,
-    dest: u16,
-    seq: u32,
-    ack_seq: u32,
-    doff_flags: u16,
-    window: u16,
-    check: u16,
-    urg_ptr: u16,
-}
+use pistonprotection_ebpf::{
+    BlockReason, DnsBudgetConfig, DnsRate, DropContext, DropCounter, EventBudget,
+    EventBudgetConfig, LatencyBucket, LatencyConfig, PayloadScratch, PenaltyConfig, PenaltyEntry,
+    SampleConfig,
+    breakdown::{DST_PORT_MAX_ENTRIES, REASON_BUCKETS},
+    dns, dns_budget_take, drop_context_reset, drop_context_set_reason, drop_context_set_source_v4,
+    drop_context_set_source_v6, drop_context_set_target, drop_events, emit_drop_event, frame_len,
+    hash_ipv6_addr, latency, latency_elapsed, latency_start, observe_verdict, parse_eth,
+    parse_ipv4, parse_ipv6_with_ext, parse_tcp, payload_view, peek_dst_port, penalty,
+    penalty_blocked, penalty_config, penalty_divisor, penalty_key_v4, record_drop, record_latency,
+    record_violation, sample_packet, sampling,
+};
 
 // ============================================================================
 // HTTP Filtering Structures
@@ -212,6 +177,8 @@ pub struct HttpStats {
     pub http2_data_frames: u64,
     pub dropped_request_smuggling: u64,
     pub dropped_header_injection: u64,
+    pub doh_requests: u64,
+    pub dropped_doh_rate_limited: u64,
 }
 
 /// Blocked path entry (for path-based filtering)
@@ -298,6 +265,10 @@ static HTTP_RATE_LIMITS: LruHashMap<u32, HttpRateLimit> = LruHashMap::with_max_e
 static HTTP_RATE_LIMITS_V6: LruHashMap<[u8; 16], HttpRateLimit> =
     LruHashMap::with_max_entries(250_000, 0);
 
+/// Copy of the inspected payload when it spans multi-buffer fragments
+#[map]
+static PAYLOAD_SCRATCH: PerCpuArray<PayloadScratch> = PerCpuArray::with_max_entries(1, 0);
+
 /// Blocked paths (by hash)
 #[map]
 static BLOCKED_PATHS: HashMap<u32, BlockedPath> = HashMap::with_max_entries(10_000, 0);
@@ -306,10 +277,30 @@ static BLOCKED_PATHS: HashMap<u32, BlockedPath> = HashMap::with_max_entries(10_0
 #[map]
 static BLOCKED_USER_AGENTS: HashMap<u32, u32> = HashMap::with_max_entries(10_000, 0);
 
+/// DNS query budget per client, written by the worker
+#[map]
+static DNS_BUDGET: Array<DnsBudgetConfig> = Array::with_max_entries(1, 0);
+
+/// DNS-over-HTTPS requests per client in the current second
+#[map]
+static DNS_RATES: LruHashMap<[u8; 16], DnsRate> = LruHashMap::with_max_entries(dns::MAX_CLIENTS, 0);
+
 /// Whitelisted IPs (bypass filtering)
 #[map]
 static HTTP_WHITELIST: HashMap<u32, u32> = HashMap::with_max_entries(10_000, 0);
 
+/// Whitelisted IPv6 addresses (bypass filtering)
+#[map]
+static HTTP_WHITELIST_V6: HashMap<[u8; 16], u32> = HashMap::with_max_entries(10_000, 0);
+
+/// Penalties of repeat offenders, shared with the other programs
+#[map(name = "PENALTY")]
+static PENALTY: LruHashMap<[u8; 16], PenaltyEntry> = LruHashMap::pinned(penalty::MAX_SOURCES, 0);
+
+/// Penalty ladder per protection level, shared with the other programs
+#[map(name = "PENALTY_CONFIG")]
+static PENALTY_CONFIG: Array<PenaltyConfig> = Array::pinned(penalty::LEVELS, 0);
+
 /// Configuration
 #[map]
 static HTTP_CONFIG: PerCpuArray<HttpConfig> = PerCpuArray::with_max_entries(1, 0);
@@ -318,6 +309,51 @@ static HTTP_CONFIG: PerCpuArray<HttpConfig> = PerCpuArray::with_max_entries(1, 0
 #[map]
 static HTTP_STATS: PerCpuArray<HttpStats> = PerCpuArray::with_max_entries(1, 0);
 
+/// Drops broken down by (protocol, destination port)
+#[map]
+static DROPS_BY_DST_PORT: LruPerCpuHashMap<u32, DropCounter> =
+    LruPerCpuHashMap::with_max_entries(DST_PORT_MAX_ENTRIES, 0);
+
+/// Drops broken down by BlockReason
+#[map]
+static DROPS_BY_REASON: PerCpuArray<DropCounter> = PerCpuArray::with_max_entries(REASON_BUCKETS, 0);
+
+/// Per-CPU drop attribution for the packet being processed
+#[map]
+static DROP_CONTEXT: PerCpuArray<DropContext> = PerCpuArray::with_max_entries(1, 0);
+
+/// Set while the worker is in observe mode, shared with the other programs
+#[map(name = "OBSERVE")]
+static OBSERVE: Array<u32> = Array::pinned(1, 0);
+
+/// Flow sampling configuration
+#[map]
+static SAMPLE_CONFIG: PerCpuArray<SampleConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Sampled packet headers for the userspace analyzer
+#[map]
+static SAMPLES: sampling::SampleMap = sampling::sample_map();
+
+/// Dropped packets for the userspace drop event aggregator
+#[map]
+static DROP_EVENTS: drop_events::EventMap = drop_events::event_map();
+
+/// Event budget of each CPU, written by userspace
+#[map]
+static EVENT_BUDGET_CONFIG: PerCpuArray<EventBudgetConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Event token buckets and per-class event counters
+#[map]
+static EVENT_BUDGET: PerCpuArray<EventBudget> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing latency measurement configuration
+#[map]
+static LATENCY_CONFIG: PerCpuArray<LatencyConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing time histograms, per protection level
+#[map]
+static LATENCY: PerCpuArray<LatencyBucket> = PerCpuArray::with_max_entries(latency::MAX_ENTRIES, 0);
+
 // ============================================================================
 // Constants
 // ============================================================================
@@ -349,12 +385,45 @@ const DEFAULT_HTTP2_MAX_STREAMS: u32 = 100; // Max concurrent streams
 // Main XDP Entry Point
 // ============================================================================
 
-#[xdp]
+#[cfg_attr(feature = "frags", xdp(frags))]
+#[cfg_attr(not(feature = "frags"), xdp)]
 pub fn xdp_http(ctx: XdpContext) -> u32 {
-    match try_xdp_http(ctx) {
+    let started = latency_start(&LATENCY_CONFIG);
+    drop_context_reset(&DROP_CONTEXT, BlockReason::InvalidProtocol);
+    let bytes = frame_len(ctx.ctx) as u64;
+    let raw_ctx = ctx.ctx;
+
+    let action = match try_xdp_http(ctx) {
         Ok(ret) => ret,
         Err(_) => xdp_action::XDP_PASS,
-    }
+    };
+
+    if action == xdp_action::XDP_DROP {
+        record_drop(&DROP_CONTEXT, &DROPS_BY_DST_PORT, &DROPS_BY_REASON, bytes);
+        emit_drop_event(
+            raw_ctx,
+            &DROP_CONTEXT,
+            &DROP_EVENTS,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+            bytes,
+        );
+    } else if action == xdp_action::XDP_PASS {
+        sample_packet(
+            raw_ctx,
+            bytes,
+            &SAMPLE_CONFIG,
+            &SAMPLES,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+        );
+    }
+
+    if let Some(elapsed) = latency_elapsed(started) {
+        record_latency(&LATENCY, elapsed, get_config().protection_level);
+    }
+
+    observe_verdict(&OBSERVE, action)
 }
 
 #[inline(always)]
@@ -368,16 +437,13 @@ fn try_xdp_http(ctx: XdpContext) -> Result<u32, ()> {
     let data_end = ctx.data_end();
 
     // Parse Ethernet header
-    if data + mem::size_of::<EthHdr>() > data_end {
+    let Some((eth_proto, l3_offset)) = parse_eth(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let eth = unsafe { &*(data as *const EthHdr) };
-    let eth_proto = u16::from_be(eth.h_proto);
+    };
 
     match eth_proto {
-        ETH_P_IP => process_ipv4(&ctx, data + mem::size_of::<EthHdr>(), data_end, &config),
-        ETH_P_IPV6 => process_ipv6(&ctx, data + mem::size_of::<EthHdr>(), data_end, &config),
+        ETH_P_IP => process_ipv4(&ctx, l3_offset, data_end, &config),
+        ETH_P_IPV6 => process_ipv6(&ctx, l3_offset, data_end, &config),
         _ => Ok(xdp_action::XDP_PASS),
     }
 }
@@ -393,11 +459,16 @@ fn process_ipv4(
     data_end: usize,
     config: &HttpConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv4Hdr>() > data_end {
+    let Some((ip, tcp_data)) = parse_ipv4(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
+    };
 
-    let ip = unsafe { &*(data as *const Ipv4Hdr) };
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        ip.protocol,
+        peek_dst_port(tcp_data, data_end, ip.protocol),
+    );
+    drop_context_set_source_v4(&DROP_CONTEXT, ip.saddr);
 
     // Only process TCP
     if ip.protocol != IPPROTO_TCP {
@@ -417,10 +488,16 @@ fn process_ipv4(
         return Ok(xdp_action::XDP_DROP);
     }
 
-    let ihl = (ip.version_ihl & 0x0f) as usize * 4;
-    let tcp_data = data + ihl;
-
-    process_tcp_http(ctx, tcp_data, data_end, src_ip, config)
+    process_tcp_http(
+        ctx,
+        tcp_data,
+        data_end,
+        &HTTP_RATE_LIMITS,
+        &src_ip,
+        &penalty_key_v4(src_ip),
+        src_ip,
+        config,
+    )
 }
 
 // ============================================================================
@@ -434,50 +511,78 @@ fn process_ipv6(
     data_end: usize,
     config: &HttpConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv6Hdr>() > data_end {
+    // Skip extension headers (hop-by-hop, routing, fragment, ...) to reach TCP
+    let Some((ip6, l4)) = parse_ipv6_with_ext(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
+    };
 
-    let ip6 = unsafe { &*(data as *const Ipv6Hdr) };
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        l4.protocol,
+        peek_dst_port(l4.offset, data_end, l4.protocol),
+    );
+    drop_context_set_source_v6(&DROP_CONTEXT, &ip6.saddr);
 
     // Only process TCP
-    if ip6.nexthdr != IPPROTO_TCP {
+    if l4.protocol != IPPROTO_TCP {
+        return Ok(xdp_action::XDP_PASS);
+    }
+
+    // Non-first fragments carry no TCP header to inspect
+    if l4.is_fragment && !l4.is_first_fragment {
         return Ok(xdp_action::XDP_PASS);
     }
 
     let src_ip = ip6.saddr;
 
+    // Check whitelist
+    if unsafe { HTTP_WHITELIST_V6.get(&src_ip) }.is_some() {
+        return Ok(xdp_action::XDP_PASS);
+    }
+
     // Check if IP is blocked
     if is_ip_blocked_v6(&src_ip) {
         update_stats_blocked();
         return Ok(xdp_action::XDP_DROP);
     }
 
-    let tcp_data = data + mem::size_of::<Ipv6Hdr>();
-
-    // For IPv6, we use a simplified check - convert to u32 key for connection tracking
-    let ip_key = u32::from_be_bytes([src_ip[12], src_ip[13], src_ip[14], src_ip[15]]);
-
-    process_tcp_http(ctx, tcp_data, data_end, ip_key, config)
+    let tcp_data = l4.offset;
+
+    // Rate limits are keyed on the full address; connection tracking only
+    // needs a 32-bit digest
+    process_tcp_http(
+        ctx,
+        tcp_data,
+        data_end,
+        &HTTP_RATE_LIMITS_V6,
+        &src_ip,
+        &src_ip,
+        hash_ipv6_addr(&src_ip),
+        config,
+    )
 }
 
 // ============================================================================
 // TCP/HTTP Processing
 // ============================================================================
 
+/// `src_key` indexes `rate_limits` (the per-IP map for the packet's address
+/// family) and `penalty_key` the shared `PENALTY` map; `src_ip` is a 32-bit
+/// digest of the source used for connection keys.
 #[inline(always)]
-fn process_tcp_http(
-    _ctx: &XdpContext,
+fn process_tcp_http<K>(
+    ctx: &XdpContext,
     data: usize,
     data_end: usize,
+    rate_limits: &LruHashMap<K, HttpRateLimit>,
+    src_key: &K,
+    penalty_key: &[u8; 16],
     src_ip: u32,
     config: &HttpConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<TcpHdr>() > data_end {
+    let Some((tcp, payload_start)) = parse_tcp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let tcp = unsafe { &*(data as *const TcpHdr) };
+    };
     let dst_port = u16::from_be(tcp.dest);
     let src_port = u16::from_be(tcp.source);
 
@@ -501,24 +606,22 @@ fn process_tcp_http(
     update_stats_total();
 
     // Check rate limit first
-    if !check_rate_limit_v4(src_ip, config) {
+    if !check_rate_limit(rate_limits, src_key, penalty_key, config) {
         update_stats_rate_limited();
         return Ok(xdp_action::XDP_DROP);
     }
 
-    // Calculate TCP payload
-    let tcp_header_len = ((u16::from_be(tcp.doff_flags) >> 12) & 0x0f) as usize * 4;
-    let payload_start = data + tcp_header_len;
-
-    if payload_start >= data_end {
+    // Payload may continue past data_end on multi-buffer frames
+    let Some((payload, payload_len)) = payload_view(
+        ctx.ctx,
+        ctx.data(),
+        data_end,
+        payload_start,
+        &PAYLOAD_SCRATCH,
+    ) else {
         // No payload (SYN, ACK, FIN, etc.) - pass through
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let payload_len = data_end - payload_start;
-    if payload_len == 0 {
-        return Ok(xdp_action::XDP_PASS);
-    }
+    };
 
     // Connection tracking key
     let conn_key = make_connection_key(src_ip, src_port, dst_port);
@@ -528,9 +631,7 @@ fn process_tcp_http(
     let _conn_state = get_or_create_connection(conn_key, now);
 
     // Validate HTTP request payload
-    let payload = unsafe {
-        core::slice::from_raw_parts(payload_start as *const u8, core::cmp::min(payload_len, 512))
-    };
+    let payload = &payload[..core::cmp::min(payload.len(), 512)];
 
     // Check for HTTP/2 preface or existing HTTP/2 connection
     if payload_len >= 24 && is_http2_preface(payload) {
@@ -544,7 +645,16 @@ fn process_tcp_http(
     if let Some(h2_state) = unsafe { HTTP2_CONNECTIONS.get(&conn_key) } {
         if h2_state.preface_seen == 1 {
             // Process HTTP/2 frames
-            return process_http2_frames(payload, payload_len, conn_key, src_ip, config, now);
+            return process_http2_frames(
+                payload,
+                payload_len,
+                conn_key,
+                rate_limits,
+                src_key,
+                penalty_key,
+                config,
+                now,
+            );
         }
     }
 
@@ -571,7 +681,7 @@ fn process_tcp_http(
             if elapsed > max_header_time {
                 // Slow loris detected
                 state.flags |= FLAG_SLOW_HEADERS;
-                block_ip_v4(src_ip, config.block_duration_ns);
+                penalize(rate_limits, src_key, penalty_key, config);
                 update_stats_slow_loris();
                 return Ok(xdp_action::XDP_DROP);
             }
@@ -615,7 +725,7 @@ fn process_tcp_http(
                 // Check if body transfer is taking too long
                 if body_elapsed > max_body_time {
                     state.flags |= FLAG_SLOW_BODY;
-                    block_ip_v4(src_ip, config.block_duration_ns);
+                    penalize(rate_limits, src_key, penalty_key, config);
                     update_stats_slow_post();
                     return Ok(xdp_action::XDP_DROP);
                 }
@@ -639,7 +749,7 @@ fn process_tcp_http(
                     if actual_rate < min_rate && state.body_bytes_received < state.content_length {
                         // Transfer rate too slow - likely slow POST attack
                         state.flags |= FLAG_SLOW_BODY;
-                        block_ip_v4(src_ip, config.block_duration_ns);
+                        penalize(rate_limits, src_key, penalty_key, config);
                         update_stats_slow_post();
                         return Ok(xdp_action::XDP_DROP);
                     }
@@ -669,20 +779,29 @@ fn process_tcp_http(
                 state.state = 2; // Headers phase
                 state.request_count += 1;
             }
+            // DNS-over-HTTPS requests have their own budget and counters
+            if is_doh_request(payload, method) {
+                update_stats_doh();
+                if !check_doh_budget(penalty_key, now) {
+                    update_stats_doh_rate_limited();
+                    return Ok(xdp_action::XDP_DROP);
+                }
+                return Ok(xdp_action::XDP_PASS);
+            }
             update_stats_passed();
             Ok(xdp_action::XDP_PASS)
         }
         HttpValidation::InvalidMethod => {
             update_stats_invalid_method();
             if config.protection_level >= 2 {
-                block_ip_v4(src_ip, config.block_duration_ns);
+                penalize(rate_limits, src_key, penalty_key, config);
             }
             Ok(xdp_action::XDP_DROP)
         }
         HttpValidation::InvalidRequest => {
             update_stats_invalid();
             if config.protection_level >= 3 {
-                block_ip_v4(src_ip, (config.block_duration_ns >> 1));
+                penalize(rate_limits, src_key, penalty_key, config);
             }
             Ok(xdp_action::XDP_DROP)
         }
@@ -694,7 +813,7 @@ fn process_tcp_http(
                 state.flags |= FLAG_SMUGGLING_DETECTED;
             }
             // Block IP for longer duration - smuggling is a serious attack
-            block_ip_v4(src_ip, config.block_duration_ns << 1);
+            penalize(rate_limits, src_key, penalty_key, config);
             Ok(xdp_action::XDP_DROP)
         }
         HttpValidation::Suspicious => {
@@ -759,11 +878,13 @@ fn get_or_create_http2_connection(conn_key: u64, now: u64) -> Http2ConnectionSta
 }
 
 #[inline(always)]
-fn process_http2_frames(
+fn process_http2_frames<K>(
     payload: &[u8],
     payload_len: usize,
     conn_key: u64,
-    src_ip: u32,
+    rate_limits: &LruHashMap<K, HttpRateLimit>,
+    src_key: &K,
+    penalty_key: &[u8; 16],
     config: &HttpConfig,
     now: u64,
 ) -> Result<u32, ()> {
@@ -831,7 +952,7 @@ fn process_http2_frames(
 
                 if h2_state.streams_opened > max_streams {
                     update_stats_http2_control_flood();
-                    block_ip_v4(src_ip, config.block_duration_ns);
+                    penalize(rate_limits, src_key, penalty_key, config);
                     return Ok(xdp_action::XDP_DROP);
                 }
             }
@@ -863,7 +984,7 @@ fn process_http2_frames(
 
                 if h2_state.rst_stream_count > max_rst {
                     update_stats_http2_rapid_reset();
-                    block_ip_v4(src_ip, config.block_duration_ns << 1); // Longer block for rapid reset
+                    penalize(rate_limits, src_key, penalty_key, config);
                     return Ok(xdp_action::XDP_DROP);
                 }
 
@@ -871,17 +992,15 @@ fn process_http2_frames(
                 // If we see 10+ rapid HEADERS→RST pairs, this is almost certainly an attack
                 if h2_state.headers_rst_pairs > 10 {
                     update_stats_http2_rapid_reset();
-                    block_ip_v4(src_ip, config.block_duration_ns << 2); // Even longer block
+                    penalize(rate_limits, src_key, penalty_key, config);
                     return Ok(xdp_action::XDP_DROP);
                 }
 
                 // Detection 3: Ratio heuristic - more resets than opens is suspicious
                 // Only trigger after enough samples to avoid false positives
-                if h2_state.streams_reset > h2_state.streams_opened
-                    && h2_state.streams_reset > 20
-                {
+                if h2_state.streams_reset > h2_state.streams_opened && h2_state.streams_reset > 20 {
                     update_stats_http2_rapid_reset();
-                    block_ip_v4(src_ip, config.block_duration_ns << 1);
+                    penalize(rate_limits, src_key, penalty_key, config);
                     return Ok(xdp_action::XDP_DROP);
                 }
             }
@@ -918,7 +1037,7 @@ fn process_http2_frames(
 
         if h2_state.control_frame_count > max_control_frames {
             update_stats_http2_control_flood();
-            block_ip_v4(src_ip, config.block_duration_ns);
+            penalize(rate_limits, src_key, penalty_key, config);
             return Ok(xdp_action::XDP_DROP);
         }
     }
@@ -1585,6 +1704,38 @@ fn get_method_length(method: u8) -> usize {
     }
 }
 
+/// Whether the request line asks for the DNS-over-HTTPS path, with a query
+/// string (GET) or without (POST)
+#[inline(always)]
+fn is_doh_request(payload: &[u8], method: u8) -> bool {
+    if method != HTTP_METHOD_GET && method != HTTP_METHOD_POST {
+        return false;
+    }
+    let path_start = get_method_length(method) + 1;
+    let path_end = path_start + dns::DOH_PATH.len();
+    if path_end >= payload.len() {
+        return false;
+    }
+    for i in 0..dns::DOH_PATH.len() {
+        if payload[path_start + i] != dns::DOH_PATH[i] {
+            return false;
+        }
+    }
+    matches!(payload[path_end], b' ' | b'?')
+}
+
+/// Count a DNS-over-HTTPS request against the client's budget; false means
+/// the client is over it
+#[inline(always)]
+fn check_doh_budget(src_addr: &[u8; 16], now: u64) -> bool {
+    match DNS_BUDGET.get(0) {
+        Some(budget) if budget.doh_qps != 0 => {
+            dns_budget_take(&DNS_RATES, src_addr, budget.doh_qps as u64, 1, now)
+        }
+        _ => true,
+    }
+}
+
 #[inline(always)]
 fn check_suspicious_path(path: &[u8]) -> bool {
     let scan_limit = core::cmp::min(path.len(), 128);
@@ -1634,7 +1785,12 @@ fn is_http2_preface(payload: &[u8]) -> bool {
 // ============================================================================
 
 #[inline(always)]
-fn check_rate_limit_v4(src_ip: u32, config: &HttpConfig) -> bool {
+fn check_rate_limit<K>(
+    rate_limits: &LruHashMap<K, HttpRateLimit>,
+    src_key: &K,
+    penalty_key: &[u8; 16],
+    config: &HttpConfig,
+) -> bool {
     let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
     let window_size = if config.window_size_ns != 0 {
         config.window_size_ns
@@ -1647,7 +1803,11 @@ fn check_rate_limit_v4(src_ip: u32, config: &HttpConfig) -> bool {
         DEFAULT_MAX_REQUESTS_PER_WINDOW as u64
     };
 
-    if let Some(rate) = unsafe { HTTP_RATE_LIMITS.get_ptr_mut(&src_ip) } {
+    // Sources with a penalty get a smaller limit
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let max_requests = max_requests / penalty_divisor(&PENALTY, penalty_key, &ladder, now);
+
+    if let Some(rate) = unsafe { rate_limits.get_ptr_mut(src_key) } {
         let rate = unsafe { &mut *rate };
 
         // Check if in new window
@@ -1664,8 +1824,15 @@ fn check_rate_limit_v4(src_ip: u32, config: &HttpConfig) -> bool {
             // Rate exceeded - consider blocking
             rate.errors += 1;
             if rate.errors > 10 {
-                // Persistent rate limit violation - block
-                rate.blocked_until = now + config.block_duration_ns;
+                // Persistent rate limit violation - escalate
+                rate.blocked_until = record_violation(
+                    &DROP_CONTEXT,
+                    &PENALTY,
+                    penalty_key,
+                    &ladder,
+                    now,
+                    rate.window_start,
+                );
             }
             return false;
         }
@@ -1681,15 +1848,18 @@ fn check_rate_limit_v4(src_ip: u32, config: &HttpConfig) -> bool {
             slow_requests: 0,
             blocked_until: 0,
         };
-        let _ = HTTP_RATE_LIMITS.insert(&src_ip, &rate, 0);
+        let _ = rate_limits.insert(src_key, &rate, 0);
         true
     }
 }
 
 #[inline(always)]
 fn is_ip_blocked_v4(src_ip: u32) -> bool {
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    if penalty_blocked(&PENALTY, &penalty_key_v4(src_ip), now) {
+        return true;
+    }
     if let Some(rate) = unsafe { HTTP_RATE_LIMITS.get(&src_ip) } {
-        let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
         rate.blocked_until > now
     } else {
         false
@@ -1698,25 +1868,44 @@ fn is_ip_blocked_v4(src_ip: u32) -> bool {
 
 #[inline(always)]
 fn is_ip_blocked_v6(src_ip: &[u8; 16]) -> bool {
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    if penalty_blocked(&PENALTY, src_ip, now) {
+        return true;
+    }
     if let Some(rate) = unsafe { HTTP_RATE_LIMITS_V6.get(src_ip) } {
-        let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
         rate.blocked_until > now
     } else {
         false
     }
 }
 
+/// Record a violation of a source and apply the block it earned, if any
 #[inline(always)]
-fn block_ip_v4(src_ip: u32, duration_ns: u64) {
+fn penalize<K>(
+    rate_limits: &LruHashMap<K, HttpRateLimit>,
+    src_key: &K,
+    penalty_key: &[u8; 16],
+    config: &HttpConfig,
+) {
     let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
-    let block_until = now
-        + if duration_ns != 0 {
-            duration_ns
-        } else {
-            DEFAULT_BLOCK_DURATION_NS
-        };
-
-    if let Some(rate) = unsafe { HTTP_RATE_LIMITS.get_ptr_mut(&src_ip) } {
+    let window_start = match unsafe { rate_limits.get(src_key) } {
+        Some(rate) => rate.window_start,
+        None => now,
+    };
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let block_until = record_violation(
+        &DROP_CONTEXT,
+        &PENALTY,
+        penalty_key,
+        &ladder,
+        now,
+        window_start,
+    );
+    if block_until <= now {
+        return;
+    }
+
+    if let Some(rate) = unsafe { rate_limits.get_ptr_mut(src_key) } {
         let rate = unsafe { &mut *rate };
         rate.blocked_until = block_until;
     } else {
@@ -1728,7 +1917,7 @@ fn block_ip_v4(src_ip: u32, duration_ns: u64) {
             slow_requests: 0,
             blocked_until: block_until,
         };
-        let _ = HTTP_RATE_LIMITS.insert(&src_ip, &rate, 0);
+        let _ = rate_limits.insert(src_key, &rate, 0);
     }
 }
 
@@ -1819,6 +2008,7 @@ fn update_stats_passed() {
 
 #[inline(always)]
 fn update_stats_invalid_method() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_invalid_method += 1;
@@ -1828,6 +2018,7 @@ fn update_stats_invalid_method() {
 
 #[inline(always)]
 fn update_stats_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::HttpRateLimit);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_rate_limited += 1;
@@ -1837,6 +2028,7 @@ fn update_stats_rate_limited() {
 
 #[inline(always)]
 fn update_stats_slow_loris() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::HttpSlowAttack);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_slow_loris += 1;
@@ -1846,6 +2038,7 @@ fn update_stats_slow_loris() {
 
 #[inline(always)]
 fn update_stats_invalid() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_invalid_request += 1;
@@ -1855,6 +2048,7 @@ fn update_stats_invalid() {
 
 #[inline(always)]
 fn update_stats_blocked() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::BlockedIp);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_blocked_ip += 1;
@@ -1873,6 +2067,7 @@ fn update_stats_http2() {
 
 #[inline(always)]
 fn update_stats_slow_post() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::HttpSlowAttack);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_slow_post += 1;
@@ -1882,6 +2077,7 @@ fn update_stats_slow_post() {
 
 #[inline(always)]
 fn update_stats_http2_rapid_reset() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::HttpRateLimit);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_http2_rapid_reset += 1;
@@ -1891,6 +2087,7 @@ fn update_stats_http2_rapid_reset() {
 
 #[inline(always)]
 fn update_stats_http2_control_flood() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::HttpRateLimit);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_http2_control_flood += 1;
@@ -1927,6 +2124,7 @@ fn update_stats_http2_data() {
 
 #[inline(always)]
 fn update_stats_request_smuggling() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_request_smuggling += 1;
@@ -1936,6 +2134,7 @@ fn update_stats_request_smuggling() {
 
 #[inline(always)]
 fn update_stats_header_injection() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
     if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_header_injection += 1;
@@ -1943,6 +2142,25 @@ fn update_stats_header_injection() {
     }
 }
 
+#[inline(always)]
+fn update_stats_doh() {
+    if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).doh_requests += 1;
+        }
+    }
+}
+
+#[inline(always)]
+fn update_stats_doh_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::HttpRateLimit);
+    if let Some(stats) = unsafe { HTTP_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).dropped_doh_rate_limited += 1;
+        }
+    }
+}
+
 // ============================================================================
 // Panic Handler
 // ============================================================================
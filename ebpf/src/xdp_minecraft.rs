@@ -8,60 +8,27 @@
 #![no_main]
 
 use aya_ebpf::{
-    bindings::xdp_action,
+    bindings::{BPF_F_NO_PREALLOC, xdp_action},
     macros::{map, xdp},
-    maps::{LruHashMap, PerCpuArray},
+    maps::{Array, HashMap, LpmTrie, LruHashMap, LruPerCpuHashMap, PerCpuArray},
     programs::XdpContext,
 };
-use core::mem;
-
-// Network header structures (same as xdp_filter.rs)
-
-#[repr(C)]
-struct EthHdr {
-    h_dest: [u8; 6],
-    h_source: [u8; 6],
-    h_proto: u16,
-}
-
-#[repr(C)]
-struct Ipv4Hdr {
-    version_ihl: u8,
-    tos: u8,
-    tot_len: u16,
-    id: u16,
-    frag_off: u16,
-    ttl: u8,
-    protocol: u8,
-    check: u16,
-    saddr: u32,
-    daddr: u32,
-}
-
-#[repr(C)]
-struct TcpHdr {
-    source: u16,
-    dest: u16,
-    seq: u32,
-    ack_seq: u32,
-    doff_flags: u16,
-    window: u16,
-    check: u16,
-    urg_ptr: u16,
-}
-
-#[repr(C)]
-struct UdpHdr {
-    source: u16,
-    dest: u16,
-    len: u16,
-    check: u16,
-}
+use pistonprotection_ebpf::{
+    BlockReason, DropContext, DropCounter, EventBudget, EventBudgetConfig, HandshakeRecord,
+    LatencyBucket, LatencyConfig, PayloadScratch, PenaltyConfig, PenaltyEntry, SampleConfig,
+    breakdown::{DST_PORT_MAX_ENTRIES, REASON_BUCKETS},
+    cgnat, drop_context_reset, drop_context_set_reason, drop_context_set_source_v4,
+    drop_context_set_target, drop_events, emit_drop_event, frame_len, known_good, latency,
+    latency_elapsed, latency_start, limit_multiplier, observe_verdict, parse_eth, parse_ipv4,
+    parse_tcp, parse_udp, payload_view, peek_dst_port, penalty, penalty_blocked, penalty_config,
+    penalty_key_v4, record_drop, record_handshake, record_latency, record_violation, sample_packet,
+    sampling,
+};
 
 /// Minecraft connection state
 #[repr(C)]
 pub struct McConnectionState {
-    pub state: u8, // 0=none, 1=status, 2=login, 3=configuration, 4=play, 5=transfer
+    pub state: u8,         // 0=none, 1=status, 2=login, 3=configuration, 4=play, 5=transfer
     pub _padding: [u8; 3], // Alignment padding
     pub protocol_version: u32,
     pub packets: u64,
@@ -99,7 +66,6 @@ pub struct McConfig {
 
 // Protection level constants
 const PROTECTION_LOW: u16 = 0;
-#[allow(dead_code)]
 const PROTECTION_MEDIUM: u16 = 1;
 const PROTECTION_HIGH: u16 = 2;
 
@@ -199,6 +165,11 @@ pub struct BedrockRateState {
     pub blocked_until: u64,
 }
 
+/// Split packets being reassembled per Bedrock connection (keyed by src_ip:src_port)
+#[map]
+static MC_BEDROCK_SPLITS: LruHashMap<u64, BedrockSplitState> =
+    LruHashMap::with_max_entries(100_000, 0);
+
 /// Per-IP connection counts
 #[map]
 static MC_IP_COUNTS: LruHashMap<u32, IpConnectionCount> =
@@ -208,6 +179,80 @@ static MC_IP_COUNTS: LruHashMap<u32, IpConnectionCount> =
 #[map]
 static MC_STATUS_RATE: LruHashMap<u32, u64> = LruHashMap::with_max_entries(100_000, 0);
 
+/// Drops broken down by (protocol, destination port)
+#[map]
+static DROPS_BY_DST_PORT: LruPerCpuHashMap<u32, DropCounter> =
+    LruPerCpuHashMap::with_max_entries(DST_PORT_MAX_ENTRIES, 0);
+
+/// Drops broken down by BlockReason
+#[map]
+static DROPS_BY_REASON: PerCpuArray<DropCounter> = PerCpuArray::with_max_entries(REASON_BUCKETS, 0);
+
+/// Per-CPU drop attribution for the packet being processed
+#[map]
+static DROP_CONTEXT: PerCpuArray<DropContext> = PerCpuArray::with_max_entries(1, 0);
+
+/// Set while the worker is in observe mode, shared with the other programs
+#[map(name = "OBSERVE")]
+static OBSERVE: Array<u32> = Array::pinned(1, 0);
+
+/// Flow sampling configuration
+#[map]
+static SAMPLE_CONFIG: PerCpuArray<SampleConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Sampled packet headers for the userspace analyzer
+#[map]
+static SAMPLES: sampling::SampleMap = sampling::sample_map();
+
+/// Dropped packets for the userspace drop event aggregator
+#[map]
+static DROP_EVENTS: drop_events::EventMap = drop_events::event_map();
+
+/// Event budget of each CPU, written by userspace
+#[map]
+static EVENT_BUDGET_CONFIG: PerCpuArray<EventBudgetConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Event token buckets and per-class event counters
+#[map]
+static EVENT_BUDGET: PerCpuArray<EventBudget> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing latency measurement configuration
+#[map]
+static LATENCY_CONFIG: PerCpuArray<LatencyConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing time histograms, per protection level
+#[map]
+static LATENCY: PerCpuArray<LatencyBucket> = PerCpuArray::with_max_entries(latency::MAX_ENTRIES, 0);
+
+/// Copy of the inspected payload when it spans multi-buffer fragments
+#[map]
+static PAYLOAD_SCRATCH: PerCpuArray<PayloadScratch> = PerCpuArray::with_max_entries(1, 0);
+
+/// Penalties of repeat offenders, shared with the other programs
+#[map(name = "PENALTY")]
+static PENALTY: LruHashMap<[u8; 16], PenaltyEntry> = LruHashMap::pinned(penalty::MAX_SOURCES, 0);
+
+/// Penalty ladder per protection level, shared with the other programs
+#[map(name = "PENALTY_CONFIG")]
+static PENALTY_CONFIG: Array<PenaltyConfig> = Array::pinned(penalty::LEVELS, 0);
+
+/// Set while a backend is learning its known-good sources, shared with the other programs
+#[map(name = "LEARNING")]
+static LEARNING: Array<u32> = Array::pinned(1, 0);
+
+/// Sources that completed handshakes while learning, shared with the other programs
+#[map(name = "HANDSHAKES")]
+static HANDSHAKES: LruHashMap<[u8; 16], HandshakeRecord> =
+    LruHashMap::pinned(known_good::MAX_HANDSHAKES, 0);
+
+/// Known-good sources and their limit multiplier, shared with the other programs
+#[map(name = "KNOWN_GOOD")]
+static KNOWN_GOOD: HashMap<[u8; 16], u32> = HashMap::pinned(known_good::MAX_SOURCES, 0);
+
+/// CGNAT ranges and their limit multiplier, shared with the other programs
+#[map(name = "CGNAT_RANGES")]
+static CGNAT_RANGES: LpmTrie<[u8; 16], u32> = LpmTrie::pinned(cgnat::MAX_RANGES, BPF_F_NO_PREALLOC);
+
 /// Configuration
 #[map]
 static MC_CONFIG: PerCpuArray<McConfig> = PerCpuArray::with_max_entries(1, 0);
@@ -228,12 +273,45 @@ const MC_JAVA_PORT: u16 = 25565;
 const MC_BEDROCK_PORT: u16 = 19132;
 
 /// Main XDP Minecraft filter
-#[xdp]
+#[cfg_attr(feature = "frags", xdp(frags))]
+#[cfg_attr(not(feature = "frags"), xdp)]
 pub fn xdp_minecraft(ctx: XdpContext) -> u32 {
-    match try_xdp_minecraft(ctx) {
+    let started = latency_start(&LATENCY_CONFIG);
+    drop_context_reset(&DROP_CONTEXT, BlockReason::InvalidMinecraft);
+    let bytes = frame_len(ctx.ctx) as u64;
+    let raw_ctx = ctx.ctx;
+
+    let action = match try_xdp_minecraft(ctx) {
         Ok(ret) => ret,
         Err(_) => xdp_action::XDP_PASS,
+    };
+
+    if action == xdp_action::XDP_DROP {
+        record_drop(&DROP_CONTEXT, &DROPS_BY_DST_PORT, &DROPS_BY_REASON, bytes);
+        emit_drop_event(
+            raw_ctx,
+            &DROP_CONTEXT,
+            &DROP_EVENTS,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+            bytes,
+        );
+    } else if action == xdp_action::XDP_PASS {
+        sample_packet(
+            raw_ctx,
+            bytes,
+            &SAMPLE_CONFIG,
+            &SAMPLES,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+        );
+    }
+
+    if let Some(elapsed) = latency_elapsed(started) {
+        record_latency(&LATENCY, elapsed, shared_protection_level());
     }
+
+    observe_verdict(&OBSERVE, action)
 }
 
 #[inline(always)]
@@ -242,31 +320,29 @@ fn try_xdp_minecraft(ctx: XdpContext) -> Result<u32, ()> {
     let data_end = ctx.data_end();
 
     // Parse Ethernet header
-    if data + mem::size_of::<EthHdr>() > data_end {
+    let Some((eth_proto, l3_offset)) = parse_eth(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let eth = unsafe { &*(data as *const EthHdr) };
-    let eth_proto = u16::from_be(eth.h_proto);
+    };
 
     if eth_proto != ETH_P_IP {
         return Ok(xdp_action::XDP_PASS);
     }
 
-    let ip_data = data + mem::size_of::<EthHdr>();
-
-    // Parse IPv4 header
-    if ip_data + mem::size_of::<Ipv4Hdr>() > data_end {
+    let Some((ip, transport_data)) = parse_ipv4(l3_offset, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let ip = unsafe { &*(ip_data as *const Ipv4Hdr) };
+    };
     let src_ip = u32::from_be(ip.saddr);
-    let ihl = (ip.version_ihl & 0x0f) as usize * 4;
-    let transport_data = ip_data + ihl;
+    let dst_ip = u32::from_be(ip.daddr);
+
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        ip.protocol,
+        peek_dst_port(transport_data, data_end, ip.protocol),
+    );
+    drop_context_set_source_v4(&DROP_CONTEXT, ip.saddr);
 
     match ip.protocol {
-        IPPROTO_TCP => process_minecraft_java(&ctx, transport_data, data_end, src_ip),
+        IPPROTO_TCP => process_minecraft_java(&ctx, transport_data, data_end, src_ip, dst_ip),
         IPPROTO_UDP => process_minecraft_bedrock(&ctx, transport_data, data_end, src_ip),
         _ => Ok(xdp_action::XDP_PASS),
     }
@@ -274,16 +350,15 @@ fn try_xdp_minecraft(ctx: XdpContext) -> Result<u32, ()> {
 
 #[inline(always)]
 fn process_minecraft_java(
-    _ctx: &XdpContext,
+    ctx: &XdpContext,
     data: usize,
     data_end: usize,
     src_ip: u32,
+    dst_ip: u32,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<TcpHdr>() > data_end {
+    let Some((tcp, payload_start)) = parse_tcp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let tcp = unsafe { &*(data as *const TcpHdr) };
+    };
     let dst_port = u16::from_be(tcp.dest);
     let src_port = u16::from_be(tcp.source);
 
@@ -344,16 +419,17 @@ fn process_minecraft_java(
     // Run periodic cleanup of stale states
     maybe_run_cleanup(src_ip, now);
 
-    // Calculate TCP data offset
-    let tcp_header_len = ((u16::from_be(tcp.doff_flags) >> 12) & 0x0f) as usize * 4;
-    let payload_start = data + tcp_header_len;
-
-    if payload_start >= data_end {
+    // Payload may continue past data_end on multi-buffer frames
+    let Some((payload, payload_len)) = payload_view(
+        ctx.ctx,
+        ctx.data(),
+        data_end,
+        payload_start,
+        &PAYLOAD_SCRATCH,
+    ) else {
         // No payload - pass through (SYN, ACK, etc.)
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let payload_len = data_end - payload_start;
+    };
     if payload_len < 3 {
         return Ok(xdp_action::XDP_PASS);
     }
@@ -361,9 +437,6 @@ fn process_minecraft_java(
     // Build connection key for state tracking (IP in upper 32 bits, port in lower 16)
     let connection_key = ((src_ip as u64) << 32) | (src_port as u64);
 
-    // Parse VarInt length prefix
-    let payload = unsafe { core::slice::from_raw_parts(payload_start as *const u8, payload_len) };
-
     // Read packet length VarInt
     let (packet_len, len_bytes) = match read_varint(payload) {
         Some(v) => v,
@@ -485,7 +558,7 @@ fn process_minecraft_java(
                 if extra_bytes > 0 && pending_bytes < payload_len {
                     // Calculate offset to the extra data (start of new packet)
                     let extra_start = pending_bytes;
-                    if extra_start < payload_len {
+                    if extra_start < payload.len() {
                         // Try to read the new packet's length VarInt
                         let extra_data = &payload[extra_start..];
                         if let Some((new_pkt_len, new_len_bytes)) = read_varint(extra_data) {
@@ -705,6 +778,9 @@ fn process_minecraft_java(
                     state.bytes += payload_len as u64;
                     state.last_seen = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
                 }
+                // Login Success and Login Acknowledged will be encrypted; answering
+                // the server's Encryption Request is the last step we can see
+                record_login(src_ip, dst_ip, dst_port, now);
                 return Ok(xdp_action::XDP_PASS);
             }
 
@@ -722,6 +798,8 @@ fn process_minecraft_java(
                     state.bytes += payload_len as u64;
                     state.last_seen = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
                 }
+                // The client acknowledged Login Success: the login is complete
+                record_login(src_ip, dst_ip, dst_port, now);
                 return Ok(xdp_action::XDP_PASS);
             }
 
@@ -1154,6 +1232,7 @@ fn cleanup_stale_bedrock_connection(connection_key: &u64, now: u64) -> bool {
                 state.bytes_in = 0;
                 state.bytes_out_estimate = 0;
             }
+            let _ = MC_BEDROCK_SPLITS.remove(connection_key);
             return true;
         }
     }
@@ -1247,6 +1326,39 @@ const RAKNET_NAK_FLOOD_THRESHOLD: u32 = 100; // Max NAK packets per second per I
 const RAKNET_MAX_ACK_RECORDS: u16 = 500; // Max ACK/NACK records per packet (DoS protection)
 const RAKNET_MAX_NAK_SEQUENCE_RANGE: u32 = 1000; // Max sequence range in a single NAK record
 
+/// RakNet split packet (fragment) limits per connection
+/// The server buffers the fragments of a split packet until all of them
+/// arrive, so many split IDs, huge split counts or a fragment flood tie up
+/// reassembly memory without ever completing a packet
+const RAKNET_MAX_SPLIT_COUNT: u32 = 128; // Max fragments of one split packet
+const RAKNET_MAX_OUTSTANDING_SPLITS: usize = 4; // Max split packets reassembled at once
+const RAKNET_MAX_REASSEMBLY_BYTES: u32 = 256 * 1024; // Max fragment bytes buffered
+const RAKNET_FRAGMENT_FLOOD_THRESHOLD: u32 = 1000; // Max fragments per second per connection
+const RAKNET_SPLIT_TIMEOUT_NS: u64 = 10_000_000_000; // Incomplete split packets expire after 10s
+
+/// Split packets being reassembled on a Bedrock connection
+///
+/// Completion is inferred from the fragment count, so retransmitted
+/// fragments may free a slot early; the limits only need to bound what the
+/// server buffers.
+#[repr(C)]
+pub struct BedrockSplitState {
+    /// First fragment timestamp per slot
+    pub started: [u64; RAKNET_MAX_OUTSTANDING_SPLITS],
+    /// Fragments still missing per slot (0 = slot free)
+    pub remaining: [u32; RAKNET_MAX_OUTSTANDING_SPLITS],
+    /// Fragment bytes buffered per slot
+    pub bytes: [u32; RAKNET_MAX_OUTSTANDING_SPLITS],
+    /// Split ID per slot
+    pub split_ids: [u16; RAKNET_MAX_OUTSTANDING_SPLITS],
+    /// Fragments in current window
+    pub fragment_count: u32,
+    /// Padding for alignment
+    pub _padding: u32,
+    /// Window start timestamp
+    pub window_start: u64,
+}
+
 /// RakNet connection state for Bedrock
 #[repr(C)]
 pub struct BedrockConnectionState {
@@ -1302,16 +1414,14 @@ const BEDROCK_FLAG_COOKIE_VALIDATED: u32 = 0x0010; // Security cookie verified
 /// - MTU validation to prevent oversized responses
 #[inline(always)]
 fn process_minecraft_bedrock(
-    _ctx: &XdpContext,
+    ctx: &XdpContext,
     data: usize,
     data_end: usize,
     src_ip: u32,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<UdpHdr>() > data_end {
+    let Some((udp, payload_start)) = parse_udp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let udp = unsafe { &*(data as *const UdpHdr) };
+    };
     let dst_port = u16::from_be(udp.dest);
     let src_port = u16::from_be(udp.source);
     let udp_len = u16::from_be(udp.len) as usize;
@@ -1348,12 +1458,16 @@ fn process_minecraft_bedrock(
     // Run periodic cleanup of stale states
     maybe_run_cleanup(src_ip, now);
 
-    let payload_start = data + mem::size_of::<UdpHdr>();
-    if payload_start >= data_end {
+    // Payload may continue past data_end on multi-buffer frames
+    let Some((payload, payload_len)) = payload_view(
+        ctx.ctx,
+        ctx.data(),
+        data_end,
+        payload_start,
+        &PAYLOAD_SCRATCH,
+    ) else {
         return Ok(xdp_action::XDP_DROP);
-    }
-
-    let payload_len = data_end - payload_start;
+    };
     if payload_len < 1 {
         return Ok(xdp_action::XDP_DROP);
     }
@@ -1365,7 +1479,6 @@ fn process_minecraft_bedrock(
         return Ok(xdp_action::XDP_DROP);
     }
 
-    let payload = unsafe { core::slice::from_raw_parts(payload_start as *const u8, payload_len) };
     let packet_id = payload[0];
 
     // Build connection key for state tracking
@@ -1585,6 +1698,11 @@ fn process_minecraft_bedrock(
                         // Split packets need: frame header + length + reliable seq + split info
                         return Ok(xdp_action::XDP_DROP);
                     }
+
+                    // Reassembly limits: outstanding splits, buffered bytes, fragment rate
+                    if is_split && !check_bedrock_split_fragment(&connection_key, payload, now) {
+                        return Ok(xdp_action::XDP_DROP);
+                    }
                 }
 
                 // Update state
@@ -1644,7 +1762,7 @@ fn process_minecraft_bedrock(
             // CVE-2024-30249 style attack prevention
             let mut offset = 3usize;
             let mut record_idx = 0u16;
-            while record_idx < record_count && offset + 4 <= payload_len {
+            while record_idx < record_count && offset + 4 <= payload.len() {
                 let is_range = payload[offset] != 0;
                 let start_seq = (payload[offset + 1] as u32)
                     | ((payload[offset + 2] as u32) << 8)
@@ -1655,6 +1773,10 @@ fn process_minecraft_bedrock(
                     if offset + 7 > payload_len {
                         return Ok(xdp_action::XDP_DROP);
                     }
+                    if offset + 7 > payload.len() {
+                        // Rest of the record is past the inspected bytes
+                        break;
+                    }
                     let end_seq = (payload[offset + 4] as u32)
                         | ((payload[offset + 5] as u32) << 8)
                         | ((payload[offset + 6] as u32) << 16);
@@ -1804,6 +1926,7 @@ fn process_minecraft_bedrock(
             // Disconnect Notification
             // Clean up connection state
             // Can be as small as just the packet ID
+            let _ = MC_BEDROCK_SPLITS.remove(&connection_key);
         }
 
         0x00 => {
@@ -1871,8 +1994,8 @@ fn check_bedrock_rate_limit(src_ip: u32, packet_len: usize, is_ping: bool, now:
             state.bytes_out_estimate += 1000; // Conservative estimate
 
             if state.ping_count > RAKNET_PING_FLOOD_THRESHOLD {
-                // Ping flood detected - block IP
-                state.blocked_until = now + 60_000_000_000; // 60 second block
+                // Ping flood detected - escalate
+                state.blocked_until = penalize(src_ip, now, state.window_start);
                 return false;
             }
         } else {
@@ -1882,7 +2005,7 @@ fn check_bedrock_rate_limit(src_ip: u32, packet_len: usize, is_ping: bool, now:
 
             if state.conn_req_count > RAKNET_CONN_REQ_FLOOD_THRESHOLD {
                 // Connection request flood detected
-                state.blocked_until = now + 60_000_000_000;
+                state.blocked_until = penalize(src_ip, now, state.window_start);
                 return false;
             }
         }
@@ -1892,7 +2015,7 @@ fn check_bedrock_rate_limit(src_ip: u32, packet_len: usize, is_ping: bool, now:
             let ratio = state.bytes_out_estimate / state.bytes_in;
             if ratio > RAKNET_MAX_AMPLIFICATION_RATIO as u64 && state.bytes_out_estimate > 10000 {
                 // Excessive amplification detected
-                state.blocked_until = now + 120_000_000_000; // 2 minute block
+                state.blocked_until = penalize(src_ip, now, state.window_start);
                 return false;
             }
         }
@@ -1944,7 +2067,7 @@ fn check_bedrock_nak_rate_limit(src_ip: u32, now: u64) -> bool {
 
         // NAK flood protection - too many NAKs can cause retransmission flood
         if state.nak_count > RAKNET_NAK_FLOOD_THRESHOLD {
-            state.blocked_until = now + 60_000_000_000; // 60 second block
+            state.blocked_until = penalize(src_ip, now, state.window_start);
             return false;
         }
 
@@ -1966,6 +2089,134 @@ fn check_bedrock_nak_rate_limit(src_ip: u32, now: u64) -> bool {
     }
 }
 
+/// Check a split frame against the reassembly limits of its connection
+///
+/// `payload` is a frame set datagram whose first frame has the split flag.
+/// Frame layout: [flags] [length in bits (u16 BE)] [message index (3) if
+/// reliable] [sequence index (3) if sequenced] [order index (3) + channel
+/// (1) if ordered] [split count (u32 BE)] [split ID (u16 BE)] [split index
+/// (u32 BE)] [body]. Returns false if the fragment should be dropped.
+#[inline(always)]
+fn check_bedrock_split_fragment(connection_key: &u64, payload: &[u8], now: u64) -> bool {
+    let frame_header = payload[4];
+    let reliability = (frame_header >> 5) & 0x07;
+
+    let mut offset = 7usize;
+    if matches!(reliability, 2 | 3 | 4 | 6 | 7) {
+        offset += 3;
+    }
+    if matches!(reliability, 1 | 4) {
+        offset += 3;
+    }
+    if matches!(reliability, 1 | 3 | 4 | 7) {
+        offset += 4;
+    }
+    if offset + 10 > payload.len() {
+        // Split header past the inspected bytes (or truncated)
+        return false;
+    }
+
+    let fragment_bytes = ((u16::from_be_bytes([payload[5], payload[6]]) as u32) + 7) / 8;
+    let split_count = u32::from_be_bytes([
+        payload[offset],
+        payload[offset + 1],
+        payload[offset + 2],
+        payload[offset + 3],
+    ]);
+    let split_id = u16::from_be_bytes([payload[offset + 4], payload[offset + 5]]);
+    let split_index = u32::from_be_bytes([
+        payload[offset + 6],
+        payload[offset + 7],
+        payload[offset + 8],
+        payload[offset + 9],
+    ]);
+
+    if split_count == 0 || split_count > RAKNET_MAX_SPLIT_COUNT || split_index >= split_count {
+        return false;
+    }
+
+    let Some(splits) = (unsafe { MC_BEDROCK_SPLITS.get_ptr_mut(connection_key) }) else {
+        let mut splits = BedrockSplitState {
+            started: [0; RAKNET_MAX_OUTSTANDING_SPLITS],
+            remaining: [0; RAKNET_MAX_OUTSTANDING_SPLITS],
+            bytes: [0; RAKNET_MAX_OUTSTANDING_SPLITS],
+            split_ids: [0; RAKNET_MAX_OUTSTANDING_SPLITS],
+            fragment_count: 1,
+            _padding: 0,
+            window_start: now,
+        };
+        if fragment_bytes > RAKNET_MAX_REASSEMBLY_BYTES {
+            return false;
+        }
+        if split_count > 1 {
+            splits.started[0] = now;
+            splits.remaining[0] = split_count - 1;
+            splits.bytes[0] = fragment_bytes;
+            splits.split_ids[0] = split_id;
+        }
+        let _ = MC_BEDROCK_SPLITS.insert(connection_key, &splits, 0);
+        return true;
+    };
+    let splits = unsafe { &mut *splits };
+
+    // Fragment rate (1 second window)
+    if now.saturating_sub(splits.window_start) > 1_000_000_000 {
+        splits.window_start = now;
+        splits.fragment_count = 0;
+    }
+    splits.fragment_count += 1;
+    if splits.fragment_count > RAKNET_FRAGMENT_FLOOD_THRESHOLD {
+        return false;
+    }
+
+    // Expire incomplete split packets and find this fragment's slot
+    let mut slot = RAKNET_MAX_OUTSTANDING_SPLITS;
+    let mut free = RAKNET_MAX_OUTSTANDING_SPLITS;
+    let mut buffered = 0u32;
+    for i in 0..RAKNET_MAX_OUTSTANDING_SPLITS {
+        if splits.remaining[i] > 0
+            && now.saturating_sub(splits.started[i]) > RAKNET_SPLIT_TIMEOUT_NS
+        {
+            splits.remaining[i] = 0;
+            splits.bytes[i] = 0;
+        }
+        if splits.remaining[i] == 0 {
+            if free == RAKNET_MAX_OUTSTANDING_SPLITS {
+                free = i;
+            }
+        } else if splits.split_ids[i] == split_id {
+            slot = i;
+        }
+        buffered += splits.bytes[i];
+    }
+
+    if buffered + fragment_bytes > RAKNET_MAX_REASSEMBLY_BYTES {
+        return false;
+    }
+
+    if slot < RAKNET_MAX_OUTSTANDING_SPLITS {
+        splits.remaining[slot] -= 1;
+        splits.bytes[slot] = if splits.remaining[slot] == 0 {
+            // Reassembled - the server releases the buffer
+            0
+        } else {
+            splits.bytes[slot] + fragment_bytes
+        };
+    } else if free < RAKNET_MAX_OUTSTANDING_SPLITS {
+        if split_count > 1 {
+            splits.started[free] = now;
+            splits.remaining[free] = split_count - 1;
+            splits.bytes[free] = fragment_bytes;
+            splits.split_ids[free] = split_id;
+        }
+    } else {
+        // Too many split packets being reassembled
+        return false;
+    }
+
+    true
+}
+
 /// Track Bedrock connection state progression
 #[inline(always)]
 fn track_bedrock_connection(
@@ -2076,6 +2327,20 @@ fn check_raknet_magic(data: &[u8]) -> bool {
     true
 }
 
+/// Record a completed Java login for allowlist learning
+#[inline(always)]
+fn record_login(src_ip: u32, dst_ip: u32, dst_port: u16, now: u64) {
+    record_handshake(
+        &LEARNING,
+        &HANDSHAKES,
+        &penalty_key_v4(src_ip),
+        &penalty_key_v4(dst_ip),
+        dst_port,
+        known_good::PROTO_MINECRAFT,
+        now,
+    );
+}
+
 #[inline(always)]
 fn check_connection_limit(src_ip: u32) -> bool {
     let max_connections = if let Some(config) = unsafe { MC_CONFIG.get_ptr(0) } {
@@ -2084,9 +2349,14 @@ fn check_connection_limit(src_ip: u32) -> bool {
         10
     };
 
+    // Sources serving a block earned in any program
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    if penalty_blocked(&PENALTY, &penalty_key_v4(src_ip), now) {
+        return false;
+    }
+
     if let Some(count) = unsafe { MC_IP_COUNTS.get_ptr_mut(&src_ip) } {
         let count = unsafe { &mut *count };
-        let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
 
         // Check if blocked
         if count.blocked_until > now {
@@ -2103,9 +2373,11 @@ fn check_connection_limit(src_ip: u32) -> bool {
         count.count += 1;
         count.last_connection = now;
 
-        if count.count > max_connections {
-            // Block for 60 seconds
-            count.blocked_until = now + 60_000_000_000;
+        // Known-good sources and CGNAT addresses may hold more connections
+        let multiplier = limit_multiplier(&KNOWN_GOOD, &CGNAT_RANGES, &penalty_key_v4(src_ip));
+        if count.count as u64 > max_connections as u64 * multiplier {
+            // Counts reset after 60 idle seconds; escalate at most once a minute
+            count.blocked_until = penalize(src_ip, now, now.saturating_sub(60_000_000_000));
             return false;
         }
 
@@ -2113,7 +2385,7 @@ fn check_connection_limit(src_ip: u32) -> bool {
     } else {
         let entry = IpConnectionCount {
             count: 1,
-            last_connection: unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() },
+            last_connection: now,
             blocked_until: 0,
         };
         let _ = MC_IP_COUNTS.insert(&src_ip, &entry, 0);
@@ -2121,6 +2393,34 @@ fn check_connection_limit(src_ip: u32) -> bool {
     }
 }
 
+/// Protection level on the shared 1-4 scale
+///
+/// The low, medium and high protection levels map to levels 1-3.
+#[inline(always)]
+fn shared_protection_level() -> u32 {
+    if let Some(config) = unsafe { MC_CONFIG.get_ptr(0) } {
+        unsafe { &*config }.protection_level as u32 + 1
+    } else {
+        PROTECTION_MEDIUM as u32 + 1
+    }
+}
+
+/// Record a violation of a source and return the end of the block it earned
+/// (0 = only rate-limited)
+#[inline(always)]
+fn penalize(src_ip: u32, now: u64, window_start: u64) -> u64 {
+    let level = shared_protection_level();
+    let ladder = penalty_config(&PENALTY_CONFIG, level);
+    record_violation(
+        &DROP_CONTEXT,
+        &PENALTY,
+        &penalty_key_v4(src_ip),
+        &ladder,
+        now,
+        window_start,
+    )
+}
+
 #[inline(always)]
 fn check_status_rate_limit(src_ip: u32) -> bool {
     let rate_limit = if let Some(config) = unsafe { MC_CONFIG.get_ptr(0) } {
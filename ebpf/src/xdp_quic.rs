@@ -6,60 +6,31 @@
 //! - Connection ID tracking
 //! - Version validation
 //! - Amplification attack prevention
+//! - Anti-amplification budget of unvalidated clients (RFC 9000 Section 8)
+//! - Optional 0-RTT rejection
+//! - Per-connection ID rate limiting of short header packets
 
 #![no_std]
 #![no_main]
 
 use aya_ebpf::{
-    bindings::xdp_action,
+    bindings::{BPF_F_NO_PREALLOC, xdp_action},
     macros::{map, xdp},
-    maps::{HashMap, LruHashMap, PerCpuArray},
+    maps::{Array, HashMap, LpmTrie, LruHashMap, LruPerCpuHashMap, PerCpuArray},
     programs::XdpContext,
 };
 use core::mem;
-
-// ============================================================================
-// Network Header Structures
-// ============================================================================
-
-#[repr(C)]
-struct EthHdr {
-    h_dest: [u8; 6],
-    h_source: [u8; 6],
-    h_proto: u16,
-}
-
-#[repr(C)]
-struct Ipv4Hdr {
-    version_ihl: u8,
-    tos: u8,
-    tot_len: u16,
-    id: u16,
-    frag_off: u16,
-    ttl: u8,
-    protocol: u8,
-    check: u16,
-    saddr: u32,
-    daddr: u32,
-}
-
-#[repr(C)]
-struct Ipv6Hdr {
-    version_tc_flow: u32,
-    payload_len: u16,
-    nexthdr: u8,
-    hop_limit: u8,
-    saddr: [u8; 16],
-    daddr: [u8; 16],
-}
-
-#[repr(C)]
-struct UdpHdr {
-    source: u16,
-    dest: u16,
-    len: u16,
-    check: u16,
-}
+use pistonprotection_ebpf::{
+    BlockReason, DropContext, DropCounter, EventBudget, EventBudgetConfig, HandshakeRecord,
+    LatencyBucket, LatencyConfig, PenaltyConfig, PenaltyEntry, SampleConfig, UdpHdr,
+    breakdown::{DST_PORT_MAX_ENTRIES, REASON_BUCKETS},
+    cgnat, drop_context_reset, drop_context_set_reason, drop_context_set_source_v4,
+    drop_context_set_source_v6, drop_context_set_target, drop_events, emit_drop_event, frame_len,
+    known_good, latency, latency_elapsed, latency_start, limit_multiplier, observe_verdict,
+    parse_eth, parse_ipv4, parse_ipv6_with_ext, parse_udp, peek_dst_port, penalty, penalty_blocked,
+    penalty_config, penalty_divisor, penalty_key_v4, record_drop, record_handshake, record_latency,
+    record_violation, sample_packet, sampling,
+};
 
 // ============================================================================
 // QUIC Structures
@@ -114,6 +85,57 @@ pub struct QuicRateLimit {
     pub blocked_until: u64,
 }
 
+/// Anti-amplification budget of a client address (RFC 9000 Section 8)
+///
+/// Until the client's address is validated the server may send at most
+/// `max_amplification_factor` times the bytes it received from it. Server
+/// bytes are only seen when the responses are routed through an interface
+/// running this program (e.g. the backend side of a gateway).
+#[repr(C)]
+pub struct QuicAmplification {
+    /// Bytes received from the client
+    pub client_bytes: u64,
+    /// Bytes sent to the client in long header packets
+    pub server_bytes: u64,
+    /// Last seen timestamp
+    pub last_seen: u64,
+    /// Address validated: the client sent a Handshake packet
+    pub validated: u32,
+    /// Padding for alignment
+    pub _padding: u32,
+}
+
+/// Short header rate limit per destination connection ID, written by the
+/// worker
+///
+/// Short headers carry no DCID length: it is fixed by the server's
+/// connection ID scheme, so userspace configures it. Limiting per DCID
+/// throttles one abusive HTTP/3 connection without hurting other clients
+/// behind the same (CGNAT) address.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct QuicCidLimit {
+    /// Maximum short header packets per DCID per window (0 = off)
+    pub max_packets_per_window: u32,
+    /// DCID length of the server's connection IDs (0 = 8 bytes)
+    pub cid_len: u32,
+    /// Rate limit window (nanoseconds)
+    pub window_ns: u64,
+    /// How long a DCID over the limit stays throttled (nanoseconds)
+    pub block_duration_ns: u64,
+}
+
+/// Short header packet rate of a destination connection ID
+#[repr(C)]
+pub struct QuicCidRate {
+    /// Packets in current window
+    pub packets: u64,
+    /// Window start timestamp
+    pub window_start: u64,
+    /// Throttled until timestamp
+    pub blocked_until: u64,
+}
+
 /// QUIC filter configuration
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -153,6 +175,8 @@ pub struct QuicStats {
     pub initial_packets: u64,
     pub handshake_packets: u64,
     pub short_header_packets: u64,
+    pub dropped_0rtt: u64,
+    pub dropped_cid_rate_limited: u64,
 }
 
 // ============================================================================
@@ -194,6 +218,7 @@ const MAX_DCID_LENGTH: u8 = 20;
 const MAX_SCID_LENGTH: u8 = 20;
 const MIN_INITIAL_PACKET_SIZE: usize = 1200; // RFC 9000 requirement
 const MAX_AMPLIFICATION_FACTOR: u32 = 3; // RFC 9000: 3x amplification limit
+const DEFAULT_SHORT_CID_LENGTH: u32 = 8;
 
 // Default configuration
 const DEFAULT_QUIC_PORT: u16 = 443;
@@ -203,6 +228,7 @@ const DEFAULT_MAX_CONNECTIONS_PER_IP: u32 = 100;
 const DEFAULT_RATE_LIMIT_WINDOW_NS: u64 = 1_000_000_000; // 1 second
 const DEFAULT_MAX_PACKETS_PER_WINDOW: u64 = 1000;
 const DEFAULT_BLOCK_DURATION_NS: u64 = 60_000_000_000; // 60 seconds
+const DEFAULT_CID_BLOCK_DURATION_NS: u64 = 10_000_000_000; // 10 seconds
 
 // ============================================================================
 // eBPF Maps
@@ -227,11 +253,53 @@ static QUIC_RATE_LIMITS_V6: LruHashMap<[u8; 16], QuicRateLimit> =
 #[map]
 static QUIC_VALID_CIDS: LruHashMap<u64, u64> = LruHashMap::with_max_entries(500_000, 0);
 
+/// Anti-amplification budgets (keyed by client ip << 32 | port)
+#[map]
+static QUIC_AMPLIFICATION: LruHashMap<u64, QuicAmplification> =
+    LruHashMap::with_max_entries(500_000, 0);
+
+/// 0-RTT rejection flag (0 = accept, 1 = reject), written by the worker
+#[map]
+static QUIC_REJECT_0RTT: Array<u32> = Array::with_max_entries(1, 0);
+
+/// Short header rate limit per DCID, written by the worker
+#[map]
+static QUIC_CID_LIMIT: Array<QuicCidLimit> = Array::with_max_entries(1, 0);
+
+/// Short header packet rates (keyed by DCID hash)
+#[map]
+static QUIC_CID_RATES: LruHashMap<u64, QuicCidRate> = LruHashMap::with_max_entries(500_000, 0);
+
 /// Whitelisted IPs
 #[map]
 static QUIC_WHITELIST: HashMap<u32, u32> = HashMap::with_max_entries(10_000, 0);
 
 /// Configuration
+/// Penalties of repeat offenders, shared with the other programs
+#[map(name = "PENALTY")]
+static PENALTY: LruHashMap<[u8; 16], PenaltyEntry> = LruHashMap::pinned(penalty::MAX_SOURCES, 0);
+
+/// Penalty ladder per protection level, shared with the other programs
+#[map(name = "PENALTY_CONFIG")]
+static PENALTY_CONFIG: Array<PenaltyConfig> = Array::pinned(penalty::LEVELS, 0);
+
+/// Set while a backend is learning its known-good sources, shared with the other programs
+#[map(name = "LEARNING")]
+static LEARNING: Array<u32> = Array::pinned(1, 0);
+
+/// Sources that completed handshakes while learning, shared with the other programs
+#[map(name = "HANDSHAKES")]
+static HANDSHAKES: LruHashMap<[u8; 16], HandshakeRecord> =
+    LruHashMap::pinned(known_good::MAX_HANDSHAKES, 0);
+
+/// Known-good sources and their limit multiplier, shared with the other programs
+#[map(name = "KNOWN_GOOD")]
+static KNOWN_GOOD: HashMap<[u8; 16], u32> = HashMap::pinned(known_good::MAX_SOURCES, 0);
+
+/// CGNAT ranges and their limit multiplier, shared with the other programs
+#[map(name = "CGNAT_RANGES")]
+static CGNAT_RANGES: LpmTrie<[u8; 16], u32> = LpmTrie::pinned(cgnat::MAX_RANGES, BPF_F_NO_PREALLOC);
+
 #[map]
 static QUIC_CONFIG: PerCpuArray<QuicConfig> = PerCpuArray::with_max_entries(1, 0);
 
@@ -239,6 +307,51 @@ static QUIC_CONFIG: PerCpuArray<QuicConfig> = PerCpuArray::with_max_entries(1, 0
 #[map]
 static QUIC_STATS: PerCpuArray<QuicStats> = PerCpuArray::with_max_entries(1, 0);
 
+/// Drops broken down by (protocol, destination port)
+#[map]
+static DROPS_BY_DST_PORT: LruPerCpuHashMap<u32, DropCounter> =
+    LruPerCpuHashMap::with_max_entries(DST_PORT_MAX_ENTRIES, 0);
+
+/// Drops broken down by BlockReason
+#[map]
+static DROPS_BY_REASON: PerCpuArray<DropCounter> = PerCpuArray::with_max_entries(REASON_BUCKETS, 0);
+
+/// Per-CPU drop attribution for the packet being processed
+#[map]
+static DROP_CONTEXT: PerCpuArray<DropContext> = PerCpuArray::with_max_entries(1, 0);
+
+/// Set while the worker is in observe mode, shared with the other programs
+#[map(name = "OBSERVE")]
+static OBSERVE: Array<u32> = Array::pinned(1, 0);
+
+/// Flow sampling configuration
+#[map]
+static SAMPLE_CONFIG: PerCpuArray<SampleConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Sampled packet headers for the userspace analyzer
+#[map]
+static SAMPLES: sampling::SampleMap = sampling::sample_map();
+
+/// Dropped packets for the userspace drop event aggregator
+#[map]
+static DROP_EVENTS: drop_events::EventMap = drop_events::event_map();
+
+/// Event budget of each CPU, written by userspace
+#[map]
+static EVENT_BUDGET_CONFIG: PerCpuArray<EventBudgetConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Event token buckets and per-class event counters
+#[map]
+static EVENT_BUDGET: PerCpuArray<EventBudget> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing latency measurement configuration
+#[map]
+static LATENCY_CONFIG: PerCpuArray<LatencyConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing time histograms, per protection level
+#[map]
+static LATENCY: PerCpuArray<LatencyBucket> = PerCpuArray::with_max_entries(latency::MAX_ENTRIES, 0);
+
 // ============================================================================
 // Constants
 // ============================================================================
@@ -251,12 +364,45 @@ const IPPROTO_UDP: u8 = 17;
 // Main XDP Entry Point
 // ============================================================================
 
-#[xdp]
+#[cfg_attr(feature = "frags", xdp(frags))]
+#[cfg_attr(not(feature = "frags"), xdp)]
 pub fn xdp_quic(ctx: XdpContext) -> u32 {
-    match try_xdp_quic(ctx) {
+    let started = latency_start(&LATENCY_CONFIG);
+    drop_context_reset(&DROP_CONTEXT, BlockReason::InvalidProtocol);
+    let bytes = frame_len(ctx.ctx) as u64;
+    let raw_ctx = ctx.ctx;
+
+    let action = match try_xdp_quic(ctx) {
         Ok(ret) => ret,
         Err(_) => xdp_action::XDP_PASS,
+    };
+
+    if action == xdp_action::XDP_DROP {
+        record_drop(&DROP_CONTEXT, &DROPS_BY_DST_PORT, &DROPS_BY_REASON, bytes);
+        emit_drop_event(
+            raw_ctx,
+            &DROP_CONTEXT,
+            &DROP_EVENTS,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+            bytes,
+        );
+    } else if action == xdp_action::XDP_PASS {
+        sample_packet(
+            raw_ctx,
+            bytes,
+            &SAMPLE_CONFIG,
+            &SAMPLES,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+        );
     }
+
+    if let Some(elapsed) = latency_elapsed(started) {
+        record_latency(&LATENCY, elapsed, get_config().protection_level);
+    }
+
+    observe_verdict(&OBSERVE, action)
 }
 
 #[inline(always)]
@@ -270,16 +416,13 @@ fn try_xdp_quic(ctx: XdpContext) -> Result<u32, ()> {
     let data_end = ctx.data_end();
 
     // Parse Ethernet header
-    if data + mem::size_of::<EthHdr>() > data_end {
+    let Some((eth_proto, l3_offset)) = parse_eth(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let eth = unsafe { &*(data as *const EthHdr) };
-    let eth_proto = u16::from_be(eth.h_proto);
+    };
 
     match eth_proto {
-        ETH_P_IP => process_ipv4(&ctx, data + mem::size_of::<EthHdr>(), data_end, &config),
-        ETH_P_IPV6 => process_ipv6(&ctx, data + mem::size_of::<EthHdr>(), data_end, &config),
+        ETH_P_IP => process_ipv4(&ctx, l3_offset, data_end, &config),
+        ETH_P_IPV6 => process_ipv6(&ctx, l3_offset, data_end, &config),
         _ => Ok(xdp_action::XDP_PASS),
     }
 }
@@ -295,11 +438,16 @@ fn process_ipv4(
     data_end: usize,
     config: &QuicConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv4Hdr>() > data_end {
+    let Some((ip, udp_data)) = parse_ipv4(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
+    };
 
-    let ip = unsafe { &*(data as *const Ipv4Hdr) };
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        ip.protocol,
+        peek_dst_port(udp_data, data_end, ip.protocol),
+    );
+    drop_context_set_source_v4(&DROP_CONTEXT, ip.saddr);
 
     // Only process UDP
     if ip.protocol != IPPROTO_UDP {
@@ -319,10 +467,15 @@ fn process_ipv4(
         return Ok(xdp_action::XDP_DROP);
     }
 
-    let ihl = (ip.version_ihl & 0x0f) as usize * 4;
-    let udp_data = data + ihl;
-
-    process_udp_quic(ctx, udp_data, data_end, src_ip, config)
+    process_udp_quic(
+        ctx,
+        udp_data,
+        data_end,
+        src_ip,
+        &penalty_key_v4(src_ip),
+        &penalty_key_v4(u32::from_be(ip.daddr)),
+        config,
+    )
 }
 
 // ============================================================================
@@ -336,14 +489,25 @@ fn process_ipv6(
     data_end: usize,
     config: &QuicConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv6Hdr>() > data_end {
+    // Skip extension headers (hop-by-hop, routing, fragment, ...) to reach UDP
+    let Some((ip6, l4)) = parse_ipv6_with_ext(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
+    };
 
-    let ip6 = unsafe { &*(data as *const Ipv6Hdr) };
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        l4.protocol,
+        peek_dst_port(l4.offset, data_end, l4.protocol),
+    );
+    drop_context_set_source_v6(&DROP_CONTEXT, &ip6.saddr);
 
     // Only process UDP
-    if ip6.nexthdr != IPPROTO_UDP {
+    if l4.protocol != IPPROTO_UDP {
+        return Ok(xdp_action::XDP_PASS);
+    }
+
+    // Non-first fragments carry no UDP header to inspect
+    if l4.is_fragment && !l4.is_first_fragment {
         return Ok(xdp_action::XDP_PASS);
     }
 
@@ -355,31 +519,34 @@ fn process_ipv6(
         return Ok(xdp_action::XDP_DROP);
     }
 
-    let udp_data = data + mem::size_of::<Ipv6Hdr>();
+    let udp_data = l4.offset;
 
     // Use last 4 bytes of IPv6 as simplified key
     let ip_key = u32::from_be_bytes([src_ip[12], src_ip[13], src_ip[14], src_ip[15]]);
 
-    process_udp_quic(ctx, udp_data, data_end, ip_key, config)
+    process_udp_quic(ctx, udp_data, data_end, ip_key, &src_ip, &ip6.daddr, config)
 }
 
 // ============================================================================
 // UDP/QUIC Processing
 // ============================================================================
 
+/// `src_ip` keys the per-IP maps (the low 32 bits for IPv6); `src_addr` and
+/// `dst_addr` are the full endpoint addresses (IPv4-mapped for IPv4) used by
+/// the learning maps.
 #[inline(always)]
 fn process_udp_quic(
     ctx: &XdpContext,
     data: usize,
     data_end: usize,
     src_ip: u32,
+    src_addr: &[u8; 16],
+    dst_addr: &[u8; 16],
     config: &QuicConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<UdpHdr>() > data_end {
+    let Some((udp, payload_start)) = parse_udp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let udp = unsafe { &*(data as *const UdpHdr) };
+    };
     let dst_port = u16::from_be(udp.dest);
     let src_port = u16::from_be(udp.source);
     let udp_len = u16::from_be(udp.len) as usize;
@@ -396,6 +563,22 @@ fn process_udp_quic(
         DEFAULT_ALT_QUIC_PORT
     };
 
+    // Server responses routed through this interface spend the client's
+    // anti-amplification budget
+    if src_port == quic_port || src_port == alt_quic_port {
+        let client_ip =
+            u32::from_be_bytes([dst_addr[12], dst_addr[13], dst_addr[14], dst_addr[15]]);
+        let quic_len = udp_len.saturating_sub(mem::size_of::<UdpHdr>());
+        return Ok(check_server_response(
+            payload_start,
+            data_end,
+            client_ip,
+            dst_port,
+            quic_len,
+            config,
+        ));
+    }
+
     if dst_port != quic_port && dst_port != alt_quic_port {
         return Ok(xdp_action::XDP_PASS);
     }
@@ -404,13 +587,13 @@ fn process_udp_quic(
     update_stats_total();
 
     // Check rate limit
-    if !check_rate_limit_v4(src_ip, config) {
+    if !check_rate_limit_v4(src_ip, src_addr, config) {
         update_stats_rate_limited();
         return Ok(xdp_action::XDP_DROP);
     }
 
     // Parse QUIC packet
-    let quic_data = data + mem::size_of::<UdpHdr>();
+    let quic_data = payload_start;
     let quic_len = udp_len.saturating_sub(mem::size_of::<UdpHdr>());
 
     if quic_data >= data_end || quic_len < 1 {
@@ -440,7 +623,11 @@ fn process_udp_quic(
 
     if is_long_header(first_byte) {
         // Long header packet
-        process_quic_long_header(ctx, quic_data, data_end, src_ip, src_port, quic_len, config)
+        credit_client_bytes(src_ip, src_port, quic_len);
+        process_quic_long_header(
+            ctx, quic_data, data_end, src_ip, src_addr, dst_addr, src_port, dst_port, quic_len,
+            config,
+        )
     } else {
         // Short header packet
         process_quic_short_header(ctx, quic_data, data_end, src_ip, src_port, quic_len, config)
@@ -457,7 +644,10 @@ fn process_quic_long_header(
     data: usize,
     data_end: usize,
     src_ip: u32,
+    src_addr: &[u8; 16],
+    dst_addr: &[u8; 16],
     src_port: u16,
+    dst_port: u16,
     quic_len: usize,
     config: &QuicConfig,
 ) -> Result<u32, ()> {
@@ -475,7 +665,7 @@ fn process_quic_long_header(
     if !is_valid_quic_version(version) {
         update_stats_invalid_version();
         if config.protection_level >= 2 {
-            block_ip_v4(src_ip, config.block_duration_ns / 2);
+            penalize_v4(src_ip, config);
         }
         return Ok(xdp_action::XDP_DROP);
     }
@@ -570,6 +760,10 @@ fn process_quic_long_header(
         QUIC_PACKET_TYPE_HANDSHAKE => {
             update_stats_handshake();
 
+            // A Handshake packet proves the client received the server's
+            // Initial, validating its address (RFC 9000 Section 8.1)
+            validate_client_address(src_ip, src_port);
+
             // Handshake packets should come from established initial connections
             let conn_key = make_connection_key(src_ip, src_port, dcid_len, data, dcid_start);
 
@@ -579,6 +773,20 @@ fn process_quic_long_header(
                 // Update state
                 if conn.state == 1 {
                     conn.state = 2; // Handshake
+                    conn.flags |= FLAG_ADDRESS_VALIDATED;
+
+                    // The client only sends Handshake packets (carrying its
+                    // Finished) after receiving the server's handshake flight,
+                    // which completes the handshake for allowlist learning
+                    record_handshake(
+                        &LEARNING,
+                        &HANDSHAKES,
+                        src_addr,
+                        dst_addr,
+                        dst_port,
+                        known_good::PROTO_QUIC,
+                        now,
+                    );
                 }
                 conn.packets += 1;
                 conn.bytes += quic_len as u64;
@@ -598,7 +806,13 @@ fn process_quic_long_header(
         }
 
         QUIC_PACKET_TYPE_0RTT => {
-            // 0-RTT packets - allow with basic validation
+            // 0-RTT data can be replayed (RFC 9001 Section 9.2); while
+            // rejection is on, clients resend it as 1-RTT data after the
+            // handshake
+            if reject_0rtt() {
+                update_stats_0rtt();
+                return Ok(xdp_action::XDP_DROP);
+            }
             update_stats_passed();
             Ok(xdp_action::XDP_PASS)
         }
@@ -652,8 +866,13 @@ fn process_quic_short_header(
         return Ok(xdp_action::XDP_DROP);
     }
 
-    // For established connections with short headers,
-    // we rely on rate limiting as we can't inspect encrypted payload
+    // The payload is encrypted, so established connections are limited by
+    // their packet rate: per IP above, and per DCID so one connection can be
+    // throttled without its neighbours behind the same address
+    if !check_cid_rate_limit(data, data_end) {
+        update_stats_cid_rate_limited();
+        return Ok(xdp_action::XDP_DROP);
+    }
 
     update_stats_passed();
     Ok(xdp_action::XDP_PASS)
@@ -750,12 +969,101 @@ fn hash_connection_id(_data: usize, dcid_start: usize, dcid_len: u8) -> u64 {
     hash
 }
 
+// ============================================================================
+// Anti-Amplification
+// ============================================================================
+
+#[inline(always)]
+fn amplification_key(client_ip: u32, client_port: u16) -> u64 {
+    ((client_ip as u64) << 32) | (client_port as u64)
+}
+
+/// Credit the bytes of a long header packet to the client's budget
+#[inline(always)]
+fn credit_client_bytes(src_ip: u32, src_port: u16, quic_len: usize) {
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    let key = amplification_key(src_ip, src_port);
+
+    if let Some(budget) = unsafe { QUIC_AMPLIFICATION.get_ptr_mut(&key) } {
+        let budget = unsafe { &mut *budget };
+        budget.client_bytes += quic_len as u64;
+        budget.last_seen = now;
+    } else {
+        let budget = QuicAmplification {
+            client_bytes: quic_len as u64,
+            server_bytes: 0,
+            last_seen: now,
+            validated: 0,
+            _padding: 0,
+        };
+        let _ = QUIC_AMPLIFICATION.insert(&key, &budget, 0);
+    }
+}
+
+/// Lift the budget of a client whose address is validated
+#[inline(always)]
+fn validate_client_address(src_ip: u32, src_port: u16) {
+    let key = amplification_key(src_ip, src_port);
+    if let Some(budget) = unsafe { QUIC_AMPLIFICATION.get_ptr_mut(&key) } {
+        unsafe { (*budget).validated = 1 };
+    }
+}
+
+/// Check a server response against the budget of its client
+///
+/// Short header packets are only sent once the handshake is confirmed, and
+/// clients without a budget were not seen by this program, so both pass.
+#[inline(always)]
+fn check_server_response(
+    data: usize,
+    data_end: usize,
+    client_ip: u32,
+    client_port: u16,
+    quic_len: usize,
+    config: &QuicConfig,
+) -> u32 {
+    if data >= data_end || quic_len < 1 {
+        return xdp_action::XDP_PASS;
+    }
+    let first_byte = unsafe { *(data as *const u8) };
+    if !is_long_header(first_byte) {
+        return xdp_action::XDP_PASS;
+    }
+
+    let key = amplification_key(client_ip, client_port);
+    let Some(budget) = (unsafe { QUIC_AMPLIFICATION.get_ptr_mut(&key) }) else {
+        return xdp_action::XDP_PASS;
+    };
+    let budget = unsafe { &mut *budget };
+    if budget.validated != 0 {
+        return xdp_action::XDP_PASS;
+    }
+
+    let factor = if config.max_amplification_factor != 0 {
+        config.max_amplification_factor
+    } else {
+        MAX_AMPLIFICATION_FACTOR
+    };
+    if budget.server_bytes + quic_len as u64 > budget.client_bytes * factor as u64 {
+        update_stats_amplification();
+        return xdp_action::XDP_DROP;
+    }
+    budget.server_bytes += quic_len as u64;
+
+    xdp_action::XDP_PASS
+}
+
+#[inline(always)]
+fn reject_0rtt() -> bool {
+    QUIC_REJECT_0RTT.get(0).is_some_and(|reject| *reject != 0)
+}
+
 // ============================================================================
 // Rate Limiting
 // ============================================================================
 
 #[inline(always)]
-fn check_rate_limit_v4(src_ip: u32, config: &QuicConfig) -> bool {
+fn check_rate_limit_v4(src_ip: u32, src_addr: &[u8; 16], config: &QuicConfig) -> bool {
     let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
     let window = if config.rate_limit_window_ns != 0 {
         config.rate_limit_window_ns
@@ -768,6 +1076,13 @@ fn check_rate_limit_v4(src_ip: u32, config: &QuicConfig) -> bool {
         DEFAULT_MAX_PACKETS_PER_WINDOW
     };
 
+    // Sources with a penalty get a smaller limit, known-good sources and
+    // CGNAT addresses a larger one
+    let penalty_key = penalty_key_v4(src_ip);
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let max_packets = max_packets * limit_multiplier(&KNOWN_GOOD, &CGNAT_RANGES, src_addr)
+        / penalty_divisor(&PENALTY, &penalty_key, &ladder, now);
+
     if let Some(rate) = unsafe { QUIC_RATE_LIMITS_V4.get_ptr_mut(&src_ip) } {
         let rate = unsafe { &mut *rate };
 
@@ -788,7 +1103,14 @@ fn check_rate_limit_v4(src_ip: u32, config: &QuicConfig) -> bool {
 
         if rate.packets > max_packets {
             // Exceeded rate limit
-            rate.blocked_until = now + config.block_duration_ns;
+            rate.blocked_until = record_violation(
+                &DROP_CONTEXT,
+                &PENALTY,
+                &penalty_key,
+                &ladder,
+                now,
+                rate.window_start,
+            );
             return false;
         }
 
@@ -806,10 +1128,97 @@ fn check_rate_limit_v4(src_ip: u32, config: &QuicConfig) -> bool {
     }
 }
 
+/// Count a short header packet against the rate of its DCID
+#[inline(always)]
+fn check_cid_rate_limit(data: usize, data_end: usize) -> bool {
+    let Some(limit) = QUIC_CID_LIMIT.get(0) else {
+        return true;
+    };
+    if limit.max_packets_per_window == 0 {
+        return true;
+    }
+
+    let cid_len = if limit.cid_len != 0 {
+        limit.cid_len.min(MAX_DCID_LENGTH as u32)
+    } else {
+        DEFAULT_SHORT_CID_LENGTH
+    };
+    // The DCID follows the header byte
+    let dcid_start = data + 1;
+    if dcid_start + cid_len as usize > data_end {
+        return true;
+    }
+    let key = hash_short_cid(dcid_start, data_end, cid_len as usize);
+
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    let window = if limit.window_ns != 0 {
+        limit.window_ns
+    } else {
+        DEFAULT_RATE_LIMIT_WINDOW_NS
+    };
+
+    let Some(rate) = (unsafe { QUIC_CID_RATES.get_ptr_mut(&key) }) else {
+        let rate = QuicCidRate {
+            packets: 1,
+            window_start: now,
+            blocked_until: 0,
+        };
+        let _ = QUIC_CID_RATES.insert(&key, &rate, 0);
+        return true;
+    };
+    let rate = unsafe { &mut *rate };
+
+    if rate.blocked_until > now {
+        return false;
+    }
+
+    if now.saturating_sub(rate.window_start) > window {
+        rate.window_start = now;
+        rate.packets = 1;
+        return true;
+    }
+
+    rate.packets += 1;
+    if rate.packets > limit.max_packets_per_window as u64 {
+        let block = if limit.block_duration_ns != 0 {
+            limit.block_duration_ns
+        } else {
+            DEFAULT_CID_BLOCK_DURATION_NS
+        };
+        rate.blocked_until = now + block;
+        return false;
+    }
+
+    true
+}
+
+/// FNV-1a hash of a short header DCID, bounds checked per byte for the
+/// verifier
+#[inline(always)]
+fn hash_short_cid(dcid_start: usize, data_end: usize, cid_len: usize) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let mut hash: u64 = FNV_OFFSET;
+    for i in 0..MAX_DCID_LENGTH as usize {
+        if i >= cid_len || dcid_start + i + 1 > data_end {
+            break;
+        }
+        let byte = unsafe { *((dcid_start + i) as *const u8) };
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
+
 #[inline(always)]
 fn is_ip_blocked_v4(src_ip: u32) -> bool {
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    if penalty_blocked(&PENALTY, &penalty_key_v4(src_ip), now) {
+        return true;
+    }
     if let Some(rate) = unsafe { QUIC_RATE_LIMITS_V4.get(&src_ip) } {
-        let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
         rate.blocked_until > now
     } else {
         false
@@ -818,24 +1227,41 @@ fn is_ip_blocked_v4(src_ip: u32) -> bool {
 
 #[inline(always)]
 fn is_ip_blocked_v6(src_ip: &[u8; 16]) -> bool {
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    if penalty_blocked(&PENALTY, src_ip, now) {
+        return true;
+    }
     if let Some(rate) = unsafe { QUIC_RATE_LIMITS_V6.get(src_ip) } {
-        let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
         rate.blocked_until > now
     } else {
         false
     }
 }
 
+/// Record a violation of a source and apply the block it earned, if any
 #[inline(always)]
-fn block_ip_v4(src_ip: u32, duration_ns: u64) {
+fn penalize_v4(src_ip: u32, config: &QuicConfig) {
     let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
-    let block_until = now
-        + if duration_ns != 0 {
-            duration_ns
-        } else {
-            DEFAULT_BLOCK_DURATION_NS
-        };
+    let window_start = match unsafe { QUIC_RATE_LIMITS_V4.get(&src_ip) } {
+        Some(rate) => rate.window_start,
+        None => now,
+    };
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let block_until = record_violation(
+        &DROP_CONTEXT,
+        &PENALTY,
+        &penalty_key_v4(src_ip),
+        &ladder,
+        now,
+        window_start,
+    );
+    if block_until > now {
+        block_ip_v4(src_ip, now, block_until);
+    }
+}
 
+#[inline(always)]
+fn block_ip_v4(src_ip: u32, now: u64, block_until: u64) {
     if let Some(rate) = unsafe { QUIC_RATE_LIMITS_V4.get_ptr_mut(&src_ip) } {
         let rate = unsafe { &mut *rate };
         rate.blocked_until = block_until;
@@ -899,6 +1325,7 @@ fn update_stats_passed() {
 
 #[inline(always)]
 fn update_stats_invalid_header() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
     if let Some(stats) = unsafe { QUIC_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_invalid_header += 1;
@@ -908,6 +1335,7 @@ fn update_stats_invalid_header() {
 
 #[inline(always)]
 fn update_stats_invalid_version() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidQuicVersion);
     if let Some(stats) = unsafe { QUIC_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_invalid_version += 1;
@@ -917,6 +1345,7 @@ fn update_stats_invalid_version() {
 
 #[inline(always)]
 fn update_stats_amplification() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::QuicAmplification);
     if let Some(stats) = unsafe { QUIC_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_amplification += 1;
@@ -926,6 +1355,7 @@ fn update_stats_amplification() {
 
 #[inline(always)]
 fn update_stats_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::RateLimit);
     if let Some(stats) = unsafe { QUIC_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_rate_limited += 1;
@@ -935,6 +1365,7 @@ fn update_stats_rate_limited() {
 
 #[inline(always)]
 fn update_stats_blocked() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::BlockedIp);
     if let Some(stats) = unsafe { QUIC_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_blocked_ip += 1;
@@ -942,6 +1373,26 @@ fn update_stats_blocked() {
     }
 }
 
+#[inline(always)]
+fn update_stats_cid_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::RateLimit);
+    if let Some(stats) = unsafe { QUIC_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).dropped_cid_rate_limited += 1;
+        }
+    }
+}
+
+#[inline(always)]
+fn update_stats_0rtt() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
+    if let Some(stats) = unsafe { QUIC_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).dropped_0rtt += 1;
+        }
+    }
+}
+
 #[inline(always)]
 fn update_stats_initial() {
     if let Some(stats) = unsafe { QUIC_STATS.get_ptr_mut(0) } {
@@ -8,57 +8,30 @@
 use aya_ebpf::{
     bindings::xdp_action,
     macros::{map, xdp},
-    maps::{LruHashMap, PerCpuArray},
+    maps::{Array, LruHashMap, LruPerCpuHashMap, PerCpuArray},
     programs::XdpContext,
 };
-use core::mem;
-
-// Network headers
-
-#[repr(C)]
-struct EthHdr {
-    h_dest: [u8; 6],
-    h_source: [u8; 6],
-    h_proto: u16,
-}
-
-#[repr(C)]
-struct Ipv4Hdr {
-    version_ihl: u8,
-    tos: u8,
-    tot_len: u16,
-    id: u16,
-    frag_off: u16,
-    ttl: u8,
-    protocol: u8,
-    check: u16,
-    saddr: u32,
-    daddr: u32,
-}
-
-#[repr(C)]
-struct Ipv6Hdr {
-    version_tc_flow: u32,
-    payload_len: u16,
-    nexthdr: u8,
-    hop_limit: u8,
-    saddr: [u8; 16],
-    daddr: [u8; 16],
-}
+use pistonprotection_ebpf::{
+    BlockReason, DropContext, DropCounter, EventBudget, EventBudgetConfig, LatencyBucket,
+    LatencyConfig, SampleConfig, SourceCounters, TokenLease,
+    breakdown::{DST_PORT_MAX_ENTRIES, REASON_BUCKETS},
+    count_source, drop_context_reset, drop_context_set_reason, drop_context_set_source_v4,
+    drop_context_set_source_v6, drop_context_set_target, drop_events, emit_drop_event, frame_len,
+    latency, latency_elapsed, latency_start, lease, observe_verdict, parse_eth, parse_ipv4,
+    parse_ipv6, peek_dst_port, record_drop, record_latency, sample_packet, sampling, spend_lease,
+    take_lease,
+};
 
 /// Token bucket state
+///
+/// Traffic of each source is counted per CPU in `SOURCE_COUNTERS_*`, so
+/// the shared bucket only changes when it refills or leases tokens.
 #[repr(C)]
 pub struct TokenBucket {
     /// Current number of tokens
     pub tokens: u64,
-    /// Last update timestamp (nanoseconds)
+    /// Last refill timestamp (nanoseconds)
     pub last_update: u64,
-    /// Total packets from this source
-    pub packets: u64,
-    /// Total bytes from this source
-    pub bytes: u64,
-    /// Dropped packets count
-    pub dropped: u64,
 }
 
 /// Rate limit configuration
@@ -98,6 +71,31 @@ static TOKEN_BUCKETS_V6: LruHashMap<[u8; 16], TokenBucket> =
 static SUBNET_BUCKETS: LruHashMap<SubnetKey, TokenBucket> =
     LruHashMap::with_max_entries(100_000, 0);
 
+/// Tokens each CPU leased from `TOKEN_BUCKETS_V4`
+#[map]
+static TOKEN_LEASES_V4: LruPerCpuHashMap<u32, TokenLease> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
+/// Tokens each CPU leased from `TOKEN_BUCKETS_V6`
+#[map]
+static TOKEN_LEASES_V6: LruPerCpuHashMap<[u8; 16], TokenLease> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
+/// Tokens each CPU leased from `SUBNET_BUCKETS`
+#[map]
+static SUBNET_LEASES: LruPerCpuHashMap<SubnetKey, TokenLease> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
+/// Per-CPU traffic of each source (IPv4)
+#[map]
+static SOURCE_COUNTERS_V4: LruPerCpuHashMap<u32, SourceCounters> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
+/// Per-CPU traffic of each source (IPv6)
+#[map]
+static SOURCE_COUNTERS_V6: LruPerCpuHashMap<[u8; 16], SourceCounters> =
+    LruPerCpuHashMap::with_max_entries(lease::MAX_SOURCES, 0);
+
 /// Global configuration
 #[map]
 static RATELIMIT_CONFIG: PerCpuArray<RateLimitConfig> = PerCpuArray::with_max_entries(1, 0);
@@ -106,6 +104,51 @@ static RATELIMIT_CONFIG: PerCpuArray<RateLimitConfig> = PerCpuArray::with_max_en
 #[map]
 static RATELIMIT_STATS: PerCpuArray<RateLimitStats> = PerCpuArray::with_max_entries(1, 0);
 
+/// Drops broken down by (protocol, destination port)
+#[map]
+static DROPS_BY_DST_PORT: LruPerCpuHashMap<u32, DropCounter> =
+    LruPerCpuHashMap::with_max_entries(DST_PORT_MAX_ENTRIES, 0);
+
+/// Drops broken down by BlockReason
+#[map]
+static DROPS_BY_REASON: PerCpuArray<DropCounter> = PerCpuArray::with_max_entries(REASON_BUCKETS, 0);
+
+/// Per-CPU drop attribution for the packet being processed
+#[map]
+static DROP_CONTEXT: PerCpuArray<DropContext> = PerCpuArray::with_max_entries(1, 0);
+
+/// Set while the worker is in observe mode, shared with the other programs
+#[map(name = "OBSERVE")]
+static OBSERVE: Array<u32> = Array::pinned(1, 0);
+
+/// Flow sampling configuration
+#[map]
+static SAMPLE_CONFIG: PerCpuArray<SampleConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Sampled packet headers for the userspace analyzer
+#[map]
+static SAMPLES: sampling::SampleMap = sampling::sample_map();
+
+/// Dropped packets for the userspace drop event aggregator
+#[map]
+static DROP_EVENTS: drop_events::EventMap = drop_events::event_map();
+
+/// Event budget of each CPU, written by userspace
+#[map]
+static EVENT_BUDGET_CONFIG: PerCpuArray<EventBudgetConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Event token buckets and per-class event counters
+#[map]
+static EVENT_BUDGET: PerCpuArray<EventBudget> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing latency measurement configuration
+#[map]
+static LATENCY_CONFIG: PerCpuArray<LatencyConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing time histograms, per protection level
+#[map]
+static LATENCY: PerCpuArray<LatencyBucket> = PerCpuArray::with_max_entries(latency::MAX_ENTRIES, 0);
+
 #[repr(C)]
 pub struct RateLimitStats {
     pub total_packets: u64,
@@ -123,12 +166,45 @@ const NANOS_PER_SEC: u64 = 1_000_000_000;
 const DEFAULT_TOKENS_PER_SEC: u64 = 1000;
 const DEFAULT_BUCKET_SIZE: u64 = 2000;
 
-#[xdp]
+#[cfg_attr(feature = "frags", xdp(frags))]
+#[cfg_attr(not(feature = "frags"), xdp)]
 pub fn xdp_ratelimit(ctx: XdpContext) -> u32 {
-    match try_xdp_ratelimit(ctx) {
+    let started = latency_start(&LATENCY_CONFIG);
+    drop_context_reset(&DROP_CONTEXT, BlockReason::RateLimit);
+    let bytes = frame_len(ctx.ctx) as u64;
+    let raw_ctx = ctx.ctx;
+
+    let action = match try_xdp_ratelimit(ctx) {
         Ok(ret) => ret,
         Err(_) => xdp_action::XDP_PASS,
+    };
+
+    if action == xdp_action::XDP_DROP {
+        record_drop(&DROP_CONTEXT, &DROPS_BY_DST_PORT, &DROPS_BY_REASON, bytes);
+        emit_drop_event(
+            raw_ctx,
+            &DROP_CONTEXT,
+            &DROP_EVENTS,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+            bytes,
+        );
+    } else if action == xdp_action::XDP_PASS {
+        sample_packet(
+            raw_ctx,
+            bytes,
+            &SAMPLE_CONFIG,
+            &SAMPLES,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+        );
+    }
+
+    if let Some(elapsed) = latency_elapsed(started) {
+        record_latency(&LATENCY, elapsed, 0);
     }
+
+    observe_verdict(&OBSERVE, action)
 }
 
 #[inline(always)]
@@ -142,34 +218,19 @@ fn try_xdp_ratelimit(ctx: XdpContext) -> Result<u32, ()> {
     let data = ctx.data();
     let data_end = ctx.data_end();
 
-    // Parse Ethernet
-    if data + mem::size_of::<EthHdr>() > data_end {
+    // Parse Ethernet header
+    let Some((eth_proto, l3_offset)) = parse_eth(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let eth = unsafe { &*(data as *const EthHdr) };
-    let eth_proto = u16::from_be(eth.h_proto);
+    };
 
     // Update stats
     update_stats_total();
 
-    let packet_size = (data_end - data) as u64;
+    let packet_size = frame_len(ctx.ctx) as u64;
 
     match eth_proto {
-        ETH_P_IP => ratelimit_ipv4(
-            &ctx,
-            data + mem::size_of::<EthHdr>(),
-            data_end,
-            packet_size,
-            &config,
-        ),
-        ETH_P_IPV6 => ratelimit_ipv6(
-            &ctx,
-            data + mem::size_of::<EthHdr>(),
-            data_end,
-            packet_size,
-            &config,
-        ),
+        ETH_P_IP => ratelimit_ipv4(&ctx, l3_offset, data_end, packet_size, &config),
+        ETH_P_IPV6 => ratelimit_ipv6(&ctx, l3_offset, data_end, packet_size, &config),
         _ => Ok(xdp_action::XDP_PASS),
     }
 }
@@ -182,26 +243,31 @@ fn ratelimit_ipv4(
     packet_size: u64,
     config: &RateLimitConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv4Hdr>() > data_end {
+    let Some((ip, l4_offset)) = parse_ipv4(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let ip = unsafe { &*(data as *const Ipv4Hdr) };
+    };
     let src_ip = u32::from_be(ip.saddr);
 
-    // Check per-IP rate limit
-    if !check_token_bucket_v4(src_ip, packet_size, config) {
-        update_stats_dropped();
-        return Ok(xdp_action::XDP_DROP);
-    }
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        ip.protocol,
+        peek_dst_port(l4_offset, data_end, ip.protocol),
+    );
+    drop_context_set_source_v4(&DROP_CONTEXT, ip.saddr);
+
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
 
-    // Check subnet rate limit (optional, for DDoS from botnets)
+    // Check per-IP rate limit, then the subnet limit (optional, for DDoS
+    // from botnets)
     let subnet = SubnetKey {
         prefix: src_ip & 0xFFFFFF00, // /24 subnet
         padding: 0,
     };
+    let passed = check_token_bucket_v4(src_ip, now, config)
+        && (config.level < 2 || check_subnet_bucket(&subnet, now, config));
 
-    if config.level >= 2 && !check_subnet_bucket(&subnet, packet_size, config) {
+    count_source(&SOURCE_COUNTERS_V4, &src_ip, packet_size, passed, now);
+    if !passed {
         update_stats_dropped();
         return Ok(xdp_action::XDP_DROP);
     }
@@ -218,15 +284,25 @@ fn ratelimit_ipv6(
     packet_size: u64,
     config: &RateLimitConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv6Hdr>() > data_end {
+    let Some((ip6, next_offset)) = parse_ipv6(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let ip6 = unsafe { &*(data as *const Ipv6Hdr) };
+    };
     let src_ip = ip6.saddr;
 
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        ip6.nexthdr,
+        peek_dst_port(next_offset, data_end, ip6.nexthdr),
+    );
+    drop_context_set_source_v6(&DROP_CONTEXT, &ip6.saddr);
+
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+
     // Check per-IP rate limit
-    if !check_token_bucket_v6(src_ip, packet_size, config) {
+    let passed = check_token_bucket_v6(src_ip, now, config);
+
+    count_source(&SOURCE_COUNTERS_V6, &src_ip, packet_size, passed, now);
+    if !passed {
         update_stats_dropped();
         return Ok(xdp_action::XDP_DROP);
     }
@@ -236,117 +312,90 @@ fn ratelimit_ipv6(
 }
 
 #[inline(always)]
-fn check_token_bucket_v4(ip: u32, packet_size: u64, config: &RateLimitConfig) -> bool {
-    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
-
-    if let Some(bucket) = unsafe { TOKEN_BUCKETS_V4.get_ptr_mut(&ip) } {
-        let bucket = unsafe { &mut *bucket };
-        return process_bucket(bucket, now, packet_size, config);
-    }
-
-    // Create new bucket for this IP
-    let bucket = TokenBucket {
-        tokens: config.bucket_size.saturating_sub(1),
-        last_update: now,
-        packets: 1,
-        bytes: packet_size,
-        dropped: 0,
-    };
-    let _ = TOKEN_BUCKETS_V4.insert(&ip, &bucket, 0);
-    true
+fn check_token_bucket_v4(ip: u32, now: u64, config: &RateLimitConfig) -> bool {
+    spend_lease(&TOKEN_LEASES_V4, &ip, now)
+        || lease_tokens(
+            &TOKEN_BUCKETS_V4,
+            &TOKEN_LEASES_V4,
+            &ip,
+            now,
+            config.tokens_per_second,
+            config.bucket_size,
+        )
 }
 
 #[inline(always)]
-fn check_token_bucket_v6(ip: [u8; 16], packet_size: u64, config: &RateLimitConfig) -> bool {
-    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
-
-    if let Some(bucket) = unsafe { TOKEN_BUCKETS_V6.get_ptr_mut(&ip) } {
-        let bucket = unsafe { &mut *bucket };
-        return process_bucket(bucket, now, packet_size, config);
-    }
-
-    let bucket = TokenBucket {
-        tokens: config.bucket_size.saturating_sub(1),
-        last_update: now,
-        packets: 1,
-        bytes: packet_size,
-        dropped: 0,
-    };
-    let _ = TOKEN_BUCKETS_V6.insert(&ip, &bucket, 0);
-    true
+fn check_token_bucket_v6(ip: [u8; 16], now: u64, config: &RateLimitConfig) -> bool {
+    spend_lease(&TOKEN_LEASES_V6, &ip, now)
+        || lease_tokens(
+            &TOKEN_BUCKETS_V6,
+            &TOKEN_LEASES_V6,
+            &ip,
+            now,
+            config.tokens_per_second,
+            config.bucket_size,
+        )
 }
 
 #[inline(always)]
-fn check_subnet_bucket(subnet: &SubnetKey, packet_size: u64, config: &RateLimitConfig) -> bool {
-    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
-
+fn check_subnet_bucket(subnet: &SubnetKey, now: u64, config: &RateLimitConfig) -> bool {
     // Subnet limits are 128x the per-IP limit (using bit shift to avoid 128-bit math)
-    let subnet_tokens_per_sec = config.tokens_per_second << 7;
-    let subnet_bucket_size = config.bucket_size << 7;
-
-    if let Some(bucket) = unsafe { SUBNET_BUCKETS.get_ptr_mut(subnet) } {
-        let bucket = unsafe { &mut *bucket };
-
-        let elapsed = now.saturating_sub(bucket.last_update);
-        // Avoid 128-bit multiply: use u32 multiplication (values are small enough)
-        // elapsed_secs is typically 0-60, subnet_tokens_per_sec is typically < 1M
-        let elapsed_secs = (elapsed >> 30) as u32;
-        let rate = subnet_tokens_per_sec as u32;
-        let tokens_to_add = (elapsed_secs * rate) as u64;
-
-        bucket.tokens = core::cmp::min(bucket.tokens + tokens_to_add, subnet_bucket_size);
-        bucket.last_update = now;
-        bucket.packets += 1;
-        bucket.bytes += packet_size;
-
-        if bucket.tokens > 0 {
-            bucket.tokens -= 1;
-            true
-        } else {
-            bucket.dropped += 1;
-            false
-        }
-    } else {
-        let bucket = TokenBucket {
-            tokens: subnet_bucket_size.saturating_sub(1),
-            last_update: now,
-            packets: 1,
-            bytes: packet_size,
-            dropped: 0,
-        };
-        let _ = SUBNET_BUCKETS.insert(subnet, &bucket, 0);
-        true
-    }
+    spend_lease(&SUBNET_LEASES, subnet, now)
+        || lease_tokens(
+            &SUBNET_BUCKETS,
+            &SUBNET_LEASES,
+            subnet,
+            now,
+            config.tokens_per_second << 7,
+            config.bucket_size << 7,
+        )
 }
 
+/// Refill the shared bucket of `key` and lease tokens from it to this CPU
+///
+/// The bucket is only written when it refills or hands out tokens, so a
+/// source over its limit does not keep dirtying it.
 #[inline(always)]
-fn process_bucket(
-    bucket: &mut TokenBucket,
+fn lease_tokens<K>(
+    buckets: &LruHashMap<K, TokenBucket>,
+    leases: &LruPerCpuHashMap<K, TokenLease>,
+    key: &K,
     now: u64,
-    packet_size: u64,
-    config: &RateLimitConfig,
+    tokens_per_second: u64,
+    bucket_size: u64,
 ) -> bool {
+    let Some(bucket) = (unsafe { buckets.get_ptr_mut(key) }) else {
+        // First packet from this source: full bucket minus this CPU's lease
+        let taken = take_lease(leases, key, bucket_size, now);
+        let bucket = TokenBucket {
+            tokens: bucket_size.saturating_sub(taken),
+            last_update: now,
+        };
+        let _ = buckets.insert(key, &bucket, 0);
+        return taken > 0;
+    };
+    let bucket = unsafe { &mut *bucket };
+
     // Calculate tokens to add based on elapsed time
     // Avoid 128-bit multiply: use u32 multiplication (values are small enough)
+    // elapsed_secs is typically 0-60, tokens_per_second is typically < 1M
     let elapsed = now.saturating_sub(bucket.last_update);
     let elapsed_secs = (elapsed >> 30) as u32;
-    let rate = config.tokens_per_second as u32;
+    let rate = tokens_per_second as u32;
     let tokens_to_add = (elapsed_secs * rate) as u64;
 
-    // Refill bucket (capped at bucket_size)
-    bucket.tokens = core::cmp::min(bucket.tokens + tokens_to_add, config.bucket_size);
-    bucket.last_update = now;
-    bucket.packets += 1;
-    bucket.bytes += packet_size;
+    // Refill bucket (capped at bucket_size); the clock only moves on refill
+    // so steady traffic still accumulates whole seconds
+    if tokens_to_add > 0 {
+        bucket.tokens = core::cmp::min(bucket.tokens + tokens_to_add, bucket_size);
+        bucket.last_update = now;
+    }
 
-    // Check if we have tokens
-    if bucket.tokens > 0 {
-        bucket.tokens -= 1;
-        true
-    } else {
-        bucket.dropped += 1;
-        false
+    let taken = take_lease(leases, key, bucket.tokens, now);
+    if taken > 0 {
+        bucket.tokens -= taken;
     }
+    taken > 0
 }
 
 #[inline(always)]
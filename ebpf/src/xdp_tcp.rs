@@ -7,64 +7,34 @@
 //! - Invalid flag combinations detection
 //! - TCP window probing detection
 //! - Connection state tracking
+//! - Per-connection bandwidth limits of established flows
+//! - Handoff of established flows to tc_flowmark for the conntrack bypass
+//! - Per-client DNS-over-TCP query budgets on protected DNS ports
 
 #![no_std]
 #![no_main]
 
 use aya_ebpf::{
-    bindings::xdp_action,
+    bindings::{BPF_F_NO_PREALLOC, xdp_action},
     macros::{map, xdp},
-    maps::{HashMap, LruHashMap, PerCpuArray},
+    maps::{Array, HashMap, LpmTrie, LruHashMap, LruPerCpuHashMap, PerCpuArray},
     programs::XdpContext,
 };
-use core::mem;
-
-// ============================================================================
-// Network Header Structures
-// ============================================================================
-
-#[repr(C)]
-struct EthHdr {
-    h_dest: [u8; 6],
-    h_source: [u8; 6],
-    h_proto: u16,
-}
-
-#[repr(C)]
-struct Ipv4Hdr {
-    version_ihl: u8,
-    tos: u8,
-    tot_len: u16,
-    id: u16,
-    frag_off: u16,
-    ttl: u8,
-    protocol: u8,
-    check: u16,
-    saddr: u32,
-    daddr: u32,
-}
-
-#[repr(C)]
-struct Ipv6Hdr {
-    version_tc_flow: u32,
-    payload_len: u16,
-    nexthdr: u8,
-    hop_limit: u8,
-    saddr: [u8; 16],
-    daddr: [u8; 16],
-}
-
-#[repr(C)]
-struct TcpHdr {
-    source: u16,
-    dest: u16,
-    seq: u32,
-    ack_seq: u32,
-    doff_flags: u16,
-    window: u16,
-    check: u16,
-    urg_ptr: u16,
-}
+use pistonprotection_ebpf::{
+    BlockReason, CgnatSignals, DnsBudgetConfig, DnsRate, DropContext, DropCounter, EventBudget,
+    EventBudgetConfig, FlowBucket, FlowKey, FlowMarkConfig, FlowMarkEntry, FlowRateConfig,
+    HandshakeRecord, LatencyBucket, LatencyConfig, PenaltyConfig, PenaltyEntry, SampleConfig,
+    TenantDstV6Key,
+    breakdown::{DST_PORT_MAX_ENTRIES, REASON_BUCKETS},
+    cgnat, dns, dns_budget_take, drop_context_reset, drop_context_set_reason,
+    drop_context_set_source_v4, drop_context_set_source_v6, drop_context_set_target, drop_events,
+    emit_drop_event, flow_bucket_take, flow_mark, flow_rate, forget_flow, frame_len,
+    hash_ipv6_addr, known_good, latency, latency_elapsed, latency_start, limit_multiplier,
+    lookup_flow_rate, observe_verdict, parse_eth, parse_ipv4, parse_ipv6_with_ext, parse_tcp,
+    peek_dst_port, penalty, penalty_blocked, penalty_config, penalty_divisor, penalty_key_v4,
+    record_cgnat_signals, record_drop, record_handshake, record_latency, record_validated_flow,
+    record_violation, sample_packet, sampling, syn_signature,
+};
 
 // ============================================================================
 // TCP Filtering Structures
@@ -81,6 +51,8 @@ pub struct TcpConnectionState {
     pub initial_seq: u32,
     /// Expected ACK (for SYN cookie)
     pub expected_ack: u32,
+    /// Shape of the SYN, for CGNAT detection
+    pub syn_fingerprint: u32,
     /// Packets seen
     pub packets: u64,
     /// Bytes seen
@@ -93,6 +65,13 @@ pub struct TcpConnectionState {
     pub window_scale: u8,
     /// MSS (if negotiated)
     pub mss: u16,
+    /// Source address (IPv4-mapped for IPv4), so userspace can reconcile
+    /// `TcpIpState::active_connections` against this table
+    pub src_addr: [u8; 16],
+    /// TCP timestamp of the SYN, valid with `CONN_FLAG_TIMESTAMP`
+    pub syn_tsval: u32,
+    /// Bandwidth bucket, used once established if the backend limits it
+    pub rate: FlowBucket,
 }
 
 /// Per-IP TCP state for flood detection
@@ -112,7 +91,8 @@ pub struct TcpIpState {
     pub window_start: u64,
     /// Last seen timestamp
     pub last_seen: u64,
-    /// Active connections count
+    /// Connections holding a slot of the per-IP budget: taken on SYN,
+    /// returned on FIN or RST and by the userspace reconciliation sweep
     pub active_connections: u32,
     /// Blocked until timestamp
     pub blocked_until: u64,
@@ -198,6 +178,9 @@ pub struct TcpStats {
     pub dropped_invalid_ack: u64,
     pub dropped_handshake_timeout: u64,
     pub incomplete_handshakes_detected: u64,
+    pub dropped_flow_rate: u64,
+    pub dns_queries: u64,
+    pub dropped_dns_rate_limited: u64,
 }
 
 /// Per-IP incomplete handshake tracking
@@ -254,6 +237,10 @@ const FLAG_CONNECTION_LIMIT: u32 = 0x0020;
 // Connection state flags
 const CONN_FLAG_SYN_COOKIE: u8 = 0x01;
 const CONN_FLAG_VALIDATED: u8 = 0x02;
+const CONN_FLAG_HANDSHAKE_RECORDED: u8 = 0x04;
+const CONN_FLAG_COUNTED: u8 = 0x08; // Holds a slot of the source's active_connections
+const CONN_FLAG_TIMESTAMP: u8 = 0x10; // The SYN carried a TCP timestamp
+const CONN_FLAG_FLOW_MARKED: u8 = 0x20; // Handed to tc_flowmark in FLOW_MARKS
 
 // Default configuration
 const DEFAULT_SYN_COOKIE_THRESHOLD: u64 = 10000; // SYNs per second to trigger cookies
@@ -274,11 +261,6 @@ const MSS_TABLE: [u16; 4] = [536, 1300, 1440, 1460];
 const IP_MF: u16 = 0x2000; // More Fragments flag
 const IP_OFFSET: u16 = 0x1FFF; // Fragment offset mask
 
-// IPv6 fragmentation constants
-const IPPROTO_FRAGMENT: u8 = 44; // IPv6 Fragment extension header
-const IPV6_FRAG_OFFSET_MASK: u16 = 0xFFF8; // Upper 13 bits (fragment offset in 8-byte units)
-const IPV6_FRAG_M_FLAG: u16 = 0x0001; // More fragments flag (lowest bit)
-
 // SYN cookie secrets MUST be set by userspace with cryptographically random values
 // No defaults are provided to prevent use of predictable secrets
 // The SYN_COOKIE_SECRETS map must be populated before enabling SYN flood protection
@@ -310,6 +292,11 @@ static SYN_COOKIES: LruHashMap<u64, SynCookieEntry> = LruHashMap::with_max_entri
 static INCOMPLETE_HANDSHAKES_V4: LruHashMap<u32, IncompleteHandshakeState> =
     LruHashMap::with_max_entries(500_000, 0);
 
+/// Incomplete handshake tracking per IP (IPv6, keyed by full address)
+#[map]
+static INCOMPLETE_HANDSHAKES_V6: LruHashMap<[u8; 16], IncompleteHandshakeState> =
+    LruHashMap::with_max_entries(250_000, 0);
+
 /// Global SYN state (for system-wide flood detection)
 #[map]
 static GLOBAL_SYN_STATE: PerCpuArray<GlobalSynState> = PerCpuArray::with_max_entries(1, 0);
@@ -322,10 +309,69 @@ static SYN_COOKIE_SECRETS: PerCpuArray<[u32; 2]> = PerCpuArray::with_max_entries
 #[map]
 static TCP_PROTECTED_PORTS: HashMap<u16, u32> = HashMap::with_max_entries(1000, 0);
 
+/// DNS query budget per client, written by the worker
+#[map]
+static DNS_BUDGET: Array<DnsBudgetConfig> = Array::with_max_entries(1, 0);
+
+/// DNS-over-TCP queries per client in the current second
+#[map]
+static DNS_RATES: LruHashMap<[u8; 16], DnsRate> = LruHashMap::with_max_entries(dns::MAX_CLIENTS, 0);
+
 /// Whitelisted IPs
 #[map]
 static TCP_WHITELIST: HashMap<u32, u32> = HashMap::with_max_entries(10_000, 0);
 
+/// Whitelisted IPs (IPv6)
+#[map]
+static TCP_WHITELIST_V6: HashMap<[u8; 16], u32> = HashMap::with_max_entries(10_000, 0);
+
+/// Penalties of repeat offenders, shared with the other programs
+#[map(name = "PENALTY")]
+static PENALTY: LruHashMap<[u8; 16], PenaltyEntry> = LruHashMap::pinned(penalty::MAX_SOURCES, 0);
+
+/// Penalty ladder per protection level, shared with the other programs
+#[map(name = "PENALTY_CONFIG")]
+static PENALTY_CONFIG: Array<PenaltyConfig> = Array::pinned(penalty::LEVELS, 0);
+
+/// Set while a backend is learning its known-good sources, shared with the other programs
+#[map(name = "LEARNING")]
+static LEARNING: Array<u32> = Array::pinned(1, 0);
+
+/// Sources that completed handshakes while learning, shared with the other programs
+#[map(name = "HANDSHAKES")]
+static HANDSHAKES: LruHashMap<[u8; 16], HandshakeRecord> =
+    LruHashMap::pinned(known_good::MAX_HANDSHAKES, 0);
+
+/// Known-good sources and their limit multiplier, shared with the other programs
+#[map(name = "KNOWN_GOOD")]
+static KNOWN_GOOD: HashMap<[u8; 16], u32> = HashMap::pinned(known_good::MAX_SOURCES, 0);
+
+/// CGNAT ranges and their limit multiplier, shared with the other programs
+#[map(name = "CGNAT_RANGES")]
+static CGNAT_RANGES: LpmTrie<[u8; 16], u32> = LpmTrie::pinned(cgnat::MAX_RANGES, BPF_F_NO_PREALLOC);
+
+/// Set while CGNAT detection is on, shared with the other programs
+#[map(name = "CGNAT_DETECT")]
+static CGNAT_DETECT: Array<u32> = Array::pinned(1, 0);
+
+/// SYN signals of validated sources, shared with the other programs
+#[map(name = "CGNAT_SIGNALS")]
+static CGNAT_SIGNALS: LruHashMap<[u8; 16], CgnatSignals> =
+    LruHashMap::pinned(cgnat::MAX_SOURCES, 0);
+
+/// Per-connection bandwidth limits, shared with xdp_udp
+#[map(name = "FLOW_RATES")]
+static FLOW_RATES: HashMap<TenantDstV6Key, FlowRateConfig> =
+    HashMap::pinned(flow_rate::MAX_DESTINATIONS, 0);
+
+/// Established flows that may skip conntrack, shared with tc_flowmark
+#[map(name = "FLOW_MARKS")]
+static FLOW_MARKS: LruHashMap<FlowKey, FlowMarkEntry> = LruHashMap::pinned(flow_mark::MAX_FLOWS, 0);
+
+/// Conntrack bypass configuration, shared with tc_flowmark
+#[map(name = "FLOW_MARK_CONFIG")]
+static FLOW_MARK_CONFIG: Array<FlowMarkConfig> = Array::pinned(1, 0);
+
 /// Configuration
 #[map]
 static TCP_CONFIG: PerCpuArray<TcpConfig> = PerCpuArray::with_max_entries(1, 0);
@@ -334,6 +380,51 @@ static TCP_CONFIG: PerCpuArray<TcpConfig> = PerCpuArray::with_max_entries(1, 0);
 #[map]
 static TCP_STATS: PerCpuArray<TcpStats> = PerCpuArray::with_max_entries(1, 0);
 
+/// Drops broken down by (protocol, destination port)
+#[map]
+static DROPS_BY_DST_PORT: LruPerCpuHashMap<u32, DropCounter> =
+    LruPerCpuHashMap::with_max_entries(DST_PORT_MAX_ENTRIES, 0);
+
+/// Drops broken down by BlockReason
+#[map]
+static DROPS_BY_REASON: PerCpuArray<DropCounter> = PerCpuArray::with_max_entries(REASON_BUCKETS, 0);
+
+/// Per-CPU drop attribution for the packet being processed
+#[map]
+static DROP_CONTEXT: PerCpuArray<DropContext> = PerCpuArray::with_max_entries(1, 0);
+
+/// Set while the worker is in observe mode, shared with the other programs
+#[map(name = "OBSERVE")]
+static OBSERVE: Array<u32> = Array::pinned(1, 0);
+
+/// Flow sampling configuration
+#[map]
+static SAMPLE_CONFIG: PerCpuArray<SampleConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Sampled packet headers for the userspace analyzer
+#[map]
+static SAMPLES: sampling::SampleMap = sampling::sample_map();
+
+/// Dropped packets for the userspace drop event aggregator
+#[map]
+static DROP_EVENTS: drop_events::EventMap = drop_events::event_map();
+
+/// Event budget of each CPU, written by userspace
+#[map]
+static EVENT_BUDGET_CONFIG: PerCpuArray<EventBudgetConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Event token buckets and per-class event counters
+#[map]
+static EVENT_BUDGET: PerCpuArray<EventBudget> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing latency measurement configuration
+#[map]
+static LATENCY_CONFIG: PerCpuArray<LatencyConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing time histograms, per protection level
+#[map]
+static LATENCY: PerCpuArray<LatencyBucket> = PerCpuArray::with_max_entries(latency::MAX_ENTRIES, 0);
+
 // ============================================================================
 // Constants
 // ============================================================================
@@ -346,12 +437,45 @@ const IPPROTO_TCP: u8 = 6;
 // Main XDP Entry Point
 // ============================================================================
 
-#[xdp]
+#[cfg_attr(feature = "frags", xdp(frags))]
+#[cfg_attr(not(feature = "frags"), xdp)]
 pub fn xdp_tcp(ctx: XdpContext) -> u32 {
-    match try_xdp_tcp(ctx) {
+    let started = latency_start(&LATENCY_CONFIG);
+    drop_context_reset(&DROP_CONTEXT, BlockReason::GenericDdos);
+    let bytes = frame_len(ctx.ctx) as u64;
+    let raw_ctx = ctx.ctx;
+
+    let action = match try_xdp_tcp(ctx) {
         Ok(ret) => ret,
         Err(_) => xdp_action::XDP_PASS,
+    };
+
+    if action == xdp_action::XDP_DROP {
+        record_drop(&DROP_CONTEXT, &DROPS_BY_DST_PORT, &DROPS_BY_REASON, bytes);
+        emit_drop_event(
+            raw_ctx,
+            &DROP_CONTEXT,
+            &DROP_EVENTS,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+            bytes,
+        );
+    } else if action == xdp_action::XDP_PASS {
+        sample_packet(
+            raw_ctx,
+            bytes,
+            &SAMPLE_CONFIG,
+            &SAMPLES,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+        );
+    }
+
+    if let Some(elapsed) = latency_elapsed(started) {
+        record_latency(&LATENCY, elapsed, 0);
     }
+
+    observe_verdict(&OBSERVE, action)
 }
 
 #[inline(always)]
@@ -365,16 +489,13 @@ fn try_xdp_tcp(ctx: XdpContext) -> Result<u32, ()> {
     let data_end = ctx.data_end();
 
     // Parse Ethernet header
-    if data + mem::size_of::<EthHdr>() > data_end {
+    let Some((eth_proto, l3_offset)) = parse_eth(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let eth = unsafe { &*(data as *const EthHdr) };
-    let eth_proto = u16::from_be(eth.h_proto);
+    };
 
     match eth_proto {
-        ETH_P_IP => process_ipv4(&ctx, data + mem::size_of::<EthHdr>(), data_end, &config),
-        ETH_P_IPV6 => process_ipv6(&ctx, data + mem::size_of::<EthHdr>(), data_end, &config),
+        ETH_P_IP => process_ipv4(&ctx, l3_offset, data_end, &config),
+        ETH_P_IPV6 => process_ipv6(&ctx, l3_offset, data_end, &config),
         _ => Ok(xdp_action::XDP_PASS),
     }
 }
@@ -390,11 +511,16 @@ fn process_ipv4(
     data_end: usize,
     config: &TcpConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv4Hdr>() > data_end {
+    let Some((ip, tcp_data)) = parse_ipv4(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
+    };
 
-    let ip = unsafe { &*(data as *const Ipv4Hdr) };
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        ip.protocol,
+        peek_dst_port(tcp_data, data_end, ip.protocol),
+    );
+    drop_context_set_source_v4(&DROP_CONTEXT, ip.saddr);
 
     // Only process TCP
     if ip.protocol != IPPROTO_TCP {
@@ -454,31 +580,33 @@ fn process_ipv4(
         return Ok(xdp_action::XDP_DROP);
     }
 
-    let ihl = (ihl_raw as usize) * 4;
-
     // Additional bounds check: ensure TCP header is within packet
-    if data + ihl > data_end {
+    if tcp_data > data_end {
         return Ok(xdp_action::XDP_DROP);
     }
 
-    let tcp_data = data + ihl;
-
-    process_tcp(ctx, tcp_data, data_end, src_ip, dst_ip, config)
+    let maps = IpMaps {
+        state: &TCP_IP_STATE_V4,
+        handshakes: &INCOMPLETE_HANDSHAKES_V4,
+    };
+    process_tcp(
+        ctx,
+        tcp_data,
+        data_end,
+        &maps,
+        &src_ip,
+        &penalty_key_v4(src_ip),
+        &penalty_key_v4(dst_ip),
+        src_ip,
+        dst_ip,
+        config,
+    )
 }
 
 // ============================================================================
 // IPv6 Processing
 // ============================================================================
 
-/// IPv6 Fragment Header structure
-#[repr(C)]
-struct Ipv6FragHdr {
-    nexthdr: u8,
-    reserved: u8,
-    frag_off_m: u16, // Fragment offset (13 bits) + Reserved (2 bits) + M flag (1 bit)
-    identification: u32,
-}
-
 #[inline(always)]
 fn process_ipv6(
     ctx: &XdpContext,
@@ -486,75 +614,19 @@ fn process_ipv6(
     data_end: usize,
     config: &TcpConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv6Hdr>() > data_end {
+    // Skip extension headers (hop-by-hop, routing, fragment, ...) to reach TCP
+    let Some((ip6, l4)) = parse_ipv6_with_ext(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let ip6 = unsafe { &*(data as *const Ipv6Hdr) };
-    let mut next_header = ip6.nexthdr;
-    let mut header_offset = data + mem::size_of::<Ipv6Hdr>();
-    let mut is_fragmented = false;
-    let mut is_first_fragment = true;
-
-    // ========================================================================
-    // IPv6 EXTENSION HEADER PARSING
-    // ========================================================================
-    // IPv6 can have extension headers between the main header and TCP.
-    // We need to check for the Fragment extension header (protocol 44).
-    // For simplicity, we only handle: Hop-by-Hop (0), Routing (43), Fragment (44), Dest Options (60)
-    // ========================================================================
-
-    // Bounded loop for eBPF verifier - max 3 extension headers is reasonable
-    for _ in 0..3 {
-        match next_header {
-            IPPROTO_TCP => {
-                // Found TCP, done parsing extension headers
-                break;
-            }
-            IPPROTO_FRAGMENT => {
-                // Fragment extension header
-                if header_offset + mem::size_of::<Ipv6FragHdr>() > data_end {
-                    return Ok(xdp_action::XDP_PASS);
-                }
-
-                let frag_hdr = unsafe { &*(header_offset as *const Ipv6FragHdr) };
-                let frag_off_m = u16::from_be(frag_hdr.frag_off_m);
-
-                // Check fragment offset (bits 3-15) and M flag (bit 0)
-                let frag_offset = frag_off_m & IPV6_FRAG_OFFSET_MASK;
-                let more_fragments = (frag_off_m & IPV6_FRAG_M_FLAG) != 0;
-
-                is_fragmented = more_fragments || frag_offset != 0;
-                is_first_fragment = frag_offset == 0;
-
-                next_header = frag_hdr.nexthdr;
-                header_offset += mem::size_of::<Ipv6FragHdr>();
-            }
-            0 | 43 | 60 => {
-                // Hop-by-Hop (0), Routing (43), Destination Options (60)
-                // These have a common format: next_header, length, data
-                if header_offset + 2 > data_end {
-                    return Ok(xdp_action::XDP_PASS);
-                }
-
-                let ext_next = unsafe { *(header_offset as *const u8) };
-                let ext_len = unsafe { *((header_offset + 1) as *const u8) };
-                // Length is in 8-byte units, not including first 8 bytes
-                let total_len = ((ext_len as usize) + 1) * 8;
+    };
 
-                next_header = ext_next;
-                header_offset += total_len;
-            }
-            _ => {
-                // Unknown or unsupported extension header
-                // If it's not TCP, we can't process it
-                return Ok(xdp_action::XDP_PASS);
-            }
-        }
-    }
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        l4.protocol,
+        peek_dst_port(l4.offset, data_end, l4.protocol),
+    );
+    drop_context_set_source_v6(&DROP_CONTEXT, &ip6.saddr);
 
-    // After parsing, check if we found TCP
-    if next_header != IPPROTO_TCP {
+    if l4.protocol != IPPROTO_TCP {
         return Ok(xdp_action::XDP_PASS);
     }
 
@@ -563,8 +635,8 @@ fn process_ipv6(
     // ========================================================================
     // Same strategy as IPv4: drop non-first fragments, be suspicious of first fragments
     // ========================================================================
-    if is_fragmented {
-        if !is_first_fragment {
+    if l4.is_fragment {
+        if !l4.is_first_fragment {
             // Non-first fragment - has no TCP header, can't inspect
             // Drop at protection level 2+ (like UDP does)
             if config.protection_level >= 2 {
@@ -583,37 +655,65 @@ fn process_ipv6(
 
     let src_ip = ip6.saddr;
 
+    // Check whitelist
+    if unsafe { TCP_WHITELIST_V6.get(&src_ip) }.is_some() {
+        return Ok(xdp_action::XDP_PASS);
+    }
+
     // Check if IP is blocked
     if is_ip_blocked_v6(&src_ip) {
         update_stats_blocked();
         return Ok(xdp_action::XDP_DROP);
     }
 
-    // Use last 4 bytes as simplified IP keys
-    let src_key = u32::from_be_bytes([src_ip[12], src_ip[13], src_ip[14], src_ip[15]]);
-    let dst_key = u32::from_be_bytes([ip6.daddr[12], ip6.daddr[13], ip6.daddr[14], ip6.daddr[15]]);
+    // Per-IP state is keyed on the full address; connection keys and SYN
+    // cookies only need a 32-bit digest of each endpoint
+    let maps = IpMaps {
+        state: &TCP_IP_STATE_V6,
+        handshakes: &INCOMPLETE_HANDSHAKES_V6,
+    };
+    let src_digest = hash_ipv6_addr(&src_ip);
+    let dst_digest = hash_ipv6_addr(&ip6.daddr);
 
-    process_tcp(ctx, header_offset, data_end, src_key, dst_key, config)
+    process_tcp(
+        ctx, l4.offset, data_end, &maps, &src_ip, &src_ip, &ip6.daddr, src_digest, dst_digest,
+        config,
+    )
 }
 
 // ============================================================================
 // TCP Processing
 // ============================================================================
 
+/// Per-IP tracking maps for one address family
+///
+/// The TCP path is generic over the source key (`u32` for IPv4, `[u8; 16]`
+/// for IPv6) so IPv6 hosts sharing their low 32 bits never share state.
+struct IpMaps<K: 'static> {
+    state: &'static LruHashMap<K, TcpIpState>,
+    handshakes: &'static LruHashMap<K, IncompleteHandshakeState>,
+}
+
+/// `src_key` indexes the per-IP maps and `penalty_key` the shared `PENALTY`
+/// and learning maps, `dst_addr` is the full destination address (IPv4-mapped
+/// for IPv4); `src_ip`/`dst_ip` are 32-bit endpoint digests used for
+/// connection keys and SYN cookies.
 #[inline(always)]
-fn process_tcp(
+fn process_tcp<K>(
     ctx: &XdpContext,
     data: usize,
     data_end: usize,
+    maps: &IpMaps<K>,
+    src_key: &K,
+    penalty_key: &[u8; 16],
+    dst_addr: &[u8; 16],
     src_ip: u32,
     dst_ip: u32,
     config: &TcpConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<TcpHdr>() > data_end {
+    let Some((tcp, options_end)) = parse_tcp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let tcp = unsafe { &*(data as *const TcpHdr) };
+    };
     let src_port = u16::from_be(tcp.source);
     let dst_port = u16::from_be(tcp.dest);
     let seq = u32::from_be(tcp.seq);
@@ -630,22 +730,46 @@ fn process_tcp(
     if is_invalid_flag_combination(flags) {
         update_stats_invalid_flags();
         if config.protection_level >= 1 {
-            record_invalid_flags(src_ip);
+            record_invalid_flags(maps, src_key);
             return Ok(xdp_action::XDP_DROP);
         }
     }
 
     // Step 2: Update per-IP state and check for floods
-    if let Some(action) = update_ip_state_and_check_floods(src_ip, flags, now, config) {
+    if let Some(action) =
+        update_ip_state_and_check_floods(maps, src_key, penalty_key, flags, now, config)
+    {
         return Ok(action);
     }
 
     // Step 3: Handle specific TCP packet types
     let tcp_flags = flags & 0x003f; // Just the 6 main flags
 
+    // DNS queries on protected DNS ports are held to the client's budget
+    if tcp_flags & TCP_ACK != 0
+        && tcp_flags & (TCP_SYN | TCP_RST) == 0
+        && !check_dns_queries(options_end, data_end, penalty_key, dst_port, now)
+    {
+        update_stats_dns_rate_limited();
+        return Ok(xdp_action::XDP_DROP);
+    }
+
     if tcp_flags == TCP_SYN {
         // Pure SYN packet - handle SYN flood protection
-        return handle_syn_packet(ctx, src_ip, dst_ip, src_port, dst_port, seq, now, config);
+        return handle_syn_packet(
+            ctx,
+            maps,
+            src_key,
+            penalty_key,
+            syn_signature(data, options_end, data_end),
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+            seq,
+            now,
+            config,
+        );
     }
 
     if tcp_flags == (TCP_SYN | TCP_ACK) {
@@ -657,13 +781,39 @@ fn process_tcp(
     if tcp_flags & TCP_ACK != 0 && tcp_flags & TCP_SYN == 0 {
         // ACK packet (possibly with other flags)
         return handle_ack_packet(
-            ctx, src_ip, dst_ip, src_port, dst_port, seq, ack_seq, tcp_flags, window, now, config,
+            ctx,
+            maps,
+            src_key,
+            penalty_key,
+            dst_addr,
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+            seq,
+            ack_seq,
+            tcp_flags,
+            window,
+            now,
+            config,
         );
     }
 
     if tcp_flags == TCP_RST || tcp_flags == (TCP_RST | TCP_ACK) {
         // RST packet
-        return handle_rst_packet(ctx, src_ip, now, config);
+        return handle_rst_packet(
+            ctx,
+            maps,
+            src_key,
+            penalty_key,
+            dst_addr,
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+            now,
+            config,
+        );
     }
 
     // Step 4: Window probing detection
@@ -677,6 +827,53 @@ fn process_tcp(
     Ok(xdp_action::XDP_PASS)
 }
 
+// ============================================================================
+// DNS-over-TCP
+// ============================================================================
+
+/// Count the DNS queries of a segment against the client's budget; false
+/// means the client is over it
+///
+/// Each query carries a two-byte length prefix (RFC 1035 Section 4.2.2), so
+/// queries pipelined in one segment are counted by walking the prefixes. A
+/// segment continuing a query split across segments is counted as a query
+/// of its own, which only errs towards the client's budget.
+#[inline(always)]
+fn check_dns_queries(
+    payload: usize,
+    data_end: usize,
+    src_addr: &[u8; 16],
+    dst_port: u16,
+    now: u64,
+) -> bool {
+    if payload + 2 > data_end {
+        return true;
+    }
+    let is_dns = unsafe { TCP_PROTECTED_PORTS.get(&dst_port) }
+        .is_some_and(|flags| *flags & dns::PORT_DNS != 0);
+    if !is_dns {
+        return true;
+    }
+
+    let mut queries: u64 = 0;
+    let mut offset = payload;
+    for _ in 0..dns::MAX_PIPELINED {
+        if offset + 2 > data_end {
+            break;
+        }
+        let len = unsafe { u16::from_be(*(offset as *const u16)) } as usize;
+        queries += 1;
+        offset += 2 + len;
+    }
+    update_stats_dns_queries(queries);
+
+    let qps = match DNS_BUDGET.get(0) {
+        Some(budget) if budget.tcp_qps != 0 => budget.tcp_qps as u64,
+        _ => return true,
+    };
+    dns_budget_take(&DNS_RATES, src_addr, qps, queries, now)
+}
+
 // ============================================================================
 // Flag Validation
 // ============================================================================
@@ -724,8 +921,8 @@ fn is_invalid_flag_combination(flags: u16) -> bool {
 }
 
 #[inline(always)]
-fn record_invalid_flags(src_ip: u32) {
-    if let Some(state) = unsafe { TCP_IP_STATE_V4.get_ptr_mut(&src_ip) } {
+fn record_invalid_flags<K>(maps: &IpMaps<K>, src_key: &K) {
+    if let Some(state) = unsafe { maps.state.get_ptr_mut(src_key) } {
         let state = unsafe { &mut *state };
         state.invalid_packets += 1;
         state.flags |= FLAG_INVALID_FLAGS;
@@ -737,8 +934,10 @@ fn record_invalid_flags(src_ip: u32) {
 // ============================================================================
 
 #[inline(always)]
-fn update_ip_state_and_check_floods(
-    src_ip: u32,
+fn update_ip_state_and_check_floods<K>(
+    maps: &IpMaps<K>,
+    src_key: &K,
+    penalty_key: &[u8; 16],
     flags: u16,
     now: u64,
     config: &TcpConfig,
@@ -749,9 +948,19 @@ fn update_ip_state_and_check_floods(
         DEFAULT_RATE_LIMIT_WINDOW_NS
     };
 
+    // Sources with a penalty get smaller thresholds, or are dropped outright
+    // while serving a block earned in any program
+    if penalty_blocked(&PENALTY, penalty_key, now) {
+        return Some(xdp_action::XDP_DROP);
+    }
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let divisor = penalty_divisor(&PENALTY, penalty_key, &ladder, now);
+    // Known-good sources and CGNAT addresses get larger ones
+    let multiplier = limit_multiplier(&KNOWN_GOOD, &CGNAT_RANGES, penalty_key);
+
     let tcp_flags = flags & 0x003f;
 
-    if let Some(state) = unsafe { TCP_IP_STATE_V4.get_ptr_mut(&src_ip) } {
+    if let Some(state) = unsafe { maps.state.get_ptr_mut(src_key) } {
         let state = unsafe { &mut *state };
 
         // Check if blocked
@@ -781,9 +990,18 @@ fn update_ip_state_and_check_floods(
                 DEFAULT_MAX_SYN_PER_IP
             };
 
-            if config.syn_flood_protection != 0 && state.syn_packets > max_syn {
+            if config.syn_flood_protection != 0
+                && state.syn_packets > max_syn * multiplier / divisor
+            {
                 state.flags |= FLAG_SYN_FLOOD;
-                state.blocked_until = now + config.block_duration_ns;
+                state.blocked_until = record_violation(
+                    &DROP_CONTEXT,
+                    &PENALTY,
+                    penalty_key,
+                    &ladder,
+                    now,
+                    state.window_start,
+                );
                 update_stats_syn_flood();
                 return Some(xdp_action::XDP_DROP);
             }
@@ -797,9 +1015,17 @@ fn update_ip_state_and_check_floods(
                 DEFAULT_MAX_ACK_PER_IP
             };
 
-            if config.ack_flood_detection != 0 && state.ack_packets > max_ack {
+            if config.ack_flood_detection != 0 && state.ack_packets > max_ack * multiplier / divisor
+            {
                 state.flags |= FLAG_ACK_FLOOD;
-                state.blocked_until = now + config.block_duration_ns;
+                state.blocked_until = record_violation(
+                    &DROP_CONTEXT,
+                    &PENALTY,
+                    penalty_key,
+                    &ladder,
+                    now,
+                    state.window_start,
+                );
                 update_stats_ack_flood();
                 return Some(xdp_action::XDP_DROP);
             }
@@ -813,9 +1039,17 @@ fn update_ip_state_and_check_floods(
                 DEFAULT_MAX_RST_PER_IP
             };
 
-            if config.rst_flood_detection != 0 && state.rst_packets > max_rst {
+            if config.rst_flood_detection != 0 && state.rst_packets > max_rst * multiplier / divisor
+            {
                 state.flags |= FLAG_RST_FLOOD;
-                state.blocked_until = now + config.block_duration_ns;
+                state.blocked_until = record_violation(
+                    &DROP_CONTEXT,
+                    &PENALTY,
+                    penalty_key,
+                    &ladder,
+                    now,
+                    state.window_start,
+                );
                 update_stats_rst_flood();
                 return Some(xdp_action::XDP_DROP);
             }
@@ -844,7 +1078,7 @@ fn update_ip_state_and_check_floods(
             blocked_until: 0,
             flags: 0,
         };
-        let _ = TCP_IP_STATE_V4.insert(&src_ip, &state, 0);
+        let _ = maps.state.insert(src_key, &state, 0);
         None
     }
 }
@@ -854,8 +1088,12 @@ fn update_ip_state_and_check_floods(
 // ============================================================================
 
 #[inline(always)]
-fn handle_syn_packet(
+fn handle_syn_packet<K>(
     ctx: &XdpContext,
+    maps: &IpMaps<K>,
+    src_key: &K,
+    src_addr: &[u8; 16],
+    (syn_fingerprint, syn_tsval): (u32, Option<u32>),
     src_ip: u32,
     dst_ip: u32,
     src_port: u16,
@@ -868,12 +1106,12 @@ fn handle_syn_packet(
     let _is_protected = unsafe { TCP_PROTECTED_PORTS.get(&dst_port) }.is_some();
 
     // Check for incomplete handshake abuse (spoofed IPs)
-    if let Some(action) = check_incomplete_handshake_limit(src_ip, now, config) {
+    if let Some(action) = check_incomplete_handshake_limit(maps, src_key, now, config) {
         return Ok(action);
     }
 
     // Track this as a new incomplete handshake
-    track_incomplete_handshake(src_ip, now, config);
+    track_incomplete_handshake(maps, src_key, now, config);
 
     // Check global SYN rate for cookie mode decision
     let use_cookies = should_use_syn_cookies(now, config);
@@ -900,14 +1138,24 @@ fn handle_syn_packet(
         // For now, we pass the SYN and rely on userspace or kernel to respond
     }
 
-    // Connection limit check
-    if let Some(state) = unsafe { TCP_IP_STATE_V4.get_ptr_mut(&src_ip) } {
+    let conn_key = make_connection_key(src_ip, dst_ip, src_port, dst_port);
+    let mut conn_flags = if use_cookies { CONN_FLAG_SYN_COOKIE } else { 0 };
+
+    // Connection limit check; a retransmitted SYN keeps the slot it took
+    let retransmit = unsafe { TCP_CONNECTIONS.get(&conn_key) }
+        .is_some_and(|conn| conn.flags & CONN_FLAG_COUNTED != 0);
+    if retransmit {
+        conn_flags |= CONN_FLAG_COUNTED;
+    } else if let Some(state) = unsafe { maps.state.get_ptr_mut(src_key) } {
         let state = unsafe { &mut *state };
         let max_conn = if config.max_connections_per_ip != 0 {
             config.max_connections_per_ip
         } else {
             DEFAULT_MAX_CONNECTIONS_PER_IP
         };
+        // Known-good sources and CGNAT addresses may hold more connections
+        let max_conn =
+            (max_conn as u64 * limit_multiplier(&KNOWN_GOOD, &CGNAT_RANGES, src_addr)) as u32;
 
         if state.active_connections >= max_conn {
             state.flags |= FLAG_CONNECTION_LIMIT;
@@ -916,21 +1164,29 @@ fn handle_syn_packet(
         }
 
         state.active_connections += 1;
+        conn_flags |= CONN_FLAG_COUNTED;
+    }
+
+    if syn_tsval.is_some() {
+        conn_flags |= CONN_FLAG_TIMESTAMP;
     }
 
     // Track the connection
-    let conn_key = make_connection_key(src_ip, dst_ip, src_port, dst_port);
     let conn_state = TcpConnectionState {
         state: 1, // SYN received
-        flags: if use_cookies { CONN_FLAG_SYN_COOKIE } else { 0 },
+        flags: conn_flags,
         initial_seq: seq,
         expected_ack: seq.wrapping_add(1),
+        syn_fingerprint,
         packets: 1,
         bytes: 0,
         first_seen: now,
         last_seen: now,
         window_scale: 0,
         mss: 0,
+        src_addr: *src_addr,
+        syn_tsval: syn_tsval.unwrap_or(0),
+        rate: FlowBucket::default(),
     };
     let _ = TCP_CONNECTIONS.insert(&conn_key, &conn_state, 0);
 
@@ -944,14 +1200,14 @@ fn handle_syn_packet(
 
 /// Track a new incomplete handshake (SYN without completing 3-way handshake)
 #[inline(always)]
-fn track_incomplete_handshake(src_ip: u32, now: u64, config: &TcpConfig) {
+fn track_incomplete_handshake<K>(maps: &IpMaps<K>, src_key: &K, now: u64, config: &TcpConfig) {
     let timeout = if config.handshake_timeout_ns != 0 {
         config.handshake_timeout_ns
     } else {
         DEFAULT_HANDSHAKE_TIMEOUT_NS
     };
 
-    if let Some(state) = unsafe { INCOMPLETE_HANDSHAKES_V4.get_ptr_mut(&src_ip) } {
+    if let Some(state) = unsafe { maps.handshakes.get_ptr_mut(src_key) } {
         let state = unsafe { &mut *state };
 
         // Check if we're in a new window
@@ -970,7 +1226,7 @@ fn track_incomplete_handshake(src_ip: u32, now: u64, config: &TcpConfig) {
             window_start: now,
             last_seen: now,
         };
-        let _ = INCOMPLETE_HANDSHAKES_V4.insert(&src_ip, &state, 0);
+        let _ = maps.handshakes.insert(src_key, &state, 0);
     }
 
     update_stats_incomplete_handshake();
@@ -978,7 +1234,12 @@ fn track_incomplete_handshake(src_ip: u32, now: u64, config: &TcpConfig) {
 
 /// Check if IP has too many incomplete handshakes (likely spoofed)
 #[inline(always)]
-fn check_incomplete_handshake_limit(src_ip: u32, now: u64, config: &TcpConfig) -> Option<u32> {
+fn check_incomplete_handshake_limit<K>(
+    maps: &IpMaps<K>,
+    src_key: &K,
+    now: u64,
+    config: &TcpConfig,
+) -> Option<u32> {
     let max_incomplete = if config.max_incomplete_handshakes_per_ip != 0 {
         config.max_incomplete_handshakes_per_ip
     } else {
@@ -991,7 +1252,7 @@ fn check_incomplete_handshake_limit(src_ip: u32, now: u64, config: &TcpConfig) -
         DEFAULT_HANDSHAKE_TIMEOUT_NS
     };
 
-    if let Some(state) = unsafe { INCOMPLETE_HANDSHAKES_V4.get(&src_ip) } {
+    if let Some(state) = unsafe { maps.handshakes.get(src_key) } {
         // Only count if within timeout window
         if now.saturating_sub(state.window_start) <= timeout {
             if state.count >= max_incomplete {
@@ -1006,8 +1267,8 @@ fn check_incomplete_handshake_limit(src_ip: u32, now: u64, config: &TcpConfig) -
 
 /// Clear incomplete handshake tracking when handshake completes
 #[inline(always)]
-fn clear_incomplete_handshake(src_ip: u32, now: u64, config: &TcpConfig) {
-    if let Some(state) = unsafe { INCOMPLETE_HANDSHAKES_V4.get_ptr_mut(&src_ip) } {
+fn clear_incomplete_handshake<K>(maps: &IpMaps<K>, src_key: &K, now: u64, config: &TcpConfig) {
+    if let Some(state) = unsafe { maps.handshakes.get_ptr_mut(src_key) } {
         let state = unsafe { &mut *state };
         // Decrement count (don't go below 0)
         if state.count > 0 {
@@ -1138,9 +1399,7 @@ fn generate_syn_cookie(
     v0 ^= m0;
 
     // Message block 2: ports + client sequence number (critical for binding to connection)
-    let m1 = ((src_port as u64) << 48)
-        | ((dst_port as u64) << 32)
-        | (seq as u64); // Include client's ISN
+    let m1 = ((src_port as u64) << 48) | ((dst_port as u64) << 32) | (seq as u64); // Include client's ISN
     v3 ^= m1;
     siphash_round(&mut v0, &mut v1, &mut v2, &mut v3);
     siphash_round(&mut v0, &mut v1, &mut v2, &mut v3);
@@ -1176,8 +1435,12 @@ fn generate_syn_cookie(
 // ============================================================================
 
 #[inline(always)]
-fn handle_ack_packet(
+fn handle_ack_packet<K>(
     ctx: &XdpContext,
+    maps: &IpMaps<K>,
+    src_key: &K,
+    penalty_key: &[u8; 16],
+    dst_addr: &[u8; 16],
     src_ip: u32,
     dst_ip: u32,
     src_port: u16,
@@ -1210,7 +1473,7 @@ fn handle_ack_packet(
                         conn.last_seen = now;
 
                         // Clear incomplete handshake tracking for this IP
-                        clear_incomplete_handshake(src_ip, now, config);
+                        clear_incomplete_handshake(maps, src_key, now, config);
                     }
                 } else {
                     // Cookie validation failed - potential ACK flood with spoofed cookies
@@ -1260,7 +1523,21 @@ fn handle_ack_packet(
             }
         }
 
+        let bytes = frame_len(ctx.ctx) as u64;
+
+        // Established flows are held to the backend's per-connection
+        // bandwidth; FIN and RST always pass so the flow can close
+        if conn.state == 3 && flags & (TCP_FIN | TCP_RST) == 0 {
+            if let Some(rate) = lookup_flow_rate(&FLOW_RATES, dst_addr, dst_port) {
+                if !flow_bucket_take(&mut conn.rate, &rate, bytes, now) {
+                    update_stats_flow_rate_limited();
+                    return Ok(xdp_action::XDP_DROP);
+                }
+            }
+        }
+
         conn.packets += 1;
+        conn.bytes += bytes;
         conn.last_seen = now;
 
         // State transitions
@@ -1281,7 +1558,7 @@ fn handle_ack_packet(
                 }
                 conn.state = 3; // Established
                 // Clear incomplete handshake tracking
-                clear_incomplete_handshake(src_ip, now, config);
+                clear_incomplete_handshake(maps, src_key, now, config);
             }
             2 => {
                 // SYN_SENT (client) -> ESTABLISHED on ACK
@@ -1290,6 +1567,33 @@ fn handle_ack_packet(
             3 => {
                 // ESTABLISHED - normal data flow
                 // Update expected_ack based on incoming seq to track received data
+
+                // The first pushed data segment completes the handshake for
+                // allowlist learning
+                if flags & TCP_PSH != 0 && conn.flags & CONN_FLAG_HANDSHAKE_RECORDED == 0 {
+                    conn.flags |= CONN_FLAG_HANDSHAKE_RECORDED;
+                    record_handshake(
+                        &LEARNING,
+                        &HANDSHAKES,
+                        penalty_key,
+                        dst_addr,
+                        dst_port,
+                        known_good::PROTO_TCP,
+                        now,
+                    );
+                    // Only sources that completed a handshake are counted,
+                    // so spoofed SYNs cannot make an address look shared
+                    let syn_tsval =
+                        (conn.flags & CONN_FLAG_TIMESTAMP != 0).then_some(conn.syn_tsval);
+                    record_cgnat_signals(
+                        &CGNAT_DETECT,
+                        &CGNAT_SIGNALS,
+                        penalty_key,
+                        conn.syn_fingerprint,
+                        syn_tsval,
+                        now,
+                    );
+                }
             }
             4 => {
                 // FIN_WAIT - closing
@@ -1299,6 +1603,35 @@ fn handle_ack_packet(
             }
             _ => {}
         }
+
+        let flow = FlowKey {
+            src_addr: *penalty_key,
+            dst_addr: *dst_addr,
+            src_port,
+            dst_port,
+        };
+
+        // Hand the validated flow to tc_flowmark once; it stays marked
+        // through the close so conntrack never sees half of a flow
+        if conn.state == 3 && conn.flags & CONN_FLAG_FLOW_MARKED == 0 {
+            conn.flags |= CONN_FLAG_FLOW_MARKED;
+            record_validated_flow(&FLOW_MARK_CONFIG, &FLOW_MARKS, &flow, now);
+        }
+
+        // A clean close returns the connection's slot right away
+        if flags & TCP_FIN != 0 {
+            if conn.state == 3 {
+                conn.state = 4; // FIN_WAIT
+            }
+            release_connection(maps, src_key, conn);
+        }
+        if flags & TCP_RST != 0 {
+            release_connection(maps, src_key, conn);
+            if conn.flags & CONN_FLAG_FLOW_MARKED != 0 {
+                forget_flow(&FLOW_MARKS, &flow);
+            }
+            let _ = TCP_CONNECTIONS.remove(&conn_key);
+        }
     } else {
         // ACK for unknown connection
         // This could be:
@@ -1345,19 +1678,58 @@ fn validate_syn_cookie(cookie: u32, expected: u32, now: u64, config: &TcpConfig)
 // ============================================================================
 
 #[inline(always)]
-fn handle_rst_packet(
+fn handle_rst_packet<K>(
     ctx: &XdpContext,
+    maps: &IpMaps<K>,
+    src_key: &K,
+    src_addr: &[u8; 16],
+    dst_addr: &[u8; 16],
     src_ip: u32,
+    dst_ip: u32,
+    src_port: u16,
+    dst_port: u16,
     now: u64,
     config: &TcpConfig,
 ) -> Result<u32, ()> {
-    // RST flood detection is handled in update_ip_state_and_check_floods
-    // Here we just do additional validation if needed
+    // RST flood detection is handled in update_ip_state_and_check_floods;
+    // here the reset connection returns its slot and leaves the table
+    let conn_key = make_connection_key(src_ip, dst_ip, src_port, dst_port);
+    if let Some(conn) = unsafe { TCP_CONNECTIONS.get_ptr_mut(&conn_key) } {
+        let conn = unsafe { &mut *conn };
+        release_connection(maps, src_key, conn);
+        if conn.flags & CONN_FLAG_FLOW_MARKED != 0 {
+            let flow = FlowKey {
+                src_addr: *src_addr,
+                dst_addr: *dst_addr,
+                src_port,
+                dst_port,
+            };
+            forget_flow(&FLOW_MARKS, &flow);
+        }
+        let _ = TCP_CONNECTIONS.remove(&conn_key);
+    }
 
     update_stats_passed();
     Ok(xdp_action::XDP_PASS)
 }
 
+/// Return the slot a connection holds in its source's `active_connections`
+///
+/// Clearing `CONN_FLAG_COUNTED` makes this idempotent, so retransmitted FINs
+/// and a FIN followed by a RST return the slot once.
+#[inline(always)]
+fn release_connection<K>(maps: &IpMaps<K>, src_key: &K, conn: &mut TcpConnectionState) {
+    if conn.flags & CONN_FLAG_COUNTED == 0 {
+        return;
+    }
+    conn.flags &= !CONN_FLAG_COUNTED;
+
+    if let Some(state) = unsafe { maps.state.get_ptr_mut(src_key) } {
+        let state = unsafe { &mut *state };
+        state.active_connections = state.active_connections.saturating_sub(1);
+    }
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
@@ -1466,6 +1838,7 @@ fn update_stats_passed() {
 
 #[inline(always)]
 fn update_stats_syn_flood() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::SynFlood);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_syn_flood += 1;
@@ -1475,6 +1848,7 @@ fn update_stats_syn_flood() {
 
 #[inline(always)]
 fn update_stats_ack_flood() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::AckFlood);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_ack_flood += 1;
@@ -1484,6 +1858,7 @@ fn update_stats_ack_flood() {
 
 #[inline(always)]
 fn update_stats_rst_flood() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::RstFlood);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_rst_flood += 1;
@@ -1493,6 +1868,7 @@ fn update_stats_rst_flood() {
 
 #[inline(always)]
 fn update_stats_invalid_flags() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_invalid_flags += 1;
@@ -1502,6 +1878,7 @@ fn update_stats_invalid_flags() {
 
 #[inline(always)]
 fn update_stats_blocked() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::BlockedIp);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_blocked_ip += 1;
@@ -1511,6 +1888,7 @@ fn update_stats_blocked() {
 
 #[inline(always)]
 fn update_stats_connection_limit() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::ConnectionLimit);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_connection_limit += 1;
@@ -1556,6 +1934,7 @@ fn update_stats_window_probe() {
 
 #[inline(always)]
 fn update_stats_dropped_fragments() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::Fragmentation);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_fragments += 1;
@@ -1565,6 +1944,7 @@ fn update_stats_dropped_fragments() {
 
 #[inline(always)]
 fn update_stats_invalid_ack() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_invalid_ack += 1;
@@ -1574,6 +1954,7 @@ fn update_stats_invalid_ack() {
 
 #[inline(always)]
 fn update_stats_handshake_timeout() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::SynFlood);
     if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_handshake_timeout += 1;
@@ -1590,6 +1971,35 @@ fn update_stats_incomplete_handshake() {
     }
 }
 
+#[inline(always)]
+fn update_stats_flow_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::FlowRateLimit);
+    if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).dropped_flow_rate += 1;
+        }
+    }
+}
+
+#[inline(always)]
+fn update_stats_dns_queries(queries: u64) {
+    if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).dns_queries += queries;
+        }
+    }
+}
+
+#[inline(always)]
+fn update_stats_dns_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::RateLimit);
+    if let Some(stats) = unsafe { TCP_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).dropped_dns_rate_limited += 1;
+        }
+    }
+}
+
 // ============================================================================
 // Panic Handler
 // ============================================================================
@@ -6,6 +6,7 @@
 //! - UDP flood mitigation
 //! - Port scan detection
 //! - Reflection attack prevention
+//! - Per-connection bandwidth limits of established flows
 
 #![no_std]
 #![no_main]
@@ -13,53 +14,22 @@
 use aya_ebpf::{
     bindings::xdp_action,
     macros::{map, xdp},
-    maps::{HashMap, LruHashMap, PerCpuArray},
+    maps::{Array, HashMap, LruHashMap, LruPerCpuHashMap, PerCpuArray},
     programs::XdpContext,
 };
 use core::mem;
-
-// ============================================================================
-// Network Header Structures
-// ============================================================================
-
-#[repr(C)]
-struct EthHdr {
-    h_dest: [u8; 6],
-    h_source: [u8; 6],
-    h_proto: u16,
-}
-
-#[repr(C)]
-struct Ipv4Hdr {
-    version_ihl: u8,
-    tos: u8,
-    tot_len: u16,
-    id: u16,
-    frag_off: u16,
-    ttl: u8,
-    protocol: u8,
-    check: u16,
-    saddr: u32,
-    daddr: u32,
-}
-
-#[repr(C)]
-struct Ipv6Hdr {
-    version_tc_flow: u32,
-    payload_len: u16,
-    nexthdr: u8,
-    hop_limit: u8,
-    saddr: [u8; 16],
-    daddr: [u8; 16],
-}
-
-#[repr(C)]
-struct UdpHdr {
-    source: u16,
-    dest: u16,
-    len: u16,
-    check: u16,
-}
+use pistonprotection_ebpf::{
+    BlockReason, DropContext, DropCounter, EventBudget, EventBudgetConfig, FlowKey, FlowRateConfig,
+    LatencyBucket, LatencyConfig, PayloadScratch, PenaltyConfig, PenaltyEntry, SampleConfig,
+    TenantDstV6Key, UdpFlowState, UdpHdr,
+    breakdown::{DST_PORT_MAX_ENTRIES, REASON_BUCKETS},
+    drop_context_reset, drop_context_set_reason, drop_context_set_source_v4,
+    drop_context_set_source_v6, drop_context_set_target, drop_events, emit_drop_event,
+    flow_bucket_take, flow_rate, frame_len, latency, latency_elapsed, latency_start,
+    lookup_flow_rate, observe_verdict, parse_eth, parse_ipv4, parse_ipv6_with_ext, parse_udp,
+    payload_view, peek_dst_port, penalty, penalty_blocked, penalty_config, penalty_divisor,
+    penalty_key_v4, record_drop, record_latency, record_violation, sample_packet, sampling,
+};
 
 // ============================================================================
 // UDP Filtering Structures
@@ -148,6 +118,7 @@ pub struct UdpStats {
     pub ntp_packets: u64,
     pub ssdp_packets: u64,
     pub memcached_packets: u64,
+    pub dropped_flow_rate: u64,
 }
 
 /// Amplification source tracking
@@ -170,7 +141,6 @@ pub struct AmpSourceEntry {
 const ETH_P_IP: u16 = 0x0800;
 const ETH_P_IPV6: u16 = 0x86DD;
 const IPPROTO_UDP: u8 = 17;
-const IPPROTO_FRAGMENT: u8 = 44; // IPv6 Fragment header next protocol
 
 // IPv4 fragmentation flags (in frag_off field)
 // frag_off is 16 bits: [3 bits flags][13 bits fragment offset]
@@ -252,6 +222,24 @@ static UDP_WHITELIST: HashMap<u32, u32> = HashMap::with_max_entries(10_000, 0);
 static PROTECTED_PORTS: HashMap<u16, u32> = HashMap::with_max_entries(1000, 0);
 
 /// Configuration
+/// Penalties of repeat offenders, shared with the other programs
+#[map(name = "PENALTY")]
+static PENALTY: LruHashMap<[u8; 16], PenaltyEntry> = LruHashMap::pinned(penalty::MAX_SOURCES, 0);
+
+/// Penalty ladder per protection level, shared with the other programs
+#[map(name = "PENALTY_CONFIG")]
+static PENALTY_CONFIG: Array<PenaltyConfig> = Array::pinned(penalty::LEVELS, 0);
+
+/// Per-connection bandwidth limits, shared with xdp_tcp
+#[map(name = "FLOW_RATES")]
+static FLOW_RATES: HashMap<TenantDstV6Key, FlowRateConfig> =
+    HashMap::pinned(flow_rate::MAX_DESTINATIONS, 0);
+
+/// Flows to destinations with a bandwidth limit
+#[map]
+static UDP_FLOWS: LruHashMap<FlowKey, UdpFlowState> =
+    LruHashMap::with_max_entries(flow_rate::MAX_FLOWS, 0);
+
 #[map]
 static UDP_CONFIG: PerCpuArray<UdpConfig> = PerCpuArray::with_max_entries(1, 0);
 
@@ -259,16 +247,98 @@ static UDP_CONFIG: PerCpuArray<UdpConfig> = PerCpuArray::with_max_entries(1, 0);
 #[map]
 static UDP_STATS: PerCpuArray<UdpStats> = PerCpuArray::with_max_entries(1, 0);
 
+/// Drops broken down by (protocol, destination port)
+#[map]
+static DROPS_BY_DST_PORT: LruPerCpuHashMap<u32, DropCounter> =
+    LruPerCpuHashMap::with_max_entries(DST_PORT_MAX_ENTRIES, 0);
+
+/// Drops broken down by BlockReason
+#[map]
+static DROPS_BY_REASON: PerCpuArray<DropCounter> = PerCpuArray::with_max_entries(REASON_BUCKETS, 0);
+
+/// Per-CPU drop attribution for the packet being processed
+#[map]
+static DROP_CONTEXT: PerCpuArray<DropContext> = PerCpuArray::with_max_entries(1, 0);
+
+/// Set while the worker is in observe mode, shared with the other programs
+#[map(name = "OBSERVE")]
+static OBSERVE: Array<u32> = Array::pinned(1, 0);
+
+/// Flow sampling configuration
+#[map]
+static SAMPLE_CONFIG: PerCpuArray<SampleConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Copy of the inspected payload when it spans multi-buffer fragments
+#[map]
+static PAYLOAD_SCRATCH: PerCpuArray<PayloadScratch> = PerCpuArray::with_max_entries(1, 0);
+
+/// Sampled packet headers for the userspace analyzer
+#[map]
+static SAMPLES: sampling::SampleMap = sampling::sample_map();
+
+/// Dropped packets for the userspace drop event aggregator
+#[map]
+static DROP_EVENTS: drop_events::EventMap = drop_events::event_map();
+
+/// Event budget of each CPU, written by userspace
+#[map]
+static EVENT_BUDGET_CONFIG: PerCpuArray<EventBudgetConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Event token buckets and per-class event counters
+#[map]
+static EVENT_BUDGET: PerCpuArray<EventBudget> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing latency measurement configuration
+#[map]
+static LATENCY_CONFIG: PerCpuArray<LatencyConfig> = PerCpuArray::with_max_entries(1, 0);
+
+/// Processing time histograms, per protection level
+#[map]
+static LATENCY: PerCpuArray<LatencyBucket> = PerCpuArray::with_max_entries(latency::MAX_ENTRIES, 0);
+
 // ============================================================================
 // Main XDP Entry Point
 // ============================================================================
 
-#[xdp]
+#[cfg_attr(feature = "frags", xdp(frags))]
+#[cfg_attr(not(feature = "frags"), xdp)]
 pub fn xdp_udp(ctx: XdpContext) -> u32 {
-    match try_xdp_udp(ctx) {
+    let started = latency_start(&LATENCY_CONFIG);
+    drop_context_reset(&DROP_CONTEXT, BlockReason::UdpFlood);
+    let bytes = frame_len(ctx.ctx) as u64;
+    let raw_ctx = ctx.ctx;
+
+    let action = match try_xdp_udp(ctx) {
         Ok(ret) => ret,
         Err(_) => xdp_action::XDP_PASS,
+    };
+
+    if action == xdp_action::XDP_DROP {
+        record_drop(&DROP_CONTEXT, &DROPS_BY_DST_PORT, &DROPS_BY_REASON, bytes);
+        emit_drop_event(
+            raw_ctx,
+            &DROP_CONTEXT,
+            &DROP_EVENTS,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+            bytes,
+        );
+    } else if action == xdp_action::XDP_PASS {
+        sample_packet(
+            raw_ctx,
+            bytes,
+            &SAMPLE_CONFIG,
+            &SAMPLES,
+            &EVENT_BUDGET_CONFIG,
+            &EVENT_BUDGET,
+        );
+    }
+
+    if let Some(elapsed) = latency_elapsed(started) {
+        record_latency(&LATENCY, elapsed, 0);
     }
+
+    observe_verdict(&OBSERVE, action)
 }
 
 #[inline(always)]
@@ -282,16 +352,13 @@ fn try_xdp_udp(ctx: XdpContext) -> Result<u32, ()> {
     let data_end = ctx.data_end();
 
     // Parse Ethernet header
-    if data + mem::size_of::<EthHdr>() > data_end {
+    let Some((eth_proto, l3_offset)) = parse_eth(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let eth = unsafe { &*(data as *const EthHdr) };
-    let eth_proto = u16::from_be(eth.h_proto);
+    };
 
     match eth_proto {
-        ETH_P_IP => process_ipv4(&ctx, data + mem::size_of::<EthHdr>(), data_end, &config),
-        ETH_P_IPV6 => process_ipv6(&ctx, data + mem::size_of::<EthHdr>(), data_end, &config),
+        ETH_P_IP => process_ipv4(&ctx, l3_offset, data_end, &config),
+        ETH_P_IPV6 => process_ipv6(&ctx, l3_offset, data_end, &config),
         _ => Ok(xdp_action::XDP_PASS),
     }
 }
@@ -307,11 +374,16 @@ fn process_ipv4(
     data_end: usize,
     config: &UdpConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv4Hdr>() > data_end {
+    let Some((ip, udp_data)) = parse_ipv4(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
+    };
 
-    let ip = unsafe { &*(data as *const Ipv4Hdr) };
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        ip.protocol,
+        peek_dst_port(udp_data, data_end, ip.protocol),
+    );
+    drop_context_set_source_v4(&DROP_CONTEXT, ip.saddr);
 
     // Only process UDP
     if ip.protocol != IPPROTO_UDP {
@@ -371,30 +443,22 @@ fn process_ipv4(
         return Ok(xdp_action::XDP_DROP);
     }
 
-    let ihl = (ip.version_ihl & 0x0f) as usize * 4;
-    let udp_data = data + ihl;
-
     // For fragmented first fragments, pass is_fragmented flag for stricter checks
-    process_udp(ctx, udp_data, data_end, src_ip, config, is_fragmented)
+    process_udp(
+        ctx,
+        udp_data,
+        data_end,
+        src_ip,
+        &penalty_key_v4(u32::from_be(ip.daddr)),
+        config,
+        is_fragmented,
+    )
 }
 
 // ============================================================================
 // IPv6 Processing
 // ============================================================================
 
-/// IPv6 Fragment Header structure
-#[repr(C)]
-struct Ipv6FragHdr {
-    nexthdr: u8,
-    reserved: u8,
-    frag_off_m: u16, // Fragment offset (13 bits) + Reserved (2 bits) + M flag (1 bit)
-    identification: u32,
-}
-
-// IPv6 fragmentation constants
-const IPV6_FRAG_OFFSET_MASK: u16 = 0xFFF8; // Upper 13 bits (shifted left by 3)
-const IPV6_FRAG_M_FLAG: u16 = 0x0001; // More fragments flag (lowest bit)
-
 #[inline(always)]
 fn process_ipv6(
     ctx: &XdpContext,
@@ -402,76 +466,19 @@ fn process_ipv6(
     data_end: usize,
     config: &UdpConfig,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<Ipv6Hdr>() > data_end {
+    // Skip extension headers (hop-by-hop, routing, fragment, ...) to reach UDP
+    let Some((ip6, l4)) = parse_ipv6_with_ext(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let ip6 = unsafe { &*(data as *const Ipv6Hdr) };
-    let mut next_header = ip6.nexthdr;
-    let mut header_offset = data + mem::size_of::<Ipv6Hdr>();
-    let mut is_fragmented = false;
-    let mut is_first_fragment = true;
-
-    // ========================================================================
-    // IPv6 EXTENSION HEADER PARSING
-    // ========================================================================
-    // IPv6 can have extension headers between the main header and UDP.
-    // We need to check for the Fragment extension header (protocol 44).
-    // For simplicity, we only handle: Hop-by-Hop (0), Routing (43), Fragment (44)
-    // Other extension headers are rare for UDP traffic.
-    // ========================================================================
-
-    // Bounded loop for eBPF verifier - max 3 extension headers is reasonable
-    for _ in 0..3 {
-        match next_header {
-            IPPROTO_UDP => {
-                // Found UDP, done parsing extension headers
-                break;
-            }
-            IPPROTO_FRAGMENT => {
-                // Fragment extension header
-                if header_offset + mem::size_of::<Ipv6FragHdr>() > data_end {
-                    return Ok(xdp_action::XDP_PASS);
-                }
-
-                let frag_hdr = unsafe { &*(header_offset as *const Ipv6FragHdr) };
-                let frag_off_m = u16::from_be(frag_hdr.frag_off_m);
-
-                // Check fragment offset (bits 3-15) and M flag (bit 0)
-                let frag_offset = frag_off_m & IPV6_FRAG_OFFSET_MASK;
-                let more_fragments = (frag_off_m & IPV6_FRAG_M_FLAG) != 0;
-
-                is_fragmented = more_fragments || frag_offset != 0;
-                is_first_fragment = frag_offset == 0;
-
-                next_header = frag_hdr.nexthdr;
-                header_offset += mem::size_of::<Ipv6FragHdr>();
-            }
-            0 | 43 | 60 => {
-                // Hop-by-Hop (0), Routing (43), Destination Options (60)
-                // These have a common format: next_header, length, data
-                if header_offset + 2 > data_end {
-                    return Ok(xdp_action::XDP_PASS);
-                }
-
-                let ext_next = unsafe { *(header_offset as *const u8) };
-                let ext_len = unsafe { *((header_offset + 1) as *const u8) };
-                // Length is in 8-byte units, not including first 8 bytes
-                let total_len = ((ext_len as usize) + 1) * 8;
+    };
 
-                next_header = ext_next;
-                header_offset += total_len;
-            }
-            _ => {
-                // Unknown or unsupported extension header
-                // If it's not UDP, we can't process it
-                return Ok(xdp_action::XDP_PASS);
-            }
-        }
-    }
+    drop_context_set_target(
+        &DROP_CONTEXT,
+        l4.protocol,
+        peek_dst_port(l4.offset, data_end, l4.protocol),
+    );
+    drop_context_set_source_v6(&DROP_CONTEXT, &ip6.saddr);
 
-    // After parsing, check if we found UDP
-    if next_header != IPPROTO_UDP {
+    if l4.protocol != IPPROTO_UDP {
         return Ok(xdp_action::XDP_PASS);
     }
 
@@ -480,8 +487,8 @@ fn process_ipv6(
     // ========================================================================
     // Same strategy as IPv4: drop non-first fragments, be suspicious of first fragments
     // ========================================================================
-    if is_fragmented {
-        if !is_first_fragment {
+    if l4.is_fragment {
+        if !l4.is_first_fragment {
             // Non-first fragment - has no UDP header, can't inspect
             if config.protection_level >= 2 {
                 update_stats_fragmented();
@@ -506,7 +513,15 @@ fn process_ipv6(
     }
 
     // Use the full IPv6 address for proper rate limiting
-    process_udp_v6(ctx, header_offset, data_end, &src_ip, config, is_fragmented)
+    process_udp_v6(
+        ctx,
+        l4.offset,
+        data_end,
+        &src_ip,
+        &ip6.daddr,
+        config,
+        l4.is_fragment,
+    )
 }
 
 // ============================================================================
@@ -519,14 +534,13 @@ fn process_udp(
     data: usize,
     data_end: usize,
     src_ip: u32,
+    dst_addr: &[u8; 16],
     config: &UdpConfig,
     is_fragmented: bool,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<UdpHdr>() > data_end {
+    let Some((udp, _)) = parse_udp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let udp = unsafe { &*(data as *const UdpHdr) };
+    };
     let src_port = u16::from_be(udp.source);
     let dst_port = u16::from_be(udp.dest);
     let udp_len = u16::from_be(udp.len);
@@ -568,7 +582,7 @@ fn process_udp(
 
         if is_amp_source && config.protection_level >= 2 {
             // Fragmented response from amplification port - almost certainly an attack
-            update_stats_amplification();
+            update_stats_amplification(src_port);
             update_stats_fragmented();
             return Ok(xdp_action::XDP_DROP);
         }
@@ -624,12 +638,24 @@ fn process_udp(
         if is_port_scan(src_ip, dst_port, now, config) {
             update_stats_port_scan();
             if config.protection_level >= 2 {
-                block_ip_v4(src_ip, config.block_duration_ns);
+                penalize_v4(src_ip, now, config);
                 return Ok(xdp_action::XDP_DROP);
             }
         }
     }
 
+    // Per-connection bandwidth of the backend
+    let flow = FlowKey {
+        src_addr: penalty_key_v4(src_ip),
+        dst_addr: *dst_addr,
+        src_port,
+        dst_port,
+    };
+    if !check_flow_rate(&flow, udp_len as u64, now) {
+        update_stats_flow_rate_limited();
+        return Ok(xdp_action::XDP_DROP);
+    }
+
     // Protocol-specific tracking (for stats)
     track_protocol_stats(src_port, dst_port);
 
@@ -694,14 +720,13 @@ fn process_udp_v6(
     data: usize,
     data_end: usize,
     src_ip: &[u8; 16],
+    dst_addr: &[u8; 16],
     config: &UdpConfig,
     is_fragmented: bool,
 ) -> Result<u32, ()> {
-    if data + mem::size_of::<UdpHdr>() > data_end {
+    let Some((udp, _)) = parse_udp(data, data_end) else {
         return Ok(xdp_action::XDP_PASS);
-    }
-
-    let udp = unsafe { &*(data as *const UdpHdr) };
+    };
     let src_port = u16::from_be(udp.source);
     let dst_port = u16::from_be(udp.dest);
     let udp_len = u16::from_be(udp.len);
@@ -736,7 +761,7 @@ fn process_udp_v6(
         );
 
         if is_amp_source && config.protection_level >= 2 {
-            update_stats_amplification();
+            update_stats_amplification(src_port);
             update_stats_fragmented();
             return Ok(xdp_action::XDP_DROP);
         }
@@ -794,12 +819,24 @@ fn process_udp_v6(
         if is_port_scan_v6(src_ip, dst_port, now, config) {
             update_stats_port_scan();
             if config.protection_level >= 2 {
-                block_ip_v6(src_ip, config.block_duration_ns);
+                penalize_v6(src_ip, now, config);
                 return Ok(xdp_action::XDP_DROP);
             }
         }
     }
 
+    // Per-connection bandwidth of the backend
+    let flow = FlowKey {
+        src_addr: *src_ip,
+        dst_addr: *dst_addr,
+        src_port,
+        dst_port,
+    };
+    if !check_flow_rate(&flow, udp_len as u64, now) {
+        update_stats_flow_rate_limited();
+        return Ok(xdp_action::XDP_DROP);
+    }
+
     // Protocol-specific tracking
     track_protocol_stats(src_port, dst_port);
 
@@ -807,13 +844,46 @@ fn process_udp_v6(
     Ok(xdp_action::XDP_PASS)
 }
 
+// ============================================================================
+// Per-Connection Bandwidth
+// ============================================================================
+
+/// Charge a packet to its flow's bandwidth bucket, if the destination has a
+/// limit; false means the packet is over the limit
+///
+/// Flows are tracked from their first packet and count as established from
+/// the second one until they go idle for `flow_rate::UDP_IDLE_NS`.
+#[inline(always)]
+fn check_flow_rate(flow: &FlowKey, bytes: u64, now: u64) -> bool {
+    let Some(rate) = lookup_flow_rate(&FLOW_RATES, &flow.dst_addr, flow.dst_port) else {
+        return true;
+    };
+
+    if let Some(state) = unsafe { UDP_FLOWS.get_ptr_mut(flow) } {
+        let state = unsafe { &mut *state };
+        if now.saturating_sub(state.last_seen_ns) < flow_rate::UDP_IDLE_NS {
+            state.last_seen_ns = now;
+            state.packets += 1;
+            return flow_bucket_take(&mut state.bucket, &rate, bytes, now);
+        }
+    }
+
+    let state = UdpFlowState {
+        packets: 1,
+        last_seen_ns: now,
+        ..Default::default()
+    };
+    let _ = UDP_FLOWS.insert(flow, &state, 0);
+    true
+}
+
 // ============================================================================
 // Amplification Attack Detection
 // ============================================================================
 
 #[inline(always)]
 fn check_amplification_attack(
-    _ctx: &XdpContext,
+    ctx: &XdpContext,
     data: usize,
     data_end: usize,
     src_ip: u32,
@@ -846,7 +916,17 @@ fn check_amplification_attack(
         return None;
     }
 
-    let payload_start = data + mem::size_of::<UdpHdr>();
+    // Payload may continue past data_end on multi-buffer frames
+    let payload: &[u8] = match payload_view(
+        ctx.ctx,
+        ctx.data(),
+        data_end,
+        data + mem::size_of::<UdpHdr>(),
+        &PAYLOAD_SCRATCH,
+    ) {
+        Some((payload, _)) => payload,
+        None => &[],
+    };
 
     // Protocol-specific validation
     match src_port {
@@ -860,10 +940,10 @@ fn check_amplification_attack(
             // - NSCOUNT: 2 bytes (number of authority records)
             // - ARCOUNT: 2 bytes (number of additional records)
 
-            if payload_start + 12 <= data_end {
-                let flags = unsafe { u16::from_be(*((payload_start + 2) as *const u16)) };
-                let qdcount = unsafe { u16::from_be(*((payload_start + 4) as *const u16)) };
-                let ancount = unsafe { u16::from_be(*((payload_start + 6) as *const u16)) };
+            if payload.len() >= 12 {
+                let flags = u16::from_be_bytes([payload[2], payload[3]]);
+                let qdcount = u16::from_be_bytes([payload[4], payload[5]]);
+                let ancount = u16::from_be_bytes([payload[6], payload[7]]);
 
                 // Check if QR bit is set (response)
                 let is_response = (flags & DNS_FLAG_RESPONSE) != 0;
@@ -886,10 +966,11 @@ fn check_amplification_attack(
                     // - Large responses (>512 bytes) are suspicious
                     // - ANY queries can return massive responses
 
-                    let is_amplification = amp_ratio_suspicious || (is_large && ancount > qdcount * 5);
+                    let is_amplification =
+                        amp_ratio_suspicious || (is_large && ancount > qdcount * 5);
 
                     if is_amplification || (is_large && payload_len > 1024) {
-                        update_stats_amplification();
+                        update_stats_amplification(src_port);
 
                         let amp_key = ((src_ip as u64) << 16) | (src_port as u64);
                         track_amp_source(amp_key, payload_len as u64, config);
@@ -923,8 +1004,8 @@ fn check_amplification_attack(
             //
             // Mode 6 (control) can also be abused but less common
 
-            if payload_start + 1 <= data_end {
-                let first_byte = unsafe { *(payload_start as *const u8) };
+            if !payload.is_empty() {
+                let first_byte = payload[0];
                 let mode = first_byte & NTP_MODE_MASK;
                 let version = (first_byte >> 3) & 0x07; // Bits 3-5
 
@@ -934,7 +1015,7 @@ fn check_amplification_attack(
                 // Mode 7 (private) - ALWAYS suspicious, this is the monlist attack vector
                 // Drop immediately regardless of payload size
                 if mode == 7 {
-                    update_stats_amplification();
+                    update_stats_amplification(src_port);
                     track_amp_source(
                         ((src_ip as u64) << 16) | (src_port as u64),
                         payload_len as u64,
@@ -949,7 +1030,7 @@ fn check_amplification_attack(
 
                 // Mode 6 (control) - also suspicious, can leak info
                 if mode == 6 && payload_len > 12 {
-                    update_stats_amplification();
+                    update_stats_amplification(src_port);
                     track_amp_source(
                         ((src_ip as u64) << 16) | (src_port as u64),
                         payload_len as u64,
@@ -966,7 +1047,7 @@ fn check_amplification_attack(
                     // Standard NTP response is 48 bytes
                     // Larger responses indicate potential abuse
                     if payload_len > 48 {
-                        update_stats_amplification();
+                        update_stats_amplification(src_port);
                         track_amp_source(
                             ((src_ip as u64) << 16) | (src_port as u64),
                             payload_len as u64,
@@ -980,8 +1061,13 @@ fn check_amplification_attack(
                 }
 
                 // Invalid version with any response mode is suspicious
-                if !valid_version && (mode == NTP_MODE_SERVER || mode == NTP_MODE_BROADCAST || mode == 6 || mode == 7) {
-                    update_stats_amplification();
+                if !valid_version
+                    && (mode == NTP_MODE_SERVER
+                        || mode == NTP_MODE_BROADCAST
+                        || mode == 6
+                        || mode == 7)
+                {
+                    update_stats_amplification(src_port);
                     if config.protection_level >= 2 {
                         return Some(xdp_action::XDP_DROP);
                     }
@@ -992,7 +1078,7 @@ fn check_amplification_attack(
         PORT_SSDP => {
             // SSDP amplification (typically large M-SEARCH responses)
             if payload_len > 200 {
-                update_stats_amplification();
+                update_stats_amplification(src_port);
                 track_amp_source(
                     ((src_ip as u64) << 16) | (src_port as u64),
                     payload_len as u64,
@@ -1015,8 +1101,8 @@ fn check_amplification_attack(
             // 0x81 = response magic (amplification indicator)
             // Text protocol responses start with "VALUE", "END", "STAT", etc.
 
-            if payload_start + 1 <= data_end {
-                let magic_byte = unsafe { *(payload_start as *const u8) };
+            if !payload.is_empty() {
+                let magic_byte = payload[0];
 
                 // Binary protocol response magic (0x81) or request magic echoed back (0x80)
                 // Both indicate potential amplification
@@ -1027,7 +1113,7 @@ fn check_amplification_attack(
                 let is_large_response = payload_len > 100;
 
                 if is_binary_protocol || is_large_response {
-                    update_stats_amplification();
+                    update_stats_amplification(src_port);
                     track_amp_source(
                         ((src_ip as u64) << 16) | (src_port as u64),
                         payload_len as u64,
@@ -1046,7 +1132,7 @@ fn check_amplification_attack(
 
         PORT_CHARGEN | PORT_QOTD => {
             // These should almost never be legitimate traffic
-            update_stats_amplification();
+            update_stats_amplification(src_port);
             if config.protection_level >= 1 {
                 return Some(xdp_action::XDP_DROP);
             }
@@ -1055,7 +1141,7 @@ fn check_amplification_attack(
         PORT_SNMP => {
             // SNMP amplification
             if payload_len > 200 {
-                update_stats_amplification();
+                update_stats_amplification(src_port);
                 track_amp_source(
                     ((src_ip as u64) << 16) | (src_port as u64),
                     payload_len as u64,
@@ -1071,7 +1157,7 @@ fn check_amplification_attack(
         PORT_LDAP | PORT_CLDAP => {
             // LDAP/CLDAP amplification
             if payload_len > 100 {
-                update_stats_amplification();
+                update_stats_amplification(src_port);
                 track_amp_source(
                     ((src_ip as u64) << 16) | (src_port as u64),
                     payload_len as u64,
@@ -1087,7 +1173,7 @@ fn check_amplification_attack(
         _ => {
             // Generic large response from known amp port
             if payload_len > 500 {
-                update_stats_amplification();
+                update_stats_amplification(src_port);
                 track_amp_source(
                     ((src_ip as u64) << 16) | (src_port as u64),
                     payload_len as u64,
@@ -1261,6 +1347,12 @@ fn check_rate_limit_v4(src_ip: u32, bytes: u64, now: u64, config: &UdpConfig) ->
         DEFAULT_MAX_BYTES_PER_WINDOW
     };
 
+    // Sources with a penalty get smaller limits
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let divisor = penalty_divisor(&PENALTY, &penalty_key_v4(src_ip), &ladder, now);
+    let max_packets = max_packets / divisor;
+    let max_bytes = max_bytes / divisor;
+
     if let Some(state) = unsafe { UDP_IP_STATE_V4.get_ptr_mut(&src_ip) } {
         let state = unsafe { &mut *state };
 
@@ -1289,7 +1381,14 @@ fn check_rate_limit_v4(src_ip: u32, bytes: u64, now: u64, config: &UdpConfig) ->
         // Check limits
         if state.window_packets > max_packets || state.bytes > max_bytes {
             state.flags |= FLAG_FLOOD_DETECTED;
-            state.blocked_until = now + config.block_duration_ns;
+            state.blocked_until = record_violation(
+                &DROP_CONTEXT,
+                &PENALTY,
+                &penalty_key_v4(src_ip),
+                &ladder,
+                now,
+                state.window_start,
+            );
             return false;
         }
 
@@ -1315,8 +1414,11 @@ fn check_rate_limit_v4(src_ip: u32, bytes: u64, now: u64, config: &UdpConfig) ->
 
 #[inline(always)]
 fn is_ip_blocked_v4(src_ip: u32) -> bool {
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    if penalty_blocked(&PENALTY, &penalty_key_v4(src_ip), now) {
+        return true;
+    }
     if let Some(state) = unsafe { UDP_IP_STATE_V4.get(&src_ip) } {
-        let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
         state.blocked_until > now
     } else {
         false
@@ -1325,24 +1427,40 @@ fn is_ip_blocked_v4(src_ip: u32) -> bool {
 
 #[inline(always)]
 fn is_ip_blocked_v6(src_ip: &[u8; 16]) -> bool {
+    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
+    if penalty_blocked(&PENALTY, src_ip, now) {
+        return true;
+    }
     if let Some(state) = unsafe { UDP_IP_STATE_V6.get(src_ip) } {
-        let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
         state.blocked_until > now
     } else {
         false
     }
 }
 
+/// Record a violation of a source and apply the block it earned, if any
 #[inline(always)]
-fn block_ip_v4(src_ip: u32, duration_ns: u64) {
-    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
-    let block_until = now
-        + if duration_ns != 0 {
-            duration_ns
-        } else {
-            DEFAULT_BLOCK_DURATION_NS
-        };
+fn penalize_v4(src_ip: u32, now: u64, config: &UdpConfig) {
+    let window_start = match unsafe { UDP_IP_STATE_V4.get(&src_ip) } {
+        Some(state) => state.window_start,
+        None => now,
+    };
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let block_until = record_violation(
+        &DROP_CONTEXT,
+        &PENALTY,
+        &penalty_key_v4(src_ip),
+        &ladder,
+        now,
+        window_start,
+    );
+    if block_until > now {
+        block_ip_v4(src_ip, now, block_until);
+    }
+}
 
+#[inline(always)]
+fn block_ip_v4(src_ip: u32, now: u64, block_until: u64) {
     if let Some(state) = unsafe { UDP_IP_STATE_V4.get_ptr_mut(&src_ip) } {
         let state = unsafe { &mut *state };
         state.blocked_until = block_until;
@@ -1387,6 +1505,12 @@ fn check_rate_limit_v6(src_ip: &[u8; 16], bytes: u64, now: u64, config: &UdpConf
         DEFAULT_MAX_BYTES_PER_WINDOW
     };
 
+    // Sources with a penalty get smaller limits
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let divisor = penalty_divisor(&PENALTY, src_ip, &ladder, now);
+    let max_packets = max_packets / divisor;
+    let max_bytes = max_bytes / divisor;
+
     if let Some(state) = unsafe { UDP_IP_STATE_V6.get_ptr_mut(src_ip) } {
         let state = unsafe { &mut *state };
 
@@ -1416,7 +1540,14 @@ fn check_rate_limit_v6(src_ip: &[u8; 16], bytes: u64, now: u64, config: &UdpConf
         // Check limits
         if state.window_packets > max_packets || state.bytes > max_bytes {
             state.flags |= FLAG_FLOOD_DETECTED;
-            state.blocked_until = now + config.block_duration_ns;
+            state.blocked_until = record_violation(
+                &DROP_CONTEXT,
+                &PENALTY,
+                src_ip,
+                &ladder,
+                now,
+                state.window_start,
+            );
             return false;
         }
 
@@ -1481,16 +1612,22 @@ fn is_port_scan_v6(src_ip: &[u8; 16], dst_port: u16, now: u64, config: &UdpConfi
     false
 }
 
+/// Record a violation of a source and apply the block it earned, if any
 #[inline(always)]
-fn block_ip_v6(src_ip: &[u8; 16], duration_ns: u64) {
-    let now = unsafe { aya_ebpf::helpers::bpf_ktime_get_ns() };
-    let block_until = now
-        + if duration_ns != 0 {
-            duration_ns
-        } else {
-            DEFAULT_BLOCK_DURATION_NS
-        };
+fn penalize_v6(src_ip: &[u8; 16], now: u64, config: &UdpConfig) {
+    let window_start = match unsafe { UDP_IP_STATE_V6.get(src_ip) } {
+        Some(state) => state.window_start,
+        None => now,
+    };
+    let ladder = penalty_config(&PENALTY_CONFIG, config.protection_level);
+    let block_until = record_violation(&DROP_CONTEXT, &PENALTY, src_ip, &ladder, now, window_start);
+    if block_until > now {
+        block_ip_v6(src_ip, now, block_until);
+    }
+}
 
+#[inline(always)]
+fn block_ip_v6(src_ip: &[u8; 16], now: u64, block_until: u64) {
     if let Some(state) = unsafe { UDP_IP_STATE_V6.get_ptr_mut(src_ip) } {
         let state = unsafe { &mut *state };
         state.blocked_until = block_until;
@@ -1588,6 +1725,7 @@ fn update_stats_passed() {
 
 #[inline(always)]
 fn update_stats_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::UdpFlood);
     if let Some(stats) = unsafe { UDP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_rate_limited += 1;
@@ -1597,6 +1735,7 @@ fn update_stats_rate_limited() {
 
 #[inline(always)]
 fn update_stats_invalid_size() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::InvalidProtocol);
     if let Some(stats) = unsafe { UDP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_invalid_size += 1;
@@ -1604,8 +1743,21 @@ fn update_stats_invalid_size() {
     }
 }
 
+/// Map an amplification source port to the matching block reason
 #[inline(always)]
-fn update_stats_amplification() {
+fn amplification_reason(src_port: u16) -> BlockReason {
+    match src_port {
+        PORT_DNS => BlockReason::DnsAmplification,
+        PORT_NTP => BlockReason::NtpAmplification,
+        PORT_SSDP => BlockReason::SsdpAmplification,
+        PORT_MEMCACHED => BlockReason::MemcachedAmplification,
+        _ => BlockReason::UdpFlood,
+    }
+}
+
+#[inline(always)]
+fn update_stats_amplification(src_port: u16) {
+    drop_context_set_reason(&DROP_CONTEXT, amplification_reason(src_port));
     if let Some(stats) = unsafe { UDP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_amplification += 1;
@@ -1615,6 +1767,7 @@ fn update_stats_amplification() {
 
 #[inline(always)]
 fn update_stats_port_scan() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::PortScan);
     if let Some(stats) = unsafe { UDP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_port_scan += 1;
@@ -1624,6 +1777,7 @@ fn update_stats_port_scan() {
 
 #[inline(always)]
 fn update_stats_blocked() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::BlockedIp);
     if let Some(stats) = unsafe { UDP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_blocked_ip += 1;
@@ -1633,6 +1787,7 @@ fn update_stats_blocked() {
 
 #[inline(always)]
 fn update_stats_blocked_port() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::Manual);
     if let Some(stats) = unsafe { UDP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_blocked_port += 1;
@@ -1642,6 +1797,7 @@ fn update_stats_blocked_port() {
 
 #[inline(always)]
 fn update_stats_fragmented() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::Fragmentation);
     if let Some(stats) = unsafe { UDP_STATS.get_ptr_mut(0) } {
         unsafe {
             (*stats).dropped_fragmented += 1;
@@ -1649,6 +1805,16 @@ fn update_stats_fragmented() {
     }
 }
 
+#[inline(always)]
+fn update_stats_flow_rate_limited() {
+    drop_context_set_reason(&DROP_CONTEXT, BlockReason::FlowRateLimit);
+    if let Some(stats) = unsafe { UDP_STATS.get_ptr_mut(0) } {
+        unsafe {
+            (*stats).dropped_flow_rate += 1;
+        }
+    }
+}
+
 // ============================================================================
 // Panic Handler
 // ============================================================================
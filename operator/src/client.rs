@@ -3,7 +3,10 @@
 //! This module provides a client for communicating with the PistonProtection
 //! gateway service to sync protection rules and configurations.
 
-use crate::crd::{BackendSpec, DDoSProtection, FilterRule, FilterRuleType, GeoFilterMode};
+use crate::crd::{
+    BackendSpec, DDoSProtection, FilterRule, FilterRuleType, GeoFilterMode, ProgramRollout,
+    RolloutPhase,
+};
 use crate::error::{Error, Result};
 use backoff::{ExponentialBackoff, backoff::Backoff};
 use std::collections::HashMap;
@@ -98,6 +101,8 @@ pub struct GatewayClient {
     channel: Arc<RwLock<Option<Channel>>>,
     /// Cached backend IDs for each DDoSProtection resource
     backend_cache: Arc<RwLock<HashMap<String, Vec<String>>>>,
+    /// Last known progress of each program rollout, by rollout id
+    rollout_cache: Arc<RwLock<HashMap<String, RolloutSnapshot>>>,
 }
 
 impl GatewayClient {
@@ -107,6 +112,7 @@ impl GatewayClient {
             config,
             channel: Arc::new(RwLock::new(None)),
             backend_cache: Arc::new(RwLock::new(HashMap::new())),
+            rollout_cache: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -423,6 +429,116 @@ impl GatewayClient {
         .await
     }
 
+    /// Start a program rollout on the control plane
+    pub async fn start_program_rollout(&self, rollout: &ProgramRollout) -> Result<RolloutSnapshot> {
+        let name = rollout.metadata.name.clone().unwrap_or_default();
+        let namespace = rollout.metadata.namespace.clone().unwrap_or_default();
+        let resource_key = format!("{}/{}", namespace, name);
+
+        info!(
+            "Starting ProgramRollout {} ({} {})",
+            resource_key, rollout.spec.program, rollout.spec.version
+        );
+
+        self.with_retry("start_program_rollout", || async {
+            let _channel = self.get_channel().await?;
+
+            // Build rollout spec
+            let _rollout_spec = RolloutSpecDto {
+                program: rollout.spec.program.clone(),
+                version: rollout.spec.version.clone(),
+                digest: rollout.spec.digest.to_lowercase(),
+                variant: rollout.spec.variant.clone().unwrap_or_default(),
+                wave_percentages: rollout.spec.waves.clone(),
+                observe_seconds: rollout.spec.observe_seconds,
+                max_drop_rate_increase: rollout.spec.max_drop_rate_increase,
+                max_failed_workers: rollout.spec.max_failed_workers,
+            };
+
+            // In production: worker_client.start_rollout(rollout_spec).await?;
+
+            let snapshot = RolloutSnapshot {
+                id: uuid::Uuid::new_v4().to_string(),
+                phase: RolloutPhase::Rolling,
+                current_wave: 0,
+                updated_workers: 0,
+                failed_workers: 0,
+                message: "Waiting for the first wave".to_string(),
+                load_failures: Vec::new(),
+            };
+            self.rollout_cache
+                .write()
+                .await
+                .insert(snapshot.id.clone(), snapshot.clone());
+
+            debug!("ProgramRollout {} started as {}", resource_key, snapshot.id);
+
+            Ok(snapshot)
+        })
+        .await
+    }
+
+    /// Get the progress of a program rollout
+    pub async fn get_program_rollout(&self, rollout_id: &str) -> Result<RolloutSnapshot> {
+        let _channel = self.get_channel().await?;
+
+        // In production: worker_client.get_rollout(rollout_id).await?;
+        // and worker_client.get_load_diagnostics(program).await? for the
+        // workers that failed to load the rolled out version
+
+        self.rollout_cache
+            .read()
+            .await
+            .get(rollout_id)
+            .cloned()
+            .ok_or_else(|| Error::GrpcStatusError {
+                code: tonic::Code::NotFound as i32,
+                message: format!("Rollout {} not found", rollout_id),
+            })
+    }
+
+    /// Abort a program rollout, optionally rolling updated workers back
+    pub async fn abort_program_rollout(
+        &self,
+        rollout_id: &str,
+        rollback: bool,
+    ) -> Result<RolloutSnapshot> {
+        info!(
+            "Aborting program rollout {} (rollback: {})",
+            rollout_id, rollback
+        );
+
+        self.with_retry("abort_program_rollout", || async {
+            let _channel = self.get_channel().await?;
+
+            // In production: worker_client.abort_rollout(rollout_id, rollback).await?;
+
+            let mut cache = self.rollout_cache.write().await;
+            let snapshot = cache
+                .entry(rollout_id.to_string())
+                .or_insert_with(|| RolloutSnapshot {
+                    id: rollout_id.to_string(),
+                    phase: RolloutPhase::Rolling,
+                    current_wave: 0,
+                    updated_workers: 0,
+                    failed_workers: 0,
+                    message: String::new(),
+                    load_failures: Vec::new(),
+                });
+            if snapshot.phase.is_active() {
+                snapshot.phase = RolloutPhase::Aborted;
+                snapshot.message = if rollback {
+                    "Aborted and rolled back".to_string()
+                } else {
+                    "Aborted".to_string()
+                };
+            }
+
+            Ok(snapshot.clone())
+        })
+        .await
+    }
+
     /// Check gateway health
     pub async fn health_check(&self) -> Result<bool> {
         let _channel = self.get_channel().await?;
@@ -484,6 +600,31 @@ pub struct GatewayMetrics {
     pub under_attack: bool,
 }
 
+/// Progress of a program rollout as reported by the control plane
+#[derive(Debug, Clone)]
+pub struct RolloutSnapshot {
+    pub id: String,
+    pub phase: RolloutPhase,
+    pub current_wave: u32,
+    pub updated_workers: u32,
+    pub failed_workers: u32,
+    pub message: String,
+    /// Workers that failed to load the program
+    pub load_failures: Vec<LoadFailure>,
+}
+
+/// Load failure of a program on a worker, from the control plane's load
+/// diagnostics
+#[derive(Debug, Clone)]
+pub struct LoadFailure {
+    pub worker_id: String,
+    pub program: String,
+    /// `object`, `verifier` or `attach`
+    pub stage: String,
+    /// Verifier log line explaining the rejection, if any
+    pub summary: String,
+}
+
 /// Configuration update stream handle
 pub struct ConfigUpdateStream {
     // In production, this would hold the streaming handle
@@ -512,6 +653,18 @@ struct HealthCheckConfig {
     healthy_threshold: u32,
 }
 
+#[derive(Debug, Clone)]
+struct RolloutSpecDto {
+    program: String,
+    version: String,
+    digest: String,
+    variant: String,
+    wave_percentages: Vec<u32>,
+    observe_seconds: u32,
+    max_drop_rate_increase: f64,
+    max_failed_workers: u32,
+}
+
 #[derive(Debug, Clone)]
 struct RateLimitConfig {
     pps_per_ip: u64,
@@ -772,6 +772,8 @@ fn build_status(
         gateway_synced,
         last_error: error_message,
         current_protection_level: Some(ddos.spec.protection_level),
+        // Left out of the patch when empty, kept up to date by the federation
+        clusters: Vec::new(),
     }
 }
 
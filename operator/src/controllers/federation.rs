@@ -0,0 +1,584 @@
+//! MemberCluster Controller
+//!
+//! This controller runs in the central cluster of a federation, handling:
+//! - Propagating federated DDoSProtection and FilterRule resources to push
+//!   member clusters, and removing copies no longer placed on them
+//! - Marking pull member clusters unreachable once their agent stops reporting
+//! - Aggregating the status of the copies into the original resources
+//!
+//! The pull agent, run by the operator of a pull member cluster, does the
+//! propagation from the member side and reports to its MemberCluster.
+
+use crate::crd::{
+    ClusterStatus, Condition, DDoSProtection, FEDERATED_COPY_SELECTOR, FEDERATED_SELECTOR,
+    FederatedResourceStatus, FederationMode, FilterRule, MemberCluster, MemberClusterPhase,
+    MemberClusterStatus,
+};
+use crate::error::{Error, Result};
+use crate::federation::{
+    FIELD_MANAGER, PULL_STALE_AFTER_SECS, ResourceKey, SYNC_INTERVAL_SECS,
+    aggregate_cluster_statuses, ddos_protection_status, federated_copy, filter_rule_status,
+    is_placed, is_stale, sync_phase,
+};
+use crate::metrics::{Metrics, ReconciliationTimer};
+
+use k8s_openapi::NamespaceResourceScope;
+use k8s_openapi::api::core::v1::Secret;
+use kube::{
+    Client, Resource, ResourceExt,
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams},
+    config::{KubeConfigOptions, Kubeconfig},
+    runtime::controller::Action,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Context shared across reconciliation calls
+pub struct Context {
+    /// Kubernetes client of the central cluster
+    pub client: Client,
+    /// Metrics collector
+    pub metrics: Arc<Metrics>,
+    /// Namespace of the federated resources (None for all namespaces)
+    pub namespace: Option<String>,
+}
+
+impl Context {
+    /// Create a new context
+    pub fn new(client: Client, metrics: Arc<Metrics>, namespace: Option<String>) -> Self {
+        Self {
+            client,
+            metrics,
+            namespace,
+        }
+    }
+}
+
+/// Reconcile a MemberCluster resource
+pub async fn reconcile(
+    cluster: Arc<MemberCluster>,
+    ctx: Arc<Context>,
+) -> std::result::Result<Action, Error> {
+    let name = cluster.name_any();
+    let namespace = cluster.namespace().unwrap_or_else(|| "default".to_string());
+
+    info!(
+        "Reconciling MemberCluster {}/{} (mode: {})",
+        namespace, name, cluster.spec.mode
+    );
+
+    let timer = ReconciliationTimer::new(&ctx.metrics, "MemberCluster", &namespace);
+
+    let result = reconcile_cluster(&cluster, &ctx, &namespace, &name).await;
+    // Aggregate even when this cluster failed, its status changed too
+    let aggregated = aggregate_statuses(&ctx).await;
+
+    match result.and(aggregated) {
+        Ok(()) => {
+            timer.success();
+            Ok(Action::requeue(Duration::from_secs(SYNC_INTERVAL_SECS)))
+        }
+        Err(e) => {
+            timer.error(e.category());
+            Err(e)
+        }
+    }
+}
+
+/// Sync a member cluster and update its status
+async fn reconcile_cluster(
+    cluster: &MemberCluster,
+    ctx: &Context,
+    namespace: &str,
+    name: &str,
+) -> Result<()> {
+    let api: Api<MemberCluster> = Api::namespaced(ctx.client.clone(), namespace);
+
+    if cluster.spec.paused {
+        return patch_phase(&api, name, MemberClusterPhase::Paused, None).await;
+    }
+
+    match cluster.spec.mode {
+        FederationMode::Push => {
+            let synced = async {
+                let member = member_client(&ctx.client, namespace, cluster).await?;
+                propagate(
+                    &ctx.client,
+                    &member,
+                    ctx.namespace.as_deref(),
+                    name,
+                    cluster.spec.region.as_deref(),
+                )
+                .await
+            }
+            .await;
+
+            match synced {
+                Ok(resources) => {
+                    let status = synced_status(cluster, resources);
+                    update_status(&api, name, &status).await
+                }
+                Err(e) => {
+                    warn!("Failed to sync member cluster {}: {}", name, e);
+                    patch_phase(
+                        &api,
+                        name,
+                        MemberClusterPhase::Unreachable,
+                        Some(e.to_string()),
+                    )
+                    .await
+                }
+            }
+        }
+        FederationMode::Pull => {
+            // The agent in the member cluster reports its own status
+            let status = cluster.status.as_ref();
+            let last_sync = status.and_then(|status| status.last_sync.as_deref());
+            let phase = status.map(|status| status.phase).unwrap_or_default();
+            if phase != MemberClusterPhase::Unreachable && is_stale(last_sync, chrono::Utc::now()) {
+                let message = format!(
+                    "Pull agent has not reported for more than {}s",
+                    PULL_STALE_AFTER_SECS
+                );
+                patch_phase(&api, name, MemberClusterPhase::Unreachable, Some(message)).await
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Create a client for a push member cluster from its kubeconfig Secret
+async fn member_client(
+    client: &Client,
+    namespace: &str,
+    cluster: &MemberCluster,
+) -> Result<Client> {
+    let secret_ref = cluster.spec.kubeconfig_secret.as_ref().ok_or_else(|| {
+        Error::MissingField("spec.kubeconfigSecret is required in Push mode".to_string())
+    })?;
+
+    let secrets: Api<Secret> = Api::namespaced(client.clone(), namespace);
+    let secret = secrets
+        .get(&secret_ref.name)
+        .await
+        .map_err(Error::KubeError)?;
+    let kubeconfig = secret
+        .data
+        .as_ref()
+        .and_then(|data| data.get(&secret_ref.key))
+        .ok_or_else(|| {
+            Error::MissingField(format!(
+                "Secret {} has no key {}",
+                secret_ref.name, secret_ref.key
+            ))
+        })?;
+    let kubeconfig = std::str::from_utf8(&kubeconfig.0)
+        .map_err(|e| Error::ConfigError(format!("Invalid kubeconfig: {}", e)))?;
+
+    client_from_kubeconfig(
+        Kubeconfig::from_yaml(kubeconfig)
+            .map_err(|e| Error::ConfigError(format!("Invalid kubeconfig: {}", e)))?,
+    )
+    .await
+}
+
+/// Create a client from a kubeconfig
+pub async fn client_from_kubeconfig(kubeconfig: Kubeconfig) -> Result<Client> {
+    let config = kube::Config::from_custom_kubeconfig(kubeconfig, &KubeConfigOptions::default())
+        .await
+        .map_err(|e| Error::ConfigError(format!("Invalid kubeconfig: {}", e)))?;
+    Client::try_from(config).map_err(Error::KubeError)
+}
+
+/// Propagate the federated resources placed on a cluster from `central` to
+/// `member`
+///
+/// Returns the status of every placed resource. Failing to list the
+/// originals or to reach the member cluster fails the whole sync; failing to
+/// apply a single copy is reported in its status.
+pub async fn propagate(
+    central: &Client,
+    member: &Client,
+    namespace: Option<&str>,
+    cluster: &str,
+    region: Option<&str>,
+) -> Result<Vec<FederatedResourceStatus>> {
+    let mut resources = propagate_kind::<DDoSProtection>(
+        central,
+        member,
+        namespace,
+        cluster,
+        region,
+        ddos_protection_status,
+    )
+    .await?;
+    resources.extend(
+        propagate_kind::<FilterRule>(
+            central,
+            member,
+            namespace,
+            cluster,
+            region,
+            filter_rule_status,
+        )
+        .await?,
+    );
+    Ok(resources)
+}
+
+async fn propagate_kind<K>(
+    central: &Client,
+    member: &Client,
+    namespace: Option<&str>,
+    cluster: &str,
+    region: Option<&str>,
+    copy_status: fn(&K) -> FederatedResourceStatus,
+) -> Result<Vec<FederatedResourceStatus>>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + Clone
+        + Debug
+        + Serialize
+        + DeserializeOwned,
+{
+    let originals = scoped_api::<K>(central, namespace)
+        .list(&ListParams::default().labels(FEDERATED_SELECTOR))
+        .await
+        .map_err(Error::KubeError)?;
+
+    let mut placed = BTreeSet::new();
+    let mut resources = Vec::new();
+
+    for original in originals
+        .items
+        .iter()
+        .filter(|original| is_placed(original.annotations(), cluster, region))
+    {
+        let resource_namespace = original.namespace().unwrap_or_default();
+        let name = original.name_any();
+        placed.insert((resource_namespace.clone(), name.clone()));
+
+        let applied = match federated_copy(original) {
+            Ok(copy) => Api::<K>::namespaced(member.clone(), &resource_namespace)
+                .patch(
+                    &name,
+                    &PatchParams::apply(FIELD_MANAGER).force(),
+                    &Patch::Apply(&copy),
+                )
+                .await
+                .map_err(Error::KubeError),
+            Err(e) => Err(e),
+        };
+
+        resources.push(match applied {
+            Ok(copy) => copy_status(&copy),
+            Err(e) => {
+                warn!(
+                    "Failed to propagate {} {}/{} to cluster {}: {}",
+                    K::kind(&()),
+                    resource_namespace,
+                    name,
+                    cluster,
+                    e
+                );
+                FederatedResourceStatus {
+                    kind: K::kind(&()).to_string(),
+                    namespace: resource_namespace,
+                    name,
+                    synced: false,
+                    message: Some(e.to_string()),
+                    ..Default::default()
+                }
+            }
+        });
+    }
+
+    // Remove copies whose original is gone or no longer placed here
+    let member_api = scoped_api::<K>(member, namespace);
+    let copies = member_api
+        .list(&ListParams::default().labels(FEDERATED_COPY_SELECTOR))
+        .await
+        .map_err(Error::KubeError)?;
+    for copy in copies.items {
+        let key = (copy.namespace().unwrap_or_default(), copy.name_any());
+        if placed.contains(&key) {
+            continue;
+        }
+        info!(
+            "Removing {} {}/{} from cluster {}",
+            K::kind(&()),
+            key.0,
+            key.1,
+            cluster
+        );
+        if let Err(e) = Api::<K>::namespaced(member.clone(), &key.0)
+            .delete(&key.1, &DeleteParams::default())
+            .await
+        {
+            warn!(
+                "Failed to remove {} {}/{} from cluster {}: {}",
+                K::kind(&()),
+                key.0,
+                key.1,
+                cluster,
+                e
+            );
+        }
+    }
+
+    Ok(resources)
+}
+
+/// Status of a cluster after a sync
+fn synced_status(
+    cluster: &MemberCluster,
+    resources: Vec<FederatedResourceStatus>,
+) -> MemberClusterStatus {
+    let phase = sync_phase(&resources);
+    let synced = resources.iter().filter(|resource| resource.synced).count() as u32;
+    let failed = resources.len() as u32 - synced;
+
+    MemberClusterStatus {
+        phase,
+        last_sync: Some(chrono::Utc::now().to_rfc3339()),
+        synced_resources: synced,
+        failed_resources: failed,
+        resources,
+        last_error: None,
+        observed_generation: cluster.metadata.generation,
+        conditions: vec![Condition::new(
+            "Synced",
+            phase == MemberClusterPhase::Ready,
+            if phase == MemberClusterPhase::Ready {
+                "SyncSucceeded"
+            } else {
+                "SyncFailed"
+            },
+            &format!("{} resources synced, {} failed", synced, failed),
+        )],
+    }
+}
+
+/// Replace the status of a MemberCluster
+async fn update_status(
+    api: &Api<MemberCluster>,
+    name: &str,
+    status: &MemberClusterStatus,
+) -> Result<()> {
+    let patch = serde_json::json!({
+        "status": status
+    });
+
+    api.patch_status(
+        name,
+        &PatchParams::apply("pistonprotection-operator"),
+        &Patch::Merge(&patch),
+    )
+    .await
+    .map_err(Error::KubeError)?;
+
+    debug!("Status updated for MemberCluster {}", name);
+
+    Ok(())
+}
+
+/// Set the phase and error of a MemberCluster, keeping its last resources
+async fn patch_phase(
+    api: &Api<MemberCluster>,
+    name: &str,
+    phase: MemberClusterPhase,
+    error: Option<String>,
+) -> Result<()> {
+    let patch = serde_json::json!({
+        "status": {
+            "phase": phase,
+            "lastError": error,
+        }
+    });
+
+    api.patch_status(
+        name,
+        &PatchParams::apply("pistonprotection-operator"),
+        &Patch::Merge(&patch),
+    )
+    .await
+    .map_err(Error::KubeError)?;
+
+    Ok(())
+}
+
+/// Write the status of the copies into every federated resource
+async fn aggregate_statuses(ctx: &Context) -> Result<()> {
+    let clusters = scoped_api::<MemberCluster>(&ctx.client, ctx.namespace.as_deref())
+        .list(&ListParams::default())
+        .await
+        .map_err(Error::KubeError)?;
+    let aggregated = aggregate_cluster_statuses(&clusters.items);
+
+    aggregate_kind::<DDoSProtection>(ctx, &aggregated, |ddos| {
+        ddos.status
+            .as_ref()
+            .map(|status| status.clusters.clone())
+            .unwrap_or_default()
+    })
+    .await?;
+    aggregate_kind::<FilterRule>(ctx, &aggregated, |rule| {
+        rule.status
+            .as_ref()
+            .map(|status| status.clusters.clone())
+            .unwrap_or_default()
+    })
+    .await
+}
+
+async fn aggregate_kind<K>(
+    ctx: &Context,
+    aggregated: &BTreeMap<ResourceKey, Vec<ClusterStatus>>,
+    current: fn(&K) -> Vec<ClusterStatus>,
+) -> Result<()>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + Clone
+        + Debug
+        + Serialize
+        + DeserializeOwned,
+{
+    let originals = scoped_api::<K>(&ctx.client, ctx.namespace.as_deref())
+        .list(&ListParams::default().labels(FEDERATED_SELECTOR))
+        .await
+        .map_err(Error::KubeError)?;
+
+    for original in originals.items {
+        let namespace = original.namespace().unwrap_or_default();
+        let name = original.name_any();
+        let key = (K::kind(&()).to_string(), namespace.clone(), name.clone());
+        let clusters = aggregated.get(&key).cloned().unwrap_or_default();
+        if current(&original) == clusters {
+            continue;
+        }
+
+        let patch = serde_json::json!({
+            "status": {
+                "clusters": clusters
+            }
+        });
+        Api::<K>::namespaced(ctx.client.clone(), &namespace)
+            .patch_status(
+                &name,
+                &PatchParams::apply("pistonprotection-operator"),
+                &Patch::Merge(&patch),
+            )
+            .await
+            .map_err(Error::KubeError)?;
+    }
+
+    Ok(())
+}
+
+fn scoped_api<K>(client: &Client, namespace: Option<&str>) -> Api<K>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>,
+{
+    match namespace {
+        Some(ns) => Api::namespaced(client.clone(), ns),
+        None => Api::all(client.clone()),
+    }
+}
+
+/// Error policy for the controller
+pub fn error_policy(cluster: Arc<MemberCluster>, error: &Error, _ctx: Arc<Context>) -> Action {
+    let name = cluster.name_any();
+    let namespace = cluster.namespace().unwrap_or_default();
+
+    warn!(
+        "Reconciliation error for MemberCluster {}/{}: {:?}",
+        namespace, name, error
+    );
+
+    if error.is_permanent() {
+        warn!(
+            "Permanent error for MemberCluster {}/{}, not requeuing",
+            namespace, name
+        );
+        Action::await_change()
+    } else {
+        Action::requeue(error.retry_delay())
+    }
+}
+
+/// Run the pull agent of a member cluster
+///
+/// Reads the MemberCluster `cluster_namespace/cluster` and the federated
+/// resources from the central cluster, applies the ones placed on this
+/// cluster locally and reports their status back, every sync interval.
+pub async fn run_pull_agent(
+    central: Client,
+    local: Client,
+    cluster_namespace: String,
+    cluster: String,
+) {
+    let api: Api<MemberCluster> = Api::namespaced(central.clone(), &cluster_namespace);
+    let mut interval = tokio::time::interval(Duration::from_secs(SYNC_INTERVAL_SECS));
+
+    info!(
+        "Starting federation pull agent for member cluster {}/{}",
+        cluster_namespace, cluster
+    );
+
+    loop {
+        interval.tick().await;
+
+        let member = match api.get(&cluster).await {
+            Ok(member) => member,
+            Err(e) => {
+                warn!("Failed to get MemberCluster {}: {}", cluster, e);
+                continue;
+            }
+        };
+        if member.spec.mode != FederationMode::Pull {
+            warn!(
+                "MemberCluster {} is in {} mode, not pulling",
+                cluster, member.spec.mode
+            );
+            continue;
+        }
+        if member.spec.paused {
+            if let Err(e) = patch_phase(&api, &cluster, MemberClusterPhase::Paused, None).await {
+                warn!("Failed to report MemberCluster {}: {}", cluster, e);
+            }
+            continue;
+        }
+
+        let result = match propagate(
+            &central,
+            &local,
+            None,
+            &cluster,
+            member.spec.region.as_deref(),
+        )
+        .await
+        {
+            Ok(resources) => {
+                update_status(&api, &cluster, &synced_status(&member, resources)).await
+            }
+            Err(e) => {
+                warn!("Failed to pull federated resources: {}", e);
+                patch_phase(
+                    &api,
+                    &cluster,
+                    MemberClusterPhase::Degraded,
+                    Some(e.to_string()),
+                )
+                .await
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to report MemberCluster {}: {}", cluster, e);
+        }
+    }
+}
@@ -10,7 +10,8 @@ use chrono::Datelike;
 
 use crate::client::GatewayClient;
 use crate::crd::{
-    Condition, DDoSProtection, FINALIZER, FilterRule, FilterRuleStatus, FilterRuleType,
+    Condition, DDoSProtection, FINALIZER, FilterAction, FilterRule, FilterRuleStatus,
+    FilterRuleType, Protocol,
 };
 use crate::error::{Error, Result};
 use crate::metrics::{Metrics, ReconciliationTimer};
@@ -24,6 +25,7 @@ use kube::{
         finalizer::{Event as FinalizerEvent, finalizer},
     },
 };
+use pistonprotection_rule_compiler as compiler;
 use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -218,12 +220,38 @@ async fn reconcile_apply(
         true // Consider it "synced" if we intentionally skip
     };
 
+    // Rules overlapping this one are resolved by priority; surface them so
+    // the intended order can be made explicit
+    let conflicts = find_conflicts(&ctx.client, rule, namespace).await;
+    if !conflicts.is_empty() {
+        warn!(
+            "FilterRule {}/{} conflicts with other rules: {}",
+            namespace,
+            name,
+            conflicts.join("; ")
+        );
+        recorder
+            .publish(
+                &Event {
+                    type_: EventType::Warning,
+                    reason: "RuleConflict".to_string(),
+                    note: Some(conflicts.join("; ")),
+                    action: "Validate".to_string(),
+                    secondary: None,
+                },
+                &obj_ref,
+            )
+            .await
+            .ok();
+    }
+
     // Update status
     let status = build_status_full(
         rule,
         should_be_active,
         gateway_synced,
         applied_to_count,
+        &conflicts,
         None,
     );
     update_status(&ctx.client, namespace, name, status).await?;
@@ -303,6 +331,44 @@ async fn reconcile_cleanup(
     Ok(Action::await_change())
 }
 
+/// Conflicts between a rule and the other enabled rules of its namespace
+///
+/// Failures to list rules are logged and yield no conflicts.
+async fn find_conflicts(client: &Client, rule: &FilterRule, namespace: &str) -> Vec<String> {
+    let api: Api<FilterRule> = Api::namespaced(client.clone(), namespace);
+    let rules = match api.list(&ListParams::default()).await {
+        Ok(list) => list.items,
+        Err(e) => {
+            warn!(
+                "Failed to list FilterRules in {} for conflict check: {}",
+                namespace, e
+            );
+            return Vec::new();
+        }
+    };
+
+    let id = compiler_rule_id(rule);
+    let mut converted: Vec<compiler::Rule> = rules
+        .iter()
+        .filter(|r| compiler_rule_id(r) != id)
+        .filter_map(|r| to_compiler_rule(r).ok())
+        .collect();
+    // The rule being reconciled may be newer than the listed copy
+    if let Ok(current) = to_compiler_rule(rule) {
+        converted.push(current);
+    }
+
+    rule_conflicts(&converted, &id)
+}
+
+/// Conflicts of one rule within a rule set
+fn rule_conflicts(rules: &[compiler::Rule], id: &str) -> Vec<String> {
+    match compiler::compile(rules) {
+        Ok(compiled) => compiled.conflicts_of(id).map(|c| c.to_string()).collect(),
+        Err(e) => vec![format!("rules of the namespace do not compile: {}", e)],
+    }
+}
+
 /// Validate FilterRule resource
 fn validate_filter_rule(rule: &FilterRule) -> Result<()> {
     // Validate name
@@ -359,9 +425,88 @@ fn validate_filter_rule(rule: &FilterRule) -> Result<()> {
         return Err(Error::validation("priority", "must be between 0 and 100"));
     }
 
+    // Validate the rule compiles into worker map entries
+    let compiled = compiler::compile(&[to_compiler_rule(rule)?])
+        .map_err(|e| Error::validation("config", &e.to_string()))?;
+    for skipped in compiled.skipped() {
+        debug!(
+            "FilterRule {} not compiled to maps: {}",
+            skipped.rule, skipped.reason
+        );
+    }
+
     Ok(())
 }
 
+/// Id of a FilterRule in the rule compiler
+fn compiler_rule_id(rule: &FilterRule) -> String {
+    format!(
+        "{}/{}",
+        rule.namespace().unwrap_or_default(),
+        rule.name_any()
+    )
+}
+
+/// Convert a FilterRule into the rule compiler's model
+fn to_compiler_rule(rule: &FilterRule) -> Result<compiler::Rule> {
+    let spec = &rule.spec;
+    let action = match spec.action {
+        FilterAction::Allow => compiler::Action::Allow,
+        FilterAction::Drop | FilterAction::Tarpit => compiler::Action::Drop,
+        FilterAction::RateLimit => compiler::Action::RateLimit,
+        FilterAction::Log => compiler::Action::Log,
+        FilterAction::Challenge => compiler::Action::Challenge,
+        FilterAction::Redirect => compiler::Action::Redirect,
+    };
+
+    // CRD priorities run 0-100 with higher first; the compiler's lower first
+    let mut out = compiler::Rule::new(compiler_rule_id(rule), 100 - spec.priority, action);
+    out.enabled = spec.enabled;
+
+    let ip_ranges = spec
+        .config
+        .ip_ranges
+        .iter()
+        .map(|r| {
+            r.parse().map_err(|_| {
+                Error::validation("config.ipRanges", &format!("invalid IP range: {}", r))
+            })
+        })
+        .collect::<Result<Vec<compiler::Cidr>>>()?;
+    if spec.rule_type == FilterRuleType::IpBlocklist {
+        out.source_ip_blacklist = ip_ranges;
+    } else {
+        out.source_ips = ip_ranges;
+    }
+
+    out.destination_ports = spec
+        .config
+        .ports
+        .iter()
+        .map(|p| compiler::PortRange::new(p.start.into(), p.end.into()))
+        .collect();
+    for protocol in &spec.config.protocols {
+        let (transport, l7) = match protocol {
+            Protocol::Tcp => (compiler::Protocol::Tcp, false),
+            Protocol::Udp => (compiler::Protocol::Udp, false),
+            Protocol::Http | Protocol::Https | Protocol::MinecraftJava => {
+                (compiler::Protocol::Tcp, true)
+            }
+            Protocol::MinecraftBedrock | Protocol::Quic => (compiler::Protocol::Udp, true),
+        };
+        if !out.protocols.contains(&transport) {
+            out.protocols.push(transport);
+        }
+        out.l7 |= l7;
+    }
+    out.countries = spec.config.countries.clone();
+    out.asns = spec.config.asns.clone();
+    out.l7 |= spec.config.http_match.is_some() || spec.config.custom_program.is_some();
+    out.scheduled = spec.schedule.is_some();
+
+    Ok(out)
+}
+
 /// Check if string is a valid IP address or CIDR range
 fn is_valid_ip_or_cidr(s: &str) -> bool {
     if s.contains('/') {
@@ -534,7 +679,7 @@ fn build_status(
     gateway_synced: bool,
     error_message: Option<String>,
 ) -> FilterRuleStatus {
-    build_status_full(rule, active, gateway_synced, 0, error_message)
+    build_status_full(rule, active, gateway_synced, 0, &[], error_message)
 }
 
 /// Build status object with all fields
@@ -543,11 +688,13 @@ fn build_status_full(
     active: bool,
     gateway_synced: bool,
     applied_to_count: i32,
+    conflicts: &[String],
     error_message: Option<String>,
 ) -> FilterRuleStatus {
     let now = chrono::Utc::now().to_rfc3339();
 
     let mut conditions = Vec::new();
+    let conflict_message = conflicts.join("; ");
 
     // Ready condition
     conditions.push(Condition::new(
@@ -597,6 +744,22 @@ fn build_status_full(
         error_message.as_deref().unwrap_or("Rule validation passed"),
     ));
 
+    // Conflicting condition
+    conditions.push(Condition::new(
+        "Conflicting",
+        !conflicts.is_empty(),
+        if conflicts.is_empty() {
+            "NoConflicts"
+        } else {
+            "ResolvedByPriority"
+        },
+        if conflicts.is_empty() {
+            "Rule does not conflict with other rules"
+        } else {
+            &conflict_message
+        },
+    ));
+
     FilterRuleStatus {
         active,
         match_count: 0, // Will be populated from gateway metrics
@@ -611,6 +774,8 @@ fn build_status_full(
         last_error: error_message,
         applied_to_count,
         conditions,
+        // Left out of the patch when empty, kept up to date by the federation
+        clusters: Vec::new(),
     }
 }
 
@@ -753,6 +918,107 @@ mod tests {
         assert!(is_rule_scheduled_active(&rule));
     }
 
+    #[test]
+    fn test_to_compiler_rule() {
+        let mut rule = create_test_rule();
+        rule.spec.config.ip_ranges = vec!["192.0.2.0/30".to_string()];
+        rule.spec.config.ports = vec![crate::crd::PortRange { start: 53, end: 53 }];
+        rule.spec.config.protocols = vec![Protocol::Udp, Protocol::Quic];
+
+        let converted = to_compiler_rule(&rule).unwrap();
+        assert_eq!(converted.id, "default/test-rule");
+        assert_eq!(converted.priority, 50);
+        assert_eq!(converted.action, compiler::Action::Drop);
+        assert_eq!(
+            converted.source_ip_blacklist,
+            vec!["192.0.2.0/30".parse().unwrap()]
+        );
+        assert!(converted.source_ips.is_empty());
+        assert_eq!(converted.protocols, vec![compiler::Protocol::Udp]);
+        assert!(converted.l7);
+
+        rule.spec.rule_type = FilterRuleType::IpAllowlist;
+        rule.spec.action = FilterAction::Allow;
+        rule.spec.priority = 90;
+        let converted = to_compiler_rule(&rule).unwrap();
+        assert_eq!(converted.priority, 10);
+        assert_eq!(converted.source_ips.len(), 1);
+        assert!(converted.source_ip_blacklist.is_empty());
+    }
+
+    #[test]
+    fn test_validate_compiles_rule() {
+        let mut rule = create_test_rule();
+        rule.spec.rule_type = FilterRuleType::Custom;
+        rule.spec.config.ip_ranges = vec![];
+        rule.spec.config.ports = vec![crate::crd::PortRange {
+            start: 1,
+            end: 65535,
+        }];
+        rule.spec.config.protocols = vec![Protocol::Udp];
+
+        // Every UDP port does not fit the worker's blocked port map
+        assert!(validate_filter_rule(&rule).is_err());
+    }
+
+    #[test]
+    fn test_rule_conflicts() {
+        let mut allow = create_test_rule();
+        allow.metadata.name = Some("allow-office".to_string());
+        allow.spec.rule_type = FilterRuleType::IpAllowlist;
+        allow.spec.action = FilterAction::Allow;
+        allow.spec.priority = 50;
+        allow.spec.config.ip_ranges = vec!["192.0.2.0/30".to_string()];
+
+        let mut block = create_test_rule();
+        block.metadata.name = Some("block-host".to_string());
+        block.spec.priority = 50;
+        block.spec.config.ip_ranges = vec!["192.0.2.1".to_string()];
+
+        let rules: Vec<compiler::Rule> = [&allow, &block]
+            .iter()
+            .map(|r| to_compiler_rule(r).unwrap())
+            .collect();
+        let conflicts = rule_conflicts(&rules, "default/block-host");
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].contains("default/allow-office takes precedence"));
+        assert!(conflicts[0].contains("same priority"));
+
+        // A higher CRD priority takes precedence
+        block.spec.priority = 60;
+        let rules: Vec<compiler::Rule> = [&allow, &block]
+            .iter()
+            .map(|r| to_compiler_rule(r).unwrap())
+            .collect();
+        let conflicts = rule_conflicts(&rules, "default/allow-office");
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].contains("default/block-host takes precedence"));
+        assert!(!conflicts[0].contains("same priority"));
+    }
+
+    #[test]
+    fn test_conflicting_condition() {
+        let rule = create_test_rule();
+
+        let status = build_status_full(&rule, true, true, 1, &[], None);
+        let condition = status
+            .conditions
+            .iter()
+            .find(|c| c.condition_type == "Conflicting")
+            .unwrap();
+        assert_eq!(condition.status, "False");
+
+        let conflicts = vec!["a and b apply different actions".to_string()];
+        let status = build_status_full(&rule, true, true, 1, &conflicts, None);
+        let condition = status
+            .conditions
+            .iter()
+            .find(|c| c.condition_type == "Conflicting")
+            .unwrap();
+        assert_eq!(condition.status, "True");
+        assert_eq!(condition.reason, "ResolvedByPriority");
+    }
+
     #[test]
     fn test_validate_priority() {
         let mut rule = create_test_rule();
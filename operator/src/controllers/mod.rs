@@ -5,14 +5,23 @@
 //! - FilterRule: Custom filtering rules
 //! - Backend: Backend service definitions
 //! - IPBlocklist: IP blocklist management
+//! - ProgramRollout: Staged eBPF program rollouts
+//! - MemberCluster: Multi-cluster federation
+//! - Protected ports: Worker port maps discovered from labeled Services
 
 pub mod backend;
 pub mod ddos_protection;
+pub mod federation;
 pub mod filter_rule;
 pub mod ip_blocklist;
+pub mod program_rollout;
+pub mod protected_ports;
 
 // Re-export for convenience
 pub use backend::Context as BackendContext;
 pub use ddos_protection::Context as DDoSProtectionContext;
+pub use federation::Context as FederationContext;
 pub use filter_rule::Context as FilterRuleContext;
 pub use ip_blocklist::Context as IPBlocklistContext;
+pub use program_rollout::Context as ProgramRolloutContext;
+pub use protected_ports::Context as ProtectedPortsContext;
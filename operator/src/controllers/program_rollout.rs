@@ -0,0 +1,556 @@
+//! ProgramRollout Controller
+//!
+//! This controller manages ProgramRollout custom resources, handling:
+//! - Starting eBPF program rollouts on the control plane
+//! - Tracking wave progress and rollbacks in the resource status
+//! - Aborting rollouts on request or deletion
+
+use crate::client::{GatewayClient, LoadFailure, RolloutSnapshot};
+use crate::crd::{Condition, FINALIZER, ProgramRollout, ProgramRolloutStatus, RolloutPhase};
+use crate::error::{Error, Result};
+use crate::metrics::{Metrics, ReconciliationTimer};
+
+use kube::{
+    Client, Resource, ResourceExt,
+    api::{Api, Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        events::{Event, EventType, Recorder, Reporter},
+        finalizer::{Event as FinalizerEvent, finalizer},
+    },
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Context shared across reconciliation calls
+pub struct Context {
+    /// Kubernetes client
+    pub client: Client,
+    /// Gateway gRPC client
+    pub gateway_client: GatewayClient,
+    /// Metrics collector
+    pub metrics: Arc<Metrics>,
+    /// Event reporter
+    pub reporter: Reporter,
+}
+
+impl Context {
+    /// Create a new context
+    pub fn new(
+        client: Client,
+        gateway_client: GatewayClient,
+        metrics: Arc<Metrics>,
+        reporter: Reporter,
+    ) -> Self {
+        Self {
+            client,
+            gateway_client,
+            metrics,
+            reporter,
+        }
+    }
+
+    /// Create a recorder for events
+    fn recorder(&self) -> Recorder {
+        Recorder::new(self.client.clone(), self.reporter.clone())
+    }
+}
+
+/// Reconcile a ProgramRollout resource
+pub async fn reconcile(
+    rollout: Arc<ProgramRollout>,
+    ctx: Arc<Context>,
+) -> std::result::Result<Action, Error> {
+    let name = rollout.name_any();
+    let namespace = rollout.namespace().unwrap_or_else(|| "default".to_string());
+
+    info!(
+        "Reconciling ProgramRollout {}/{} (generation: {:?})",
+        namespace, name, rollout.metadata.generation
+    );
+
+    let timer = ReconciliationTimer::new(&ctx.metrics, "ProgramRollout", &namespace);
+
+    // Create recorder for events
+    let recorder = ctx.recorder();
+
+    // Get API for this namespace
+    let rollout_api: Api<ProgramRollout> = Api::namespaced(ctx.client.clone(), &namespace);
+
+    // Handle finalizer
+    let result = finalizer(&rollout_api, FINALIZER, rollout, |event| async {
+        match event {
+            FinalizerEvent::Apply(rollout) => {
+                reconcile_apply(&rollout, &ctx, &recorder, &namespace, &name).await
+            }
+            FinalizerEvent::Cleanup(rollout) => {
+                reconcile_cleanup(&rollout, &ctx, &recorder, &namespace, &name).await
+            }
+        }
+    })
+    .await;
+
+    match result {
+        Ok(action) => {
+            timer.success();
+            Ok(action)
+        }
+        Err(e) => {
+            let error = match e {
+                kube::runtime::finalizer::Error::ApplyFailed(e) => e,
+                kube::runtime::finalizer::Error::CleanupFailed(e) => e,
+                kube::runtime::finalizer::Error::AddFinalizer(e) => Error::KubeError(e),
+                kube::runtime::finalizer::Error::RemoveFinalizer(e) => Error::KubeError(e),
+                kube::runtime::finalizer::Error::UnnamedObject => {
+                    Error::Permanent("Resource has no name".to_string())
+                }
+                kube::runtime::finalizer::Error::InvalidFinalizer => {
+                    Error::Permanent("Invalid finalizer".to_string())
+                }
+            };
+            timer.error(error.category());
+            Err(error)
+        }
+    }
+}
+
+/// Apply reconciliation - handle create/update
+async fn reconcile_apply(
+    rollout: &ProgramRollout,
+    ctx: &Context,
+    recorder: &Recorder,
+    namespace: &str,
+    name: &str,
+) -> Result<Action> {
+    info!("Applying ProgramRollout {}/{}", namespace, name);
+
+    // Validate the rollout
+    validate_rollout(rollout)?;
+
+    // Create object reference for events
+    let obj_ref = rollout.object_ref(&());
+
+    let previous_phase = rollout.status.as_ref().map(|s| s.phase).unwrap_or_default();
+    let rollout_id = rollout.status.as_ref().and_then(|s| s.rollout_id.clone());
+
+    let snapshot = match rollout_id {
+        // Finished rollouts are never restarted; create a new resource instead
+        Some(_) if !previous_phase.is_active() => None,
+        Some(id) if rollout.spec.abort => Some(
+            ctx.gateway_client
+                .abort_program_rollout(&id, rollout.spec.rollback_on_abort)
+                .await?,
+        ),
+        Some(id) => Some(ctx.gateway_client.get_program_rollout(&id).await?),
+        None if rollout.spec.abort => None,
+        None => {
+            let snapshot = ctx.gateway_client.start_program_rollout(rollout).await?;
+
+            recorder
+                .publish(
+                    &Event {
+                        type_: EventType::Normal,
+                        reason: "RolloutStarted".to_string(),
+                        note: Some(format!(
+                            "Rolling out {} {} in {} waves",
+                            rollout.spec.program,
+                            rollout.spec.version,
+                            rollout.spec.waves.len()
+                        )),
+                        action: "Reconcile".to_string(),
+                        secondary: None,
+                    },
+                    &obj_ref,
+                )
+                .await
+                .ok();
+
+            Some(snapshot)
+        }
+    };
+
+    let Some(snapshot) = snapshot else {
+        debug!(
+            "ProgramRollout {}/{} is {}, nothing to do",
+            namespace, name, previous_phase
+        );
+        return Ok(Action::await_change());
+    };
+
+    // Record phase changes that need attention
+    if snapshot.phase != previous_phase && !snapshot.phase.is_active() {
+        let type_ = if snapshot.phase == RolloutPhase::Completed {
+            EventType::Normal
+        } else {
+            EventType::Warning
+        };
+        recorder
+            .publish(
+                &Event {
+                    type_,
+                    reason: format!("Rollout{}", snapshot.phase),
+                    note: Some(snapshot.message.clone()),
+                    action: "Reconcile".to_string(),
+                    secondary: None,
+                },
+                &obj_ref,
+            )
+            .await
+            .ok();
+    }
+
+    let status = build_status(rollout, &snapshot);
+    update_status(&ctx.client, namespace, name, status).await?;
+
+    // Poll active rollouts; finished ones only change when edited
+    if snapshot.phase.is_active() {
+        Ok(Action::requeue(Duration::from_secs(15)))
+    } else {
+        Ok(Action::await_change())
+    }
+}
+
+/// Cleanup reconciliation - handle delete
+async fn reconcile_cleanup(
+    rollout: &ProgramRollout,
+    ctx: &Context,
+    recorder: &Recorder,
+    namespace: &str,
+    name: &str,
+) -> Result<Action> {
+    info!("Cleaning up ProgramRollout {}/{}", namespace, name);
+
+    let Some(status) = rollout.status.as_ref() else {
+        return Ok(Action::await_change());
+    };
+
+    // Deleting an active rollout stops it where it is; workers keep the
+    // version they run unless rollback is requested
+    if let Some(id) = status.rollout_id.as_ref()
+        && status.phase.is_active()
+    {
+        let obj_ref = rollout.object_ref(&());
+        recorder
+            .publish(
+                &Event {
+                    type_: EventType::Normal,
+                    reason: "Deleting".to_string(),
+                    note: Some("Aborting active program rollout".to_string()),
+                    action: "Delete".to_string(),
+                    secondary: None,
+                },
+                &obj_ref,
+            )
+            .await
+            .ok();
+
+        if let Err(e) = ctx
+            .gateway_client
+            .abort_program_rollout(id, rollout.spec.rollback_on_abort)
+            .await
+        {
+            warn!(
+                "Failed to abort rollout for ProgramRollout {}/{}: {}",
+                namespace, name, e
+            );
+            // Continue with cleanup even if the abort fails
+        }
+    }
+
+    info!("Cleanup complete for ProgramRollout {}/{}", namespace, name);
+
+    Ok(Action::await_change())
+}
+
+/// Validate ProgramRollout resource
+fn validate_rollout(rollout: &ProgramRollout) -> Result<()> {
+    let spec = &rollout.spec;
+
+    if spec.program.is_empty() {
+        return Err(Error::validation("program", "program name is required"));
+    }
+
+    if spec.version.is_empty() {
+        return Err(Error::validation("version", "version is required"));
+    }
+
+    if spec.digest.len() != 64 || !spec.digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::validation(
+            "digest",
+            "must be a hex-encoded SHA-256 digest",
+        ));
+    }
+
+    if spec.waves.iter().any(|&w| w == 0 || w > 100) {
+        return Err(Error::validation("waves", "must be between 1 and 100"));
+    }
+
+    if spec.waves.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(Error::validation("waves", "must be increasing"));
+    }
+
+    if spec.waves.last() != Some(&100) {
+        return Err(Error::validation("waves", "last wave must be 100"));
+    }
+
+    if !(0.0..=1.0).contains(&spec.max_drop_rate_increase) {
+        return Err(Error::validation(
+            "maxDropRateIncrease",
+            "must be between 0 and 1",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Update the status of a ProgramRollout resource
+async fn update_status(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+    status: ProgramRolloutStatus,
+) -> Result<()> {
+    let api: Api<ProgramRollout> = Api::namespaced(client.clone(), namespace);
+
+    let patch = serde_json::json!({
+        "status": status
+    });
+
+    api.patch_status(
+        name,
+        &PatchParams::apply("pistonprotection-operator"),
+        &Patch::Merge(&patch),
+    )
+    .await
+    .map_err(Error::KubeError)?;
+
+    debug!("Status updated for ProgramRollout {}/{}", namespace, name);
+
+    Ok(())
+}
+
+/// Build status object
+fn build_status(rollout: &ProgramRollout, snapshot: &RolloutSnapshot) -> ProgramRolloutStatus {
+    let mut conditions = Vec::new();
+
+    // Ready condition
+    conditions.push(Condition::new(
+        "Ready",
+        snapshot.phase == RolloutPhase::Completed,
+        match snapshot.phase {
+            RolloutPhase::Completed => "RolloutCompleted",
+            RolloutPhase::RolledBack => "RolledBack",
+            RolloutPhase::Aborted => "Aborted",
+            _ => "RolloutInProgress",
+        },
+        &snapshot.message,
+    ));
+
+    // Progressing condition
+    conditions.push(Condition::new(
+        "Progressing",
+        snapshot.phase.is_active(),
+        if snapshot.phase.is_active() {
+            "WaveInProgress"
+        } else {
+            "RolloutFinished"
+        },
+        &format!(
+            "Wave {}/{}: {} workers updated, {} failed",
+            snapshot.current_wave,
+            rollout.spec.waves.len(),
+            snapshot.updated_workers,
+            snapshot.failed_workers
+        ),
+    ));
+
+    // Loaded condition
+    conditions.push(Condition::new(
+        "Loaded",
+        snapshot.load_failures.is_empty(),
+        if snapshot.load_failures.is_empty() {
+            "ProgramLoaded"
+        } else if snapshot.load_failures.iter().any(|f| f.stage == "verifier") {
+            "VerifierRejected"
+        } else {
+            "LoadFailed"
+        },
+        &load_failures_message(&snapshot.load_failures),
+    ));
+
+    ProgramRolloutStatus {
+        rollout_id: Some(snapshot.id.clone()),
+        phase: snapshot.phase,
+        current_wave: snapshot.current_wave,
+        total_waves: rollout.spec.waves.len() as u32,
+        updated_workers: snapshot.updated_workers,
+        failed_workers: snapshot.failed_workers,
+        message: Some(snapshot.message.clone()),
+        last_updated: Some(chrono::Utc::now().to_rfc3339()),
+        observed_generation: rollout.metadata.generation,
+        conditions,
+    }
+}
+
+/// Summary of the load failures for the Loaded condition
+fn load_failures_message(failures: &[LoadFailure]) -> String {
+    let Some(first) = failures.first() else {
+        return "All updated workers loaded the program".to_string();
+    };
+
+    let mut message = format!(
+        "{} worker(s) failed to load the program; {} ({} stage)",
+        failures.len(),
+        first.worker_id,
+        first.stage
+    );
+    if !first.summary.is_empty() {
+        message.push_str(&format!(": {}", first.summary));
+    }
+    message
+}
+
+/// Error policy for the controller
+pub fn error_policy(rollout: Arc<ProgramRollout>, error: &Error, _ctx: Arc<Context>) -> Action {
+    let name = rollout.name_any();
+    let namespace = rollout.namespace().unwrap_or_default();
+
+    warn!(
+        "Reconciliation error for ProgramRollout {}/{}: {:?}",
+        namespace, name, error
+    );
+
+    let delay = error.retry_delay();
+
+    if error.is_permanent() {
+        warn!(
+            "Permanent error for ProgramRollout {}/{}, not requeuing",
+            namespace, name
+        );
+        Action::await_change()
+    } else {
+        info!(
+            "Requeuing ProgramRollout {}/{} in {:?}",
+            namespace, name, delay
+        );
+        Action::requeue(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crd::ProgramRolloutSpec;
+    use kube::api::ObjectMeta;
+
+    fn create_test_rollout() -> ProgramRollout {
+        ProgramRollout {
+            metadata: ObjectMeta {
+                name: Some("xdp-filter-0-2-0".to_string()),
+                namespace: Some("default".to_string()),
+                generation: Some(1),
+                ..Default::default()
+            },
+            spec: ProgramRolloutSpec {
+                program: "xdp_filter".to_string(),
+                version: "0.2.0".to_string(),
+                digest: "ab".repeat(32),
+                variant: None,
+                waves: vec![10, 50, 100],
+                observe_seconds: 300,
+                max_drop_rate_increase: 0.05,
+                max_failed_workers: 0,
+                abort: false,
+                rollback_on_abort: true,
+            },
+            status: None,
+        }
+    }
+
+    fn snapshot(phase: RolloutPhase) -> RolloutSnapshot {
+        RolloutSnapshot {
+            id: "rollout-1".to_string(),
+            phase,
+            current_wave: 2,
+            updated_workers: 5,
+            failed_workers: 1,
+            message: "Wave 2/3: 50% of workers".to_string(),
+            load_failures: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate_rollout() {
+        let rollout = create_test_rollout();
+        assert!(validate_rollout(&rollout).is_ok());
+    }
+
+    #[test]
+    fn test_validate_invalid_digest() {
+        let mut rollout = create_test_rollout();
+        rollout.spec.digest = "not-a-digest".to_string();
+        assert!(validate_rollout(&rollout).is_err());
+    }
+
+    #[test]
+    fn test_validate_waves() {
+        let mut rollout = create_test_rollout();
+
+        rollout.spec.waves = vec![50, 10, 100];
+        assert!(validate_rollout(&rollout).is_err());
+
+        rollout.spec.waves = vec![10, 50];
+        assert!(validate_rollout(&rollout).is_err());
+
+        rollout.spec.waves = vec![100];
+        assert!(validate_rollout(&rollout).is_ok());
+    }
+
+    #[test]
+    fn test_build_status_in_progress() {
+        let rollout = create_test_rollout();
+        let status = build_status(&rollout, &snapshot(RolloutPhase::Observing));
+
+        assert_eq!(status.rollout_id.as_deref(), Some("rollout-1"));
+        assert_eq!(status.current_wave, 2);
+        assert_eq!(status.total_waves, 3);
+        assert_eq!(status.failed_workers, 1);
+        assert_eq!(status.conditions[0].status, "False");
+        assert_eq!(status.conditions[1].status, "True");
+    }
+
+    #[test]
+    fn test_build_status_verifier_rejected() {
+        let rollout = create_test_rollout();
+        let mut snapshot = snapshot(RolloutPhase::Rolling);
+        assert_eq!(
+            build_status(&rollout, &snapshot).conditions[2].status,
+            "True"
+        );
+
+        snapshot.load_failures = vec![LoadFailure {
+            worker_id: "worker-1".to_string(),
+            program: "xdp_filter".to_string(),
+            stage: "verifier".to_string(),
+            summary: "R2 invalid mem access 'scalar'".to_string(),
+        }];
+        let status = build_status(&rollout, &snapshot);
+
+        assert_eq!(status.conditions[2].condition_type, "Loaded");
+        assert_eq!(status.conditions[2].status, "False");
+        assert_eq!(status.conditions[2].reason, "VerifierRejected");
+        assert!(status.conditions[2].message.contains("worker-1"));
+        assert!(status.conditions[2].message.contains("invalid mem access"));
+    }
+
+    #[test]
+    fn test_build_status_rolled_back() {
+        let rollout = create_test_rollout();
+        let status = build_status(&rollout, &snapshot(RolloutPhase::RolledBack));
+
+        assert_eq!(status.phase, RolloutPhase::RolledBack);
+        assert_eq!(status.conditions[0].reason, "RolledBack");
+        assert_eq!(status.conditions[1].status, "False");
+    }
+}
@@ -0,0 +1,213 @@
+//! Protected Port Discovery Controller
+//!
+//! This controller keeps the protected port maps of the workers in sync with
+//! the Services labeled `pistonprotection.io/protect=true`:
+//! - Watching labeled Services and their Endpoints, which carry the same labels
+//! - Planning the ports of each worker from the Services it receives traffic for
+//! - Pushing changed port sets to the workers, and all of them periodically so
+//!   restarted workers recover theirs
+
+use crate::crd::PROTECT_SELECTOR;
+use crate::error::{Error, Result};
+use crate::protected_ports::{ProtectedPorts, ProtectedService, plan_protected_ports};
+use crate::worker::WorkerManager;
+
+use futures::{StreamExt, stream};
+use k8s_openapi::api::core::v1::{Endpoints, Service};
+use kube::{
+    Client, ResourceExt,
+    api::{Api, ListParams},
+    runtime::watcher,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+/// Delay between a change and the sync, so bursts of changes are pushed once
+const SYNC_DEBOUNCE: Duration = Duration::from_secs(5);
+
+/// Interval of syncs without changes, to follow worker pods coming and going
+const RESYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval of pushes to every worker, changed or not
+const FULL_PUSH_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Context of the protected port discovery
+pub struct Context {
+    /// Kubernetes client
+    pub client: Client,
+    /// Worker manager
+    pub worker_manager: WorkerManager,
+    /// Namespace to watch (None for all namespaces)
+    pub namespace: Option<String>,
+    /// Ports last pushed to each worker
+    pushed: Mutex<HashMap<String, ProtectedPorts>>,
+}
+
+impl Context {
+    /// Create a new context
+    pub fn new(client: Client, worker_manager: WorkerManager, namespace: Option<String>) -> Self {
+        Self {
+            client,
+            worker_manager,
+            namespace,
+            pushed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn api<K>(&self) -> Api<K>
+    where
+        K: kube::Resource<Scope = k8s_openapi::NamespaceResourceScope>,
+        <K as kube::Resource>::DynamicType: Default,
+    {
+        match &self.namespace {
+            Some(ns) => Api::namespaced(self.client.clone(), ns),
+            None => Api::all(self.client.clone()),
+        }
+    }
+}
+
+/// Watch labeled Services and Endpoints and keep the workers in sync
+pub async fn run(ctx: Arc<Context>) {
+    let config = watcher::Config::default().labels(PROTECT_SELECTOR);
+    let services = watcher(ctx.api::<Service>(), config.clone()).map(|event| event.map(|_| ()));
+    let endpoints = watcher(ctx.api::<Endpoints>(), config).map(|event| event.map(|_| ()));
+    let mut changes = stream::select(services, endpoints);
+
+    let mut tick = tokio::time::interval(SYNC_DEBOUNCE);
+    let mut dirty = true;
+    let mut last_sync = Instant::now();
+    let mut last_full_push = Instant::now();
+
+    info!("Starting protected port discovery");
+
+    loop {
+        tokio::select! {
+            change = changes.next() => match change {
+                Some(Ok(())) => dirty = true,
+                Some(Err(e)) => warn!("Protected port watch error: {}", e),
+                None => break,
+            },
+            _ = tick.tick() => {
+                if !dirty && last_sync.elapsed() < RESYNC_INTERVAL {
+                    continue;
+                }
+                let force = last_full_push.elapsed() >= FULL_PUSH_INTERVAL;
+                match sync(&ctx, force).await {
+                    Ok(()) => {
+                        dirty = false;
+                        if force {
+                            last_full_push = Instant::now();
+                        }
+                    }
+                    Err(e) => warn!("Protected port sync failed: {}", e),
+                }
+                last_sync = Instant::now();
+            }
+        }
+    }
+}
+
+/// Push the planned protected ports to the workers
+///
+/// Only workers whose ports changed since the last push are updated, unless
+/// `force` is set.
+pub async fn sync(ctx: &Context, force: bool) -> Result<()> {
+    let params = ListParams::default().labels(PROTECT_SELECTOR);
+    let services = ctx
+        .api::<Service>()
+        .list(&params)
+        .await
+        .map_err(Error::KubeError)?;
+    let mut endpoints: HashMap<(String, String), Endpoints> = ctx
+        .api::<Endpoints>()
+        .list(&params)
+        .await
+        .map_err(Error::KubeError)?
+        .items
+        .into_iter()
+        .map(|endpoints| {
+            let key = (
+                endpoints.namespace().unwrap_or_default(),
+                endpoints.name_any(),
+            );
+            (key, endpoints)
+        })
+        .collect();
+
+    let services: Vec<ProtectedService> = services
+        .items
+        .into_iter()
+        .map(|service| {
+            let key = (service.namespace().unwrap_or_default(), service.name_any());
+            ProtectedService {
+                endpoints: endpoints.remove(&key),
+                service,
+            }
+        })
+        .collect();
+
+    let workers = ctx.worker_manager.discover_workers().await?;
+    let plan = plan_protected_ports(&services, &workers);
+
+    let mut pushed = ctx.pushed.lock().await;
+    let mut failed = 0;
+    pushed.retain(|name, _| plan.contains_key(name));
+
+    for worker in workers.iter().filter(|worker| worker.ready) {
+        let Some(mut ports) = plan.get(&worker.name).cloned() else {
+            continue;
+        };
+        if ports.truncate() {
+            warn!(
+                "Worker {} receives more protected ports than it can hold, keeping the lowest",
+                worker.name
+            );
+        }
+        if !force && pushed.get(&worker.name) == Some(&ports) {
+            continue;
+        }
+
+        match ctx
+            .worker_manager
+            .set_protected_ports_on_worker(worker, &ports)
+            .await
+        {
+            Ok(()) => {
+                debug!(
+                    "Pushed {} TCP and {} UDP protected ports to worker {}",
+                    ports.tcp.len(),
+                    ports.udp.len(),
+                    worker.name
+                );
+                pushed.insert(worker.name.clone(), ports);
+            }
+            Err(e) => {
+                failed += 1;
+                pushed.remove(&worker.name);
+                warn!(
+                    "Failed to push protected ports to worker {}: {}",
+                    worker.name, e
+                );
+            }
+        }
+    }
+
+    if failed > 0 {
+        // Leaves the state dirty, so the next sync retries them
+        return Err(Error::ReconciliationFailed(format!(
+            "protected ports not pushed to {} workers",
+            failed
+        )));
+    }
+
+    info!(
+        "Synced protected ports of {} services to {} workers",
+        services.len(),
+        plan.len()
+    );
+
+    Ok(())
+}
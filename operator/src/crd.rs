@@ -4,6 +4,7 @@
 //! - DDoSProtection: Main protection configuration for backends
 //! - FilterRule: Custom filtering rules
 //! - Backend: Backend service definitions (optional)
+//! - MemberCluster: Member clusters of a federation
 
 use kube::CustomResource;
 use schemars::JsonSchema;
@@ -361,6 +362,10 @@ pub struct DDoSProtectionStatus {
     /// Current protection level (may differ from spec during escalation)
     #[serde(default)]
     pub current_protection_level: Option<u8>,
+
+    /// Status of the copies in member clusters (federated resources only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clusters: Vec<ClusterStatus>,
 }
 
 /// Phase of the DDoSProtection resource
@@ -721,6 +726,10 @@ pub struct FilterRuleStatus {
     /// Status conditions
     #[serde(default)]
     pub conditions: Vec<Condition>,
+
+    /// Status of the copies in member clusters (federated resources only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clusters: Vec<ClusterStatus>,
 }
 
 // ============================================================================
@@ -1194,6 +1203,392 @@ pub struct IPBlocklistStatus {
     pub conditions: Vec<Condition>,
 }
 
+// ============================================================================
+// ProgramRollout CRD
+// ============================================================================
+
+/// ProgramRollout Custom Resource Definition
+///
+/// Rolls a new eBPF program version out to the workers in waves, rolling
+/// back automatically if workers fail to load it or drop noticeably more
+/// traffic.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(
+    group = "pistonprotection.io",
+    version = "v1alpha1",
+    kind = "ProgramRollout",
+    namespaced,
+    status = "ProgramRolloutStatus",
+    shortname = "prollout",
+    printcolumn = r#"{"name":"Program", "type":"string", "jsonPath":".spec.program"}"#,
+    printcolumn = r#"{"name":"Version", "type":"string", "jsonPath":".spec.version"}"#,
+    printcolumn = r#"{"name":"Phase", "type":"string", "jsonPath":".status.phase"}"#,
+    printcolumn = r#"{"name":"Wave", "type":"integer", "jsonPath":".status.currentWave"}"#,
+    printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramRolloutSpec {
+    /// Name of the eBPF program (e.g. xdp_filter)
+    pub program: String,
+
+    /// Version to roll out; workers load `<program>-<version>.o`
+    pub version: String,
+
+    /// Hex-encoded SHA-256 of the program object
+    pub digest: String,
+
+    /// Program variant the object is built for (full or compat); only workers
+    /// running that variant take part. Defaults to full.
+    #[serde(default)]
+    pub variant: Option<String>,
+
+    /// Cumulative percentage of workers updated by each wave; the last must be 100
+    #[serde(default = "default_rollout_waves")]
+    pub waves: Vec<u32>,
+
+    /// How long to watch each wave before starting the next (in seconds)
+    #[serde(default = "default_observe_seconds")]
+    pub observe_seconds: u32,
+
+    /// Allowed increase of the drop rate on updated workers (0.05 = 5 points)
+    #[serde(default = "default_max_drop_rate_increase")]
+    pub max_drop_rate_increase: f64,
+
+    /// Workers that may fail to load the new version before rolling back
+    #[serde(default)]
+    pub max_failed_workers: u32,
+
+    /// Stop the rollout
+    #[serde(default)]
+    pub abort: bool,
+
+    /// Send updated workers back to their previous version when aborting
+    #[serde(default = "default_true")]
+    pub rollback_on_abort: bool,
+}
+
+fn default_rollout_waves() -> Vec<u32> {
+    vec![10, 50, 100]
+}
+
+fn default_observe_seconds() -> u32 {
+    300 // 5 minutes
+}
+
+fn default_max_drop_rate_increase() -> f64 {
+    0.05
+}
+
+/// Phase of a program rollout
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, JsonSchema, PartialEq, Eq)]
+pub enum RolloutPhase {
+    /// Not yet submitted to the control plane
+    #[default]
+    Pending,
+    /// Workers of the current wave are loading the new version
+    Rolling,
+    /// Watching the drop rate of the current wave
+    Observing,
+    /// Every worker runs the new version
+    Completed,
+    /// Workers were sent back to their previous version
+    RolledBack,
+    /// Stopped by the user
+    Aborted,
+}
+
+impl RolloutPhase {
+    /// Whether the rollout can still change worker versions
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            RolloutPhase::Pending | RolloutPhase::Rolling | RolloutPhase::Observing
+        )
+    }
+}
+
+impl std::fmt::Display for RolloutPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RolloutPhase::Pending => write!(f, "Pending"),
+            RolloutPhase::Rolling => write!(f, "Rolling"),
+            RolloutPhase::Observing => write!(f, "Observing"),
+            RolloutPhase::Completed => write!(f, "Completed"),
+            RolloutPhase::RolledBack => write!(f, "RolledBack"),
+            RolloutPhase::Aborted => write!(f, "Aborted"),
+        }
+    }
+}
+
+/// Status of the ProgramRollout resource
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramRolloutStatus {
+    /// Rollout id in the control plane
+    #[serde(default)]
+    pub rollout_id: Option<String>,
+
+    /// Current phase
+    #[serde(default)]
+    pub phase: RolloutPhase,
+
+    /// Wave in progress (1-based, 0 = not started)
+    #[serde(default)]
+    pub current_wave: u32,
+
+    /// Number of waves
+    #[serde(default)]
+    pub total_waves: u32,
+
+    /// Workers running the new version
+    #[serde(default)]
+    pub updated_workers: u32,
+
+    /// Workers that failed to load the new version
+    #[serde(default)]
+    pub failed_workers: u32,
+
+    /// Human-readable progress message
+    #[serde(default)]
+    pub message: Option<String>,
+
+    /// Last time the status was refreshed
+    #[serde(default)]
+    pub last_updated: Option<String>,
+
+    /// Observed generation
+    #[serde(default)]
+    pub observed_generation: Option<i64>,
+
+    /// Status conditions
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+// ============================================================================
+// MemberCluster CRD
+// ============================================================================
+
+/// MemberCluster Custom Resource Definition
+///
+/// Registers a member cluster with the central operator of a federation.
+/// DDoSProtection and FilterRule resources labeled
+/// `pistonprotection.io/federated=true` are propagated to the member
+/// clusters they are placed on, and the status of their copies is
+/// aggregated back into the originals.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
+#[kube(
+    group = "pistonprotection.io",
+    version = "v1alpha1",
+    kind = "MemberCluster",
+    namespaced,
+    status = "MemberClusterStatus",
+    shortname = "mcluster",
+    printcolumn = r#"{"name":"Region", "type":"string", "jsonPath":".spec.region"}"#,
+    printcolumn = r#"{"name":"Mode", "type":"string", "jsonPath":".spec.mode"}"#,
+    printcolumn = r#"{"name":"Phase", "type":"string", "jsonPath":".status.phase"}"#,
+    printcolumn = r#"{"name":"Synced", "type":"integer", "jsonPath":".status.syncedResources"}"#,
+    printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#
+)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberClusterSpec {
+    /// Region served by the cluster, usable for placement
+    #[serde(default)]
+    pub region: Option<String>,
+
+    /// How resources reach the cluster
+    #[serde(default)]
+    pub mode: FederationMode,
+
+    /// Secret in the namespace of this resource holding a kubeconfig for
+    /// the cluster (push mode only)
+    #[serde(default)]
+    pub kubeconfig_secret: Option<SecretKeyRef>,
+
+    /// Stop propagating to the cluster, leaving its copies in place
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// How resources reach a member cluster
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, JsonSchema, PartialEq, Eq)]
+pub enum FederationMode {
+    /// The central operator writes to the member cluster
+    #[default]
+    Push,
+    /// The member cluster's operator reads from the central cluster and
+    /// reports back
+    Pull,
+}
+
+impl std::fmt::Display for FederationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FederationMode::Push => write!(f, "Push"),
+            FederationMode::Pull => write!(f, "Pull"),
+        }
+    }
+}
+
+/// Reference to a key of a Secret
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretKeyRef {
+    /// Secret name
+    pub name: String,
+
+    /// Key within the Secret
+    #[serde(default = "default_kubeconfig_key")]
+    pub key: String,
+}
+
+fn default_kubeconfig_key() -> String {
+    "kubeconfig".to_string()
+}
+
+/// Phase of a member cluster
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, JsonSchema, PartialEq, Eq)]
+pub enum MemberClusterPhase {
+    /// Not synced yet
+    #[default]
+    Pending,
+    /// Every placed resource is synced
+    Ready,
+    /// Some placed resources failed to sync
+    Degraded,
+    /// The cluster cannot be reached, or its pull agent stopped reporting
+    Unreachable,
+    /// Propagation is paused
+    Paused,
+}
+
+impl std::fmt::Display for MemberClusterPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemberClusterPhase::Pending => write!(f, "Pending"),
+            MemberClusterPhase::Ready => write!(f, "Ready"),
+            MemberClusterPhase::Degraded => write!(f, "Degraded"),
+            MemberClusterPhase::Unreachable => write!(f, "Unreachable"),
+            MemberClusterPhase::Paused => write!(f, "Paused"),
+        }
+    }
+}
+
+/// Status of the MemberCluster resource
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberClusterStatus {
+    /// Current phase
+    #[serde(default)]
+    pub phase: MemberClusterPhase,
+
+    /// Last successful sync
+    #[serde(default)]
+    pub last_sync: Option<String>,
+
+    /// Resources synced to the cluster
+    #[serde(default)]
+    pub synced_resources: u32,
+
+    /// Resources that failed to sync
+    #[serde(default)]
+    pub failed_resources: u32,
+
+    /// Status of every resource placed on the cluster
+    #[serde(default)]
+    pub resources: Vec<FederatedResourceStatus>,
+
+    /// Last error message (if any)
+    #[serde(default)]
+    pub last_error: Option<String>,
+
+    /// Observed generation
+    #[serde(default)]
+    pub observed_generation: Option<i64>,
+
+    /// Status conditions
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+/// Status of a propagated resource in a member cluster
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FederatedResourceStatus {
+    /// Kind of the resource (DDoSProtection or FilterRule)
+    pub kind: String,
+
+    /// Namespace of the resource
+    pub namespace: String,
+
+    /// Name of the resource
+    pub name: String,
+
+    /// Whether the copy matches the original
+    #[serde(default)]
+    pub synced: bool,
+
+    /// Phase of the copy (DDoSProtection phase, or Active/Inactive for rules)
+    #[serde(default)]
+    pub phase: Option<String>,
+
+    /// Ready worker pods of the copy (DDoSProtection only)
+    #[serde(default)]
+    pub ready_workers: Option<i32>,
+
+    /// Whether the copy is under attack (DDoSProtection only)
+    #[serde(default)]
+    pub under_attack: bool,
+
+    /// Matches of the copy (FilterRule only)
+    #[serde(default)]
+    pub match_count: Option<u64>,
+
+    /// Sync error or other detail
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Status of a federated resource's copy in one member cluster
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterStatus {
+    /// MemberCluster name
+    pub cluster: String,
+
+    /// Region of the cluster
+    #[serde(default)]
+    pub region: Option<String>,
+
+    /// Whether the copy matches the original
+    #[serde(default)]
+    pub synced: bool,
+
+    /// Phase of the copy
+    #[serde(default)]
+    pub phase: Option<String>,
+
+    /// Ready worker pods of the copy (DDoSProtection only)
+    #[serde(default)]
+    pub ready_workers: Option<i32>,
+
+    /// Whether the copy is under attack (DDoSProtection only)
+    #[serde(default)]
+    pub under_attack: bool,
+
+    /// Matches of the copy (FilterRule only)
+    #[serde(default)]
+    pub match_count: Option<u64>,
+
+    /// Sync error or other detail
+    #[serde(default)]
+    pub message: Option<String>,
+
+    /// Last successful sync of the cluster
+    #[serde(default)]
+    pub last_sync: Option<String>,
+}
+
 // ============================================================================
 // Constants
 // ============================================================================
@@ -1210,6 +1605,22 @@ pub const COMPONENT_LABEL: &str = "app.kubernetes.io/component";
 pub const INSTANCE_LABEL: &str = "app.kubernetes.io/instance";
 pub const NAME_LABEL: &str = "app.kubernetes.io/name";
 
+/// Label selecting Services whose ports workers protect
+pub const PROTECT_LABEL: &str = "pistonprotection.io/protect";
+pub const PROTECT_SELECTOR: &str = "pistonprotection.io/protect=true";
+
+/// Label selecting DDoSProtection and FilterRule resources to federate
+pub const FEDERATED_LABEL: &str = "pistonprotection.io/federated";
+pub const FEDERATED_SELECTOR: &str = "pistonprotection.io/federated=true";
+
+/// Annotation restricting a federated resource to some member clusters
+/// (comma-separated cluster names or regions; all clusters when absent)
+pub const FEDERATION_PLACEMENT_ANNOTATION: &str = "pistonprotection.io/federation-placement";
+
+/// Label marking the copies of federated resources in member clusters
+pub const FEDERATED_COPY_LABEL: &str = "pistonprotection.io/federated-copy";
+pub const FEDERATED_COPY_SELECTOR: &str = "pistonprotection.io/federated-copy=true";
+
 /// Worker image
 pub const WORKER_IMAGE: &str = "pistonprotection/worker:latest";
 
@@ -0,0 +1,369 @@
+//! Multi-Cluster Federation
+//!
+//! Large deployments run an edge cluster per region. In federation mode a
+//! central operator propagates the DDoSProtection and FilterRule resources
+//! labeled `pistonprotection.io/federated=true` to the member clusters
+//! registered as MemberCluster resources, either by writing to them (push)
+//! or by letting a pull agent in the member cluster read them (pull). The
+//! status of the copies is reported in the MemberCluster status and
+//! aggregated back into the originals.
+//!
+//! This module holds the parts independent of the Kubernetes API: placement,
+//! building the copies, summarizing their status and aggregating it.
+
+use crate::crd::{
+    ClusterStatus, DDoSProtection, FEDERATED_COPY_LABEL, FEDERATED_LABEL,
+    FEDERATION_PLACEMENT_ANNOTATION, FederatedResourceStatus, FilterRule, MANAGED_BY_LABEL,
+    MANAGED_BY_VALUE, MemberCluster, MemberClusterPhase,
+};
+use crate::error::{Error, Result};
+
+use kube::{Resource, ResourceExt};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Field manager of the copies in member clusters
+pub const FIELD_MANAGER: &str = "pistonprotection-federation";
+
+/// Interval between syncs of a member cluster
+pub const SYNC_INTERVAL_SECS: u64 = 30;
+
+/// Age after which a pull cluster that stopped reporting is unreachable
+pub const PULL_STALE_AFTER_SECS: i64 = (SYNC_INTERVAL_SECS * 4) as i64;
+
+/// Annotation added by `kubectl apply`, not copied to member clusters
+const LAST_APPLIED_ANNOTATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Kind, namespace and name of a federated resource
+pub type ResourceKey = (String, String, String);
+
+/// Whether a federated resource is placed on a member cluster
+///
+/// Without a placement annotation resources go to every member cluster;
+/// otherwise the annotation lists the cluster names or regions to use.
+pub fn is_placed(
+    annotations: &BTreeMap<String, String>,
+    cluster: &str,
+    region: Option<&str>,
+) -> bool {
+    let Some(placement) = annotations.get(FEDERATION_PLACEMENT_ANNOTATION) else {
+        return true;
+    };
+    placement
+        .split(',')
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+        .any(|target| target == cluster || Some(target) == region)
+}
+
+/// Build the copy of a federated resource to apply in a member cluster
+///
+/// The copy keeps the name, namespace, spec, labels and annotations of the
+/// original, without the federation label and placement, and is labeled as
+/// a copy so it is cleaned up once the original is gone or moved elsewhere.
+pub fn federated_copy<K>(source: &K) -> Result<serde_json::Value>
+where
+    K: Resource<DynamicType = ()> + Serialize,
+{
+    let mut labels = source.labels().clone();
+    labels.remove(FEDERATED_LABEL);
+    labels.insert(FEDERATED_COPY_LABEL.to_string(), "true".to_string());
+    labels.insert(MANAGED_BY_LABEL.to_string(), MANAGED_BY_VALUE.to_string());
+
+    let mut annotations = source.annotations().clone();
+    annotations.remove(FEDERATION_PLACEMENT_ANNOTATION);
+    annotations.remove(LAST_APPLIED_ANNOTATION);
+
+    let spec = serde_json::to_value(source)?
+        .get("spec")
+        .cloned()
+        .ok_or_else(|| Error::InvalidResource(format!("{} has no spec", source.name_any())))?;
+
+    Ok(serde_json::json!({
+        "apiVersion": K::api_version(&()),
+        "kind": K::kind(&()),
+        "metadata": {
+            "name": source.name_any(),
+            "namespace": source.namespace(),
+            "labels": labels,
+            "annotations": annotations,
+        },
+        "spec": spec,
+    }))
+}
+
+/// Status of a DDoSProtection copy in a member cluster
+pub fn ddos_protection_status(copy: &DDoSProtection) -> FederatedResourceStatus {
+    let status = copy.status.as_ref();
+    FederatedResourceStatus {
+        kind: DDoSProtection::kind(&()).to_string(),
+        namespace: copy.namespace().unwrap_or_default(),
+        name: copy.name_any(),
+        synced: true,
+        phase: status.map(|status| status.phase.to_string()),
+        ready_workers: status.map(|status| status.ready_workers),
+        under_attack: status
+            .and_then(|status| status.metrics.as_ref())
+            .is_some_and(|metrics| metrics.under_attack),
+        match_count: None,
+        message: status.and_then(|status| status.last_error.clone()),
+    }
+}
+
+/// Status of a FilterRule copy in a member cluster
+pub fn filter_rule_status(copy: &FilterRule) -> FederatedResourceStatus {
+    let status = copy.status.as_ref();
+    FederatedResourceStatus {
+        kind: FilterRule::kind(&()).to_string(),
+        namespace: copy.namespace().unwrap_or_default(),
+        name: copy.name_any(),
+        synced: true,
+        phase: status.map(|status| {
+            if status.active {
+                "Active".to_string()
+            } else {
+                "Inactive".to_string()
+            }
+        }),
+        ready_workers: None,
+        under_attack: false,
+        match_count: status.map(|status| status.match_count),
+        message: status.and_then(|status| status.last_error.clone()),
+    }
+}
+
+/// Phase of a member cluster from the status of its resources
+pub fn sync_phase(resources: &[FederatedResourceStatus]) -> MemberClusterPhase {
+    if resources.iter().all(|resource| resource.synced) {
+        MemberClusterPhase::Ready
+    } else {
+        MemberClusterPhase::Degraded
+    }
+}
+
+/// Whether a pull cluster last reported longer ago than `PULL_STALE_AFTER_SECS`
+pub fn is_stale(last_sync: Option<&str>, now: chrono::DateTime<chrono::Utc>) -> bool {
+    let Some(last_sync) =
+        last_sync.and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+    else {
+        return true;
+    };
+    (now - last_sync.with_timezone(&chrono::Utc)).num_seconds() > PULL_STALE_AFTER_SECS
+}
+
+/// Status of the copies of every federated resource, by resource
+///
+/// Clusters are listed in name order. Copies in unreachable clusters are
+/// reported as not synced, with their last known state.
+pub fn aggregate_cluster_statuses(
+    clusters: &[MemberCluster],
+) -> BTreeMap<ResourceKey, Vec<ClusterStatus>> {
+    let mut ordered: Vec<&MemberCluster> = clusters.iter().collect();
+    ordered.sort_by_key(|cluster| cluster.name_any());
+
+    let mut aggregated: BTreeMap<ResourceKey, Vec<ClusterStatus>> = BTreeMap::new();
+    for cluster in ordered {
+        let Some(status) = cluster.status.as_ref() else {
+            continue;
+        };
+        let unreachable = status.phase == MemberClusterPhase::Unreachable;
+
+        for resource in &status.resources {
+            let key = (
+                resource.kind.clone(),
+                resource.namespace.clone(),
+                resource.name.clone(),
+            );
+            aggregated.entry(key).or_default().push(ClusterStatus {
+                cluster: cluster.name_any(),
+                region: cluster.spec.region.clone(),
+                synced: resource.synced && !unreachable,
+                phase: resource.phase.clone(),
+                ready_workers: resource.ready_workers,
+                under_attack: resource.under_attack,
+                match_count: resource.match_count,
+                message: if unreachable {
+                    Some("Cluster unreachable".to_string())
+                } else {
+                    resource.message.clone()
+                },
+                last_sync: status.last_sync.clone(),
+            });
+        }
+    }
+    aggregated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crd::{
+        DDoSProtectionSpec, DDoSProtectionStatus, FederationMode, MemberClusterSpec,
+        MemberClusterStatus, MetricsSummary, Phase,
+    };
+
+    fn member(name: &str, region: &str, status: MemberClusterStatus) -> MemberCluster {
+        let mut cluster = MemberCluster::new(
+            name,
+            MemberClusterSpec {
+                region: Some(region.to_string()),
+                mode: FederationMode::Push,
+                kubeconfig_secret: None,
+                paused: false,
+            },
+        );
+        cluster.status = Some(status);
+        cluster
+    }
+
+    fn resource(name: &str, synced: bool) -> FederatedResourceStatus {
+        FederatedResourceStatus {
+            kind: "DDoSProtection".to_string(),
+            namespace: "games".to_string(),
+            name: name.to_string(),
+            synced,
+            phase: Some("Active".to_string()),
+            ready_workers: Some(2),
+            ..Default::default()
+        }
+    }
+
+    fn protection() -> DDoSProtection {
+        let spec: DDoSProtectionSpec =
+            serde_json::from_value(serde_json::json!({ "backends": [] })).unwrap();
+        let mut ddos = DDoSProtection::new("lobby", spec);
+        ddos.metadata.namespace = Some("games".to_string());
+        ddos.metadata.labels = Some(BTreeMap::from([
+            (FEDERATED_LABEL.to_string(), "true".to_string()),
+            ("team".to_string(), "edge".to_string()),
+        ]));
+        ddos.metadata.annotations = Some(BTreeMap::from([(
+            FEDERATION_PLACEMENT_ANNOTATION.to_string(),
+            "eu-west".to_string(),
+        )]));
+        ddos.metadata.resource_version = Some("42".to_string());
+        ddos
+    }
+
+    #[test]
+    fn test_is_placed() {
+        let mut annotations = BTreeMap::new();
+        assert!(is_placed(&annotations, "edge-1", None));
+
+        annotations.insert(
+            FEDERATION_PLACEMENT_ANNOTATION.to_string(),
+            "edge-1, us-east".to_string(),
+        );
+        assert!(is_placed(&annotations, "edge-1", Some("eu-west")));
+        assert!(is_placed(&annotations, "edge-2", Some("us-east")));
+        assert!(!is_placed(&annotations, "edge-3", Some("eu-west")));
+        assert!(!is_placed(&annotations, "edge-3", None));
+    }
+
+    #[test]
+    fn test_federated_copy() {
+        let copy = federated_copy(&protection()).unwrap();
+
+        assert_eq!(copy["apiVersion"], "pistonprotection.io/v1alpha1");
+        assert_eq!(copy["kind"], "DDoSProtection");
+        assert_eq!(copy["metadata"]["name"], "lobby");
+        assert_eq!(copy["metadata"]["namespace"], "games");
+        assert_eq!(copy["metadata"]["labels"][FEDERATED_COPY_LABEL], "true");
+        assert_eq!(copy["metadata"]["labels"]["team"], "edge");
+        assert!(copy["metadata"]["labels"].get(FEDERATED_LABEL).is_none());
+        assert!(
+            copy["metadata"]["annotations"]
+                .get(FEDERATION_PLACEMENT_ANNOTATION)
+                .is_none()
+        );
+        assert!(copy["metadata"].get("resourceVersion").is_none());
+        assert_eq!(copy["spec"]["protectionLevel"], 3);
+        assert!(copy.get("status").is_none());
+    }
+
+    #[test]
+    fn test_ddos_protection_status() {
+        let mut ddos = protection();
+        assert_eq!(ddos_protection_status(&ddos).phase, None);
+
+        ddos.status = Some(DDoSProtectionStatus {
+            phase: Phase::Active,
+            ready_workers: 3,
+            metrics: Some(MetricsSummary {
+                total_requests: 0,
+                blocked_requests: 0,
+                avg_latency_ms: 0.0,
+                requests_per_second: None,
+                under_attack: true,
+            }),
+            ..Default::default()
+        });
+        let status = ddos_protection_status(&ddos);
+        assert!(status.synced);
+        assert_eq!(status.phase.as_deref(), Some("Active"));
+        assert_eq!(status.ready_workers, Some(3));
+        assert!(status.under_attack);
+    }
+
+    #[test]
+    fn test_sync_phase() {
+        assert_eq!(sync_phase(&[]), MemberClusterPhase::Ready);
+        assert_eq!(
+            sync_phase(&[resource("a", true), resource("b", false)]),
+            MemberClusterPhase::Degraded
+        );
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let now = chrono::Utc::now();
+        let recent = (now - chrono::Duration::seconds(10)).to_rfc3339();
+        let old = (now - chrono::Duration::seconds(PULL_STALE_AFTER_SECS + 1)).to_rfc3339();
+
+        assert!(!is_stale(Some(&recent), now));
+        assert!(is_stale(Some(&old), now));
+        assert!(is_stale(None, now));
+        assert!(is_stale(Some("not a time"), now));
+    }
+
+    #[test]
+    fn test_aggregate_cluster_statuses() {
+        let clusters = vec![
+            member(
+                "edge-2",
+                "us-east",
+                MemberClusterStatus {
+                    phase: MemberClusterPhase::Unreachable,
+                    resources: vec![resource("lobby", true)],
+                    ..Default::default()
+                },
+            ),
+            member(
+                "edge-1",
+                "eu-west",
+                MemberClusterStatus {
+                    phase: MemberClusterPhase::Ready,
+                    resources: vec![resource("lobby", true), resource("survival", false)],
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        let aggregated = aggregate_cluster_statuses(&clusters);
+        assert_eq!(aggregated.len(), 2);
+
+        let lobby = &aggregated[&(
+            "DDoSProtection".to_string(),
+            "games".to_string(),
+            "lobby".to_string(),
+        )];
+        assert_eq!(lobby.len(), 2);
+        assert_eq!(lobby[0].cluster, "edge-1");
+        assert_eq!(lobby[0].region.as_deref(), Some("eu-west"));
+        assert!(lobby[0].synced);
+        assert_eq!(lobby[1].cluster, "edge-2");
+        assert!(!lobby[1].synced);
+        assert_eq!(lobby[1].message.as_deref(), Some("Cluster unreachable"));
+        assert_eq!(lobby[1].phase.as_deref(), Some("Active"));
+    }
+}
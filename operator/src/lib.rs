@@ -6,7 +6,9 @@ pub mod client;
 pub mod controllers;
 pub mod crd;
 pub mod error;
+pub mod federation;
 pub mod metrics;
+pub mod protected_ports;
 pub mod worker;
 
 #[cfg(test)]
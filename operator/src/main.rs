@@ -6,6 +6,9 @@
 //! - FilterRule CRD for custom filtering rules
 //! - Backend CRD for backend service definitions
 //! - IPBlocklist CRD for IP blocklist management
+//! - ProgramRollout CRD for staged eBPF program rollouts
+//! - MemberCluster CRD for federating resources to member clusters
+//! - Protected port discovery from Services labeled for protection
 //!
 //! The operator synchronizes configuration with the PistonProtection gateway
 //! service via gRPC and manages worker deployments for traffic filtering.
@@ -25,7 +28,9 @@ use tracing::{error, info, warn};
 
 use pistonprotection_operator::client::{GatewayClient, GatewayClientConfig};
 use pistonprotection_operator::controllers;
-use pistonprotection_operator::crd::{Backend, DDoSProtection, FilterRule, IPBlocklist};
+use pistonprotection_operator::crd::{
+    Backend, DDoSProtection, FilterRule, IPBlocklist, MemberCluster, ProgramRollout,
+};
 use pistonprotection_operator::metrics::Metrics;
 use pistonprotection_operator::worker::WorkerManager;
 
@@ -77,6 +82,18 @@ struct OperatorConfig {
     enable_backend_controller: bool,
     /// Enable IPBlocklist controller
     enable_ipblocklist_controller: bool,
+    /// Enable ProgramRollout controller
+    enable_program_rollout_controller: bool,
+    /// Enable protected port discovery from labeled Services
+    enable_protected_port_discovery: bool,
+    /// Enable the MemberCluster controller (central cluster of a federation)
+    enable_federation_controller: bool,
+    /// Kubeconfig of the central cluster, for the pull agent of a member cluster
+    federation_central_kubeconfig: Option<String>,
+    /// Name of this cluster's MemberCluster in the central cluster
+    federation_cluster_name: Option<String>,
+    /// Namespace of this cluster's MemberCluster in the central cluster
+    federation_cluster_namespace: String,
     /// Worker namespace for pod discovery
     worker_namespace: String,
     /// Worker pod selector
@@ -108,6 +125,19 @@ impl Default for OperatorConfig {
             enable_ipblocklist_controller: std::env::var("ENABLE_IPBLOCKLIST_CONTROLLER")
                 .map(|v| v.to_lowercase() == "true")
                 .unwrap_or(true),
+            enable_program_rollout_controller: std::env::var("ENABLE_PROGRAM_ROLLOUT_CONTROLLER")
+                .map(|v| v.to_lowercase() == "true")
+                .unwrap_or(true),
+            enable_protected_port_discovery: std::env::var("ENABLE_PROTECTED_PORT_DISCOVERY")
+                .map(|v| v.to_lowercase() == "true")
+                .unwrap_or(true),
+            enable_federation_controller: std::env::var("ENABLE_FEDERATION_CONTROLLER")
+                .map(|v| v.to_lowercase() == "true")
+                .unwrap_or(true),
+            federation_central_kubeconfig: std::env::var("FEDERATION_CENTRAL_KUBECONFIG").ok(),
+            federation_cluster_name: std::env::var("FEDERATION_CLUSTER_NAME").ok(),
+            federation_cluster_namespace: std::env::var("FEDERATION_CLUSTER_NAMESPACE")
+                .unwrap_or_else(|_| "pistonprotection-system".to_string()),
             worker_namespace: std::env::var("WORKER_NAMESPACE")
                 .unwrap_or_else(|_| "pistonprotection-system".to_string()),
             worker_selector: std::env::var("WORKER_SELECTOR")
@@ -263,6 +293,51 @@ async fn main() -> Result<()> {
         None
     };
 
+    let program_rollout_controller = if config.enable_program_rollout_controller {
+        Some(start_program_rollout_controller(
+            client.clone(),
+            gateway_client.clone(),
+            metrics.clone(),
+            &config,
+            reporter.clone(),
+        ))
+    } else {
+        None
+    };
+
+    let protected_port_discovery = if config.enable_protected_port_discovery {
+        Some(start_protected_port_discovery(client.clone(), &config))
+    } else {
+        None
+    };
+
+    let federation_controller = if config.enable_federation_controller {
+        Some(start_federation_controller(
+            client.clone(),
+            metrics.clone(),
+            &config,
+        ))
+    } else {
+        None
+    };
+
+    // Run the pull agent when this is a pull member cluster of a federation
+    let federation_pull_agent = match (
+        &config.federation_central_kubeconfig,
+        &config.federation_cluster_name,
+    ) {
+        (Some(kubeconfig), Some(cluster)) => Some(
+            start_federation_pull_agent(
+                client.clone(),
+                kubeconfig,
+                config.federation_cluster_namespace.clone(),
+                cluster.clone(),
+            )
+            .await?,
+        ),
+        _ => None,
+    };
+
     // Mark as ready
     state.ready.store(true, Ordering::SeqCst);
     info!("Operator is ready");
@@ -300,6 +375,46 @@ async fn main() -> Result<()> {
         } => {
             error!("IPBlocklist controller exited unexpectedly");
         }
+        _ = async {
+            if let Some(ctrl) = program_rollout_controller {
+                ctrl.await
+            } else {
+                // Never completes if ProgramRollout controller is disabled
+                std::future::pending::<()>().await
+            }
+        } => {
+            error!("ProgramRollout controller exited unexpectedly");
+        }
+        _ = async {
+            if let Some(discovery) = protected_port_discovery {
+                discovery.await
+            } else {
+                // Never completes if protected port discovery is disabled
+                std::future::pending::<()>().await
+            }
+        } => {
+            error!("Protected port discovery exited unexpectedly");
+        }
+        _ = async {
+            if let Some(ctrl) = federation_controller {
+                ctrl.await
+            } else {
+                // Never completes if the MemberCluster controller is disabled
+                std::future::pending::<()>().await
+            }
+        } => {
+            error!("MemberCluster controller exited unexpectedly");
+        }
+        _ = async {
+            if let Some(agent) = federation_pull_agent {
+                agent.await
+            } else {
+                // Never completes if this is not a pull member cluster
+                std::future::pending::<()>().await
+            }
+        } => {
+            error!("Federation pull agent exited unexpectedly");
+        }
         _ = tokio::signal::ctrl_c() => {
             info!("Received shutdown signal");
         }
@@ -479,6 +594,126 @@ async fn start_ipblocklist_controller(
         .await;
 }
 
+/// Start the ProgramRollout controller
+async fn start_program_rollout_controller(
+    client: Client,
+    gateway_client: Arc<GatewayClient>,
+    metrics: Arc<Metrics>,
+    config: &OperatorConfig,
+    reporter: Reporter,
+) {
+    let api: Api<ProgramRollout> = match &config.namespace {
+        Some(ns) => Api::namespaced(client.clone(), ns),
+        None => Api::all(client.clone()),
+    };
+
+    let ctx = Arc::new(controllers::program_rollout::Context::new(
+        client.clone(),
+        (*gateway_client).clone(),
+        metrics.clone(),
+        reporter,
+    ));
+
+    info!("Starting ProgramRollout controller");
+
+    Controller::new(api, WatcherConfig::default().any_semantic())
+        .shutdown_on_signal()
+        .run(
+            controllers::program_rollout::reconcile,
+            controllers::program_rollout::error_policy,
+            ctx,
+        )
+        .for_each(|result| async {
+            match result {
+                Ok((obj, _action)) => {
+                    info!("Reconciled ProgramRollout: {}", obj.name);
+                }
+                Err(e) => {
+                    error!("Reconciliation error: {:?}", e);
+                }
+            }
+        })
+        .await;
+}
+
+/// Start protected port discovery
+async fn start_protected_port_discovery(client: Client, config: &OperatorConfig) {
+    let worker_manager = WorkerManager::new(
+        client.clone(),
+        config.worker_namespace.clone(),
+        config.worker_selector.clone(),
+        config.worker_grpc_port,
+    );
+
+    let ctx = Arc::new(controllers::protected_ports::Context::new(
+        client,
+        worker_manager,
+        config.namespace.clone(),
+    ));
+
+    controllers::protected_ports::run(ctx).await;
+}
+
+/// Start the MemberCluster controller
+async fn start_federation_controller(
+    client: Client,
+    metrics: Arc<Metrics>,
+    config: &OperatorConfig,
+) {
+    let api: Api<MemberCluster> = match &config.namespace {
+        Some(ns) => Api::namespaced(client.clone(), ns),
+        None => Api::all(client.clone()),
+    };
+
+    let ctx = Arc::new(controllers::federation::Context::new(
+        client.clone(),
+        metrics.clone(),
+        config.namespace.clone(),
+    ));
+
+    info!("Starting MemberCluster controller");
+
+    Controller::new(api, WatcherConfig::default().any_semantic())
+        .shutdown_on_signal()
+        .run(
+            controllers::federation::reconcile,
+            controllers::federation::error_policy,
+            ctx,
+        )
+        .for_each(|result| async {
+            match result {
+                Ok((obj, _action)) => {
+                    info!("Reconciled MemberCluster: {}", obj.name);
+                }
+                Err(e) => {
+                    error!("Reconciliation error: {:?}", e);
+                }
+            }
+        })
+        .await;
+}
+
+/// Connect to the central cluster and prepare the federation pull agent
+async fn start_federation_pull_agent(
+    client: Client,
+    central_kubeconfig: &str,
+    cluster_namespace: String,
+    cluster: String,
+) -> Result<impl std::future::Future<Output = ()>> {
+    let kubeconfig = kube::config::Kubeconfig::read_from(central_kubeconfig)
+        .context("Failed to read the central cluster kubeconfig")?;
+    let central = controllers::federation::client_from_kubeconfig(kubeconfig)
+        .await
+        .context("Failed to create the central cluster client")?;
+
+    Ok(controllers::federation::run_pull_agent(
+        central,
+        client,
+        cluster_namespace,
+        cluster,
+    ))
+}
+
 /// Start the health and metrics HTTP server
 async fn start_health_server(state: Arc<AppState>, config: &OperatorConfig) -> Result<()> {
     let app = Router::new()
@@ -549,6 +784,16 @@ fn print_crd_info() {
     info!("  - {}/{}", FilterRule::group(&()), FilterRule::kind(&()));
     info!("  - {}/{}", Backend::group(&()), Backend::kind(&()));
     info!("  - {}/{}", IPBlocklist::group(&()), IPBlocklist::kind(&()));
+    info!(
+        "  - {}/{}",
+        ProgramRollout::group(&()),
+        ProgramRollout::kind(&())
+    );
+    info!(
+        "  - {}/{}",
+        MemberCluster::group(&()),
+        MemberCluster::kind(&())
+    );
 }
 
 /// Generate CRD YAML manifests (for installation)
@@ -558,10 +803,12 @@ fn generate_crds() -> String {
     let filter_crd = serde_yaml::to_string(&FilterRule::crd()).unwrap();
     let backend_crd = serde_yaml::to_string(&Backend::crd()).unwrap();
     let ipblocklist_crd = serde_yaml::to_string(&IPBlocklist::crd()).unwrap();
+    let program_rollout_crd = serde_yaml::to_string(&ProgramRollout::crd()).unwrap();
+    let member_cluster_crd = serde_yaml::to_string(&MemberCluster::crd()).unwrap();
 
     format!(
-        "---\n{}\n---\n{}\n---\n{}\n---\n{}",
-        ddos_crd, filter_crd, backend_crd, ipblocklist_crd
+        "---\n{}\n---\n{}\n---\n{}\n---\n{}\n---\n{}\n---\n{}",
+        ddos_crd, filter_crd, backend_crd, ipblocklist_crd, program_rollout_crd, member_cluster_crd
     )
 }
 
@@ -576,6 +823,9 @@ mod main_tests {
         assert_eq!(config.concurrency, 4);
         assert!(config.enable_backend_controller);
         assert!(config.enable_ipblocklist_controller);
+        assert!(config.enable_program_rollout_controller);
+        assert!(config.enable_protected_port_discovery);
+        assert!(config.enable_federation_controller);
     }
 
     #[test]
@@ -585,6 +835,7 @@ mod main_tests {
         assert!(crds.contains("FilterRule"));
         assert!(crds.contains("Backend"));
         assert!(crds.contains("IPBlocklist"));
+        assert!(crds.contains("MemberCluster"));
         assert!(crds.contains("pistonprotection.io"));
     }
 
@@ -0,0 +1,328 @@
+//! Protected Port Discovery
+//!
+//! Services labeled `pistonprotection.io/protect=true` have their ports
+//! programmed into the protected port maps of the workers (`TCP_PROTECTED_PORTS`
+//! in xdp_tcp, `PROTECTED_PORTS` in xdp_udp), so they no longer need to be
+//! listed by hand. This module turns Services and their Endpoints into the
+//! ports each worker should protect:
+//! - the Service port and node port of every TCP and UDP port
+//! - for headless Services, which route straight to the pods, the endpoint
+//!   ports instead of the Service port
+//! - Services with `externalTrafficPolicy: Local` only receive traffic on
+//!   nodes running one of their ready endpoints, so only the workers on
+//!   those nodes protect them; other Services are protected everywhere
+
+use crate::worker::WorkerInfo;
+
+use k8s_openapi::api::core::v1::{Endpoints, Service};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Capacity of each protected port map on the workers
+pub const MAX_PROTECTED_PORTS: usize = 1000;
+
+/// Protected destination ports by protocol, as set on a worker
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ProtectedPorts {
+    pub tcp: BTreeSet<u16>,
+    pub udp: BTreeSet<u16>,
+}
+
+impl ProtectedPorts {
+    /// Add a port of the given Kubernetes protocol (TCP when unset); SCTP
+    /// and invalid ports are ignored
+    pub fn insert(&mut self, protocol: Option<&str>, port: i32) {
+        let Ok(port) = u16::try_from(port) else {
+            return;
+        };
+        if port == 0 {
+            return;
+        }
+        match protocol.unwrap_or("TCP") {
+            "TCP" => {
+                self.tcp.insert(port);
+            }
+            "UDP" => {
+                self.udp.insert(port);
+            }
+            _ => {}
+        }
+    }
+
+    /// Add every port of `other`
+    pub fn extend(&mut self, other: &ProtectedPorts) {
+        self.tcp.extend(other.tcp.iter().copied());
+        self.udp.extend(other.udp.iter().copied());
+    }
+
+    /// Keep the lowest `MAX_PROTECTED_PORTS` ports of each protocol
+    ///
+    /// Returns whether ports were dropped.
+    pub fn truncate(&mut self) -> bool {
+        let mut truncated = false;
+        for ports in [&mut self.tcp, &mut self.udp] {
+            if ports.len() > MAX_PROTECTED_PORTS {
+                *ports = ports.iter().copied().take(MAX_PROTECTED_PORTS).collect();
+                truncated = true;
+            }
+        }
+        truncated
+    }
+}
+
+/// A Service labeled for protection and its Endpoints, if any
+#[derive(Clone, Debug)]
+pub struct ProtectedService {
+    pub service: Service,
+    pub endpoints: Option<Endpoints>,
+}
+
+impl ProtectedService {
+    /// Whether the Service routes straight to its pods
+    fn is_headless(&self) -> bool {
+        self.service
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.cluster_ip.as_deref())
+            == Some("None")
+    }
+
+    /// Ports of the Service to protect
+    pub fn ports(&self) -> ProtectedPorts {
+        let mut ports = ProtectedPorts::default();
+
+        if self.is_headless() {
+            for subset in self.endpoint_subsets() {
+                for port in subset.ports.iter().flatten() {
+                    ports.insert(port.protocol.as_deref(), port.port);
+                }
+            }
+            return ports;
+        }
+
+        let service_ports = self
+            .service
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.ports.as_ref());
+        for port in service_ports.into_iter().flatten() {
+            ports.insert(port.protocol.as_deref(), port.port);
+            if let Some(node_port) = port.node_port {
+                ports.insert(port.protocol.as_deref(), node_port);
+            }
+        }
+        ports
+    }
+
+    /// Nodes whose workers receive the Service's traffic, `None` for all
+    pub fn nodes(&self) -> Option<BTreeSet<String>> {
+        let policy = self
+            .service
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.external_traffic_policy.as_deref());
+        if policy != Some("Local") {
+            return None;
+        }
+
+        Some(
+            self.endpoint_subsets()
+                .flat_map(|subset| subset.addresses.iter().flatten())
+                .filter_map(|address| address.node_name.clone())
+                .collect(),
+        )
+    }
+
+    fn endpoint_subsets(
+        &self,
+    ) -> impl Iterator<Item = &k8s_openapi::api::core::v1::EndpointSubset> {
+        self.endpoints
+            .as_ref()
+            .and_then(|endpoints| endpoints.subsets.as_ref())
+            .into_iter()
+            .flatten()
+    }
+}
+
+/// Ports each ready worker should protect, by worker name
+///
+/// Every ready worker gets an entry, empty if it protects nothing, so ports
+/// of removed Services are cleared too.
+pub fn plan_protected_ports(
+    services: &[ProtectedService],
+    workers: &[WorkerInfo],
+) -> BTreeMap<String, ProtectedPorts> {
+    let resolved: Vec<(ProtectedPorts, Option<BTreeSet<String>>)> = services
+        .iter()
+        .map(|service| (service.ports(), service.nodes()))
+        .collect();
+
+    workers
+        .iter()
+        .filter(|worker| worker.ready)
+        .map(|worker| {
+            let mut ports = ProtectedPorts::default();
+            for (service_ports, nodes) in &resolved {
+                let receives = match (nodes, &worker.node_name) {
+                    (None, _) => true,
+                    (Some(nodes), Some(node)) => nodes.contains(node),
+                    (Some(_), None) => false,
+                };
+                if receives {
+                    ports.extend(service_ports);
+                }
+            }
+            (worker.name.clone(), ports)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::core::v1::{
+        EndpointAddress, EndpointPort, EndpointSubset, ServicePort, ServiceSpec,
+    };
+    use std::collections::HashMap;
+
+    fn service(ports: &[(&str, i32, Option<i32>)], policy: Option<&str>) -> Service {
+        Service {
+            spec: Some(ServiceSpec {
+                ports: Some(
+                    ports
+                        .iter()
+                        .map(|&(protocol, port, node_port)| ServicePort {
+                            protocol: Some(protocol.to_string()),
+                            port,
+                            node_port,
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+                external_traffic_policy: policy.map(str::to_string),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn endpoints(nodes: &[&str], ports: &[(&str, i32)]) -> Endpoints {
+        Endpoints {
+            subsets: Some(vec![EndpointSubset {
+                addresses: Some(
+                    nodes
+                        .iter()
+                        .map(|node| EndpointAddress {
+                            ip: "10.0.0.1".to_string(),
+                            node_name: Some(node.to_string()),
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+                ports: Some(
+                    ports
+                        .iter()
+                        .map(|&(protocol, port)| EndpointPort {
+                            protocol: Some(protocol.to_string()),
+                            port,
+                            ..Default::default()
+                        })
+                        .collect(),
+                ),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn worker(name: &str, node: &str, ready: bool) -> WorkerInfo {
+        WorkerInfo {
+            name: name.to_string(),
+            namespace: "pistonprotection-system".to_string(),
+            ip: "10.0.1.1".to_string(),
+            grpc_port: 50052,
+            node_name: Some(node.to_string()),
+            ready,
+            labels: HashMap::new(),
+            last_heartbeat: None,
+            config_version: 0,
+        }
+    }
+
+    #[test]
+    fn test_service_ports() {
+        let protected = ProtectedService {
+            service: service(
+                &[
+                    ("TCP", 25565, Some(30565)),
+                    ("UDP", 19132, None),
+                    ("SCTP", 9000, None),
+                ],
+                None,
+            ),
+            endpoints: None,
+        };
+        let ports = protected.ports();
+        assert_eq!(ports.tcp, [25565, 30565].into_iter().collect());
+        assert_eq!(ports.udp, [19132].into_iter().collect());
+        assert_eq!(protected.nodes(), None);
+    }
+
+    #[test]
+    fn test_headless_service_uses_endpoint_ports() {
+        let mut headless = service(&[("TCP", 80, None)], None);
+        headless.spec.as_mut().unwrap().cluster_ip = Some("None".to_string());
+        let protected = ProtectedService {
+            service: headless,
+            endpoints: Some(endpoints(&["node-a"], &[("TCP", 8080)])),
+        };
+        assert_eq!(protected.ports().tcp, [8080].into_iter().collect());
+    }
+
+    #[test]
+    fn test_plan_local_traffic_policy() {
+        let services = vec![
+            ProtectedService {
+                service: service(&[("TCP", 25565, None)], Some("Local")),
+                endpoints: Some(endpoints(&["node-a"], &[("TCP", 25565)])),
+            },
+            ProtectedService {
+                service: service(&[("UDP", 19132, None)], Some("Cluster")),
+                endpoints: None,
+            },
+        ];
+        let workers = vec![
+            worker("worker-a", "node-a", true),
+            worker("worker-b", "node-b", true),
+            worker("worker-c", "node-a", false),
+        ];
+
+        let plan = plan_protected_ports(&services, &workers);
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan["worker-a"].tcp, [25565].into_iter().collect());
+        assert_eq!(plan["worker-a"].udp, [19132].into_iter().collect());
+        assert!(plan["worker-b"].tcp.is_empty());
+        assert_eq!(plan["worker-b"].udp, [19132].into_iter().collect());
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut ports = ProtectedPorts {
+            tcp: (1..=MAX_PROTECTED_PORTS as u16 + 10).collect(),
+            udp: [53].into_iter().collect(),
+        };
+        assert!(ports.truncate());
+        assert_eq!(ports.tcp.len(), MAX_PROTECTED_PORTS);
+        assert_eq!(ports.tcp.last(), Some(&(MAX_PROTECTED_PORTS as u16)));
+        assert!(!ports.truncate());
+    }
+
+    #[test]
+    fn test_insert_ignores_invalid_ports() {
+        let mut ports = ProtectedPorts::default();
+        ports.insert(None, 0);
+        ports.insert(None, 70000);
+        ports.insert(None, 443);
+        assert_eq!(ports.tcp, [443].into_iter().collect());
+    }
+}
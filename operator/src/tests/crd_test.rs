@@ -411,6 +411,105 @@ mod backend_crd_tests {
     }
 }
 
+// ============================================================================
+// ProgramRollout CRD Tests
+// ============================================================================
+
+#[cfg(test)]
+mod program_rollout_tests {
+    use crate::crd::{ProgramRolloutSpec, ProgramRolloutStatus, RolloutPhase};
+
+    /// Test spec defaults when only the required fields are set
+    #[test]
+    fn test_program_rollout_defaults() {
+        let spec: ProgramRolloutSpec = serde_json::from_value(serde_json::json!({
+            "program": "xdp_filter",
+            "version": "0.2.0",
+            "digest": "ab".repeat(32),
+        }))
+        .unwrap();
+
+        assert_eq!(spec.waves, vec![10, 50, 100]);
+        assert_eq!(spec.variant, None);
+        assert_eq!(spec.observe_seconds, 300);
+        assert_eq!(spec.max_drop_rate_increase, 0.05);
+        assert_eq!(spec.max_failed_workers, 0);
+        assert!(!spec.abort);
+        assert!(spec.rollback_on_abort);
+    }
+
+    /// Test rollout phase display and activity
+    #[test]
+    fn test_rollout_phase() {
+        assert_eq!(RolloutPhase::default(), RolloutPhase::Pending);
+        assert_eq!(RolloutPhase::RolledBack.to_string(), "RolledBack");
+
+        assert!(RolloutPhase::Pending.is_active());
+        assert!(RolloutPhase::Observing.is_active());
+        assert!(!RolloutPhase::Completed.is_active());
+        assert!(!RolloutPhase::Aborted.is_active());
+    }
+
+    /// Test status serialization uses camelCase
+    #[test]
+    fn test_program_rollout_status_serialization() {
+        let status = ProgramRolloutStatus {
+            phase: RolloutPhase::Rolling,
+            current_wave: 1,
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["phase"], "Rolling");
+        assert_eq!(json["currentWave"], 1);
+    }
+}
+
+// ============================================================================
+// MemberCluster CRD Tests
+// ============================================================================
+
+#[cfg(test)]
+mod member_cluster_tests {
+    use crate::crd::{
+        ClusterStatus, DDoSProtectionStatus, FederationMode, MemberClusterPhase, MemberClusterSpec,
+    };
+
+    /// Test spec defaults and the kubeconfig key default
+    #[test]
+    fn test_member_cluster_defaults() {
+        let spec: MemberClusterSpec = serde_json::from_value(serde_json::json!({
+            "region": "eu-west",
+            "kubeconfigSecret": { "name": "edge-1-kubeconfig" },
+        }))
+        .unwrap();
+
+        assert_eq!(spec.mode, FederationMode::Push);
+        assert!(!spec.paused);
+        assert_eq!(spec.kubeconfig_secret.unwrap().key, "kubeconfig");
+        assert_eq!(MemberClusterPhase::default(), MemberClusterPhase::Pending);
+        assert_eq!(MemberClusterPhase::Unreachable.to_string(), "Unreachable");
+    }
+
+    /// Test cluster statuses are left out of status patches when empty
+    #[test]
+    fn test_clusters_status_serialization() {
+        let mut status = DDoSProtectionStatus::default();
+        let json = serde_json::to_value(&status).unwrap();
+        assert!(json.get("clusters").is_none());
+
+        status.clusters.push(ClusterStatus {
+            cluster: "edge-1".to_string(),
+            synced: true,
+            ready_workers: Some(2),
+            ..Default::default()
+        });
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["clusters"][0]["cluster"], "edge-1");
+        assert_eq!(json["clusters"][0]["readyWorkers"], 2);
+    }
+}
+
 // ============================================================================
 // Serialization Tests
 // ============================================================================
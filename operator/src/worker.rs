@@ -8,6 +8,7 @@
 
 use crate::crd::{Backend, DDoSProtection, FilterRule, IPBlocklist};
 use crate::error::{Error, Result};
+use crate::protected_ports::ProtectedPorts;
 
 use k8s_openapi::api::core::v1::Pod;
 use kube::{
@@ -689,6 +690,25 @@ impl WorkerManager {
         Ok(())
     }
 
+    /// Set the protected ports of a single worker
+    pub async fn set_protected_ports_on_worker(
+        &self,
+        worker: &WorkerInfo,
+        ports: &ProtectedPorts,
+    ) -> Result<()> {
+        debug!(
+            "Setting {} TCP and {} UDP protected ports on worker {}",
+            ports.tcp.len(),
+            ports.udp.len(),
+            worker.name
+        );
+
+        // In production, this would call the worker's admin API
+        // PUT http://{ip}:8080/admin/protected-ports with the ports as JSON
+
+        Ok(())
+    }
+
     /// Increment and get next config version
     pub async fn next_config_version(&self) -> u32 {
         let mut version = self.config_version.write().await;
@@ -10,6 +10,10 @@
 #   ./scripts/build-ebpf.sh --release   # Build in release mode
 #   ./scripts/build-ebpf.sh --install   # Install to /opt/pistonprotection/ebpf
 #
+# Every program is built in two variants (see ebpf/Cargo.toml): the full
+# variant for kernels 5.18+ and the compat variant for kernels from 5.4,
+# installed with a ".compat" suffix. Both carry BTF for CO-RE relocation.
+#
 # =============================================================================
 
 set -euo pipefail
@@ -31,6 +35,7 @@ RELEASE_MODE=false
 INSTALL=false
 INSTALL_DIR="/opt/pistonprotection/ebpf"
 NIGHTLY_TOOLCHAIN="nightly-2026-01-10"
+VARIANTS=(full compat)
 
 # Print colored message
 log_info() {
@@ -61,6 +66,7 @@ Options:
     --install           Install eBPF programs to $INSTALL_DIR
     --install-dir DIR   Custom installation directory
     --toolchain TC      Nightly toolchain version (default: $NIGHTLY_TOOLCHAIN)
+    --variant NAME      Only build one program variant (full or compat)
     --help, -h          Show this help message
 
 Requirements:
@@ -95,6 +101,16 @@ parse_args() {
                 NIGHTLY_TOOLCHAIN="$2"
                 shift 2
                 ;;
+            --variant)
+                case $2 in
+                    full|compat) VARIANTS=("$2") ;;
+                    *)
+                        log_error "Unknown variant: $2"
+                        exit 1
+                        ;;
+                esac
+                shift 2
+                ;;
             --help|-h)
                 print_help
                 exit 0
@@ -155,21 +171,36 @@ check_dependencies() {
     log_success "All dependencies satisfied"
 }
 
+# Output directory of a program variant
+variant_dir() {
+    local dir="$EBPF_DIR/target"
+    if [[ "$1" != "full" ]]; then
+        dir="$dir/$1"
+    fi
+    dir="$dir/bpfel-unknown-none"
+    if $RELEASE_MODE; then
+        echo "$dir/release"
+    else
+        echo "$dir/debug"
+    fi
+}
+
+# Installed file name of a program variant
+variant_file() {
+    if [[ "$2" == "full" ]]; then
+        echo "$1"
+    else
+        echo "$1.$2"
+    fi
+}
+
 # Build eBPF programs
 build_ebpf() {
     log_info "Building eBPF programs..."
 
     cd "$EBPF_DIR"
 
-    local cargo_args=(
-        "+$NIGHTLY_TOOLCHAIN"
-        "build"
-        "--target" "bpfel-unknown-none"
-        "-Z" "build-std=core"
-    )
-
     if $RELEASE_MODE; then
-        cargo_args+=("--release")
         log_info "Building in release mode..."
     else
         log_info "Building in debug mode..."
@@ -178,25 +209,40 @@ build_ebpf() {
     # Set environment for BPF target
     export CARGO_CFG_BPF_TARGET_ARCH="x86_64"
 
-    # Run build
-    cargo "${cargo_args[@]}"
+    # Emit BTF so the loader can relocate the programs against kernel BTF
+    export RUSTFLAGS="${RUSTFLAGS:-} -C debuginfo=2 -C link-arg=--btf"
 
-    # Determine output directory
-    local output_dir="$EBPF_DIR/target/bpfel-unknown-none"
-    if $RELEASE_MODE; then
-        output_dir="$output_dir/release"
-    else
-        output_dir="$output_dir/debug"
-    fi
+    for variant in "${VARIANTS[@]}"; do
+        local cargo_args=(
+            "+$NIGHTLY_TOOLCHAIN"
+            "build"
+            "--target" "bpfel-unknown-none"
+            "-Z" "build-std=core"
+        )
+
+        if $RELEASE_MODE; then
+            cargo_args+=("--release")
+        fi
 
-    # List built programs
-    log_info "Built eBPF programs:"
-    for prog in "$output_dir"/*; do
-        if [[ -f "$prog" ]] && file "$prog" | grep -q "BPF\|ELF"; then
-            local size
-            size=$(du -h "$prog" | cut -f1)
-            echo "  - $(basename "$prog") ($size)"
+        # The compat variant drops xdp.frags and ring buffers
+        if [[ "$variant" == "compat" ]]; then
+            cargo_args+=("--no-default-features" "--target-dir" "target/compat")
         fi
+
+        log_info "Building $variant variant..."
+        cargo "${cargo_args[@]}"
+
+        # List built programs
+        local output_dir
+        output_dir=$(variant_dir "$variant")
+        log_info "Built eBPF programs ($variant):"
+        for prog in "$output_dir"/*; do
+            if [[ -f "$prog" ]] && file "$prog" | grep -q "BPF\|ELF"; then
+                local size
+                size=$(du -h "$prog" | cut -f1)
+                echo "  - $(basename "$prog") ($size)"
+            fi
+        done
     done
 
     log_success "eBPF programs built successfully"
@@ -206,14 +252,6 @@ build_ebpf() {
 install_ebpf() {
     log_info "Installing eBPF programs to $INSTALL_DIR..."
 
-    # Determine source directory
-    local source_dir="$EBPF_DIR/target/bpfel-unknown-none"
-    if $RELEASE_MODE; then
-        source_dir="$source_dir/release"
-    else
-        source_dir="$source_dir/debug"
-    fi
-
     # Create installation directory
     if [[ ! -d "$INSTALL_DIR" ]]; then
         log_info "Creating installation directory..."
@@ -222,15 +260,19 @@ install_ebpf() {
 
     # Copy programs
     local installed=0
-    for prog in "$source_dir"/*; do
-        if [[ -f "$prog" ]] && file "$prog" | grep -q "BPF\|ELF"; then
-            local name
-            name=$(basename "$prog")
-            sudo cp "$prog" "$INSTALL_DIR/$name"
-            sudo chmod 644 "$INSTALL_DIR/$name"
-            log_info "Installed: $name"
-            ((installed++))
-        fi
+    for variant in "${VARIANTS[@]}"; do
+        local source_dir
+        source_dir=$(variant_dir "$variant")
+        for prog in "$source_dir"/*; do
+            if [[ -f "$prog" ]] && file "$prog" | grep -q "BPF\|ELF"; then
+                local name
+                name=$(variant_file "$(basename "$prog")" "$variant")
+                sudo cp "$prog" "$INSTALL_DIR/$name"
+                sudo chmod 644 "$INSTALL_DIR/$name"
+                log_info "Installed: $name"
+                ((installed++))
+            fi
+        done
     done
 
     if [[ $installed -eq 0 ]]; then
@@ -244,12 +286,8 @@ install_ebpf() {
 verify_programs() {
     log_info "Verifying eBPF programs..."
 
-    local source_dir="$EBPF_DIR/target/bpfel-unknown-none"
-    if $RELEASE_MODE; then
-        source_dir="$source_dir/release"
-    else
-        source_dir="$source_dir/debug"
-    fi
+    local source_dir
+    source_dir=$(variant_dir "${VARIANTS[0]}")
 
     # Check with llvm-objdump if available
     if command -v llvm-objdump &> /dev/null; then
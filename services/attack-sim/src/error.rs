@@ -0,0 +1,23 @@
+//! Simulator errors
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, SimError>;
+
+#[derive(Debug, Error)]
+pub enum SimError {
+    #[error("Failed to load scenario: {0}")]
+    Config(#[from] config::ConfigError),
+
+    #[error("Invalid scenario: {0}")]
+    Scenario(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Metrics service unavailable: {0}")]
+    Transport(#[from] tonic::transport::Error),
+
+    #[error("Metrics request failed: {0}")]
+    Metrics(#[from] tonic::Status),
+}
@@ -0,0 +1,280 @@
+//! Flood generators
+//!
+//! Every flood is paced to the phase rate: packets are sent in bursts each
+//! millisecond so the count sent so far follows `pps * elapsed`. SYN and
+//! UDP floods run on a blocking thread; HTTP and Minecraft floods open a new
+//! connection per request, with at most `concurrency` open at once, and
+//! record what the client got back.
+//!
+//! Packets carry the simulator's own address: the floods test mitigation of
+//! volume from real sources, not spoofing.
+
+use crate::error::{Result, SimError};
+use crate::packets;
+use crate::scenario::{FloodKind, Phase};
+use nix::sys::socket::{
+    self, AddressFamily, MsgFlags, SockFlag, SockProtocol, SockType, SockaddrIn,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+
+/// Time a connection-based request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a flood sent and, for connection-based floods, what came back
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FloodStats {
+    pub sent: u64,
+    pub send_errors: u64,
+    /// Outcome of each request of HTTP and Minecraft floods
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub outcomes: BTreeMap<String, u64>,
+    pub elapsed_ms: u64,
+}
+
+impl FloodStats {
+    fn record(&mut self, outcome: String) {
+        *self.outcomes.entry(outcome).or_insert(0) += 1;
+    }
+}
+
+/// Send rate of a phase
+struct Pacer {
+    start: Instant,
+    pps: u64,
+    duration: Duration,
+}
+
+impl Pacer {
+    fn new(phase: &Phase) -> Self {
+        Self {
+            start: Instant::now(),
+            pps: phase.pps,
+            duration: Duration::from_secs(phase.duration_secs),
+        }
+    }
+
+    /// Packets that should have been sent by now, `None` once the phase is over
+    fn due(&self) -> Option<u64> {
+        let elapsed = self.start.elapsed();
+        (elapsed < self.duration).then(|| (elapsed.as_secs_f64() * self.pps as f64) as u64 + 1)
+    }
+}
+
+/// Run the flood of a phase against `target`
+pub async fn run(phase: &Phase, target: IpAddr, host: &str) -> Result<FloodStats> {
+    let start = Instant::now();
+    let addr = SocketAddr::new(target, phase.port);
+
+    let mut stats = match phase.kind {
+        FloodKind::Syn => {
+            let phase = phase.clone();
+            tokio::task::spawn_blocking(move || syn_flood(&phase, addr))
+                .await
+                .map_err(|e| SimError::Io(std::io::Error::other(e)))??
+        }
+        FloodKind::Udp => {
+            let phase = phase.clone();
+            tokio::task::spawn_blocking(move || udp_flood(&phase, addr))
+                .await
+                .map_err(|e| SimError::Io(std::io::Error::other(e)))??
+        }
+        FloodKind::HttpGet => {
+            let request = Arc::new(packets::http_get(host));
+            connection_flood(phase, addr, request, http_outcome).await
+        }
+        FloodKind::MinecraftStatus => {
+            let request = Arc::new(packets::minecraft_status(host, phase.port));
+            connection_flood(phase, addr, request, minecraft_outcome).await
+        }
+    };
+
+    stats.elapsed_ms = start.elapsed().as_millis() as u64;
+    Ok(stats)
+}
+
+/// Local address used to reach `target`
+fn source_for(target: SocketAddr) -> Result<IpAddr> {
+    let bind: SocketAddr = match target {
+        SocketAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+        SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(target)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+fn syn_flood(phase: &Phase, target: SocketAddr) -> Result<FloodStats> {
+    let (IpAddr::V4(src), SocketAddr::V4(dst)) = (source_for(target)?, target) else {
+        return Err(SimError::Scenario(
+            "SYN floods need an IPv4 target".to_string(),
+        ));
+    };
+    let fd = socket::socket(
+        AddressFamily::Inet,
+        SockType::Raw,
+        SockFlag::empty(),
+        SockProtocol::Tcp,
+    )
+    .map_err(std::io::Error::from)?;
+    let dest = SockaddrIn::from(SocketAddrV4::new(*dst.ip(), 0));
+
+    let mut stats = FloodStats::default();
+    let pacer = Pacer::new(phase);
+    while let Some(due) = pacer.due() {
+        while stats.sent < due {
+            let segment = packets::tcp_syn(
+                src,
+                *dst.ip(),
+                rand::random_range(1024..=65535),
+                dst.port(),
+                rand::random(),
+            );
+            if socket::sendto(fd.as_raw_fd(), &segment, &dest, MsgFlags::empty()).is_err() {
+                stats.send_errors += 1;
+            }
+            stats.sent += 1;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    Ok(stats)
+}
+
+fn udp_flood(phase: &Phase, target: SocketAddr) -> Result<FloodStats> {
+    let socket = UdpSocket::bind(SocketAddr::new(source_for(target)?, 0))?;
+    socket.connect(target)?;
+    let payload: Vec<u8> = (0..phase.payload_size).map(|_| rand::random()).collect();
+
+    let mut stats = FloodStats::default();
+    let pacer = Pacer::new(phase);
+    while let Some(due) = pacer.due() {
+        while stats.sent < due {
+            if socket.send(&payload).is_err() {
+                stats.send_errors += 1;
+            }
+            stats.sent += 1;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    Ok(stats)
+}
+
+async fn connection_flood(
+    phase: &Phase,
+    target: SocketAddr,
+    request: Arc<Vec<u8>>,
+    outcome: fn(&[u8]) -> String,
+) -> FloodStats {
+    let stats = Arc::new(Mutex::new(FloodStats::default()));
+    let slots = Arc::new(Semaphore::new(phase.concurrency));
+
+    let pacer = Pacer::new(phase);
+    let mut sent = 0;
+    while let Some(due) = pacer.due() {
+        while sent < due {
+            let Ok(permit) = Arc::clone(&slots).acquire_owned().await else {
+                break;
+            };
+            sent += 1;
+            let request = Arc::clone(&request);
+            let stats = Arc::clone(&stats);
+            tokio::spawn(async move {
+                let result =
+                    tokio::time::timeout(REQUEST_TIMEOUT, exchange(target, &request)).await;
+                let outcome = match result {
+                    Ok(Ok(response)) if response.is_empty() => "closed".to_string(),
+                    Ok(Ok(response)) => outcome(&response),
+                    Ok(Err(e)) => match e.kind() {
+                        ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset => {
+                            "reset".to_string()
+                        }
+                        _ => "connect_error".to_string(),
+                    },
+                    Err(_) => "timeout".to_string(),
+                };
+                stats.lock().record(outcome);
+                drop(permit);
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    // Wait for the requests still open
+    let _ = slots.acquire_many(phase.concurrency as u32).await;
+
+    let mut stats = stats.lock().clone();
+    stats.sent = sent;
+    stats.send_errors = stats.outcomes.get("connect_error").copied().unwrap_or(0);
+    stats
+}
+
+/// Send a request on a new connection and read the start of the response
+async fn exchange(target: SocketAddr, request: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(target).await?;
+    stream.write_all(request).await?;
+    let mut response = vec![0u8; 1024];
+    let n = stream.read(&mut response).await?;
+    response.truncate(n);
+    Ok(response)
+}
+
+fn http_outcome(response: &[u8]) -> String {
+    match packets::http_status(response) {
+        Some(status) => format!("http_{}", status),
+        None => "invalid_response".to_string(),
+    }
+}
+
+fn minecraft_outcome(_response: &[u8]) -> String {
+    "response".to_string()
+}
+
+/// Requests of a connection-based flood the deployment refused: blocked,
+/// rate limited or challenged responses, and connections reset, closed or
+/// left unanswered
+pub fn client_mitigated(kind: FloodKind, outcomes: &BTreeMap<String, u64>) -> u64 {
+    outcomes
+        .iter()
+        .filter(|(outcome, _)| match kind {
+            FloodKind::HttpGet => matches!(
+                outcome.as_str(),
+                "http_307" | "http_403" | "http_429" | "http_503" | "reset" | "closed" | "timeout"
+            ),
+            FloodKind::MinecraftStatus => {
+                matches!(outcome.as_str(), "reset" | "closed" | "timeout")
+            }
+            FloodKind::Syn | FloodKind::Udp => false,
+        })
+        .map(|(_, count)| count)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_mitigated() {
+        let outcomes: BTreeMap<String, u64> = [
+            ("http_200".to_string(), 10),
+            ("http_429".to_string(), 70),
+            ("http_404".to_string(), 5),
+            ("timeout".to_string(), 15),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(client_mitigated(FloodKind::HttpGet, &outcomes), 85);
+        assert_eq!(client_mitigated(FloodKind::MinecraftStatus, &outcomes), 15);
+        assert_eq!(client_mitigated(FloodKind::Udp, &outcomes), 0);
+    }
+}
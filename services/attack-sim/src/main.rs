@@ -0,0 +1,162 @@
+//! PistonProtection Attack Simulator
+//!
+//! Sends controlled floods (SYN, UDP, HTTP GET, Minecraft status) at a
+//! staging deployment, phase by phase, while sampling the metrics service
+//! for the staging backend, and prints a mitigation effectiveness report
+//! as JSON. Used for pre-release validation; never point it at production.
+//!
+//! Usage: `attack-simulator <scenario file> [report file]`
+//!
+//! Exits with 1 if a phase fell short of its `min_effectiveness` and with 2
+//! if the scenario could not run.
+
+mod error;
+mod flood;
+mod metrics;
+mod packets;
+mod report;
+mod scenario;
+
+use chrono::Utc;
+use error::Result;
+use metrics::{MetricsObservation, MetricsProbe, MetricsSample};
+use report::{PhaseReport, Report};
+use scenario::{Phase, Scenario};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Interval between metrics samples during a phase
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        error!("Usage: attack-simulator <scenario file> [report file]");
+        return ExitCode::from(2);
+    };
+    let output = args.next();
+
+    let report = match run(&path).await {
+        Ok(report) => report,
+        Err(e) => {
+            error!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let json = serde_json::to_string_pretty(&report).expect("report serializes");
+    println!("{}", json);
+    if let Some(output) = output {
+        if let Err(e) = std::fs::write(&output, &json) {
+            error!("Failed to write report to {}: {}", output, e);
+            return ExitCode::from(2);
+        }
+    }
+
+    if report.passed {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+async fn run(path: &str) -> Result<Report> {
+    let scenario = Scenario::load(path)?;
+    info!(
+        "Running scenario {} against {} ({} phases)",
+        scenario.name,
+        scenario.target,
+        scenario.phases.len()
+    );
+
+    let mut probe = match &scenario.metrics_endpoint {
+        Some(endpoint) => Some(MetricsProbe::connect(endpoint, &scenario.backend_id).await?),
+        None => {
+            warn!("No metrics endpoint, the report only has client-side results");
+            None
+        }
+    };
+    let host = scenario
+        .http_host
+        .clone()
+        .unwrap_or_else(|| scenario.target.to_string());
+
+    let started_at = Utc::now();
+    let mut phases = Vec::with_capacity(scenario.phases.len());
+    for phase in &scenario.phases {
+        let report = run_phase(&scenario, phase, &host, probe.as_mut()).await?;
+        info!(
+            phase = %phase.name,
+            sent = report.flood.sent,
+            effectiveness = ?report.effectiveness,
+            passed = ?report.passed,
+            "Phase finished"
+        );
+        phases.push(report);
+    }
+
+    Ok(Report {
+        scenario: scenario.name.clone(),
+        target: scenario.target.to_string(),
+        backend_id: scenario.backend_id.clone(),
+        started_at,
+        finished_at: Utc::now(),
+        passed: Report::passed(&phases),
+        phases,
+    })
+}
+
+/// Run one flood, sampling the metrics service until it settled
+async fn run_phase(
+    scenario: &Scenario,
+    phase: &Phase,
+    host: &str,
+    probe: Option<&mut MetricsProbe>,
+) -> Result<PhaseReport> {
+    info!(
+        "Phase {}: {:?} flood at {} pps for {}s on port {}",
+        phase.name, phase.kind, phase.pps, phase.duration_secs, phase.port
+    );
+
+    let Some(probe) = probe else {
+        let stats = flood::run(phase, scenario.target, host).await?;
+        tokio::time::sleep(Duration::from_secs(scenario.settle_secs)).await;
+        return Ok(PhaseReport::new(phase, stats, None));
+    };
+
+    let before = probe.sample(0.0).await?;
+    let start = Instant::now();
+    let flood = flood::run(phase, scenario.target, host);
+    tokio::pin!(flood);
+
+    let mut samples: Vec<MetricsSample> = Vec::new();
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    interval.tick().await;
+    let stats = loop {
+        tokio::select! {
+            stats = &mut flood => break stats?,
+            _ = interval.tick() => {
+                match probe.sample(start.elapsed().as_secs_f64()).await {
+                    Ok(sample) => samples.push(sample),
+                    Err(e) => warn!("Failed to sample metrics: {}", e),
+                }
+            }
+        }
+    };
+
+    tokio::time::sleep(Duration::from_secs(scenario.settle_secs)).await;
+    samples.push(probe.sample(start.elapsed().as_secs_f64()).await?);
+
+    let observation = MetricsObservation::from_samples(&before, &samples);
+    Ok(PhaseReport::new(phase, stats, Some(observation)))
+}
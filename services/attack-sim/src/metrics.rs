@@ -0,0 +1,163 @@
+//! Metrics service probe
+//!
+//! Samples the traffic and attack metrics the metrics service keeps for the
+//! staging backend while a phase runs, to see whether the deployment
+//! detected the flood, how fast, and how much of it was mitigated.
+
+use crate::error::Result;
+use pistonprotection_proto::metrics::{
+    GetAttackMetricsRequest, GetTrafficMetricsRequest, metrics_service_client::MetricsServiceClient,
+};
+use serde::Serialize;
+use tonic::transport::Channel;
+
+/// Metrics of the backend at one point in time
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MetricsSample {
+    /// Seconds since the phase started
+    pub at_secs: f64,
+    pub packets_in: u64,
+    pub requests_total: u64,
+    pub under_attack: bool,
+    pub attack_type: String,
+    pub attack_pps: u64,
+    pub requests_dropped: u64,
+    pub requests_challenged: u64,
+    pub requests_rate_limited: u64,
+}
+
+impl MetricsSample {
+    fn mitigated(&self) -> u64 {
+        self.requests_dropped + self.requests_challenged + self.requests_rate_limited
+    }
+}
+
+/// What the metrics service saw of a phase
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetricsObservation {
+    /// The backend was reported under attack during the phase
+    pub detected: bool,
+    /// Seconds from the start of the phase to the first report
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_to_detect_secs: Option<f64>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub attack_type: String,
+    pub peak_attack_pps: u64,
+    /// Packets received by the backend during the phase
+    pub packets_in: u64,
+    /// Requests dropped, challenged or rate limited during the phase
+    pub mitigated: u64,
+    pub samples: usize,
+}
+
+impl MetricsObservation {
+    /// Summarize samples taken before, during and after a phase
+    pub fn from_samples(before: &MetricsSample, samples: &[MetricsSample]) -> Self {
+        let Some(after) = samples.last() else {
+            return Self::default();
+        };
+        let first_detection = samples.iter().find(|s| s.under_attack);
+
+        Self {
+            detected: first_detection.is_some(),
+            time_to_detect_secs: first_detection.map(|s| s.at_secs),
+            attack_type: first_detection
+                .map(|s| s.attack_type.clone())
+                .unwrap_or_default(),
+            peak_attack_pps: samples.iter().map(|s| s.attack_pps).max().unwrap_or(0),
+            packets_in: after.packets_in.saturating_sub(before.packets_in),
+            mitigated: after.mitigated().saturating_sub(before.mitigated()),
+            samples: samples.len(),
+        }
+    }
+}
+
+/// Client of the metrics service for one backend
+pub struct MetricsProbe {
+    client: MetricsServiceClient<Channel>,
+    backend_id: String,
+}
+
+impl MetricsProbe {
+    pub async fn connect(endpoint: &str, backend_id: &str) -> Result<Self> {
+        Ok(Self {
+            client: MetricsServiceClient::connect(endpoint.to_string()).await?,
+            backend_id: backend_id.to_string(),
+        })
+    }
+
+    /// Current metrics of the backend
+    pub async fn sample(&mut self, at_secs: f64) -> Result<MetricsSample> {
+        let traffic = self
+            .client
+            .get_traffic_metrics(GetTrafficMetricsRequest {
+                backend_id: self.backend_id.clone(),
+            })
+            .await?
+            .into_inner()
+            .metrics
+            .unwrap_or_default();
+        let attack = self
+            .client
+            .get_attack_metrics(GetAttackMetricsRequest {
+                backend_id: self.backend_id.clone(),
+            })
+            .await?
+            .into_inner()
+            .metrics
+            .unwrap_or_default();
+
+        Ok(MetricsSample {
+            at_secs,
+            packets_in: traffic.packets_in,
+            requests_total: traffic.requests_total,
+            under_attack: attack.under_attack,
+            attack_type: attack.attack_type,
+            attack_pps: attack.attack_pps,
+            requests_dropped: attack.requests_dropped,
+            requests_challenged: attack.requests_challenged,
+            requests_rate_limited: attack.requests_rate_limited,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observation_from_samples() {
+        let before = MetricsSample {
+            packets_in: 1_000,
+            requests_dropped: 50,
+            ..Default::default()
+        };
+        let samples = vec![
+            MetricsSample {
+                at_secs: 5.0,
+                packets_in: 40_000,
+                requests_dropped: 20_000,
+                attack_pps: 8_000,
+                ..Default::default()
+            },
+            MetricsSample {
+                at_secs: 10.0,
+                packets_in: 90_000,
+                under_attack: true,
+                attack_type: "syn_flood".to_string(),
+                attack_pps: 10_000,
+                requests_dropped: 80_000,
+                requests_rate_limited: 5_000,
+                ..Default::default()
+            },
+        ];
+
+        let observation = MetricsObservation::from_samples(&before, &samples);
+        assert!(observation.detected);
+        assert_eq!(observation.time_to_detect_secs, Some(10.0));
+        assert_eq!(observation.attack_type, "syn_flood");
+        assert_eq!(observation.peak_attack_pps, 10_000);
+        assert_eq!(observation.packets_in, 89_000);
+        assert_eq!(observation.mitigated, 84_950);
+    }
+}
@@ -0,0 +1,129 @@
+//! Packets and requests of the floods
+
+use std::net::Ipv4Addr;
+
+const TCP_SYN: u8 = 0x02;
+const IPPROTO_TCP: u8 = 6;
+
+/// Minecraft protocol version sent in status pings (1.21.4)
+pub const MINECRAFT_PROTOCOL: i32 = 769;
+
+/// TCP SYN segment (without IP header), checksummed for `src` -> `dst`
+pub fn tcp_syn(src: Ipv4Addr, dst: Ipv4Addr, src_port: u16, dst_port: u16, seq: u32) -> [u8; 20] {
+    let mut tcp = [0u8; 20];
+    tcp[0..2].copy_from_slice(&src_port.to_be_bytes());
+    tcp[2..4].copy_from_slice(&dst_port.to_be_bytes());
+    tcp[4..8].copy_from_slice(&seq.to_be_bytes());
+    tcp[12] = 5 << 4;
+    tcp[13] = TCP_SYN;
+    tcp[14..16].copy_from_slice(&64240u16.to_be_bytes());
+
+    let mut pseudo = Vec::with_capacity(32);
+    pseudo.extend_from_slice(&src.octets());
+    pseudo.extend_from_slice(&dst.octets());
+    pseudo.extend_from_slice(&[0, IPPROTO_TCP]);
+    pseudo.extend_from_slice(&(tcp.len() as u16).to_be_bytes());
+    pseudo.extend_from_slice(&tcp);
+    let checksum = internet_checksum(&pseudo);
+    tcp[16..18].copy_from_slice(&checksum.to_be_bytes());
+    tcp
+}
+
+/// HTTP/1.1 GET request closing its connection
+pub fn http_get(host: &str) -> Vec<u8> {
+    format!(
+        "GET / HTTP/1.1\r\nHost: {}\r\nUser-Agent: pistonprotection-attack-sim\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        host
+    )
+    .into_bytes()
+}
+
+/// Minecraft Java handshake to the status state followed by a status request
+pub fn minecraft_status(host: &str, port: u16) -> Vec<u8> {
+    let mut handshake = Vec::with_capacity(host.len() + 16);
+    write_varint(&mut handshake, 0x00);
+    write_varint(&mut handshake, MINECRAFT_PROTOCOL);
+    write_varint(&mut handshake, host.len() as i32);
+    handshake.extend_from_slice(host.as_bytes());
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, 1);
+
+    let mut packets = Vec::with_capacity(handshake.len() + 8);
+    write_varint(&mut packets, handshake.len() as i32);
+    packets.extend_from_slice(&handshake);
+    // Status request: length 1, packet ID 0
+    packets.extend_from_slice(&[0x01, 0x00]);
+    packets
+}
+
+/// HTTP status code of a response head
+pub fn http_status(head: &[u8]) -> Option<u16> {
+    let line = head.split(|&b| b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7f == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tcp_syn_checksum() {
+        let src = Ipv4Addr::new(10, 0, 0, 1);
+        let dst = Ipv4Addr::new(10, 0, 0, 2);
+        let tcp = tcp_syn(src, dst, 40000, 25565, 7);
+
+        let mut pseudo = Vec::new();
+        pseudo.extend_from_slice(&src.octets());
+        pseudo.extend_from_slice(&dst.octets());
+        pseudo.extend_from_slice(&[0, IPPROTO_TCP, 0, 20]);
+        pseudo.extend_from_slice(&tcp);
+        assert_eq!(internet_checksum(&pseudo), 0);
+        assert_eq!(tcp[13], TCP_SYN);
+    }
+
+    #[test]
+    fn test_minecraft_status_encoding() {
+        let packets = minecraft_status("mc.example.com", 25565);
+        // Handshake length, packet ID 0, protocol 769 as a two byte VarInt
+        assert_eq!(packets[0] as usize, packets.len() - 3);
+        assert_eq!(&packets[1..4], &[0x00, 0x81, 0x06]);
+        assert_eq!(&packets[packets.len() - 3..], &[0x01, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_http_status() {
+        assert_eq!(
+            http_status(b"HTTP/1.1 429 Too Many Requests\r\n"),
+            Some(429)
+        );
+        assert_eq!(http_status(b"garbage"), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! Mitigation effectiveness report
+
+use crate::flood::{self, FloodStats};
+use crate::metrics::MetricsObservation;
+use crate::scenario::{FloodKind, Phase};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Result of a phase
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseReport {
+    pub name: String,
+    pub kind: FloodKind,
+    pub target_pps: u64,
+    pub achieved_pps: f64,
+    pub flood: FloodStats,
+    /// Requests the client saw refused (connection-based floods)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_mitigated: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<MetricsObservation>,
+    /// Share of the flood mitigated, from 0 to 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effectiveness: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_effectiveness: Option<f64>,
+    /// Whether the phase met `min_effectiveness`, if it has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passed: Option<bool>,
+}
+
+impl PhaseReport {
+    pub fn new(phase: &Phase, flood: FloodStats, metrics: Option<MetricsObservation>) -> Self {
+        let client_mitigated = phase
+            .kind
+            .is_connection_based()
+            .then(|| flood::client_mitigated(phase.kind, &flood.outcomes));
+        let effectiveness = effectiveness(
+            flood.sent,
+            metrics.as_ref().map(|m| m.mitigated),
+            client_mitigated,
+        );
+        let achieved_pps = if flood.elapsed_ms == 0 {
+            0.0
+        } else {
+            flood.sent as f64 * 1000.0 / flood.elapsed_ms as f64
+        };
+
+        Self {
+            name: phase.name.clone(),
+            kind: phase.kind,
+            target_pps: phase.pps,
+            achieved_pps,
+            client_mitigated,
+            metrics,
+            passed: phase
+                .min_effectiveness
+                .map(|min| effectiveness.is_some_and(|e| e >= min)),
+            effectiveness,
+            min_effectiveness: phase.min_effectiveness,
+            flood,
+        }
+    }
+}
+
+/// Share of `sent` that was mitigated
+///
+/// The metrics service's count is used when it saw the phase; otherwise
+/// connection-based floods fall back to what the client saw refused.
+pub fn effectiveness(sent: u64, metrics: Option<u64>, client: Option<u64>) -> Option<f64> {
+    if sent == 0 {
+        return None;
+    }
+    let mitigated = metrics.filter(|&m| m > 0).or(client)?;
+    Some((mitigated as f64 / sent as f64).min(1.0))
+}
+
+/// Result of a scenario
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub scenario: String,
+    pub target: String,
+    pub backend_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub phases: Vec<PhaseReport>,
+    /// No phase fell short of its `min_effectiveness`
+    pub passed: bool,
+}
+
+impl Report {
+    pub fn passed(phases: &[PhaseReport]) -> bool {
+        phases.iter().all(|phase| phase.passed != Some(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_effectiveness_prefers_metrics() {
+        assert_eq!(effectiveness(1000, Some(900), Some(100)), Some(0.9));
+        // Metrics service did not see the phase
+        assert_eq!(effectiveness(1000, Some(0), Some(250)), Some(0.25));
+        assert_eq!(effectiveness(1000, None, None), None);
+        assert_eq!(effectiveness(1000, Some(5000), None), Some(1.0));
+        assert_eq!(effectiveness(0, Some(10), None), None);
+    }
+
+    #[test]
+    fn test_phase_report_threshold() {
+        let phase = Phase {
+            name: "http".to_string(),
+            kind: FloodKind::HttpGet,
+            port: 80,
+            pps: 100,
+            duration_secs: 10,
+            payload_size: 0,
+            concurrency: 10,
+            min_effectiveness: Some(0.8),
+        };
+        let outcomes: BTreeMap<String, u64> =
+            [("http_200".to_string(), 300), ("http_429".to_string(), 700)]
+                .into_iter()
+                .collect();
+        let flood = FloodStats {
+            sent: 1000,
+            send_errors: 0,
+            outcomes,
+            elapsed_ms: 10_000,
+        };
+
+        let report = PhaseReport::new(&phase, flood, None);
+        assert_eq!(report.client_mitigated, Some(700));
+        assert_eq!(report.effectiveness, Some(0.7));
+        assert_eq!(report.passed, Some(false));
+        assert_eq!(report.achieved_pps, 100.0);
+        assert!(!Report::passed(&[report]));
+    }
+}
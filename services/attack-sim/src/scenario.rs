@@ -0,0 +1,223 @@
+//! Simulation scenarios
+//!
+//! A scenario names the staging deployment under test and the floods sent
+//! at it, one phase after another. Targets must fall inside the scenario's
+//! `allowed_targets` and rates under `max_pps`, so a mistyped address or
+//! rate cannot turn the simulator against something it was not meant for.
+
+use crate::error::{Result, SimError};
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Highest rate a scenario may allow
+pub const MAX_PPS: u64 = 1_000_000;
+
+/// Longest phase
+pub const MAX_PHASE_SECS: u64 = 3600;
+
+/// Flood sent during a phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FloodKind {
+    /// TCP SYNs from random source ports (needs CAP_NET_RAW)
+    Syn,
+    /// UDP datagrams of `payload_size` bytes
+    Udp,
+    /// HTTP/1.1 GET requests on new connections
+    HttpGet,
+    /// Minecraft Java status pings on new connections
+    MinecraftStatus,
+}
+
+impl FloodKind {
+    /// Whether the flood opens connections, so the client sees the verdict
+    pub fn is_connection_based(self) -> bool {
+        matches!(self, FloodKind::HttpGet | FloodKind::MinecraftStatus)
+    }
+}
+
+/// One flood
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Phase {
+    pub name: String,
+    pub kind: FloodKind,
+    pub port: u16,
+    /// Packets (or requests) per second
+    pub pps: u64,
+    pub duration_secs: u64,
+    /// UDP payload size
+    #[serde(default = "default_payload_size")]
+    pub payload_size: usize,
+    /// Connections open at once for HTTP and Minecraft floods
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Share of the flood the deployment must mitigate, from 0 to 1
+    #[serde(default)]
+    pub min_effectiveness: Option<f64>,
+}
+
+fn default_payload_size() -> usize {
+    512
+}
+
+fn default_concurrency() -> usize {
+    256
+}
+
+/// Staging deployment under test and the floods sent at it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    /// Protected frontend address of the staging backend
+    pub target: IpAddr,
+    /// Networks the simulator may send to
+    pub allowed_targets: Vec<IpNetwork>,
+    /// Backend the metrics service reports the floods under
+    pub backend_id: String,
+    /// Host header of HTTP floods
+    #[serde(default)]
+    pub http_host: Option<String>,
+    /// gRPC endpoint of the metrics service; the report only has client-side
+    /// results without it
+    #[serde(default)]
+    pub metrics_endpoint: Option<String>,
+    /// Highest rate of any phase
+    #[serde(default = "default_max_pps")]
+    pub max_pps: u64,
+    /// Pause after each phase so mitigation state and metrics settle
+    #[serde(default = "default_settle_secs")]
+    pub settle_secs: u64,
+    pub phases: Vec<Phase>,
+}
+
+fn default_max_pps() -> u64 {
+    100_000
+}
+
+fn default_settle_secs() -> u64 {
+    30
+}
+
+impl Scenario {
+    /// Load a scenario file, with `PISTON_SIM__*` environment overrides
+    pub fn load(path: &str) -> Result<Self> {
+        let scenario: Scenario = config::Config::builder()
+            .add_source(config::File::with_name(path))
+            .add_source(
+                config::Environment::with_prefix("PISTON_SIM")
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()?
+            .try_deserialize()?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Check the target is allowed and every phase is within limits
+    pub fn validate(&self) -> Result<()> {
+        if !self
+            .allowed_targets
+            .iter()
+            .any(|network| network.contains(self.target))
+        {
+            return Err(SimError::Scenario(format!(
+                "Target {} is not in allowed_targets",
+                self.target
+            )));
+        }
+        if self.max_pps > MAX_PPS {
+            return Err(SimError::Scenario(format!(
+                "max_pps cannot exceed {}",
+                MAX_PPS
+            )));
+        }
+        if self.phases.is_empty() {
+            return Err(SimError::Scenario("Scenario has no phases".to_string()));
+        }
+
+        for phase in &self.phases {
+            let invalid = |reason: String| {
+                Err(SimError::Scenario(format!(
+                    "Phase {}: {}",
+                    phase.name, reason
+                )))
+            };
+            if phase.pps == 0 || phase.pps > self.max_pps {
+                return invalid(format!("pps must be between 1 and {}", self.max_pps));
+            }
+            if phase.duration_secs == 0 || phase.duration_secs > MAX_PHASE_SECS {
+                return invalid(format!(
+                    "duration_secs must be between 1 and {}",
+                    MAX_PHASE_SECS
+                ));
+            }
+            if phase.port == 0 {
+                return invalid("port is required".to_string());
+            }
+            if phase.kind == FloodKind::Syn && !self.target.is_ipv4() {
+                return invalid("SYN floods need an IPv4 target".to_string());
+            }
+            if phase.kind == FloodKind::Udp && !(1..=1472).contains(&phase.payload_size) {
+                return invalid("payload_size must be between 1 and 1472".to_string());
+            }
+            if phase.kind.is_connection_based() && phase.concurrency == 0 {
+                return invalid("concurrency must be at least 1".to_string());
+            }
+            if phase
+                .min_effectiveness
+                .is_some_and(|min| !(0.0..=1.0).contains(&min))
+            {
+                return invalid("min_effectiveness must be between 0 and 1".to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario() -> Scenario {
+        Scenario {
+            name: "staging".to_string(),
+            target: "10.20.0.5".parse().unwrap(),
+            allowed_targets: vec!["10.20.0.0/16".parse().unwrap()],
+            backend_id: "backend-1".to_string(),
+            http_host: None,
+            metrics_endpoint: None,
+            max_pps: 50_000,
+            settle_secs: 0,
+            phases: vec![Phase {
+                name: "syn".to_string(),
+                kind: FloodKind::Syn,
+                port: 25565,
+                pps: 10_000,
+                duration_secs: 30,
+                payload_size: default_payload_size(),
+                concurrency: default_concurrency(),
+                min_effectiveness: Some(0.9),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_validate_limits_target_and_rate() {
+        assert!(scenario().validate().is_ok());
+
+        let mut outside = scenario();
+        outside.target = "203.0.113.9".parse().unwrap();
+        assert!(outside.validate().is_err());
+
+        let mut too_fast = scenario();
+        too_fast.phases[0].pps = 60_000;
+        assert!(too_fast.validate().is_err());
+
+        let mut bad_threshold = scenario();
+        bad_threshold.phases[0].min_effectiveness = Some(1.5);
+        assert!(bad_threshold.validate().is_err());
+    }
+}
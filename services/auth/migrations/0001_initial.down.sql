@@ -0,0 +1,26 @@
+-- Revert 0001_initial (development only: drops every auth table)
+-- Dropping the tables drops their indexes and updated_at triggers
+
+DROP TABLE IF EXISTS audit_logs;
+DROP TABLE IF EXISTS invitations;
+DROP TABLE IF EXISTS api_keys;
+DROP TABLE IF EXISTS refresh_tokens;
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS role_assignments;
+DROP TABLE IF EXISTS role_permissions;
+DROP TABLE IF EXISTS permissions;
+DROP TABLE IF EXISTS roles;
+DROP TABLE IF EXISTS organization_members;
+DROP TABLE IF EXISTS organization_usage;
+DROP TABLE IF EXISTS organization_limits;
+DROP TABLE IF EXISTS subscriptions;
+DROP TABLE IF EXISTS organizations;
+DROP TABLE IF EXISTS user_oauth_providers;
+DROP TABLE IF EXISTS users;
+
+DROP FUNCTION IF EXISTS update_updated_at_column();
+
+DROP TYPE IF EXISTS invitation_status;
+DROP TYPE IF EXISTS subscription_status;
+DROP TYPE IF EXISTS organization_role;
+DROP TYPE IF EXISTS user_role;
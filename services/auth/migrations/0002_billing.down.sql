@@ -0,0 +1,20 @@
+-- Revert 0002_billing (development only: drops every billing record)
+
+DROP TABLE IF EXISTS billing_events;
+DROP TABLE IF EXISTS payment_methods;
+DROP TABLE IF EXISTS usage_summaries;
+DROP TABLE IF EXISTS usage_records;
+DROP TABLE IF EXISTS invoices;
+DROP TABLE IF EXISTS plans;
+
+DROP INDEX IF EXISTS idx_subscriptions_stripe_sub;
+ALTER TABLE subscriptions
+    DROP COLUMN IF EXISTS cancellation_reason,
+    DROP COLUMN IF EXISTS stripe_payment_method_id,
+    DROP COLUMN IF EXISTS plan_type,
+    DROP COLUMN IF EXISTS billing_period;
+
+DROP TYPE IF EXISTS usage_metric_type;
+DROP TYPE IF EXISTS invoice_status;
+DROP TYPE IF EXISTS billing_period;
+DROP TYPE IF EXISTS plan_type;
@@ -0,0 +1,233 @@
+-- PistonProtection Auth Service - Billing
+-- Plans, invoices, usage tracking and payment methods, plus the billing
+-- columns of subscriptions
+
+-- Create additional enum types for billing
+DO $$ BEGIN
+    CREATE TYPE plan_type AS ENUM ('free', 'starter', 'pro', 'enterprise');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE billing_period AS ENUM ('monthly', 'yearly');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE invoice_status AS ENUM ('draft', 'open', 'paid', 'uncollectible', 'void');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+DO $$ BEGIN
+    CREATE TYPE usage_metric_type AS ENUM ('requests', 'bandwidth_bytes', 'blocked_requests', 'challenges_served');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+-- Create plans table
+CREATE TABLE IF NOT EXISTS plans (
+    id VARCHAR(36) PRIMARY KEY,
+    name VARCHAR(100) NOT NULL,
+    plan_type plan_type NOT NULL DEFAULT 'starter',
+    description TEXT,
+    stripe_product_id VARCHAR(255) UNIQUE,
+    stripe_price_id_monthly VARCHAR(255),
+    stripe_price_id_yearly VARCHAR(255),
+    price_monthly_cents BIGINT NOT NULL DEFAULT 0,
+    price_yearly_cents BIGINT NOT NULL DEFAULT 0,
+    max_backends INTEGER NOT NULL DEFAULT 3,
+    max_origins_per_backend INTEGER NOT NULL DEFAULT 2,
+    max_domains INTEGER NOT NULL DEFAULT 5,
+    max_filter_rules INTEGER NOT NULL DEFAULT 10,
+    max_bandwidth_bytes BIGINT NOT NULL DEFAULT 10737418240,
+    max_requests BIGINT NOT NULL DEFAULT 1000000,
+    advanced_protection BOOLEAN NOT NULL DEFAULT FALSE,
+    priority_support BOOLEAN NOT NULL DEFAULT FALSE,
+    custom_ssl BOOLEAN NOT NULL DEFAULT FALSE,
+    api_access BOOLEAN NOT NULL DEFAULT TRUE,
+    data_retention_days INTEGER NOT NULL DEFAULT 7,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_plans_type ON plans(plan_type);
+CREATE INDEX IF NOT EXISTS idx_plans_active ON plans(is_active);
+CREATE INDEX IF NOT EXISTS idx_plans_stripe_product ON plans(stripe_product_id);
+
+-- Add billing_period to subscriptions table
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'subscriptions' AND column_name = 'billing_period'
+    ) THEN
+        ALTER TABLE subscriptions ADD COLUMN billing_period billing_period NOT NULL DEFAULT 'monthly';
+    END IF;
+END $$;
+
+-- Add plan_type to subscriptions table
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'subscriptions' AND column_name = 'plan_type'
+    ) THEN
+        ALTER TABLE subscriptions ADD COLUMN plan_type plan_type NOT NULL DEFAULT 'free';
+    END IF;
+END $$;
+
+-- Add stripe_payment_method_id to subscriptions table
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'subscriptions' AND column_name = 'stripe_payment_method_id'
+    ) THEN
+        ALTER TABLE subscriptions ADD COLUMN stripe_payment_method_id VARCHAR(255);
+    END IF;
+END $$;
+
+-- Add cancellation_reason to subscriptions table
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'subscriptions' AND column_name = 'cancellation_reason'
+    ) THEN
+        ALTER TABLE subscriptions ADD COLUMN cancellation_reason TEXT;
+    END IF;
+END $$;
+
+-- Add index on stripe_subscription_id
+CREATE INDEX IF NOT EXISTS idx_subscriptions_stripe_sub ON subscriptions(stripe_subscription_id);
+
+-- Create invoices table
+CREATE TABLE IF NOT EXISTS invoices (
+    id VARCHAR(36) PRIMARY KEY,
+    organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    subscription_id VARCHAR(36) NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    stripe_invoice_id VARCHAR(255) UNIQUE,
+    stripe_payment_intent_id VARCHAR(255),
+    number VARCHAR(100),
+    status invoice_status NOT NULL DEFAULT 'draft',
+    currency VARCHAR(3) NOT NULL DEFAULT 'usd',
+    subtotal_cents BIGINT NOT NULL DEFAULT 0,
+    tax_cents BIGINT NOT NULL DEFAULT 0,
+    total_cents BIGINT NOT NULL DEFAULT 0,
+    amount_paid_cents BIGINT NOT NULL DEFAULT 0,
+    amount_due_cents BIGINT NOT NULL DEFAULT 0,
+    description TEXT,
+    invoice_pdf_url TEXT,
+    hosted_invoice_url TEXT,
+    period_start TIMESTAMPTZ NOT NULL,
+    period_end TIMESTAMPTZ NOT NULL,
+    due_date TIMESTAMPTZ,
+    paid_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_invoices_org ON invoices(organization_id);
+CREATE INDEX IF NOT EXISTS idx_invoices_subscription ON invoices(subscription_id);
+CREATE INDEX IF NOT EXISTS idx_invoices_stripe ON invoices(stripe_invoice_id);
+CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices(status);
+CREATE INDEX IF NOT EXISTS idx_invoices_created ON invoices(created_at);
+
+-- Create usage_records table for detailed usage tracking
+CREATE TABLE IF NOT EXISTS usage_records (
+    id VARCHAR(36) PRIMARY KEY,
+    organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    subscription_id VARCHAR(36) NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    metric_type usage_metric_type NOT NULL,
+    quantity BIGINT NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    stripe_usage_record_id VARCHAR(255),
+    idempotency_key VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_usage_records_org ON usage_records(organization_id);
+CREATE INDEX IF NOT EXISTS idx_usage_records_subscription ON usage_records(subscription_id);
+CREATE INDEX IF NOT EXISTS idx_usage_records_timestamp ON usage_records(timestamp);
+CREATE INDEX IF NOT EXISTS idx_usage_records_metric ON usage_records(metric_type);
+CREATE INDEX IF NOT EXISTS idx_usage_records_idempotency ON usage_records(idempotency_key);
+
+-- Create usage_summaries table for monthly aggregates
+CREATE TABLE IF NOT EXISTS usage_summaries (
+    id VARCHAR(36) PRIMARY KEY,
+    organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    subscription_id VARCHAR(36) NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
+    period_start TIMESTAMPTZ NOT NULL,
+    period_end TIMESTAMPTZ NOT NULL,
+    total_requests BIGINT NOT NULL DEFAULT 0,
+    total_bandwidth_bytes BIGINT NOT NULL DEFAULT 0,
+    total_blocked_requests BIGINT NOT NULL DEFAULT 0,
+    total_challenges_served BIGINT NOT NULL DEFAULT 0,
+    overage_requests BIGINT NOT NULL DEFAULT 0,
+    overage_bandwidth_bytes BIGINT NOT NULL DEFAULT 0,
+    overage_charges_cents BIGINT NOT NULL DEFAULT 0,
+    reported_to_stripe BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(organization_id, period_start, period_end)
+);
+CREATE INDEX IF NOT EXISTS idx_usage_summaries_org ON usage_summaries(organization_id);
+CREATE INDEX IF NOT EXISTS idx_usage_summaries_period ON usage_summaries(period_start, period_end);
+
+-- Create payment_methods table
+CREATE TABLE IF NOT EXISTS payment_methods (
+    id VARCHAR(36) PRIMARY KEY,
+    organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    stripe_payment_method_id VARCHAR(255) NOT NULL UNIQUE,
+    payment_type VARCHAR(50) NOT NULL,
+    card_brand VARCHAR(50),
+    card_last4 VARCHAR(4),
+    card_exp_month INTEGER,
+    card_exp_year INTEGER,
+    is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_payment_methods_org ON payment_methods(organization_id);
+CREATE INDEX IF NOT EXISTS idx_payment_methods_stripe ON payment_methods(stripe_payment_method_id);
+
+-- Create billing_events table for webhook event tracking
+CREATE TABLE IF NOT EXISTS billing_events (
+    id VARCHAR(36) PRIMARY KEY,
+    stripe_event_id VARCHAR(255) NOT NULL UNIQUE,
+    event_type VARCHAR(100) NOT NULL,
+    organization_id VARCHAR(36) REFERENCES organizations(id) ON DELETE SET NULL,
+    subscription_id VARCHAR(36) REFERENCES subscriptions(id) ON DELETE SET NULL,
+    invoice_id VARCHAR(36) REFERENCES invoices(id) ON DELETE SET NULL,
+    payload JSONB NOT NULL,
+    processed BOOLEAN NOT NULL DEFAULT FALSE,
+    processed_at TIMESTAMPTZ,
+    error_message TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_billing_events_stripe ON billing_events(stripe_event_id);
+CREATE INDEX IF NOT EXISTS idx_billing_events_type ON billing_events(event_type);
+CREATE INDEX IF NOT EXISTS idx_billing_events_processed ON billing_events(processed);
+CREATE INDEX IF NOT EXISTS idx_billing_events_created ON billing_events(created_at);
+
+-- Insert default plans if they don't exist
+INSERT INTO plans (id, name, plan_type, description, price_monthly_cents, price_yearly_cents,
+    max_backends, max_origins_per_backend, max_domains, max_filter_rules,
+    max_bandwidth_bytes, max_requests, advanced_protection, priority_support,
+    custom_ssl, api_access, data_retention_days, is_active)
+VALUES
+    ('plan_free', 'Free', 'free', 'Get started with basic DDoS protection',
+     0, 0, 1, 1, 1, 5, 1073741824, 100000, false, false, false, false, 1, true),
+    ('plan_starter', 'Starter', 'starter', 'For small websites and applications',
+     2900, 29000, 3, 2, 5, 20, 10737418240, 1000000, false, false, false, true, 7, true),
+    ('plan_pro', 'Pro', 'pro', 'For growing businesses',
+     9900, 99000, 10, 5, 20, 100, 107374182400, 10000000, true, false, true, true, 30, true),
+    ('plan_enterprise', 'Enterprise', 'enterprise', 'For large organizations with custom needs',
+     29900, 299000, 100, 20, 100, 1000, 1099511627776, 100000000, true, true, true, true, 365, true)
+ON CONFLICT (id) DO UPDATE SET
+    name = EXCLUDED.name,
+    description = EXCLUDED.description,
+    price_monthly_cents = EXCLUDED.price_monthly_cents,
+    price_yearly_cents = EXCLUDED.price_yearly_cents,
+    updated_at = NOW();
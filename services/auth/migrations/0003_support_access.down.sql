@@ -0,0 +1,3 @@
+-- Revert 0003_support_access (development only: drops every support access grant)
+
+DROP TABLE IF EXISTS support_access_grants;
@@ -0,0 +1,18 @@
+-- PistonProtection Auth Service - Support access
+-- Time-limited, read-only access customers grant PistonProtection support
+-- staff to their organization. Staff exchange an active grant for a
+-- short-lived support token, which the gateway accepts for reads only.
+
+CREATE TABLE IF NOT EXISTS support_access_grants (
+    id VARCHAR(36) PRIMARY KEY,
+    organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    granted_by_user_id VARCHAR(36) NOT NULL REFERENCES users(id),
+    reason TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    revoked_by_user_id VARCHAR(36) REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_support_access_grants_org
+    ON support_access_grants(organization_id, created_at DESC);
@@ -0,0 +1,4 @@
+-- Revert 0004_session_devices (development only: drops session countries)
+
+DROP INDEX IF EXISTS idx_sessions_user_created;
+ALTER TABLE sessions DROP COLUMN IF EXISTS country_code;
@@ -0,0 +1,9 @@
+-- PistonProtection Auth Service - Session devices
+-- Country each session was opened from (GeoIP, ISO 3166-1 alpha-2), so
+-- users see where they are signed in and logins from a new device or
+-- country can be reported to them.
+
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS country_code VARCHAR(2);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user_created
+    ON sessions(user_id, created_at DESC);
@@ -0,0 +1,4 @@
+-- Revert 0005_refresh_token_rotation (development only: drops rotation links)
+
+DROP INDEX IF EXISTS idx_refresh_tokens_session;
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS replaced_by;
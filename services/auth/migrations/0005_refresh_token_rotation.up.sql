@@ -0,0 +1,8 @@
+-- PistonProtection Auth Service - Refresh token rotation
+-- Every refresh replaces the refresh token it was made with. The replaced
+-- token keeps a link to its successor, so presenting it again is told apart
+-- from a logout and revokes the whole session as a stolen token.
+
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS replaced_by VARCHAR(36);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_session ON refresh_tokens(session_id);
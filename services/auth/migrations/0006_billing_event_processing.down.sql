@@ -0,0 +1,8 @@
+-- Revert 0006_billing_event_processing (development only: drops retry state)
+
+DROP INDEX IF EXISTS idx_billing_events_pending;
+ALTER TABLE billing_events DROP COLUMN IF EXISTS updated_at;
+ALTER TABLE billing_events DROP COLUMN IF EXISTS next_attempt_at;
+ALTER TABLE billing_events DROP COLUMN IF EXISTS attempts;
+ALTER TABLE billing_events DROP COLUMN IF EXISTS status;
+DROP TYPE IF EXISTS billing_event_status;
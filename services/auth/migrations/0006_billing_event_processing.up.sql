@@ -0,0 +1,20 @@
+-- PistonProtection Auth Service - Billing event processing
+-- Stripe webhook events are stored before they are processed and move
+-- through received -> processing -> processed. A failed event is retried
+-- from its stored payload with backoff until it is given up on as dead.
+
+DO $$ BEGIN
+    CREATE TYPE billing_event_status AS ENUM ('received', 'processing', 'processed', 'failed', 'dead');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+ALTER TABLE billing_events ADD COLUMN IF NOT EXISTS status billing_event_status NOT NULL DEFAULT 'received';
+ALTER TABLE billing_events ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE billing_events ADD COLUMN IF NOT EXISTS next_attempt_at TIMESTAMPTZ;
+ALTER TABLE billing_events ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+UPDATE billing_events SET status = 'processed' WHERE processed AND status = 'received';
+
+CREATE INDEX IF NOT EXISTS idx_billing_events_pending
+    ON billing_events(status, next_attempt_at);
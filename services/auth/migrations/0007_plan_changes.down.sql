@@ -0,0 +1,6 @@
+-- Revert 0007_plan_changes (development only: drops scheduled downgrades)
+
+DROP INDEX IF EXISTS idx_subscriptions_scheduled_change;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS scheduled_change_at;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS scheduled_billing_period;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS scheduled_plan_id;
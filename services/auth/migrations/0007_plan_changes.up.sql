@@ -0,0 +1,10 @@
+-- PistonProtection Auth Service - Plan changes
+-- A downgrade takes effect at the end of the current period; until then the
+-- target plan is kept on the subscription.
+
+ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS scheduled_plan_id VARCHAR(36);
+ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS scheduled_billing_period billing_period;
+ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS scheduled_change_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_subscriptions_scheduled_change
+    ON subscriptions(scheduled_change_at) WHERE scheduled_change_at IS NOT NULL;
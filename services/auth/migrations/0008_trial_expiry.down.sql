@@ -0,0 +1,8 @@
+-- Revert 0008_trial_expiry (development only: drops trial expiry state)
+
+DROP INDEX IF EXISTS idx_subscriptions_trial_ends;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS protection_suspended_at;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS trial_grace_ends_at;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS trial_reminder_days;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS trial_outcome;
+DROP TYPE IF EXISTS trial_outcome;
@@ -0,0 +1,22 @@
+-- PistonProtection Auth Service - Trial expiry
+-- Tracks where an organization's trial stands once it ends: a grace period,
+-- then a move to the free plan or suspended protection, unless it converted
+-- to a paid subscription first.
+
+DO $$ BEGIN
+    CREATE TYPE trial_outcome AS ENUM ('grace', 'converted', 'downgraded', 'suspended');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS trial_outcome trial_outcome;
+ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS trial_reminder_days INTEGER;
+ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS trial_grace_ends_at TIMESTAMPTZ;
+ALTER TABLE subscriptions ADD COLUMN IF NOT EXISTS protection_suspended_at TIMESTAMPTZ;
+
+-- Trials that ended before this migration were handled by hand
+UPDATE subscriptions SET trial_outcome = 'converted'
+WHERE trial_ends_at <= NOW() AND status <> 'trialing' AND trial_outcome IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_subscriptions_trial_ends
+    ON subscriptions(trial_ends_at) WHERE trial_ends_at IS NOT NULL;
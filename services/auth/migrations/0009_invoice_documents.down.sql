@@ -0,0 +1,14 @@
+-- Revert 0009_invoice_documents (development only: drops invoice line items and PDF state)
+
+DROP INDEX IF EXISTS idx_invoices_pdf_pending;
+ALTER TABLE invoices DROP COLUMN IF EXISTS pdf_generated_at;
+ALTER TABLE invoices DROP COLUMN IF EXISTS pdf_url;
+ALTER TABLE invoices DROP COLUMN IF EXISTS pdf_object_key;
+ALTER TABLE invoices DROP COLUMN IF EXISTS vat_reverse_charge;
+ALTER TABLE invoices DROP COLUMN IF EXISTS vat_rate_bps;
+ALTER TABLE invoices DROP COLUMN IF EXISTS customer_vat_id;
+ALTER TABLE invoices DROP COLUMN IF EXISTS customer_country;
+ALTER TABLE invoices DROP COLUMN IF EXISTS customer_address;
+ALTER TABLE invoices DROP COLUMN IF EXISTS customer_name;
+DROP TABLE IF EXISTS invoice_line_items;
+DROP TYPE IF EXISTS invoice_line_kind;
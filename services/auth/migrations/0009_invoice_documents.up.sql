@@ -0,0 +1,37 @@
+-- PistonProtection Auth Service - Invoice documents
+-- Keeps the line items and the customer details of Stripe invoices, so
+-- invoices can be rendered as PDFs with the VAT of the customer's country,
+-- and where the rendered PDF is stored.
+
+DO $$ BEGIN
+    CREATE TYPE invoice_line_kind AS ENUM ('subscription', 'proration', 'overage', 'other');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+
+CREATE TABLE IF NOT EXISTS invoice_line_items (
+    id VARCHAR(36) PRIMARY KEY,
+    invoice_id VARCHAR(36) NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0,
+    description TEXT NOT NULL,
+    kind invoice_line_kind NOT NULL DEFAULT 'other',
+    quantity BIGINT NOT NULL DEFAULT 0,
+    unit_amount_cents BIGINT,
+    amount_cents BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_invoice_line_items_invoice ON invoice_line_items(invoice_id);
+
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS customer_name TEXT;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS customer_address TEXT;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS customer_country VARCHAR(2);
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS customer_vat_id VARCHAR(64);
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS vat_rate_bps INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS vat_reverse_charge BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS pdf_object_key TEXT;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS pdf_url TEXT;
+ALTER TABLE invoices ADD COLUMN IF NOT EXISTS pdf_generated_at TIMESTAMPTZ;
+
+-- Invoices still to be rendered
+CREATE INDEX IF NOT EXISTS idx_invoices_pdf_pending
+    ON invoices(updated_at) WHERE status IN ('open', 'paid');
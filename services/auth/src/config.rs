@@ -1,5 +1,7 @@
 //! Authentication service configuration
 
+use pistonprotection_common::config::secrets::Secrets;
+use pistonprotection_common::telemetry;
 use serde::Deserialize;
 use std::env;
 
@@ -33,6 +35,18 @@ pub struct AuthConfig {
     /// Stripe configuration
     #[serde(default)]
     pub stripe: StripeConfig,
+
+    /// Security analytics configuration
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    /// Trial expiry configuration
+    #[serde(default)]
+    pub trial: TrialConfig,
+
+    /// Invoice document configuration
+    #[serde(default)]
+    pub invoices: InvoiceConfig,
 }
 
 /// JWT configuration
@@ -370,6 +384,267 @@ fn default_member_role() -> String {
     "member".to_string()
 }
 
+/// Security analytics configuration
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecurityConfig {
+    /// Analyze logins and API key usage for attacks
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Travel speed between two logins above which it is impossible (km/h)
+    #[serde(default = "default_impossible_travel_speed")]
+    pub impossible_travel_speed_kmh: f64,
+
+    /// Logins closer than this are never impossible travel (km), as GeoIP
+    /// locations are only accurate to a region
+    #[serde(default = "default_impossible_travel_min_distance")]
+    pub impossible_travel_min_distance_km: f64,
+
+    /// Window in which failed logins from one IP are counted in seconds
+    #[serde(default = "default_stuffing_window")]
+    pub stuffing_window_secs: u64,
+
+    /// Distinct accounts failing to log in from one IP within the window
+    /// that make it credential stuffing
+    #[serde(default = "default_stuffing_threshold")]
+    pub stuffing_account_threshold: usize,
+
+    /// How long the networks (ASNs) an API key was used from are remembered
+    /// in seconds
+    #[serde(default = "default_api_key_asn_ttl")]
+    pub api_key_asn_ttl_secs: u64,
+
+    /// Metrics service endpoint security alerts are posted to
+    #[serde(default)]
+    pub alert_url: Option<String>,
+
+    /// Bearer token for the alert endpoint
+    #[serde(default)]
+    pub alert_token: String,
+
+    /// Control plane gRPC address; when set, credential stuffing IPs are
+    /// blocked at the XDP layer
+    #[serde(default)]
+    pub block_endpoint: Option<String>,
+
+    /// How long an auto-blocked IP stays blocked in seconds
+    #[serde(default = "default_block_duration")]
+    pub block_duration_secs: u32,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            impossible_travel_speed_kmh: default_impossible_travel_speed(),
+            impossible_travel_min_distance_km: default_impossible_travel_min_distance(),
+            stuffing_window_secs: default_stuffing_window(),
+            stuffing_account_threshold: default_stuffing_threshold(),
+            api_key_asn_ttl_secs: default_api_key_asn_ttl(),
+            alert_url: None,
+            alert_token: String::new(),
+            block_endpoint: None,
+            block_duration_secs: default_block_duration(),
+        }
+    }
+}
+
+fn default_impossible_travel_speed() -> f64 {
+    1000.0 // Faster than a commercial flight
+}
+
+fn default_impossible_travel_min_distance() -> f64 {
+    500.0
+}
+
+fn default_stuffing_window() -> u64 {
+    600 // 10 minutes
+}
+
+fn default_stuffing_threshold() -> usize {
+    20
+}
+
+fn default_api_key_asn_ttl() -> u64 {
+    90 * 86400 // 90 days
+}
+
+fn default_block_duration() -> u32 {
+    3600 // 1 hour
+}
+
+/// What happens to an organization whose trial ended without converting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TrialExpiryAction {
+    /// Move the organization to the free plan
+    #[default]
+    Downgrade,
+    /// Keep the plan but stop filtering its traffic
+    Suspend,
+}
+
+/// Trial expiry configuration
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrialConfig {
+    /// Send trial reminders and act on expired trials
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Days before the trial ends at which a reminder is sent
+    #[serde(default = "default_trial_reminder_days")]
+    pub reminder_days: Vec<u32>,
+
+    /// Days between the end of a trial and the expiry action
+    #[serde(default = "default_trial_grace_period")]
+    pub grace_period_days: u32,
+
+    /// What happens once the grace period is over
+    #[serde(default)]
+    pub expiry_action: TrialExpiryAction,
+
+    /// How often trials are checked in seconds
+    #[serde(default = "default_trial_check_interval")]
+    pub check_interval_secs: u64,
+}
+
+impl Default for TrialConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reminder_days: default_trial_reminder_days(),
+            grace_period_days: default_trial_grace_period(),
+            expiry_action: TrialExpiryAction::default(),
+            check_interval_secs: default_trial_check_interval(),
+        }
+    }
+}
+
+fn default_trial_reminder_days() -> Vec<u32> {
+    vec![7, 3, 1]
+}
+
+fn default_trial_grace_period() -> u32 {
+    3
+}
+
+fn default_trial_check_interval() -> u64 {
+    3600 // 1 hour
+}
+
+/// Invoice document configuration
+#[derive(Debug, Clone, Deserialize)]
+pub struct InvoiceConfig {
+    /// Legal name of the seller printed on invoices
+    #[serde(default = "default_seller_name")]
+    pub seller_name: String,
+
+    /// Postal address of the seller, one entry per line
+    #[serde(default)]
+    pub seller_address: Vec<String>,
+
+    /// ISO country code of the seller; VAT only applies to sellers in the EU
+    #[serde(default)]
+    pub seller_country: String,
+
+    /// VAT identification number of the seller
+    #[serde(default)]
+    pub seller_vat_id: String,
+
+    /// Prefix of the object keys of invoice PDFs
+    #[serde(default = "default_invoice_key_prefix")]
+    pub key_prefix: String,
+
+    /// Object storage the PDFs are kept in
+    #[serde(default)]
+    pub storage: ObjectStorageConfig,
+
+    /// How long a download URL is valid in seconds
+    #[serde(default = "default_invoice_url_ttl")]
+    pub download_url_ttl_secs: u64,
+
+    /// How often new and changed invoices are rendered in seconds
+    #[serde(default = "default_invoice_render_interval")]
+    pub render_interval_secs: u64,
+}
+
+impl Default for InvoiceConfig {
+    fn default() -> Self {
+        Self {
+            seller_name: default_seller_name(),
+            seller_address: Vec::new(),
+            seller_country: String::new(),
+            seller_vat_id: String::new(),
+            key_prefix: default_invoice_key_prefix(),
+            storage: ObjectStorageConfig::default(),
+            download_url_ttl_secs: default_invoice_url_ttl(),
+            render_interval_secs: default_invoice_render_interval(),
+        }
+    }
+}
+
+fn default_seller_name() -> String {
+    "PistonProtection".to_string()
+}
+
+fn default_invoice_key_prefix() -> String {
+    "invoices".to_string()
+}
+
+fn default_invoice_url_ttl() -> u64 {
+    900 // 15 minutes
+}
+
+fn default_invoice_render_interval() -> u64 {
+    300 // 5 minutes
+}
+
+/// S3-compatible object storage configuration
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObjectStorageConfig {
+    /// Endpoint URL, e.g. `https://s3.eu-central-1.amazonaws.com`; storage is
+    /// disabled when empty
+    #[serde(default)]
+    pub endpoint: String,
+
+    /// Region requests are signed for
+    #[serde(default = "default_storage_region")]
+    pub region: String,
+
+    /// Bucket name
+    #[serde(default)]
+    pub bucket: String,
+
+    /// Access key ID
+    #[serde(default)]
+    pub access_key_id: String,
+
+    /// Secret access key
+    #[serde(default)]
+    pub secret_access_key: String,
+
+    /// Address the bucket in the path instead of the host name (MinIO)
+    #[serde(default)]
+    pub path_style: bool,
+}
+
+impl Default for ObjectStorageConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            region: default_storage_region(),
+            bucket: String::new(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            path_style: false,
+        }
+    }
+}
+
+fn default_storage_region() -> String {
+    "us-east-1".to_string()
+}
+
 /// Stripe configuration for billing integration
 #[derive(Debug, Clone, Deserialize)]
 pub struct StripeConfig {
@@ -408,6 +683,19 @@ pub struct StripeConfig {
     /// Billing portal configuration
     #[serde(default)]
     pub billing_portal: StripeBillingPortalConfig,
+
+    /// Attempts at processing a webhook event before it is given up on
+    #[serde(default = "default_webhook_max_attempts")]
+    pub webhook_max_attempts: u32,
+
+    /// How often failed webhook events are retried in seconds
+    #[serde(default = "default_webhook_retry_interval")]
+    pub webhook_retry_interval_secs: u64,
+
+    /// How often subscriptions and open invoices are reconciled with Stripe
+    /// in seconds
+    #[serde(default = "default_reconcile_interval")]
+    pub reconcile_interval_secs: u64,
 }
 
 impl Default for StripeConfig {
@@ -422,6 +710,9 @@ impl Default for StripeConfig {
             plans: StripePlansConfig::default(),
             checkout: StripeCheckoutConfig::default(),
             billing_portal: StripeBillingPortalConfig::default(),
+            webhook_max_attempts: default_webhook_max_attempts(),
+            webhook_retry_interval_secs: default_webhook_retry_interval(),
+            reconcile_interval_secs: default_reconcile_interval(),
         }
     }
 }
@@ -438,6 +729,18 @@ fn default_stripe_webhook_secret() -> String {
     env::var("STRIPE_WEBHOOK_SECRET").unwrap_or_else(|_| String::new())
 }
 
+fn default_webhook_max_attempts() -> u32 {
+    8
+}
+
+fn default_webhook_retry_interval() -> u64 {
+    60 // 1 minute
+}
+
+fn default_reconcile_interval() -> u64 {
+    21600 // 6 hours
+}
+
 fn default_trial_days() -> u32 {
     14
 }
@@ -678,6 +981,39 @@ impl AuthConfig {
         // Try to deserialize, fall back to defaults
         cfg.try_deserialize().or_else(|_| Ok(AuthConfig::default()))
     }
+
+    /// Replace the JWT secret, OAuth client secrets, Stripe keys and the
+    /// security alert token referring to Kubernetes Secrets or Vault with the
+    /// secrets, and keep all of them out of the logs
+    pub async fn resolve_secrets(
+        &mut self,
+        secrets: &Secrets,
+    ) -> pistonprotection_common::error::Result<()> {
+        let oauth = [
+            &mut self.oauth.github,
+            &mut self.oauth.google,
+            &mut self.oauth.gitlab,
+        ];
+        let values = [
+            &mut self.jwt.secret,
+            &mut self.stripe.secret_key,
+            &mut self.stripe.webhook_secret,
+            &mut self.security.alert_token,
+            &mut self.invoices.storage.secret_access_key,
+        ]
+        .into_iter()
+        .chain(
+            oauth
+                .into_iter()
+                .flatten()
+                .map(|provider| &mut provider.client_secret),
+        );
+        for value in values {
+            secrets.resolve_in_place(value).await?;
+            telemetry::register_secret(value);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -751,4 +1087,31 @@ mod tests {
         config.webhook_secret = "whsec_123".to_string();
         assert!(config.is_configured());
     }
+
+    #[test]
+    fn test_security_config_defaults() {
+        let security = SecurityConfig::default();
+        assert!(security.enabled);
+        assert_eq!(security.stuffing_account_threshold, 20);
+        assert!(security.alert_url.is_none());
+        assert!(security.block_endpoint.is_none());
+    }
+
+    #[test]
+    fn test_trial_config_defaults() {
+        let trial = TrialConfig::default();
+        assert!(trial.enabled);
+        assert_eq!(trial.reminder_days, vec![7, 3, 1]);
+        assert_eq!(trial.grace_period_days, 3);
+        assert_eq!(trial.expiry_action, TrialExpiryAction::Downgrade);
+    }
+
+    #[test]
+    fn test_invoice_config_defaults() {
+        let invoices = InvoiceConfig::default();
+        assert_eq!(invoices.key_prefix, "invoices");
+        assert_eq!(invoices.download_url_ttl_secs, 900);
+        assert!(invoices.storage.endpoint.is_empty());
+        assert_eq!(invoices.storage.region, "us-east-1");
+    }
 }
@@ -1,749 +1,552 @@
 //! Database migrations for the auth service
+//!
+//! Migrations are numbered files in `auth/migrations`, embedded at build
+//! time: `NNNN_name.up.sql` applies a change and `NNNN_name.down.sql`
+//! reverts it. Applied versions are recorded with their checksum in the
+//! `schema_version` table. Each migration runs in its own transaction while
+//! an advisory lock is held, so replicas starting together apply it once.
+//!
+//! `PISTON_AUTH_MIGRATIONS` selects what happens at startup:
+//!
+//! - `apply` (default): apply pending migrations and start the service
+//! - `plan`: print the pending migrations and exit
+//! - `down:<version>`: revert to `<version>` and exit (development only)
+//! - `plan-down:<version>`: print what `down:<version>` would revert and exit
+//!
+//! Databases created by the inline migrations of earlier releases have no
+//! `schema_version` rows yet. Every statement of 0001 and 0002 is idempotent
+//! apart from the `updated_at` triggers, which those databases lack, so both
+//! migrations apply cleanly on top of them.
+
+use chrono::{DateTime, Utc};
+use sqlx::migrate::{Migration, Migrator};
+use sqlx::{PgConnection, PgPool};
+use std::fmt;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Migrations embedded from `auth/migrations`
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Advisory lock held while migrating ("pp_auth_" as bytes)
+const MIGRATION_LOCK_ID: i64 = 0x70705f6175746800;
+
+/// What to do with the schema at startup (`PISTON_AUTH_MIGRATIONS`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MigrationMode {
+    /// Apply pending migrations
+    #[default]
+    Apply,
+    /// Print the pending migrations
+    Plan,
+    /// Revert to a version
+    Down(i64),
+    /// Print what reverting to a version would do
+    PlanDown(i64),
+}
 
-use sqlx::PgPool;
-use tracing::info;
-
-/// Run database migrations
-pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
-    info!("Running auth service database migrations");
-
-    // Create custom types
-    sqlx::query(
-        r#"
-        DO $$ BEGIN
-            CREATE TYPE user_role AS ENUM ('user', 'admin');
-        EXCEPTION
-            WHEN duplicate_object THEN null;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        DO $$ BEGIN
-            CREATE TYPE organization_role AS ENUM ('owner', 'admin', 'member', 'viewer');
-        EXCEPTION
-            WHEN duplicate_object THEN null;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        DO $$ BEGIN
-            CREATE TYPE subscription_status AS ENUM ('active', 'trialing', 'past_due', 'canceled', 'unpaid');
-        EXCEPTION
-            WHEN duplicate_object THEN null;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
+impl MigrationMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        match value {
+            "" | "apply" => Some(Self::Apply),
+            "plan" => Some(Self::Plan),
+            _ => {
+                if let Some(version) = value.strip_prefix("down:") {
+                    version.parse().ok().map(Self::Down)
+                } else if let Some(version) = value.strip_prefix("plan-down:") {
+                    version.parse().ok().map(Self::PlanDown)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Mode from `PISTON_AUTH_MIGRATIONS`
+    pub fn from_env() -> Result<Self, MigrationError> {
+        match std::env::var("PISTON_AUTH_MIGRATIONS") {
+            Ok(value) => Self::parse(&value).ok_or(MigrationError::InvalidMode(value)),
+            Err(_) => Ok(Self::Apply),
+        }
+    }
+}
 
-    sqlx::query(
-        r#"
-        DO $$ BEGIN
-            CREATE TYPE invitation_status AS ENUM ('pending', 'accepted', 'expired', 'revoked');
-        EXCEPTION
-            WHEN duplicate_object THEN null;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Direction of a migration step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+}
 
-    // Create users table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id VARCHAR(36) PRIMARY KEY,
-            email VARCHAR(255) NOT NULL UNIQUE,
-            username VARCHAR(50) NOT NULL UNIQUE,
-            name VARCHAR(100) NOT NULL,
-            avatar_url TEXT,
-            password_hash TEXT,
-            email_verified BOOLEAN NOT NULL DEFAULT FALSE,
-            two_factor_enabled BOOLEAN NOT NULL DEFAULT FALSE,
-            two_factor_secret TEXT,
-            role user_role NOT NULL DEFAULT 'user',
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            last_login_at TIMESTAMPTZ,
-            deleted_at TIMESTAMPTZ
-        );
-        CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
-        CREATE INDEX IF NOT EXISTS idx_users_username ON users(username);
-        CREATE INDEX IF NOT EXISTS idx_users_deleted_at ON users(deleted_at);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// One migration to apply or revert
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStep {
+    pub version: i64,
+    pub description: String,
+    pub direction: Direction,
+}
 
-    // Create user_oauth_providers table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS user_oauth_providers (
-            id VARCHAR(36) PRIMARY KEY,
-            user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-            provider VARCHAR(50) NOT NULL,
-            provider_user_id VARCHAR(255) NOT NULL,
-            access_token TEXT,
-            refresh_token TEXT,
-            expires_at TIMESTAMPTZ,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(provider, provider_user_id)
-        );
-        CREATE INDEX IF NOT EXISTS idx_oauth_user_id ON user_oauth_providers(user_id);
-        CREATE INDEX IF NOT EXISTS idx_oauth_provider ON user_oauth_providers(provider, provider_user_id);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Migrations needed to reach a target version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationPlan {
+    /// Highest applied version, `None` for an empty database
+    pub current: Option<i64>,
+    /// Version after the plan ran, `None` when everything is reverted
+    pub target: Option<i64>,
+    pub steps: Vec<MigrationStep>,
+}
 
-    // Create organizations table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS organizations (
-            id VARCHAR(36) PRIMARY KEY,
-            name VARCHAR(100) NOT NULL,
-            slug VARCHAR(50) NOT NULL UNIQUE,
-            logo_url TEXT,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            deleted_at TIMESTAMPTZ
-        );
-        CREATE INDEX IF NOT EXISTS idx_organizations_slug ON organizations(slug);
-        CREATE INDEX IF NOT EXISTS idx_organizations_deleted_at ON organizations(deleted_at);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+impl MigrationPlan {
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
 
-    // Create subscriptions table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS subscriptions (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            plan_id VARCHAR(50) NOT NULL,
-            plan_name VARCHAR(100) NOT NULL,
-            status subscription_status NOT NULL DEFAULT 'trialing',
-            stripe_customer_id VARCHAR(255),
-            stripe_subscription_id VARCHAR(255),
-            current_period_start TIMESTAMPTZ NOT NULL,
-            current_period_end TIMESTAMPTZ NOT NULL,
-            in_trial BOOLEAN NOT NULL DEFAULT TRUE,
-            trial_ends_at TIMESTAMPTZ,
-            cancel_at_period_end BOOLEAN NOT NULL DEFAULT FALSE,
-            canceled_at TIMESTAMPTZ,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_subscriptions_org ON subscriptions(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_subscriptions_stripe ON subscriptions(stripe_customer_id);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+impl fmt::Display for MigrationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = |v: Option<i64>| v.map_or("none".to_string(), |v| format!("{:04}", v));
+        if self.steps.is_empty() {
+            return write!(
+                f,
+                "Schema at version {}, nothing to do",
+                version(self.current)
+            );
+        }
+        write!(
+            f,
+            "Schema at version {}, migrating to {}:",
+            version(self.current),
+            version(self.target)
+        )?;
+        for step in &self.steps {
+            let action = match step.direction {
+                Direction::Up => "apply ",
+                Direction::Down => "revert",
+            };
+            write!(f, "\n  {} {:04} {}", action, step.version, step.description)?;
+        }
+        Ok(())
+    }
+}
 
-    // Create organization_limits table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS organization_limits (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            max_backends INTEGER NOT NULL DEFAULT 3,
-            max_origins_per_backend INTEGER NOT NULL DEFAULT 2,
-            max_domains INTEGER NOT NULL DEFAULT 5,
-            max_filter_rules INTEGER NOT NULL DEFAULT 10,
-            max_bandwidth_bytes BIGINT NOT NULL DEFAULT 10737418240,
-            max_requests BIGINT NOT NULL DEFAULT 1000000,
-            advanced_protection BOOLEAN NOT NULL DEFAULT FALSE,
-            priority_support BOOLEAN NOT NULL DEFAULT FALSE,
-            custom_ssl BOOLEAN NOT NULL DEFAULT FALSE,
-            api_access BOOLEAN NOT NULL DEFAULT TRUE,
-            data_retention_days INTEGER NOT NULL DEFAULT 7,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(organization_id)
-        );
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Row of the `schema_version` table
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub checksum: Vec<u8>,
+    pub applied_at: DateTime<Utc>,
+    pub execution_ms: i64,
+}
 
-    // Create organization_usage table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS organization_usage (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            backends_count INTEGER NOT NULL DEFAULT 0,
-            domains_count INTEGER NOT NULL DEFAULT 0,
-            filter_rules_count INTEGER NOT NULL DEFAULT 0,
-            bandwidth_used BIGINT NOT NULL DEFAULT 0,
-            requests_used BIGINT NOT NULL DEFAULT 0,
-            usage_reset_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(organization_id)
-        );
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Migration errors
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
 
-    // Create organization_members table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS organization_members (
-            id VARCHAR(36) PRIMARY KEY,
-            user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            role organization_role NOT NULL DEFAULT 'member',
-            joined_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(user_id, organization_id)
-        );
-        CREATE INDEX IF NOT EXISTS idx_org_members_user ON organization_members(user_id);
-        CREATE INDEX IF NOT EXISTS idx_org_members_org ON organization_members(organization_id);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    #[error("Migration {0:04} is applied but missing from this build")]
+    Missing(i64),
 
-    // Create roles table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS roles (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) REFERENCES organizations(id) ON DELETE CASCADE,
-            name VARCHAR(50) NOT NULL,
-            display_name VARCHAR(100) NOT NULL,
-            description TEXT,
-            is_system BOOLEAN NOT NULL DEFAULT FALSE,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(organization_id, name)
-        );
-        CREATE INDEX IF NOT EXISTS idx_roles_org ON roles(organization_id);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    #[error("Migration {0:04} was modified after it was applied")]
+    Modified(i64),
 
-    // Create permissions table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS permissions (
-            id VARCHAR(36) PRIMARY KEY,
-            name VARCHAR(100) NOT NULL UNIQUE,
-            display_name VARCHAR(150) NOT NULL,
-            description TEXT,
-            resource_type VARCHAR(50) NOT NULL,
-            action VARCHAR(50) NOT NULL,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_permissions_resource ON permissions(resource_type);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    #[error("Migration {0:04} has no down migration")]
+    NoDownMigration(i64),
 
-    // Create role_permissions table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS role_permissions (
-            id VARCHAR(36) PRIMARY KEY,
-            role_id VARCHAR(36) NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
-            permission_id VARCHAR(36) NOT NULL REFERENCES permissions(id) ON DELETE CASCADE,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(role_id, permission_id)
-        );
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    #[error("Migration {version:04} failed: {source}")]
+    Failed {
+        version: i64,
+        #[source]
+        source: sqlx::Error,
+    },
 
-    // Create role_assignments table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS role_assignments (
-            id VARCHAR(36) PRIMARY KEY,
-            user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-            role_id VARCHAR(36) NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(user_id, role_id, organization_id)
-        );
-        CREATE INDEX IF NOT EXISTS idx_role_assignments_user ON role_assignments(user_id);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    #[error("Invalid PISTON_AUTH_MIGRATIONS value: {0}")]
+    InvalidMode(String),
+}
 
-    // Create sessions table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS sessions (
-            id VARCHAR(36) PRIMARY KEY,
-            user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-            token_hash VARCHAR(64) NOT NULL UNIQUE,
-            ip_address VARCHAR(45),
-            user_agent TEXT,
-            device_type VARCHAR(20),
-            active BOOLEAN NOT NULL DEFAULT TRUE,
-            expires_at TIMESTAMPTZ NOT NULL,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            last_active_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions(user_id);
-        CREATE INDEX IF NOT EXISTS idx_sessions_token ON sessions(token_hash);
-        CREATE INDEX IF NOT EXISTS idx_sessions_expires ON sessions(expires_at);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Find the file of a migration in one direction
+fn find(migrations: &[Migration], version: i64, direction: Direction) -> Option<&Migration> {
+    migrations.iter().find(|m| {
+        m.version == version
+            && m.migration_type.is_down_migration() == (direction == Direction::Down)
+    })
+}
 
-    // Create refresh_tokens table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS refresh_tokens (
-            id VARCHAR(36) PRIMARY KEY,
-            user_id VARCHAR(36) NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-            session_id VARCHAR(36) NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
-            token_hash VARCHAR(64) NOT NULL UNIQUE,
-            expires_at TIMESTAMPTZ NOT NULL,
-            revoked BOOLEAN NOT NULL DEFAULT FALSE,
-            revoked_at TIMESTAMPTZ,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_refresh_tokens_user ON refresh_tokens(user_id);
-        CREATE INDEX IF NOT EXISTS idx_refresh_tokens_token ON refresh_tokens(token_hash);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Check that every applied migration is part of this build, unchanged
+fn check_applied(
+    migrations: &[Migration],
+    applied: &[AppliedMigration],
+) -> Result<(), MigrationError> {
+    for row in applied {
+        let migration = find(migrations, row.version, Direction::Up)
+            .ok_or(MigrationError::Missing(row.version))?;
+        if *migration.checksum != *row.checksum {
+            return Err(MigrationError::Modified(row.version));
+        }
+    }
+    Ok(())
+}
 
-    // Create api_keys table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS api_keys (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            created_by_user_id VARCHAR(36) NOT NULL REFERENCES users(id),
-            name VARCHAR(100) NOT NULL,
-            prefix VARCHAR(20) NOT NULL,
-            key_hash VARCHAR(64) NOT NULL UNIQUE,
-            permissions JSONB NOT NULL DEFAULT '[]'::JSONB,
-            allowed_ips JSONB NOT NULL DEFAULT '[]'::JSONB,
-            enabled BOOLEAN NOT NULL DEFAULT TRUE,
-            expires_at TIMESTAMPTZ,
-            last_used_at TIMESTAMPTZ,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_api_keys_org ON api_keys(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_api_keys_hash ON api_keys(key_hash);
-        CREATE INDEX IF NOT EXISTS idx_api_keys_prefix ON api_keys(prefix);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Plan applying every pending migration, in version order
+pub fn plan_up(
+    migrations: &[Migration],
+    applied: &[AppliedMigration],
+) -> Result<MigrationPlan, MigrationError> {
+    check_applied(migrations, applied)?;
+
+    let mut steps: Vec<MigrationStep> = migrations
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .filter(|m| !applied.iter().any(|row| row.version == m.version))
+        .map(|m| MigrationStep {
+            version: m.version,
+            description: m.description.to_string(),
+            direction: Direction::Up,
+        })
+        .collect();
+    steps.sort_by_key(|step| step.version);
+
+    let current = applied.iter().map(|row| row.version).max();
+    Ok(MigrationPlan {
+        current,
+        target: steps.last().map(|step| step.version).or(current),
+        steps,
+    })
+}
 
-    // Create invitations table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS invitations (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            email VARCHAR(255) NOT NULL,
-            role organization_role NOT NULL DEFAULT 'member',
-            invited_by_user_id VARCHAR(36) NOT NULL REFERENCES users(id),
-            status invitation_status NOT NULL DEFAULT 'pending',
-            token_hash VARCHAR(64) NOT NULL UNIQUE,
-            expires_at TIMESTAMPTZ NOT NULL,
-            accepted_at TIMESTAMPTZ,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_invitations_org ON invitations(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_invitations_email ON invitations(email);
-        CREATE INDEX IF NOT EXISTS idx_invitations_token ON invitations(token_hash);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Plan reverting every applied migration above `target`, newest first
+pub fn plan_down(
+    migrations: &[Migration],
+    applied: &[AppliedMigration],
+    target: i64,
+) -> Result<MigrationPlan, MigrationError> {
+    check_applied(migrations, applied)?;
+
+    let mut reverted: Vec<&AppliedMigration> =
+        applied.iter().filter(|row| row.version > target).collect();
+    reverted.sort_by_key(|row| std::cmp::Reverse(row.version));
+
+    let steps = reverted
+        .into_iter()
+        .map(|row| {
+            find(migrations, row.version, Direction::Down)
+                .map(|m| MigrationStep {
+                    version: m.version,
+                    description: m.description.to_string(),
+                    direction: Direction::Down,
+                })
+                .ok_or(MigrationError::NoDownMigration(row.version))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(MigrationPlan {
+        current: applied.iter().map(|row| row.version).max(),
+        target: applied
+            .iter()
+            .map(|row| row.version)
+            .filter(|v| *v <= target)
+            .max(),
+        steps,
+    })
+}
 
-    // Create audit_logs table
+/// Create the `schema_version` table
+async fn ensure_version_table(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
     sqlx::query(
         r#"
-        CREATE TABLE IF NOT EXISTS audit_logs (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            user_id VARCHAR(36),
-            user_email VARCHAR(255),
-            action VARCHAR(100) NOT NULL,
-            resource_type VARCHAR(50) NOT NULL,
-            resource_id VARCHAR(36),
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version BIGINT PRIMARY KEY,
             description TEXT NOT NULL,
-            metadata JSONB NOT NULL DEFAULT '{}'::JSONB,
-            ip_address VARCHAR(45),
-            user_agent TEXT,
-            timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_audit_logs_org ON audit_logs(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_audit_logs_user ON audit_logs(user_id);
-        CREATE INDEX IF NOT EXISTS idx_audit_logs_timestamp ON audit_logs(timestamp);
-        CREATE INDEX IF NOT EXISTS idx_audit_logs_action ON audit_logs(action);
+            checksum BYTEA NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            execution_ms BIGINT NOT NULL
+        )
         "#,
     )
-    .execute(pool)
+    .execute(conn)
     .await?;
-
-    // Run billing-related migrations
-    run_billing_migrations(pool).await?;
-
-    info!("Auth service database migrations completed");
     Ok(())
 }
 
-/// Run billing and subscription related migrations
-async fn run_billing_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
-    info!("Running billing migrations");
-
-    // Create additional enum types for billing
-    sqlx::query(
-        r#"
-        DO $$ BEGIN
-            CREATE TYPE plan_type AS ENUM ('free', 'starter', 'pro', 'enterprise');
-        EXCEPTION
-            WHEN duplicate_object THEN null;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        DO $$ BEGIN
-            CREATE TYPE billing_period AS ENUM ('monthly', 'yearly');
-        EXCEPTION
-            WHEN duplicate_object THEN null;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        DO $$ BEGIN
-            CREATE TYPE invoice_status AS ENUM ('draft', 'open', 'paid', 'uncollectible', 'void');
-        EXCEPTION
-            WHEN duplicate_object THEN null;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        DO $$ BEGIN
-            CREATE TYPE usage_metric_type AS ENUM ('requests', 'bandwidth_bytes', 'blocked_requests', 'challenges_served');
-        EXCEPTION
-            WHEN duplicate_object THEN null;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    // Create plans table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS plans (
-            id VARCHAR(36) PRIMARY KEY,
-            name VARCHAR(100) NOT NULL,
-            plan_type plan_type NOT NULL DEFAULT 'starter',
-            description TEXT,
-            stripe_product_id VARCHAR(255) UNIQUE,
-            stripe_price_id_monthly VARCHAR(255),
-            stripe_price_id_yearly VARCHAR(255),
-            price_monthly_cents BIGINT NOT NULL DEFAULT 0,
-            price_yearly_cents BIGINT NOT NULL DEFAULT 0,
-            max_backends INTEGER NOT NULL DEFAULT 3,
-            max_origins_per_backend INTEGER NOT NULL DEFAULT 2,
-            max_domains INTEGER NOT NULL DEFAULT 5,
-            max_filter_rules INTEGER NOT NULL DEFAULT 10,
-            max_bandwidth_bytes BIGINT NOT NULL DEFAULT 10737418240,
-            max_requests BIGINT NOT NULL DEFAULT 1000000,
-            advanced_protection BOOLEAN NOT NULL DEFAULT FALSE,
-            priority_support BOOLEAN NOT NULL DEFAULT FALSE,
-            custom_ssl BOOLEAN NOT NULL DEFAULT FALSE,
-            api_access BOOLEAN NOT NULL DEFAULT TRUE,
-            data_retention_days INTEGER NOT NULL DEFAULT 7,
-            is_active BOOLEAN NOT NULL DEFAULT TRUE,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_plans_type ON plans(plan_type);
-        CREATE INDEX IF NOT EXISTS idx_plans_active ON plans(is_active);
-        CREATE INDEX IF NOT EXISTS idx_plans_stripe_product ON plans(stripe_product_id);
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    // Add billing_period to subscriptions table
-    sqlx::query(
-        r#"
-        DO $$
-        BEGIN
-            IF NOT EXISTS (
-                SELECT 1 FROM information_schema.columns
-                WHERE table_name = 'subscriptions' AND column_name = 'billing_period'
-            ) THEN
-                ALTER TABLE subscriptions ADD COLUMN billing_period billing_period NOT NULL DEFAULT 'monthly';
-            END IF;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Applied migrations, empty when `schema_version` does not exist yet
+async fn applied_migrations(conn: &mut PgConnection) -> Result<Vec<AppliedMigration>, sqlx::Error> {
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('schema_version') IS NOT NULL")
+        .fetch_one(&mut *conn)
+        .await?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query_as(
+        "SELECT version, description, checksum, applied_at, execution_ms \
+         FROM schema_version ORDER BY version",
+    )
+    .fetch_all(conn)
+    .await
+}
 
-    // Add plan_type to subscriptions table
-    sqlx::query(
-        r#"
-        DO $$
-        BEGIN
-            IF NOT EXISTS (
-                SELECT 1 FROM information_schema.columns
-                WHERE table_name = 'subscriptions' AND column_name = 'plan_type'
-            ) THEN
-                ALTER TABLE subscriptions ADD COLUMN plan_type plan_type NOT NULL DEFAULT 'free';
-            END IF;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Run one step in a transaction and record it in `schema_version`
+async fn execute_step(
+    conn: &mut PgConnection,
+    migrations: &[Migration],
+    step: &MigrationStep,
+) -> Result<(), MigrationError> {
+    let migration = find(migrations, step.version, step.direction)
+        .ok_or(MigrationError::NoDownMigration(step.version))?;
+    let started = Instant::now();
+    let failed = |source| MigrationError::Failed {
+        version: step.version,
+        source,
+    };
+
+    let mut tx = sqlx::Connection::begin(conn).await?;
+    sqlx::raw_sql(&migration.sql)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+
+    let execution_ms = started.elapsed().as_millis() as i64;
+    match step.direction {
+        Direction::Up => {
+            sqlx::query(
+                "INSERT INTO schema_version (version, description, checksum, execution_ms) \
+                 VALUES ($1, $2, $3, $4)",
+            )
+            .bind(migration.version)
+            .bind(migration.description.as_ref())
+            .bind(migration.checksum.as_ref())
+            .bind(execution_ms)
+            .execute(&mut *tx)
+            .await?;
+        }
+        Direction::Down => {
+            sqlx::query("DELETE FROM schema_version WHERE version = $1")
+                .bind(migration.version)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    tx.commit().await?;
+
+    info!(
+        "{} migration {:04} {} in {}ms",
+        match step.direction {
+            Direction::Up => "Applied",
+            Direction::Down => "Reverted",
+        },
+        step.version,
+        step.description,
+        execution_ms
+    );
+    Ok(())
+}
 
-    // Add stripe_payment_method_id to subscriptions table
-    sqlx::query(
-        r#"
-        DO $$
-        BEGIN
-            IF NOT EXISTS (
-                SELECT 1 FROM information_schema.columns
-                WHERE table_name = 'subscriptions' AND column_name = 'stripe_payment_method_id'
-            ) THEN
-                ALTER TABLE subscriptions ADD COLUMN stripe_payment_method_id VARCHAR(255);
-            END IF;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Plan with `plan`, then run it under the migration lock
+async fn migrate<F>(pool: &PgPool, plan: F) -> Result<MigrationPlan, MigrationError>
+where
+    F: FnOnce(&[Migration], &[AppliedMigration]) -> Result<MigrationPlan, MigrationError>,
+{
+    let mut conn = pool.acquire().await?;
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(&mut *conn)
+        .await?;
+
+    let result = async {
+        ensure_version_table(&mut conn).await?;
+        let applied = applied_migrations(&mut conn).await?;
+        let plan = plan(MIGRATOR.migrations.as_ref(), &applied)?;
+        for step in &plan.steps {
+            execute_step(&mut conn, MIGRATOR.migrations.as_ref(), step).await?;
+        }
+        Ok(plan)
+    }
+    .await;
+
+    // The migration outcome matters more than the unlock. A connection that
+    // failed to unlock is closed rather than returned to the pool, which
+    // releases its session lock.
+    if let Err(e) = sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(&mut *conn)
+        .await
+    {
+        warn!("Failed to release migration lock: {}", e);
+        conn.close_on_drop();
+    }
+    result
+}
 
-    // Add cancellation_reason to subscriptions table
-    sqlx::query(
-        r#"
-        DO $$
-        BEGIN
-            IF NOT EXISTS (
-                SELECT 1 FROM information_schema.columns
-                WHERE table_name = 'subscriptions' AND column_name = 'cancellation_reason'
-            ) THEN
-                ALTER TABLE subscriptions ADD COLUMN cancellation_reason TEXT;
-            END IF;
-        END $$;
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Run database migrations
+pub async fn run_migrations(pool: &PgPool) -> Result<(), MigrationError> {
+    info!("Running auth service database migrations");
+    let plan = migrate(pool, plan_up).await?;
+    info!(
+        "Auth service database migrations completed ({} applied)",
+        plan.steps.len()
+    );
+    Ok(())
+}
 
-    // Add index on stripe_subscription_id
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_subscriptions_stripe_sub ON subscriptions(stripe_subscription_id);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Revert every migration above `target` (development only)
+pub async fn revert_to(pool: &PgPool, target: i64) -> Result<MigrationPlan, MigrationError> {
+    info!("Reverting auth service database to version {:04}", target);
+    migrate(pool, |migrations, applied| {
+        plan_down(migrations, applied, target)
+    })
+    .await
+}
 
-    // Create invoices table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS invoices (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            subscription_id VARCHAR(36) NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
-            stripe_invoice_id VARCHAR(255) UNIQUE,
-            stripe_payment_intent_id VARCHAR(255),
-            number VARCHAR(100),
-            status invoice_status NOT NULL DEFAULT 'draft',
-            currency VARCHAR(3) NOT NULL DEFAULT 'usd',
-            subtotal_cents BIGINT NOT NULL DEFAULT 0,
-            tax_cents BIGINT NOT NULL DEFAULT 0,
-            total_cents BIGINT NOT NULL DEFAULT 0,
-            amount_paid_cents BIGINT NOT NULL DEFAULT 0,
-            amount_due_cents BIGINT NOT NULL DEFAULT 0,
-            description TEXT,
-            invoice_pdf_url TEXT,
-            hosted_invoice_url TEXT,
-            period_start TIMESTAMPTZ NOT NULL,
-            period_end TIMESTAMPTZ NOT NULL,
-            due_date TIMESTAMPTZ,
-            paid_at TIMESTAMPTZ,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        );
-        CREATE INDEX IF NOT EXISTS idx_invoices_org ON invoices(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_invoices_subscription ON invoices(subscription_id);
-        CREATE INDEX IF NOT EXISTS idx_invoices_stripe ON invoices(stripe_invoice_id);
-        CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices(status);
-        CREATE INDEX IF NOT EXISTS idx_invoices_created ON invoices(created_at);
-        "#,
-    )
-    .execute(pool)
-    .await?;
+/// Migrations `mode` would run, without running them
+pub async fn plan(pool: &PgPool, mode: MigrationMode) -> Result<MigrationPlan, MigrationError> {
+    let mut conn = pool.acquire().await?;
+    let applied = applied_migrations(&mut conn).await?;
+    let migrations = MIGRATOR.migrations.as_ref();
+    match mode {
+        MigrationMode::Down(target) | MigrationMode::PlanDown(target) => {
+            plan_down(migrations, &applied, target)
+        }
+        MigrationMode::Apply | MigrationMode::Plan => plan_up(migrations, &applied),
+    }
+}
 
-    // Create usage_records table for detailed usage tracking
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS usage_records (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            subscription_id VARCHAR(36) NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
-            metric_type usage_metric_type NOT NULL,
-            quantity BIGINT NOT NULL,
-            timestamp TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            stripe_usage_record_id VARCHAR(255),
-            idempotency_key VARCHAR(255),
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::migrate::MigrationType;
+
+    fn migration(version: i64, kind: MigrationType, sql: &'static str) -> Migration {
+        Migration::new(
+            version,
+            format!("step {}", version).into(),
+            kind,
+            sql.into(),
+            false,
+        )
+    }
+
+    fn migrations() -> Vec<Migration> {
+        vec![
+            migration(1, MigrationType::ReversibleUp, "CREATE TABLE a (id INT);"),
+            migration(1, MigrationType::ReversibleDown, "DROP TABLE a;"),
+            migration(2, MigrationType::ReversibleUp, "CREATE TABLE b (id INT);"),
+            migration(2, MigrationType::ReversibleDown, "DROP TABLE b;"),
+            migration(3, MigrationType::ReversibleUp, "CREATE TABLE c (id INT);"),
+        ]
+    }
+
+    fn applied(migration: &Migration) -> AppliedMigration {
+        AppliedMigration {
+            version: migration.version,
+            description: migration.description.to_string(),
+            checksum: migration.checksum.to_vec(),
+            applied_at: Utc::now(),
+            execution_ms: 1,
+        }
+    }
+
+    fn versions(plan: &MigrationPlan) -> Vec<(i64, Direction)> {
+        plan.steps
+            .iter()
+            .map(|s| (s.version, s.direction))
+            .collect()
+    }
+
+    #[test]
+    fn test_embedded_migrations() {
+        let migrations = MIGRATOR.migrations.as_ref();
+        let plan = plan_up(migrations, &[]).unwrap();
+        assert_eq!(plan.current, None);
+        assert_eq!(plan.steps[0].version, 1);
+        assert!(
+            plan.steps
+                .windows(2)
+                .all(|pair| pair[0].version < pair[1].version)
         );
-        CREATE INDEX IF NOT EXISTS idx_usage_records_org ON usage_records(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_usage_records_subscription ON usage_records(subscription_id);
-        CREATE INDEX IF NOT EXISTS idx_usage_records_timestamp ON usage_records(timestamp);
-        CREATE INDEX IF NOT EXISTS idx_usage_records_metric ON usage_records(metric_type);
-        CREATE INDEX IF NOT EXISTS idx_usage_records_idempotency ON usage_records(idempotency_key);
-        "#,
-    )
-    .execute(pool)
-    .await?;
 
-    // Create usage_summaries table for monthly aggregates
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS usage_summaries (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            subscription_id VARCHAR(36) NOT NULL REFERENCES subscriptions(id) ON DELETE CASCADE,
-            period_start TIMESTAMPTZ NOT NULL,
-            period_end TIMESTAMPTZ NOT NULL,
-            total_requests BIGINT NOT NULL DEFAULT 0,
-            total_bandwidth_bytes BIGINT NOT NULL DEFAULT 0,
-            total_blocked_requests BIGINT NOT NULL DEFAULT 0,
-            total_challenges_served BIGINT NOT NULL DEFAULT 0,
-            overage_requests BIGINT NOT NULL DEFAULT 0,
-            overage_bandwidth_bytes BIGINT NOT NULL DEFAULT 0,
-            overage_charges_cents BIGINT NOT NULL DEFAULT 0,
-            reported_to_stripe BOOLEAN NOT NULL DEFAULT FALSE,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE(organization_id, period_start, period_end)
+        // Every migration can be reverted in development
+        let applied: Vec<_> = migrations
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .map(applied)
+            .collect();
+        let down = plan_down(migrations, &applied, 0).unwrap();
+        assert_eq!(down.steps.len(), plan.steps.len());
+        assert_eq!(down.target, None);
+    }
+
+    #[test]
+    fn test_plan_up_skips_applied() {
+        let migrations = migrations();
+        let plan = plan_up(&migrations, &[applied(&migrations[0])]).unwrap();
+        assert_eq!(
+            versions(&plan),
+            vec![(2, Direction::Up), (3, Direction::Up)]
         );
-        CREATE INDEX IF NOT EXISTS idx_usage_summaries_org ON usage_summaries(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_usage_summaries_period ON usage_summaries(period_start, period_end);
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    // Create payment_methods table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS payment_methods (
-            id VARCHAR(36) PRIMARY KEY,
-            organization_id VARCHAR(36) NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
-            stripe_payment_method_id VARCHAR(255) NOT NULL UNIQUE,
-            payment_type VARCHAR(50) NOT NULL,
-            card_brand VARCHAR(50),
-            card_last4 VARCHAR(4),
-            card_exp_month INTEGER,
-            card_exp_year INTEGER,
-            is_default BOOLEAN NOT NULL DEFAULT FALSE,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        assert_eq!(plan.current, Some(1));
+        assert_eq!(plan.target, Some(3));
+        assert!(plan.to_string().contains("apply  0002 step 2"));
+
+        let all: Vec<_> = [0, 2, 4].iter().map(|&i| applied(&migrations[i])).collect();
+        let plan = plan_up(&migrations, &all).unwrap();
+        assert!(plan.is_empty());
+        assert_eq!(plan.to_string(), "Schema at version 0003, nothing to do");
+    }
+
+    #[test]
+    fn test_plan_rejects_changed_history() {
+        let migrations = migrations();
+        let mut modified = applied(&migrations[0]);
+        modified.checksum = vec![0; 48];
+        assert!(matches!(
+            plan_up(&migrations, &[modified]),
+            Err(MigrationError::Modified(1))
+        ));
+
+        let mut missing = applied(&migrations[0]);
+        missing.version = 7;
+        assert!(matches!(
+            plan_up(&migrations, &[missing]),
+            Err(MigrationError::Missing(7))
+        ));
+    }
+
+    #[test]
+    fn test_plan_down() {
+        let migrations = migrations();
+        let two: Vec<_> = [0, 2].iter().map(|&i| applied(&migrations[i])).collect();
+        let plan = plan_down(&migrations, &two, 0).unwrap();
+        assert_eq!(
+            versions(&plan),
+            vec![(2, Direction::Down), (1, Direction::Down)]
         );
-        CREATE INDEX IF NOT EXISTS idx_payment_methods_org ON payment_methods(organization_id);
-        CREATE INDEX IF NOT EXISTS idx_payment_methods_stripe ON payment_methods(stripe_payment_method_id);
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    // Create billing_events table for webhook event tracking
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS billing_events (
-            id VARCHAR(36) PRIMARY KEY,
-            stripe_event_id VARCHAR(255) NOT NULL UNIQUE,
-            event_type VARCHAR(100) NOT NULL,
-            organization_id VARCHAR(36) REFERENCES organizations(id) ON DELETE SET NULL,
-            subscription_id VARCHAR(36) REFERENCES subscriptions(id) ON DELETE SET NULL,
-            invoice_id VARCHAR(36) REFERENCES invoices(id) ON DELETE SET NULL,
-            payload JSONB NOT NULL,
-            processed BOOLEAN NOT NULL DEFAULT FALSE,
-            processed_at TIMESTAMPTZ,
-            error_message TEXT,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        assert_eq!(plan.target, None);
+
+        let plan = plan_down(&migrations, &two, 1).unwrap();
+        assert_eq!(versions(&plan), vec![(2, Direction::Down)]);
+        assert_eq!(plan.target, Some(1));
+
+        // 0003 has no down migration
+        let all: Vec<_> = [0, 2, 4].iter().map(|&i| applied(&migrations[i])).collect();
+        assert!(matches!(
+            plan_down(&migrations, &all, 1),
+            Err(MigrationError::NoDownMigration(3))
+        ));
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(MigrationMode::parse("apply"), Some(MigrationMode::Apply));
+        assert_eq!(MigrationMode::parse(""), Some(MigrationMode::Apply));
+        assert_eq!(MigrationMode::parse("plan"), Some(MigrationMode::Plan));
+        assert_eq!(MigrationMode::parse("down:1"), Some(MigrationMode::Down(1)));
+        assert_eq!(
+            MigrationMode::parse("plan-down:0"),
+            Some(MigrationMode::PlanDown(0))
         );
-        CREATE INDEX IF NOT EXISTS idx_billing_events_stripe ON billing_events(stripe_event_id);
-        CREATE INDEX IF NOT EXISTS idx_billing_events_type ON billing_events(event_type);
-        CREATE INDEX IF NOT EXISTS idx_billing_events_processed ON billing_events(processed);
-        CREATE INDEX IF NOT EXISTS idx_billing_events_created ON billing_events(created_at);
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    // Insert default plans if they don't exist
-    sqlx::query(
-        r#"
-        INSERT INTO plans (id, name, plan_type, description, price_monthly_cents, price_yearly_cents,
-            max_backends, max_origins_per_backend, max_domains, max_filter_rules,
-            max_bandwidth_bytes, max_requests, advanced_protection, priority_support,
-            custom_ssl, api_access, data_retention_days, is_active)
-        VALUES
-            ('plan_free', 'Free', 'free', 'Get started with basic DDoS protection',
-             0, 0, 1, 1, 1, 5, 1073741824, 100000, false, false, false, false, 1, true),
-            ('plan_starter', 'Starter', 'starter', 'For small websites and applications',
-             2900, 29000, 3, 2, 5, 20, 10737418240, 1000000, false, false, false, true, 7, true),
-            ('plan_pro', 'Pro', 'pro', 'For growing businesses',
-             9900, 99000, 10, 5, 20, 100, 107374182400, 10000000, true, false, true, true, 30, true),
-            ('plan_enterprise', 'Enterprise', 'enterprise', 'For large organizations with custom needs',
-             29900, 299000, 100, 20, 100, 1000, 1099511627776, 100000000, true, true, true, true, 365, true)
-        ON CONFLICT (id) DO UPDATE SET
-            name = EXCLUDED.name,
-            description = EXCLUDED.description,
-            price_monthly_cents = EXCLUDED.price_monthly_cents,
-            price_yearly_cents = EXCLUDED.price_yearly_cents,
-            updated_at = NOW();
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    info!("Billing migrations completed");
-    Ok(())
+        assert_eq!(MigrationMode::parse("down:latest"), None);
+        assert_eq!(MigrationMode::parse("rollback"), None);
+    }
 }
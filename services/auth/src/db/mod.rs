@@ -3,5 +3,5 @@
 pub mod migrations;
 pub mod queries;
 
-pub use migrations::run_migrations;
+pub use migrations::{MigrationMode, run_migrations};
 pub use queries::*;
@@ -1,7 +1,8 @@
 //! Database queries for the auth service
 
 use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use pistonprotection_common::pagination::{Page, PageRequest};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
 use std::collections::HashMap;
 
 use crate::models::*;
@@ -561,6 +562,198 @@ pub async fn count_active_sessions(pool: &PgPool, user_id: &str) -> Result<i64,
     Ok(count.0)
 }
 
+/// Get session by ID
+pub async fn get_session(pool: &PgPool, id: &str) -> Result<Option<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>(
+        r#"
+        SELECT * FROM sessions WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Record the country a session was opened from
+pub async fn set_session_country(
+    pool: &PgPool,
+    id: &str,
+    country_code: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE sessions SET country_code = $2 WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(country_code)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Invalidate all user sessions but one, returning the invalidated IDs
+pub async fn invalidate_other_user_sessions(
+    pool: &PgPool,
+    user_id: &str,
+    keep_session_id: &str,
+) -> Result<Vec<String>, sqlx::Error> {
+    let ids: Vec<(String,)> = sqlx::query_as(
+        r#"
+        UPDATE sessions SET active = FALSE
+        WHERE user_id = $1 AND id <> $2 AND active = TRUE
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(keep_session_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ids.into_iter().map(|(id,)| id).collect())
+}
+
+/// Earlier sessions of a user, active or not, newest first
+pub async fn list_user_session_history(
+    pool: &PgPool,
+    user_id: &str,
+    exclude_session_id: &str,
+    limit: u32,
+) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>(
+        r#"
+        SELECT * FROM sessions
+        WHERE user_id = $1 AND id <> $2
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(user_id)
+    .bind(exclude_session_id)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await
+}
+
+// ============================================================================
+// Refresh Token Queries
+// ============================================================================
+
+/// Record an issued refresh token
+pub async fn create_refresh_token(
+    pool: &PgPool,
+    id: &str,
+    user_id: &str,
+    session_id: &str,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<RefreshToken, sqlx::Error> {
+    sqlx::query_as::<_, RefreshToken>(
+        r#"
+        INSERT INTO refresh_tokens (id, user_id, session_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(session_id)
+    .bind(token_hash)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await
+}
+
+/// Get refresh token by token hash
+pub async fn get_refresh_token_by_hash(
+    pool: &PgPool,
+    token_hash: &str,
+) -> Result<Option<RefreshToken>, sqlx::Error> {
+    sqlx::query_as::<_, RefreshToken>(
+        r#"
+        SELECT * FROM refresh_tokens WHERE token_hash = $1
+        "#,
+    )
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Count the refresh tokens ever issued for a session
+pub async fn count_session_refresh_tokens(
+    pool: &PgPool,
+    session_id: &str,
+) -> Result<i64, sqlx::Error> {
+    let count: (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM refresh_tokens WHERE session_id = $1
+        "#,
+    )
+    .bind(session_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count.0)
+}
+
+/// Mark a refresh token as exchanged for its successor
+///
+/// Returns false if the token was already revoked or exchanged, e.g. by a
+/// concurrent refresh with a copy of it.
+pub async fn rotate_refresh_token(
+    pool: &PgPool,
+    id: &str,
+    replaced_by: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked = TRUE, revoked_at = NOW(), replaced_by = $2
+        WHERE id = $1 AND revoked = FALSE
+        "#,
+    )
+    .bind(id)
+    .bind(replaced_by)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Revoke the refresh tokens of a session
+pub async fn revoke_session_refresh_tokens(
+    pool: &PgPool,
+    session_id: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE refresh_tokens SET revoked = TRUE, revoked_at = NOW()
+        WHERE session_id = $1 AND revoked = FALSE
+        "#,
+    )
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Revoke the refresh tokens of every session of a user
+pub async fn revoke_user_refresh_tokens(pool: &PgPool, user_id: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE refresh_tokens SET revoked = TRUE, revoked_at = NOW()
+        WHERE user_id = $1 AND revoked = FALSE
+        "#,
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
 // ============================================================================
 // API Key Queries
 // ============================================================================
@@ -835,6 +1028,108 @@ pub async fn list_invitations(
     Ok((invitations, count.0 as u32))
 }
 
+// ============================================================================
+// Support Access Queries
+// ============================================================================
+
+/// Create a support access grant
+pub async fn create_support_access_grant(
+    pool: &PgPool,
+    id: &str,
+    organization_id: &str,
+    granted_by_user_id: &str,
+    reason: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<SupportAccessGrant, sqlx::Error> {
+    sqlx::query_as::<_, SupportAccessGrant>(
+        r#"
+        INSERT INTO support_access_grants (id, organization_id, granted_by_user_id, reason, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(organization_id)
+    .bind(granted_by_user_id)
+    .bind(reason)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await
+}
+
+/// Get support access grant by ID
+pub async fn get_support_access_grant(
+    pool: &PgPool,
+    id: &str,
+) -> Result<Option<SupportAccessGrant>, sqlx::Error> {
+    sqlx::query_as::<_, SupportAccessGrant>("SELECT * FROM support_access_grants WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Get the active support access grant of an organization lasting longest
+pub async fn get_active_support_access_grant(
+    pool: &PgPool,
+    organization_id: &str,
+) -> Result<Option<SupportAccessGrant>, sqlx::Error> {
+    sqlx::query_as::<_, SupportAccessGrant>(
+        r#"
+        SELECT * FROM support_access_grants
+        WHERE organization_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY expires_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(organization_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Revoke support access grant
+pub async fn revoke_support_access_grant(
+    pool: &PgPool,
+    id: &str,
+    revoked_by_user_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE support_access_grants
+        SET revoked_at = NOW(), revoked_by_user_id = $2
+        WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        "#,
+    )
+    .bind(id)
+    .bind(revoked_by_user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// List support access grants of an organization, newest first
+pub async fn list_support_access_grants(
+    pool: &PgPool,
+    organization_id: &str,
+    active_only: bool,
+    limit: u32,
+) -> Result<Vec<SupportAccessGrant>, sqlx::Error> {
+    sqlx::query_as::<_, SupportAccessGrant>(
+        r#"
+        SELECT * FROM support_access_grants
+        WHERE organization_id = $1
+        AND (NOT $2 OR (revoked_at IS NULL AND expires_at > NOW()))
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(organization_id)
+    .bind(active_only)
+    .bind(limit as i32)
+    .fetch_all(pool)
+    .await
+}
+
 // ============================================================================
 // Audit Log Queries
 // ============================================================================
@@ -880,97 +1175,50 @@ pub async fn create_audit_log(
 pub async fn list_audit_logs(
     pool: &PgPool,
     filter: &AuditLogFilter,
-    page: u32,
-    page_size: u32,
-) -> Result<(Vec<AuditLogEntry>, u32), sqlx::Error> {
-    let offset = (page.saturating_sub(1)) * page_size;
-
-    // Build dynamic query based on filters
-    let mut query = String::from("SELECT * FROM audit_logs WHERE organization_id = $1");
-    let mut count_query =
-        String::from("SELECT COUNT(*) FROM audit_logs WHERE organization_id = $1");
-
-    let mut param_idx = 2;
-    let mut conditions = Vec::new();
-
-    if filter.user_id.is_some() {
-        conditions.push(format!("user_id = ${}", param_idx));
-        param_idx += 1;
-    }
-    if filter.resource_type.is_some() {
-        conditions.push(format!("resource_type = ${}", param_idx));
-        param_idx += 1;
-    }
-    if filter.action.is_some() {
-        conditions.push(format!("action = ${}", param_idx));
-        param_idx += 1;
-    }
-    if filter.start_time.is_some() {
-        conditions.push(format!("timestamp >= ${}", param_idx));
-        param_idx += 1;
-    }
-    if filter.end_time.is_some() {
-        conditions.push(format!("timestamp <= ${}", param_idx));
-        param_idx += 1;
+    request: &PageRequest,
+) -> Result<(Page<AuditLogEntry>, u64), sqlx::Error> {
+    fn push_conditions(
+        qb: &mut QueryBuilder<'_, Postgres>,
+        filter: &AuditLogFilter,
+        request: &PageRequest,
+    ) {
+        qb.push(" WHERE organization_id = ")
+            .push_bind(filter.organization_id.clone());
+        if let Some(ref user_id) = filter.user_id {
+            qb.push(" AND user_id = ").push_bind(user_id.clone());
+        }
+        if let Some(ref resource_type) = filter.resource_type {
+            qb.push(" AND resource_type = ")
+                .push_bind(resource_type.clone());
+        }
+        if let Some(ref action) = filter.action {
+            qb.push(" AND action = ").push_bind(action.clone());
+        }
+        if let Some(start_time) = filter.start_time {
+            qb.push(" AND timestamp >= ").push_bind(start_time);
+        }
+        if let Some(end_time) = filter.end_time {
+            qb.push(" AND timestamp <= ").push_bind(end_time);
+        }
+        request.push_filter(qb);
     }
 
-    for cond in &conditions {
-        query.push_str(&format!(" AND {}", cond));
-        count_query.push_str(&format!(" AND {}", cond));
-    }
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM audit_logs");
+    push_conditions(&mut count, filter, request);
+    let total: i64 = count.build_query_scalar().fetch_one(pool).await?;
 
-    query.push_str(&format!(
-        " ORDER BY timestamp DESC LIMIT ${} OFFSET ${}",
-        param_idx,
-        param_idx + 1
+    let mut query = QueryBuilder::new(format!(
+        "SELECT *, {} FROM audit_logs",
+        request.select_key()
     ));
-
-    // For simplicity, we'll use a simpler approach with optional bindings
-    let entries = sqlx::query_as::<_, AuditLogEntry>(
-        r#"
-        SELECT * FROM audit_logs
-        WHERE organization_id = $1
-        AND ($2::VARCHAR IS NULL OR user_id = $2)
-        AND ($3::VARCHAR IS NULL OR resource_type = $3)
-        AND ($4::VARCHAR IS NULL OR action = $4)
-        AND ($5::TIMESTAMPTZ IS NULL OR timestamp >= $5)
-        AND ($6::TIMESTAMPTZ IS NULL OR timestamp <= $6)
-        ORDER BY timestamp DESC
-        LIMIT $7 OFFSET $8
-        "#,
-    )
-    .bind(&filter.organization_id)
-    .bind(&filter.user_id)
-    .bind(&filter.resource_type)
-    .bind(&filter.action)
-    .bind(filter.start_time)
-    .bind(filter.end_time)
-    .bind(page_size as i32)
-    .bind(offset as i32)
-    .fetch_all(pool)
-    .await?;
-
-    let count: (i64,) = sqlx::query_as(
-        r#"
-        SELECT COUNT(*) FROM audit_logs
-        WHERE organization_id = $1
-        AND ($2::VARCHAR IS NULL OR user_id = $2)
-        AND ($3::VARCHAR IS NULL OR resource_type = $3)
-        AND ($4::VARCHAR IS NULL OR action = $4)
-        AND ($5::TIMESTAMPTZ IS NULL OR timestamp >= $5)
-        AND ($6::TIMESTAMPTZ IS NULL OR timestamp <= $6)
-        "#,
-    )
-    .bind(&filter.organization_id)
-    .bind(&filter.user_id)
-    .bind(&filter.resource_type)
-    .bind(&filter.action)
-    .bind(filter.start_time)
-    .bind(filter.end_time)
-    .fetch_one(pool)
-    .await?;
-
-    Ok((entries, count.0 as u32))
+    push_conditions(&mut query, filter, request);
+    request.push_page(&mut query);
+    let rows = query.build().fetch_all(pool).await?;
+
+    let page = request
+        .finish_rows(rows)?
+        .try_map(|row| AuditLogEntry::from_row(&row))?;
+    Ok((page, total as u64))
 }
 
 // ============================================================================
@@ -1,5 +1,6 @@
 //! gRPC handlers implementing the AuthService
 
+use pistonprotection_common::pagination::PageRequest;
 use pistonprotection_proto::PaginationInfo;
 use pistonprotection_proto::auth::{
     auth_service_server::{AuthService as ProtoAuthService, AuthServiceServer},
@@ -9,10 +10,12 @@ use tonic::{Request, Response, Status};
 use tracing::{error, info};
 
 use crate::models::{
-    AuditLogFilter, CreateApiKeyRequest as ModelCreateApiKeyRequest, InvitationStatus,
-    InvitationTokenGenerator, OrganizationRole,
+    AUDIT_LOG_LIST, AuditLogFilter, CreateApiKeyRequest as ModelCreateApiKeyRequest,
+    InvitationStatus, InvitationTokenGenerator, OrganizationRole,
 };
 use crate::services::AppState;
+use crate::services::invoice_documents;
+use crate::services::security_analytics::AuthEvent;
 
 /// gRPC service implementation
 pub struct AuthServiceImpl {
@@ -282,6 +285,15 @@ impl ProtoAuthService for AuthServiceImpl {
             .await
         {
             Ok(key) => {
+                // Look for keys used from networks they haven't been used from
+                self.state
+                    .security_analytics
+                    .record_from(client_ip.as_deref(), |ip| AuthEvent::ApiKeyUsed {
+                        key_id: key.id.clone(),
+                        organization_id: key.organization_id.clone(),
+                        ip,
+                    });
+
                 // Get organization
                 let org = self
                     .state
@@ -307,6 +319,65 @@ impl ProtoAuthService for AuthServiceImpl {
         }
     }
 
+    // =========================================================================
+    // Sessions
+    // =========================================================================
+
+    async fn list_sessions(
+        &self,
+        request: Request<ListSessionsRequest>,
+    ) -> Result<Response<ListSessionsResponse>, Status> {
+        let user_id = caller_id(&request)?;
+        let req = request.into_inner();
+
+        let sessions = self.state.session_device_service().list(&user_id).await?;
+
+        Ok(Response::new(ListSessionsResponse {
+            sessions: sessions
+                .iter()
+                .map(|s| {
+                    let mut session = s.to_proto();
+                    session.current = s.id == req.current_session_id;
+                    session
+                })
+                .collect(),
+        }))
+    }
+
+    async fn revoke_session(
+        &self,
+        request: Request<RevokeSessionRequest>,
+    ) -> Result<Response<RevokeSessionResponse>, Status> {
+        let user_id = caller_id(&request)?;
+        let req = request.into_inner();
+
+        let success = self
+            .state
+            .session_device_service()
+            .revoke(&user_id, &req.session_id)
+            .await?;
+
+        Ok(Response::new(RevokeSessionResponse { success }))
+    }
+
+    async fn revoke_other_sessions(
+        &self,
+        request: Request<RevokeOtherSessionsRequest>,
+    ) -> Result<Response<RevokeOtherSessionsResponse>, Status> {
+        let user_id = caller_id(&request)?;
+        let req = request.into_inner();
+
+        let revoked = self
+            .state
+            .session_device_service()
+            .revoke_others(&user_id, &req.current_session_id)
+            .await?;
+
+        Ok(Response::new(RevokeOtherSessionsResponse {
+            revoked_count: revoked as u32,
+        }))
+    }
+
     // =========================================================================
     // API Keys
     // =========================================================================
@@ -525,9 +596,7 @@ impl ProtoAuthService for AuthServiceImpl {
     ) -> Result<Response<ListAuditLogsResponse>, Status> {
         let req = request.into_inner();
 
-        let pagination = req.pagination.unwrap_or_default();
-        let page = pagination.page.max(1);
-        let page_size = pagination.page_size.clamp(1, 100);
+        let page_request = PageRequest::new(&AUDIT_LOG_LIST, req.pagination.as_ref())?;
 
         let filter = AuditLogFilter {
             organization_id: req.organization_id,
@@ -552,22 +621,16 @@ impl ProtoAuthService for AuthServiceImpl {
             }),
         };
 
-        let (entries, total) = self
+        let (page, total) = self
             .state
             .audit_service()
-            .list(&filter, page, page_size)
+            .list(&filter, &page_request)
             .await
             .map_err(Status::from)?;
 
         Ok(Response::new(ListAuditLogsResponse {
-            entries: entries.into_iter().map(|e| e.to_proto()).collect(),
-            pagination: Some(PaginationInfo {
-                total_count: total,
-                page,
-                page_size,
-                has_next: (page * page_size) < total,
-                next_cursor: String::new(),
-            }),
+            pagination: Some(page_request.info(&page, total)),
+            entries: page.items.into_iter().map(|e| e.to_proto()).collect(),
         }))
     }
 
@@ -671,6 +734,87 @@ impl ProtoAuthService for AuthServiceImpl {
         Ok(Response::new(RevokeInvitationResponse { success }))
     }
 
+    // =========================================================================
+    // Support Access
+    // =========================================================================
+
+    async fn grant_support_access(
+        &self,
+        request: Request<GrantSupportAccessRequest>,
+    ) -> Result<Response<GrantSupportAccessResponse>, Status> {
+        let user_id = caller_id(&request)?;
+        let req = request.into_inner();
+
+        let grant = self
+            .state
+            .support_access_service()
+            .grant(
+                &req.organization_id,
+                &user_id,
+                &req.reason,
+                req.duration_seconds,
+            )
+            .await?;
+
+        Ok(Response::new(GrantSupportAccessResponse {
+            grant: Some(grant.to_proto()),
+        }))
+    }
+
+    async fn list_support_access_grants(
+        &self,
+        request: Request<ListSupportAccessGrantsRequest>,
+    ) -> Result<Response<ListSupportAccessGrantsResponse>, Status> {
+        let user_id = caller_id(&request)?;
+        let req = request.into_inner();
+
+        let grants = self
+            .state
+            .support_access_service()
+            .list(&req.organization_id, &user_id, req.active_only)
+            .await?;
+
+        Ok(Response::new(ListSupportAccessGrantsResponse {
+            grants: grants.iter().map(|g| g.to_proto()).collect(),
+        }))
+    }
+
+    async fn revoke_support_access(
+        &self,
+        request: Request<RevokeSupportAccessRequest>,
+    ) -> Result<Response<RevokeSupportAccessResponse>, Status> {
+        let user_id = caller_id(&request)?;
+        let req = request.into_inner();
+
+        let success = self
+            .state
+            .support_access_service()
+            .revoke(&req.grant_id, &user_id)
+            .await?;
+
+        Ok(Response::new(RevokeSupportAccessResponse { success }))
+    }
+
+    async fn issue_support_token(
+        &self,
+        request: Request<IssueSupportTokenRequest>,
+    ) -> Result<Response<IssueSupportTokenResponse>, Status> {
+        let user_id = caller_id(&request)?;
+        let req = request.into_inner();
+
+        let issued = self
+            .state
+            .support_access_service()
+            .issue_token(&req.organization_id, &user_id, &req.reason)
+            .await?;
+
+        Ok(Response::new(IssueSupportTokenResponse {
+            token: issued.token,
+            expires_at: Some(pistonprotection_proto::Timestamp::from(issued.expires_at)),
+            grant: Some(issued.grant.to_proto()),
+        }))
+    }
+
     // =========================================================================
     // Billing Operations
     // =========================================================================
@@ -767,63 +911,33 @@ impl ProtoAuthService for AuthServiceImpl {
             .state
             .stripe_service()
             .ok_or_else(|| Status::failed_precondition("Stripe is not configured"))?;
-
+        let plan_changes = self
+            .state
+            .plan_change_service()
+            .ok_or_else(|| Status::failed_precondition("Stripe is not configured"))?;
+        let actor = caller_id(&request).ok();
         let req = request.into_inner();
 
-        // Get current subscription
-        let subscription = stripe_service
-            .get_subscription_by_org_id(&req.organization_id)
-            .await
-            .map_err(|e| {
-                error!("Failed to get subscription: {}", e);
-                Status::internal("Failed to get subscription")
-            })?
-            .ok_or_else(|| Status::not_found("Subscription not found"))?;
-
-        let stripe_sub_id = subscription
-            .stripe_subscription_id
-            .as_ref()
-            .ok_or_else(|| Status::failed_precondition("No Stripe subscription ID"))?;
-
-        // Get new price ID if plan is changing
-        let new_price_id = if !req.plan_id.is_empty() {
-            let plan = stripe_service
-                .get_plan_by_id(&req.plan_id)
-                .await
-                .map_err(|e| {
-                    error!("Failed to get plan: {}", e);
-                    Status::not_found("Plan not found")
-                })?;
-
-            // Determine price based on billing period
-            let price_id = match req.billing_period {
-                2 => plan.stripe_price_id_yearly.as_ref(),
-                _ => plan.stripe_price_id_monthly.as_ref(),
-            };
-
-            price_id.cloned()
-        } else {
-            None
-        };
-
-        // Convert proration behavior
+        let plan_id = Some(req.plan_id.as_str()).filter(|id| !id.is_empty());
+        let billing_period =
+            crate::models::subscription::BillingPeriod::try_from(req.billing_period).ok();
         let proration = match req.proration_behavior {
             2 => crate::models::subscription::ProrationBehavior::None,
             3 => crate::models::subscription::ProrationBehavior::AlwaysInvoice,
             _ => crate::models::subscription::ProrationBehavior::CreateProrations,
         };
 
-        // Update subscription in Stripe
-        let _updated_stripe_sub = stripe_service
-            .update_subscription(stripe_sub_id, new_price_id.as_deref(), proration)
-            .await
-            .map_err(|e| {
-                error!("Failed to update Stripe subscription: {}", e);
-                Status::internal("Failed to update subscription")
-            })?;
+        let change = plan_changes
+            .change_plan(
+                &req.organization_id,
+                plan_id,
+                billing_period,
+                proration,
+                actor.as_deref(),
+            )
+            .await?;
 
-        // Fetch updated subscription
-        let updated_subscription = stripe_service
+        let subscription = stripe_service
             .get_subscription_by_org_id(&req.organization_id)
             .await
             .map_err(|e| {
@@ -834,11 +948,45 @@ impl ProtoAuthService for AuthServiceImpl {
 
         info!(
             organization_id = %req.organization_id,
+            change = ?change,
             "Updated subscription"
         );
 
         Ok(Response::new(UpdateSubscriptionResponse {
-            subscription: Some(updated_subscription.to_proto()),
+            subscription: Some(subscription.to_proto()),
+        }))
+    }
+
+    async fn cancel_plan_change(
+        &self,
+        request: Request<CancelPlanChangeRequest>,
+    ) -> Result<Response<CancelPlanChangeResponse>, Status> {
+        let stripe_service = self
+            .state
+            .stripe_service()
+            .ok_or_else(|| Status::failed_precondition("Stripe is not configured"))?;
+        let plan_changes = self
+            .state
+            .plan_change_service()
+            .ok_or_else(|| Status::failed_precondition("Stripe is not configured"))?;
+        let actor = caller_id(&request).ok();
+        let req = request.into_inner();
+
+        plan_changes
+            .cancel_change(&req.organization_id, actor.as_deref())
+            .await?;
+
+        let subscription = stripe_service
+            .get_subscription_by_org_id(&req.organization_id)
+            .await
+            .map_err(|e| {
+                error!("Failed to get updated subscription: {}", e);
+                Status::internal("Failed to get updated subscription")
+            })?
+            .ok_or_else(|| Status::internal("Subscription not found after update"))?;
+
+        Ok(Response::new(CancelPlanChangeResponse {
+            subscription: Some(subscription.to_proto()),
         }))
     }
 
@@ -1067,8 +1215,19 @@ impl ProtoAuthService for AuthServiceImpl {
                 Status::internal("Failed to list invoices")
             })?;
 
+        let invoice_ids: Vec<String> = invoices.iter().map(|i| i.id.clone()).collect();
+        let mut line_items = invoice_documents::line_items(&self.state.db, &invoice_ids)
+            .await
+            .map_err(|e| {
+                error!("Failed to list invoice line items: {}", e);
+                Status::internal("Failed to list invoices")
+            })?;
+
         Ok(Response::new(ListInvoicesResponse {
-            invoices: invoices.into_iter().map(|i| i.to_proto()).collect(),
+            invoices: invoices
+                .iter()
+                .map(|i| i.to_proto(&line_items.remove(&i.id).unwrap_or_default()))
+                .collect(),
             pagination: Some(PaginationInfo {
                 total_count: 0, // Would need a count query
                 page,
@@ -1154,6 +1313,7 @@ impl ProtoAuthService for AuthServiceImpl {
                     seconds: ts,
                     nanos: 0,
                 }),
+            ..Default::default()
         };
 
         Ok(Response::new(GetInvoiceResponse {
@@ -1161,6 +1321,32 @@ impl ProtoAuthService for AuthServiceImpl {
         }))
     }
 
+    async fn get_invoice_pdf(
+        &self,
+        request: Request<GetInvoicePdfRequest>,
+    ) -> Result<Response<GetInvoicePdfResponse>, Status> {
+        let invoice_documents = self
+            .state
+            .invoice_document_service()
+            .ok_or_else(|| Status::failed_precondition("Invoice documents are not configured"))?;
+
+        let req = request.into_inner();
+
+        let download = invoice_documents
+            .download_url(&req.organization_id, &req.invoice_id)
+            .await
+            .map_err(|e| {
+                error!("Failed to get invoice PDF: {}", e);
+                Status::internal("Failed to get invoice PDF")
+            })?
+            .ok_or_else(|| Status::not_found("Invoice not found"))?;
+
+        Ok(Response::new(GetInvoicePdfResponse {
+            download_url: download.url,
+            expires_at: Some(pistonprotection_proto::Timestamp::from(download.expires_at)),
+        }))
+    }
+
     async fn get_usage_summary(
         &self,
         request: Request<GetUsageSummaryRequest>,
@@ -1294,34 +1480,31 @@ impl ProtoAuthService for AuthServiceImpl {
 
         let req = request.into_inner();
 
-        // Get subscription to find customer ID
-        let subscription = stripe_service
-            .get_subscription_by_org_id(&req.organization_id)
+        // Payment methods are kept in sync by the Stripe webhooks
+        let payment_methods = stripe_service
+            .list_payment_methods(&req.organization_id)
             .await
             .map_err(|e| {
-                error!("Failed to get subscription: {}", e);
-                Status::internal("Failed to get subscription")
+                error!("Failed to list payment methods: {}", e);
+                Status::internal("Failed to list payment methods")
             })?;
 
-        let customer_id = match subscription {
-            Some(ref sub) => sub.stripe_customer_id.as_ref(),
-            None => None,
-        };
-
-        if customer_id.is_none() {
-            return Ok(Response::new(ListPaymentMethodsResponse {
-                payment_methods: vec![],
-            }));
-        }
-
-        // Payment methods would be fetched from local DB or Stripe
-        // For now, return empty list (would need to implement Stripe PaymentMethod listing)
         Ok(Response::new(ListPaymentMethodsResponse {
-            payment_methods: vec![],
+            payment_methods: payment_methods.iter().map(|pm| pm.to_proto()).collect(),
         }))
     }
 }
 
+/// User making the request, as forwarded by the gateway
+fn caller_id<T>(request: &Request<T>) -> Result<String, Status> {
+    request
+        .metadata()
+        .get("x-user-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| Status::unauthenticated("User ID required"))
+}
+
 /// Create the gRPC server
 pub async fn create_server(
     state: AppState,
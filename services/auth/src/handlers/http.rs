@@ -3,16 +3,24 @@
 use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
 use serde::Serialize;
 
+use crate::handlers::webhook::create_webhook_router;
 use crate::services::AppState;
 
 /// Create the HTTP router
 pub fn create_router(state: AppState) -> Router {
-    Router::new()
+    let webhooks = state.webhook_state().map(create_webhook_router);
+
+    let router = Router::new()
         .route("/health", get(health_check))
         .route("/health/live", get(liveness_check))
         .route("/health/ready", get(readiness_check))
         .route("/metrics", get(metrics_handler))
-        .with_state(state)
+        .with_state(state);
+
+    match webhooks {
+        Some(webhooks) => router.nest("/webhooks", webhooks),
+        None => router,
+    }
 }
 
 /// Health check response
@@ -1,4 +1,9 @@
 //! Stripe webhook handler for processing billing events
+//!
+//! Events are stored before they are processed and acknowledged to Stripe
+//! once stored; failures are retried here from the stored payload rather
+//! than by Stripe. A background job retries due events and periodically
+//! reconciles subscriptions and invoices with the Stripe API.
 
 use axum::{
     Json, Router,
@@ -9,16 +14,20 @@ use axum::{
     routing::post,
 };
 use hmac::{Hmac, Mac};
+use pistonprotection_common::redis::CacheService;
 use serde::Serialize;
 use sha2::Sha256;
 use std::sync::Arc;
+use std::time::Duration;
 use stripe_rust::{
     CheckoutSession, Customer, Event, EventObject, EventType, Invoice as StripeInvoice,
-    PaymentIntent, Subscription as StripeSubscription,
+    PaymentIntent, PaymentMethod as StripePaymentMethod, Subscription as StripeSubscription,
 };
 use tracing::{error, info, warn};
 
 use crate::models::SubscriptionStatus;
+use crate::models::subscription::BillingEventStatus;
+use crate::services::billing_events::BillingEventService;
 use crate::services::email::{EmailRecipient, EmailService};
 use crate::services::stripe::StripeService;
 
@@ -27,6 +36,7 @@ use crate::services::stripe::StripeService;
 pub struct WebhookState {
     pub stripe_service: Arc<StripeService>,
     pub email_service: Arc<EmailService>,
+    pub events: BillingEventService,
 }
 
 /// Create the webhook router
@@ -80,8 +90,11 @@ async fn handle_stripe_webhook(
     }
 
     // Parse the event
-    let event: Event = match serde_json::from_slice(&body) {
-        Ok(e) => e,
+    let parsed = serde_json::from_slice::<serde_json::Value>(&body).and_then(|payload| {
+        serde_json::from_value::<Event>(payload.clone()).map(|event| (payload, event))
+    });
+    let (payload, event) = match parsed {
+        Ok(parsed) => parsed,
         Err(e) => {
             error!(error = %e, "Failed to parse webhook event");
             return (
@@ -97,24 +110,148 @@ async fn handle_stripe_webhook(
         "Received Stripe webhook"
     );
 
-    // Process the event
-    if let Err(e) = process_webhook_event(&state, &event).await {
-        error!(
-            event_type = %event.type_,
-            event_id = %event.id,
-            error = %e,
-            "Failed to process webhook event"
-        );
-        // Still return 200 to prevent Stripe from retrying (we've logged the error)
-        return (
-            StatusCode::OK,
-            Json(WebhookResponse::error(&format!("Processing error: {}", e))),
-        );
-    }
+    // Store the event first, so it can be retried from the database and a
+    // redelivered event is recognized
+    let stored = match state
+        .events
+        .record(event.id.as_ref(), &event.type_.to_string(), &payload)
+        .await
+    {
+        Ok(stored) => stored,
+        Err(e) => {
+            error!(event_id = %event.id, error = %e, "Failed to store webhook event");
+            // Stripe delivers the event again
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(WebhookResponse::error("Failed to store event")),
+            );
+        }
+    };
+
+    // Failures are retried from the stored event, not by Stripe
+    process_stored_event(&state, &stored.id, &event).await;
 
     (StatusCode::OK, Json(WebhookResponse::success()))
 }
 
+/// Process a stored event unless it is processed or in progress elsewhere,
+/// and record the outcome
+async fn process_stored_event(state: &WebhookState, id: &str, event: &Event) {
+    let claimed = match state.events.claim(id).await {
+        Ok(Some(claimed)) => claimed,
+        Ok(None) => {
+            info!(event_id = %event.id, "Webhook event already processed or in progress");
+            return;
+        }
+        Err(e) => {
+            error!(event_id = %event.id, error = %e, "Failed to claim webhook event");
+            return;
+        }
+    };
+
+    let result = match process_webhook_event(state, event).await {
+        Ok(()) => state.events.complete(&claimed.id).await,
+        Err(e) => match state.events.fail(&claimed, &e.to_string()).await {
+            Ok(BillingEventStatus::Dead) => {
+                error!(
+                    event_type = %event.type_,
+                    event_id = %event.id,
+                    attempts = claimed.attempts,
+                    error = %e,
+                    "Webhook event failed on every attempt, giving up"
+                );
+                Ok(())
+            }
+            Ok(_) => {
+                warn!(
+                    event_type = %event.type_,
+                    event_id = %event.id,
+                    attempt = claimed.attempts,
+                    error = %e,
+                    "Failed to process webhook event, will retry"
+                );
+                Ok(())
+            }
+            Err(db_error) => Err(db_error),
+        },
+    };
+    if let Err(e) = result {
+        error!(event_id = %event.id, error = %e, "Failed to record webhook event outcome");
+    }
+}
+
+/// Retry stored events that are due
+async fn retry_due_events(state: &WebhookState) {
+    let due = match state.events.due().await {
+        Ok(due) => due,
+        Err(e) => {
+            error!(error = %e, "Failed to fetch due webhook events");
+            return;
+        }
+    };
+    if !due.is_empty() {
+        info!(count = due.len(), "Retrying webhook events");
+    }
+
+    for stored in due {
+        match serde_json::from_value::<Event>(stored.payload.clone()) {
+            Ok(event) => process_stored_event(state, &stored.id, &event).await,
+            Err(e) => {
+                // Counts as an attempt, so the event ends up dead
+                error!(event_id = %stored.stripe_event_id, error = %e, "Stored webhook event is invalid");
+                if let Ok(Some(claimed)) = state.events.claim(&stored.id).await {
+                    let _ = state
+                        .events
+                        .fail(&claimed, &format!("Invalid payload: {}", e))
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+/// Reconcile with Stripe, once per interval across all replicas
+async fn reconcile_with_stripe(state: &WebhookState, cache: &CacheService, interval: Duration) {
+    match cache.set_nx("billing:reconcile", &true, interval).await {
+        Ok(true) => {}
+        Ok(false) => return, // Another replica reconciled this interval
+        Err(e) => {
+            warn!(error = %e, "Failed to take billing reconciliation turn");
+            return;
+        }
+    }
+
+    match state.stripe_service.reconcile().await {
+        Ok(stats) => info!(
+            subscriptions = stats.subscriptions,
+            invoices = stats.invoices,
+            corrected = stats.corrected,
+            failed = stats.failed,
+            "Reconciled billing with Stripe"
+        ),
+        Err(e) => error!(error = %e, "Failed to reconcile billing with Stripe"),
+    }
+}
+
+/// Retry failed webhook events and reconcile with Stripe on schedule
+pub async fn run_billing_jobs(state: WebhookState, cache: CacheService) {
+    let config = state.stripe_service.config();
+    let reconcile_interval = Duration::from_secs(config.reconcile_interval_secs.max(60));
+    let mut retry = tokio::time::interval(Duration::from_secs(
+        config.webhook_retry_interval_secs.max(1),
+    ));
+    let mut reconcile = tokio::time::interval(reconcile_interval);
+    retry.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    reconcile.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = retry.tick() => retry_due_events(&state).await,
+            _ = reconcile.tick() => reconcile_with_stripe(&state, &cache, reconcile_interval).await,
+        }
+    }
+}
+
 /// Webhook response
 #[derive(Debug, Serialize)]
 struct WebhookResponse {
@@ -232,6 +369,20 @@ async fn process_webhook_event(state: &WebhookState, event: &Event) -> Result<()
             }
         }
 
+        // Payment method events
+        EventType::PaymentMethodAttached
+        | EventType::PaymentMethodUpdated
+        | EventType::PaymentMethodAutomaticallyUpdated => {
+            if let EventObject::PaymentMethod(payment_method) = &event.data.object {
+                handle_payment_method_changed(state, payment_method).await?;
+            }
+        }
+        EventType::PaymentMethodDetached => {
+            if let EventObject::PaymentMethod(payment_method) = &event.data.object {
+                handle_payment_method_detached(state, payment_method).await?;
+            }
+        }
+
         // Subscription events
         EventType::CustomerSubscriptionCreated => {
             if let EventObject::Subscription(subscription) = &event.data.object {
@@ -370,14 +521,20 @@ async fn handle_customer_created(
 }
 
 async fn handle_customer_updated(
-    _state: &WebhookState,
+    state: &WebhookState,
     customer: &Customer,
 ) -> Result<(), anyhow::Error> {
     info!(
         customer_id = %customer.id,
         "Customer updated in Stripe"
     );
-    // Could update local customer data if needed
+
+    // The default payment method is set on the customer
+    state
+        .stripe_service
+        .sync_default_payment_method(customer)
+        .await?;
+
     Ok(())
 }
 
@@ -393,6 +550,42 @@ async fn handle_customer_deleted(
     Ok(())
 }
 
+// ========== Payment Method Event Handlers ==========
+
+async fn handle_payment_method_changed(
+    state: &WebhookState,
+    payment_method: &StripePaymentMethod,
+) -> Result<(), anyhow::Error> {
+    info!(
+        payment_method_id = %payment_method.id,
+        "Payment method attached or updated"
+    );
+
+    state
+        .stripe_service
+        .sync_payment_method(payment_method)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_payment_method_detached(
+    state: &WebhookState,
+    payment_method: &StripePaymentMethod,
+) -> Result<(), anyhow::Error> {
+    info!(
+        payment_method_id = %payment_method.id,
+        "Payment method detached"
+    );
+
+    state
+        .stripe_service
+        .remove_payment_method(payment_method.id.as_ref())
+        .await?;
+
+    Ok(())
+}
+
 // ========== Email Notification Helpers ==========
 
 /// Get email recipient from subscription's customer
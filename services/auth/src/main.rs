@@ -3,8 +3,12 @@
 //! Provides authentication, authorization, user management, organization management,
 //! API key handling, and role-based access control (RBAC) for the PistonProtection platform.
 
-use pistonprotection_common::{config::Config, telemetry};
+use pistonprotection_common::{
+    config::{Config, secrets::Secrets},
+    telemetry,
+};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::signal;
 use tracing::{error, info};
 
@@ -15,21 +19,26 @@ mod models;
 mod services;
 
 use config::AuthConfig;
-use services::AppState;
+use services::{AppState, SecurityAnalytics};
 
 const SERVICE_NAME: &str = "auth";
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load base configuration
-    let base_config = Config::load(SERVICE_NAME)?;
+    let mut base_config = Config::load(SERVICE_NAME)?;
 
     // Load auth-specific configuration
-    let auth_config = AuthConfig::load()?;
+    let mut auth_config = AuthConfig::load()?;
 
     // Initialize telemetry
     telemetry::init(SERVICE_NAME, &base_config.telemetry)?;
 
+    // Resolve credentials kept in Kubernetes Secrets or Vault
+    let secrets = Arc::new(Secrets::from_env()?);
+    let db_credentials = base_config.resolve_secrets(&secrets).await?;
+    auth_config.resolve_secrets(&secrets).await?;
+
     info!(
         "Starting {} service v{}",
         SERVICE_NAME,
@@ -40,8 +49,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let db_pool = match &base_config.database {
         Some(db_config) => {
             let pool = pistonprotection_common::db::create_pool(db_config).await?;
-            // Run migrations
-            db::run_migrations(&pool).await?;
+            if let Some(credentials) = db_credentials {
+                pistonprotection_common::db::spawn_credential_renewal(
+                    vec![pool.clone()],
+                    Arc::clone(&secrets),
+                    credentials,
+                );
+            }
+            // Run migrations, or only plan or revert them (PISTON_AUTH_MIGRATIONS)
+            match db::MigrationMode::from_env()? {
+                db::MigrationMode::Apply => db::run_migrations(&pool).await?,
+                db::MigrationMode::Down(target) => {
+                    if !base_config.is_development() {
+                        error!("Down migrations are only allowed in development");
+                        return Err("Down migrations require PISTON_ENV=development".into());
+                    }
+                    let plan = db::migrations::revert_to(&pool, target).await?;
+                    println!("{}", plan);
+                    return Ok(());
+                }
+                mode => {
+                    println!("{}", db::migrations::plan(&pool, mode).await?);
+                    return Ok(());
+                }
+            }
             pool
         }
         None => {
@@ -60,7 +91,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     // Create shared state
-    let app_state = AppState::new(db_pool, redis_pool, base_config.clone(), auth_config);
+    let (security_analytics, security_events) = SecurityAnalytics::channel();
+    let app_state = AppState::new(
+        db_pool,
+        redis_pool,
+        base_config.clone(),
+        auth_config,
+        security_analytics,
+    );
+
+    // Analyze logins and API key use for attacks in the background
+    if app_state.auth_config.security.enabled {
+        let detector = app_state.security_detector()?;
+        tokio::spawn(detector.run(security_events));
+    }
+
+    // Retry failed Stripe webhook events and reconcile billing with Stripe
+    if let Some(webhook_state) = app_state.webhook_state() {
+        tokio::spawn(handlers::webhook::run_billing_jobs(
+            webhook_state,
+            app_state.cache.clone(),
+        ));
+    }
+
+    // Remind trials before they end and act on expired ones
+    if let Some(trial_service) = app_state.trial_service() {
+        tokio::spawn(trial_service.run(app_state.cache.clone()));
+    }
+
+    // Render invoice PDFs into object storage
+    if let Some(invoice_document_service) = app_state.invoice_document_service() {
+        tokio::spawn(invoice_document_service.run(app_state.cache.clone()));
+    }
 
     // Start HTTP server (health checks, metrics)
     let http_addr: SocketAddr = base_config.http_addr().parse()?;
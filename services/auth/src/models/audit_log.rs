@@ -1,10 +1,27 @@
 //! Audit log model definitions
 
 use chrono::{DateTime, Utc};
+use pistonprotection_common::pagination::{Field, FieldKind, ListSpec};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::collections::HashMap;
 
+/// Sort and filter fields of audit log lists
+pub static AUDIT_LOG_LIST: ListSpec = ListSpec {
+    fields: &[
+        Field::sortable("timestamp", "timestamp", FieldKind::Timestamp),
+        Field::sortable("action", "action", FieldKind::Text),
+        Field::sortable("resource_type", "resource_type", FieldKind::Text),
+        Field::filterable("resource_id", "resource_id", FieldKind::Text),
+        Field::filterable("user_id", "user_id", FieldKind::Text),
+        Field::filterable("user_email", "user_email", FieldKind::Text),
+        Field::filterable("ip_address", "ip_address", FieldKind::Text),
+        Field::filterable("description", "description", FieldKind::Text),
+    ],
+    id_column: "id",
+    default_order: "-timestamp",
+};
+
 /// Audit log entry model
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct AuditLogEntry {
@@ -109,6 +126,12 @@ impl AuditActions {
     pub const INVITATION_ACCEPTED: &'static str = "invitation.accepted";
     pub const INVITATION_REVOKED: &'static str = "invitation.revoked";
 
+    // Support access actions
+    pub const SUPPORT_ACCESS_GRANTED: &'static str = "support_access.granted";
+    pub const SUPPORT_ACCESS_REVOKED: &'static str = "support_access.revoked";
+    pub const SUPPORT_ACCESS_TOKEN_ISSUED: &'static str = "support_access.token_issued";
+    pub const SUPPORT_ACCESS_USED: &'static str = "support_access.used";
+
     // API Key actions
     pub const API_KEY_CREATED: &'static str = "api_key.created";
     pub const API_KEY_REVOKED: &'static str = "api_key.revoked";
@@ -117,6 +140,11 @@ impl AuditActions {
     // Session actions
     pub const SESSION_CREATED: &'static str = "session.created";
     pub const SESSION_REVOKED: &'static str = "session.revoked";
+    pub const SESSION_REFRESH_TOKEN_REUSED: &'static str = "session.refresh_token_reused";
+
+    // Security alerts
+    pub const SECURITY_IMPOSSIBLE_TRAVEL: &'static str = "security.impossible_travel";
+    pub const SECURITY_API_KEY_NEW_NETWORK: &'static str = "security.api_key_new_network";
 
     // Resource actions
     pub const BACKEND_CREATED: &'static str = "backend.created";
@@ -138,6 +166,14 @@ impl AuditActions {
     pub const SUBSCRIPTION_CREATED: &'static str = "subscription.created";
     pub const SUBSCRIPTION_UPDATED: &'static str = "subscription.updated";
     pub const SUBSCRIPTION_CANCELED: &'static str = "subscription.canceled";
+    pub const SUBSCRIPTION_UPGRADED: &'static str = "subscription.upgraded";
+    pub const SUBSCRIPTION_DOWNGRADE_SCHEDULED: &'static str = "subscription.downgrade_scheduled";
+    pub const SUBSCRIPTION_DOWNGRADED: &'static str = "subscription.downgraded";
+    pub const SUBSCRIPTION_PLAN_CHANGE_CANCELED: &'static str = "subscription.plan_change_canceled";
+    pub const TRIAL_EXPIRED: &'static str = "trial.expired";
+    pub const TRIAL_CONVERTED: &'static str = "trial.converted";
+    pub const TRIAL_DOWNGRADED: &'static str = "trial.downgraded";
+    pub const TRIAL_PROTECTION_SUSPENDED: &'static str = "trial.protection_suspended";
 }
 
 /// Audit log builder for easy creation
@@ -239,6 +275,11 @@ mod tests {
         assert_eq!(entry.metadata.get("method"), Some(&"password".to_string()));
     }
 
+    #[test]
+    fn test_audit_log_list_conformance() {
+        pistonprotection_common::pagination::check_conformance(&AUDIT_LOG_LIST).unwrap();
+    }
+
     #[test]
     fn test_audit_log_filter() {
         let filter = AuditLogFilter::new("org123")
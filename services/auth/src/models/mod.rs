@@ -8,6 +8,7 @@ pub mod permission;
 pub mod role;
 pub mod session;
 pub mod subscription;
+pub mod support_access;
 pub mod user;
 
 pub use api_key::*;
@@ -17,4 +18,5 @@ pub use organization::*;
 pub use permission::*;
 pub use role::*;
 pub use session::*;
+pub use support_access::*;
 pub use user::*;
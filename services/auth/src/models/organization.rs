@@ -145,6 +145,9 @@ pub struct Subscription {
     pub trial_ends_at: Option<DateTime<Utc>>,
     pub cancel_at_period_end: bool,
     pub canceled_at: Option<DateTime<Utc>>,
+    pub scheduled_plan_id: Option<String>,
+    pub scheduled_billing_period: Option<super::subscription::BillingPeriod>,
+    pub scheduled_change_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -312,6 +315,9 @@ impl Organization {
                 trial_ends_at: s.trial_ends_at.map(Timestamp::from),
                 cancel_at_period_end: s.cancel_at_period_end,
                 canceled_at: s.canceled_at.map(Timestamp::from),
+                scheduled_plan_id: s.scheduled_plan_id.clone().unwrap_or_default(),
+                scheduled_billing_period: s.scheduled_billing_period.map_or(0, i32::from),
+                scheduled_change_at: s.scheduled_change_at.map(Timestamp::from),
             }),
             limits: limits.map(|l| auth::OrganizationLimits {
                 max_backends: l.max_backends as u32,
@@ -377,6 +383,9 @@ impl Subscription {
             trial_ends_at: self.trial_ends_at.map(Timestamp::from),
             cancel_at_period_end: self.cancel_at_period_end,
             canceled_at: self.canceled_at.map(Timestamp::from),
+            scheduled_plan_id: self.scheduled_plan_id.clone().unwrap_or_default(),
+            scheduled_billing_period: self.scheduled_billing_period.map_or(0, i32::from),
+            scheduled_change_at: self.scheduled_change_at.map(Timestamp::from),
         }
     }
 }
@@ -13,6 +13,8 @@ pub struct Session {
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
     pub device_type: Option<String>,
+    /// Country the session was opened from (GeoIP, ISO 3166-1 alpha-2)
+    pub country_code: Option<String>,
     pub active: bool,
     pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
@@ -27,6 +29,8 @@ pub struct SessionResponse {
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
     pub device_type: Option<String>,
+    /// Country the session was opened from (GeoIP, ISO 3166-1 alpha-2)
+    pub country_code: Option<String>,
     pub active: bool,
     pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
@@ -41,6 +45,7 @@ impl From<Session> for SessionResponse {
             ip_address: session.ip_address,
             user_agent: session.user_agent,
             device_type: session.device_type,
+            country_code: session.country_code,
             active: session.active,
             expires_at: session.expires_at,
             created_at: session.created_at,
@@ -139,6 +144,34 @@ pub struct RefreshToken {
     pub revoked: bool,
     pub revoked_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    /// Token issued in exchange for this one
+    pub replaced_by: Option<String>,
+}
+
+/// What presenting a stored refresh token amounts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshTokenState {
+    /// Current token of its session, exchanged for a new one
+    Usable,
+    /// Already exchanged: a copy of the token is being replayed
+    Reused,
+    /// Revoked by a logout
+    Revoked,
+    Expired,
+}
+
+impl RefreshToken {
+    pub fn state(&self, now: DateTime<Utc>) -> RefreshTokenState {
+        if self.replaced_by.is_some() {
+            RefreshTokenState::Reused
+        } else if self.revoked {
+            RefreshTokenState::Revoked
+        } else if self.expires_at <= now {
+            RefreshTokenState::Expired
+        } else {
+            RefreshTokenState::Usable
+        }
+    }
 }
 
 /// Token pair returned after successful authentication
@@ -178,6 +211,8 @@ impl Session {
             expires_at: Some(Timestamp::from(self.expires_at)),
             created_at: Some(Timestamp::from(self.created_at)),
             last_active_at: Some(Timestamp::from(self.last_active_at)),
+            country_code: self.country_code.clone().unwrap_or_default(),
+            current: false,
         }
     }
 }
@@ -206,6 +241,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_refresh_token_state() {
+        let now = Utc::now();
+        let mut token = RefreshToken {
+            id: "token1".to_string(),
+            user_id: "user1".to_string(),
+            session_id: "session1".to_string(),
+            token_hash: "hash".to_string(),
+            expires_at: now + chrono::Duration::days(1),
+            revoked: false,
+            revoked_at: None,
+            created_at: now,
+            replaced_by: None,
+        };
+        assert_eq!(token.state(now), RefreshTokenState::Usable);
+        assert_eq!(
+            token.state(now + chrono::Duration::days(2)),
+            RefreshTokenState::Expired
+        );
+
+        token.revoked = true;
+        token.revoked_at = Some(now);
+        assert_eq!(token.state(now), RefreshTokenState::Revoked);
+
+        // A rotated token stays a replay even once it has expired
+        token.replaced_by = Some("token2".to_string());
+        assert_eq!(token.state(now), RefreshTokenState::Reused);
+        assert_eq!(
+            token.state(now + chrono::Duration::days(2)),
+            RefreshTokenState::Reused
+        );
+    }
+
     #[test]
     fn test_create_session_builder() {
         let session = CreateSession::new("user123")
@@ -234,6 +234,32 @@ impl InvoiceStatus {
     }
 }
 
+/// Kind of an invoice line item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "invoice_line_kind", rename_all = "lowercase")]
+#[derive(Default)]
+pub enum InvoiceLineKind {
+    /// Recurring plan charge
+    Subscription,
+    /// Credit or charge for a plan change within the period
+    Proration,
+    /// Metered usage above the plan's limits
+    Overage,
+    #[default]
+    Other,
+}
+
+impl From<InvoiceLineKind> for i32 {
+    fn from(kind: InvoiceLineKind) -> Self {
+        match kind {
+            InvoiceLineKind::Subscription => 1,
+            InvoiceLineKind::Proration => 2,
+            InvoiceLineKind::Overage => 3,
+            InvoiceLineKind::Other => 4,
+        }
+    }
+}
+
 /// Payment intent status
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "payment_status", rename_all = "lowercase")]
@@ -265,6 +291,68 @@ impl PaymentStatus {
     }
 }
 
+/// Processing state of a stored Stripe webhook event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "billing_event_status", rename_all = "lowercase")]
+#[derive(Default)]
+pub enum BillingEventStatus {
+    /// Stored, not processed yet
+    #[default]
+    Received,
+    /// Claimed by a replica processing it
+    Processing,
+    Processed,
+    /// Processing failed, retried at `next_attempt_at`
+    Failed,
+    /// Failed on every attempt, no longer retried
+    Dead,
+}
+
+impl BillingEventStatus {
+    /// State of an event after a failed attempt
+    pub fn after_failure(attempts: i32, max_attempts: u32) -> Self {
+        if attempts >= max_attempts as i32 {
+            BillingEventStatus::Dead
+        } else {
+            BillingEventStatus::Failed
+        }
+    }
+}
+
+/// Where an organization's trial stands once it ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "trial_outcome", rename_all = "lowercase")]
+pub enum TrialOutcome {
+    /// Ended unpaid, the expiry action waits for `trial_grace_ends_at`
+    Grace,
+    /// Became a paid subscription
+    Converted,
+    /// Moved to the free plan
+    Downgraded,
+    /// Protection suspended until the subscription is paid
+    Suspended,
+}
+
+/// Stripe webhook event stored for processing
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct BillingEvent {
+    pub id: String,
+    pub stripe_event_id: String,
+    pub event_type: String,
+    pub organization_id: Option<String>,
+    pub subscription_id: Option<String>,
+    pub invoice_id: Option<String>,
+    pub payload: serde_json::Value,
+    pub processed: bool,
+    pub processed_at: Option<DateTime<Utc>>,
+    pub error_message: Option<String>,
+    pub status: BillingEventStatus,
+    pub attempts: i32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
 /// Subscription plan definition
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Plan {
@@ -313,6 +401,9 @@ pub struct SubscriptionDetails {
     pub cancel_at_period_end: bool,
     pub canceled_at: Option<DateTime<Utc>>,
     pub cancellation_reason: Option<String>,
+    pub scheduled_plan_id: Option<String>,
+    pub scheduled_billing_period: Option<BillingPeriod>,
+    pub scheduled_change_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -340,10 +431,36 @@ pub struct Invoice {
     pub period_end: DateTime<Utc>,
     pub due_date: Option<DateTime<Utc>>,
     pub paid_at: Option<DateTime<Utc>>,
+    pub customer_name: Option<String>,
+    /// Postal address of the customer, one line per line
+    pub customer_address: Option<String>,
+    pub customer_country: Option<String>,
+    pub customer_vat_id: Option<String>,
+    pub vat_rate_bps: i32,
+    pub vat_reverse_charge: bool,
+    /// Object key of the rendered PDF
+    pub pdf_object_key: Option<String>,
+    pub pdf_url: Option<String>,
+    pub pdf_generated_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Line item of an invoice
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct InvoiceLineItem {
+    pub id: String,
+    pub invoice_id: String,
+    /// Order of the item on the invoice
+    pub position: i32,
+    pub description: String,
+    pub kind: InvoiceLineKind,
+    pub quantity: i64,
+    pub unit_amount_cents: Option<i64>,
+    pub amount_cents: i64,
+    pub created_at: DateTime<Utc>,
+}
+
 /// Usage tracking entry
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct UsageRecord {
@@ -503,6 +620,9 @@ impl SubscriptionDetails {
             trial_ends_at: self.trial_ends_at.map(Timestamp::from),
             cancel_at_period_end: self.cancel_at_period_end,
             canceled_at: self.canceled_at.map(Timestamp::from),
+            scheduled_plan_id: self.scheduled_plan_id.clone().unwrap_or_default(),
+            scheduled_billing_period: self.scheduled_billing_period.map_or(0, i32::from),
+            scheduled_change_at: self.scheduled_change_at.map(Timestamp::from),
         }
     }
 }
@@ -537,7 +657,10 @@ impl Plan {
 
 /// Convert Invoice to proto
 impl Invoice {
-    pub fn to_proto(&self) -> pistonprotection_proto::auth::Invoice {
+    pub fn to_proto(
+        &self,
+        line_items: &[InvoiceLineItem],
+    ) -> pistonprotection_proto::auth::Invoice {
         use pistonprotection_proto::Timestamp;
         use pistonprotection_proto::auth::Invoice as ProtoInvoice;
 
@@ -562,6 +685,44 @@ impl Invoice {
             due_date: self.due_date.map(Timestamp::from),
             paid_at: self.paid_at.map(Timestamp::from),
             created_at: Some(Timestamp::from(self.created_at)),
+            line_items: line_items.iter().map(InvoiceLineItem::to_proto).collect(),
+            customer_country: self.customer_country.clone().unwrap_or_default(),
+            vat_rate_bps: self.vat_rate_bps,
+            vat_reverse_charge: self.vat_reverse_charge,
+            pdf_url: self.pdf_url.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Convert InvoiceLineItem to proto
+impl InvoiceLineItem {
+    pub fn to_proto(&self) -> pistonprotection_proto::auth::InvoiceLineItem {
+        pistonprotection_proto::auth::InvoiceLineItem {
+            description: self.description.clone(),
+            kind: i32::from(self.kind),
+            quantity: self.quantity,
+            unit_amount_cents: self.unit_amount_cents.unwrap_or_default(),
+            amount_cents: self.amount_cents,
+        }
+    }
+}
+
+/// Convert PaymentMethod to proto
+impl PaymentMethod {
+    pub fn to_proto(&self) -> pistonprotection_proto::auth::PaymentMethod {
+        use pistonprotection_proto::Timestamp;
+        use pistonprotection_proto::auth::PaymentMethod as ProtoPaymentMethod;
+
+        ProtoPaymentMethod {
+            id: self.id.clone(),
+            organization_id: self.organization_id.clone(),
+            payment_type: self.payment_type.clone(),
+            card_brand: self.card_brand.clone().unwrap_or_default(),
+            card_last4: self.card_last4.clone().unwrap_or_default(),
+            card_exp_month: self.card_exp_month.unwrap_or_default(),
+            card_exp_year: self.card_exp_year.unwrap_or_default(),
+            is_default: self.is_default,
+            created_at: Some(Timestamp::from(self.created_at)),
         }
     }
 }
@@ -609,4 +770,16 @@ mod tests {
             InvoiceStatus::Draft
         );
     }
+
+    #[test]
+    fn test_billing_event_status_after_failure() {
+        assert_eq!(
+            BillingEventStatus::after_failure(1, 8),
+            BillingEventStatus::Failed
+        );
+        assert_eq!(
+            BillingEventStatus::after_failure(8, 8),
+            BillingEventStatus::Dead
+        );
+    }
 }
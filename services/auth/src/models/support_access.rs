@@ -0,0 +1,45 @@
+//! Support access model definitions
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Read-only access to an organization granted to support staff
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SupportAccessGrant {
+    pub id: String,
+    pub organization_id: String,
+    pub granted_by_user_id: String,
+    pub reason: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub revoked_by_user_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SupportAccessGrant {
+    /// Whether staff may still use the grant at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+/// Convert to proto SupportAccessGrant
+impl SupportAccessGrant {
+    pub fn to_proto(&self) -> pistonprotection_proto::auth::SupportAccessGrant {
+        use pistonprotection_proto::Timestamp;
+        use pistonprotection_proto::auth;
+
+        auth::SupportAccessGrant {
+            id: self.id.clone(),
+            organization_id: self.organization_id.clone(),
+            granted_by_user_id: self.granted_by_user_id.clone(),
+            reason: self.reason.clone(),
+            expires_at: Some(Timestamp::from(self.expires_at)),
+            revoked_at: self.revoked_at.map(Timestamp::from),
+            revoked_by_user_id: self.revoked_by_user_id.clone().unwrap_or_default(),
+            created_at: Some(Timestamp::from(self.created_at)),
+            active: self.is_active(Utc::now()),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 //! Audit logging service
 
+use pistonprotection_common::pagination::{Page, PageRequest};
 use sqlx::PgPool;
 use tracing::debug;
 
@@ -55,10 +56,9 @@ impl AuditService {
     pub async fn list(
         &self,
         filter: &AuditLogFilter,
-        page: u32,
-        page_size: u32,
-    ) -> Result<(Vec<AuditLogEntry>, u32), AuditError> {
-        db::list_audit_logs(&self.db, filter, page, page_size)
+        request: &PageRequest,
+    ) -> Result<(Page<AuditLogEntry>, u64), AuditError> {
+        db::list_audit_logs(&self.db, filter, request)
             .await
             .map_err(|e| AuditError::DatabaseError(e.to_string()))
     }
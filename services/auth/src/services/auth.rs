@@ -10,8 +10,14 @@ use tracing::{info, warn};
 
 use crate::config::AuthConfig;
 use crate::db;
-use crate::models::{CreateSession, Session, TokenPair, User};
-use crate::services::{JwtService, SessionService};
+use crate::models::{
+    AuditActions, AuditLogBuilder, CreateSession, RefreshToken, RefreshTokenState, Session,
+    TokenPair, User,
+};
+use crate::services::security_analytics::AuthEvent;
+use crate::services::{
+    AuditService, JwtService, SecurityAnalytics, SessionDeviceService, SessionService,
+};
 
 /// Authentication service
 pub struct AuthService {
@@ -19,6 +25,8 @@ pub struct AuthService {
     jwt_service: Arc<JwtService>,
     session_service: Arc<SessionService>,
     config: Arc<AuthConfig>,
+    devices: Option<SessionDeviceService>,
+    security: Option<SecurityAnalytics>,
 }
 
 impl AuthService {
@@ -34,7 +42,34 @@ impl AuthService {
             jwt_service,
             session_service,
             config,
+            devices: None,
+            security: None,
+        }
+    }
+
+    /// Locate logins and notify users of logins from new devices
+    pub fn with_devices(mut self, devices: SessionDeviceService) -> Self {
+        self.devices = Some(devices);
+        self
+    }
+
+    /// Analyze logins for impossible travel and credential stuffing
+    pub fn with_security_analytics(mut self, security: SecurityAnalytics) -> Self {
+        self.security = Some(security);
+        self
+    }
+
+    /// Record a failed login for analysis and return the error to fail with
+    fn failed_login(&self, email: &str, session_info: &CreateSession) -> AuthError {
+        if let Some(security) = &self.security {
+            security.record_from(session_info.ip_address.as_deref(), |ip| {
+                AuthEvent::LoginFailed {
+                    email: email.to_string(),
+                    ip,
+                }
+            });
         }
+        AuthError::InvalidCredentials
     }
 
     /// Hash a password using Argon2
@@ -131,18 +166,18 @@ impl AuthService {
         let user = db::get_user_by_email(&self.db, email)
             .await
             .map_err(|e| AuthError::DatabaseError(e.to_string()))?
-            .ok_or(AuthError::InvalidCredentials)?;
+            .ok_or_else(|| self.failed_login(email, &session_info))?;
 
         // Check if user has a password (not OAuth-only account)
         let password_hash = user
             .password_hash
             .as_ref()
-            .ok_or(AuthError::InvalidCredentials)?;
+            .ok_or_else(|| self.failed_login(email, &session_info))?;
 
         // Verify password
         if !self.verify_password(password, password_hash)? {
             warn!("Failed login attempt for user: {}", email);
-            return Err(AuthError::InvalidCredentials);
+            return Err(self.failed_login(email, &session_info));
         }
 
         // Check session limit
@@ -165,7 +200,7 @@ impl AuthService {
             .session_service
             .detect_device_type(session_info.user_agent.as_deref());
 
-        let session = db::create_session(
+        let mut session = db::create_session(
             &self.db,
             &session_id,
             &user.id,
@@ -178,6 +213,13 @@ impl AuthService {
         .await
         .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
 
+        // Locate the login and report it if the device or country is new
+        if let Some(devices) = &self.devices
+            && let Err(e) = devices.record_login(&user, &mut session).await
+        {
+            warn!("Failed to record login device for {}: {}", user.id, e);
+        }
+
         // Cache session
         self.session_service
             .cache_session(&session)
@@ -210,10 +252,7 @@ impl AuthService {
             )
             .map_err(|e| AuthError::TokenError(e.to_string()))?;
 
-        let refresh_token = self
-            .jwt_service
-            .generate_refresh_token(&user.id, &user.email, user.role, orgs, Some(&session_id))
-            .map_err(|e| AuthError::TokenError(e.to_string()))?;
+        let (refresh_token, _) = self.issue_refresh_token(&user, orgs, &session_id).await?;
 
         let token_pair = TokenPair::new(
             access_token,
@@ -226,6 +265,16 @@ impl AuthService {
             .await
             .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
 
+        if let Some(security) = &self.security {
+            security.record_from(session_info.ip_address.as_deref(), |ip| {
+                AuthEvent::LoginSucceeded {
+                    user_id: user.id.clone(),
+                    email: user.email.clone(),
+                    ip,
+                }
+            });
+        }
+
         info!("User logged in: {}", user.email);
 
         Ok((user, token_pair, session))
@@ -233,10 +282,7 @@ impl AuthService {
 
     /// Logout (invalidate session)
     pub async fn logout(&self, session_id: &str, user_id: &str) -> Result<(), AuthError> {
-        // Invalidate session in database
-        db::invalidate_session(&self.db, session_id)
-            .await
-            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        revoke_session(&self.db, session_id).await?;
 
         // Invalidate session in cache
         self.session_service
@@ -251,10 +297,13 @@ impl AuthService {
 
     /// Logout all sessions for a user
     pub async fn logout_all(&self, user_id: &str) -> Result<u64, AuthError> {
-        // Invalidate all sessions in database
+        // Invalidate all sessions and their refresh tokens in database
         let count = db::invalidate_user_sessions(&self.db, user_id)
             .await
             .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        db::revoke_user_refresh_tokens(&self.db, user_id)
+            .await
+            .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
 
         // Invalidate all sessions in cache
         self.session_service
@@ -268,12 +317,20 @@ impl AuthService {
     }
 
     /// Refresh access token using refresh token
+    ///
+    /// Refresh tokens rotate: each refresh revokes the presented token and
+    /// issues its successor. Presenting a token that was already exchanged
+    /// means a copy of it is being replayed, so the whole session is revoked
+    /// and a security audit event is raised.
     pub async fn refresh_token(&self, refresh_token: &str) -> Result<TokenPair, AuthError> {
         // Validate refresh token
         let claims = self
             .jwt_service
             .validate_refresh_token(refresh_token)
             .map_err(|e| AuthError::TokenError(e.to_string()))?;
+        let session_id = claims.sid.clone().ok_or_else(|| {
+            AuthError::TokenError("Refresh token is not bound to a session".to_string())
+        })?;
 
         // Get user
         let user = db::get_user_by_id(&self.db, &claims.sub)
@@ -281,19 +338,6 @@ impl AuthService {
             .map_err(|e| AuthError::DatabaseError(e.to_string()))?
             .ok_or(AuthError::UserNotFound)?;
 
-        // Check if session is still valid (if session ID in token)
-        if let Some(session_id) = &claims.sid {
-            let session = self
-                .session_service
-                .get_cached_session(session_id)
-                .await
-                .map_err(|e| AuthError::SessionError(e.to_string()))?;
-
-            if session.is_none() {
-                return Err(AuthError::SessionExpired);
-            }
-        }
-
         // Get user's organizations
         let orgs: Vec<String> = db::list_user_organizations(&self.db, &user.id)
             .await
@@ -302,6 +346,27 @@ impl AuthService {
             .map(|o| o.id)
             .collect();
 
+        let stored =
+            match presented_refresh_token(&self.db, refresh_token, &session_id, &user.id).await? {
+                PresentedRefreshToken::Current(stored) => Some(stored),
+                PresentedRefreshToken::Legacy => None,
+                PresentedRefreshToken::Reused(stored) => {
+                    self.revoke_reused_session(&user, &orgs, &session_id, &stored.id)
+                        .await?;
+                    return Err(AuthError::TokenReused);
+                }
+            };
+
+        // Check the session is still valid
+        let session = self
+            .session_service
+            .get_cached_session(&session_id)
+            .await
+            .map_err(|e| AuthError::SessionError(e.to_string()))?;
+        if session.is_none() {
+            return Err(AuthError::SessionExpired);
+        }
+
         // Generate new tokens
         let access_token = self
             .jwt_service
@@ -310,20 +375,26 @@ impl AuthService {
                 &user.email,
                 user.role,
                 orgs.clone(),
-                claims.sid.as_deref(),
+                Some(&session_id),
             )
             .map_err(|e| AuthError::TokenError(e.to_string()))?;
 
-        let new_refresh_token = self
-            .jwt_service
-            .generate_refresh_token(
-                &user.id,
-                &user.email,
-                user.role,
-                orgs,
-                claims.sid.as_deref(),
-            )
-            .map_err(|e| AuthError::TokenError(e.to_string()))?;
+        let (new_refresh_token, new_id) = self
+            .issue_refresh_token(&user, orgs.clone(), &session_id)
+            .await?;
+
+        // Retire the presented token; losing the race against a concurrent
+        // refresh with the same token is a replay as well
+        if let Some(stored) = &stored {
+            let rotated = db::rotate_refresh_token(&self.db, &stored.id, &new_id)
+                .await
+                .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+            if !rotated {
+                self.revoke_reused_session(&user, &orgs, &session_id, &stored.id)
+                    .await?;
+                return Err(AuthError::TokenReused);
+            }
+        }
 
         let token_pair = TokenPair::new(
             access_token,
@@ -334,6 +405,79 @@ impl AuthService {
         Ok(token_pair)
     }
 
+    /// Issue a refresh token for a session and record it for rotation
+    ///
+    /// Returns the token and the ID of its record.
+    async fn issue_refresh_token(
+        &self,
+        user: &User,
+        orgs: Vec<String>,
+        session_id: &str,
+    ) -> Result<(String, String), AuthError> {
+        let token = self
+            .jwt_service
+            .generate_refresh_token(&user.id, &user.email, user.role, orgs, Some(session_id))
+            .map_err(|e| AuthError::TokenError(e.to_string()))?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let expires_at = chrono::Utc::now()
+            + chrono::Duration::seconds(self.jwt_service.refresh_token_ttl_secs());
+        db::create_refresh_token(
+            &self.db,
+            &id,
+            &user.id,
+            session_id,
+            &SessionService::hash_token(&token),
+            expires_at,
+        )
+        .await
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+
+        Ok((token, id))
+    }
+
+    /// Revoke a session whose refresh token was replayed
+    ///
+    /// Whoever holds a copy of the token is locked out along with the
+    /// legitimate client, which has to log in again.
+    async fn revoke_reused_session(
+        &self,
+        user: &User,
+        orgs: &[String],
+        session_id: &str,
+        token_id: &str,
+    ) -> Result<(), AuthError> {
+        // Revokes the session's refresh tokens, including any issued since
+        self.logout(session_id, &user.id).await?;
+
+        warn!(
+            user_id = %user.id,
+            session_id = %session_id,
+            "Refresh token reuse detected, session revoked"
+        );
+
+        // Sessions belong to users, so every organization of the user sees it
+        let audit = AuditService::new(self.db.clone());
+        for org_id in orgs {
+            audit
+                .log_builder(
+                    AuditLogBuilder::new(
+                        org_id,
+                        AuditActions::SESSION_REFRESH_TOKEN_REUSED,
+                        "session",
+                    )
+                    .user(&user.id, Some(&user.email))
+                    .resource(session_id)
+                    .description("Refresh token reused, session revoked as compromised")
+                    .metadata("refresh_token_id", token_id),
+                )
+                .await
+                .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
     /// Validate access token and return user
     pub async fn validate_token(&self, access_token: &str) -> Result<(User, Session), AuthError> {
         // Validate token
@@ -370,6 +514,7 @@ impl AuthService {
                 ip_address: cached.ip_address,
                 user_agent: cached.user_agent,
                 device_type: cached.device_type,
+                country_code: cached.country_code,
                 active: true,
                 expires_at: chrono::DateTime::from_timestamp(cached.expires_at, 0)
                     .unwrap_or_else(chrono::Utc::now),
@@ -387,6 +532,7 @@ impl AuthService {
                 ip_address: None,
                 user_agent: None,
                 device_type: None,
+                country_code: None,
                 active: true,
                 expires_at: chrono::DateTime::from_timestamp(claims.exp, 0)
                     .unwrap_or_else(chrono::Utc::now),
@@ -442,6 +588,64 @@ impl AuthService {
     }
 }
 
+/// Refresh token presented for an exchange
+#[derive(Debug)]
+enum PresentedRefreshToken {
+    /// Current token of its session
+    Current(RefreshToken),
+    /// Token of a session opened before rotation, which has no stored
+    /// tokens; it is exchanged once and rotates from then on
+    Legacy,
+    /// Already exchanged: a copy of the token is being replayed
+    Reused(RefreshToken),
+}
+
+/// Find a presented refresh token among those issued for its session
+async fn presented_refresh_token(
+    pool: &PgPool,
+    refresh_token: &str,
+    session_id: &str,
+    user_id: &str,
+) -> Result<PresentedRefreshToken, AuthError> {
+    let token_hash = SessionService::hash_token(refresh_token);
+    let stored = db::get_refresh_token_by_hash(pool, &token_hash)
+        .await
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+
+    match stored {
+        Some(stored) if stored.session_id == session_id && stored.user_id == user_id => {
+            match stored.state(chrono::Utc::now()) {
+                RefreshTokenState::Usable => Ok(PresentedRefreshToken::Current(stored)),
+                RefreshTokenState::Reused => Ok(PresentedRefreshToken::Reused(stored)),
+                RefreshTokenState::Revoked | RefreshTokenState::Expired => {
+                    Err(AuthError::SessionExpired)
+                }
+            }
+        }
+        Some(_) => Err(AuthError::TokenError("Invalid refresh token".to_string())),
+        None => {
+            let issued = db::count_session_refresh_tokens(pool, session_id)
+                .await
+                .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+            if issued > 0 {
+                return Err(AuthError::TokenError("Invalid refresh token".to_string()));
+            }
+            Ok(PresentedRefreshToken::Legacy)
+        }
+    }
+}
+
+/// Invalidate a session and its refresh tokens in the database
+async fn revoke_session(pool: &PgPool, session_id: &str) -> Result<(), AuthError> {
+    db::invalidate_session(pool, session_id)
+        .await
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    db::revoke_session_refresh_tokens(pool, session_id)
+        .await
+        .map_err(|e| AuthError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
 /// Authentication errors
 #[derive(Debug, thiserror::Error)]
 pub enum AuthError {
@@ -457,6 +661,9 @@ pub enum AuthError {
     #[error("Maximum sessions exceeded")]
     MaxSessionsExceeded,
 
+    #[error("Refresh token reused")]
+    TokenReused,
+
     #[error("Weak password: {0}")]
     WeakPassword(String),
 
@@ -484,6 +691,9 @@ impl From<AuthError> for tonic::Status {
             AuthError::MaxSessionsExceeded => tonic::Status::resource_exhausted(
                 "Maximum sessions exceeded. Please logout from another device.",
             ),
+            AuthError::TokenReused => tonic::Status::unauthenticated(
+                "Refresh token was already used. The session has been revoked, please log in again.",
+            ),
             AuthError::WeakPassword(msg) => tonic::Status::invalid_argument(msg),
             AuthError::PasswordHashError(msg) => {
                 tonic::Status::internal(format!("Password processing error: {}", msg))
@@ -494,3 +704,207 @@ impl From<AuthError> for tonic::Status {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use sqlx::postgres::PgConnectOptions;
+    use std::str::FromStr;
+
+    /// Pool on a throwaway schema of the database at `DATABASE_URL` with the
+    /// auth migrations applied, and the schema's name; `None` without one
+    async fn test_pool() -> Option<(PgPool, String)> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        let schema = format!("auth_test_{}", uuid::Uuid::new_v4().simple());
+
+        let options = PgConnectOptions::from_str(&url)
+            .unwrap()
+            .options([("search_path", schema.as_str())]);
+        let pool = PgPool::connect_with(options).await.unwrap();
+        sqlx::raw_sql(&format!("CREATE SCHEMA {schema}"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        db::run_migrations(&pool).await.unwrap();
+
+        sqlx::raw_sql(
+            r#"
+            INSERT INTO users (id, email, username, name)
+            VALUES ('user-1', 'user@example.com', 'user', 'User');
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        Some((pool, schema))
+    }
+
+    async fn drop_schema(pool: PgPool, schema: &str) {
+        sqlx::raw_sql(&format!("DROP SCHEMA {schema} CASCADE"))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    async fn session(pool: &PgPool, id: &str) {
+        db::create_session(
+            pool,
+            id,
+            "user-1",
+            &SessionService::hash_token(id),
+            None,
+            None,
+            None,
+            Utc::now() + Duration::days(1),
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Store `token` as a refresh token of `session_id`, returning its ID
+    async fn issue(pool: &PgPool, session_id: &str, token: &str) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        db::create_refresh_token(
+            pool,
+            &id,
+            "user-1",
+            session_id,
+            &SessionService::hash_token(token),
+            Utc::now() + Duration::days(1),
+        )
+        .await
+        .unwrap();
+        id
+    }
+
+    async fn present(pool: &PgPool, token: &str) -> Result<PresentedRefreshToken, AuthError> {
+        presented_refresh_token(pool, token, "session-1", "user-1").await
+    }
+
+    /// Replaying an exchanged token is reuse, and revoking the session
+    /// retires the successor it was exchanged for; skipped without a
+    /// `DATABASE_URL`
+    #[tokio::test]
+    async fn test_refresh_token_reuse_revokes_session() {
+        let Some((pool, schema)) = test_pool().await else {
+            return;
+        };
+        session(&pool, "session-1").await;
+        let first = issue(&pool, "session-1", "token-1").await;
+
+        assert!(matches!(
+            present(&pool, "token-1").await,
+            Ok(PresentedRefreshToken::Current(stored)) if stored.id == first
+        ));
+        let second = issue(&pool, "session-1", "token-2").await;
+        assert!(
+            db::rotate_refresh_token(&pool, &first, &second)
+                .await
+                .unwrap()
+        );
+
+        assert!(matches!(
+            present(&pool, "token-1").await,
+            Ok(PresentedRefreshToken::Reused(stored)) if stored.id == first
+        ));
+        revoke_session(&pool, "session-1").await.unwrap();
+
+        let active: bool = sqlx::query_scalar("SELECT active FROM sessions WHERE id = 'session-1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(!active);
+        assert!(matches!(
+            present(&pool, "token-2").await,
+            Err(AuthError::SessionExpired)
+        ));
+        // Replays after the revocation are still told apart from a logout
+        assert!(matches!(
+            present(&pool, "token-1").await,
+            Ok(PresentedRefreshToken::Reused(_))
+        ));
+
+        drop_schema(pool, &schema).await;
+    }
+
+    /// Of two refreshes with the same token only one rotates it; the other
+    /// finds it exchanged, as a replay would; skipped without a
+    /// `DATABASE_URL`
+    #[tokio::test]
+    async fn test_refresh_token_rotate_race() {
+        let Some((pool, schema)) = test_pool().await else {
+            return;
+        };
+        session(&pool, "session-1").await;
+        let first = issue(&pool, "session-1", "token-1").await;
+
+        // Both refreshes look the token up before either rotates it
+        for _ in 0..2 {
+            assert!(matches!(
+                present(&pool, "token-1").await,
+                Ok(PresentedRefreshToken::Current(_))
+            ));
+        }
+        let winner = issue(&pool, "session-1", "token-2").await;
+        let loser = issue(&pool, "session-1", "token-3").await;
+        assert!(
+            db::rotate_refresh_token(&pool, &first, &winner)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !db::rotate_refresh_token(&pool, &first, &loser)
+                .await
+                .unwrap()
+        );
+
+        let replaced_by: Option<String> =
+            sqlx::query_scalar("SELECT replaced_by FROM refresh_tokens WHERE id = $1")
+                .bind(&first)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(replaced_by, Some(winner));
+        assert!(matches!(
+            present(&pool, "token-1").await,
+            Ok(PresentedRefreshToken::Reused(_))
+        ));
+
+        drop_schema(pool, &schema).await;
+    }
+
+    /// The token of a session opened before rotation is exchanged once;
+    /// skipped without a `DATABASE_URL`
+    #[tokio::test]
+    async fn test_legacy_refresh_token_exchanged_once() {
+        let Some((pool, schema)) = test_pool().await else {
+            return;
+        };
+        session(&pool, "session-1").await;
+
+        assert!(matches!(
+            present(&pool, "legacy-token").await,
+            Ok(PresentedRefreshToken::Legacy)
+        ));
+        issue(&pool, "session-1", "token-1").await;
+
+        assert!(matches!(
+            present(&pool, "legacy-token").await,
+            Err(AuthError::TokenError(_))
+        ));
+        assert!(matches!(
+            present(&pool, "token-1").await,
+            Ok(PresentedRefreshToken::Current(_))
+        ));
+
+        // Stored tokens are bound to their session
+        session(&pool, "session-2").await;
+        assert!(matches!(
+            presented_refresh_token(&pool, "token-1", "session-2", "user-1").await,
+            Err(AuthError::TokenError(_))
+        ));
+
+        drop_schema(pool, &schema).await;
+    }
+}
@@ -0,0 +1,189 @@
+//! Stored Stripe webhook events and their processing state
+//!
+//! Every webhook event is stored with its payload before it is processed, so
+//! a failed event can be replayed from the database instead of waiting for
+//! Stripe to send it again. Events move through received -> processing ->
+//! processed; claiming one is a conditional update, so an event delivered
+//! twice, or retried by several replicas at once, is processed once. Failed
+//! events are retried with exponential backoff and marked dead after the
+//! configured number of attempts.
+
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::subscription::{BillingEvent, BillingEventStatus};
+
+/// Delay before the first retry of a failed event
+const BASE_RETRY_DELAY_SECS: i64 = 60;
+
+/// Longest delay between two retries
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 3600;
+
+/// A claim on an event that is older than this is from a replica that died
+/// while processing it
+const PROCESSING_TIMEOUT_SECS: f64 = 600.0;
+
+/// Events retried per run
+const RETRY_BATCH_SIZE: i64 = 100;
+
+/// Delay before retrying an event that failed on its n-th attempt
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    let delay = BASE_RETRY_DELAY_SECS.saturating_mul(1 << exponent);
+    Duration::seconds(delay.min(MAX_RETRY_DELAY_SECS))
+}
+
+/// Store of Stripe webhook events
+#[derive(Clone)]
+pub struct BillingEventService {
+    db: PgPool,
+    max_attempts: u32,
+}
+
+impl BillingEventService {
+    /// Create a new billing event service
+    pub fn new(db: PgPool, max_attempts: u32) -> Self {
+        Self { db, max_attempts }
+    }
+
+    /// Store a received event, or return the stored one if it was delivered
+    /// before
+    pub async fn record(
+        &self,
+        stripe_event_id: &str,
+        event_type: &str,
+        payload: &serde_json::Value,
+    ) -> Result<BillingEvent> {
+        sqlx::query(
+            r#"
+            INSERT INTO billing_events (
+                id, stripe_event_id, event_type, payload, status, created_at, updated_at
+            ) VALUES ($1, $2, $3, $4, 'received', NOW(), NOW())
+            ON CONFLICT (stripe_event_id) DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(stripe_event_id)
+        .bind(event_type)
+        .bind(payload)
+        .execute(&self.db)
+        .await
+        .context("Failed to store billing event")?;
+
+        sqlx::query_as::<_, BillingEvent>(
+            r#"
+            SELECT * FROM billing_events WHERE stripe_event_id = $1
+            "#,
+        )
+        .bind(stripe_event_id)
+        .fetch_one(&self.db)
+        .await
+        .context("Failed to fetch billing event")
+    }
+
+    /// Claim an event for processing
+    ///
+    /// Returns `None` if the event is processed, dead, or being processed
+    /// elsewhere.
+    pub async fn claim(&self, id: &str) -> Result<Option<BillingEvent>> {
+        sqlx::query_as::<_, BillingEvent>(
+            r#"
+            UPDATE billing_events
+            SET status = 'processing', attempts = attempts + 1, updated_at = NOW()
+            WHERE id = $1
+              AND (status IN ('received', 'failed')
+                   OR (status = 'processing'
+                       AND updated_at < NOW() - make_interval(secs => $2)))
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(PROCESSING_TIMEOUT_SECS)
+        .fetch_optional(&self.db)
+        .await
+        .context("Failed to claim billing event")
+    }
+
+    /// Mark a claimed event processed
+    pub async fn complete(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE billing_events
+            SET status = 'processed', processed = TRUE, processed_at = NOW(),
+                error_message = NULL, next_attempt_at = NULL, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(&self.db)
+        .await
+        .context("Failed to complete billing event")?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt at a claimed event, scheduling its retry
+    ///
+    /// Returns the state the event is left in.
+    pub async fn fail(&self, event: &BillingEvent, error: &str) -> Result<BillingEventStatus> {
+        let status = BillingEventStatus::after_failure(event.attempts, self.max_attempts);
+        let next_attempt_at = (status == BillingEventStatus::Failed)
+            .then(|| Utc::now() + retry_delay(event.attempts));
+
+        sqlx::query(
+            r#"
+            UPDATE billing_events
+            SET status = $2, error_message = $3, next_attempt_at = $4, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(&event.id)
+        .bind(status)
+        .bind(error)
+        .bind(next_attempt_at)
+        .execute(&self.db)
+        .await
+        .context("Failed to record billing event failure")?;
+
+        Ok(status)
+    }
+
+    /// Events due for another attempt, oldest first
+    ///
+    /// Besides failed events whose retry is due, these are events stored but
+    /// never processed and claims abandoned by a replica that went away.
+    pub async fn due(&self) -> Result<Vec<BillingEvent>> {
+        sqlx::query_as::<_, BillingEvent>(
+            r#"
+            SELECT * FROM billing_events
+            WHERE (status = 'failed' AND next_attempt_at <= NOW())
+               OR (status IN ('received', 'processing')
+                   AND updated_at < NOW() - make_interval(secs => $1))
+            ORDER BY created_at
+            LIMIT $2
+            "#,
+        )
+        .bind(PROCESSING_TIMEOUT_SECS)
+        .bind(RETRY_BATCH_SIZE)
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to fetch due billing events")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::seconds(60));
+        assert_eq!(retry_delay(2), Duration::seconds(120));
+        assert_eq!(retry_delay(4), Duration::seconds(480));
+        // Capped, however often it failed
+        assert_eq!(retry_delay(30), Duration::seconds(MAX_RETRY_DELAY_SECS));
+        assert_eq!(retry_delay(0), Duration::seconds(60));
+    }
+}
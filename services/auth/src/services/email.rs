@@ -520,6 +520,28 @@ impl EmailService {
                 base_style, btn_style
             ),
 
+            EmailTemplate::TrialExpired => format!(
+                r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><meta name="viewport" content="width=device-width, initial-scale=1.0"></head>
+<body style="{}">
+<div style="max-width: 600px; margin: 0 auto; padding: 20px; background: #ffffff;">
+    <h1 style="color: #dc2626;">Your Trial Has Ended</h1>
+    <p>Hi {{{{recipient_name}}}},</p>
+    <p>Your free trial of PistonProtection ended on <strong>{{{{trial_end_date}}}}</strong>.</p>
+    <div style="background: #fef2f2; padding: 16px; border-radius: 8px; margin: 24px 0; border-left: 4px solid #dc2626;">
+        <p style="margin: 0;">Unless you upgrade by <strong>{{{{grace_end_date}}}}</strong>, {{{{expiry_consequence}}}}.</p>
+    </div>
+    <p style="text-align: center; margin: 32px 0;">
+        <a href="{{{{base_url}}}}/dashboard/billing" style="{}">Upgrade Now</a>
+    </p>
+    <p style="color: #6b7280;">Best regards,<br>The PistonProtection Team</p>
+</div>
+</body>
+</html>"#,
+                base_style, btn_style
+            ),
+
             EmailTemplate::PaymentReceived => format!(
                 r#"<!DOCTYPE html>
 <html>
@@ -741,6 +763,32 @@ impl EmailService {
                 base_style, btn_style
             ),
 
+            EmailTemplate::NewDeviceLogin => format!(
+                r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><meta name="viewport" content="width=device-width, initial-scale=1.0"></head>
+<body style="{}">
+<div style="max-width: 600px; margin: 0 auto; padding: 20px; background: #ffffff;">
+    <h1 style="color: #2563eb;">New Sign-in to Your Account</h1>
+    <p>Hi {{{{recipient_name}}}},</p>
+    <p>Your PistonProtection account was just signed in to {{{{reason}}}}:</p>
+    <div style="background: #f3f4f6; padding: 16px; border-radius: 8px; margin: 24px 0;">
+        <p style="margin: 4px 0;"><strong>Device:</strong> {{{{device}}}}</p>
+        <p style="margin: 4px 0;"><strong>Location:</strong> {{{{location}}}}</p>
+        <p style="margin: 4px 0;"><strong>IP Address:</strong> {{{{ip_address}}}}</p>
+        <p style="margin: 4px 0;"><strong>Time:</strong> {{{{timestamp}}}}</p>
+    </div>
+    <p>If this was you, there is nothing to do. If you don't recognize this sign-in, sign out the session and change your password right away.</p>
+    <p style="text-align: center; margin: 32px 0;">
+        <a href="{{{{base_url}}}}/settings/security" style="{}">Review Sessions</a>
+    </p>
+    <p style="color: #6b7280;">Best regards,<br>The PistonProtection Team</p>
+</div>
+</body>
+</html>"#,
+                base_style, danger_btn_style
+            ),
+
             // Default template for other types
             _ => format!(
                 r#"<!DOCTYPE html>
@@ -800,6 +848,21 @@ impl EmailService {
         self.send(message).await
     }
 
+    /// Send trial expired notification
+    pub async fn send_trial_expired_email(
+        &self,
+        recipient: EmailRecipient,
+        trial_end_date: &str,
+        grace_end_date: &str,
+        expiry_consequence: &str,
+    ) -> Result<EmailResult> {
+        let message = EmailMessage::new(recipient, EmailTemplate::TrialExpired)
+            .with_variable("trial_end_date", trial_end_date)
+            .with_variable("grace_end_date", grace_end_date)
+            .with_variable("expiry_consequence", expiry_consequence);
+        self.send(message).await
+    }
+
     /// Send payment received email
     pub async fn send_payment_received_email(
         &self,
@@ -874,6 +937,30 @@ impl EmailService {
         self.send(message).await
     }
 
+    /// Send new device or country login notification
+    pub async fn send_new_device_login_email(
+        &self,
+        recipient: EmailRecipient,
+        reason: &str,
+        device: &str,
+        location: &str,
+        ip_address: &str,
+    ) -> Result<EmailResult> {
+        // The device comes from the login's user agent, which the client controls
+        let message = EmailMessage::new(recipient, EmailTemplate::NewDeviceLogin)
+            .with_variable("reason", reason)
+            .with_variable("device", escape_html(device))
+            .with_variable("location", escape_html(location))
+            .with_variable("ip_address", escape_html(ip_address))
+            .with_variable(
+                "timestamp",
+                chrono::Utc::now()
+                    .format("%Y-%m-%d %H:%M:%S UTC")
+                    .to_string(),
+            );
+        self.send(message).await
+    }
+
     /// Send team invitation email
     pub async fn send_invitation_email(
         &self,
@@ -930,6 +1017,22 @@ impl EmailService {
     }
 }
 
+/// Escape text for inclusion in an HTML template
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -964,6 +1067,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+        assert_eq!(escape_html("Firefox/128.0"), "Firefox/128.0");
+    }
+
     #[tokio::test]
     async fn test_email_service_disabled() {
         let config = EmailConfig {
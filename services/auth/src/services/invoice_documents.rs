@@ -0,0 +1,327 @@
+//! Invoice documents
+//!
+//! Invoices synced from Stripe are rendered as PDFs listing their line items,
+//! with overages in their own section and the VAT of the customer's country
+//! (see `vat`). The PDFs are stored in object storage and the object's URL is
+//! saved on the invoice. An invoice is rendered again whenever its record
+//! changes, e.g. once it is paid. Customers download PDFs through short-lived
+//! presigned URLs, so the bucket stays private.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use pistonprotection_common::redis::CacheService;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::config::InvoiceConfig;
+use crate::models::subscription::{Invoice, InvoiceLineItem, InvoiceLineKind, InvoiceStatus};
+use crate::services::invoice_pdf::{self, InvoiceDocument, Party};
+use crate::services::object_storage::{MAX_PRESIGN_SECS, ObjectStorage};
+use crate::services::vat::{self, VatTreatment};
+
+/// Shortest time between two rendering runs in seconds
+const MIN_RENDER_INTERVAL_SECS: u64 = 30;
+
+/// Invoices rendered per run
+const RENDER_BATCH_SIZE: i64 = 100;
+
+/// Presigned URL of an invoice PDF
+#[derive(Debug, Clone)]
+pub struct InvoiceDownload {
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Line items of invoices, by invoice ID
+pub async fn line_items(
+    db: &PgPool,
+    invoice_ids: &[String],
+) -> Result<HashMap<String, Vec<InvoiceLineItem>>> {
+    let items = sqlx::query_as::<_, InvoiceLineItem>(
+        r#"
+        SELECT * FROM invoice_line_items
+        WHERE invoice_id = ANY($1)
+        ORDER BY invoice_id, position
+        "#,
+    )
+    .bind(invoice_ids)
+    .fetch_all(db)
+    .await
+    .context("Failed to fetch invoice line items")?;
+
+    let mut by_invoice: HashMap<String, Vec<InvoiceLineItem>> = HashMap::new();
+    for item in items {
+        by_invoice
+            .entry(item.invoice_id.clone())
+            .or_default()
+            .push(item);
+    }
+    Ok(by_invoice)
+}
+
+/// Object key of the PDF of an invoice
+fn object_key(prefix: &str, invoice: &Invoice) -> String {
+    let prefix = prefix.trim_matches('/');
+    let key = format!("{}/{}.pdf", invoice.organization_id, invoice.id);
+    if prefix.is_empty() {
+        key
+    } else {
+        format!("{}/{}", prefix, key)
+    }
+}
+
+/// Legal notes of an invoice with the given VAT treatment
+fn vat_notes(treatment: VatTreatment, seller_in_eu: bool, tax_cents: i64) -> Vec<String> {
+    let note = match treatment {
+        VatTreatment::ReverseCharge => Some(
+            "Reverse charge: VAT is to be accounted for by the recipient \
+             (Article 196, Council Directive 2006/112/EC).",
+        ),
+        VatTreatment::Exempt if seller_in_eu && tax_cents == 0 => {
+            Some("Not subject to EU VAT: the services are supplied outside the EU.")
+        }
+        VatTreatment::Standard { .. } if tax_cents == 0 => Some("Amounts include VAT."),
+        _ => None,
+    };
+    note.map(str::to_string).into_iter().collect()
+}
+
+/// Invoice PDF renderer and storage
+pub struct InvoiceDocumentService {
+    db: PgPool,
+    config: InvoiceConfig,
+    storage: ObjectStorage,
+}
+
+impl InvoiceDocumentService {
+    /// Create a new invoice document service
+    pub fn new(db: PgPool, config: InvoiceConfig) -> Result<Self> {
+        let storage = ObjectStorage::new(config.storage.clone())?;
+        Ok(Self {
+            db,
+            config,
+            storage,
+        })
+    }
+
+    /// Render new and changed invoices on schedule, once per interval across
+    /// all replicas
+    pub async fn run(self, cache: CacheService) {
+        let interval = Duration::from_secs(
+            self.config
+                .render_interval_secs
+                .max(MIN_RENDER_INTERVAL_SECS),
+        );
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            ticker.tick().await;
+
+            match cache
+                .set_nx("billing:invoice_documents", &true, interval)
+                .await
+            {
+                Ok(true) => {}
+                Ok(false) => continue, // Another replica rendered this interval
+                Err(e) => {
+                    warn!(error = %e, "Failed to take invoice rendering turn");
+                    continue;
+                }
+            }
+
+            match self.render_pending().await {
+                Ok((0, 0)) => {}
+                Ok((rendered, failed)) => info!(rendered, failed, "Rendered invoices"),
+                Err(e) => error!(error = %e, "Failed to render invoices"),
+            }
+        }
+    }
+
+    /// Render open and paid invoices without an up-to-date PDF
+    ///
+    /// Returns the number of invoices rendered and failed.
+    async fn render_pending(&self) -> Result<(usize, usize)> {
+        let invoices = sqlx::query_as::<_, Invoice>(
+            r#"
+            SELECT * FROM invoices
+            WHERE status IN ('open', 'paid')
+              AND (pdf_generated_at IS NULL OR updated_at > pdf_generated_at)
+            ORDER BY updated_at
+            LIMIT $1
+            "#,
+        )
+        .bind(RENDER_BATCH_SIZE)
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to fetch invoices to render")?;
+
+        let (mut rendered, mut failed) = (0, 0);
+        for invoice in invoices {
+            match self.render(&invoice).await {
+                Ok(_) => rendered += 1,
+                Err(e) => {
+                    warn!(invoice_id = %invoice.id, error = %e, "Failed to render invoice");
+                    failed += 1;
+                }
+            }
+        }
+        Ok((rendered, failed))
+    }
+
+    /// Render an invoice and store its PDF
+    ///
+    /// Returns the object key of the PDF.
+    async fn render(&self, invoice: &Invoice) -> Result<String> {
+        let mut items = line_items(&self.db, std::slice::from_ref(&invoice.id))
+            .await?
+            .remove(&invoice.id)
+            .unwrap_or_default();
+        if items.is_empty() {
+            // Invoices synced before line items were stored
+            items.push(InvoiceLineItem {
+                id: String::new(),
+                invoice_id: invoice.id.clone(),
+                position: 0,
+                description: invoice
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| "Subscription".to_string()),
+                kind: InvoiceLineKind::Subscription,
+                quantity: 0,
+                unit_amount_cents: None,
+                amount_cents: invoice.subtotal_cents,
+                created_at: invoice.created_at,
+            });
+        }
+
+        let organization_name: Option<(String,)> =
+            sqlx::query_as("SELECT name FROM organizations WHERE id = $1")
+                .bind(&invoice.organization_id)
+                .fetch_optional(&self.db)
+                .await
+                .context("Failed to fetch organization")?;
+
+        let seller_in_eu = vat::standard_rate_bps(&self.config.seller_country).is_some();
+        let treatment = vat::treatment(
+            &self.config.seller_country,
+            invoice.customer_country.as_deref(),
+            invoice.customer_vat_id.as_deref(),
+        );
+        let breakdown = vat::breakdown(treatment, invoice.total_cents, invoice.tax_cents);
+
+        let mut customer_address: Vec<String> = invoice
+            .customer_address
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        customer_address.extend(invoice.customer_country.clone());
+
+        let document = InvoiceDocument {
+            invoice,
+            line_items: &items,
+            seller: Party {
+                name: self.config.seller_name.clone(),
+                address: self.config.seller_address.clone(),
+                vat_id: Some(self.config.seller_vat_id.clone()).filter(|id| !id.is_empty()),
+            },
+            customer: Party {
+                name: invoice
+                    .customer_name
+                    .clone()
+                    .or(organization_name.map(|(name,)| name))
+                    .unwrap_or_default(),
+                address: customer_address,
+                vat_id: invoice.customer_vat_id.clone(),
+            },
+            vat: breakdown,
+            notes: vat_notes(treatment, seller_in_eu, invoice.tax_cents),
+        };
+        let pdf = invoice_pdf::render(&document);
+
+        let key = object_key(&self.config.key_prefix, invoice);
+        let url = self.storage.put(&key, pdf, "application/pdf").await?;
+
+        // The PDF shows the invoice as of `updated_at`; a change synced in the
+        // meantime renders it again
+        sqlx::query(
+            r#"
+            UPDATE invoices
+            SET pdf_object_key = $2, pdf_url = $3, pdf_generated_at = $4,
+                vat_rate_bps = $5, vat_reverse_charge = $6
+            WHERE id = $1
+            "#,
+        )
+        .bind(&invoice.id)
+        .bind(&key)
+        .bind(&url)
+        .bind(invoice.updated_at)
+        .bind(breakdown.rate_bps as i32)
+        .bind(breakdown.reverse_charge)
+        .execute(&self.db)
+        .await
+        .context("Failed to save invoice PDF")?;
+
+        Ok(key)
+    }
+
+    /// Presigned URL of the PDF of an organization's invoice, rendering it
+    /// first if it has none or it is out of date
+    ///
+    /// Returns `None` for unknown invoices and drafts.
+    pub async fn download_url(
+        &self,
+        organization_id: &str,
+        invoice_id: &str,
+    ) -> Result<Option<InvoiceDownload>> {
+        let invoice = sqlx::query_as::<_, Invoice>(
+            "SELECT * FROM invoices WHERE id = $1 AND organization_id = $2",
+        )
+        .bind(invoice_id)
+        .bind(organization_id)
+        .fetch_optional(&self.db)
+        .await
+        .context("Failed to fetch invoice")?;
+
+        let Some(invoice) = invoice.filter(|invoice| invoice.status != InvoiceStatus::Draft) else {
+            return Ok(None);
+        };
+
+        let key = match (&invoice.pdf_object_key, invoice.pdf_generated_at) {
+            (Some(key), Some(generated_at)) if generated_at >= invoice.updated_at => key.clone(),
+            _ => self.render(&invoice).await?,
+        };
+
+        let now = Utc::now();
+        let ttl_secs = self.config.download_url_ttl_secs.clamp(1, MAX_PRESIGN_SECS);
+        let url = self
+            .storage
+            .presigned_get_url(&key, Duration::from_secs(ttl_secs), now);
+        let expires_at = now + chrono::Duration::seconds(ttl_secs as i64);
+
+        Ok(Some(InvoiceDownload { url, expires_at }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vat_notes() {
+        assert!(vat_notes(VatTreatment::ReverseCharge, true, 0)[0].contains("Reverse charge"));
+        assert_eq!(
+            vat_notes(VatTreatment::Standard { rate_bps: 1900 }, true, 0),
+            vec!["Amounts include VAT.".to_string()]
+        );
+        // VAT Stripe charged on top is listed in the totals
+        assert!(vat_notes(VatTreatment::Standard { rate_bps: 1900 }, true, 1900).is_empty());
+        assert_eq!(vat_notes(VatTreatment::Exempt, true, 0).len(), 1);
+        // Sellers outside the EU don't deal with EU VAT
+        assert!(vat_notes(VatTreatment::Exempt, false, 0).is_empty());
+    }
+}
@@ -0,0 +1,654 @@
+//! Invoice PDFs
+//!
+//! Invoices are rendered as plain PDF 1.4 documents on A4 pages using the
+//! standard Helvetica fonts, which every PDF reader ships, so no fonts are
+//! embedded. Text is WinAnsi encoded; characters outside of it are replaced.
+
+use chrono::{DateTime, Utc};
+
+use crate::models::subscription::{Invoice, InvoiceLineItem, InvoiceLineKind, InvoiceStatus};
+use crate::services::vat::{self, VatBreakdown};
+
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+const MARGIN: f64 = 50.0;
+
+/// Lowest baseline of the content of a page, above the footer
+const CONTENT_BOTTOM: f64 = 80.0;
+
+const DESCRIPTION_WIDTH: f64 = 260.0;
+const QUANTITY_RIGHT: f64 = 375.0;
+const UNIT_PRICE_RIGHT: f64 = 460.0;
+const AMOUNT_RIGHT: f64 = PAGE_WIDTH - MARGIN;
+const TOTALS_LEFT: f64 = 330.0;
+
+const TEXT_SIZE: f64 = 9.0;
+const LINE_HEIGHT: f64 = 12.0;
+
+/// Widths of the characters 32 to 126 of Helvetica in 1/1000 em
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Widths of the characters 32 to 126 of Helvetica-Bold in 1/1000 em
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+
+    /// Width of a text in points
+    fn width(self, text: &str, size: f64) -> f64 {
+        let widths = match self {
+            Font::Regular => &HELVETICA_WIDTHS,
+            Font::Bold => &HELVETICA_BOLD_WIDTHS,
+        };
+        let units: u32 = text
+            .chars()
+            .map(|c| match c {
+                ' '..='~' => widths[c as usize - 32] as u32,
+                _ => 556,
+            })
+            .sum();
+        units as f64 * size / 1000.0
+    }
+}
+
+/// Seller or customer of an invoice
+#[derive(Debug, Clone, Default)]
+pub struct Party {
+    pub name: String,
+    pub address: Vec<String>,
+    pub vat_id: Option<String>,
+}
+
+/// Everything printed on an invoice
+pub struct InvoiceDocument<'a> {
+    pub invoice: &'a Invoice,
+    pub line_items: &'a [InvoiceLineItem],
+    pub seller: Party,
+    pub customer: Party,
+    pub vat: VatBreakdown,
+    pub notes: Vec<String>,
+}
+
+/// Amount in cents as text, e.g. `1,234.50 EUR`
+pub fn format_amount(cents: i64, currency: &str) -> String {
+    let units = (cents.abs() / 100).to_string();
+    let mut grouped = String::with_capacity(units.len() + units.len() / 3);
+    let first_group = units.len() % 3;
+    for (i, digit) in units.chars().enumerate() {
+        if i > 0 && i % 3 == first_group {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!(
+        "{}{}.{:02} {}",
+        if cents < 0 { "-" } else { "" },
+        grouped,
+        cents.abs() % 100,
+        currency.to_uppercase()
+    )
+}
+
+fn format_date(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+/// Text as the content of a PDF string in WinAnsi encoding
+fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                encoded.push('\\');
+                encoded.push(c);
+            }
+            ' '..='~' => encoded.push(c),
+            '\u{20AC}' => encoded.push_str("\\200"),
+            '\u{A0}'..='\u{FF}' => encoded.push_str(&format!("\\{:03o}", c as u32)),
+            _ => encoded.push('?'),
+        }
+    }
+    encoded
+}
+
+/// Split a text into lines no wider than `max_width`
+fn wrap(text: &str, font: Font, size: f64, max_width: f64) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if line.is_empty() || font.width(&candidate, size) <= max_width {
+            line = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Pages being drawn, top to bottom
+struct Canvas {
+    pages: Vec<String>,
+    page: String,
+    y: f64,
+}
+
+impl Canvas {
+    fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            page: String::new(),
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    fn text(&mut self, x: f64, font: Font, size: f64, text: &str) {
+        self.page.push_str(&format!(
+            "BT /{} {} Tf {:.2} {:.2} Td ({}) Tj ET\n",
+            font.resource(),
+            size,
+            x,
+            self.y,
+            encode(text)
+        ));
+    }
+
+    fn text_right(&mut self, right: f64, font: Font, size: f64, text: &str) {
+        self.text(right - font.width(text, size), font, size, text);
+    }
+
+    /// Horizontal line from `left` to the right margin just below the cursor
+    fn rule(&mut self, left: f64) {
+        let y = self.y - 4.0;
+        self.page.push_str(&format!(
+            "0.5 w {:.2} {:.2} m {:.2} {:.2} l S\n",
+            left, y, AMOUNT_RIGHT, y
+        ));
+    }
+
+    fn advance(&mut self, height: f64) {
+        self.y -= height;
+    }
+
+    /// Whether `height` still fits on the current page
+    fn fits(&self, height: f64) -> bool {
+        self.y - height >= CONTENT_BOTTOM
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(std::mem::take(&mut self.page));
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Finish the last page and number all pages
+    fn finish(mut self, footer: &str) -> Vec<String> {
+        self.pages.push(std::mem::take(&mut self.page));
+        let count = self.pages.len();
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            let text = format!("{}  -  Page {} of {}", footer, i + 1, count);
+            let x = (PAGE_WIDTH - Font::Regular.width(&text, 8.0)) / 2.0;
+            page.push_str(&format!(
+                "BT /F1 8 Tf {:.2} {:.2} Td ({}) Tj ET\n",
+                x,
+                MARGIN - 10.0,
+                encode(&text)
+            ));
+        }
+        self.pages
+    }
+}
+
+/// Render an invoice as a PDF
+pub fn render(document: &InvoiceDocument) -> Vec<u8> {
+    let invoice = document.invoice;
+    let number = invoice.number.as_deref().unwrap_or(&invoice.id);
+    let mut canvas = Canvas::new();
+
+    draw_header(&mut canvas, document, number);
+    draw_table_header(&mut canvas);
+
+    let (overages, charges): (Vec<_>, Vec<_>) = document
+        .line_items
+        .iter()
+        .partition(|item| item.kind == InvoiceLineKind::Overage);
+    for item in charges {
+        draw_line_item(&mut canvas, item, &invoice.currency);
+    }
+    if !overages.is_empty() {
+        if !canvas.fits(3.0 * LINE_HEIGHT) {
+            canvas.new_page();
+            draw_table_header(&mut canvas);
+        }
+        canvas.advance(6.0);
+        canvas.text(MARGIN, Font::Bold, TEXT_SIZE, "Usage overages");
+        canvas.advance(LINE_HEIGHT + 2.0);
+        for item in overages {
+            draw_line_item(&mut canvas, item, &invoice.currency);
+        }
+    }
+
+    draw_totals(&mut canvas, document);
+    draw_notes(&mut canvas, &document.notes);
+
+    let footer = format!("{}  -  Invoice {}", document.seller.name, number);
+    write_pdf(&canvas.finish(&footer), &format!("Invoice {}", number))
+}
+
+fn draw_header(canvas: &mut Canvas, document: &InvoiceDocument, number: &str) {
+    let invoice = document.invoice;
+    let top = canvas.y;
+
+    // Seller on the left
+    canvas.text(MARGIN, Font::Bold, 16.0, &document.seller.name);
+    canvas.advance(16.0);
+    for line in &document.seller.address {
+        canvas.text(MARGIN, Font::Regular, TEXT_SIZE, line);
+        canvas.advance(LINE_HEIGHT);
+    }
+    if let Some(vat_id) = &document.seller.vat_id {
+        canvas.text(
+            MARGIN,
+            Font::Regular,
+            TEXT_SIZE,
+            &format!("VAT ID: {}", vat_id),
+        );
+        canvas.advance(LINE_HEIGHT);
+    }
+    let seller_bottom = canvas.y;
+
+    // Invoice details on the right
+    canvas.y = top;
+    let title = if invoice.status == InvoiceStatus::Paid {
+        "INVOICE - PAID"
+    } else {
+        "INVOICE"
+    };
+    canvas.text_right(AMOUNT_RIGHT, Font::Bold, 18.0, title);
+    canvas.advance(24.0);
+    let mut details = vec![
+        format!("Invoice number: {}", number),
+        format!("Invoice date: {}", format_date(invoice.created_at)),
+    ];
+    if let Some(due_date) = invoice.due_date {
+        details.push(format!("Due date: {}", format_date(due_date)));
+    }
+    details.push(format!(
+        "Service period: {} to {}",
+        format_date(invoice.period_start),
+        format_date(invoice.period_end)
+    ));
+    for line in &details {
+        canvas.text_right(AMOUNT_RIGHT, Font::Regular, TEXT_SIZE, line);
+        canvas.advance(LINE_HEIGHT);
+    }
+
+    // Customer below both
+    canvas.y = canvas.y.min(seller_bottom) - 2.0 * LINE_HEIGHT;
+    canvas.text(MARGIN, Font::Bold, 10.0, "Bill to");
+    canvas.advance(LINE_HEIGHT + 2.0);
+    canvas.text(MARGIN, Font::Regular, TEXT_SIZE, &document.customer.name);
+    canvas.advance(LINE_HEIGHT);
+    for line in &document.customer.address {
+        canvas.text(MARGIN, Font::Regular, TEXT_SIZE, line);
+        canvas.advance(LINE_HEIGHT);
+    }
+    if let Some(vat_id) = &document.customer.vat_id {
+        canvas.text(
+            MARGIN,
+            Font::Regular,
+            TEXT_SIZE,
+            &format!("VAT ID: {}", vat_id),
+        );
+        canvas.advance(LINE_HEIGHT);
+    }
+    canvas.advance(2.0 * LINE_HEIGHT);
+}
+
+fn draw_table_header(canvas: &mut Canvas) {
+    canvas.text(MARGIN, Font::Bold, TEXT_SIZE, "Description");
+    canvas.text_right(QUANTITY_RIGHT, Font::Bold, TEXT_SIZE, "Qty");
+    canvas.text_right(UNIT_PRICE_RIGHT, Font::Bold, TEXT_SIZE, "Unit price");
+    canvas.text_right(AMOUNT_RIGHT, Font::Bold, TEXT_SIZE, "Amount");
+    canvas.rule(MARGIN);
+    canvas.advance(LINE_HEIGHT + 6.0);
+}
+
+fn draw_line_item(canvas: &mut Canvas, item: &InvoiceLineItem, currency: &str) {
+    let lines = wrap(
+        &item.description,
+        Font::Regular,
+        TEXT_SIZE,
+        DESCRIPTION_WIDTH,
+    );
+    if !canvas.fits(lines.len() as f64 * LINE_HEIGHT) {
+        canvas.new_page();
+        draw_table_header(canvas);
+    }
+
+    if item.quantity > 0 {
+        canvas.text_right(
+            QUANTITY_RIGHT,
+            Font::Regular,
+            TEXT_SIZE,
+            &item.quantity.to_string(),
+        );
+        if let Some(unit_amount) = item.unit_amount_cents {
+            canvas.text_right(
+                UNIT_PRICE_RIGHT,
+                Font::Regular,
+                TEXT_SIZE,
+                &format_amount(unit_amount, currency),
+            );
+        }
+    }
+    canvas.text_right(
+        AMOUNT_RIGHT,
+        Font::Regular,
+        TEXT_SIZE,
+        &format_amount(item.amount_cents, currency),
+    );
+    for line in lines {
+        canvas.text(MARGIN, Font::Regular, TEXT_SIZE, &line);
+        canvas.advance(LINE_HEIGHT);
+    }
+}
+
+fn draw_totals(canvas: &mut Canvas, document: &InvoiceDocument) {
+    let invoice = document.invoice;
+    let vat = &document.vat;
+    let currency = &invoice.currency;
+
+    let mut rows = vec![(
+        Font::Regular,
+        "Subtotal".to_string(),
+        invoice.subtotal_cents,
+    )];
+    let discount = invoice.subtotal_cents + invoice.tax_cents - invoice.total_cents;
+    if discount != 0 {
+        rows.push((Font::Regular, "Discount".to_string(), -discount));
+    }
+    rows.push((Font::Regular, "Net amount".to_string(), vat.net_cents));
+    let vat_label = if vat.reverse_charge {
+        "VAT (reverse charge)".to_string()
+    } else {
+        format!("VAT {}", vat::format_rate(vat.rate_bps))
+    };
+    rows.push((Font::Regular, vat_label, vat.vat_cents));
+    rows.push((Font::Bold, "Total".to_string(), vat.total_cents));
+    rows.push((
+        Font::Regular,
+        "Amount paid".to_string(),
+        invoice.amount_paid_cents,
+    ));
+    rows.push((
+        Font::Bold,
+        "Amount due".to_string(),
+        invoice.amount_due_cents,
+    ));
+
+    let height = (rows.len() as f64 + 1.0) * LINE_HEIGHT + 6.0;
+    if !canvas.fits(height) {
+        canvas.new_page();
+    }
+
+    canvas.rule(MARGIN);
+    canvas.advance(LINE_HEIGHT + 6.0);
+    for (font, label, amount) in rows {
+        canvas.text(TOTALS_LEFT, font, TEXT_SIZE, &label);
+        canvas.text_right(
+            AMOUNT_RIGHT,
+            font,
+            TEXT_SIZE,
+            &format_amount(amount, currency),
+        );
+        canvas.advance(LINE_HEIGHT);
+    }
+    canvas.advance(LINE_HEIGHT);
+}
+
+fn draw_notes(canvas: &mut Canvas, notes: &[String]) {
+    let width = AMOUNT_RIGHT - MARGIN;
+    for note in notes {
+        let lines = wrap(note, Font::Regular, 8.0, width);
+        if !canvas.fits(lines.len() as f64 * 10.0) {
+            canvas.new_page();
+        }
+        for line in lines {
+            canvas.text(MARGIN, Font::Regular, 8.0, &line);
+            canvas.advance(10.0);
+        }
+        canvas.advance(4.0);
+    }
+}
+
+/// Assemble the content streams of the pages into a PDF file
+fn write_pdf(pages: &[String], title: &str) -> Vec<u8> {
+    // 1: catalog, 2: page tree, 3-4: fonts, 5: info, then page and content
+    // stream of every page
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 6 + 2 * i))
+        .collect();
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        format!("<< /Title ({}) >>", encode(title)),
+    ];
+    for (i, content) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            7 + 2 * i
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.len(),
+            content
+        ));
+    }
+
+    let mut pdf = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::vat::{VatTreatment, breakdown};
+    use chrono::TimeZone;
+
+    fn invoice() -> Invoice {
+        let date = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        Invoice {
+            id: "inv-1".to_string(),
+            organization_id: "org-1".to_string(),
+            subscription_id: "sub-1".to_string(),
+            stripe_invoice_id: Some("in_1".to_string()),
+            stripe_payment_intent_id: None,
+            number: Some("PP-0001".to_string()),
+            status: InvoiceStatus::Paid,
+            currency: "eur".to_string(),
+            subtotal_cents: 11900,
+            tax_cents: 0,
+            total_cents: 11900,
+            amount_paid_cents: 11900,
+            amount_due_cents: 0,
+            description: None,
+            invoice_pdf_url: None,
+            hosted_invoice_url: None,
+            period_start: date,
+            period_end: date,
+            due_date: None,
+            paid_at: Some(date),
+            customer_name: None,
+            customer_address: None,
+            customer_country: Some("DE".to_string()),
+            customer_vat_id: None,
+            vat_rate_bps: 1900,
+            vat_reverse_charge: false,
+            pdf_object_key: None,
+            pdf_url: None,
+            pdf_generated_at: None,
+            created_at: date,
+            updated_at: date,
+        }
+    }
+
+    fn line_item(description: &str, kind: InvoiceLineKind, amount_cents: i64) -> InvoiceLineItem {
+        InvoiceLineItem {
+            id: "li-1".to_string(),
+            invoice_id: "inv-1".to_string(),
+            position: 0,
+            description: description.to_string(),
+            kind,
+            quantity: 1,
+            unit_amount_cents: Some(amount_cents),
+            amount_cents,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(9900, "eur"), "99.00 EUR");
+        assert_eq!(format_amount(123456789, "usd"), "1,234,567.89 USD");
+        assert_eq!(format_amount(-550, "eur"), "-5.50 EUR");
+        assert_eq!(format_amount(5, "eur"), "0.05 EUR");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("Plan (Pro)"), "Plan \\(Pro\\)");
+        assert_eq!(encode("Gro\u{DF}e \u{20AC}"), "Gro\\337e \\200");
+        assert_eq!(encode("\u{4E2D}"), "?");
+    }
+
+    #[test]
+    fn test_wrap() {
+        let lines = wrap(
+            "Pro plan (monthly) for the period from March to April",
+            Font::Regular,
+            TEXT_SIZE,
+            100.0,
+        );
+        assert!(lines.len() > 1);
+        assert!(
+            lines
+                .iter()
+                .all(|line| Font::Regular.width(line, TEXT_SIZE) <= 100.0)
+        );
+        assert_eq!(
+            wrap("", Font::Regular, TEXT_SIZE, 100.0),
+            vec![String::new()]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let invoice = invoice();
+        let mut line_items = vec![line_item("Pro plan", InvoiceLineKind::Subscription, 9900)];
+        // Enough overages to need a second page
+        for _ in 0..60 {
+            line_items.push(line_item(
+                "Requests above plan limit",
+                InvoiceLineKind::Overage,
+                40,
+            ));
+        }
+        let document = InvoiceDocument {
+            invoice: &invoice,
+            line_items: &line_items,
+            seller: Party {
+                name: "PistonProtection".to_string(),
+                ..Default::default()
+            },
+            customer: Party {
+                name: "Example GmbH".to_string(),
+                ..Default::default()
+            },
+            vat: breakdown(VatTreatment::Standard { rate_bps: 1900 }, 11900, 0),
+            notes: vec!["Amounts include VAT.".to_string()],
+        };
+
+        let pdf = render(&document);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains("(Usage overages) Tj"));
+        assert!(text.contains("(VAT 19%) Tj"));
+
+        // The cross-reference table points at the objects
+        let xref: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|tail| tail.lines().next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(pdf[xref..].starts_with(b"xref"));
+        let table = String::from_utf8_lossy(&pdf[xref..]);
+        let first_offset: usize = table.lines().nth(3).unwrap()[..10].parse().unwrap();
+        assert!(pdf[first_offset..].starts_with(b"1 0 obj"));
+    }
+}
@@ -1,6 +1,6 @@
 //! JWT service for token generation and validation
 
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, TokenData, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -35,8 +35,11 @@ pub struct Claims {
     /// Session ID (for session-based JWTs)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sid: Option<String>,
-    /// Token type (access, refresh)
+    /// Token type (access, refresh, support)
     pub typ: String,
+    /// Support access grant a support token was issued under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant: Option<String>,
 }
 
 /// Token type
@@ -44,6 +47,8 @@ pub struct Claims {
 pub enum TokenType {
     Access,
     Refresh,
+    /// Read-only access of support staff to a customer organization
+    Support,
 }
 
 impl TokenType {
@@ -51,6 +56,7 @@ impl TokenType {
         match self {
             TokenType::Access => "access",
             TokenType::Refresh => "refresh",
+            TokenType::Support => "support",
         }
     }
 }
@@ -102,6 +108,43 @@ impl JwtService {
         self.generate_token(user_id, email, role, orgs, session_id, TokenType::Refresh)
     }
 
+    /// Generate a read-only support token for one organization
+    ///
+    /// The token carries the grant it was issued under, so the gateway can
+    /// refuse it as soon as the grant is revoked, and never outlives it.
+    pub fn generate_support_token(
+        &self,
+        user_id: &str,
+        email: &str,
+        organization_id: &str,
+        grant_id: &str,
+        grant_expires_at: DateTime<Utc>,
+    ) -> Result<(String, DateTime<Utc>), JwtError> {
+        let now = Utc::now();
+        let exp = (now + self.access_token_ttl).min(grant_expires_at);
+
+        let claims = Claims {
+            sub: user_id.to_string(),
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            exp: exp.timestamp(),
+            iat: now.timestamp(),
+            nbf: now.timestamp(),
+            jti: uuid::Uuid::new_v4().to_string(),
+            email: email.to_string(),
+            // Never "admin": support staff only see the granting organization
+            role: TokenType::Support.as_str().to_string(),
+            orgs: vec![organization_id.to_string()],
+            sid: None,
+            typ: TokenType::Support.as_str().to_string(),
+            grant: Some(grant_id.to_string()),
+        };
+
+        let token = encode(&Header::default(), &claims, &self.encoding_key)
+            .map_err(|e| JwtError::EncodingError(e.to_string()))?;
+        Ok((token, exp))
+    }
+
     /// Generate a token with specified type
     fn generate_token(
         &self,
@@ -114,7 +157,7 @@ impl JwtService {
     ) -> Result<String, JwtError> {
         let now = Utc::now();
         let ttl = match token_type {
-            TokenType::Access => self.access_token_ttl,
+            TokenType::Access | TokenType::Support => self.access_token_ttl,
             TokenType::Refresh => self.refresh_token_ttl,
         };
         let exp = now + ttl;
@@ -135,6 +178,7 @@ impl JwtService {
             orgs,
             sid: session_id.map(|s| s.to_string()),
             typ: token_type.as_str().to_string(),
+            grant: None,
         };
 
         encode(&Header::default(), &claims, &self.encoding_key)
@@ -299,4 +343,32 @@ mod tests {
         let result = service.validate_access_token(&refresh_token);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_support_token() {
+        let service = JwtService::new(&test_config());
+        let grant_expires_at = Utc::now() + Duration::minutes(10);
+
+        let (token, expires_at) = service
+            .generate_support_token(
+                "staff1",
+                "staff@example.com",
+                "org1",
+                "grant1",
+                grant_expires_at,
+            )
+            .unwrap();
+
+        // Never outlives the grant
+        assert_eq!(expires_at, grant_expires_at);
+
+        let claims = service.validate_token(&token).unwrap();
+        assert_eq!(claims.typ, "support");
+        assert_eq!(claims.role, "support");
+        assert_eq!(claims.orgs, vec!["org1".to_string()]);
+        assert_eq!(claims.grant, Some("grant1".to_string()));
+
+        // Not accepted where a regular access token is expected
+        assert!(service.validate_access_token(&token).is_err());
+    }
 }
@@ -1,35 +1,55 @@
 //! Service layer for the authentication service
 
-use deadpool_redis::Pool as RedisPool;
+use pistonprotection_common::geoip::GeoIpService;
+use pistonprotection_common::redis::RedisPool;
 use pistonprotection_common::{config::Config, redis::CacheService};
 use sqlx::PgPool;
 use std::sync::Arc;
+use tracing::warn;
 
 pub mod apikey;
 pub mod audit;
 pub mod auth;
+pub mod billing_events;
 pub mod dunning;
 pub mod email;
+pub mod invoice_documents;
+pub mod invoice_pdf;
 pub mod jwt;
+pub mod object_storage;
 pub mod organization;
 pub mod permission;
+pub mod plan_change;
+pub mod security_analytics;
 pub mod session;
+pub mod session_device;
 pub mod stripe;
+pub mod support_access;
+pub mod trial;
 pub mod user;
+pub mod vat;
 
 pub use apikey::ApiKeyService;
 pub use audit::AuditService;
 pub use auth::AuthService;
+pub use billing_events::BillingEventService;
 pub use dunning::{DunningConfig, DunningService};
 pub use email::{EmailConfig, EmailService};
+pub use invoice_documents::InvoiceDocumentService;
 pub use jwt::JwtService;
 pub use organization::OrganizationService;
 pub use permission::PermissionService;
+pub use plan_change::PlanChangeService;
+pub use security_analytics::{SecurityAnalytics, SecurityDetector};
 pub use session::SessionService;
+pub use session_device::SessionDeviceService;
 pub use stripe::StripeService;
+pub use support_access::SupportAccessService;
+pub use trial::TrialService;
 pub use user::UserService;
 
 use crate::config::AuthConfig;
+use crate::handlers::webhook::WebhookState;
 
 /// Shared application state
 #[derive(Clone)]
@@ -44,11 +64,19 @@ pub struct AppState {
     pub stripe_service: Option<Arc<StripeService>>,
     pub email_service: Arc<EmailService>,
     pub dunning_service: Option<Arc<DunningService>>,
+    pub geoip: Arc<GeoIpService>,
+    pub security_analytics: SecurityAnalytics,
 }
 
 impl AppState {
     /// Create new application state
-    pub fn new(db: PgPool, redis: RedisPool, config: Config, auth_config: AuthConfig) -> Self {
+    pub fn new(
+        db: PgPool,
+        redis: RedisPool,
+        config: Config,
+        auth_config: AuthConfig,
+        security_analytics: SecurityAnalytics,
+    ) -> Self {
         let cache = CacheService::new(redis, "piston:auth");
 
         let jwt_service = Arc::new(JwtService::new(&auth_config.jwt));
@@ -64,6 +92,18 @@ impl AppState {
         // Initialize email service
         let email_service = Arc::new(EmailService::new(EmailConfig::default()));
 
+        // Initialize GeoIP service, used to locate logins
+        let geoip = Arc::new(
+            GeoIpService::new(
+                std::env::var("GEOIP_CITY_DB").ok().as_deref(),
+                std::env::var("GEOIP_ASN_DB").ok().as_deref(),
+            )
+            .unwrap_or_else(|_| {
+                warn!("Failed to load GeoIP databases, using dummy service");
+                GeoIpService::dummy()
+            }),
+        );
+
         // Initialize Stripe service if configured
         let stripe_service = if auth_config.stripe.is_configured() {
             Some(Arc::new(StripeService::new(
@@ -95,6 +135,8 @@ impl AppState {
             stripe_service,
             email_service,
             dunning_service,
+            geoip,
+            security_analytics,
         }
     }
 
@@ -106,6 +148,30 @@ impl AppState {
             self.session_service.clone(),
             self.auth_config.clone(),
         )
+        .with_devices(self.session_device_service())
+        .with_security_analytics(self.security_analytics.clone())
+    }
+
+    /// Get a new SessionDeviceService instance
+    pub fn session_device_service(&self) -> SessionDeviceService {
+        SessionDeviceService::new(
+            self.db.clone(),
+            self.session_service.clone(),
+            self.email_service.clone(),
+            self.geoip.clone(),
+        )
+    }
+
+    /// Create the detector analyzing events recorded by `security_analytics`
+    pub fn security_detector(
+        &self,
+    ) -> Result<SecurityDetector, security_analytics::SecurityAnalyticsError> {
+        SecurityDetector::new(
+            self.db.clone(),
+            self.cache.clone(),
+            self.geoip.clone(),
+            self.auth_config.security.clone(),
+        )
     }
 
     /// Get a new UserService instance
@@ -132,11 +198,22 @@ impl AppState {
         AuditService::new(self.db.clone())
     }
 
+    /// Get a new SupportAccessService instance
+    pub fn support_access_service(&self) -> SupportAccessService {
+        SupportAccessService::new(self.db.clone(), self.jwt_service.clone())
+    }
+
     /// Get the Stripe service if configured
     pub fn stripe_service(&self) -> Option<Arc<StripeService>> {
         self.stripe_service.clone()
     }
 
+    /// Get a new PlanChangeService instance if Stripe is configured
+    pub fn plan_change_service(&self) -> Option<PlanChangeService> {
+        let stripe_service = self.stripe_service.clone()?;
+        Some(PlanChangeService::new(self.db.clone(), stripe_service))
+    }
+
     /// Check if Stripe is enabled
     pub fn is_stripe_enabled(&self) -> bool {
         self.stripe_service.is_some()
@@ -151,4 +228,47 @@ impl AppState {
     pub fn dunning_service(&self) -> Option<Arc<DunningService>> {
         self.dunning_service.clone()
     }
+
+    /// Get the Stripe webhook state if Stripe is configured
+    pub fn webhook_state(&self) -> Option<WebhookState> {
+        let stripe_service = self.stripe_service.clone()?;
+        Some(WebhookState {
+            events: BillingEventService::new(
+                self.db.clone(),
+                self.auth_config.stripe.webhook_max_attempts,
+            ),
+            stripe_service,
+            email_service: self.email_service.clone(),
+        })
+    }
+
+    /// Get the trial expiry scheduler if Stripe is configured and trial
+    /// expiry is enabled
+    pub fn trial_service(&self) -> Option<TrialService> {
+        if !self.auth_config.trial.enabled {
+            return None;
+        }
+        let stripe_service = self.stripe_service.clone()?;
+        Some(TrialService::new(
+            self.db.clone(),
+            self.auth_config.trial.clone(),
+            stripe_service,
+            self.email_service.clone(),
+        ))
+    }
+
+    /// Get the invoice document service if object storage is configured
+    pub fn invoice_document_service(&self) -> Option<InvoiceDocumentService> {
+        let config = &self.auth_config.invoices;
+        if config.storage.endpoint.is_empty() {
+            return None;
+        }
+        match InvoiceDocumentService::new(self.db.clone(), config.clone()) {
+            Ok(service) => Some(service),
+            Err(e) => {
+                warn!(error = %e, "Invoice documents disabled");
+                None
+            }
+        }
+    }
 }
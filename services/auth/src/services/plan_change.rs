@@ -0,0 +1,774 @@
+//! Subscription plan changes
+//!
+//! An upgrade takes effect immediately: the Stripe subscription moves to the
+//! new price with the difference prorated, and the organization gets the new
+//! plan's limits right away. A downgrade takes effect at the end of the period
+//! the organization already paid for: Stripe bills the new price from the next
+//! renewal, and the target plan is kept on the subscription until the period
+//! ends. A change is refused while the organization uses more than the target
+//! plan allows, such as more backends than it includes.
+//!
+//! The subscription's plan and the organization's limits always change in one
+//! transaction, and every change is written to the audit log.
+
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::cmp::Ordering;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::models::subscription::{BillingPeriod, Plan, ProrationBehavior, SubscriptionDetails};
+use crate::models::{AuditActions, AuditLogBuilder};
+use crate::services::{AuditService, StripeService};
+
+/// Direction of a plan change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanChangeKind {
+    /// Takes effect immediately, prorated
+    Upgrade,
+    /// Takes effect at the end of the current period
+    Downgrade,
+    /// Already on the plan and billing period
+    Unchanged,
+}
+
+/// Monthly price of a plan on a billing period, in cents
+fn monthly_price_cents(plan: &Plan, period: BillingPeriod) -> i64 {
+    match period {
+        BillingPeriod::Monthly => plan.price_monthly_cents,
+        BillingPeriod::Yearly => plan.price_yearly_cents / 12,
+    }
+}
+
+/// Classify a change from one plan and billing period to another
+///
+/// Whatever costs more per month is an upgrade; at the same price the higher
+/// plan tier decides. A change between equal plans is applied immediately.
+pub fn classify_change(
+    current: &Plan,
+    current_period: BillingPeriod,
+    target: &Plan,
+    target_period: BillingPeriod,
+) -> PlanChangeKind {
+    if current.id == target.id && current_period == target_period {
+        return PlanChangeKind::Unchanged;
+    }
+
+    let current_key = (
+        monthly_price_cents(current, current_period),
+        i32::from(current.plan_type),
+    );
+    let target_key = (
+        monthly_price_cents(target, target_period),
+        i32::from(target.plan_type),
+    );
+    match target_key.cmp(&current_key) {
+        Ordering::Less => PlanChangeKind::Downgrade,
+        Ordering::Equal | Ordering::Greater => PlanChangeKind::Upgrade,
+    }
+}
+
+/// Stripe price of a plan on a billing period
+fn stripe_price(plan: &Plan, period: BillingPeriod) -> Option<&str> {
+    match period {
+        BillingPeriod::Monthly => plan.stripe_price_id_monthly.as_deref(),
+        BillingPeriod::Yearly => plan.stripe_price_id_yearly.as_deref(),
+    }
+}
+
+/// Usage of the organization `$1`, from the tables of the gateway
+///
+/// Deleted backends are removed along with their origins, domains and rules.
+const USAGE_QUERY: &str = r#"
+    WITH org_backends AS (
+        SELECT id FROM backends WHERE organization_id = $1
+    )
+    SELECT
+        (SELECT COUNT(*) FROM org_backends) AS backends,
+        (SELECT COALESCE(MAX(origins), 0) FROM (
+            SELECT COUNT(*) AS origins FROM backend_origins
+            WHERE backend_id IN (SELECT id FROM org_backends)
+            GROUP BY backend_id
+        ) per_backend) AS max_origins_per_backend,
+        (SELECT COUNT(*) FROM backend_domains
+         WHERE backend_id IN (SELECT id FROM org_backends)) AS domains,
+        (SELECT COUNT(*) FROM filter_rules
+         WHERE backend_id IN (SELECT id FROM org_backends)) AS filter_rules
+"#;
+
+/// Resources of an organization that plans limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromRow)]
+pub struct PlanUsage {
+    pub backends: i64,
+    /// Origins of the backend with the most
+    pub max_origins_per_backend: i64,
+    pub domains: i64,
+    pub filter_rules: i64,
+}
+
+/// How the usage exceeds a plan, one entry per limit
+pub fn usage_violations(plan: &Plan, usage: &PlanUsage) -> Vec<String> {
+    [
+        ("backends", usage.backends, plan.max_backends),
+        (
+            "origins on a backend",
+            usage.max_origins_per_backend,
+            plan.max_origins_per_backend,
+        ),
+        ("domains", usage.domains, plan.max_domains),
+        ("filter rules", usage.filter_rules, plan.max_filter_rules),
+    ]
+    .into_iter()
+    .filter(|(_, used, limit)| *used > i64::from(*limit))
+    .map(|(resource, used, limit)| {
+        format!(
+            "{} {} in use, {} allows {}",
+            used, resource, plan.name, limit
+        )
+    })
+    .collect()
+}
+
+/// Set an organization's limits to those of a plan
+pub async fn upsert_limits<'e>(
+    executor: impl PgExecutor<'e>,
+    organization_id: &str,
+    plan: &Plan,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO organization_limits (
+            id, organization_id, max_backends, max_origins_per_backend,
+            max_domains, max_filter_rules, max_bandwidth_bytes, max_requests,
+            advanced_protection, priority_support, custom_ssl, api_access,
+            data_retention_days, created_at, updated_at
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, NOW(), NOW()
+        )
+        ON CONFLICT (organization_id) DO UPDATE SET
+            max_backends = EXCLUDED.max_backends,
+            max_origins_per_backend = EXCLUDED.max_origins_per_backend,
+            max_domains = EXCLUDED.max_domains,
+            max_filter_rules = EXCLUDED.max_filter_rules,
+            max_bandwidth_bytes = EXCLUDED.max_bandwidth_bytes,
+            max_requests = EXCLUDED.max_requests,
+            advanced_protection = EXCLUDED.advanced_protection,
+            priority_support = EXCLUDED.priority_support,
+            custom_ssl = EXCLUDED.custom_ssl,
+            api_access = EXCLUDED.api_access,
+            data_retention_days = EXCLUDED.data_retention_days,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(organization_id)
+    .bind(plan.max_backends)
+    .bind(plan.max_origins_per_backend)
+    .bind(plan.max_domains)
+    .bind(plan.max_filter_rules)
+    .bind(plan.max_bandwidth_bytes)
+    .bind(plan.max_requests)
+    .bind(plan.advanced_protection)
+    .bind(plan.priority_support)
+    .bind(plan.custom_ssl)
+    .bind(plan.api_access)
+    .bind(plan.data_retention_days)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Move a subscription to a plan and the organization to its limits
+///
+/// Clears any scheduled change.
+async fn apply_plan(
+    db: &PgPool,
+    subscription: &SubscriptionDetails,
+    plan: &Plan,
+    period: BillingPeriod,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        r#"
+        UPDATE subscriptions SET
+            plan_id = $1, plan_name = $2, plan_type = $3, billing_period = $4,
+            scheduled_plan_id = NULL, scheduled_billing_period = NULL,
+            scheduled_change_at = NULL, updated_at = NOW()
+        WHERE id = $5
+        "#,
+    )
+    .bind(&plan.id)
+    .bind(&plan.name)
+    .bind(plan.plan_type)
+    .bind(period)
+    .bind(&subscription.id)
+    .execute(&mut *tx)
+    .await?;
+    upsert_limits(&mut *tx, &subscription.organization_id, plan).await?;
+
+    tx.commit().await
+}
+
+/// Bring a subscription's plan in line with the Stripe price it is billed on
+///
+/// Called whenever a Stripe subscription is synced. A downgrade to the billed
+/// price waits until its scheduled time, which is how scheduled downgrades
+/// take effect at the end of the period.
+pub async fn sync_billed_plan(
+    db: &PgPool,
+    stripe_subscription_id: &str,
+    plan: &Plan,
+    period: BillingPeriod,
+) -> Result<(), sqlx::Error> {
+    let Some(subscription) = sqlx::query_as::<_, SubscriptionDetails>(
+        r#"
+        SELECT * FROM subscriptions WHERE stripe_subscription_id = $1
+        "#,
+    )
+    .bind(stripe_subscription_id)
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let scheduled = subscription.scheduled_change_at;
+    if scheduled.is_some_and(|at| at > Utc::now()) {
+        return Ok(());
+    }
+
+    apply_plan(db, &subscription, plan, period).await?;
+
+    if scheduled.is_some() {
+        info!(
+            organization_id = %subscription.organization_id,
+            from_plan = %subscription.plan_id,
+            to_plan = %plan.id,
+            "Scheduled downgrade took effect"
+        );
+        let entry = AuditLogBuilder::new(
+            &subscription.organization_id,
+            AuditActions::SUBSCRIPTION_DOWNGRADED,
+            "subscription",
+        )
+        .resource(&subscription.id)
+        .description(&format!("Downgraded to {}", plan.name))
+        .metadata("from_plan", &subscription.plan_id)
+        .metadata("to_plan", &plan.id);
+        if let Err(e) = AuditService::new(db.clone()).log_builder(entry).await {
+            warn!(
+                organization_id = %subscription.organization_id,
+                "Failed to audit downgrade: {}",
+                e
+            );
+        }
+    } else if subscription.plan_id != plan.id {
+        info!(
+            organization_id = %subscription.organization_id,
+            from_plan = %subscription.plan_id,
+            to_plan = %plan.id,
+            "Plan changed in Stripe"
+        );
+    }
+
+    Ok(())
+}
+
+/// Plan change service
+pub struct PlanChangeService {
+    db: PgPool,
+    stripe: Arc<StripeService>,
+    audit: AuditService,
+}
+
+impl PlanChangeService {
+    /// Create a new plan change service
+    pub fn new(db: PgPool, stripe: Arc<StripeService>) -> Self {
+        Self {
+            audit: AuditService::new(db.clone()),
+            db,
+            stripe,
+        }
+    }
+
+    /// Change an organization's plan, billing period, or both
+    ///
+    /// Whatever isn't given stays as it is. Upgrades are applied now and
+    /// prorated as requested; downgrades are scheduled for the end of the
+    /// current period. Changing to the current plan cancels a scheduled
+    /// downgrade.
+    pub async fn change_plan(
+        &self,
+        organization_id: &str,
+        plan_id: Option<&str>,
+        period: Option<BillingPeriod>,
+        proration: ProrationBehavior,
+        actor: Option<&str>,
+    ) -> Result<PlanChangeKind, PlanChangeError> {
+        let subscription = self.subscription(organization_id).await?;
+        let stripe_subscription_id = subscription
+            .stripe_subscription_id
+            .as_deref()
+            .ok_or(PlanChangeError::NoStripeSubscription)?;
+
+        let current = self.plan(&subscription.plan_id).await?;
+        let target = self.plan(plan_id.unwrap_or(&subscription.plan_id)).await?;
+        let period = period.unwrap_or(subscription.billing_period);
+        if !target.is_active {
+            return Err(PlanChangeError::InvalidRequest(format!(
+                "{} is no longer available",
+                target.name
+            )));
+        }
+        let price = stripe_price(&target, period).ok_or_else(|| {
+            PlanChangeError::InvalidRequest(format!("{} has no {:?} price", target.name, period))
+        })?;
+
+        let kind = classify_change(&current, subscription.billing_period, &target, period);
+        if kind == PlanChangeKind::Unchanged {
+            if subscription.scheduled_change_at.is_some() {
+                self.cancel_change(organization_id, actor).await?;
+            }
+            return Ok(kind);
+        }
+
+        let violations = usage_violations(&target, &self.usage(organization_id).await?);
+        if !violations.is_empty() {
+            return Err(PlanChangeError::UsageExceeded(violations.join("; ")));
+        }
+
+        match kind {
+            PlanChangeKind::Upgrade => {
+                self.stripe
+                    .update_subscription(stripe_subscription_id, Some(price), proration)
+                    .await
+                    .map_err(|e| PlanChangeError::Stripe(e.to_string()))?;
+                apply_plan(&self.db, &subscription, &target, period).await?;
+
+                info!(
+                    organization_id = %organization_id,
+                    from_plan = %current.id,
+                    to_plan = %target.id,
+                    "Subscription upgraded"
+                );
+                self.audit(
+                    &subscription,
+                    AuditActions::SUBSCRIPTION_UPGRADED,
+                    actor,
+                    &format!("Upgraded from {} to {}", current.name, target.name),
+                    &current,
+                    &target,
+                )
+                .await;
+            }
+            PlanChangeKind::Downgrade => {
+                // Scheduled before Stripe is told, so the webhook for the
+                // new price doesn't apply the downgrade right away
+                self.schedule(
+                    &subscription.id,
+                    Some(&target.id),
+                    Some(period),
+                    Some(subscription.current_period_end),
+                )
+                .await?;
+
+                // Stripe bills the new price from the next renewal; nothing
+                // is credited for the rest of this period
+                if let Err(e) = self
+                    .stripe
+                    .update_subscription(
+                        stripe_subscription_id,
+                        Some(price),
+                        ProrationBehavior::None,
+                    )
+                    .await
+                {
+                    self.schedule(
+                        &subscription.id,
+                        subscription.scheduled_plan_id.as_deref(),
+                        subscription.scheduled_billing_period,
+                        subscription.scheduled_change_at,
+                    )
+                    .await?;
+                    return Err(PlanChangeError::Stripe(e.to_string()));
+                }
+
+                info!(
+                    organization_id = %organization_id,
+                    from_plan = %current.id,
+                    to_plan = %target.id,
+                    change_at = %subscription.current_period_end,
+                    "Subscription downgrade scheduled"
+                );
+                self.audit(
+                    &subscription,
+                    AuditActions::SUBSCRIPTION_DOWNGRADE_SCHEDULED,
+                    actor,
+                    &format!(
+                        "Downgrade from {} to {} scheduled for {}",
+                        current.name,
+                        target.name,
+                        subscription.current_period_end.to_rfc3339()
+                    ),
+                    &current,
+                    &target,
+                )
+                .await;
+            }
+            PlanChangeKind::Unchanged => {}
+        }
+
+        Ok(kind)
+    }
+
+    /// Cancel a scheduled downgrade, staying on the current plan
+    pub async fn cancel_change(
+        &self,
+        organization_id: &str,
+        actor: Option<&str>,
+    ) -> Result<(), PlanChangeError> {
+        let subscription = self.subscription(organization_id).await?;
+        let scheduled_plan_id = subscription
+            .scheduled_plan_id
+            .as_deref()
+            .ok_or(PlanChangeError::NoScheduledChange)?;
+        let stripe_subscription_id = subscription
+            .stripe_subscription_id
+            .as_deref()
+            .ok_or(PlanChangeError::NoStripeSubscription)?;
+
+        let current = self.plan(&subscription.plan_id).await?;
+        let scheduled = self.plan(scheduled_plan_id).await?;
+        let price = stripe_price(&current, subscription.billing_period).ok_or_else(|| {
+            PlanChangeError::InvalidRequest(format!(
+                "{} has no {:?} price",
+                current.name, subscription.billing_period
+            ))
+        })?;
+
+        // Back to the price of the current plan, which is paid for already
+        self.stripe
+            .update_subscription(stripe_subscription_id, Some(price), ProrationBehavior::None)
+            .await
+            .map_err(|e| PlanChangeError::Stripe(e.to_string()))?;
+        self.schedule(&subscription.id, None, None, None).await?;
+
+        info!(organization_id = %organization_id, "Scheduled downgrade canceled");
+        self.audit(
+            &subscription,
+            AuditActions::SUBSCRIPTION_PLAN_CHANGE_CANCELED,
+            actor,
+            &format!("Canceled downgrade to {}", scheduled.name),
+            &current,
+            &scheduled,
+        )
+        .await;
+
+        Ok(())
+    }
+
+    /// Resources the organization uses that plans limit
+    ///
+    /// Backends, and everything on them, are stored by the gateway in the
+    /// same database.
+    async fn usage(&self, organization_id: &str) -> Result<PlanUsage, PlanChangeError> {
+        let usage = sqlx::query_as::<_, PlanUsage>(USAGE_QUERY)
+            .bind(organization_id)
+            .fetch_one(&self.db)
+            .await?;
+
+        Ok(usage)
+    }
+
+    /// Set or clear the scheduled change of a subscription
+    async fn schedule(
+        &self,
+        subscription_id: &str,
+        plan_id: Option<&str>,
+        period: Option<BillingPeriod>,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<(), PlanChangeError> {
+        sqlx::query(
+            r#"
+            UPDATE subscriptions SET
+                scheduled_plan_id = $1, scheduled_billing_period = $2,
+                scheduled_change_at = $3, updated_at = NOW()
+            WHERE id = $4
+            "#,
+        )
+        .bind(plan_id)
+        .bind(period)
+        .bind(at)
+        .bind(subscription_id)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn subscription(
+        &self,
+        organization_id: &str,
+    ) -> Result<SubscriptionDetails, PlanChangeError> {
+        sqlx::query_as::<_, SubscriptionDetails>(
+            r#"
+            SELECT * FROM subscriptions WHERE organization_id = $1
+            ORDER BY created_at DESC LIMIT 1
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(PlanChangeError::SubscriptionNotFound)
+    }
+
+    async fn plan(&self, plan_id: &str) -> Result<Plan, PlanChangeError> {
+        sqlx::query_as::<_, Plan>(
+            r#"
+            SELECT * FROM plans WHERE id = $1
+            "#,
+        )
+        .bind(plan_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| PlanChangeError::PlanNotFound(plan_id.to_string()))
+    }
+
+    async fn audit(
+        &self,
+        subscription: &SubscriptionDetails,
+        action: &str,
+        actor: Option<&str>,
+        description: &str,
+        from: &Plan,
+        to: &Plan,
+    ) {
+        let mut entry = AuditLogBuilder::new(&subscription.organization_id, action, "subscription")
+            .resource(&subscription.id)
+            .description(description)
+            .metadata("from_plan", &from.id)
+            .metadata("to_plan", &to.id);
+        if let Some(actor) = actor {
+            entry = entry.user(actor, None);
+        }
+
+        // The change is made; a missing audit entry must not undo it
+        if let Err(e) = self.audit.log_builder(entry).await {
+            warn!(
+                organization_id = %subscription.organization_id,
+                action = %action,
+                "Failed to audit plan change: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Plan change errors
+#[derive(Debug, thiserror::Error)]
+pub enum PlanChangeError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Subscription not found")]
+    SubscriptionNotFound,
+
+    #[error("Plan not found: {0}")]
+    PlanNotFound(String),
+
+    #[error("The subscription is not billed through Stripe")]
+    NoStripeSubscription,
+
+    #[error("No plan change is scheduled")]
+    NoScheduledChange,
+
+    #[error("Current usage exceeds the plan: {0}")]
+    UsageExceeded(String),
+
+    #[error("Stripe error: {0}")]
+    Stripe(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<PlanChangeError> for tonic::Status {
+    fn from(err: PlanChangeError) -> Self {
+        match err {
+            PlanChangeError::InvalidRequest(msg) => tonic::Status::invalid_argument(msg),
+            PlanChangeError::SubscriptionNotFound => {
+                tonic::Status::not_found("Subscription not found")
+            }
+            PlanChangeError::PlanNotFound(_) => tonic::Status::not_found("Plan not found"),
+            err @ (PlanChangeError::NoStripeSubscription
+            | PlanChangeError::NoScheduledChange
+            | PlanChangeError::UsageExceeded(_)) => {
+                tonic::Status::failed_precondition(err.to_string())
+            }
+            PlanChangeError::Stripe(msg) => tonic::Status::unavailable(msg),
+            PlanChangeError::Database(e) => tonic::Status::internal(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::subscription::PlanType;
+
+    fn plan(id: &str, plan_type: PlanType, monthly: i64, yearly: i64, max_backends: i32) -> Plan {
+        let now = Utc::now();
+        Plan {
+            id: id.to_string(),
+            name: id.to_string(),
+            plan_type,
+            description: None,
+            stripe_product_id: None,
+            stripe_price_id_monthly: Some(format!("price_{}_monthly", id)),
+            stripe_price_id_yearly: None,
+            price_monthly_cents: monthly,
+            price_yearly_cents: yearly,
+            max_backends,
+            max_origins_per_backend: 2,
+            max_domains: 5,
+            max_filter_rules: 20,
+            max_bandwidth_bytes: 0,
+            max_requests: 0,
+            advanced_protection: false,
+            priority_support: false,
+            custom_ssl: false,
+            api_access: true,
+            data_retention_days: 7,
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_classify_change() {
+        let starter = plan("starter", PlanType::Starter, 2900, 29000, 3);
+        let pro = plan("pro", PlanType::Pro, 9900, 99000, 10);
+        let monthly = BillingPeriod::Monthly;
+        let yearly = BillingPeriod::Yearly;
+
+        assert_eq!(
+            classify_change(&starter, monthly, &pro, monthly),
+            PlanChangeKind::Upgrade
+        );
+        assert_eq!(
+            classify_change(&pro, monthly, &starter, monthly),
+            PlanChangeKind::Downgrade
+        );
+        assert_eq!(
+            classify_change(&pro, yearly, &pro, yearly),
+            PlanChangeKind::Unchanged
+        );
+        // Yearly billing is cheaper per month
+        assert_eq!(
+            classify_change(&pro, monthly, &pro, yearly),
+            PlanChangeKind::Downgrade
+        );
+        assert_eq!(
+            classify_change(&starter, yearly, &pro, monthly),
+            PlanChangeKind::Upgrade
+        );
+    }
+
+    #[test]
+    fn test_usage_violations() {
+        let free = plan("free", PlanType::Free, 0, 0, 1);
+        let usage = PlanUsage {
+            backends: 3,
+            max_origins_per_backend: 2,
+            domains: 5,
+            filter_rules: 4,
+        };
+
+        let violations = usage_violations(&free, &usage);
+        assert_eq!(violations, vec!["3 backends in use, free allows 1"]);
+
+        let within = PlanUsage {
+            backends: 1,
+            ..usage
+        };
+        assert!(usage_violations(&free, &within).is_empty());
+    }
+
+    /// Runs the usage query against the gateway schema in a throwaway schema
+    /// of the database at `DATABASE_URL`; skipped without one
+    #[tokio::test]
+    async fn test_usage_query_against_gateway_schema() {
+        let Ok(url) = std::env::var("DATABASE_URL") else {
+            return;
+        };
+        let pool = PgPool::connect(&url).await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+
+        let schema = format!("plan_usage_{}", Uuid::new_v4().simple());
+        sqlx::raw_sql(&format!(
+            "CREATE SCHEMA {schema}; SET LOCAL search_path TO {schema};"
+        ))
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        sqlx::raw_sql(include_str!(
+            "../../../gateway/migrations/0001_gateway_schema.sql"
+        ))
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+
+        sqlx::raw_sql(
+            r#"
+            INSERT INTO backends (id, organization_id, name) VALUES
+                ('b1', 'org', 'one'), ('b2', 'org', 'two'), ('b3', 'other', 'three');
+            INSERT INTO backend_origins (id, backend_id, name, port) VALUES
+                ('o1', 'b1', 'a', 25565), ('o2', 'b1', 'b', 25565),
+                ('o3', 'b2', 'c', 25565), ('o4', 'b3', 'd', 25565),
+                ('o5', 'b3', 'e', 25565), ('o6', 'b3', 'f', 25565);
+            INSERT INTO backend_domains (backend_id, domain, verification_token) VALUES
+                ('b1', 'one.example', 't1'), ('b3', 'three.example', 't3');
+            INSERT INTO filter_rules (id, backend_id, name) VALUES
+                ('r1', 'b2', 'a'), ('r2', 'b2', 'b'), ('r3', 'b3', 'c');
+            "#,
+        )
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+
+        let usage = sqlx::query_as::<_, PlanUsage>(USAGE_QUERY)
+            .bind("org")
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(
+            usage,
+            PlanUsage {
+                backends: 2,
+                max_origins_per_backend: 2,
+                domains: 1,
+                filter_rules: 2,
+            }
+        );
+
+        let none = sqlx::query_as::<_, PlanUsage>(USAGE_QUERY)
+            .bind("unknown")
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+        assert_eq!(none, PlanUsage::default());
+
+        tx.rollback().await.unwrap();
+    }
+
+    #[test]
+    fn test_error_conversion() {
+        let status: tonic::Status = PlanChangeError::UsageExceeded("3 backends".into()).into();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        assert!(status.message().contains("3 backends"));
+    }
+}
@@ -0,0 +1,615 @@
+//! Security analytics on authentication events
+//!
+//! Logins and API key use are recorded without waiting and analyzed by a
+//! background task for three kinds of attack:
+//!
+//! - Impossible travel: two logins of a user further apart than anyone could
+//!   have travelled in the time between them.
+//! - Credential stuffing: failed logins for many different accounts from one
+//!   IP within a short window.
+//! - API keys used from a network (ASN) the key has not been used from.
+//!
+//! What has been seen is kept in Redis, so every replica of the service adds
+//! to the same picture. Alerts are written to the audit log of the affected
+//! organizations and posted to the metrics service, which publishes them on
+//! its event feed. Credential stuffing IPs are blocked at the XDP layer
+//! through the control plane when a block endpoint is configured.
+
+use chrono::Utc;
+use pistonprotection_common::geoip::{GeoIpInfo, GeoIpService};
+use pistonprotection_common::redis::CacheService;
+use pistonprotection_proto::worker::{BlockIpRequest, worker_service_client::WorkerServiceClient};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tonic::transport::{Channel, Endpoint};
+use tracing::{debug, info, warn};
+
+use crate::config::SecurityConfig;
+use crate::db;
+use crate::models::{AuditActions, AuditLogBuilder};
+use crate::services::{AuditService, SessionService};
+
+/// Events waiting for analysis before new ones are dropped
+const QUEUE_SIZE: usize = 10_000;
+
+/// How long a user's last login location is remembered
+const LAST_LOGIN_TTL: Duration = Duration::from_secs(30 * 86400);
+
+/// Shortest time assumed between two logins, so logins in the same second
+/// don't travel infinitely fast
+const MIN_TRAVEL_SECS: i64 = 60;
+
+/// Mean radius of the earth in km
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Timeout of alert and block requests
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Authentication event to analyze
+#[derive(Debug, Clone)]
+pub enum AuthEvent {
+    /// A user logged in
+    LoginSucceeded {
+        user_id: String,
+        email: String,
+        ip: IpAddr,
+    },
+    /// A login failed, whether or not the account exists
+    LoginFailed { email: String, ip: IpAddr },
+    /// An API key was validated
+    ApiKeyUsed {
+        key_id: String,
+        organization_id: String,
+        ip: IpAddr,
+    },
+}
+
+/// Handle recording authentication events for analysis
+#[derive(Clone)]
+pub struct SecurityAnalytics {
+    tx: mpsc::Sender<AuthEvent>,
+}
+
+impl SecurityAnalytics {
+    /// Create a handle and the receiver the analysis task reads from
+    pub fn channel() -> (Self, mpsc::Receiver<AuthEvent>) {
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        (Self { tx }, rx)
+    }
+
+    /// Record an event without waiting for its analysis
+    ///
+    /// Events are dropped when analysis is disabled or can't keep up, so an
+    /// attack never slows down logins.
+    pub fn record(&self, event: AuthEvent) {
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.tx.try_send(event) {
+            warn!("Security analytics queue full, dropping event");
+        }
+    }
+
+    /// Record an event from an IP address given as text, if it parses
+    pub fn record_from(&self, ip: Option<&str>, event: impl FnOnce(IpAddr) -> AuthEvent) {
+        if let Some(ip) = ip.and_then(|ip| ip.parse().ok()) {
+            self.record(event(ip));
+        }
+    }
+}
+
+/// Where a user last logged in from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginLocation {
+    pub ip: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub country_code: Option<String>,
+    pub city: Option<String>,
+    /// Unix timestamp
+    pub at: i64,
+}
+
+impl LoginLocation {
+    /// Location of a GeoIP lookup, if it has coordinates
+    pub fn from_geo(ip: IpAddr, geo: &GeoIpInfo, at: i64) -> Option<Self> {
+        Some(Self {
+            ip: ip.to_string(),
+            latitude: geo.latitude?,
+            longitude: geo.longitude?,
+            country_code: geo.country_code.clone(),
+            city: geo.city.clone(),
+            at,
+        })
+    }
+
+    /// City and country, or whatever of them is known
+    fn describe(&self) -> String {
+        match (&self.city, &self.country_code) {
+            (Some(city), Some(country)) => format!("{}, {}", city, country),
+            (None, Some(country)) => country.clone(),
+            _ => format!("{:.2}, {:.2}", self.latitude, self.longitude),
+        }
+    }
+}
+
+/// Great-circle distance between two locations in km
+pub fn distance_km(from: &LoginLocation, to: &LoginLocation) -> f64 {
+    let (lat1, lat2) = (from.latitude.to_radians(), to.latitude.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (to.longitude - from.longitude).to_radians();
+
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Travel between two logins
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Travel {
+    pub distance_km: f64,
+    pub speed_kmh: f64,
+}
+
+/// The travel between two logins, if it is impossible
+pub fn impossible_travel(
+    previous: &LoginLocation,
+    current: &LoginLocation,
+    config: &SecurityConfig,
+) -> Option<Travel> {
+    let distance_km = distance_km(previous, current);
+    if distance_km < config.impossible_travel_min_distance_km {
+        return None;
+    }
+
+    let elapsed = (current.at - previous.at).max(MIN_TRAVEL_SECS);
+    let speed_kmh = distance_km / (elapsed as f64 / 3600.0);
+    (speed_kmh > config.impossible_travel_speed_kmh).then_some(Travel {
+        distance_km,
+        speed_kmh,
+    })
+}
+
+/// Window failed logins at a time are counted in
+pub fn stuffing_window(now: i64, window_secs: u64) -> i64 {
+    now.div_euclid(window_secs.max(1) as i64)
+}
+
+/// Whether a key used from an ASN is used from a new network
+///
+/// A key's first use has nothing to compare with and is never new.
+pub fn is_new_network(known: &[String], asn: u32) -> bool {
+    !known.is_empty() && !known.iter().any(|known| *known == asn.to_string())
+}
+
+/// Security alert posted to the metrics service
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityAlert {
+    pub alert_type: &'static str,
+    pub severity: &'static str,
+    pub source: &'static str,
+    pub message: String,
+    pub ip_address: Option<String>,
+    pub user_id: Option<String>,
+    pub organization_id: Option<String>,
+    pub blocked: bool,
+}
+
+impl SecurityAlert {
+    fn new(alert_type: &'static str, severity: &'static str, message: String) -> Self {
+        Self {
+            alert_type,
+            severity,
+            source: "auth",
+            message,
+            ip_address: None,
+            user_id: None,
+            organization_id: None,
+            blocked: false,
+        }
+    }
+}
+
+/// Background analysis of authentication events
+pub struct SecurityDetector {
+    db: PgPool,
+    cache: CacheService,
+    geoip: Arc<GeoIpService>,
+    config: SecurityConfig,
+    http: reqwest::Client,
+    blocker: Option<WorkerServiceClient<Channel>>,
+}
+
+impl SecurityDetector {
+    /// Create a detector
+    ///
+    /// The control plane is connected to on the first block.
+    pub fn new(
+        db: PgPool,
+        cache: CacheService,
+        geoip: Arc<GeoIpService>,
+        config: SecurityConfig,
+    ) -> Result<Self, SecurityAnalyticsError> {
+        let blocker = config
+            .block_endpoint
+            .as_ref()
+            .map(|endpoint| {
+                Endpoint::from_shared(endpoint.clone())
+                    .map(|endpoint| {
+                        WorkerServiceClient::new(endpoint.timeout(REQUEST_TIMEOUT).connect_lazy())
+                    })
+                    .map_err(|e| SecurityAnalyticsError::InvalidConfig(e.to_string()))
+            })
+            .transpose()?;
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| SecurityAnalyticsError::InvalidConfig(e.to_string()))?;
+
+        Ok(Self {
+            db,
+            cache,
+            geoip,
+            config,
+            http,
+            blocker,
+        })
+    }
+
+    /// Analyze events until every handle is dropped
+    pub async fn run(self, mut events: mpsc::Receiver<AuthEvent>) {
+        info!(
+            blocking = self.blocker.is_some(),
+            "Security analytics started"
+        );
+        while let Some(event) = events.recv().await {
+            if let Err(e) = self.analyze(&event).await {
+                warn!("Failed to analyze {:?}: {}", event, e);
+            }
+        }
+        info!("Security analytics stopped");
+    }
+
+    async fn analyze(&self, event: &AuthEvent) -> Result<(), SecurityAnalyticsError> {
+        match event {
+            AuthEvent::LoginSucceeded { user_id, email, ip } => {
+                self.check_travel(user_id, email, *ip).await
+            }
+            AuthEvent::LoginFailed { email, ip } => self.check_stuffing(email, *ip).await,
+            AuthEvent::ApiKeyUsed {
+                key_id,
+                organization_id,
+                ip,
+            } => self.check_network(key_id, organization_id, *ip).await,
+        }
+    }
+
+    /// Compare a login with the user's last one
+    async fn check_travel(
+        &self,
+        user_id: &str,
+        email: &str,
+        ip: IpAddr,
+    ) -> Result<(), SecurityAnalyticsError> {
+        let Some(current) =
+            LoginLocation::from_geo(ip, &self.geoip.lookup(ip), Utc::now().timestamp())
+        else {
+            return Ok(());
+        };
+
+        let key = format!("security:last_login:{}", user_id);
+        let previous: Option<LoginLocation> = self.cache.get(&key).await?;
+        self.cache.set(&key, &current, LAST_LOGIN_TTL).await?;
+
+        let Some(previous) = previous else {
+            return Ok(());
+        };
+        let Some(travel) = impossible_travel(&previous, &current, &self.config) else {
+            return Ok(());
+        };
+
+        let message = format!(
+            "Login of {} from {} ({}) {:.0} km from the previous login from {} ({}), {:.0} km/h",
+            email,
+            current.describe(),
+            current.ip,
+            travel.distance_km,
+            previous.describe(),
+            previous.ip,
+            travel.speed_kmh,
+        );
+        let mut alert = SecurityAlert::new("impossible_travel", "high", message);
+        alert.ip_address = Some(current.ip.clone());
+        alert.user_id = Some(user_id.to_string());
+
+        // Users aren't owned by one organization, so every organization of
+        // the user sees it
+        let audit = AuditService::new(self.db.clone());
+        for org in db::list_user_organizations(&self.db, user_id).await? {
+            audit
+                .log_builder(
+                    AuditLogBuilder::new(&org.id, AuditActions::SECURITY_IMPOSSIBLE_TRAVEL, "user")
+                        .user(user_id, Some(email))
+                        .resource(user_id)
+                        .request_info(Some(&current.ip), None)
+                        .description(&alert.message)
+                        .metadata("previous_ip", &previous.ip)
+                        .metadata("distance_km", &format!("{:.0}", travel.distance_km))
+                        .metadata("speed_kmh", &format!("{:.0}", travel.speed_kmh)),
+                )
+                .await
+                .map_err(|e| SecurityAnalyticsError::Audit(e.to_string()))?;
+        }
+
+        self.alert(alert).await;
+        Ok(())
+    }
+
+    /// Count the accounts failing to log in from an IP
+    async fn check_stuffing(&self, email: &str, ip: IpAddr) -> Result<(), SecurityAnalyticsError> {
+        let window_secs = self.config.stuffing_window_secs.max(1);
+        let window = stuffing_window(Utc::now().timestamp(), window_secs);
+        let key = format!("security:failed_logins:{}:{}", ip, window);
+
+        // Accounts are counted by hash, so the set holds no addresses
+        let account = SessionService::hash_token(&email.trim().to_lowercase());
+        if self.cache.sadd(&key, &account).await? {
+            self.cache
+                .expire(&key, Duration::from_secs(window_secs))
+                .await?;
+        }
+        let accounts = self.cache.smembers(&key).await?.len();
+        if accounts < self.config.stuffing_account_threshold {
+            return Ok(());
+        }
+
+        // Alert once per window, however many replicas see the threshold
+        let alerted = format!("security:stuffing_alerted:{}:{}", ip, window);
+        if !self
+            .cache
+            .set_nx(&alerted, &true, Duration::from_secs(window_secs))
+            .await?
+        {
+            return Ok(());
+        }
+
+        let message = format!(
+            "Failed logins for {} accounts from {} within {} seconds",
+            accounts, ip, window_secs
+        );
+        let mut alert = SecurityAlert::new("credential_stuffing", "critical", message);
+        alert.ip_address = Some(ip.to_string());
+        alert.blocked = self.block(ip, "Credential stuffing").await;
+
+        self.alert(alert).await;
+        Ok(())
+    }
+
+    /// Compare the network an API key is used from with earlier ones
+    async fn check_network(
+        &self,
+        key_id: &str,
+        organization_id: &str,
+        ip: IpAddr,
+    ) -> Result<(), SecurityAnalyticsError> {
+        let geo = self.geoip.lookup(ip);
+        let Some(asn) = geo.asn else {
+            return Ok(());
+        };
+
+        let key = format!("security:api_key_networks:{}", key_id);
+        let known = self.cache.smembers(&key).await?;
+        if known.iter().any(|known| *known == asn.to_string()) {
+            return Ok(());
+        }
+        self.cache.sadd(&key, &asn.to_string()).await?;
+        self.cache
+            .expire(&key, Duration::from_secs(self.config.api_key_asn_ttl_secs))
+            .await?;
+        if !is_new_network(&known, asn) {
+            return Ok(());
+        }
+
+        let network = match &geo.as_org {
+            Some(org) => format!("AS{} ({})", asn, org),
+            None => format!("AS{}", asn),
+        };
+        let message = format!(
+            "API key {} used from new network {} at {}",
+            key_id, network, ip
+        );
+        let mut alert = SecurityAlert::new("api_key_new_network", "medium", message);
+        alert.ip_address = Some(ip.to_string());
+        alert.organization_id = Some(organization_id.to_string());
+
+        AuditService::new(self.db.clone())
+            .log_builder(
+                AuditLogBuilder::new(
+                    organization_id,
+                    AuditActions::SECURITY_API_KEY_NEW_NETWORK,
+                    "api_key",
+                )
+                .resource(key_id)
+                .request_info(Some(&ip.to_string()), None)
+                .description(&alert.message)
+                .metadata("asn", &asn.to_string()),
+            )
+            .await
+            .map_err(|e| SecurityAnalyticsError::Audit(e.to_string()))?;
+
+        self.alert(alert).await;
+        Ok(())
+    }
+
+    /// Block an IP on every worker, returning whether it was blocked
+    async fn block(&self, ip: IpAddr, reason: &str) -> bool {
+        let Some(blocker) = &self.blocker else {
+            return false;
+        };
+
+        let request = BlockIpRequest {
+            ip: Some(ip.into()),
+            reason: reason.to_string(),
+            duration_seconds: self.config.block_duration_secs,
+        };
+        match blocker.clone().block_ip(request).await {
+            Ok(response) if response.get_ref().success => {
+                info!(ip = %ip, reason, "Blocked IP at the XDP layer");
+                true
+            }
+            Ok(_) => {
+                warn!(ip = %ip, "Control plane refused to block IP");
+                false
+            }
+            Err(e) => {
+                warn!(ip = %ip, "Failed to block IP: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Log an alert and post it to the metrics service
+    async fn alert(&self, alert: SecurityAlert) {
+        warn!(
+            alert_type = alert.alert_type,
+            severity = alert.severity,
+            blocked = alert.blocked,
+            "Security alert: {}",
+            alert.message
+        );
+
+        let Some(url) = &self.config.alert_url else {
+            return;
+        };
+        let mut request = self.http.post(url).json(&alert);
+        if !self.config.alert_token.is_empty() {
+            request = request.bearer_auth(&self.config.alert_token);
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!(alert_type = alert.alert_type, "Security alert posted");
+            }
+            Ok(response) => warn!(
+                status = %response.status(),
+                "Metrics service rejected security alert"
+            ),
+            Err(e) => warn!("Failed to post security alert: {}", e),
+        }
+    }
+}
+
+/// Security analytics errors
+#[derive(Debug, thiserror::Error)]
+pub enum SecurityAnalyticsError {
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Cache error: {0}")]
+    Cache(#[from] pistonprotection_common::error::Error),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Audit log error: {0}")]
+    Audit(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(latitude: f64, longitude: f64, at: i64) -> LoginLocation {
+        LoginLocation {
+            ip: "203.0.113.7".to_string(),
+            latitude,
+            longitude,
+            country_code: None,
+            city: None,
+            at,
+        }
+    }
+
+    #[test]
+    fn test_distance() {
+        let berlin = location(52.52, 13.405, 0);
+        let new_york = location(40.7128, -74.006, 0);
+
+        let distance = distance_km(&berlin, &new_york);
+        assert!((distance - 6385.0).abs() < 20.0, "{}", distance);
+        assert!(distance_km(&berlin, &berlin) < 1e-6);
+    }
+
+    #[test]
+    fn test_impossible_travel() {
+        let config = SecurityConfig::default();
+        let berlin = location(52.52, 13.405, 0);
+
+        // Berlin to New York in an hour
+        let travel = impossible_travel(&berlin, &location(40.7128, -74.006, 3600), &config)
+            .expect("impossible travel");
+        assert!(travel.speed_kmh > 6000.0);
+
+        // ... but not in a day
+        assert!(impossible_travel(&berlin, &location(40.7128, -74.006, 86400), &config).is_none());
+
+        // Berlin to Potsdam at once is within GeoIP accuracy
+        assert!(impossible_travel(&berlin, &location(52.39, 13.06, 0), &config).is_none());
+    }
+
+    #[test]
+    fn test_stuffing_window() {
+        assert_eq!(stuffing_window(1199, 600), 1);
+        assert_eq!(stuffing_window(1200, 600), 2);
+        // A zero window doesn't divide by zero
+        assert_eq!(stuffing_window(5, 0), 5);
+    }
+
+    #[test]
+    fn test_new_network() {
+        assert!(!is_new_network(&[], 64500));
+
+        let known = vec!["64500".to_string()];
+        assert!(!is_new_network(&known, 64500));
+        assert!(is_new_network(&known, 64501));
+    }
+
+    #[test]
+    fn test_location_needs_coordinates() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        assert!(LoginLocation::from_geo(ip, &GeoIpInfo::default(), 0).is_none());
+
+        let geo = GeoIpInfo {
+            country_code: Some("DE".to_string()),
+            city: Some("Berlin".to_string()),
+            latitude: Some(52.52),
+            longitude: Some(13.405),
+            ..Default::default()
+        };
+        let location = LoginLocation::from_geo(ip, &geo, 0).unwrap();
+        assert_eq!(location.describe(), "Berlin, DE");
+    }
+
+    #[test]
+    fn test_alert_serialization() {
+        let mut alert = SecurityAlert::new("credential_stuffing", "critical", "test".to_string());
+        alert.blocked = true;
+
+        let json = serde_json::to_value(&alert).unwrap();
+        assert_eq!(json["alertType"], "credential_stuffing");
+        assert_eq!(json["source"], "auth");
+        assert_eq!(json["blocked"], true);
+    }
+
+    #[tokio::test]
+    async fn test_record_without_analysis() {
+        let (analytics, events) = SecurityAnalytics::channel();
+        drop(events);
+
+        // Dropped silently when nothing analyzes events
+        analytics.record_from(Some("203.0.113.7"), |ip| AuthEvent::LoginFailed {
+            email: "user@example.com".to_string(),
+            ip,
+        });
+    }
+}
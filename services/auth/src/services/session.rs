@@ -16,6 +16,8 @@ pub struct CachedSession {
     pub ip_address: Option<String>,
     pub user_agent: Option<String>,
     pub device_type: Option<String>,
+    #[serde(default)]
+    pub country_code: Option<String>,
     pub created_at: i64,
     pub last_active_at: i64,
     pub expires_at: i64,
@@ -29,6 +31,7 @@ impl From<&Session> for CachedSession {
             ip_address: session.ip_address.clone(),
             user_agent: session.user_agent.clone(),
             device_type: session.device_type.clone(),
+            country_code: session.country_code.clone(),
             created_at: session.created_at.timestamp(),
             last_active_at: session.last_active_at.timestamp(),
             expires_at: session.expires_at.timestamp(),
@@ -192,7 +195,7 @@ impl SessionService {
         user_id: &str,
     ) -> Result<(), SessionError> {
         let session_key = format!("session:{}", session_id);
-        let _user_key = format!("user:{}:sessions", user_id);
+        let user_key = format!("user:{}:sessions", user_id);
 
         // Delete session from cache
         self.cache
@@ -200,8 +203,11 @@ impl SessionService {
             .await
             .map_err(|e| SessionError::CacheError(e.to_string()))?;
 
-        // Note: We don't remove from user's session set here as it will eventually expire
-        // In production, you might want to use SREM
+        // Free the session's slot towards the per-user limit
+        self.cache
+            .srem(&user_key, session_id)
+            .await
+            .map_err(|e| SessionError::CacheError(e.to_string()))?;
 
         Ok(())
     }
@@ -0,0 +1,379 @@
+//! Session device management
+//!
+//! Users list the sessions signed in to their account (device, country,
+//! last activity) and sign out any one of them, or every session but the one
+//! they are using. Each login is located with GeoIP, and a login from a
+//! device or country the account has not used before is reported to the user
+//! by email, so a leaked password shows up before it does damage.
+
+use pistonprotection_common::geoip::{GeoIpInfo, GeoIpService};
+use sqlx::PgPool;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::db;
+use crate::models::{Session, User};
+use crate::services::email::{EmailRecipient, EmailService};
+use crate::services::session::SessionService;
+
+/// Earlier sessions compared against a new login
+const HISTORY_LIMIT: u32 = 200;
+
+/// Longest user agent quoted in a notification
+const MAX_USER_AGENT_LEN: usize = 200;
+
+/// What is unfamiliar about a login
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoginNovelty {
+    /// No earlier session used this user agent
+    pub new_device: bool,
+    /// No earlier session came from this country
+    pub new_country: bool,
+}
+
+impl LoginNovelty {
+    /// The login is worth telling the user about
+    pub fn is_new(&self) -> bool {
+        self.new_device || self.new_country
+    }
+
+    /// Phrase completing "signed in to ..." in the notification
+    fn describe(&self) -> &'static str {
+        match (self.new_device, self.new_country) {
+            (true, true) => "from a new device and a new country",
+            (true, false) => "from a new device",
+            _ => "from a new country",
+        }
+    }
+}
+
+/// Compare a login with the user's earlier sessions
+///
+/// The first login of an account has nothing to compare with and is never
+/// new. Countries are only compared once an earlier session has one, so
+/// sessions from before GeoIP lookups don't flag every user's next login.
+pub fn login_novelty(
+    history: &[Session],
+    user_agent: Option<&str>,
+    country_code: Option<&str>,
+) -> LoginNovelty {
+    if history.is_empty() {
+        return LoginNovelty::default();
+    }
+
+    let new_device = !history
+        .iter()
+        .any(|s| s.user_agent.as_deref() == user_agent);
+    let new_country = country_code.is_some_and(|country| {
+        history.iter().any(|s| s.country_code.is_some())
+            && !history
+                .iter()
+                .any(|s| s.country_code.as_deref() == Some(country))
+    });
+
+    LoginNovelty {
+        new_device,
+        new_country,
+    }
+}
+
+/// Human readable location of a GeoIP lookup
+fn describe_location(geo: &GeoIpInfo) -> String {
+    let country = geo.country_name.as_ref().or(geo.country_code.as_ref());
+    match (&geo.city, country) {
+        (Some(city), Some(country)) => format!("{}, {}", city, country),
+        (None, Some(country)) => country.clone(),
+        _ => "Unknown location".to_string(),
+    }
+}
+
+/// Human readable device of a session
+fn describe_device(session: &Session) -> String {
+    let device_type = session.device_type.as_deref().unwrap_or("unknown");
+    match &session.user_agent {
+        Some(user_agent) => {
+            let user_agent: String = user_agent.chars().take(MAX_USER_AGENT_LEN).collect();
+            format!("{} ({})", device_type, user_agent)
+        }
+        None => device_type.to_string(),
+    }
+}
+
+/// Session device service
+pub struct SessionDeviceService {
+    db: PgPool,
+    session_service: Arc<SessionService>,
+    email_service: Arc<EmailService>,
+    geoip: Arc<GeoIpService>,
+}
+
+impl SessionDeviceService {
+    /// Create a new session device service
+    pub fn new(
+        db: PgPool,
+        session_service: Arc<SessionService>,
+        email_service: Arc<EmailService>,
+        geoip: Arc<GeoIpService>,
+    ) -> Self {
+        Self {
+            db,
+            session_service,
+            email_service,
+            geoip,
+        }
+    }
+
+    /// List the active sessions of a user, most recently used first
+    pub async fn list(&self, user_id: &str) -> Result<Vec<Session>, SessionDeviceError> {
+        db::list_user_sessions(&self.db, user_id)
+            .await
+            .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))
+    }
+
+    /// Sign out one of the user's sessions
+    ///
+    /// Returns false if the session had already ended.
+    pub async fn revoke(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> Result<bool, SessionDeviceError> {
+        // Other users' sessions look just like missing ones
+        let session = db::get_session(&self.db, session_id)
+            .await
+            .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))?
+            .filter(|s| s.user_id == user_id)
+            .ok_or(SessionDeviceError::NotFound)?;
+        if !session.active {
+            return Ok(false);
+        }
+
+        db::invalidate_session(&self.db, &session.id)
+            .await
+            .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))?;
+        db::revoke_session_refresh_tokens(&self.db, &session.id)
+            .await
+            .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))?;
+        self.session_service
+            .invalidate_session(&session.id, user_id)
+            .await
+            .map_err(|e| SessionDeviceError::SessionError(e.to_string()))?;
+
+        info!(user_id = %user_id, session_id = %session.id, "Session revoked");
+
+        Ok(true)
+    }
+
+    /// Sign out every session of the user but the current one
+    ///
+    /// Returns how many sessions were signed out.
+    pub async fn revoke_others(
+        &self,
+        user_id: &str,
+        current_session_id: &str,
+    ) -> Result<u64, SessionDeviceError> {
+        if current_session_id.is_empty() {
+            return Err(SessionDeviceError::InvalidRequest(
+                "The current session is required".to_string(),
+            ));
+        }
+        db::get_session(&self.db, current_session_id)
+            .await
+            .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))?
+            .filter(|s| s.user_id == user_id && s.active)
+            .ok_or(SessionDeviceError::NotFound)?;
+
+        let revoked = db::invalidate_other_user_sessions(&self.db, user_id, current_session_id)
+            .await
+            .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))?;
+        for session_id in &revoked {
+            db::revoke_session_refresh_tokens(&self.db, session_id)
+                .await
+                .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))?;
+            self.session_service
+                .invalidate_session(session_id, user_id)
+                .await
+                .map_err(|e| SessionDeviceError::SessionError(e.to_string()))?;
+        }
+
+        info!(
+            user_id = %user_id,
+            kept_session_id = %current_session_id,
+            revoked = revoked.len(),
+            "Other sessions revoked"
+        );
+
+        Ok(revoked.len() as u64)
+    }
+
+    /// Locate a new login and tell the user if it is unfamiliar
+    ///
+    /// Stores the country on the session. A notification that can't be sent
+    /// is logged and doesn't fail the login.
+    pub async fn record_login(
+        &self,
+        user: &User,
+        session: &mut Session,
+    ) -> Result<LoginNovelty, SessionDeviceError> {
+        let geo = session
+            .ip_address
+            .as_deref()
+            .and_then(|ip| ip.parse::<IpAddr>().ok())
+            .map(|ip| self.geoip.lookup(ip))
+            .unwrap_or_default();
+        if let Some(country_code) = &geo.country_code {
+            db::set_session_country(&self.db, &session.id, country_code)
+                .await
+                .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))?;
+            session.country_code = Some(country_code.clone());
+        }
+
+        let history = db::list_user_session_history(&self.db, &user.id, &session.id, HISTORY_LIMIT)
+            .await
+            .map_err(|e| SessionDeviceError::DatabaseError(e.to_string()))?;
+        let novelty = login_novelty(
+            &history,
+            session.user_agent.as_deref(),
+            geo.country_code.as_deref(),
+        );
+        if !novelty.is_new() {
+            return Ok(novelty);
+        }
+
+        info!(
+            user_id = %user.id,
+            session_id = %session.id,
+            new_device = novelty.new_device,
+            new_country = novelty.new_country,
+            "Login from a new device or country"
+        );
+
+        let recipient = EmailRecipient {
+            email: user.email.clone(),
+            name: Some(user.name.clone()),
+        };
+        if let Err(e) = self
+            .email_service
+            .send_new_device_login_email(
+                recipient,
+                novelty.describe(),
+                &describe_device(session),
+                &describe_location(&geo),
+                session.ip_address.as_deref().unwrap_or("unknown"),
+            )
+            .await
+        {
+            warn!(user_id = %user.id, "Failed to send new device login email: {}", e);
+        }
+
+        Ok(novelty)
+    }
+}
+
+/// Session device errors
+#[derive(Debug, thiserror::Error)]
+pub enum SessionDeviceError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Session not found")]
+    NotFound,
+
+    #[error("Session error: {0}")]
+    SessionError(String),
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+impl From<SessionDeviceError> for tonic::Status {
+    fn from(err: SessionDeviceError) -> Self {
+        match err {
+            SessionDeviceError::InvalidRequest(msg) => tonic::Status::invalid_argument(msg),
+            SessionDeviceError::NotFound => tonic::Status::not_found("Session not found"),
+            SessionDeviceError::SessionError(msg) => tonic::Status::internal(msg),
+            SessionDeviceError::DatabaseError(msg) => tonic::Status::internal(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn session(user_agent: Option<&str>, country_code: Option<&str>) -> Session {
+        let now = Utc::now();
+        Session {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id: "user1".to_string(),
+            token_hash: String::new(),
+            ip_address: Some("203.0.113.7".to_string()),
+            user_agent: user_agent.map(str::to_string),
+            device_type: Some("desktop".to_string()),
+            country_code: country_code.map(str::to_string),
+            active: false,
+            expires_at: now,
+            created_at: now,
+            last_active_at: now,
+        }
+    }
+
+    #[test]
+    fn test_first_login_is_not_new() {
+        let novelty = login_novelty(&[], Some("Firefox"), Some("DE"));
+        assert!(!novelty.is_new());
+    }
+
+    #[test]
+    fn test_new_device_and_country() {
+        let history = vec![session(Some("Firefox"), Some("DE"))];
+
+        assert!(!login_novelty(&history, Some("Firefox"), Some("DE")).is_new());
+        assert_eq!(
+            login_novelty(&history, Some("Safari"), Some("DE")),
+            LoginNovelty {
+                new_device: true,
+                new_country: false,
+            }
+        );
+        assert_eq!(
+            login_novelty(&history, Some("Firefox"), Some("BR")),
+            LoginNovelty {
+                new_device: false,
+                new_country: true,
+            }
+        );
+        // An unknown country is never new
+        assert!(!login_novelty(&history, Some("Firefox"), None).is_new());
+    }
+
+    #[test]
+    fn test_country_needs_located_history() {
+        // Sessions from before GeoIP lookups have no country to compare with
+        let history = vec![session(Some("Firefox"), None)];
+        assert!(!login_novelty(&history, Some("Firefox"), Some("DE")).is_new());
+    }
+
+    #[test]
+    fn test_describe() {
+        let geo = GeoIpInfo {
+            country_code: Some("DE".to_string()),
+            country_name: Some("Germany".to_string()),
+            city: Some("Berlin".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(describe_location(&geo), "Berlin, Germany");
+        assert_eq!(describe_location(&GeoIpInfo::default()), "Unknown location");
+
+        let s = session(Some("Firefox"), None);
+        assert_eq!(describe_device(&s), "desktop (Firefox)");
+    }
+
+    #[test]
+    fn test_error_conversion() {
+        let status: tonic::Status = SessionDeviceError::NotFound.into();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+}
@@ -9,9 +9,11 @@ use stripe_rust::{
     BillingPortalSession, CheckoutSession, CheckoutSessionMode, Client, CreateBillingPortalSession,
     CreateCheckoutSession, CreateCheckoutSessionLineItems, CreateCustomer, CreateSubscription,
     CreateSubscriptionItems, CreateUsageRecord, Customer, CustomerId, Invoice as StripeInvoice,
-    InvoiceId, ListInvoices, Price, PriceId, Product, Subscription as StripeSubscription,
-    SubscriptionId, SubscriptionStatus as StripeSubscriptionStatus, UpdateCustomer,
-    UpdateSubscription, UsageRecord as StripeUsageRecord,
+    InvoiceId, InvoiceLineItem as StripeInvoiceLineItem, InvoiceLineItemType, ListInvoices,
+    PaymentMethod as StripePaymentMethod, Price, PriceId, Product,
+    Subscription as StripeSubscription, SubscriptionId, SubscriptionProrationBehavior,
+    SubscriptionStatus as StripeSubscriptionStatus, UpdateCustomer, UpdateSubscription,
+    UsageRecord as StripeUsageRecord,
 };
 use tracing::{info, warn};
 use uuid::Uuid;
@@ -21,9 +23,11 @@ use crate::models::{
     Organization, Subscription, SubscriptionStatus,
     subscription::{
         BillingPeriod, CreateBillingPortalSessionRequest, CreateCheckoutSessionRequest, Invoice,
-        InvoiceStatus, Plan, PlanType, ProrationBehavior, UsageMetricType, UsageSummary,
+        InvoiceLineKind, InvoiceStatus, PaymentMethod, Plan, PlanType, ProrationBehavior,
+        UsageMetricType, UsageSummary,
     },
 };
+use crate::services::plan_change;
 
 /// Stripe service for handling all Stripe-related operations
 #[derive(Clone)]
@@ -186,7 +190,7 @@ impl StripeService {
         &self,
         subscription_id: &str,
         new_price_id: Option<&str>,
-        _proration_behavior: ProrationBehavior,
+        proration_behavior: ProrationBehavior,
     ) -> Result<StripeSubscription> {
         let subscription_id_parsed: SubscriptionId = subscription_id
             .parse()
@@ -208,8 +212,11 @@ impl StripeService {
             }
         }
 
-        // Note: proration_behavior setting is not applied due to type export issues
-        // with the async-stripe crate. Default behavior (create_prorations) will be used.
+        update.proration_behavior = Some(match proration_behavior {
+            ProrationBehavior::CreateProrations => SubscriptionProrationBehavior::CreateProrations,
+            ProrationBehavior::None => SubscriptionProrationBehavior::None,
+            ProrationBehavior::AlwaysInvoice => SubscriptionProrationBehavior::AlwaysInvoice,
+        });
 
         StripeSubscription::update(&self.client, &subscription_id_parsed, update)
             .await
@@ -813,7 +820,7 @@ impl StripeService {
             r#"
             UPDATE subscriptions
             SET status = $1, updated_at = NOW()
-            WHERE id = $1 OR stripe_subscription_id = $2
+            WHERE id = $2 OR stripe_subscription_id = $2
             "#,
         )
         .bind(status)
@@ -830,14 +837,7 @@ impl StripeService {
         &self,
         stripe_sub: &StripeSubscription,
     ) -> Result<()> {
-        let status = match stripe_sub.status {
-            StripeSubscriptionStatus::Active => SubscriptionStatus::Active,
-            StripeSubscriptionStatus::Trialing => SubscriptionStatus::Trialing,
-            StripeSubscriptionStatus::PastDue => SubscriptionStatus::PastDue,
-            StripeSubscriptionStatus::Canceled => SubscriptionStatus::Canceled,
-            StripeSubscriptionStatus::Unpaid => SubscriptionStatus::Unpaid,
-            _ => SubscriptionStatus::Active,
-        };
+        let status = local_subscription_status(&stripe_sub.status);
 
         let current_period_start = Utc
             .timestamp_opt(stripe_sub.current_period_start, 0)
@@ -851,6 +851,8 @@ impl StripeService {
         let trial_end = stripe_sub
             .trial_end
             .and_then(|ts| Utc.timestamp_opt(ts, 0).single());
+        // `trial_end` stays set once the trial is over
+        let in_trial = status == SubscriptionStatus::Trialing;
 
         let canceled_at = stripe_sub
             .canceled_at
@@ -886,7 +888,7 @@ impl StripeService {
             .bind(status)
             .bind(current_period_start)
             .bind(current_period_end)
-            .bind(stripe_sub.trial_end.is_some())
+            .bind(in_trial)
             .bind(trial_end)
             .bind(stripe_sub.cancel_at_period_end)
             .bind(canceled_at)
@@ -919,7 +921,7 @@ impl StripeService {
                 .bind(status)
                 .bind(current_period_start)
                 .bind(current_period_end)
-                .bind(stripe_sub.trial_end.is_some())
+                .bind(in_trial)
                 .bind(trial_end)
                 .bind(stripe_sub.cancel_at_period_end)
                 .bind(canceled_at)
@@ -936,82 +938,28 @@ impl StripeService {
             }
         }
 
-        // Update organization limits based on plan
-        if let Some(item) = stripe_sub.items.data.first() {
-            if let Some(price) = &item.price {
-                if let Ok(plan) = self.get_plan_by_stripe_price(price.id.as_ref()).await {
-                    self.update_organization_limits_from_plan(stripe_sub.id.as_ref(), &plan)
-                        .await?;
-                }
-            }
+        // Apply the plan Stripe bills for; a scheduled downgrade waits for
+        // the end of the period
+        if let Some(price) = stripe_sub
+            .items
+            .data
+            .first()
+            .and_then(|item| item.price.as_ref())
+            && let Ok(plan) = self.get_plan_by_stripe_price(price.id.as_ref()).await
+        {
+            let period = if plan.stripe_price_id_yearly.as_deref() == Some(price.id.as_str()) {
+                BillingPeriod::Yearly
+            } else {
+                BillingPeriod::Monthly
+            };
+            plan_change::sync_billed_plan(&self.db, stripe_sub.id.as_ref(), &plan, period)
+                .await
+                .context("Failed to sync subscription plan")?;
         }
 
         Ok(())
     }
 
-    /// Update organization limits based on plan
-    async fn update_organization_limits_from_plan(
-        &self,
-        stripe_subscription_id: &str,
-        plan: &Plan,
-    ) -> Result<()> {
-        // Get the subscription to find organization
-        let sub = self
-            .get_subscription_by_stripe_id(stripe_subscription_id)
-            .await?
-            .ok_or_else(|| anyhow!("Subscription not found"))?;
-
-        sqlx::query(
-            r#"
-            INSERT INTO organization_limits (
-                id, organization_id, max_backends, max_origins_per_backend,
-                max_domains, max_filter_rules, max_bandwidth_bytes, max_requests,
-                advanced_protection, priority_support, custom_ssl, api_access,
-                data_retention_days, created_at, updated_at
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, NOW(), NOW()
-            )
-            ON CONFLICT (organization_id) DO UPDATE SET
-                max_backends = EXCLUDED.max_backends,
-                max_origins_per_backend = EXCLUDED.max_origins_per_backend,
-                max_domains = EXCLUDED.max_domains,
-                max_filter_rules = EXCLUDED.max_filter_rules,
-                max_bandwidth_bytes = EXCLUDED.max_bandwidth_bytes,
-                max_requests = EXCLUDED.max_requests,
-                advanced_protection = EXCLUDED.advanced_protection,
-                priority_support = EXCLUDED.priority_support,
-                custom_ssl = EXCLUDED.custom_ssl,
-                api_access = EXCLUDED.api_access,
-                data_retention_days = EXCLUDED.data_retention_days,
-                updated_at = NOW()
-            "#,
-        )
-        .bind(Uuid::new_v4().to_string())
-        .bind(&sub.organization_id)
-        .bind(plan.max_backends)
-        .bind(plan.max_origins_per_backend)
-        .bind(plan.max_domains)
-        .bind(plan.max_filter_rules)
-        .bind(plan.max_bandwidth_bytes)
-        .bind(plan.max_requests)
-        .bind(plan.advanced_protection)
-        .bind(plan.priority_support)
-        .bind(plan.custom_ssl)
-        .bind(plan.api_access)
-        .bind(plan.data_retention_days)
-        .execute(&self.db)
-        .await
-        .context("Failed to update organization limits")?;
-
-        info!(
-            organization_id = %sub.organization_id,
-            plan_id = %plan.id,
-            "Updated organization limits from plan"
-        );
-
-        Ok(())
-    }
-
     /// Store a local invoice record from Stripe
     pub async fn sync_invoice_from_stripe(&self, stripe_invoice: &StripeInvoice) -> Result<()> {
         let customer_id = match &stripe_invoice.customer {
@@ -1064,26 +1012,50 @@ impl StripeService {
             stripe_rust::Expandable::Object(pi) => pi.id.to_string(),
         });
 
-        sqlx::query(
+        let customer_address = stripe_invoice.customer_address.as_ref();
+        let customer_vat_id = stripe_invoice
+            .customer_tax_ids
+            .iter()
+            .flatten()
+            .find(|tax_id| tax_id.type_.as_str() == "eu_vat")
+            .and_then(|tax_id| tax_id.value.clone());
+
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+
+        let (invoice_id,): (String,) = sqlx::query_as(
             r#"
             INSERT INTO invoices (
                 id, organization_id, subscription_id, stripe_invoice_id,
                 stripe_payment_intent_id, number, status, currency,
                 subtotal_cents, tax_cents, total_cents, amount_paid_cents,
                 amount_due_cents, description, invoice_pdf_url, hosted_invoice_url,
-                period_start, period_end, due_date, paid_at, created_at, updated_at
+                period_start, period_end, due_date, paid_at, customer_name,
+                customer_address, customer_country, customer_vat_id, created_at, updated_at
             ) VALUES (
                 $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15,
-                $16, $17, $18, $19, $20, NOW(), NOW()
+                $16, $17, $18, $19, $20, $21, $22, $23, $24, NOW(), NOW()
             )
             ON CONFLICT (stripe_invoice_id) DO UPDATE SET
+                number = EXCLUDED.number,
                 status = EXCLUDED.status,
+                subtotal_cents = EXCLUDED.subtotal_cents,
+                tax_cents = EXCLUDED.tax_cents,
+                total_cents = EXCLUDED.total_cents,
                 amount_paid_cents = EXCLUDED.amount_paid_cents,
                 amount_due_cents = EXCLUDED.amount_due_cents,
                 invoice_pdf_url = EXCLUDED.invoice_pdf_url,
                 hosted_invoice_url = EXCLUDED.hosted_invoice_url,
                 paid_at = EXCLUDED.paid_at,
+                customer_name = EXCLUDED.customer_name,
+                customer_address = EXCLUDED.customer_address,
+                customer_country = EXCLUDED.customer_country,
+                customer_vat_id = EXCLUDED.customer_vat_id,
                 updated_at = NOW()
+            RETURNING id
             "#,
         )
         .bind(Uuid::new_v4().to_string())
@@ -1111,10 +1083,55 @@ impl StripeService {
         .bind(period_end)
         .bind(due_date)
         .bind(paid_at)
-        .execute(&self.db)
+        .bind(&stripe_invoice.customer_name)
+        .bind(customer_address.and_then(address_text))
+        .bind(customer_address.and_then(|address| address.country.clone()))
+        .bind(customer_vat_id)
+        .fetch_one(&mut *tx)
         .await
         .context("Failed to sync invoice")?;
 
+        // Stripe embeds the first page of line items, which covers the plan,
+        // prorations and one item per metered price
+        if let Some(lines) = &stripe_invoice.lines {
+            sqlx::query("DELETE FROM invoice_line_items WHERE invoice_id = $1")
+                .bind(&invoice_id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to replace invoice line items")?;
+
+            for (position, item) in lines.data.iter().enumerate() {
+                let quantity = item.quantity.unwrap_or(0) as i64;
+                let unit_amount = item
+                    .price
+                    .as_ref()
+                    .and_then(|price| price.unit_amount)
+                    .or_else(|| (quantity > 0).then(|| item.amount / quantity));
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO invoice_line_items (
+                        id, invoice_id, position, description, kind, quantity,
+                        unit_amount_cents, amount_cents, created_at
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+                    "#,
+                )
+                .bind(Uuid::new_v4().to_string())
+                .bind(&invoice_id)
+                .bind(position as i32)
+                .bind(item.description.as_deref().unwrap_or("Invoice item"))
+                .bind(line_item_kind(item))
+                .bind(quantity)
+                .bind(unit_amount)
+                .bind(item.amount)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to store invoice line item")?;
+            }
+        }
+
+        tx.commit().await.context("Failed to commit invoice sync")?;
+
         Ok(())
     }
 
@@ -1329,57 +1346,301 @@ impl StripeService {
         organization_id: &str,
         plan: &Plan,
     ) -> Result<()> {
+        plan_change::upsert_limits(&self.db, organization_id, plan)
+            .await
+            .context("Failed to update organization limits")
+    }
+
+    // ========== Payment Methods ==========
+
+    /// Get the payment methods of an organization, default first
+    pub async fn list_payment_methods(&self, organization_id: &str) -> Result<Vec<PaymentMethod>> {
+        sqlx::query_as::<_, PaymentMethod>(
+            r#"
+            SELECT * FROM payment_methods
+            WHERE organization_id = $1
+            ORDER BY is_default DESC, created_at DESC
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to fetch payment methods")
+    }
+
+    /// Store a payment method attached to or updated on a customer
+    pub async fn sync_payment_method(&self, payment_method: &StripePaymentMethod) -> Result<()> {
+        let customer_id = match &payment_method.customer {
+            Some(stripe_rust::Expandable::Id(id)) => id.to_string(),
+            Some(stripe_rust::Expandable::Object(customer)) => customer.id.to_string(),
+            None => return Ok(()), // Not attached to a customer
+        };
+
+        let Some(sub) = self.get_subscription_by_customer_id(&customer_id).await? else {
+            warn!(
+                payment_method_id = %payment_method.id,
+                customer_id = %customer_id,
+                "Could not find organization for payment method"
+            );
+            return Ok(());
+        };
+
+        let card = payment_method.card.as_ref();
         sqlx::query(
             r#"
-            INSERT INTO organization_limits (
-                id, organization_id, max_backends, max_origins_per_backend,
-                max_domains, max_filter_rules, max_bandwidth_bytes, max_requests,
-                advanced_protection, priority_support, custom_ssl, api_access,
-                data_retention_days, created_at, updated_at
+            INSERT INTO payment_methods (
+                id, organization_id, stripe_payment_method_id, payment_type,
+                card_brand, card_last4, card_exp_month, card_exp_year,
+                is_default, created_at, updated_at
             ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, NOW(), NOW()
+                $1, $2, $3, $4, $5, $6, $7, $8,
+                COALESCE(
+                    (SELECT stripe_payment_method_id = $3 FROM subscriptions WHERE id = $9),
+                    FALSE
+                ),
+                NOW(), NOW()
             )
-            ON CONFLICT (organization_id) DO UPDATE SET
-                max_backends = EXCLUDED.max_backends,
-                max_origins_per_backend = EXCLUDED.max_origins_per_backend,
-                max_domains = EXCLUDED.max_domains,
-                max_filter_rules = EXCLUDED.max_filter_rules,
-                max_bandwidth_bytes = EXCLUDED.max_bandwidth_bytes,
-                max_requests = EXCLUDED.max_requests,
-                advanced_protection = EXCLUDED.advanced_protection,
-                priority_support = EXCLUDED.priority_support,
-                custom_ssl = EXCLUDED.custom_ssl,
-                api_access = EXCLUDED.api_access,
-                data_retention_days = EXCLUDED.data_retention_days,
+            ON CONFLICT (stripe_payment_method_id) DO UPDATE SET
+                organization_id = EXCLUDED.organization_id,
+                payment_type = EXCLUDED.payment_type,
+                card_brand = EXCLUDED.card_brand,
+                card_last4 = EXCLUDED.card_last4,
+                card_exp_month = EXCLUDED.card_exp_month,
+                card_exp_year = EXCLUDED.card_exp_year,
                 updated_at = NOW()
             "#,
         )
         .bind(Uuid::new_v4().to_string())
-        .bind(organization_id)
-        .bind(plan.max_backends)
-        .bind(plan.max_origins_per_backend)
-        .bind(plan.max_domains)
-        .bind(plan.max_filter_rules)
-        .bind(plan.max_bandwidth_bytes)
-        .bind(plan.max_requests)
-        .bind(plan.advanced_protection)
-        .bind(plan.priority_support)
-        .bind(plan.custom_ssl)
-        .bind(plan.api_access)
-        .bind(plan.data_retention_days)
+        .bind(&sub.organization_id)
+        .bind(payment_method.id.to_string())
+        .bind(payment_method.type_.as_str())
+        .bind(card.map(|c| c.brand.clone()))
+        .bind(card.map(|c| c.last4.clone()))
+        .bind(card.map(|c| c.exp_month as i32))
+        .bind(card.map(|c| c.exp_year as i32))
+        .bind(&sub.id)
         .execute(&self.db)
         .await
-        .context("Failed to update organization limits")?;
+        .context("Failed to store payment method")?;
+
+        Ok(())
+    }
+
+    /// Remove a payment method detached from its customer
+    pub async fn remove_payment_method(&self, payment_method_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM payment_methods WHERE stripe_payment_method_id = $1")
+            .bind(payment_method_id)
+            .execute(&self.db)
+            .await
+            .context("Failed to remove payment method")?;
+
+        Ok(())
+    }
+
+    /// Record which payment method a customer pays invoices with
+    pub async fn sync_default_payment_method(&self, customer: &Customer) -> Result<()> {
+        let default = customer
+            .invoice_settings
+            .as_ref()
+            .and_then(|settings| settings.default_payment_method.as_ref())
+            .map(|pm| match pm {
+                stripe_rust::Expandable::Id(id) => id.to_string(),
+                stripe_rust::Expandable::Object(pm) => pm.id.to_string(),
+            });
+
+        let Some(sub) = self
+            .get_subscription_by_customer_id(customer.id.as_ref())
+            .await?
+        else {
+            return Ok(());
+        };
+
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+        sqlx::query(
+            r#"
+            UPDATE subscriptions SET stripe_payment_method_id = $1, updated_at = NOW()
+            WHERE stripe_customer_id = $2
+            "#,
+        )
+        .bind(&default)
+        .bind(customer.id.to_string())
+        .execute(&mut *tx)
+        .await
+        .context("Failed to update default payment method")?;
+        sqlx::query(
+            r#"
+            UPDATE payment_methods
+            SET is_default = (stripe_payment_method_id = $2), updated_at = NOW()
+            WHERE organization_id = $1
+            "#,
+        )
+        .bind(&sub.organization_id)
+        .bind(&default)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to update default payment method")?;
+        tx.commit().await.context("Failed to commit transaction")?;
+
+        info!(
+            organization_id = %sub.organization_id,
+            payment_method_id = ?default,
+            "Synced default payment method"
+        );
 
         Ok(())
     }
 
+    // ========== Reconciliation ==========
+
+    /// Re-sync live subscriptions and open invoices from Stripe
+    ///
+    /// Webhooks can be lost or fail for good; this catches the local records
+    /// up with Stripe, which is the source of truth.
+    pub async fn reconcile(&self) -> Result<ReconcileStats> {
+        let mut stats = ReconcileStats::default();
+
+        let subscriptions = sqlx::query_as::<_, Subscription>(
+            r#"
+            SELECT * FROM subscriptions
+            WHERE stripe_subscription_id IS NOT NULL AND status <> 'canceled'
+            "#,
+        )
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to fetch subscriptions to reconcile")?;
+
+        for sub in subscriptions {
+            let Some(stripe_id) = sub.stripe_subscription_id.as_deref() else {
+                continue;
+            };
+            stats.subscriptions += 1;
+
+            let result = async {
+                let stripe_sub = self.get_subscription(stripe_id).await?;
+                let drifted = local_subscription_status(&stripe_sub.status) != sub.status;
+                self.sync_subscription_from_stripe(&stripe_sub).await?;
+                Ok::<_, anyhow::Error>(drifted)
+            }
+            .await;
+            match result {
+                Ok(true) => {
+                    warn!(
+                        subscription_id = %sub.id,
+                        stripe_subscription_id = %stripe_id,
+                        "Subscription status was out of sync with Stripe"
+                    );
+                    stats.corrected += 1;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    warn!(subscription_id = %sub.id, "Failed to reconcile subscription: {}", e);
+                    stats.failed += 1;
+                }
+            }
+        }
+
+        let invoice_ids: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT stripe_invoice_id FROM invoices
+            WHERE stripe_invoice_id IS NOT NULL AND status IN ('draft', 'open')
+            "#,
+        )
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to fetch invoices to reconcile")?;
+
+        for invoice_id in invoice_ids {
+            stats.invoices += 1;
+            let result = async {
+                let invoice = self.get_invoice(&invoice_id).await?;
+                self.sync_invoice_from_stripe(&invoice).await
+            }
+            .await;
+            if let Err(e) = result {
+                warn!(stripe_invoice_id = %invoice_id, "Failed to reconcile invoice: {}", e);
+                stats.failed += 1;
+            }
+        }
+
+        Ok(stats)
+    }
+
     /// Get the Stripe config
     pub fn config(&self) -> &StripeConfig {
         &self.config
     }
 }
 
+/// Outcome of a reconciliation with Stripe
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReconcileStats {
+    /// Subscriptions compared with Stripe
+    pub subscriptions: u32,
+    /// Open invoices compared with Stripe
+    pub invoices: u32,
+    /// Subscriptions whose status had drifted from Stripe
+    pub corrected: u32,
+    /// Records that could not be fetched or stored
+    pub failed: u32,
+}
+
+/// Local status of a Stripe subscription status
+fn local_subscription_status(status: &StripeSubscriptionStatus) -> SubscriptionStatus {
+    match status {
+        StripeSubscriptionStatus::Active => SubscriptionStatus::Active,
+        StripeSubscriptionStatus::Trialing => SubscriptionStatus::Trialing,
+        StripeSubscriptionStatus::PastDue => SubscriptionStatus::PastDue,
+        StripeSubscriptionStatus::Canceled => SubscriptionStatus::Canceled,
+        StripeSubscriptionStatus::Unpaid => SubscriptionStatus::Unpaid,
+        _ => SubscriptionStatus::Active,
+    }
+}
+
+/// Kind of a Stripe invoice line item
+fn line_item_kind(item: &StripeInvoiceLineItem) -> InvoiceLineKind {
+    let metered = item
+        .price
+        .as_ref()
+        .and_then(|price| price.recurring.as_ref())
+        .is_some_and(|recurring| recurring.usage_type == stripe_rust::RecurringUsageType::Metered);
+
+    if item.proration {
+        InvoiceLineKind::Proration
+    } else if metered {
+        InvoiceLineKind::Overage
+    } else if item.type_ == InvoiceLineItemType::Subscription {
+        InvoiceLineKind::Subscription
+    } else {
+        InvoiceLineKind::Other
+    }
+}
+
+/// Postal address without the country, one line per line
+fn address_text(address: &stripe_rust::Address) -> Option<String> {
+    let city = [address.postal_code.as_deref(), address.city.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let lines: Vec<&str> = [
+        address.line1.as_deref(),
+        address.line2.as_deref(),
+        Some(city.as_str()),
+        address.state.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(str::trim)
+    .filter(|line| !line.is_empty())
+    .collect();
+
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1396,4 +1657,48 @@ mod tests {
             "always_invoice"
         );
     }
+
+    #[test]
+    fn test_local_subscription_status() {
+        assert_eq!(
+            local_subscription_status(&StripeSubscriptionStatus::PastDue),
+            SubscriptionStatus::PastDue
+        );
+        assert_eq!(
+            local_subscription_status(&StripeSubscriptionStatus::Incomplete),
+            SubscriptionStatus::Active
+        );
+    }
+
+    #[test]
+    fn test_line_item_kind() {
+        let mut item = StripeInvoiceLineItem {
+            type_: InvoiceLineItemType::Subscription,
+            ..Default::default()
+        };
+        assert_eq!(line_item_kind(&item), InvoiceLineKind::Subscription);
+
+        item.proration = true;
+        assert_eq!(line_item_kind(&item), InvoiceLineKind::Proration);
+
+        item.proration = false;
+        item.type_ = InvoiceLineItemType::InvoiceItem;
+        assert_eq!(line_item_kind(&item), InvoiceLineKind::Other);
+    }
+
+    #[test]
+    fn test_address_text() {
+        let address = stripe_rust::Address {
+            line1: Some("Hauptstr. 1".to_string()),
+            postal_code: Some("10115".to_string()),
+            city: Some("Berlin".to_string()),
+            country: Some("DE".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            address_text(&address).as_deref(),
+            Some("Hauptstr. 1\n10115 Berlin")
+        );
+        assert_eq!(address_text(&stripe_rust::Address::default()), None);
+    }
 }
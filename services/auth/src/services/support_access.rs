@@ -0,0 +1,369 @@
+//! Support access service
+//!
+//! Organization owners and admins grant PistonProtection support staff
+//! read-only access to their organization for a limited time, and may revoke
+//! it at any time. Staff (platform admins) exchange an active grant for a
+//! short-lived support token, which the gateway accepts for reads only and
+//! refuses once the grant is revoked. Every step lands in the organization's
+//! audit log.
+
+use chrono::{DateTime, Duration, Utc};
+use pistonprotection_common::duration::bounded_duration;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::db;
+use crate::models::{
+    AuditActions, AuditLogBuilder, OrganizationRole, SupportAccessGrant, UserRole,
+};
+use crate::services::audit::AuditService;
+use crate::services::jwt::{JwtError, JwtService};
+
+/// Grant duration when none is requested
+pub const DEFAULT_GRANT_DURATION_SECS: u64 = 24 * 60 * 60;
+
+/// Shortest grant worth handing to support
+pub const MIN_GRANT_DURATION_SECS: u64 = 15 * 60;
+
+/// Longest grant, so forgotten access runs out on its own
+pub const MAX_GRANT_DURATION_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Longest reason kept with a grant or token
+const MAX_REASON_LEN: usize = 500;
+
+/// Grants returned by a listing
+const LIST_LIMIT: u32 = 100;
+
+/// Support token issued to staff
+#[derive(Debug, Clone)]
+pub struct SupportToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub grant: SupportAccessGrant,
+}
+
+/// Support access service
+pub struct SupportAccessService {
+    db: PgPool,
+    jwt_service: Arc<JwtService>,
+}
+
+impl SupportAccessService {
+    /// Create a new support access service
+    pub fn new(db: PgPool, jwt_service: Arc<JwtService>) -> Self {
+        Self { db, jwt_service }
+    }
+
+    /// Grant support staff read-only access to an organization
+    pub async fn grant(
+        &self,
+        organization_id: &str,
+        user_id: &str,
+        reason: &str,
+        duration_secs: u64,
+    ) -> Result<SupportAccessGrant, SupportAccessError> {
+        let reason = validate_reason(reason)?;
+        let duration_secs = bounded_duration(
+            duration_secs,
+            DEFAULT_GRANT_DURATION_SECS,
+            MAX_GRANT_DURATION_SECS,
+        )
+        .filter(|secs| *secs >= MIN_GRANT_DURATION_SECS)
+        .ok_or_else(|| {
+            SupportAccessError::InvalidRequest(format!(
+                "Duration must be between {} and {} seconds",
+                MIN_GRANT_DURATION_SECS, MAX_GRANT_DURATION_SECS
+            ))
+        })?;
+        let duration = Duration::seconds(duration_secs as i64);
+        let user = self.user(user_id).await?;
+        self.require_manager(organization_id, user_id).await?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let grant = db::create_support_access_grant(
+            &self.db,
+            &id,
+            organization_id,
+            user_id,
+            &reason,
+            Utc::now() + duration,
+        )
+        .await
+        .map_err(|e| SupportAccessError::DatabaseError(e.to_string()))?;
+
+        let description = format!(
+            "Granted support read-only access until {}",
+            grant.expires_at.to_rfc3339()
+        );
+        self.audit(
+            AuditLogBuilder::new(
+                organization_id,
+                AuditActions::SUPPORT_ACCESS_GRANTED,
+                "support_access",
+            )
+            .user(user_id, Some(&user.email))
+            .resource(&grant.id)
+            .description(&description)
+            .metadata("reason", &reason)
+            .metadata("expires_at", &grant.expires_at.to_rfc3339()),
+        )
+        .await?;
+
+        info!(
+            organization_id = %organization_id,
+            grant_id = %grant.id,
+            "Support access granted"
+        );
+
+        Ok(grant)
+    }
+
+    /// List the support access grants of an organization, newest first
+    pub async fn list(
+        &self,
+        organization_id: &str,
+        user_id: &str,
+        active_only: bool,
+    ) -> Result<Vec<SupportAccessGrant>, SupportAccessError> {
+        let user = self.user(user_id).await?;
+        if user.role != UserRole::Admin {
+            self.require_manager(organization_id, user_id).await?;
+        }
+
+        db::list_support_access_grants(&self.db, organization_id, active_only, LIST_LIMIT)
+            .await
+            .map_err(|e| SupportAccessError::DatabaseError(e.to_string()))
+    }
+
+    /// Revoke a grant, ending support access at once
+    ///
+    /// Owners and admins of the organization revoke grants, and staff may
+    /// give up access they no longer need.
+    pub async fn revoke(&self, grant_id: &str, user_id: &str) -> Result<bool, SupportAccessError> {
+        let grant = db::get_support_access_grant(&self.db, grant_id)
+            .await
+            .map_err(|e| SupportAccessError::DatabaseError(e.to_string()))?
+            .ok_or(SupportAccessError::NotFound)?;
+        let user = self.user(user_id).await?;
+        if user.role != UserRole::Admin {
+            self.require_manager(&grant.organization_id, user_id)
+                .await?;
+        }
+
+        let revoked = db::revoke_support_access_grant(&self.db, grant_id, user_id)
+            .await
+            .map_err(|e| SupportAccessError::DatabaseError(e.to_string()))?;
+        if !revoked {
+            return Ok(false);
+        }
+
+        self.audit(
+            AuditLogBuilder::new(
+                &grant.organization_id,
+                AuditActions::SUPPORT_ACCESS_REVOKED,
+                "support_access",
+            )
+            .user(user_id, Some(&user.email))
+            .resource(&grant.id)
+            .description("Revoked support read-only access"),
+        )
+        .await?;
+
+        info!(
+            organization_id = %grant.organization_id,
+            grant_id = %grant.id,
+            "Support access revoked"
+        );
+
+        Ok(true)
+    }
+
+    /// Issue a support token to staff for an organization with an active grant
+    pub async fn issue_token(
+        &self,
+        organization_id: &str,
+        user_id: &str,
+        reason: &str,
+    ) -> Result<SupportToken, SupportAccessError> {
+        let reason = validate_reason(reason)?;
+        let user = self.user(user_id).await?;
+        if user.role != UserRole::Admin {
+            return Err(SupportAccessError::PermissionDenied(
+                "Only support staff may use support access".to_string(),
+            ));
+        }
+
+        let grant = db::get_active_support_access_grant(&self.db, organization_id)
+            .await
+            .map_err(|e| SupportAccessError::DatabaseError(e.to_string()))?
+            .ok_or(SupportAccessError::NoActiveGrant)?;
+
+        let (token, expires_at) = self.jwt_service.generate_support_token(
+            &user.id,
+            &user.email,
+            organization_id,
+            &grant.id,
+            grant.expires_at,
+        )?;
+
+        // No token leaves here without its audit entry
+        self.audit(
+            AuditLogBuilder::new(
+                organization_id,
+                AuditActions::SUPPORT_ACCESS_TOKEN_ISSUED,
+                "support_access",
+            )
+            .user(&user.id, Some(&user.email))
+            .resource(&grant.id)
+            .description("Support staff started a read-only session")
+            .metadata("reason", &reason)
+            .metadata("expires_at", &expires_at.to_rfc3339()),
+        )
+        .await?;
+
+        info!(
+            organization_id = %organization_id,
+            grant_id = %grant.id,
+            staff_user_id = %user.id,
+            "Support token issued"
+        );
+
+        Ok(SupportToken {
+            token,
+            expires_at,
+            grant,
+        })
+    }
+
+    /// Load the acting user
+    async fn user(&self, user_id: &str) -> Result<crate::models::User, SupportAccessError> {
+        db::get_user_by_id(&self.db, user_id)
+            .await
+            .map_err(|e| SupportAccessError::DatabaseError(e.to_string()))?
+            .ok_or_else(|| SupportAccessError::PermissionDenied("Unknown user".to_string()))
+    }
+
+    /// Require the user to own or administer the organization
+    async fn require_manager(
+        &self,
+        organization_id: &str,
+        user_id: &str,
+    ) -> Result<(), SupportAccessError> {
+        let member = db::get_organization_member(&self.db, organization_id, user_id)
+            .await
+            .map_err(|e| SupportAccessError::DatabaseError(e.to_string()))?;
+        match member.map(|m| m.role) {
+            Some(OrganizationRole::Owner | OrganizationRole::Admin) => Ok(()),
+            _ => Err(SupportAccessError::PermissionDenied(
+                "Only owners and admins manage support access".to_string(),
+            )),
+        }
+    }
+
+    async fn audit(&self, builder: AuditLogBuilder) -> Result<(), SupportAccessError> {
+        AuditService::new(self.db.clone())
+            .log_builder(builder)
+            .await
+            .map(|_| ())
+            .map_err(|e| SupportAccessError::DatabaseError(e.to_string()))
+    }
+}
+
+/// Validate the reason given for a grant or support session
+pub fn validate_reason(reason: &str) -> Result<String, SupportAccessError> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(SupportAccessError::InvalidRequest(
+            "A reason is required".to_string(),
+        ));
+    }
+    if reason.chars().count() > MAX_REASON_LEN {
+        return Err(SupportAccessError::InvalidRequest(format!(
+            "Reason must be at most {} characters",
+            MAX_REASON_LEN
+        )));
+    }
+    Ok(reason.to_string())
+}
+
+/// Support access errors
+#[derive(Debug, thiserror::Error)]
+pub enum SupportAccessError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("Support access grant not found")]
+    NotFound,
+
+    #[error("No active support access grant")]
+    NoActiveGrant,
+
+    #[error("Token error: {0}")]
+    Token(#[from] JwtError),
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+impl From<SupportAccessError> for tonic::Status {
+    fn from(err: SupportAccessError) -> Self {
+        match err {
+            SupportAccessError::InvalidRequest(msg) => tonic::Status::invalid_argument(msg),
+            SupportAccessError::PermissionDenied(msg) => tonic::Status::permission_denied(msg),
+            SupportAccessError::NotFound => {
+                tonic::Status::not_found("Support access grant not found")
+            }
+            SupportAccessError::NoActiveGrant => {
+                tonic::Status::failed_precondition("Organization has not granted support access")
+            }
+            SupportAccessError::Token(e) => e.into(),
+            SupportAccessError::DatabaseError(msg) => tonic::Status::internal(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_reason() {
+        assert_eq!(validate_reason("  Ticket #4521  ").unwrap(), "Ticket #4521");
+        assert!(validate_reason("   ").is_err());
+        assert!(validate_reason(&"x".repeat(MAX_REASON_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_grant_is_active() {
+        let now = Utc::now();
+        let mut grant = SupportAccessGrant {
+            id: "grant1".to_string(),
+            organization_id: "org1".to_string(),
+            granted_by_user_id: "user1".to_string(),
+            reason: "Ticket #4521".to_string(),
+            expires_at: now + Duration::hours(1),
+            revoked_at: None,
+            revoked_by_user_id: None,
+            created_at: now,
+        };
+        assert!(grant.is_active(now));
+        assert!(!grant.is_active(now + Duration::hours(2)));
+
+        grant.revoked_at = Some(now);
+        assert!(!grant.is_active(now));
+    }
+
+    #[test]
+    fn test_error_conversion() {
+        let status: tonic::Status = SupportAccessError::NoActiveGrant.into();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        let status: tonic::Status =
+            SupportAccessError::PermissionDenied("staff only".to_string()).into();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+}
@@ -0,0 +1,586 @@
+//! Trial expiry
+//!
+//! Organizations on a trial are reminded as its end approaches, at the
+//! configured number of days before `trial_ends_at`. A trial that ends
+//! without becoming a paid subscription enters a grace period, and the owner
+//! is told what happens when it is over: the organization either moves to the
+//! free plan, or keeps its plan with protection suspended until it pays. The
+//! gateway suspends and restores the protection of the organization's
+//! backends. Paying at any point ends the grace period or the suspension.
+//!
+//! Every step is a conditional update on the subscription, so replicas that
+//! check at the same time don't remind or act twice.
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use pistonprotection_common::redis::CacheService;
+use sqlx::{FromRow, PgPool};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::config::{TrialConfig, TrialExpiryAction};
+use crate::models::subscription::{Plan, TrialOutcome};
+use crate::models::{AuditActions, AuditLogBuilder, SubscriptionStatus};
+use crate::services::email::{EmailRecipient, EmailService};
+use crate::services::plan_change::upsert_limits;
+use crate::services::{AuditService, StripeService};
+
+/// Time Stripe gets after a trial ends to start the paid subscription, before
+/// the trial counts as expired
+const EXPIRY_SETTLE_SECS: f64 = 3600.0;
+
+/// Shortest time between two checks in seconds
+const MIN_CHECK_INTERVAL_SECS: u64 = 60;
+
+/// Whole days left of a trial, counting a started day as a full one
+pub fn days_remaining(trial_ends_at: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    let seconds = (trial_ends_at - now).num_seconds().max(0);
+    (seconds + 86399) / 86400
+}
+
+/// Reminder due for a trial with `days_left` days left
+///
+/// Returns the reminder threshold reached, if its reminder wasn't sent yet.
+/// Only the closest threshold is sent, so a trial that starts with two days
+/// left gets one reminder instead of the seven and three day ones at once.
+pub fn due_reminder(reminder_days: &[u32], days_left: i64, last_sent: Option<u32>) -> Option<u32> {
+    let reached = reminder_days
+        .iter()
+        .copied()
+        .filter(|&days| i64::from(days) >= days_left)
+        .min()?;
+    match last_sent {
+        Some(sent) if sent <= reached => None,
+        _ => Some(reached),
+    }
+}
+
+/// What the trial expired email says happens once the grace period is over
+fn expiry_consequence(action: TrialExpiryAction) -> &'static str {
+    match action {
+        TrialExpiryAction::Downgrade => "your organization will be moved to the free plan",
+        TrialExpiryAction::Suspend => "DDoS protection for your backends will be suspended",
+    }
+}
+
+/// Date as shown in emails
+fn format_date(date: DateTime<Utc>) -> String {
+    date.format("%B %d, %Y").to_string()
+}
+
+/// Subscription with a trial
+#[derive(Debug, Clone, FromRow)]
+struct TrialSubscription {
+    id: String,
+    organization_id: String,
+    plan_id: String,
+    status: SubscriptionStatus,
+    stripe_subscription_id: Option<String>,
+    trial_ends_at: DateTime<Utc>,
+    trial_reminder_days: Option<i32>,
+    trial_grace_ends_at: Option<DateTime<Utc>>,
+}
+
+/// What one check did
+#[derive(Debug, Default)]
+struct TrialCheckStats {
+    converted: usize,
+    reminded: usize,
+    expired: usize,
+    downgraded: usize,
+    suspended: usize,
+    failed: usize,
+}
+
+impl TrialCheckStats {
+    fn is_empty(&self) -> bool {
+        self.converted
+            + self.reminded
+            + self.expired
+            + self.downgraded
+            + self.suspended
+            + self.failed
+            == 0
+    }
+}
+
+/// Trial expiry scheduler
+pub struct TrialService {
+    db: PgPool,
+    config: TrialConfig,
+    stripe: Arc<StripeService>,
+    email: Arc<EmailService>,
+    audit: AuditService,
+}
+
+impl TrialService {
+    /// Create a new trial service
+    pub fn new(
+        db: PgPool,
+        config: TrialConfig,
+        stripe: Arc<StripeService>,
+        email: Arc<EmailService>,
+    ) -> Self {
+        let audit = AuditService::new(db.clone());
+        Self {
+            db,
+            config,
+            stripe,
+            email,
+            audit,
+        }
+    }
+
+    /// Check trials on schedule, once per interval across all replicas
+    pub async fn run(self, cache: CacheService) {
+        let interval =
+            Duration::from_secs(self.config.check_interval_secs.max(MIN_CHECK_INTERVAL_SECS));
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            ticker.tick().await;
+
+            match cache.set_nx("billing:trials", &true, interval).await {
+                Ok(true) => {}
+                Ok(false) => continue, // Another replica checked this interval
+                Err(e) => {
+                    warn!(error = %e, "Failed to take trial check turn");
+                    continue;
+                }
+            }
+
+            match self.check().await {
+                Ok(stats) if stats.is_empty() => {}
+                Ok(stats) => info!(
+                    converted = stats.converted,
+                    reminded = stats.reminded,
+                    expired = stats.expired,
+                    downgraded = stats.downgraded,
+                    suspended = stats.suspended,
+                    failed = stats.failed,
+                    "Checked trials"
+                ),
+                Err(e) => error!(error = %e, "Failed to check trials"),
+            }
+        }
+    }
+
+    /// Run every step of the trial lifecycle once
+    async fn check(&self) -> Result<TrialCheckStats> {
+        let mut stats = TrialCheckStats::default();
+
+        // Conversions first, so a trial paid for in the meantime is never
+        // acted on
+        self.mark_converted(&mut stats).await?;
+        self.send_reminders(&mut stats).await?;
+        self.expire_trials(&mut stats).await?;
+        self.end_grace_periods(&mut stats).await?;
+
+        Ok(stats)
+    }
+
+    /// Record trials that became paid subscriptions, ending their grace
+    /// period or suspension
+    async fn mark_converted(&self, stats: &mut TrialCheckStats) -> Result<()> {
+        let converted = sqlx::query_as::<_, TrialSubscription>(
+            r#"
+            UPDATE subscriptions
+            SET trial_outcome = 'converted', trial_grace_ends_at = NULL, updated_at = NOW()
+            WHERE trial_ends_at IS NOT NULL AND status = 'active'
+              AND (trial_outcome IS NULL OR trial_outcome IN ('grace', 'suspended'))
+            RETURNING *
+            "#,
+        )
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to mark converted trials")?;
+
+        for sub in converted {
+            info!(organization_id = %sub.organization_id, "Trial converted");
+            self.audit(
+                AuditLogBuilder::new(
+                    &sub.organization_id,
+                    AuditActions::TRIAL_CONVERTED,
+                    "subscription",
+                )
+                .resource(&sub.id)
+                .description("Trial became a paid subscription")
+                .metadata("plan", &sub.plan_id),
+            )
+            .await;
+            stats.converted += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Remind owners of trials that reached a reminder threshold
+    async fn send_reminders(&self, stats: &mut TrialCheckStats) -> Result<()> {
+        let Some(max_days) = self.config.reminder_days.iter().copied().max() else {
+            return Ok(());
+        };
+
+        let trials = sqlx::query_as::<_, TrialSubscription>(
+            r#"
+            SELECT * FROM subscriptions
+            WHERE status = 'trialing' AND trial_outcome IS NULL
+              AND trial_ends_at > NOW()
+              AND trial_ends_at <= NOW() + make_interval(days => $1)
+            "#,
+        )
+        .bind(max_days as i32)
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to fetch trials to remind")?;
+
+        let now = Utc::now();
+        for sub in trials {
+            let days_left = days_remaining(sub.trial_ends_at, now);
+            let last_sent = sub.trial_reminder_days.map(|days| days as u32);
+            let Some(threshold) = due_reminder(&self.config.reminder_days, days_left, last_sent)
+            else {
+                continue;
+            };
+
+            // Claim the reminder before sending it
+            let claimed = sqlx::query(
+                r#"
+                UPDATE subscriptions SET trial_reminder_days = $2, updated_at = NOW()
+                WHERE id = $1 AND (trial_reminder_days IS NULL OR trial_reminder_days > $2)
+                "#,
+            )
+            .bind(&sub.id)
+            .bind(threshold as i32)
+            .execute(&self.db)
+            .await
+            .context("Failed to record trial reminder")?
+            .rows_affected()
+                > 0;
+            if !claimed {
+                continue;
+            }
+
+            let Some(recipient) = self.owner(&sub.organization_id).await else {
+                stats.failed += 1;
+                continue;
+            };
+            match self
+                .email
+                .send_trial_ending_email(
+                    recipient,
+                    days_left as u32,
+                    &format_date(sub.trial_ends_at),
+                )
+                .await
+            {
+                Ok(_) => stats.reminded += 1,
+                Err(e) => {
+                    warn!(organization_id = %sub.organization_id, "Failed to send trial reminder: {}", e);
+                    stats.failed += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start the grace period of trials that ended unpaid
+    async fn expire_trials(&self, stats: &mut TrialCheckStats) -> Result<()> {
+        let expired = sqlx::query_as::<_, TrialSubscription>(
+            r#"
+            UPDATE subscriptions
+            SET trial_outcome = 'grace',
+                trial_grace_ends_at = NOW() + make_interval(days => $1),
+                updated_at = NOW()
+            WHERE trial_outcome IS NULL AND status <> 'active'
+              AND trial_ends_at <= NOW() - make_interval(secs => $2)
+            RETURNING *
+            "#,
+        )
+        .bind(self.config.grace_period_days as i32)
+        .bind(EXPIRY_SETTLE_SECS)
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to expire trials")?;
+
+        for sub in expired {
+            let grace_ends_at = sub.trial_grace_ends_at.unwrap_or_else(Utc::now);
+            info!(
+                organization_id = %sub.organization_id,
+                grace_ends_at = %grace_ends_at,
+                "Trial expired"
+            );
+            self.audit(
+                AuditLogBuilder::new(
+                    &sub.organization_id,
+                    AuditActions::TRIAL_EXPIRED,
+                    "subscription",
+                )
+                .resource(&sub.id)
+                .description("Trial ended without a paid subscription")
+                .metadata("grace_ends_at", &grace_ends_at.to_rfc3339()),
+            )
+            .await;
+            stats.expired += 1;
+
+            let Some(recipient) = self.owner(&sub.organization_id).await else {
+                stats.failed += 1;
+                continue;
+            };
+            if let Err(e) = self
+                .email
+                .send_trial_expired_email(
+                    recipient,
+                    &format_date(sub.trial_ends_at),
+                    &format_date(grace_ends_at),
+                    expiry_consequence(self.config.expiry_action),
+                )
+                .await
+            {
+                warn!(organization_id = %sub.organization_id, "Failed to send trial expired email: {}", e);
+                stats.failed += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Downgrade or suspend organizations whose grace period is over
+    async fn end_grace_periods(&self, stats: &mut TrialCheckStats) -> Result<()> {
+        let ended = sqlx::query_as::<_, TrialSubscription>(
+            r#"
+            SELECT * FROM subscriptions
+            WHERE trial_outcome = 'grace' AND trial_grace_ends_at <= NOW()
+              AND status <> 'active'
+            "#,
+        )
+        .fetch_all(&self.db)
+        .await
+        .context("Failed to fetch ended grace periods")?;
+        if ended.is_empty() {
+            return Ok(());
+        }
+
+        match self.config.expiry_action {
+            TrialExpiryAction::Downgrade => {
+                let free_plan = self.free_plan().await?;
+                for sub in ended {
+                    match self.downgrade(&sub, &free_plan).await {
+                        Ok(true) => stats.downgraded += 1,
+                        Ok(false) => {}
+                        Err(e) => {
+                            warn!(organization_id = %sub.organization_id, "Failed to downgrade expired trial: {:#}", e);
+                            stats.failed += 1;
+                        }
+                    }
+                }
+            }
+            TrialExpiryAction::Suspend => {
+                for sub in ended {
+                    match self.suspend(&sub).await {
+                        Ok(true) => stats.suspended += 1,
+                        Ok(false) => {}
+                        Err(e) => {
+                            warn!(organization_id = %sub.organization_id, "Failed to suspend expired trial: {:#}", e);
+                            stats.failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move an expired trial to the free plan
+    ///
+    /// The Stripe subscription is canceled first. It is then detached from
+    /// the local subscription, so Stripe's cancellation webhook leaves the
+    /// free plan alone. Returns false if the trial was handled elsewhere.
+    async fn downgrade(&self, sub: &TrialSubscription, free_plan: &Plan) -> Result<bool> {
+        if let Some(stripe_id) = &sub.stripe_subscription_id
+            && sub.status != SubscriptionStatus::Canceled
+        {
+            self.stripe
+                .cancel_subscription(stripe_id, false)
+                .await
+                .context("Failed to cancel Stripe subscription")?;
+        }
+
+        let mut tx = self.db.begin().await?;
+        let updated = sqlx::query(
+            r#"
+            UPDATE subscriptions SET
+                plan_id = $1, plan_name = $2, plan_type = $3, billing_period = 'monthly',
+                status = 'active', in_trial = false, stripe_subscription_id = NULL,
+                cancel_at_period_end = false,
+                scheduled_plan_id = NULL, scheduled_billing_period = NULL,
+                scheduled_change_at = NULL,
+                trial_outcome = $4, trial_grace_ends_at = NULL, updated_at = NOW()
+            WHERE id = $5 AND trial_outcome = 'grace'
+            "#,
+        )
+        .bind(&free_plan.id)
+        .bind(&free_plan.name)
+        .bind(free_plan.plan_type)
+        .bind(TrialOutcome::Downgraded)
+        .bind(&sub.id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to downgrade subscription")?
+        .rows_affected();
+        if updated == 0 {
+            return Ok(false);
+        }
+        upsert_limits(&mut *tx, &sub.organization_id, free_plan)
+            .await
+            .context("Failed to update organization limits")?;
+        tx.commit().await?;
+
+        info!(
+            organization_id = %sub.organization_id,
+            from_plan = %sub.plan_id,
+            "Expired trial moved to the free plan"
+        );
+        self.audit(
+            AuditLogBuilder::new(
+                &sub.organization_id,
+                AuditActions::TRIAL_DOWNGRADED,
+                "subscription",
+            )
+            .resource(&sub.id)
+            .description(&format!("Trial expired, moved to {}", free_plan.name))
+            .metadata("from_plan", &sub.plan_id)
+            .metadata("to_plan", &free_plan.id),
+        )
+        .await;
+
+        Ok(true)
+    }
+
+    /// Suspend the protection of an expired trial
+    ///
+    /// Returns false if the trial was handled elsewhere.
+    async fn suspend(&self, sub: &TrialSubscription) -> Result<bool> {
+        let updated = sqlx::query(
+            r#"
+            UPDATE subscriptions SET
+                trial_outcome = $1, trial_grace_ends_at = NULL,
+                protection_suspended_at = NOW(), updated_at = NOW()
+            WHERE id = $2 AND trial_outcome = 'grace'
+            "#,
+        )
+        .bind(TrialOutcome::Suspended)
+        .bind(&sub.id)
+        .execute(&self.db)
+        .await
+        .context("Failed to suspend subscription")?
+        .rows_affected();
+        if updated == 0 {
+            return Ok(false);
+        }
+
+        info!(organization_id = %sub.organization_id, "Protection of expired trial suspended");
+        self.audit(
+            AuditLogBuilder::new(
+                &sub.organization_id,
+                AuditActions::TRIAL_PROTECTION_SUSPENDED,
+                "subscription",
+            )
+            .resource(&sub.id)
+            .description("Trial expired, protection suspended until the subscription is paid"),
+        )
+        .await;
+
+        Ok(true)
+    }
+
+    /// The plan expired trials move to
+    async fn free_plan(&self) -> Result<Plan> {
+        sqlx::query_as::<_, Plan>(
+            r#"
+            SELECT * FROM plans
+            WHERE plan_type = 'free' AND is_active = true
+            ORDER BY price_monthly_cents ASC
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&self.db)
+        .await
+        .context("Failed to fetch free plan")?
+        .ok_or_else(|| anyhow!("No active free plan to move expired trials to"))
+    }
+
+    /// Owner of an organization, who trial emails go to
+    async fn owner(&self, organization_id: &str) -> Option<EmailRecipient> {
+        let owner = sqlx::query_as::<_, (String, Option<String>)>(
+            r#"
+            SELECT u.email, u.name
+            FROM organizations o
+            JOIN users u ON u.id = o.owner_id
+            WHERE o.id = $1
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_optional(&self.db)
+        .await;
+
+        match owner {
+            Ok(Some((email, name))) => Some(EmailRecipient { email, name }),
+            Ok(None) => {
+                warn!(organization_id = %organization_id, "Organization owner not found");
+                None
+            }
+            Err(e) => {
+                warn!(organization_id = %organization_id, "Failed to fetch organization owner: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Write an audit entry, logging instead of failing
+    async fn audit(&self, entry: AuditLogBuilder) {
+        if let Err(e) = self.audit.log_builder(entry).await {
+            warn!("Failed to audit trial change: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as ChronoDuration;
+
+    #[test]
+    fn test_days_remaining() {
+        let now = Utc::now();
+        assert_eq!(days_remaining(now + ChronoDuration::days(3), now), 3);
+        // A started day counts in full
+        assert_eq!(days_remaining(now + ChronoDuration::hours(49), now), 3);
+        assert_eq!(days_remaining(now + ChronoDuration::minutes(5), now), 1);
+        assert_eq!(days_remaining(now - ChronoDuration::days(1), now), 0);
+    }
+
+    #[test]
+    fn test_due_reminder() {
+        let days = [7, 3, 1];
+
+        assert_eq!(due_reminder(&days, 10, None), None);
+        assert_eq!(due_reminder(&days, 7, None), Some(7));
+        assert_eq!(due_reminder(&days, 5, Some(7)), None);
+        assert_eq!(due_reminder(&days, 3, Some(7)), Some(3));
+        assert_eq!(due_reminder(&days, 1, Some(3)), Some(1));
+        assert_eq!(due_reminder(&days, 1, Some(1)), None);
+        // Only the closest threshold of a short trial is sent
+        assert_eq!(due_reminder(&days, 2, None), Some(3));
+        assert_eq!(due_reminder(&[], 1, None), None);
+    }
+
+    #[test]
+    fn test_expiry_consequence() {
+        assert!(expiry_consequence(TrialExpiryAction::Downgrade).contains("free plan"));
+        assert!(expiry_consequence(TrialExpiryAction::Suspend).contains("suspended"));
+    }
+}
@@ -0,0 +1,199 @@
+//! EU VAT of invoices
+//!
+//! Which VAT an invoice carries depends on where the seller and the customer
+//! are:
+//! - a seller outside the EU charges no VAT
+//! - a customer outside the EU is exempt (export of services)
+//! - a business customer in another EU country with a VAT ID pays VAT
+//!   itself (reverse charge)
+//! - everyone else pays the standard rate of their own country
+//!
+//! Prices are gross, so unless Stripe charged tax on top, the VAT is the part
+//! of the total the rate accounts for. The total on the document always
+//! matches what Stripe charged.
+
+/// Standard VAT rates of the EU member states in basis points
+const STANDARD_RATES: &[(&str, u32)] = &[
+    ("AT", 2000),
+    ("BE", 2100),
+    ("BG", 2000),
+    ("CY", 1900),
+    ("CZ", 2100),
+    ("DE", 1900),
+    ("DK", 2500),
+    ("EE", 2400),
+    ("ES", 2100),
+    ("FI", 2550),
+    ("FR", 2000),
+    ("GR", 2400),
+    ("HR", 2500),
+    ("HU", 2700),
+    ("IE", 2300),
+    ("IT", 2200),
+    ("LT", 2100),
+    ("LU", 1700),
+    ("LV", 2100),
+    ("MT", 1800),
+    ("NL", 2100),
+    ("PL", 2300),
+    ("PT", 2300),
+    ("RO", 2100),
+    ("SE", 2500),
+    ("SI", 2200),
+    ("SK", 2300),
+];
+
+/// Standard VAT rate of an EU member state in basis points
+pub fn standard_rate_bps(country: &str) -> Option<u32> {
+    STANDARD_RATES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(country.trim()))
+        .map(|(_, rate)| *rate)
+}
+
+/// How VAT applies to an invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VatTreatment {
+    /// VAT at the rate of the customer's country
+    Standard { rate_bps: u32 },
+    /// The customer accounts for the VAT
+    ReverseCharge,
+    /// No VAT applies
+    Exempt,
+}
+
+/// VAT treatment of an invoice to a customer
+///
+/// A customer whose country is unknown is taken to be in the seller's
+/// country.
+pub fn treatment(
+    seller_country: &str,
+    customer_country: Option<&str>,
+    customer_vat_id: Option<&str>,
+) -> VatTreatment {
+    if standard_rate_bps(seller_country).is_none() {
+        return VatTreatment::Exempt;
+    }
+
+    let customer_country = customer_country
+        .map(str::trim)
+        .filter(|country| !country.is_empty())
+        .unwrap_or(seller_country);
+    let Some(rate_bps) = standard_rate_bps(customer_country) else {
+        return VatTreatment::Exempt;
+    };
+
+    let has_vat_id = customer_vat_id.is_some_and(|id| !id.trim().is_empty());
+    if has_vat_id && !customer_country.eq_ignore_ascii_case(seller_country.trim()) {
+        return VatTreatment::ReverseCharge;
+    }
+
+    VatTreatment::Standard { rate_bps }
+}
+
+/// Amounts of an invoice split into net amount and VAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VatBreakdown {
+    pub net_cents: i64,
+    pub vat_cents: i64,
+    pub total_cents: i64,
+    pub rate_bps: u32,
+    pub reverse_charge: bool,
+}
+
+/// Split the total of an invoice, of which Stripe charged `tax_cents` as tax
+pub fn breakdown(treatment: VatTreatment, total_cents: i64, tax_cents: i64) -> VatBreakdown {
+    let (vat_cents, rate_bps) = match treatment {
+        VatTreatment::Standard { rate_bps } if tax_cents != 0 => (tax_cents, rate_bps),
+        VatTreatment::Standard { rate_bps } => {
+            let vat = total_cents as f64 * rate_bps as f64 / (10_000 + rate_bps) as f64;
+            (vat.round() as i64, rate_bps)
+        }
+        // Tax Stripe charged regardless, e.g. by Stripe Tax
+        _ if tax_cents != 0 => {
+            let net = total_cents - tax_cents;
+            let rate = if net != 0 {
+                (tax_cents as f64 * 10_000.0 / net as f64).round() as u32
+            } else {
+                0
+            };
+            (tax_cents, rate)
+        }
+        _ => (0, 0),
+    };
+
+    VatBreakdown {
+        net_cents: total_cents - vat_cents,
+        vat_cents,
+        total_cents,
+        rate_bps,
+        reverse_charge: treatment == VatTreatment::ReverseCharge && vat_cents == 0,
+    }
+}
+
+/// Rate in basis points as a percentage, e.g. `25.5%`
+pub fn format_rate(rate_bps: u32) -> String {
+    let rate = format!("{}.{:02}", rate_bps / 100, rate_bps % 100);
+    format!("{}%", rate.trim_end_matches('0').trim_end_matches('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_treatment() {
+        // Domestic sale
+        assert_eq!(
+            treatment("DE", Some("DE"), Some("DE123456789")),
+            VatTreatment::Standard { rate_bps: 1900 }
+        );
+        // Consumer in another member state pays their own rate
+        assert_eq!(
+            treatment("DE", Some("fi"), None),
+            VatTreatment::Standard { rate_bps: 2550 }
+        );
+        // Business in another member state
+        assert_eq!(
+            treatment("DE", Some("FR"), Some("FR12345678901")),
+            VatTreatment::ReverseCharge
+        );
+        // Customer outside the EU
+        assert_eq!(treatment("DE", Some("US"), None), VatTreatment::Exempt);
+        // Unknown country falls back to the seller's
+        assert_eq!(
+            treatment("DE", None, None),
+            VatTreatment::Standard { rate_bps: 1900 }
+        );
+        // Seller outside the EU
+        assert_eq!(treatment("US", Some("DE"), None), VatTreatment::Exempt);
+    }
+
+    #[test]
+    fn test_breakdown() {
+        // Gross price of 119.00 at 19%
+        let vat = breakdown(VatTreatment::Standard { rate_bps: 1900 }, 11900, 0);
+        assert_eq!(vat.net_cents, 10000);
+        assert_eq!(vat.vat_cents, 1900);
+        assert_eq!(vat.total_cents, 11900);
+
+        // Tax Stripe charged is kept as is
+        let vat = breakdown(VatTreatment::Standard { rate_bps: 2000 }, 12000, 2000);
+        assert_eq!((vat.net_cents, vat.vat_cents), (10000, 2000));
+
+        let vat = breakdown(VatTreatment::ReverseCharge, 9900, 0);
+        assert_eq!((vat.net_cents, vat.vat_cents), (9900, 0));
+        assert!(vat.reverse_charge);
+
+        let vat = breakdown(VatTreatment::Exempt, 9900, 0);
+        assert_eq!((vat.vat_cents, vat.rate_bps), (0, 0));
+        assert!(!vat.reverse_charge);
+    }
+
+    #[test]
+    fn test_format_rate() {
+        assert_eq!(format_rate(1900), "19%");
+        assert_eq!(format_rate(2550), "25.5%");
+        assert_eq!(format_rate(0), "0%");
+    }
+}
@@ -0,0 +1,77 @@
+//! Backend modes
+//!
+//! A backend can be taken out of protection for debugging: in passthrough
+//! mode workers forward its traffic without filtering, in block-all mode
+//! they drop all of it. The gateway stores a `BackendModeOverride` under the
+//! backend's mode key and adds the backend to the active mode set; a backend
+//! without an override is protected. Overrides carry a revert time, and
+//! workers fall back to protection on their own once it passed, even if the
+//! gateway never removes the override. Both sides use a cache prefix of
+//! `piston`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Set of the backends with a mode override
+pub const ACTIVE_MODES_KEY: &str = "backend_modes:active";
+
+/// Time an override is kept past its revert time, in case the gateway does
+/// not remove it
+pub const MODE_TTL_MARGIN: Duration = Duration::from_secs(3600);
+
+/// Key of the mode override of a backend
+pub fn backend_mode_key(backend_id: &str) -> String {
+    format!("backend_mode:{}", backend_id)
+}
+
+/// How workers treat the traffic of a backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendMode {
+    /// Filter traffic with the configured protection
+    #[default]
+    Protect,
+    /// Forward all traffic without filtering
+    Passthrough,
+    /// Drop all traffic
+    BlockAll,
+}
+
+impl BackendMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BackendMode::Protect => "protect",
+            BackendMode::Passthrough => "passthrough",
+            BackendMode::BlockAll => "block_all",
+        }
+    }
+}
+
+impl fmt::Display for BackendMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Mode a backend is switched to until its revert time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackendModeOverride {
+    pub backend_id: String,
+    pub mode: BackendMode,
+    /// Hostnames of the backend, for the HTTP proxy
+    pub hosts: Vec<String>,
+    pub reason: String,
+    pub changed_by: String,
+    /// Unix seconds
+    pub changed_at: i64,
+    /// Time the backend goes back to protection (Unix seconds)
+    pub revert_at: i64,
+}
+
+impl BackendModeOverride {
+    /// Whether the revert time has passed
+    pub fn expired(&self, now: i64) -> bool {
+        now >= self.revert_at
+    }
+}
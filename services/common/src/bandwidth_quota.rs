@@ -0,0 +1,142 @@
+//! Bandwidth quotas
+//!
+//! Workers count the traffic sent to every backend and report it to the
+//! config manager, which adds it up per UTC calendar month. Once the clean
+//! traffic (received minus dropped) of an organization's backends exceeds
+//! the bandwidth of its plan, the gateway applies the organization's quota
+//! action: notify only, throttle every connection to its backends, or
+//! suspend them until the next month. Throttles are published as a
+//! `BandwidthThrottle` per backend under its throttle key, with the backend
+//! in the throttled set; workers stop applying one once it ended, even if
+//! the gateway never removes it. Both sides use a cache prefix of `piston`.
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Set of the backends throttled for exceeding their bandwidth
+pub const THROTTLED_BACKENDS_KEY: &str = "bandwidth_throttles:active";
+
+/// Time a throttle is kept past its end, in case the gateway does not
+/// remove it
+pub const THROTTLE_TTL_MARGIN: Duration = Duration::from_secs(3600);
+
+/// Key of the bandwidth throttle of a backend
+pub fn bandwidth_throttle_key(backend_id: &str) -> String {
+    format!("bandwidth_throttle:{}", backend_id)
+}
+
+/// Per-connection bandwidth cap of a backend until a time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandwidthThrottle {
+    pub backend_id: String,
+    pub organization_id: String,
+    pub bytes_per_second: u64,
+    /// Unix seconds, the end of the quota period
+    pub until: i64,
+}
+
+impl BandwidthThrottle {
+    /// Whether the throttle has ended
+    pub fn expired(&self, now: i64) -> bool {
+        now >= self.until
+    }
+}
+
+/// What happens once an organization exceeds its bandwidth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuotaAction {
+    #[default]
+    Notify,
+    Throttle,
+    Suspend,
+}
+
+impl QuotaAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaAction::Notify => "notify",
+            QuotaAction::Throttle => "throttle",
+            QuotaAction::Suspend => "suspend",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "notify" => Some(QuotaAction::Notify),
+            "throttle" => Some(QuotaAction::Throttle),
+            "suspend" => Some(QuotaAction::Suspend),
+            _ => None,
+        }
+    }
+}
+
+/// First day of the UTC calendar month containing `now`
+pub fn period_start(now: DateTime<Utc>) -> NaiveDate {
+    now.date_naive().with_day(1).unwrap_or(now.date_naive())
+}
+
+/// Start and end of the quota period starting on `start`
+pub fn period_bounds(start: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let end = start
+        .checked_add_months(chrono::Months::new(1))
+        .unwrap_or(start);
+    let at_midnight = |day: NaiveDate| Utc.from_utc_datetime(&day.and_time(NaiveTime::MIN));
+    (at_midnight(start), at_midnight(end))
+}
+
+/// Usage in percent of `limit`, 0 when there is no limit
+pub fn usage_percent(used: u64, limit: u64) -> u64 {
+    if limit == 0 {
+        return 0;
+    }
+    (u128::from(used) * 100 / u128::from(limit)).min(u128::from(u64::MAX)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_period_bounds() {
+        let now = Utc.with_ymd_and_hms(2026, 12, 31, 23, 59, 59).unwrap();
+        let start = period_start(now);
+        assert_eq!(start, NaiveDate::from_ymd_opt(2026, 12, 1).unwrap());
+
+        let (from, to) = period_bounds(start);
+        assert_eq!(from, Utc.with_ymd_and_hms(2026, 12, 1, 0, 0, 0).unwrap());
+        assert_eq!(to, Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_quota_action_round_trip() {
+        for action in [
+            QuotaAction::Notify,
+            QuotaAction::Throttle,
+            QuotaAction::Suspend,
+        ] {
+            assert_eq!(QuotaAction::parse(action.as_str()), Some(action));
+        }
+        assert_eq!(QuotaAction::parse("block"), None);
+    }
+
+    #[test]
+    fn test_usage_percent() {
+        assert_eq!(usage_percent(50, 200), 25);
+        assert_eq!(usage_percent(300, 200), 150);
+        assert_eq!(usage_percent(u64::MAX, 1), u64::MAX);
+        assert_eq!(usage_percent(10, 0), 0);
+    }
+
+    #[test]
+    fn test_throttle_expired() {
+        let throttle = BandwidthThrottle {
+            backend_id: "backend-1".to_string(),
+            organization_id: "org-1".to_string(),
+            bytes_per_second: 1 << 20,
+            until: 1000,
+        };
+        assert!(!throttle.expired(999));
+        assert!(throttle.expired(1000));
+    }
+}
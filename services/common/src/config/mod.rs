@@ -1,5 +1,12 @@
 //! Configuration management for PistonProtection services
+//!
+//! Credentials may be given as references to Kubernetes Secrets or Vault
+//! (see [`secrets`]), replaced with their values by
+//! [`Config::resolve_secrets`].
 
+pub mod secrets;
+
+use secrets::{LeasedSecret, SecretRef, Secrets};
 use serde::Deserialize;
 use std::env;
 
@@ -133,6 +140,25 @@ pub struct DatabaseConfig {
     /// Enable SSL
     #[serde(default)]
     pub ssl_enabled: bool,
+
+    /// Read replica URLs (`PISTON_DATABASE__REPLICA_URLS`, comma separated)
+    #[serde(default)]
+    pub replica_urls: Vec<String>,
+
+    /// Replication lag above which a replica stops receiving reads, in
+    /// milliseconds
+    #[serde(default = "default_db_max_replica_lag_ms")]
+    pub max_replica_lag_ms: u64,
+
+    /// Interval between replica lag checks in seconds
+    #[serde(default = "default_db_replica_check_interval")]
+    pub replica_check_interval_secs: u64,
+
+    /// Short-lived credentials (`username` and `password`) replacing the
+    /// ones in the URLs, e.g. `vault:database/creds/piston#username`; renewed
+    /// by [`crate::db::spawn_credential_renewal`]
+    #[serde(default)]
+    pub credentials: Option<String>,
 }
 
 fn default_db_pool_size() -> u32 {
@@ -151,6 +177,14 @@ fn default_db_idle_timeout() -> u64 {
     300
 }
 
+fn default_db_max_replica_lag_ms() -> u64 {
+    5000
+}
+
+fn default_db_replica_check_interval() -> u64 {
+    5
+}
+
 /// Redis configuration
 #[derive(Debug, Clone, Deserialize)]
 pub struct RedisConfig {
@@ -169,9 +203,58 @@ pub struct RedisConfig {
     #[serde(default)]
     pub cluster_enabled: bool,
 
-    /// Cluster nodes (if cluster mode enabled)
+    /// Cluster nodes (if cluster mode enabled, `PISTON_REDIS__CLUSTER_NODES`,
+    /// comma separated). Falls back to `url` when empty.
     #[serde(default)]
     pub cluster_nodes: Vec<String>,
+
+    /// Sentinel master name. Setting it switches to Sentinel mode unless
+    /// cluster mode is enabled.
+    #[serde(default)]
+    pub sentinel_master: Option<String>,
+
+    /// Sentinel URLs (`PISTON_REDIS__SENTINEL_NODES`, comma separated).
+    /// Falls back to `url` when empty.
+    #[serde(default)]
+    pub sentinel_nodes: Vec<String>,
+}
+
+/// How the Redis deployment is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedisTopology {
+    /// A single server
+    Standalone,
+    /// Redis Cluster, keys sharded across primaries by hash slot
+    Cluster,
+    /// A primary and replicas monitored by Sentinel
+    Sentinel,
+}
+
+impl RedisConfig {
+    /// Topology selected by this configuration
+    pub fn topology(&self) -> RedisTopology {
+        if self.cluster_enabled {
+            RedisTopology::Cluster
+        } else if self.sentinel_master.is_some() {
+            RedisTopology::Sentinel
+        } else {
+            RedisTopology::Standalone
+        }
+    }
+
+    /// Nodes to connect to first for the selected topology
+    pub fn seed_nodes(&self) -> Vec<String> {
+        let nodes = match self.topology() {
+            RedisTopology::Standalone => &[][..],
+            RedisTopology::Cluster => &self.cluster_nodes[..],
+            RedisTopology::Sentinel => &self.sentinel_nodes[..],
+        };
+        if nodes.is_empty() {
+            vec![self.url.clone()]
+        } else {
+            nodes.to_vec()
+        }
+    }
 }
 
 fn default_redis_pool_size() -> usize {
@@ -296,7 +379,11 @@ impl Config {
             .add_source(
                 config::Environment::with_prefix("PISTON")
                     .separator("__")
-                    .try_parsing(true),
+                    .try_parsing(true)
+                    .list_separator(",")
+                    .with_list_parse_key("database.replica_urls")
+                    .with_list_parse_key("redis.cluster_nodes")
+                    .with_list_parse_key("redis.sentinel_nodes"),
             );
 
         config_builder.build()?.try_deserialize()
@@ -321,6 +408,87 @@ impl Config {
     pub fn http_addr(&self) -> String {
         format!("{}:{}", self.server.host, self.server.http_port)
     }
+
+    /// Replace the settings referring to secrets with the secrets
+    ///
+    /// Returns the leased database credentials when `database.credentials`
+    /// is set, already written into the database URLs.
+    pub async fn resolve_secrets(
+        &mut self,
+        secrets: &Secrets,
+    ) -> crate::error::Result<Option<LeasedSecret>> {
+        let mut leased = None;
+
+        if let Some(database) = &mut self.database {
+            secrets.resolve_in_place(&mut database.url).await?;
+            for url in &mut database.replica_urls {
+                secrets.resolve_in_place(url).await?;
+            }
+            if let Some(credentials) = &database.credentials {
+                let reference = SecretRef::parse(credentials)?.ok_or_else(|| {
+                    crate::error::Error::validation("database.credentials must refer to a secret")
+                })?;
+                let lease = secrets.lease(&reference).await?;
+                let username = lease.value("username")?.expose();
+                let password = lease.value("password")?.expose();
+                database.url = with_credentials(&database.url, username, password);
+                for url in &mut database.replica_urls {
+                    *url = with_credentials(url, username, password);
+                }
+                leased = Some(LeasedSecret { reference, lease });
+            }
+        }
+
+        if let Some(redis) = &mut self.redis {
+            secrets.resolve_in_place(&mut redis.url).await?;
+            for url in redis
+                .cluster_nodes
+                .iter_mut()
+                .chain(redis.sentinel_nodes.iter_mut())
+            {
+                secrets.resolve_in_place(url).await?;
+            }
+        }
+
+        if let Some(auth) = &mut self.auth {
+            secrets.resolve_in_place(&mut auth.jwt_secret).await?;
+            crate::telemetry::register_secret(&auth.jwt_secret);
+        }
+
+        Ok(leased)
+    }
+}
+
+/// `url` with its user and password replaced
+fn with_credentials(url: &str, username: &str, password: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let host = match rest[..authority_end].rfind('@') {
+        Some(at) => &rest[at + 1..],
+        None => rest,
+    };
+    format!(
+        "{}://{}:{}@{}",
+        scheme,
+        encode_userinfo(username),
+        encode_userinfo(password),
+        host
+    )
+}
+
+/// Percent-encode everything but unreserved characters
+fn encode_userinfo(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -334,4 +502,45 @@ mod tests {
         assert_eq!(server.grpc_port, 50051);
         assert_eq!(server.http_port, 8080);
     }
+
+    #[test]
+    fn test_with_credentials() {
+        assert_eq!(
+            with_credentials(
+                "postgres://old:pw@db:5432/piston?sslmode=require",
+                "v-auth",
+                "a/b@c"
+            ),
+            "postgres://v-auth:a%2Fb%40c@db:5432/piston?sslmode=require"
+        );
+        assert_eq!(
+            with_credentials("postgres://db/piston", "v-auth", "pw"),
+            "postgres://v-auth:pw@db/piston"
+        );
+    }
+
+    #[test]
+    fn test_redis_topology() {
+        let mut redis = RedisConfig {
+            url: "redis://cache:6379".to_string(),
+            pool_size: 10,
+            timeout_secs: 5,
+            cluster_enabled: false,
+            cluster_nodes: Vec::new(),
+            sentinel_master: None,
+            sentinel_nodes: Vec::new(),
+        };
+        assert_eq!(redis.topology(), RedisTopology::Standalone);
+        assert_eq!(redis.seed_nodes(), vec!["redis://cache:6379"]);
+
+        redis.sentinel_master = Some("mymaster".to_string());
+        redis.sentinel_nodes = vec!["redis://s1:26379".into(), "redis://s2:26379".into()];
+        assert_eq!(redis.topology(), RedisTopology::Sentinel);
+        assert_eq!(redis.seed_nodes().len(), 2);
+
+        // Cluster mode wins over a leftover sentinel master
+        redis.cluster_enabled = true;
+        assert_eq!(redis.topology(), RedisTopology::Cluster);
+        assert_eq!(redis.seed_nodes(), vec!["redis://cache:6379"]);
+    }
 }
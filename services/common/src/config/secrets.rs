@@ -0,0 +1,760 @@
+//! Secrets providers
+//!
+//! Credentials in the configuration (database and Redis URLs, the JWT
+//! secret, Stripe keys) can be given as references instead of values:
+//! - `k8s:<name>#<key>` or `k8s:<namespace>/<name>#<key>`: a key of a
+//!   Kubernetes Secret, read through the API with the pod's service account
+//!   (which needs `get` on the secret)
+//! - `vault:<path>#<field>`: a field of a HashiCorp Vault secret at
+//!   `VAULT_ADDR` (KV version 1 or 2, or a dynamic secrets engine such as
+//!   `database/creds/<role>`), authenticated with `VAULT_TOKEN` or the
+//!   Kubernetes auth method (`VAULT_K8S_ROLE`)
+//!
+//! Anything else is taken literally. Short-lived credentials come with a
+//! [`Lease`] that [`spawn_renewal`] renews before it expires, reading new
+//! credentials once Vault stops extending it. Every value read from a
+//! provider is registered with [`crate::telemetry::register_secret`] so it is
+//! scrubbed from the logs.
+
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Prefix of Kubernetes Secret references
+pub const KUBERNETES_SCHEME: &str = "k8s:";
+
+/// Prefix of Vault secret references
+pub const VAULT_SCHEME: &str = "vault:";
+
+/// Directory of the service account credentials in a pod
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Upper bound on a single request to a secrets backend
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shortest wait between two renewals of a lease, and between retries of a
+/// failed one
+const MIN_RENEW_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Secret value, redacted in debug output
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The value itself, to hand to the client that needs it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+/// Where a secret is kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    Kubernetes {
+        /// Namespace of the pod when unset
+        namespace: Option<String>,
+        name: String,
+        key: String,
+    },
+    Vault {
+        path: String,
+        field: String,
+    },
+}
+
+impl SecretRef {
+    /// Parse a reference; `None` for a literal value
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        if let Some(rest) = value.strip_prefix(KUBERNETES_SCHEME) {
+            let (secret, key) = split_field(value, rest)?;
+            let (namespace, name) = match secret.split_once('/') {
+                Some((namespace, name)) => (Some(namespace.to_string()), name),
+                None => (None, secret),
+            };
+            if name.is_empty() || namespace.as_deref() == Some("") {
+                return Err(Error::validation(format!(
+                    "Invalid Kubernetes secret reference '{}'",
+                    value
+                )));
+            }
+            return Ok(Some(Self::Kubernetes {
+                namespace,
+                name: name.to_string(),
+                key,
+            }));
+        }
+        if let Some(rest) = value.strip_prefix(VAULT_SCHEME) {
+            let (path, field) = split_field(value, rest)?;
+            let path = path.trim_matches('/');
+            if path.is_empty() {
+                return Err(Error::validation(format!(
+                    "Invalid Vault secret reference '{}'",
+                    value
+                )));
+            }
+            return Ok(Some(Self::Vault {
+                path: path.to_string(),
+                field,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Key of the value within the secret
+    pub fn field(&self) -> &str {
+        match self {
+            Self::Kubernetes { key, .. } => key,
+            Self::Vault { field, .. } => field,
+        }
+    }
+}
+
+/// Split `<location>#<field>`
+fn split_field<'a>(value: &str, rest: &'a str) -> Result<(&'a str, String)> {
+    match rest.rsplit_once('#') {
+        Some((location, field)) if !field.is_empty() => Ok((location, field.to_string())),
+        _ => Err(Error::validation(format!(
+            "Secret reference '{}' needs a #field",
+            value
+        ))),
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kubernetes {
+                namespace: Some(namespace),
+                name,
+                key,
+            } => write!(f, "{}{}/{}#{}", KUBERNETES_SCHEME, namespace, name, key),
+            Self::Kubernetes {
+                namespace: None,
+                name,
+                key,
+            } => write!(f, "{}{}#{}", KUBERNETES_SCHEME, name, key),
+            Self::Vault { path, field } => write!(f, "{}{}#{}", VAULT_SCHEME, path, field),
+        }
+    }
+}
+
+/// Values of a secret and how long they stay valid
+#[derive(Debug, Clone, Default)]
+pub struct Lease {
+    pub values: BTreeMap<String, Secret>,
+    /// Vault lease of a dynamic secret
+    pub lease_id: Option<String>,
+    /// Time left, `None` for secrets that don't expire
+    pub ttl: Option<Duration>,
+    pub renewable: bool,
+}
+
+impl Lease {
+    /// Value of `key` in the secret
+    pub fn value(&self, key: &str) -> Result<&Secret> {
+        self.values
+            .get(key)
+            .ok_or_else(|| Error::not_found("Secret field", key))
+    }
+
+    /// When to renew the lease: two thirds into its TTL
+    pub fn renew_after(&self) -> Option<Duration> {
+        self.ttl.map(|ttl| (ttl * 2 / 3).max(MIN_RENEW_INTERVAL))
+    }
+
+    /// A renewal granted less than half the time left before it, so the
+    /// lease is close to its maximum TTL and new credentials are needed
+    fn nearly_exhausted(&self, renewed: &Lease) -> bool {
+        match (self.ttl, renewed.ttl) {
+            (Some(before), Some(after)) => after < before / 2,
+            _ => false,
+        }
+    }
+}
+
+/// Secret read from a provider with the reference it came from, for
+/// [`spawn_renewal`]
+#[derive(Debug, Clone)]
+pub struct LeasedSecret {
+    pub reference: SecretRef,
+    pub lease: Lease,
+}
+
+/// Backend holding secrets
+#[async_trait]
+pub trait SecretProvider: Send + Sync {
+    /// Read the secret at `reference`, with every field it holds
+    async fn read(&self, reference: &SecretRef) -> Result<Lease>;
+
+    /// Extend a lease; the returned lease carries the granted TTL
+    async fn renew(&self, lease: &Lease) -> Result<Lease> {
+        let _ = lease;
+        Err(Error::validation("Secrets of this provider are not leased"))
+    }
+}
+
+/// HTTP client trusting `ca` (PEM) on top of the system roots
+fn http_client(ca: Option<&[u8]>) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+    if let Some(ca) = ca {
+        let ca = reqwest::Certificate::from_pem(ca)
+            .map_err(|e| Error::Internal(format!("Invalid CA certificate: {}", e)))?;
+        builder = builder.add_root_certificate(ca);
+    }
+    builder
+        .build()
+        .map_err(|e| Error::Internal(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// Service account token of the pod, re-read on every use since projected
+/// tokens are rotated
+fn service_account_token() -> Result<String> {
+    std::fs::read_to_string(Path::new(SERVICE_ACCOUNT_DIR).join("token"))
+        .map(|token| token.trim().to_string())
+        .map_err(|e| Error::Internal(format!("Failed to read service account token: {}", e)))
+}
+
+/// Kubernetes Secrets, read through the API server the pod runs under
+pub struct KubernetesSecrets {
+    client: reqwest::Client,
+    api: String,
+    namespace: String,
+}
+
+impl KubernetesSecrets {
+    /// Client of the cluster the service runs in, `None` outside Kubernetes
+    pub fn in_cluster() -> Result<Option<Self>> {
+        let Ok(host) = std::env::var("KUBERNETES_SERVICE_HOST") else {
+            return Ok(None);
+        };
+        let port = std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+        let dir = Path::new(SERVICE_ACCOUNT_DIR);
+        let ca = std::fs::read(dir.join("ca.crt"))
+            .map_err(|e| Error::Internal(format!("Failed to read cluster CA: {}", e)))?;
+        let namespace = std::fs::read_to_string(dir.join("namespace"))
+            .map(|namespace| namespace.trim().to_string())
+            .unwrap_or_else(|_| "default".to_string());
+
+        // IPv6 service hosts need brackets in the URL
+        let host = if host.contains(':') {
+            format!("[{}]", host)
+        } else {
+            host
+        };
+        Ok(Some(Self {
+            client: http_client(Some(&ca))?,
+            api: format!("https://{}:{}", host, port),
+            namespace,
+        }))
+    }
+}
+
+#[async_trait]
+impl SecretProvider for KubernetesSecrets {
+    async fn read(&self, reference: &SecretRef) -> Result<Lease> {
+        #[derive(Deserialize)]
+        struct KubernetesSecret {
+            #[serde(default)]
+            data: BTreeMap<String, String>,
+        }
+
+        let SecretRef::Kubernetes {
+            namespace, name, ..
+        } = reference
+        else {
+            return Err(Error::validation(format!(
+                "{} is not a Kubernetes secret",
+                reference
+            )));
+        };
+        let namespace = namespace.as_deref().unwrap_or(&self.namespace);
+        let secret: KubernetesSecret = self
+            .client
+            .get(format!(
+                "{}/api/v1/namespaces/{}/secrets/{}",
+                self.api, namespace, name
+            ))
+            .bearer_auth(service_account_token()?)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::external_service("kubernetes", e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| Error::external_service("kubernetes", e.to_string()))?;
+
+        let values = secret
+            .data
+            .into_iter()
+            .map(|(key, value)| {
+                let value = STANDARD
+                    .decode(value)
+                    .ok()
+                    .and_then(|value| String::from_utf8(value).ok())
+                    .ok_or_else(|| {
+                        Error::external_service(
+                            "kubernetes",
+                            format!("Key {} of secret {} is not UTF-8 text", key, name),
+                        )
+                    })?;
+                Ok((key, Secret::new(value)))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Lease {
+            values,
+            ..Default::default()
+        })
+    }
+}
+
+/// How the service logs in to Vault
+enum VaultAuth {
+    Token(Secret),
+    /// Kubernetes auth method, logging in with the service account token
+    Kubernetes {
+        mount: String,
+        role: String,
+    },
+}
+
+/// Vault token and when it must be replaced
+struct VaultToken {
+    token: Secret,
+    refresh_at: Option<Instant>,
+}
+
+/// Secrets of a HashiCorp Vault server
+pub struct VaultSecrets {
+    client: reqwest::Client,
+    addr: String,
+    auth: VaultAuth,
+    token: RwLock<Option<VaultToken>>,
+}
+
+/// Response to a Vault read, renewal or login
+#[derive(Deserialize)]
+struct VaultResponse {
+    #[serde(default)]
+    lease_id: String,
+    #[serde(default)]
+    lease_duration: u64,
+    #[serde(default)]
+    renewable: bool,
+    #[serde(default)]
+    data: Option<serde_json::Map<String, serde_json::Value>>,
+    #[serde(default)]
+    auth: Option<VaultLogin>,
+}
+
+#[derive(Deserialize)]
+struct VaultLogin {
+    client_token: String,
+    #[serde(default)]
+    lease_duration: u64,
+}
+
+impl VaultResponse {
+    fn into_lease(self) -> Lease {
+        let mut data = self.data.unwrap_or_default();
+        // KV version 2 nests the secret under data.data, next to its metadata
+        if let (Some(serde_json::Value::Object(inner)), true) =
+            (data.get("data"), data.contains_key("metadata"))
+        {
+            data = inner.clone();
+        }
+        let values = data
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    serde_json::Value::String(value) => value,
+                    other => other.to_string(),
+                };
+                (key, Secret::new(value))
+            })
+            .collect();
+        Lease {
+            values,
+            lease_id: (!self.lease_id.is_empty()).then_some(self.lease_id),
+            ttl: (self.lease_duration > 0).then(|| Duration::from_secs(self.lease_duration)),
+            renewable: self.renewable,
+        }
+    }
+}
+
+impl VaultSecrets {
+    /// Vault client configured by `VAULT_ADDR`, `VAULT_TOKEN` or
+    /// `VAULT_K8S_ROLE` (and `VAULT_K8S_MOUNT`), and `VAULT_CACERT`; `None`
+    /// when `VAULT_ADDR` is unset
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(addr) = std::env::var("VAULT_ADDR") else {
+            return Ok(None);
+        };
+        let auth = if let Ok(token) = std::env::var("VAULT_TOKEN") {
+            VaultAuth::Token(Secret::new(token))
+        } else if let Ok(role) = std::env::var("VAULT_K8S_ROLE") {
+            VaultAuth::Kubernetes {
+                mount: std::env::var("VAULT_K8S_MOUNT")
+                    .unwrap_or_else(|_| "kubernetes".to_string()),
+                role,
+            }
+        } else {
+            return Err(Error::validation(
+                "VAULT_ADDR is set but neither VAULT_TOKEN nor VAULT_K8S_ROLE",
+            ));
+        };
+        let ca = match std::env::var("VAULT_CACERT") {
+            Ok(path) => Some(std::fs::read(&path).map_err(|e| {
+                Error::Internal(format!("Failed to read Vault CA {}: {}", path, e))
+            })?),
+            Err(_) => None,
+        };
+
+        Ok(Some(Self {
+            client: http_client(ca.as_deref())?,
+            addr: addr.trim_end_matches('/').to_string(),
+            auth,
+            token: RwLock::new(None),
+        }))
+    }
+
+    /// Token for the next request, logging in again once it is due
+    async fn token(&self) -> Result<String> {
+        let (mount, role) = match &self.auth {
+            VaultAuth::Token(token) => return Ok(token.expose().to_string()),
+            VaultAuth::Kubernetes { mount, role } => (mount, role),
+        };
+        if let Some(token) = self.token.read().as_ref()
+            && token.refresh_at.is_none_or(|at| Instant::now() < at)
+        {
+            return Ok(token.token.expose().to_string());
+        }
+
+        let response: VaultResponse = self
+            .send(
+                self.client
+                    .post(format!("{}/v1/auth/{}/login", self.addr, mount))
+                    .json(&serde_json::json!({ "role": role, "jwt": service_account_token()? })),
+            )
+            .await?;
+        let login = response
+            .auth
+            .ok_or_else(|| Error::external_service("vault", "Login returned no token"))?;
+        crate::telemetry::register_secret(&login.client_token);
+        let refresh_at = (login.lease_duration > 0)
+            .then(|| Instant::now() + Duration::from_secs(login.lease_duration) * 2 / 3);
+        *self.token.write() = Some(VaultToken {
+            token: Secret::new(login.client_token.clone()),
+            refresh_at,
+        });
+        Ok(login.client_token)
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<VaultResponse> {
+        request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::external_service("vault", e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| Error::external_service("vault", e.to_string()))
+    }
+}
+
+#[async_trait]
+impl SecretProvider for VaultSecrets {
+    async fn read(&self, reference: &SecretRef) -> Result<Lease> {
+        let SecretRef::Vault { path, .. } = reference else {
+            return Err(Error::validation(format!(
+                "{} is not a Vault secret",
+                reference
+            )));
+        };
+        let token = self.token().await?;
+        let response = self
+            .send(
+                self.client
+                    .get(format!("{}/v1/{}", self.addr, path))
+                    .header("X-Vault-Token", token),
+            )
+            .await?;
+        Ok(response.into_lease())
+    }
+
+    async fn renew(&self, lease: &Lease) -> Result<Lease> {
+        let lease_id = lease
+            .lease_id
+            .as_deref()
+            .ok_or_else(|| Error::validation("Secret has no lease to renew"))?;
+        let increment = lease.ttl.map(|ttl| ttl.as_secs()).unwrap_or_default();
+        let token = self.token().await?;
+        let response = self
+            .send(
+                self.client
+                    .put(format!("{}/v1/sys/leases/renew", self.addr))
+                    .header("X-Vault-Token", token)
+                    .json(&serde_json::json!({ "lease_id": lease_id, "increment": increment })),
+            )
+            .await?;
+        Ok(Lease {
+            values: lease.values.clone(),
+            lease_id: Some(lease_id.to_string()),
+            ttl: (response.lease_duration > 0)
+                .then(|| Duration::from_secs(response.lease_duration)),
+            renewable: response.renewable,
+        })
+    }
+}
+
+/// Secrets providers by reference scheme
+#[derive(Clone, Default)]
+pub struct Secrets {
+    kubernetes: Option<Arc<dyn SecretProvider>>,
+    vault: Option<Arc<dyn SecretProvider>>,
+}
+
+impl Secrets {
+    /// Providers available to the service: Kubernetes when running in a
+    /// cluster, Vault when `VAULT_ADDR` is set
+    pub fn from_env() -> Result<Self> {
+        let mut secrets = Self::default();
+        if let Some(kubernetes) = KubernetesSecrets::in_cluster()? {
+            secrets = secrets.with_kubernetes(kubernetes);
+        }
+        if let Some(vault) = VaultSecrets::from_env()? {
+            secrets = secrets.with_vault(vault);
+        }
+        Ok(secrets)
+    }
+
+    pub fn with_kubernetes(mut self, provider: impl SecretProvider + 'static) -> Self {
+        self.kubernetes = Some(Arc::new(provider));
+        self
+    }
+
+    pub fn with_vault(mut self, provider: impl SecretProvider + 'static) -> Self {
+        self.vault = Some(Arc::new(provider));
+        self
+    }
+
+    fn provider(&self, reference: &SecretRef) -> Result<&Arc<dyn SecretProvider>> {
+        let (provider, name) = match reference {
+            SecretRef::Kubernetes { .. } => (&self.kubernetes, "Kubernetes"),
+            SecretRef::Vault { .. } => (&self.vault, "Vault"),
+        };
+        provider.as_ref().ok_or_else(|| {
+            Error::validation(format!(
+                "{} refers to {}, which is not configured",
+                reference, name
+            ))
+        })
+    }
+
+    /// Read the secret at `reference`
+    pub async fn lease(&self, reference: &SecretRef) -> Result<Lease> {
+        let lease = self.provider(reference)?.read(reference).await?;
+        for value in lease.values.values() {
+            crate::telemetry::register_secret(value.expose());
+        }
+        Ok(lease)
+    }
+
+    /// Extend a lease of the secret at `reference`
+    pub async fn renew(&self, reference: &SecretRef, lease: &Lease) -> Result<Lease> {
+        self.provider(reference)?.renew(lease).await
+    }
+
+    /// Value of a config setting: the secret it refers to, or the setting
+    /// itself if it is not a reference
+    pub async fn resolve(&self, value: &str) -> Result<String> {
+        match SecretRef::parse(value)? {
+            Some(reference) => {
+                let lease = self.lease(&reference).await?;
+                Ok(lease.value(reference.field())?.expose().to_string())
+            }
+            None => Ok(value.to_string()),
+        }
+    }
+
+    /// Replace a setting referring to a secret with the secret
+    pub async fn resolve_in_place(&self, value: &mut String) -> Result<()> {
+        *value = self.resolve(value).await?;
+        Ok(())
+    }
+}
+
+/// Keep a leased secret valid: renew it two thirds into its TTL and, once
+/// it cannot be extended any further, read the secret again and pass the new
+/// values to `on_rotate`
+///
+/// Returns `None` for secrets that don't expire.
+pub fn spawn_renewal<F>(
+    secrets: Arc<Secrets>,
+    secret: LeasedSecret,
+    on_rotate: F,
+) -> Option<JoinHandle<()>>
+where
+    F: Fn(&Lease) + Send + 'static,
+{
+    let LeasedSecret {
+        reference,
+        mut lease,
+    } = secret;
+    let mut wait = lease.renew_after()?;
+
+    Some(tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(wait).await;
+
+            if lease.renewable {
+                match secrets.renew(&reference, &lease).await {
+                    Ok(renewed) if !lease.nearly_exhausted(&renewed) => {
+                        lease = renewed;
+                        wait = lease.renew_after().unwrap_or(MIN_RENEW_INTERVAL);
+                        continue;
+                    }
+                    Ok(_) => info!("Lease of {} is running out, reading it again", reference),
+                    Err(e) => warn!("Failed to renew lease of {}: {}", reference, e),
+                }
+            }
+
+            match secrets.lease(&reference).await {
+                Ok(new) => {
+                    on_rotate(&new);
+                    info!("Rotated credentials of {}", reference);
+                    lease = new;
+                    match lease.renew_after() {
+                        Some(after) => wait = after,
+                        None => break,
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to read {} again: {}", reference, e);
+                    wait = MIN_RENEW_INTERVAL;
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_references() {
+        assert_eq!(SecretRef::parse("postgres://db/piston").unwrap(), None);
+        assert_eq!(
+            SecretRef::parse("k8s:piston/stripe#secret-key").unwrap(),
+            Some(SecretRef::Kubernetes {
+                namespace: Some("piston".to_string()),
+                name: "stripe".to_string(),
+                key: "secret-key".to_string(),
+            })
+        );
+        assert_eq!(
+            SecretRef::parse("vault:/database/creds/auth#username").unwrap(),
+            Some(SecretRef::Vault {
+                path: "database/creds/auth".to_string(),
+                field: "username".to_string(),
+            })
+        );
+        let reference = SecretRef::parse("k8s:redis#url").unwrap().unwrap();
+        assert_eq!(reference.to_string(), "k8s:redis#url");
+
+        assert!(SecretRef::parse("k8s:redis").is_err());
+        assert!(SecretRef::parse("k8s:/redis#url").is_err());
+        assert!(SecretRef::parse("vault:#password").is_err());
+    }
+
+    #[test]
+    fn test_secret_debug_is_redacted() {
+        let secret = Secret::new("hunter22");
+        assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+        assert_eq!(secret.expose(), "hunter22");
+    }
+
+    #[test]
+    fn test_vault_responses() {
+        let dynamic: VaultResponse = serde_json::from_value(serde_json::json!({
+            "lease_id": "database/creds/auth/abc",
+            "lease_duration": 3600,
+            "renewable": true,
+            "data": { "username": "v-auth-1", "password": "p4ss" }
+        }))
+        .unwrap();
+        let lease = dynamic.into_lease();
+        assert_eq!(lease.value("username").unwrap().expose(), "v-auth-1");
+        assert_eq!(lease.lease_id.as_deref(), Some("database/creds/auth/abc"));
+        assert_eq!(lease.renew_after(), Some(Duration::from_secs(2400)));
+
+        let kv2: VaultResponse = serde_json::from_value(serde_json::json!({
+            "data": {
+                "data": { "secret-key": "sk_live_1", "retries": 3 },
+                "metadata": { "version": 4 }
+            }
+        }))
+        .unwrap();
+        let lease = kv2.into_lease();
+        assert_eq!(lease.value("secret-key").unwrap().expose(), "sk_live_1");
+        assert_eq!(lease.value("retries").unwrap().expose(), "3");
+        assert_eq!(lease.ttl, None);
+        assert!(lease.value("metadata").is_err());
+    }
+
+    #[test]
+    fn test_nearly_exhausted() {
+        let lease = |secs| Lease {
+            ttl: Some(Duration::from_secs(secs)),
+            ..Default::default()
+        };
+        assert!(!lease(1200).nearly_exhausted(&lease(3600)));
+        assert!(lease(1200).nearly_exhausted(&lease(300)));
+    }
+
+    struct Static;
+
+    #[async_trait]
+    impl SecretProvider for Static {
+        async fn read(&self, _reference: &SecretRef) -> Result<Lease> {
+            Ok(Lease {
+                values: [("key".to_string(), Secret::new("sk_test_123"))].into(),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let secrets = Secrets::default().with_kubernetes(Static);
+        assert_eq!(
+            secrets.resolve("k8s:stripe#key").await.unwrap(),
+            "sk_test_123"
+        );
+        assert_eq!(secrets.resolve("plain").await.unwrap(), "plain");
+        assert!(secrets.resolve("k8s:stripe#other").await.is_err());
+        // No Vault configured
+        assert!(secrets.resolve("vault:kv/stripe#key").await.is_err());
+    }
+}
@@ -0,0 +1,35 @@
+//! Durations requested through the API
+//!
+//! Timed features (restart modes, exemption tokens, revert timers, grace
+//! periods, ...) take a duration in which 0 selects the feature's default and
+//! anything above its maximum is refused. Each feature keeps its own default,
+//! maximum, unit and error message.
+
+/// A requested duration, `default` when `value` is 0
+///
+/// Returns `None` when the duration exceeds `max`.
+pub fn bounded_duration<T>(value: T, default: T, max: T) -> Option<T>
+where
+    T: Copy + Default + PartialOrd,
+{
+    let value = if value == T::default() {
+        default
+    } else {
+        value
+    };
+    (value <= max).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_duration() {
+        assert_eq!(bounded_duration(0u32, 60, 3600), Some(60));
+        assert_eq!(bounded_duration(300u32, 60, 3600), Some(300));
+        assert_eq!(bounded_duration(3600u32, 60, 3600), Some(3600));
+        assert_eq!(bounded_duration(3601u32, 60, 3600), None);
+        assert_eq!(bounded_duration(u64::MAX, 60, 3600), None);
+    }
+}
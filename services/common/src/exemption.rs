@@ -0,0 +1,110 @@
+//! Exemption tokens
+//!
+//! Customers running a load test against a protected backend exempt their
+//! tester addresses for its length: workers whitelist the addresses in the
+//! layer 4 XDP programs, and flag the backend's traffic so the test is kept
+//! out of the attack detection baselines. The gateway stores an
+//! `ExemptionToken` under the token's key and adds its ID to the active
+//! exemption set. Workers stop applying it on their own once it expired,
+//! even if the gateway never removes it. Both sides use a cache prefix of
+//! `piston`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Set of the IDs of active exemption tokens
+pub const ACTIVE_EXEMPTIONS_KEY: &str = "exemption_tokens:active";
+
+/// Time a token is kept past its expiry, in case the gateway does not
+/// remove it
+pub const EXEMPTION_TTL_MARGIN: Duration = Duration::from_secs(3600);
+
+/// Key of an exemption token
+pub fn exemption_token_key(token_id: &str) -> String {
+    format!("exemption_token:{}", token_id)
+}
+
+/// Tester addresses of a backend exempted until a time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExemptionToken {
+    pub id: String,
+    pub backend_id: String,
+    pub ips: Vec<IpAddr>,
+    pub reason: String,
+    pub created_by: String,
+    /// Unix seconds
+    pub created_at: i64,
+    /// Unix seconds
+    pub expires_at: i64,
+}
+
+impl ExemptionToken {
+    /// Whether the token has expired
+    pub fn expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// Addresses exempted by the tokens not expired at `now`
+pub fn exempt_ips(tokens: &[ExemptionToken], now: i64) -> BTreeSet<IpAddr> {
+    tokens
+        .iter()
+        .filter(|token| !token.expired(now))
+        .flat_map(|token| token.ips.iter().copied())
+        .collect()
+}
+
+/// Backends with a token not expired at `now`, whose traffic is kept out of
+/// the attack detection baselines
+pub fn exempt_backends(tokens: &[ExemptionToken], now: i64) -> BTreeSet<String> {
+    tokens
+        .iter()
+        .filter(|token| !token.expired(now))
+        .map(|token| token.backend_id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(id: &str, backend_id: &str, ips: &[&str], expires_at: i64) -> ExemptionToken {
+        ExemptionToken {
+            id: id.to_string(),
+            backend_id: backend_id.to_string(),
+            ips: ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+            reason: String::new(),
+            created_by: "user-1".to_string(),
+            created_at: 1000,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_expired() {
+        let token = token("t1", "backend-1", &["203.0.113.7"], 1600);
+        assert!(!token.expired(1599));
+        assert!(token.expired(1600));
+    }
+
+    #[test]
+    fn test_exempt_ips_skip_expired_tokens() {
+        let tokens = vec![
+            token("t1", "backend-1", &["203.0.113.7", "2001:db8::1"], 1600),
+            token("t2", "backend-1", &["203.0.113.7"], 2000),
+            token("t3", "backend-2", &["198.51.100.1"], 1200),
+        ];
+
+        let ips = exempt_ips(&tokens, 1500);
+        assert_eq!(ips.len(), 2);
+        assert!(ips.contains(&"2001:db8::1".parse().unwrap()));
+
+        assert_eq!(
+            exempt_backends(&tokens, 1500),
+            BTreeSet::from(["backend-1".to_string()])
+        );
+        assert!(exempt_backends(&tokens, 2000).is_empty());
+    }
+}
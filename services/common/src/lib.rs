@@ -5,14 +5,25 @@
 // Allow large error variants - the Error enum includes tonic::Status which is large
 #![allow(clippy::result_large_err)]
 
+pub mod backend_mode;
+pub mod bandwidth_quota;
 pub mod config;
 pub mod db;
+pub mod duration;
 pub mod error;
+pub mod exemption;
 pub mod geoip;
 pub mod metrics;
+pub mod origin_switch;
+pub mod pagination;
+pub mod probe;
+pub mod rate_profile;
 pub mod ratelimit;
 pub mod redis;
+pub mod resilience;
+pub mod restart_mode;
 pub mod scoring;
+pub mod signing;
 pub mod telemetry;
 
 pub use config::Config;
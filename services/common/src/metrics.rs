@@ -1,9 +1,14 @@
 //! Prometheus metrics utilities
 
+use parking_lot::Mutex;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::{Bucket, Histogram, LabelPair, Metric, MetricFamily, MetricType};
 use prometheus::{
     CounterVec, Encoder, GaugeVec, HistogramVec, TextEncoder, register_counter_vec,
     register_gauge_vec, register_histogram_vec,
 };
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 lazy_static::lazy_static! {
     /// gRPC request counter
@@ -43,6 +48,44 @@ lazy_static::lazy_static! {
         vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
     ).unwrap();
 
+    /// Replication lag of each read replica
+    pub static ref DB_REPLICA_LAG_SECONDS: GaugeVec = register_gauge_vec!(
+        "db_replica_lag_seconds",
+        "Replication lag of a database read replica in seconds",
+        &["replica"]
+    ).unwrap();
+
+    /// Whether each read replica receives read-only queries (1) or not (0)
+    pub static ref DB_REPLICA_IN_ROTATION: GaugeVec = register_gauge_vec!(
+        "db_replica_in_rotation",
+        "Whether a database read replica receives read-only queries",
+        &["replica"]
+    ).unwrap();
+
+    /// Circuit breaker state of each gRPC dependency: 0 closed, 1 half-open,
+    /// 2 open
+    pub static ref GRPC_CLIENT_CIRCUIT_STATE: GaugeVec = register_gauge_vec!(
+        "grpc_client_circuit_state",
+        "Circuit breaker state of a gRPC dependency (0 closed, 1 half-open, 2 open)",
+        &["target"]
+    ).unwrap();
+
+    /// gRPC client calls by outcome: ok, failed, retried, rejected (breaker
+    /// open) or budget_exhausted (retry withheld)
+    pub static ref GRPC_CLIENT_CALLS_TOTAL: CounterVec = register_counter_vec!(
+        "grpc_client_calls_total",
+        "gRPC client calls to other services by outcome",
+        &["target", "outcome"]
+    ).unwrap();
+
+    /// API quota decisions by scope, plan and decision (allowed, limited,
+    /// or unchecked when the counters were unreachable)
+    pub static ref API_QUOTA_DECISIONS_TOTAL: CounterVec = register_counter_vec!(
+        "api_quota_decisions_total",
+        "API rate limit decisions by scope, plan and decision",
+        &["scope", "plan", "decision"]
+    ).unwrap();
+
     /// Cache operations counter
     pub static ref CACHE_OPERATIONS_TOTAL: CounterVec = register_counter_vec!(
         "cache_operations_total",
@@ -71,12 +114,359 @@ lazy_static::lazy_static! {
         &["backend_id", "attack_type"]
     ).unwrap();
 
+    /// XDP drops by destination port
+    pub static ref XDP_DROPS_BY_DST_PORT: CounterVec = register_counter_vec!(
+        "xdp_drops_by_dst_port_total",
+        "Packets dropped by XDP programs, by destination port",
+        &["program", "protocol", "port"]
+    ).unwrap();
+
+    /// XDP drops by block reason
+    pub static ref XDP_DROPS_BY_REASON: CounterVec = register_counter_vec!(
+        "xdp_drops_by_reason_total",
+        "Packets dropped by XDP programs, by block reason",
+        &["program", "reason"]
+    ).unwrap();
+
+    /// Packets sampled by XDP programs
+    pub static ref XDP_SAMPLED_PACKETS: CounterVec = register_counter_vec!(
+        "xdp_sampled_packets_total",
+        "Packets sampled into userspace by XDP programs",
+        &["program", "protocol"]
+    ).unwrap();
+
+    /// Passed packets extrapolated from samples
+    pub static ref XDP_ESTIMATED_PACKETS: CounterVec = register_counter_vec!(
+        "xdp_estimated_packets_total",
+        "Passed packets estimated from sampling (samples x sample rate)",
+        &["program", "protocol"]
+    ).unwrap();
+
+    /// Passed bytes extrapolated from samples
+    pub static ref XDP_ESTIMATED_BYTES: CounterVec = register_counter_vec!(
+        "xdp_estimated_bytes_total",
+        "Passed bytes estimated from sampling (sampled bytes x sample rate)",
+        &["program", "protocol"]
+    ).unwrap();
+
+    /// UDP session affinity table entries
+    pub static ref UDP_AFFINITY_ENTRIES: GaugeVec = register_gauge_vec!(
+        "udp_affinity_entries",
+        "UDP sessions pinned to an origin, by backend",
+        &["backend_id"]
+    ).unwrap();
+
+    /// UDP session affinity lookups
+    pub static ref UDP_AFFINITY_LOOKUPS: CounterVec = register_counter_vec!(
+        "udp_affinity_lookups_total",
+        "UDP session affinity lookups, by result (hit, miss, hash_fallback)",
+        &["result"]
+    ).unwrap();
+
+    /// Origin connection pool connections
+    pub static ref ORIGIN_POOL_CONNECTIONS: GaugeVec = register_gauge_vec!(
+        "origin_pool_connections",
+        "Proxied connections per origin pool, by state (active, pending)",
+        &["backend_id", "origin_id", "state"]
+    ).unwrap();
+
+    /// Origin connection pool utilization
+    pub static ref ORIGIN_POOL_UTILIZATION: GaugeVec = register_gauge_vec!(
+        "origin_pool_utilization",
+        "Share of the connection slots of an origin pool in use (0-1)",
+        &["backend_id", "origin_id"]
+    ).unwrap();
+
+    /// Connections shed by origin connection pools
+    pub static ref ORIGIN_POOL_SHED: CounterVec = register_counter_vec!(
+        "origin_pool_shed_total",
+        "Connections shed because their origin was saturated, by reason",
+        &["backend_id", "reason"]
+    ).unwrap();
+
+    /// HTTP proxy cache requests
+    pub static ref HTTP_CACHE_REQUESTS: CounterVec = register_counter_vec!(
+        "http_cache_requests_total",
+        "HTTP proxy requests by cache result (hit, miss, stale, stale_if_error, revalidated, bypass)",
+        &["result"]
+    ).unwrap();
+
+    /// HTTP proxy cache size
+    pub static ref HTTP_CACHE_BYTES: GaugeVec = register_gauge_vec!(
+        "http_cache_bytes",
+        "Bytes held by the HTTP proxy cache, by tier (memory, disk)",
+        &["tier"]
+    ).unwrap();
+
+    /// Open WebSocket connections relayed by the HTTP proxy
+    pub static ref WEBSOCKET_ACTIVE_CONNECTIONS: GaugeVec = register_gauge_vec!(
+        "websocket_active_connections",
+        "Open WebSocket connections relayed by the HTTP proxy",
+        &["backend_id"]
+    ).unwrap();
+
+    /// Closed WebSocket connections by close reason
+    pub static ref WEBSOCKET_CLOSED: CounterVec = register_counter_vec!(
+        "websocket_closed_total",
+        "Closed WebSocket connections by close reason",
+        &["backend_id", "reason"]
+    ).unwrap();
+
+    /// Share of failed origin requests in the last anomaly detection window
+    pub static ref ORIGIN_ERROR_RATE: GaugeVec = register_gauge_vec!(
+        "origin_error_rate",
+        "Share of origin requests answered with 5xx or failing to connect",
+        &["backend_id"]
+    ).unwrap();
+
+    /// Active layer 7 mitigations started by origin anomalies
+    pub static ref L7_MITIGATION_ACTIVE: GaugeVec = register_gauge_vec!(
+        "l7_mitigation_active",
+        "Active layer 7 mitigations by mode (strict_limits, challenge)",
+        &["backend_id", "mode"]
+    ).unwrap();
+
+    /// Requests handled under a layer 7 mitigation
+    pub static ref L7_MITIGATED_REQUESTS: CounterVec = register_counter_vec!(
+        "l7_mitigated_requests_total",
+        "Requests handled under a layer 7 mitigation by action",
+        &["backend_id", "action"]
+    ).unwrap();
+
+    /// Whether the last synthetic probe of a region reached the frontend
+    pub static ref PROBE_UP: GaugeVec = register_gauge_vec!(
+        "probe_up",
+        "Whether the last synthetic probe from a region reached the frontend (1) or not (0)",
+        &["backend_id", "region"]
+    ).unwrap();
+
+    /// Latency of the last successful synthetic probe of a region
+    pub static ref PROBE_LATENCY: GaugeVec = register_gauge_vec!(
+        "probe_latency_ms",
+        "Latency of the last successful synthetic probe from a region in milliseconds",
+        &["backend_id", "region"]
+    ).unwrap();
+
+    /// Backends whose protection endpoints are degraded across regions
+    pub static ref PROBE_DEGRADED: GaugeVec = register_gauge_vec!(
+        "probe_degraded",
+        "Whether synthetic probes find the protection endpoint degraded (1) or not (0)",
+        &["backend_id"]
+    ).unwrap();
+
+    /// Rate limit profile applied to a backend
+    pub static ref RATE_PROFILE_ACTIVE: GaugeVec = register_gauge_vec!(
+        "rate_profile_active",
+        "Rate limit profile applied to a backend (1 for the active one)",
+        &["backend_id", "profile", "source"]
+    ).unwrap();
+
+    /// Backends in restart mode
+    pub static ref RESTART_MODE_ACTIVE: GaugeVec = register_gauge_vec!(
+        "restart_mode_active",
+        "Connection multiplier of a backend in restart mode",
+        &["backend_id"]
+    ).unwrap();
+
+    /// Load test exemption tokens applied by a worker
+    pub static ref EXEMPTION_TOKEN_ACTIVE: GaugeVec = register_gauge_vec!(
+        "exemption_token_active",
+        "Addresses exempted by an active load test exemption token",
+        &["backend_id", "token_id"]
+    ).unwrap();
+
+    /// CGNAT ranges with relaxed per-IP limits
+    pub static ref CGNAT_RANGES: GaugeVec = register_gauge_vec!(
+        "cgnat_ranges",
+        "CGNAT ranges whose per-IP limits are multiplied, by origin (configured or detected)",
+        &["origin"]
+    ).unwrap();
+
     /// Protection level gauge
     pub static ref PROTECTION_LEVEL: GaugeVec = register_gauge_vec!(
         "protection_level",
         "Current protection level (0-5)",
         &["backend_id"]
     ).unwrap();
+
+    /// Entries in use per eBPF map
+    pub static ref EBPF_MAP_ENTRIES: GaugeVec = register_gauge_vec!(
+        "ebpf_map_entries",
+        "Entries in use in an eBPF map",
+        &["program", "map"]
+    ).unwrap();
+
+    /// Capacity of each eBPF map
+    pub static ref EBPF_MAP_MAX_ENTRIES: GaugeVec = register_gauge_vec!(
+        "ebpf_map_max_entries",
+        "Maximum entries of an eBPF map",
+        &["program", "map"]
+    ).unwrap();
+
+    /// Estimated kernel memory of each eBPF map
+    pub static ref EBPF_MAP_MEMORY_BYTES: GaugeVec = register_gauge_vec!(
+        "ebpf_map_memory_bytes",
+        "Estimated kernel memory of an eBPF map at full capacity",
+        &["program", "map"]
+    ).unwrap();
+
+    /// Per-packet processing time of XDP programs, from in-kernel histograms
+    pub static ref XDP_PROCESSING_SECONDS: ExternalHistogramVec = ExternalHistogramVec::register(
+        "xdp_processing_seconds",
+        "Processing time of sampled packets in XDP programs, by protection level",
+        &["program", "protection_level"]
+    ).unwrap();
+
+    /// Listen and SYN queue events counted by the kernel of worker nodes
+    pub static ref KERNEL_TCP_EVENTS: CounterVec = register_counter_vec!(
+        "kernel_tcp_events_total",
+        "Listen and SYN queue events from /proc/net/netstat",
+        &["event"]
+    ).unwrap();
+
+    /// Half-open connections and accept queues of worker nodes
+    pub static ref KERNEL_TCP_SOCKETS: GaugeVec = register_gauge_vec!(
+        "kernel_tcp_sockets",
+        "Half-open connections, listeners and queued connections in the kernel",
+        &["kind"]
+    ).unwrap();
+
+    /// SYN flood verdict from XDP and kernel counters
+    pub static ref SYN_FLOOD_STATE: GaugeVec = register_gauge_vec!(
+        "syn_flood_state",
+        "SYN flood verdict of the node (1 for the current verdict)",
+        &["verdict"]
+    ).unwrap();
+
+    /// Enforcement backend of worker nodes (XDP or the nftables fallback)
+    pub static ref ENFORCEMENT_BACKEND: GaugeVec = register_gauge_vec!(
+        "enforcement_backend",
+        "Enforcement backend of the worker (1 for the active one)",
+        &["backend"]
+    ).unwrap();
+
+    /// Elements programmed into the nftables fallback table
+    pub static ref NFT_SET_ELEMENTS: GaugeVec = register_gauge_vec!(
+        "nft_set_elements",
+        "Elements in the nftables fallback sets",
+        &["set"]
+    ).unwrap();
+
+    /// Per-packet drop events read from the XDP programs
+    pub static ref DROP_EVENTS_TOTAL: CounterVec = register_counter_vec!(
+        "xdp_drop_events_total",
+        "Drop events read from the XDP programs, aggregated or shed",
+        &["outcome"]
+    ).unwrap();
+
+    /// Summaries flushed by the drop event aggregator
+    pub static ref DROP_EVENT_SUMMARIES_TOTAL: CounterVec = register_counter_vec!(
+        "xdp_drop_event_summaries_total",
+        "Drop summaries per source and reason flushed by the worker",
+        &["reason"]
+    ).unwrap();
+
+    /// Events the XDP programs submitted or suppressed through their event
+    /// budget, since each program was loaded
+    pub static ref XDP_EVENT_BUDGET_EVENTS: GaugeVec = register_gauge_vec!(
+        "xdp_event_budget_events",
+        "Drop events and samples emitted or suppressed by the in-kernel event budget",
+        &["program", "priority", "outcome"]
+    ).unwrap();
+}
+
+/// Histograms whose buckets are counted elsewhere (e.g. in eBPF maps)
+///
+/// `HistogramVec` only takes single observations; this exports bucket
+/// counts, count and sum as last set, so counts kept by the kernel can be
+/// scraped without replaying every observation.
+#[derive(Clone)]
+pub struct ExternalHistogramVec {
+    desc: Arc<Desc>,
+    label_names: Arc<Vec<String>>,
+    histograms: Arc<Mutex<BTreeMap<Vec<String>, Histogram>>>,
+}
+
+impl ExternalHistogramVec {
+    /// Create the histogram vector and register it with the default registry
+    pub fn register(name: &str, help: &str, label_names: &[&str]) -> prometheus::Result<Self> {
+        let label_names: Vec<String> = label_names.iter().map(|l| l.to_string()).collect();
+        let vec = Self {
+            desc: Arc::new(Desc::new(
+                name.to_string(),
+                help.to_string(),
+                label_names.clone(),
+                Default::default(),
+            )?),
+            label_names: Arc::new(label_names),
+            histograms: Arc::new(Mutex::new(BTreeMap::new())),
+        };
+        prometheus::register(Box::new(vec.clone()))?;
+        Ok(vec)
+    }
+
+    /// Replace the histogram of a label set
+    ///
+    /// `buckets` holds `(upper bound, count)` pairs in increasing order of
+    /// bound, with per-bucket (not cumulative) counts; the count of the
+    /// implicit `+Inf` bucket is `count`.
+    pub fn set(&self, label_values: &[&str], buckets: &[(f64, u64)], count: u64, sum: f64) {
+        let mut histogram = Histogram::default();
+        let mut cumulative = 0;
+        let buckets = buckets
+            .iter()
+            .map(|&(upper_bound, bucket_count)| {
+                cumulative += bucket_count;
+                let mut bucket = Bucket::default();
+                bucket.set_upper_bound(upper_bound);
+                bucket.set_cumulative_count(cumulative);
+                bucket
+            })
+            .collect();
+        histogram.set_bucket(buckets);
+        histogram.set_sample_count(count);
+        histogram.set_sample_sum(sum);
+
+        let key = label_values.iter().map(|v| v.to_string()).collect();
+        self.histograms.lock().insert(key, histogram);
+    }
+}
+
+impl Collector for ExternalHistogramVec {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let metrics = self
+            .histograms
+            .lock()
+            .iter()
+            .map(|(values, histogram)| {
+                let labels = self
+                    .label_names
+                    .iter()
+                    .zip(values)
+                    .map(|(name, value)| {
+                        let mut pair = LabelPair::default();
+                        pair.set_name(name.clone());
+                        pair.set_value(value.clone());
+                        pair
+                    })
+                    .collect();
+                let mut metric = Metric::from_label(labels);
+                metric.set_histogram(histogram.clone());
+                metric
+            })
+            .collect();
+
+        let mut family = MetricFamily::default();
+        family.set_name(self.desc.fq_name.clone());
+        family.set_help(self.desc.help.clone());
+        family.set_field_type(MetricType::HISTOGRAM);
+        family.set_metric(metrics);
+        vec![family]
+    }
 }
 
 /// Encode all metrics as Prometheus text format
@@ -145,4 +535,25 @@ mod tests {
         let output = encode_metrics();
         assert!(output.contains("grpc_requests_total"));
     }
+
+    #[test]
+    fn test_external_histogram_encode() {
+        XDP_PROCESSING_SECONDS.set(
+            &["xdp_test", "2"],
+            &[(0.000_000_064, 3), (0.000_000_128, 5)],
+            10,
+            0.000_001,
+        );
+
+        let output = encode_metrics();
+        assert!(output.contains(
+            r#"xdp_processing_seconds_bucket{program="xdp_test",protection_level="2",le="0.000000128"} 8"#
+        ));
+        assert!(output.contains(
+            r#"xdp_processing_seconds_bucket{program="xdp_test",protection_level="2",le="+Inf"} 10"#
+        ));
+        assert!(output.contains(
+            r#"xdp_processing_seconds_count{program="xdp_test",protection_level="2"} 10"#
+        ));
+    }
 }
@@ -0,0 +1,71 @@
+//! Origin switches
+//!
+//! Moving a backend to a new origin must not drop players. The gateway
+//! stores an `OriginSwitchPlan` under the backend's plan key and adds the
+//! backend to the active switch set. Workers stop routing new connections
+//! to the old origin, and report the connections still open to it in a
+//! `DrainReport` under their report key until the plan is removed. The
+//! gateway completes the switch once every worker drained or the grace
+//! period ended. Both sides use a cache prefix of `piston`.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Cache prefix of the switch keys
+pub const SWITCH_CACHE_PREFIX: &str = "piston";
+
+/// Set of the backends with a switch in progress
+pub const ACTIVE_SWITCHES_KEY: &str = "origin_switches:active";
+
+/// Time a drain report is kept; workers refresh theirs every few seconds
+pub const DRAIN_REPORT_TTL: Duration = Duration::from_secs(30);
+
+/// Time a plan is kept past the end of its grace period, in case the
+/// gateway does not complete it
+pub const PLAN_TTL_MARGIN: Duration = Duration::from_secs(3600);
+
+/// Key of the switch plan of a backend
+pub fn switch_plan_key(backend_id: &str) -> String {
+    format!("origin_switch:{}", backend_id)
+}
+
+/// Set of the workers reporting on a switch
+pub fn switch_workers_key(switch_id: &str) -> String {
+    format!("origin_switch:{}:workers", switch_id)
+}
+
+/// Key of the drain report of a worker
+pub fn drain_report_key(switch_id: &str, worker_id: &str) -> String {
+    format!("origin_switch:{}:worker:{}", switch_id, worker_id)
+}
+
+/// Switch of a backend from one origin to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OriginSwitchPlan {
+    pub id: String,
+    pub backend_id: String,
+    pub from_origin_id: String,
+    /// Address and port of the old origin, to count connections to it
+    pub from_address: String,
+    pub from_port: u16,
+    pub to_origin_id: String,
+    /// End of the grace period (Unix seconds)
+    pub drain_until: i64,
+}
+
+impl OriginSwitchPlan {
+    /// Whether the grace period has ended
+    pub fn expired(&self, now: i64) -> bool {
+        now >= self.drain_until
+    }
+}
+
+/// Connections a worker still has open to the old origin
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DrainReport {
+    pub switch_id: String,
+    pub worker_id: String,
+    pub active_connections: u64,
+    /// Unix seconds
+    pub reported_at: i64,
+}
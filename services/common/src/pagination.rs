@@ -0,0 +1,947 @@
+//! Cursor pagination, sorting and filtering for list APIs
+//!
+//! Each list API describes the fields clients may sort and filter on with a
+//! [`ListSpec`]. A [`PageRequest`] is parsed from the request's
+//! [`Pagination`] against that spec:
+//!
+//! - `page_size` defaults to 50 and is capped at 100
+//! - `order_by` names one sortable field, prefixed with `-` for descending
+//!   order; the spec's default order applies when it is empty
+//! - `filter` is a conjunction of comparisons such as
+//!   `name ~ "api" AND created_at >= 2024-01-01T00:00:00Z`, with the
+//!   operators `=`, `!=`, `<`, `<=`, `>`, `>=` and `~` (contains, text only)
+//! - `cursor` is the `next_cursor` of the previous page
+//!
+//! Pages are keyset paginated on the sort field with the row ID breaking
+//! ties, so rows created or deleted between requests do not shift later
+//! pages. A cursor is only accepted with the `order_by` and `filter` it was
+//! issued for. Requests without a cursor but with `page` > 1 are served by
+//! offset for older clients.
+//!
+//! Database lists add [`PageRequest::select_key`] to their select list and
+//! push [`PageRequest::push_filter`] and [`PageRequest::push_page`] after
+//! their own conditions. Lists held in memory use [`PageRequest::apply`].
+
+use crate::{Error, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, SecondsFormat, Utc};
+use pistonprotection_proto::{Pagination, PaginationInfo};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgRow;
+use sqlx::{Postgres, QueryBuilder, Row};
+use std::cmp::Ordering;
+
+/// Page size when the request does not set one
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Largest page size served
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// Most comparisons accepted in one filter
+pub const MAX_FILTER_CONDITIONS: usize = 16;
+
+/// Type of a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    Integer,
+    Boolean,
+    Timestamp,
+}
+
+impl FieldKind {
+    fn sql_type(self) -> &'static str {
+        match self {
+            Self::Text => "TEXT",
+            Self::Integer => "BIGINT",
+            Self::Boolean => "BOOLEAN",
+            Self::Timestamp => "TIMESTAMPTZ",
+        }
+    }
+}
+
+/// A field clients may filter, and possibly sort, on
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    /// Name used in `order_by` and `filter`
+    pub name: &'static str,
+    /// SQL expression of the field. Sortable fields must not be NULL; wrap
+    /// nullable columns in `COALESCE`.
+    pub column: &'static str,
+    pub kind: FieldKind,
+    pub sortable: bool,
+}
+
+impl Field {
+    /// A field that can be sorted and filtered on
+    pub const fn sortable(name: &'static str, column: &'static str, kind: FieldKind) -> Self {
+        Self {
+            name,
+            column,
+            kind,
+            sortable: true,
+        }
+    }
+
+    /// A field that can only be filtered on
+    pub const fn filterable(name: &'static str, column: &'static str, kind: FieldKind) -> Self {
+        Self {
+            name,
+            column,
+            kind,
+            sortable: false,
+        }
+    }
+
+    /// SQL expression cast to the field's type
+    fn typed_column(&self) -> String {
+        format!("({})::{}", self.column, self.kind.sql_type())
+    }
+}
+
+/// Fields of a list API
+#[derive(Debug)]
+pub struct ListSpec {
+    pub fields: &'static [Field],
+    /// SQL expression of the unique row ID breaking sort ties
+    pub id_column: &'static str,
+    /// Order used when the request does not set one, e.g. `-created_at`
+    pub default_order: &'static str,
+}
+
+impl ListSpec {
+    fn field(&self, name: &str) -> Option<&'static Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Check the spec itself: unique field names, a sortable default order
+    /// and identifier-like names that the filter syntax can express
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        for (i, field) in self.fields.iter().enumerate() {
+            let valid_name = field.name.starts_with(|c: char| c.is_ascii_lowercase())
+                && field
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            if !valid_name {
+                return Err(format!("invalid field name {:?}", field.name));
+            }
+            if self.fields[..i]
+                .iter()
+                .any(|other| other.name == field.name)
+            {
+                return Err(format!("duplicate field {:?}", field.name));
+            }
+        }
+        let default = self.default_order.trim_start_matches('-');
+        match self.field(default) {
+            Some(field) if field.sortable => Ok(()),
+            _ => Err(format!(
+                "default order {:?} is not a sortable field",
+                self.default_order
+            )),
+        }
+    }
+}
+
+/// Check that a list API's spec is valid and that requests sorting by each
+/// sortable field and filtering on each field are accepted. Every list API
+/// runs this in its tests.
+pub fn check_conformance(spec: &'static ListSpec) -> std::result::Result<(), String> {
+    spec.validate()?;
+    for field in spec.fields {
+        let sample = match field.kind {
+            FieldKind::Text => r#""sample""#,
+            FieldKind::Integer => "1",
+            FieldKind::Boolean => "true",
+            FieldKind::Timestamp => "2024-01-01T00:00:00Z",
+        };
+        let mut orders = vec![String::new()];
+        if field.sortable {
+            orders.push(field.name.to_string());
+            orders.push(format!("-{}", field.name));
+        }
+        for order_by in orders {
+            let pagination = Pagination {
+                order_by,
+                filter: format!("{} = {}", field.name, sample),
+                ..Default::default()
+            };
+            PageRequest::new(spec, Some(&pagination))
+                .map_err(|e| format!("field {:?}: {}", field.name, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// A field value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    Boolean(bool),
+    Timestamp(DateTime<Utc>),
+}
+
+impl Value {
+    /// Parse a value of `kind` from filter or cursor text
+    pub fn parse(kind: FieldKind, text: &str) -> Option<Self> {
+        match kind {
+            FieldKind::Text => Some(Self::Text(text.to_string())),
+            FieldKind::Integer => text.parse().ok().map(Self::Integer),
+            FieldKind::Boolean => match text {
+                "true" => Some(Self::Boolean(true)),
+                "false" => Some(Self::Boolean(false)),
+                _ => None,
+            },
+            FieldKind::Timestamp => DateTime::parse_from_rfc3339(text)
+                .ok()
+                .map(|time| Self::Timestamp(time.with_timezone(&Utc))),
+        }
+    }
+
+    /// Text that [`Value::parse`] reads back
+    fn to_text(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Integer(value) => value.to_string(),
+            Self::Boolean(value) => value.to_string(),
+            Self::Timestamp(time) => time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        }
+    }
+
+    fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            (Self::Integer(a), Self::Integer(b)) => a.cmp(b),
+            (Self::Boolean(a), Self::Boolean(b)) => a.cmp(b),
+            (Self::Timestamp(a), Self::Timestamp(b)) => a.cmp(b),
+            _ => Ordering::Equal,
+        }
+    }
+
+    fn push_bind(self, qb: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::Text(text) => qb.push_bind(text),
+            Self::Integer(value) => qb.push_bind(value),
+            Self::Boolean(value) => qb.push_bind(value),
+            Self::Timestamp(time) => qb.push_bind(time),
+        };
+    }
+
+    fn from_row(kind: FieldKind, row: &PgRow, column: &str) -> sqlx::Result<Self> {
+        Ok(match kind {
+            FieldKind::Text => Self::Text(row.try_get(column)?),
+            FieldKind::Integer => Self::Integer(row.try_get(column)?),
+            FieldKind::Boolean => Self::Boolean(row.try_get(column)?),
+            FieldKind::Timestamp => Self::Timestamp(row.try_get(column)?),
+        })
+    }
+}
+
+/// Comparison operator of a filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Case-insensitive substring match
+    Contains,
+}
+
+impl Operator {
+    const ALL: [(&'static str, Operator); 7] = [
+        ("!=", Self::Ne),
+        ("<=", Self::Le),
+        (">=", Self::Ge),
+        ("=", Self::Eq),
+        ("<", Self::Lt),
+        (">", Self::Gt),
+        ("~", Self::Contains),
+    ];
+
+    fn sql(self) -> &'static str {
+        match self {
+            Self::Eq => " = ",
+            Self::Ne => " <> ",
+            Self::Lt => " < ",
+            Self::Le => " <= ",
+            Self::Gt => " > ",
+            Self::Ge => " >= ",
+            Self::Contains => " ILIKE ",
+        }
+    }
+
+    fn matches(self, value: &Value, operand: &Value) -> bool {
+        if self == Self::Contains {
+            return match (value, operand) {
+                (Value::Text(value), Value::Text(operand)) => {
+                    value.to_lowercase().contains(&operand.to_lowercase())
+                }
+                _ => false,
+            };
+        }
+        let ordering = value.compare(operand);
+        match self {
+            Self::Eq => ordering == Ordering::Equal,
+            Self::Ne => ordering != Ordering::Equal,
+            Self::Lt => ordering == Ordering::Less,
+            Self::Le => ordering != Ordering::Greater,
+            Self::Gt => ordering == Ordering::Greater,
+            Self::Ge => ordering != Ordering::Less,
+            Self::Contains => unreachable!(),
+        }
+    }
+}
+
+/// One comparison of a filter
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub field: &'static Field,
+    pub op: Operator,
+    pub value: Value,
+}
+
+/// Parse a filter expression against the fields of `spec`
+pub fn parse_filter(spec: &ListSpec, filter: &str) -> Result<Vec<Condition>> {
+    let mut conditions = Vec::new();
+    let mut rest = filter.trim();
+    while !rest.is_empty() {
+        if !conditions.is_empty() {
+            rest = strip_keyword(rest, "AND").ok_or_else(|| {
+                Error::InvalidInput(format!("Expected AND in filter at {:?}", rest))
+            })?;
+        }
+        if conditions.len() == MAX_FILTER_CONDITIONS {
+            return Err(Error::InvalidInput(format!(
+                "Filter has more than {} conditions",
+                MAX_FILTER_CONDITIONS
+            )));
+        }
+
+        let name_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        let field = spec
+            .field(name)
+            .ok_or_else(|| Error::InvalidInput(format!("Cannot filter on {:?}", name)))?;
+        rest = rest[name_len..].trim_start();
+
+        let (op_text, op) = Operator::ALL
+            .into_iter()
+            .find(|(text, _)| rest.starts_with(text))
+            .ok_or_else(|| Error::InvalidInput(format!("Expected an operator after {:?}", name)))?;
+        rest = rest[op_text.len()..].trim_start();
+
+        let (text, remainder) = parse_operand(rest)?;
+        rest = remainder.trim_start();
+
+        let valid_op = match field.kind {
+            FieldKind::Text => true,
+            FieldKind::Boolean => matches!(op, Operator::Eq | Operator::Ne),
+            FieldKind::Integer | FieldKind::Timestamp => op != Operator::Contains,
+        };
+        if !valid_op {
+            return Err(Error::InvalidInput(format!(
+                "Operator {} is not supported for {:?}",
+                op_text, name
+            )));
+        }
+        let value = Value::parse(field.kind, &text).ok_or_else(|| {
+            Error::InvalidInput(format!("Invalid value {:?} for {:?}", text, name))
+        })?;
+        conditions.push(Condition { field, op, value });
+    }
+    Ok(conditions)
+}
+
+/// Strip a case-insensitive keyword followed by whitespace
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = text.get(..keyword.len())?;
+    let rest = &text[keyword.len()..];
+    (head.eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace))
+        .then(|| rest.trim_start())
+}
+
+/// Parse a quoted or bare operand, returning it and the remaining text
+fn parse_operand(text: &str) -> Result<(String, &str)> {
+    let Some(quoted) = text.strip_prefix('"') else {
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        if end == 0 {
+            return Err(Error::InvalidInput("Missing value in filter".to_string()));
+        }
+        return Ok((text[..end].to_string(), &text[end..]));
+    };
+
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &quoted[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, escaped)) => value.push(escaped),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err(Error::InvalidInput(
+        "Unterminated string in filter".to_string(),
+    ))
+}
+
+/// Position after the last row of a page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Cursor {
+    /// `order_by` the cursor was issued for
+    o: String,
+    /// Fingerprint of the filter the cursor was issued for
+    f: String,
+    /// Sort value of the last row
+    k: String,
+    /// ID of the last row
+    i: String,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let json = URL_SAFE_NO_PAD.decode(text).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+fn filter_fingerprint(filter: &str) -> String {
+    hex_prefix(&Sha256::digest(filter.trim().as_bytes()))
+}
+
+fn hex_prefix(digest: &[u8]) -> String {
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// One page of results
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, when there is one
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Convert the items of the page
+    pub fn try_map<U, E>(
+        self,
+        f: impl FnMut(T) -> std::result::Result<U, E>,
+    ) -> std::result::Result<Page<U>, E> {
+        Ok(Page {
+            items: self
+                .items
+                .into_iter()
+                .map(f)
+                .collect::<std::result::Result<_, _>>()?,
+            next_cursor: self.next_cursor,
+        })
+    }
+}
+
+/// Items of in-memory lists
+pub trait Pageable {
+    /// Value of a field named in the list's spec
+    fn field_value(&self, field: &str) -> Value;
+
+    /// Unique ID breaking sort ties
+    fn page_id(&self) -> String;
+}
+
+/// A parsed and validated page request
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    spec: &'static ListSpec,
+    /// Number of rows per page
+    pub page_size: u32,
+    /// Legacy page number, echoed back in [`PaginationInfo`]
+    page: u32,
+    sort: &'static Field,
+    descending: bool,
+    order_by: String,
+    filter: String,
+    conditions: Vec<Condition>,
+    after: Option<(Value, String)>,
+    offset: u64,
+}
+
+impl PageRequest {
+    /// Parse the pagination of a request against `spec`
+    pub fn new(spec: &'static ListSpec, pagination: Option<&Pagination>) -> Result<Self> {
+        let default = Pagination::default();
+        let pagination = pagination.unwrap_or(&default);
+
+        let page_size = match pagination.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+
+        let order_by = match pagination.order_by.trim() {
+            "" => spec.default_order,
+            order_by => order_by,
+        };
+        let (descending, name) = match order_by.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, order_by),
+        };
+        let sort = spec
+            .field(name)
+            .filter(|field| field.sortable)
+            .ok_or_else(|| Error::InvalidInput(format!("Cannot sort by {:?}", name)))?;
+
+        let filter = pagination.filter.trim().to_string();
+        let conditions = parse_filter(spec, &filter)?;
+
+        let mut request = Self {
+            spec,
+            page_size,
+            page: pagination.page.max(1),
+            sort,
+            descending,
+            order_by: order_by.to_string(),
+            filter,
+            conditions,
+            after: None,
+            offset: 0,
+        };
+
+        if pagination.cursor.is_empty() {
+            request.offset = u64::from(request.page - 1) * u64::from(page_size);
+            return Ok(request);
+        }
+        let invalid = || Error::InvalidInput("Invalid pagination cursor".to_string());
+        let cursor = Cursor::decode(&pagination.cursor).ok_or_else(invalid)?;
+        if cursor.o != request.order_by || cursor.f != filter_fingerprint(&request.filter) {
+            return Err(Error::InvalidInput(
+                "Pagination cursor was issued for a different order_by or filter".to_string(),
+            ));
+        }
+        let value = Value::parse(sort.kind, &cursor.k).ok_or_else(invalid)?;
+        request.after = Some((value, cursor.i));
+        Ok(request)
+    }
+
+    /// Parsed filter conditions
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    /// Select list entries holding the sort key and ID of each row, read by
+    /// [`PageRequest::finish_rows`]
+    pub fn select_key(&self) -> String {
+        format!(
+            "{} AS page_key, ({})::TEXT AS page_id",
+            self.sort.typed_column(),
+            self.spec.id_column
+        )
+    }
+
+    /// Append the filter conditions, each prefixed with `AND`
+    pub fn push_filter(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        for condition in &self.conditions {
+            qb.push(" AND ")
+                .push(condition.field.typed_column())
+                .push(condition.op.sql());
+            match (&condition.op, &condition.value) {
+                (Operator::Contains, Value::Text(text)) => {
+                    qb.push_bind(format!("%{}%", escape_like(text)));
+                }
+                (_, value) => value.clone().push_bind(qb),
+            }
+        }
+    }
+
+    /// Append the cursor condition, ordering and limit. The query fetches
+    /// one row more than the page size to tell whether a next page exists.
+    pub fn push_page(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        let sort = self.sort.typed_column();
+        let id = format!("({})::TEXT", self.spec.id_column);
+        let direction = if self.descending { "DESC" } else { "ASC" };
+
+        if let Some((value, last_id)) = &self.after {
+            qb.push(format_args!(" AND ({}, {}) ", sort, id))
+                .push(if self.descending { "<" } else { ">" })
+                .push(" (");
+            value.clone().push_bind(qb);
+            qb.push(", ").push_bind(last_id.clone()).push(")");
+        }
+        qb.push(format_args!(
+            " ORDER BY {} {}, {} {} LIMIT ",
+            sort, direction, id, direction
+        ))
+        .push_bind(i64::from(self.page_size) + 1);
+        if self.offset > 0 {
+            qb.push(" OFFSET ").push_bind(self.offset as i64);
+        }
+    }
+
+    /// Build the page from rows fetched with [`PageRequest::push_page`]
+    pub fn finish_rows(&self, mut rows: Vec<PgRow>) -> sqlx::Result<Page<PgRow>> {
+        let next_cursor = if rows.len() > self.page_size as usize {
+            rows.truncate(self.page_size as usize);
+            let last = rows.last().expect("page size is at least one");
+            let value = Value::from_row(self.sort.kind, last, "page_key")?;
+            Some(self.cursor(&value, last.try_get("page_id")?))
+        } else {
+            None
+        };
+        Ok(Page {
+            items: rows,
+            next_cursor,
+        })
+    }
+
+    /// Filter, sort and page an in-memory list. Also returns the number of
+    /// items matching the filter.
+    pub fn apply<T: Pageable>(&self, items: impl IntoIterator<Item = T>) -> (Page<T>, u64) {
+        let mut keyed: Vec<(Value, String, T)> = items
+            .into_iter()
+            .filter(|item| {
+                self.conditions.iter().all(|condition| {
+                    condition
+                        .op
+                        .matches(&item.field_value(condition.field.name), &condition.value)
+                })
+            })
+            .map(|item| (item.field_value(self.sort.name), item.page_id(), item))
+            .collect();
+        let total = keyed.len() as u64;
+
+        let compare = |a: (&Value, &String), b: (&Value, &String)| {
+            let ordering = a.0.compare(b.0).then_with(|| a.1.cmp(b.1));
+            if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        keyed.sort_by(|a, b| compare((&a.0, &a.1), (&b.0, &b.1)));
+
+        let mut page: Vec<(Value, String, T)> = keyed
+            .into_iter()
+            .filter(|(value, id, _)| match &self.after {
+                Some((after, after_id)) => compare((value, id), (after, after_id)).is_gt(),
+                None => true,
+            })
+            .skip(self.offset as usize)
+            .take(self.page_size as usize + 1)
+            .collect();
+
+        let next_cursor = if page.len() > self.page_size as usize {
+            page.truncate(self.page_size as usize);
+            page.last()
+                .map(|(value, id, _)| self.cursor(value, id.clone()))
+        } else {
+            None
+        };
+        let page = Page {
+            items: page.into_iter().map(|(_, _, item)| item).collect(),
+            next_cursor,
+        };
+        (page, total)
+    }
+
+    /// Pagination details of a response
+    pub fn info<T>(&self, page: &Page<T>, total: u64) -> PaginationInfo {
+        PaginationInfo {
+            total_count: u32::try_from(total).unwrap_or(u32::MAX),
+            page: self.page,
+            page_size: self.page_size,
+            has_next: page.next_cursor.is_some(),
+            next_cursor: page.next_cursor.clone().unwrap_or_default(),
+        }
+    }
+
+    fn cursor(&self, value: &Value, id: String) -> String {
+        Cursor {
+            o: self.order_by.clone(),
+            f: filter_fingerprint(&self.filter),
+            k: value.to_text(),
+            i: id,
+        }
+        .encode()
+    }
+}
+
+/// Escape `LIKE` wildcards so that the text matches literally
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    static SPEC: ListSpec = ListSpec {
+        fields: &[
+            Field::sortable("name", "name", FieldKind::Text),
+            Field::sortable("priority", "COALESCE(priority, 0)", FieldKind::Integer),
+            Field::sortable("created_at", "created_at", FieldKind::Timestamp),
+            Field::filterable("enabled", "enabled", FieldKind::Boolean),
+        ],
+        id_column: "id",
+        default_order: "-created_at",
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Item {
+        id: String,
+        name: String,
+        priority: i64,
+        created_at: DateTime<Utc>,
+        enabled: bool,
+    }
+
+    impl Pageable for Item {
+        fn field_value(&self, field: &str) -> Value {
+            match field {
+                "name" => Value::Text(self.name.clone()),
+                "priority" => Value::Integer(self.priority),
+                "created_at" => Value::Timestamp(self.created_at),
+                "enabled" => Value::Boolean(self.enabled),
+                _ => Value::Text(String::new()),
+            }
+        }
+
+        fn page_id(&self) -> String {
+            self.id.clone()
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        (0..23)
+            .map(|i| Item {
+                id: format!("id-{:02}", i),
+                name: format!("backend-{}", i % 7),
+                // Repeated values exercise the ID tie breaker
+                priority: i % 4,
+                created_at: Utc.timestamp_opt(1_700_000_000 + (i % 5) * 60, 0).unwrap(),
+                enabled: i % 3 != 0,
+            })
+            .collect()
+    }
+
+    fn pagination(page_size: u32, order_by: &str, filter: &str, cursor: &str) -> Pagination {
+        Pagination {
+            page: 0,
+            page_size,
+            cursor: cursor.to_string(),
+            order_by: order_by.to_string(),
+            filter: filter.to_string(),
+        }
+    }
+
+    /// Walk all pages and return the IDs in the order served
+    fn walk(page_size: u32, order_by: &str, filter: &str) -> Vec<String> {
+        let mut ids = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let request = PageRequest::new(
+                &SPEC,
+                Some(&pagination(page_size, order_by, filter, &cursor)),
+            )
+            .unwrap();
+            let (page, _) = request.apply(items());
+            assert!(page.items.len() <= page_size as usize);
+            ids.extend(page.items.iter().map(|item| item.id.clone()));
+            match page.next_cursor {
+                Some(next) => cursor = next,
+                None => return ids,
+            }
+        }
+    }
+
+    #[test]
+    fn test_spec_validation() {
+        assert!(check_conformance(&SPEC).is_ok());
+
+        static BAD_DEFAULT: ListSpec = ListSpec {
+            fields: &[Field::filterable("enabled", "enabled", FieldKind::Boolean)],
+            id_column: "id",
+            default_order: "enabled",
+        };
+        assert!(BAD_DEFAULT.validate().is_err());
+
+        static DUPLICATE: ListSpec = ListSpec {
+            fields: &[
+                Field::sortable("name", "name", FieldKind::Text),
+                Field::sortable("name", "title", FieldKind::Text),
+            ],
+            id_column: "id",
+            default_order: "name",
+        };
+        assert!(DUPLICATE.validate().is_err());
+    }
+
+    #[test]
+    fn test_page_size_caps() {
+        let request = PageRequest::new(&SPEC, None).unwrap();
+        assert_eq!(request.page_size, DEFAULT_PAGE_SIZE);
+
+        let request = PageRequest::new(&SPEC, Some(&pagination(10_000, "", "", ""))).unwrap();
+        assert_eq!(request.page_size, MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_pages_cover_every_item_once_in_order() {
+        for order_by in ["name", "-name", "priority", "-priority", "created_at", ""] {
+            for page_size in [1, 4, 23, 50] {
+                let ids = walk(page_size, order_by, "");
+
+                let request =
+                    PageRequest::new(&SPEC, Some(&pagination(100, order_by, "", ""))).unwrap();
+                let (all, total) = request.apply(items());
+                let expected: Vec<String> = all.items.iter().map(|item| item.id.clone()).collect();
+
+                assert_eq!(total, 23);
+                assert_eq!(
+                    ids, expected,
+                    "order_by {:?}, page size {}",
+                    order_by, page_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_sort_order() {
+        let request = PageRequest::new(&SPEC, Some(&pagination(100, "-priority", "", ""))).unwrap();
+        let (page, _) = request.apply(items());
+        let priorities: Vec<i64> = page.items.iter().map(|item| item.priority).collect();
+        assert!(priorities.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_filter_applies_across_pages() {
+        let filter = r#"name ~ "END-1" AND enabled = true AND priority >= 1"#;
+        let ids = walk(2, "name", filter);
+        let expected: Vec<String> = items()
+            .into_iter()
+            .filter(|item| item.name == "backend-1" && item.enabled && item.priority >= 1)
+            .map(|item| item.id)
+            .collect();
+        assert!(!expected.is_empty());
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn test_filter_parsing() {
+        let conditions = parse_filter(
+            &SPEC,
+            r#"name = "say \"hi\"" and created_at < 2024-01-01T00:00:00Z"#,
+        )
+        .unwrap();
+        assert_eq!(conditions.len(), 2);
+        assert_eq!(conditions[0].value, Value::Text("say \"hi\"".to_string()));
+        assert_eq!(conditions[1].op, Operator::Lt);
+
+        for invalid in [
+            "unknown = 1",
+            "name",
+            "name =",
+            "name = \"open",
+            "priority = high",
+            "priority ~ 1",
+            "enabled > false",
+            "name = a name = b",
+            "created_at >= yesterday",
+        ] {
+            assert!(parse_filter(&SPEC, invalid).is_err(), "{:?}", invalid);
+        }
+
+        let too_many = vec!["priority > 0"; MAX_FILTER_CONDITIONS + 1].join(" AND ");
+        assert!(parse_filter(&SPEC, &too_many).is_err());
+    }
+
+    #[test]
+    fn test_sort_whitelist() {
+        assert!(PageRequest::new(&SPEC, Some(&pagination(10, "secret", "", ""))).is_err());
+        // Filterable fields are not necessarily sortable
+        assert!(PageRequest::new(&SPEC, Some(&pagination(10, "enabled", "", ""))).is_err());
+    }
+
+    #[test]
+    fn test_cursor_is_bound_to_request() {
+        let request = PageRequest::new(&SPEC, Some(&pagination(5, "name", "", ""))).unwrap();
+        let cursor = request.apply(items()).0.next_cursor.unwrap();
+
+        assert!(PageRequest::new(&SPEC, Some(&pagination(5, "name", "", &cursor))).is_ok());
+        // A different page size is fine, a different order or filter is not
+        assert!(PageRequest::new(&SPEC, Some(&pagination(9, "name", "", &cursor))).is_ok());
+        assert!(PageRequest::new(&SPEC, Some(&pagination(5, "-name", "", &cursor))).is_err());
+        assert!(
+            PageRequest::new(&SPEC, Some(&pagination(5, "name", "priority = 1", &cursor))).is_err()
+        );
+        assert!(PageRequest::new(&SPEC, Some(&pagination(5, "name", "", "garbage"))).is_err());
+    }
+
+    #[test]
+    fn test_legacy_page_numbers() {
+        let mut legacy = pagination(5, "name", "", "");
+        legacy.page = 2;
+        let request = PageRequest::new(&SPEC, Some(&legacy)).unwrap();
+        let (page, _) = request.apply(items());
+
+        let ids = walk(5, "name", "");
+        let served: Vec<String> = page.items.iter().map(|item| item.id.clone()).collect();
+        assert_eq!(served, ids[5..10]);
+        assert_eq!(request.info(&page, 23).page, 2);
+    }
+
+    #[test]
+    fn test_sql() {
+        let request = PageRequest::new(
+            &SPEC,
+            Some(&pagination(10, "-priority", r#"name ~ "50%""#, "")),
+        )
+        .unwrap();
+        let cursor = request.cursor(&Value::Integer(3), "id-07".to_string());
+        let request = PageRequest::new(
+            &SPEC,
+            Some(&pagination(10, "-priority", r#"name ~ "50%""#, &cursor)),
+        )
+        .unwrap();
+
+        let mut qb = QueryBuilder::new("SELECT id FROM rules WHERE backend_id = ");
+        qb.push_bind("backend");
+        request.push_filter(&mut qb);
+        request.push_page(&mut qb);
+
+        assert_eq!(
+            qb.sql(),
+            "SELECT id FROM rules WHERE backend_id = $1 \
+             AND (name)::TEXT ILIKE $2 \
+             AND ((COALESCE(priority, 0))::BIGINT, (id)::TEXT) < ($3, $4) \
+             ORDER BY (COALESCE(priority, 0))::BIGINT DESC, (id)::TEXT DESC LIMIT $5"
+        );
+        assert_eq!(
+            request.select_key(),
+            "(COALESCE(priority, 0))::BIGINT AS page_key, (id)::TEXT AS page_id"
+        );
+        assert_eq!(escape_like(r"50%_\"), r"50\%\_\\");
+    }
+}
@@ -0,0 +1,61 @@
+//! Origin probes
+//!
+//! Onboarding checks that an origin answers a status ping, and the ping has
+//! to come from a worker: that is where protected traffic will be proxied
+//! from. The gateway queues an `OriginProbeRequest` in Redis, the first
+//! worker to pop it pings the origin and stores an `OriginProbeResult` under
+//! the probe's result key. Both sides use a cache prefix of `piston`.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Cache prefix of the probe keys
+pub const PROBE_CACHE_PREFIX: &str = "piston";
+
+/// List of probes waiting for a worker
+pub const PROBE_QUEUE_KEY: &str = "onboarding:probes";
+
+/// Time queued probes and their results are kept
+pub const PROBE_TTL: Duration = Duration::from_secs(60);
+
+/// Key of the result of a probe
+pub fn probe_result_key(id: &str) -> String {
+    format!("onboarding:probe:{}", id)
+}
+
+/// Status ping to send
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeKind {
+    /// Server List Ping over TCP
+    MinecraftJava,
+    /// RakNet unconnected ping over UDP
+    MinecraftBedrock,
+}
+
+/// Origin to ping
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OriginProbeRequest {
+    pub id: String,
+    pub kind: ProbeKind,
+    /// Address to connect to
+    pub address: String,
+    pub port: u16,
+    /// Hostname players connect with, sent in the Java handshake
+    pub hostname: String,
+}
+
+/// Outcome of a probe
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OriginProbeResult {
+    pub id: String,
+    pub worker_id: Option<String>,
+    pub reachable: bool,
+    pub latency_ms: u64,
+    /// Version name reported by the origin
+    pub version: String,
+    pub protocol: i32,
+    pub players_online: u32,
+    pub players_max: u32,
+    pub error: Option<String>,
+}
@@ -0,0 +1,242 @@
+//! Rate limit profiles
+//!
+//! A backend can have named rate limit profiles (e.g. `normal`, `event`,
+//! `restart-storm`) with their own per-source limits, a weekly schedule of
+//! when each applies and a manual trigger overriding the schedule for a
+//! while. The gateway stores the `BackendRateProfiles` of a backend under
+//! its profiles key and adds it to the active profile set. Workers resolve
+//! the active profile themselves on every tick, so schedules switch on time
+//! and manual triggers end even while the gateway is unreachable. Both
+//! sides use a cache prefix of `piston`.
+
+use chrono::{Datelike, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Set of the backends with rate limit profiles
+pub const ACTIVE_PROFILES_KEY: &str = "rate_profiles:active";
+
+/// Time published profiles are kept; the gateway republishes them well
+/// before
+pub const PROFILES_TTL: Duration = Duration::from_secs(24 * 3600);
+
+/// Profile label of backends using the limits of their protection settings
+pub const CONFIGURED_PROFILE: &str = "configured";
+
+/// Minutes in a day
+pub const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Key of the rate limit profiles of a backend
+pub fn rate_profiles_key(backend_id: &str) -> String {
+    format!("rate_profiles:{}", backend_id)
+}
+
+/// Named set of per-source limits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitProfile {
+    pub name: String,
+    /// Packets per second of each source
+    pub per_ip_pps: u64,
+    /// Bucket size of each source (0 = one second of `per_ip_pps`)
+    pub per_ip_burst: u64,
+}
+
+/// Weekly window during which a profile applies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileWindow {
+    pub profile: String,
+    /// Days the window starts on, 0 = Monday; empty = every day
+    pub days: Vec<u8>,
+    /// Minutes after local midnight
+    pub start_minute: u32,
+    /// Minutes after local midnight; at or before the start the window
+    /// runs past midnight, equal to it the window lasts a whole day
+    pub end_minute: u32,
+}
+
+impl ProfileWindow {
+    /// Whether the window covers a local time, given as day of the week
+    /// (0 = Monday) and minutes after midnight
+    pub fn contains(&self, weekday: u8, minute: u32) -> bool {
+        let starts_on = |day: u8| self.days.is_empty() || self.days.contains(&day);
+
+        if self.start_minute < self.end_minute {
+            starts_on(weekday) && minute >= self.start_minute && minute < self.end_minute
+        } else {
+            (starts_on(weekday) && minute >= self.start_minute)
+                || (starts_on((weekday + 6) % 7) && minute < self.end_minute)
+        }
+    }
+}
+
+/// Profile applied by hand until a time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManualProfile {
+    pub profile: String,
+    pub triggered_by: String,
+    /// Unix seconds
+    pub until: i64,
+}
+
+/// Why a profile is active
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileSource {
+    Manual,
+    Schedule,
+    /// Outside every window
+    Default,
+}
+
+impl ProfileSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProfileSource::Manual => "manual",
+            ProfileSource::Schedule => "schedule",
+            ProfileSource::Default => "default",
+        }
+    }
+}
+
+impl fmt::Display for ProfileSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Rate limit profiles of a backend
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackendRateProfiles {
+    pub backend_id: String,
+    pub profiles: Vec<RateLimitProfile>,
+    /// The first window covering the current time wins
+    pub windows: Vec<ProfileWindow>,
+    /// Profile outside every window; empty = the limits of the protection
+    /// settings
+    pub default_profile: String,
+    /// Offset of the local time of the schedule from UTC
+    pub utc_offset_minutes: i32,
+    pub manual: Option<ManualProfile>,
+}
+
+impl BackendRateProfiles {
+    /// Profile by name
+    pub fn profile(&self, name: &str) -> Option<&RateLimitProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Profile active at a time (Unix seconds), `None` when the limits of
+    /// the protection settings apply
+    pub fn active(&self, now: i64) -> Option<(&RateLimitProfile, ProfileSource)> {
+        if let Some(manual) = self.manual.as_ref().filter(|manual| now < manual.until) {
+            if let Some(profile) = self.profile(&manual.profile) {
+                return Some((profile, ProfileSource::Manual));
+            }
+        }
+
+        let local = Utc.timestamp_opt(now + i64::from(self.utc_offset_minutes) * 60, 0);
+        if let Some(local) = local.single() {
+            let weekday = local.weekday().num_days_from_monday() as u8;
+            let minute = local.hour() * 60 + local.minute();
+            let scheduled = self
+                .windows
+                .iter()
+                .filter(|window| window.contains(weekday, minute))
+                .find_map(|window| self.profile(&window.profile));
+            if let Some(profile) = scheduled {
+                return Some((profile, ProfileSource::Schedule));
+            }
+        }
+
+        self.profile(&self.default_profile)
+            .map(|profile| (profile, ProfileSource::Default))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles() -> BackendRateProfiles {
+        let profile = |name: &str, pps| RateLimitProfile {
+            name: name.to_string(),
+            per_ip_pps: pps,
+            per_ip_burst: 0,
+        };
+        BackendRateProfiles {
+            backend_id: "backend-1".to_string(),
+            profiles: vec![
+                profile("normal", 1000),
+                profile("night", 200),
+                profile("event", 5000),
+            ],
+            windows: vec![ProfileWindow {
+                profile: "night".to_string(),
+                days: vec![4],
+                start_minute: 22 * 60,
+                end_minute: 6 * 60,
+            }],
+            default_profile: "normal".to_string(),
+            utc_offset_minutes: 60,
+            manual: None,
+        }
+    }
+
+    // 2026-01-02 is a Friday
+    fn at(day: u32, hour: u32) -> i64 {
+        Utc.with_ymd_and_hms(2026, 1, day, hour, 0, 0)
+            .unwrap()
+            .timestamp()
+    }
+
+    #[test]
+    fn test_window_past_midnight() {
+        let window = &profiles().windows[0];
+        assert!(window.contains(4, 23 * 60));
+        assert!(window.contains(5, 5 * 60));
+        assert!(!window.contains(5, 23 * 60));
+        assert!(!window.contains(4, 5 * 60));
+    }
+
+    #[test]
+    fn test_whole_day_window() {
+        let window = ProfileWindow {
+            profile: "event".to_string(),
+            days: vec![],
+            start_minute: 0,
+            end_minute: 0,
+        };
+        assert!(window.contains(0, 0));
+        assert!(window.contains(6, MINUTES_PER_DAY - 1));
+    }
+
+    #[test]
+    fn test_active_profile() {
+        let mut profiles = profiles();
+
+        // 21:00 UTC is 22:00 local
+        let (profile, source) = profiles.active(at(2, 21)).unwrap();
+        assert_eq!(
+            (profile.name.as_str(), source),
+            ("night", ProfileSource::Schedule)
+        );
+        let (profile, source) = profiles.active(at(2, 12)).unwrap();
+        assert_eq!(
+            (profile.name.as_str(), source),
+            ("normal", ProfileSource::Default)
+        );
+
+        profiles.manual = Some(ManualProfile {
+            profile: "event".to_string(),
+            triggered_by: "user-1".to_string(),
+            until: at(3, 0),
+        });
+        assert_eq!(profiles.active(at(2, 21)).unwrap().0.name, "event");
+        assert_eq!(profiles.active(at(3, 1)).unwrap().0.name, "night");
+
+        profiles.manual = None;
+        profiles.default_profile.clear();
+        assert!(profiles.active(at(2, 12)).is_none());
+    }
+}
@@ -1,6 +1,7 @@
 //! Rate limiting utilities
 
 use crate::error::{Error, Result};
+use crate::redis::RedisPool;
 use dashmap::DashMap;
 use governor::{
     Quota, RateLimiter,
@@ -142,7 +143,7 @@ impl IpRateLimiter {
 
 /// Sliding window rate limiter using Redis
 pub struct RedisRateLimiter {
-    pool: deadpool_redis::Pool,
+    pool: RedisPool,
     prefix: String,
     window_seconds: u64,
     max_requests: u64,
@@ -151,13 +152,13 @@ pub struct RedisRateLimiter {
 impl RedisRateLimiter {
     /// Create a new Redis-backed rate limiter
     pub fn new(
-        pool: deadpool_redis::Pool,
+        pool: impl Into<RedisPool>,
         prefix: &str,
         window_seconds: u64,
         max_requests: u64,
     ) -> Self {
         Self {
-            pool,
+            pool: pool.into(),
             prefix: prefix.to_string(),
             window_seconds,
             max_requests,
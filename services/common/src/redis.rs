@@ -1,24 +1,207 @@
 //! Redis connection and caching utilities
-
-use crate::config::RedisConfig;
+//!
+//! [`create_pool`] connects to a single server, a Redis Cluster or a
+//! Sentinel-managed primary depending on [`RedisConfig::topology`]. The
+//! resulting [`RedisPool`] hands out [`RedisConnection`]s that run commands
+//! and pipelines the same way on every topology, so [`CacheService`] and
+//! raw pool users do not need to know which one they talk to.
+//!
+//! Cluster connections follow `MOVED`/`ASK` redirects and refresh the slot
+//! map on their own, but a pipeline must stay within one hash slot; the
+//! multi-key cache operations split their pipelines by slot. Sentinel
+//! connections are pinned to the primary they resolved at connect time, so
+//! when it is demoted (`READONLY`) or goes away they are dropped from the
+//! pool and the command is retried once against the newly elected primary.
+
+use crate::config::{RedisConfig, RedisTopology};
 use crate::error::{Error, Result};
-use deadpool_redis::{Config as DeadpoolConfig, Pool, Runtime, redis, redis::AsyncCommands};
+use deadpool_redis::redis::aio::ConnectionLike;
+use deadpool_redis::redis::{Cmd, ErrorKind, FromRedisValue, Pipeline, RedisError, Value};
+use deadpool_redis::{Config as DeadpoolConfig, PoolError, Runtime, cluster, redis, sentinel};
 use serde::{Serialize, de::DeserializeOwned};
+use std::collections::HashMap;
 use std::time::Duration;
-use tracing::info;
+use tracing::{info, warn};
+
+/// Redis connection pool for any supported topology
+#[derive(Clone)]
+pub enum RedisPool {
+    Standalone(deadpool_redis::Pool),
+    Cluster(cluster::Pool),
+    Sentinel(sentinel::Pool),
+}
+
+impl RedisPool {
+    /// Take a connection from the pool
+    pub async fn get(&self) -> std::result::Result<RedisConnection, PoolError> {
+        Ok(match self {
+            Self::Standalone(pool) => RedisConnection::Standalone(pool.get().await?),
+            Self::Cluster(pool) => RedisConnection::Cluster(pool.get().await?),
+            Self::Sentinel(pool) => RedisConnection::Sentinel {
+                conn: Some(pool.get().await?),
+                pool: pool.clone(),
+            },
+        })
+    }
+
+    /// Topology this pool connects to
+    pub fn topology(&self) -> RedisTopology {
+        match self {
+            Self::Standalone(_) => RedisTopology::Standalone,
+            Self::Cluster(_) => RedisTopology::Cluster,
+            Self::Sentinel(_) => RedisTopology::Sentinel,
+        }
+    }
+}
+
+impl From<deadpool_redis::Pool> for RedisPool {
+    fn from(pool: deadpool_redis::Pool) -> Self {
+        Self::Standalone(pool)
+    }
+}
+
+/// Pooled connection of a [`RedisPool`]
+pub enum RedisConnection {
+    Standalone(deadpool_redis::Connection),
+    Cluster(cluster::Connection),
+    Sentinel {
+        /// `None` once detached after a failover
+        conn: Option<sentinel::Connection>,
+        pool: sentinel::Pool,
+    },
+}
+
+impl RedisConnection {
+    /// Drop a Sentinel connection that still points at a former primary,
+    /// along with every idle connection of its pool, so the next checkout
+    /// asks the sentinels for the current primary
+    fn on_error(&mut self, error: &RedisError) {
+        if let Self::Sentinel { conn, pool } = self
+            && is_failover(error)
+        {
+            warn!(
+                "Redis primary unavailable ({}), reconnecting through Sentinel",
+                error
+            );
+            if let Some(conn) = conn.take() {
+                drop(sentinel::Connection::take(conn));
+            }
+            pool.retain(|_, _| false);
+        }
+    }
+}
+
+fn detached() -> RedisError {
+    RedisError::from((
+        ErrorKind::IoError,
+        "connection detached after a Sentinel failover",
+    ))
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> redis::RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let result = match self {
+                Self::Standalone(conn) => conn.req_packed_command(cmd).await,
+                Self::Cluster(conn) => conn.req_packed_command(cmd).await,
+                Self::Sentinel { conn, .. } => match conn {
+                    Some(conn) => conn.req_packed_command(cmd).await,
+                    None => Err(detached()),
+                },
+            };
+            if let Err(ref e) = result {
+                self.on_error(e);
+            }
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> redis::RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let result = match self {
+                Self::Standalone(conn) => conn.req_packed_commands(cmd, offset, count).await,
+                Self::Cluster(conn) => conn.req_packed_commands(cmd, offset, count).await,
+                Self::Sentinel { conn, .. } => match conn {
+                    Some(conn) => conn.req_packed_commands(cmd, offset, count).await,
+                    None => Err(detached()),
+                },
+            };
+            if let Err(ref e) = result {
+                self.on_error(e);
+            }
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Self::Standalone(conn) => conn.get_db(),
+            Self::Cluster(conn) => conn.get_db(),
+            Self::Sentinel { conn, .. } => conn.as_ref().map_or(0, |conn| conn.get_db()),
+        }
+    }
+}
 
-/// Create a Redis connection pool
-pub async fn create_pool(config: &RedisConfig) -> Result<Pool> {
-    info!("Connecting to Redis with pool size: {}", config.pool_size);
+/// Whether an error means the command reached no writable primary and can
+/// be retried on a fresh connection without being applied twice
+fn is_failover(error: &RedisError) -> bool {
+    matches!(error.kind(), ErrorKind::ReadOnly | ErrorKind::MasterDown)
+        || error.is_connection_refusal()
+}
 
-    let cfg = DeadpoolConfig::from_url(&config.url);
-    let pool = cfg
-        .builder()
-        .map_err(|e| Error::Internal(format!("Redis pool builder error: {}", e)))?
-        .max_size(config.pool_size)
-        .runtime(Runtime::Tokio1)
-        .build()
-        .map_err(|e| Error::Internal(format!("Redis pool build error: {}", e)))?;
+/// Create a Redis connection pool for the configured topology
+pub async fn create_pool(config: &RedisConfig) -> Result<RedisPool> {
+    let topology = config.topology();
+    info!(
+        "Connecting to Redis ({:?}) with pool size: {}",
+        topology, config.pool_size
+    );
+
+    let timeout = Some(Duration::from_secs(config.timeout_secs));
+    let pool = match topology {
+        RedisTopology::Standalone => RedisPool::Standalone(
+            DeadpoolConfig::from_url(&config.url)
+                .builder()
+                .map_err(|e| Error::Internal(format!("Redis pool builder error: {}", e)))?
+                .max_size(config.pool_size)
+                .create_timeout(timeout)
+                .wait_timeout(timeout)
+                .runtime(Runtime::Tokio1)
+                .build()
+                .map_err(|e| Error::Internal(format!("Redis pool build error: {}", e)))?,
+        ),
+        RedisTopology::Cluster => RedisPool::Cluster(
+            cluster::Config::from_urls(config.seed_nodes())
+                .builder()
+                .map_err(|e| Error::Internal(format!("Redis pool builder error: {}", e)))?
+                .max_size(config.pool_size)
+                .create_timeout(timeout)
+                .wait_timeout(timeout)
+                .runtime(Runtime::Tokio1)
+                .build()
+                .map_err(|e| Error::Internal(format!("Redis pool build error: {}", e)))?,
+        ),
+        RedisTopology::Sentinel => RedisPool::Sentinel(
+            sentinel::Config::from_urls(
+                config.seed_nodes(),
+                config.sentinel_master.clone().unwrap_or_default(),
+                sentinel::SentinelServerType::Master,
+            )
+            .builder()
+            .map_err(|e| Error::Internal(format!("Redis pool builder error: {}", e)))?
+            .max_size(config.pool_size)
+            .create_timeout(timeout)
+            .wait_timeout(timeout)
+            .runtime(Runtime::Tokio1)
+            .build()
+            .map_err(|e| Error::Internal(format!("Redis pool build error: {}", e)))?,
+        ),
+    };
 
     // Test connection
     let mut conn = pool
@@ -26,7 +209,7 @@ pub async fn create_pool(config: &RedisConfig) -> Result<Pool> {
         .await
         .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
     let _: String = redis::cmd("PING")
-        .query_async(&mut *conn)
+        .query_async(&mut conn)
         .await
         .map_err(|e| Error::Internal(format!("Redis ping error: {}", e)))?;
 
@@ -34,22 +217,107 @@ pub async fn create_pool(config: &RedisConfig) -> Result<Pool> {
     Ok(pool)
 }
 
+/// Number of hash slots in a Redis Cluster
+const CLUSTER_SLOTS: u16 = 16384;
+
+/// Cluster hash slot of a key: CRC16 (XMODEM) of the key, or of its
+/// `{hash tag}` when it has a non-empty one
+fn key_slot(key: &[u8]) -> u16 {
+    let hashed = key
+        .iter()
+        .position(|&b| b == b'{')
+        .and_then(|open| {
+            let tag = &key[open + 1..];
+            let close = tag.iter().position(|&b| b == b'}')?;
+            (close > 0).then(|| &tag[..close])
+        })
+        .unwrap_or(key);
+
+    let mut crc: u16 = 0;
+    for &byte in hashed {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc % CLUSTER_SLOTS
+}
+
+/// Split keys into groups that can share a pipeline: one group per hash
+/// slot on a cluster, a single group otherwise. Each group holds indexes
+/// into `keys`.
+fn slot_groups(topology: RedisTopology, keys: &[String]) -> Vec<Vec<usize>> {
+    if topology != RedisTopology::Cluster {
+        return if keys.is_empty() {
+            Vec::new()
+        } else {
+            vec![(0..keys.len()).collect()]
+        };
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut by_slot: HashMap<u16, usize> = HashMap::new();
+    for (i, key) in keys.iter().enumerate() {
+        let group = *by_slot.entry(key_slot(key.as_bytes())).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+    groups
+}
+
 /// Cache service for Redis operations
 #[derive(Clone)]
 pub struct CacheService {
-    pool: Pool,
+    pool: RedisPool,
     prefix: String,
 }
 
 impl CacheService {
     /// Create a new cache service
-    pub fn new(pool: Pool, prefix: &str) -> Self {
+    pub fn new(pool: impl Into<RedisPool>, prefix: &str) -> Self {
         Self {
-            pool,
+            pool: pool.into(),
             prefix: prefix.to_string(),
         }
     }
 
+    /// Run a command, retrying once on a new connection after a failover
+    async fn query<T: FromRedisValue>(&self, cmd: &Cmd) -> Result<T> {
+        let mut conn = self.connection().await?;
+        match cmd.query_async(&mut conn).await {
+            Err(e) if is_failover(&e) => {
+                let mut conn = self.connection().await?;
+                Ok(cmd.query_async(&mut conn).await?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Run a pipeline, retrying once on a new connection after a failover
+    async fn query_pipeline<T: FromRedisValue>(&self, pipe: &Pipeline) -> Result<T> {
+        let mut conn = self.connection().await?;
+        match pipe.query_async(&mut conn).await {
+            Err(e) if is_failover(&e) => {
+                let mut conn = self.connection().await?;
+                Ok(pipe.query_async(&mut conn).await?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    async fn connection(&self) -> Result<RedisConnection> {
+        self.pool
+            .get()
+            .await
+            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))
+    }
+
     /// Build a cache key with prefix
     fn key(&self, key: &str) -> String {
         format!("{}:{}", self.prefix, key)
@@ -57,159 +325,183 @@ impl CacheService {
 
     /// Get a value from cache
     pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
+        let value: Option<String> = self.query(&Cmd::get(self.key(key))).await?;
+        value.map(|v| deserialize(&v)).transpose()
+    }
 
-        let value: Option<String> = conn.get(self.key(key)).await?;
+    /// Get several values at once. Results are in the order of `keys`.
+    pub async fn get_many<T: DeserializeOwned>(&self, keys: &[&str]) -> Result<Vec<Option<T>>> {
+        let keys: Vec<String> = keys.iter().map(|key| self.key(key)).collect();
+        let mut values = vec![None; keys.len()];
 
-        match value {
-            Some(v) => {
-                let parsed: T = serde_json::from_str(&v)
-                    .map_err(|e| Error::Internal(format!("Cache deserialization error: {}", e)))?;
-                Ok(Some(parsed))
+        for group in slot_groups(self.pool.topology(), &keys) {
+            let mut pipe = redis::pipe();
+            for &i in &group {
+                pipe.get(&keys[i]);
+            }
+            let found: Vec<Option<String>> = self.query_pipeline(&pipe).await?;
+            for (i, value) in group.into_iter().zip(found) {
+                values[i] = value;
             }
-            None => Ok(None),
         }
+
+        values
+            .into_iter()
+            .map(|value| value.map(|v| deserialize(&v)).transpose())
+            .collect()
     }
 
     /// Set a value in cache with TTL
     pub async fn set<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) -> Result<()> {
-        let mut conn = self
-            .pool
-            .get()
+        let json = serialize(value)?;
+        self.query(&Cmd::set_ex(self.key(key), json, ttl.as_secs()))
             .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
+    }
 
-        let json = serde_json::to_string(value)
-            .map_err(|e| Error::Internal(format!("Cache serialization error: {}", e)))?;
+    /// Set a value with TTL only if the key does not exist. Returns whether
+    /// the value was set.
+    pub async fn set_nx<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) -> Result<bool> {
+        let json = serialize(value)?;
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(key))
+            .arg(json)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl.as_secs());
+        let reply: Option<String> = self.query(&cmd).await?;
+        Ok(reply.is_some())
+    }
 
-        let _: () = conn.set_ex(self.key(key), json, ttl.as_secs()).await?;
+    /// Set several values at once with the same TTL
+    pub async fn set_many<T: Serialize>(&self, entries: &[(&str, T)], ttl: Duration) -> Result<()> {
+        let keys: Vec<String> = entries.iter().map(|(key, _)| self.key(key)).collect();
+        let values = entries
+            .iter()
+            .map(|(_, value)| serialize(value))
+            .collect::<Result<Vec<_>>>()?;
+
+        for group in slot_groups(self.pool.topology(), &keys) {
+            let mut pipe = redis::pipe();
+            for i in group {
+                pipe.set_ex(&keys[i], &values[i], ttl.as_secs()).ignore();
+            }
+            let _: () = self.query_pipeline(&pipe).await?;
+        }
         Ok(())
     }
 
     /// Delete a value from cache
     pub async fn delete(&self, key: &str) -> Result<()> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
-
-        let _: () = conn.del(self.key(key)).await?;
-        Ok(())
+        self.query(&Cmd::del(self.key(key))).await
     }
 
-    /// Delete multiple values matching a pattern
+    /// Delete multiple values matching a pattern. On a cluster every
+    /// primary is searched.
     pub async fn delete_pattern(&self, pattern: &str) -> Result<u64> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
-
-        let keys: Vec<String> = redis::cmd("KEYS")
-            .arg(self.key(pattern))
-            .query_async(&mut *conn)
-            .await?;
+        let keys: Vec<String> = self.query(&Cmd::keys(self.key(pattern))).await?;
 
         if keys.is_empty() {
             return Ok(0);
         }
 
-        let count: u64 = conn.del(&keys).await?;
-        Ok(count)
+        self.query(&Cmd::del(&keys)).await
     }
 
     /// Increment a counter
     pub async fn incr(&self, key: &str, delta: i64) -> Result<i64> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
-
-        let value: i64 = conn.incr(self.key(key), delta).await?;
-        Ok(value)
+        self.query(&Cmd::incr(self.key(key), delta)).await
     }
 
     /// Set expiration on a key
     pub async fn expire(&self, key: &str, ttl: Duration) -> Result<bool> {
-        let mut conn = self
-            .pool
-            .get()
+        self.query(&Cmd::expire(self.key(key), ttl.as_secs() as i64))
             .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
-
-        let result: bool = conn.expire(self.key(key), ttl.as_secs() as i64).await?;
-        Ok(result)
     }
 
     /// Check if key exists
     pub async fn exists(&self, key: &str) -> Result<bool> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
-
-        let exists: bool = conn.exists(self.key(key)).await?;
-        Ok(exists)
+        self.query(&Cmd::exists(self.key(key))).await
     }
 
     /// Add to a set
     pub async fn sadd(&self, key: &str, member: &str) -> Result<bool> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
-
-        let added: bool = conn.sadd(self.key(key), member).await?;
-        Ok(added)
+        self.query(&Cmd::sadd(self.key(key), member)).await
     }
 
     /// Check if member is in set
     pub async fn sismember(&self, key: &str, member: &str) -> Result<bool> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
-
-        let is_member: bool = conn.sismember(self.key(key), member).await?;
-        Ok(is_member)
+        self.query(&Cmd::sismember(self.key(key), member)).await
     }
 
     /// Get all members of a set
     pub async fn smembers(&self, key: &str) -> Result<Vec<String>> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
+        self.query(&Cmd::smembers(self.key(key))).await
+    }
+
+    /// Remove from a set
+    pub async fn srem(&self, key: &str, member: &str) -> Result<bool> {
+        self.query(&Cmd::srem(self.key(key), member)).await
+    }
+
+    /// Append a value to a list, keeping the list for `ttl` after the push
+    pub async fn push<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) -> Result<()> {
+        let json = serialize(value)?;
+
+        // Both commands touch the same key, so the pipeline stays in one slot
+        let key = self.key(key);
+        self.query_pipeline(
+            redis::pipe()
+                .rpush(&key, json)
+                .ignore()
+                .expire(&key, ttl.as_secs() as i64)
+                .ignore(),
+        )
+        .await
+    }
+
+    /// Remove and return the first value of a list
+    pub async fn pop<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let value: Option<String> = self.query(&Cmd::lpop(self.key(key), None)).await?;
+        value.map(|v| deserialize(&v)).transpose()
+    }
 
-        let members: Vec<String> = conn.smembers(self.key(key)).await?;
-        Ok(members)
+    /// Run a Lua script with prefixed keys and integer arguments. On a
+    /// cluster the keys must share a `{hash tag}`.
+    pub async fn eval<T: FromRedisValue>(
+        &self,
+        script: &str,
+        keys: &[&str],
+        args: &[i64],
+    ) -> Result<T> {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(script).arg(keys.len());
+        for key in keys {
+            cmd.arg(self.key(key));
+        }
+        cmd.arg(args);
+        self.query(&cmd).await
     }
 
     /// Publish a message to a channel
     pub async fn publish(&self, channel: &str, message: &str) -> Result<()> {
-        let mut conn = self
-            .pool
-            .get()
-            .await
-            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
-
-        let _: () = conn.publish(channel, message).await?;
-        Ok(())
+        self.query(&Cmd::publish(channel, message)).await
     }
 }
 
+fn serialize<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value)
+        .map_err(|e| Error::Internal(format!("Cache serialization error: {}", e)))
+}
+
+fn deserialize<T: DeserializeOwned>(value: &str) -> Result<T> {
+    serde_json::from_str(value)
+        .map_err(|e| Error::Internal(format!("Cache deserialization error: {}", e)))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_cache_key_format() {
         // Test key format: prefix:key
@@ -218,4 +510,65 @@ mod tests {
         let expected = format!("{}:{}", prefix, key);
         assert_eq!(expected, "test:mykey");
     }
+
+    #[test]
+    fn test_key_slot() {
+        // Reference values from the Redis Cluster specification
+        assert_eq!(key_slot(b"123456789"), 0x31c3);
+        assert_eq!(key_slot(b"foo"), 12182);
+        assert_eq!(
+            key_slot(b"{user1000}.following"),
+            key_slot(b"{user1000}.followers")
+        );
+        // Only the first tag counts, and an empty one hashes the whole key
+        assert_eq!(key_slot(b"foo{bar}{zap}"), key_slot(b"bar"));
+        assert_ne!(key_slot(b"foo{}{bar}"), key_slot(b"bar"));
+    }
+
+    #[test]
+    fn test_slot_groups() {
+        let keys: Vec<String> = [
+            "piston:a",
+            "piston:b",
+            "{user:1}:x",
+            "{user:1}:y",
+            "piston:a",
+        ]
+        .iter()
+        .map(|key| key.to_string())
+        .collect();
+
+        assert_eq!(
+            slot_groups(RedisTopology::Standalone, &keys),
+            vec![vec![0, 1, 2, 3, 4]]
+        );
+        assert!(slot_groups(RedisTopology::Sentinel, &[]).is_empty());
+
+        let groups = slot_groups(RedisTopology::Cluster, &keys);
+        assert_eq!(groups.iter().map(Vec::len).sum::<usize>(), keys.len());
+        // Hash tags and repeated keys share a group, and a group never
+        // spans two slots
+        assert!(groups.contains(&vec![2, 3]));
+        assert!(
+            groups
+                .iter()
+                .any(|group| group.contains(&0) && group.contains(&4))
+        );
+        for group in &groups {
+            let slot = key_slot(keys[group[0]].as_bytes());
+            assert!(group.iter().all(|&i| key_slot(keys[i].as_bytes()) == slot));
+        }
+    }
+
+    #[test]
+    fn test_failover_errors() {
+        let readonly = RedisError::from((
+            ErrorKind::ReadOnly,
+            "You can't write against a read only replica.",
+        ));
+        assert!(is_failover(&readonly));
+
+        let type_error = RedisError::from((ErrorKind::TypeError, "bad type"));
+        assert!(!is_failover(&type_error));
+    }
 }
@@ -0,0 +1,632 @@
+//! Resilience layer for gRPC calls between services
+//!
+//! [`ResilienceLayer`] wraps a tonic channel so that one slow or failing
+//! dependency cannot tie up its callers:
+//!
+//! - every attempt is bounded by a timeout
+//! - calls that fail before reaching the service (transport errors and
+//!   `UNAVAILABLE`) are retried a bounded number of times, with full-jitter
+//!   exponential backoff, as long as the retry budget allows it. The budget
+//!   earns a fraction of a retry per call, so retries cannot multiply the
+//!   load on a dependency that is already struggling.
+//! - a circuit breaker opens after consecutive failures and rejects calls
+//!   without sending them. Once the open period is over a single probe call
+//!   is let through (half-open); its outcome closes or re-opens the breaker.
+//!
+//! Breaker state is exported as `grpc_client_circuit_state` and call
+//! outcomes as `grpc_client_calls_total`, both labelled by target.
+//!
+//! Retrying requires a second copy of the request, so request bodies are
+//! buffered. The layer is meant for unary and server-streaming methods;
+//! client-streaming calls must use a channel without it.
+
+use crate::metrics::{GRPC_CLIENT_CALLS_TOTAL, GRPC_CLIENT_CIRCUIT_STATE};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use parking_lot::Mutex;
+use rand::Rng;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tonic::body::Body;
+use tonic::{Code, Status};
+use tower::{Layer, Service};
+use tracing::warn;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Resilience settings for calls to one dependency
+#[derive(Debug, Clone)]
+pub struct ResilienceConfig {
+    /// Timeout of a single attempt, until response headers arrive
+    pub attempt_timeout: Duration,
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled for each further retry
+    pub retry_base_delay: Duration,
+    /// Upper bound of the backoff
+    pub retry_max_delay: Duration,
+    /// Retries earned per call
+    pub retry_budget_ratio: f64,
+    /// Retries that can be banked, available from the start
+    pub retry_budget_reserve: f64,
+    /// Consecutive failures that open the breaker
+    pub failure_threshold: u32,
+    /// How long the breaker stays open before letting a probe through
+    pub open_duration: Duration,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            attempt_timeout: Duration::from_secs(10),
+            max_retries: 2,
+            retry_base_delay: Duration::from_millis(50),
+            retry_max_delay: Duration::from_secs(2),
+            retry_budget_ratio: 0.2,
+            retry_budget_reserve: 10.0,
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ResilienceConfig {
+    /// Load from environment variables, keeping defaults for unset ones
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(secs) = env_parse("PISTON_GRPC_ATTEMPT_TIMEOUT_SECS") {
+            config.attempt_timeout = Duration::from_secs(secs);
+        }
+        if let Some(retries) = env_parse("PISTON_GRPC_MAX_RETRIES") {
+            config.max_retries = retries;
+        }
+        if let Some(ratio) = env_parse("PISTON_GRPC_RETRY_BUDGET_RATIO") {
+            config.retry_budget_ratio = ratio;
+        }
+        if let Some(failures) = env_parse("PISTON_GRPC_BREAKER_FAILURES") {
+            config.failure_threshold = failures;
+        }
+        if let Some(secs) = env_parse("PISTON_GRPC_BREAKER_OPEN_SECS") {
+            config.open_duration = Duration::from_secs(secs);
+        }
+        config
+    }
+
+    /// Backoff before retry number `retry` (starting at 0): a random delay
+    /// up to the capped exponential bound
+    fn backoff(&self, retry: u32) -> Duration {
+        let bound = self
+            .retry_base_delay
+            .saturating_mul(1u32 << retry.min(16))
+            .min(self.retry_max_delay);
+        bound.mul_f64(rand::rng().random_range(0.0..=1.0))
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// State of a circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// One probe call is let through to test the dependency
+    HalfOpen,
+    /// Calls are rejected without being sent
+    Open,
+}
+
+impl CircuitState {
+    fn gauge_value(self) -> f64 {
+        match self {
+            Self::Closed => 0.0,
+            Self::HalfOpen => 1.0,
+            Self::Open => 2.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probing: bool },
+}
+
+/// Circuit breaker for one dependency
+#[derive(Debug)]
+struct CircuitBreaker {
+    target: String,
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(target: &str, config: &ResilienceConfig) -> Self {
+        GRPC_CLIENT_CIRCUIT_STATE
+            .with_label_values(&[target])
+            .set(CircuitState::Closed.gauge_value());
+        Self {
+            target: target.to_string(),
+            failure_threshold: config.failure_threshold.max(1),
+            open_duration: config.open_duration,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    fn state(&self) -> CircuitState {
+        match *self.state.lock() {
+            BreakerState::Closed { .. } => CircuitState::Closed,
+            BreakerState::Open { .. } => CircuitState::Open,
+            BreakerState::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether a call may be sent now. Moves an expired open breaker to
+    /// half-open and claims its probe.
+    fn acquire(&self, now: Instant) -> bool {
+        let mut state = self.state.lock();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } if now >= until => {
+                *state = BreakerState::HalfOpen { probing: true };
+                drop(state);
+                self.publish(CircuitState::HalfOpen);
+                true
+            }
+            BreakerState::Open { .. } => false,
+            BreakerState::HalfOpen { ref mut probing } => !std::mem::replace(probing, true),
+        }
+    }
+
+    /// Record the outcome of an acquired call
+    fn record(&self, healthy: bool, now: Instant) {
+        let mut state = self.state.lock();
+        let next = match (&mut *state, healthy) {
+            (BreakerState::Closed { failures }, true) => {
+                *failures = 0;
+                None
+            }
+            (BreakerState::Closed { failures }, false) => {
+                *failures += 1;
+                (*failures >= self.failure_threshold).then_some(CircuitState::Open)
+            }
+            (BreakerState::HalfOpen { .. }, true) => Some(CircuitState::Closed),
+            (BreakerState::HalfOpen { .. }, false) => Some(CircuitState::Open),
+            // Calls sent before the breaker opened
+            (BreakerState::Open { .. }, _) => None,
+        };
+
+        match next {
+            Some(CircuitState::Open) => {
+                *state = BreakerState::Open {
+                    until: now + self.open_duration,
+                };
+                drop(state);
+                warn!(
+                    "Circuit to {} opened for {:?}",
+                    self.target, self.open_duration
+                );
+                self.publish(CircuitState::Open);
+            }
+            Some(CircuitState::Closed) => {
+                *state = BreakerState::Closed { failures: 0 };
+                drop(state);
+                self.publish(CircuitState::Closed);
+            }
+            _ => {}
+        }
+    }
+
+    /// Release a probe whose call was abandoned before it finished, so the
+    /// next call can probe instead
+    fn abandon(&self) {
+        if let BreakerState::HalfOpen { probing } = &mut *self.state.lock() {
+            *probing = false;
+        }
+    }
+
+    fn publish(&self, state: CircuitState) {
+        GRPC_CLIENT_CIRCUIT_STATE
+            .with_label_values(&[&self.target])
+            .set(state.gauge_value());
+    }
+}
+
+/// Retries banked from successful traffic
+#[derive(Debug)]
+struct RetryBudget {
+    ratio: f64,
+    reserve: f64,
+    balance: Mutex<f64>,
+}
+
+impl RetryBudget {
+    fn new(config: &ResilienceConfig) -> Self {
+        Self {
+            ratio: config.retry_budget_ratio,
+            reserve: config.retry_budget_reserve,
+            balance: Mutex::new(config.retry_budget_reserve),
+        }
+    }
+
+    fn deposit(&self) {
+        let mut balance = self.balance.lock();
+        *balance = (*balance + self.ratio).min(self.reserve);
+    }
+
+    fn withdraw(&self) -> bool {
+        let mut balance = self.balance.lock();
+        if *balance >= 1.0 {
+            *balance -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    target: String,
+    config: ResilienceConfig,
+    breaker: CircuitBreaker,
+    budget: RetryBudget,
+}
+
+impl Shared {
+    fn count(&self, outcome: &str) {
+        GRPC_CLIENT_CALLS_TOTAL
+            .with_label_values(&[self.target.as_str(), outcome])
+            .inc();
+    }
+}
+
+/// Tower layer adding timeouts, retries and circuit breaking to calls to
+/// one dependency. Clones share the breaker and retry budget, so the layer
+/// can be kept across reconnections.
+#[derive(Debug, Clone)]
+pub struct ResilienceLayer {
+    shared: Arc<Shared>,
+}
+
+impl ResilienceLayer {
+    /// Create a layer for calls to `target`, the name used in metrics
+    pub fn new(target: &str, config: ResilienceConfig) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                target: target.to_string(),
+                breaker: CircuitBreaker::new(target, &config),
+                budget: RetryBudget::new(&config),
+                config,
+            }),
+        }
+    }
+
+    /// Current breaker state
+    pub fn circuit_state(&self) -> CircuitState {
+        self.shared.breaker.state()
+    }
+}
+
+impl<S> Layer<S> for ResilienceLayer {
+    type Service = Resilient<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Resilient {
+            inner,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Service produced by [`ResilienceLayer`]
+#[derive(Debug, Clone)]
+pub struct Resilient<S> {
+    inner: S,
+    shared: Arc<Shared>,
+}
+
+/// Breaker permit of one attempt; releases a half-open probe if the call
+/// is dropped before its outcome is recorded
+struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    recorded: bool,
+}
+
+impl Permit<'_> {
+    fn record(mut self, healthy: bool) {
+        self.recorded = true;
+        self.breaker.record(healthy, Instant::now());
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.breaker.abandon();
+        }
+    }
+}
+
+/// Outcome of one attempt
+struct Attempt<T> {
+    result: Result<T, BoxError>,
+    /// Whether the dependency answered in time without an availability
+    /// error
+    healthy: bool,
+    /// Whether the request can be sent again without being applied twice
+    retryable: bool,
+}
+
+/// Status codes that say the dependency is unhealthy, as opposed to
+/// rejecting this particular request
+fn unhealthy(code: Code) -> bool {
+    matches!(
+        code,
+        Code::Unavailable | Code::DeadlineExceeded | Code::ResourceExhausted
+    )
+}
+
+impl<S, B> Service<http::Request<Body>> for Resilient<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    B: Send + 'static,
+{
+    type Response = http::Response<B>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        // The service polled ready is the one that must take the call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let shared = self.shared.clone();
+
+        Box::pin(async move {
+            let config = &shared.config;
+            shared.budget.deposit();
+
+            let (parts, body) = request.into_parts();
+            let body = body.collect().await?.to_bytes();
+
+            let mut retry = 0;
+            loop {
+                if !shared.breaker.acquire(Instant::now()) {
+                    shared.count("rejected");
+                    return Err(Status::unavailable(format!(
+                        "circuit to {} is open",
+                        shared.target
+                    ))
+                    .into());
+                }
+                let permit = Permit {
+                    breaker: &shared.breaker,
+                    recorded: false,
+                };
+
+                if retry > 0 {
+                    std::future::poll_fn(|cx| inner.poll_ready(cx))
+                        .await
+                        .map_err(Into::into)?;
+                }
+                let request = http::Request::from_parts(
+                    parts.clone(),
+                    Body::new(Full::<Bytes>::new(body.clone())),
+                );
+                let attempt = send(&mut inner, request, config.attempt_timeout).await;
+                permit.record(attempt.healthy);
+
+                if attempt.healthy {
+                    shared.count("ok");
+                    return attempt.result;
+                }
+                if !attempt.retryable || retry >= config.max_retries {
+                    shared.count("failed");
+                    return attempt.result;
+                }
+                if !shared.budget.withdraw() {
+                    shared.count("budget_exhausted");
+                    return attempt.result;
+                }
+
+                shared.count("retried");
+                tokio::time::sleep(config.backoff(retry)).await;
+                retry += 1;
+            }
+        })
+    }
+}
+
+async fn send<S, B>(
+    inner: &mut S,
+    request: http::Request<Body>,
+    timeout: Duration,
+) -> Attempt<http::Response<B>>
+where
+    S: Service<http::Request<Body>, Response = http::Response<B>>,
+    S::Error: Into<BoxError>,
+{
+    match tokio::time::timeout(timeout, inner.call(request)).await {
+        Ok(Ok(response)) => {
+            // Errors without a message body arrive as trailers-only
+            // responses, with the status in the headers
+            let code = Status::from_header_map(response.headers()).map(|status| status.code());
+            Attempt {
+                healthy: !code.is_some_and(unhealthy),
+                retryable: code == Some(Code::Unavailable),
+                result: Ok(response),
+            }
+        }
+        // Transport errors: the connection could not be used
+        Ok(Err(e)) => Attempt {
+            result: Err(e.into()),
+            healthy: false,
+            retryable: true,
+        },
+        // The call may still be processed, so it is not retried
+        Err(_) => Attempt {
+            result: Err(
+                Status::deadline_exceeded(format!("no response within {:?}", timeout)).into(),
+            ),
+            healthy: false,
+            retryable: false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn config() -> ResilienceConfig {
+        ResilienceConfig {
+            retry_base_delay: Duration::from_millis(1),
+            retry_max_delay: Duration::from_millis(1),
+            failure_threshold: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_breaker_opens_and_probes() {
+        let breaker = CircuitBreaker::new("test-breaker", &config());
+        let start = Instant::now();
+
+        for _ in 0..2 {
+            assert!(breaker.acquire(start));
+            breaker.record(false, start);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record(false, start);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.acquire(start));
+
+        // After the open period one probe goes through, others wait for it
+        let later = start + Duration::from_secs(31);
+        assert!(breaker.acquire(later));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.acquire(later));
+
+        // A failed probe re-opens, a successful one closes
+        breaker.record(false, later);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        let much_later = later + Duration::from_secs(31);
+        assert!(breaker.acquire(much_later));
+        breaker.record(true, much_later);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // An abandoned probe frees the slot for the next call
+        let mut state = breaker.state.lock();
+        *state = BreakerState::HalfOpen { probing: true };
+        drop(state);
+        breaker.abandon();
+        assert!(breaker.acquire(much_later));
+    }
+
+    #[test]
+    fn test_retry_budget() {
+        let budget = RetryBudget::new(&ResilienceConfig {
+            retry_budget_ratio: 0.5,
+            retry_budget_reserve: 1.0,
+            ..Default::default()
+        });
+        assert!(budget.withdraw());
+        assert!(!budget.withdraw());
+        budget.deposit();
+        assert!(!budget.withdraw());
+        budget.deposit();
+        assert!(budget.withdraw());
+    }
+
+    #[test]
+    fn test_backoff_bounds() {
+        let config = ResilienceConfig {
+            retry_base_delay: Duration::from_millis(100),
+            retry_max_delay: Duration::from_millis(300),
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            assert!(config.backoff(0) <= Duration::from_millis(100));
+            assert!(config.backoff(1) <= Duration::from_millis(200));
+            assert!(config.backoff(10) <= Duration::from_millis(300));
+        }
+    }
+
+    /// Answers with `UNAVAILABLE` for the first `failures` calls
+    #[derive(Clone)]
+    struct Flaky {
+        calls: Arc<AtomicU32>,
+        failures: u32,
+    }
+
+    impl Service<http::Request<Body>> for Flaky {
+        type Response = http::Response<()>;
+        type Error = BoxError;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: http::Request<Body>) -> Self::Future {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            let failures = self.failures;
+            Box::pin(async move {
+                let mut response = http::Response::new(());
+                if call < failures {
+                    response
+                        .headers_mut()
+                        .insert("grpc-status", http::HeaderValue::from_static("14"));
+                }
+                Ok(response)
+            })
+        }
+    }
+
+    fn request() -> http::Request<Body> {
+        http::Request::new(Body::new(Full::new(Bytes::from_static(b"payload"))))
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_rejects() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let layer = ResilienceLayer::new("test-flaky", config());
+        let mut service = layer.layer(Flaky {
+            calls: calls.clone(),
+            failures: 2,
+        });
+
+        // Two UNAVAILABLE answers are retried away
+        let response = service.call(request()).await.unwrap();
+        assert!(Status::from_header_map(response.headers()).is_none());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(layer.circuit_state(), CircuitState::Closed);
+
+        // A dependency that keeps failing opens the breaker, after which
+        // calls are rejected without being sent
+        let mut service = layer.layer(Flaky {
+            calls: calls.clone(),
+            failures: u32::MAX,
+        });
+        let _ = service.call(request()).await;
+        assert_eq!(layer.circuit_state(), CircuitState::Open);
+        let sent = calls.load(Ordering::SeqCst);
+        let error = service.call(request()).await.unwrap_err();
+        assert_eq!(Status::from_error(error).code(), Code::Unavailable);
+        assert_eq!(calls.load(Ordering::SeqCst), sent);
+    }
+}
@@ -0,0 +1,96 @@
+//! Restart modes
+//!
+//! When a popular game server restarts, thousands of players reconnect at
+//! once and look like a SYN or login flood. A backend in restart mode gets
+//! its per-source limits multiplied so the reconnect wave gets through,
+//! while protocol validation runs at least at the high protection level so
+//! floods hiding in the wave still fail the handshake checks. The gateway
+//! stores a `RestartMode` under the backend's restart key and adds the
+//! backend to the active restart set. Workers stop applying it on their own
+//! once it ended, even if the gateway never removes it. Both sides use a
+//! cache prefix of `piston`.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Set of the backends in restart mode
+pub const ACTIVE_RESTARTS_KEY: &str = "restart_modes:active";
+
+/// Time a restart mode is kept past its end, in case the gateway does not
+/// remove it
+pub const RESTART_TTL_MARGIN: Duration = Duration::from_secs(3600);
+
+/// Lowest protection level during a restart (`ProtectionLevel::High`)
+pub const RESTART_PROTECTION_LEVEL: u8 = 4;
+
+/// Key of the restart mode of a backend
+pub fn restart_mode_key(backend_id: &str) -> String {
+    format!("restart_mode:{}", backend_id)
+}
+
+/// Backend in restart mode until a time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestartMode {
+    pub backend_id: String,
+    /// Factor applied to the per-source packet rate and burst
+    pub connection_multiplier: u32,
+    pub reason: String,
+    pub started_by: String,
+    /// Unix seconds
+    pub started_at: i64,
+    /// Unix seconds
+    pub ends_at: i64,
+}
+
+impl RestartMode {
+    /// Whether the restart mode has ended
+    pub fn expired(&self, now: i64) -> bool {
+        now >= self.ends_at
+    }
+
+    /// Relaxed per-source limit, 0 staying 0
+    pub fn relax(&self, limit: u64) -> u64 {
+        limit.saturating_mul(u64::from(self.connection_multiplier.max(1)))
+    }
+
+    /// Protection level during the restart
+    pub fn protection_level(&self, configured: u8) -> u8 {
+        configured.max(RESTART_PROTECTION_LEVEL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restart(connection_multiplier: u32) -> RestartMode {
+        RestartMode {
+            backend_id: "backend-1".to_string(),
+            connection_multiplier,
+            reason: String::new(),
+            started_by: "user-1".to_string(),
+            started_at: 1000,
+            ends_at: 1600,
+        }
+    }
+
+    #[test]
+    fn test_relax() {
+        assert_eq!(restart(5).relax(1000), 5000);
+        assert_eq!(restart(5).relax(0), 0);
+        assert_eq!(restart(0).relax(1000), 1000);
+        assert_eq!(restart(5).relax(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_protection_level_only_rises() {
+        assert_eq!(restart(5).protection_level(2), RESTART_PROTECTION_LEVEL);
+        assert_eq!(restart(5).protection_level(5), 5);
+    }
+
+    #[test]
+    fn test_expired() {
+        assert!(!restart(5).expired(1599));
+        assert!(restart(5).expired(1600));
+    }
+}
@@ -0,0 +1,247 @@
+//! Artifact signatures
+//!
+//! Files distributed to workers are signed with ed25519 detached signatures:
+//! the signature of `<file>` is kept next to it in `<file>.sig`, base64
+//! encoded. Workers trust a set of public keys (base64 encoded, 32 bytes)
+//! and accept a file signed by any of them, so a signing key is rotated by
+//! trusting the old and the new key for a while.
+
+use crate::error::{Error, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ring::rand::SystemRandom;
+use ring::signature::{ED25519, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Extension of detached signature files
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// Length of an ed25519 public key
+pub const PUBLIC_KEY_LEN: usize = 32;
+
+/// Length of an ed25519 signature
+pub const SIGNATURE_LEN: usize = 64;
+
+/// Path of the detached signature of `path`
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Read `path` and its detached signature, if there is one
+pub fn read_signed(path: &Path) -> Result<(Vec<u8>, Option<String>)> {
+    let content = std::fs::read(path)
+        .map_err(|e| Error::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+    let sig_path = signature_path(path);
+    let signature = match std::fs::read_to_string(&sig_path) {
+        Ok(signature) => Some(signature),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(Error::Internal(format!(
+                "Failed to read {}: {}",
+                sig_path.display(),
+                e
+            )));
+        }
+    };
+    Ok((content, signature))
+}
+
+/// Trusted ed25519 public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey([u8; PUBLIC_KEY_LEN]);
+
+impl PublicKey {
+    /// Parse a base64 encoded key
+    pub fn parse(encoded: &str) -> Result<Self> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| Error::validation(format!("Invalid public key encoding: {}", e)))?;
+        let key = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            Error::validation(format!(
+                "Public key must be {} bytes, got {}",
+                PUBLIC_KEY_LEN,
+                bytes.len()
+            ))
+        })?;
+        Ok(Self(key))
+    }
+
+    /// Short fingerprint of the key, for logs and status reports
+    pub fn id(&self) -> String {
+        hex_prefix(&Sha256::digest(self.0))
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        UnparsedPublicKey::new(&ED25519, &self.0)
+            .verify(message, signature)
+            .is_ok()
+    }
+}
+
+fn hex_prefix(digest: &[u8]) -> String {
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Public keys whose signatures are accepted
+#[derive(Debug, Clone, Default)]
+pub struct TrustedKeys {
+    keys: Vec<PublicKey>,
+}
+
+impl TrustedKeys {
+    pub fn new(keys: Vec<PublicKey>) -> Self {
+        Self { keys }
+    }
+
+    /// Parse a comma separated list of base64 encoded keys
+    pub fn parse(list: &str) -> Result<Self> {
+        let keys = list
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(PublicKey::parse)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Keys listed in the environment variable `var`, none if it is unset
+    pub fn from_env(var: &str) -> Result<Self> {
+        match std::env::var(var) {
+            Ok(list) => Self::parse(&list),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Fingerprints of the trusted keys
+    pub fn ids(&self) -> Vec<String> {
+        self.keys.iter().map(PublicKey::id).collect()
+    }
+
+    /// Check the detached `signature` (base64) of `message`
+    ///
+    /// Returns the fingerprint of the key that made the signature.
+    pub fn verify(&self, message: &[u8], signature: &str) -> Result<String> {
+        if self.keys.is_empty() {
+            return Err(Error::Unauthorized(
+                "No trusted signing keys configured".to_string(),
+            ));
+        }
+        let signature = STANDARD
+            .decode(signature.trim())
+            .map_err(|e| Error::Unauthorized(format!("Invalid signature encoding: {}", e)))?;
+        if signature.len() != SIGNATURE_LEN {
+            return Err(Error::Unauthorized(format!(
+                "Signature must be {} bytes, got {}",
+                SIGNATURE_LEN,
+                signature.len()
+            )));
+        }
+
+        self.keys
+            .iter()
+            .find(|key| key.verify(message, &signature))
+            .map(PublicKey::id)
+            .ok_or_else(|| {
+                Error::Unauthorized("Signature does not match any trusted key".to_string())
+            })
+    }
+
+    /// Read `path` and check it against its detached signature file
+    ///
+    /// Returns the content of the file and the fingerprint of the signing key.
+    pub fn verify_file(&self, path: &Path) -> Result<(Vec<u8>, String)> {
+        let content = std::fs::read(path)
+            .map_err(|e| Error::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+        let sig_path = signature_path(path);
+        let signature = std::fs::read_to_string(&sig_path).map_err(|e| {
+            Error::Unauthorized(format!("Missing signature {}: {}", sig_path.display(), e))
+        })?;
+        let key_id = self.verify(&content, &signature)?;
+        Ok((content, key_id))
+    }
+}
+
+/// ed25519 signing key, for the tools producing signed artifacts
+pub struct SigningKey(Ed25519KeyPair);
+
+impl SigningKey {
+    /// Generate a key, returned with its PKCS#8 encoding to store it
+    pub fn generate() -> Result<(Self, Vec<u8>)> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| Error::Internal("Failed to generate signing key".to_string()))?;
+        let key = Self::from_pkcs8(pkcs8.as_ref())?;
+        Ok((key, pkcs8.as_ref().to_vec()))
+    }
+
+    /// Load a PKCS#8 encoded key
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        Ed25519KeyPair::from_pkcs8(pkcs8)
+            .map(Self)
+            .map_err(|e| Error::validation(format!("Invalid signing key: {}", e)))
+    }
+
+    /// Public key to trust, base64 encoded
+    pub fn public_key(&self) -> String {
+        STANDARD.encode(self.0.public_key().as_ref())
+    }
+
+    /// Detached signature of `message`, base64 encoded
+    pub fn sign(&self, message: &[u8]) -> String {
+        STANDARD.encode(self.0.sign(message).as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let (signer, pkcs8) = SigningKey::generate().unwrap();
+        let (other, _) = SigningKey::generate().unwrap();
+        let keys = TrustedKeys::parse(&format!("{}, {}", other.public_key(), signer.public_key()))
+            .unwrap();
+        assert_eq!(keys.ids().len(), 2);
+
+        let signature = signer.sign(b"bundle");
+        assert_eq!(keys.verify(b"bundle", &signature).unwrap(), keys.ids()[1]);
+
+        // A stored key signs the same way
+        let reloaded = SigningKey::from_pkcs8(&pkcs8).unwrap();
+        assert_eq!(reloaded.sign(b"bundle"), signature);
+
+        // Tampered content, foreign keys and garbage are all refused
+        assert!(keys.verify(b"bundle!", &signature).is_err());
+        let only_other = TrustedKeys::parse(&other.public_key()).unwrap();
+        assert!(only_other.verify(b"bundle", &signature).is_err());
+        assert!(keys.verify(b"bundle", "not base64!").is_err());
+        assert!(
+            TrustedKeys::default()
+                .verify(b"bundle", &signature)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_keys() {
+        assert!(TrustedKeys::parse("").unwrap().is_empty());
+        assert!(TrustedKeys::parse("AAAA").is_err());
+        assert!(PublicKey::parse("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_signature_path() {
+        assert_eq!(
+            signature_path(Path::new("/var/lib/piston/bundle-3.json")),
+            PathBuf::from("/var/lib/piston/bundle-3.json.sig")
+        );
+    }
+}
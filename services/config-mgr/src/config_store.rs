@@ -3,8 +3,8 @@
 //! Provides persistent storage for filter configurations with versioning,
 //! validation, and caching support.
 
-use deadpool_redis::Pool as RedisPool;
 use parking_lot::RwLock;
+use pistonprotection_common::redis::RedisPool;
 use pistonprotection_common::{
     error::{Error, Result},
     redis::CacheService,
@@ -158,8 +158,11 @@ impl ConfigStore {
                 log_sampling_rate: 100,
                 emergency_mode: false,
                 emergency_pps_threshold: 1_000_000,
+                penalty_ladder: vec![],
             }),
             generated_at: Some(chrono::Utc::now().into()),
+            canary: None,
+            programs: vec![],
         };
 
         // Cache the config
@@ -177,7 +180,7 @@ impl ConfigStore {
     async fn load_all_backends(&self) -> Result<Vec<BackendFilter>> {
         let rows = sqlx::query(
             r#"
-            SELECT b.id, b.type, b.protection_settings,
+            SELECT b.id, b.organization_id, b.type, b.protection_settings,
                    array_agg(DISTINCT o.ip_address) as origin_ips,
                    array_agg(DISTINCT o.port) as origin_ports
             FROM backends b
@@ -195,8 +198,9 @@ impl ConfigStore {
             let backend_id: String = row.get("id");
             let protection_json: Option<serde_json::Value> = row.get("protection_settings");
 
-            // Load filter rules for this backend
+            // Load filter rules and hostnames for this backend
             let rules = self.load_backend_rules(&backend_id).await?;
+            let domains = self.load_backend_domains(&backend_id).await?;
 
             let backend_filter = BackendFilter {
                 backend_id,
@@ -205,6 +209,8 @@ impl ConfigStore {
                 protocol: row.get::<i32, _>("type"),
                 protection: protection_json.and_then(|v| serde_json::from_value(v).ok()),
                 rules,
+                organization_id: row.get("organization_id"),
+                domains,
             };
 
             backends.push(backend_filter);
@@ -256,6 +262,18 @@ impl ConfigStore {
         Ok(rules)
     }
 
+    /// Load the hostnames of a specific backend
+    async fn load_backend_domains(&self, backend_id: &str) -> Result<Vec<String>> {
+        let domains = sqlx::query_scalar(
+            "SELECT domain FROM backend_domains WHERE backend_id = $1 ORDER BY domain",
+        )
+        .bind(backend_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(domains)
+    }
+
     /// Mark configuration as updated (increment version)
     pub async fn mark_updated(&self, backend_id: &str) -> Result<u32> {
         let new_version = self.next_version();
@@ -271,26 +289,84 @@ impl ConfigStore {
                 .await;
         }
 
-        // Store version in database
+        self.record_version(new_version, Some(backend_id)).await?;
+
+        Ok(new_version)
+    }
+
+    /// Mark per-worker assignments (backend placement, program versions) as
+    /// changed so workers refetch their configuration
+    pub async fn mark_assignments_changed(&self, reason: &str) -> Result<u32> {
+        let new_version = self.next_version();
+
+        info!(version = new_version, reason = %reason, "Worker assignments changed");
+
+        if let Some(ref cache) = self.cache {
+            let _ = cache.delete_pattern("filter_config:*").await;
+        }
+
+        self.record_version(new_version, None).await?;
+
+        Ok(new_version)
+    }
+
+    /// Store a version in the database
+    async fn record_version(&self, version: u32, backend_id: Option<&str>) -> Result<()> {
         sqlx::query(
             r#"
             INSERT INTO config_versions (version, backend_id, created_at)
             VALUES ($1, $2, now())
             "#,
         )
-        .bind(new_version as i32)
+        .bind(version as i32)
         .bind(backend_id)
         .execute(&self.db)
         .await?;
 
-        Ok(new_version)
+        Ok(())
+    }
+
+    /// Add traffic reported by a worker to the bandwidth usage of a backend
+    /// in the current UTC month
+    ///
+    /// Reports for backends that no longer exist are ignored.
+    pub async fn record_bandwidth(
+        &self,
+        backend_id: &str,
+        packets_in: u64,
+        bytes_in: u64,
+        bytes_dropped: u64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO backend_bandwidth_usage (
+                backend_id, period_start, packets_in, bytes_in, bytes_dropped, updated_at
+            )
+            SELECT id, date_trunc('month', now() AT TIME ZONE 'UTC')::date, $2, $3, $4, now()
+            FROM backends
+            WHERE id = $1
+            ON CONFLICT (backend_id, period_start) DO UPDATE
+            SET packets_in = backend_bandwidth_usage.packets_in + EXCLUDED.packets_in,
+                bytes_in = backend_bandwidth_usage.bytes_in + EXCLUDED.bytes_in,
+                bytes_dropped = backend_bandwidth_usage.bytes_dropped + EXCLUDED.bytes_dropped,
+                updated_at = EXCLUDED.updated_at
+            "#,
+        )
+        .bind(backend_id)
+        .bind(packets_in.min(i64::MAX as u64) as i64)
+        .bind(bytes_in.min(i64::MAX as u64) as i64)
+        .bind(bytes_dropped.min(i64::MAX as u64) as i64)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
     }
 
     /// Get configuration for a specific backend
     pub async fn get_backend_config(&self, backend_id: &str) -> Result<BackendFilter> {
         let row = sqlx::query(
             r#"
-            SELECT b.id, b.type, b.protection_settings
+            SELECT b.id, b.organization_id, b.type, b.protection_settings
             FROM backends b
             WHERE b.id = $1 AND b.deleted_at IS NULL
             "#,
@@ -301,6 +377,7 @@ impl ConfigStore {
         .ok_or_else(|| Error::not_found("Backend", backend_id))?;
 
         let rules = self.load_backend_rules(backend_id).await?;
+        let domains = self.load_backend_domains(backend_id).await?;
         let protection_json: Option<serde_json::Value> = row.get("protection_settings");
 
         Ok(BackendFilter {
@@ -310,6 +387,8 @@ impl ConfigStore {
             protocol: row.get::<i32, _>("type"),
             protection: protection_json.and_then(|v| serde_json::from_value(v).ok()),
             rules,
+            organization_id: row.get("organization_id"),
+            domains,
         })
     }
 
@@ -407,6 +486,94 @@ impl ConfigStore {
                     });
                 }
             }
+
+            // Honeypot ports must be valid and never carry the backend's own traffic
+            if let Some(ref honeypot) = protection.honeypot {
+                for &port in &honeypot.ports {
+                    let message = if port == 0 || port > u16::MAX as u32 {
+                        format!("Invalid honeypot port {}", port)
+                    } else if backend
+                        .destination_ports
+                        .iter()
+                        .any(|range| range.start <= port && port <= range.end)
+                    {
+                        format!(
+                            "Honeypot port {} is a destination port of the backend",
+                            port
+                        )
+                    } else {
+                        continue;
+                    };
+                    errors.push(ValidationError {
+                        field: format!("backends[{}].protection.honeypot", backend.backend_id),
+                        message,
+                        severity: ValidationSeverity::Error,
+                    });
+                }
+            }
+
+            // Learning needs a training window and must relax, not tighten, limits
+            if let Some(ref learning) = protection.learning {
+                let message = if !learning.enabled {
+                    None
+                } else if learning.training_seconds == 0 {
+                    Some("Learning training window cannot be 0 seconds".to_string())
+                } else if learning.limit_multiplier == 0 {
+                    Some("Learning limit multiplier cannot be 0".to_string())
+                } else {
+                    None
+                };
+                if let Some(message) = message {
+                    errors.push(ValidationError {
+                        field: format!("backends[{}].protection.learning", backend.backend_id),
+                        message,
+                        severity: ValidationSeverity::Error,
+                    });
+                }
+            }
+
+            // Kick messages are sent to clients as JSON text components
+            if let Some(ref block_responses) = protection.block_responses {
+                let kick = &block_responses.minecraft_kick_message;
+                if !kick.is_empty() && serde_json::from_str::<serde_json::Value>(kick).is_err() {
+                    errors.push(ValidationError {
+                        field: format!(
+                            "backends[{}].protection.block_responses",
+                            backend.backend_id
+                        ),
+                        message: "Minecraft kick message is not valid JSON".to_string(),
+                        severity: ValidationSeverity::Error,
+                    });
+                }
+            }
+
+            // A burst below one full-size frame drops every large packet
+            if let Some(ref connection_rate) = protection.connection_rate {
+                if connection_rate.bytes_per_second != 0
+                    && connection_rate.burst_bytes != 0
+                    && connection_rate.burst_bytes < 1514
+                {
+                    errors.push(ValidationError {
+                        field: format!(
+                            "backends[{}].protection.connection_rate",
+                            backend.backend_id
+                        ),
+                        message: "Connection burst is smaller than a full-size frame".to_string(),
+                        severity: ValidationSeverity::Warning,
+                    });
+                }
+            }
+
+            // DSCP is a 6-bit field; workers clamp larger values
+            if let Some(ref marking) = protection.marking {
+                if marking.enabled && marking.dscp > 63 {
+                    errors.push(ValidationError {
+                        field: format!("backends[{}].protection.marking", backend.backend_id),
+                        message: format!("DSCP {} is out of range (0-63)", marking.dscp),
+                        severity: ValidationSeverity::Error,
+                    });
+                }
+            }
         }
 
         // Validate filter rules
@@ -490,6 +657,47 @@ impl ConfigStore {
             });
         }
 
+        let mut seen_levels = std::collections::HashSet::new();
+        for (i, level) in settings.penalty_ladder.iter().enumerate() {
+            let field = format!("global.penalty_ladder[{}]", i);
+            let mut error = |message: String| {
+                errors.push(ValidationError {
+                    field: field.clone(),
+                    message,
+                    severity: ValidationSeverity::Error,
+                });
+            };
+
+            if level.protection_level > 4 {
+                error(format!(
+                    "Unknown protection level {}",
+                    level.protection_level
+                ));
+            } else if !seen_levels.insert(level.protection_level) {
+                error(format!(
+                    "Duplicate ladder for protection level {}",
+                    level.protection_level
+                ));
+            }
+            if level.rate_limit_divisor == 0 {
+                error("Rate limit divisor must be at least 1".to_string());
+            }
+            if level.rate_limit_threshold == 0
+                || level.rate_limit_threshold > level.short_block_threshold
+                || level.short_block_threshold > level.long_block_threshold
+            {
+                error(
+                    "Thresholds must satisfy 1 <= rate limit <= short block <= long block"
+                        .to_string(),
+                );
+            }
+            if level.short_block_seconds == 0
+                || level.long_block_seconds < level.short_block_seconds
+            {
+                error("Block durations must satisfy 0 < short block <= long block".to_string());
+            }
+        }
+
         errors
     }
 
@@ -1,26 +1,20 @@
 //! Configuration distribution to workers
 
 use crate::config_store::ConfigStore;
-use deadpool_redis::Pool as RedisPool;
+use crate::registry::{HeartbeatOutcome, RegisteredWorker, WorkerRegistry};
+use crate::rollout::{ProgramRollout, RolloutManager, RolloutObservations};
 use parking_lot::RwLock;
+use pistonprotection_common::redis::RedisPool;
 use pistonprotection_common::{error::Result, redis::CacheService};
-use pistonprotection_proto::worker::FilterConfig;
-use std::collections::HashMap;
+use pistonprotection_proto::worker::{
+    FilterConfig, ProgramVersion, RolloutSpec, Worker, WorkerLoadDiagnostic, WorkerMetrics,
+    WorkerStatus,
+};
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tokio::time::{Duration, interval};
 use tracing::{debug, info, warn};
 
-/// Worker registration info
-#[derive(Debug, Clone)]
-pub struct RegisteredWorker {
-    pub worker_id: String,
-    pub node_name: String,
-    pub interfaces: Vec<String>,
-    pub last_heartbeat: chrono::DateTime<chrono::Utc>,
-    pub config_version: u32,
-}
-
 /// Configuration update notification
 #[derive(Debug, Clone)]
 pub struct ConfigUpdate {
@@ -32,20 +26,24 @@ pub struct ConfigUpdate {
 pub struct ConfigDistributor {
     store: Arc<ConfigStore>,
     cache: Option<CacheService>,
-    workers: RwLock<HashMap<String, RegisteredWorker>>,
+    registry: RwLock<WorkerRegistry>,
+    rollouts: RwLock<RolloutManager>,
     /// Broadcast channel for config updates
     config_tx: broadcast::Sender<ConfigUpdate>,
 }
 
 impl ConfigDistributor {
-    pub fn new(store: Arc<ConfigStore>, redis: Option<RedisPool>) -> Self {
+    /// `backend_replicas` is the number of workers each backend is placed on
+    /// (0 = every worker protects every backend)
+    pub fn new(store: Arc<ConfigStore>, redis: Option<RedisPool>, backend_replicas: usize) -> Self {
         let cache = redis.map(|pool| CacheService::new(pool, "piston:workers"));
         let (config_tx, _) = broadcast::channel(16);
 
         Self {
             store,
             cache,
-            workers: RwLock::new(HashMap::new()),
+            registry: RwLock::new(WorkerRegistry::new(backend_replicas)),
+            rollouts: RwLock::new(RolloutManager::new()),
             config_tx,
         }
     }
@@ -64,84 +62,268 @@ impl ConfigDistributor {
     }
 
     /// Register a worker
-    pub fn register_worker(&self, worker_id: String, node_name: String, interfaces: Vec<String>) {
+    pub async fn register_worker(&self, worker_id: String, worker: Worker) {
         info!(
             worker_id = %worker_id,
-            node_name = %node_name,
+            node_name = %worker.node_name,
+            region = %worker.region,
             "Worker registered"
         );
 
-        self.workers.write().insert(
-            worker_id.clone(),
-            RegisteredWorker {
-                worker_id,
-                node_name,
-                interfaces,
-                last_heartbeat: chrono::Utc::now(),
-                config_version: 0,
-            },
-        );
+        let worker = RegisteredWorker::from_proto(worker_id.clone(), worker, chrono::Utc::now());
+        let changed = self.registry.write().register(worker);
+
+        // The new worker gets its assignment with its initial config
+        if changed.iter().any(|id| *id != worker_id) {
+            self.placement_changed().await;
+        }
     }
 
     /// Deregister a worker
-    pub fn deregister_worker(&self, worker_id: &str) {
-        if self.workers.write().remove(worker_id).is_some() {
+    pub async fn deregister_worker(&self, worker_id: &str) {
+        let changed = self.registry.write().deregister(worker_id);
+        if let Some(changed) = changed {
             info!(worker_id = %worker_id, "Worker deregistered");
+            if !changed.is_empty() {
+                self.placement_changed().await;
+            }
         }
     }
 
     /// Update worker heartbeat
-    pub fn update_heartbeat(&self, worker_id: &str, config_version: u32) {
-        if let Some(worker) = self.workers.write().get_mut(worker_id) {
-            worker.last_heartbeat = chrono::Utc::now();
-            worker.config_version = config_version;
-            debug!(worker_id = %worker_id, "Heartbeat updated");
+    pub async fn update_heartbeat(
+        &self,
+        worker_id: &str,
+        status: WorkerStatus,
+        config_version: u32,
+    ) -> HeartbeatOutcome {
+        let outcome =
+            self.registry
+                .write()
+                .heartbeat(worker_id, status, config_version, chrono::Utc::now());
+
+        match outcome {
+            HeartbeatOutcome::Unknown => {
+                debug!(worker_id = %worker_id, "Heartbeat from unknown worker");
+            }
+            HeartbeatOutcome::Unchanged => {
+                debug!(worker_id = %worker_id, "Heartbeat updated");
+            }
+            HeartbeatOutcome::CameOnline => {
+                info!(worker_id = %worker_id, "Worker back online");
+                self.placement_changed().await;
+            }
+            HeartbeatOutcome::WentOffline => {
+                info!(worker_id = %worker_id, status = ?status, "Worker unavailable");
+                self.placement_changed().await;
+            }
         }
+
+        outcome
+    }
+
+    /// Record the program versions and XDP counters a worker reported
+    pub fn record_worker_programs(
+        &self,
+        worker_id: &str,
+        programs: Vec<ProgramVersion>,
+        metrics: Option<&WorkerMetrics>,
+    ) {
+        let (xdp_pass, xdp_drop) = metrics
+            .map(|m| {
+                m.interfaces.iter().fold((0, 0), |(pass, drop), i| {
+                    (pass + i.xdp_pass, drop + i.xdp_drop)
+                })
+            })
+            .unwrap_or_default();
+
+        self.registry
+            .write()
+            .record_programs(worker_id, programs, xdp_pass, xdp_drop);
+    }
+
+    /// Get a registered worker
+    pub fn get_worker(&self, worker_id: &str) -> Option<RegisteredWorker> {
+        self.registry.read().get(worker_id).cloned()
     }
 
     /// Get list of all workers
     pub fn list_workers(&self) -> Vec<RegisteredWorker> {
-        self.workers.read().values().cloned().collect()
+        self.registry.read().list(None, None)
+    }
+
+    /// Load failures reported by workers, optionally for one worker and/or
+    /// program
+    pub fn load_diagnostics(
+        &self,
+        worker_id: Option<&str>,
+        program: Option<&str>,
+    ) -> Vec<WorkerLoadDiagnostic> {
+        self.registry
+            .read()
+            .list(None, None)
+            .iter()
+            .filter(|w| worker_id.is_none_or(|id| w.worker_id == id))
+            .flat_map(|w| w.load_diagnostics(program))
+            .collect()
+    }
+
+    /// Get workers in a region and/or with a status
+    pub fn find_workers(
+        &self,
+        region: Option<&str>,
+        status: Option<WorkerStatus>,
+    ) -> Vec<RegisteredWorker> {
+        self.registry.read().list(region, status)
     }
 
     /// Get workers that need configuration updates
     pub fn get_outdated_workers(&self) -> Vec<RegisteredWorker> {
         let current_version = self.store.current_version();
 
-        self.workers
+        self.registry
             .read()
-            .values()
-            .filter(|w| w.config_version < current_version)
-            .cloned()
+            .list(None, None)
+            .into_iter()
+            .filter(|w| w.is_online() && w.config_version < current_version)
             .collect()
     }
 
-    /// Clean up stale workers (no heartbeat for > 60 seconds)
-    pub fn cleanup_stale_workers(&self) {
-        let now = chrono::Utc::now();
-        let stale_threshold = chrono::Duration::seconds(60);
+    /// Mark workers without recent heartbeats offline and reschedule their backends
+    pub async fn cleanup_stale_workers(&self) {
+        let offline = self.registry.write().mark_stale(chrono::Utc::now());
 
-        let stale_workers: Vec<String> = self
-            .workers
-            .read()
+        for worker_id in &offline {
+            warn!(worker_id = %worker_id, "Worker missed heartbeats, marked offline");
+        }
+        if !offline.is_empty() {
+            self.placement_changed().await;
+        }
+    }
+
+    /// Refresh the set of backends to place from the current configuration
+    pub async fn refresh_backends(&self) -> Result<()> {
+        let config = self.store.generate_config().await?;
+        self.update_backends(&config).await;
+        Ok(())
+    }
+
+    async fn update_backends(&self, config: &FilterConfig) {
+        let backend_ids = config
+            .backends
             .iter()
-            .filter(|(_, w)| now - w.last_heartbeat > stale_threshold)
-            .map(|(id, _)| id.clone())
+            .map(|b| b.backend_id.clone())
             .collect();
+        let changed = self.registry.write().set_backends(backend_ids);
+        if !changed.is_empty() {
+            self.placement_changed().await;
+        }
+    }
 
-        let mut workers = self.workers.write();
-        for worker_id in stale_workers {
-            if workers.remove(&worker_id).is_some() {
-                warn!(worker_id = %worker_id, "Removed stale worker");
-            }
+    /// Bump the config version so workers pick up their new assignments
+    async fn placement_changed(&self) {
+        if self.registry.read().replicas() == 0 {
+            // Every worker gets every backend, so placement is not part of the config
+            return;
+        }
+
+        self.assignments_changed("Backend placement").await;
+    }
+
+    /// Bump the config version so workers pick up per-worker changes
+    async fn assignments_changed(&self, reason: &str) {
+        match self.store.mark_assignments_changed(reason).await {
+            Ok(version) => self.notify_update(version, None),
+            Err(e) => warn!(error = %e, reason = %reason, "Failed to record assignment change"),
+        }
+    }
+
+    /// Start a program rollout
+    pub async fn start_rollout(&self, spec: RolloutSpec) -> Result<ProgramRollout> {
+        let rollout = self.rollouts.write().start(spec, chrono::Utc::now())?;
+        info!(
+            rollout_id = %rollout.id,
+            program = %rollout.spec.program,
+            version = %rollout.spec.version,
+            "Program rollout started"
+        );
+
+        // Select the first wave right away instead of waiting for the next tick
+        self.advance_rollouts().await;
+        Ok(self.get_rollout(&rollout.id).unwrap_or(rollout))
+    }
+
+    /// Get a rollout by id
+    pub fn get_rollout(&self, id: &str) -> Option<ProgramRollout> {
+        self.rollouts.read().get(id).cloned()
+    }
+
+    /// Get rollouts, newest first, optionally for one program
+    pub fn list_rollouts(&self, program: Option<&str>) -> Vec<ProgramRollout> {
+        self.rollouts.read().list(program)
+    }
+
+    /// Abort a rollout, optionally sending updated workers back
+    pub async fn abort_rollout(&self, id: &str, rollback: bool) -> Result<ProgramRollout> {
+        let rollout = self
+            .rollouts
+            .write()
+            .abort(id, rollback, chrono::Utc::now())?;
+        info!(rollout_id = %id, rollback, "Program rollout aborted");
+
+        self.assignments_changed("Program rollout").await;
+        Ok(rollout)
+    }
+
+    /// Advance active rollouts with the latest worker reports
+    pub async fn advance_rollouts(&self) {
+        let observations: Vec<RolloutObservations> = self
+            .registry
+            .read()
+            .list(None, None)
+            .into_iter()
+            .filter(|w| w.is_online())
+            .map(|w| RolloutObservations {
+                variant: w.program_variant().to_string(),
+                worker_id: w.worker_id,
+                programs: w.programs,
+                drop_rate: w.drop_rate,
+            })
+            .collect();
+
+        let changed = self
+            .rollouts
+            .write()
+            .advance(&observations, chrono::Utc::now());
+        if changed {
+            self.assignments_changed("Program rollout").await;
         }
     }
 
     /// Get current configuration for a worker
-    pub async fn get_config_for_worker(&self, _worker_id: &str) -> Result<FilterConfig> {
-        // For now, all workers get the same configuration
-        // In the future, this could be customized per-worker
-        self.store.generate_config().await
+    ///
+    /// With backend replicas configured, the configuration only contains the
+    /// backends placed on the worker. Program versions decided by rollouts are
+    /// included for every worker, for the program variant it loads.
+    pub async fn get_config_for_worker(&self, worker_id: &str) -> Result<FilterConfig> {
+        let mut config = self.store.generate_config().await?;
+        self.update_backends(&config).await;
+
+        let registry = self.registry.read();
+        if let Some(assigned) = registry.backend_filter(worker_id) {
+            config
+                .backends
+                .retain(|b| assigned.contains(b.backend_id.as_str()));
+        }
+        let variant = registry
+            .get(worker_id)
+            .map_or(crate::rollout::DEFAULT_VARIANT, |w| w.program_variant())
+            .to_string();
+        drop(registry);
+
+        config.programs = self.rollouts.read().targets_for(worker_id, &variant);
+
+        Ok(config)
     }
 
     /// Check if worker needs config update
@@ -157,9 +339,14 @@ impl ConfigDistributor {
         loop {
             tokio::select! {
                 _ = cleanup_interval.tick() => {
-                    self.cleanup_stale_workers();
+                    if let Err(e) = self.refresh_backends().await {
+                        warn!(error = %e, "Failed to refresh backends for placement");
+                    }
+                    self.cleanup_stale_workers().await;
                 }
                 _ = notify_interval.tick() => {
+                    self.advance_rollouts().await;
+
                     // Check for outdated workers and notify them
                     let outdated = self.get_outdated_workers();
                     if !outdated.is_empty() {
@@ -1,7 +1,11 @@
 //! HTTP and gRPC handlers for config-mgr
 
-use crate::{config_store::ConfigStore, distributor::ConfigDistributor};
+use crate::{
+    config_store::ConfigStore, distributor::ConfigDistributor, log_levels::LogLevelStore,
+    registry::HeartbeatOutcome, rollout::ProgramRollout,
+};
 use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use chrono::Utc;
 use pistonprotection_common::config::Config;
 use pistonprotection_proto::worker::{
     worker_service_server::{WorkerService, WorkerServiceServer},
@@ -24,6 +28,7 @@ use tracing::{info, warn};
 pub struct AppState {
     pub store: Arc<ConfigStore>,
     pub distributor: Arc<ConfigDistributor>,
+    pub log_levels: Arc<LogLevelStore>,
     pub config: Arc<Config>,
 }
 
@@ -41,6 +46,7 @@ pub fn create_router(state: AppState) -> Router {
         .route("/health/ready", get(readiness_check))
         .route("/metrics", get(metrics))
         .route("/workers", get(list_workers))
+        .route("/rollouts", get(list_rollouts))
         .layer(TraceLayer::new_for_http())
         .layer(cors)
         .with_state(state)
@@ -93,20 +99,82 @@ struct WorkersResponse {
 struct WorkerInfo {
     worker_id: String,
     node_name: String,
+    region: String,
+    status: &'static str,
     interfaces: Vec<String>,
+    program_variant: String,
+    bpf_features: Vec<String>,
+    programs: Vec<ProgramInfo>,
+    assigned_backends: Vec<String>,
     config_version: u32,
     last_heartbeat: String,
 }
 
+#[derive(Serialize)]
+struct ProgramInfo {
+    name: String,
+    version: String,
+    generation: u64,
+    digest: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    load_error: String,
+    /// Stage and verifier explanation of the last load failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    load_failure: Option<LoadFailureInfo>,
+}
+
+#[derive(Serialize)]
+struct LoadFailureInfo {
+    section: String,
+    stage: &'static str,
+    summary: String,
+    kernel_release: String,
+}
+
+fn load_stage_name(stage: LoadStage) -> &'static str {
+    match stage {
+        LoadStage::Unspecified => "unknown",
+        LoadStage::Object => "object",
+        LoadStage::Verifier => "verifier",
+        LoadStage::Attach => "attach",
+    }
+}
+
 async fn list_workers(State(state): State<AppState>) -> impl IntoResponse {
     let workers = state
         .distributor
         .list_workers()
         .into_iter()
         .map(|w| WorkerInfo {
+            program_variant: w.program_variant().to_string(),
+            bpf_features: w
+                .capabilities
+                .as_ref()
+                .map(|c| c.bpf_features.clone())
+                .unwrap_or_default(),
             worker_id: w.worker_id,
             node_name: w.node_name,
+            region: w.region,
+            status: worker_status_name(w.status),
             interfaces: w.interfaces,
+            programs: w
+                .programs
+                .into_iter()
+                .map(|p| ProgramInfo {
+                    load_failure: p.diagnostic.map(|d| LoadFailureInfo {
+                        stage: load_stage_name(d.stage()),
+                        section: d.section,
+                        summary: d.summary,
+                        kernel_release: d.kernel_release,
+                    }),
+                    name: p.name,
+                    version: p.version,
+                    generation: p.generation,
+                    digest: p.digest,
+                    load_error: p.load_error,
+                })
+                .collect(),
+            assigned_backends: w.assigned_backends,
             config_version: w.config_version,
             last_heartbeat: w.last_heartbeat.to_rfc3339(),
         })
@@ -115,16 +183,110 @@ async fn list_workers(State(state): State<AppState>) -> impl IntoResponse {
     Json(WorkersResponse { workers })
 }
 
+#[derive(Serialize)]
+struct RolloutsResponse {
+    rollouts: Vec<RolloutInfo>,
+}
+
+#[derive(Serialize)]
+struct RolloutInfo {
+    id: String,
+    program: String,
+    version: String,
+    variant: String,
+    digest: String,
+    phase: &'static str,
+    current_wave: u32,
+    waves: Vec<u32>,
+    updated_workers: usize,
+    failed_workers: usize,
+    drop_rate_increase: Option<f64>,
+    message: String,
+    started_at: String,
+    updated_at: String,
+}
+
+impl From<ProgramRollout> for RolloutInfo {
+    fn from(rollout: ProgramRollout) -> Self {
+        let count = |state: WorkerRolloutState| {
+            rollout
+                .workers
+                .values()
+                .filter(|w| w.state == state)
+                .count()
+        };
+
+        Self {
+            updated_workers: count(WorkerRolloutState::Updated),
+            failed_workers: count(WorkerRolloutState::Failed),
+            drop_rate_increase: rollout.drop_rate_increase(),
+            phase: rollout_phase_name(rollout.phase),
+            current_wave: rollout.current_wave,
+            message: rollout.message,
+            started_at: rollout.started_at.to_rfc3339(),
+            updated_at: rollout.updated_at.to_rfc3339(),
+            id: rollout.id,
+            program: rollout.spec.program,
+            version: rollout.spec.version,
+            variant: rollout.spec.variant,
+            digest: rollout.spec.digest,
+            waves: rollout.spec.wave_percentages,
+        }
+    }
+}
+
+async fn list_rollouts(State(state): State<AppState>) -> impl IntoResponse {
+    let rollouts = state
+        .distributor
+        .list_rollouts(None)
+        .into_iter()
+        .map(RolloutInfo::from)
+        .collect();
+
+    Json(RolloutsResponse { rollouts })
+}
+
+fn rollout_phase_name(phase: RolloutPhase) -> &'static str {
+    match phase {
+        RolloutPhase::Unspecified => "unknown",
+        RolloutPhase::Rolling => "rolling",
+        RolloutPhase::Observing => "observing",
+        RolloutPhase::Completed => "completed",
+        RolloutPhase::RolledBack => "rolled_back",
+        RolloutPhase::Aborted => "aborted",
+    }
+}
+
+fn worker_status_name(status: WorkerStatus) -> &'static str {
+    match status {
+        WorkerStatus::Unspecified => "unknown",
+        WorkerStatus::Registering => "registering",
+        WorkerStatus::Ready => "ready",
+        WorkerStatus::Draining => "draining",
+        WorkerStatus::Unhealthy => "unhealthy",
+        WorkerStatus::Offline => "offline",
+    }
+}
+
 // gRPC Handlers
 
 pub struct WorkerGrpcService {
     store: Arc<ConfigStore>,
     distributor: Arc<ConfigDistributor>,
+    log_levels: Arc<LogLevelStore>,
 }
 
 impl WorkerGrpcService {
-    pub fn new(store: Arc<ConfigStore>, distributor: Arc<ConfigDistributor>) -> Self {
-        Self { store, distributor }
+    pub fn new(
+        store: Arc<ConfigStore>,
+        distributor: Arc<ConfigDistributor>,
+        log_levels: Arc<LogLevelStore>,
+    ) -> Self {
+        Self {
+            store,
+            distributor,
+            log_levels,
+        }
     }
 }
 
@@ -139,17 +301,25 @@ impl WorkerService for WorkerGrpcService {
             .worker
             .ok_or_else(|| Status::invalid_argument("Worker info required"))?;
 
-        let worker_id = uuid::Uuid::new_v4().to_string();
+        // Keep the id of a worker re-registering after a control plane restart
+        let worker_id = if worker.id.is_empty() {
+            uuid::Uuid::new_v4().to_string()
+        } else {
+            worker.id.clone()
+        };
 
-        let interfaces: Vec<String> = worker.interfaces.iter().map(|i| i.name.clone()).collect();
+        // Directives set for the node before this worker (re)started
+        let node_name = worker.node_name.clone();
+        self.log_levels.load(&node_name).await;
 
         self.distributor
-            .register_worker(worker_id.clone(), worker.node_name, interfaces);
+            .register_worker(worker_id.clone(), worker)
+            .await;
 
         // Get initial configuration
         let config = self
-            .store
-            .generate_config()
+            .distributor
+            .get_config_for_worker(&worker_id)
             .await
             .map_err(|e| Status::internal(format!("Failed to generate config: {}", e)))?;
 
@@ -158,6 +328,10 @@ impl WorkerService for WorkerGrpcService {
         Ok(Response::new(RegisterResponse {
             worker_id,
             initial_config: Some(config),
+            log_level: self
+                .log_levels
+                .get(&node_name, Utc::now())
+                .map(|level| level.to_proto()),
         }))
     }
 
@@ -169,8 +343,13 @@ impl WorkerService for WorkerGrpcService {
 
         // Update heartbeat with worker's current version
         let worker_version = req.current_config_version;
+        let status = WorkerStatus::try_from(req.status).unwrap_or(WorkerStatus::Unspecified);
+        let outcome = self
+            .distributor
+            .update_heartbeat(&req.worker_id, status, worker_version)
+            .await;
         self.distributor
-            .update_heartbeat(&req.worker_id, worker_version);
+            .record_worker_programs(&req.worker_id, req.programs, req.metrics.as_ref());
 
         // Check if config update is needed by comparing versions
         let latest_version = self.store.current_version();
@@ -178,9 +357,17 @@ impl WorkerService for WorkerGrpcService {
         // Config update is available if worker's version is older than latest
         let config_update_available = worker_version < latest_version;
 
+        let log_level = self
+            .distributor
+            .get_worker(&req.worker_id)
+            .and_then(|worker| self.log_levels.get(&worker.node_name, Utc::now()))
+            .map(|level| level.to_proto());
+
         Ok(Response::new(HeartbeatResponse {
             config_update_available,
             latest_config_version: latest_version,
+            reregister_required: outcome == HeartbeatOutcome::Unknown,
+            log_level,
         }))
     }
 
@@ -190,11 +377,154 @@ impl WorkerService for WorkerGrpcService {
     ) -> Result<Response<DeregisterResponse>, Status> {
         let req = request.into_inner();
 
-        self.distributor.deregister_worker(&req.worker_id);
+        self.distributor.deregister_worker(&req.worker_id).await;
 
         Ok(Response::new(DeregisterResponse { success: true }))
     }
 
+    async fn list_workers(
+        &self,
+        request: Request<ListWorkersRequest>,
+    ) -> Result<Response<ListWorkersResponse>, Status> {
+        let req = request.into_inner();
+
+        let region = (!req.region.is_empty()).then_some(req.region.as_str());
+        let status = match WorkerStatus::try_from(req.status) {
+            Ok(WorkerStatus::Unspecified) => None,
+            Ok(status) => Some(status),
+            Err(_) => return Err(Status::invalid_argument("Unknown worker status")),
+        };
+
+        let workers = self
+            .distributor
+            .find_workers(region, status)
+            .iter()
+            .map(|w| w.to_proto())
+            .collect();
+
+        Ok(Response::new(ListWorkersResponse { workers }))
+    }
+
+    async fn start_rollout(
+        &self,
+        request: Request<StartRolloutRequest>,
+    ) -> Result<Response<Rollout>, Status> {
+        let req = request.into_inner();
+        let spec = req
+            .spec
+            .ok_or_else(|| Status::invalid_argument("Rollout spec is required"))?;
+
+        let rollout = self.distributor.start_rollout(spec).await?;
+
+        Ok(Response::new(rollout.to_proto()))
+    }
+
+    async fn get_rollout(
+        &self,
+        request: Request<GetRolloutRequest>,
+    ) -> Result<Response<Rollout>, Status> {
+        let req = request.into_inner();
+
+        let rollout = self
+            .distributor
+            .get_rollout(&req.id)
+            .ok_or_else(|| Status::not_found(format!("Rollout {} not found", req.id)))?;
+
+        Ok(Response::new(rollout.to_proto()))
+    }
+
+    async fn list_rollouts(
+        &self,
+        request: Request<ListRolloutsRequest>,
+    ) -> Result<Response<ListRolloutsResponse>, Status> {
+        let req = request.into_inner();
+
+        let program = (!req.program.is_empty()).then_some(req.program.as_str());
+        let rollouts = self
+            .distributor
+            .list_rollouts(program)
+            .iter()
+            .map(|r| r.to_proto())
+            .collect();
+
+        Ok(Response::new(ListRolloutsResponse { rollouts }))
+    }
+
+    async fn abort_rollout(
+        &self,
+        request: Request<AbortRolloutRequest>,
+    ) -> Result<Response<Rollout>, Status> {
+        let req = request.into_inner();
+
+        let rollout = self
+            .distributor
+            .abort_rollout(&req.id, req.rollback)
+            .await?;
+
+        Ok(Response::new(rollout.to_proto()))
+    }
+
+    async fn get_log_level(
+        &self,
+        request: Request<GetLogLevelRequest>,
+    ) -> Result<Response<LogLevel>, Status> {
+        let req = request.into_inner();
+        if req.node_name.is_empty() {
+            return Err(Status::invalid_argument("Node name is required"));
+        }
+
+        self.log_levels.load(&req.node_name).await;
+        let level = self
+            .log_levels
+            .get(&req.node_name, Utc::now())
+            .map(|level| level.to_proto())
+            .unwrap_or(LogLevel {
+                node_name: req.node_name,
+                ..Default::default()
+            });
+
+        Ok(Response::new(level))
+    }
+
+    async fn set_log_level(
+        &self,
+        request: Request<SetLogLevelRequest>,
+    ) -> Result<Response<LogLevel>, Status> {
+        let req = request.into_inner();
+
+        let level = self
+            .log_levels
+            .set(&req.node_name, req.directives, req.ttl_seconds, Utc::now())
+            .await?;
+
+        info!(
+            node = %req.node_name,
+            directives = ?level.as_ref().map(|l| &l.directives),
+            ttl_seconds = req.ttl_seconds,
+            "Log directives updated"
+        );
+
+        Ok(Response::new(level.map(|l| l.to_proto()).unwrap_or(
+            LogLevel {
+                node_name: req.node_name,
+                ..Default::default()
+            },
+        )))
+    }
+
+    async fn get_load_diagnostics(
+        &self,
+        request: Request<GetLoadDiagnosticsRequest>,
+    ) -> Result<Response<GetLoadDiagnosticsResponse>, Status> {
+        let req = request.into_inner();
+
+        let worker_id = (!req.worker_id.is_empty()).then_some(req.worker_id.as_str());
+        let program = (!req.program.is_empty()).then_some(req.program.as_str());
+        let diagnostics = self.distributor.load_diagnostics(worker_id, program);
+
+        Ok(Response::new(GetLoadDiagnosticsResponse { diagnostics }))
+    }
+
     async fn get_config(
         &self,
         request: Request<GetConfigRequest>,
@@ -229,15 +559,14 @@ impl WorkerService for WorkerGrpcService {
         // Get current version from worker's registration
         let mut current_version = self
             .distributor
-            .list_workers()
-            .into_iter()
-            .find(|w| w.worker_id == worker_id)
+            .get_worker(&worker_id)
             .map(|w| w.config_version)
             .unwrap_or(0);
 
         let store = self.store.clone();
         let distributor = self.distributor.clone();
         let mut rx = distributor.subscribe();
+        let stream_worker_id = worker_id.clone();
 
         info!(worker_id = %worker_id, "Worker subscribed to config stream");
 
@@ -245,7 +574,7 @@ impl WorkerService for WorkerGrpcService {
             // Send initial config if version differs
             let latest_version = store.current_version();
             if current_version < latest_version {
-                match store.generate_config().await {
+                match distributor.get_config_for_worker(&stream_worker_id).await {
                     Ok(config) => {
                         current_version = config.version;
                         yield Ok(config);
@@ -262,7 +591,7 @@ impl WorkerService for WorkerGrpcService {
                 match rx.recv().await {
                     Ok(update) => {
                         if update.version > current_version {
-                            match store.generate_config().await {
+                            match distributor.get_config_for_worker(&stream_worker_id).await {
                                 Ok(config) => {
                                     current_version = config.version;
                                     yield Ok(config);
@@ -277,7 +606,7 @@ impl WorkerService for WorkerGrpcService {
                     Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
                         warn!(skipped = n, "Config stream lagged, sending latest");
                         // Send latest config after lag
-                        if let Ok(config) = store.generate_config().await {
+                        if let Ok(config) = distributor.get_config_for_worker(&stream_worker_id).await {
                             current_version = config.version;
                             yield Ok(config);
                         }
@@ -352,6 +681,26 @@ impl WorkerService for WorkerGrpcService {
             pistonprotection_common::metrics::TRAFFIC_BYTES_TOTAL
                 .with_label_values(&[backend_id, "out"])
                 .inc_by(metrics.bytes_out as f64);
+
+            // Traffic to the backend counts towards its monthly bandwidth
+            if metrics.bytes_in > 0
+                && let Err(e) = self
+                    .store
+                    .record_bandwidth(
+                        backend_id,
+                        metrics.packets_in,
+                        metrics.bytes_in,
+                        metrics.bytes_dropped,
+                    )
+                    .await
+            {
+                warn!(
+                    worker_id = %req.worker_id,
+                    backend_id = %backend_id,
+                    error = %e,
+                    "Failed to record bandwidth usage"
+                );
+            }
         }
 
         Ok(Response::new(ReportMetricsResponse { success: true }))
@@ -606,7 +955,7 @@ pub async fn create_grpc_server(
         .set_serving::<WorkerServiceServer<WorkerGrpcService>>()
         .await;
 
-    let worker_service = WorkerGrpcService::new(state.store, state.distributor);
+    let worker_service = WorkerGrpcService::new(state.store, state.distributor, state.log_levels);
 
     Ok(Server::builder()
         .add_service(health_service)
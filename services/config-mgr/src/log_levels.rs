@@ -0,0 +1,173 @@
+//! Runtime log filter directives per node
+//!
+//! Operators raise the verbosity of one node during an incident (e.g.
+//! `blocklist_sync=debug`) without restarting its worker. The directives
+//! are kept per node name, so they survive worker restarts and
+//! re-registration, and are mirrored to Redis so a restarted config-mgr
+//! still knows them. Workers receive them in the register and heartbeat
+//! responses and apply them when the revision changes. Directives may be
+//! given a lifetime, after which the node falls back to its base filter.
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::redis::{CacheService, RedisPool};
+use pistonprotection_common::telemetry;
+use pistonprotection_proto::worker::LogLevel;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Most directives a node may carry
+pub const MAX_DIRECTIVES: usize = 32;
+
+/// Redis TTL of directives set without a lifetime
+const PERSISTENT_TTL: std::time::Duration = std::time::Duration::from_secs(90 * 24 * 3600);
+
+/// Directives of one node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeLogLevel {
+    pub node_name: String,
+    pub directives: Vec<String>,
+    pub revision: u64,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl NodeLogLevel {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn to_proto(&self) -> LogLevel {
+        LogLevel {
+            node_name: self.node_name.clone(),
+            directives: self.directives.clone(),
+            revision: self.revision,
+            updated_at: Some(self.updated_at.into()),
+            expires_at: self.expires_at.map(Into::into),
+        }
+    }
+}
+
+/// Check the directives of a set request
+pub fn validate_directives(node_name: &str, directives: &[String]) -> Result<()> {
+    if node_name.is_empty() {
+        return Err(Error::validation("Node name is required"));
+    }
+    if directives.len() > MAX_DIRECTIVES {
+        return Err(Error::validation(format!(
+            "At most {} log directives are allowed",
+            MAX_DIRECTIVES
+        )));
+    }
+    directives
+        .iter()
+        .try_for_each(|directive| telemetry::validate_directive(directive))
+}
+
+/// Log filter directives of all nodes
+pub struct LogLevelStore {
+    levels: RwLock<HashMap<String, NodeLogLevel>>,
+    cache: Option<CacheService>,
+}
+
+impl LogLevelStore {
+    pub fn new(redis: Option<RedisPool>) -> Self {
+        Self {
+            levels: RwLock::new(HashMap::new()),
+            cache: redis.map(|pool| CacheService::new(pool, "piston:log-levels")),
+        }
+    }
+
+    /// Fill in the directives of `node_name` from Redis unless they are
+    /// already known
+    pub async fn load(&self, node_name: &str) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        if self.levels.read().contains_key(node_name) {
+            return;
+        }
+
+        match cache.get::<NodeLogLevel>(node_name).await {
+            Ok(Some(level)) => {
+                self.levels
+                    .write()
+                    .entry(node_name.to_string())
+                    .or_insert(level);
+            }
+            Ok(None) => {}
+            Err(e) => warn!(node = %node_name, error = %e, "Failed to load log directives"),
+        }
+    }
+
+    /// Directives in effect for `node_name`
+    pub fn get(&self, node_name: &str, now: DateTime<Utc>) -> Option<NodeLogLevel> {
+        self.levels
+            .read()
+            .get(node_name)
+            .filter(|level| !level.is_expired(now))
+            .cloned()
+    }
+
+    /// Replace the directives of `node_name`, for `ttl_seconds` (0 = until
+    /// cleared). An empty list clears them; returns the directives now in
+    /// effect.
+    pub async fn set(
+        &self,
+        node_name: &str,
+        directives: Vec<String>,
+        ttl_seconds: u32,
+        now: DateTime<Utc>,
+    ) -> Result<Option<NodeLogLevel>> {
+        validate_directives(node_name, &directives)?;
+
+        let level = self.update(node_name, directives, ttl_seconds, now);
+        if let Some(cache) = &self.cache {
+            let persisted = match &level {
+                Some(level) => {
+                    let ttl = level
+                        .expires_at
+                        .and_then(|expires_at| (expires_at - now).to_std().ok())
+                        .unwrap_or(PERSISTENT_TTL);
+                    cache.set(node_name, level, ttl).await
+                }
+                None => cache.delete(node_name).await,
+            };
+            if let Err(e) = persisted {
+                warn!(node = %node_name, error = %e, "Failed to persist log directives");
+            }
+        }
+        Ok(level)
+    }
+
+    /// Apply a set request in memory
+    ///
+    /// Revisions follow the update time so they keep increasing across
+    /// config-mgr restarts.
+    pub fn update(
+        &self,
+        node_name: &str,
+        directives: Vec<String>,
+        ttl_seconds: u32,
+        now: DateTime<Utc>,
+    ) -> Option<NodeLogLevel> {
+        let mut levels = self.levels.write();
+        if directives.is_empty() {
+            levels.remove(node_name);
+            return None;
+        }
+
+        let previous = levels.get(node_name).map_or(0, |level| level.revision);
+        let level = NodeLogLevel {
+            node_name: node_name.to_string(),
+            directives,
+            revision: (previous + 1).max(now.timestamp_micros().max(0) as u64),
+            updated_at: now,
+            expires_at: (ttl_seconds > 0).then(|| now + Duration::seconds(ttl_seconds.into())),
+        };
+        levels.insert(node_name.to_string(), level.clone());
+        Some(level)
+    }
+}
@@ -3,7 +3,11 @@
 //! Manages configuration distribution to worker nodes and handles
 //! configuration versioning and updates.
 
-use pistonprotection_common::{config::Config, error::Error, telemetry};
+use pistonprotection_common::{
+    config::{Config, secrets::Secrets},
+    error::Error,
+    telemetry,
+};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::signal;
@@ -13,6 +17,9 @@ use tracing::{error, info, warn};
 mod config_store;
 mod distributor;
 mod handlers;
+mod log_levels;
+mod registry;
+mod rollout;
 
 #[cfg(test)]
 mod tests;
@@ -24,11 +31,18 @@ type BoxError = Box<dyn std::error::Error + Send + Sync>;
 #[tokio::main]
 async fn main() -> Result<(), BoxError> {
     // Load configuration
-    let config = Config::load(SERVICE_NAME).map_err(|e| Box::new(e) as BoxError)?;
+    let mut config = Config::load(SERVICE_NAME).map_err(|e| Box::new(e) as BoxError)?;
 
     // Initialize telemetry
     telemetry::init(SERVICE_NAME, &config.telemetry).map_err(|e| Box::new(e) as BoxError)?;
 
+    // Resolve credentials kept in Kubernetes Secrets or Vault
+    let secrets = Arc::new(Secrets::from_env().map_err(|e| Box::new(e) as BoxError)?);
+    let db_credentials = config
+        .resolve_secrets(&secrets)
+        .await
+        .map_err(|e| Box::new(e) as BoxError)?;
+
     info!(
         service = SERVICE_NAME,
         version = env!("CARGO_PKG_VERSION"),
@@ -39,9 +53,17 @@ async fn main() -> Result<(), BoxError> {
     let db_pool = match &config.database {
         Some(db_config) => {
             info!("Initializing database connection pool");
-            pistonprotection_common::db::create_pool(db_config)
+            let pool = pistonprotection_common::db::create_pool(db_config)
                 .await
-                .map_err(|e| Box::new(e) as BoxError)?
+                .map_err(|e| Box::new(e) as BoxError)?;
+            if let Some(credentials) = db_credentials {
+                pistonprotection_common::db::spawn_credential_renewal(
+                    vec![pool.clone()],
+                    Arc::clone(&secrets),
+                    credentials,
+                );
+            }
+            pool
         }
         None => {
             error!("Database configuration is required for config-mgr");
@@ -78,16 +100,27 @@ async fn main() -> Result<(), BoxError> {
         redis_pool.clone(),
     ));
 
+    // Workers each backend is placed on (0 = all workers protect all backends)
+    let backend_replicas = std::env::var("PISTON_BACKEND_REPLICAS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+
     // Create distributor
     let distributor = Arc::new(distributor::ConfigDistributor::new(
         store.clone(),
         redis_pool.clone(),
+        backend_replicas,
     ));
 
+    // Runtime log directives of the nodes
+    let log_levels = Arc::new(log_levels::LogLevelStore::new(redis_pool.clone()));
+
     // Create shared state
     let state = handlers::AppState {
         store,
         distributor,
+        log_levels,
         config: Arc::new(config.clone()),
     };
 
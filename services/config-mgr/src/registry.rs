@@ -0,0 +1,374 @@
+//! Worker registry and backend placement
+//!
+//! Workers register on startup and heartbeat periodically. A worker that
+//! misses heartbeats for [`STALE_AFTER_SECS`] is marked offline and its
+//! backends are placed on the remaining online workers; offline workers are
+//! forgotten after [`EXPIRE_AFTER_SECS`]. Placement uses rendezvous hashing,
+//! so a membership change only moves the backends of the workers that
+//! joined or left.
+
+use pistonprotection_proto::worker::{
+    ProgramVersion, Worker, WorkerCapabilities, WorkerLoadDiagnostic, WorkerStatus,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Seconds without a heartbeat before a worker is marked offline
+pub const STALE_AFTER_SECS: i64 = 60;
+
+/// Seconds without a heartbeat before an offline worker is removed
+pub const EXPIRE_AFTER_SECS: i64 = 600;
+
+/// Worker registration info
+#[derive(Debug, Clone)]
+pub struct RegisteredWorker {
+    pub worker_id: String,
+    pub node_name: String,
+    pub hostname: String,
+    pub region: String,
+    pub interfaces: Vec<String>,
+    pub labels: HashMap<String, String>,
+    pub capabilities: Option<WorkerCapabilities>,
+    pub programs: Vec<ProgramVersion>,
+    pub status: WorkerStatus,
+    pub registered_at: chrono::DateTime<chrono::Utc>,
+    pub last_heartbeat: chrono::DateTime<chrono::Utc>,
+    pub config_version: u32,
+    /// Backends placed on this worker, sorted
+    pub assigned_backends: Vec<String>,
+    /// Cumulative XDP pass/drop counters from the last heartbeat
+    pub xdp_pass: u64,
+    pub xdp_drop: u64,
+    /// Fraction of packets dropped between the last two heartbeats
+    pub drop_rate: f64,
+}
+
+impl RegisteredWorker {
+    pub fn from_proto(
+        worker_id: String,
+        worker: Worker,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Self {
+        Self {
+            worker_id,
+            node_name: worker.node_name,
+            hostname: worker.hostname,
+            region: worker.region,
+            interfaces: worker.interfaces.into_iter().map(|i| i.name).collect(),
+            labels: worker.labels,
+            capabilities: worker.capabilities,
+            programs: worker.programs,
+            status: WorkerStatus::Ready,
+            registered_at: now,
+            last_heartbeat: now,
+            config_version: 0,
+            assigned_backends: Vec::new(),
+            xdp_pass: 0,
+            xdp_drop: 0,
+            drop_rate: 0.0,
+        }
+    }
+
+    /// Program variant the worker loads (workers that do not report one load the full variant)
+    pub fn program_variant(&self) -> &str {
+        self.capabilities
+            .as_ref()
+            .map(|c| c.program_variant.as_str())
+            .filter(|v| !v.is_empty())
+            .unwrap_or(crate::rollout::DEFAULT_VARIANT)
+    }
+
+    /// Whether backends can be placed on the worker
+    pub fn is_online(&self) -> bool {
+        !matches!(
+            self.status,
+            WorkerStatus::Offline | WorkerStatus::Draining | WorkerStatus::Unhealthy
+        )
+    }
+
+    /// Load failures the worker reported, optionally for one program
+    pub fn load_diagnostics(&self, program: Option<&str>) -> Vec<WorkerLoadDiagnostic> {
+        self.programs
+            .iter()
+            .filter(|p| program.is_none_or(|name| p.name == name))
+            .filter_map(|p| p.diagnostic.clone())
+            .map(|diagnostic| WorkerLoadDiagnostic {
+                worker_id: self.worker_id.clone(),
+                node_name: self.node_name.clone(),
+                diagnostic: Some(diagnostic),
+            })
+            .collect()
+    }
+
+    pub fn to_proto(&self) -> Worker {
+        Worker {
+            id: self.worker_id.clone(),
+            node_name: self.node_name.clone(),
+            hostname: self.hostname.clone(),
+            interfaces: self
+                .interfaces
+                .iter()
+                .map(|name| pistonprotection_proto::worker::NetworkInterface {
+                    name: name.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            capabilities: self.capabilities.clone(),
+            status: self.status.into(),
+            labels: self.labels.clone(),
+            registered_at: Some(self.registered_at.into()),
+            last_heartbeat: Some(self.last_heartbeat.into()),
+            region: self.region.clone(),
+            programs: self.programs.clone(),
+            assigned_backends: self.assigned_backends.clone(),
+        }
+    }
+}
+
+/// Result of a heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatOutcome {
+    /// Worker is not registered and must register again
+    Unknown,
+    /// Worker stays online (or stays unavailable)
+    Unchanged,
+    /// Worker was offline or unavailable and can take backends again
+    CameOnline,
+    /// Worker reported itself draining or unhealthy
+    WentOffline,
+}
+
+/// Registered workers and the placement of backends on them
+#[derive(Debug, Default)]
+pub struct WorkerRegistry {
+    workers: HashMap<String, RegisteredWorker>,
+    backends: Vec<String>,
+    /// Workers each backend is placed on (0 = every online worker)
+    replicas: usize,
+}
+
+impl WorkerRegistry {
+    pub fn new(replicas: usize) -> Self {
+        Self {
+            replicas,
+            ..Default::default()
+        }
+    }
+
+    pub fn replicas(&self) -> usize {
+        self.replicas
+    }
+
+    /// Register (or re-register) a worker; returns workers whose placement changed
+    pub fn register(&mut self, worker: RegisteredWorker) -> Vec<String> {
+        self.workers.insert(worker.worker_id.clone(), worker);
+        self.reschedule()
+    }
+
+    /// Remove a worker; returns workers whose placement changed
+    pub fn deregister(&mut self, worker_id: &str) -> Option<Vec<String>> {
+        self.workers.remove(worker_id)?;
+        Some(self.reschedule())
+    }
+
+    /// Record a heartbeat with the worker's reported status and config version
+    pub fn heartbeat(
+        &mut self,
+        worker_id: &str,
+        status: WorkerStatus,
+        config_version: u32,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> HeartbeatOutcome {
+        let Some(worker) = self.workers.get_mut(worker_id) else {
+            return HeartbeatOutcome::Unknown;
+        };
+
+        let was_online = worker.is_online();
+        worker.last_heartbeat = now;
+        worker.config_version = config_version;
+        worker.status = match status {
+            WorkerStatus::Unspecified | WorkerStatus::Registering | WorkerStatus::Offline => {
+                WorkerStatus::Ready
+            }
+            status => status,
+        };
+
+        let outcome = match (was_online, worker.is_online()) {
+            (false, true) => HeartbeatOutcome::CameOnline,
+            (true, false) => HeartbeatOutcome::WentOffline,
+            _ => return HeartbeatOutcome::Unchanged,
+        };
+        self.reschedule();
+        outcome
+    }
+
+    /// Record the program versions and XDP counters from a heartbeat
+    ///
+    /// An empty program list keeps the versions reported before.
+    pub fn record_programs(
+        &mut self,
+        worker_id: &str,
+        programs: Vec<ProgramVersion>,
+        xdp_pass: u64,
+        xdp_drop: u64,
+    ) {
+        let Some(worker) = self.workers.get_mut(worker_id) else {
+            return;
+        };
+
+        if !programs.is_empty() {
+            worker.programs = programs;
+        }
+
+        // Counters reset when programs are reloaded; use the raw values then
+        let (pass, drop) = if xdp_pass >= worker.xdp_pass && xdp_drop >= worker.xdp_drop {
+            (xdp_pass - worker.xdp_pass, xdp_drop - worker.xdp_drop)
+        } else {
+            (xdp_pass, xdp_drop)
+        };
+        if pass + drop > 0 {
+            worker.drop_rate = drop as f64 / (pass + drop) as f64;
+        }
+        worker.xdp_pass = xdp_pass;
+        worker.xdp_drop = xdp_drop;
+    }
+
+    /// Mark workers without recent heartbeats offline and drop expired ones
+    ///
+    /// Returns the ids of workers that went offline.
+    pub fn mark_stale(&mut self, now: chrono::DateTime<chrono::Utc>) -> Vec<String> {
+        let stale_after = chrono::Duration::seconds(STALE_AFTER_SECS);
+        let expire_after = chrono::Duration::seconds(EXPIRE_AFTER_SECS);
+
+        self.workers
+            .retain(|_, w| now - w.last_heartbeat <= expire_after);
+
+        let mut offline = Vec::new();
+        for worker in self.workers.values_mut() {
+            if worker.status != WorkerStatus::Offline && now - worker.last_heartbeat > stale_after {
+                worker.status = WorkerStatus::Offline;
+                offline.push(worker.worker_id.clone());
+            }
+        }
+
+        self.reschedule();
+        offline.sort();
+        offline
+    }
+
+    /// Replace the set of backends to place; returns workers whose placement changed
+    pub fn set_backends(&mut self, mut backends: Vec<String>) -> Vec<String> {
+        backends.sort();
+        backends.dedup();
+        if backends == self.backends {
+            return Vec::new();
+        }
+        self.backends = backends;
+        self.reschedule()
+    }
+
+    /// Recompute placement; returns online workers whose assignment changed
+    pub fn reschedule(&mut self) -> Vec<String> {
+        let online_ids: Vec<String> = self
+            .workers
+            .values()
+            .filter(|w| w.is_online())
+            .map(|w| w.worker_id.clone())
+            .collect();
+        let online: Vec<&str> = online_ids.iter().map(String::as_str).collect();
+        let placement = place_backends(&self.backends, &online, self.replicas);
+
+        let mut changed = Vec::new();
+        for worker in self.workers.values_mut() {
+            let assigned = placement
+                .get(worker.worker_id.as_str())
+                .cloned()
+                .unwrap_or_default();
+            if assigned != worker.assigned_backends {
+                worker.assigned_backends = assigned;
+                if worker.is_online() {
+                    changed.push(worker.worker_id.clone());
+                }
+            }
+        }
+
+        changed.sort();
+        changed
+    }
+
+    pub fn get(&self, worker_id: &str) -> Option<&RegisteredWorker> {
+        self.workers.get(worker_id)
+    }
+
+    /// All workers, optionally filtered by region and status, sorted by id
+    pub fn list(
+        &self,
+        region: Option<&str>,
+        status: Option<WorkerStatus>,
+    ) -> Vec<RegisteredWorker> {
+        let mut workers: Vec<RegisteredWorker> = self
+            .workers
+            .values()
+            .filter(|w| region.is_none_or(|r| w.region == r))
+            .filter(|w| status.is_none_or(|s| w.status == s))
+            .cloned()
+            .collect();
+        workers.sort_by(|a, b| a.worker_id.cmp(&b.worker_id));
+        workers
+    }
+
+    /// Backends the worker should protect, or `None` if it gets every backend
+    pub fn backend_filter(&self, worker_id: &str) -> Option<HashSet<&str>> {
+        if self.replicas == 0 {
+            return None;
+        }
+        let worker = self.workers.get(worker_id)?;
+        Some(
+            worker
+                .assigned_backends
+                .iter()
+                .map(String::as_str)
+                .collect(),
+        )
+    }
+}
+
+/// Rendezvous score of a backend on a worker
+fn placement_score(backend_id: &str, worker_id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    backend_id.hash(&mut hasher);
+    worker_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Place each backend on the `replicas` highest-scoring online workers
+///
+/// With `replicas == 0`, or fewer online workers than replicas, every
+/// backend goes to every online worker.
+pub fn place_backends<'a>(
+    backends: &[String],
+    online_workers: &[&'a str],
+    replicas: usize,
+) -> HashMap<&'a str, Vec<String>> {
+    let mut placement: HashMap<&'a str, Vec<String>> =
+        online_workers.iter().map(|&w| (w, Vec::new())).collect();
+
+    for backend_id in backends {
+        let mut ranked: Vec<&'a str> = online_workers.to_vec();
+        if replicas > 0 && replicas < ranked.len() {
+            ranked.sort_by_key(|w| std::cmp::Reverse((placement_score(backend_id, w), *w)));
+            ranked.truncate(replicas);
+        }
+        for worker_id in ranked {
+            if let Some(assigned) = placement.get_mut(worker_id) {
+                assigned.push(backend_id.clone());
+            }
+        }
+    }
+
+    for assigned in placement.values_mut() {
+        assigned.sort();
+    }
+    placement
+}
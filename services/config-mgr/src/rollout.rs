@@ -0,0 +1,588 @@
+//! Staged eBPF program rollouts
+//!
+//! A rollout moves one program to a new version in waves. Each wave selects
+//! a percentage of the online workers, waits until they report the new
+//! digest, then watches their drop rate for the observation window before
+//! starting the next wave. If too many workers fail to load the new version,
+//! or the drop rate of the updated workers rises by more than the allowed
+//! margin, every selected worker is sent back to the version it ran before.
+//!
+//! Each rollout targets one program variant (the build a worker can load on
+//! its kernel, see `WorkerCapabilities.program_variant`); workers running
+//! another variant are neither selected nor counted.
+//!
+//! Rollouts are kept in memory; a restarted config-mgr forgets them and
+//! workers keep whatever version they last loaded.
+
+use chrono::{DateTime, Duration, Utc};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::worker::{
+    ProgramTarget, Rollout, RolloutPhase, RolloutSpec, WorkerRollout, WorkerRolloutState,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Waves used when the spec does not list any (percent of online workers)
+pub const DEFAULT_WAVES: [u32; 3] = [10, 50, 100];
+
+/// Observation window after each wave when the spec does not set one
+pub const DEFAULT_OBSERVE_SECS: u32 = 300;
+
+/// Allowed drop rate increase when the spec does not set one
+pub const DEFAULT_MAX_DROP_RATE_INCREASE: f64 = 0.05;
+
+/// Program variant used when the spec or the worker does not name one
+pub const DEFAULT_VARIANT: &str = "full";
+
+/// Seconds a selected worker has to report the new version before it counts as failed
+pub const UPDATE_TIMEOUT_SECS: i64 = 300;
+
+/// Validate a rollout spec and fill in defaults
+pub fn validate_spec(spec: &mut RolloutSpec) -> Result<()> {
+    if spec.program.is_empty() {
+        return Err(Error::validation("Rollout program is required"));
+    }
+    if spec.version.is_empty() {
+        return Err(Error::validation("Rollout version is required"));
+    }
+    if spec.digest.len() != 64 || !spec.digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::validation(
+            "Rollout digest must be a hex-encoded SHA-256",
+        ));
+    }
+    spec.digest.make_ascii_lowercase();
+
+    if spec.variant.is_empty() {
+        spec.variant = DEFAULT_VARIANT.to_string();
+    }
+    if !spec
+        .variant
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(Error::validation(
+            "Rollout variant must be lowercase alphanumeric",
+        ));
+    }
+
+    if spec.wave_percentages.is_empty() {
+        spec.wave_percentages = DEFAULT_WAVES.to_vec();
+    }
+    if spec.wave_percentages.iter().any(|&p| p == 0 || p > 100) {
+        return Err(Error::validation(
+            "Wave percentages must be between 1 and 100",
+        ));
+    }
+    if spec.wave_percentages.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(Error::validation("Wave percentages must be increasing"));
+    }
+    if spec.wave_percentages.last() != Some(&100) {
+        return Err(Error::validation(
+            "The last wave must cover 100% of workers",
+        ));
+    }
+
+    if spec.observe_seconds == 0 {
+        spec.observe_seconds = DEFAULT_OBSERVE_SECS;
+    }
+    if !(0.0..=1.0).contains(&spec.max_drop_rate_increase) {
+        return Err(Error::validation(
+            "Max drop rate increase must be between 0 and 1",
+        ));
+    }
+    if spec.max_drop_rate_increase == 0.0 {
+        spec.max_drop_rate_increase = DEFAULT_MAX_DROP_RATE_INCREASE;
+    }
+
+    Ok(())
+}
+
+/// What an online worker last reported about the rolled out program
+#[derive(Debug, Clone, Default)]
+pub struct WorkerObservation {
+    pub worker_id: String,
+    /// Version and digest currently loaded (empty if not loaded)
+    pub version: String,
+    pub digest: String,
+    /// Error from the worker's last attempt to change the program version
+    pub load_error: String,
+    /// Fraction of packets dropped since the previous heartbeat
+    pub drop_rate: f64,
+}
+
+/// Progress of one selected worker
+#[derive(Debug, Clone)]
+pub struct WorkerProgress {
+    pub wave: u32,
+    pub state: WorkerRolloutState,
+    pub previous_version: String,
+    pub previous_digest: String,
+    /// Drop rate when the worker was selected
+    pub baseline_drop_rate: f64,
+    pub drop_rate: f64,
+    pub error: String,
+    pub selected_at: DateTime<Utc>,
+}
+
+impl WorkerProgress {
+    fn previous_target(&self, program: &str) -> Option<ProgramTarget> {
+        // A worker that did not run the program before has nothing to go back to
+        if self.previous_digest.is_empty() {
+            return None;
+        }
+        Some(ProgramTarget {
+            name: program.to_string(),
+            version: self.previous_version.clone(),
+            digest: self.previous_digest.clone(),
+        })
+    }
+}
+
+/// A rollout of one program version
+#[derive(Debug, Clone)]
+pub struct ProgramRollout {
+    pub id: String,
+    pub spec: RolloutSpec,
+    pub phase: RolloutPhase,
+    /// 1-based index of the wave in progress (0 = not started)
+    pub current_wave: u32,
+    /// Selected workers, by id
+    pub workers: BTreeMap<String, WorkerProgress>,
+    pub message: String,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    observe_until: Option<DateTime<Utc>>,
+}
+
+impl ProgramRollout {
+    pub fn new(id: String, spec: RolloutSpec, now: DateTime<Utc>) -> Self {
+        Self {
+            id,
+            spec,
+            phase: RolloutPhase::Rolling,
+            current_wave: 0,
+            workers: BTreeMap::new(),
+            message: "Waiting for the first wave".to_string(),
+            started_at: now,
+            updated_at: now,
+            observe_until: None,
+        }
+    }
+
+    /// Whether the rollout still changes worker versions
+    pub fn is_active(&self) -> bool {
+        matches!(self.phase, RolloutPhase::Rolling | RolloutPhase::Observing)
+    }
+
+    fn target(&self) -> ProgramTarget {
+        ProgramTarget {
+            name: self.spec.program.clone(),
+            version: self.spec.version.clone(),
+            digest: self.spec.digest.clone(),
+        }
+    }
+
+    /// Version of the program a worker should run, if the rollout decides it
+    pub fn target_for(&self, worker_id: &str) -> Option<ProgramTarget> {
+        if self.phase == RolloutPhase::Completed {
+            return Some(self.target());
+        }
+
+        let progress = self.workers.get(worker_id)?;
+        let keep_new = match progress.state {
+            WorkerRolloutState::Failed | WorkerRolloutState::Reverted => false,
+            WorkerRolloutState::Updating => self.is_active(),
+            _ => self.phase != RolloutPhase::RolledBack,
+        };
+
+        if keep_new {
+            Some(self.target())
+        } else {
+            progress.previous_target(&self.spec.program)
+        }
+    }
+
+    /// Drive the rollout with the latest worker reports
+    ///
+    /// `observations` holds the online workers. Returns true if the version
+    /// any worker should run has changed.
+    pub fn advance(&mut self, observations: &[WorkerObservation], now: DateTime<Utc>) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+
+        let mut changed = self.observe(observations, now);
+
+        let failed = self
+            .workers
+            .values()
+            .filter(|w| w.state == WorkerRolloutState::Failed)
+            .count();
+        if failed > self.spec.max_failed_workers as usize {
+            self.roll_back(
+                format!("{} workers failed to load the new version", failed),
+                now,
+            );
+            return true;
+        }
+
+        match self.phase {
+            RolloutPhase::Rolling if self.current_wave == 0 => {
+                changed |= self.start_next_wave(observations, now);
+            }
+            RolloutPhase::Rolling => {
+                let updating = self
+                    .workers
+                    .values()
+                    .any(|w| w.state == WorkerRolloutState::Updating);
+                if !updating {
+                    self.phase = RolloutPhase::Observing;
+                    self.observe_until =
+                        Some(now + Duration::seconds(i64::from(self.spec.observe_seconds)));
+                    self.message = format!("Observing wave {}", self.current_wave);
+                    self.updated_at = now;
+                }
+            }
+            RolloutPhase::Observing => {
+                if let Some(increase) = self.drop_rate_increase()
+                    && increase > self.spec.max_drop_rate_increase
+                {
+                    self.roll_back(
+                        format!(
+                            "Drop rate rose by {:.2}% on updated workers",
+                            increase * 100.0
+                        ),
+                        now,
+                    );
+                    return true;
+                }
+                if self.observe_until.is_none_or(|until| now >= until) {
+                    changed |= self.start_next_wave(observations, now);
+                }
+            }
+            _ => {}
+        }
+
+        changed
+    }
+
+    /// Merge worker reports into the progress of selected workers
+    ///
+    /// Returns true if a worker failed.
+    fn observe(&mut self, observations: &[WorkerObservation], now: DateTime<Utc>) -> bool {
+        let timeout = Duration::seconds(UPDATE_TIMEOUT_SECS);
+        let mut failed = false;
+
+        for observation in observations {
+            let Some(progress) = self.workers.get_mut(&observation.worker_id) else {
+                continue;
+            };
+            progress.drop_rate = observation.drop_rate;
+
+            if progress.state != WorkerRolloutState::Updating {
+                continue;
+            }
+            if observation.digest == self.spec.digest {
+                progress.state = WorkerRolloutState::Updated;
+            } else if !observation.load_error.is_empty() {
+                progress.state = WorkerRolloutState::Failed;
+                progress.error = observation.load_error.clone();
+                failed = true;
+            }
+        }
+
+        // Workers that never report the new version (including ones that
+        // went offline) fail once the update timeout passes
+        for progress in self.workers.values_mut() {
+            if progress.state == WorkerRolloutState::Updating
+                && now - progress.selected_at > timeout
+            {
+                progress.state = WorkerRolloutState::Failed;
+                progress.error = "Timed out waiting for the new version".to_string();
+                failed = true;
+            }
+        }
+
+        if failed {
+            self.updated_at = now;
+        }
+        failed
+    }
+
+    /// Average drop rate increase of updated workers over their baseline
+    pub fn drop_rate_increase(&self) -> Option<f64> {
+        let increases: Vec<f64> = self
+            .workers
+            .values()
+            .filter(|w| w.state == WorkerRolloutState::Updated)
+            .map(|w| w.drop_rate - w.baseline_drop_rate)
+            .collect();
+        if increases.is_empty() {
+            return None;
+        }
+        Some(increases.iter().sum::<f64>() / increases.len() as f64)
+    }
+
+    /// Select the workers of the next wave, or complete the rollout
+    fn start_next_wave(&mut self, observations: &[WorkerObservation], now: DateTime<Utc>) -> bool {
+        let next = self.current_wave + 1;
+        let Some(&percent) = self.spec.wave_percentages.get(next as usize - 1) else {
+            self.phase = RolloutPhase::Completed;
+            self.observe_until = None;
+            self.message = format!(
+                "Rolled out {} {} to all workers",
+                self.spec.program, self.spec.version
+            );
+            self.updated_at = now;
+            return true;
+        };
+
+        if observations.is_empty() {
+            self.message = "Waiting for online workers".to_string();
+            return false;
+        }
+
+        // Stable per-rollout order, so waves grow as supersets of each other
+        let mut candidates: Vec<&WorkerObservation> = observations.iter().collect();
+        candidates.sort_by_key(|o| (selection_order(&self.id, &o.worker_id), o.worker_id.clone()));
+
+        let wanted = (percent as usize * candidates.len()).div_ceil(100);
+        let mut selected = candidates
+            .iter()
+            .filter(|o| self.workers.contains_key(&o.worker_id))
+            .count();
+
+        for observation in candidates {
+            if selected >= wanted {
+                break;
+            }
+            if self.workers.contains_key(&observation.worker_id) {
+                continue;
+            }
+
+            let state = if observation.digest == self.spec.digest {
+                WorkerRolloutState::Updated
+            } else {
+                WorkerRolloutState::Updating
+            };
+            self.workers.insert(
+                observation.worker_id.clone(),
+                WorkerProgress {
+                    wave: next,
+                    state,
+                    previous_version: observation.version.clone(),
+                    previous_digest: observation.digest.clone(),
+                    baseline_drop_rate: observation.drop_rate,
+                    drop_rate: observation.drop_rate,
+                    error: String::new(),
+                    selected_at: now,
+                },
+            );
+            selected += 1;
+        }
+
+        self.current_wave = next;
+        self.phase = RolloutPhase::Rolling;
+        self.observe_until = None;
+        self.message = format!(
+            "Wave {}/{}: {}% of workers",
+            next,
+            self.spec.wave_percentages.len(),
+            percent
+        );
+        self.updated_at = now;
+        true
+    }
+
+    fn roll_back(&mut self, reason: String, now: DateTime<Utc>) {
+        self.revert_workers();
+        self.phase = RolloutPhase::RolledBack;
+        self.observe_until = None;
+        self.message = format!("Rolled back: {}", reason);
+        self.updated_at = now;
+    }
+
+    fn revert_workers(&mut self) {
+        for progress in self.workers.values_mut() {
+            if progress.state != WorkerRolloutState::Failed {
+                progress.state = WorkerRolloutState::Reverted;
+            }
+        }
+    }
+
+    /// Stop the rollout, optionally sending selected workers back
+    pub fn abort(&mut self, rollback: bool, now: DateTime<Utc>) -> Result<()> {
+        if !self.is_active() {
+            return Err(Error::validation(format!(
+                "Rollout {} has already finished",
+                self.id
+            )));
+        }
+
+        if rollback {
+            self.revert_workers();
+        }
+        self.phase = RolloutPhase::Aborted;
+        self.observe_until = None;
+        self.message = if rollback {
+            "Aborted and rolled back".to_string()
+        } else {
+            "Aborted".to_string()
+        };
+        self.updated_at = now;
+        Ok(())
+    }
+
+    pub fn to_proto(&self) -> Rollout {
+        Rollout {
+            id: self.id.clone(),
+            spec: Some(self.spec.clone()),
+            phase: self.phase.into(),
+            current_wave: self.current_wave,
+            workers: self
+                .workers
+                .iter()
+                .map(|(worker_id, w)| WorkerRollout {
+                    worker_id: worker_id.clone(),
+                    wave: w.wave,
+                    state: w.state.into(),
+                    previous_version: w.previous_version.clone(),
+                    previous_digest: w.previous_digest.clone(),
+                    baseline_drop_rate: w.baseline_drop_rate,
+                    drop_rate: w.drop_rate,
+                    error: w.error.clone(),
+                })
+                .collect(),
+            message: self.message.clone(),
+            started_at: Some(self.started_at.into()),
+            updated_at: Some(self.updated_at.into()),
+        }
+    }
+}
+
+/// Position of a worker in a rollout's selection order
+fn selection_order(rollout_id: &str, worker_id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    rollout_id.hash(&mut hasher);
+    worker_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// All rollouts, oldest first
+#[derive(Debug, Default)]
+pub struct RolloutManager {
+    rollouts: Vec<ProgramRollout>,
+}
+
+impl RolloutManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a rollout; only one rollout per program variant may be active
+    pub fn start(&mut self, mut spec: RolloutSpec, now: DateTime<Utc>) -> Result<ProgramRollout> {
+        validate_spec(&mut spec)?;
+
+        if let Some(active) = self.rollouts.iter().find(|r| {
+            r.is_active() && r.spec.program == spec.program && r.spec.variant == spec.variant
+        }) {
+            return Err(Error::already_exists(
+                "rollout",
+                "program",
+                format!("{} {} ({})", spec.program, spec.variant, active.id),
+            ));
+        }
+
+        let rollout = ProgramRollout::new(uuid::Uuid::new_v4().to_string(), spec, now);
+        self.rollouts.push(rollout.clone());
+        Ok(rollout)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ProgramRollout> {
+        self.rollouts.iter().find(|r| r.id == id)
+    }
+
+    /// Rollouts, newest first, optionally for one program
+    pub fn list(&self, program: Option<&str>) -> Vec<ProgramRollout> {
+        self.rollouts
+            .iter()
+            .rev()
+            .filter(|r| program.is_none_or(|p| r.spec.program == p))
+            .cloned()
+            .collect()
+    }
+
+    pub fn abort(
+        &mut self,
+        id: &str,
+        rollback: bool,
+        now: DateTime<Utc>,
+    ) -> Result<ProgramRollout> {
+        let rollout = self
+            .rollouts
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or_else(|| Error::not_found("rollout", id))?;
+        rollout.abort(rollback, now)?;
+        Ok(rollout.clone())
+    }
+
+    /// Advance every active rollout; returns true if any worker target changed
+    ///
+    /// Each rollout only sees the workers running its variant.
+    pub fn advance(&mut self, observations: &[RolloutObservations], now: DateTime<Utc>) -> bool {
+        let mut changed = false;
+        for rollout in self.rollouts.iter_mut().filter(|r| r.is_active()) {
+            let program_observations: Vec<WorkerObservation> = observations
+                .iter()
+                .filter(|worker| worker.variant == rollout.spec.variant)
+                .map(|worker| worker.for_program(&rollout.spec.program))
+                .collect();
+            changed |= rollout.advance(&program_observations, now);
+        }
+        changed
+    }
+
+    /// Program versions a worker running `variant` should run, one per program
+    ///
+    /// The newest rollout of the variant that decides a program wins.
+    pub fn targets_for(&self, worker_id: &str, variant: &str) -> Vec<ProgramTarget> {
+        let mut seen = HashSet::new();
+        let mut targets = Vec::new();
+        for rollout in self.rollouts.iter().rev() {
+            if rollout.spec.variant != variant || seen.contains(rollout.spec.program.as_str()) {
+                continue;
+            }
+            if let Some(target) = rollout.target_for(worker_id) {
+                seen.insert(rollout.spec.program.as_str());
+                targets.push(target);
+            }
+        }
+        targets.sort_by(|a, b| a.name.cmp(&b.name));
+        targets
+    }
+}
+
+/// Everything an online worker reported, for all programs
+#[derive(Debug, Clone, Default)]
+pub struct RolloutObservations {
+    pub worker_id: String,
+    /// Program variant the worker loads
+    pub variant: String,
+    pub programs: Vec<pistonprotection_proto::worker::ProgramVersion>,
+    pub drop_rate: f64,
+}
+
+impl RolloutObservations {
+    fn for_program(&self, program: &str) -> WorkerObservation {
+        let reported = self.programs.iter().find(|p| p.name == program);
+        WorkerObservation {
+            worker_id: self.worker_id.clone(),
+            version: reported.map(|p| p.version.clone()).unwrap_or_default(),
+            digest: reported.map(|p| p.digest.clone()).unwrap_or_default(),
+            load_error: reported.map(|p| p.load_error.clone()).unwrap_or_default(),
+            drop_rate: self.drop_rate,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Runtime log directive tests
+//!
+//! These tests use a store without Redis and explicit timestamps.
+
+use crate::log_levels::*;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+fn t0() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn directives(list: &[&str]) -> Vec<String> {
+    list.iter().map(|d| d.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_set_and_get() {
+    let store = LogLevelStore::new(None);
+    assert!(store.get("node-a", t0()).is_none());
+
+    let level = store
+        .set("node-a", directives(&["blocklist_sync=debug"]), 0, t0())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(level.directives, directives(&["blocklist_sync=debug"]));
+    assert!(level.expires_at.is_none());
+
+    assert_eq!(store.get("node-a", t0()), Some(level.clone()));
+    assert!(store.get("node-b", t0()).is_none());
+
+    let proto = level.to_proto();
+    assert_eq!(proto.node_name, "node-a");
+    assert_eq!(proto.revision, level.revision);
+    assert!(proto.expires_at.is_none());
+}
+
+#[tokio::test]
+async fn test_revision_increases() {
+    let store = LogLevelStore::new(None);
+    let first = store
+        .set("node-a", directives(&["debug"]), 0, t0())
+        .await
+        .unwrap()
+        .unwrap();
+    // Same timestamp: the revision still moves forward
+    let second = store
+        .set("node-a", directives(&["trace"]), 0, t0())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(second.revision > first.revision);
+}
+
+#[tokio::test]
+async fn test_empty_list_clears() {
+    let store = LogLevelStore::new(None);
+    store
+        .set("node-a", directives(&["debug"]), 0, t0())
+        .await
+        .unwrap();
+
+    let cleared = store.set("node-a", Vec::new(), 0, t0()).await.unwrap();
+    assert!(cleared.is_none());
+    assert!(store.get("node-a", t0()).is_none());
+}
+
+#[tokio::test]
+async fn test_directives_expire() {
+    let store = LogLevelStore::new(None);
+    let level = store
+        .set("node-a", directives(&["worker::ebpf=trace"]), 600, t0())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(level.expires_at, Some(t0() + Duration::seconds(600)));
+
+    assert!(store.get("node-a", t0() + Duration::seconds(599)).is_some());
+    assert!(store.get("node-a", t0() + Duration::seconds(600)).is_none());
+}
+
+#[tokio::test]
+async fn test_invalid_requests_rejected() {
+    let store = LogLevelStore::new(None);
+    assert!(
+        store
+            .set("", directives(&["debug"]), 0, t0())
+            .await
+            .is_err()
+    );
+    assert!(
+        store
+            .set("node-a", directives(&["worker=loud"]), 0, t0())
+            .await
+            .is_err()
+    );
+
+    let too_many = vec!["debug".to_string(); MAX_DIRECTIVES + 1];
+    assert!(store.set("node-a", too_many, 0, t0()).await.is_err());
+
+    // Rejected requests leave the node untouched
+    assert!(store.get("node-a", t0()).is_none());
+}
@@ -1,4 +1,7 @@
 //! Config Manager Tests
 
 mod config_store_test;
+mod log_levels_test;
+mod registry_test;
+mod rollout_test;
 mod validation_test;
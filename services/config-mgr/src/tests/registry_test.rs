@@ -0,0 +1,319 @@
+//! Worker registry tests
+//!
+//! These tests drive the registry with explicit timestamps to check offline
+//! detection, expiry and backend placement without a running distributor.
+
+use crate::registry::*;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use pistonprotection_proto::worker::{
+    LoadDiagnostic, LoadStage, ProgramVersion, Worker, WorkerStatus,
+};
+use std::collections::HashSet;
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn t0() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn worker(id: &str, region: &str, now: DateTime<Utc>) -> RegisteredWorker {
+    let proto = Worker {
+        node_name: format!("node-{}", id),
+        region: region.to_string(),
+        programs: vec![ProgramVersion {
+            name: "xdp_filter".to_string(),
+            version: "0.1.0".to_string(),
+            generation: 1,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    RegisteredWorker::from_proto(id.to_string(), proto, now)
+}
+
+fn backends(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("backend-{}", i)).collect()
+}
+
+fn registry_with(replicas: usize, workers: &[&str]) -> WorkerRegistry {
+    let mut registry = WorkerRegistry::new(replicas);
+    for id in workers {
+        registry.register(worker(id, "eu-west", t0()));
+    }
+    registry.set_backends(backends(20));
+    registry
+}
+
+fn assigned(registry: &WorkerRegistry, id: &str) -> Vec<String> {
+    registry.get(id).unwrap().assigned_backends.clone()
+}
+
+// ============================================================================
+// Registration Tests
+// ============================================================================
+
+#[cfg(test)]
+mod registration_tests {
+    use super::*;
+
+    #[test]
+    fn test_register_keeps_reported_info() {
+        let registry = registry_with(0, &["w1"]);
+        let w = registry.get("w1").unwrap();
+
+        assert_eq!(w.node_name, "node-w1");
+        assert_eq!(w.region, "eu-west");
+        assert_eq!(w.status, WorkerStatus::Ready);
+        assert_eq!(w.programs[0].name, "xdp_filter");
+    }
+
+    #[test]
+    fn test_to_proto_round_trip() {
+        let registry = registry_with(0, &["w1"]);
+        let proto = registry.get("w1").unwrap().to_proto();
+
+        assert_eq!(proto.id, "w1");
+        assert_eq!(proto.region, "eu-west");
+        assert_eq!(proto.status, WorkerStatus::Ready as i32);
+        assert_eq!(proto.assigned_backends.len(), 20);
+        assert!(proto.last_heartbeat.is_some());
+    }
+
+    #[test]
+    fn test_load_diagnostics_of_failed_programs() {
+        let mut w = worker("w1", "eu-west", t0());
+        w.programs.push(ProgramVersion {
+            name: "tc_filter".to_string(),
+            load_error: "verifier rejected the program".to_string(),
+            diagnostic: Some(LoadDiagnostic {
+                program: "tc_filter".to_string(),
+                section: "classifier".to_string(),
+                stage: LoadStage::Verifier.into(),
+                summary: "R2 invalid mem access 'scalar'".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        let diagnostics = w.load_diagnostics(None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].worker_id, "w1");
+        assert_eq!(diagnostics[0].node_name, "node-w1");
+        assert_eq!(
+            diagnostics[0].diagnostic.as_ref().unwrap().stage(),
+            LoadStage::Verifier
+        );
+        assert!(w.load_diagnostics(Some("xdp_filter")).is_empty());
+    }
+
+    #[test]
+    fn test_list_filters_by_region_and_status() {
+        let mut registry = WorkerRegistry::new(0);
+        registry.register(worker("w1", "eu-west", t0()));
+        registry.register(worker("w2", "us-east", t0()));
+        registry.register(worker("w3", "eu-west", t0()));
+        registry.heartbeat("w3", WorkerStatus::Draining, 1, t0());
+
+        let ids = |workers: Vec<RegisteredWorker>| {
+            workers.into_iter().map(|w| w.worker_id).collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(registry.list(None, None)), ["w1", "w2", "w3"]);
+        assert_eq!(ids(registry.list(Some("eu-west"), None)), ["w1", "w3"]);
+        assert_eq!(
+            ids(registry.list(Some("eu-west"), Some(WorkerStatus::Ready))),
+            ["w1"]
+        );
+    }
+
+    #[test]
+    fn test_deregister_unknown_worker() {
+        let mut registry = registry_with(2, &["w1"]);
+        assert!(registry.deregister("missing").is_none());
+        assert!(registry.deregister("w1").is_some());
+        assert!(registry.get("w1").is_none());
+    }
+}
+
+// ============================================================================
+// Heartbeat and Staleness Tests
+// ============================================================================
+
+#[cfg(test)]
+mod heartbeat_tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_from_unknown_worker() {
+        let mut registry = WorkerRegistry::new(0);
+        assert_eq!(
+            registry.heartbeat("ghost", WorkerStatus::Ready, 1, t0()),
+            HeartbeatOutcome::Unknown
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_updates_version() {
+        let mut registry = registry_with(0, &["w1"]);
+        let outcome =
+            registry.heartbeat("w1", WorkerStatus::Ready, 7, t0() + Duration::seconds(10));
+
+        assert_eq!(outcome, HeartbeatOutcome::Unchanged);
+        assert_eq!(registry.get("w1").unwrap().config_version, 7);
+    }
+
+    #[test]
+    fn test_stale_worker_marked_offline() {
+        let mut registry = registry_with(0, &["w1", "w2"]);
+        let later = t0() + Duration::seconds(STALE_AFTER_SECS);
+        registry.heartbeat("w2", WorkerStatus::Ready, 1, later);
+
+        // Exactly at the threshold is still fine
+        assert!(registry.mark_stale(later).is_empty());
+
+        let offline = registry.mark_stale(later + Duration::seconds(1));
+        assert_eq!(offline, ["w1"]);
+        assert_eq!(registry.get("w1").unwrap().status, WorkerStatus::Offline);
+        assert!(assigned(&registry, "w1").is_empty());
+
+        // Already offline workers are not reported again
+        assert!(registry.mark_stale(later + Duration::seconds(2)).is_empty());
+    }
+
+    #[test]
+    fn test_offline_worker_recovers() {
+        let mut registry = registry_with(0, &["w1"]);
+        registry.mark_stale(t0() + Duration::seconds(STALE_AFTER_SECS + 1));
+
+        let outcome = registry.heartbeat(
+            "w1",
+            WorkerStatus::Ready,
+            1,
+            t0() + Duration::seconds(STALE_AFTER_SECS + 2),
+        );
+        assert_eq!(outcome, HeartbeatOutcome::CameOnline);
+        assert_eq!(registry.get("w1").unwrap().status, WorkerStatus::Ready);
+        assert_eq!(assigned(&registry, "w1").len(), 20);
+    }
+
+    #[test]
+    fn test_draining_worker_gives_up_backends() {
+        let mut registry = registry_with(1, &["w1", "w2"]);
+
+        let outcome = registry.heartbeat("w1", WorkerStatus::Draining, 1, t0());
+        assert_eq!(outcome, HeartbeatOutcome::WentOffline);
+        assert!(assigned(&registry, "w1").is_empty());
+        assert_eq!(assigned(&registry, "w2").len(), 20);
+    }
+
+    #[test]
+    fn test_expired_worker_removed() {
+        let mut registry = registry_with(0, &["w1"]);
+        registry.mark_stale(t0() + Duration::seconds(EXPIRE_AFTER_SECS));
+        assert!(registry.get("w1").is_some());
+
+        registry.mark_stale(t0() + Duration::seconds(EXPIRE_AFTER_SECS + 1));
+        assert!(registry.get("w1").is_none());
+    }
+}
+
+// ============================================================================
+// Placement Tests
+// ============================================================================
+
+#[cfg(test)]
+mod placement_tests {
+    use super::*;
+
+    #[test]
+    fn test_no_replicas_places_everything_everywhere() {
+        let registry = registry_with(0, &["w1", "w2", "w3"]);
+
+        for id in ["w1", "w2", "w3"] {
+            assert_eq!(assigned(&registry, id), {
+                let mut all = backends(20);
+                all.sort();
+                all
+            });
+            assert!(registry.backend_filter(id).is_none());
+        }
+    }
+
+    #[test]
+    fn test_each_backend_gets_replica_count() {
+        let registry = registry_with(2, &["w1", "w2", "w3", "w4"]);
+
+        for backend in backends(20) {
+            let holders = ["w1", "w2", "w3", "w4"]
+                .iter()
+                .filter(|id| assigned(&registry, id).contains(&backend))
+                .count();
+            assert_eq!(holders, 2, "{} placed on {} workers", backend, holders);
+        }
+    }
+
+    #[test]
+    fn test_placement_is_deterministic() {
+        let a = registry_with(2, &["w1", "w2", "w3"]);
+        let b = registry_with(2, &["w3", "w1", "w2"]);
+
+        for id in ["w1", "w2", "w3"] {
+            assert_eq!(assigned(&a, id), assigned(&b, id));
+        }
+    }
+
+    #[test]
+    fn test_more_replicas_than_workers() {
+        let registry = registry_with(5, &["w1", "w2"]);
+        assert_eq!(assigned(&registry, "w1").len(), 20);
+        assert_eq!(assigned(&registry, "w2").len(), 20);
+    }
+
+    #[test]
+    fn test_offline_worker_backends_rescheduled() {
+        let mut registry = registry_with(1, &["w1", "w2", "w3"]);
+        let w1_before = assigned(&registry, "w1");
+        let w2_before: HashSet<String> = assigned(&registry, "w2").into_iter().collect();
+        let w3_before: HashSet<String> = assigned(&registry, "w3").into_iter().collect();
+
+        // w1 misses heartbeats while w2 and w3 stay alive
+        let later = t0() + Duration::seconds(STALE_AFTER_SECS + 1);
+        registry.heartbeat("w2", WorkerStatus::Ready, 1, later);
+        registry.heartbeat("w3", WorkerStatus::Ready, 1, later);
+        assert_eq!(registry.mark_stale(later), ["w1"]);
+
+        let w2_after: HashSet<String> = assigned(&registry, "w2").into_iter().collect();
+        let w3_after: HashSet<String> = assigned(&registry, "w3").into_iter().collect();
+
+        // Surviving workers keep their backends and split w1's between them
+        assert!(w2_after.is_superset(&w2_before));
+        assert!(w3_after.is_superset(&w3_before));
+        for backend in &w1_before {
+            assert!(w2_after.contains(backend) || w3_after.contains(backend));
+        }
+        assert_eq!(w2_after.len() + w3_after.len(), 20);
+    }
+
+    #[test]
+    fn test_reschedule_reports_changed_workers() {
+        let mut registry = registry_with(1, &["w1", "w2"]);
+
+        // Placement only moves when membership or backends change
+        assert!(registry.reschedule().is_empty());
+        assert!(registry.set_backends(backends(20)).is_empty());
+
+        let changed = registry.set_backends(backends(40));
+        assert!(!changed.is_empty());
+    }
+
+    #[test]
+    fn test_backend_filter_matches_assignment() {
+        let registry = registry_with(1, &["w1", "w2"]);
+        let filter = registry.backend_filter("w1").unwrap();
+
+        assert_eq!(filter.len(), assigned(&registry, "w1").len());
+        assert!(registry.backend_filter("missing").is_none());
+    }
+}
@@ -0,0 +1,473 @@
+//! Program rollout tests
+//!
+//! These tests drive rollouts with synthetic worker reports and explicit
+//! timestamps to check wave selection, observation and rollback.
+
+use crate::rollout::*;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use pistonprotection_proto::worker::{
+    ProgramVersion, RolloutPhase, RolloutSpec, WorkerRolloutState,
+};
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+const OLD_DIGEST: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+const NEW_DIGEST: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+
+fn t0() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn spec(waves: &[u32]) -> RolloutSpec {
+    RolloutSpec {
+        program: "xdp_filter".to_string(),
+        version: "0.2.0".to_string(),
+        digest: NEW_DIGEST.to_string(),
+        wave_percentages: waves.to_vec(),
+        observe_seconds: 60,
+        max_drop_rate_increase: 0.1,
+        max_failed_workers: 0,
+        variant: String::new(),
+    }
+}
+
+fn rollout(waves: &[u32]) -> ProgramRollout {
+    let mut spec = spec(waves);
+    validate_spec(&mut spec).unwrap();
+    ProgramRollout::new("rollout-1".to_string(), spec, t0())
+}
+
+/// Fleet of workers running the old version
+fn fleet(count: usize) -> Vec<WorkerObservation> {
+    (0..count)
+        .map(|i| WorkerObservation {
+            worker_id: format!("w{:02}", i),
+            version: "0.1.0".to_string(),
+            digest: OLD_DIGEST.to_string(),
+            load_error: String::new(),
+            drop_rate: 0.01,
+        })
+        .collect()
+}
+
+/// Have every selected worker report the new version
+fn apply_targets(rollout: &ProgramRollout, fleet: &mut [WorkerObservation]) {
+    for worker in fleet.iter_mut() {
+        if let Some(target) = rollout.target_for(&worker.worker_id) {
+            worker.version = target.version;
+            worker.digest = target.digest;
+        }
+    }
+}
+
+fn selected(rollout: &ProgramRollout) -> Vec<String> {
+    rollout.workers.keys().cloned().collect()
+}
+
+/// Run a rollout through the update and observation of its current wave
+fn finish_wave(
+    rollout: &mut ProgramRollout,
+    fleet: &mut [WorkerObservation],
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    apply_targets(rollout, fleet);
+    rollout.advance(fleet, now);
+    assert_eq!(rollout.phase, RolloutPhase::Observing);
+
+    let later = now + Duration::seconds(60);
+    rollout.advance(fleet, later);
+    later
+}
+
+// ============================================================================
+// Spec Validation Tests
+// ============================================================================
+
+#[cfg(test)]
+mod spec_tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_filled_in() {
+        let mut spec = RolloutSpec {
+            wave_percentages: vec![],
+            observe_seconds: 0,
+            max_drop_rate_increase: 0.0,
+            digest: NEW_DIGEST.to_uppercase(),
+            ..spec(&[])
+        };
+        validate_spec(&mut spec).unwrap();
+
+        assert_eq!(spec.wave_percentages, DEFAULT_WAVES);
+        assert_eq!(spec.observe_seconds, DEFAULT_OBSERVE_SECS);
+        assert_eq!(spec.max_drop_rate_increase, DEFAULT_MAX_DROP_RATE_INCREASE);
+        assert_eq!(spec.digest, NEW_DIGEST);
+        assert_eq!(spec.variant, DEFAULT_VARIANT);
+    }
+
+    #[test]
+    fn test_invalid_specs_rejected() {
+        let cases = [
+            RolloutSpec {
+                program: String::new(),
+                ..spec(&[100])
+            },
+            RolloutSpec {
+                digest: "abc".to_string(),
+                ..spec(&[100])
+            },
+            spec(&[50, 10, 100]),
+            spec(&[10, 50]),
+            spec(&[0, 100]),
+            RolloutSpec {
+                max_drop_rate_increase: 1.5,
+                ..spec(&[100])
+            },
+            RolloutSpec {
+                variant: "../compat".to_string(),
+                ..spec(&[100])
+            },
+        ];
+
+        for mut case in cases {
+            assert!(validate_spec(&mut case).is_err(), "{:?} accepted", case);
+        }
+    }
+}
+
+// ============================================================================
+// Wave Tests
+// ============================================================================
+
+#[cfg(test)]
+mod wave_tests {
+    use super::*;
+
+    #[test]
+    fn test_first_wave_rounds_up() {
+        let mut rollout = rollout(&[10, 50, 100]);
+        let fleet = fleet(15);
+
+        assert!(rollout.advance(&fleet, t0()));
+        assert_eq!(rollout.current_wave, 1);
+        assert_eq!(rollout.phase, RolloutPhase::Rolling);
+        assert_eq!(rollout.workers.len(), 2);
+
+        // Only selected workers get the new version
+        let first = &selected(&rollout)[0];
+        assert_eq!(rollout.target_for(first).unwrap().digest, NEW_DIGEST);
+        let unselected = fleet
+            .iter()
+            .find(|w| !rollout.workers.contains_key(&w.worker_id))
+            .unwrap();
+        assert!(rollout.target_for(&unselected.worker_id).is_none());
+    }
+
+    #[test]
+    fn test_waits_for_workers() {
+        let mut rollout = rollout(&[100]);
+        assert!(!rollout.advance(&[], t0()));
+        assert_eq!(rollout.current_wave, 0);
+    }
+
+    #[test]
+    fn test_observes_before_next_wave() {
+        let mut rollout = rollout(&[10, 50, 100]);
+        let mut fleet = fleet(10);
+        rollout.advance(&fleet, t0());
+
+        apply_targets(&rollout, &mut fleet);
+        assert!(!rollout.advance(&fleet, t0() + Duration::seconds(5)));
+        assert_eq!(rollout.phase, RolloutPhase::Observing);
+
+        // Still inside the observation window
+        assert!(!rollout.advance(&fleet, t0() + Duration::seconds(30)));
+        assert_eq!(rollout.current_wave, 1);
+
+        assert!(rollout.advance(&fleet, t0() + Duration::seconds(65)));
+        assert_eq!(rollout.current_wave, 2);
+        assert_eq!(rollout.workers.len(), 5);
+    }
+
+    #[test]
+    fn test_waves_grow_as_supersets() {
+        let mut rollout = rollout(&[10, 50, 100]);
+        let mut fleet = fleet(20);
+        rollout.advance(&fleet, t0());
+        let first = selected(&rollout);
+
+        finish_wave(&mut rollout, &mut fleet, t0());
+        let second = selected(&rollout);
+
+        assert!(first.iter().all(|id| second.contains(id)));
+        assert_eq!(second.len(), 10);
+    }
+
+    #[test]
+    fn test_completes_after_last_wave() {
+        let mut rollout = rollout(&[50, 100]);
+        let mut fleet = fleet(4);
+        rollout.advance(&fleet, t0());
+
+        let now = finish_wave(&mut rollout, &mut fleet, t0());
+        assert_eq!(rollout.current_wave, 2);
+        let now = finish_wave(&mut rollout, &mut fleet, now);
+
+        assert_eq!(rollout.phase, RolloutPhase::Completed);
+        assert!(!rollout.advance(&fleet, now + Duration::seconds(60)));
+
+        // Workers joining later get the new version too
+        assert_eq!(rollout.target_for("new-worker").unwrap().digest, NEW_DIGEST);
+    }
+}
+
+// ============================================================================
+// Rollback Tests
+// ============================================================================
+
+#[cfg(test)]
+mod rollback_tests {
+    use super::*;
+
+    #[test]
+    fn test_load_failure_rolls_back() {
+        let mut rollout = rollout(&[50, 100]);
+        let mut fleet = fleet(4);
+        rollout.advance(&fleet, t0());
+
+        let failing = selected(&rollout)[0].clone();
+        for worker in fleet.iter_mut().filter(|w| w.worker_id == failing) {
+            worker.load_error = "verifier rejected program".to_string();
+        }
+
+        assert!(rollout.advance(&fleet, t0() + Duration::seconds(5)));
+        assert_eq!(rollout.phase, RolloutPhase::RolledBack);
+        assert_eq!(rollout.workers[&failing].state, WorkerRolloutState::Failed);
+        assert_eq!(rollout.workers[&failing].error, "verifier rejected program");
+
+        for id in selected(&rollout) {
+            assert_eq!(rollout.target_for(&id).unwrap().digest, OLD_DIGEST);
+        }
+    }
+
+    #[test]
+    fn test_failures_within_budget_continue() {
+        let mut spec = spec(&[50, 100]);
+        spec.max_failed_workers = 1;
+        validate_spec(&mut spec).unwrap();
+        let mut rollout = ProgramRollout::new("rollout-1".to_string(), spec, t0());
+        let mut fleet = fleet(4);
+        rollout.advance(&fleet, t0());
+
+        apply_targets(&rollout, &mut fleet);
+        let failing = selected(&rollout)[0].clone();
+        for worker in fleet.iter_mut().filter(|w| w.worker_id == failing) {
+            worker.digest = OLD_DIGEST.to_string();
+            worker.load_error = "map missing".to_string();
+        }
+
+        rollout.advance(&fleet, t0() + Duration::seconds(5));
+        assert_eq!(rollout.phase, RolloutPhase::Observing);
+        // The failed worker stays on its old version
+        assert_eq!(rollout.target_for(&failing).unwrap().digest, OLD_DIGEST);
+    }
+
+    #[test]
+    fn test_update_timeout_fails_worker() {
+        let mut rollout = rollout(&[100]);
+        let fleet = fleet(2);
+        rollout.advance(&fleet, t0());
+
+        rollout.advance(&fleet, t0() + Duration::seconds(UPDATE_TIMEOUT_SECS));
+        assert_eq!(rollout.phase, RolloutPhase::Rolling);
+
+        rollout.advance(&fleet, t0() + Duration::seconds(UPDATE_TIMEOUT_SECS + 1));
+        assert_eq!(rollout.phase, RolloutPhase::RolledBack);
+        assert!(
+            rollout
+                .workers
+                .values()
+                .all(|w| w.state == WorkerRolloutState::Failed)
+        );
+    }
+
+    #[test]
+    fn test_drop_rate_increase_rolls_back() {
+        let mut rollout = rollout(&[50, 100]);
+        let mut fleet = fleet(4);
+        rollout.advance(&fleet, t0());
+        apply_targets(&rollout, &mut fleet);
+        rollout.advance(&fleet, t0() + Duration::seconds(5));
+
+        // Updated workers start dropping far more traffic
+        for worker in fleet.iter_mut() {
+            if rollout.workers.contains_key(&worker.worker_id) {
+                worker.drop_rate = 0.5;
+            }
+        }
+
+        assert!(rollout.advance(&fleet, t0() + Duration::seconds(10)));
+        assert_eq!(rollout.phase, RolloutPhase::RolledBack);
+        assert!(rollout.message.contains("Drop rate"));
+        assert!(
+            rollout
+                .workers
+                .values()
+                .all(|w| w.state == WorkerRolloutState::Reverted)
+        );
+    }
+
+    #[test]
+    fn test_small_drop_rate_increase_tolerated() {
+        let mut rollout = rollout(&[50, 100]);
+        let mut fleet = fleet(4);
+        rollout.advance(&fleet, t0());
+        apply_targets(&rollout, &mut fleet);
+        rollout.advance(&fleet, t0() + Duration::seconds(5));
+
+        for worker in fleet.iter_mut() {
+            worker.drop_rate = 0.05;
+        }
+
+        rollout.advance(&fleet, t0() + Duration::seconds(10));
+        assert_eq!(rollout.phase, RolloutPhase::Observing);
+        assert!((rollout.drop_rate_increase().unwrap() - 0.04).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_abort_without_rollback_keeps_updated_workers() {
+        let mut rollout = rollout(&[50, 100]);
+        let mut fleet = fleet(4);
+        rollout.advance(&fleet, t0());
+        apply_targets(&rollout, &mut fleet);
+        rollout.advance(&fleet, t0() + Duration::seconds(5));
+
+        rollout.abort(false, t0() + Duration::seconds(6)).unwrap();
+        assert_eq!(rollout.phase, RolloutPhase::Aborted);
+        for id in selected(&rollout) {
+            assert_eq!(rollout.target_for(&id).unwrap().digest, NEW_DIGEST);
+        }
+
+        // Finished rollouts cannot be aborted again
+        assert!(rollout.abort(true, t0() + Duration::seconds(7)).is_err());
+    }
+
+    #[test]
+    fn test_abort_with_rollback_reverts() {
+        let mut rollout = rollout(&[50, 100]);
+        let fleet = fleet(4);
+        rollout.advance(&fleet, t0());
+
+        rollout.abort(true, t0() + Duration::seconds(1)).unwrap();
+        for id in selected(&rollout) {
+            assert_eq!(rollout.target_for(&id).unwrap().digest, OLD_DIGEST);
+        }
+    }
+}
+
+// ============================================================================
+// Manager Tests
+// ============================================================================
+
+#[cfg(test)]
+mod manager_tests {
+    use super::*;
+
+    #[test]
+    fn test_one_active_rollout_per_program() {
+        let mut manager = RolloutManager::new();
+        let first = manager.start(spec(&[100]), t0()).unwrap();
+        assert!(manager.start(spec(&[100]), t0()).is_err());
+
+        let other = RolloutSpec {
+            program: "xdp_ratelimit".to_string(),
+            ..spec(&[100])
+        };
+        assert!(manager.start(other, t0()).is_ok());
+
+        // Once the first is finished, the program can be rolled out again
+        manager.abort(&first.id, false, t0()).unwrap();
+        assert!(manager.start(spec(&[100]), t0()).is_ok());
+        assert_eq!(manager.list(Some("xdp_filter")).len(), 2);
+        assert_eq!(manager.list(None).len(), 3);
+    }
+
+    #[test]
+    fn test_abort_unknown_rollout() {
+        let mut manager = RolloutManager::new();
+        assert!(manager.abort("missing", true, t0()).is_err());
+    }
+
+    #[test]
+    fn test_targets_from_newest_rollout() {
+        let mut manager = RolloutManager::new();
+        // w1 already runs the first rollout's version
+        let observations = vec![RolloutObservations {
+            worker_id: "w1".to_string(),
+            variant: DEFAULT_VARIANT.to_string(),
+            programs: vec![ProgramVersion {
+                name: "xdp_filter".to_string(),
+                version: "0.2.0".to_string(),
+                digest: NEW_DIGEST.to_string(),
+                ..Default::default()
+            }],
+            drop_rate: 0.0,
+        }];
+
+        let first = manager.start(spec(&[100]), t0()).unwrap();
+        manager.advance(&observations, t0());
+        manager.abort(&first.id, false, t0()).unwrap();
+        assert_eq!(manager.targets_for("w1", "full")[0].version, "0.2.0");
+
+        let newer = RolloutSpec {
+            version: "0.3.0".to_string(),
+            digest: OLD_DIGEST.to_string(),
+            ..spec(&[100])
+        };
+        manager.start(newer, t0()).unwrap();
+        manager.advance(&observations, t0());
+
+        let targets = manager.targets_for("w1", "full");
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].version, "0.3.0");
+        assert!(manager.targets_for("w2", "full").is_empty());
+    }
+
+    #[test]
+    fn test_rollouts_scoped_to_variant() {
+        let mut manager = RolloutManager::new();
+        let observations: Vec<RolloutObservations> = ["full", "compat"]
+            .into_iter()
+            .map(|variant| RolloutObservations {
+                worker_id: format!("w-{}", variant),
+                variant: variant.to_string(),
+                ..Default::default()
+            })
+            .collect();
+
+        let full = manager.start(spec(&[100]), t0()).unwrap();
+        assert_eq!(full.spec.variant, "full");
+        let compat = RolloutSpec {
+            variant: "compat".to_string(),
+            digest: OLD_DIGEST.to_string(),
+            ..spec(&[100])
+        };
+        // Variants of the same program roll out independently
+        let compat = manager.start(compat, t0()).unwrap();
+        manager.advance(&observations, t0());
+
+        let full = manager.get(&full.id).unwrap();
+        assert_eq!(selected(full), ["w-full"]);
+        let compat = manager.get(&compat.id).unwrap();
+        assert_eq!(selected(compat), ["w-compat"]);
+
+        assert_eq!(manager.targets_for("w-full", "full")[0].digest, NEW_DIGEST);
+        assert_eq!(
+            manager.targets_for("w-compat", "compat")[0].digest,
+            OLD_DIGEST
+        );
+        assert!(manager.targets_for("w-compat", "full").is_empty());
+    }
+}
@@ -37,8 +37,11 @@ fn create_valid_config() -> FilterConfig {
             log_sampling_rate: 10,
             emergency_mode: false,
             emergency_pps_threshold: 1_000_000,
+            penalty_ladder: vec![],
         }),
         generated_at: None,
+        canary: None,
+        programs: vec![],
     }
 }
 
@@ -62,6 +65,8 @@ fn create_valid_backend(id: &str) -> BackendFilter {
             ..Default::default()
         }),
         rules: vec![create_valid_rule("rule-1")],
+        organization_id: "org-1".to_string(),
+        domains: vec![],
     }
 }
 
@@ -105,6 +110,8 @@ mod filter_config_tests {
             backends: vec![],
             global: None,
             generated_at: None,
+            canary: None,
+            programs: vec![],
         };
 
         // Empty config_id should be invalid
@@ -131,6 +138,8 @@ mod filter_config_tests {
             ],
             global: None,
             generated_at: None,
+            canary: None,
+            programs: vec![],
         };
 
         // Should have duplicate backend IDs
@@ -221,6 +230,8 @@ mod backend_validation_tests {
                     ..Default::default()
                 },
             ],
+            organization_id: String::new(),
+            domains: vec![],
         };
 
         // Check for duplicate priorities
@@ -310,6 +321,7 @@ mod global_settings_tests {
             log_sampling_rate: 50,
             emergency_mode: false,
             emergency_pps_threshold: 1_000_000,
+            penalty_ladder: vec![],
         };
 
         assert!(settings.log_sampling_rate <= 100);
@@ -323,6 +335,7 @@ mod global_settings_tests {
             log_sampling_rate: 150, // Invalid
             emergency_mode: false,
             emergency_pps_threshold: 0,
+            penalty_ladder: vec![],
         };
 
         assert!(settings.log_sampling_rate > 100);
@@ -335,6 +348,7 @@ mod global_settings_tests {
             log_sampling_rate: 10,
             emergency_mode: true,
             emergency_pps_threshold: 0, // Zero with emergency mode enabled
+            penalty_ladder: vec![],
         };
 
         assert!(settings.emergency_mode && settings.emergency_pps_threshold == 0);
@@ -347,6 +361,7 @@ mod global_settings_tests {
             log_sampling_rate: 10,
             emergency_mode: true,
             emergency_pps_threshold: 500_000,
+            penalty_ladder: vec![],
         };
 
         assert!(settings.emergency_mode);
@@ -433,6 +448,8 @@ mod edge_case_tests {
             backends,
             global: None,
             generated_at: None,
+            canary: None,
+            programs: vec![],
         };
 
         assert_eq!(config.backends.len(), 100);
@@ -458,6 +475,8 @@ mod edge_case_tests {
             protocol: 1,
             protection: None,
             rules,
+            organization_id: String::new(),
+            domains: vec![],
         };
 
         assert_eq!(backend.rules.len(), 50);
@@ -509,6 +528,8 @@ mod edge_case_tests {
             backends: vec![],
             global: None,
             generated_at: None,
+            canary: None,
+            programs: vec![],
         };
 
         assert!(config.config_id.is_empty());
@@ -0,0 +1,23 @@
+-- =============================================================================
+-- Backend Onboarding Migration
+-- =============================================================================
+-- This migration adds the onboarding status of backends added by hostname.
+-- Protection cannot be enabled until their onboarding is active.
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS backend_onboarding (
+    backend_id VARCHAR(36) PRIMARY KEY REFERENCES backends(id) ON DELETE CASCADE,
+    hostname VARCHAR(255) NOT NULL,
+    port INTEGER NOT NULL DEFAULT 0,  -- 0: from SRV record or default port
+    status JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_backend_onboarding_hostname ON backend_onboarding(hostname);
+
+-- Apply update timestamp trigger
+DROP TRIGGER IF EXISTS update_backend_onboarding_updated_at ON backend_onboarding;
+CREATE TRIGGER update_backend_onboarding_updated_at
+    BEFORE UPDATE ON backend_onboarding
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
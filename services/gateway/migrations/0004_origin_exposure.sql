@@ -0,0 +1,26 @@
+-- =============================================================================
+-- Origin Exposure Migration
+-- =============================================================================
+-- This migration adds the latest origin concealment scan of each backend:
+-- DNS records, certificate transparency names and HTTP headers leaking the
+-- origin address.
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS backend_exposure (
+    backend_id VARCHAR(36) PRIMARY KEY REFERENCES backends(id) ON DELETE CASCADE,
+    exposed BOOLEAN NOT NULL DEFAULT FALSE,
+    report JSONB NOT NULL DEFAULT '{}',
+    scanned_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_backend_exposure_scanned ON backend_exposure(scanned_at);
+CREATE INDEX IF NOT EXISTS idx_backend_exposure_exposed ON backend_exposure(exposed)
+    WHERE exposed = TRUE;
+
+-- Apply update timestamp trigger
+DROP TRIGGER IF EXISTS update_backend_exposure_updated_at ON backend_exposure;
+CREATE TRIGGER update_backend_exposure_updated_at
+    BEFORE UPDATE ON backend_exposure
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
@@ -0,0 +1,34 @@
+-- =============================================================================
+-- Origin Switches Migration
+-- =============================================================================
+-- This migration adds zero-downtime origin switches: new connections go to
+-- the new origin while the old one drains over a grace period.
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS origin_switches (
+    id VARCHAR(36) PRIMARY KEY,
+    backend_id VARCHAR(36) NOT NULL REFERENCES backends(id) ON DELETE CASCADE,
+    from_origin_id VARCHAR(36) NOT NULL,
+    to_origin_id VARCHAR(36) NOT NULL,
+    state INTEGER NOT NULL DEFAULT 1,  -- 1: draining, 2: completed, 3: cancelled
+    drain_seconds INTEGER NOT NULL,
+    remaining_connections BIGINT NOT NULL DEFAULT 0,
+    workers_reporting INTEGER NOT NULL DEFAULT 0,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    drain_deadline TIMESTAMPTZ NOT NULL,
+    completed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_origin_switches_backend ON origin_switches(backend_id, started_at DESC);
+
+-- At most one switch in progress per backend
+CREATE UNIQUE INDEX IF NOT EXISTS idx_origin_switches_draining ON origin_switches(backend_id)
+    WHERE state = 1;
+
+-- Apply update timestamp trigger
+DROP TRIGGER IF EXISTS update_origin_switches_updated_at ON origin_switches;
+CREATE TRIGGER update_origin_switches_updated_at
+    BEFORE UPDATE ON origin_switches
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
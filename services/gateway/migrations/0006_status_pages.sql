@@ -0,0 +1,37 @@
+-- =============================================================================
+-- Status Pages Migration
+-- =============================================================================
+-- This migration adds public per-organization status pages, reachable
+-- through a secret token, and the synthetic probe results they are built
+-- from.
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS status_pages (
+    organization_id VARCHAR(36) PRIMARY KEY,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    title VARCHAR(255) NOT NULL DEFAULT '',
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    hidden_backend_ids TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Synthetic probe results, written by the regional probers of the metrics
+-- service
+CREATE TABLE IF NOT EXISTS probe_results_ts (
+    backend_id VARCHAR(36) NOT NULL,
+    region VARCHAR(64) NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    check_kind VARCHAR(16) NOT NULL,
+    availability DOUBLE PRECISION NOT NULL,
+    latency_ms DOUBLE PRECISION,
+    error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_probe_results_backend_time ON probe_results_ts(backend_id, timestamp DESC);
+
+-- Apply update timestamp trigger
+DROP TRIGGER IF EXISTS update_status_pages_updated_at ON status_pages;
+CREATE TRIGGER update_status_pages_updated_at
+    BEFORE UPDATE ON status_pages
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
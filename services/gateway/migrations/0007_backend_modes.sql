@@ -0,0 +1,31 @@
+-- =============================================================================
+-- Backend Modes Migration
+-- =============================================================================
+-- This migration adds per-backend passthrough and block-all modes, which
+-- revert to protection after a timeout, and their audit log.
+-- =============================================================================
+
+-- Backends not listed are protected
+CREATE TABLE IF NOT EXISTS backend_modes (
+    backend_id VARCHAR(36) PRIMARY KEY REFERENCES backends(id) ON DELETE CASCADE,
+    mode INTEGER NOT NULL,  -- 2: passthrough, 3: block all
+    reason TEXT NOT NULL DEFAULT '',
+    changed_by VARCHAR(255) NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revert_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_backend_modes_revert ON backend_modes(revert_at);
+
+CREATE TABLE IF NOT EXISTS backend_mode_audit (
+    id VARCHAR(36) PRIMARY KEY,
+    backend_id VARCHAR(36) NOT NULL,
+    from_mode INTEGER NOT NULL,
+    to_mode INTEGER NOT NULL,
+    reason TEXT NOT NULL DEFAULT '',
+    changed_by VARCHAR(255) NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revert_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_backend_mode_audit_backend ON backend_mode_audit(backend_id, changed_at DESC);
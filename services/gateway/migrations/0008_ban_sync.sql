@@ -0,0 +1,39 @@
+-- =============================================================================
+-- Ban Sync Migration
+-- =============================================================================
+-- This migration adds the synchronization of in-game bans pushed by game
+-- server plugins, which turns the addresses of banned players into a
+-- network-level block.
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS ban_syncs (
+    backend_id VARCHAR(36) PRIMARY KEY REFERENCES backends(id) ON DELETE CASCADE,
+    token VARCHAR(64) NOT NULL UNIQUE,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Filter rule maintained from the active bans
+    rule_id VARCHAR(36) REFERENCES filter_rules(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Ban history; a ban is active until revoked or expired
+CREATE TABLE IF NOT EXISTS player_bans (
+    id VARCHAR(36) PRIMARY KEY,
+    backend_id VARCHAR(36) NOT NULL REFERENCES backends(id) ON DELETE CASCADE,
+    player_uuid UUID,  -- NULL for address bans
+    ip VARCHAR(45),  -- NULL for bans of offline players
+    reason TEXT NOT NULL DEFAULT '',
+    server VARCHAR(255) NOT NULL DEFAULT '',
+    expires_at TIMESTAMPTZ,  -- NULL for permanent bans
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_player_bans_active ON player_bans(backend_id) WHERE revoked_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_player_bans_expiry ON player_bans(expires_at) WHERE revoked_at IS NULL AND expires_at IS NOT NULL;
+
+-- Apply update timestamp trigger
+DROP TRIGGER IF EXISTS update_ban_syncs_updated_at ON ban_syncs;
+CREATE TRIGGER update_ban_syncs_updated_at
+    BEFORE UPDATE ON ban_syncs
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
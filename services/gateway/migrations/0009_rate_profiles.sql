@@ -0,0 +1,27 @@
+-- =============================================================================
+-- Rate Profiles Migration
+-- =============================================================================
+-- This migration adds named per-backend rate limit profiles, applied on a
+-- weekly schedule or triggered by hand for a while.
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS rate_profiles (
+    backend_id VARCHAR(36) PRIMARY KEY REFERENCES backends(id) ON DELETE CASCADE,
+    profiles JSONB NOT NULL DEFAULT '[]',
+    windows JSONB NOT NULL DEFAULT '[]',
+    default_profile VARCHAR(64) NOT NULL DEFAULT '',
+    utc_offset_minutes INTEGER NOT NULL DEFAULT 0,
+    manual_profile VARCHAR(64),
+    manual_until TIMESTAMPTZ,
+    triggered_by VARCHAR(255),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_rate_profiles_manual ON rate_profiles(manual_until) WHERE manual_until IS NOT NULL;
+
+-- Apply update timestamp trigger
+DROP TRIGGER IF EXISTS update_rate_profiles_updated_at ON rate_profiles;
+CREATE TRIGGER update_rate_profiles_updated_at
+    BEFORE UPDATE ON rate_profiles
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
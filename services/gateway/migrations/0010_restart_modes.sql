@@ -0,0 +1,19 @@
+-- =============================================================================
+-- Restart Modes Migration
+-- =============================================================================
+-- This migration adds restart modes, which relax per-source limits and
+-- tighten protocol validation while a game server restarts, ending on their
+-- own after a few minutes.
+-- =============================================================================
+
+-- Backends not listed are not restarting
+CREATE TABLE IF NOT EXISTS restart_modes (
+    backend_id VARCHAR(36) PRIMARY KEY REFERENCES backends(id) ON DELETE CASCADE,
+    connection_multiplier INTEGER NOT NULL,
+    reason TEXT NOT NULL DEFAULT '',
+    started_by VARCHAR(255) NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ends_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_restart_modes_ends ON restart_modes(ends_at);
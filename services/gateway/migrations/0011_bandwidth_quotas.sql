@@ -0,0 +1,44 @@
+-- =============================================================================
+-- Bandwidth Quotas Migration
+-- =============================================================================
+-- This migration adds monthly bandwidth accounting per backend, reported by
+-- workers through the config manager, and the policy applied once an
+-- organization exceeds the bandwidth of its plan.
+-- =============================================================================
+
+-- Traffic of a backend per UTC calendar month
+CREATE TABLE IF NOT EXISTS backend_bandwidth_usage (
+    backend_id VARCHAR(36) NOT NULL REFERENCES backends(id) ON DELETE CASCADE,
+    period_start DATE NOT NULL,
+    packets_in BIGINT NOT NULL DEFAULT 0,
+    bytes_in BIGINT NOT NULL DEFAULT 0,
+    -- Part of bytes_in dropped by the filters
+    bytes_dropped BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (backend_id, period_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_backend_bandwidth_usage_period
+    ON backend_bandwidth_usage(period_start);
+
+-- Organizations not listed are notified only
+CREATE TABLE IF NOT EXISTS bandwidth_quota_policies (
+    organization_id VARCHAR(36) PRIMARY KEY,
+    action VARCHAR(16) NOT NULL DEFAULT 'notify',
+    throttle_bytes_per_second BIGINT NOT NULL,
+    warn_percent INTEGER NOT NULL DEFAULT 80,
+    updated_by VARCHAR(255) NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Enforcement of the quota of an organization in a period
+CREATE TABLE IF NOT EXISTS bandwidth_quota_states (
+    organization_id VARCHAR(36) NOT NULL,
+    period_start DATE NOT NULL,
+    -- Action in effect, NULL while under the limit
+    applied_action VARCHAR(16),
+    warned BOOLEAN NOT NULL DEFAULT FALSE,
+    exceeded_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (organization_id, period_start)
+);
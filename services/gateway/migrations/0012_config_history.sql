@@ -0,0 +1,36 @@
+-- =============================================================================
+-- Configuration History Migration
+-- =============================================================================
+-- This migration adds immutable revisions of the protection settings and
+-- filter rules of each backend, recorded on every change, so the history of
+-- a configuration can be reviewed and an earlier revision restored.
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS config_revisions (
+    backend_id VARCHAR(36) NOT NULL REFERENCES backends(id) ON DELETE CASCADE,
+    revision BIGINT NOT NULL,
+    -- Protection settings and rules by ID after the change
+    snapshot JSONB NOT NULL,
+    -- Changes from the previous revision
+    changes JSONB NOT NULL DEFAULT '[]',
+    summary VARCHAR(255) NOT NULL,
+    changed_by VARCHAR(255) NOT NULL,
+    -- Revision restored by a rollback
+    restored_from BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (backend_id, revision)
+);
+
+-- Revisions are never edited; they go away only with their backend
+CREATE OR REPLACE FUNCTION reject_config_revision_update()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'config revisions are immutable';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS config_revisions_immutable ON config_revisions;
+CREATE TRIGGER config_revisions_immutable
+    BEFORE UPDATE ON config_revisions
+    FOR EACH ROW
+    EXECUTE FUNCTION reject_config_revision_update();
@@ -0,0 +1,43 @@
+-- =============================================================================
+-- Approvals Migration
+-- =============================================================================
+-- This migration adds optional two-person approval of high-impact actions:
+-- deleting a backend, disabling its protection and allowlisting very large
+-- networks. While an organization's policy is enabled these actions are held
+-- as pending until a second member approves or rejects them.
+-- =============================================================================
+
+-- Organizations not listed apply actions right away
+CREATE TABLE IF NOT EXISTS approval_policies (
+    organization_id VARCHAR(36) PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Least organization role of approvers
+    approver_role VARCHAR(16) NOT NULL DEFAULT 'admin',
+    expiry_seconds INTEGER NOT NULL DEFAULT 86400,
+    updated_by VARCHAR(255) NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS pending_actions (
+    id VARCHAR(36) PRIMARY KEY,
+    organization_id VARCHAR(36) NOT NULL,
+    -- Kept when the backend goes, the action stays in the record
+    backend_id VARCHAR(36) NOT NULL,
+    kind VARCHAR(32) NOT NULL,
+    -- Operation applied once approved
+    operation JSONB NOT NULL,
+    description TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    requested_by VARCHAR(255) NOT NULL,
+    decided_by VARCHAR(255),
+    reason TEXT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    decided_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_actions_org
+    ON pending_actions(organization_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_pending_actions_pending
+    ON pending_actions(expires_at) WHERE status = 'pending';
@@ -0,0 +1,33 @@
+-- =============================================================================
+-- Exemption Tokens Migration
+-- =============================================================================
+-- This migration adds load test exemption tokens, which whitelist the tester
+-- addresses of a backend and keep its traffic out of the attack detection
+-- baselines until they expire, and their audit log.
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS exemption_tokens (
+    id VARCHAR(36) PRIMARY KEY,
+    backend_id VARCHAR(36) NOT NULL REFERENCES backends(id) ON DELETE CASCADE,
+    ips JSONB NOT NULL DEFAULT '[]',
+    reason TEXT NOT NULL DEFAULT '',
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_exemption_tokens_backend ON exemption_tokens(backend_id);
+CREATE INDEX IF NOT EXISTS idx_exemption_tokens_expires ON exemption_tokens(expires_at);
+
+-- Kept when the token goes, so the record outlives it
+CREATE TABLE IF NOT EXISTS exemption_token_audit (
+    id VARCHAR(36) PRIMARY KEY,
+    token_id VARCHAR(36) NOT NULL,
+    backend_id VARCHAR(36) NOT NULL,
+    action VARCHAR(16) NOT NULL,  -- created, revoked, expired
+    actor VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_exemption_token_audit_backend
+    ON exemption_token_audit(backend_id, created_at DESC);
@@ -1,7 +1,19 @@
 //! gRPC service handlers
 
 use crate::services::AppState;
+use crate::services::approval::{
+    ApprovalService, Operation, PENDING_ACTION_HEADER, PENDING_ACTION_LIST,
+};
+use crate::services::backend::BACKEND_LIST;
+use crate::services::config_history::REVISION_LIST;
+use crate::services::filter::RULE_LIST;
+use crate::services::idempotency::{
+    Claim, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER, IdempotencyError,
+    IdempotencyService, StoredResponse, canonical_json,
+};
+use crate::services::metrics::ATTACK_EVENT_LIST;
 use futures::StreamExt;
+use pistonprotection_common::pagination::PageRequest;
 use pistonprotection_proto::{
     FILE_DESCRIPTOR_SET,
     backend::{
@@ -17,9 +29,10 @@ use pistonprotection_proto::{
         *,
     },
 };
+use std::future::Future;
 use std::pin::Pin;
 use tokio_stream::Stream;
-use tonic::{Request, Response, Status, transport::Server};
+use tonic::{Request, Response, Status, metadata::MetadataValue, transport::Server};
 use tonic_health::server::health_reporter;
 use tonic_reflection::server::Builder as ReflectionBuilder;
 use tracing::{info, instrument};
@@ -27,12 +40,41 @@ use tracing::{info, instrument};
 /// Backend gRPC service implementation
 pub struct BackendGrpcService {
     service: crate::services::backend::BackendService,
+    approvals: ApprovalService,
+    exposure: crate::services::exposure::ExposureService,
+    switches: crate::services::origin_switch::OriginSwitchService,
+    status_pages: crate::services::status_page::StatusPageService,
+    modes: crate::services::backend_mode::BackendModeService,
+    ban_syncs: crate::services::ban_sync::BanSyncService,
+    rate_profiles: crate::services::rate_profile::RateProfileService,
+    restarts: crate::services::restart_mode::RestartModeService,
+    exemptions: crate::services::exemption::ExemptionService,
+    bandwidth_quotas: crate::services::bandwidth_quota::BandwidthQuotaService,
+    history: crate::services::config_history::ConfigHistoryService,
+    idempotency: IdempotencyService,
 }
 
 impl BackendGrpcService {
     pub fn new(state: AppState) -> Self {
         Self {
-            service: crate::services::backend::BackendService::new(state),
+            service: crate::services::backend::BackendService::new(state.clone()),
+            switches: crate::services::origin_switch::OriginSwitchService::new(state.clone()),
+            status_pages: crate::services::status_page::StatusPageService::new(state.clone()),
+            modes: crate::services::backend_mode::BackendModeService::new(state.clone()),
+            ban_syncs: crate::services::ban_sync::BanSyncService::new(state.clone()),
+            rate_profiles: crate::services::rate_profile::RateProfileService::new(state.clone()),
+            restarts: crate::services::restart_mode::RestartModeService::new(state.clone()),
+            exemptions: crate::services::exemption::ExemptionService::new(state.clone()),
+            bandwidth_quotas: crate::services::bandwidth_quota::BandwidthQuotaService::new(
+                state.clone(),
+            ),
+            history: crate::services::config_history::ConfigHistoryService::new(state.clone()),
+            approvals: ApprovalService::new(state.clone()),
+            idempotency: IdempotencyService::new(state.clone()),
+            exposure: crate::services::exposure::ExposureService::new(
+                state,
+                crate::services::exposure::ExposureConfig::from_env(),
+            ),
         }
     }
 }
@@ -44,20 +86,27 @@ impl BackendServiceTrait for BackendGrpcService {
         &self,
         request: Request<CreateBackendRequest>,
     ) -> Result<Response<CreateBackendResponse>, Status> {
-        let req = request.into_inner();
-        let backend = req
-            .backend
-            .ok_or_else(|| Status::invalid_argument("Backend is required"))?;
-
-        let created = self
-            .service
-            .create(&req.organization_id, backend)
-            .await
-            .map_err(Status::from)?;
-
-        Ok(Response::new(CreateBackendResponse {
-            backend: Some(created),
-        }))
+        idempotent(
+            &self.idempotency,
+            "create_backend",
+            request,
+            |req| async move {
+                let backend = req
+                    .backend
+                    .ok_or_else(|| Status::invalid_argument("Backend is required"))?;
+
+                let created = self
+                    .service
+                    .create(&req.organization_id, backend)
+                    .await
+                    .map_err(Status::from)?;
+
+                Ok(CreateBackendResponse {
+                    backend: Some(created),
+                })
+            },
+        )
+        .await
     }
 
     #[instrument(skip(self, request))]
@@ -100,8 +149,18 @@ impl BackendServiceTrait for BackendGrpcService {
         &self,
         request: Request<DeleteBackendRequest>,
     ) -> Result<Response<DeleteBackendResponse>, Status> {
+        let changed_by = caller(&request);
         let req = request.into_inner();
 
+        if let Some(action) = self
+            .approvals
+            .hold(&req.backend_id, Operation::DeleteBackend, &changed_by)
+            .await
+            .map_err(Status::from)?
+        {
+            return Err(held(&action));
+        }
+
         self.service
             .delete(&req.backend_id)
             .await
@@ -116,257 +175,1166 @@ impl BackendServiceTrait for BackendGrpcService {
         request: Request<ListBackendsRequest>,
     ) -> Result<Response<ListBackendsResponse>, Status> {
         let req = request.into_inner();
-        let pagination = req.pagination.unwrap_or_default();
-        let page = pagination.page.max(1);
-        let page_size = pagination.page_size.clamp(1, 100);
+        let page_request = PageRequest::new(&BACKEND_LIST, req.pagination.as_ref())?;
 
-        let (backends, total) = self
+        let (page, total) = self
             .service
-            .list(&req.organization_id, page, page_size)
+            .list(&req.organization_id, &page_request)
             .await
             .map_err(Status::from)?;
 
-        let has_next = (page * page_size) < total as u32;
-
         Ok(Response::new(ListBackendsResponse {
-            backends,
-            pagination: Some(pistonprotection_proto::common::PaginationInfo {
-                page,
-                page_size,
-                total_count: total as u32,
-                has_next,
-                next_cursor: if has_next {
-                    format!("{}", page + 1)
-                } else {
-                    String::new()
-                },
-            }),
+            pagination: Some(page_request.info(&page, total)),
+            backends: page.items,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn add_origin(
+        &self,
+        request: Request<AddOriginRequest>,
+    ) -> Result<Response<AddOriginResponse>, Status> {
+        let req = request.into_inner();
+        let origin = req
+            .origin
+            .ok_or_else(|| Status::invalid_argument("Origin is required"))?;
+
+        let created = self
+            .service
+            .add_origin(&req.backend_id, origin)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(AddOriginResponse {
+            origin: Some(created),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn update_origin(
+        &self,
+        request: Request<UpdateOriginRequest>,
+    ) -> Result<Response<UpdateOriginResponse>, Status> {
+        let req = request.into_inner();
+        let origin = req
+            .origin
+            .ok_or_else(|| Status::invalid_argument("Origin is required"))?;
+
+        let updated = self
+            .service
+            .update_origin(&req.backend_id, origin)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(UpdateOriginResponse {
+            origin: Some(updated),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn remove_origin(
+        &self,
+        request: Request<RemoveOriginRequest>,
+    ) -> Result<Response<RemoveOriginResponse>, Status> {
+        let req = request.into_inner();
+
+        self.service
+            .remove_origin(&req.backend_id, &req.origin_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(RemoveOriginResponse { success: true }))
+    }
+
+    // =========================================================================
+    // Protection Settings
+    // =========================================================================
+
+    #[instrument(skip(self, request))]
+    async fn update_protection(
+        &self,
+        request: Request<UpdateProtectionRequest>,
+    ) -> Result<Response<UpdateProtectionResponse>, Status> {
+        let changed_by = caller(&request);
+        let req = request.into_inner();
+        let protection = req
+            .protection
+            .ok_or_else(|| Status::invalid_argument("Protection settings are required"))?;
+
+        let operation = Operation::UpdateProtection {
+            protection: protection.clone(),
+        };
+        if let Some(action) = self
+            .approvals
+            .hold(&req.backend_id, operation, &changed_by)
+            .await
+            .map_err(Status::from)?
+        {
+            return Err(held(&action));
+        }
+
+        let updated = self
+            .service
+            .update_protection(&req.backend_id, protection)
+            .await
+            .map_err(Status::from)?;
+        self.history
+            .record_change(&req.backend_id, "protection settings updated", &changed_by)
+            .await;
+
+        Ok(Response::new(UpdateProtectionResponse {
+            protection: Some(updated),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn set_protection_level(
+        &self,
+        request: Request<SetProtectionLevelRequest>,
+    ) -> Result<Response<SetProtectionLevelResponse>, Status> {
+        let changed_by = caller(&request);
+        let req = request.into_inner();
+
+        let level = ProtectionLevel::try_from(req.level)
+            .map_err(|_| Status::invalid_argument("Invalid protection level"))?;
+
+        let operation = Operation::SetProtectionLevel { level: req.level };
+        if let Some(action) = self
+            .approvals
+            .hold(&req.backend_id, operation, &changed_by)
+            .await
+            .map_err(Status::from)?
+        {
+            return Err(held(&action));
+        }
+
+        let updated_level = self
+            .service
+            .set_protection_level(&req.backend_id, level)
+            .await
+            .map_err(Status::from)?;
+        self.history
+            .record_change(&req.backend_id, "protection level changed", &changed_by)
+            .await;
+
+        Ok(Response::new(SetProtectionLevelResponse {
+            level: updated_level as i32,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn set_honeypot(
+        &self,
+        request: Request<SetHoneypotRequest>,
+    ) -> Result<Response<SetHoneypotResponse>, Status> {
+        let changed_by = caller(&request);
+        let req = request.into_inner();
+        let honeypot = req
+            .honeypot
+            .ok_or_else(|| Status::invalid_argument("Honeypot settings are required"))?;
+
+        let updated = self
+            .service
+            .set_honeypot(&req.backend_id, honeypot)
+            .await
+            .map_err(Status::from)?;
+        self.history
+            .record_change(&req.backend_id, "honeypot updated", &changed_by)
+            .await;
+
+        Ok(Response::new(SetHoneypotResponse {
+            honeypot: Some(updated),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn set_block_responses(
+        &self,
+        request: Request<SetBlockResponsesRequest>,
+    ) -> Result<Response<SetBlockResponsesResponse>, Status> {
+        let changed_by = caller(&request);
+        let req = request.into_inner();
+        let block_responses = req
+            .block_responses
+            .ok_or_else(|| Status::invalid_argument("Block response settings are required"))?;
+
+        let updated = self
+            .service
+            .set_block_responses(&req.backend_id, block_responses)
+            .await
+            .map_err(Status::from)?;
+        self.history
+            .record_change(&req.backend_id, "block responses updated", &changed_by)
+            .await;
+
+        Ok(Response::new(SetBlockResponsesResponse {
+            block_responses: Some(updated),
+        }))
+    }
+
+    // =========================================================================
+    // Status and Streaming
+    // =========================================================================
+
+    #[instrument(skip(self, request))]
+    async fn get_backend_status(
+        &self,
+        request: Request<GetBackendStatusRequest>,
+    ) -> Result<Response<GetBackendStatusResponse>, Status> {
+        let req = request.into_inner();
+
+        let status = self
+            .service
+            .get_status(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetBackendStatusResponse {
+            status: Some(status),
+        }))
+    }
+
+    type WatchBackendStatusStream =
+        Pin<Box<dyn Stream<Item = Result<BackendStatus, Status>> + Send>>;
+
+    #[instrument(skip(self, request))]
+    async fn watch_backend_status(
+        &self,
+        request: Request<WatchBackendStatusRequest>,
+    ) -> Result<Response<Self::WatchBackendStatusStream>, Status> {
+        let req = request.into_inner();
+        let backend_id = req.backend_id;
+
+        let stream = self
+            .service
+            .watch_status(&backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        // Convert Result<BackendStatus> to Result<BackendStatus, Status>
+        let mapped_stream = stream.map(|result| result.map_err(Status::from));
+
+        Ok(Response::new(Box::pin(mapped_stream)))
+    }
+
+    // =========================================================================
+    // Domain Management
+    // =========================================================================
+
+    #[instrument(skip(self, request))]
+    async fn add_domain(
+        &self,
+        request: Request<AddDomainRequest>,
+    ) -> Result<Response<AddDomainResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.domain.is_empty() {
+            return Err(Status::invalid_argument("Domain is required"));
+        }
+
+        let (domain, verification_token, verification_method) = self
+            .service
+            .add_domain(&req.backend_id, &req.domain)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(AddDomainResponse {
+            domain,
+            verification_token,
+            verification_method,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn remove_domain(
+        &self,
+        request: Request<RemoveDomainRequest>,
+    ) -> Result<Response<RemoveDomainResponse>, Status> {
+        let req = request.into_inner();
+
+        self.service
+            .remove_domain(&req.backend_id, &req.domain)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(RemoveDomainResponse { success: true }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn verify_domain(
+        &self,
+        request: Request<VerifyDomainRequest>,
+    ) -> Result<Response<VerifyDomainResponse>, Status> {
+        let req = request.into_inner();
+
+        let (verified, error) = self
+            .service
+            .verify_domain(&req.backend_id, &req.domain)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(VerifyDomainResponse { verified, error }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn start_onboarding(
+        &self,
+        request: Request<StartOnboardingRequest>,
+    ) -> Result<Response<StartOnboardingResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.hostname.is_empty() {
+            return Err(Status::invalid_argument("Hostname is required"));
+        }
+
+        let onboarding = self
+            .service
+            .start_onboarding(&req.backend_id, &req.hostname, req.port)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(StartOnboardingResponse {
+            onboarding: Some(onboarding),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn check_onboarding(
+        &self,
+        request: Request<CheckOnboardingRequest>,
+    ) -> Result<Response<CheckOnboardingResponse>, Status> {
+        let req = request.into_inner();
+
+        let onboarding = self
+            .service
+            .check_onboarding(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(CheckOnboardingResponse {
+            onboarding: Some(onboarding),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn switch_origin(
+        &self,
+        request: Request<SwitchOriginRequest>,
+    ) -> Result<Response<SwitchOriginResponse>, Status> {
+        use crate::services::origin_switch::SwitchTarget;
+
+        let req = request.into_inner();
+
+        if req.from_origin_id.is_empty() {
+            return Err(Status::invalid_argument("From origin ID is required"));
+        }
+        let target = match req.to {
+            Some(switch_origin_request::To::ToOriginId(id)) => SwitchTarget::Existing(id),
+            Some(switch_origin_request::To::NewOrigin(origin)) => {
+                SwitchTarget::New(Box::new(origin))
+            }
+            None => return Err(Status::invalid_argument("Target origin is required")),
+        };
+
+        let origin_switch = self
+            .switches
+            .start(
+                &req.backend_id,
+                &req.from_origin_id,
+                target,
+                req.drain_seconds,
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(SwitchOriginResponse {
+            origin_switch: Some(origin_switch),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_origin_switch(
+        &self,
+        request: Request<GetOriginSwitchRequest>,
+    ) -> Result<Response<GetOriginSwitchResponse>, Status> {
+        let req = request.into_inner();
+
+        let origin_switch = self
+            .switches
+            .get(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetOriginSwitchResponse { origin_switch }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn cancel_origin_switch(
+        &self,
+        request: Request<CancelOriginSwitchRequest>,
+    ) -> Result<Response<CancelOriginSwitchResponse>, Status> {
+        let req = request.into_inner();
+
+        let origin_switch = self
+            .switches
+            .cancel(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(CancelOriginSwitchResponse {
+            origin_switch: Some(origin_switch),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_origin_exposure(
+        &self,
+        request: Request<GetOriginExposureRequest>,
+    ) -> Result<Response<GetOriginExposureResponse>, Status> {
+        let req = request.into_inner();
+
+        let report = self
+            .exposure
+            .get_report(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetOriginExposureResponse { report }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn scan_origin_exposure(
+        &self,
+        request: Request<ScanOriginExposureRequest>,
+    ) -> Result<Response<ScanOriginExposureResponse>, Status> {
+        let req = request.into_inner();
+
+        let report = self
+            .exposure
+            .scan(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(ScanOriginExposureResponse {
+            report: Some(report),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_status_page(
+        &self,
+        request: Request<GetStatusPageRequest>,
+    ) -> Result<Response<GetStatusPageResponse>, Status> {
+        let req = request.into_inner();
+
+        let status_page = self
+            .status_pages
+            .get(&req.organization_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetStatusPageResponse { status_page }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn update_status_page(
+        &self,
+        request: Request<UpdateStatusPageRequest>,
+    ) -> Result<Response<UpdateStatusPageResponse>, Status> {
+        let req = request.into_inner();
+
+        let status_page = self
+            .status_pages
+            .update(
+                &req.organization_id,
+                req.enabled,
+                &req.title,
+                req.hidden_backend_ids,
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(UpdateStatusPageResponse {
+            status_page: Some(status_page),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn rotate_status_page_token(
+        &self,
+        request: Request<RotateStatusPageTokenRequest>,
+    ) -> Result<Response<RotateStatusPageTokenResponse>, Status> {
+        let req = request.into_inner();
+
+        let status_page = self
+            .status_pages
+            .rotate_token(&req.organization_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(RotateStatusPageTokenResponse {
+            status_page: Some(status_page),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn set_backend_mode(
+        &self,
+        request: Request<SetBackendModeRequest>,
+    ) -> Result<Response<SetBackendModeResponse>, Status> {
+        let changed_by = caller(&request);
+        let req = request.into_inner();
+
+        let operation = Operation::SetBackendMode {
+            mode: req.mode,
+            revert_after_seconds: req.revert_after_seconds,
+            reason: req.reason.clone(),
+        };
+        if let Some(action) = self
+            .approvals
+            .hold(&req.backend_id, operation, &changed_by)
+            .await
+            .map_err(Status::from)?
+        {
+            return Err(held(&action));
+        }
+
+        let state = self
+            .modes
+            .set(
+                &req.backend_id,
+                req.mode(),
+                req.revert_after_seconds,
+                &req.reason,
+                &changed_by,
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(SetBackendModeResponse { state: Some(state) }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_backend_mode(
+        &self,
+        request: Request<GetBackendModeRequest>,
+    ) -> Result<Response<GetBackendModeResponse>, Status> {
+        let req = request.into_inner();
+
+        let (state, history) = self
+            .modes
+            .get(&req.backend_id, req.history_limit)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetBackendModeResponse {
+            state: Some(state),
+            history,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_ban_sync(
+        &self,
+        request: Request<GetBanSyncRequest>,
+    ) -> Result<Response<GetBanSyncResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let ban_sync = self
+            .ban_syncs
+            .get(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetBanSyncResponse { ban_sync }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn update_ban_sync(
+        &self,
+        request: Request<UpdateBanSyncRequest>,
+    ) -> Result<Response<UpdateBanSyncResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let ban_sync = self
+            .ban_syncs
+            .update(&req.backend_id, req.enabled)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(UpdateBanSyncResponse {
+            ban_sync: Some(ban_sync),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn rotate_ban_sync_token(
+        &self,
+        request: Request<RotateBanSyncTokenRequest>,
+    ) -> Result<Response<RotateBanSyncTokenResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let ban_sync = self
+            .ban_syncs
+            .rotate_token(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(RotateBanSyncTokenResponse {
+            ban_sync: Some(ban_sync),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_rate_profiles(
+        &self,
+        request: Request<GetRateProfilesRequest>,
+    ) -> Result<Response<GetRateProfilesResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let rate_profiles = self
+            .rate_profiles
+            .get(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetRateProfilesResponse {
+            rate_profiles: Some(rate_profiles),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn update_rate_profiles(
+        &self,
+        request: Request<UpdateRateProfilesRequest>,
+    ) -> Result<Response<UpdateRateProfilesResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let rate_profiles = self
+            .rate_profiles
+            .update(
+                &req.backend_id,
+                req.profiles,
+                req.windows,
+                &req.default_profile,
+                req.utc_offset_minutes,
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(UpdateRateProfilesResponse {
+            rate_profiles: Some(rate_profiles),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn trigger_rate_profile(
+        &self,
+        request: Request<TriggerRateProfileRequest>,
+    ) -> Result<Response<TriggerRateProfileResponse>, Status> {
+        let triggered_by = request
+            .extensions()
+            .get::<crate::middleware::auth::AuthContext>()
+            .map(|context| context.user_id.clone())
+            .unwrap_or_else(|| "api".to_string());
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let rate_profiles = self
+            .rate_profiles
+            .trigger(
+                &req.backend_id,
+                &req.profile,
+                req.duration_seconds,
+                &triggered_by,
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(TriggerRateProfileResponse {
+            rate_profiles: Some(rate_profiles),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn start_restart_mode(
+        &self,
+        request: Request<StartRestartModeRequest>,
+    ) -> Result<Response<StartRestartModeResponse>, Status> {
+        let started_by = request
+            .extensions()
+            .get::<crate::middleware::auth::AuthContext>()
+            .map(|context| context.user_id.clone())
+            .unwrap_or_else(|| "api".to_string());
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let restart_mode = self
+            .restarts
+            .start(
+                &req.backend_id,
+                req.duration_minutes,
+                req.connection_multiplier,
+                &req.reason,
+                &started_by,
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(StartRestartModeResponse {
+            restart_mode: Some(restart_mode),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn stop_restart_mode(
+        &self,
+        request: Request<StopRestartModeRequest>,
+    ) -> Result<Response<StopRestartModeResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let restart_mode = self
+            .restarts
+            .stop(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(StopRestartModeResponse {
+            restart_mode: Some(restart_mode),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_restart_mode(
+        &self,
+        request: Request<GetRestartModeRequest>,
+    ) -> Result<Response<GetRestartModeResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let restart_mode = self
+            .restarts
+            .get(&req.backend_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetRestartModeResponse {
+            restart_mode: Some(restart_mode),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn get_bandwidth_quota(
+        &self,
+        request: Request<GetBandwidthQuotaRequest>,
+    ) -> Result<Response<GetBandwidthQuotaResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.organization_id.is_empty() {
+            return Err(Status::invalid_argument("Organization ID is required"));
+        }
+
+        let bandwidth_quota = self
+            .bandwidth_quotas
+            .get(&req.organization_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(GetBandwidthQuotaResponse {
+            bandwidth_quota: Some(bandwidth_quota),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn update_bandwidth_quota(
+        &self,
+        request: Request<UpdateBandwidthQuotaRequest>,
+    ) -> Result<Response<UpdateBandwidthQuotaResponse>, Status> {
+        let updated_by = request
+            .extensions()
+            .get::<crate::middleware::auth::AuthContext>()
+            .map(|context| context.user_id.clone())
+            .unwrap_or_else(|| "api".to_string());
+        let req = request.into_inner();
+
+        if req.organization_id.is_empty() {
+            return Err(Status::invalid_argument("Organization ID is required"));
+        }
+
+        let bandwidth_quota = self
+            .bandwidth_quotas
+            .update(
+                &req.organization_id,
+                req.action(),
+                req.throttle_bytes_per_second,
+                req.warn_percent,
+                &updated_by,
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(UpdateBandwidthQuotaResponse {
+            bandwidth_quota: Some(bandwidth_quota),
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn list_config_revisions(
+        &self,
+        request: Request<ListConfigRevisionsRequest>,
+    ) -> Result<Response<ListConfigRevisionsResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let page_request = PageRequest::new(&REVISION_LIST, req.pagination.as_ref())?;
+
+        let (page, total) = self
+            .history
+            .list(&req.backend_id, &page_request)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(ListConfigRevisionsResponse {
+            pagination: Some(page_request.info(&page, total)),
+            revisions: page.items,
         }))
     }
 
-    // =========================================================================
-    // Origin Management
-    // =========================================================================
-
     #[instrument(skip(self, request))]
-    async fn add_origin(
+    async fn get_config_revision(
         &self,
-        request: Request<AddOriginRequest>,
-    ) -> Result<Response<AddOriginResponse>, Status> {
+        request: Request<GetConfigRevisionRequest>,
+    ) -> Result<Response<GetConfigRevisionResponse>, Status> {
         let req = request.into_inner();
-        let origin = req
-            .origin
-            .ok_or_else(|| Status::invalid_argument("Origin is required"))?;
 
-        let created = self
-            .service
-            .add_origin(&req.backend_id, origin)
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+        if req.revision == 0 {
+            return Err(Status::invalid_argument("Revision is required"));
+        }
+
+        let compare_to = (req.compare_to != 0).then_some(req.compare_to);
+        let (revision, snapshot) = self
+            .history
+            .get(&req.backend_id, req.revision, compare_to)
             .await
             .map_err(Status::from)?;
+        let snapshot = serde_json::to_string(&snapshot)
+            .map_err(|e| Status::internal(format!("Failed to encode snapshot: {}", e)))?;
 
-        Ok(Response::new(AddOriginResponse {
-            origin: Some(created),
+        Ok(Response::new(GetConfigRevisionResponse {
+            revision: Some(revision),
+            snapshot,
         }))
     }
 
     #[instrument(skip(self, request))]
-    async fn update_origin(
+    async fn rollback_config(
         &self,
-        request: Request<UpdateOriginRequest>,
-    ) -> Result<Response<UpdateOriginResponse>, Status> {
+        request: Request<RollbackConfigRequest>,
+    ) -> Result<Response<RollbackConfigResponse>, Status> {
+        let changed_by = caller(&request);
         let req = request.into_inner();
-        let origin = req
-            .origin
-            .ok_or_else(|| Status::invalid_argument("Origin is required"))?;
 
-        let updated = self
-            .service
-            .update_origin(&req.backend_id, origin)
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+        if req.revision == 0 {
+            return Err(Status::invalid_argument("Revision is required"));
+        }
+
+        let (revision, warnings) = self
+            .history
+            .rollback(&req.backend_id, req.revision, &changed_by)
             .await
             .map_err(Status::from)?;
 
-        Ok(Response::new(UpdateOriginResponse {
-            origin: Some(updated),
+        Ok(Response::new(RollbackConfigResponse {
+            revision: Some(revision),
+            warnings,
         }))
     }
 
     #[instrument(skip(self, request))]
-    async fn remove_origin(
+    async fn get_approval_policy(
         &self,
-        request: Request<RemoveOriginRequest>,
-    ) -> Result<Response<RemoveOriginResponse>, Status> {
+        request: Request<GetApprovalPolicyRequest>,
+    ) -> Result<Response<GetApprovalPolicyResponse>, Status> {
         let req = request.into_inner();
 
-        self.service
-            .remove_origin(&req.backend_id, &req.origin_id)
+        if req.organization_id.is_empty() {
+            return Err(Status::invalid_argument("Organization ID is required"));
+        }
+
+        let approval_policy = self
+            .approvals
+            .get_policy(&req.organization_id)
             .await
             .map_err(Status::from)?;
 
-        Ok(Response::new(RemoveOriginResponse { success: true }))
+        Ok(Response::new(GetApprovalPolicyResponse {
+            approval_policy: Some(approval_policy),
+        }))
     }
 
-    // =========================================================================
-    // Protection Settings
-    // =========================================================================
-
     #[instrument(skip(self, request))]
-    async fn update_protection(
+    async fn update_approval_policy(
         &self,
-        request: Request<UpdateProtectionRequest>,
-    ) -> Result<Response<UpdateProtectionResponse>, Status> {
+        request: Request<UpdateApprovalPolicyRequest>,
+    ) -> Result<Response<UpdateApprovalPolicyResponse>, Status> {
+        let updated_by = caller(&request);
         let req = request.into_inner();
-        let protection = req
-            .protection
-            .ok_or_else(|| Status::invalid_argument("Protection settings are required"))?;
 
-        let updated = self
-            .service
-            .update_protection(&req.backend_id, protection)
+        if req.organization_id.is_empty() {
+            return Err(Status::invalid_argument("Organization ID is required"));
+        }
+
+        let approval_policy = self
+            .approvals
+            .update_policy(
+                &req.organization_id,
+                req.enabled,
+                &req.approver_role,
+                req.expiry_seconds,
+                &updated_by,
+            )
             .await
             .map_err(Status::from)?;
 
-        Ok(Response::new(UpdateProtectionResponse {
-            protection: Some(updated),
+        Ok(Response::new(UpdateApprovalPolicyResponse {
+            approval_policy: Some(approval_policy),
         }))
     }
 
     #[instrument(skip(self, request))]
-    async fn set_protection_level(
+    async fn list_pending_actions(
         &self,
-        request: Request<SetProtectionLevelRequest>,
-    ) -> Result<Response<SetProtectionLevelResponse>, Status> {
+        request: Request<ListPendingActionsRequest>,
+    ) -> Result<Response<ListPendingActionsResponse>, Status> {
         let req = request.into_inner();
 
-        let level = ProtectionLevel::try_from(req.level)
-            .map_err(|_| Status::invalid_argument("Invalid protection level"))?;
+        if req.organization_id.is_empty() {
+            return Err(Status::invalid_argument("Organization ID is required"));
+        }
 
-        let updated_level = self
-            .service
-            .set_protection_level(&req.backend_id, level)
+        let page_request = PageRequest::new(&PENDING_ACTION_LIST, req.pagination.as_ref())?;
+
+        let (page, total) = self
+            .approvals
+            .list(&req.organization_id, &page_request)
             .await
             .map_err(Status::from)?;
 
-        Ok(Response::new(SetProtectionLevelResponse {
-            level: updated_level as i32,
+        Ok(Response::new(ListPendingActionsResponse {
+            pagination: Some(page_request.info(&page, total)),
+            actions: page.items,
         }))
     }
 
-    // =========================================================================
-    // Status and Streaming
-    // =========================================================================
-
     #[instrument(skip(self, request))]
-    async fn get_backend_status(
+    async fn approve_pending_action(
         &self,
-        request: Request<GetBackendStatusRequest>,
-    ) -> Result<Response<GetBackendStatusResponse>, Status> {
+        request: Request<ApprovePendingActionRequest>,
+    ) -> Result<Response<ApprovePendingActionResponse>, Status> {
+        let context = decider(&request)?;
         let req = request.into_inner();
 
-        let status = self
-            .service
-            .get_status(&req.backend_id)
+        if req.id.is_empty() {
+            return Err(Status::invalid_argument("Pending action ID is required"));
+        }
+
+        let action = self
+            .approvals
+            .approve(&req.id, &context, &req.reason)
             .await
             .map_err(Status::from)?;
 
-        Ok(Response::new(GetBackendStatusResponse {
-            status: Some(status),
+        Ok(Response::new(ApprovePendingActionResponse {
+            action: Some(action),
         }))
     }
 
-    type WatchBackendStatusStream =
-        Pin<Box<dyn Stream<Item = Result<BackendStatus, Status>> + Send>>;
-
     #[instrument(skip(self, request))]
-    async fn watch_backend_status(
+    async fn reject_pending_action(
         &self,
-        request: Request<WatchBackendStatusRequest>,
-    ) -> Result<Response<Self::WatchBackendStatusStream>, Status> {
+        request: Request<RejectPendingActionRequest>,
+    ) -> Result<Response<RejectPendingActionResponse>, Status> {
+        let context = decider(&request)?;
         let req = request.into_inner();
-        let backend_id = req.backend_id;
 
-        let stream = self
-            .service
-            .watch_status(&backend_id)
+        if req.id.is_empty() {
+            return Err(Status::invalid_argument("Pending action ID is required"));
+        }
+
+        let action = self
+            .approvals
+            .reject(&req.id, &context, &req.reason)
             .await
             .map_err(Status::from)?;
 
-        // Convert Result<BackendStatus> to Result<BackendStatus, Status>
-        let mapped_stream = stream.map(|result| result.map_err(Status::from));
-
-        Ok(Response::new(Box::pin(mapped_stream)))
+        Ok(Response::new(RejectPendingActionResponse {
+            action: Some(action),
+        }))
     }
 
-    // =========================================================================
-    // Domain Management
-    // =========================================================================
-
     #[instrument(skip(self, request))]
-    async fn add_domain(
+    async fn create_exemption_token(
         &self,
-        request: Request<AddDomainRequest>,
-    ) -> Result<Response<AddDomainResponse>, Status> {
+        request: Request<CreateExemptionTokenRequest>,
+    ) -> Result<Response<CreateExemptionTokenResponse>, Status> {
+        let created_by = request
+            .extensions()
+            .get::<crate::middleware::auth::AuthContext>()
+            .map(|context| context.user_id.clone())
+            .unwrap_or_else(|| "api".to_string());
         let req = request.into_inner();
 
-        if req.domain.is_empty() {
-            return Err(Status::invalid_argument("Domain is required"));
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
         }
 
-        let (domain, verification_token, verification_method) = self
-            .service
-            .add_domain(&req.backend_id, &req.domain)
+        let token = self
+            .exemptions
+            .create(
+                &req.backend_id,
+                &req.ips,
+                req.duration_minutes,
+                &req.reason,
+                &created_by,
+            )
             .await
             .map_err(Status::from)?;
 
-        Ok(Response::new(AddDomainResponse {
-            domain,
-            verification_token,
-            verification_method,
+        Ok(Response::new(CreateExemptionTokenResponse {
+            token: Some(token),
         }))
     }
 
     #[instrument(skip(self, request))]
-    async fn remove_domain(
+    async fn revoke_exemption_token(
         &self,
-        request: Request<RemoveDomainRequest>,
-    ) -> Result<Response<RemoveDomainResponse>, Status> {
+        request: Request<RevokeExemptionTokenRequest>,
+    ) -> Result<Response<RevokeExemptionTokenResponse>, Status> {
+        let revoked_by = request
+            .extensions()
+            .get::<crate::middleware::auth::AuthContext>()
+            .map(|context| context.user_id.clone())
+            .unwrap_or_else(|| "api".to_string());
         let req = request.into_inner();
 
-        self.service
-            .remove_domain(&req.backend_id, &req.domain)
+        if req.id.is_empty() {
+            return Err(Status::invalid_argument("Exemption token ID is required"));
+        }
+
+        let token = self
+            .exemptions
+            .revoke(&req.id, &revoked_by)
             .await
             .map_err(Status::from)?;
 
-        Ok(Response::new(RemoveDomainResponse { success: true }))
+        Ok(Response::new(RevokeExemptionTokenResponse {
+            token: Some(token),
+        }))
     }
 
     #[instrument(skip(self, request))]
-    async fn verify_domain(
+    async fn list_exemption_tokens(
         &self,
-        request: Request<VerifyDomainRequest>,
-    ) -> Result<Response<VerifyDomainResponse>, Status> {
+        request: Request<ListExemptionTokensRequest>,
+    ) -> Result<Response<ListExemptionTokensResponse>, Status> {
         let req = request.into_inner();
 
-        let (verified, error) = self
-            .service
-            .verify_domain(&req.backend_id, &req.domain)
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+
+        let (tokens, history) = self
+            .exemptions
+            .list(&req.backend_id, req.history_limit)
             .await
             .map_err(Status::from)?;
 
-        Ok(Response::new(VerifyDomainResponse { verified, error }))
+        Ok(Response::new(ListExemptionTokensResponse {
+            tokens,
+            history,
+        }))
     }
 }
 
 /// Filter gRPC service implementation
 pub struct FilterGrpcService {
     service: crate::services::filter::FilterService,
+    approvals: ApprovalService,
+    history: crate::services::config_history::ConfigHistoryService,
+    idempotency: IdempotencyService,
+    transfer: crate::services::rule_transfer::RuleTransferService,
+    provider_import: crate::services::provider_import::ProviderImportService,
 }
 
 impl FilterGrpcService {
     pub fn new(state: AppState) -> Self {
         Self {
+            approvals: ApprovalService::new(state.clone()),
+            history: crate::services::config_history::ConfigHistoryService::new(state.clone()),
+            idempotency: IdempotencyService::new(state.clone()),
+            transfer: crate::services::rule_transfer::RuleTransferService::new(state.clone()),
+            provider_import: crate::services::provider_import::ProviderImportService::new(
+                state.clone(),
+            ),
             service: crate::services::filter::FilterService::new(state),
         }
     }
+
+    /// Record a revision of the backends of the given rules
+    async fn record_rule_changes(&self, rule_ids: &[String], summary: &str, changed_by: &str) {
+        match self.service.rule_backends(rule_ids).await {
+            Ok(backend_ids) => {
+                for backend_id in &backend_ids {
+                    self.history
+                        .record_change(backend_id, summary, changed_by)
+                        .await;
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "Failed to look up backends of changed rules"),
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -376,20 +1344,42 @@ impl FilterServiceTrait for FilterGrpcService {
         &self,
         request: Request<CreateRuleRequest>,
     ) -> Result<Response<CreateRuleResponse>, Status> {
-        let req = request.into_inner();
-        let rule = req
-            .rule
-            .ok_or_else(|| Status::invalid_argument("Rule is required"))?;
-
-        let created = self
-            .service
-            .create(&req.backend_id, rule)
-            .await
-            .map_err(Status::from)?;
+        let changed_by = caller(&request);
+        idempotent(
+            &self.idempotency,
+            "create_rule",
+            request,
+            |req| async move {
+                let rule = req
+                    .rule
+                    .ok_or_else(|| Status::invalid_argument("Rule is required"))?;
+
+                let operation = Operation::CreateRule { rule: rule.clone() };
+                if let Some(action) = self
+                    .approvals
+                    .hold(&req.backend_id, operation, &changed_by)
+                    .await
+                    .map_err(Status::from)?
+                {
+                    return Err(held(&action));
+                }
 
-        Ok(Response::new(CreateRuleResponse {
-            rule: Some(created),
-        }))
+                let (created, warnings) = self
+                    .service
+                    .create(&req.backend_id, rule)
+                    .await
+                    .map_err(Status::from)?;
+                self.history
+                    .record_change(&req.backend_id, "rule created", &changed_by)
+                    .await;
+
+                Ok(CreateRuleResponse {
+                    rule: Some(created),
+                    warnings,
+                })
+            },
+        )
+        .await
     }
 
     #[instrument(skip(self, request))]
@@ -409,15 +1399,42 @@ impl FilterServiceTrait for FilterGrpcService {
         &self,
         request: Request<UpdateRuleRequest>,
     ) -> Result<Response<UpdateRuleResponse>, Status> {
+        let changed_by = caller(&request);
         let req = request.into_inner();
         let rule = req
             .rule
             .ok_or_else(|| Status::invalid_argument("Rule is required"))?;
 
-        let updated = self.service.update(rule).await.map_err(Status::from)?;
+        let operation = Operation::UpdateRule { rule: rule.clone() };
+        if operation.is_high_impact() {
+            let backend_ids = self
+                .service
+                .rule_backends(std::slice::from_ref(&rule.id))
+                .await
+                .map_err(Status::from)?;
+            for backend_id in &backend_ids {
+                if let Some(action) = self
+                    .approvals
+                    .hold(backend_id, operation.clone(), &changed_by)
+                    .await
+                    .map_err(Status::from)?
+                {
+                    return Err(held(&action));
+                }
+            }
+        }
+
+        let (updated, warnings) = self.service.update(rule).await.map_err(Status::from)?;
+        self.record_rule_changes(
+            std::slice::from_ref(&updated.id),
+            "rule updated",
+            &changed_by,
+        )
+        .await;
 
         Ok(Response::new(UpdateRuleResponse {
             rule: Some(updated),
+            warnings,
         }))
     }
 
@@ -426,12 +1443,24 @@ impl FilterServiceTrait for FilterGrpcService {
         &self,
         request: Request<DeleteRuleRequest>,
     ) -> Result<Response<DeleteRuleResponse>, Status> {
+        let changed_by = caller(&request);
         let req = request.into_inner();
 
+        // Looked up first, the rule is gone afterwards
+        let backend_ids = self
+            .service
+            .rule_backends(std::slice::from_ref(&req.rule_id))
+            .await
+            .map_err(Status::from)?;
         self.service
             .delete(&req.rule_id)
             .await
             .map_err(Status::from)?;
+        for backend_id in &backend_ids {
+            self.history
+                .record_change(backend_id, "rule deleted", &changed_by)
+                .await;
+        }
 
         Ok(Response::new(DeleteRuleResponse { success: true }))
     }
@@ -442,43 +1471,26 @@ impl FilterServiceTrait for FilterGrpcService {
         request: Request<ListRulesRequest>,
     ) -> Result<Response<ListRulesResponse>, Status> {
         let req = request.into_inner();
-        let pagination = req.pagination.unwrap_or_default();
-        let page = pagination.page.max(1);
-        let page_size = pagination.page_size.clamp(1, 100);
+        let page_request = PageRequest::new(&RULE_LIST, req.pagination.as_ref())?;
 
-        let (rules, total) = self
+        let (page, total) = self
             .service
-            .list(&req.backend_id, req.include_disabled, page, page_size)
+            .list(&req.backend_id, req.include_disabled, &page_request)
             .await
             .map_err(Status::from)?;
 
-        let has_next = (page * page_size) < total as u32;
-
         Ok(Response::new(ListRulesResponse {
-            rules,
-            pagination: Some(pistonprotection_proto::common::PaginationInfo {
-                page,
-                page_size,
-                total_count: total as u32,
-                has_next,
-                next_cursor: if has_next {
-                    format!("{}", page + 1)
-                } else {
-                    String::new()
-                },
-            }),
+            pagination: Some(page_request.info(&page, total)),
+            rules: page.items,
         }))
     }
 
-    // =========================================================================
-    // Bulk Operations
-    // =========================================================================
-
     #[instrument(skip(self, request))]
     async fn bulk_create_rules(
         &self,
         request: Request<BulkCreateRulesRequest>,
     ) -> Result<Response<BulkCreateRulesResponse>, Status> {
+        let changed_by = caller(&request);
         let req = request.into_inner();
 
         if req.rules.is_empty() {
@@ -492,15 +1504,19 @@ impl FilterServiceTrait for FilterGrpcService {
             ));
         }
 
-        let (created_rules, errors) = self
+        let (created_rules, errors, warnings) = self
             .service
             .bulk_create(&req.backend_id, req.rules)
             .await
             .map_err(Status::from)?;
+        self.history
+            .record_change(&req.backend_id, "rules created", &changed_by)
+            .await;
 
         Ok(Response::new(BulkCreateRulesResponse {
             rules: created_rules,
             errors,
+            warnings,
         }))
     }
 
@@ -509,6 +1525,7 @@ impl FilterServiceTrait for FilterGrpcService {
         &self,
         request: Request<BulkDeleteRulesRequest>,
     ) -> Result<Response<BulkDeleteRulesResponse>, Status> {
+        let changed_by = caller(&request);
         let req = request.into_inner();
 
         if req.rule_ids.is_empty() {
@@ -522,11 +1539,21 @@ impl FilterServiceTrait for FilterGrpcService {
             ));
         }
 
+        let backend_ids = self
+            .service
+            .rule_backends(&req.rule_ids)
+            .await
+            .map_err(Status::from)?;
         let (deleted_count, errors) = self
             .service
             .bulk_delete(req.rule_ids)
             .await
             .map_err(Status::from)?;
+        for backend_id in &backend_ids {
+            self.history
+                .record_change(backend_id, "rules deleted", &changed_by)
+                .await;
+        }
 
         Ok(Response::new(BulkDeleteRulesResponse {
             deleted_count,
@@ -534,17 +1561,143 @@ impl FilterServiceTrait for FilterGrpcService {
         }))
     }
 
+    #[instrument(skip(self, request))]
+    async fn export_rules(
+        &self,
+        request: Request<ExportRulesRequest>,
+    ) -> Result<Response<ExportRulesResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.organization_id.is_empty() {
+            return Err(Status::invalid_argument("Organization ID is required"));
+        }
+        let format = TransferFormat::try_from(req.format).unwrap_or_default();
+        let content_type =
+            crate::services::rule_transfer::content_type(format).map_err(Status::from)?;
+
+        let backend_id = Some(req.backend_id.as_str()).filter(|id| !id.is_empty());
+        let (data, rule_count) = self
+            .transfer
+            .export(
+                &req.organization_id,
+                backend_id,
+                req.include_disabled,
+                format,
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(ExportRulesResponse {
+            data,
+            content_type: content_type.to_string(),
+            rule_count,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn import_rules(
+        &self,
+        request: Request<ImportRulesRequest>,
+    ) -> Result<Response<ImportRulesResponse>, Status> {
+        let changed_by = caller(&request);
+        let req = request.into_inner();
+
+        if req.organization_id.is_empty() {
+            return Err(Status::invalid_argument("Organization ID is required"));
+        }
+        if req.data.is_empty() {
+            return Err(Status::invalid_argument("No data to import"));
+        }
+        let format = TransferFormat::try_from(req.format).unwrap_or_default();
+
+        let backend_id = Some(req.backend_id.as_str()).filter(|id| !id.is_empty());
+        let report = self
+            .transfer
+            .import(
+                &req.organization_id,
+                backend_id,
+                format,
+                &req.data,
+                req.dry_run,
+            )
+            .await
+            .map_err(Status::from)?;
+        for backend_id in &report.backend_ids {
+            self.history
+                .record_change(backend_id, "rules imported", &changed_by)
+                .await;
+        }
+
+        Ok(Response::new(ImportRulesResponse {
+            total_rows: report.total_rows,
+            imported: report.imported,
+            errors: report.errors,
+            warnings: report.warnings,
+            dry_run: req.dry_run,
+        }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn import_provider_config(
+        &self,
+        request: Request<ImportProviderConfigRequest>,
+    ) -> Result<Response<ImportProviderConfigResponse>, Status> {
+        let changed_by = caller(&request);
+        let req = request.into_inner();
+
+        if req.organization_id.is_empty() {
+            return Err(Status::invalid_argument("Organization ID is required"));
+        }
+        if req.backend_id.is_empty() {
+            return Err(Status::invalid_argument("Backend ID is required"));
+        }
+        if req.data.is_empty() {
+            return Err(Status::invalid_argument("No data to import"));
+        }
+        let provider = ConfigProvider::try_from(req.provider).unwrap_or_default();
+
+        let (total_entries, report, unsupported) = self
+            .provider_import
+            .import(
+                &req.organization_id,
+                &req.backend_id,
+                provider,
+                &req.data,
+                req.dry_run,
+            )
+            .await
+            .map_err(Status::from)?;
+        for backend_id in &report.backend_ids {
+            self.history
+                .record_change(backend_id, "provider config imported", &changed_by)
+                .await;
+        }
+
+        Ok(Response::new(ImportProviderConfigResponse {
+            total_entries: total_entries as u32,
+            imported: report.imported,
+            errors: report.errors,
+            warnings: report.warnings,
+            unsupported,
+            dry_run: req.dry_run,
+        }))
+    }
+
     #[instrument(skip(self, request))]
     async fn reorder_rules(
         &self,
         request: Request<ReorderRulesRequest>,
     ) -> Result<Response<ReorderRulesResponse>, Status> {
+        let changed_by = caller(&request);
         let req = request.into_inner();
 
         self.service
             .reorder(&req.backend_id, req.rule_ids)
             .await
             .map_err(Status::from)?;
+        self.history
+            .record_change(&req.backend_id, "rules reordered", &changed_by)
+            .await;
 
         Ok(Response::new(ReorderRulesResponse { success: true }))
     }
@@ -1040,36 +2193,105 @@ impl MetricsServiceTrait for MetricsGrpcService {
             .map(chrono::DateTime::from)
             .ok_or_else(|| Status::invalid_argument("end_time is required"))?;
 
-        let (page, page_size) = req
-            .pagination
-            .map(|p| (p.page.max(1), p.page_size.clamp(1, 100)))
-            .unwrap_or((1, 20));
+        let page_request = PageRequest::new(&ATTACK_EVENT_LIST, req.pagination.as_ref())?;
 
-        let (events, total) = self
+        let (page, total) = self
             .service
-            .list_attack_events(&req.backend_id, start_time, end_time, page, page_size)
+            .list_attack_events(&req.backend_id, start_time, end_time, &page_request)
             .await
             .map_err(Status::from)?;
 
-        let has_next = (page * page_size) < total as u32;
-
         Ok(Response::new(ListAttackEventsResponse {
-            events,
-            pagination: Some(pistonprotection_proto::common::PaginationInfo {
-                page,
-                page_size,
-                total_count: total as u32,
-                has_next,
-                next_cursor: if has_next {
-                    format!("{}", page + 1)
-                } else {
-                    String::new()
-                },
-            }),
+            pagination: Some(page_request.info(&page, total)),
+            events: page.items,
         }))
     }
 }
 
+/// User making a request, recorded with the changes it makes
+fn caller<T>(request: &Request<T>) -> String {
+    request
+        .extensions()
+        .get::<crate::middleware::auth::AuthContext>()
+        .map(|context| context.user_id.clone())
+        .unwrap_or_else(|| "api".to_string())
+}
+
+/// Authenticated user deciding on a pending action
+fn decider<T>(request: &Request<T>) -> Result<crate::middleware::auth::AuthContext, Status> {
+    request
+        .extensions()
+        .get::<crate::middleware::auth::AuthContext>()
+        .cloned()
+        .ok_or_else(|| Status::unauthenticated("Deciding on pending actions requires a user"))
+}
+
+/// Answer to a request held for approval
+fn held(action: &PendingAction) -> Status {
+    let mut status = Status::failed_precondition(format!(
+        "{} requires approval (pending action {})",
+        action.description, action.id
+    ));
+    if let Ok(value) = MetadataValue::try_from(action.id.as_str()) {
+        status.metadata_mut().insert(PENDING_ACTION_HEADER, value);
+    }
+    status
+}
+
+/// Run a create handler under the request's `idempotency-key`, answering
+/// retries with the stored response instead of running it again
+async fn idempotent<Req, Resp, F, Fut>(
+    idempotency: &IdempotencyService,
+    operation: &str,
+    request: Request<Req>,
+    handler: F,
+) -> Result<Response<Resp>, Status>
+where
+    Req: serde::Serialize,
+    Resp: prost::Message + Default,
+    F: FnOnce(Req) -> Fut,
+    Fut: Future<Output = Result<Resp, Status>>,
+{
+    let key = request
+        .metadata()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|value| value.to_str().map_err(|_| IdempotencyError::InvalidKey))
+        .transpose()?
+        .map(str::to_string);
+    let caller = caller(&request);
+    let req = request.into_inner();
+
+    let claim = idempotency
+        .begin(operation, &caller, key.as_deref(), &canonical_json(&req))
+        .await?;
+    let pending = match claim {
+        Claim::Replay(stored) => {
+            let message = Resp::decode(stored.body().as_slice())
+                .map_err(|_| Status::internal("Stored response could not be decoded"))?;
+            let mut response = Response::new(message);
+            response.metadata_mut().insert(
+                IDEMPOTENT_REPLAYED_HEADER,
+                MetadataValue::from_static("true"),
+            );
+            return Ok(response);
+        }
+        Claim::Proceed(pending) => pending,
+    };
+
+    let result = handler(req).await;
+    if let Some(pending) = pending {
+        match result {
+            Ok(ref message) => {
+                pending
+                    .complete(StoredResponse::new(200, &message.encode_to_vec()))
+                    .await
+            }
+            Err(_) => pending.release().await,
+        }
+    }
+    result.map(Response::new)
+}
+
 /// Create the gRPC server
 pub async fn create_server(
     state: AppState,
@@ -1,8 +1,21 @@
-//! HTTP handlers for health checks and metrics
+//! HTTP handlers for health checks, metrics, public status pages, game
+//! server plugins and the dashboard overview
 
+use crate::middleware::auth::AuthState;
 use crate::services::AppState;
-use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use crate::services::ban_sync::{BanSyncService, PushRequest};
+use crate::services::overview::{OverviewQuery, OverviewService};
+use crate::services::status_page::{StatusPageService, render_html};
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+};
+use pistonprotection_common::error::Error;
 use serde::Serialize;
+use std::sync::Arc;
 use tower_http::{
     compression::CompressionLayer,
     cors::{Any, CorsLayer},
@@ -16,12 +29,24 @@ pub fn create_router(state: AppState) -> Router {
         .allow_methods(Any)
         .allow_headers(Any);
 
+    let auth = AuthState::new(
+        state.config.auth.as_ref(),
+        state.db.clone().map(Arc::new),
+        state.config.is_production(),
+    );
+
     Router::new()
         .route("/health", get(health_check))
         .route("/health/live", get(liveness_check))
         .route("/health/ready", get(readiness_check))
         .route("/metrics", get(metrics))
         .route("/version", get(version))
+        .route("/status/{token}", get(status_page))
+        .route("/status/{token}/json", get(status_page_json))
+        .route("/plugin/v1/bans", post(push_bans))
+        .route("/plugin/v1/blocklist", get(blocklist))
+        .route("/api/v1/overview", get(overview))
+        .layer(Extension(auth))
         .layer(TraceLayer::new_for_http())
         .layer(CompressionLayer::new())
         .layer(cors)
@@ -147,3 +172,135 @@ async fn version() -> impl IntoResponse {
         rust_version: option_env!("RUSTC_VERSION").unwrap_or("unknown"),
     })
 }
+
+/// Public status page
+async fn status_page(State(state): State<AppState>, Path(token): Path<String>) -> Response {
+    match StatusPageService::new(state).public_status(&token).await {
+        Ok(Some(status)) => (
+            [(header::CACHE_CONTROL, STATUS_PAGE_CACHE_CONTROL)],
+            Html(render_html(&status)),
+        )
+            .into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Not Found").into_response(),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to build status page");
+            (StatusCode::SERVICE_UNAVAILABLE, "Status unavailable").into_response()
+        }
+    }
+}
+
+/// Public status page as JSON, for embedding in other sites
+async fn status_page_json(State(state): State<AppState>, Path(token): Path<String>) -> Response {
+    match StatusPageService::new(state).public_status(&token).await {
+        Ok(Some(status)) => (
+            [(header::CACHE_CONTROL, STATUS_PAGE_CACHE_CONTROL)],
+            Json(status),
+        )
+            .into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Not Found").into_response(),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to build status page");
+            (StatusCode::SERVICE_UNAVAILABLE, "Status unavailable").into_response()
+        }
+    }
+}
+
+/// Status pages are public; let browsers and CDNs reuse them briefly
+const STATUS_PAGE_CACHE_CONTROL: &str = "public, max-age=30";
+
+/// Token of a plugin request, from its `Authorization: Bearer` header
+fn bearer_token(headers: &HeaderMap) -> &str {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .unwrap_or_default()
+}
+
+/// Ban events pushed by a game server plugin
+async fn push_bans(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<PushRequest>,
+) -> Response {
+    match BanSyncService::new(state)
+        .push(bearer_token(&headers), request.events)
+        .await
+    {
+        Ok(Some(report)) => Json(report).into_response(),
+        Ok(None) => (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
+        Err(Error::Validation(message)) => (StatusCode::BAD_REQUEST, message).into_response(),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to apply ban events");
+            (StatusCode::SERVICE_UNAVAILABLE, "Ban sync unavailable").into_response()
+        }
+    }
+}
+
+/// Network blocklist pulled by a game server plugin
+async fn blocklist(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    match BanSyncService::new(state)
+        .blocklist(bearer_token(&headers))
+        .await
+    {
+        Ok(Some(blocklist)) => {
+            let etag = blocklist.etag();
+            let unchanged = headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+            if unchanged {
+                (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response()
+            } else {
+                ([(header::ETAG, etag)], Json(blocklist)).into_response()
+            }
+        }
+        Ok(None) => (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to build blocklist");
+            (StatusCode::SERVICE_UNAVAILABLE, "Ban sync unavailable").into_response()
+        }
+    }
+}
+
+/// Landing data of the dashboard, filtered by the caller's role
+async fn overview(
+    State(state): State<AppState>,
+    Extension(auth): Extension<AuthState>,
+    Query(query): Query<OverviewQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let context = match auth.authenticate(&headers, None).await {
+        Ok(Some(context)) => context,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
+        Err(e) => {
+            tracing::debug!(error_category = ?e, "Rejected overview request");
+            return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+        }
+    };
+
+    if context.support_grant_id.is_some() {
+        if let Err(e) = auth.record_support_use(&context, "/api/v1/overview").await {
+            tracing::warn!(error_category = ?e, "Refused unrecorded support access");
+            return (StatusCode::SERVICE_UNAVAILABLE, "Overview unavailable").into_response();
+        }
+    }
+
+    match OverviewService::new(state)
+        .get(&context, query.organization_id.as_deref())
+        .await
+    {
+        Ok(overview) => (
+            [(header::CACHE_CONTROL, "private, no-store")],
+            Json(overview),
+        )
+            .into_response(),
+        Err(Error::Validation(message)) => (StatusCode::BAD_REQUEST, message).into_response(),
+        Err(Error::Forbidden(message)) => (StatusCode::FORBIDDEN, message).into_response(),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to build overview");
+            (StatusCode::SERVICE_UNAVAILABLE, "Overview unavailable").into_response()
+        }
+    }
+}
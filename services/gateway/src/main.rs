@@ -3,8 +3,12 @@
 //! The main API gateway that handles all external requests and routes them
 //! to the appropriate internal services.
 
-use pistonprotection_common::{config::Config, telemetry};
+use pistonprotection_common::{
+    config::{Config, secrets::Secrets},
+    telemetry,
+};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::signal;
 use tokio::sync::watch;
 use tracing::{error, info, warn};
@@ -49,11 +53,15 @@ enum AppError {
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
     // Load configuration
-    let config = Config::load(SERVICE_NAME)?;
+    let mut config = Config::load(SERVICE_NAME)?;
 
     // Initialize telemetry
     telemetry::init(SERVICE_NAME, &config.telemetry)?;
 
+    // Resolve credentials kept in Kubernetes Secrets or Vault
+    let secrets = Arc::new(Secrets::from_env()?);
+    let db_credentials = config.resolve_secrets(&secrets).await?;
+
     info!(
         service = SERVICE_NAME,
         version = env!("CARGO_PKG_VERSION"),
@@ -61,13 +69,20 @@ async fn main() -> Result<(), AppError> {
     );
 
     // Initialize database connection
-    let db_pool = match &config.database {
+    let db_pools = match &config.database {
         Some(db_config) => {
             info!("Initializing database connection pool");
-            match pistonprotection_common::db::create_pool(db_config).await {
-                Ok(pool) => {
+            match pistonprotection_common::db::create_pools(db_config).await {
+                Ok(pools) => {
                     info!("Database connection pool initialized successfully");
-                    Some(pool)
+                    if let Some(credentials) = db_credentials {
+                        pistonprotection_common::db::spawn_credential_renewal(
+                            pools.pools(),
+                            Arc::clone(&secrets),
+                            credentials,
+                        );
+                    }
+                    Some(pools)
                 }
                 Err(e) => {
                     error!(error = %e, "Failed to initialize database connection pool");
@@ -103,7 +118,10 @@ async fn main() -> Result<(), AppError> {
     };
 
     // Create shared state
-    let app_state = services::AppState::new(db_pool, redis_pool, config.clone());
+    let mut app_state = services::AppState::new(None, redis_pool, config.clone());
+    if let Some(pools) = db_pools {
+        app_state = app_state.with_db_pools(pools);
+    }
 
     // Create shutdown channel
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
@@ -178,6 +196,49 @@ async fn main() -> Result<(), AppError> {
         Ok::<_, tonic::transport::Error>(())
     });
 
+    // Start origin exposure scans
+    let exposure_handle = services::exposure::spawn_scanner(
+        app_state.clone(),
+        services::exposure::ExposureConfig::from_env(),
+        shutdown_rx.clone(),
+    );
+
+    // Complete origin switches once drained
+    let switch_handle =
+        services::origin_switch::spawn_monitor(app_state.clone(), shutdown_rx.clone());
+
+    // Revert backend modes once their timer expired
+    let mode_handle = services::backend_mode::spawn_monitor(app_state.clone(), shutdown_rx.clone());
+
+    // Lift expired in-game bans from the network blocklist
+    let ban_handle = services::ban_sync::spawn_monitor(app_state.clone(), shutdown_rx.clone());
+
+    // Keep rate limit profiles published to workers
+    let profile_handle =
+        services::rate_profile::spawn_monitor(app_state.clone(), shutdown_rx.clone());
+
+    // End restart modes once their time is up
+    let restart_handle =
+        services::restart_mode::spawn_monitor(app_state.clone(), shutdown_rx.clone());
+
+    // Remove load test exemption tokens once expired
+    let exemption_handle =
+        services::exemption::spawn_monitor(app_state.clone(), shutdown_rx.clone());
+
+    // Enforce monthly bandwidth quotas
+    let quota_handle =
+        services::bandwidth_quota::spawn_monitor(app_state.clone(), shutdown_rx.clone());
+
+    // Suspend and restore the protection of expired trials
+    let trial_handle =
+        services::trial_suspension::spawn_monitor(app_state.clone(), shutdown_rx.clone());
+
+    // Move read replicas in and out of rotation by replication lag
+    let replica_handle = app_state
+        .db_pools
+        .as_ref()
+        .and_then(|pools| pools.spawn_replica_monitor());
+
     // Wait for shutdown signal
     shutdown_signal().await;
     info!("Shutdown signal received, initiating graceful shutdown...");
@@ -216,6 +277,24 @@ async fn main() -> Result<(), AppError> {
         }
     }
 
+    for handle in [
+        exposure_handle,
+        switch_handle,
+        mode_handle,
+        ban_handle,
+        profile_handle,
+        restart_handle,
+        exemption_handle,
+        quota_handle,
+        trial_handle,
+        replica_handle,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        handle.abort();
+    }
+
     // Cleanup telemetry
     telemetry::shutdown();
     info!("Shutdown complete");
@@ -16,6 +16,8 @@ use sqlx::PgPool;
 use tower::{Layer, Service};
 use tracing::{debug, error, warn};
 
+use crate::services::audit::AuditLogBuilder;
+
 type BoxBody = UnsyncBoxBody<Bytes, tonic::Status>;
 
 /// JWT claims structure (must match auth service claims)
@@ -45,8 +47,11 @@ pub struct Claims {
     /// Session ID (for session-based JWTs)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sid: Option<String>,
-    /// Token type (access, refresh)
+    /// Token type (access, refresh, support)
     pub typ: String,
+    /// Support access grant a support token was issued under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant: Option<String>,
 }
 
 /// Authentication context extracted from the request
@@ -62,6 +67,10 @@ pub struct AuthContext {
     pub organizations: Vec<String>,
     /// Authentication method used
     pub auth_method: AuthMethod,
+    /// ID of the API key, when authenticated with one
+    pub api_key_id: Option<String>,
+    /// Support access grant, when support staff act under one (read-only)
+    pub support_grant_id: Option<String>,
 }
 
 /// Authentication method
@@ -104,9 +113,11 @@ impl JwtValidator {
                 AuthError::InvalidToken(e.to_string())
             })?;
 
-        // Ensure it's an access token
-        if token_data.claims.typ != "access" {
-            return Err(AuthError::InvalidTokenType);
+        // Ensure it's an access token, or a support token bound to a grant
+        match token_data.claims.typ.as_str() {
+            "access" => {}
+            "support" if token_data.claims.grant.is_some() => {}
+            _ => return Err(AuthError::InvalidTokenType),
         }
 
         Ok(token_data.claims)
@@ -180,6 +191,8 @@ impl ApiKeyValidator {
                 .map(|id| vec![id])
                 .unwrap_or_default(),
             auth_method: AuthMethod::ApiKey,
+            api_key_id: Some(api_key_row.id),
+            support_grant_id: None,
         })
     }
 }
@@ -202,6 +215,93 @@ struct ApiKeyRow {
     user_role: String,
 }
 
+/// Support access grant checks using database
+///
+/// Support tokens outlive neither their grant nor its revocation: every
+/// request made with one checks the grant is still active, and is recorded
+/// in the organization's audit log.
+pub struct SupportAccessValidator {
+    db_pool: Option<Arc<PgPool>>,
+}
+
+impl SupportAccessValidator {
+    /// Create a new support access validator
+    pub fn new(db_pool: Option<Arc<PgPool>>) -> Self {
+        Self { db_pool }
+    }
+
+    /// Ensure the grant of a support token is still active for its organization
+    pub async fn validate(&self, grant_id: &str, organization_id: &str) -> Result<(), AuthError> {
+        let pool = self
+            .db_pool
+            .as_ref()
+            .ok_or(AuthError::DatabaseNotAvailable)?;
+
+        let active: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM support_access_grants
+                WHERE id = $1 AND organization_id = $2
+                AND revoked_at IS NULL AND expires_at > NOW()
+            )
+            "#,
+        )
+        .bind(grant_id)
+        .bind(organization_id)
+        .fetch_one(pool.as_ref())
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Database error validating support access grant");
+            AuthError::DatabaseError(e.to_string())
+        })?;
+
+        if active {
+            Ok(())
+        } else {
+            Err(AuthError::SupportAccessRevoked)
+        }
+    }
+
+    /// Record a request made under a grant in the organization's audit log
+    ///
+    /// Requests that cannot be recorded are refused, as support access is
+    /// only granted audited.
+    pub async fn record_use(&self, context: &AuthContext, method: &str) -> Result<(), AuthError> {
+        let pool = self
+            .db_pool
+            .as_ref()
+            .ok_or(AuthError::DatabaseNotAvailable)?;
+        let (Some(grant_id), Some(organization_id)) = (
+            context.support_grant_id.as_deref(),
+            context.organizations.first(),
+        ) else {
+            return Err(AuthError::InvalidTokenType);
+        };
+
+        AuditLogBuilder::new(organization_id, "support_access.used", "support_access")
+            .user(&context.user_id, Some(&context.email))
+            .resource(grant_id)
+            .description(&format!("Support staff called {}", method))
+            .metadata("method", method)
+            .write(pool)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Database error recording support access use");
+                AuthError::DatabaseError(e.to_string())
+            })
+    }
+}
+
+/// Whether a gRPC method only reads, and so may be called with a support token
+pub fn is_read_only_method(path: &str) -> bool {
+    const READ_PREFIXES: [&str; 5] = ["Get", "List", "Stream", "Watch", "Search"];
+
+    let method = path.rsplit('/').next().unwrap_or_default();
+    READ_PREFIXES
+        .iter()
+        .any(|prefix| method.starts_with(prefix))
+}
+
 /// Authentication errors
 #[derive(Debug, thiserror::Error)]
 pub enum AuthError {
@@ -225,6 +325,12 @@ pub enum AuthError {
 
     #[error("Token expired")]
     TokenExpired,
+
+    #[error("Support access revoked or expired")]
+    SupportAccessRevoked,
+
+    #[error("Support access is read-only")]
+    SupportAccessReadOnly,
 }
 
 impl From<AuthError> for tonic::Status {
@@ -235,6 +341,12 @@ impl From<AuthError> for tonic::Status {
             AuthError::InvalidTokenType => tonic::Status::unauthenticated("Invalid token type"),
             AuthError::InvalidApiKey => tonic::Status::unauthenticated("Invalid API key"),
             AuthError::TokenExpired => tonic::Status::unauthenticated("Token expired"),
+            AuthError::SupportAccessRevoked => {
+                tonic::Status::unauthenticated("Support access revoked or expired")
+            }
+            AuthError::SupportAccessReadOnly => {
+                tonic::Status::permission_denied("Support access is read-only")
+            }
             AuthError::DatabaseNotAvailable => {
                 tonic::Status::unavailable("Authentication service unavailable")
             }
@@ -248,6 +360,7 @@ impl From<AuthError> for tonic::Status {
 pub struct AuthState {
     jwt_validator: Option<Arc<JwtValidator>>,
     api_key_validator: Arc<ApiKeyValidator>,
+    support_access_validator: Arc<SupportAccessValidator>,
     public_paths: HashSet<String>,
     skip_auth: bool,
     is_production: bool,
@@ -277,7 +390,8 @@ impl AuthState {
 
         Self {
             jwt_validator,
-            api_key_validator: Arc::new(ApiKeyValidator::new(db_pool)),
+            api_key_validator: Arc::new(ApiKeyValidator::new(db_pool.clone())),
+            support_access_validator: Arc::new(SupportAccessValidator::new(db_pool)),
             public_paths,
             skip_auth,
             is_production,
@@ -290,9 +404,14 @@ impl AuthState {
     }
 
     /// Validate the request and return auth context
-    async fn authenticate(
+    ///
+    /// `method` is the gRPC method called, if any. Support tokens calling a
+    /// method that is not read-only are refused before their grant is
+    /// checked.
+    pub(crate) async fn authenticate(
         &self,
         headers: &http::HeaderMap,
+        method: Option<&str>,
     ) -> Result<Option<AuthContext>, AuthError> {
         // Try JWT first (Authorization: Bearer <token>)
         if let Some(auth_header) = headers.get("authorization") {
@@ -300,12 +419,25 @@ impl AuthState {
                 if let Some(token) = auth_str.strip_prefix("Bearer ") {
                     if let Some(ref validator) = self.jwt_validator {
                         let claims = validator.validate(token)?;
+                        if let Some(ref grant_id) = claims.grant {
+                            // Support staff only read, whatever the method would allow
+                            if method.is_some_and(|method| !is_read_only_method(method)) {
+                                return Err(AuthError::SupportAccessReadOnly);
+                            }
+                            let organization_id =
+                                claims.orgs.first().ok_or(AuthError::InvalidTokenType)?;
+                            self.support_access_validator
+                                .validate(grant_id, organization_id)
+                                .await?;
+                        }
                         return Ok(Some(AuthContext {
                             user_id: claims.sub,
                             email: claims.email,
                             role: claims.role,
                             organizations: claims.orgs,
                             auth_method: AuthMethod::Jwt,
+                            api_key_id: None,
+                            support_grant_id: claims.grant,
                         }));
                     }
                 }
@@ -323,6 +455,17 @@ impl AuthState {
         // No authentication provided
         Ok(None)
     }
+
+    /// Record a request made with a support token in the audit log
+    pub(crate) async fn record_support_use(
+        &self,
+        context: &AuthContext,
+        path: &str,
+    ) -> Result<(), AuthError> {
+        self.support_access_validator
+            .record_use(context, path)
+            .await
+    }
 }
 
 /// Authentication middleware
@@ -377,7 +520,7 @@ where
             }
 
             // Authenticate the request
-            match state.authenticate(req.headers()).await {
+            match state.authenticate(req.headers(), Some(path.as_str())).await {
                 Ok(Some(context)) => {
                     debug!(
                         user_id = %context.user_id,
@@ -386,6 +529,25 @@ where
                         "Request authenticated"
                     );
 
+                    if context.support_grant_id.is_some() {
+                        if let Err(e) = state.record_support_use(&context, &path).await {
+                            warn!(path = %path, error_category = ?e, "Refused unrecorded support access");
+
+                            let status: tonic::Status = e.into();
+                            let response = http::Response::builder()
+                                .status(http::StatusCode::SERVICE_UNAVAILABLE)
+                                .header("content-type", "application/grpc")
+                                .header("grpc-status", "14") // UNAVAILABLE
+                                .header("grpc-message", status.message())
+                                .body(UnsyncBoxBody::default())
+                                .expect(
+                                    "auth error response should always build with valid inputs",
+                                );
+
+                            return Ok(response);
+                        }
+                    }
+
                     // Add auth context to request extensions for handlers to access
                     req.extensions_mut().insert(context);
                     inner.call(req).await
@@ -404,6 +566,19 @@ where
 
                     Ok(response)
                 }
+                Err(AuthError::SupportAccessReadOnly) => {
+                    warn!(path = %path, "Refused write with a support token");
+
+                    let response = http::Response::builder()
+                        .status(http::StatusCode::FORBIDDEN)
+                        .header("content-type", "application/grpc")
+                        .header("grpc-status", "7") // PERMISSION_DENIED
+                        .header("grpc-message", "Support access is read-only")
+                        .body(UnsyncBoxBody::default())
+                        .expect("auth error response should always build with valid inputs");
+
+                    Ok(response)
+                }
                 Err(e) => {
                     // Log only the error category, not full details to avoid information leakage
                     warn!(path = %path, error_category = ?e, "Authentication failed");
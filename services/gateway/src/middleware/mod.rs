@@ -2,4 +2,5 @@
 
 pub mod auth;
 pub mod logging;
+pub mod quota;
 pub mod ratelimit;
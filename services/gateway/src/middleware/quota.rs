@@ -0,0 +1,744 @@
+//! API rate limiting and quotas
+//!
+//! Authenticated requests are counted per API key (or per user for JWT
+//! sessions) and per organization, in sliding windows kept in Redis. The
+//! limits come from the organization's plan: a per-minute limit for the
+//! organization as a whole, a per-minute limit for each key and a per-second
+//! burst limit for each key.
+//!
+//! A sliding window is approximated from two fixed windows: the count of the
+//! previous window, weighted by how much of it still overlaps the sliding
+//! window, plus the count of the current one. All windows of a scope are
+//! checked and counted by one script; a rejected request is not counted.
+//!
+//! Every answer carries `RateLimit-Limit`, `RateLimit-Remaining`,
+//! `RateLimit-Reset` and `RateLimit-Policy` headers for the most constrained
+//! window. Rejected requests get `429` with `Retry-After`, as
+//! `RESOURCE_EXHAUSTED` for gRPC clients and as an `application/problem+json`
+//! body otherwise. The first rejection of a scope in a minute is written to
+//! the organization's audit log.
+//!
+//! The layer reads the [`AuthContext`] left by the auth middleware, so it
+//! must sit inside it; unauthenticated requests are not limited here. When
+//! Redis is unreachable requests are let through.
+
+use crate::middleware::auth::AuthContext;
+use crate::services::audit::AuditLogBuilder;
+use bytes::Bytes;
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full};
+use pistonprotection_common::metrics::API_QUOTA_DECISIONS_TOTAL;
+use pistonprotection_common::redis::CacheService;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tower::{Layer, Service};
+use tracing::{debug, warn};
+
+type BoxBody = UnsyncBoxBody<Bytes, tonic::Status>;
+
+/// Plan used for organizations without an active subscription
+const DEFAULT_PLAN: &str = "free";
+
+/// How long an organization's plan is cached
+const PLAN_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Checks and counts the windows of one scope.
+///
+/// KEYS: current and previous counter of each window.
+/// ARGV: now in milliseconds, then length in milliseconds and limit of each
+/// window.
+/// Returns whether the request was allowed, then the count of each window
+/// including the request when it was allowed.
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local counts = {}
+local allowed = 1
+for i = 1, #KEYS / 2 do
+    local size = tonumber(ARGV[i * 2])
+    local limit = tonumber(ARGV[i * 2 + 1])
+    local current = tonumber(redis.call('GET', KEYS[i * 2 - 1]) or '0')
+    local previous = tonumber(redis.call('GET', KEYS[i * 2]) or '0')
+    local count = math.floor(previous * (1 - (now % size) / size)) + current
+    if count >= limit then
+        allowed = 0
+    end
+    counts[i] = count
+end
+if allowed == 1 then
+    for i = 1, #KEYS / 2 do
+        redis.call('INCR', KEYS[i * 2 - 1])
+        redis.call('PEXPIRE', KEYS[i * 2 - 1], tonumber(ARGV[i * 2]) * 2)
+        counts[i] = counts[i] + 1
+    end
+end
+table.insert(counts, 1, allowed)
+return counts
+"#;
+
+/// Limits of one plan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlanQuota {
+    /// Requests per minute across the organization
+    pub org_per_minute: u64,
+    /// Requests per minute for each API key or user
+    pub key_per_minute: u64,
+    /// Requests per second for each API key or user
+    pub burst_per_second: u64,
+}
+
+impl PlanQuota {
+    /// Parse `org_per_minute,key_per_minute,burst_per_second`
+    fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(',').map(|part| part.trim().parse().ok());
+        let quota = Self {
+            org_per_minute: parts.next()??,
+            key_per_minute: parts.next()??,
+            burst_per_second: parts.next()??,
+        };
+        parts.next().is_none().then_some(quota)
+    }
+}
+
+/// API quota settings
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    /// Limits by plan type
+    pub plans: HashMap<String, PlanQuota>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        let plans = [
+            ("free", 120, 60, 10),
+            ("starter", 600, 300, 30),
+            ("pro", 3000, 1200, 100),
+            ("enterprise", 12000, 6000, 300),
+        ]
+        .into_iter()
+        .map(|(plan, org, key, burst)| {
+            (
+                plan.to_string(),
+                PlanQuota {
+                    org_per_minute: org,
+                    key_per_minute: key,
+                    burst_per_second: burst,
+                },
+            )
+        })
+        .collect();
+        Self { plans }
+    }
+}
+
+impl QuotaConfig {
+    /// Override plan limits from `PISTON_API_QUOTA_<PLAN>` environment
+    /// variables, formatted `org_per_minute,key_per_minute,burst_per_second`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        for (plan, quota) in config.plans.iter_mut() {
+            let name = format!("PISTON_API_QUOTA_{}", plan.to_uppercase());
+            if let Ok(value) = std::env::var(&name) {
+                match PlanQuota::parse(&value) {
+                    Some(parsed) => *quota = parsed,
+                    None => warn!("Ignoring invalid {}: {}", name, value),
+                }
+            }
+        }
+        config
+    }
+
+    /// Limits of a plan, falling back to the default plan
+    fn plan(&self, plan: &str) -> PlanQuota {
+        self.plans
+            .get(plan)
+            .or_else(|| self.plans.get(DEFAULT_PLAN))
+            .copied()
+            .unwrap_or(PlanQuota {
+                org_per_minute: 120,
+                key_per_minute: 60,
+                burst_per_second: 10,
+            })
+    }
+}
+
+/// What a set of counters belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Scope {
+    ApiKey(String),
+    User(String),
+    Organization(String),
+}
+
+impl Scope {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::ApiKey(_) => "api_key",
+            Self::User(_) => "user",
+            Self::Organization(_) => "organization",
+        }
+    }
+
+    fn id(&self) -> &str {
+        match self {
+            Self::ApiKey(id) | Self::User(id) | Self::Organization(id) => id,
+        }
+    }
+
+    /// Hash tag keeping every counter of the scope in one cluster slot
+    fn tag(&self) -> String {
+        format!("{{{}:{}}}", self.kind(), self.id())
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::ApiKey(_) => "this API key",
+            Self::User(_) => "this user",
+            Self::Organization(_) => "this organization",
+        }
+    }
+}
+
+/// A fixed-length window and its limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Window {
+    secs: u64,
+    limit: u64,
+}
+
+/// Scopes and windows that apply to a request
+fn checks(context: &AuthContext, quota: PlanQuota) -> Vec<(Scope, Vec<Window>)> {
+    let caller = match &context.api_key_id {
+        Some(key_id) => Scope::ApiKey(key_id.clone()),
+        None => Scope::User(context.user_id.clone()),
+    };
+    let mut checks = vec![(
+        caller,
+        vec![
+            Window {
+                secs: 60,
+                limit: quota.key_per_minute,
+            },
+            Window {
+                secs: 1,
+                limit: quota.burst_per_second,
+            },
+        ],
+    )];
+    if let Some(org) = context.organizations.first() {
+        checks.push((
+            Scope::Organization(org.clone()),
+            vec![Window {
+                secs: 60,
+                limit: quota.org_per_minute,
+            }],
+        ));
+    }
+    checks
+}
+
+/// State of one window after a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WindowState {
+    window: Window,
+    remaining: u64,
+    /// Seconds until the current fixed window ends
+    reset: u64,
+    exceeded: bool,
+}
+
+impl WindowState {
+    fn new(window: Window, count: u64, now_ms: u64) -> Self {
+        let size_ms = window.secs * 1000;
+        Self {
+            window,
+            remaining: window.limit.saturating_sub(count),
+            reset: (size_ms - now_ms % size_ms).div_ceil(1000),
+            exceeded: count >= window.limit,
+        }
+    }
+}
+
+/// Outcome of all checks of a request
+#[derive(Debug)]
+struct Decision {
+    /// Every window checked, for `RateLimit-Policy`
+    windows: Vec<Window>,
+    /// Window reported in the headers: the one that rejected the request, or
+    /// the one with the least headroom
+    reported: WindowState,
+    /// Scope that rejected the request
+    limited: Option<Scope>,
+}
+
+impl Decision {
+    fn allowed(&self) -> bool {
+        self.limited.is_none()
+    }
+
+    fn policy(&self) -> String {
+        self.windows
+            .iter()
+            .map(|window| format!("{};w={}", window.limit, window.secs))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn apply_headers(&self, headers: &mut http::HeaderMap) {
+        let values = [
+            ("ratelimit-limit", self.reported.window.limit.to_string()),
+            ("ratelimit-remaining", self.reported.remaining.to_string()),
+            ("ratelimit-reset", self.reported.reset.to_string()),
+            ("ratelimit-policy", self.policy()),
+        ];
+        for (name, value) in values {
+            if let Ok(value) = http::HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+        if !self.allowed() {
+            headers.insert("retry-after", self.reported.reset.into());
+        }
+    }
+}
+
+/// RFC 9457 problem details of a rejected request
+#[derive(Debug, Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    retry_after: u64,
+}
+
+/// Build the answer to a rejected request
+fn rejection(decision: &Decision, scope: &Scope, grpc: bool) -> http::Response<BoxBody> {
+    let window = decision.reported.window;
+    let detail = format!(
+        "Rate limit of {} requests per {}s exceeded for {}",
+        window.limit,
+        window.secs,
+        scope.describe()
+    );
+
+    let mut builder = http::Response::builder().status(http::StatusCode::TOO_MANY_REQUESTS);
+    let body = if grpc {
+        builder = builder
+            .header("content-type", "application/grpc")
+            .header("grpc-status", "8") // RESOURCE_EXHAUSTED
+            .header("grpc-message", detail.as_str());
+        Bytes::new()
+    } else {
+        builder = builder.header("content-type", "application/problem+json");
+        let problem = Problem {
+            kind: "about:blank",
+            title: "Too Many Requests",
+            status: 429,
+            detail,
+            retry_after: decision.reported.reset,
+        };
+        serde_json::to_vec(&problem).unwrap_or_default().into()
+    };
+
+    let mut response = builder
+        .body(
+            Full::new(body)
+                .map_err(|never| match never {})
+                .boxed_unsync(),
+        )
+        .unwrap_or_else(|_| {
+            http::Response::builder()
+                .status(http::StatusCode::TOO_MANY_REQUESTS)
+                .body(UnsyncBoxBody::default())
+                .expect("minimal response should always build")
+        });
+    decision.apply_headers(response.headers_mut());
+    response
+}
+
+/// Counters, plans and audit log shared by middleware instances
+struct QuotaState {
+    cache: Option<CacheService>,
+    db: Option<Arc<PgPool>>,
+    config: QuotaConfig,
+}
+
+impl QuotaState {
+    /// Plan type of an organization
+    async fn plan(&self, organization_id: Option<&str>) -> String {
+        let Some(org) = organization_id else {
+            return DEFAULT_PLAN.to_string();
+        };
+        let key = format!("quota:plan:{}", org);
+        if let Some(ref cache) = self.cache
+            && let Ok(Some(plan)) = cache.get::<String>(&key).await
+        {
+            return plan;
+        }
+
+        let plan = match self.db {
+            Some(ref db) => sqlx::query_scalar::<_, String>(
+                r#"
+                SELECT plan_type::text FROM subscriptions
+                WHERE organization_id = $1 AND status IN ('active', 'trialing', 'past_due')
+                ORDER BY created_at DESC
+                LIMIT 1
+                "#,
+            )
+            .bind(org)
+            .fetch_optional(db.as_ref())
+            .await
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "Failed to look up plan of organization {}", org);
+                None
+            }),
+            None => None,
+        }
+        .unwrap_or_else(|| DEFAULT_PLAN.to_string());
+
+        if let Some(ref cache) = self.cache {
+            let _ = cache.set(&key, &plan, PLAN_CACHE_TTL).await;
+        }
+        plan
+    }
+
+    /// Check and count a request. `None` when the counters are unavailable.
+    async fn check(&self, context: &AuthContext, plan: &str) -> Option<Decision> {
+        let cache = self.cache.as_ref()?;
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let mut windows = Vec::new();
+        let mut reported: Option<WindowState> = None;
+        for (scope, scope_windows) in checks(context, self.config.plan(plan)) {
+            let tag = scope.tag();
+            let mut keys = Vec::new();
+            let mut args = vec![now_ms as i64];
+            for window in &scope_windows {
+                let index = now_ms / (window.secs * 1000);
+                keys.push(format!("quota:{}:{}:{}", tag, window.secs, index));
+                keys.push(format!("quota:{}:{}:{}", tag, window.secs, index - 1));
+                args.push((window.secs * 1000) as i64);
+                args.push(window.limit as i64);
+            }
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+            let result: Vec<i64> = match cache.eval(SLIDING_WINDOW_SCRIPT, &keys, &args).await {
+                Ok(result) => result,
+                Err(e) => {
+                    warn!(error = %e, "API quota check failed, letting request through");
+                    return None;
+                }
+            };
+            let allowed = result.first() == Some(&1);
+            let states: Vec<WindowState> = scope_windows
+                .iter()
+                .zip(result.iter().skip(1))
+                .map(|(window, &count)| WindowState::new(*window, count.max(0) as u64, now_ms))
+                .collect();
+            windows.extend(scope_windows);
+
+            if !allowed {
+                let exceeded = states.iter().find(|state| state.exceeded).copied()?;
+                return Some(Decision {
+                    windows,
+                    reported: exceeded,
+                    limited: Some(scope),
+                });
+            }
+            for state in states {
+                if reported.is_none_or(|r| state.remaining < r.remaining) {
+                    reported = Some(state);
+                }
+            }
+        }
+
+        Some(Decision {
+            windows,
+            reported: reported?,
+            limited: None,
+        })
+    }
+
+    /// Write the first rejection of a scope in a minute to the audit log
+    async fn audit(
+        &self,
+        context: &AuthContext,
+        decision: &Decision,
+        scope: &Scope,
+        request: &RequestInfo,
+    ) {
+        let (Some(cache), Some(db), Some(org)) =
+            (&self.cache, &self.db, context.organizations.first())
+        else {
+            return;
+        };
+        let minute = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / 60;
+        let flag = format!("quota:{}:audited:{}", scope.tag(), minute);
+        match cache.incr(&flag, 1).await {
+            Ok(1) => {
+                let _ = cache.expire(&flag, Duration::from_secs(120)).await;
+            }
+            _ => return,
+        }
+
+        let window = decision.reported.window;
+        AuditLogBuilder::new(org, "api.rate_limited", scope.kind())
+            .user(&context.user_id, Some(&context.email))
+            .resource(scope.id())
+            .description(&format!(
+                "API rate limit of {} requests per {}s exceeded",
+                window.limit, window.secs
+            ))
+            .metadata("limit", window.limit)
+            .metadata("window_secs", window.secs)
+            .metadata("path", request.path.as_str())
+            .request_info(request.ip_address.as_deref(), request.user_agent.as_deref())
+            .record(db)
+            .await;
+    }
+}
+
+/// Request details kept for the audit log
+struct RequestInfo {
+    path: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl RequestInfo {
+    fn new<B>(req: &http::Request<B>) -> Self {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            path: req.uri().path().to_string(),
+            ip_address: header("x-forwarded-for")
+                .and_then(|value| value.split(',').next().map(|ip| ip.trim().to_string())),
+            user_agent: header("user-agent"),
+        }
+    }
+}
+
+/// API quota middleware
+#[derive(Clone)]
+pub struct QuotaMiddleware<S> {
+    inner: S,
+    state: Arc<QuotaState>,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for QuotaMiddleware<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let state = Arc::clone(&self.state);
+
+        Box::pin(async move {
+            let Some(context) = req.extensions().get::<AuthContext>().cloned() else {
+                return inner.call(req).await;
+            };
+
+            let plan = state
+                .plan(context.organizations.first().map(String::as_str))
+                .await;
+            let Some(decision) = state.check(&context, &plan).await else {
+                API_QUOTA_DECISIONS_TOTAL
+                    .with_label_values(&["none", &plan, "unchecked"])
+                    .inc();
+                return inner.call(req).await;
+            };
+
+            if let Some(ref scope) = decision.limited {
+                API_QUOTA_DECISIONS_TOTAL
+                    .with_label_values(&[scope.kind(), &plan, "limited"])
+                    .inc();
+                let info = RequestInfo::new(&req);
+                debug!(
+                    scope = scope.kind(),
+                    id = scope.id(),
+                    path = %info.path,
+                    "API rate limit exceeded"
+                );
+                let grpc = req
+                    .headers()
+                    .get(http::header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.starts_with("application/grpc"));
+
+                state.audit(&context, &decision, scope, &info).await;
+                return Ok(rejection(&decision, scope, grpc));
+            }
+
+            let caller = if context.api_key_id.is_some() {
+                "api_key"
+            } else {
+                "user"
+            };
+            API_QUOTA_DECISIONS_TOTAL
+                .with_label_values(&[caller, &plan, "allowed"])
+                .inc();
+            let mut response = inner.call(req).await?;
+            decision.apply_headers(response.headers_mut());
+            Ok(response)
+        })
+    }
+}
+
+/// Layer for API quota middleware
+#[derive(Clone)]
+pub struct QuotaLayer {
+    state: Arc<QuotaState>,
+}
+
+impl QuotaLayer {
+    /// Create a quota layer. Without a cache every request is let through.
+    pub fn new(cache: Option<CacheService>, db: Option<Arc<PgPool>>, config: QuotaConfig) -> Self {
+        Self {
+            state: Arc::new(QuotaState { cache, db, config }),
+        }
+    }
+}
+
+impl<S> Layer<S> for QuotaLayer {
+    type Service = QuotaMiddleware<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        QuotaMiddleware {
+            inner: service,
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::AuthMethod;
+
+    fn context(api_key_id: Option<&str>, organizations: &[&str]) -> AuthContext {
+        AuthContext {
+            user_id: "user-1".to_string(),
+            email: "user@example.com".to_string(),
+            role: "user".to_string(),
+            organizations: organizations.iter().map(|org| org.to_string()).collect(),
+            auth_method: if api_key_id.is_some() {
+                AuthMethod::ApiKey
+            } else {
+                AuthMethod::Jwt
+            },
+            api_key_id: api_key_id.map(str::to_string),
+            support_grant_id: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_quota_parse() {
+        assert_eq!(
+            PlanQuota::parse("100, 50,5"),
+            Some(PlanQuota {
+                org_per_minute: 100,
+                key_per_minute: 50,
+                burst_per_second: 5,
+            })
+        );
+        assert_eq!(PlanQuota::parse("100,50"), None);
+        assert_eq!(PlanQuota::parse("100,50,5,1"), None);
+        assert_eq!(PlanQuota::parse("100,fifty,5"), None);
+
+        let config = QuotaConfig::default();
+        assert_eq!(config.plan("unknown"), config.plan(DEFAULT_PLAN));
+        assert!(config.plan("pro").org_per_minute > config.plan("free").org_per_minute);
+    }
+
+    #[test]
+    fn test_checks_by_credentials() {
+        let quota = QuotaConfig::default().plan("starter");
+
+        let checks_key = checks(&context(Some("key-1"), &["org-1"]), quota);
+        assert_eq!(checks_key.len(), 2);
+        assert_eq!(checks_key[0].0, Scope::ApiKey("key-1".to_string()));
+        assert_eq!(checks_key[0].1[0].limit, quota.key_per_minute);
+        assert_eq!(checks_key[0].1[1].limit, quota.burst_per_second);
+        assert_eq!(checks_key[1].0, Scope::Organization("org-1".to_string()));
+        assert_eq!(checks_key[1].1[0].limit, quota.org_per_minute);
+
+        // Sessions are limited per user, and only organization members
+        // share an organization limit
+        let checks_user = checks(&context(None, &[]), quota);
+        assert_eq!(checks_user.len(), 1);
+        assert_eq!(checks_user[0].0, Scope::User("user-1".to_string()));
+        assert_eq!(checks_user[0].0.tag(), "{user:user-1}");
+    }
+
+    #[test]
+    fn test_window_state() {
+        let window = Window {
+            secs: 60,
+            limit: 10,
+        };
+        // 15.5s into the window
+        let state = WindowState::new(window, 4, 60_000 * 7 + 15_500);
+        assert_eq!(state.remaining, 6);
+        assert_eq!(state.reset, 45);
+        assert!(!state.exceeded);
+
+        let full = WindowState::new(window, 10, 60_000 * 7);
+        assert_eq!(full.remaining, 0);
+        assert_eq!(full.reset, 60);
+        assert!(full.exceeded);
+    }
+
+    #[test]
+    fn test_rejection_response() {
+        let minute = Window {
+            secs: 60,
+            limit: 60,
+        };
+        let decision = Decision {
+            windows: vec![minute, Window { secs: 1, limit: 10 }],
+            reported: WindowState::new(minute, 60, 30_000),
+            limited: Some(Scope::ApiKey("key-1".to_string())),
+        };
+        let scope = Scope::ApiKey("key-1".to_string());
+
+        let response = rejection(&decision, &scope, false);
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        let headers = response.headers();
+        assert_eq!(headers["content-type"], "application/problem+json");
+        assert_eq!(headers["ratelimit-limit"], "60");
+        assert_eq!(headers["ratelimit-remaining"], "0");
+        assert_eq!(headers["ratelimit-reset"], "30");
+        assert_eq!(headers["ratelimit-policy"], "60;w=60, 10;w=1");
+        assert_eq!(headers["retry-after"], "30");
+
+        let grpc = rejection(&decision, &scope, true);
+        assert_eq!(grpc.headers()["grpc-status"], "8");
+        assert_eq!(grpc.headers()["content-type"], "application/grpc");
+    }
+}
@@ -0,0 +1,808 @@
+//! Two-person approval
+//!
+//! Organizations may require a second member to approve high-impact
+//! actions: deleting a backend, disabling its protection (switching it off,
+//! setting its level to off or passing its traffic through unfiltered) and
+//! allowlisting a network of a /8 (IPv4) or /32 (IPv6) or larger. While the
+//! policy is enabled these actions are held as pending rather than applied,
+//! and the handler answers `FAILED_PRECONDITION` with the ID of the pending
+//! action in the `x-pending-action-id` metadata.
+//!
+//! A member other than the requester, with at least the approver role of
+//! the policy, then approves or rejects the action; approving applies it on
+//! behalf of the requester, who may also withdraw it by rejecting it.
+//! Actions nobody decided on expire. Actions are only held when someone
+//! else can approve them, so a team of one is never locked out. Every step
+//! is written to the organization's audit log.
+
+use crate::middleware::auth::{AuthContext, AuthMethod};
+use crate::services::AppState;
+use crate::services::audit::AuditLogBuilder;
+use crate::services::backend::BackendService;
+use crate::services::backend_mode::BackendModeService;
+use crate::services::config_history::ConfigHistoryService;
+use crate::services::filter::FilterService;
+use crate::services::overview::{OrgRole, member_role};
+use crate::services::rule_transfer::format_networks;
+use chrono::{DateTime, Utc};
+use pistonprotection_common::duration::bounded_duration;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::pagination::{Field, FieldKind, ListSpec, Page, PageRequest};
+use pistonprotection_proto::backend::{
+    ApprovalActionKind, ApprovalPolicy, BackendMode, PendingAction, PendingActionStatus,
+    ProtectionLevel, ProtectionSettings,
+};
+use pistonprotection_proto::common::{Action, IpNetwork};
+use pistonprotection_proto::filter::FilterRule;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+/// Metadata carrying the ID of the pending action a request was held as
+pub const PENDING_ACTION_HEADER: &str = "x-pending-action-id";
+
+/// How long actions wait for a decision when the policy leaves it unset
+pub const DEFAULT_EXPIRY_SECONDS: u32 = 24 * 60 * 60;
+
+/// Shortest time an action waits for a decision
+pub const MIN_EXPIRY_SECONDS: u32 = 5 * 60;
+
+/// Longest time an action waits for a decision
+pub const MAX_EXPIRY_SECONDS: u32 = 7 * 24 * 60 * 60;
+
+/// Longest IPv4 prefix of an allowlisted network held for approval
+pub const WIDE_PREFIX_V4: u32 = 8;
+
+/// Longest IPv6 prefix of an allowlisted network held for approval
+pub const WIDE_PREFIX_V6: u32 = 32;
+
+/// Longest decision reason stored
+const MAX_REASON_LEN: usize = 500;
+
+/// Sort and filter fields of pending action lists
+pub static PENDING_ACTION_LIST: ListSpec = ListSpec {
+    fields: &[
+        Field::sortable("created_at", "created_at", FieldKind::Timestamp),
+        Field::sortable("expires_at", "expires_at", FieldKind::Timestamp),
+        Field::filterable("status", "status", FieldKind::Text),
+        Field::filterable("kind", "kind", FieldKind::Text),
+        Field::filterable("backend_id", "backend_id", FieldKind::Text),
+        Field::filterable("requested_by", "requested_by", FieldKind::Text),
+    ],
+    id_column: "id",
+    default_order: "-created_at",
+};
+
+/// Operation held for approval, applied as requested once approved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum Operation {
+    DeleteBackend,
+    UpdateProtection {
+        protection: ProtectionSettings,
+    },
+    SetProtectionLevel {
+        level: i32,
+    },
+    SetBackendMode {
+        mode: i32,
+        revert_after_seconds: u32,
+        reason: String,
+    },
+    CreateRule {
+        rule: FilterRule,
+    },
+    UpdateRule {
+        rule: FilterRule,
+    },
+}
+
+impl Operation {
+    pub fn kind(&self) -> ApprovalActionKind {
+        match self {
+            Operation::DeleteBackend => ApprovalActionKind::DeleteBackend,
+            Operation::UpdateProtection { .. }
+            | Operation::SetProtectionLevel { .. }
+            | Operation::SetBackendMode { .. } => ApprovalActionKind::DisableProtection,
+            Operation::CreateRule { .. } | Operation::UpdateRule { .. } => {
+                ApprovalActionKind::WideAllowlist
+            }
+        }
+    }
+
+    /// Whether the operation may need approval, before looking at the
+    /// current configuration
+    pub fn is_high_impact(&self) -> bool {
+        match self {
+            Operation::DeleteBackend => true,
+            Operation::UpdateProtection { protection } => !protection_active(protection),
+            Operation::SetProtectionLevel { level } => *level == ProtectionLevel::Off as i32,
+            Operation::SetBackendMode { mode, .. } => *mode == BackendMode::Passthrough as i32,
+            Operation::CreateRule { rule } | Operation::UpdateRule { rule } => {
+                !wide_allowlist(rule).is_empty()
+            }
+        }
+    }
+
+    /// What the operation does to a backend, for people deciding on it
+    pub fn describe(&self, backend_name: &str) -> String {
+        match self {
+            Operation::DeleteBackend => format!("Delete backend {}", backend_name),
+            Operation::UpdateProtection { .. } => {
+                format!("Disable protection of backend {}", backend_name)
+            }
+            Operation::SetProtectionLevel { .. } => {
+                format!("Turn protection of backend {} off", backend_name)
+            }
+            Operation::SetBackendMode { .. } => format!(
+                "Pass traffic to backend {} through unfiltered",
+                backend_name
+            ),
+            Operation::CreateRule { rule } | Operation::UpdateRule { rule } => {
+                let networks = rule
+                    .r#match
+                    .as_ref()
+                    .map(|m| format_networks(&m.source_ips).join(", "))
+                    .unwrap_or_default();
+                format!(
+                    "Allow {} on backend {} with rule '{}'",
+                    networks, backend_name, rule.name
+                )
+            }
+        }
+    }
+}
+
+/// Whether a network is wide enough to need approval when allowlisted
+pub fn is_wide_network(network: &IpNetwork) -> bool {
+    let Some(address) = network.address.as_ref() else {
+        return false;
+    };
+    match std::net::IpAddr::try_from(address) {
+        Ok(std::net::IpAddr::V4(_)) => network.prefix_length <= WIDE_PREFIX_V4,
+        Ok(std::net::IpAddr::V6(_)) => network.prefix_length <= WIDE_PREFIX_V6,
+        Err(_) => false,
+    }
+}
+
+/// Wide networks an enabled allow rule lets through
+pub fn wide_allowlist(rule: &FilterRule) -> Vec<&IpNetwork> {
+    if !rule.enabled || rule.action != Action::Allow as i32 {
+        return Vec::new();
+    }
+    rule.r#match
+        .iter()
+        .flat_map(|m| m.source_ips.iter())
+        .filter(|network| is_wide_network(network))
+        .collect()
+}
+
+/// Whether saving a rule allowlists a wide network that was not before
+pub fn widens_allowlist(before: Option<&FilterRule>, after: &FilterRule) -> bool {
+    if wide_allowlist(after).is_empty() {
+        return false;
+    }
+    match before {
+        Some(before) => wide_allowlist(before) != wide_allowlist(after),
+        None => true,
+    }
+}
+
+/// Whether protection settings filter traffic
+pub fn protection_active(protection: &ProtectionSettings) -> bool {
+    protection.enabled && protection.level() != ProtectionLevel::Off
+}
+
+/// Decision on a pending action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Approve,
+    Reject,
+}
+
+impl Decision {
+    fn status(&self) -> &'static str {
+        match self {
+            Decision::Approve => "approved",
+            Decision::Reject => "rejected",
+        }
+    }
+}
+
+/// Check a caller may decide on an action
+///
+/// Approvers are people other than the requester with at least the
+/// approver role, or platform admins; requesters may only withdraw their
+/// own actions.
+pub fn check_decider(
+    decision: Decision,
+    context: &AuthContext,
+    role: Option<OrgRole>,
+    approver_role: OrgRole,
+    requested_by: &str,
+) -> Result<()> {
+    if matches!(context.auth_method, AuthMethod::ApiKey) {
+        return Err(Error::forbidden(
+            "Pending actions are decided on by a person, not an API key",
+        ));
+    }
+    if context.user_id == requested_by {
+        return match decision {
+            Decision::Reject => Ok(()),
+            Decision::Approve => Err(Error::forbidden(
+                "Pending actions must be approved by someone other than their requester",
+            )),
+        };
+    }
+    if context.role == "admin" || role.is_some_and(|role| role >= approver_role) {
+        Ok(())
+    } else {
+        Err(Error::forbidden(format!(
+            "Deciding on pending actions requires the {} role",
+            approver_role.as_str()
+        )))
+    }
+}
+
+/// Validate the least role of approvers, empty meaning admin
+pub fn validate_approver_role(role: &str) -> Result<OrgRole> {
+    match role {
+        "" => Ok(OrgRole::Admin),
+        role => match OrgRole::parse(role) {
+            Some(OrgRole::Viewer) | None => Err(Error::validation(
+                "Approver role must be member, admin or owner",
+            )),
+            Some(role) => Ok(role),
+        },
+    }
+}
+
+/// Approval service implementation
+pub struct ApprovalService {
+    state: AppState,
+    backends: BackendService,
+    filters: FilterService,
+    modes: BackendModeService,
+    history: ConfigHistoryService,
+}
+
+impl ApprovalService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            backends: BackendService::new(state.clone()),
+            filters: FilterService::new(state.clone()),
+            modes: BackendModeService::new(state.clone()),
+            history: ConfigHistoryService::new(state.clone()),
+            state,
+        }
+    }
+
+    /// Get the approval policy of an organization
+    #[instrument(skip(self))]
+    pub async fn get_policy(&self, organization_id: &str) -> Result<ApprovalPolicy> {
+        let db = self.state.db()?;
+        load_policy(db, organization_id).await
+    }
+
+    /// Set the approval policy of an organization
+    ///
+    /// Actions already pending keep waiting for a decision when the policy
+    /// is disabled.
+    #[instrument(skip(self))]
+    pub async fn update_policy(
+        &self,
+        organization_id: &str,
+        enabled: bool,
+        approver_role: &str,
+        expiry_seconds: u32,
+        updated_by: &str,
+    ) -> Result<ApprovalPolicy> {
+        let db = self.state.db()?;
+        let approver_role = validate_approver_role(approver_role)?;
+        let expiry_seconds =
+            bounded_duration(expiry_seconds, DEFAULT_EXPIRY_SECONDS, MAX_EXPIRY_SECONDS)
+                .filter(|seconds| *seconds >= MIN_EXPIRY_SECONDS)
+                .ok_or_else(|| {
+                    Error::validation(format!(
+                        "Expiry must be between {} and {} seconds",
+                        MIN_EXPIRY_SECONDS, MAX_EXPIRY_SECONDS
+                    ))
+                })?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO approval_policies (
+                organization_id, enabled, approver_role, expiry_seconds, updated_by, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT (organization_id) DO UPDATE
+            SET enabled = EXCLUDED.enabled,
+                approver_role = EXCLUDED.approver_role,
+                expiry_seconds = EXCLUDED.expiry_seconds,
+                updated_by = EXCLUDED.updated_by,
+                updated_at = EXCLUDED.updated_at
+            "#,
+        )
+        .bind(organization_id)
+        .bind(enabled)
+        .bind(approver_role.as_str())
+        .bind(expiry_seconds as i32)
+        .bind(updated_by)
+        .execute(db)
+        .await?;
+
+        info!(
+            organization_id = %organization_id,
+            enabled,
+            approver_role = approver_role.as_str(),
+            updated_by = %updated_by,
+            "Updated approval policy"
+        );
+        AuditLogBuilder::new(organization_id, "approval.policy_updated", "organization")
+            .user(updated_by, None)
+            .resource(organization_id)
+            .description(&format!(
+                "Two-person approval {} with {} approvers",
+                if enabled { "enabled" } else { "disabled" },
+                approver_role.as_str()
+            ))
+            .metadata("enabled", enabled)
+            .metadata("expiry_seconds", expiry_seconds)
+            .record(db)
+            .await;
+
+        load_policy(db, organization_id).await
+    }
+
+    /// Hold an operation on a backend for approval if its organization
+    /// requires it
+    ///
+    /// Returns the pending action, or `None` if the operation may be
+    /// applied right away.
+    #[instrument(skip(self, operation))]
+    pub async fn hold(
+        &self,
+        backend_id: &str,
+        operation: Operation,
+        requested_by: &str,
+    ) -> Result<Option<PendingAction>> {
+        if !operation.is_high_impact() {
+            return Ok(None);
+        }
+        let db = self.state.db()?;
+        let backend = self.backends.get(backend_id).await?;
+
+        let policy = load_policy(db, &backend.organization_id).await?;
+        if !policy.enabled || !self.requires_approval(backend_id, &operation).await? {
+            return Ok(None);
+        }
+        let approver_role = validate_approver_role(&policy.approver_role)?;
+        if !has_other_approver(db, &backend.organization_id, requested_by, approver_role).await? {
+            return Ok(None);
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let kind = operation.kind();
+        let description = operation.describe(&backend.name);
+        let operation_json = serde_json::to_value(&operation)
+            .map_err(|e| Error::Internal(format!("Failed to serialize operation: {}", e)))?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO pending_actions (
+                id, organization_id, backend_id, kind, operation, description,
+                requested_by, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, NOW() + make_interval(secs => $8))
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(&backend.organization_id)
+        .bind(backend_id)
+        .bind(kind_str(kind))
+        .bind(&operation_json)
+        .bind(&description)
+        .bind(requested_by)
+        .bind(f64::from(policy.expiry_seconds))
+        .fetch_one(db)
+        .await?;
+        let action = row_to_action(&row);
+
+        info!(
+            action_id = %id,
+            backend_id = %backend_id,
+            requested_by = %requested_by,
+            "Held action for approval"
+        );
+        AuditLogBuilder::new(
+            &backend.organization_id,
+            "approval.requested",
+            "pending_action",
+        )
+        .user(requested_by, None)
+        .resource(&id)
+        .description(&description)
+        .metadata("backend_id", backend_id)
+        .metadata("kind", kind_str(kind))
+        .record(db)
+        .await;
+
+        Ok(Some(action))
+    }
+
+    /// Pending and decided actions of an organization
+    #[instrument(skip(self))]
+    pub async fn list(
+        &self,
+        organization_id: &str,
+        request: &PageRequest,
+    ) -> Result<(Page<PendingAction>, u64)> {
+        let db = self.state.db()?;
+        expire(db, organization_id).await?;
+
+        let mut count = sqlx::QueryBuilder::new(
+            "SELECT COUNT(*) FROM pending_actions WHERE organization_id = ",
+        );
+        count.push_bind(organization_id);
+        request.push_filter(&mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(db).await?;
+
+        let mut query = sqlx::QueryBuilder::new(format!(
+            "SELECT *, {} FROM pending_actions WHERE organization_id = ",
+            request.select_key()
+        ));
+        query.push_bind(organization_id);
+        request.push_filter(&mut query);
+        request.push_page(&mut query);
+        let rows = query.build().fetch_all(db).await?;
+
+        let page = request
+            .finish_rows(rows)?
+            .try_map(|row| Ok::<_, Error>(row_to_action(&row)))?;
+        Ok((page, total as u64))
+    }
+
+    /// Approve a pending action and apply it
+    ///
+    /// An action that fails to apply is marked failed, with the error.
+    #[instrument(skip(self, context, reason))]
+    pub async fn approve(
+        &self,
+        id: &str,
+        context: &AuthContext,
+        reason: &str,
+    ) -> Result<PendingAction> {
+        let (organization_id, operation, action) =
+            self.decide(id, Decision::Approve, context, reason).await?;
+        let db = self.state.db()?;
+
+        let e = match self.apply(&action, operation).await {
+            Ok(()) => {
+                AuditLogBuilder::new(&organization_id, "approval.approved", "pending_action")
+                    .user(&context.user_id, None)
+                    .resource(id)
+                    .description(&action.description)
+                    .metadata("requested_by", action.requested_by.as_str())
+                    .record(db)
+                    .await;
+                return Ok(action);
+            }
+            Err(e) => e,
+        };
+
+        warn!(action_id = %id, error = %e, "Failed to apply approved action");
+        let row = sqlx::query(
+            "UPDATE pending_actions SET status = 'failed', error = $2 WHERE id = $1 RETURNING *",
+        )
+        .bind(id)
+        .bind(e.to_string())
+        .fetch_one(db)
+        .await?;
+        AuditLogBuilder::new(&organization_id, "approval.failed", "pending_action")
+            .user(&context.user_id, None)
+            .resource(id)
+            .description(&action.description)
+            .metadata("error", e.to_string())
+            .record(db)
+            .await;
+        Ok(row_to_action(&row))
+    }
+
+    /// Reject a pending action, or withdraw it as its requester
+    #[instrument(skip(self, context, reason))]
+    pub async fn reject(
+        &self,
+        id: &str,
+        context: &AuthContext,
+        reason: &str,
+    ) -> Result<PendingAction> {
+        let (organization_id, _, action) =
+            self.decide(id, Decision::Reject, context, reason).await?;
+
+        AuditLogBuilder::new(&organization_id, "approval.rejected", "pending_action")
+            .user(&context.user_id, None)
+            .resource(id)
+            .description(&action.description)
+            .metadata("requested_by", action.requested_by.as_str())
+            .metadata("reason", reason)
+            .record(self.state.db()?)
+            .await;
+        Ok(action)
+    }
+
+    /// Record a decision on a pending action
+    ///
+    /// The status only moves from pending once, so concurrent decisions
+    /// cannot both apply.
+    async fn decide(
+        &self,
+        id: &str,
+        decision: Decision,
+        context: &AuthContext,
+        reason: &str,
+    ) -> Result<(String, Operation, PendingAction)> {
+        let db = self.state.db()?;
+        if reason.chars().count() > MAX_REASON_LEN {
+            return Err(Error::validation(format!(
+                "Reason must be at most {} characters",
+                MAX_REASON_LEN
+            )));
+        }
+
+        let row =
+            sqlx::query("SELECT organization_id, requested_by FROM pending_actions WHERE id = $1")
+                .bind(id)
+                .fetch_optional(db)
+                .await?
+                .ok_or_else(|| Error::not_found("PendingAction", id))?;
+        let organization_id: String = row.get("organization_id");
+        let requested_by: String = row.get("requested_by");
+
+        let policy = load_policy(db, &organization_id).await?;
+        let approver_role = validate_approver_role(&policy.approver_role)?;
+        let role = member_role(db, &context.user_id, &organization_id).await?;
+        check_decider(decision, context, role, approver_role, &requested_by)?;
+
+        expire(db, &organization_id).await?;
+        let row = sqlx::query(
+            r#"
+            UPDATE pending_actions
+            SET status = $2, decided_by = $3, reason = NULLIF($4, ''), decided_at = NOW()
+            WHERE id = $1 AND status = 'pending'
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(decision.status())
+        .bind(&context.user_id)
+        .bind(reason)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| {
+            Error::Grpc(tonic::Status::failed_precondition(
+                "Action is no longer pending",
+            ))
+        })?;
+
+        let operation: Operation = serde_json::from_value(row.get("operation"))
+            .map_err(|e| Error::Internal(format!("Failed to parse operation: {}", e)))?;
+        info!(
+            action_id = %id,
+            decision = decision.status(),
+            decided_by = %context.user_id,
+            "Decided on pending action"
+        );
+        Ok((organization_id, operation, row_to_action(&row)))
+    }
+
+    /// Whether a high-impact operation changes the current configuration of
+    /// a backend
+    async fn requires_approval(&self, backend_id: &str, operation: &Operation) -> Result<bool> {
+        Ok(match operation {
+            Operation::DeleteBackend | Operation::SetBackendMode { .. } => true,
+            Operation::UpdateProtection { .. } | Operation::SetProtectionLevel { .. } => {
+                protection_active(&self.backends.get_protection(backend_id).await?)
+            }
+            Operation::CreateRule { rule } => widens_allowlist(None, rule),
+            Operation::UpdateRule { rule } => match self.filters.get(&rule.id).await {
+                Ok(before) => widens_allowlist(Some(&before), rule),
+                Err(Error::NotFound { .. }) => false,
+                Err(e) => return Err(e),
+            },
+        })
+    }
+
+    /// Apply an approved operation on behalf of its requester
+    async fn apply(&self, action: &PendingAction, operation: Operation) -> Result<()> {
+        let backend_id = action.backend_id.as_str();
+        let requested_by = action.requested_by.as_str();
+        let summary = match operation {
+            Operation::DeleteBackend => {
+                return self.backends.delete(backend_id).await;
+            }
+            Operation::UpdateProtection { protection } => {
+                self.backends
+                    .update_protection(backend_id, protection)
+                    .await?;
+                "protection settings updated"
+            }
+            Operation::SetProtectionLevel { level } => {
+                let level = ProtectionLevel::try_from(level)
+                    .map_err(|_| Error::validation("Invalid protection level"))?;
+                self.backends
+                    .set_protection_level(backend_id, level)
+                    .await?;
+                "protection level changed"
+            }
+            Operation::SetBackendMode {
+                mode,
+                revert_after_seconds,
+                reason,
+            } => {
+                let mode = BackendMode::try_from(mode)
+                    .map_err(|_| Error::validation("Invalid backend mode"))?;
+                self.modes
+                    .set(
+                        backend_id,
+                        mode,
+                        revert_after_seconds,
+                        &reason,
+                        requested_by,
+                    )
+                    .await?;
+                return Ok(());
+            }
+            Operation::CreateRule { rule } => {
+                self.filters.create(backend_id, rule).await?;
+                "rule created"
+            }
+            Operation::UpdateRule { rule } => {
+                self.filters.update(rule).await?;
+                "rule updated"
+            }
+        };
+        self.history
+            .record_change(backend_id, summary, requested_by)
+            .await;
+        Ok(())
+    }
+}
+
+/// Expire the actions of an organization nobody decided on in time
+async fn expire(db: &PgPool, organization_id: &str) -> Result<()> {
+    let rows = sqlx::query(
+        r#"
+        UPDATE pending_actions SET status = 'expired'
+        WHERE organization_id = $1 AND status = 'pending' AND expires_at <= NOW()
+        RETURNING id, requested_by, description
+        "#,
+    )
+    .bind(organization_id)
+    .fetch_all(db)
+    .await?;
+
+    for row in rows {
+        let id: String = row.get("id");
+        let requested_by: String = row.get("requested_by");
+        let description: String = row.get("description");
+        AuditLogBuilder::new(organization_id, "approval.expired", "pending_action")
+            .user(&requested_by, None)
+            .resource(&id)
+            .description(&description)
+            .record(db)
+            .await;
+    }
+    Ok(())
+}
+
+/// Whether a member other than the requester may approve actions
+async fn has_other_approver(
+    db: &PgPool,
+    organization_id: &str,
+    requested_by: &str,
+    approver_role: OrgRole,
+) -> Result<bool> {
+    let roles: Vec<&str> = [OrgRole::Member, OrgRole::Admin, OrgRole::Owner]
+        .into_iter()
+        .filter(|role| *role >= approver_role)
+        .map(|role| role.as_str())
+        .collect();
+    let exists: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM organization_members
+            WHERE organization_id = $1 AND user_id <> $2 AND role::text = ANY($3)
+        )
+        "#,
+    )
+    .bind(organization_id)
+    .bind(requested_by)
+    .bind(&roles)
+    .fetch_one(db)
+    .await?;
+    Ok(exists)
+}
+
+async fn load_policy(db: &PgPool, organization_id: &str) -> Result<ApprovalPolicy> {
+    let row = sqlx::query(
+        r#"
+        SELECT enabled, approver_role, expiry_seconds, updated_by, updated_at
+        FROM approval_policies
+        WHERE organization_id = $1
+        "#,
+    )
+    .bind(organization_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(match row {
+        Some(row) => ApprovalPolicy {
+            organization_id: organization_id.to_string(),
+            enabled: row.get("enabled"),
+            approver_role: row.get("approver_role"),
+            expiry_seconds: row.get::<i32, _>("expiry_seconds").max(0) as u32,
+            updated_by: row.get("updated_by"),
+            updated_at: Some(row.get::<DateTime<Utc>, _>("updated_at").into()),
+        },
+        None => ApprovalPolicy {
+            organization_id: organization_id.to_string(),
+            enabled: false,
+            approver_role: OrgRole::Admin.as_str().to_string(),
+            expiry_seconds: DEFAULT_EXPIRY_SECONDS,
+            ..Default::default()
+        },
+    })
+}
+
+fn kind_str(kind: ApprovalActionKind) -> &'static str {
+    match kind {
+        ApprovalActionKind::Unspecified => "unspecified",
+        ApprovalActionKind::DeleteBackend => "delete_backend",
+        ApprovalActionKind::DisableProtection => "disable_protection",
+        ApprovalActionKind::WideAllowlist => "wide_allowlist",
+    }
+}
+
+fn parse_kind(kind: &str) -> ApprovalActionKind {
+    match kind {
+        "delete_backend" => ApprovalActionKind::DeleteBackend,
+        "disable_protection" => ApprovalActionKind::DisableProtection,
+        "wide_allowlist" => ApprovalActionKind::WideAllowlist,
+        _ => ApprovalActionKind::Unspecified,
+    }
+}
+
+fn parse_status(status: &str) -> PendingActionStatus {
+    match status {
+        "pending" => PendingActionStatus::Pending,
+        "approved" => PendingActionStatus::Approved,
+        "rejected" => PendingActionStatus::Rejected,
+        "expired" => PendingActionStatus::Expired,
+        "failed" => PendingActionStatus::Failed,
+        _ => PendingActionStatus::Unspecified,
+    }
+}
+
+fn row_to_action(row: &sqlx::postgres::PgRow) -> PendingAction {
+    PendingAction {
+        id: row.get("id"),
+        organization_id: row.get("organization_id"),
+        backend_id: row.get("backend_id"),
+        kind: parse_kind(row.get("kind")) as i32,
+        description: row.get("description"),
+        status: parse_status(row.get("status")) as i32,
+        requested_by: row.get("requested_by"),
+        decided_by: row
+            .get::<Option<String>, _>("decided_by")
+            .unwrap_or_default(),
+        reason: row.get::<Option<String>, _>("reason").unwrap_or_default(),
+        error: row.get::<Option<String>, _>("error").unwrap_or_default(),
+        created_at: Some(row.get::<DateTime<Utc>, _>("created_at").into()),
+        expires_at: Some(row.get::<DateTime<Utc>, _>("expires_at").into()),
+        decided_at: row
+            .get::<Option<DateTime<Utc>>, _>("decided_at")
+            .map(Into::into),
+    }
+}
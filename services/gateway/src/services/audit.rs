@@ -0,0 +1,116 @@
+//! Organization audit log
+//!
+//! The gateway records what happens to an organization's resources in the
+//! `audit_logs` table the auth service owns and serves. Every gateway writer
+//! builds its entry with [`AuditLogBuilder`].
+
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Entry written to the organization audit log
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditLogEntry {
+    pub organization_id: String,
+    pub user_id: Option<String>,
+    pub user_email: Option<String>,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Option<String>,
+    pub description: String,
+    pub metadata: Map<String, Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Audit log builder for easy creation
+pub struct AuditLogBuilder {
+    entry: AuditLogEntry,
+}
+
+impl AuditLogBuilder {
+    pub fn new(organization_id: &str, action: &str, resource_type: &str) -> Self {
+        Self {
+            entry: AuditLogEntry {
+                organization_id: organization_id.to_string(),
+                user_id: None,
+                user_email: None,
+                action: action.to_string(),
+                resource_type: resource_type.to_string(),
+                resource_id: None,
+                description: String::new(),
+                metadata: Map::new(),
+                ip_address: None,
+                user_agent: None,
+            },
+        }
+    }
+
+    pub fn user(mut self, user_id: &str, email: Option<&str>) -> Self {
+        self.entry.user_id = Some(user_id.to_string());
+        self.entry.user_email = email.map(|e| e.to_string());
+        self
+    }
+
+    pub fn resource(mut self, resource_id: &str) -> Self {
+        self.entry.resource_id = Some(resource_id.to_string());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.entry.description = description.to_string();
+        self
+    }
+
+    pub fn metadata(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.entry.metadata.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn request_info(mut self, ip: Option<&str>, user_agent: Option<&str>) -> Self {
+        self.entry.ip_address = ip.map(|s| s.to_string());
+        self.entry.user_agent = user_agent.map(|s| s.to_string());
+        self
+    }
+
+    pub fn build(self) -> AuditLogEntry {
+        self.entry
+    }
+
+    /// Write the entry
+    pub async fn write(self, db: &PgPool) -> Result<(), sqlx::Error> {
+        let entry = self.entry;
+        sqlx::query(
+            r#"
+            INSERT INTO audit_logs (
+                id, organization_id, user_id, user_email, action, resource_type,
+                resource_id, description, metadata, ip_address, user_agent
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&entry.organization_id)
+        .bind(&entry.user_id)
+        .bind(&entry.user_email)
+        .bind(&entry.action)
+        .bind(&entry.resource_type)
+        .bind(&entry.resource_id)
+        .bind(&entry.description)
+        .bind(Value::Object(entry.metadata))
+        .bind(&entry.ip_address)
+        .bind(&entry.user_agent)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Write the entry, logging a failure rather than returning it
+    pub async fn record(self, db: &PgPool) {
+        let action = self.entry.action.clone();
+        if let Err(e) = self.write(db).await {
+            warn!(error = %e, action = %action, "Failed to write audit entry");
+        }
+    }
+}
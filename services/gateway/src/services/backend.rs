@@ -3,6 +3,10 @@
 use crate::services::AppState;
 use futures::StreamExt;
 use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::pagination::{Field, FieldKind, ListSpec, Page, PageRequest};
+use pistonprotection_common::probe::{
+    OriginProbeRequest, OriginProbeResult, PROBE_QUEUE_KEY, PROBE_TTL, ProbeKind, probe_result_key,
+};
 use pistonprotection_proto::backend::*;
 use pistonprotection_proto::common::HealthStatus;
 use sqlx::Row;
@@ -13,6 +17,27 @@ use tokio_stream::wrappers::BroadcastStream;
 use tracing::{info, instrument, warn};
 use uuid::Uuid;
 
+/// Sort and filter fields of backend lists
+pub static BACKEND_LIST: ListSpec = ListSpec {
+    fields: &[
+        Field::sortable("name", "name", FieldKind::Text),
+        Field::sortable(
+            "created_at",
+            "COALESCE(created_at, 'epoch')",
+            FieldKind::Timestamp,
+        ),
+        Field::sortable(
+            "updated_at",
+            "COALESCE(updated_at, 'epoch')",
+            FieldKind::Timestamp,
+        ),
+        Field::filterable("type", "type", FieldKind::Integer),
+        Field::filterable("description", "description", FieldKind::Text),
+    ],
+    id_column: "id",
+    default_order: "-created_at",
+};
+
 /// Backend service implementation
 pub struct BackendService {
     state: AppState,
@@ -89,6 +114,7 @@ impl BackendService {
             name: row.get("name"),
             description: row.get("description"),
             r#type: row.get::<i32, _>("type"),
+            onboarding: self.get_onboarding(id).await?,
             ..Default::default()
         };
 
@@ -109,42 +135,28 @@ impl BackendService {
     /// List backends for an organization with pagination
     /// Returns (backends, total_count)
     #[instrument(skip(self))]
-    pub async fn list(
-        &self,
-        org_id: &str,
-        page: u32,
-        page_size: u32,
-    ) -> Result<(Vec<Backend>, u64)> {
-        let db = self.state.db()?;
-
-        let offset = (page.saturating_sub(1)) * page_size;
+    pub async fn list(&self, org_id: &str, request: &PageRequest) -> Result<(Page<Backend>, u64)> {
+        let db = self.state.db_read()?;
 
         // Get total count for pagination
-        let count_row: (i64,) =
-            sqlx::query_as("SELECT COUNT(*) FROM backends WHERE organization_id = $1")
-                .bind(org_id)
-                .fetch_one(db)
-                .await?;
-        let total = count_row.0 as u64;
-
-        let rows = sqlx::query(
-            r#"
-            SELECT id, organization_id, name, description, type, created_at, updated_at
-            FROM backends
-            WHERE organization_id = $1
-            ORDER BY created_at DESC
-            LIMIT $2 OFFSET $3
-            "#,
-        )
-        .bind(org_id)
-        .bind(page_size as i64)
-        .bind(offset as i64)
-        .fetch_all(db)
-        .await?;
-
-        let backends = rows
-            .iter()
-            .map(|row| Backend {
+        let mut count =
+            sqlx::QueryBuilder::new("SELECT COUNT(*) FROM backends WHERE organization_id = ");
+        count.push_bind(org_id);
+        request.push_filter(&mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(db).await?;
+
+        let mut query = sqlx::QueryBuilder::new(format!(
+            "SELECT id, organization_id, name, description, type, created_at, updated_at, {} \
+             FROM backends WHERE organization_id = ",
+            request.select_key()
+        ));
+        query.push_bind(org_id);
+        request.push_filter(&mut query);
+        request.push_page(&mut query);
+        let rows = query.build().fetch_all(db).await?;
+
+        let page = request.finish_rows(rows)?.try_map(|row| {
+            Ok::<_, Error>(Backend {
                 id: row.get("id"),
                 organization_id: row.get("organization_id"),
                 name: row.get("name"),
@@ -152,9 +164,9 @@ impl BackendService {
                 r#type: row.get::<i32, _>("type"),
                 ..Default::default()
             })
-            .collect();
+        })?;
 
-        Ok((backends, total))
+        Ok((page, total as u64))
     }
 
     /// Update a backend
@@ -358,7 +370,7 @@ impl BackendService {
     /// Get all origins for a backend
     #[instrument(skip(self))]
     pub async fn get_origins(&self, backend_id: &str) -> Result<Vec<Origin>> {
-        let db = self.state.db()?;
+        let db = self.state.db_read()?;
 
         let rows = sqlx::query(
             r#"
@@ -548,7 +560,7 @@ impl BackendService {
     /// Get all domains for a backend
     #[instrument(skip(self))]
     pub async fn get_domains(&self, backend_id: &str) -> Result<Vec<String>> {
-        let db = self.state.db()?;
+        let db = self.state.db_read()?;
 
         let rows: Vec<(String,)> = sqlx::query_as(
             "SELECT domain FROM backend_domains WHERE backend_id = $1 AND verified = true",
@@ -622,7 +634,7 @@ impl BackendService {
     }
 
     /// Validate domain format
-    fn is_valid_domain(domain: &str) -> bool {
+    pub(crate) fn is_valid_domain(domain: &str) -> bool {
         if domain.is_empty() || domain.len() > 253 {
             return false;
         }
@@ -647,6 +659,240 @@ impl BackendService {
         true
     }
 
+    // =========================================================================
+    // Onboarding
+    // =========================================================================
+
+    /// Start onboarding a Minecraft backend by hostname
+    ///
+    /// Returns the TXT record to create, after a first check. Restarting
+    /// with the same hostname keeps the verification token.
+    #[instrument(skip(self))]
+    pub async fn start_onboarding(
+        &self,
+        backend_id: &str,
+        hostname: &str,
+        port: u32,
+    ) -> Result<OnboardingStatus> {
+        let db = self.state.db()?;
+
+        let backend = self.get(backend_id).await?;
+        let hostname = hostname.trim_end_matches('.').to_ascii_lowercase();
+        validate_onboarding_target(backend.r#type(), &hostname, port)?;
+
+        let token = match self.get_onboarding(backend_id).await? {
+            Some(existing) if existing.hostname == hostname => existing.verification_token,
+            _ => format!("piston-verify-{}", Uuid::new_v4()),
+        };
+        let onboarding = OnboardingStatus {
+            state: OnboardingState::PendingDns as i32,
+            verification_record: verification_record(&hostname),
+            verification_token: token,
+            hostname,
+            port,
+            ..Default::default()
+        };
+        let status_json = serde_json::to_value(&onboarding).map_err(|e| {
+            Error::Internal(format!("Failed to serialize onboarding status: {}", e))
+        })?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO backend_onboarding (backend_id, hostname, port, status, updated_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (backend_id)
+            DO UPDATE SET hostname = $2, port = $3, status = $4, updated_at = NOW()
+            "#,
+        )
+        .bind(backend_id)
+        .bind(&onboarding.hostname)
+        .bind(port as i32)
+        .bind(&status_json)
+        .execute(db)
+        .await?;
+
+        info!(backend_id = %backend_id, hostname = %onboarding.hostname, "Started backend onboarding");
+
+        self.check_onboarding(backend_id).await
+    }
+
+    /// Re-run the onboarding checks of a backend: DNS resolution, ownership
+    /// TXT record and a status ping from a worker
+    #[instrument(skip(self))]
+    pub async fn check_onboarding(&self, backend_id: &str) -> Result<OnboardingStatus> {
+        let db = self.state.db()?;
+
+        let backend = self.get(backend_id).await?;
+        let mut onboarding = self
+            .get_onboarding(backend_id)
+            .await?
+            .ok_or_else(|| Error::not_found("Onboarding", backend_id))?;
+        let kind = probe_kind(backend.r#type()).ok_or_else(|| {
+            Error::validation("Onboarding by hostname is only supported for Minecraft backends")
+        })?;
+
+        // The requested port is kept in the row, the status holds the
+        // resolved one
+        let (requested_port,): (i32,) =
+            sqlx::query_as("SELECT port FROM backend_onboarding WHERE backend_id = $1")
+                .bind(backend_id)
+                .fetch_one(db)
+                .await?;
+
+        self.run_onboarding_checks(&mut onboarding, kind, requested_port as u32)
+            .await;
+        onboarding.checked_at = Some(chrono::Utc::now().into());
+
+        let status_json = serde_json::to_value(&onboarding).map_err(|e| {
+            Error::Internal(format!("Failed to serialize onboarding status: {}", e))
+        })?;
+        sqlx::query(
+            "UPDATE backend_onboarding SET status = $2, updated_at = NOW() WHERE backend_id = $1",
+        )
+        .bind(backend_id)
+        .bind(&status_json)
+        .execute(db)
+        .await?;
+
+        info!(
+            backend_id = %backend_id,
+            state = ?onboarding.state(),
+            "Checked backend onboarding"
+        );
+
+        self.invalidate_backend_cache(backend_id).await;
+        self.publish_backend_update(backend_id, "onboarding_updated")
+            .await;
+
+        Ok(onboarding)
+    }
+
+    /// Get the onboarding status of a backend, if it was added by hostname
+    pub async fn get_onboarding(&self, backend_id: &str) -> Result<Option<OnboardingStatus>> {
+        let db = self.state.db()?;
+
+        let row: Option<(serde_json::Value,)> =
+            sqlx::query_as("SELECT status FROM backend_onboarding WHERE backend_id = $1")
+                .bind(backend_id)
+                .fetch_optional(db)
+                .await?;
+
+        row.map(|(json,)| {
+            serde_json::from_value(json).map_err(|e| {
+                Error::Internal(format!("Failed to deserialize onboarding status: {}", e))
+            })
+        })
+        .transpose()
+    }
+
+    /// Run the checks in order, stopping at the first one failing
+    async fn run_onboarding_checks(
+        &self,
+        onboarding: &mut OnboardingStatus,
+        kind: ProbeKind,
+        requested_port: u32,
+    ) {
+        onboarding.error.clear();
+        onboarding.ownership_verified = false;
+        onboarding.origin_reachable = false;
+        onboarding.origin_version.clear();
+        onboarding.origin_latency_ms = 0;
+
+        match resolve_origin(&onboarding.hostname, requested_port, kind).await {
+            Ok((target, port, addresses)) => {
+                onboarding.target = target;
+                onboarding.port = port as u32;
+                onboarding.addresses = addresses;
+            }
+            Err(e) => {
+                onboarding.state = OnboardingState::PendingDns as i32;
+                onboarding.error = e.to_string();
+                return;
+            }
+        }
+
+        onboarding.ownership_verified = self
+            .check_dns_txt_record(&onboarding.hostname, &onboarding.verification_token)
+            .await;
+        if !onboarding.ownership_verified {
+            onboarding.state = OnboardingState::PendingVerification as i32;
+            onboarding.error = format!(
+                "Add TXT record '{}' with value '{}'",
+                onboarding.verification_record, onboarding.verification_token
+            );
+            return;
+        }
+
+        let request = OriginProbeRequest {
+            id: Uuid::new_v4().to_string(),
+            kind,
+            address: onboarding.addresses[0].clone(),
+            port: onboarding.port as u16,
+            hostname: onboarding.hostname.clone(),
+        };
+        match self.probe_origin(&request).await {
+            Ok(result) if result.reachable => {
+                onboarding.state = OnboardingState::Active as i32;
+                onboarding.origin_reachable = true;
+                onboarding.origin_version = result.version;
+                onboarding.origin_latency_ms = result.latency_ms.min(u32::MAX as u64) as u32;
+            }
+            Ok(result) => {
+                onboarding.state = OnboardingState::PendingProbe as i32;
+                onboarding.error = format!(
+                    "Origin {}:{} did not answer a status ping: {}",
+                    request.address,
+                    request.port,
+                    result.error.unwrap_or_default()
+                );
+            }
+            Err(e) => {
+                onboarding.state = OnboardingState::PendingProbe as i32;
+                onboarding.error = e.to_string();
+            }
+        }
+    }
+
+    /// Queue a status ping for the workers and wait for its result
+    async fn probe_origin(&self, request: &OriginProbeRequest) -> Result<OriginProbeResult> {
+        let cache = self
+            .state
+            .cache
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Origin probes require Redis".to_string()))?;
+
+        cache.push(PROBE_QUEUE_KEY, request, PROBE_TTL).await?;
+
+        let key = probe_result_key(&request.id);
+        let deadline = tokio::time::Instant::now() + PROBE_WAIT;
+        while tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            if let Some(result) = cache.get::<OriginProbeResult>(&key).await? {
+                let _ = cache.delete(&key).await;
+                return Ok(result);
+            }
+        }
+
+        Err(Error::timeout(
+            "No worker answered the origin probe in time",
+        ))
+    }
+
+    /// Refuse to enable protection of a backend whose onboarding is not
+    /// complete
+    async fn ensure_onboarded(&self, backend_id: &str) -> Result<()> {
+        match self.get_onboarding(backend_id).await? {
+            Some(onboarding) if onboarding.state() != OnboardingState::Active => {
+                Err(Error::validation(format!(
+                    "Backend onboarding is not complete ({:?}): {}",
+                    onboarding.state(),
+                    onboarding.error
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
     // =========================================================================
     // Protection Settings
     // =========================================================================
@@ -663,6 +909,32 @@ impl BackendService {
         // Verify backend exists
         let _backend = self.get(backend_id).await?;
 
+        let mut protection = protection;
+        if let Some(honeypot) = protection.honeypot.take() {
+            let origin_ports: Vec<u32> = self
+                .get_origins(backend_id)
+                .await?
+                .iter()
+                .map(|origin| origin.port)
+                .collect();
+            protection.honeypot = Some(validate_honeypot(honeypot, &origin_ports)?);
+        }
+        if let Some(learning) = protection.learning {
+            protection.learning = Some(validate_learning(learning)?);
+        }
+        if let Some(block_responses) = protection.block_responses.take() {
+            protection.block_responses = Some(validate_block_responses(block_responses)?);
+        }
+        if let Some(connection_rate) = protection.connection_rate {
+            protection.connection_rate = Some(validate_connection_rate(connection_rate)?);
+        }
+        if let Some(marking) = protection.marking {
+            protection.marking = Some(validate_marking(marking)?);
+        }
+        if protection.enabled {
+            self.ensure_onboarded(backend_id).await?;
+        }
+
         let protection_json = serde_json::to_value(&protection).map_err(|e| {
             Error::Internal(format!("Failed to serialize protection settings: {}", e))
         })?;
@@ -707,6 +979,9 @@ impl BackendService {
 
         // Verify backend exists
         let _backend = self.get(backend_id).await?;
+        if level != ProtectionLevel::Off {
+            self.ensure_onboarded(backend_id).await?;
+        }
 
         let now = chrono::Utc::now();
 
@@ -790,6 +1065,48 @@ impl BackendService {
         Ok(level)
     }
 
+    /// Set the honeypot ports of a backend
+    #[instrument(skip(self, honeypot))]
+    pub async fn set_honeypot(
+        &self,
+        backend_id: &str,
+        honeypot: HoneypotSettings,
+    ) -> Result<HoneypotSettings> {
+        let mut protection = self.get_protection(backend_id).await?;
+        protection.honeypot = Some(honeypot);
+        let protection = self.update_protection(backend_id, protection).await?;
+        let honeypot = protection.honeypot.unwrap_or_default();
+
+        info!(
+            backend_id = %backend_id,
+            ports = ?honeypot.ports,
+            auto_block = honeypot.auto_block,
+            "Set honeypot ports"
+        );
+        Ok(honeypot)
+    }
+
+    /// Set the responses served to clients of a backend refused at L7
+    #[instrument(skip(self, block_responses))]
+    pub async fn set_block_responses(
+        &self,
+        backend_id: &str,
+        block_responses: BlockResponseSettings,
+    ) -> Result<BlockResponseSettings> {
+        let mut protection = self.get_protection(backend_id).await?;
+        protection.block_responses = Some(block_responses);
+        let protection = self.update_protection(backend_id, protection).await?;
+        let block_responses = protection.block_responses.unwrap_or_default();
+
+        info!(
+            backend_id = %backend_id,
+            http_block_page = !block_responses.http_block_page.is_empty(),
+            minecraft_kick_message = !block_responses.minecraft_kick_message.is_empty(),
+            "Set block responses"
+        );
+        Ok(block_responses)
+    }
+
     /// Get protection settings for a backend
     #[instrument(skip(self))]
     pub async fn get_protection(&self, backend_id: &str) -> Result<ProtectionSettings> {
@@ -843,15 +1160,7 @@ impl BackendService {
 
         let (total_origins, healthy_origins) = row.unwrap_or((0, 0));
 
-        let health = if total_origins == 0 {
-            HealthStatus::Unhealthy
-        } else if healthy_origins == total_origins {
-            HealthStatus::Healthy
-        } else if healthy_origins > 0 {
-            HealthStatus::Degraded
-        } else {
-            HealthStatus::Unhealthy
-        };
+        let health = origin_health(healthy_origins, total_origins);
 
         Ok(BackendStatus {
             health: health as i32,
@@ -916,7 +1225,7 @@ impl BackendService {
     // =========================================================================
 
     /// Invalidate all cache entries for a backend
-    async fn invalidate_backend_cache(&self, backend_id: &str) {
+    pub(crate) async fn invalidate_backend_cache(&self, backend_id: &str) {
         if let Some(cache) = &self.state.cache {
             let _ = cache.delete(&format!("backend:{}", backend_id)).await;
             let _ = cache
@@ -929,7 +1238,7 @@ impl BackendService {
     }
 
     /// Publish a backend update event for workers to consume
-    async fn publish_backend_update(&self, backend_id: &str, event_type: &str) {
+    pub(crate) async fn publish_backend_update(&self, backend_id: &str, event_type: &str) {
         if let Some(cache) = &self.state.cache {
             let message = format!("{}:{}", event_type, backend_id);
             if let Err(e) = cache.publish("backend_updates", &message).await {
@@ -938,3 +1247,309 @@ impl BackendService {
         }
     }
 }
+
+/// Maximum number of honeypot ports per backend
+pub const MAX_HONEYPOT_PORTS: usize = 64;
+
+/// Validate honeypot settings, returning them with ports sorted and
+/// deduplicated
+///
+/// Ports the backend's origins serve on are rejected: real clients send to
+/// them, and would be flagged (and possibly blocked) as attackers.
+pub fn validate_honeypot(
+    mut honeypot: HoneypotSettings,
+    service_ports: &[u32],
+) -> Result<HoneypotSettings> {
+    honeypot.ports.sort_unstable();
+    honeypot.ports.dedup();
+
+    if let Some(port) = honeypot
+        .ports
+        .iter()
+        .find(|&&port| port == 0 || port > u16::MAX as u32)
+    {
+        return Err(Error::validation(format!("Invalid honeypot port {}", port)));
+    }
+    if let Some(port) = honeypot
+        .ports
+        .iter()
+        .find(|port| service_ports.contains(port))
+    {
+        return Err(Error::validation(format!(
+            "Port {} is served by an origin and cannot be a honeypot",
+            port
+        )));
+    }
+    if honeypot.ports.len() > MAX_HONEYPOT_PORTS {
+        return Err(Error::validation(format!(
+            "At most {} honeypot ports are allowed per backend",
+            MAX_HONEYPOT_PORTS
+        )));
+    }
+
+    Ok(honeypot)
+}
+
+/// Maximum training window of allowlist learning (7 days)
+pub const MAX_TRAINING_SECONDS: u32 = 7 * 24 * 3600;
+
+/// Maximum factor applied to the limits of known-good sources
+pub const MAX_LIMIT_MULTIPLIER: u32 = 100;
+
+/// Validate allowlist learning settings, returning them with defaults filled in
+///
+/// A zero `min_handshakes` means a single completed handshake is enough.
+pub fn validate_learning(mut learning: LearningSettings) -> Result<LearningSettings> {
+    if !learning.enabled {
+        return Ok(learning);
+    }
+
+    if learning.training_seconds == 0 || learning.training_seconds > MAX_TRAINING_SECONDS {
+        return Err(Error::validation(format!(
+            "Training window must be between 1 and {} seconds",
+            MAX_TRAINING_SECONDS
+        )));
+    }
+    if learning.limit_multiplier < 2 || learning.limit_multiplier > MAX_LIMIT_MULTIPLIER {
+        return Err(Error::validation(format!(
+            "Limit multiplier must be between 2 and {}",
+            MAX_LIMIT_MULTIPLIER
+        )));
+    }
+    learning.min_handshakes = learning.min_handshakes.max(1);
+
+    Ok(learning)
+}
+
+/// Largest custom HTTP block page
+pub const MAX_BLOCK_PAGE_BYTES: usize = 64 * 1024;
+
+/// Largest custom Minecraft kick message (the protocol allows 256 KiB)
+pub const MAX_KICK_MESSAGE_BYTES: usize = 32 * 1024;
+
+/// Validate custom block responses, returning them normalized
+///
+/// Blank fields are cleared so workers keep their default response. A kick
+/// message that is not a JSON text component (object, array or string) is
+/// taken as plain text and wrapped in one.
+pub fn validate_block_responses(
+    mut block_responses: BlockResponseSettings,
+) -> Result<BlockResponseSettings> {
+    if block_responses.http_block_page.trim().is_empty() {
+        block_responses.http_block_page.clear();
+    }
+    if block_responses.http_block_page.len() > MAX_BLOCK_PAGE_BYTES {
+        return Err(Error::validation(format!(
+            "Block page must be at most {} bytes",
+            MAX_BLOCK_PAGE_BYTES
+        )));
+    }
+
+    let kick = block_responses.minecraft_kick_message.trim();
+    block_responses.minecraft_kick_message = if kick.is_empty() {
+        String::new()
+    } else {
+        match serde_json::from_str::<serde_json::Value>(kick) {
+            Ok(serde_json::Value::Object(_) | serde_json::Value::Array(_))
+            | Ok(serde_json::Value::String(_)) => kick.to_string(),
+            _ => serde_json::json!({ "text": kick }).to_string(),
+        }
+    };
+    if block_responses.minecraft_kick_message.len() > MAX_KICK_MESSAGE_BYTES {
+        return Err(Error::validation(format!(
+            "Kick message must be at most {} bytes",
+            MAX_KICK_MESSAGE_BYTES
+        )));
+    }
+
+    Ok(block_responses)
+}
+
+/// Lowest per-connection bandwidth (1 KiB/s)
+pub const MIN_CONNECTION_BYTES_PER_SECOND: u64 = 1024;
+
+/// Smallest per-connection burst; anything smaller drops every full-size frame
+pub const MIN_CONNECTION_BURST_BYTES: u64 = 1514;
+
+/// Validate per-connection bandwidth settings
+///
+/// A zero `bytes_per_second` leaves connections unlimited, in which case the
+/// burst is cleared too.
+pub fn validate_connection_rate(
+    mut connection_rate: ConnectionRateSettings,
+) -> Result<ConnectionRateSettings> {
+    if connection_rate.bytes_per_second == 0 {
+        connection_rate.burst_bytes = 0;
+        return Ok(connection_rate);
+    }
+
+    if connection_rate.bytes_per_second < MIN_CONNECTION_BYTES_PER_SECOND {
+        return Err(Error::validation(format!(
+            "Connection bandwidth must be at least {} bytes per second",
+            MIN_CONNECTION_BYTES_PER_SECOND
+        )));
+    }
+    if connection_rate.burst_bytes != 0 && connection_rate.burst_bytes < MIN_CONNECTION_BURST_BYTES
+    {
+        return Err(Error::validation(format!(
+            "Connection burst must be at least {} bytes",
+            MIN_CONNECTION_BURST_BYTES
+        )));
+    }
+
+    Ok(connection_rate)
+}
+
+/// Largest DSCP value (6 bits)
+pub const MAX_DSCP: u32 = 63;
+
+/// Validate traffic marking settings
+pub fn validate_marking(marking: MarkingSettings) -> Result<MarkingSettings> {
+    if marking.dscp > MAX_DSCP {
+        return Err(Error::validation(format!(
+            "DSCP must be between 0 and {}",
+            MAX_DSCP
+        )));
+    }
+
+    Ok(marking)
+}
+
+/// Time to wait for a worker to ping an origin
+pub const PROBE_WAIT: Duration = Duration::from_secs(10);
+
+/// Health of a backend from its enabled origins
+pub fn origin_health(healthy_origins: i64, total_origins: i64) -> HealthStatus {
+    if total_origins == 0 {
+        HealthStatus::Unhealthy
+    } else if healthy_origins == total_origins {
+        HealthStatus::Healthy
+    } else if healthy_origins > 0 {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Unhealthy
+    }
+}
+
+/// Default port of Minecraft Java servers
+pub const MINECRAFT_JAVA_PORT: u16 = 25565;
+
+/// Default port of Minecraft Bedrock servers
+pub const MINECRAFT_BEDROCK_PORT: u16 = 19132;
+
+/// Status ping used to onboard a backend type, `None` if it cannot be
+/// onboarded by hostname
+pub fn probe_kind(backend_type: BackendType) -> Option<ProbeKind> {
+    match backend_type {
+        BackendType::MinecraftJava => Some(ProbeKind::MinecraftJava),
+        BackendType::MinecraftBedrock => Some(ProbeKind::MinecraftBedrock),
+        _ => None,
+    }
+}
+
+/// Name of the TXT record proving ownership of a hostname
+pub fn verification_record(hostname: &str) -> String {
+    format!("_piston-verify.{}", hostname)
+}
+
+/// Validate the backend type, hostname and port of an onboarding
+pub fn validate_onboarding_target(
+    backend_type: BackendType,
+    hostname: &str,
+    port: u32,
+) -> Result<()> {
+    if probe_kind(backend_type).is_none() {
+        return Err(Error::validation(
+            "Onboarding by hostname is only supported for Minecraft backends",
+        ));
+    }
+    if !BackendService::is_valid_domain(hostname) {
+        return Err(Error::validation(format!("Invalid hostname: {}", hostname)));
+    }
+    if port > u16::MAX as u32 {
+        return Err(Error::validation(format!("Invalid port {}", port)));
+    }
+    Ok(())
+}
+
+/// SRV record of a Minecraft Java server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvTarget {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// Pick the SRV record clients connect to: lowest priority, then highest
+/// weight
+pub fn select_srv(records: &[SrvTarget]) -> Option<&SrvTarget> {
+    records
+        .iter()
+        .filter(|record| record.target != "." && record.port != 0)
+        .min_by(|a, b| a.priority.cmp(&b.priority).then(b.weight.cmp(&a.weight)))
+}
+
+/// Resolve the address players reach a hostname at
+///
+/// Without an explicit port, Java servers are looked up through the
+/// `_minecraft._tcp` SRV record like the vanilla client does, falling back
+/// to the hostname on the default port.
+async fn resolve_origin(
+    hostname: &str,
+    port: u32,
+    kind: ProbeKind,
+) -> Result<(String, u16, Vec<String>)> {
+    use hickory_resolver::TokioResolver;
+
+    let resolver = TokioResolver::builder_tokio()
+        .map_err(|e| Error::Internal(format!("Failed to create DNS resolver: {}", e)))?
+        .build();
+
+    let mut target = (
+        hostname.to_string(),
+        match kind {
+            ProbeKind::MinecraftJava => MINECRAFT_JAVA_PORT,
+            ProbeKind::MinecraftBedrock => MINECRAFT_BEDROCK_PORT,
+        },
+    );
+    if port != 0 {
+        target.1 = port as u16;
+    } else if kind == ProbeKind::MinecraftJava {
+        if let Ok(lookup) = resolver
+            .srv_lookup(format!("_minecraft._tcp.{}", hostname))
+            .await
+        {
+            let records: Vec<SrvTarget> = lookup
+                .iter()
+                .map(|srv| SrvTarget {
+                    priority: srv.priority(),
+                    weight: srv.weight(),
+                    port: srv.port(),
+                    target: srv.target().to_utf8().trim_end_matches('.').to_string(),
+                })
+                .collect();
+            if let Some(srv) = select_srv(&records) {
+                target = (srv.target.clone(), srv.port);
+            }
+        }
+    }
+
+    let addresses: Vec<String> = resolver
+        .lookup_ip(target.0.as_str())
+        .await
+        .map_err(|e| {
+            Error::external_service("DNS", format!("{} does not resolve: {}", target.0, e))
+        })?
+        .iter()
+        .map(|ip| ip.to_string())
+        .collect();
+    if addresses.is_empty() {
+        return Err(Error::external_service(
+            "DNS",
+            format!("{} has no A or AAAA records", target.0),
+        ));
+    }
+
+    Ok((target.0, target.1, addresses))
+}
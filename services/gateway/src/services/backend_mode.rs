@@ -0,0 +1,392 @@
+//! Backend modes
+//!
+//! A backend can be switched to passthrough, so workers forward its traffic
+//! without filtering, or to block-all, so they drop all of it. This answers
+//! "is the protection breaking my server" without touching the protection
+//! settings. Every change is written to an audit log, and every mode other
+//! than protection reverts after a timeout: the monitor switches the backend
+//! back, and workers stop applying the override on their own once it
+//! expired (see `pistonprotection_common::backend_mode`).
+
+use crate::services::AppState;
+use crate::services::backend::BackendService;
+use chrono::{DateTime, Utc};
+use pistonprotection_common::backend_mode::{
+    ACTIVE_MODES_KEY, BackendMode as Mode, BackendModeOverride, MODE_TTL_MARGIN, backend_mode_key,
+};
+use pistonprotection_common::duration::bounded_duration;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::redis::CacheService;
+use pistonprotection_proto::backend::{BackendMode, BackendModeChange, BackendModeState};
+use sqlx::Row;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+/// Time until a mode reverts when the request leaves it unset
+pub const DEFAULT_REVERT_SECONDS: u32 = 3600;
+
+/// Longest time until a mode reverts
+pub const MAX_REVERT_SECONDS: u32 = 7 * 24 * 3600;
+
+/// Longest reason
+pub const MAX_REASON_LEN: usize = 1024;
+
+/// Audit log entries returned when the request leaves it unset
+pub const DEFAULT_HISTORY_LIMIT: u32 = 20;
+
+/// Most audit log entries returned
+pub const MAX_HISTORY_LIMIT: u32 = 500;
+
+/// Author of automatic reverts in the audit log
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// How often the monitor reverts expired modes
+const MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Backend mode service implementation
+pub struct BackendModeService {
+    state: AppState,
+    backends: BackendService,
+}
+
+impl BackendModeService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            backends: BackendService::new(state.clone()),
+            state,
+        }
+    }
+
+    fn cache(&self) -> Result<&CacheService> {
+        self.state
+            .cache
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Backend modes require Redis".to_string()))
+    }
+
+    /// Switch a backend to a mode
+    ///
+    /// Modes other than protection revert after `revert_after_seconds`.
+    #[instrument(skip(self, reason))]
+    pub async fn set(
+        &self,
+        backend_id: &str,
+        mode: BackendMode,
+        revert_after_seconds: u32,
+        reason: &str,
+        changed_by: &str,
+    ) -> Result<BackendModeState> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+        let mode = mode_from_proto(mode)?;
+        let revert_after_seconds = bounded_duration(
+            revert_after_seconds,
+            DEFAULT_REVERT_SECONDS,
+            MAX_REVERT_SECONDS,
+        )
+        .ok_or_else(|| {
+            Error::validation(format!(
+                "Revert timer cannot exceed {} seconds",
+                MAX_REVERT_SECONDS
+            ))
+        })?;
+        let reason = validate_reason(reason)?;
+
+        // The backend must exist
+        self.backends.get(backend_id).await?;
+        let hosts: Vec<String> = sqlx::query_as::<_, (String,)>(
+            "SELECT domain FROM backend_domains WHERE backend_id = $1",
+        )
+        .bind(backend_id)
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|(domain,)| domain)
+        .collect();
+
+        let changed_at = Utc::now();
+        let revert_at = (mode != Mode::Protect)
+            .then(|| changed_at + chrono::Duration::seconds(revert_after_seconds as i64));
+
+        let mut tx = db.begin().await?;
+        let from_mode: Option<(i32,)> =
+            sqlx::query_as("SELECT mode FROM backend_modes WHERE backend_id = $1 FOR UPDATE")
+                .bind(backend_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let from_mode = from_mode
+            .map(|(mode,)| mode)
+            .unwrap_or(BackendMode::Protect as i32);
+
+        match revert_at {
+            Some(revert_at) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO backend_modes (
+                        backend_id, mode, reason, changed_by, changed_at, revert_at
+                    )
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    ON CONFLICT (backend_id) DO UPDATE
+                    SET mode = EXCLUDED.mode,
+                        reason = EXCLUDED.reason,
+                        changed_by = EXCLUDED.changed_by,
+                        changed_at = EXCLUDED.changed_at,
+                        revert_at = EXCLUDED.revert_at
+                    "#,
+                )
+                .bind(backend_id)
+                .bind(mode_to_proto(mode) as i32)
+                .bind(&reason)
+                .bind(changed_by)
+                .bind(changed_at)
+                .bind(revert_at)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM backend_modes WHERE backend_id = $1")
+                    .bind(backend_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO backend_mode_audit (
+                id, backend_id, from_mode, to_mode, reason, changed_by, changed_at, revert_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(backend_id)
+        .bind(from_mode)
+        .bind(mode_to_proto(mode) as i32)
+        .bind(&reason)
+        .bind(changed_by)
+        .bind(changed_at)
+        .bind(revert_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        match revert_at {
+            Some(revert_at) => {
+                let plan = BackendModeOverride {
+                    backend_id: backend_id.to_string(),
+                    mode,
+                    hosts,
+                    reason: reason.clone(),
+                    changed_by: changed_by.to_string(),
+                    changed_at: changed_at.timestamp(),
+                    revert_at: revert_at.timestamp(),
+                };
+                let ttl = Duration::from_secs(revert_after_seconds as u64) + MODE_TTL_MARGIN;
+                cache.set(&backend_mode_key(backend_id), &plan, ttl).await?;
+                cache.sadd(ACTIVE_MODES_KEY, backend_id).await?;
+            }
+            None => {
+                cache.delete(&backend_mode_key(backend_id)).await?;
+                cache.srem(ACTIVE_MODES_KEY, backend_id).await?;
+            }
+        }
+
+        info!(
+            backend_id = %backend_id,
+            from = ?BackendMode::try_from(from_mode).unwrap_or_default(),
+            to = %mode,
+            changed_by = %changed_by,
+            reason = %reason,
+            revert_at = ?revert_at,
+            "Changed backend mode"
+        );
+
+        self.backends.invalidate_backend_cache(backend_id).await;
+        self.backends
+            .publish_backend_update(backend_id, "backend_mode_changed")
+            .await;
+
+        self.state(backend_id).await
+    }
+
+    /// Get the mode of a backend and its latest changes
+    #[instrument(skip(self))]
+    pub async fn get(
+        &self,
+        backend_id: &str,
+        history_limit: u32,
+    ) -> Result<(BackendModeState, Vec<BackendModeChange>)> {
+        let db = self.state.db()?;
+        self.backends.get(backend_id).await?;
+
+        let state = self.state(backend_id).await?;
+        let history = sqlx::query(
+            r#"
+            SELECT id, backend_id, from_mode, to_mode, reason, changed_by, changed_at, revert_at
+            FROM backend_mode_audit
+            WHERE backend_id = $1
+            ORDER BY changed_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(backend_id)
+        .bind(history_limit_or_default(history_limit) as i64)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| {
+            let changed_at: DateTime<Utc> = row.get("changed_at");
+            let revert_at: Option<DateTime<Utc>> = row.get("revert_at");
+            BackendModeChange {
+                id: row.get("id"),
+                backend_id: row.get("backend_id"),
+                from_mode: row.get("from_mode"),
+                to_mode: row.get("to_mode"),
+                reason: row.get("reason"),
+                changed_by: row.get("changed_by"),
+                changed_at: Some(changed_at.into()),
+                revert_at: revert_at.map(Into::into),
+            }
+        })
+        .collect();
+
+        Ok((state, history))
+    }
+
+    /// Current mode of a backend as stored
+    async fn state(&self, backend_id: &str) -> Result<BackendModeState> {
+        let db = self.state.db()?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT mode, reason, changed_by, changed_at, revert_at
+            FROM backend_modes
+            WHERE backend_id = $1
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(match row {
+            Some(row) => {
+                let changed_at: DateTime<Utc> = row.get("changed_at");
+                let revert_at: DateTime<Utc> = row.get("revert_at");
+                BackendModeState {
+                    backend_id: backend_id.to_string(),
+                    mode: row.get("mode"),
+                    reason: row.get("reason"),
+                    changed_by: row.get("changed_by"),
+                    changed_at: Some(changed_at.into()),
+                    revert_at: Some(revert_at.into()),
+                }
+            }
+            None => BackendModeState {
+                backend_id: backend_id.to_string(),
+                mode: BackendMode::Protect as i32,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Backends whose mode is past its revert time
+    async fn expired_backends(&self) -> Result<Vec<String>> {
+        let db = self.state.db()?;
+
+        let rows: Vec<(String,)> =
+            sqlx::query_as("SELECT backend_id FROM backend_modes WHERE revert_at <= NOW()")
+                .fetch_all(db)
+                .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+}
+
+/// Spawn the monitor reverting backends to protection once their mode
+/// expired
+pub fn spawn_monitor(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() || state.cache.is_none() {
+        info!("Backend mode monitor disabled");
+        return None;
+    }
+
+    let service = BackendModeService::new(state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    let backend_ids = match service.expired_backends().await {
+                        Ok(ids) => ids,
+                        Err(e) => {
+                            warn!(error = %e, "Failed to list expired backend modes");
+                            continue;
+                        }
+                    };
+                    for backend_id in backend_ids {
+                        if let Err(e) = service
+                            .set(
+                                &backend_id,
+                                BackendMode::Protect,
+                                0,
+                                "Revert timer expired",
+                                SYSTEM_ACTOR,
+                            )
+                            .await
+                        {
+                            warn!(error = %e, backend_id = %backend_id, "Failed to revert backend mode");
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Mode workers apply for a requested mode
+pub fn mode_from_proto(mode: BackendMode) -> Result<Mode> {
+    match mode {
+        BackendMode::Unspecified => Err(Error::validation("Backend mode is required")),
+        BackendMode::Protect => Ok(Mode::Protect),
+        BackendMode::Passthrough => Ok(Mode::Passthrough),
+        BackendMode::BlockAll => Ok(Mode::BlockAll),
+    }
+}
+
+/// Requested mode of a mode workers apply
+pub fn mode_to_proto(mode: Mode) -> BackendMode {
+    match mode {
+        Mode::Protect => BackendMode::Protect,
+        Mode::Passthrough => BackendMode::Passthrough,
+        Mode::BlockAll => BackendMode::BlockAll,
+    }
+}
+
+/// Validate the reason of a mode change
+pub fn validate_reason(reason: &str) -> Result<String> {
+    let reason = reason.trim();
+    if reason.len() > MAX_REASON_LEN {
+        return Err(Error::validation(format!(
+            "Reason cannot exceed {} bytes",
+            MAX_REASON_LEN
+        )));
+    }
+    Ok(reason.to_string())
+}
+
+/// Number of audit log entries to return, 0 meaning the default
+pub fn history_limit_or_default(limit: u32) -> u32 {
+    match limit {
+        0 => DEFAULT_HISTORY_LIMIT,
+        l => l.min(MAX_HISTORY_LIMIT),
+    }
+}
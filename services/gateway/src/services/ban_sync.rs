@@ -0,0 +1,682 @@
+//! Ban list synchronization with game server plugins
+//!
+//! Plugins on the game servers behind a backend (Paper, Velocity, ...) push
+//! the bans issued in game and pull the blocklist of the network, so a
+//! player banned by the moderators is also kept off the network and every
+//! server of the backend learns about bans issued elsewhere.
+//!
+//! The protocol is plain JSON over HTTP under `/plugin/v1`, authenticated
+//! with the token of the backend as bearer credential:
+//!
+//! - `POST /plugin/v1/bans` takes `{"events": [...]}` where each event is
+//!   `{"action": "ban" | "unban", "uuid", "ip", "reason", "server",
+//!   "expires_at"}`. A ban needs the UUID or the address of the player; an
+//!   unban lifts every ban matching either. Events are applied in order and
+//!   invalid ones are reported by index without failing the others.
+//! - `GET /plugin/v1/blocklist` returns the active bans and the networks
+//!   dropped by the filter rules of the backend, with an `ETag` so plugins
+//!   can poll with `If-None-Match` cheaply.
+//!
+//! The addresses of banned players are dropped by one filter rule per
+//! backend, maintained from the active bans. Bans expire on their own; the
+//! monitor lifts them from the rule.
+
+use crate::services::AppState;
+use crate::services::backend_mode::SYSTEM_ACTOR;
+use crate::services::config_history::ConfigHistoryService;
+use crate::services::filter::FilterService;
+use crate::services::rule_transfer::format_networks;
+use chrono::{DateTime, Utc};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::backend::BanSync;
+use pistonprotection_proto::common::{Action, IpAddress, IpNetwork};
+use pistonprotection_proto::filter::{FilterMatch, FilterRule};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+/// Most events accepted in one push
+pub const MAX_EVENTS: usize = 1000;
+
+/// Longest ban reason
+pub const MAX_REASON_LEN: usize = 1024;
+
+/// Longest server name
+pub const MAX_SERVER_LEN: usize = 255;
+
+/// Name of the filter rule maintained from the bans
+pub const BAN_RULE_NAME: &str = "Minecraft bans";
+
+/// How often the monitor lifts expired bans
+const MONITOR_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Change pushed by a plugin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BanAction {
+    Ban,
+    Unban,
+}
+
+/// Ban event as sent by a plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanEvent {
+    pub action: BanAction,
+    /// Player UUID, with or without hyphens
+    #[serde(default)]
+    pub uuid: Option<String>,
+    /// Last address of the player
+    #[serde(default)]
+    pub ip: Option<String>,
+    #[serde(default)]
+    pub reason: String,
+    /// Server the ban was issued on
+    #[serde(default)]
+    pub server: String,
+    /// Unset for permanent bans
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Body of a push
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushRequest {
+    pub events: Vec<BanEvent>,
+}
+
+/// Validated ban event
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerBan {
+    pub action: BanAction,
+    pub uuid: Option<Uuid>,
+    pub ip: Option<IpAddr>,
+    pub reason: String,
+    pub server: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Event of a push that was not applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedEvent {
+    /// Position of the event in the push
+    pub index: usize,
+    pub error: String,
+}
+
+/// Outcome of a push
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushReport {
+    pub accepted: usize,
+    pub rejected: Vec<RejectedEvent>,
+    /// Bans in effect after the push
+    pub active_bans: u64,
+}
+
+/// Active ban in the blocklist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockedPlayer {
+    pub uuid: Option<Uuid>,
+    pub ip: Option<String>,
+    pub reason: String,
+    pub server: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Blocklist pulled by plugins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Blocklist {
+    pub bans: Vec<BlockedPlayer>,
+    /// Networks dropped by the filter rules of the backend
+    pub networks: Vec<String>,
+}
+
+impl Blocklist {
+    /// Entity tag of the blocklist, changing with its content
+    pub fn etag(&self) -> String {
+        let body = serde_json::to_vec(self).unwrap_or_default();
+        format!("\"{}\"", hex::encode(&Sha256::digest(&body)[..16]))
+    }
+}
+
+/// Ban sync service implementation
+pub struct BanSyncService {
+    state: AppState,
+    filters: FilterService,
+    history: ConfigHistoryService,
+}
+
+impl BanSyncService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            filters: FilterService::new(state.clone()),
+            history: ConfigHistoryService::new(state.clone()),
+            state,
+        }
+    }
+
+    /// Get the ban sync settings of a backend
+    #[instrument(skip(self))]
+    pub async fn get(&self, backend_id: &str) -> Result<Option<BanSync>> {
+        let db = self.state.db()?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT s.backend_id, s.token, s.enabled, s.rule_id, s.created_at, s.updated_at,
+                   (SELECT COUNT(*) FROM player_bans b
+                    WHERE b.backend_id = s.backend_id AND b.revoked_at IS NULL
+                      AND (b.expires_at IS NULL OR b.expires_at > NOW())) AS active_bans
+            FROM ban_syncs s
+            WHERE s.backend_id = $1
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(row.map(|row| ban_sync_from_row(&row)))
+    }
+
+    /// Enable or disable ban sync for a backend, creating its token the first
+    /// time
+    ///
+    /// Disabling locks the plugins out; bans already pushed stay in effect.
+    #[instrument(skip(self))]
+    pub async fn update(&self, backend_id: &str, enabled: bool) -> Result<BanSync> {
+        let db = self.state.db()?;
+
+        sqlx::query("SELECT 1 FROM backends WHERE id = $1")
+            .bind(backend_id)
+            .fetch_optional(db)
+            .await?
+            .ok_or_else(|| Error::not_found("Backend", backend_id))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO ban_syncs (backend_id, token, enabled)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (backend_id) DO UPDATE
+            SET enabled = EXCLUDED.enabled
+            "#,
+        )
+        .bind(backend_id)
+        .bind(generate_token())
+        .bind(enabled)
+        .execute(db)
+        .await?;
+
+        info!(backend_id = %backend_id, enabled, "Updated ban sync");
+
+        self.get(backend_id)
+            .await?
+            .ok_or_else(|| Error::not_found("BanSync", backend_id))
+    }
+
+    /// Give the plugins of a backend a new token. The old one stops working
+    /// right away.
+    #[instrument(skip(self))]
+    pub async fn rotate_token(&self, backend_id: &str) -> Result<BanSync> {
+        let db = self.state.db()?;
+
+        let result = sqlx::query("UPDATE ban_syncs SET token = $2 WHERE backend_id = $1")
+            .bind(backend_id)
+            .bind(generate_token())
+            .execute(db)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::not_found("BanSync", backend_id));
+        }
+
+        info!(backend_id = %backend_id, "Rotated ban sync token");
+
+        self.get(backend_id)
+            .await?
+            .ok_or_else(|| Error::not_found("BanSync", backend_id))
+    }
+
+    /// Apply the events pushed by a plugin. Unknown tokens and disabled
+    /// backends give `None`.
+    #[instrument(skip(self, token, events), fields(events = events.len()))]
+    pub async fn push(&self, token: &str, events: Vec<BanEvent>) -> Result<Option<PushReport>> {
+        let Some((backend_id, _)) = self.authenticate(token).await? else {
+            return Ok(None);
+        };
+        if events.len() > MAX_EVENTS {
+            return Err(Error::validation(format!(
+                "A push cannot contain more than {} events",
+                MAX_EVENTS
+            )));
+        }
+
+        let now = Utc::now();
+        let mut accepted = 0;
+        let mut rejected = Vec::new();
+        for (index, event) in events.into_iter().enumerate() {
+            let ban = match validate_event(event, now) {
+                Ok(ban) => ban,
+                Err(error) => {
+                    rejected.push(RejectedEvent { index, error });
+                    continue;
+                }
+            };
+            self.apply(&backend_id, &ban).await?;
+            accepted += 1;
+        }
+
+        let active_bans = if accepted > 0 {
+            self.sync_rule(&backend_id).await?
+        } else {
+            self.active_ips(&backend_id).await?.1
+        };
+
+        info!(
+            backend_id = %backend_id,
+            accepted,
+            rejected = rejected.len(),
+            "Applied ban events"
+        );
+
+        Ok(Some(PushReport {
+            accepted,
+            rejected,
+            active_bans,
+        }))
+    }
+
+    /// Build the blocklist of the backend behind a token. Unknown tokens and
+    /// disabled backends give `None`.
+    #[instrument(skip(self, token))]
+    pub async fn blocklist(&self, token: &str) -> Result<Option<Blocklist>> {
+        let Some((backend_id, organization_id)) = self.authenticate(token).await? else {
+            return Ok(None);
+        };
+        let db = self.state.db_read()?;
+
+        let bans = sqlx::query(
+            r#"
+            SELECT player_uuid, ip, reason, server, expires_at
+            FROM player_bans
+            WHERE backend_id = $1 AND revoked_at IS NULL
+              AND (expires_at IS NULL OR expires_at > NOW())
+            ORDER BY created_at, id
+            "#,
+        )
+        .bind(&backend_id)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| BlockedPlayer {
+            uuid: row.get("player_uuid"),
+            ip: row.get("ip"),
+            reason: row.get("reason"),
+            server: row.get("server"),
+            expires_at: row.get("expires_at"),
+        })
+        .collect();
+
+        let networks: BTreeSet<String> = self
+            .filters
+            .list_for_organization(&organization_id, Some(&backend_id), false)
+            .await?
+            .into_iter()
+            .filter(|(_, rule)| rule.action == Action::Drop as i32)
+            .filter_map(|(_, rule)| rule.r#match)
+            .flat_map(|m| format_networks(&m.source_ips))
+            .collect();
+
+        Ok(Some(Blocklist {
+            bans,
+            networks: networks.into_iter().collect(),
+        }))
+    }
+
+    /// Lift expired bans from the filter rules, giving the number of
+    /// backends updated
+    #[instrument(skip(self))]
+    pub async fn expire(&self) -> Result<usize> {
+        let db = self.state.db()?;
+
+        let backend_ids: BTreeSet<String> = sqlx::query(
+            r#"
+            UPDATE player_bans
+            SET revoked_at = expires_at
+            WHERE revoked_at IS NULL AND expires_at <= NOW()
+            RETURNING backend_id
+            "#,
+        )
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| row.get("backend_id"))
+        .collect();
+
+        for backend_id in &backend_ids {
+            self.sync_rule(backend_id).await?;
+        }
+        Ok(backend_ids.len())
+    }
+
+    /// Backend and organization behind a token
+    async fn authenticate(&self, token: &str) -> Result<Option<(String, String)>> {
+        if !valid_token(token) {
+            return Ok(None);
+        }
+        let db = self.state.db()?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT s.backend_id, b.organization_id
+            FROM ban_syncs s JOIN backends b ON b.id = s.backend_id
+            WHERE s.token = $1 AND s.enabled
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(row.map(|row| (row.get("backend_id"), row.get("organization_id"))))
+    }
+
+    async fn apply(&self, backend_id: &str, ban: &PlayerBan) -> Result<()> {
+        let db = self.state.db()?;
+        let ip = ban.ip.map(|ip| ip.to_string());
+
+        match ban.action {
+            BanAction::Ban => {
+                // A new ban of the same player replaces the previous one
+                sqlx::query(
+                    r#"
+                    UPDATE player_bans
+                    SET revoked_at = NOW()
+                    WHERE backend_id = $1 AND revoked_at IS NULL
+                      AND CASE WHEN $2::uuid IS NULL
+                               THEN player_uuid IS NULL AND ip = $3
+                               ELSE player_uuid = $2 END
+                    "#,
+                )
+                .bind(backend_id)
+                .bind(ban.uuid)
+                .bind(&ip)
+                .execute(db)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO player_bans (id, backend_id, player_uuid, ip, reason, server, expires_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    "#,
+                )
+                .bind(Uuid::new_v4().to_string())
+                .bind(backend_id)
+                .bind(ban.uuid)
+                .bind(&ip)
+                .bind(&ban.reason)
+                .bind(&ban.server)
+                .bind(ban.expires_at)
+                .execute(db)
+                .await?;
+            }
+            BanAction::Unban => {
+                sqlx::query(
+                    r#"
+                    UPDATE player_bans
+                    SET revoked_at = NOW()
+                    WHERE backend_id = $1 AND revoked_at IS NULL
+                      AND (player_uuid = $2 OR ip = $3)
+                    "#,
+                )
+                .bind(backend_id)
+                .bind(ban.uuid)
+                .bind(&ip)
+                .execute(db)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Addresses of the active bans of a backend, and the number of active
+    /// bans
+    async fn active_ips(&self, backend_id: &str) -> Result<(Vec<IpAddr>, u64)> {
+        let db = self.state.db()?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT ip
+            FROM player_bans
+            WHERE backend_id = $1 AND revoked_at IS NULL
+              AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_all(db)
+        .await?;
+
+        let ips: BTreeSet<IpAddr> = rows
+            .iter()
+            .filter_map(|row| row.get::<Option<String>, _>("ip"))
+            .filter_map(|ip| ip.parse().ok())
+            .collect();
+        Ok((ips.into_iter().collect(), rows.len() as u64))
+    }
+
+    /// Bring the filter rule of a backend in line with its active bans,
+    /// giving the number of active bans
+    async fn sync_rule(&self, backend_id: &str) -> Result<u64> {
+        let db = self.state.db()?;
+        let (ips, active_bans) = self.active_ips(backend_id).await?;
+
+        let rule_id: Option<String> =
+            sqlx::query_scalar("SELECT rule_id FROM ban_syncs WHERE backend_id = $1")
+                .bind(backend_id)
+                .fetch_optional(db)
+                .await?
+                .flatten();
+
+        let rule_id = match (rule_id, ban_rule(&ips)) {
+            (None, None) => return Ok(active_bans),
+            (Some(id), None) => {
+                match self.filters.delete(&id).await {
+                    Ok(()) | Err(Error::NotFound { .. }) => {}
+                    Err(e) => return Err(e),
+                }
+                None
+            }
+            (Some(id), Some(rule)) => match self
+                .filters
+                .update(FilterRule {
+                    id: id.clone(),
+                    ..rule.clone()
+                })
+                .await
+            {
+                Ok(_) => Some(id),
+                Err(Error::NotFound { .. }) => {
+                    Some(self.filters.create(backend_id, rule).await?.0.id)
+                }
+                Err(e) => return Err(e),
+            },
+            (None, Some(rule)) => Some(self.filters.create(backend_id, rule).await?.0.id),
+        };
+
+        sqlx::query("UPDATE ban_syncs SET rule_id = $2 WHERE backend_id = $1")
+            .bind(backend_id)
+            .bind(&rule_id)
+            .execute(db)
+            .await?;
+        self.history
+            .record_change(backend_id, "ban list synchronized", SYSTEM_ACTOR)
+            .await;
+
+        info!(backend_id = %backend_id, addresses = ips.len(), "Synchronized ban rule");
+        Ok(active_bans)
+    }
+}
+
+/// Spawn the task lifting expired bans
+pub fn spawn_monitor(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() {
+        info!("Ban expiry monitor disabled");
+        return None;
+    }
+
+    let service = BanSyncService::new(state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = service.expire().await {
+                        warn!(error = %e, "Failed to lift expired bans");
+                    }
+                }
+            }
+        }
+    }))
+}
+
+fn ban_sync_from_row(row: &sqlx::postgres::PgRow) -> BanSync {
+    let created_at: Option<DateTime<Utc>> = row.get("created_at");
+    let updated_at: Option<DateTime<Utc>> = row.get("updated_at");
+    let rule_id: Option<String> = row.get("rule_id");
+
+    BanSync {
+        backend_id: row.get("backend_id"),
+        enabled: row.get("enabled"),
+        token: row.get("token"),
+        active_bans: row.get::<i64, _>("active_bans") as u32,
+        rule_id: rule_id.unwrap_or_default(),
+        created_at: created_at.map(Into::into),
+        updated_at: updated_at.map(Into::into),
+    }
+}
+
+fn generate_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Whether a token can belong to a backend, checked before touching the
+/// database
+pub fn valid_token(token: &str) -> bool {
+    token.len() == 32 && token.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Validate an event pushed by a plugin
+pub fn validate_event(
+    event: BanEvent,
+    now: DateTime<Utc>,
+) -> std::result::Result<PlayerBan, String> {
+    let uuid = match event.uuid.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(uuid) => Some(Uuid::parse_str(uuid).map_err(|_| format!("Invalid UUID '{}'", uuid))?),
+    };
+    let ip = match event.ip.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(ip) => {
+            let addr: IpAddr = ip
+                .parse()
+                .map_err(|_| format!("Invalid address '{}'", ip))?;
+            if !is_public(addr) {
+                // Usually the address of a proxy in front of the server
+                return Err(format!("Address '{}' is not public", ip));
+            }
+            Some(addr)
+        }
+    };
+    if uuid.is_none() && ip.is_none() {
+        return Err("An event needs a UUID or an address".to_string());
+    }
+
+    let reason = event.reason.trim();
+    if reason.chars().count() > MAX_REASON_LEN {
+        return Err(format!(
+            "Reason cannot exceed {} characters",
+            MAX_REASON_LEN
+        ));
+    }
+    let server = event.server.trim();
+    if server.chars().count() > MAX_SERVER_LEN {
+        return Err(format!(
+            "Server cannot exceed {} characters",
+            MAX_SERVER_LEN
+        ));
+    }
+    if event.action == BanAction::Ban && event.expires_at.is_some_and(|at| at <= now) {
+        return Err("Ban already expired".to_string());
+    }
+
+    Ok(PlayerBan {
+        action: event.action,
+        uuid,
+        ip,
+        reason: reason.to_string(),
+        server: server.to_string(),
+        expires_at: event.expires_at,
+    })
+}
+
+/// Whether an address is routable on the internet
+fn is_public(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(v4) => {
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast())
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let segment = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || segment & 0xfe00 == 0xfc00
+                || segment & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// Filter rule dropping the given addresses, `None` without addresses
+pub fn ban_rule(ips: &[IpAddr]) -> Option<FilterRule> {
+    if ips.is_empty() {
+        return None;
+    }
+
+    let source_ips = ips
+        .iter()
+        .map(|ip| IpNetwork {
+            address: Some(IpAddress::from(*ip)),
+            prefix_length: if ip.is_ipv4() { 32 } else { 128 },
+        })
+        .collect();
+
+    Some(FilterRule {
+        name: BAN_RULE_NAME.to_string(),
+        description: "Addresses of players banned in game, maintained by ban sync".to_string(),
+        // Ahead of other rules, so an allow rule cannot let a banned player in
+        priority: 0,
+        r#match: Some(FilterMatch {
+            source_ips,
+            ..Default::default()
+        }),
+        action: Action::Drop as i32,
+        enabled: true,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,685 @@
+//! Bandwidth quotas
+//!
+//! Workers report the traffic of every backend, which the config manager
+//! adds up per UTC calendar month in `backend_bandwidth_usage`. The monitor
+//! compares the clean traffic of each organization's backends with the
+//! bandwidth of its plan and, once it is exceeded, applies the
+//! organization's quota action:
+//!
+//! - notify: the overage is written to the audit log, like every action
+//! - throttle: every connection to the backends is capped to the throttle
+//!   bandwidth (see `pistonprotection_common::bandwidth_quota`)
+//! - suspend: the backends are switched to block-all by the system actor,
+//!   reverting at the end of the period or after the longest backend mode,
+//!   whichever comes first
+//!
+//! Actions are lifted when a new period starts or the organization is back
+//! under its limit, e.g. after an upgrade. An operator may lift a
+//! suspension earlier by switching the backends back to protection.
+
+use crate::services::AppState;
+use crate::services::audit::AuditLogBuilder;
+use crate::services::backend::MIN_CONNECTION_BYTES_PER_SECOND;
+use crate::services::backend_mode::{BackendModeService, MAX_REVERT_SECONDS, SYSTEM_ACTOR};
+use chrono::{DateTime, NaiveDate, Utc};
+use pistonprotection_common::bandwidth_quota::{
+    BandwidthThrottle, QuotaAction, THROTTLE_TTL_MARGIN, THROTTLED_BACKENDS_KEY,
+    bandwidth_throttle_key, period_bounds, period_start, usage_percent,
+};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::redis::CacheService;
+use pistonprotection_proto::backend::{
+    BackendBandwidth, BackendMode, BandwidthQuota, BandwidthQuotaAction,
+};
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+
+/// Per-connection bandwidth while throttled when the policy leaves it unset
+pub const DEFAULT_THROTTLE_BYTES_PER_SECOND: u64 = 1024 * 1024;
+
+/// Usage in percent of the limit logged as a warning without a policy
+pub const DEFAULT_WARN_PERCENT: u32 = 80;
+
+/// Bandwidth of organizations without limits (`OrganizationLimits` default)
+pub const DEFAULT_LIMIT_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+/// How often the monitor enforces the quotas
+const MONITOR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Organizations with traffic in the period `$1` or an action in effect
+pub const ORGANIZATIONS_QUERY: &str = r#"
+    SELECT DISTINCT b.organization_id
+    FROM backend_bandwidth_usage u
+    JOIN backends b ON b.id = u.backend_id
+    WHERE u.period_start = $1
+    UNION
+    SELECT organization_id FROM bandwidth_quota_states
+    WHERE applied_action IS NOT NULL
+"#;
+
+/// Traffic of the backends of the organization `$1` in the period `$2`
+///
+/// Deleted backends are removed along with their usage.
+pub const USAGE_QUERY: &str = r#"
+    SELECT u.backend_id, u.bytes_in, u.bytes_dropped
+    FROM backend_bandwidth_usage u
+    JOIN backends b ON b.id = u.backend_id
+    WHERE b.organization_id = $1 AND u.period_start = $2
+    ORDER BY u.backend_id
+"#;
+
+/// Backends of the organization `$1`, which quota actions apply to
+pub const BACKEND_IDS_QUERY: &str = "SELECT id FROM backends WHERE organization_id = $1";
+
+/// Quota policy of an organization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaPolicy {
+    pub action: QuotaAction,
+    pub throttle_bytes_per_second: u64,
+    pub warn_percent: u32,
+    pub updated_by: String,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Default for QuotaPolicy {
+    fn default() -> Self {
+        Self {
+            action: QuotaAction::Notify,
+            throttle_bytes_per_second: DEFAULT_THROTTLE_BYTES_PER_SECOND,
+            warn_percent: DEFAULT_WARN_PERCENT,
+            updated_by: String::new(),
+            updated_at: None,
+        }
+    }
+}
+
+/// Enforcement of a quota in the current period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Enforcement {
+    applied_action: Option<QuotaAction>,
+    warned: bool,
+}
+
+/// What the monitor does about a quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaStep {
+    /// Nothing changed
+    Keep,
+    /// Usage reached the warning threshold
+    Warn,
+    /// Usage went past the limit, apply the action
+    Exceed(QuotaAction),
+    /// Usage is back under the limit, lift the applied action
+    Restore(QuotaAction),
+}
+
+/// Bandwidth quota service implementation
+pub struct BandwidthQuotaService {
+    state: AppState,
+    modes: BackendModeService,
+}
+
+impl BandwidthQuotaService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            modes: BackendModeService::new(state.clone()),
+            state,
+        }
+    }
+
+    fn cache(&self) -> Result<&CacheService> {
+        self.state
+            .cache
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Bandwidth quotas require Redis".to_string()))
+    }
+
+    /// Get the quota of an organization and its usage in the current period
+    #[instrument(skip(self))]
+    pub async fn get(&self, organization_id: &str) -> Result<BandwidthQuota> {
+        let db = self.state.db()?;
+        let period = period_start(Utc::now());
+
+        let policy = load_policy(db, organization_id).await?;
+        let enforcement = load_enforcement(db, organization_id, period).await?;
+        let limit_bytes = load_limit(db, organization_id).await?;
+        let backends = load_usage(db, organization_id, period).await?;
+
+        Ok(bandwidth_quota_to_proto(
+            organization_id,
+            &policy,
+            limit_bytes,
+            period,
+            enforcement.applied_action,
+            backends,
+        ))
+    }
+
+    /// Set the quota policy of an organization
+    ///
+    /// A new action applies the next time the limit is exceeded; an action
+    /// already in effect stays until the end of the period.
+    #[instrument(skip(self))]
+    pub async fn update(
+        &self,
+        organization_id: &str,
+        action: BandwidthQuotaAction,
+        throttle_bytes_per_second: u64,
+        warn_percent: u32,
+        updated_by: &str,
+    ) -> Result<BandwidthQuota> {
+        let db = self.state.db()?;
+        let action = action_from_proto(action)?;
+        let throttle_bytes_per_second =
+            validate_throttle_bytes_per_second(throttle_bytes_per_second)?;
+        let warn_percent = validate_warn_percent(warn_percent)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO bandwidth_quota_policies (
+                organization_id, action, throttle_bytes_per_second, warn_percent,
+                updated_by, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT (organization_id) DO UPDATE
+            SET action = EXCLUDED.action,
+                throttle_bytes_per_second = EXCLUDED.throttle_bytes_per_second,
+                warn_percent = EXCLUDED.warn_percent,
+                updated_by = EXCLUDED.updated_by,
+                updated_at = EXCLUDED.updated_at
+            "#,
+        )
+        .bind(organization_id)
+        .bind(action.as_str())
+        .bind(throttle_bytes_per_second as i64)
+        .bind(warn_percent as i32)
+        .bind(updated_by)
+        .execute(db)
+        .await?;
+
+        info!(
+            organization_id = %organization_id,
+            action = action.as_str(),
+            throttle_bytes_per_second,
+            warn_percent,
+            updated_by = %updated_by,
+            "Updated bandwidth quota policy"
+        );
+
+        self.get(organization_id).await
+    }
+
+    /// Enforce the quotas of every organization with traffic or an action
+    /// in effect
+    #[instrument(skip(self))]
+    pub async fn enforce(&self) -> Result<()> {
+        let db = self.state.db()?;
+        let period = period_start(Utc::now());
+
+        let organization_ids: Vec<(String,)> = sqlx::query_as(ORGANIZATIONS_QUERY)
+            .bind(period)
+            .fetch_all(db)
+            .await?;
+
+        for (organization_id,) in organization_ids {
+            if let Err(e) = self.enforce_organization(&organization_id, period).await {
+                warn!(
+                    organization_id = %organization_id,
+                    error = %e,
+                    "Failed to enforce bandwidth quota"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn enforce_organization(&self, organization_id: &str, period: NaiveDate) -> Result<()> {
+        let db = self.state.db()?;
+
+        // Actions of past periods end with them
+        let ended: Vec<(NaiveDate, String)> = sqlx::query_as(
+            r#"
+            SELECT period_start, applied_action FROM bandwidth_quota_states
+            WHERE organization_id = $1 AND period_start < $2 AND applied_action IS NOT NULL
+            "#,
+        )
+        .bind(organization_id)
+        .bind(period)
+        .fetch_all(db)
+        .await?;
+        for (ended_period, action) in ended {
+            if let Some(action) = QuotaAction::parse(&action) {
+                self.lift(organization_id, action, "Bandwidth quota period ended")
+                    .await?;
+            }
+            set_applied_action(db, organization_id, ended_period, None).await?;
+        }
+
+        let policy = load_policy(db, organization_id).await?;
+        let enforcement = load_enforcement(db, organization_id, period).await?;
+        let limit_bytes = load_limit(db, organization_id).await?;
+        let used_bytes: u64 = load_usage(db, organization_id, period)
+            .await?
+            .iter()
+            .map(clean_bytes)
+            .fold(0, u64::saturating_add);
+        let (_, period_end) = period_bounds(period);
+
+        let step = quota_step(
+            used_bytes,
+            limit_bytes,
+            policy.warn_percent,
+            policy.action,
+            enforcement.applied_action,
+            enforcement.warned,
+        );
+        let event = |action: &str| {
+            AuditLogBuilder::new(organization_id, action, "organization")
+                .resource(organization_id)
+                .metadata("used_bytes", used_bytes)
+                .metadata("limit_bytes", limit_bytes)
+                .metadata("period_start", period.to_string())
+        };
+
+        match step {
+            QuotaStep::Keep => {}
+            QuotaStep::Warn => {
+                event("bandwidth.quota_warning")
+                    .description(&format!(
+                        "Bandwidth usage reached {}% of the monthly limit",
+                        usage_percent(used_bytes, limit_bytes)
+                    ))
+                    .record(db)
+                    .await;
+                sqlx::query(
+                    r#"
+                    INSERT INTO bandwidth_quota_states (organization_id, period_start, warned)
+                    VALUES ($1, $2, TRUE)
+                    ON CONFLICT (organization_id, period_start) DO UPDATE
+                    SET warned = TRUE, updated_at = NOW()
+                    "#,
+                )
+                .bind(organization_id)
+                .bind(period)
+                .execute(db)
+                .await?;
+            }
+            QuotaStep::Exceed(action) => {
+                self.apply(organization_id, action, &policy, period_end)
+                    .await?;
+                event("bandwidth.quota_exceeded")
+                    .description(&format!(
+                        "Monthly bandwidth limit of {} bytes exceeded, backends {}",
+                        limit_bytes,
+                        match action {
+                            QuotaAction::Notify => "left unchanged",
+                            QuotaAction::Throttle => "throttled",
+                            QuotaAction::Suspend => "suspended",
+                        }
+                    ))
+                    .record(db)
+                    .await;
+                set_applied_action(db, organization_id, period, Some(action)).await?;
+                info!(
+                    organization_id = %organization_id,
+                    used_bytes,
+                    limit_bytes,
+                    action = action.as_str(),
+                    "Bandwidth quota exceeded"
+                );
+            }
+            QuotaStep::Restore(action) => {
+                self.lift(organization_id, action, "Back under the bandwidth quota")
+                    .await?;
+                event("bandwidth.quota_restored")
+                    .description("Bandwidth usage back under the monthly limit")
+                    .record(db)
+                    .await;
+                set_applied_action(db, organization_id, period, None).await?;
+                info!(organization_id = %organization_id, "Bandwidth quota restored");
+            }
+        }
+
+        // Keep throttles published for backends added while throttled
+        if step == QuotaStep::Keep && enforcement.applied_action == Some(QuotaAction::Throttle) {
+            self.apply(organization_id, QuotaAction::Throttle, &policy, period_end)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Apply a quota action to the backends of an organization
+    async fn apply(
+        &self,
+        organization_id: &str,
+        action: QuotaAction,
+        policy: &QuotaPolicy,
+        period_end: DateTime<Utc>,
+    ) -> Result<()> {
+        let remaining = (period_end - Utc::now()).num_seconds().max(1) as u64;
+
+        match action {
+            QuotaAction::Notify => {}
+            QuotaAction::Throttle => {
+                let cache = self.cache()?;
+                for backend_id in self.backend_ids(organization_id).await? {
+                    let throttle = BandwidthThrottle {
+                        backend_id: backend_id.clone(),
+                        organization_id: organization_id.to_string(),
+                        bytes_per_second: policy.throttle_bytes_per_second,
+                        until: period_end.timestamp(),
+                    };
+                    let ttl = Duration::from_secs(remaining) + THROTTLE_TTL_MARGIN;
+                    cache
+                        .set(&bandwidth_throttle_key(&backend_id), &throttle, ttl)
+                        .await?;
+                    cache.sadd(THROTTLED_BACKENDS_KEY, &backend_id).await?;
+                }
+            }
+            QuotaAction::Suspend => {
+                let revert_after_seconds = remaining.min(u64::from(MAX_REVERT_SECONDS)) as u32;
+                for backend_id in self.backend_ids(organization_id).await? {
+                    self.modes
+                        .set(
+                            &backend_id,
+                            BackendMode::BlockAll,
+                            revert_after_seconds,
+                            "Monthly bandwidth quota exceeded",
+                            SYSTEM_ACTOR,
+                        )
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lift a quota action from the backends of an organization
+    ///
+    /// Only suspensions still in place are lifted, so a mode an operator
+    /// chose since is left alone.
+    async fn lift(&self, organization_id: &str, action: QuotaAction, reason: &str) -> Result<()> {
+        match action {
+            QuotaAction::Notify => {}
+            QuotaAction::Throttle => {
+                let cache = self.cache()?;
+                for backend_id in self.backend_ids(organization_id).await? {
+                    cache.delete(&bandwidth_throttle_key(&backend_id)).await?;
+                    cache.srem(THROTTLED_BACKENDS_KEY, &backend_id).await?;
+                }
+            }
+            QuotaAction::Suspend => {
+                for backend_id in self.backend_ids(organization_id).await? {
+                    let (mode, _) = self.modes.get(&backend_id, 1).await?;
+                    if mode.mode != BackendMode::BlockAll as i32 || mode.changed_by != SYSTEM_ACTOR
+                    {
+                        continue;
+                    }
+                    self.modes
+                        .set(&backend_id, BackendMode::Protect, 0, reason, SYSTEM_ACTOR)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn backend_ids(&self, organization_id: &str) -> Result<Vec<String>> {
+        let db = self.state.db()?;
+
+        let rows: Vec<(String,)> = sqlx::query_as(BACKEND_IDS_QUERY)
+            .bind(organization_id)
+            .fetch_all(db)
+            .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+}
+
+/// Spawn the monitor enforcing bandwidth quotas
+pub fn spawn_monitor(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() || state.cache.is_none() {
+        info!("Bandwidth quota monitor disabled");
+        return None;
+    }
+
+    let service = BandwidthQuotaService::new(state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = service.enforce().await {
+                        warn!(error = %e, "Failed to enforce bandwidth quotas");
+                    }
+                }
+            }
+        }
+    }))
+}
+
+async fn load_policy(db: &PgPool, organization_id: &str) -> Result<QuotaPolicy> {
+    let row = sqlx::query(
+        r#"
+        SELECT action, throttle_bytes_per_second, warn_percent, updated_by, updated_at
+        FROM bandwidth_quota_policies
+        WHERE organization_id = $1
+        "#,
+    )
+    .bind(organization_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(match row {
+        Some(row) => {
+            let action: String = row.get("action");
+            let throttle_bytes_per_second: i64 = row.get("throttle_bytes_per_second");
+            let warn_percent: i32 = row.get("warn_percent");
+            QuotaPolicy {
+                action: QuotaAction::parse(&action).unwrap_or_default(),
+                throttle_bytes_per_second: throttle_bytes_per_second.max(0) as u64,
+                warn_percent: warn_percent.max(0) as u32,
+                updated_by: row.get("updated_by"),
+                updated_at: Some(row.get("updated_at")),
+            }
+        }
+        None => QuotaPolicy::default(),
+    })
+}
+
+async fn load_enforcement(
+    db: &PgPool,
+    organization_id: &str,
+    period: NaiveDate,
+) -> Result<Enforcement> {
+    let row: Option<(Option<String>, bool)> = sqlx::query_as(
+        r#"
+        SELECT applied_action, warned FROM bandwidth_quota_states
+        WHERE organization_id = $1 AND period_start = $2
+        "#,
+    )
+    .bind(organization_id)
+    .bind(period)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(match row {
+        Some((applied_action, warned)) => Enforcement {
+            applied_action: applied_action.as_deref().and_then(QuotaAction::parse),
+            warned,
+        },
+        None => Enforcement::default(),
+    })
+}
+
+/// Bandwidth of the organization's plan
+async fn load_limit(db: &PgPool, organization_id: &str) -> Result<u64> {
+    let limit: Option<i64> = sqlx::query_scalar(
+        "SELECT max_bandwidth_bytes FROM organization_limits WHERE organization_id = $1",
+    )
+    .bind(organization_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(limit.map_or(DEFAULT_LIMIT_BYTES, |limit| limit.max(0) as u64))
+}
+
+async fn load_usage(
+    db: &PgPool,
+    organization_id: &str,
+    period: NaiveDate,
+) -> Result<Vec<BackendBandwidth>> {
+    let rows = sqlx::query(USAGE_QUERY)
+        .bind(organization_id)
+        .bind(period)
+        .fetch_all(db)
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let bytes_in: i64 = row.get("bytes_in");
+            let bytes_dropped: i64 = row.get("bytes_dropped");
+            BackendBandwidth {
+                backend_id: row.get("backend_id"),
+                bytes_in: bytes_in.max(0) as u64,
+                bytes_dropped: bytes_dropped.max(0) as u64,
+            }
+        })
+        .collect())
+}
+
+async fn set_applied_action(
+    db: &PgPool,
+    organization_id: &str,
+    period: NaiveDate,
+    action: Option<QuotaAction>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO bandwidth_quota_states (organization_id, period_start, applied_action, exceeded_at)
+        VALUES ($1, $2, $3::VARCHAR, CASE WHEN $3::VARCHAR IS NULL THEN NULL ELSE NOW() END)
+        ON CONFLICT (organization_id, period_start) DO UPDATE
+        SET applied_action = EXCLUDED.applied_action,
+            exceeded_at = COALESCE(bandwidth_quota_states.exceeded_at, EXCLUDED.exceeded_at),
+            updated_at = NOW()
+        "#,
+    )
+    .bind(organization_id)
+    .bind(period)
+    .bind(action.map(|action| action.as_str()))
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Traffic of a backend counted towards the quota
+pub fn clean_bytes(usage: &BackendBandwidth) -> u64 {
+    usage.bytes_in.saturating_sub(usage.bytes_dropped)
+}
+
+/// Decide what to do about a quota given the usage of the period and what
+/// was done so far
+pub fn quota_step(
+    used_bytes: u64,
+    limit_bytes: u64,
+    warn_percent: u32,
+    action: QuotaAction,
+    applied_action: Option<QuotaAction>,
+    warned: bool,
+) -> QuotaStep {
+    let exceeded = used_bytes > limit_bytes;
+    match applied_action {
+        Some(_) if exceeded => QuotaStep::Keep,
+        Some(applied) => QuotaStep::Restore(applied),
+        None if exceeded => QuotaStep::Exceed(action),
+        None if !warned
+            && warn_percent > 0
+            && usage_percent(used_bytes, limit_bytes) >= u64::from(warn_percent) =>
+        {
+            QuotaStep::Warn
+        }
+        None => QuotaStep::Keep,
+    }
+}
+
+/// Quota of an organization as returned by the API
+pub fn bandwidth_quota_to_proto(
+    organization_id: &str,
+    policy: &QuotaPolicy,
+    limit_bytes: u64,
+    period: NaiveDate,
+    applied_action: Option<QuotaAction>,
+    backends: Vec<BackendBandwidth>,
+) -> BandwidthQuota {
+    let (period_start, period_end) = period_bounds(period);
+    BandwidthQuota {
+        organization_id: organization_id.to_string(),
+        action: action_to_proto(policy.action) as i32,
+        throttle_bytes_per_second: policy.throttle_bytes_per_second,
+        warn_percent: policy.warn_percent,
+        limit_bytes,
+        used_bytes: backends
+            .iter()
+            .map(clean_bytes)
+            .fold(0, u64::saturating_add),
+        period_start: Some(period_start.into()),
+        period_end: Some(period_end.into()),
+        applied_action: applied_action.map_or(BandwidthQuotaAction::Unspecified, action_to_proto)
+            as i32,
+        backends,
+        updated_by: policy.updated_by.clone(),
+        updated_at: policy.updated_at.map(Into::into),
+    }
+}
+
+/// Quota action for a requested action
+pub fn action_from_proto(action: BandwidthQuotaAction) -> Result<QuotaAction> {
+    match action {
+        BandwidthQuotaAction::Notify => Ok(QuotaAction::Notify),
+        BandwidthQuotaAction::Throttle => Ok(QuotaAction::Throttle),
+        BandwidthQuotaAction::Suspend => Ok(QuotaAction::Suspend),
+        BandwidthQuotaAction::Unspecified => {
+            Err(Error::validation("Bandwidth quota action is required"))
+        }
+    }
+}
+
+pub fn action_to_proto(action: QuotaAction) -> BandwidthQuotaAction {
+    match action {
+        QuotaAction::Notify => BandwidthQuotaAction::Notify,
+        QuotaAction::Throttle => BandwidthQuotaAction::Throttle,
+        QuotaAction::Suspend => BandwidthQuotaAction::Suspend,
+    }
+}
+
+/// Validate the throttle bandwidth, 0 meaning the default
+pub fn validate_throttle_bytes_per_second(bytes_per_second: u64) -> Result<u64> {
+    match bytes_per_second {
+        0 => Ok(DEFAULT_THROTTLE_BYTES_PER_SECOND),
+        b if b < MIN_CONNECTION_BYTES_PER_SECOND => Err(Error::validation(format!(
+            "Throttle bandwidth must be at least {} bytes per second",
+            MIN_CONNECTION_BYTES_PER_SECOND
+        ))),
+        b => Ok(b),
+    }
+}
+
+/// Validate the warning threshold, 0 disabling the warning
+pub fn validate_warn_percent(warn_percent: u32) -> Result<u32> {
+    if warn_percent >= 100 {
+        return Err(Error::validation(
+            "Warning threshold must be below 100 percent",
+        ));
+    }
+    Ok(warn_percent)
+}
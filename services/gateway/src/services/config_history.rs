@@ -0,0 +1,648 @@
+//! Configuration history
+//!
+//! Every change to the protection settings or filter rules of a backend,
+//! blocklists included, is recorded as an immutable revision holding a
+//! snapshot of the whole configuration and its changes from the previous
+//! revision. Revisions are numbered per backend, and a change that leaves
+//! the configuration as it was records nothing.
+//!
+//! A rollback restores the snapshot of an earlier revision. The restored
+//! rules are replayed through the rule compiler first, so a revision the
+//! workers could no longer enforce is refused rather than half applied. The
+//! rollback itself is recorded as a new revision.
+
+use crate::services::AppState;
+use crate::services::backend::BackendService;
+use crate::services::filter::{FilterService, conflict_warning, to_compiler_rule};
+use chrono::{DateTime, Utc};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::pagination::{Field, FieldKind, ListSpec, Page, PageRequest};
+use pistonprotection_proto::backend::{ConfigChange, ConfigChangeKind, ConfigRevision};
+use pistonprotection_proto::filter::{FilterMatch, FilterRule};
+use pistonprotection_rule_compiler as compiler;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgConnection, Row};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tracing::{info, instrument, warn};
+
+/// Sort and filter fields of revision lists
+pub static REVISION_LIST: ListSpec = ListSpec {
+    fields: &[
+        Field::sortable("revision", "revision", FieldKind::Integer),
+        Field::sortable("created_at", "created_at", FieldKind::Timestamp),
+        Field::filterable("changed_by", "changed_by", FieldKind::Text),
+        Field::filterable("summary", "summary", FieldKind::Text),
+    ],
+    id_column: "revision",
+    default_order: "-revision",
+};
+
+/// Longest summary stored
+const MAX_SUMMARY_LEN: usize = 255;
+
+/// Configuration of a backend at a revision
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfigSnapshot {
+    /// Protection settings as stored, unset until first saved
+    #[serde(default)]
+    pub protection: Option<Value>,
+    /// Filter rules by ID
+    #[serde(default)]
+    pub rules: BTreeMap<String, RuleSnapshot>,
+}
+
+/// Filter rule as stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSnapshot {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub priority: i32,
+    pub match_criteria: Value,
+    pub action: i32,
+    #[serde(default)]
+    pub rate_limit: Option<Value>,
+    pub enabled: bool,
+}
+
+impl RuleSnapshot {
+    /// The rule, as the filter service returns it
+    pub fn to_rule(&self, id: &str) -> Result<FilterRule> {
+        let invalid = |what: &str, e: serde_json::Error| {
+            Error::validation(format!(
+                "Rule '{}' has an invalid {}: {}",
+                self.name, what, e
+            ))
+        };
+        let filter_match: Option<FilterMatch> =
+            serde_json::from_value(self.match_criteria.clone()).map_err(|e| invalid("match", e))?;
+        let rate_limit = match &self.rate_limit {
+            Some(rate_limit) => {
+                serde_json::from_value(rate_limit.clone()).map_err(|e| invalid("rate limit", e))?
+            }
+            None => None,
+        };
+
+        Ok(FilterRule {
+            id: id.to_string(),
+            name: self.name.clone(),
+            description: self.description.clone().unwrap_or_default(),
+            priority: self.priority.max(0) as u32,
+            r#match: filter_match,
+            action: self.action,
+            rate_limit,
+            enabled: self.enabled,
+            ..Default::default()
+        })
+    }
+}
+
+/// Kind of a change between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Value changed between two snapshots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    /// Dotted path of the value, e.g. `rules.<id>.action`
+    pub path: String,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+/// Changes turning one snapshot into another
+///
+/// Objects are compared key by key, so a rule edit shows up as the fields
+/// that changed; arrays and scalars are compared as a whole.
+pub fn diff(before: &ConfigSnapshot, after: &ConfigSnapshot) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let to_value =
+        |snapshot: &ConfigSnapshot| serde_json::to_value(snapshot).unwrap_or(Value::Null);
+    diff_values("", &to_value(before), &to_value(after), &mut changes);
+    changes
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, changes: &mut Vec<Change>) {
+    let change = |kind, before: &Value, after: &Value| Change {
+        path: path.to_string(),
+        kind,
+        before: Some(before.clone()).filter(|v| !v.is_null()),
+        after: Some(after.clone()).filter(|v| !v.is_null()),
+    };
+
+    match (before, after) {
+        (Value::Object(old), Value::Object(new)) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let path = match path {
+                    "" => key.clone(),
+                    path => format!("{}.{}", path, key),
+                };
+                let null = Value::Null;
+                diff_values(
+                    &path,
+                    old.get(key).unwrap_or(&null),
+                    new.get(key).unwrap_or(&null),
+                    changes,
+                );
+            }
+        }
+        _ if before == after => {}
+        (Value::Null, _) => changes.push(change(ChangeKind::Added, before, after)),
+        (_, Value::Null) => changes.push(change(ChangeKind::Removed, before, after)),
+        _ => changes.push(change(ChangeKind::Modified, before, after)),
+    }
+}
+
+/// Replay the rule compiler on the rules of a snapshot
+///
+/// Returns the conflicts between the rules as warnings, and fails if the
+/// rules do not compile.
+pub fn replay(snapshot: &ConfigSnapshot) -> Result<Vec<String>> {
+    let rules: Vec<FilterRule> = snapshot
+        .rules
+        .iter()
+        .map(|(id, rule)| rule.to_rule(id))
+        .collect::<Result<_>>()?;
+    let converted: Vec<compiler::Rule> = rules
+        .iter()
+        .filter(|rule| rule.enabled)
+        .map(|rule| {
+            to_compiler_rule(rule)
+                .map_err(|e| Error::validation(format!("Rule '{}': {}", rule.name, e)))
+        })
+        .collect::<Result<_>>()?;
+    let compiled = compiler::compile(&converted)
+        .map_err(|e| Error::validation(format!("Rules do not compile: {}", e)))?;
+
+    let names: HashMap<&str, &str> = rules
+        .iter()
+        .map(|rule| (rule.id.as_str(), rule.name.as_str()))
+        .collect();
+    Ok(compiled
+        .conflicts()
+        .iter()
+        .map(|conflict| conflict_warning(conflict, &names))
+        .collect())
+}
+
+/// Change in its API form
+pub fn change_to_proto(change: &Change) -> ConfigChange {
+    let json = |value: &Option<Value>| value.as_ref().map(Value::to_string).unwrap_or_default();
+    ConfigChange {
+        path: change.path.clone(),
+        kind: match change.kind {
+            ChangeKind::Added => ConfigChangeKind::Added,
+            ChangeKind::Removed => ConfigChangeKind::Removed,
+            ChangeKind::Modified => ConfigChangeKind::Modified,
+        } as i32,
+        before: json(&change.before),
+        after: json(&change.after),
+    }
+}
+
+/// Configuration history service implementation
+pub struct ConfigHistoryService {
+    state: AppState,
+}
+
+impl ConfigHistoryService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Record the current configuration of a backend as a new revision
+    ///
+    /// Returns `None` when the configuration did not change since the last
+    /// revision.
+    #[instrument(skip(self))]
+    pub async fn record(
+        &self,
+        backend_id: &str,
+        summary: &str,
+        changed_by: &str,
+    ) -> Result<Option<ConfigRevision>> {
+        let db = self.state.db()?;
+        let mut tx = db.begin().await?;
+
+        lock_backend(&mut tx, backend_id).await?;
+        let snapshot = load_snapshot(&mut tx, backend_id).await?;
+        let revision =
+            insert_revision(&mut tx, backend_id, &snapshot, summary, changed_by, None).await?;
+        tx.commit().await?;
+
+        if let Some(revision) = &revision {
+            info!(
+                backend_id = %backend_id,
+                revision = revision.revision,
+                changes = revision.change_count,
+                "Recorded config revision"
+            );
+        }
+        Ok(revision)
+    }
+
+    /// Record a revision after a change, logging failures
+    ///
+    /// The change is applied by then, so failing the request over its
+    /// history would only make the caller retry it.
+    pub async fn record_change(&self, backend_id: &str, summary: &str, changed_by: &str) {
+        if let Err(e) = self.record(backend_id, summary, changed_by).await {
+            warn!(backend_id = %backend_id, error = %e, "Failed to record config revision");
+        }
+    }
+
+    /// Revisions of a backend, without their changes
+    #[instrument(skip(self))]
+    pub async fn list(
+        &self,
+        backend_id: &str,
+        request: &PageRequest,
+    ) -> Result<(Page<ConfigRevision>, u64)> {
+        let db = self.state.db_read()?;
+
+        let mut count =
+            sqlx::QueryBuilder::new("SELECT COUNT(*) FROM config_revisions WHERE backend_id = ");
+        count.push_bind(backend_id);
+        request.push_filter(&mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(db).await?;
+
+        let mut query = sqlx::QueryBuilder::new(format!(
+            "SELECT backend_id, revision, summary, changed_by, restored_from, created_at, \
+                    jsonb_array_length(changes) AS change_count, {} \
+             FROM config_revisions WHERE backend_id = ",
+            request.select_key()
+        ));
+        query.push_bind(backend_id);
+        request.push_filter(&mut query);
+        request.push_page(&mut query);
+        let rows = query.build().fetch_all(db).await?;
+
+        let page = request.finish_rows(rows)?.try_map(|row| {
+            let change_count: i32 = row.get("change_count");
+            Ok::<_, Error>(ConfigRevision {
+                change_count: change_count.max(0) as u32,
+                ..row_to_revision(&row)
+            })
+        })?;
+        Ok((page, total as u64))
+    }
+
+    /// A revision with its snapshot and changes
+    ///
+    /// Changes are from `compare_to` when set, from the previous revision
+    /// otherwise.
+    #[instrument(skip(self))]
+    pub async fn get(
+        &self,
+        backend_id: &str,
+        revision: u64,
+        compare_to: Option<u64>,
+    ) -> Result<(ConfigRevision, ConfigSnapshot)> {
+        let db = self.state.db_read()?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT backend_id, revision, summary, changed_by, restored_from, created_at,
+                   snapshot, changes
+            FROM config_revisions
+            WHERE backend_id = $1 AND revision = $2
+            "#,
+        )
+        .bind(backend_id)
+        .bind(revision as i64)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| Error::not_found("ConfigRevision", revision.to_string()))?;
+        let snapshot = parse_snapshot(row.get("snapshot"))?;
+
+        let changes = match compare_to {
+            Some(other) => {
+                let other = self.snapshot(backend_id, other).await?;
+                diff(&other, &snapshot)
+            }
+            None => serde_json::from_value(row.get("changes"))
+                .map_err(|e| Error::Internal(format!("Failed to parse changes: {}", e)))?,
+        };
+
+        let changes: Vec<ConfigChange> = changes.iter().map(change_to_proto).collect();
+        let revision = ConfigRevision {
+            change_count: changes.len() as u32,
+            changes,
+            ..row_to_revision(&row)
+        };
+        Ok((revision, snapshot))
+    }
+
+    /// Restore the configuration of an earlier revision
+    ///
+    /// Returns the revision recording the rollback, the latest revision if
+    /// the configuration already matches, and the conflicts between the
+    /// restored rules.
+    #[instrument(skip(self))]
+    pub async fn rollback(
+        &self,
+        backend_id: &str,
+        revision: u64,
+        changed_by: &str,
+    ) -> Result<(ConfigRevision, Vec<String>)> {
+        let target = self.snapshot(backend_id, revision).await?;
+        let warnings = replay(&target)?;
+
+        let db = self.state.db()?;
+        let mut tx = db.begin().await?;
+        lock_backend(&mut tx, backend_id).await?;
+        restore_snapshot(&mut tx, backend_id, &target).await?;
+        let recorded = insert_revision(
+            &mut tx,
+            backend_id,
+            &target,
+            &format!("rolled back to revision {}", revision),
+            changed_by,
+            Some(revision),
+        )
+        .await?;
+        tx.commit().await?;
+
+        let recorded = match recorded {
+            Some(recorded) => recorded,
+            None => {
+                return Ok((self.latest(backend_id).await?, warnings));
+            }
+        };
+        info!(
+            backend_id = %backend_id,
+            restored = revision,
+            revision = recorded.revision,
+            "Rolled back config"
+        );
+
+        // Workers reload rules and settings on these events
+        FilterService::new(self.state.clone())
+            .invalidate_cache(backend_id)
+            .await;
+        let backends = BackendService::new(self.state.clone());
+        backends.invalidate_backend_cache(backend_id).await;
+        backends
+            .publish_backend_update(backend_id, "protection_updated")
+            .await;
+
+        Ok((recorded, warnings))
+    }
+
+    /// Snapshot of a revision
+    async fn snapshot(&self, backend_id: &str, revision: u64) -> Result<ConfigSnapshot> {
+        let db = self.state.db_read()?;
+        let snapshot: Value = sqlx::query_scalar(
+            "SELECT snapshot FROM config_revisions WHERE backend_id = $1 AND revision = $2",
+        )
+        .bind(backend_id)
+        .bind(revision as i64)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| Error::not_found("ConfigRevision", revision.to_string()))?;
+        parse_snapshot(snapshot)
+    }
+
+    /// Latest revision of a backend
+    async fn latest(&self, backend_id: &str) -> Result<ConfigRevision> {
+        let db = self.state.db()?;
+        let row = sqlx::query(
+            r#"
+            SELECT backend_id, revision, summary, changed_by, restored_from, created_at
+            FROM config_revisions
+            WHERE backend_id = $1
+            ORDER BY revision DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| Error::not_found("ConfigRevision", backend_id))?;
+        Ok(row_to_revision(&row))
+    }
+}
+
+/// Lock the backend row, serializing revisions of the backend
+async fn lock_backend(conn: &mut PgConnection, backend_id: &str) -> Result<()> {
+    sqlx::query("SELECT id FROM backends WHERE id = $1 FOR UPDATE")
+        .bind(backend_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| Error::not_found("Backend", backend_id))?;
+    Ok(())
+}
+
+/// Current configuration of a backend
+async fn load_snapshot(conn: &mut PgConnection, backend_id: &str) -> Result<ConfigSnapshot> {
+    let protection: Option<Value> =
+        sqlx::query_scalar("SELECT settings FROM backend_protection WHERE backend_id = $1")
+            .bind(backend_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT id, name, description, priority, match_criteria, action, rate_limit, enabled
+        FROM filter_rules
+        WHERE backend_id = $1
+        "#,
+    )
+    .bind(backend_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let rules = rows
+        .iter()
+        .map(|row| {
+            let rule = RuleSnapshot {
+                name: row.get("name"),
+                description: row.get("description"),
+                priority: row.get::<Option<i32>, _>("priority").unwrap_or(0),
+                match_criteria: row.get("match_criteria"),
+                action: row.get("action"),
+                rate_limit: row
+                    .get::<Option<Value>, _>("rate_limit")
+                    .filter(|v| !v.is_null()),
+                enabled: row.get::<Option<bool>, _>("enabled").unwrap_or(true),
+            };
+            (row.get("id"), rule)
+        })
+        .collect();
+
+    Ok(ConfigSnapshot { protection, rules })
+}
+
+/// Write a snapshot back as the configuration of a backend
+///
+/// Rules of the snapshot are upserted under their IDs, keeping the
+/// statistics of rules that still exist; rules missing from it are deleted.
+async fn restore_snapshot(
+    conn: &mut PgConnection,
+    backend_id: &str,
+    snapshot: &ConfigSnapshot,
+) -> Result<()> {
+    match &snapshot.protection {
+        Some(settings) => {
+            sqlx::query(
+                r#"
+                INSERT INTO backend_protection (backend_id, settings, updated_at)
+                VALUES ($1, $2, NOW())
+                ON CONFLICT (backend_id)
+                DO UPDATE SET settings = $2, updated_at = NOW()
+                "#,
+            )
+            .bind(backend_id)
+            .bind(settings)
+            .execute(&mut *conn)
+            .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM backend_protection WHERE backend_id = $1")
+                .bind(backend_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    let ids: Vec<String> = snapshot.rules.keys().cloned().collect();
+    sqlx::query("DELETE FROM filter_rules WHERE backend_id = $1 AND NOT (id = ANY($2))")
+        .bind(backend_id)
+        .bind(&ids)
+        .execute(&mut *conn)
+        .await?;
+
+    for (id, rule) in &snapshot.rules {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO filter_rules (
+                id, backend_id, name, description, priority,
+                match_criteria, action, rate_limit, enabled, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW(), NOW())
+            ON CONFLICT (id) DO UPDATE
+            SET name = $3, description = $4, priority = $5, match_criteria = $6,
+                action = $7, rate_limit = $8, enabled = $9, updated_at = NOW()
+            WHERE filter_rules.backend_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(backend_id)
+        .bind(&rule.name)
+        .bind(&rule.description)
+        .bind(rule.priority)
+        .bind(&rule.match_criteria)
+        .bind(rule.action)
+        // The filter service reads rules without a rate limit as JSON null
+        .bind(rule.rate_limit.clone().unwrap_or(Value::Null))
+        .bind(rule.enabled)
+        .execute(&mut *conn)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::validation(format!(
+                "Rule {} now belongs to another backend",
+                id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Append a revision unless the snapshot matches the latest one
+async fn insert_revision(
+    conn: &mut PgConnection,
+    backend_id: &str,
+    snapshot: &ConfigSnapshot,
+    summary: &str,
+    changed_by: &str,
+    restored_from: Option<u64>,
+) -> Result<Option<ConfigRevision>> {
+    let latest: Option<(i64, Value)> = sqlx::query_as(
+        r#"
+        SELECT revision, snapshot FROM config_revisions
+        WHERE backend_id = $1
+        ORDER BY revision DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(backend_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let (number, previous) = match latest {
+        Some((number, previous)) => (number + 1, parse_snapshot(previous)?),
+        None => (1, ConfigSnapshot::default()),
+    };
+    let changes = diff(&previous, snapshot);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+
+    let summary: String = summary.chars().take(MAX_SUMMARY_LEN).collect();
+    let snapshot_json = serde_json::to_value(snapshot)
+        .map_err(|e| Error::Internal(format!("Failed to serialize snapshot: {}", e)))?;
+    let changes_json = serde_json::to_value(&changes)
+        .map_err(|e| Error::Internal(format!("Failed to serialize changes: {}", e)))?;
+
+    let created_at: DateTime<Utc> = sqlx::query_scalar(
+        r#"
+        INSERT INTO config_revisions (
+            backend_id, revision, snapshot, changes, summary, changed_by, restored_from
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING created_at
+        "#,
+    )
+    .bind(backend_id)
+    .bind(number)
+    .bind(&snapshot_json)
+    .bind(&changes_json)
+    .bind(&summary)
+    .bind(changed_by)
+    .bind(restored_from.map(|r| r as i64))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(Some(ConfigRevision {
+        backend_id: backend_id.to_string(),
+        revision: number as u64,
+        summary,
+        changed_by: changed_by.to_string(),
+        created_at: Some(created_at.into()),
+        restored_from: restored_from.unwrap_or(0),
+        change_count: changes.len() as u32,
+        changes: changes.iter().map(change_to_proto).collect(),
+    }))
+}
+
+fn parse_snapshot(snapshot: Value) -> Result<ConfigSnapshot> {
+    serde_json::from_value(snapshot)
+        .map_err(|e| Error::Internal(format!("Failed to parse config snapshot: {}", e)))
+}
+
+fn row_to_revision(row: &sqlx::postgres::PgRow) -> ConfigRevision {
+    ConfigRevision {
+        backend_id: row.get("backend_id"),
+        revision: row.get::<i64, _>("revision") as u64,
+        summary: row.get("summary"),
+        changed_by: row.get("changed_by"),
+        created_at: Some(row.get::<DateTime<Utc>, _>("created_at").into()),
+        restored_from: row
+            .get::<Option<i64>, _>("restored_from")
+            .unwrap_or(0)
+            .max(0) as u64,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,395 @@
+//! Load test exemption tokens
+//!
+//! Ahead of a load test, a customer creates an exemption token listing the
+//! addresses of their testers: workers whitelist them in the layer 4 XDP
+//! programs and keep the backend's traffic out of the attack detection
+//! baselines, so the test neither gets dropped nor teaches the detector a
+//! bogus normal. Tokens expire on their own; the monitor removes expired
+//! ones, and workers stop applying them once expired even while the gateway
+//! is unreachable (see `pistonprotection_common::exemption`). Creation,
+//! revocation and expiry are written to an audit log.
+
+use crate::services::AppState;
+use crate::services::backend::BackendService;
+use crate::services::backend_mode::{SYSTEM_ACTOR, history_limit_or_default, validate_reason};
+use chrono::{DateTime, Utc};
+use pistonprotection_common::duration::bounded_duration;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::exemption::{
+    ACTIVE_EXEMPTIONS_KEY, EXEMPTION_TTL_MARGIN, ExemptionToken as Token, exemption_token_key,
+};
+use pistonprotection_common::redis::CacheService;
+use pistonprotection_proto::backend::{ExemptionToken, ExemptionTokenEvent};
+use sqlx::{PgConnection, Row};
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+/// Length of a token when the request leaves it unset
+pub const DEFAULT_DURATION_MINUTES: u32 = 60;
+
+/// Longest token
+pub const MAX_DURATION_MINUTES: u32 = 24 * 60;
+
+/// Most addresses of a token
+pub const MAX_TOKEN_IPS: usize = 256;
+
+/// Audit log action of a new token
+pub const ACTION_CREATED: &str = "created";
+
+/// Audit log action of a token revoked by hand
+pub const ACTION_REVOKED: &str = "revoked";
+
+/// Audit log action of a token removed by the monitor
+pub const ACTION_EXPIRED: &str = "expired";
+
+/// How often the monitor removes expired tokens
+const MONITOR_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Exemption token service implementation
+pub struct ExemptionService {
+    state: AppState,
+    backends: BackendService,
+}
+
+impl ExemptionService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            backends: BackendService::new(state.clone()),
+            state,
+        }
+    }
+
+    fn cache(&self) -> Result<&CacheService> {
+        self.state
+            .cache
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Exemption tokens require Redis".to_string()))
+    }
+
+    /// Exempt addresses of a backend for `duration_minutes`
+    #[instrument(skip(self, ips, reason))]
+    pub async fn create(
+        &self,
+        backend_id: &str,
+        ips: &[String],
+        duration_minutes: u32,
+        reason: &str,
+        created_by: &str,
+    ) -> Result<ExemptionToken> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+        let ips = validate_ips(ips)?;
+        let duration_minutes = bounded_duration(
+            duration_minutes,
+            DEFAULT_DURATION_MINUTES,
+            MAX_DURATION_MINUTES,
+        )
+        .ok_or_else(|| {
+            Error::validation(format!(
+                "Exemption token cannot last more than {} minutes",
+                MAX_DURATION_MINUTES
+            ))
+        })?;
+        let reason = validate_reason(reason)?;
+        self.backends.get(backend_id).await?;
+
+        let created_at = Utc::now();
+        let expires_at = created_at + chrono::Duration::minutes(duration_minutes as i64);
+        let token = Token {
+            id: Uuid::new_v4().to_string(),
+            backend_id: backend_id.to_string(),
+            ips,
+            reason,
+            created_by: created_by.to_string(),
+            created_at: created_at.timestamp(),
+            expires_at: expires_at.timestamp(),
+        };
+        let ips_json = serde_json::to_value(&token.ips)
+            .map_err(|e| Error::Internal(format!("Failed to serialize addresses: {}", e)))?;
+
+        let mut tx = db.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO exemption_tokens (
+                id, backend_id, ips, reason, created_by, created_at, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(&token.id)
+        .bind(backend_id)
+        .bind(ips_json)
+        .bind(&token.reason)
+        .bind(created_by)
+        .bind(created_at)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+        record_event(&mut *tx, &token.id, backend_id, ACTION_CREATED, created_by).await?;
+        tx.commit().await?;
+
+        let ttl = Duration::from_secs(duration_minutes as u64 * 60) + EXEMPTION_TTL_MARGIN;
+        cache
+            .set(&exemption_token_key(&token.id), &token, ttl)
+            .await?;
+        cache.sadd(ACTIVE_EXEMPTIONS_KEY, &token.id).await?;
+
+        info!(
+            backend_id = %backend_id,
+            token_id = %token.id,
+            ips = token.ips.len(),
+            duration_minutes,
+            created_by = %created_by,
+            "Created exemption token"
+        );
+
+        Ok(token_to_proto(&token, token.created_at))
+    }
+
+    /// Revoke an exemption token before it expires
+    #[instrument(skip(self))]
+    pub async fn revoke(&self, token_id: &str, revoked_by: &str) -> Result<ExemptionToken> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+
+        let mut tx = db.begin().await?;
+        let row = sqlx::query(
+            r#"
+            DELETE FROM exemption_tokens
+            WHERE id = $1
+            RETURNING id, backend_id, ips, reason, created_by, created_at, expires_at
+            "#,
+        )
+        .bind(token_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| Error::not_found("Exemption token", token_id))?;
+        let token = token_from_row(&row)?;
+        record_event(
+            &mut *tx,
+            token_id,
+            &token.backend_id,
+            ACTION_REVOKED,
+            revoked_by,
+        )
+        .await?;
+        tx.commit().await?;
+
+        cache.delete(&exemption_token_key(token_id)).await?;
+        cache.srem(ACTIVE_EXEMPTIONS_KEY, token_id).await?;
+
+        info!(
+            backend_id = %token.backend_id,
+            token_id = %token_id,
+            revoked_by = %revoked_by,
+            "Revoked exemption token"
+        );
+
+        // Revoked tokens are reported as inactive
+        Ok(token_to_proto(&token, token.expires_at))
+    }
+
+    /// Active tokens of a backend and its latest token events
+    #[instrument(skip(self))]
+    pub async fn list(
+        &self,
+        backend_id: &str,
+        history_limit: u32,
+    ) -> Result<(Vec<ExemptionToken>, Vec<ExemptionTokenEvent>)> {
+        let db = self.state.db()?;
+        self.backends.get(backend_id).await?;
+
+        let now = Utc::now().timestamp();
+        let tokens = sqlx::query(
+            r#"
+            SELECT id, backend_id, ips, reason, created_by, created_at, expires_at
+            FROM exemption_tokens
+            WHERE backend_id = $1 AND expires_at > NOW()
+            ORDER BY expires_at
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| token_from_row(row).map(|token| token_to_proto(&token, now)))
+        .collect::<Result<Vec<_>>>()?;
+
+        let history = sqlx::query(
+            r#"
+            SELECT id, token_id, backend_id, action, actor, created_at
+            FROM exemption_token_audit
+            WHERE backend_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(backend_id)
+        .bind(history_limit_or_default(history_limit) as i64)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| {
+            let created_at: DateTime<Utc> = row.get("created_at");
+            ExemptionTokenEvent {
+                id: row.get("id"),
+                token_id: row.get("token_id"),
+                backend_id: row.get("backend_id"),
+                action: row.get("action"),
+                actor: row.get("actor"),
+                created_at: Some(created_at.into()),
+            }
+        })
+        .collect();
+
+        Ok((tokens, history))
+    }
+
+    /// Remove expired tokens, giving their IDs
+    #[instrument(skip(self))]
+    pub async fn expire(&self) -> Result<Vec<String>> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+
+        let mut tx = db.begin().await?;
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "DELETE FROM exemption_tokens WHERE expires_at <= NOW() RETURNING id, backend_id",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for (token_id, backend_id) in &rows {
+            record_event(&mut *tx, token_id, backend_id, ACTION_EXPIRED, SYSTEM_ACTOR).await?;
+        }
+        tx.commit().await?;
+
+        let mut token_ids = Vec::with_capacity(rows.len());
+        for (token_id, backend_id) in rows {
+            cache.delete(&exemption_token_key(&token_id)).await?;
+            cache.srem(ACTIVE_EXEMPTIONS_KEY, &token_id).await?;
+            info!(backend_id = %backend_id, token_id = %token_id, "Exemption token expired");
+            token_ids.push(token_id);
+        }
+        Ok(token_ids)
+    }
+}
+
+/// Spawn the monitor removing expired exemption tokens
+pub fn spawn_monitor(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() || state.cache.is_none() {
+        info!("Exemption token monitor disabled");
+        return None;
+    }
+
+    let service = ExemptionService::new(state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = service.expire().await {
+                        warn!(error = %e, "Failed to remove expired exemption tokens");
+                    }
+                }
+            }
+        }
+    }))
+}
+
+async fn record_event(
+    conn: &mut PgConnection,
+    token_id: &str,
+    backend_id: &str,
+    action: &str,
+    actor: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO exemption_token_audit (id, token_id, backend_id, action, actor)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(token_id)
+    .bind(backend_id)
+    .bind(action)
+    .bind(actor)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+fn token_from_row(row: &sqlx::postgres::PgRow) -> Result<Token> {
+    let ips: Vec<IpAddr> = serde_json::from_value(row.get("ips"))
+        .map_err(|e| Error::Internal(format!("Invalid stored addresses: {}", e)))?;
+    let created_at: DateTime<Utc> = row.get("created_at");
+    let expires_at: DateTime<Utc> = row.get("expires_at");
+
+    Ok(Token {
+        id: row.get("id"),
+        backend_id: row.get("backend_id"),
+        ips,
+        reason: row.get("reason"),
+        created_by: row.get("created_by"),
+        created_at: created_at.timestamp(),
+        expires_at: expires_at.timestamp(),
+    })
+}
+
+/// Exemption token as returned by the API at `now` (Unix seconds); an
+/// expired token is reported as inactive
+pub fn token_to_proto(token: &Token, now: i64) -> ExemptionToken {
+    ExemptionToken {
+        id: token.id.clone(),
+        backend_id: token.backend_id.clone(),
+        active: !token.expired(now),
+        ips: token.ips.iter().map(ToString::to_string).collect(),
+        reason: token.reason.clone(),
+        created_by: token.created_by.clone(),
+        created_at: DateTime::from_timestamp(token.created_at, 0).map(Into::into),
+        expires_at: DateTime::from_timestamp(token.expires_at, 0).map(Into::into),
+    }
+}
+
+/// Validate the addresses of a token, dropping duplicates
+///
+/// Only single unicast addresses can be exempted, so a token never opens
+/// filtering to a whole network.
+pub fn validate_ips(ips: &[String]) -> Result<Vec<IpAddr>> {
+    if ips.is_empty() {
+        return Err(Error::validation("At least one address is required"));
+    }
+
+    let mut parsed: Vec<IpAddr> = Vec::with_capacity(ips.len());
+    for ip in ips {
+        let addr: IpAddr = ip
+            .trim()
+            .parse()
+            .map_err(|_| Error::validation(format!("Invalid address: {}", ip)))?;
+        if addr.is_unspecified() || addr.is_multicast() || addr.is_loopback() {
+            return Err(Error::validation(format!(
+                "Address {} cannot be exempted",
+                addr
+            )));
+        }
+        if !parsed.contains(&addr) {
+            parsed.push(addr);
+        }
+    }
+
+    if parsed.len() > MAX_TOKEN_IPS {
+        return Err(Error::validation(format!(
+            "Exemption token cannot hold more than {} addresses",
+            MAX_TOKEN_IPS
+        )));
+    }
+    Ok(parsed)
+}
@@ -0,0 +1,624 @@
+//! Origin exposure scanner
+//!
+//! Protection is pointless once attackers know the origin address, so the
+//! scanner periodically looks for it the way they would:
+//!
+//! - DNS records of the backend domains and common subdomains (`direct.`,
+//!   `ftp.`, `mail.`, ...) left pointing at the origin
+//! - Names and IP addresses logged in certificate transparency (crt.sh)
+//! - HTTP response headers of the domains leaking the origin address
+//!
+//! The latest report of each backend is stored in `backend_exposure`. New
+//! exposures are published on `backend_updates` and evaluated against the
+//! backend alerts watching the `origin_exposures` metric.
+
+use crate::services::AppState;
+use crate::services::backend::BackendService;
+use crate::services::metrics::MetricsService;
+use futures::StreamExt;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::backend::{ExposureFinding, ExposureSource, OriginExposureReport};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+
+/// Alert metric holding the number of findings of the latest scan
+pub const ORIGIN_EXPOSURE_METRIC: &str = "origin_exposures";
+
+/// Subdomains commonly left pointing at the origin
+pub const COMMON_SUBDOMAINS: &[&str] = &[
+    "direct", "origin", "ftp", "mail", "smtp", "cpanel", "webmail", "panel", "dev", "staging",
+    "test", "old", "backend", "server", "node", "mc", "play",
+];
+
+/// Time allowed for each HTTP request
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// DNS lookups running at once during a scan
+const CONCURRENT_LOOKUPS: usize = 16;
+
+/// Backends scanned per scheduler tick
+const SCANS_PER_TICK: i64 = 10;
+
+/// How often the scheduler looks for backends due a scan
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+
+/// Scanner settings
+#[derive(Debug, Clone)]
+pub struct ExposureConfig {
+    /// Time between scans of a backend, zero disables scheduled scans
+    pub scan_interval: Duration,
+    /// Certificate transparency search endpoint (crt.sh compatible)
+    pub ct_url: String,
+    /// Maximum names resolved per scan
+    pub max_names: usize,
+}
+
+impl Default for ExposureConfig {
+    fn default() -> Self {
+        Self {
+            scan_interval: Duration::from_secs(6 * 3600),
+            ct_url: "https://crt.sh".to_string(),
+            max_names: 200,
+        }
+    }
+}
+
+impl ExposureConfig {
+    /// Load settings from `PISTON_EXPOSURE_*` environment variables
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str| std::env::var(name).ok();
+
+        Self {
+            scan_interval: var("PISTON_EXPOSURE_SCAN_INTERVAL_SECS")
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.scan_interval),
+            ct_url: var("PISTON_EXPOSURE_CT_URL").unwrap_or(defaults.ct_url),
+            max_names: var("PISTON_EXPOSURE_MAX_NAMES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_names),
+        }
+    }
+}
+
+/// Origin exposure service implementation
+pub struct ExposureService {
+    state: AppState,
+    config: ExposureConfig,
+    http: reqwest::Client,
+}
+
+impl ExposureService {
+    pub fn new(state: AppState, config: ExposureConfig) -> Self {
+        // Certificates are not checked: a domain answering straight from the
+        // origin with a self-signed certificate is exactly what we look for
+        let http = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .danger_accept_invalid_certs(true)
+            .user_agent(concat!(
+                "PistonProtection-ExposureScanner/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            state,
+            config,
+            http,
+        }
+    }
+
+    /// Get the latest exposure report of a backend, if it was scanned
+    pub async fn get_report(&self, backend_id: &str) -> Result<Option<OriginExposureReport>> {
+        let db = self.state.db()?;
+
+        let row: Option<(serde_json::Value,)> =
+            sqlx::query_as("SELECT report FROM backend_exposure WHERE backend_id = $1")
+                .bind(backend_id)
+                .fetch_optional(db)
+                .await?;
+
+        row.map(|(json,)| {
+            serde_json::from_value(json).map_err(|e| {
+                Error::Internal(format!("Failed to deserialize exposure report: {}", e))
+            })
+        })
+        .transpose()
+    }
+
+    /// Scan a backend for exposed origin addresses and store the report
+    #[instrument(skip(self))]
+    pub async fn scan(&self, backend_id: &str) -> Result<OriginExposureReport> {
+        let db = self.state.db()?;
+        let backends = BackendService::new(self.state.clone());
+
+        // Fails with not found for unknown backends
+        backends.get(backend_id).await?;
+        let previous = self.get_report(backend_id).await?;
+
+        let (origin_ips, origin_hosts) = self.origin_addresses(&backends, backend_id).await?;
+        let mut domains = backends.get_domains(backend_id).await?;
+        if let Some(onboarding) = backends.get_onboarding(backend_id).await? {
+            domains.push(onboarding.hostname);
+        }
+        domains.sort();
+        domains.dedup();
+
+        let mut report = OriginExposureReport::default();
+        if origin_ips.is_empty() && origin_hosts.is_empty() {
+            report
+                .errors
+                .push("Backend has no origin addresses to look for".to_string());
+        } else if domains.is_empty() {
+            report
+                .errors
+                .push("Backend has no verified domains to scan".to_string());
+        } else {
+            self.run_checks(&domains, &origin_ips, &origin_hosts, &mut report)
+                .await;
+        }
+        report.exposed = !report.findings.is_empty();
+        report.scanned_at = Some(chrono::Utc::now().into());
+
+        let report_json = serde_json::to_value(&report)
+            .map_err(|e| Error::Internal(format!("Failed to serialize exposure report: {}", e)))?;
+        sqlx::query(
+            r#"
+            INSERT INTO backend_exposure (backend_id, exposed, report, scanned_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (backend_id)
+            DO UPDATE SET exposed = $2, report = $3, scanned_at = NOW()
+            "#,
+        )
+        .bind(backend_id)
+        .bind(report.exposed)
+        .bind(&report_json)
+        .execute(db)
+        .await?;
+
+        self.report_findings(backend_id, previous.as_ref(), &report)
+            .await;
+
+        info!(
+            backend_id = %backend_id,
+            exposed = report.exposed,
+            findings = report.findings.len(),
+            names_checked = report.names_checked,
+            "Scanned origin exposure"
+        );
+
+        Ok(report)
+    }
+
+    /// Origin IP addresses, and the hostnames origins are configured with
+    async fn origin_addresses(
+        &self,
+        backends: &BackendService,
+        backend_id: &str,
+    ) -> Result<(Vec<IpAddr>, Vec<String>)> {
+        let mut ips = Vec::new();
+        let mut hosts = Vec::new();
+
+        for origin in backends.get_origins(backend_id).await? {
+            if let Some(ip) = origin
+                .address
+                .as_ref()
+                .and_then(|addr| IpAddr::try_from(addr).ok())
+            {
+                ips.push(ip);
+            }
+            if !origin.hostname.is_empty() {
+                hosts.push(origin.hostname.to_ascii_lowercase());
+            }
+        }
+        if let Some(onboarding) = backends.get_onboarding(backend_id).await? {
+            ips.extend(
+                onboarding
+                    .addresses
+                    .iter()
+                    .filter_map(|a| a.parse::<IpAddr>().ok()),
+            );
+            if !onboarding.target.is_empty() && onboarding.target != onboarding.hostname {
+                hosts.push(onboarding.target.to_ascii_lowercase());
+            }
+        }
+
+        ips.sort();
+        ips.dedup();
+        hosts.sort();
+        hosts.dedup();
+        Ok((ips, hosts))
+    }
+
+    async fn run_checks(
+        &self,
+        domains: &[String],
+        origin_ips: &[IpAddr],
+        origin_hosts: &[String],
+        report: &mut OriginExposureReport,
+    ) {
+        let resolver = match hickory_resolver::TokioResolver::builder_tokio() {
+            Ok(builder) => builder.build(),
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("Failed to create DNS resolver: {}", e));
+                return;
+            }
+        };
+
+        // Origins configured by hostname are looked for by address too
+        let mut origin_ips = origin_ips.to_vec();
+        for host in origin_hosts {
+            if let Ok(lookup) = resolver.lookup_ip(host.as_str()).await {
+                origin_ips.extend(lookup.iter());
+            }
+        }
+        let origin_ips = origin_ips.as_slice();
+
+        // Certificate transparency
+        let mut ct_names = Vec::new();
+        for domain in domains {
+            match self.ct_names(domain).await {
+                Ok(names) => ct_names.extend(names),
+                Err(e) => report.errors.push(e.to_string()),
+            }
+        }
+        for ip in ct_names
+            .iter()
+            .filter_map(|name| name.parse::<IpAddr>().ok())
+        {
+            if origin_ips.contains(&ip) {
+                push_finding(
+                    report,
+                    ExposureSource::CertificateTransparency,
+                    &ip.to_string(),
+                    &ip.to_string(),
+                    "Origin IP logged in a certificate",
+                );
+            }
+        }
+
+        // DNS records
+        let names = candidate_names(domains, &ct_names, origin_hosts, self.config.max_names);
+        report.names_checked = names.len() as u32;
+
+        let lookups: Vec<(String, Vec<IpAddr>)> = futures::stream::iter(names)
+            .map(|name| {
+                let resolver = &resolver;
+                async move {
+                    let ips = resolver
+                        .lookup_ip(name.as_str())
+                        .await
+                        .map(|lookup| lookup.iter().collect())
+                        .unwrap_or_default();
+                    (name, ips)
+                }
+            })
+            .buffer_unordered(CONCURRENT_LOOKUPS)
+            .collect()
+            .await;
+        let ct_names: HashSet<&str> = ct_names.iter().map(String::as_str).collect();
+        for (name, ips) in lookups {
+            let (source, detail) = if ct_names.contains(name.as_str()) {
+                (
+                    ExposureSource::CertificateTransparency,
+                    "Name logged in a certificate resolves to the origin",
+                )
+            } else {
+                (ExposureSource::DnsRecord, "DNS record points to the origin")
+            };
+            for ip in ips.iter().filter(|ip| origin_ips.contains(ip)) {
+                push_finding(report, source, &name, &ip.to_string(), detail);
+            }
+        }
+
+        // HTTP headers
+        for domain in domains {
+            self.check_http(domain, origin_ips, origin_hosts, report)
+                .await;
+        }
+        report
+            .findings
+            .sort_by(|a, b| (a.source, &a.name, &a.address).cmp(&(b.source, &b.name, &b.address)));
+    }
+
+    /// Names logged in certificate transparency for a domain and its
+    /// subdomains
+    async fn ct_names(&self, domain: &str) -> Result<Vec<String>> {
+        let url = format!(
+            "{}/?q=%25.{}&output=json",
+            self.config.ct_url.trim_end_matches('/'),
+            domain
+        );
+        let body = self
+            .http
+            .get(&url)
+            // crt.sh is slow for large domains
+            .timeout(HTTP_TIMEOUT * 6)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::external_service("crt.sh", e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| Error::external_service("crt.sh", e.to_string()))?;
+
+        parse_ct_names(&body).ok_or_else(|| {
+            Error::external_service("crt.sh", format!("Malformed response for {}", domain))
+        })
+    }
+
+    /// Look for origin addresses in the response headers of a domain
+    async fn check_http(
+        &self,
+        domain: &str,
+        origin_ips: &[IpAddr],
+        origin_hosts: &[String],
+        report: &mut OriginExposureReport,
+    ) {
+        for scheme in ["https", "http"] {
+            let url = format!("{}://{}/", scheme, domain);
+            // Domains without a website are fine
+            let Ok(response) = self.http.get(&url).send().await else {
+                continue;
+            };
+
+            for (header, value) in response.headers() {
+                let Ok(value) = value.to_str() else {
+                    continue;
+                };
+                let detail = format!("{} header of {}", header, url);
+                for ip in leaked_addresses(value, origin_ips) {
+                    push_finding(
+                        report,
+                        ExposureSource::HttpHeader,
+                        domain,
+                        &ip.to_string(),
+                        &detail,
+                    );
+                }
+                let value = value.to_ascii_lowercase();
+                for host in origin_hosts
+                    .iter()
+                    .filter(|host| value.contains(host.as_str()))
+                {
+                    push_finding(report, ExposureSource::HttpHeader, domain, host, &detail);
+                }
+            }
+        }
+    }
+
+    /// Warn about new exposures and update the alerts watching them
+    async fn report_findings(
+        &self,
+        backend_id: &str,
+        previous: Option<&OriginExposureReport>,
+        report: &OriginExposureReport,
+    ) {
+        let known: HashSet<(i32, &str, &str)> = previous
+            .map(|previous| {
+                previous
+                    .findings
+                    .iter()
+                    .map(|f| (f.source, f.name.as_str(), f.address.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let new: Vec<&ExposureFinding> = report
+            .findings
+            .iter()
+            .filter(|f| !known.contains(&(f.source, f.name.as_str(), f.address.as_str())))
+            .collect();
+
+        for finding in &new {
+            warn!(
+                backend_id = %backend_id,
+                source = ?finding.source(),
+                name = %finding.name,
+                address = %finding.address,
+                "Origin address exposed: {}",
+                finding.detail
+            );
+        }
+        if !new.is_empty() {
+            if let Some(cache) = &self.state.cache {
+                let message = format!("origin_exposed:{}", backend_id);
+                if let Err(e) = cache.publish("backend_updates", &message).await {
+                    warn!(error = %e, backend_id = %backend_id, "Failed to publish origin exposure");
+                }
+            }
+        }
+
+        let message = match report.findings.first() {
+            Some(finding) => format!(
+                "Origin {} exposed through {} ({} findings)",
+                finding.address,
+                finding.name,
+                report.findings.len()
+            ),
+            None => "Origin not exposed".to_string(),
+        };
+        if let Err(e) = MetricsService::new(self.state.clone())
+            .record_alert_value(
+                backend_id,
+                ORIGIN_EXPOSURE_METRIC,
+                report.findings.len() as f64,
+                &message,
+            )
+            .await
+        {
+            warn!(error = %e, backend_id = %backend_id, "Failed to evaluate exposure alerts");
+        }
+    }
+
+    /// Backends never scanned or whose last scan is older than the interval
+    async fn due_backends(&self) -> Result<Vec<String>> {
+        let db = self.state.db()?;
+
+        let rows: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT b.id
+            FROM backends b
+            LEFT JOIN backend_exposure e ON e.backend_id = b.id
+            WHERE (e.scanned_at IS NULL OR e.scanned_at < NOW() - make_interval(secs => $1))
+              AND (EXISTS (SELECT 1 FROM backend_domains d WHERE d.backend_id = b.id AND d.verified = true)
+                   OR EXISTS (SELECT 1 FROM backend_onboarding o WHERE o.backend_id = b.id))
+            ORDER BY e.scanned_at ASC NULLS FIRST
+            LIMIT $2
+            "#,
+        )
+        .bind(self.config.scan_interval.as_secs_f64())
+        .bind(SCANS_PER_TICK)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+}
+
+/// Spawn the scheduler scanning backends every scan interval
+pub fn spawn_scanner(
+    state: AppState,
+    config: ExposureConfig,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() || config.scan_interval.is_zero() {
+        info!("Origin exposure scans disabled");
+        return None;
+    }
+
+    let service = ExposureService::new(state, config);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULER_TICK);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    let backend_ids = match service.due_backends().await {
+                        Ok(ids) => ids,
+                        Err(e) => {
+                            warn!(error = %e, "Failed to list backends due an exposure scan");
+                            continue;
+                        }
+                    };
+                    for backend_id in backend_ids {
+                        if let Err(e) = service.scan(&backend_id).await {
+                            warn!(error = %e, backend_id = %backend_id, "Origin exposure scan failed");
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+fn push_finding(
+    report: &mut OriginExposureReport,
+    source: ExposureSource,
+    name: &str,
+    address: &str,
+    detail: &str,
+) {
+    let duplicate = report
+        .findings
+        .iter()
+        .any(|f| f.source == source as i32 && f.name == name && f.address == address);
+    if !duplicate {
+        report.findings.push(ExposureFinding {
+            source: source as i32,
+            name: name.to_string(),
+            address: address.to_string(),
+            detail: detail.to_string(),
+        });
+    }
+}
+
+#[derive(Deserialize)]
+struct CtEntry {
+    #[serde(default)]
+    common_name: String,
+    #[serde(default)]
+    name_value: String,
+}
+
+/// Parse the names of a crt.sh JSON response, without wildcards and
+/// duplicates
+pub fn parse_ct_names(body: &str) -> Option<Vec<String>> {
+    let entries: Vec<CtEntry> = serde_json::from_str(body).ok()?;
+
+    let mut names: Vec<String> = entries
+        .iter()
+        .flat_map(|entry| entry.name_value.lines().chain([entry.common_name.as_str()]))
+        .map(|name| {
+            name.trim()
+                .trim_start_matches("*.")
+                .trim_end_matches('.')
+                .to_ascii_lowercase()
+        })
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+    Some(names)
+}
+
+/// Names to resolve: the domains, their common subdomains, then names from
+/// certificate transparency under the domains, skipping the hostnames
+/// origins are configured with
+pub fn candidate_names(
+    domains: &[String],
+    ct_names: &[String],
+    origin_hosts: &[String],
+    limit: usize,
+) -> Vec<String> {
+    let under_domain = |name: &str| {
+        domains
+            .iter()
+            .any(|domain| name == domain || name.ends_with(&format!(".{}", domain)))
+    };
+
+    let mut seen = HashSet::new();
+    domains
+        .iter()
+        .flat_map(|domain| {
+            std::iter::once(domain.clone()).chain(
+                COMMON_SUBDOMAINS
+                    .iter()
+                    .map(move |sub| format!("{}.{}", sub, domain)),
+            )
+        })
+        .chain(ct_names.iter().filter(|name| under_domain(name)).cloned())
+        .filter(|name| BackendService::is_valid_domain(name))
+        .filter(|name| !origin_hosts.contains(name))
+        .filter(|name| seen.insert(name.clone()))
+        .take(limit)
+        .collect()
+}
+
+/// Origin addresses appearing in a header value, alone or with a port
+pub fn leaked_addresses(value: &str, origin_ips: &[IpAddr]) -> Vec<IpAddr> {
+    let mut leaked: Vec<IpAddr> = value
+        .split(|c: char| !(c.is_ascii_hexdigit() || matches!(c, ':' | '.' | '[' | ']')))
+        .filter_map(|token| {
+            token
+                .parse::<IpAddr>()
+                .or_else(|_| token.parse::<SocketAddr>().map(|addr| addr.ip()))
+                .or_else(|_| token.trim_matches(|c| c == '[' || c == ']').parse())
+                .ok()
+        })
+        .filter(|ip| origin_ips.contains(ip))
+        .collect();
+    leaked.sort();
+    leaked.dedup();
+    leaked
+}
@@ -3,16 +3,41 @@
 use crate::services::AppState;
 use futures::StreamExt;
 use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::pagination::{Field, FieldKind, ListSpec, Page, PageRequest};
 use pistonprotection_proto::common;
 use pistonprotection_proto::filter::*;
+use pistonprotection_rule_compiler as compiler;
 use sqlx::Row;
 use std::time::Duration;
 use tokio::sync::broadcast;
 use tokio_stream::Stream;
 use tokio_stream::wrappers::BroadcastStream;
-use tracing::{info, instrument};
+use tracing::{debug, info, instrument, warn};
 use uuid::Uuid;
 
+/// Sort and filter fields of rule lists
+pub static RULE_LIST: ListSpec = ListSpec {
+    fields: &[
+        Field::sortable("priority", "COALESCE(priority, 0)", FieldKind::Integer),
+        Field::sortable("name", "name", FieldKind::Text),
+        Field::sortable(
+            "created_at",
+            "COALESCE(created_at, 'epoch')",
+            FieldKind::Timestamp,
+        ),
+        Field::sortable(
+            "updated_at",
+            "COALESCE(updated_at, 'epoch')",
+            FieldKind::Timestamp,
+        ),
+        Field::filterable("action", "action", FieldKind::Integer),
+        Field::filterable("enabled", "enabled", FieldKind::Boolean),
+        Field::filterable("description", "description", FieldKind::Text),
+    ],
+    id_column: "id",
+    default_order: "priority",
+};
+
 /// Filter service implementation
 pub struct FilterService {
     state: AppState,
@@ -24,9 +49,17 @@ impl FilterService {
     }
 
     /// Create a new filter rule
+    ///
+    /// Returns the rule along with warnings about conflicts with other rules
+    /// of the backend.
     #[instrument(skip(self, rule))]
-    pub async fn create(&self, backend_id: &str, rule: FilterRule) -> Result<FilterRule> {
+    pub async fn create(
+        &self,
+        backend_id: &str,
+        rule: FilterRule,
+    ) -> Result<(FilterRule, Vec<String>)> {
         let db = self.state.db()?;
+        compile_rule(&rule)?;
 
         let id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now();
@@ -67,7 +100,10 @@ impl FilterService {
         // Invalidate cache
         self.invalidate_cache(backend_id).await;
 
-        self.get(&id).await
+        let warnings = self
+            .conflict_warnings(backend_id, std::slice::from_ref(&id))
+            .await;
+        Ok((self.get(&id).await?, warnings))
     }
 
     /// Get a filter rule by ID
@@ -98,60 +134,91 @@ impl FilterService {
         &self,
         backend_id: &str,
         include_disabled: bool,
-        page: u32,
-        page_size: u32,
-    ) -> Result<(Vec<FilterRule>, u64)> {
+        request: &PageRequest,
+    ) -> Result<(Page<FilterRule>, u64)> {
         let db = self.state.db()?;
-        let offset = (page.saturating_sub(1)) * page_size;
-
-        // Get total count for pagination
-        let count_query = if include_disabled {
-            "SELECT COUNT(*) FROM filter_rules WHERE backend_id = $1"
+        let enabled = if include_disabled {
+            ""
         } else {
-            "SELECT COUNT(*) FROM filter_rules WHERE backend_id = $1 AND enabled = true"
+            " AND enabled = true"
         };
 
-        let count_row: (i64,) = sqlx::query_as(count_query)
-            .bind(backend_id)
-            .fetch_one(db)
-            .await?;
-        let total = count_row.0 as u64;
+        // Get total count for pagination
+        let mut count =
+            sqlx::QueryBuilder::new("SELECT COUNT(*) FROM filter_rules WHERE backend_id = ");
+        count.push_bind(backend_id).push(enabled);
+        request.push_filter(&mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(db).await?;
+
+        let mut query = sqlx::QueryBuilder::new(format!(
+            "SELECT id, backend_id, name, description, priority, \
+                    match_criteria, action, rate_limit, enabled, created_at, updated_at, {} \
+             FROM filter_rules WHERE backend_id = ",
+            request.select_key()
+        ));
+        query.push_bind(backend_id).push(enabled);
+        request.push_filter(&mut query);
+        request.push_page(&mut query);
+        let rows = query.build().fetch_all(db).await?;
+
+        let page = request
+            .finish_rows(rows)?
+            .try_map(|row| self.row_to_rule(&row))?;
+        Ok((page, total as u64))
+    }
 
-        let query = if include_disabled {
-            r#"
-            SELECT id, backend_id, name, description, priority,
-                   match_criteria, action, rate_limit, enabled, created_at, updated_at
-            FROM filter_rules
-            WHERE backend_id = $1
-            ORDER BY priority ASC, created_at ASC
-            LIMIT $2 OFFSET $3
-            "#
-        } else {
-            r#"
-            SELECT id, backend_id, name, description, priority,
-                   match_criteria, action, rate_limit, enabled, created_at, updated_at
-            FROM filter_rules
-            WHERE backend_id = $1 AND enabled = true
-            ORDER BY priority ASC, created_at ASC
-            LIMIT $2 OFFSET $3
-            "#
-        };
+    /// All rules of an organization's backends, optionally limited to one
+    /// backend, as (backend ID, rule) pairs ordered by backend and priority
+    #[instrument(skip(self))]
+    pub async fn list_for_organization(
+        &self,
+        org_id: &str,
+        backend_id: Option<&str>,
+        include_disabled: bool,
+    ) -> Result<Vec<(String, FilterRule)>> {
+        let db = self.state.db_read()?;
+
+        let mut query = sqlx::QueryBuilder::new(
+            "SELECT r.id, r.backend_id, r.name, r.description, r.priority, \
+                    r.match_criteria, r.action, r.rate_limit, r.enabled, r.created_at, r.updated_at \
+             FROM filter_rules r JOIN backends b ON b.id = r.backend_id \
+             WHERE b.organization_id = ",
+        );
+        query.push_bind(org_id);
+        if let Some(backend_id) = backend_id {
+            query.push(" AND r.backend_id = ").push_bind(backend_id);
+        }
+        if !include_disabled {
+            query.push(" AND r.enabled = true");
+        }
+        query.push(" ORDER BY r.backend_id, r.priority, r.name, r.id");
+        let rows = query.build().fetch_all(db).await?;
 
-        let rows = sqlx::query(query)
-            .bind(backend_id)
-            .bind(page_size as i64)
-            .bind(offset as i64)
-            .fetch_all(db)
-            .await?;
+        rows.iter()
+            .map(|row| Ok((row.get("backend_id"), self.row_to_rule(row)?)))
+            .collect()
+    }
 
-        let rules: Result<Vec<FilterRule>> = rows.iter().map(|row| self.row_to_rule(row)).collect();
-        Ok((rules?, total))
+    /// Backends of the given rules, each listed once
+    pub async fn rule_backends(&self, rule_ids: &[String]) -> Result<Vec<String>> {
+        let db = self.state.db()?;
+        let backend_ids: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT backend_id FROM filter_rules WHERE id = ANY($1) ORDER BY backend_id",
+        )
+        .bind(rule_ids)
+        .fetch_all(db)
+        .await?;
+        Ok(backend_ids)
     }
 
     /// Update a filter rule
+    ///
+    /// Returns the rule along with warnings about conflicts with other rules
+    /// of the backend.
     #[instrument(skip(self, rule))]
-    pub async fn update(&self, rule: FilterRule) -> Result<FilterRule> {
+    pub async fn update(&self, rule: FilterRule) -> Result<(FilterRule, Vec<String>)> {
         let db = self.state.db()?;
+        compile_rule(&rule)?;
         let now = chrono::Utc::now();
 
         let match_json = serde_json::to_value(&rule.r#match)
@@ -199,7 +266,10 @@ impl FilterService {
         // Invalidate cache
         self.invalidate_cache(&backend_id).await;
 
-        self.get(&rule.id).await
+        let warnings = self
+            .conflict_warnings(&backend_id, std::slice::from_ref(&rule.id))
+            .await;
+        Ok((self.get(&rule.id).await?, warnings))
     }
 
     /// Delete a filter rule
@@ -262,6 +332,91 @@ impl FilterService {
         Ok(())
     }
 
+    /// Conflicts between the given rules and the other enabled rules of a
+    /// backend
+    ///
+    /// Conflicts are resolved by priority when compiling, so they are
+    /// reported as warnings rather than errors. Failures to load the rule set
+    /// are logged and yield no warnings.
+    async fn conflict_warnings(&self, backend_id: &str, rule_ids: &[String]) -> Vec<String> {
+        self.conflict_warnings_with(backend_id, Vec::new(), rule_ids)
+            .await
+    }
+
+    /// Conflicts that creating the given rules would introduce, without
+    /// creating them
+    pub async fn preview_conflicts(&self, backend_id: &str, rules: &[FilterRule]) -> Vec<String> {
+        // Rules without an ID are identified by name when compiled
+        let names: Vec<String> = rules
+            .iter()
+            .filter(|r| r.enabled)
+            .map(|r| r.name.clone())
+            .collect();
+        self.conflict_warnings_with(backend_id, rules.to_vec(), &names)
+            .await
+    }
+
+    /// Conflicts between the given rules and the other enabled rules of a
+    /// backend, including `extra` rules that are not stored
+    async fn conflict_warnings_with(
+        &self,
+        backend_id: &str,
+        extra: Vec<FilterRule>,
+        rule_ids: &[String],
+    ) -> Vec<String> {
+        let rules = match self.enabled_rules(backend_id).await {
+            Ok(mut rules) => {
+                rules.extend(extra.into_iter().filter(|r| r.enabled));
+                rules
+            }
+            Err(e) => {
+                warn!(backend_id = %backend_id, error = %e, "Failed to load rules for conflict check");
+                return Vec::new();
+            }
+        };
+
+        // Rules stored before validation existed may not convert
+        let converted: Vec<compiler::Rule> = rules
+            .iter()
+            .filter_map(|r| to_compiler_rule(r).ok())
+            .collect();
+        let compiled = match compiler::compile(&converted) {
+            Ok(compiled) => compiled,
+            Err(e) => return vec![format!("Rules of the backend do not compile: {}", e)],
+        };
+
+        let names: std::collections::HashMap<&str, &str> = rules
+            .iter()
+            .filter(|r| !r.id.is_empty())
+            .map(|r| (r.id.as_str(), r.name.as_str()))
+            .collect();
+        compiled
+            .conflicts()
+            .iter()
+            .filter(|c| rule_ids.iter().any(|id| c.involves(id)))
+            .map(|c| conflict_warning(c, &names))
+            .collect()
+    }
+
+    /// All enabled rules of a backend
+    async fn enabled_rules(&self, backend_id: &str) -> Result<Vec<FilterRule>> {
+        let db = self.state.db()?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, backend_id, name, description, priority,
+                   match_criteria, action, rate_limit, enabled, created_at, updated_at
+            FROM filter_rules
+            WHERE backend_id = $1 AND enabled = true
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_all(db)
+        .await?;
+
+        rows.iter().map(|row| self.row_to_rule(row)).collect()
+    }
+
     /// Convert database row to FilterRule
     fn row_to_rule(&self, row: &sqlx::postgres::PgRow) -> Result<FilterRule> {
         let match_json: serde_json::Value = row.get("match_criteria");
@@ -288,7 +443,7 @@ impl FilterService {
     }
 
     /// Invalidate cache for a backend's filter rules
-    async fn invalidate_cache(&self, backend_id: &str) {
+    pub(crate) async fn invalidate_cache(&self, backend_id: &str) {
         if let Some(cache) = &self.state.cache {
             let _ = cache
                 .delete_pattern(&format!("filters:{}:*", backend_id))
@@ -305,12 +460,15 @@ impl FilterService {
     // =========================================================================
 
     /// Bulk create multiple filter rules
+    ///
+    /// Returns the created rules, per-rule errors and warnings about
+    /// conflicts of the created rules.
     #[instrument(skip(self, rules))]
     pub async fn bulk_create(
         &self,
         backend_id: &str,
         rules: Vec<FilterRule>,
-    ) -> Result<(Vec<FilterRule>, Vec<common::Error>)> {
+    ) -> Result<(Vec<FilterRule>, Vec<common::Error>, Vec<String>)> {
         let db = self.state.db()?;
         let now = chrono::Utc::now();
 
@@ -330,7 +488,7 @@ impl FilterService {
                     errors.push(common::Error {
                         code: "SERIALIZATION_ERROR".to_string(),
                         message: format!("Rule {}: Failed to serialize match: {}", index, e),
-                        details: std::collections::HashMap::new(),
+                        details: index_detail(index),
                     });
                     continue;
                 }
@@ -343,7 +501,7 @@ impl FilterService {
                     errors.push(common::Error {
                         code: "SERIALIZATION_ERROR".to_string(),
                         message: format!("Rule {}: Failed to serialize rate_limit: {}", index, e),
-                        details: std::collections::HashMap::new(),
+                        details: index_detail(index),
                     });
                     continue;
                 }
@@ -354,7 +512,15 @@ impl FilterService {
                 errors.push(common::Error {
                     code: "VALIDATION_ERROR".to_string(),
                     message: format!("Rule {}: Name is required", index),
-                    details: std::collections::HashMap::new(),
+                    details: index_detail(index),
+                });
+                continue;
+            }
+            if let Err(e) = compile_rule(&rule) {
+                errors.push(common::Error {
+                    code: "VALIDATION_ERROR".to_string(),
+                    message: format!("Rule {}: {}", index, e),
+                    details: index_detail(index),
                 });
                 continue;
             }
@@ -392,7 +558,7 @@ impl FilterService {
                     errors.push(common::Error {
                         code: "DATABASE_ERROR".to_string(),
                         message: format!("Rule {}: {}", index, e),
-                        details: std::collections::HashMap::new(),
+                        details: index_detail(index),
                     });
                 }
             }
@@ -417,7 +583,10 @@ impl FilterService {
                 .await;
         }
 
-        Ok((created_rules, errors))
+        let created_ids: Vec<String> = created_rules.iter().map(|r| r.id.clone()).collect();
+        let warnings = self.conflict_warnings(backend_id, &created_ids).await;
+
+        Ok((created_rules, errors, warnings))
     }
 
     /// Bulk delete multiple filter rules
@@ -669,3 +838,121 @@ impl FilterService {
         }
     }
 }
+
+/// Compile a rule into worker map entries, rejecting rules that cannot be
+/// enforced as written
+pub fn compile_rule(rule: &FilterRule) -> Result<compiler::CompiledRules> {
+    let compiled = compiler::compile(&[to_compiler_rule(rule)?])
+        .map_err(|e| Error::validation(e.to_string()))?;
+    for skipped in compiled.skipped() {
+        debug!(rule = %skipped.rule, reason = %skipped.reason, "Rule part not compiled to maps");
+    }
+    Ok(compiled)
+}
+
+/// Error details pointing at a rule of a bulk request
+fn index_detail(index: usize) -> std::collections::HashMap<String, String> {
+    std::collections::HashMap::from([("index".to_string(), index.to_string())])
+}
+
+/// Describe a conflict using rule names
+pub(crate) fn conflict_warning(
+    conflict: &compiler::Conflict,
+    names: &std::collections::HashMap<&str, &str>,
+) -> String {
+    let name = |id: &str| names.get(id).copied().unwrap_or(id).to_string();
+    let what = match conflict.kind {
+        compiler::ConflictKind::Sources => "the same sources",
+        compiler::ConflictKind::Ports => "the same destination ports",
+        compiler::ConflictKind::Traffic => "overlapping traffic",
+    };
+    let mut warning = format!(
+        "Rules '{}' and '{}' apply different actions to {}; '{}' takes precedence",
+        name(&conflict.winner),
+        name(&conflict.loser),
+        what,
+        name(&conflict.winner)
+    );
+    if conflict.tie {
+        warning.push_str(
+            " as both have the same priority; set distinct priorities to make the order explicit",
+        );
+    }
+    warning
+}
+
+/// Convert a rule into the rule compiler's model
+pub fn to_compiler_rule(rule: &FilterRule) -> Result<compiler::Rule> {
+    let action = match common::Action::try_from(rule.action) {
+        Ok(common::Action::Allow) => compiler::Action::Allow,
+        Ok(common::Action::Drop) => compiler::Action::Drop,
+        Ok(common::Action::RateLimit) => compiler::Action::RateLimit,
+        Ok(common::Action::Challenge) => compiler::Action::Challenge,
+        Ok(common::Action::Redirect) => compiler::Action::Redirect,
+        // Without an action only the blacklist is enforced
+        Ok(common::Action::Log | common::Action::Unspecified) | Err(_) => compiler::Action::Log,
+    };
+    let id = if rule.id.is_empty() {
+        &rule.name
+    } else {
+        &rule.id
+    };
+
+    let mut out = compiler::Rule::new(
+        id.as_str(),
+        i32::try_from(rule.priority).unwrap_or(i32::MAX),
+        action,
+    );
+    out.enabled = rule.enabled;
+
+    if let Some(m) = &rule.r#match {
+        out.source_ips = to_cidrs(&m.source_ips)?;
+        out.source_ip_blacklist = to_cidrs(&m.source_ip_blacklist)?;
+        out.destination_ips = to_cidrs(&m.destination_ips)?;
+        out.destination_ports = m
+            .destination_ports
+            .iter()
+            .map(|p| compiler::PortRange::new(p.start, p.end))
+            .collect();
+        out.protocols = m
+            .protocols
+            .iter()
+            .map(|p| match common::Protocol::try_from(*p) {
+                Ok(common::Protocol::Tcp) => Ok(compiler::Protocol::Tcp),
+                Ok(common::Protocol::Udp) => Ok(compiler::Protocol::Udp),
+                Ok(common::Protocol::Icmp) => Ok(compiler::Protocol::Icmp),
+                _ => Err(Error::validation(format!("Unknown protocol {}", p))),
+            })
+            .collect::<Result<_>>()?;
+        out.countries = m
+            .source_countries
+            .iter()
+            .chain(&m.source_country_blacklist)
+            .cloned()
+            .collect();
+        out.asns = m.source_asns.clone();
+        out.l7 = !m.l7_protocols.is_empty() || m.l7_match.is_some();
+        out.scheduled = m.time_match.is_some();
+    }
+
+    Ok(out)
+}
+
+fn to_cidrs(networks: &[common::IpNetwork]) -> Result<Vec<compiler::Cidr>> {
+    networks
+        .iter()
+        .map(|network| {
+            let addr = network
+                .address
+                .as_ref()
+                .and_then(|a| std::net::IpAddr::try_from(a).ok())
+                .ok_or_else(|| Error::validation("Network without a valid address"))?;
+            let cidr =
+                compiler::Cidr::new(addr, u8::try_from(network.prefix_length).unwrap_or(u8::MAX));
+            if !cidr.is_valid() {
+                return Err(Error::validation(format!("Invalid network {}", cidr)));
+            }
+            Ok(cidr)
+        })
+        .collect()
+}
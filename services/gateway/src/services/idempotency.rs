@@ -0,0 +1,335 @@
+//! Idempotency keys for mutating endpoints
+//!
+//! A client may send an `Idempotency-Key` with a create request. The first
+//! request with a key claims it in Redis; once it succeeds its response is
+//! stored under the key, together with a fingerprint of the request, and
+//! retries with the same key get the stored response instead of creating the
+//! resource again. A retry whose request differs from the original is
+//! rejected, as is one arriving while the original is still running. Failed
+//! requests release the key so they can be retried.
+//!
+//! Keys are scoped to the operation and the caller. Without Redis, requests
+//! run without idempotency.
+
+use super::AppState;
+use pistonprotection_common::redis::CacheService;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::warn;
+
+/// Header or metadata key carrying the idempotency key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header or metadata key set on replayed responses
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Longest accepted idempotency key
+pub const MAX_KEY_LENGTH: usize = 255;
+
+/// How long completed responses are kept
+const RESPONSE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a claim is held by a request that has not finished
+const PENDING_TTL: Duration = Duration::from_secs(60);
+
+/// Idempotency errors
+#[derive(Debug, thiserror::Error)]
+pub enum IdempotencyError {
+    #[error("Idempotency key must be 1 to 255 visible ASCII characters")]
+    InvalidKey,
+
+    #[error("A request with this idempotency key is still in progress")]
+    InProgress,
+
+    #[error("Idempotency key was already used for a different request")]
+    Mismatch,
+}
+
+impl From<IdempotencyError> for tonic::Status {
+    fn from(err: IdempotencyError) -> Self {
+        match err {
+            IdempotencyError::InvalidKey => tonic::Status::invalid_argument(err.to_string()),
+            IdempotencyError::InProgress => tonic::Status::aborted(err.to_string()),
+            IdempotencyError::Mismatch => tonic::Status::failed_precondition(err.to_string()),
+        }
+    }
+}
+
+impl IdempotencyError {
+    /// HTTP status for REST endpoints
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Self::InvalidKey => 400,
+            Self::InProgress => 409,
+            Self::Mismatch => 422,
+        }
+    }
+}
+
+/// A response stored for replay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    /// HTTP status, or 200 for gRPC
+    pub status: u16,
+    /// Hex encoded response body
+    body: String,
+}
+
+impl StoredResponse {
+    pub fn new(status: u16, body: &[u8]) -> Self {
+        Self {
+            status,
+            body: hex::encode(body),
+        }
+    }
+
+    /// Decoded response body
+    pub fn body(&self) -> Vec<u8> {
+        hex::decode(&self.body).unwrap_or_default()
+    }
+}
+
+/// What is stored under an idempotency key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    fingerprint: String,
+    response: Option<StoredResponse>,
+}
+
+/// How to handle a request
+#[derive(Debug)]
+pub enum Claim {
+    /// Run the request, then report the outcome to the pending claim if any
+    Proceed(Option<PendingClaim>),
+    /// Answer with the stored response
+    Replay(StoredResponse),
+}
+
+/// A claimed key whose request is running
+pub struct PendingClaim {
+    cache: CacheService,
+    key: String,
+    fingerprint: String,
+}
+
+impl std::fmt::Debug for PendingClaim {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingClaim")
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PendingClaim {
+    /// Store the response of a successful request
+    pub async fn complete(self, response: StoredResponse) {
+        let record = Record {
+            fingerprint: self.fingerprint,
+            response: Some(response),
+        };
+        if let Err(e) = self.cache.set(&self.key, &record, RESPONSE_TTL).await {
+            warn!(error = %e, "Failed to store idempotent response");
+        }
+    }
+
+    /// Release the key after a failed request so it can be retried
+    pub async fn release(self) {
+        if let Err(e) = self.cache.delete(&self.key).await {
+            warn!(error = %e, "Failed to release idempotency key");
+        }
+    }
+}
+
+/// Idempotency key store
+#[derive(Clone)]
+pub struct IdempotencyService {
+    cache: Option<CacheService>,
+}
+
+impl IdempotencyService {
+    /// Create a new idempotency service
+    pub fn new(state: AppState) -> Self {
+        Self { cache: state.cache }
+    }
+
+    /// Claim `key` for a request of `operation` by `caller`, whose content
+    /// is `request`
+    pub async fn begin(
+        &self,
+        operation: &str,
+        caller: &str,
+        key: Option<&str>,
+        request: &[u8],
+    ) -> Result<Claim, IdempotencyError> {
+        let Some(key) = key else {
+            return Ok(Claim::Proceed(None));
+        };
+        validate_key(key)?;
+        let Some(ref cache) = self.cache else {
+            return Ok(Claim::Proceed(None));
+        };
+
+        let key = storage_key(operation, caller, key);
+        let fingerprint = fingerprint(operation, request);
+        let pending = Record {
+            fingerprint: fingerprint.clone(),
+            response: None,
+        };
+
+        match cache.set_nx(&key, &pending, PENDING_TTL).await {
+            Ok(true) => {
+                return Ok(Claim::Proceed(Some(PendingClaim {
+                    cache: cache.clone(),
+                    key,
+                    fingerprint,
+                })));
+            }
+            Ok(false) => {}
+            Err(e) => {
+                warn!(error = %e, "Idempotency store unavailable, running request without it");
+                return Ok(Claim::Proceed(None));
+            }
+        }
+
+        match cache.get::<Record>(&key).await {
+            Ok(existing) => resolve(existing, &fingerprint),
+            Err(e) => {
+                warn!(error = %e, "Idempotency store unavailable, running request without it");
+                Ok(Claim::Proceed(None))
+            }
+        }
+    }
+}
+
+/// Check that a key is 1 to 255 visible ASCII characters
+pub fn validate_key(key: &str) -> Result<(), IdempotencyError> {
+    let valid =
+        !key.is_empty() && key.len() <= MAX_KEY_LENGTH && key.bytes().all(|b| b.is_ascii_graphic());
+    if valid {
+        Ok(())
+    } else {
+        Err(IdempotencyError::InvalidKey)
+    }
+}
+
+/// Hash of the operation and request content
+pub fn fingerprint(operation: &str, request: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(operation.as_bytes());
+    hasher.update([0]);
+    hasher.update(request);
+    hex::encode(hasher.finalize())
+}
+
+/// Request content with map entries in a stable order, for fingerprinting
+/// messages whose maps encode in arbitrary order
+pub fn canonical_json<T: Serialize>(request: &T) -> Vec<u8> {
+    serde_json::to_value(request)
+        .and_then(|value| serde_json::to_vec(&value))
+        .unwrap_or_default()
+}
+
+/// Cache key of an idempotency key. The client's key is hashed to bound the
+/// length of the stored key.
+fn storage_key(operation: &str, caller: &str, key: &str) -> String {
+    let key = hex::encode(Sha256::digest(key.as_bytes()));
+    format!("idempotency:{}:{}:{}", operation, caller, key)
+}
+
+/// Decide what to do with a request whose key is already claimed
+fn resolve(existing: Option<Record>, fingerprint: &str) -> Result<Claim, IdempotencyError> {
+    match existing {
+        // The claim expired or was released in between; treat the request
+        // as a fresh attempt without storing its response
+        None => Ok(Claim::Proceed(None)),
+        Some(record) if record.fingerprint != fingerprint => Err(IdempotencyError::Mismatch),
+        Some(Record {
+            response: Some(response),
+            ..
+        }) => Ok(Claim::Replay(response)),
+        Some(_) => Err(IdempotencyError::InProgress),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("5f1c0f8e-3c1a-4a3e-9a51-5d0e8e3d7f42").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("has space").is_err());
+        assert!(validate_key("ключ").is_err());
+        assert!(validate_key(&"k".repeat(MAX_KEY_LENGTH)).is_ok());
+        assert!(validate_key(&"k".repeat(MAX_KEY_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(
+            fingerprint("create_rule", b"a"),
+            fingerprint("create_rule", b"a")
+        );
+        assert_ne!(
+            fingerprint("create_rule", b"a"),
+            fingerprint("create_rule", b"b")
+        );
+        assert_ne!(
+            fingerprint("create_rule", b"a"),
+            fingerprint("block_ip", b"a")
+        );
+    }
+
+    #[test]
+    fn test_canonical_json_orders_maps() {
+        let forward: std::collections::HashMap<_, _> =
+            (0..32).map(|i| (i.to_string(), i)).collect();
+        let backward: std::collections::HashMap<_, _> =
+            (0..32).rev().map(|i| (i.to_string(), i)).collect();
+        assert_eq!(canonical_json(&forward), canonical_json(&backward));
+    }
+
+    #[test]
+    fn test_storage_key_is_scoped() {
+        let key = storage_key("create_backend", "user-1", "retry-1");
+        assert!(key.starts_with("idempotency:create_backend:user-1:"));
+        assert_ne!(key, storage_key("create_backend", "user-2", "retry-1"));
+        assert_ne!(key, storage_key("create_rule", "user-1", "retry-1"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let fp = fingerprint("create_rule", b"request");
+        let response = StoredResponse::new(200, b"response");
+
+        let done = Record {
+            fingerprint: fp.clone(),
+            response: Some(response.clone()),
+        };
+        match resolve(Some(done.clone()), &fp) {
+            Ok(Claim::Replay(stored)) => {
+                assert_eq!(stored, response);
+                assert_eq!(stored.body(), b"response");
+            }
+            other => panic!("expected replay, got {:?}", other),
+        }
+
+        let other = fingerprint("create_rule", b"other request");
+        assert!(matches!(
+            resolve(Some(done), &other),
+            Err(IdempotencyError::Mismatch)
+        ));
+
+        let pending = Record {
+            fingerprint: fp.clone(),
+            response: None,
+        };
+        assert!(matches!(
+            resolve(Some(pending), &fp),
+            Err(IdempotencyError::InProgress)
+        ));
+        assert!(matches!(resolve(None, &fp), Ok(Claim::Proceed(None))));
+    }
+}
@@ -1,11 +1,37 @@
 //! Metrics aggregation service
 
 use crate::services::AppState;
-use pistonprotection_common::error::Result;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::pagination::{Field, FieldKind, ListSpec, Page, PageRequest};
 use pistonprotection_proto::metrics::*;
 use sqlx::Row;
 use tracing::instrument;
 
+/// Sort and filter fields of attack event lists
+pub static ATTACK_EVENT_LIST: ListSpec = ListSpec {
+    fields: &[
+        Field::sortable("started_at", "started_at", FieldKind::Timestamp),
+        Field::sortable("severity", "COALESCE(severity, 1)", FieldKind::Integer),
+        Field::sortable("peak_pps", "COALESCE(peak_pps, 0)", FieldKind::Integer),
+        Field::sortable("peak_bps", "COALESCE(peak_bps, 0)", FieldKind::Integer),
+        Field::sortable(
+            "duration_seconds",
+            "COALESCE(duration_seconds, 0)",
+            FieldKind::Integer,
+        ),
+        Field::sortable(
+            "unique_sources",
+            "COALESCE(unique_sources, 0)",
+            FieldKind::Integer,
+        ),
+        Field::filterable("attack_type", "attack_type", FieldKind::Text),
+        Field::filterable("ended_at", "ended_at", FieldKind::Timestamp),
+        Field::filterable("ongoing", "ended_at IS NULL", FieldKind::Boolean),
+    ],
+    id_column: "id",
+    default_order: "-started_at",
+};
+
 /// Metrics service implementation
 pub struct MetricsService {
     state: AppState,
@@ -65,7 +91,7 @@ impl MetricsService {
         start_time: chrono::DateTime<chrono::Utc>,
         end_time: chrono::DateTime<chrono::Utc>,
     ) -> Result<GeoMetrics> {
-        let db = self.state.db()?;
+        let db = self.state.db_read()?;
 
         let rows = sqlx::query(
             r#"
@@ -117,7 +143,7 @@ impl MetricsService {
         end_time: chrono::DateTime<chrono::Utc>,
         granularity: TimeGranularity,
     ) -> Result<TimeSeries> {
-        let db = self.state.db()?;
+        let db = self.state.db_read()?;
 
         let interval = match granularity {
             TimeGranularity::Minute => "1 minute",
@@ -167,7 +193,7 @@ impl MetricsService {
         })
     }
 
-    /// List attack events with pagination
+    /// List attack events started within a time range with pagination
     /// Returns (events, total_count)
     #[instrument(skip(self))]
     pub async fn list_attack_events(
@@ -175,78 +201,64 @@ impl MetricsService {
         backend_id: &str,
         start_time: chrono::DateTime<chrono::Utc>,
         end_time: chrono::DateTime<chrono::Utc>,
-        page: u32,
-        page_size: u32,
-    ) -> Result<(Vec<AttackEvent>, u64)> {
-        let db = self.state.db()?;
-        let offset = (page.saturating_sub(1)) * page_size;
+        request: &PageRequest,
+    ) -> Result<(Page<AttackEvent>, u64)> {
+        let db = self.state.db_read()?;
 
         // Get total count for pagination
-        let count_row: (i64,) = sqlx::query_as(
-            r#"
-            SELECT COUNT(*)
-            FROM attack_events
-            WHERE backend_id = $1
-              AND started_at >= $2
-              AND started_at < $3
-            "#,
-        )
-        .bind(backend_id)
-        .bind(start_time)
-        .bind(end_time)
-        .fetch_one(db)
-        .await?;
-        let total = count_row.0 as u64;
-
-        let rows = sqlx::query(
-            r#"
-            SELECT id, backend_id, started_at, ended_at, duration_seconds,
-                   attack_type, severity, peak_pps, peak_bps,
-                   total_packets, total_bytes, packets_mitigated,
-                   mitigation_rate, unique_sources
-            FROM attack_events
-            WHERE backend_id = $1
-              AND started_at >= $2
-              AND started_at < $3
-            ORDER BY started_at DESC
-            LIMIT $4 OFFSET $5
-            "#,
-        )
-        .bind(backend_id)
-        .bind(start_time)
-        .bind(end_time)
-        .bind(page_size as i64)
-        .bind(offset as i64)
-        .fetch_all(db)
-        .await?;
+        let mut count =
+            sqlx::QueryBuilder::new("SELECT COUNT(*) FROM attack_events WHERE backend_id = ");
+        count
+            .push_bind(backend_id)
+            .push(" AND started_at >= ")
+            .push_bind(start_time)
+            .push(" AND started_at < ")
+            .push_bind(end_time);
+        request.push_filter(&mut count);
+        let total: i64 = count.build_query_scalar().fetch_one(db).await?;
+
+        let mut query = sqlx::QueryBuilder::new(format!(
+            "SELECT id, backend_id, started_at, ended_at, duration_seconds, \
+                    attack_type, severity, peak_pps, peak_bps, \
+                    total_packets, total_bytes, packets_mitigated, \
+                    mitigation_rate, unique_sources, {} \
+             FROM attack_events WHERE backend_id = ",
+            request.select_key()
+        ));
+        query
+            .push_bind(backend_id)
+            .push(" AND started_at >= ")
+            .push_bind(start_time)
+            .push(" AND started_at < ")
+            .push_bind(end_time);
+        request.push_filter(&mut query);
+        request.push_page(&mut query);
+        let rows = query.build().fetch_all(db).await?;
+
+        let page = request.finish_rows(rows)?.try_map(|row| {
+            let started_at: chrono::DateTime<chrono::Utc> = row.get("started_at");
+            let ended_at: Option<chrono::DateTime<chrono::Utc>> = row.get("ended_at");
 
-        let events = rows
-            .iter()
-            .map(|row| {
-                let started_at: chrono::DateTime<chrono::Utc> = row.get("started_at");
-                let ended_at: Option<chrono::DateTime<chrono::Utc>> = row.get("ended_at");
-
-                AttackEvent {
-                    id: row.get("id"),
-                    backend_id: row.get("backend_id"),
-                    started_at: Some(started_at.into()),
-                    ended_at: ended_at.map(|t| t.into()),
-                    duration_seconds: row.get::<i32, _>("duration_seconds") as u32,
-                    attack_type: row.get("attack_type"),
-                    severity: row.get::<i32, _>("severity"),
-                    peak_pps: row.get::<i64, _>("peak_pps") as u64,
-                    peak_bps: row.get::<i64, _>("peak_bps") as u64,
-                    total_packets: row.get::<i64, _>("total_packets") as u64,
-                    total_bytes: row.get::<i64, _>("total_bytes") as u64,
-                    packets_mitigated: row.get::<i64, _>("packets_mitigated") as u64,
-                    mitigation_rate: row.get("mitigation_rate"),
-                    unique_sources: row.get::<i32, _>("unique_sources") as u32,
-                    ..Default::default()
-                }
+            Ok::<_, Error>(AttackEvent {
+                id: row.get("id"),
+                backend_id: row.get("backend_id"),
+                started_at: Some(started_at.into()),
+                ended_at: ended_at.map(|t| t.into()),
+                duration_seconds: row.get::<i32, _>("duration_seconds") as u32,
+                attack_type: row.get("attack_type"),
+                severity: row.get::<i32, _>("severity"),
+                peak_pps: row.get::<i64, _>("peak_pps") as u64,
+                peak_bps: row.get::<i64, _>("peak_bps") as u64,
+                total_packets: row.get::<i64, _>("total_packets") as u64,
+                total_bytes: row.get::<i64, _>("total_bytes") as u64,
+                packets_mitigated: row.get::<i64, _>("packets_mitigated") as u64,
+                mitigation_rate: row.get("mitigation_rate"),
+                unique_sources: row.get::<i32, _>("unique_sources") as u32,
+                ..Default::default()
             })
-            .collect();
+        })?;
 
-        Ok((events, total))
+        Ok((page, total as u64))
     }
 
     /// Record metrics from worker
@@ -383,7 +395,7 @@ impl MetricsService {
     /// Get single attack event
     #[instrument(skip(self))]
     pub async fn get_attack_event(&self, event_id: &str) -> Result<Option<AttackEvent>> {
-        let db = self.state.db()?;
+        let db = self.state.db_read()?;
 
         let row = sqlx::query(
             r#"
@@ -556,7 +568,7 @@ impl MetricsService {
         page: u32,
         page_size: u32,
     ) -> Result<(Vec<Alert>, u64)> {
-        let db = self.state.db()?;
+        let db = self.state.db_read()?;
         let offset = (page.saturating_sub(1)) * page_size;
 
         // Get total count
@@ -588,6 +600,68 @@ impl MetricsService {
         Ok((alerts, total))
     }
 
+    /// Evaluate the alerts of a backend watching a metric measured by the
+    /// gateway itself, firing or resolving them and keeping their history
+    #[instrument(skip(self))]
+    pub async fn record_alert_value(
+        &self,
+        backend_id: &str,
+        metric: &str,
+        value: f64,
+        message: &str,
+    ) -> Result<()> {
+        let db = self.state.db()?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, backend_id, name, condition, notifications, enabled, state,
+                   last_triggered, created_at, updated_at
+            FROM alerts
+            WHERE backend_id = $1 AND enabled = true
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_all(db)
+        .await?;
+
+        for alert in rows.iter().map(|row| self.row_to_alert(row)) {
+            let Some(condition) = alert.condition.as_ref().filter(|c| c.metric == metric) else {
+                continue;
+            };
+            let firing = alert.state() == AlertState::Firing;
+
+            if alert_condition_met(condition, value) && !firing {
+                sqlx::query("UPDATE alerts SET state = $2, last_triggered = NOW() WHERE id = $1")
+                    .bind(&alert.id)
+                    .bind(AlertState::Firing as i32)
+                    .execute(db)
+                    .await?;
+                sqlx::query(
+                    "INSERT INTO alert_history (alert_id, value, message) VALUES ($1, $2, $3)",
+                )
+                .bind(&alert.id)
+                .bind(value)
+                .bind(message)
+                .execute(db)
+                .await?;
+            } else if !alert_condition_met(condition, value) && firing {
+                sqlx::query("UPDATE alerts SET state = $2 WHERE id = $1")
+                    .bind(&alert.id)
+                    .bind(AlertState::Ok as i32)
+                    .execute(db)
+                    .await?;
+                sqlx::query(
+                    "UPDATE alert_history SET resolved_at = NOW() WHERE alert_id = $1 AND resolved_at IS NULL",
+                )
+                .bind(&alert.id)
+                .execute(db)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Helper to convert a database row to an Alert
     fn row_to_alert(&self, row: &sqlx::postgres::PgRow) -> Alert {
         let created_at: chrono::DateTime<chrono::Utc> = row.get("created_at");
@@ -618,3 +692,14 @@ impl Clone for MetricsService {
         }
     }
 }
+
+/// Whether a metric value meets an alert condition
+pub fn alert_condition_met(condition: &AlertCondition, value: f64) -> bool {
+    match AlertOperator::try_from(condition.operator).unwrap_or(AlertOperator::Unspecified) {
+        AlertOperator::GreaterThan => value > condition.threshold,
+        AlertOperator::LessThan => value < condition.threshold,
+        AlertOperator::Equal => (value - condition.threshold).abs() < f64::EPSILON,
+        AlertOperator::NotEqual => (value - condition.threshold).abs() >= f64::EPSILON,
+        AlertOperator::Unspecified => false,
+    }
+}
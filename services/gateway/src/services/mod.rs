@@ -1,21 +1,39 @@
 //! Service layer for the gateway
 
-use deadpool_redis::Pool as RedisPool;
+use pistonprotection_common::redis::RedisPool;
 use pistonprotection_common::{
     config::Config,
+    db::DbPools,
     redis::CacheService,
     scoring::{ScoringConfig, ScoringEngine},
 };
 use sqlx::PgPool;
 use std::sync::Arc;
 
+pub mod approval;
+pub mod audit;
 pub mod backend;
+pub mod backend_mode;
+pub mod ban_sync;
+pub mod bandwidth_quota;
 pub mod circuit_breaker;
+pub mod config_history;
 pub mod connection_pool;
+pub mod exemption;
+pub mod exposure;
 pub mod filter;
+pub mod idempotency;
 pub mod load_balancer;
 pub mod metrics;
+pub mod origin_switch;
+pub mod overview;
+pub mod provider_import;
+pub mod rate_profile;
+pub mod restart_mode;
+pub mod rule_transfer;
 pub mod scoring;
+pub mod status_page;
+pub mod trial_suspension;
 
 use circuit_breaker::{CircuitBreakerConfig, CircuitBreakerManager};
 use connection_pool::{ConnectionPoolConfig, ConnectionPoolManager};
@@ -25,6 +43,8 @@ use load_balancer::{LoadBalancerManager, LoadBalancingAlgorithm};
 #[derive(Clone)]
 pub struct AppState {
     pub db: Option<PgPool>,
+    /// Primary and read replica pools, when read routing is configured
+    pub db_pools: Option<Arc<DbPools>>,
     pub cache: Option<CacheService>,
     pub config: Arc<Config>,
     pub circuit_breakers: Arc<CircuitBreakerManager>,
@@ -58,6 +78,7 @@ impl AppState {
 
         Self {
             db,
+            db_pools: None,
             cache,
             config: Arc::new(config),
             circuit_breakers,
@@ -93,6 +114,7 @@ impl AppState {
 
         Self {
             db,
+            db_pools: None,
             cache,
             config: Arc::new(config),
             circuit_breakers,
@@ -102,6 +124,14 @@ impl AppState {
         }
     }
 
+    /// Route read-only queries through `pools`, using its primary for
+    /// everything else
+    pub fn with_db_pools(mut self, pools: Arc<DbPools>) -> Self {
+        self.db = Some(pools.primary().clone());
+        self.db_pools = Some(pools);
+        self
+    }
+
     /// Check if database is available
     pub fn has_db(&self) -> bool {
         self.db.is_some()
@@ -119,6 +149,17 @@ impl AppState {
             .ok_or_else(|| tonic::Status::unavailable("Database not configured"))
     }
 
+    /// Get a database pool for a read-only query or error
+    ///
+    /// Uses a read replica in rotation when replicas are configured, so the
+    /// query may not see writes made within the replication lag.
+    pub fn db_read(&self) -> Result<&PgPool, tonic::Status> {
+        match &self.db_pools {
+            Some(pools) => Ok(pools.reader()),
+            None => self.db(),
+        }
+    }
+
     /// Get cache service or error
     pub fn cache(&self) -> Result<&CacheService, tonic::Status> {
         self.cache
@@ -0,0 +1,473 @@
+//! Zero-downtime origin switches
+//!
+//! Switching a backend to a new origin publishes a plan to the workers (see
+//! `pistonprotection_common::origin_switch`). They route new connections to
+//! the new origin, keep established ones on the old origin and report how
+//! many are left. The monitor completes the switch, disabling the old
+//! origin, once every reporting worker drained or the grace period ended.
+
+use crate::services::AppState;
+use crate::services::backend::BackendService;
+use chrono::{DateTime, Utc};
+use pistonprotection_common::duration::bounded_duration;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::origin_switch::{
+    ACTIVE_SWITCHES_KEY, DrainReport, OriginSwitchPlan, PLAN_TTL_MARGIN, drain_report_key,
+    switch_plan_key, switch_workers_key,
+};
+use pistonprotection_common::redis::CacheService;
+use pistonprotection_proto::backend::{Origin, OriginSwitch, OriginSwitchState};
+use sqlx::Row;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+/// Grace period used when the request leaves it unset
+pub const DEFAULT_DRAIN_SECONDS: u32 = 300;
+
+/// Longest grace period
+pub const MAX_DRAIN_SECONDS: u32 = 24 * 3600;
+
+/// Time workers get to apply a plan and report before a switch can complete
+/// early
+pub const MIN_DRAIN: Duration = Duration::from_secs(15);
+
+/// How often the monitor refreshes switches in progress
+const MONITOR_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Origin a backend switches to
+#[derive(Debug, Clone)]
+pub enum SwitchTarget {
+    /// Origin already configured on the backend
+    Existing(String),
+    /// Origin added to the backend for the switch
+    New(Box<Origin>),
+}
+
+/// Origin switch service implementation
+pub struct OriginSwitchService {
+    state: AppState,
+    backends: BackendService,
+}
+
+impl OriginSwitchService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            backends: BackendService::new(state.clone()),
+            state,
+        }
+    }
+
+    fn cache(&self) -> Result<&CacheService> {
+        self.state
+            .cache
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Origin switches require Redis".to_string()))
+    }
+
+    /// Start switching a backend from one origin to another
+    ///
+    /// New connections go to the new origin right away; connections to the
+    /// old origin are kept for up to `drain_seconds`.
+    #[instrument(skip(self, target))]
+    pub async fn start(
+        &self,
+        backend_id: &str,
+        from_origin_id: &str,
+        target: SwitchTarget,
+        drain_seconds: u32,
+    ) -> Result<OriginSwitch> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+        let drain_seconds =
+            bounded_duration(drain_seconds, DEFAULT_DRAIN_SECONDS, MAX_DRAIN_SECONDS).ok_or_else(
+                || {
+                    Error::validation(format!(
+                        "Grace period cannot exceed {} seconds",
+                        MAX_DRAIN_SECONDS
+                    ))
+                },
+            )?;
+
+        if let Some(current) = self.latest(backend_id).await? {
+            if current.state() == OriginSwitchState::Draining {
+                return Err(Error::already_exists(
+                    "OriginSwitch",
+                    "backend_id",
+                    backend_id,
+                ));
+            }
+        }
+
+        let origins = self.backends.get_origins(backend_id).await?;
+        let from = origins
+            .iter()
+            .find(|o| o.id == from_origin_id)
+            .ok_or_else(|| Error::not_found("Origin", from_origin_id))?;
+        let (to_origin_id, added) = match target {
+            SwitchTarget::Existing(id) => {
+                let to = origins
+                    .iter()
+                    .find(|o| o.id == id)
+                    .ok_or_else(|| Error::not_found("Origin", &id))?;
+                if to.id == from.id {
+                    return Err(Error::validation("Cannot switch an origin to itself"));
+                }
+                if !to.enabled {
+                    return Err(Error::validation(format!("Origin {} is disabled", to.id)));
+                }
+                (id, false)
+            }
+            SwitchTarget::New(mut origin) => {
+                if origin.address.is_none() && origin.hostname.is_empty() {
+                    return Err(Error::validation("New origin needs an address or hostname"));
+                }
+                origin.enabled = true;
+                let origin = self.backends.add_origin(backend_id, *origin).await?;
+                (origin.id, true)
+            }
+        };
+
+        let id = Uuid::new_v4().to_string();
+        let started_at = Utc::now();
+        let drain_deadline = started_at + chrono::Duration::seconds(drain_seconds as i64);
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO origin_switches (
+                id, backend_id, from_origin_id, to_origin_id, state,
+                drain_seconds, started_at, drain_deadline
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(&id)
+        .bind(backend_id)
+        .bind(from_origin_id)
+        .bind(&to_origin_id)
+        .bind(OriginSwitchState::Draining as i32)
+        .bind(drain_seconds as i32)
+        .bind(started_at)
+        .bind(drain_deadline)
+        .execute(db)
+        .await;
+        if let Err(e) = inserted {
+            // Another switch started concurrently
+            if added {
+                let _ = self.backends.remove_origin(backend_id, &to_origin_id).await;
+            }
+            return Err(match e.as_database_error() {
+                Some(db_err) if db_err.is_unique_violation() => {
+                    Error::already_exists("OriginSwitch", "backend_id", backend_id)
+                }
+                _ => e.into(),
+            });
+        }
+
+        let plan = OriginSwitchPlan {
+            id: id.clone(),
+            backend_id: backend_id.to_string(),
+            from_origin_id: from_origin_id.to_string(),
+            from_address: origin_address(from),
+            from_port: from.port.min(u16::MAX as u32) as u16,
+            to_origin_id: to_origin_id.clone(),
+            drain_until: drain_deadline.timestamp(),
+        };
+        let ttl = Duration::from_secs(drain_seconds as u64) + PLAN_TTL_MARGIN;
+        cache.set(&switch_plan_key(backend_id), &plan, ttl).await?;
+        cache.sadd(ACTIVE_SWITCHES_KEY, backend_id).await?;
+
+        info!(
+            backend_id = %backend_id,
+            switch_id = %id,
+            from = %from_origin_id,
+            to = %to_origin_id,
+            drain_seconds,
+            "Started origin switch"
+        );
+
+        self.backends.invalidate_backend_cache(backend_id).await;
+        self.backends
+            .publish_backend_update(backend_id, "origin_switch_started")
+            .await;
+
+        self.latest(backend_id)
+            .await?
+            .ok_or_else(|| Error::not_found("OriginSwitch", &id))
+    }
+
+    /// Get the latest switch of a backend, refreshing its progress if it is
+    /// in progress
+    #[instrument(skip(self))]
+    pub async fn get(&self, backend_id: &str) -> Result<Option<OriginSwitch>> {
+        match self.latest(backend_id).await? {
+            Some(switch) if switch.state() == OriginSwitchState::Draining => {
+                self.refresh(switch).await.map(Some)
+            }
+            other => Ok(other),
+        }
+    }
+
+    /// Cancel the switch in progress: the old origin gets new connections
+    /// again. The new origin is left in place.
+    #[instrument(skip(self))]
+    pub async fn cancel(&self, backend_id: &str) -> Result<OriginSwitch> {
+        let switch = self
+            .latest(backend_id)
+            .await?
+            .filter(|s| s.state() == OriginSwitchState::Draining)
+            .ok_or_else(|| Error::validation("No origin switch in progress"))?;
+
+        self.finish(&switch, OriginSwitchState::Cancelled).await?;
+        info!(backend_id = %backend_id, switch_id = %switch.id, "Cancelled origin switch");
+
+        self.latest(backend_id)
+            .await?
+            .ok_or_else(|| Error::not_found("OriginSwitch", &switch.id))
+    }
+
+    /// Sum the drain reports of a switch in progress, completing it once
+    /// drained
+    async fn refresh(&self, switch: OriginSwitch) -> Result<OriginSwitch> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+
+        let mut reports = Vec::new();
+        for worker_id in cache.smembers(&switch_workers_key(&switch.id)).await? {
+            if let Some(report) = cache
+                .get::<DrainReport>(&drain_report_key(&switch.id, &worker_id))
+                .await?
+            {
+                reports.push(report);
+            }
+        }
+        let (remaining, workers) = drain_totals(&reports);
+
+        let now = Utc::now();
+        let started_at = timestamp(&switch.started_at);
+        let deadline = timestamp(&switch.drain_deadline);
+        if drain_complete(started_at, deadline, remaining, workers, now) {
+            self.finish(&switch, OriginSwitchState::Completed).await?;
+            info!(
+                backend_id = %switch.backend_id,
+                switch_id = %switch.id,
+                remaining_connections = remaining,
+                "Completed origin switch"
+            );
+        } else {
+            sqlx::query(
+                r#"
+                UPDATE origin_switches
+                SET remaining_connections = $2, workers_reporting = $3
+                WHERE id = $1
+                "#,
+            )
+            .bind(&switch.id)
+            .bind(remaining as i64)
+            .bind(workers as i32)
+            .execute(db)
+            .await?;
+        }
+
+        self.latest(&switch.backend_id)
+            .await?
+            .ok_or_else(|| Error::not_found("OriginSwitch", &switch.id))
+    }
+
+    /// End a switch; a completed switch disables the old origin
+    async fn finish(&self, switch: &OriginSwitch, state: OriginSwitchState) -> Result<()> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+
+        let mut tx = db.begin().await?;
+        if state == OriginSwitchState::Completed {
+            sqlx::query(
+                "UPDATE backend_origins SET enabled = false, updated_at = NOW() WHERE id = $1 AND backend_id = $2",
+            )
+            .bind(&switch.from_origin_id)
+            .bind(&switch.backend_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            "UPDATE origin_switches SET state = $2, completed_at = NOW() WHERE id = $1 AND state = $3",
+        )
+        .bind(&switch.id)
+        .bind(state as i32)
+        .bind(OriginSwitchState::Draining as i32)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        cache.delete(&switch_plan_key(&switch.backend_id)).await?;
+        cache.srem(ACTIVE_SWITCHES_KEY, &switch.backend_id).await?;
+        let _ = cache.delete(&switch_workers_key(&switch.id)).await;
+
+        self.backends
+            .invalidate_backend_cache(&switch.backend_id)
+            .await;
+        let event = match state {
+            OriginSwitchState::Completed => "origin_switch_completed",
+            _ => "origin_switch_cancelled",
+        };
+        self.backends
+            .publish_backend_update(&switch.backend_id, event)
+            .await;
+        Ok(())
+    }
+
+    /// Latest switch of a backend as stored
+    async fn latest(&self, backend_id: &str) -> Result<Option<OriginSwitch>> {
+        let db = self.state.db()?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT id, backend_id, from_origin_id, to_origin_id, state, drain_seconds,
+                   remaining_connections, workers_reporting, started_at, drain_deadline,
+                   completed_at
+            FROM origin_switches
+            WHERE backend_id = $1
+            ORDER BY started_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(row.map(|row| {
+            let started_at: DateTime<Utc> = row.get("started_at");
+            let drain_deadline: DateTime<Utc> = row.get("drain_deadline");
+            let completed_at: Option<DateTime<Utc>> = row.get("completed_at");
+            let state = row.get::<i32, _>("state");
+
+            let progress = if state == OriginSwitchState::Completed as i32 {
+                1.0
+            } else {
+                switch_progress(
+                    started_at,
+                    drain_deadline,
+                    completed_at.unwrap_or_else(Utc::now),
+                )
+            };
+
+            OriginSwitch {
+                id: row.get("id"),
+                backend_id: row.get("backend_id"),
+                from_origin_id: row.get("from_origin_id"),
+                to_origin_id: row.get("to_origin_id"),
+                state,
+                drain_seconds: row.get::<i32, _>("drain_seconds") as u32,
+                remaining_connections: row.get::<i64, _>("remaining_connections") as u64,
+                workers_reporting: row.get::<i32, _>("workers_reporting") as u32,
+                progress,
+                started_at: Some(started_at.into()),
+                drain_deadline: Some(drain_deadline.into()),
+                completed_at: completed_at.map(|t| t.into()),
+            }
+        }))
+    }
+
+    /// Backends with a switch in progress
+    async fn draining_backends(&self) -> Result<Vec<String>> {
+        let db = self.state.db()?;
+
+        let rows: Vec<(String,)> =
+            sqlx::query_as("SELECT backend_id FROM origin_switches WHERE state = $1")
+                .bind(OriginSwitchState::Draining as i32)
+                .fetch_all(db)
+                .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+}
+
+/// Spawn the monitor completing switches once drained
+pub fn spawn_monitor(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() || state.cache.is_none() {
+        info!("Origin switch monitor disabled");
+        return None;
+    }
+
+    let service = OriginSwitchService::new(state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    let backend_ids = match service.draining_backends().await {
+                        Ok(ids) => ids,
+                        Err(e) => {
+                            warn!(error = %e, "Failed to list origin switches in progress");
+                            continue;
+                        }
+                    };
+                    for backend_id in backend_ids {
+                        if let Err(e) = service.get(&backend_id).await {
+                            warn!(error = %e, backend_id = %backend_id, "Failed to refresh origin switch");
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Address workers count connections to: the IP address of the origin, or
+/// its hostname
+fn origin_address(origin: &Origin) -> String {
+    origin
+        .address
+        .as_ref()
+        .and_then(|addr| IpAddr::try_from(addr).ok())
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| origin.hostname.clone())
+}
+
+fn timestamp(ts: &Option<pistonprotection_proto::common::Timestamp>) -> DateTime<Utc> {
+    ts.as_ref().map(DateTime::from).unwrap_or_else(Utc::now)
+}
+
+/// Connections left on the old origin and number of workers reporting
+pub fn drain_totals(reports: &[DrainReport]) -> (u64, u32) {
+    (
+        reports.iter().map(|r| r.active_connections).sum(),
+        reports.len() as u32,
+    )
+}
+
+/// Whether a switch can complete: the grace period ended, or the workers
+/// reporting after the settle time have no connections left
+pub fn drain_complete(
+    started_at: DateTime<Utc>,
+    deadline: DateTime<Utc>,
+    remaining: u64,
+    workers: u32,
+    now: DateTime<Utc>,
+) -> bool {
+    if now >= deadline {
+        return true;
+    }
+    let settled = (now - started_at).to_std().unwrap_or_default() >= MIN_DRAIN;
+    settled && workers > 0 && remaining == 0
+}
+
+/// Share of the grace period elapsed
+pub fn switch_progress(
+    started_at: DateTime<Utc>,
+    deadline: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> f64 {
+    let total = (deadline - started_at).num_milliseconds();
+    if total <= 0 {
+        return 1.0;
+    }
+    ((now - started_at).num_milliseconds() as f64 / total as f64).clamp(0.0, 1.0)
+}
@@ -0,0 +1,531 @@
+//! Dashboard overview
+//!
+//! The landing page of the dashboard shows the status of every backend of
+//! an organization, the attacks in progress, a summary of the last 24 hours
+//! of traffic, the bandwidth quota usage and, while the organization is on a
+//! trial or its trial ended unpaid, how the trial stands. `/api/v1/overview`
+//! assembles them in one round trip, with one query per section rather than
+//! one call per backend.
+//!
+//! Sections are filtered by the caller's role in the organization: viewers
+//! see backend statuses, incidents and the trial, members also see traffic,
+//! owners and admins also see quota usage. Platform admins see everything.
+//! Sections a caller may not see are left out of the response.
+
+use crate::middleware::auth::{AuthContext, AuthMethod};
+use crate::services::AppState;
+use crate::services::backend::origin_health;
+use crate::services::bandwidth_quota::BandwidthQuotaService;
+use chrono::{DateTime, Duration, Utc};
+use pistonprotection_common::bandwidth_quota::usage_percent;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::backend::{BackendStatus, BandwidthQuotaAction};
+use pistonprotection_proto::common::HealthStatus;
+use pistonprotection_proto::metrics::AttackSeverity;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use tracing::{instrument, warn};
+
+/// Most backends listed
+pub const MAX_BACKENDS: i64 = 500;
+
+/// Most incidents listed
+pub const MAX_INCIDENTS: i64 = 50;
+
+/// Window of the traffic summary
+pub const TRAFFIC_WINDOW: Duration = Duration::hours(24);
+
+/// Role of a user in an organization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrgRole {
+    Viewer,
+    Member,
+    Admin,
+    Owner,
+}
+
+impl OrgRole {
+    /// Role from its `organization_role` database value
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "viewer" => Some(OrgRole::Viewer),
+            "member" => Some(OrgRole::Member),
+            "admin" => Some(OrgRole::Admin),
+            "owner" => Some(OrgRole::Owner),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrgRole::Viewer => "viewer",
+            OrgRole::Member => "member",
+            OrgRole::Admin => "admin",
+            OrgRole::Owner => "owner",
+        }
+    }
+}
+
+/// Sections of the overview a caller may see
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverviewAccess {
+    pub backends: bool,
+    pub incidents: bool,
+    pub traffic: bool,
+    pub quota: bool,
+    pub trial: bool,
+}
+
+impl OverviewAccess {
+    /// Sections visible to a platform role and organization role
+    pub fn for_roles(platform_role: &str, org_role: Option<OrgRole>) -> Self {
+        if platform_role == "admin" {
+            return Self::full();
+        }
+        let role = org_role.unwrap_or(OrgRole::Viewer);
+        Self {
+            backends: org_role.is_some(),
+            incidents: org_role.is_some(),
+            traffic: org_role.is_some() && role >= OrgRole::Member,
+            quota: org_role.is_some() && role >= OrgRole::Admin,
+            trial: org_role.is_some(),
+        }
+    }
+
+    fn full() -> Self {
+        Self {
+            backends: true,
+            incidents: true,
+            traffic: true,
+            quota: true,
+            trial: true,
+        }
+    }
+
+    fn any(&self) -> bool {
+        self.backends || self.incidents || self.traffic || self.quota || self.trial
+    }
+}
+
+/// Current status of a backend
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendSummary {
+    pub id: String,
+    pub name: String,
+    /// healthy, degraded or unhealthy
+    pub health: String,
+    pub healthy_origins: u32,
+    pub total_origins: u32,
+    pub under_attack: bool,
+    pub requests_per_second: u64,
+}
+
+/// Attack in progress
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveIncident {
+    pub id: String,
+    pub backend_id: String,
+    pub backend_name: String,
+    pub attack_type: String,
+    /// low, medium, high or critical
+    pub severity: String,
+    pub started_at: DateTime<Utc>,
+    pub peak_pps: u64,
+}
+
+/// Traffic of the organization over the last 24 hours
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TrafficSummary {
+    pub peak_requests_per_second: u64,
+    pub average_requests_per_second: f64,
+    pub attacks: u64,
+    pub packets_mitigated: u64,
+}
+
+/// Bandwidth quota usage in the current period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuotaUsage {
+    pub limit_bytes: u64,
+    pub used_bytes: u64,
+    pub used_percent: u64,
+    pub period_end: Option<DateTime<Utc>>,
+    /// Action in effect, unset while under the limit
+    pub applied_action: Option<String>,
+}
+
+/// Trial of the organization
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrialStatus {
+    /// trialing, ended, grace, downgraded or suspended
+    pub state: String,
+    pub ends_at: DateTime<Utc>,
+    /// Days left of the trial, a started day counting in full
+    pub days_remaining: u64,
+    /// End of the grace period after an unpaid trial
+    pub grace_ends_at: Option<DateTime<Utc>>,
+    /// Protection of the backends is suspended until the subscription is paid
+    pub protection_suspended: bool,
+}
+
+/// Landing data of the dashboard
+#[derive(Debug, Clone, Serialize)]
+pub struct Overview {
+    pub organization_id: String,
+    /// Role of the caller in the organization, unset for platform admins
+    /// who are not members
+    pub role: Option<OrgRole>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backends: Option<Vec<BackendSummary>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incidents: Option<Vec<ActiveIncident>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traffic: Option<TrafficSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trial: Option<TrialStatus>,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Query parameters of the overview
+#[derive(Debug, Default, Deserialize)]
+pub struct OverviewQuery {
+    /// Organization shown, the caller's first organization when unset
+    pub organization_id: Option<String>,
+}
+
+/// Overview service implementation
+pub struct OverviewService {
+    state: AppState,
+}
+
+impl OverviewService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Overview of an organization, as seen by the caller
+    #[instrument(skip(self, context), fields(user_id = %context.user_id))]
+    pub async fn get(
+        &self,
+        context: &AuthContext,
+        organization_id: Option<&str>,
+    ) -> Result<Overview> {
+        let organization_id = organization_id
+            .or(context.organizations.first().map(String::as_str))
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| Error::Validation("organization_id is required".to_string()))?;
+
+        // Keys bound to an organization only see that organization
+        if matches!(context.auth_method, AuthMethod::ApiKey)
+            && !context.organizations.is_empty()
+            && !context.organizations.iter().any(|id| id == organization_id)
+        {
+            return Err(Error::Forbidden(
+                "API key is not valid for this organization".to_string(),
+            ));
+        }
+
+        let (role, access) = if context.support_grant_id.is_some() {
+            // Support tokens read everything of the organization that granted them
+            if !context.organizations.iter().any(|id| id == organization_id) {
+                return Err(Error::Forbidden(
+                    "Support access is not granted for this organization".to_string(),
+                ));
+            }
+            (None, OverviewAccess::full())
+        } else {
+            let role =
+                member_role(self.state.db_read()?, &context.user_id, organization_id).await?;
+            (role, OverviewAccess::for_roles(&context.role, role))
+        };
+        if !access.any() {
+            return Err(Error::Forbidden(
+                "Not a member of this organization".to_string(),
+            ));
+        }
+
+        let (backends, incidents, traffic, quota, trial) = tokio::try_join!(
+            section(access.backends, self.backends(organization_id)),
+            section(access.incidents, self.incidents(organization_id)),
+            section(access.traffic, self.traffic(organization_id)),
+            section(access.quota, self.quota(organization_id)),
+            section(access.trial, self.trial(organization_id)),
+        )?;
+
+        Ok(Overview {
+            organization_id: organization_id.to_string(),
+            role,
+            backends,
+            incidents,
+            traffic,
+            quota,
+            trial: trial.flatten(),
+            generated_at: Utc::now(),
+        })
+    }
+
+    /// Status of every backend, with live traffic from the status cache
+    async fn backends(&self, organization_id: &str) -> Result<Vec<BackendSummary>> {
+        let db = self.state.db_read()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                b.id,
+                b.name,
+                COUNT(o.id) FILTER (WHERE o.enabled = true) AS total_origins,
+                COUNT(o.id) FILTER (WHERE o.enabled = true AND o.health_status = 1)
+                    AS healthy_origins,
+                EXISTS (
+                    SELECT 1 FROM attack_events a
+                    WHERE a.backend_id = b.id AND a.ended_at IS NULL
+                ) AS under_attack
+            FROM backends b
+            LEFT JOIN backend_origins o ON o.backend_id = b.id
+            WHERE b.organization_id = $1
+            GROUP BY b.id, b.name
+            ORDER BY b.name
+            LIMIT $2
+            "#,
+        )
+        .bind(organization_id)
+        .bind(MAX_BACKENDS)
+        .fetch_all(db)
+        .await?;
+
+        let mut backends: Vec<BackendSummary> = rows
+            .iter()
+            .map(|row| {
+                let total: i64 = row.get("total_origins");
+                let healthy: i64 = row.get("healthy_origins");
+                BackendSummary {
+                    id: row.get("id"),
+                    name: row.get("name"),
+                    health: health_name(origin_health(healthy, total)),
+                    healthy_origins: healthy as u32,
+                    total_origins: total as u32,
+                    under_attack: row.get("under_attack"),
+                    requests_per_second: 0,
+                }
+            })
+            .collect();
+
+        if let Some(cache) = &self.state.cache {
+            let keys: Vec<String> = backends
+                .iter()
+                .map(|backend| format!("backend_status:{}", backend.id))
+                .collect();
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+            match cache.get_many::<BackendStatus>(&keys).await {
+                Ok(statuses) => {
+                    for (backend, status) in backends.iter_mut().zip(statuses) {
+                        if let Some(status) = status {
+                            apply_live_status(backend, &status);
+                        }
+                    }
+                }
+                Err(e) => warn!(error = %e, "Failed to read cached backend statuses"),
+            }
+        }
+
+        Ok(backends)
+    }
+
+    /// Attacks in progress, newest first
+    async fn incidents(&self, organization_id: &str) -> Result<Vec<ActiveIncident>> {
+        let db = self.state.db_read()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT a.id, a.backend_id, b.name AS backend_name, a.attack_type, a.severity,
+                   a.started_at, a.peak_pps
+            FROM attack_events a
+            JOIN backends b ON b.id = a.backend_id
+            WHERE b.organization_id = $1 AND a.ended_at IS NULL
+            ORDER BY a.started_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(organization_id)
+        .bind(MAX_INCIDENTS)
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ActiveIncident {
+                id: row.get("id"),
+                backend_id: row.get("backend_id"),
+                backend_name: row.get("backend_name"),
+                attack_type: row.get("attack_type"),
+                severity: severity_name(row.get::<Option<i32>, _>("severity").unwrap_or(0)),
+                started_at: row.get("started_at"),
+                peak_pps: row.get::<Option<i64>, _>("peak_pps").unwrap_or(0).max(0) as u64,
+            })
+            .collect())
+    }
+
+    /// Traffic and attacks of the last 24 hours
+    async fn traffic(&self, organization_id: &str) -> Result<TrafficSummary> {
+        let db = self.state.db_read()?;
+        let since = Utc::now() - TRAFFIC_WINDOW;
+
+        let (peak, average): (Option<i64>, Option<f64>) = sqlx::query_as(
+            r#"
+            SELECT MAX(t.requests_per_second), AVG(t.requests_per_second)::DOUBLE PRECISION
+            FROM traffic_metrics t
+            JOIN backends b ON b.id = t.backend_id
+            WHERE b.organization_id = $1 AND t.timestamp >= $2
+            "#,
+        )
+        .bind(organization_id)
+        .bind(since)
+        .fetch_one(db)
+        .await?;
+
+        let (attacks, mitigated): (i64, Option<i64>) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*), SUM(a.packets_mitigated)::BIGINT
+            FROM attack_events a
+            JOIN backends b ON b.id = a.backend_id
+            WHERE b.organization_id = $1 AND (a.ended_at IS NULL OR a.ended_at >= $2)
+            "#,
+        )
+        .bind(organization_id)
+        .bind(since)
+        .fetch_one(db)
+        .await?;
+
+        Ok(TrafficSummary {
+            peak_requests_per_second: peak.unwrap_or(0).max(0) as u64,
+            average_requests_per_second: average.unwrap_or(0.0),
+            attacks: attacks.max(0) as u64,
+            packets_mitigated: mitigated.unwrap_or(0).max(0) as u64,
+        })
+    }
+
+    /// Bandwidth quota usage in the current period
+    async fn quota(&self, organization_id: &str) -> Result<QuotaUsage> {
+        let quota = BandwidthQuotaService::new(self.state.clone())
+            .get(organization_id)
+            .await?;
+        let applied = BandwidthQuotaAction::try_from(quota.applied_action)
+            .unwrap_or(BandwidthQuotaAction::Unspecified);
+
+        Ok(QuotaUsage {
+            limit_bytes: quota.limit_bytes,
+            used_bytes: quota.used_bytes,
+            used_percent: usage_percent(quota.used_bytes, quota.limit_bytes),
+            period_end: quota.period_end.as_ref().map(DateTime::from),
+            applied_action: (applied != BandwidthQuotaAction::Unspecified)
+                .then(|| enum_name(applied.as_str_name(), "BANDWIDTH_QUOTA_ACTION_")),
+        })
+    }
+
+    /// Trial of the organization, unset without one or once it converted
+    async fn trial(&self, organization_id: &str) -> Result<Option<TrialStatus>> {
+        let db = self.state.db_read()?;
+        let row = sqlx::query(
+            r#"
+            SELECT trial_ends_at, trial_outcome::text AS trial_outcome, trial_grace_ends_at,
+                   protection_suspended_at IS NOT NULL AS protection_suspended
+            FROM subscriptions
+            WHERE organization_id = $1
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(row.and_then(|row| {
+            trial_status(
+                row.get("trial_ends_at"),
+                row.get::<Option<String>, _>("trial_outcome").as_deref(),
+                row.get("trial_grace_ends_at"),
+                row.get("protection_suspended"),
+                Utc::now(),
+            )
+        }))
+    }
+}
+
+/// Role of a user in an organization, unset if not a member
+pub(crate) async fn member_role(
+    db: &PgPool,
+    user_id: &str,
+    organization_id: &str,
+) -> Result<Option<OrgRole>> {
+    let role: Option<String> = sqlx::query_scalar(
+        r#"
+        SELECT role::text FROM organization_members
+        WHERE user_id = $1 AND organization_id = $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(organization_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(role.as_deref().and_then(OrgRole::parse))
+}
+
+/// Load a section only if the caller may see it
+async fn section<T>(visible: bool, load: impl Future<Output = Result<T>>) -> Result<Option<T>> {
+    if visible {
+        load.await.map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Trial shown for a subscription, unset without a trial or once it
+/// converted to a paid subscription
+pub fn trial_status(
+    ends_at: Option<DateTime<Utc>>,
+    outcome: Option<&str>,
+    grace_ends_at: Option<DateTime<Utc>>,
+    protection_suspended: bool,
+    now: DateTime<Utc>,
+) -> Option<TrialStatus> {
+    let ends_at = ends_at?;
+    let state = match outcome {
+        Some("converted") => return None,
+        Some(outcome) => outcome.to_string(),
+        // Ended, waiting for the first check to start the grace period
+        None if ends_at <= now => "ended".to_string(),
+        None => "trialing".to_string(),
+    };
+    let seconds_left = (ends_at - now).num_seconds().max(0) as u64;
+
+    Some(TrialStatus {
+        state,
+        ends_at,
+        days_remaining: seconds_left.div_ceil(86400),
+        grace_ends_at,
+        protection_suspended,
+    })
+}
+
+/// Overlay the live status reported by workers
+pub fn apply_live_status(backend: &mut BackendSummary, status: &BackendStatus) {
+    backend.requests_per_second = status.requests_per_second;
+    backend.under_attack |= status.under_attack;
+}
+
+/// Name of a health status, as shown on the dashboard
+pub fn health_name(health: HealthStatus) -> String {
+    enum_name(health.as_str_name(), "HEALTH_STATUS_")
+}
+
+/// Name of a stored attack severity
+pub fn severity_name(severity: i32) -> String {
+    let severity = AttackSeverity::try_from(severity).unwrap_or(AttackSeverity::Unspecified);
+    enum_name(severity.as_str_name(), "ATTACK_SEVERITY_")
+}
+
+fn enum_name(name: &str, prefix: &str) -> String {
+    name.strip_prefix(prefix)
+        .unwrap_or(name)
+        .to_ascii_lowercase()
+}
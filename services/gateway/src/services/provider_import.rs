@@ -0,0 +1,891 @@
+//! Import of configuration exported from other providers
+//!
+//! Cloudflare IP access rules, rate limits and custom rules, and a generic
+//! list of IP, country and ASN entries, are translated into rows of the rule
+//! file format of [`super::rule_transfer`] and imported like any rule file,
+//! so they go through the same validation and rule compiler.
+//!
+//! Single-value entries such as IP access rules are merged into one rule per
+//! action and kind of value. Provider features without an equivalent are
+//! dropped and reported; entries that cannot be translated at all are
+//! reported as errors, numbered by their position in the export.
+
+use super::AppState;
+use super::rule_transfer::{ImportReport, RowError, RuleRow, RuleTransferService};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::filter::{ConfigProvider, HttpMatch, L7Match, l7_match};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tracing::{info, instrument};
+
+/// Most alternatives an `or` of a custom rule expression may expand to
+const MAX_ALTERNATIVES: usize = 32;
+
+/// Keys under which Cloudflare exports nest their entries
+const CLOUDFLARE_SECTIONS: &[&str] = &[
+    "result",
+    "rules",
+    "access_rules",
+    "rate_limits",
+    "firewall_rules",
+];
+
+/// Rows translated from a provider export
+#[derive(Debug, Default)]
+pub struct Translation {
+    /// Rows numbered by the first export entry they come from
+    pub rows: Vec<(usize, std::result::Result<RuleRow, RowError>)>,
+    pub total_entries: usize,
+    /// Dropped provider features
+    pub unsupported: Vec<String>,
+}
+
+/// Kind of value of a single-value entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ListKind {
+    Network,
+    Country,
+    Asn,
+}
+
+impl ListKind {
+    fn plural(self) -> &'static str {
+        match self {
+            ListKind::Network => "networks",
+            ListKind::Country => "countries",
+            ListKind::Asn => "ASNs",
+        }
+    }
+
+    fn apply(self, row: &mut RuleRow, value: String) {
+        match self {
+            ListKind::Network => row.source_ips.push(value),
+            ListKind::Country => row.source_countries.push(value),
+            ListKind::Asn => row.source_asns.push(value),
+        }
+    }
+}
+
+/// Builds the rows of one export
+struct Translator {
+    provider: &'static str,
+    out: Translation,
+    /// Merged single-value entries by kind, action and enabled state, with
+    /// the number of the first entry and the entry count
+    lists: BTreeMap<(ListKind, &'static str, bool), (usize, RuleRow, usize)>,
+}
+
+impl Translator {
+    fn new(provider: &'static str) -> Self {
+        Self {
+            provider,
+            out: Translation::default(),
+            lists: BTreeMap::new(),
+        }
+    }
+
+    fn error(&mut self, entry: usize, field: &'static str, message: impl Into<String>) {
+        self.out
+            .rows
+            .push((entry, Err(RowError::new(field, message))));
+    }
+
+    fn unsupported(&mut self, entry: usize, message: impl std::fmt::Display) {
+        self.out
+            .unsupported
+            .push(format!("Entry {}: {}", entry, message));
+    }
+
+    /// Add a single-value entry, validating the value on its own so one bad
+    /// entry does not fail the merged rule
+    fn list_entry(
+        &mut self,
+        entry: usize,
+        kind: ListKind,
+        value: &str,
+        action: &'static str,
+        enabled: bool,
+    ) {
+        let value = value.trim().to_string();
+        let mut single = RuleRow {
+            name: "entry".to_string(),
+            action: action.to_string(),
+            ..Default::default()
+        };
+        kind.apply(&mut single, value.clone());
+        if let Err(mut e) = single.into_rule() {
+            e.field = "value";
+            self.out.rows.push((entry, Err(e)));
+            return;
+        }
+
+        let provider = self.provider;
+        let (_, row, count) = self
+            .lists
+            .entry((kind, action, enabled))
+            .or_insert_with(|| {
+                (
+                    entry,
+                    RuleRow {
+                        action: action.to_string(),
+                        enabled,
+                        priority: u32::try_from(entry).unwrap_or(u32::MAX),
+                        ..Default::default()
+                    },
+                    0,
+                )
+            });
+        kind.apply(row, value);
+        *count += 1;
+        row.name = format!("{}: {} {} {}", provider, action, count, kind.plural());
+        row.description = format!("Imported from {} {} entries", count, provider);
+    }
+
+    fn finish(mut self) -> Translation {
+        for (_, (entry, row, _)) in std::mem::take(&mut self.lists) {
+            self.out.rows.push((entry, Ok(row)));
+        }
+        self.out.rows.sort_by_key(|(entry, _)| *entry);
+        self.out
+    }
+}
+
+/// Translate a provider export into rule rows
+pub fn translate(provider: ConfigProvider, data: &[u8]) -> Result<Translation> {
+    let value: Value = serde_json::from_slice(data)
+        .map_err(|e| Error::validation(format!("Expected a JSON export: {}", e)))?;
+
+    let translation = match provider {
+        ConfigProvider::Cloudflare => {
+            let mut entries = Vec::new();
+            cloudflare_entries(value, &mut entries);
+            let mut translator = Translator::new("Cloudflare");
+            translator.out.total_entries = entries.len();
+            for (i, entry) in entries.into_iter().enumerate() {
+                cloudflare_entry(&mut translator, i + 1, entry);
+            }
+            translator.finish()
+        }
+        ConfigProvider::Generic => {
+            let Value::Array(entries) = value else {
+                return Err(Error::validation("Expected a JSON array of entries"));
+            };
+            let mut translator = Translator::new("Imported");
+            translator.out.total_entries = entries.len();
+            for (i, entry) in entries.into_iter().enumerate() {
+                generic_entry(&mut translator, i + 1, entry);
+            }
+            translator.finish()
+        }
+        ConfigProvider::Unspecified => return Err(Error::validation("A provider is required")),
+    };
+
+    if translation.total_entries > super::rule_transfer::MAX_IMPORT_ROWS {
+        return Err(Error::validation(format!(
+            "Cannot import more than {} entries at once",
+            super::rule_transfer::MAX_IMPORT_ROWS
+        )));
+    }
+    Ok(translation)
+}
+
+/// Flatten API envelopes, rulesets and section objects into their entries
+fn cloudflare_entries(value: Value, out: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                cloudflare_entries(item, out);
+            }
+        }
+        Value::Object(mut map) => {
+            let sections: Vec<Value> = CLOUDFLARE_SECTIONS
+                .iter()
+                .filter_map(|key| map.remove(*key))
+                .filter(|v| v.is_array() || v.is_object())
+                .collect();
+            if sections.is_empty() {
+                out.push(Value::Object(map));
+            }
+            for section in sections {
+                cloudflare_entries(section, out);
+            }
+        }
+        other => out.push(other),
+    }
+}
+
+#[derive(Deserialize)]
+struct AccessRule {
+    mode: String,
+    configuration: AccessTarget,
+    #[serde(default)]
+    paused: bool,
+}
+
+#[derive(Deserialize)]
+struct AccessTarget {
+    target: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct LegacyRateLimit {
+    #[serde(default)]
+    disabled: bool,
+    #[serde(default)]
+    description: String,
+    #[serde(rename = "match", default)]
+    matches: LegacyRateLimitMatch,
+    threshold: u64,
+    period: u64,
+    action: LegacyRateLimitAction,
+}
+
+#[derive(Default, Deserialize)]
+struct LegacyRateLimitMatch {
+    #[serde(default)]
+    request: LegacyRequestMatch,
+    #[serde(default)]
+    response: Option<Value>,
+}
+
+#[derive(Default, Deserialize)]
+struct LegacyRequestMatch {
+    #[serde(default)]
+    methods: Vec<String>,
+    #[serde(default)]
+    schemes: Vec<String>,
+    #[serde(default)]
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct LegacyRateLimitAction {
+    mode: String,
+    #[serde(default)]
+    timeout: u64,
+}
+
+/// Custom, rate limiting or legacy firewall rule
+#[derive(Deserialize)]
+struct ExpressionRule {
+    action: String,
+    #[serde(default)]
+    expression: String,
+    #[serde(default)]
+    filter: Option<FirewallFilter>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    paused: bool,
+    #[serde(default)]
+    ratelimit: Option<RulesetRateLimit>,
+}
+
+#[derive(Deserialize)]
+struct FirewallFilter {
+    expression: String,
+}
+
+#[derive(Deserialize)]
+struct RulesetRateLimit {
+    #[serde(default)]
+    characteristics: Vec<String>,
+    period: u64,
+    #[serde(default)]
+    requests_per_period: u64,
+    #[serde(default)]
+    mitigation_timeout: u64,
+    #[serde(default)]
+    counting_expression: String,
+}
+
+fn cloudflare_entry(t: &mut Translator, entry: usize, value: Value) {
+    let has = |key: &str| value.get(key).is_some();
+    if has("configuration") {
+        match serde_json::from_value::<AccessRule>(value) {
+            Ok(rule) => access_rule(t, entry, rule),
+            Err(e) => t.error(entry, "", format!("Invalid IP access rule: {}", e)),
+        }
+    } else if has("threshold") {
+        match serde_json::from_value::<LegacyRateLimit>(value) {
+            Ok(rule) => legacy_rate_limit(t, entry, rule),
+            Err(e) => t.error(entry, "", format!("Invalid rate limit: {}", e)),
+        }
+    } else if has("expression") || has("filter") {
+        match serde_json::from_value::<ExpressionRule>(value) {
+            Ok(rule) => expression_rule(t, entry, rule),
+            Err(e) => t.error(entry, "", format!("Invalid rule: {}", e)),
+        }
+    } else {
+        t.error(entry, "", "Unrecognized Cloudflare entry");
+    }
+}
+
+fn access_rule(t: &mut Translator, entry: usize, rule: AccessRule) {
+    let Some(action) = cloudflare_action(&rule.mode) else {
+        t.unsupported(entry, format!("IP access rule mode '{}'", rule.mode));
+        return;
+    };
+    let value = rule.configuration.value;
+    let kind = match rule.configuration.target.as_str() {
+        "ip" | "ip6" | "ip_range" => ListKind::Network,
+        "country" if matches!(value.as_str(), "T1" | "XX") => {
+            t.unsupported(
+                entry,
+                format!("pseudo-country '{}' has no equivalent", value),
+            );
+            return;
+        }
+        "country" => ListKind::Country,
+        "asn" => ListKind::Asn,
+        target => {
+            t.error(entry, "target", format!("Unknown target '{}'", target));
+            return;
+        }
+    };
+    let value = if kind == ListKind::Asn && !value.to_ascii_uppercase().starts_with("AS") {
+        format!("AS{}", value)
+    } else {
+        value
+    };
+    t.list_entry(entry, kind, &value, action, !rule.paused);
+}
+
+/// Rule action of a Cloudflare rule action or access rule mode
+fn cloudflare_action(mode: &str) -> Option<&'static str> {
+    match mode {
+        "block" => Some("drop"),
+        "whitelist" | "allow" => Some("allow"),
+        "challenge" | "js_challenge" | "managed_challenge" => Some("challenge"),
+        "log" | "simulate" => Some("log"),
+        _ => None,
+    }
+}
+
+fn legacy_rate_limit(t: &mut Translator, entry: usize, rule: LegacyRateLimit) {
+    let action = match rule.action.mode.as_str() {
+        "simulate" => "log",
+        "ban" | "challenge" | "js_challenge" | "managed_challenge" => "rate_limit",
+        mode => {
+            t.unsupported(entry, format!("rate limit action '{}'", mode));
+            return;
+        }
+    };
+    if rule.action.timeout > 0 {
+        t.unsupported(
+            entry,
+            format!(
+                "the {}s mitigation timeout; clients are limited only while over the rate",
+                rule.action.timeout
+            ),
+        );
+    }
+    if rule.matches.response.is_some() {
+        t.unsupported(entry, "matching on origin responses");
+    }
+
+    let request = rule.matches.request;
+    let mut http = HttpMatch {
+        methods: request
+            .methods
+            .into_iter()
+            .filter(|m| m != "_ALL_")
+            .collect(),
+        require_tls: request.schemes.len() == 1 && request.schemes[0] == "HTTPS",
+        ..Default::default()
+    };
+    let url = request.url.trim_start_matches("http://");
+    let url = url.trim_start_matches("https://");
+    let (host, path) = match url.find('/') {
+        Some(i) => (&url[..i], &url[i..]),
+        None => (url, ""),
+    };
+    if !host.is_empty() && host != "*" {
+        http.hosts.push(host.to_string());
+    }
+    if !path.is_empty() && path != "/*" && path != "*" {
+        http.paths.push(path.to_string());
+    }
+
+    let mut row = rate_limited_row(rule.threshold, rule.period);
+    row.name = rule_name(t, entry, &rule.description);
+    row.description = rule.description;
+    row.action = action.to_string();
+    row.enabled = !rule.disabled;
+    row.priority = u32::try_from(entry).unwrap_or(u32::MAX);
+    set_http(&mut row, http);
+    t.out.rows.push((entry, Ok(row)));
+}
+
+fn expression_rule(t: &mut Translator, entry: usize, rule: ExpressionRule) {
+    let expression = match rule.filter {
+        Some(filter) if rule.expression.is_empty() => filter.expression,
+        _ => rule.expression,
+    };
+    let action = match (rule.action.as_str(), &rule.ratelimit) {
+        ("log", _) => "log",
+        (_, Some(_)) => "rate_limit",
+        ("skip" | "bypass", None) => {
+            t.unsupported(entry, "skip actions are imported as allow rules");
+            "allow"
+        }
+        (action, None) => match cloudflare_action(action) {
+            Some(action) => action,
+            None => {
+                t.unsupported(entry, format!("rule action '{}'", action));
+                return;
+            }
+        },
+    };
+
+    let alternatives = match parse_expression(&expression) {
+        Ok(alternatives) => alternatives,
+        Err(e) => {
+            t.unsupported(entry, format!("{}; the rule was not imported", e));
+            return;
+        }
+    };
+
+    let mut template = match &rule.ratelimit {
+        Some(limit) => {
+            for characteristic in &limit.characteristics {
+                if characteristic != "ip.src" && characteristic != "cf.colo.id" {
+                    t.unsupported(
+                        entry,
+                        format!(
+                            "counting by '{}'; requests are counted per source IP",
+                            characteristic
+                        ),
+                    );
+                }
+            }
+            if limit.mitigation_timeout > 0 {
+                t.unsupported(
+                    entry,
+                    format!(
+                        "the {}s mitigation timeout; clients are limited only while over the rate",
+                        limit.mitigation_timeout
+                    ),
+                );
+            }
+            if !limit.counting_expression.is_empty() {
+                t.unsupported(entry, "counting expressions; matching requests are counted");
+            }
+            rate_limited_row(limit.requests_per_period, limit.period)
+        }
+        None => RuleRow::default(),
+    };
+    template.description = rule.description.clone();
+    template.action = action.to_string();
+    template.enabled = rule.enabled.unwrap_or(!rule.paused);
+    template.priority = u32::try_from(entry).unwrap_or(u32::MAX);
+
+    let name = rule_name(t, entry, &rule.description);
+    let count = alternatives.len();
+    for (i, clauses) in alternatives.into_iter().enumerate() {
+        let mut row = template.clone();
+        row.name = if count > 1 {
+            format!("{} ({}/{})", name, i + 1, count)
+        } else {
+            name.clone()
+        };
+        let mut http = HttpMatch::default();
+        for clause in clauses {
+            clause.apply(&mut row, &mut http);
+        }
+        if http != HttpMatch::default() {
+            set_http(&mut row, http);
+        }
+        t.out.rows.push((entry, Ok(row)));
+    }
+}
+
+/// Name of an imported rule, from its description if it has one
+fn rule_name(t: &Translator, entry: usize, description: &str) -> String {
+    let description = description.trim();
+    if description.is_empty() {
+        format!("{} rule {}", t.provider, entry)
+    } else {
+        description.chars().take(100).collect()
+    }
+}
+
+/// Row limiting to `requests` per `period` seconds
+fn rate_limited_row(requests: u64, period: u64) -> RuleRow {
+    let period = period.max(1);
+    RuleRow {
+        rate_limit_rps: Some(requests.div_ceil(period).max(1)),
+        rate_limit_burst: Some(requests),
+        rate_limit_window_seconds: Some(u32::try_from(period).unwrap_or(u32::MAX)),
+        ..Default::default()
+    }
+}
+
+fn set_http(row: &mut RuleRow, http: HttpMatch) {
+    if http == HttpMatch::default() {
+        return;
+    }
+    row.l7_protocols = vec!["http".to_string()];
+    row.l7_match = serde_json::to_value(L7Match {
+        r#match: Some(l7_match::Match::Http(http)),
+    })
+    .ok();
+}
+
+/// A field of a custom rule expression compared with a set of values
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Clause {
+    field: Field,
+    values: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    SourceIp,
+    Country,
+    Asn,
+    TcpPort,
+    UdpPort,
+    Host,
+    Path,
+    Method,
+    UserAgent,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "ip.src" => Field::SourceIp,
+            "ip.geoip.country" | "ip.src.country" => Field::Country,
+            "ip.geoip.asnum" | "ip.src.asnum" => Field::Asn,
+            "tcp.dstport" => Field::TcpPort,
+            "udp.dstport" => Field::UdpPort,
+            "http.host" => Field::Host,
+            "http.request.uri.path" => Field::Path,
+            "http.request.method" => Field::Method,
+            "http.user_agent" => Field::UserAgent,
+            _ => return None,
+        })
+    }
+}
+
+impl Clause {
+    fn apply(self, row: &mut RuleRow, http: &mut HttpMatch) {
+        let values = self.values;
+        match self.field {
+            Field::SourceIp => row.source_ips = values,
+            Field::Country => row.source_countries = values,
+            Field::Asn => {
+                row.source_asns = values.into_iter().map(|v| format!("AS{}", v)).collect()
+            }
+            Field::TcpPort | Field::UdpPort => {
+                row.destination_ports = values.into_iter().map(|v| v.replace("..", "-")).collect();
+                let protocol = if self.field == Field::TcpPort {
+                    "tcp"
+                } else {
+                    "udp"
+                };
+                row.protocols = vec![protocol.to_string()];
+            }
+            Field::Host => http.hosts = values,
+            Field::Path => http.paths = values,
+            Field::Method => http.methods = values,
+            Field::UserAgent => http.user_agents = values,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(expression: &str) -> std::result::Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => s.extend(chars.next()),
+                    Some(c) => s.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(Token::Str(s));
+        } else if c.is_ascii_alphanumeric() || "_.:/-$".contains(c) {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_ascii_alphanumeric() || "_.:/-$".contains(c) {
+                    word.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Word(word));
+        } else {
+            chars.next();
+            let symbol = match (c, chars.peek()) {
+                ('=', Some('=')) => "==",
+                ('!', Some('=')) => "!=",
+                ('&', Some('&')) => "&&",
+                ('|', Some('|')) => "||",
+                ('!', _) => "!",
+                ('(', _) => "(",
+                (')', _) => ")",
+                ('{', _) => "{",
+                ('}', _) => "}",
+                _ => return Err(format!("unexpected character '{}'", c)),
+            };
+            if symbol.len() == 2 {
+                chars.next();
+            }
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse a custom rule expression into alternatives of clauses that must
+/// all match
+///
+/// Only `eq`/`==` and `in` comparisons of supported fields, combined with
+/// `and`, `or` and parentheses, can be expressed as rules.
+fn parse_expression(expression: &str) -> std::result::Result<Vec<Vec<Clause>>, String> {
+    let tokens = tokenize(expression)?;
+    let mut parser = ExpressionParser { tokens, pos: 0 };
+    let alternatives = parser.or()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!(
+            "unexpected {:?} in the expression",
+            parser.tokens[parser.pos]
+        ));
+    }
+    Ok(alternatives)
+}
+
+struct ExpressionParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExpressionParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn at_operator(&self, words: &[&str]) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) => words.contains(&w.as_str()),
+            Some(Token::Symbol(s)) => words.contains(s),
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> std::result::Result<Vec<Vec<Clause>>, String> {
+        let mut alternatives = self.and()?;
+        while self.at_operator(&["or", "||"]) {
+            self.pos += 1;
+            alternatives.extend(self.and()?);
+            if alternatives.len() > MAX_ALTERNATIVES {
+                return Err("the expression has too many alternatives".to_string());
+            }
+        }
+        Ok(alternatives)
+    }
+
+    fn and(&mut self) -> std::result::Result<Vec<Vec<Clause>>, String> {
+        let mut alternatives = self.primary()?;
+        while self.at_operator(&["and", "&&"]) {
+            self.pos += 1;
+            let right = self.primary()?;
+            let mut combined = Vec::new();
+            for left in &alternatives {
+                for right in &right {
+                    let mut clauses = left.clone();
+                    for clause in right {
+                        if clauses.iter().any(|c| c.field == clause.field) {
+                            return Err(format!(
+                                "{:?} is compared more than once in one alternative",
+                                clause.field
+                            ));
+                        }
+                        clauses.push(clause.clone());
+                    }
+                    combined.push(clauses);
+                }
+            }
+            if combined.len() > MAX_ALTERNATIVES {
+                return Err("the expression has too many alternatives".to_string());
+            }
+            alternatives = combined;
+        }
+        Ok(alternatives)
+    }
+
+    fn primary(&mut self) -> std::result::Result<Vec<Vec<Clause>>, String> {
+        match self.next() {
+            Some(Token::Symbol("(")) => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some(Token::Symbol(")")) => Ok(inner),
+                    _ => Err("unbalanced parentheses".to_string()),
+                }
+            }
+            Some(Token::Symbol("!")) => Err("negated conditions are not supported".to_string()),
+            Some(Token::Word(w)) if w == "not" => {
+                Err("negated conditions are not supported".to_string())
+            }
+            Some(Token::Word(name)) => {
+                let field = Field::parse(&name)
+                    .ok_or_else(|| format!("field '{}' is not supported", name))?;
+                let values = if self.at_operator(&["eq", "=="]) {
+                    self.pos += 1;
+                    vec![self.value()?]
+                } else if self.at_operator(&["in"]) {
+                    self.pos += 1;
+                    if self.next() != Some(Token::Symbol("{")) {
+                        return Err(format!("expected a set of values after '{} in'", name));
+                    }
+                    let mut values = Vec::new();
+                    while self.peek() != Some(&Token::Symbol("}")) {
+                        values.push(self.value()?);
+                    }
+                    self.pos += 1;
+                    values
+                } else {
+                    return Err(format!(
+                        "only 'eq' and 'in' comparisons of '{}' are supported",
+                        name
+                    ));
+                };
+                Ok(vec![vec![Clause { field, values }]])
+            }
+            other => Err(format!("unexpected {:?} in the expression", other)),
+        }
+    }
+
+    fn value(&mut self) -> std::result::Result<String, String> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(s),
+            Some(Token::Word(w)) if w.starts_with('$') => {
+                Err(format!("lists such as '{}' are not supported", w))
+            }
+            Some(Token::Word(w)) => Ok(w),
+            other => Err(format!("expected a value, found {:?}", other)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GenericEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    value: String,
+    #[serde(default)]
+    action: String,
+    #[serde(default = "enabled")]
+    enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn generic_entry(t: &mut Translator, entry: usize, value: Value) {
+    let entry_value = match serde_json::from_value::<GenericEntry>(value) {
+        Ok(e) => e,
+        Err(e) => {
+            t.error(entry, "", format!("Invalid entry: {}", e));
+            return;
+        }
+    };
+    let kind = match entry_value.kind.to_ascii_lowercase().as_str() {
+        "ip" | "cidr" | "network" | "ip_range" => ListKind::Network,
+        "country" => ListKind::Country,
+        "asn" => ListKind::Asn,
+        kind => {
+            t.error(entry, "type", format!("Unknown entry type '{}'", kind));
+            return;
+        }
+    };
+    let action = match entry_value.action.to_ascii_lowercase().as_str() {
+        "" | "block" | "deny" | "drop" => "drop",
+        "allow" | "whitelist" | "accept" => "allow",
+        "challenge" | "captcha" => "challenge",
+        "log" | "count" | "monitor" => "log",
+        action => {
+            t.error(entry, "action", format!("Unknown action '{}'", action));
+            return;
+        }
+    };
+    let value =
+        if kind == ListKind::Asn && !entry_value.value.to_ascii_uppercase().starts_with("AS") {
+            format!("AS{}", entry_value.value.trim())
+        } else {
+            entry_value.value
+        };
+    t.list_entry(entry, kind, &value, action, entry_value.enabled);
+}
+
+/// Import of provider configuration into a backend
+pub struct ProviderImportService {
+    transfer: RuleTransferService,
+}
+
+impl ProviderImportService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            transfer: RuleTransferService::new(state),
+        }
+    }
+
+    /// Translate a provider export and import the rules into a backend
+    ///
+    /// Returns the import report along with the dropped provider features.
+    #[instrument(skip(self, data))]
+    pub async fn import(
+        &self,
+        org_id: &str,
+        backend_id: &str,
+        provider: ConfigProvider,
+        data: &[u8],
+        dry_run: bool,
+    ) -> Result<(usize, ImportReport, Vec<String>)> {
+        let translation = translate(provider, data)?;
+        let report = self
+            .transfer
+            .import_rows(org_id, Some(backend_id), translation.rows, dry_run)
+            .await?;
+
+        info!(
+            org_id = %org_id,
+            backend_id = %backend_id,
+            entries = translation.total_entries,
+            imported = report.imported,
+            errors = report.errors.len(),
+            unsupported = translation.unsupported.len(),
+            dry_run,
+            "Imported provider configuration"
+        );
+        Ok((translation.total_entries, report, translation.unsupported))
+    }
+}
@@ -0,0 +1,570 @@
+//! Rate limit profiles
+//!
+//! Operators define named profiles of per-source limits for a backend, a
+//! weekly schedule of when each applies and a default outside the
+//! schedule, e.g. a strict `night` profile and a generous `event` one.
+//! A profile can also be triggered by hand for a while, ahead of an event
+//! or a server restart. Profiles are published to workers, which resolve
+//! the active one themselves and apply it to their XDP rate limit maps
+//! (see `pistonprotection_common::rate_profile`). The monitor republishes
+//! all profiles regularly and clears manual triggers that ended.
+
+use crate::services::AppState;
+use crate::services::backend::BackendService;
+use chrono::{DateTime, Utc};
+use pistonprotection_common::duration::bounded_duration;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::rate_profile::{
+    ACTIVE_PROFILES_KEY, BackendRateProfiles, MINUTES_PER_DAY, ManualProfile, PROFILES_TTL,
+    ProfileWindow, RateLimitProfile as Profile, rate_profiles_key,
+};
+use pistonprotection_common::redis::CacheService;
+use pistonprotection_proto::backend::{RateLimitProfile, RateProfileWindow, RateProfiles};
+use sqlx::Row;
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+
+/// Most profiles of a backend
+pub const MAX_PROFILES: usize = 16;
+
+/// Most schedule windows of a backend
+pub const MAX_WINDOWS: usize = 64;
+
+/// Longest profile name
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Largest offset of the schedule from UTC
+pub const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Time a manual trigger lasts when the request leaves it unset
+pub const DEFAULT_TRIGGER_SECONDS: u32 = 3600;
+
+/// Longest manual trigger
+pub const MAX_TRIGGER_SECONDS: u32 = 7 * 24 * 3600;
+
+/// How often the monitor republishes profiles
+const MONITOR_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Rate profile service implementation
+pub struct RateProfileService {
+    state: AppState,
+    backends: BackendService,
+}
+
+impl RateProfileService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            backends: BackendService::new(state.clone()),
+            state,
+        }
+    }
+
+    fn cache(&self) -> Result<&CacheService> {
+        self.state
+            .cache
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Rate limit profiles require Redis".to_string()))
+    }
+
+    /// Get the profiles of a backend and the one in effect
+    #[instrument(skip(self))]
+    pub async fn get(&self, backend_id: &str) -> Result<RateProfiles> {
+        self.backends.get(backend_id).await?;
+
+        Ok(match self.load(backend_id).await? {
+            Some((profiles, updated_at)) => {
+                rate_profiles_to_proto(&profiles, updated_at, Utc::now().timestamp())
+            }
+            None => RateProfiles {
+                backend_id: backend_id.to_string(),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Replace the profiles and schedule of a backend
+    ///
+    /// A manual trigger stays in effect while its profile still exists.
+    /// Without profiles the backend goes back to its protection settings.
+    #[instrument(skip(self, profiles, windows))]
+    pub async fn update(
+        &self,
+        backend_id: &str,
+        profiles: Vec<RateLimitProfile>,
+        windows: Vec<RateProfileWindow>,
+        default_profile: &str,
+        utc_offset_minutes: i32,
+    ) -> Result<RateProfiles> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+        self.backends.get(backend_id).await?;
+
+        let mut validated = validate_profiles(
+            backend_id,
+            profiles,
+            windows,
+            default_profile,
+            utc_offset_minutes,
+        )?;
+
+        if validated.profiles.is_empty() {
+            sqlx::query("DELETE FROM rate_profiles WHERE backend_id = $1")
+                .bind(backend_id)
+                .execute(db)
+                .await?;
+            cache.delete(&rate_profiles_key(backend_id)).await?;
+            cache.srem(ACTIVE_PROFILES_KEY, backend_id).await?;
+            info!(backend_id = %backend_id, "Removed rate limit profiles");
+            return self.get(backend_id).await;
+        }
+
+        let profiles_json = serde_json::to_value(&validated.profiles)
+            .map_err(|e| Error::Internal(format!("Failed to serialize profiles: {}", e)))?;
+        let windows_json = serde_json::to_value(&validated.windows)
+            .map_err(|e| Error::Internal(format!("Failed to serialize windows: {}", e)))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO rate_profiles (
+                backend_id, profiles, windows, default_profile, utc_offset_minutes
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (backend_id) DO UPDATE
+            SET profiles = EXCLUDED.profiles,
+                windows = EXCLUDED.windows,
+                default_profile = EXCLUDED.default_profile,
+                utc_offset_minutes = EXCLUDED.utc_offset_minutes
+            "#,
+        )
+        .bind(backend_id)
+        .bind(&profiles_json)
+        .bind(&windows_json)
+        .bind(&validated.default_profile)
+        .bind(validated.utc_offset_minutes)
+        .execute(db)
+        .await?;
+
+        // A trigger of a removed profile ends with it
+        let (stored, updated_at) = self
+            .load(backend_id)
+            .await?
+            .ok_or_else(|| Error::not_found("RateProfiles", backend_id))?;
+        if let Some(manual) = stored.manual {
+            if validated.profile(&manual.profile).is_some() {
+                validated.manual = Some(manual);
+            } else {
+                self.clear_trigger(backend_id).await?;
+            }
+        }
+
+        self.publish(cache, &validated).await?;
+        info!(
+            backend_id = %backend_id,
+            profiles = validated.profiles.len(),
+            windows = validated.windows.len(),
+            "Updated rate limit profiles"
+        );
+
+        Ok(rate_profiles_to_proto(
+            &validated,
+            updated_at,
+            Utc::now().timestamp(),
+        ))
+    }
+
+    /// Apply a profile by hand for a while, or go back to the schedule with
+    /// an empty profile name
+    #[instrument(skip(self))]
+    pub async fn trigger(
+        &self,
+        backend_id: &str,
+        profile: &str,
+        duration_seconds: u32,
+        triggered_by: &str,
+    ) -> Result<RateProfiles> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+        self.backends.get(backend_id).await?;
+        let duration_seconds = bounded_duration(
+            duration_seconds,
+            DEFAULT_TRIGGER_SECONDS,
+            MAX_TRIGGER_SECONDS,
+        )
+        .ok_or_else(|| {
+            Error::validation(format!(
+                "Trigger duration cannot exceed {} seconds",
+                MAX_TRIGGER_SECONDS
+            ))
+        })?;
+
+        let (mut profiles, _) = self.load(backend_id).await?.ok_or_else(|| {
+            Error::validation("The backend has no rate limit profiles to trigger")
+        })?;
+
+        let profile = profile.trim().to_ascii_lowercase();
+        if profile.is_empty() {
+            self.clear_trigger(backend_id).await?;
+            profiles.manual = None;
+        } else {
+            if profiles.profile(&profile).is_none() {
+                return Err(Error::not_found("RateLimitProfile", &profile));
+            }
+            let until = Utc::now() + chrono::Duration::seconds(duration_seconds as i64);
+            sqlx::query(
+                r#"
+                UPDATE rate_profiles
+                SET manual_profile = $2, manual_until = $3, triggered_by = $4
+                WHERE backend_id = $1
+                "#,
+            )
+            .bind(backend_id)
+            .bind(&profile)
+            .bind(until)
+            .bind(triggered_by)
+            .execute(db)
+            .await?;
+            profiles.manual = Some(ManualProfile {
+                profile: profile.clone(),
+                triggered_by: triggered_by.to_string(),
+                until: until.timestamp(),
+            });
+        }
+
+        self.publish(cache, &profiles).await?;
+        info!(
+            backend_id = %backend_id,
+            profile = %profile,
+            triggered_by = %triggered_by,
+            duration_seconds,
+            "Triggered rate limit profile"
+        );
+
+        self.get(backend_id).await
+    }
+
+    /// Clear ended manual triggers and republish the profiles of every
+    /// backend, giving the number published
+    #[instrument(skip(self))]
+    pub async fn republish(&self) -> Result<usize> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+
+        sqlx::query(
+            r#"
+            UPDATE rate_profiles
+            SET manual_profile = NULL, manual_until = NULL, triggered_by = NULL
+            WHERE manual_until <= NOW()
+            "#,
+        )
+        .execute(db)
+        .await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT backend_id, profiles, windows, default_profile, utc_offset_minutes,
+                   manual_profile, manual_until, triggered_by, updated_at
+            FROM rate_profiles
+            "#,
+        )
+        .fetch_all(db)
+        .await?;
+
+        for row in &rows {
+            let (profiles, _) = rate_profiles_from_row(row)?;
+            self.publish(cache, &profiles).await?;
+        }
+        Ok(rows.len())
+    }
+
+    async fn load(
+        &self,
+        backend_id: &str,
+    ) -> Result<Option<(BackendRateProfiles, Option<DateTime<Utc>>)>> {
+        let db = self.state.db()?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT backend_id, profiles, windows, default_profile, utc_offset_minutes,
+                   manual_profile, manual_until, triggered_by, updated_at
+            FROM rate_profiles
+            WHERE backend_id = $1
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_optional(db)
+        .await?;
+
+        row.map(|row| rate_profiles_from_row(&row)).transpose()
+    }
+
+    async fn clear_trigger(&self, backend_id: &str) -> Result<()> {
+        let db = self.state.db()?;
+
+        sqlx::query(
+            r#"
+            UPDATE rate_profiles
+            SET manual_profile = NULL, manual_until = NULL, triggered_by = NULL
+            WHERE backend_id = $1
+            "#,
+        )
+        .bind(backend_id)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    async fn publish(&self, cache: &CacheService, profiles: &BackendRateProfiles) -> Result<()> {
+        cache
+            .set(
+                &rate_profiles_key(&profiles.backend_id),
+                profiles,
+                PROFILES_TTL,
+            )
+            .await?;
+        cache
+            .sadd(ACTIVE_PROFILES_KEY, &profiles.backend_id)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Spawn the monitor republishing rate limit profiles
+pub fn spawn_monitor(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() || state.cache.is_none() {
+        info!("Rate profile monitor disabled");
+        return None;
+    }
+
+    let service = RateProfileService::new(state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = service.republish().await {
+                        warn!(error = %e, "Failed to republish rate limit profiles");
+                    }
+                }
+            }
+        }
+    }))
+}
+
+fn rate_profiles_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> Result<(BackendRateProfiles, Option<DateTime<Utc>>)> {
+    let profiles: Vec<Profile> = serde_json::from_value(row.get("profiles"))
+        .map_err(|e| Error::Internal(format!("Failed to parse profiles: {}", e)))?;
+    let windows: Vec<ProfileWindow> = serde_json::from_value(row.get("windows"))
+        .map_err(|e| Error::Internal(format!("Failed to parse windows: {}", e)))?;
+    let manual_profile: Option<String> = row.get("manual_profile");
+    let manual_until: Option<DateTime<Utc>> = row.get("manual_until");
+    let triggered_by: Option<String> = row.get("triggered_by");
+
+    Ok((
+        BackendRateProfiles {
+            backend_id: row.get("backend_id"),
+            profiles,
+            windows,
+            default_profile: row.get("default_profile"),
+            utc_offset_minutes: row.get("utc_offset_minutes"),
+            manual: manual_profile
+                .zip(manual_until)
+                .map(|(profile, until)| ManualProfile {
+                    profile,
+                    triggered_by: triggered_by.unwrap_or_default(),
+                    until: until.timestamp(),
+                }),
+        },
+        row.get("updated_at"),
+    ))
+}
+
+/// Profiles of a backend as returned by the API, with the one in effect at
+/// `now` (Unix seconds)
+pub fn rate_profiles_to_proto(
+    profiles: &BackendRateProfiles,
+    updated_at: Option<DateTime<Utc>>,
+    now: i64,
+) -> RateProfiles {
+    let active = profiles.active(now);
+    let manual = profiles.manual.as_ref().filter(|manual| now < manual.until);
+
+    RateProfiles {
+        backend_id: profiles.backend_id.clone(),
+        profiles: profiles
+            .profiles
+            .iter()
+            .map(|profile| RateLimitProfile {
+                name: profile.name.clone(),
+                per_ip_pps: profile.per_ip_pps,
+                per_ip_burst: profile.per_ip_burst,
+            })
+            .collect(),
+        windows: profiles
+            .windows
+            .iter()
+            .map(|window| RateProfileWindow {
+                profile: window.profile.clone(),
+                days: window.days.iter().map(|&day| u32::from(day)).collect(),
+                start_minute: window.start_minute,
+                end_minute: window.end_minute,
+            })
+            .collect(),
+        default_profile: profiles.default_profile.clone(),
+        utc_offset_minutes: profiles.utc_offset_minutes,
+        manual_profile: manual
+            .map(|manual| manual.profile.clone())
+            .unwrap_or_default(),
+        manual_until: manual
+            .and_then(|manual| DateTime::from_timestamp(manual.until, 0))
+            .map(Into::into),
+        triggered_by: manual
+            .map(|manual| manual.triggered_by.clone())
+            .unwrap_or_default(),
+        active_profile: active
+            .map(|(profile, _)| profile.name.clone())
+            .unwrap_or_default(),
+        active_source: active
+            .map(|(_, source)| source.to_string())
+            .unwrap_or_default(),
+        updated_at: updated_at.map(Into::into),
+    }
+}
+
+/// Validate the profiles and schedule of a backend
+pub fn validate_profiles(
+    backend_id: &str,
+    profiles: Vec<RateLimitProfile>,
+    windows: Vec<RateProfileWindow>,
+    default_profile: &str,
+    utc_offset_minutes: i32,
+) -> Result<BackendRateProfiles> {
+    if profiles.len() > MAX_PROFILES {
+        return Err(Error::validation(format!(
+            "A backend cannot have more than {} rate limit profiles",
+            MAX_PROFILES
+        )));
+    }
+    if windows.len() > MAX_WINDOWS {
+        return Err(Error::validation(format!(
+            "A schedule cannot have more than {} windows",
+            MAX_WINDOWS
+        )));
+    }
+    if utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+        return Err(Error::validation(format!(
+            "UTC offset cannot exceed {} minutes",
+            MAX_UTC_OFFSET_MINUTES
+        )));
+    }
+
+    let mut names = HashSet::new();
+    let profiles: Vec<Profile> = profiles
+        .into_iter()
+        .map(|profile| {
+            let name = validate_name(&profile.name)?;
+            if !names.insert(name.clone()) {
+                return Err(Error::validation(format!(
+                    "Duplicate rate limit profile '{}'",
+                    name
+                )));
+            }
+            if profile.per_ip_pps == 0 {
+                return Err(Error::validation(format!(
+                    "Rate limit profile '{}' needs a packet rate",
+                    name
+                )));
+            }
+            Ok(Profile {
+                name,
+                per_ip_pps: profile.per_ip_pps,
+                per_ip_burst: profile.per_ip_burst,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let known = |name: &str| -> Result<String> {
+        let name = name.trim().to_ascii_lowercase();
+        if names.contains(&name) {
+            Ok(name)
+        } else {
+            Err(Error::validation(format!(
+                "Unknown rate limit profile '{}'",
+                name
+            )))
+        }
+    };
+
+    let windows = windows
+        .into_iter()
+        .map(|window| {
+            if window.start_minute >= MINUTES_PER_DAY || window.end_minute >= MINUTES_PER_DAY {
+                return Err(Error::validation(format!(
+                    "Window minutes must be below {}",
+                    MINUTES_PER_DAY
+                )));
+            }
+            let mut days = Vec::with_capacity(window.days.len());
+            for day in window.days {
+                let day = u8::try_from(day)
+                    .ok()
+                    .filter(|&day| day < 7)
+                    .ok_or_else(|| Error::validation(format!("Invalid day of week {}", day)))?;
+                if !days.contains(&day) {
+                    days.push(day);
+                }
+            }
+            days.sort_unstable();
+            Ok(ProfileWindow {
+                profile: known(&window.profile)?,
+                days,
+                start_minute: window.start_minute,
+                end_minute: window.end_minute,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let default_profile = match default_profile.trim() {
+        "" => String::new(),
+        name => known(name)?,
+    };
+
+    Ok(BackendRateProfiles {
+        backend_id: backend_id.to_string(),
+        profiles,
+        windows,
+        default_profile,
+        utc_offset_minutes,
+        manual: None,
+    })
+}
+
+/// Validate a profile name, lowercased
+pub fn validate_name(name: &str) -> Result<String> {
+    let name = name.trim().to_ascii_lowercase();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(Error::validation(format!(
+            "Profile names must have 1 to {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    if !name
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    {
+        return Err(Error::validation(format!(
+            "Profile name '{}' may only contain letters, digits, '-' and '_'",
+            name
+        )));
+    }
+    Ok(name)
+}
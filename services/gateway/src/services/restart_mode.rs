@@ -0,0 +1,294 @@
+//! Restart modes
+//!
+//! Ahead of a game server restart, an operator puts its backend in restart
+//! mode for a few minutes: workers multiply its per-source limits so the
+//! reconnect wave is not taken for a flood, and raise protocol validation
+//! to at least the high protection level. The mode ends on its own; the
+//! monitor removes ended modes, and workers stop applying them once ended
+//! even while the gateway is unreachable (see
+//! `pistonprotection_common::restart_mode`).
+
+use crate::services::AppState;
+use crate::services::backend::BackendService;
+use crate::services::backend_mode::validate_reason;
+use chrono::{DateTime, Utc};
+use pistonprotection_common::duration::bounded_duration;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::redis::CacheService;
+use pistonprotection_common::restart_mode::{
+    ACTIVE_RESTARTS_KEY, RESTART_TTL_MARGIN, RestartMode as Restart, restart_mode_key,
+};
+use pistonprotection_proto::backend::RestartMode;
+use sqlx::Row;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+
+/// Length of a restart mode when the request leaves it unset
+pub const DEFAULT_DURATION_MINUTES: u32 = 10;
+
+/// Longest restart mode
+pub const MAX_DURATION_MINUTES: u32 = 120;
+
+/// Connection multiplier when the request leaves it unset
+pub const DEFAULT_CONNECTION_MULTIPLIER: u32 = 5;
+
+/// Largest connection multiplier
+pub const MAX_CONNECTION_MULTIPLIER: u32 = 50;
+
+/// How often the monitor removes ended restart modes
+const MONITOR_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Restart mode service implementation
+pub struct RestartModeService {
+    state: AppState,
+    backends: BackendService,
+}
+
+impl RestartModeService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            backends: BackendService::new(state.clone()),
+            state,
+        }
+    }
+
+    fn cache(&self) -> Result<&CacheService> {
+        self.state
+            .cache
+            .as_ref()
+            .ok_or_else(|| Error::Internal("Restart modes require Redis".to_string()))
+    }
+
+    /// Put a backend in restart mode for `duration_minutes`
+    ///
+    /// Starting it again while active replaces the multiplier and restarts
+    /// the timer.
+    #[instrument(skip(self, reason))]
+    pub async fn start(
+        &self,
+        backend_id: &str,
+        duration_minutes: u32,
+        connection_multiplier: u32,
+        reason: &str,
+        started_by: &str,
+    ) -> Result<RestartMode> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+        let duration_minutes = bounded_duration(
+            duration_minutes,
+            DEFAULT_DURATION_MINUTES,
+            MAX_DURATION_MINUTES,
+        )
+        .ok_or_else(|| {
+            Error::validation(format!(
+                "Restart mode cannot last more than {} minutes",
+                MAX_DURATION_MINUTES
+            ))
+        })?;
+        let connection_multiplier = validate_connection_multiplier(connection_multiplier)?;
+        let reason = validate_reason(reason)?;
+        self.backends.get(backend_id).await?;
+
+        let started_at = Utc::now();
+        let ends_at = started_at + chrono::Duration::minutes(duration_minutes as i64);
+
+        sqlx::query(
+            r#"
+            INSERT INTO restart_modes (
+                backend_id, connection_multiplier, reason, started_by, started_at, ends_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (backend_id) DO UPDATE
+            SET connection_multiplier = EXCLUDED.connection_multiplier,
+                reason = EXCLUDED.reason,
+                started_by = EXCLUDED.started_by,
+                started_at = EXCLUDED.started_at,
+                ends_at = EXCLUDED.ends_at
+            "#,
+        )
+        .bind(backend_id)
+        .bind(connection_multiplier as i32)
+        .bind(&reason)
+        .bind(started_by)
+        .bind(started_at)
+        .bind(ends_at)
+        .execute(db)
+        .await?;
+
+        let restart = Restart {
+            backend_id: backend_id.to_string(),
+            connection_multiplier,
+            reason,
+            started_by: started_by.to_string(),
+            started_at: started_at.timestamp(),
+            ends_at: ends_at.timestamp(),
+        };
+        let ttl = Duration::from_secs(duration_minutes as u64 * 60) + RESTART_TTL_MARGIN;
+        cache
+            .set(&restart_mode_key(backend_id), &restart, ttl)
+            .await?;
+        cache.sadd(ACTIVE_RESTARTS_KEY, backend_id).await?;
+
+        info!(
+            backend_id = %backend_id,
+            connection_multiplier,
+            duration_minutes,
+            started_by = %started_by,
+            "Started restart mode"
+        );
+
+        Ok(restart_mode_to_proto(
+            Some(&restart),
+            backend_id,
+            started_at.timestamp(),
+        ))
+    }
+
+    /// End the restart mode of a backend
+    #[instrument(skip(self))]
+    pub async fn stop(&self, backend_id: &str) -> Result<RestartMode> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+        self.backends.get(backend_id).await?;
+
+        let removed = sqlx::query("DELETE FROM restart_modes WHERE backend_id = $1")
+            .bind(backend_id)
+            .execute(db)
+            .await?
+            .rows_affected();
+        cache.delete(&restart_mode_key(backend_id)).await?;
+        cache.srem(ACTIVE_RESTARTS_KEY, backend_id).await?;
+
+        if removed > 0 {
+            info!(backend_id = %backend_id, "Stopped restart mode");
+        }
+
+        Ok(restart_mode_to_proto(None, backend_id, 0))
+    }
+
+    /// Get the restart mode of a backend
+    #[instrument(skip(self))]
+    pub async fn get(&self, backend_id: &str) -> Result<RestartMode> {
+        let db = self.state.db()?;
+        self.backends.get(backend_id).await?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT backend_id, connection_multiplier, reason, started_by, started_at, ends_at
+            FROM restart_modes
+            WHERE backend_id = $1
+            "#,
+        )
+        .bind(backend_id)
+        .fetch_optional(db)
+        .await?;
+
+        let restart = row.map(|row| restart_from_row(&row));
+        Ok(restart_mode_to_proto(
+            restart.as_ref(),
+            backend_id,
+            Utc::now().timestamp(),
+        ))
+    }
+
+    /// Remove ended restart modes, giving the backends they belonged to
+    #[instrument(skip(self))]
+    pub async fn expire(&self) -> Result<Vec<String>> {
+        let db = self.state.db()?;
+        let cache = self.cache()?;
+
+        let rows: Vec<(String,)> =
+            sqlx::query_as("DELETE FROM restart_modes WHERE ends_at <= NOW() RETURNING backend_id")
+                .fetch_all(db)
+                .await?;
+
+        let mut backend_ids = Vec::with_capacity(rows.len());
+        for (backend_id,) in rows {
+            cache.delete(&restart_mode_key(&backend_id)).await?;
+            cache.srem(ACTIVE_RESTARTS_KEY, &backend_id).await?;
+            info!(backend_id = %backend_id, "Restart mode ended");
+            backend_ids.push(backend_id);
+        }
+        Ok(backend_ids)
+    }
+}
+
+/// Spawn the monitor removing ended restart modes
+pub fn spawn_monitor(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() || state.cache.is_none() {
+        info!("Restart mode monitor disabled");
+        return None;
+    }
+
+    let service = RestartModeService::new(state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = service.expire().await {
+                        warn!(error = %e, "Failed to remove ended restart modes");
+                    }
+                }
+            }
+        }
+    }))
+}
+
+fn restart_from_row(row: &sqlx::postgres::PgRow) -> Restart {
+    let connection_multiplier: i32 = row.get("connection_multiplier");
+    let started_at: DateTime<Utc> = row.get("started_at");
+    let ends_at: DateTime<Utc> = row.get("ends_at");
+
+    Restart {
+        backend_id: row.get("backend_id"),
+        connection_multiplier: connection_multiplier as u32,
+        reason: row.get("reason"),
+        started_by: row.get("started_by"),
+        started_at: started_at.timestamp(),
+        ends_at: ends_at.timestamp(),
+    }
+}
+
+/// Restart mode of a backend as returned by the API at `now` (Unix
+/// seconds); an ended mode is reported as inactive
+pub fn restart_mode_to_proto(restart: Option<&Restart>, backend_id: &str, now: i64) -> RestartMode {
+    match restart.filter(|restart| !restart.expired(now)) {
+        Some(restart) => RestartMode {
+            backend_id: restart.backend_id.clone(),
+            active: true,
+            connection_multiplier: restart.connection_multiplier,
+            reason: restart.reason.clone(),
+            started_by: restart.started_by.clone(),
+            started_at: DateTime::from_timestamp(restart.started_at, 0).map(Into::into),
+            ends_at: DateTime::from_timestamp(restart.ends_at, 0).map(Into::into),
+        },
+        None => RestartMode {
+            backend_id: backend_id.to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+/// Validate the connection multiplier of a restart mode, 0 meaning the
+/// default
+pub fn validate_connection_multiplier(connection_multiplier: u32) -> Result<u32> {
+    match connection_multiplier {
+        0 => Ok(DEFAULT_CONNECTION_MULTIPLIER),
+        1 => Err(Error::validation(
+            "Connection multiplier must be at least 2",
+        )),
+        m if m > MAX_CONNECTION_MULTIPLIER => Err(Error::validation(format!(
+            "Connection multiplier cannot exceed {}",
+            MAX_CONNECTION_MULTIPLIER
+        ))),
+        m => Ok(m),
+    }
+}
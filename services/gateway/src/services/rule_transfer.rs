@@ -0,0 +1,822 @@
+//! Import and export of filter rules
+//!
+//! An organization's rules are exported to, and imported from, JSON or CSV
+//! files in a flat row format that does not depend on the protobuf encoding,
+//! so rule sets can be migrated from other providers with little reshaping.
+//! Blocklists are rules with the `drop` action matching source addresses;
+//! rows without an action are imported as such blocks.
+//!
+//! Imports validate every row, import the valid ones and report the others
+//! by row number. A dry run only validates.
+
+use super::AppState;
+use super::filter::{FilterService, compile_rule};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::common::{self, IpAddress, IpNetwork, PortRange};
+use pistonprotection_proto::filter::{FilterMatch, FilterRule, ImportRowError, TransferFormat};
+use pistonprotection_rule_compiler::Cidr;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use tracing::{info, instrument};
+
+/// Most rows accepted in one import
+pub const MAX_IMPORT_ROWS: usize = 10_000;
+
+/// Separator of list entries within a CSV cell
+const LIST_SEPARATOR: char = ';';
+
+/// Columns of CSV files, in export order
+pub const CSV_COLUMNS: &[&str] = &[
+    "backend_id",
+    "name",
+    "description",
+    "priority",
+    "action",
+    "enabled",
+    "source_ips",
+    "source_ip_blacklist",
+    "source_countries",
+    "source_country_blacklist",
+    "source_asns",
+    "destination_ips",
+    "destination_ports",
+    "protocols",
+    "l7_protocols",
+    "rate_limit_rps",
+    "rate_limit_burst",
+    "rate_limit_window_seconds",
+    "l7_match",
+    "time_match",
+];
+
+/// A rule in the portable row format
+///
+/// Networks are written in CIDR notation or as bare addresses, ports as
+/// `443` or `8000-8100`, and enum values by their lowercase names such as
+/// `rate_limit` or `minecraft_java`. L7 and time matching are kept as the
+/// JSON of their API messages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleRow {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub backend_id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    pub priority: u32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub action: String,
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source_ips: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source_ip_blacklist: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source_countries: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source_country_blacklist: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source_asns: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub destination_ips: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub destination_ports: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub protocols: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub l7_protocols: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_rps: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_burst: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_window_seconds: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l7_match: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_match: Option<serde_json::Value>,
+}
+
+impl Default for RuleRow {
+    fn default() -> Self {
+        Self {
+            backend_id: String::new(),
+            name: String::new(),
+            description: String::new(),
+            priority: 0,
+            action: String::new(),
+            enabled: true,
+            source_ips: Vec::new(),
+            source_ip_blacklist: Vec::new(),
+            source_countries: Vec::new(),
+            source_country_blacklist: Vec::new(),
+            source_asns: Vec::new(),
+            destination_ips: Vec::new(),
+            destination_ports: Vec::new(),
+            protocols: Vec::new(),
+            l7_protocols: Vec::new(),
+            rate_limit_rps: None,
+            rate_limit_burst: None,
+            rate_limit_window_seconds: None,
+            l7_match: None,
+            time_match: None,
+        }
+    }
+}
+
+/// A row that failed to parse or validate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    /// Column at fault, empty if the row as a whole is
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl RowError {
+    pub fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+
+    pub fn at(self, row: usize) -> ImportRowError {
+        ImportRowError {
+            row: row as u32,
+            field: self.field.to_string(),
+            message: self.message,
+        }
+    }
+}
+
+impl RuleRow {
+    /// Row of a stored rule
+    pub fn from_rule(backend_id: &str, rule: &FilterRule) -> Self {
+        let mut row = Self {
+            backend_id: backend_id.to_string(),
+            name: rule.name.clone(),
+            description: rule.description.clone(),
+            priority: rule.priority,
+            action: common::Action::try_from(rule.action)
+                .map(|a| enum_name(a.as_str_name(), "ACTION_"))
+                .unwrap_or_default(),
+            enabled: rule.enabled,
+            ..Default::default()
+        };
+
+        if let Some(m) = &rule.r#match {
+            row.source_ips = format_networks(&m.source_ips);
+            row.source_ip_blacklist = format_networks(&m.source_ip_blacklist);
+            row.source_countries = m.source_countries.clone();
+            row.source_country_blacklist = m.source_country_blacklist.clone();
+            row.source_asns = m.source_asns.clone();
+            row.destination_ips = format_networks(&m.destination_ips);
+            row.destination_ports = m
+                .destination_ports
+                .iter()
+                .map(|p| {
+                    if p.start == p.end {
+                        p.start.to_string()
+                    } else {
+                        format!("{}-{}", p.start, p.end)
+                    }
+                })
+                .collect();
+            row.protocols = m
+                .protocols
+                .iter()
+                .filter_map(|p| common::Protocol::try_from(*p).ok())
+                .map(|p| enum_name(p.as_str_name(), "PROTOCOL_"))
+                .collect();
+            row.l7_protocols = m
+                .l7_protocols
+                .iter()
+                .filter_map(|p| common::L7Protocol::try_from(*p).ok())
+                .map(|p| enum_name(p.as_str_name(), "L7_PROTOCOL_"))
+                .collect();
+            row.l7_match = m
+                .l7_match
+                .as_ref()
+                .and_then(|l7| serde_json::to_value(l7).ok());
+            row.time_match = m
+                .time_match
+                .as_ref()
+                .and_then(|t| serde_json::to_value(t).ok());
+        }
+
+        if let Some(limit) = &rule.rate_limit {
+            row.rate_limit_rps = Some(limit.requests_per_second);
+            row.rate_limit_burst = Some(limit.burst_size);
+            row.rate_limit_window_seconds = Some(limit.window_seconds);
+        }
+
+        row
+    }
+
+    /// Validate the row and convert it into a rule
+    ///
+    /// The backend ID is not part of the rule and is checked by the caller.
+    pub fn into_rule(self) -> std::result::Result<FilterRule, RowError> {
+        if self.name.trim().is_empty() {
+            return Err(RowError::new("name", "Name is required"));
+        }
+
+        let action = if self.action.trim().is_empty() {
+            common::Action::Drop
+        } else {
+            parse_enum(&self.action, "ACTION_", common::Action::from_str_name)
+                .filter(|a| *a != common::Action::Unspecified)
+                .ok_or_else(|| {
+                    RowError::new("action", format!("Unknown action '{}'", self.action))
+                })?
+        };
+
+        let rate_limit = match (
+            self.rate_limit_rps,
+            self.rate_limit_burst,
+            self.rate_limit_window_seconds,
+        ) {
+            (None, None, None) => None,
+            (rps, burst, window) => Some(common::RateLimit {
+                requests_per_second: rps.unwrap_or_default(),
+                burst_size: burst.unwrap_or_default(),
+                window_seconds: window.unwrap_or_default(),
+            }),
+        };
+        if action == common::Action::RateLimit
+            && rate_limit
+                .as_ref()
+                .is_none_or(|r| r.requests_per_second == 0)
+        {
+            return Err(RowError::new(
+                "rate_limit_rps",
+                "Rate limit rules need a rate",
+            ));
+        }
+
+        let filter_match = FilterMatch {
+            source_ips: parse_networks("source_ips", &self.source_ips)?,
+            source_ip_blacklist: parse_networks("source_ip_blacklist", &self.source_ip_blacklist)?,
+            source_countries: parse_countries("source_countries", &self.source_countries)?,
+            source_country_blacklist: parse_countries(
+                "source_country_blacklist",
+                &self.source_country_blacklist,
+            )?,
+            source_asns: parse_asns(&self.source_asns)?,
+            destination_ips: parse_networks("destination_ips", &self.destination_ips)?,
+            destination_ports: parse_ports(&self.destination_ports)?,
+            protocols: self
+                .protocols
+                .iter()
+                .map(|p| {
+                    parse_enum(p, "PROTOCOL_", common::Protocol::from_str_name)
+                        .filter(|p| *p != common::Protocol::Unspecified)
+                        .map(|p| p as i32)
+                        .ok_or_else(|| {
+                            RowError::new("protocols", format!("Unknown protocol '{}'", p))
+                        })
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            l7_protocols: self
+                .l7_protocols
+                .iter()
+                .map(|p| {
+                    parse_enum(p, "L7_PROTOCOL_", common::L7Protocol::from_str_name)
+                        .filter(|p| *p != common::L7Protocol::Unspecified)
+                        .map(|p| p as i32)
+                        .ok_or_else(|| {
+                            RowError::new("l7_protocols", format!("Unknown L7 protocol '{}'", p))
+                        })
+                })
+                .collect::<std::result::Result<_, _>>()?,
+            l7_match: self
+                .l7_match
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| RowError::new("l7_match", e.to_string()))?,
+            time_match: self
+                .time_match
+                .map(serde_json::from_value)
+                .transpose()
+                .map_err(|e| RowError::new("time_match", e.to_string()))?,
+        };
+
+        let rule = FilterRule {
+            name: self.name.trim().to_string(),
+            description: self.description,
+            priority: self.priority,
+            r#match: Some(filter_match),
+            action: action as i32,
+            rate_limit,
+            enabled: self.enabled,
+            ..Default::default()
+        };
+        compile_rule(&rule).map_err(|e| RowError::new("", e.to_string()))?;
+        Ok(rule)
+    }
+}
+
+/// MIME type of a format
+pub fn content_type(format: TransferFormat) -> Result<&'static str> {
+    match format {
+        TransferFormat::Json => Ok("application/json"),
+        TransferFormat::Csv => Ok("text/csv"),
+        TransferFormat::Unspecified => Err(Error::validation("A format is required")),
+    }
+}
+
+/// Write rows as a file of the given format
+pub fn encode(format: TransferFormat, rows: &[RuleRow]) -> Result<Vec<u8>> {
+    match format {
+        TransferFormat::Json => serde_json::to_vec_pretty(rows)
+            .map_err(|e| Error::Internal(format!("Failed to encode rules: {}", e))),
+        TransferFormat::Csv => encode_csv(rows),
+        TransferFormat::Unspecified => Err(Error::validation("A format is required")),
+    }
+}
+
+/// Read the rows of a file
+///
+/// A file that cannot be read as a whole is an error; rows that cannot be
+/// read are returned as row errors. Rows are numbered from 1.
+pub fn decode(
+    format: TransferFormat,
+    data: &[u8],
+) -> Result<Vec<(usize, std::result::Result<RuleRow, RowError>)>> {
+    let rows = match format {
+        TransferFormat::Json => decode_json(data)?,
+        TransferFormat::Csv => decode_csv(data)?,
+        TransferFormat::Unspecified => return Err(Error::validation("A format is required")),
+    };
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(Error::validation(format!(
+            "Cannot import more than {} rules at once",
+            MAX_IMPORT_ROWS
+        )));
+    }
+    Ok(rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| (i + 1, row))
+        .collect())
+}
+
+fn decode_json(data: &[u8]) -> Result<Vec<std::result::Result<RuleRow, RowError>>> {
+    let values: Vec<serde_json::Value> = serde_json::from_slice(data)
+        .map_err(|e| Error::validation(format!("Expected a JSON array of rules: {}", e)))?;
+    Ok(values
+        .into_iter()
+        .map(|value| serde_json::from_value(value).map_err(|e| RowError::new("", e.to_string())))
+        .collect())
+}
+
+fn encode_csv(rows: &[RuleRow]) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let write_err = |e: csv::Error| Error::Internal(format!("Failed to encode rules: {}", e));
+
+    writer.write_record(CSV_COLUMNS).map_err(write_err)?;
+    for row in rows {
+        let json = |value: &Option<serde_json::Value>| {
+            value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+        };
+        let optional = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+        writer
+            .write_record([
+                row.backend_id.clone(),
+                row.name.clone(),
+                row.description.clone(),
+                row.priority.to_string(),
+                row.action.clone(),
+                row.enabled.to_string(),
+                join_list(&row.source_ips),
+                join_list(&row.source_ip_blacklist),
+                join_list(&row.source_countries),
+                join_list(&row.source_country_blacklist),
+                join_list(&row.source_asns),
+                join_list(&row.destination_ips),
+                join_list(&row.destination_ports),
+                join_list(&row.protocols),
+                join_list(&row.l7_protocols),
+                optional(row.rate_limit_rps),
+                optional(row.rate_limit_burst),
+                optional(row.rate_limit_window_seconds.map(u64::from)),
+                json(&row.l7_match),
+                json(&row.time_match),
+            ])
+            .map_err(write_err)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| Error::Internal(format!("Failed to encode rules: {}", e)))
+}
+
+fn decode_csv(data: &[u8]) -> Result<Vec<std::result::Result<RuleRow, RowError>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| Error::validation(format!("Invalid CSV header: {}", e)))?
+        .clone();
+    let mut columns = Vec::with_capacity(headers.len());
+    for header in &headers {
+        let column = CSV_COLUMNS
+            .iter()
+            .copied()
+            .find(|c| c.eq_ignore_ascii_case(header))
+            .ok_or_else(|| Error::validation(format!("Unknown column '{}'", header)))?;
+        if columns.contains(&column) {
+            return Err(Error::validation(format!("Duplicate column '{}'", column)));
+        }
+        columns.push(column);
+    }
+    if !columns.contains(&"name") {
+        return Err(Error::validation("The 'name' column is required"));
+    }
+
+    Ok(reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| RowError::new("", e.to_string()))?;
+            let mut row = RuleRow::default();
+            for (column, cell) in columns.iter().zip(record.iter()) {
+                set_cell(&mut row, column, cell)?;
+            }
+            Ok(row)
+        })
+        .collect())
+}
+
+/// Set a field of a row from a CSV cell
+fn set_cell(
+    row: &mut RuleRow,
+    column: &'static str,
+    cell: &str,
+) -> std::result::Result<(), RowError> {
+    fn number<T: std::str::FromStr>(
+        column: &'static str,
+        cell: &str,
+    ) -> std::result::Result<Option<T>, RowError> {
+        if cell.is_empty() {
+            return Ok(None);
+        }
+        cell.parse()
+            .map(Some)
+            .map_err(|_| RowError::new(column, format!("Invalid number '{}'", cell)))
+    }
+    fn json(
+        column: &'static str,
+        cell: &str,
+    ) -> std::result::Result<Option<serde_json::Value>, RowError> {
+        if cell.is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_str(cell)
+            .map(Some)
+            .map_err(|e| RowError::new(column, e.to_string()))
+    }
+
+    match column {
+        "backend_id" => row.backend_id = cell.to_string(),
+        "name" => row.name = cell.to_string(),
+        "description" => row.description = cell.to_string(),
+        "priority" => row.priority = number(column, cell)?.unwrap_or_default(),
+        "action" => row.action = cell.to_string(),
+        "enabled" => {
+            row.enabled = match cell.to_ascii_lowercase().as_str() {
+                "" | "true" | "yes" | "1" => true,
+                "false" | "no" | "0" => false,
+                _ => return Err(RowError::new(column, format!("Invalid boolean '{}'", cell))),
+            }
+        }
+        "source_ips" => row.source_ips = split_list(cell),
+        "source_ip_blacklist" => row.source_ip_blacklist = split_list(cell),
+        "source_countries" => row.source_countries = split_list(cell),
+        "source_country_blacklist" => row.source_country_blacklist = split_list(cell),
+        "source_asns" => row.source_asns = split_list(cell),
+        "destination_ips" => row.destination_ips = split_list(cell),
+        "destination_ports" => row.destination_ports = split_list(cell),
+        "protocols" => row.protocols = split_list(cell),
+        "l7_protocols" => row.l7_protocols = split_list(cell),
+        "rate_limit_rps" => row.rate_limit_rps = number(column, cell)?,
+        "rate_limit_burst" => row.rate_limit_burst = number(column, cell)?,
+        "rate_limit_window_seconds" => row.rate_limit_window_seconds = number(column, cell)?,
+        "l7_match" => row.l7_match = json(column, cell)?,
+        "time_match" => row.time_match = json(column, cell)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn split_list(cell: &str) -> Vec<String> {
+    cell.split(LIST_SEPARATOR)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn join_list(list: &[String]) -> String {
+    list.join(&LIST_SEPARATOR.to_string())
+}
+
+/// Lowercase name of an enum value without its prefix, e.g. `drop` for
+/// `ACTION_DROP`
+fn enum_name(name: &str, prefix: &str) -> String {
+    name.strip_prefix(prefix)
+        .unwrap_or(name)
+        .to_ascii_lowercase()
+}
+
+fn parse_enum<T>(value: &str, prefix: &str, from_name: fn(&str) -> Option<T>) -> Option<T> {
+    let name = value.trim().to_ascii_uppercase().replace('-', "_");
+    from_name(&format!("{}{}", prefix, name)).or_else(|| from_name(&name))
+}
+
+/// Networks as plain addresses, or in CIDR notation when they cover more
+/// than one address
+pub fn format_networks(networks: &[IpNetwork]) -> Vec<String> {
+    networks
+        .iter()
+        .filter_map(|network| {
+            let addr = std::net::IpAddr::try_from(network.address.as_ref()?).ok()?;
+            let cidr = Cidr::new(addr, u8::try_from(network.prefix_length).ok()?);
+            Some(if cidr.prefix == cidr.max_prefix() {
+                addr.to_string()
+            } else {
+                cidr.to_string()
+            })
+        })
+        .collect()
+}
+
+fn parse_networks(
+    field: &'static str,
+    networks: &[String],
+) -> std::result::Result<Vec<IpNetwork>, RowError> {
+    networks
+        .iter()
+        .map(|network| {
+            let cidr: Cidr = network
+                .trim()
+                .parse()
+                .map_err(|_| RowError::new(field, format!("Invalid network '{}'", network)))?;
+            Ok(IpNetwork {
+                address: Some(IpAddress::from(cidr.addr)),
+                prefix_length: u32::from(cidr.prefix),
+            })
+        })
+        .collect()
+}
+
+/// ISO 3166-1 alpha-2 codes, uppercased
+fn parse_countries(
+    field: &'static str,
+    countries: &[String],
+) -> std::result::Result<Vec<String>, RowError> {
+    countries
+        .iter()
+        .map(|country| {
+            let country = country.trim();
+            if country.len() == 2 && country.bytes().all(|b| b.is_ascii_alphabetic()) {
+                Ok(country.to_ascii_uppercase())
+            } else {
+                Err(RowError::new(
+                    field,
+                    format!("Invalid country code '{}'", country),
+                ))
+            }
+        })
+        .collect()
+}
+
+/// AS numbers, with or without an `AS` prefix
+fn parse_asns(asns: &[String]) -> std::result::Result<Vec<String>, RowError> {
+    asns.iter()
+        .map(|asn| {
+            let asn = asn.trim();
+            let digits = asn
+                .strip_prefix("AS")
+                .or_else(|| asn.strip_prefix("as"))
+                .unwrap_or(asn);
+            digits
+                .parse::<u32>()
+                .map(|_| asn.to_string())
+                .map_err(|_| RowError::new("source_asns", format!("Invalid AS number '{}'", asn)))
+        })
+        .collect()
+}
+
+fn parse_ports(ports: &[String]) -> std::result::Result<Vec<PortRange>, RowError> {
+    ports
+        .iter()
+        .map(|port| {
+            let invalid = || {
+                RowError::new(
+                    "destination_ports",
+                    format!("Invalid port or port range '{}'", port),
+                )
+            };
+            let (start, end) = match port.split_once('-') {
+                Some((start, end)) => (start.trim(), end.trim()),
+                None => (port.trim(), port.trim()),
+            };
+            let start: u16 = start.parse().map_err(|_| invalid())?;
+            let end: u16 = end.parse().map_err(|_| invalid())?;
+            if start == 0 || start > end {
+                return Err(invalid());
+            }
+            Ok(PortRange {
+                start: u32::from(start),
+                end: u32::from(end),
+            })
+        })
+        .collect()
+}
+
+/// Outcome of an import
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub total_rows: u32,
+    /// Rules created, or that would be created in a dry run
+    pub imported: u32,
+    pub errors: Vec<ImportRowError>,
+    pub warnings: Vec<String>,
+    /// Backends rules were created on
+    pub backend_ids: Vec<String>,
+}
+
+/// Rule import and export service
+pub struct RuleTransferService {
+    state: AppState,
+    filters: FilterService,
+}
+
+impl RuleTransferService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            filters: FilterService::new(state.clone()),
+            state,
+        }
+    }
+
+    /// Export the rules of an organization, or of one of its backends
+    ///
+    /// Returns the file and the number of rules in it.
+    #[instrument(skip(self))]
+    pub async fn export(
+        &self,
+        org_id: &str,
+        backend_id: Option<&str>,
+        include_disabled: bool,
+        format: TransferFormat,
+    ) -> Result<(Vec<u8>, u32)> {
+        content_type(format)?;
+        if let Some(backend_id) = backend_id {
+            self.check_backend(org_id, backend_id).await?;
+        }
+
+        let rules = self
+            .filters
+            .list_for_organization(org_id, backend_id, include_disabled)
+            .await?;
+        let rows: Vec<RuleRow> = rules
+            .iter()
+            .map(|(backend_id, rule)| RuleRow::from_rule(backend_id, rule))
+            .collect();
+
+        info!(org_id = %org_id, rules = rows.len(), "Exported filter rules");
+        Ok((encode(format, &rows)?, rows.len() as u32))
+    }
+
+    /// Import rules into an organization's backends
+    ///
+    /// Rows without a backend go to `default_backend`. Rows that fail to
+    /// validate are reported and the others imported, unless `dry_run` is
+    /// set, in which case nothing is created.
+    #[instrument(skip(self, data))]
+    pub async fn import(
+        &self,
+        org_id: &str,
+        default_backend: Option<&str>,
+        format: TransferFormat,
+        data: &[u8],
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        let report = self
+            .import_rows(org_id, default_backend, decode(format, data)?, dry_run)
+            .await?;
+
+        info!(
+            org_id = %org_id,
+            rows = report.total_rows,
+            imported = report.imported,
+            errors = report.errors.len(),
+            dry_run,
+            "Imported filter rules"
+        );
+        Ok(report)
+    }
+
+    /// Import numbered rows, as read by [`decode`], into an organization's
+    /// backends
+    pub async fn import_rows(
+        &self,
+        org_id: &str,
+        default_backend: Option<&str>,
+        rows: Vec<(usize, std::result::Result<RuleRow, RowError>)>,
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        if let Some(backend_id) = default_backend {
+            self.check_backend(org_id, backend_id).await?;
+        }
+        let backends = self.backend_ids(org_id).await?;
+
+        let mut report = ImportReport {
+            total_rows: rows.len() as u32,
+            ..Default::default()
+        };
+
+        // Valid rules by backend, with their row numbers
+        let mut batches: BTreeMap<String, Vec<(usize, FilterRule)>> = BTreeMap::new();
+        for (row, parsed) in rows {
+            let result = parsed.and_then(|parsed| {
+                let backend_id = match (parsed.backend_id.trim(), default_backend) {
+                    ("", Some(default)) => default.to_string(),
+                    ("", None) => {
+                        return Err(RowError::new("backend_id", "No backend given"));
+                    }
+                    (backend_id, _) => backend_id.to_string(),
+                };
+                if !backends.contains(&backend_id) {
+                    return Err(RowError::new(
+                        "backend_id",
+                        format!("Backend {} is not part of the organization", backend_id),
+                    ));
+                }
+                Ok((backend_id, parsed.into_rule()?))
+            });
+            match result {
+                Ok((backend_id, rule)) => batches.entry(backend_id).or_default().push((row, rule)),
+                Err(e) => report.errors.push(e.at(row)),
+            }
+        }
+
+        for (backend_id, batch) in batches {
+            let (rows, rules): (Vec<usize>, Vec<FilterRule>) = batch.into_iter().unzip();
+
+            if dry_run {
+                report.imported += rules.len() as u32;
+                report
+                    .warnings
+                    .extend(self.filters.preview_conflicts(&backend_id, &rules).await);
+                continue;
+            }
+
+            let (created, errors, warnings) = self.filters.bulk_create(&backend_id, rules).await?;
+            if !created.is_empty() {
+                report.backend_ids.push(backend_id.clone());
+            }
+            report.imported += created.len() as u32;
+            report.warnings.extend(warnings);
+            report.errors.extend(errors.into_iter().map(|e| {
+                let row = e
+                    .details
+                    .get("index")
+                    .and_then(|i| i.parse::<usize>().ok())
+                    .and_then(|i| rows.get(i).copied())
+                    .unwrap_or_default();
+                RowError::new("", e.message).at(row)
+            }));
+        }
+        report.errors.sort_by_key(|e| e.row);
+        Ok(report)
+    }
+
+    /// IDs of an organization's backends
+    async fn backend_ids(&self, org_id: &str) -> Result<HashSet<String>> {
+        let db = self.state.db_read()?;
+        let ids: Vec<String> =
+            sqlx::query_scalar("SELECT id FROM backends WHERE organization_id = $1")
+                .bind(org_id)
+                .fetch_all(db)
+                .await?;
+        Ok(ids.into_iter().collect())
+    }
+
+    /// Reject backends outside the organization
+    async fn check_backend(&self, org_id: &str, backend_id: &str) -> Result<()> {
+        let db = self.state.db_read()?;
+        let found: Option<String> =
+            sqlx::query_scalar("SELECT id FROM backends WHERE id = $1 AND organization_id = $2")
+                .bind(backend_id)
+                .bind(org_id)
+                .fetch_optional(db)
+                .await?;
+        found
+            .map(|_| ())
+            .ok_or_else(|| Error::not_found("Backend", backend_id))
+    }
+}
@@ -14,17 +14,20 @@ use pistonprotection_common::scoring::{
 use tracing::info;
 
 use super::AppState;
+use super::idempotency::IdempotencyService;
 
 /// Service for IP scoring operations
 pub struct ScoringService {
     state: AppState,
     threat_intel: Arc<ThreatIntelFeed>,
+    idempotency: IdempotencyService,
 }
 
 impl ScoringService {
     /// Create a new scoring service
     pub fn new(state: AppState) -> Self {
         Self {
+            idempotency: IdempotencyService::new(state.clone()),
             state,
             threat_intel: Arc::new(ThreatIntelFeed::new()),
         }
@@ -150,13 +153,21 @@ pub struct ScoringStats {
 /// REST API handlers for scoring service
 pub mod api {
     use super::*;
+    use crate::services::idempotency::{
+        Claim, IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER, IdempotencyError,
+        StoredResponse, canonical_json,
+    };
     use axum::{
         Json, Router,
         extract::{Path, Query, State},
-        http::StatusCode,
-        response::IntoResponse,
+        http::{HeaderMap, StatusCode, header},
+        response::{IntoResponse, Response},
         routing::{delete, get, post},
     };
+    use pistonprotection_common::pagination::{
+        Field, FieldKind, ListSpec, PageRequest, Pageable, Value,
+    };
+    use pistonprotection_proto::Pagination;
     use serde::{Deserialize, Serialize};
 
     /// Create scoring API router
@@ -268,7 +279,7 @@ pub mod api {
         (StatusCode::OK, Json(response)).into_response()
     }
 
-    #[derive(Deserialize)]
+    #[derive(Serialize, Deserialize)]
     pub struct BlockIPRequest {
         pub duration_seconds: Option<u64>,
         pub reason: String,
@@ -283,8 +294,9 @@ pub mod api {
     async fn block_ip(
         State(service): State<Arc<ScoringService>>,
         Path(ip_str): Path<String>,
+        headers: HeaderMap,
         Json(request): Json<BlockIPRequest>,
-    ) -> impl IntoResponse {
+    ) -> Response {
         let ip = match IpAddr::from_str(&ip_str) {
             Ok(ip) => ip,
             Err(_) => {
@@ -294,7 +306,49 @@ pub mod api {
                         success: false,
                         message: "Invalid IP address".to_string(),
                     }),
-                );
+                )
+                    .into_response();
+            }
+        };
+
+        let claim = match headers
+            .get(IDEMPOTENCY_KEY_HEADER)
+            .map(|value| value.to_str().map_err(|_| IdempotencyError::InvalidKey))
+            .transpose()
+        {
+            Ok(key) => {
+                service
+                    .idempotency
+                    .begin("block_ip", "api", key, &canonical_json(&(ip, &request)))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let pending = match claim {
+            Ok(Claim::Proceed(pending)) => pending,
+            Ok(Claim::Replay(stored)) => {
+                return (
+                    StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK),
+                    [
+                        (header::CONTENT_TYPE, "application/json"),
+                        (
+                            header::HeaderName::from_static(IDEMPOTENT_REPLAYED_HEADER),
+                            "true",
+                        ),
+                    ],
+                    stored.body(),
+                )
+                    .into_response();
+            }
+            Err(e) => {
+                return (
+                    StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::BAD_REQUEST),
+                    Json(BlockIPResponse {
+                        success: false,
+                        message: e.to_string(),
+                    }),
+                )
+                    .into_response();
             }
         };
 
@@ -306,13 +360,18 @@ pub mod api {
 
         info!(ip = %ip, reason = %request.reason, "IP blocked via API");
 
-        (
-            StatusCode::OK,
-            Json(BlockIPResponse {
-                success: true,
-                message: format!("IP {} blocked: {}", ip, request.reason),
-            }),
-        )
+        let response = BlockIPResponse {
+            success: true,
+            message: format!("IP {} blocked: {}", ip, request.reason),
+        };
+        if let Some(pending) = pending {
+            let body = serde_json::to_vec(&response).unwrap_or_default();
+            pending
+                .complete(StoredResponse::new(StatusCode::OK.as_u16(), &body))
+                .await;
+        }
+
+        (StatusCode::OK, Json(response)).into_response()
     }
 
     async fn unblock_ip(
@@ -443,9 +502,37 @@ pub mod api {
             .into_response()
     }
 
+    /// Sort and filter fields of blocked IP lists
+    pub static BLOCKED_IP_LIST: ListSpec = ListSpec {
+        fields: &[
+            Field::sortable("threat_score", "threat_score", FieldKind::Integer),
+            Field::sortable("ip", "ip", FieldKind::Text),
+            Field::sortable("total_requests", "total_requests", FieldKind::Integer),
+            Field::sortable("blocked_requests", "blocked_requests", FieldKind::Integer),
+            Field::filterable("block_reason", "block_reason", FieldKind::Text),
+        ],
+        id_column: "ip",
+        default_order: "-threat_score",
+    };
+
+    #[derive(Deserialize)]
+    pub struct ListBlockedQuery {
+        #[serde(default)]
+        pub page_size: u32,
+        #[serde(default)]
+        pub cursor: String,
+        #[serde(default)]
+        pub order_by: String,
+        #[serde(default)]
+        pub filter: String,
+    }
+
     #[derive(Serialize)]
     pub struct BlockedIPsResponse {
         pub count: usize,
+        pub total: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub next_cursor: Option<String>,
         pub ips: Vec<BlockedIPEntry>,
     }
 
@@ -458,26 +545,67 @@ pub mod api {
         pub blocked_requests: u64,
     }
 
-    async fn list_blocked(State(service): State<Arc<ScoringService>>) -> impl IntoResponse {
+    impl Pageable for BlockedIPEntry {
+        fn field_value(&self, field: &str) -> Value {
+            match field {
+                "threat_score" => Value::Integer(self.threat_score.into()),
+                "total_requests" => Value::Integer(self.total_requests as i64),
+                "blocked_requests" => Value::Integer(self.blocked_requests as i64),
+                "block_reason" => Value::Text(self.block_reason.clone().unwrap_or_default()),
+                _ => Value::Text(self.ip.clone()),
+            }
+        }
+
+        fn page_id(&self) -> String {
+            self.ip.clone()
+        }
+    }
+
+    async fn list_blocked(
+        State(service): State<Arc<ScoringService>>,
+        Query(query): Query<ListBlockedQuery>,
+    ) -> Response {
+        let pagination = Pagination {
+            page_size: query.page_size,
+            cursor: query.cursor,
+            order_by: query.order_by,
+            filter: query.filter,
+            ..Default::default()
+        };
+        let request = match PageRequest::new(&BLOCKED_IP_LIST, Some(&pagination)) {
+            Ok(request) => request,
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(BlockIPResponse {
+                        success: false,
+                        message: e.to_string(),
+                    }),
+                )
+                    .into_response();
+            }
+        };
+
         let blocked = service.get_blocked_ips();
-        let entries: Vec<BlockedIPEntry> = blocked
-            .iter()
-            .map(|r| BlockedIPEntry {
-                ip: r.ip.to_string(),
-                threat_score: r.threat_score,
-                block_reason: r.block_reason.clone(),
-                total_requests: r.total_requests,
-                blocked_requests: r.blocked_requests,
-            })
-            .collect();
+        let entries = blocked.iter().map(|r| BlockedIPEntry {
+            ip: r.ip.to_string(),
+            threat_score: r.threat_score,
+            block_reason: r.block_reason.clone(),
+            total_requests: r.total_requests,
+            blocked_requests: r.blocked_requests,
+        });
+        let (page, total) = request.apply(entries);
 
         (
             StatusCode::OK,
             Json(BlockedIPsResponse {
-                count: entries.len(),
-                ips: entries,
+                count: page.items.len(),
+                total,
+                next_cursor: page.next_cursor,
+                ips: page.items,
             }),
         )
+            .into_response()
     }
 
     #[derive(Deserialize)]
@@ -512,6 +640,8 @@ pub mod api {
             StatusCode::OK,
             Json(BlockedIPsResponse {
                 count: entries.len(),
+                total: entries.len() as u64,
+                next_cursor: None,
                 ips: entries,
             }),
         )
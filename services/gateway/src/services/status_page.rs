@@ -0,0 +1,621 @@
+//! Public status pages
+//!
+//! An organization can publish the status of its backends at
+//! `/status/{token}`. The page shows the current availability seen by the
+//! synthetic probes, whether an attack is being mitigated, the daily uptime
+//! over the last 90 days and recent attacks. It is public, so it only
+//! exposes backend names and coarse states, and is cached for a short time
+//! to keep a flood of visitors off the database.
+
+use crate::services::AppState;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::backend::StatusPage;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+/// Title used when the organization leaves it unset
+pub const DEFAULT_TITLE: &str = "Service Status";
+
+/// Longest title
+pub const MAX_TITLE_LEN: usize = 255;
+
+/// Days of uptime history shown
+pub const UPTIME_DAYS: i64 = 90;
+
+/// Days of attack history shown
+pub const INCIDENT_DAYS: i64 = 30;
+
+/// Most attacks listed
+const MAX_INCIDENTS: i64 = 50;
+
+/// Probe results older than this no longer count as current
+const PROBE_FRESHNESS: Duration = Duration::minutes(5);
+
+/// How long a rendered status is served from the cache
+const CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Current state of a backend on the status page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentState {
+    /// No recent probe results
+    Unknown,
+    Operational,
+    /// Reachable while an attack is being filtered
+    Mitigating,
+    /// Unreachable from some regions
+    Degraded,
+    /// Unreachable from every region
+    Outage,
+}
+
+impl ComponentState {
+    pub fn label(self) -> &'static str {
+        match self {
+            ComponentState::Unknown => "No data",
+            ComponentState::Operational => "Operational",
+            ComponentState::Mitigating => "Mitigating attack",
+            ComponentState::Degraded => "Degraded",
+            ComponentState::Outage => "Outage",
+        }
+    }
+
+    fn severity(self) -> u8 {
+        match self {
+            ComponentState::Unknown => 0,
+            ComponentState::Operational => 1,
+            ComponentState::Mitigating => 2,
+            ComponentState::Degraded => 3,
+            ComponentState::Outage => 4,
+        }
+    }
+}
+
+/// Availability of a backend over one day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyUptime {
+    pub date: NaiveDate,
+    /// Share of successful probes, 0 to 1
+    pub availability: f64,
+}
+
+/// Backend shown on a status page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentStatus {
+    pub backend_id: String,
+    pub name: String,
+    pub state: ComponentState,
+    /// Share of regions whose latest probe succeeded, unset without recent
+    /// probes
+    pub availability: Option<f64>,
+    /// Uptime over the history shown, in percent
+    pub uptime_percent: Option<f64>,
+    pub daily: Vec<DailyUptime>,
+}
+
+/// Attack shown on a status page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentSummary {
+    pub backend_name: String,
+    pub attack_type: String,
+    pub started_at: DateTime<Utc>,
+    /// Unset while the attack is ongoing
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+/// Content of a public status page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicStatus {
+    pub title: String,
+    pub state: ComponentState,
+    pub components: Vec<ComponentStatus>,
+    pub incidents: Vec<IncidentSummary>,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Status page service implementation
+pub struct StatusPageService {
+    state: AppState,
+}
+
+impl StatusPageService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    /// Get the status page of an organization
+    #[instrument(skip(self))]
+    pub async fn get(&self, organization_id: &str) -> Result<Option<StatusPage>> {
+        let db = self.state.db()?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT organization_id, token, title, enabled, hidden_backend_ids,
+                   created_at, updated_at
+            FROM status_pages
+            WHERE organization_id = $1
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(row.map(|row| status_page_from_row(&row)))
+    }
+
+    /// Configure the status page of an organization, creating it with a new
+    /// token the first time
+    #[instrument(skip(self, hidden_backend_ids))]
+    pub async fn update(
+        &self,
+        organization_id: &str,
+        enabled: bool,
+        title: &str,
+        hidden_backend_ids: Vec<String>,
+    ) -> Result<StatusPage> {
+        let db = self.state.db()?;
+        let title = validate_title(title)?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO status_pages (organization_id, token, title, enabled, hidden_backend_ids)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (organization_id) DO UPDATE
+            SET title = EXCLUDED.title,
+                enabled = EXCLUDED.enabled,
+                hidden_backend_ids = EXCLUDED.hidden_backend_ids
+            RETURNING organization_id, token, title, enabled, hidden_backend_ids,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(organization_id)
+        .bind(generate_token())
+        .bind(&title)
+        .bind(enabled)
+        .bind(&hidden_backend_ids)
+        .fetch_one(db)
+        .await?;
+
+        let page = status_page_from_row(&row);
+        self.invalidate(&page.token).await;
+        info!(organization_id = %organization_id, enabled, "Updated status page");
+
+        Ok(page)
+    }
+
+    /// Give the status page of an organization a new token. The old URL
+    /// stops working right away.
+    #[instrument(skip(self))]
+    pub async fn rotate_token(&self, organization_id: &str) -> Result<StatusPage> {
+        let db = self.state.db()?;
+        let old = self
+            .get(organization_id)
+            .await?
+            .ok_or_else(|| Error::not_found("StatusPage", organization_id))?;
+
+        let row = sqlx::query(
+            r#"
+            UPDATE status_pages
+            SET token = $2
+            WHERE organization_id = $1
+            RETURNING organization_id, token, title, enabled, hidden_backend_ids,
+                      created_at, updated_at
+            "#,
+        )
+        .bind(organization_id)
+        .bind(generate_token())
+        .fetch_one(db)
+        .await?;
+
+        self.invalidate(&old.token).await;
+        info!(organization_id = %organization_id, "Rotated status page token");
+
+        Ok(status_page_from_row(&row))
+    }
+
+    /// Build the public status behind a token. Unknown tokens and disabled
+    /// pages give `None`.
+    #[instrument(skip(self, token))]
+    pub async fn public_status(&self, token: &str) -> Result<Option<PublicStatus>> {
+        if !valid_token(token) {
+            return Ok(None);
+        }
+
+        if let Some(cache) = &self.state.cache {
+            if let Ok(Some(status)) = cache.get::<PublicStatus>(&cache_key(token)).await {
+                return Ok(Some(status));
+            }
+        }
+
+        let db = self.state.db_read()?;
+        let Some(page) = sqlx::query(
+            r#"
+            SELECT organization_id, title, hidden_backend_ids
+            FROM status_pages
+            WHERE token = $1 AND enabled
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(db)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let organization_id: String = page.get("organization_id");
+        let hidden: HashSet<String> = page
+            .get::<Vec<String>, _>("hidden_backend_ids")
+            .into_iter()
+            .collect();
+        let title: String = page.get("title");
+
+        let backends: Vec<(String, String)> = sqlx::query(
+            r#"
+            SELECT id, name
+            FROM backends
+            WHERE organization_id = $1
+            ORDER BY name
+            "#,
+        )
+        .bind(&organization_id)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| (row.get("id"), row.get("name")))
+        .filter(|(id, _)| !hidden.contains(id))
+        .collect();
+        let backend_ids: Vec<String> = backends.iter().map(|(id, _)| id.clone()).collect();
+        let names: HashMap<&str, &str> = backends
+            .iter()
+            .map(|(id, name)| (id.as_str(), name.as_str()))
+            .collect();
+
+        let now = Utc::now();
+
+        // Latest probe result of each region
+        let mut regions: HashMap<String, Vec<f64>> = HashMap::new();
+        for row in sqlx::query(
+            r#"
+            SELECT DISTINCT ON (backend_id, region) backend_id, availability
+            FROM probe_results_ts
+            WHERE backend_id = ANY($1) AND timestamp > $2
+            ORDER BY backend_id, region, timestamp DESC
+            "#,
+        )
+        .bind(&backend_ids)
+        .bind(now - PROBE_FRESHNESS)
+        .fetch_all(db)
+        .await?
+        {
+            regions
+                .entry(row.get("backend_id"))
+                .or_default()
+                .push(row.get("availability"));
+        }
+
+        let mitigating: HashSet<String> = sqlx::query(
+            r#"
+            SELECT DISTINCT backend_id
+            FROM attack_events
+            WHERE backend_id = ANY($1) AND ended_at IS NULL
+            "#,
+        )
+        .bind(&backend_ids)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| row.get("backend_id"))
+        .collect();
+
+        let mut daily: HashMap<String, Vec<DailyUptime>> = HashMap::new();
+        for row in sqlx::query(
+            r#"
+            SELECT backend_id, date_trunc('day', timestamp) AS day,
+                   AVG(availability) AS availability
+            FROM probe_results_ts
+            WHERE backend_id = ANY($1) AND timestamp > $2
+            GROUP BY backend_id, day
+            ORDER BY day
+            "#,
+        )
+        .bind(&backend_ids)
+        .bind(now - Duration::days(UPTIME_DAYS))
+        .fetch_all(db)
+        .await?
+        {
+            daily
+                .entry(row.get("backend_id"))
+                .or_default()
+                .push(DailyUptime {
+                    date: row.get::<DateTime<Utc>, _>("day").date_naive(),
+                    availability: row.get("availability"),
+                });
+        }
+
+        let incidents = sqlx::query(
+            r#"
+            SELECT backend_id, attack_type, started_at, ended_at
+            FROM attack_events
+            WHERE backend_id = ANY($1) AND started_at > $2
+            ORDER BY started_at DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(&backend_ids)
+        .bind(now - Duration::days(INCIDENT_DAYS))
+        .bind(MAX_INCIDENTS)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| {
+            let backend_id: String = row.get("backend_id");
+            IncidentSummary {
+                backend_name: names
+                    .get(backend_id.as_str())
+                    .copied()
+                    .unwrap_or_default()
+                    .to_string(),
+                attack_type: row.get("attack_type"),
+                started_at: row.get("started_at"),
+                ended_at: row.get("ended_at"),
+            }
+        })
+        .collect();
+
+        let components: Vec<ComponentStatus> = backends
+            .into_iter()
+            .map(|(backend_id, name)| {
+                let availability = regions.get(&backend_id).map(|r| mean(r));
+                let daily = daily.remove(&backend_id).unwrap_or_default();
+                ComponentStatus {
+                    state: component_state(availability, mitigating.contains(&backend_id)),
+                    availability,
+                    uptime_percent: uptime_percent(&daily),
+                    daily,
+                    backend_id,
+                    name,
+                }
+            })
+            .collect();
+
+        let status = PublicStatus {
+            title: if title.is_empty() {
+                DEFAULT_TITLE.to_string()
+            } else {
+                title
+            },
+            state: overall_state(components.iter().map(|c| c.state)),
+            components,
+            incidents,
+            generated_at: now,
+        };
+
+        if let Some(cache) = &self.state.cache {
+            if let Err(e) = cache.set(&cache_key(token), &status, CACHE_TTL).await {
+                warn!(error = %e, "Failed to cache status page");
+            }
+        }
+
+        Ok(Some(status))
+    }
+
+    async fn invalidate(&self, token: &str) {
+        if let Some(cache) = &self.state.cache {
+            let _ = cache.delete(&cache_key(token)).await;
+        }
+    }
+}
+
+fn status_page_from_row(row: &sqlx::postgres::PgRow) -> StatusPage {
+    let token: String = row.get("token");
+    let created_at: Option<DateTime<Utc>> = row.get("created_at");
+    let updated_at: Option<DateTime<Utc>> = row.get("updated_at");
+
+    StatusPage {
+        organization_id: row.get("organization_id"),
+        enabled: row.get("enabled"),
+        title: row.get("title"),
+        path: status_page_path(&token),
+        token,
+        hidden_backend_ids: row.get("hidden_backend_ids"),
+        created_at: created_at.map(Into::into),
+        updated_at: updated_at.map(Into::into),
+    }
+}
+
+fn cache_key(token: &str) -> String {
+    format!("status_page:{}", token)
+}
+
+fn generate_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Path of a status page on the gateway
+pub fn status_page_path(token: &str) -> String {
+    format!("/status/{}", token)
+}
+
+/// Whether a token can belong to a status page, checked before touching the
+/// database
+pub fn valid_token(token: &str) -> bool {
+    token.len() == 32 && token.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Validate a status page title
+pub fn validate_title(title: &str) -> Result<String> {
+    let title = title.trim();
+    if title.chars().count() > MAX_TITLE_LEN {
+        return Err(Error::validation(format!(
+            "Title cannot exceed {} characters",
+            MAX_TITLE_LEN
+        )));
+    }
+    Ok(title.to_string())
+}
+
+/// State of a backend from the share of regions reaching it and whether an
+/// attack on it is being mitigated
+pub fn component_state(availability: Option<f64>, mitigating: bool) -> ComponentState {
+    match availability {
+        None if mitigating => ComponentState::Mitigating,
+        None => ComponentState::Unknown,
+        Some(a) if a <= 0.0 => ComponentState::Outage,
+        Some(a) if a < 1.0 => ComponentState::Degraded,
+        Some(_) if mitigating => ComponentState::Mitigating,
+        Some(_) => ComponentState::Operational,
+    }
+}
+
+/// State of a whole page: the worst state of its backends
+pub fn overall_state(states: impl IntoIterator<Item = ComponentState>) -> ComponentState {
+    states
+        .into_iter()
+        .max_by_key(|s| s.severity())
+        .unwrap_or(ComponentState::Unknown)
+}
+
+/// Uptime in percent over the days with probe results
+pub fn uptime_percent(daily: &[DailyUptime]) -> Option<f64> {
+    if daily.is_empty() {
+        return None;
+    }
+    let total: f64 = daily.iter().map(|d| d.availability).sum();
+    Some(total / daily.len() as f64 * 100.0)
+}
+
+/// Escape text for HTML
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render a status page as a standalone HTML document
+pub fn render_html(status: &PublicStatus) -> String {
+    let title = escape_html(&status.title);
+    let mut html = String::new();
+
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta http-equiv="refresh" content="60">
+<title>{title}</title>
+<style>
+body{{font-family:system-ui,sans-serif;max-width:56rem;margin:2rem auto;padding:0 1rem;color:#1f2933}}
+.banner{{padding:1rem;border-radius:.5rem;color:#fff;font-weight:600}}
+.component{{border-bottom:1px solid #e4e7eb;padding:1rem 0}}
+.bars{{display:flex;gap:1px;height:2rem;margin-top:.5rem}}
+.bars span{{flex:1;border-radius:1px}}
+.unknown{{background:#9aa5b1}}.operational{{background:#3ebd93}}.mitigating{{background:#2680c2}}
+.degraded{{background:#f0b429}}.outage{{background:#e12d39}}
+.muted{{color:#7b8794;font-size:.875rem}}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<div class="banner {state_class}">{state_label}</div>
+"#,
+        state_class = state_class(status.state),
+        state_label = overall_label(status.state),
+    );
+
+    html.push_str("<h2>Services</h2>\n");
+    for component in &status.components {
+        let uptime = component
+            .uptime_percent
+            .map(|u| format!("{:.2}% uptime", u))
+            .unwrap_or_else(|| "No history".to_string());
+        let _ = write!(
+            html,
+            r#"<div class="component"><strong>{}</strong> <span class="{}">&nbsp;</span> {} <span class="muted">{}</span>
+<div class="bars">"#,
+            escape_html(&component.name),
+            state_class(component.state),
+            component.state.label(),
+            uptime,
+        );
+        for day in &component.daily {
+            let _ = write!(
+                html,
+                r#"<span class="{}" title="{}: {:.2}%"></span>"#,
+                state_class(component_state(Some(day.availability), false)),
+                day.date,
+                day.availability * 100.0,
+            );
+        }
+        html.push_str("</div></div>\n");
+    }
+
+    let _ = writeln!(html, "<h2>Attacks in the last {} days</h2>", INCIDENT_DAYS);
+    if status.incidents.is_empty() {
+        html.push_str("<p class=\"muted\">No attacks.</p>\n");
+    } else {
+        html.push_str("<ul>\n");
+        for incident in &status.incidents {
+            let ended = incident
+                .ended_at
+                .map(|t| format!("mitigated until {}", t.format("%Y-%m-%d %H:%M UTC")))
+                .unwrap_or_else(|| "being mitigated".to_string());
+            let _ = writeln!(
+                html,
+                "<li><strong>{}</strong>: {} attack since {}, {}</li>",
+                escape_html(&incident.backend_name),
+                escape_html(&incident.attack_type),
+                incident.started_at.format("%Y-%m-%d %H:%M UTC"),
+                ended,
+            );
+        }
+        html.push_str("</ul>\n");
+    }
+
+    let _ = write!(
+        html,
+        "<p class=\"muted\">Updated {}</p>\n</body>\n</html>\n",
+        status.generated_at.format("%Y-%m-%d %H:%M:%S UTC"),
+    );
+
+    html
+}
+
+fn state_class(state: ComponentState) -> &'static str {
+    match state {
+        ComponentState::Unknown => "unknown",
+        ComponentState::Operational => "operational",
+        ComponentState::Mitigating => "mitigating",
+        ComponentState::Degraded => "degraded",
+        ComponentState::Outage => "outage",
+    }
+}
+
+fn overall_label(state: ComponentState) -> &'static str {
+    match state {
+        ComponentState::Unknown => "No status data yet",
+        ComponentState::Operational => "All systems operational",
+        ComponentState::Mitigating => "Mitigating an attack, all systems operational",
+        ComponentState::Degraded => "Partial outage",
+        ComponentState::Outage => "Major outage",
+    }
+}
@@ -0,0 +1,219 @@
+//! Trial suspensions
+//!
+//! When a trial ends unpaid and the grace period is over, the auth service
+//! may suspend the organization's protection instead of moving it to the
+//! free plan (`trial_outcome = 'suspended'`). The monitor switches the
+//! organization's backends to passthrough by the system actor, renewing the
+//! mode before it reverts and covering backends added later, and switches
+//! them back to protection once the subscription is paid. A mode an operator
+//! chose after the suspension started is left alone.
+
+use crate::services::AppState;
+use crate::services::backend_mode::{BackendModeService, MAX_REVERT_SECONDS, SYSTEM_ACTOR};
+use chrono::{DateTime, Utc};
+use pistonprotection_common::error::Result;
+use pistonprotection_proto::backend::{BackendMode, BackendModeState};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+
+/// Reason of the passthrough mode of suspended backends
+pub const SUSPENSION_REASON: &str = "Trial expired without a paid subscription";
+
+/// Reason of the switch back to protection
+pub const RESTORE_REASON: &str = "Subscription paid, trial suspension lifted";
+
+/// A suspension is renewed once it reverts within this many seconds
+pub const RENEW_BEFORE_SECONDS: i64 = 24 * 3600;
+
+/// How often the monitor enforces suspensions
+const MONITOR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Suspended organizations and when their suspension started
+pub const SUSPENDED_QUERY: &str = r#"
+    SELECT organization_id, protection_suspended_at FROM subscriptions
+    WHERE trial_outcome = 'suspended' AND protection_suspended_at IS NOT NULL
+"#;
+
+/// Organizations still marked suspended whose trial outcome changed since
+pub const RESTORED_QUERY: &str = r#"
+    SELECT organization_id FROM subscriptions
+    WHERE protection_suspended_at IS NOT NULL
+      AND trial_outcome IS DISTINCT FROM 'suspended'
+"#;
+
+/// Clear the suspension of the organization `$1`, unless it was suspended
+/// again meanwhile
+pub const CLEAR_SUSPENSION_QUERY: &str = r#"
+    UPDATE subscriptions SET protection_suspended_at = NULL, updated_at = NOW()
+    WHERE organization_id = $1 AND trial_outcome IS DISTINCT FROM 'suspended'
+"#;
+
+/// Backends of the organization `$1`
+pub const BACKEND_IDS_QUERY: &str = "SELECT id FROM backends WHERE organization_id = $1";
+
+/// Whether a backend is in the passthrough mode of a trial suspension
+pub fn is_suspension(mode: &BackendModeState) -> bool {
+    mode.mode == BackendMode::Passthrough as i32
+        && mode.changed_by == SYSTEM_ACTOR
+        && mode.reason == SUSPENSION_REASON
+}
+
+/// Whether a backend of an organization suspended at `suspended_at` has to
+/// be switched (back) to the suspension
+pub fn needs_suspension(
+    mode: &BackendModeState,
+    suspended_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> bool {
+    if is_suspension(mode) {
+        let revert_at = mode.revert_at.as_ref().map(DateTime::<Utc>::from);
+        return revert_at.is_none_or(|at| (at - now).num_seconds() < RENEW_BEFORE_SECONDS);
+    }
+
+    // An operator chose a mode since the suspension started
+    let changed_at = mode.changed_at.as_ref().map(DateTime::<Utc>::from);
+    mode.changed_by == SYSTEM_ACTOR || changed_at.is_none_or(|at| at <= suspended_at)
+}
+
+/// Trial suspension service implementation
+pub struct TrialSuspensionService {
+    state: AppState,
+    modes: BackendModeService,
+}
+
+impl TrialSuspensionService {
+    pub fn new(state: AppState) -> Self {
+        Self {
+            modes: BackendModeService::new(state.clone()),
+            state,
+        }
+    }
+
+    /// Suspend the backends of suspended organizations and restore the
+    /// backends of organizations that paid since
+    #[instrument(skip(self))]
+    pub async fn enforce(&self) -> Result<()> {
+        let db = self.state.db()?;
+
+        let suspended: Vec<(String, DateTime<Utc>)> =
+            sqlx::query_as(SUSPENDED_QUERY).fetch_all(db).await?;
+        for (organization_id, suspended_at) in suspended {
+            if let Err(e) = self.suspend(&organization_id, suspended_at).await {
+                warn!(
+                    organization_id = %organization_id,
+                    error = %e,
+                    "Failed to suspend trial backends"
+                );
+            }
+        }
+
+        let restored: Vec<(String,)> = sqlx::query_as(RESTORED_QUERY).fetch_all(db).await?;
+        for (organization_id,) in restored {
+            if let Err(e) = self.restore(&organization_id).await {
+                warn!(
+                    organization_id = %organization_id,
+                    error = %e,
+                    "Failed to restore trial backends"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn suspend(&self, organization_id: &str, suspended_at: DateTime<Utc>) -> Result<()> {
+        let now = Utc::now();
+        for backend_id in self.backend_ids(organization_id).await? {
+            let (mode, _) = self.modes.get(&backend_id, 1).await?;
+            if !needs_suspension(&mode, suspended_at, now) {
+                continue;
+            }
+            self.modes
+                .set(
+                    &backend_id,
+                    BackendMode::Passthrough,
+                    MAX_REVERT_SECONDS,
+                    SUSPENSION_REASON,
+                    SYSTEM_ACTOR,
+                )
+                .await?;
+            if !is_suspension(&mode) {
+                info!(
+                    organization_id = %organization_id,
+                    backend_id = %backend_id,
+                    "Suspended protection of expired trial"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Switch suspended backends back to protection, then clear the
+    /// suspension, so a failed switch is retried
+    async fn restore(&self, organization_id: &str) -> Result<()> {
+        let db = self.state.db()?;
+
+        for backend_id in self.backend_ids(organization_id).await? {
+            let (mode, _) = self.modes.get(&backend_id, 1).await?;
+            if !is_suspension(&mode) {
+                continue;
+            }
+            self.modes
+                .set(
+                    &backend_id,
+                    BackendMode::Protect,
+                    0,
+                    RESTORE_REASON,
+                    SYSTEM_ACTOR,
+                )
+                .await?;
+        }
+
+        sqlx::query(CLEAR_SUSPENSION_QUERY)
+            .bind(organization_id)
+            .execute(db)
+            .await?;
+
+        info!(organization_id = %organization_id, "Restored protection after trial suspension");
+        Ok(())
+    }
+
+    async fn backend_ids(&self, organization_id: &str) -> Result<Vec<String>> {
+        let db = self.state.db()?;
+
+        let rows: Vec<(String,)> = sqlx::query_as(BACKEND_IDS_QUERY)
+            .bind(organization_id)
+            .fetch_all(db)
+            .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+}
+
+/// Spawn the monitor enforcing trial suspensions
+pub fn spawn_monitor(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Option<JoinHandle<()>> {
+    if state.db.is_none() || state.cache.is_none() {
+        info!("Trial suspension monitor disabled");
+        return None;
+    }
+
+    let service = TrialSuspensionService::new(state);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => break,
+                _ = interval.tick() => {
+                    if let Err(e) = service.enforce().await {
+                        warn!(error = %e, "Failed to enforce trial suspensions");
+                    }
+                }
+            }
+        }
+    }))
+}
@@ -0,0 +1,305 @@
+//! Tests for two-person approval
+
+use super::mock_db::create_test_filter_rule;
+use super::test_utils::{assert_grpc_status_code, constants, create_test_app_state};
+use crate::middleware::auth::{AuthContext, AuthMethod};
+use crate::services::approval::{
+    ApprovalService, Decision, Operation, check_decider, is_wide_network, protection_active,
+    validate_approver_role, wide_allowlist, widens_allowlist,
+};
+use crate::services::overview::OrgRole;
+use pistonprotection_common::error::Error;
+use pistonprotection_proto::backend::backend_service_server::BackendService;
+use pistonprotection_proto::backend::*;
+use pistonprotection_proto::common::{Action, IpAddress, IpNetwork};
+use pistonprotection_proto::filter::{FilterMatch, FilterRule};
+use tonic::Code;
+
+fn network(addr: &str, prefix_length: u32) -> IpNetwork {
+    IpNetwork {
+        address: Some(IpAddress::from(addr.parse::<std::net::IpAddr>().unwrap())),
+        prefix_length,
+    }
+}
+
+fn allow_rule(source: IpNetwork) -> FilterRule {
+    let mut rule = create_test_filter_rule("rule-1", "Allow partners");
+    rule.action = Action::Allow as i32;
+    rule.r#match = Some(FilterMatch {
+        source_ips: vec![source],
+        ..Default::default()
+    });
+    rule
+}
+
+fn context(user_id: &str, role: &str, method: AuthMethod) -> AuthContext {
+    AuthContext {
+        user_id: user_id.to_string(),
+        email: format!("{}@example.com", user_id),
+        role: role.to_string(),
+        organizations: vec![constants::TEST_ORG_ID.to_string()],
+        auth_method: method,
+        api_key_id: None,
+        support_grant_id: None,
+    }
+}
+
+/// Test networks of a /8 (IPv4) or /32 (IPv6) and larger are wide
+#[test]
+fn test_is_wide_network() {
+    assert!(is_wide_network(&network("10.0.0.0", 8)));
+    assert!(is_wide_network(&network("0.0.0.0", 0)));
+    assert!(!is_wide_network(&network("10.0.0.0", 9)));
+    assert!(is_wide_network(&network("2001:db8::", 32)));
+    assert!(!is_wide_network(&network("2001:db8::", 48)));
+    assert!(!is_wide_network(&IpNetwork {
+        address: None,
+        prefix_length: 0,
+    }));
+}
+
+/// Test only enabled allow rules allowlist networks
+#[test]
+fn test_wide_allowlist() {
+    let rule = allow_rule(network("10.0.0.0", 8));
+    assert_eq!(wide_allowlist(&rule).len(), 1);
+
+    let mut drop = rule.clone();
+    drop.action = Action::Drop as i32;
+    assert!(wide_allowlist(&drop).is_empty());
+
+    let mut disabled = rule;
+    disabled.enabled = false;
+    assert!(wide_allowlist(&disabled).is_empty());
+
+    assert!(wide_allowlist(&allow_rule(network("192.0.2.0", 24))).is_empty());
+}
+
+/// Test saving a rule needs approval only when it widens the allowlist
+#[test]
+fn test_widens_allowlist() {
+    let narrow = allow_rule(network("192.0.2.0", 24));
+    let wide = allow_rule(network("10.0.0.0", 8));
+
+    assert!(widens_allowlist(None, &wide));
+    assert!(!widens_allowlist(None, &narrow));
+    assert!(widens_allowlist(Some(&narrow), &wide));
+
+    // Renaming an approved allowlist keeps it as it was
+    let mut renamed = wide.clone();
+    renamed.name = "Allow carrier".to_string();
+    assert!(!widens_allowlist(Some(&wide), &renamed));
+
+    // Enabling it again lets the network through again
+    let mut disabled = wide.clone();
+    disabled.enabled = false;
+    assert!(widens_allowlist(Some(&disabled), &wide));
+}
+
+/// Test protection filters only when enabled and not off
+#[test]
+fn test_protection_active() {
+    let mut protection = ProtectionSettings {
+        enabled: true,
+        level: ProtectionLevel::Medium as i32,
+        ..Default::default()
+    };
+    assert!(protection_active(&protection));
+
+    protection.level = ProtectionLevel::Off as i32;
+    assert!(!protection_active(&protection));
+
+    assert!(!protection_active(&ProtectionSettings::default()));
+}
+
+/// Test which operations may need approval
+#[test]
+fn test_operation_is_high_impact() {
+    assert!(Operation::DeleteBackend.is_high_impact());
+    assert!(
+        Operation::SetProtectionLevel {
+            level: ProtectionLevel::Off as i32
+        }
+        .is_high_impact()
+    );
+    assert!(
+        !Operation::SetProtectionLevel {
+            level: ProtectionLevel::High as i32
+        }
+        .is_high_impact()
+    );
+    assert!(
+        Operation::SetBackendMode {
+            mode: BackendMode::Passthrough as i32,
+            revert_after_seconds: 600,
+            reason: "debugging".to_string(),
+        }
+        .is_high_impact()
+    );
+    assert!(
+        !Operation::SetBackendMode {
+            mode: BackendMode::BlockAll as i32,
+            revert_after_seconds: 600,
+            reason: "attack".to_string(),
+        }
+        .is_high_impact()
+    );
+    assert!(
+        Operation::UpdateProtection {
+            protection: ProtectionSettings::default()
+        }
+        .is_high_impact()
+    );
+    assert!(
+        Operation::CreateRule {
+            rule: allow_rule(network("10.0.0.0", 8))
+        }
+        .is_high_impact()
+    );
+    assert!(
+        !Operation::UpdateRule {
+            rule: allow_rule(network("192.0.2.0", 24))
+        }
+        .is_high_impact()
+    );
+}
+
+/// Test operations are described for the people deciding on them
+#[test]
+fn test_operation_describe() {
+    let operation = Operation::CreateRule {
+        rule: allow_rule(network("10.0.0.0", 8)),
+    };
+    assert_eq!(operation.kind(), ApprovalActionKind::WideAllowlist);
+    assert_eq!(
+        operation.describe("lobby"),
+        "Allow 10.0.0.0/8 on backend lobby with rule 'Allow partners'"
+    );
+    assert_eq!(
+        Operation::DeleteBackend.describe("lobby"),
+        "Delete backend lobby"
+    );
+}
+
+/// Test operations are stored and read back unchanged
+#[test]
+fn test_operation_round_trip() {
+    let operation = Operation::SetBackendMode {
+        mode: BackendMode::Passthrough as i32,
+        revert_after_seconds: 900,
+        reason: "checking origin".to_string(),
+    };
+    let json = serde_json::to_value(&operation).unwrap();
+    assert_eq!(json["operation"], "set_backend_mode");
+    assert_eq!(
+        serde_json::from_value::<Operation>(json).unwrap(),
+        operation
+    );
+}
+
+/// Test who may approve and reject
+#[test]
+fn test_check_decider() {
+    let admin = context("user-2", "user", AuthMethod::Jwt);
+
+    assert!(
+        check_decider(
+            Decision::Approve,
+            &admin,
+            Some(OrgRole::Admin),
+            OrgRole::Admin,
+            "user-1"
+        )
+        .is_ok()
+    );
+    assert!(matches!(
+        check_decider(
+            Decision::Approve,
+            &admin,
+            Some(OrgRole::Member),
+            OrgRole::Admin,
+            "user-1"
+        ),
+        Err(Error::Forbidden(_))
+    ));
+
+    // Requesters withdraw their actions but never approve them
+    assert!(matches!(
+        check_decider(
+            Decision::Approve,
+            &admin,
+            Some(OrgRole::Owner),
+            OrgRole::Admin,
+            "user-2"
+        ),
+        Err(Error::Forbidden(_))
+    ));
+    assert!(
+        check_decider(
+            Decision::Reject,
+            &admin,
+            Some(OrgRole::Viewer),
+            OrgRole::Admin,
+            "user-2"
+        )
+        .is_ok()
+    );
+
+    // API keys are not a second person
+    let key = context("user-2", "user", AuthMethod::ApiKey);
+    assert!(matches!(
+        check_decider(
+            Decision::Approve,
+            &key,
+            Some(OrgRole::Owner),
+            OrgRole::Admin,
+            "user-1"
+        ),
+        Err(Error::Forbidden(_))
+    ));
+
+    // Platform admins approve for any organization
+    let platform = context("user-3", "admin", AuthMethod::Jwt);
+    assert!(check_decider(Decision::Approve, &platform, None, OrgRole::Owner, "user-1").is_ok());
+}
+
+/// Test approver roles default to admin and exclude viewers
+#[test]
+fn test_validate_approver_role() {
+    assert_eq!(validate_approver_role("").unwrap(), OrgRole::Admin);
+    assert_eq!(validate_approver_role("owner").unwrap(), OrgRole::Owner);
+    assert!(validate_approver_role("viewer").is_err());
+    assert!(validate_approver_role("root").is_err());
+}
+
+/// Test routine operations are applied without looking up a policy
+#[tokio::test]
+async fn test_hold_routine_operation() {
+    let service = ApprovalService::new(create_test_app_state());
+    let held = service
+        .hold(
+            constants::TEST_BACKEND_ID,
+            Operation::CreateRule {
+                rule: allow_rule(network("192.0.2.0", 24)),
+            },
+            "user-1",
+        )
+        .await
+        .unwrap();
+    assert!(held.is_none());
+}
+
+/// Test deciding on an action requires an authenticated user
+#[tokio::test]
+async fn test_approve_requires_user() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let result = service
+        .approve_pending_action(tonic::Request::new(ApprovePendingActionRequest {
+            id: "action-1".to_string(),
+            reason: String::new(),
+        }))
+        .await;
+
+    assert_grpc_status_code(&result.unwrap_err(), Code::Unauthenticated);
+}
@@ -0,0 +1,30 @@
+//! Tests for the organization audit log
+
+use crate::services::audit::AuditLogBuilder;
+use serde_json::json;
+
+/// Test audit entries carry what the builder was given
+#[test]
+fn test_audit_log_builder() {
+    let entry = AuditLogBuilder::new("org-001", "approval.requested", "pending_action")
+        .user("user-001", Some("user@example.com"))
+        .resource("action-001")
+        .description("Delete backend web")
+        .metadata("backend_id", "backend-001")
+        .metadata("expiry_seconds", 3600u32)
+        .request_info(Some("192.0.2.1"), None)
+        .build();
+
+    assert_eq!(entry.organization_id, "org-001");
+    assert_eq!(entry.action, "approval.requested");
+    assert_eq!(entry.resource_type, "pending_action");
+    assert_eq!(entry.user_id.as_deref(), Some("user-001"));
+    assert_eq!(entry.user_email.as_deref(), Some("user@example.com"));
+    assert_eq!(entry.resource_id.as_deref(), Some("action-001"));
+    assert_eq!(
+        serde_json::Value::Object(entry.metadata),
+        json!({ "backend_id": "backend-001", "expiry_seconds": 3600 })
+    );
+    assert_eq!(entry.ip_address.as_deref(), Some("192.0.2.1"));
+    assert_eq!(entry.user_agent, None);
+}
@@ -0,0 +1,47 @@
+//! Tests for backend modes
+
+use crate::services::backend_mode::{
+    DEFAULT_HISTORY_LIMIT, MAX_HISTORY_LIMIT, MAX_REASON_LEN, history_limit_or_default,
+    mode_from_proto, mode_to_proto, validate_reason,
+};
+use pistonprotection_common::backend_mode::BackendMode as Mode;
+use pistonprotection_common::error::Error;
+use pistonprotection_proto::backend::BackendMode;
+
+/// Test requested modes map to the modes workers apply and back
+#[test]
+fn test_mode_mapping() {
+    assert!(matches!(
+        mode_from_proto(BackendMode::Unspecified),
+        Err(Error::Validation(_))
+    ));
+    for (proto, mode) in [
+        (BackendMode::Protect, Mode::Protect),
+        (BackendMode::Passthrough, Mode::Passthrough),
+        (BackendMode::BlockAll, Mode::BlockAll),
+    ] {
+        assert_eq!(mode_from_proto(proto).unwrap(), mode);
+        assert_eq!(mode_to_proto(mode), proto);
+    }
+}
+
+/// Test reasons are trimmed and bounded
+#[test]
+fn test_validate_reason() {
+    assert_eq!(
+        validate_reason("  customer reports timeouts ").unwrap(),
+        "customer reports timeouts"
+    );
+    assert!(matches!(
+        validate_reason(&"a".repeat(MAX_REASON_LEN + 1)),
+        Err(Error::Validation(_))
+    ));
+}
+
+/// Test audit log limits default and are capped
+#[test]
+fn test_history_limit() {
+    assert_eq!(history_limit_or_default(0), DEFAULT_HISTORY_LIMIT);
+    assert_eq!(history_limit_or_default(5), 5);
+    assert_eq!(history_limit_or_default(u32::MAX), MAX_HISTORY_LIMIT);
+}
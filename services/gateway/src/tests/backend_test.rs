@@ -0,0 +1,226 @@
+//! Tests for backend settings validation
+
+use crate::services::backend::{
+    MAX_BLOCK_PAGE_BYTES, MAX_DSCP, MAX_HONEYPOT_PORTS, MAX_KICK_MESSAGE_BYTES,
+    MAX_LIMIT_MULTIPLIER, MAX_TRAINING_SECONDS, MIN_CONNECTION_BURST_BYTES,
+    MIN_CONNECTION_BYTES_PER_SECOND, SrvTarget, probe_kind, select_srv, validate_block_responses,
+    validate_connection_rate, validate_honeypot, validate_learning, validate_marking,
+    validate_onboarding_target, verification_record,
+};
+use pistonprotection_common::error::Error;
+use pistonprotection_common::probe::ProbeKind;
+use pistonprotection_proto::backend::{
+    BackendType, BlockResponseSettings, ConnectionRateSettings, HoneypotSettings, LearningSettings,
+    MarkingSettings,
+};
+
+fn honeypot(ports: &[u32]) -> HoneypotSettings {
+    HoneypotSettings {
+        ports: ports.to_vec(),
+        auto_block: true,
+        block_duration_seconds: 3600,
+    }
+}
+
+/// Test honeypot ports are sorted and deduplicated
+#[test]
+fn test_validate_honeypot_normalizes_ports() {
+    let validated = validate_honeypot(honeypot(&[2323, 23, 2323, 445]), &[25565]).unwrap();
+    assert_eq!(validated.ports, [23, 445, 2323]);
+    assert!(validated.auto_block);
+    assert_eq!(validated.block_duration_seconds, 3600);
+
+    // No ports disables the honeypot
+    assert!(
+        validate_honeypot(honeypot(&[]), &[])
+            .unwrap()
+            .ports
+            .is_empty()
+    );
+}
+
+/// Test out of range honeypot ports are rejected
+#[test]
+fn test_validate_honeypot_port_range() {
+    for port in [0, 65536] {
+        let err = validate_honeypot(honeypot(&[22, port]), &[]).unwrap_err();
+        assert!(matches!(err, Error::Validation(_)), "port {}", port);
+    }
+}
+
+/// Test ports served by an origin cannot be honeypots
+#[test]
+fn test_validate_honeypot_rejects_origin_ports() {
+    let err = validate_honeypot(honeypot(&[22, 25565]), &[25565]).unwrap_err();
+    assert!(matches!(err, Error::Validation(ref msg) if msg.contains("25565")));
+}
+
+/// Test the number of honeypot ports is bounded
+#[test]
+fn test_validate_honeypot_port_limit() {
+    let ports: Vec<u32> = (1..=MAX_HONEYPOT_PORTS as u32 + 1).collect();
+    assert!(validate_honeypot(honeypot(&ports), &[]).is_err());
+    assert!(validate_honeypot(honeypot(&ports[..MAX_HONEYPOT_PORTS]), &[]).is_ok());
+}
+
+fn learning(training_seconds: u32, limit_multiplier: u32) -> LearningSettings {
+    LearningSettings {
+        enabled: true,
+        training_seconds,
+        limit_multiplier,
+        min_handshakes: 0,
+    }
+}
+
+/// Test learning settings get a handshake minimum
+#[test]
+fn test_validate_learning_defaults() {
+    let validated = validate_learning(learning(3600, 4)).unwrap();
+    assert_eq!(validated.min_handshakes, 1);
+    assert_eq!(validated.training_seconds, 3600);
+
+    // Disabled learning is not checked
+    let disabled = LearningSettings::default();
+    assert_eq!(validate_learning(disabled).unwrap(), disabled);
+}
+
+/// Test out of range learning settings are rejected
+#[test]
+fn test_validate_learning_ranges() {
+    for settings in [
+        learning(0, 4),
+        learning(MAX_TRAINING_SECONDS + 1, 4),
+        learning(3600, 1),
+        learning(3600, MAX_LIMIT_MULTIPLIER + 1),
+    ] {
+        let err = validate_learning(settings).unwrap_err();
+        assert!(matches!(err, Error::Validation(_)), "{:?}", settings);
+    }
+}
+
+fn block_responses(page: &str, kick: &str) -> BlockResponseSettings {
+    BlockResponseSettings {
+        http_block_page: page.to_string(),
+        minecraft_kick_message: kick.to_string(),
+    }
+}
+
+/// Test kick messages are stored as JSON text components
+#[test]
+fn test_validate_block_responses_kick_message() {
+    let component = r#"{"text":"Blocked","color":"red"}"#;
+    let validated = validate_block_responses(block_responses("", component)).unwrap();
+    assert_eq!(validated.minecraft_kick_message, component);
+
+    // Plain text is wrapped in a text component
+    let validated = validate_block_responses(block_responses("", " Go away ")).unwrap();
+    assert_eq!(validated.minecraft_kick_message, r#"{"text":"Go away"}"#);
+
+    // Blank fields keep the defaults
+    let validated = validate_block_responses(block_responses(" \n", " ")).unwrap();
+    assert_eq!(validated, BlockResponseSettings::default());
+}
+
+/// Test the size of block responses is bounded
+#[test]
+fn test_validate_block_responses_size() {
+    let page = "x".repeat(MAX_BLOCK_PAGE_BYTES + 1);
+    assert!(validate_block_responses(block_responses(&page, "")).is_err());
+    assert!(validate_block_responses(block_responses(&page[1..], "")).is_ok());
+
+    let kick = "x".repeat(MAX_KICK_MESSAGE_BYTES);
+    let err = validate_block_responses(block_responses("", &kick)).unwrap_err();
+    assert!(matches!(err, Error::Validation(_)));
+}
+
+fn connection_rate(bytes_per_second: u64, burst_bytes: u64) -> ConnectionRateSettings {
+    ConnectionRateSettings {
+        bytes_per_second,
+        burst_bytes,
+    }
+}
+
+/// Test per-connection bandwidth settings are bounded
+#[test]
+fn test_validate_connection_rate() {
+    let validated = validate_connection_rate(connection_rate(1_000_000, 0)).unwrap();
+    assert_eq!(validated, connection_rate(1_000_000, 0));
+
+    // Unlimited connections carry no burst
+    let validated = validate_connection_rate(connection_rate(0, 4096)).unwrap();
+    assert_eq!(validated, ConnectionRateSettings::default());
+
+    for settings in [
+        connection_rate(MIN_CONNECTION_BYTES_PER_SECOND - 1, 0),
+        connection_rate(1_000_000, MIN_CONNECTION_BURST_BYTES - 1),
+    ] {
+        let err = validate_connection_rate(settings).unwrap_err();
+        assert!(matches!(err, Error::Validation(_)), "{:?}", settings);
+    }
+}
+
+/// Test marking settings only accept 6-bit DSCP values
+#[test]
+fn test_validate_marking() {
+    let marking = MarkingSettings {
+        enabled: true,
+        dscp: MAX_DSCP,
+        ecn_ce: true,
+        mark_all_greylisted: false,
+    };
+    assert_eq!(validate_marking(marking).unwrap(), marking);
+
+    let err = validate_marking(MarkingSettings {
+        dscp: MAX_DSCP + 1,
+        ..marking
+    })
+    .unwrap_err();
+    assert!(matches!(err, Error::Validation(_)));
+}
+
+fn srv(priority: u16, weight: u16, target: &str) -> SrvTarget {
+    SrvTarget {
+        priority,
+        weight,
+        port: 25566,
+        target: target.to_string(),
+    }
+}
+
+/// Test the SRV record clients would connect to is picked
+#[test]
+fn test_select_srv() {
+    let records = [
+        srv(10, 5, "backup.example.com"),
+        srv(0, 1, "light.example.com"),
+        srv(0, 10, "heavy.example.com"),
+    ];
+    assert_eq!(select_srv(&records).unwrap().target, "heavy.example.com");
+
+    // "." means the service is not available
+    assert_eq!(select_srv(&[srv(0, 1, ".")]), None);
+    assert_eq!(select_srv(&[]), None);
+}
+
+/// Test only Minecraft backends can be onboarded by hostname
+#[test]
+fn test_validate_onboarding_target() {
+    assert_eq!(
+        probe_kind(BackendType::MinecraftBedrock),
+        Some(ProbeKind::MinecraftBedrock)
+    );
+    assert!(validate_onboarding_target(BackendType::MinecraftJava, "play.example.com", 0).is_ok());
+    assert_eq!(
+        verification_record("play.example.com"),
+        "_piston-verify.play.example.com"
+    );
+
+    for (backend_type, hostname, port) in [
+        (BackendType::Http, "play.example.com", 0),
+        (BackendType::MinecraftJava, "localhost", 0),
+        (BackendType::MinecraftJava, "play.example.com", 70000),
+    ] {
+        let err = validate_onboarding_target(backend_type, hostname, port).unwrap_err();
+        assert!(matches!(err, Error::Validation(_)), "{}", hostname);
+    }
+}
@@ -0,0 +1,205 @@
+//! Tests for ban list synchronization with game server plugins
+
+use super::test_utils::{assert_grpc_status_code, create_test_app_state, create_test_request};
+use crate::services::ban_sync::{
+    BanAction, BanEvent, BlockedPlayer, Blocklist, MAX_REASON_LEN, ban_rule, validate_event,
+};
+use crate::services::filter::compile_rule;
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use chrono::{Duration, Utc};
+use pistonprotection_proto::backend::GetBanSyncRequest;
+use pistonprotection_proto::backend::backend_service_server::BackendService;
+use std::net::IpAddr;
+use tonic::Code;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn event(action: BanAction, uuid: Option<&str>, ip: Option<&str>) -> BanEvent {
+    BanEvent {
+        action,
+        uuid: uuid.map(str::to_string),
+        ip: ip.map(str::to_string),
+        reason: String::new(),
+        server: String::new(),
+        expires_at: None,
+    }
+}
+
+/// Test player UUIDs and addresses of events are parsed
+#[test]
+fn test_validate_event() {
+    let now = Utc::now();
+    let uuid = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+    let ban = validate_event(
+        event(
+            BanAction::Ban,
+            Some("069a79f444e94726a5befca90e38aaf5"),
+            Some(" 198.51.100.4 "),
+        ),
+        now,
+    )
+    .unwrap();
+    assert_eq!(ban.uuid, Some(Uuid::parse_str(uuid).unwrap()));
+    assert_eq!(ban.ip, Some("198.51.100.4".parse::<IpAddr>().unwrap()));
+
+    let ban = validate_event(event(BanAction::Unban, Some(uuid), Some("")), now).unwrap();
+    assert_eq!(ban.ip, None);
+
+    assert!(validate_event(event(BanAction::Ban, None, Some("2001:db8::1")), now).is_ok());
+    assert!(validate_event(event(BanAction::Ban, None, None), now).is_err());
+    assert!(validate_event(event(BanAction::Ban, Some("notch"), None), now).is_err());
+    assert!(validate_event(event(BanAction::Ban, None, Some("256.0.0.1")), now).is_err());
+}
+
+/// Test addresses of proxies in front of the servers are never blocked
+#[test]
+fn test_validate_event_private_addresses() {
+    let now = Utc::now();
+    for ip in [
+        "127.0.0.1",
+        "10.0.0.5",
+        "192.168.1.20",
+        "172.16.0.1",
+        "0.0.0.0",
+        "::1",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:10.0.0.1",
+    ] {
+        assert!(
+            validate_event(event(BanAction::Ban, None, Some(ip)), now).is_err(),
+            "{} accepted",
+            ip
+        );
+    }
+}
+
+/// Test reasons are bounded and expired bans refused
+#[test]
+fn test_validate_event_reason_and_expiry() {
+    let now = Utc::now();
+
+    let mut long = event(BanAction::Ban, None, Some("198.51.100.4"));
+    long.reason = "a".repeat(MAX_REASON_LEN + 1);
+    assert!(validate_event(long, now).is_err());
+
+    let mut expired = event(BanAction::Ban, None, Some("198.51.100.4"));
+    expired.expires_at = Some(now - Duration::minutes(1));
+    assert!(validate_event(expired.clone(), now).is_err());
+
+    // Lifting a ban that already ran out is harmless
+    expired.action = BanAction::Unban;
+    assert!(validate_event(expired, now).is_ok());
+
+    let mut temporary = event(BanAction::Ban, None, Some("198.51.100.4"));
+    temporary.expires_at = Some(now + Duration::days(7));
+    temporary.reason = "  Griefing  ".to_string();
+    assert_eq!(validate_event(temporary, now).unwrap().reason, "Griefing");
+}
+
+/// Test push bodies use the plugin protocol field names
+#[test]
+fn test_push_request_format() {
+    let request: crate::services::ban_sync::PushRequest = serde_json::from_str(
+        r#"{"events": [
+            {"action": "ban", "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5", "ip": "198.51.100.4",
+             "reason": "Cheating", "server": "survival-1", "expires_at": "2030-01-01T00:00:00Z"},
+            {"action": "unban", "ip": "198.51.100.4"}
+        ]}"#,
+    )
+    .unwrap();
+    assert_eq!(request.events.len(), 2);
+    assert_eq!(request.events[0].action, BanAction::Ban);
+    assert_eq!(request.events[0].server, "survival-1");
+    assert_eq!(request.events[1].action, BanAction::Unban);
+    assert!(request.events[1].uuid.is_none());
+}
+
+/// Test the ban rule drops exactly the banned addresses
+#[test]
+fn test_ban_rule() {
+    assert!(ban_rule(&[]).is_none());
+
+    let ips: Vec<IpAddr> = vec![
+        "198.51.100.4".parse().unwrap(),
+        "2001:db8::1".parse().unwrap(),
+    ];
+    let rule = ban_rule(&ips).unwrap();
+    let source_ips = &rule.r#match.as_ref().unwrap().source_ips;
+    assert_eq!(source_ips[0].prefix_length, 32);
+    assert_eq!(source_ips[1].prefix_length, 128);
+    assert_eq!(
+        rule.action,
+        pistonprotection_proto::common::Action::Drop as i32
+    );
+    compile_rule(&rule).unwrap();
+}
+
+/// Test the entity tag follows the content of the blocklist
+#[test]
+fn test_blocklist_etag() {
+    let mut blocklist = Blocklist {
+        bans: vec![BlockedPlayer {
+            uuid: None,
+            ip: Some("198.51.100.4".to_string()),
+            reason: String::new(),
+            server: String::new(),
+            expires_at: None,
+        }],
+        networks: vec!["203.0.113.0/24".to_string()],
+    };
+    let etag = blocklist.etag();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+    assert_eq!(blocklist.clone().etag(), etag);
+
+    blocklist.bans.clear();
+    assert_ne!(blocklist.etag(), etag);
+}
+
+/// Test plugins without a valid token are turned away without a database
+#[tokio::test]
+async fn test_plugin_requests_require_token() {
+    let app = crate::handlers::http::create_router(create_test_app_state());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/plugin/v1/blocklist")
+                .header(header::AUTHORIZATION, "Bearer not-a-token")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/plugin/v1/bans")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"events": []}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_get_ban_sync_requires_backend() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(GetBanSyncRequest {
+        backend_id: String::new(),
+    });
+
+    let status = service.get_ban_sync(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
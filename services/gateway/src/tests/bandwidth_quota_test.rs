@@ -0,0 +1,207 @@
+//! Tests for bandwidth quotas
+
+use super::test_utils::{
+    assert_grpc_status_code, create_test_app_state, create_test_request, gateway_schema,
+};
+use crate::services::bandwidth_quota::{
+    BACKEND_IDS_QUERY, DEFAULT_THROTTLE_BYTES_PER_SECOND, ORGANIZATIONS_QUERY, QuotaPolicy,
+    QuotaStep, USAGE_QUERY, action_from_proto, bandwidth_quota_to_proto, quota_step,
+    validate_throttle_bytes_per_second, validate_warn_percent,
+};
+use chrono::NaiveDate;
+use pistonprotection_common::bandwidth_quota::QuotaAction;
+use pistonprotection_common::error::Error;
+use pistonprotection_proto::backend::backend_service_server::BackendService;
+use pistonprotection_proto::backend::{
+    BackendBandwidth, BandwidthQuotaAction, GetBandwidthQuotaRequest, UpdateBandwidthQuotaRequest,
+};
+use tonic::Code;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// Test the throttle defaults and cannot starve connections
+#[test]
+fn test_validate_throttle_bytes_per_second() {
+    assert_eq!(
+        validate_throttle_bytes_per_second(0).unwrap(),
+        DEFAULT_THROTTLE_BYTES_PER_SECOND
+    );
+    assert_eq!(
+        validate_throttle_bytes_per_second(500_000).unwrap(),
+        500_000
+    );
+    assert!(matches!(
+        validate_throttle_bytes_per_second(100),
+        Err(Error::Validation(_))
+    ));
+}
+
+#[test]
+fn test_validate_warn_percent() {
+    assert_eq!(validate_warn_percent(0).unwrap(), 0);
+    assert_eq!(validate_warn_percent(90).unwrap(), 90);
+    assert!(validate_warn_percent(100).is_err());
+}
+
+#[test]
+fn test_action_from_proto() {
+    assert_eq!(
+        action_from_proto(BandwidthQuotaAction::Suspend).unwrap(),
+        QuotaAction::Suspend
+    );
+    assert!(action_from_proto(BandwidthQuotaAction::Unspecified).is_err());
+}
+
+/// Test the monitor warns once, acts once and lifts the action when back
+/// under the limit
+#[test]
+fn test_quota_step() {
+    let throttle = QuotaAction::Throttle;
+
+    assert_eq!(
+        quota_step(GIB / 2, GIB, 80, throttle, None, false),
+        QuotaStep::Keep
+    );
+    assert_eq!(
+        quota_step(GIB * 9 / 10, GIB, 80, throttle, None, false),
+        QuotaStep::Warn
+    );
+    assert_eq!(
+        quota_step(GIB * 9 / 10, GIB, 80, throttle, None, true),
+        QuotaStep::Keep
+    );
+    assert_eq!(
+        quota_step(GIB * 9 / 10, GIB, 0, throttle, None, false),
+        QuotaStep::Keep
+    );
+    assert_eq!(
+        quota_step(GIB + 1, GIB, 80, throttle, None, true),
+        QuotaStep::Exceed(throttle)
+    );
+    assert_eq!(
+        quota_step(GIB + 1, GIB, 80, throttle, Some(throttle), true),
+        QuotaStep::Keep
+    );
+
+    // The plan was upgraded
+    assert_eq!(
+        quota_step(GIB + 1, 10 * GIB, 80, throttle, Some(throttle), true),
+        QuotaStep::Restore(throttle)
+    );
+}
+
+/// Test dropped traffic does not count towards the quota
+#[test]
+fn test_bandwidth_quota_to_proto() {
+    let period = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+    let backends = vec![
+        BackendBandwidth {
+            backend_id: "backend-1".to_string(),
+            bytes_in: 3 * GIB,
+            bytes_dropped: 2 * GIB,
+        },
+        BackendBandwidth {
+            backend_id: "backend-2".to_string(),
+            bytes_in: GIB,
+            bytes_dropped: 0,
+        },
+    ];
+
+    let quota = bandwidth_quota_to_proto(
+        "org-1",
+        &QuotaPolicy::default(),
+        10 * GIB,
+        period,
+        Some(QuotaAction::Notify),
+        backends,
+    );
+    assert_eq!(quota.used_bytes, 2 * GIB);
+    assert_eq!(quota.limit_bytes, 10 * GIB);
+    assert_eq!(quota.action(), BandwidthQuotaAction::Notify);
+    assert_eq!(quota.applied_action(), BandwidthQuotaAction::Notify);
+    assert_eq!(quota.backends.len(), 2);
+    assert_eq!(
+        quota.period_end.unwrap().seconds - quota.period_start.unwrap().seconds,
+        31 * 86400
+    );
+}
+
+#[tokio::test]
+async fn test_get_bandwidth_quota_requires_organization() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(GetBandwidthQuotaRequest::default());
+
+    let status = service.get_bandwidth_quota(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_update_bandwidth_quota_requires_organization() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(UpdateBandwidthQuotaRequest {
+        action: BandwidthQuotaAction::Throttle as i32,
+        ..Default::default()
+    });
+
+    let status = service.update_bandwidth_quota(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
+
+/// Test the quota queries against the gateway schema; skipped without a
+/// `DATABASE_URL`
+#[tokio::test]
+async fn test_quota_queries_against_gateway_schema() {
+    let Some(mut tx) = gateway_schema().await else {
+        return;
+    };
+    let period = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+    sqlx::raw_sql(
+        r#"
+        INSERT INTO backends (id, organization_id, name) VALUES
+            ('b1', 'org', 'one'), ('b2', 'org', 'two'), ('b3', 'quiet', 'three');
+        INSERT INTO backend_bandwidth_usage (backend_id, period_start, bytes_in, bytes_dropped) VALUES
+            ('b2', '2024-03-01', 5000, 1000), ('b1', '2024-03-01', 100, 0),
+            ('b3', '2024-02-01', 700, 0);
+        INSERT INTO bandwidth_quota_states (organization_id, period_start, applied_action) VALUES
+            ('suspended', '2024-02-01', 'suspend'), ('restored', '2024-03-01', NULL);
+        "#,
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    let mut organizations: Vec<(String,)> = sqlx::query_as(ORGANIZATIONS_QUERY)
+        .bind(period)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    organizations.sort();
+    assert_eq!(
+        organizations,
+        [("org".to_string(),), ("suspended".to_string(),)]
+    );
+
+    let usage: Vec<(String, i64, i64)> = sqlx::query_as(USAGE_QUERY)
+        .bind("org")
+        .bind(period)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(
+        usage,
+        [("b1".to_string(), 100, 0), ("b2".to_string(), 5000, 1000)]
+    );
+
+    let mut backend_ids: Vec<(String,)> = sqlx::query_as(BACKEND_IDS_QUERY)
+        .bind("org")
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    backend_ids.sort();
+    assert_eq!(backend_ids, [("b1".to_string(),), ("b2".to_string(),)]);
+
+    tx.rollback().await.unwrap();
+}
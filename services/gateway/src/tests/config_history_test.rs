@@ -0,0 +1,193 @@
+//! Tests for configuration history
+
+use super::mock_db::create_test_filter_rule;
+use crate::services::config_history::{
+    Change, ChangeKind, ConfigSnapshot, RuleSnapshot, change_to_proto, diff, replay,
+};
+use pistonprotection_common::error::Error;
+use pistonprotection_proto::backend::ConfigChangeKind;
+use pistonprotection_proto::common::{Action, IpAddress, IpNetwork, PortRange};
+use pistonprotection_proto::filter::{FilterMatch, FilterRule};
+use serde_json::json;
+
+fn network(addr: &str, prefix_length: u32) -> IpNetwork {
+    IpNetwork {
+        address: Some(IpAddress::from(addr.parse::<std::net::IpAddr>().unwrap())),
+        prefix_length,
+    }
+}
+
+fn snapshot_rule(rule: &FilterRule) -> RuleSnapshot {
+    RuleSnapshot {
+        name: rule.name.clone(),
+        description: Some(rule.description.clone()),
+        priority: rule.priority as i32,
+        match_criteria: serde_json::to_value(&rule.r#match).unwrap(),
+        action: rule.action,
+        rate_limit: None,
+        enabled: rule.enabled,
+    }
+}
+
+fn block_rule(id: &str, name: &str, source: IpNetwork) -> FilterRule {
+    let mut rule = create_test_filter_rule(id, name);
+    rule.action = Action::Drop as i32;
+    rule.r#match = Some(FilterMatch {
+        source_ips: vec![source],
+        ..Default::default()
+    });
+    rule
+}
+
+fn snapshot(rules: &[FilterRule]) -> ConfigSnapshot {
+    ConfigSnapshot {
+        protection: Some(json!({ "level": 2, "challenge_enabled": true })),
+        rules: rules
+            .iter()
+            .map(|rule| (rule.id.clone(), snapshot_rule(rule)))
+            .collect(),
+    }
+}
+
+/// Test a rule round trips through its snapshot
+#[test]
+fn test_rule_snapshot_round_trip() {
+    let rule = block_rule("rule-1", "Block scanners", network("203.0.113.0", 24));
+    assert_eq!(snapshot_rule(&rule).to_rule("rule-1").unwrap(), rule);
+
+    let mut invalid = snapshot_rule(&rule);
+    invalid.match_criteria = json!({ "source_ips": "not a list" });
+    assert!(matches!(
+        invalid.to_rule("rule-1"),
+        Err(Error::Validation(_))
+    ));
+}
+
+/// Test identical snapshots have no changes
+#[test]
+fn test_diff_unchanged() {
+    let rules = [block_rule(
+        "rule-1",
+        "Block scanners",
+        network("203.0.113.0", 24),
+    )];
+    assert!(diff(&snapshot(&rules), &snapshot(&rules)).is_empty());
+}
+
+/// Test changes are reported per field, rule and setting
+#[test]
+fn test_diff_changes() {
+    let kept = block_rule("rule-1", "Block scanners", network("203.0.113.0", 24));
+    let removed = block_rule("rule-2", "Block relay", network("198.51.100.0", 24));
+    let added = block_rule("rule-3", "Block botnet", network("192.0.2.0", 24));
+
+    let before = snapshot(&[kept.clone(), removed]);
+    let mut edited = kept;
+    edited.action = Action::RateLimit as i32;
+    let mut after = snapshot(&[edited, added]);
+    after.protection = Some(json!({ "level": 3, "challenge_enabled": true }));
+
+    let changes = diff(&before, &after);
+    let find = |path: &str| -> &Change {
+        changes
+            .iter()
+            .find(|change| change.path == path)
+            .unwrap_or_else(|| panic!("no change at {}", path))
+    };
+
+    let level = find("protection.level");
+    assert_eq!(level.kind, ChangeKind::Modified);
+    assert_eq!(level.before, Some(json!(2)));
+    assert_eq!(level.after, Some(json!(3)));
+
+    let action = find("rules.rule-1.action");
+    assert_eq!(action.kind, ChangeKind::Modified);
+    assert_eq!(find("rules.rule-2").kind, ChangeKind::Removed);
+    assert!(find("rules.rule-2").after.is_none());
+    assert_eq!(find("rules.rule-3").kind, ChangeKind::Added);
+    assert!(find("rules.rule-3").before.is_none());
+
+    // Unchanged fields of an edited rule are left out
+    assert!(
+        !changes
+            .iter()
+            .any(|change| change.path == "rules.rule-1.name")
+    );
+    assert_eq!(changes.len(), 4);
+}
+
+/// Test first saved protection settings show up as added
+#[test]
+fn test_diff_initial_protection() {
+    let before = ConfigSnapshot::default();
+    let after = ConfigSnapshot {
+        protection: Some(json!({ "level": 1 })),
+        ..Default::default()
+    };
+
+    let changes = diff(&before, &after);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].path, "protection");
+    assert_eq!(changes[0].kind, ChangeKind::Added);
+}
+
+/// Test replay reports conflicts between restored rules
+#[test]
+fn test_replay_conflicts() {
+    let mut allow = create_test_filter_rule("rule-1", "Allow office");
+    allow.action = Action::Allow as i32;
+    allow.r#match = Some(FilterMatch {
+        source_ips: vec![network("192.0.2.0", 30)],
+        ..Default::default()
+    });
+    let block = block_rule("rule-2", "Block host", network("192.0.2.1", 32));
+
+    let warnings = replay(&snapshot(&[allow.clone(), block.clone()])).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("'Allow office' and 'Block host'"));
+
+    // Disabled rules are not enforced, so they cannot conflict
+    let mut disabled = block;
+    disabled.enabled = false;
+    assert!(replay(&snapshot(&[allow, disabled])).unwrap().is_empty());
+}
+
+/// Test replay refuses rules the workers cannot enforce
+#[test]
+fn test_replay_invalid_rule() {
+    let mut rule = create_test_filter_rule("rule-1", "Bad ports");
+    rule.r#match = Some(FilterMatch {
+        destination_ports: vec![PortRange {
+            start: 9000,
+            end: 8000,
+        }],
+        ..Default::default()
+    });
+
+    let err = replay(&snapshot(&[rule])).unwrap_err();
+    assert!(matches!(err, Error::Validation(_)));
+    assert!(err.to_string().contains("Bad ports"));
+}
+
+/// Test changes convert to their API form
+#[test]
+fn test_change_to_proto() {
+    let change = change_to_proto(&Change {
+        path: "rules.rule-1.enabled".to_string(),
+        kind: ChangeKind::Modified,
+        before: Some(json!(true)),
+        after: Some(json!(false)),
+    });
+    assert_eq!(change.kind(), ConfigChangeKind::Modified);
+    assert_eq!(change.before, "true");
+    assert_eq!(change.after, "false");
+
+    let removed = change_to_proto(&Change {
+        path: "rules.rule-2".to_string(),
+        kind: ChangeKind::Removed,
+        before: Some(json!({ "name": "Block relay" })),
+        after: None,
+    });
+    assert_eq!(removed.kind(), ConfigChangeKind::Removed);
+    assert!(removed.after.is_empty());
+}
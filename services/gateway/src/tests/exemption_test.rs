@@ -0,0 +1,88 @@
+//! Tests for load test exemption tokens
+
+use super::test_utils::{assert_grpc_status_code, create_test_app_state, create_test_request};
+use crate::services::exemption::{MAX_TOKEN_IPS, token_to_proto, validate_ips};
+use pistonprotection_common::exemption::ExemptionToken;
+use pistonprotection_proto::backend::backend_service_server::BackendService;
+use pistonprotection_proto::backend::{
+    CreateExemptionTokenRequest, ListExemptionTokensRequest, RevokeExemptionTokenRequest,
+};
+use tonic::Code;
+
+/// Test only single unicast addresses can be exempted
+#[test]
+fn test_validate_ips() {
+    let ips = validate_ips(&[
+        "203.0.113.7".to_string(),
+        " 2001:db8::1 ".to_string(),
+        "203.0.113.7".to_string(),
+    ])
+    .unwrap();
+    assert_eq!(ips.len(), 2);
+
+    assert!(validate_ips(&[]).is_err());
+    assert!(validate_ips(&["203.0.113.0/24".to_string()]).is_err());
+    assert!(validate_ips(&["0.0.0.0".to_string()]).is_err());
+    assert!(validate_ips(&["127.0.0.1".to_string()]).is_err());
+    assert!(validate_ips(&["ff02::1".to_string()]).is_err());
+
+    let too_many: Vec<String> = (0..=MAX_TOKEN_IPS)
+        .map(|i| format!("10.0.{}.{}", i / 200, i % 200 + 1))
+        .collect();
+    assert!(validate_ips(&too_many).is_err());
+}
+
+/// Test expired tokens are reported as inactive
+#[test]
+fn test_token_to_proto() {
+    let token = ExemptionToken {
+        id: "token-1".to_string(),
+        backend_id: "backend-1".to_string(),
+        ips: vec!["203.0.113.7".parse().unwrap()],
+        reason: "Load test".to_string(),
+        created_by: "user-1".to_string(),
+        created_at: 1000,
+        expires_at: 4600,
+    };
+
+    let proto = token_to_proto(&token, 1200);
+    assert!(proto.active);
+    assert_eq!(proto.ips, vec!["203.0.113.7".to_string()]);
+    assert_eq!(proto.created_by, "user-1");
+    assert_eq!(proto.expires_at.unwrap().seconds, 4600);
+
+    assert!(!token_to_proto(&token, 4600).active);
+}
+
+#[tokio::test]
+async fn test_create_exemption_token_requires_backend() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(CreateExemptionTokenRequest {
+        ips: vec!["203.0.113.7".to_string()],
+        ..Default::default()
+    });
+
+    let status = service.create_exemption_token(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_revoke_exemption_token_requires_id() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(RevokeExemptionTokenRequest::default());
+
+    let status = service.revoke_exemption_token(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_list_exemption_tokens_requires_backend() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(ListExemptionTokensRequest::default());
+
+    let status = service.list_exemption_tokens(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
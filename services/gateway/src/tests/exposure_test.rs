@@ -0,0 +1,103 @@
+//! Tests for origin exposure scan helpers
+
+use crate::services::exposure::{
+    COMMON_SUBDOMAINS, candidate_names, leaked_addresses, parse_ct_names,
+};
+use crate::services::metrics::alert_condition_met;
+use pistonprotection_proto::metrics::{AlertCondition, AlertOperator};
+use std::net::IpAddr;
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+/// Test crt.sh entries are split, unwildcarded and deduplicated
+#[test]
+fn test_parse_ct_names() {
+    let body = r#"[
+        {"common_name": "example.com", "name_value": "example.com\n*.example.com"},
+        {"common_name": "Direct.Example.com", "name_value": "direct.example.com\n203.0.113.7"},
+        {"name_value": ""}
+    ]"#;
+
+    let names = parse_ct_names(body).unwrap();
+    assert_eq!(names, ["203.0.113.7", "direct.example.com", "example.com"]);
+
+    assert_eq!(parse_ct_names("[]").unwrap(), Vec::<String>::new());
+    assert!(parse_ct_names("<html>rate limited</html>").is_none());
+}
+
+/// Test candidate names cover common subdomains and stay under the domains
+#[test]
+fn test_candidate_names() {
+    let domains = strings(&["example.com"]);
+    let ct = strings(&[
+        "shop.example.com",
+        "example.org",
+        "203.0.113.7",
+        "mc1.example.com",
+    ]);
+    let origin_hosts = strings(&["mc1.example.com"]);
+
+    let names = candidate_names(&domains, &ct, &origin_hosts, 100);
+    assert_eq!(names[0], "example.com");
+    assert!(names.contains(&"direct.example.com".to_string()));
+    assert!(names.contains(&"shop.example.com".to_string()));
+    // Outside the domains, IPs and origin hostnames are skipped
+    assert!(!names.contains(&"example.org".to_string()));
+    assert!(!names.contains(&"203.0.113.7".to_string()));
+    assert!(!names.contains(&"mc1.example.com".to_string()));
+    assert_eq!(names.len(), COMMON_SUBDOMAINS.len() + 2);
+
+    // No duplicates and the limit is applied
+    let names = candidate_names(&domains, &strings(&["direct.example.com"]), &[], 5);
+    assert_eq!(names.len(), 5);
+    let names = candidate_names(&domains, &strings(&["direct.example.com"]), &[], 100);
+    assert_eq!(names.len(), COMMON_SUBDOMAINS.len() + 1);
+}
+
+/// Test origin addresses are found in header values
+#[test]
+fn test_leaked_addresses() {
+    let origin: IpAddr = "203.0.113.7".parse().unwrap();
+    let origin_v6: IpAddr = "2001:db8::7".parse().unwrap();
+    let origins = [origin, origin_v6];
+
+    assert_eq!(leaked_addresses("203.0.113.7", &origins), [origin]);
+    assert_eq!(
+        leaked_addresses("http://203.0.113.7:8080/login", &origins),
+        [origin]
+    );
+    assert_eq!(
+        leaked_addresses("for=[2001:db8::7]:443", &origins),
+        [origin_v6]
+    );
+    assert_eq!(
+        leaked_addresses("10.0.0.1, 203.0.113.7, 203.0.113.7", &origins),
+        [origin]
+    );
+
+    // Longer addresses containing the origin are not matches
+    assert!(leaked_addresses("1203.0.113.70", &origins).is_empty());
+    assert!(leaked_addresses("203.0.113.70", &origins).is_empty());
+    assert!(leaked_addresses("nginx/1.25.3", &origins).is_empty());
+}
+
+/// Test exposure alerts fire on any finding
+#[test]
+fn test_alert_condition_met() {
+    let condition = AlertCondition {
+        metric: "origin_exposures".to_string(),
+        operator: AlertOperator::GreaterThan as i32,
+        threshold: 0.0,
+        duration_seconds: 0,
+    };
+    assert!(alert_condition_met(&condition, 2.0));
+    assert!(!alert_condition_met(&condition, 0.0));
+
+    let unspecified = AlertCondition {
+        operator: AlertOperator::Unspecified as i32,
+        ..condition
+    };
+    assert!(!alert_condition_met(&unspecified, 2.0));
+}
@@ -0,0 +1,142 @@
+//! Tests for filter rule compilation
+
+use super::mock_db::create_test_filter_rule;
+use crate::services::filter::{compile_rule, conflict_warning, to_compiler_rule};
+use pistonprotection_common::error::Error;
+use pistonprotection_proto::common::{Action, IpAddress, IpNetwork, PortRange, Protocol};
+use pistonprotection_proto::filter::*;
+use pistonprotection_rule_compiler as compiler;
+
+fn network(addr: &str, prefix_length: u32) -> IpNetwork {
+    IpNetwork {
+        address: Some(IpAddress::from(addr.parse::<std::net::IpAddr>().unwrap())),
+        prefix_length,
+    }
+}
+
+/// Test a proto rule converts into the compiler's model
+#[test]
+fn test_to_compiler_rule() {
+    let mut rule = create_test_filter_rule("rule-1", "Block scanners");
+    rule.action = Action::Drop as i32;
+    rule.r#match = Some(FilterMatch {
+        source_ip_blacklist: vec![network("203.0.113.0", 30)],
+        destination_ports: vec![PortRange {
+            start: 5060,
+            end: 5061,
+        }],
+        protocols: vec![Protocol::Udp as i32],
+        ..Default::default()
+    });
+
+    let converted = to_compiler_rule(&rule).unwrap();
+    assert_eq!(converted.id, "rule-1");
+    assert_eq!(converted.priority, 50);
+    assert_eq!(converted.action, compiler::Action::Drop);
+    assert_eq!(
+        converted.source_ip_blacklist,
+        vec!["203.0.113.0/30".parse().unwrap()]
+    );
+    assert_eq!(
+        converted.destination_ports,
+        vec![compiler::PortRange::new(5060, 5061)]
+    );
+    assert_eq!(converted.protocols, vec![compiler::Protocol::Udp]);
+
+    let compiled = compile_rule(&rule).unwrap();
+    // 4 blocked addresses and 2 blocked ports
+    assert_eq!(compiled.len(), 6);
+}
+
+/// Test rules without match criteria still compile
+#[test]
+fn test_compile_rule_without_match() {
+    let rule = create_test_filter_rule("rule-1", "Empty");
+    assert!(compile_rule(&rule).unwrap().is_empty());
+}
+
+/// Test networks too wide for map entries are left to userspace
+#[test]
+fn test_compile_rule_skips_wide_network() {
+    let mut rule = create_test_filter_rule("rule-1", "Block a range");
+    rule.r#match = Some(FilterMatch {
+        source_ip_blacklist: vec![network("10.0.0.0", 8)],
+        ..Default::default()
+    });
+
+    let compiled = compile_rule(&rule).unwrap();
+    assert!(compiled.is_empty());
+    assert_eq!(compiled.skipped().len(), 1);
+}
+
+/// Test inverted port ranges are rejected
+#[test]
+fn test_compile_rule_rejects_invalid_port_range() {
+    let mut rule = create_test_filter_rule("rule-1", "Bad ports");
+    rule.r#match = Some(FilterMatch {
+        destination_ports: vec![PortRange {
+            start: 9000,
+            end: 8000,
+        }],
+        ..Default::default()
+    });
+
+    let err = compile_rule(&rule).unwrap_err();
+    assert!(matches!(err, Error::Validation(_)));
+}
+
+/// Test malformed networks are rejected
+#[test]
+fn test_compile_rule_rejects_invalid_network() {
+    let mut rule = create_test_filter_rule("rule-1", "Bad prefix");
+    rule.r#match = Some(FilterMatch {
+        source_ips: vec![network("192.0.2.1", 33)],
+        ..Default::default()
+    });
+    assert!(compile_rule(&rule).is_err());
+
+    rule.r#match = Some(FilterMatch {
+        source_ips: vec![IpNetwork {
+            address: None,
+            prefix_length: 32,
+        }],
+        ..Default::default()
+    });
+    assert!(compile_rule(&rule).is_err());
+}
+
+/// Test conflict warnings name the rules and the precedence
+#[test]
+fn test_conflict_warning() {
+    let mut allow = create_test_filter_rule("rule-1", "Allow office");
+    allow.action = Action::Allow as i32;
+    allow.r#match = Some(FilterMatch {
+        source_ips: vec![network("192.0.2.0", 30)],
+        ..Default::default()
+    });
+    let mut block = create_test_filter_rule("rule-2", "Block host");
+    block.action = Action::Drop as i32;
+    block.r#match = Some(FilterMatch {
+        source_ips: vec![network("192.0.2.1", 32)],
+        ..Default::default()
+    });
+
+    let rules = [
+        to_compiler_rule(&allow).unwrap(),
+        to_compiler_rule(&block).unwrap(),
+    ];
+    let compiled = compiler::compile(&rules).unwrap();
+    let conflicts = compiled.conflicts();
+    assert_eq!(conflicts.len(), 1);
+
+    let names = [("rule-1", "Allow office"), ("rule-2", "Block host")]
+        .into_iter()
+        .collect();
+    let warning = conflict_warning(&conflicts[0], &names);
+    assert_eq!(
+        warning,
+        "Rules 'Allow office' and 'Block host' apply different actions to the same sources; \
+         'Allow office' takes precedence as both have the same priority; set distinct \
+         priorities to make the order explicit"
+    );
+}
@@ -70,6 +70,44 @@ mod backend_service_tests {
         assert!(status.message().contains("Backend is required"));
     }
 
+    /// Test that a malformed idempotency key is rejected before the handler runs
+    #[tokio::test]
+    async fn test_create_backend_invalid_idempotency_key() {
+        let state = create_test_app_state();
+        let service = crate::handlers::grpc::BackendGrpcService::new(state);
+
+        let mut request = create_test_request(CreateBackendRequest {
+            organization_id: constants::TEST_ORG_ID.to_string(),
+            backend: None,
+        });
+        request
+            .metadata_mut()
+            .insert("idempotency-key", "has space".parse().unwrap());
+
+        let status = service.create_backend(request).await.err().unwrap();
+        assert_grpc_status_code(&status, Code::InvalidArgument);
+        assert!(status.message().contains("Idempotency key"));
+    }
+
+    /// Test that an idempotency key without Redis runs the request as usual
+    #[tokio::test]
+    async fn test_create_backend_idempotency_key_without_cache() {
+        let state = create_test_app_state();
+        let service = crate::handlers::grpc::BackendGrpcService::new(state);
+
+        let mut request = create_test_request(CreateBackendRequest {
+            organization_id: constants::TEST_ORG_ID.to_string(),
+            backend: None,
+        });
+        request
+            .metadata_mut()
+            .insert("idempotency-key", "retry-1".parse().unwrap());
+
+        let status = service.create_backend(request).await.err().unwrap();
+        assert_grpc_status_code(&status, Code::InvalidArgument);
+        assert!(status.message().contains("Backend is required"));
+    }
+
     /// Test getting a backend that doesn't exist
     #[tokio::test]
     async fn test_get_backend_not_found() {
@@ -138,6 +176,7 @@ mod backend_service_tests {
                 page: 1,
                 page_size: 10,
                 cursor: String::new(),
+                ..Default::default()
             }),
         });
 
@@ -155,7 +194,7 @@ mod backend_service_tests {
         let state = create_test_app_state();
         let service = crate::handlers::grpc::BackendGrpcService::new(state);
 
-        // Test page_size = 0 (should be bounded to 1)
+        // Test page_size = 0 (should fall back to the default page size)
         let request = create_test_request(ListBackendsRequest {
             organization_id: constants::TEST_ORG_ID.to_string(),
             type_filter: 0,
@@ -163,6 +202,7 @@ mod backend_service_tests {
                 page: 1,
                 page_size: 0,
                 cursor: String::new(),
+                ..Default::default()
             }),
         });
 
@@ -185,6 +225,7 @@ mod backend_service_tests {
                 page: 1,
                 page_size: 500,
                 cursor: String::new(),
+                ..Default::default()
             }),
         });
 
@@ -234,6 +275,42 @@ mod backend_service_tests {
         let status = result.err().unwrap();
         assert_grpc_status_code(&status, Code::InvalidArgument);
     }
+
+    /// Test setting honeypot ports without settings
+    #[tokio::test]
+    async fn test_set_honeypot_missing_settings() {
+        let state = create_test_app_state();
+        let service = crate::handlers::grpc::BackendGrpcService::new(state);
+
+        let request = create_test_request(SetHoneypotRequest {
+            backend_id: "test".to_string(),
+            honeypot: None,
+        });
+
+        let result = service.set_honeypot(request).await;
+
+        assert!(result.is_err());
+        let status = result.err().unwrap();
+        assert_grpc_status_code(&status, Code::InvalidArgument);
+    }
+
+    /// Test setting block responses without settings
+    #[tokio::test]
+    async fn test_set_block_responses_missing_settings() {
+        let state = create_test_app_state();
+        let service = crate::handlers::grpc::BackendGrpcService::new(state);
+
+        let request = create_test_request(SetBlockResponsesRequest {
+            backend_id: "test".to_string(),
+            block_responses: None,
+        });
+
+        let result = service.set_block_responses(request).await;
+
+        assert!(result.is_err());
+        let status = result.err().unwrap();
+        assert_grpc_status_code(&status, Code::InvalidArgument);
+    }
 }
 
 // ============================================================================
@@ -1,6 +1,26 @@
 //! Gateway service tests
 
+mod approval_test;
+mod audit_test;
+mod backend_mode_test;
+mod backend_test;
+mod ban_sync_test;
+mod bandwidth_quota_test;
+mod config_history_test;
+mod exemption_test;
+mod exposure_test;
+mod filter_test;
 mod grpc_test;
 mod handlers_test;
 mod mock_db;
+mod origin_switch_test;
+mod overview_test;
+mod pagination_test;
+mod provider_import_test;
+mod rate_profile_test;
+mod restart_mode_test;
+mod rule_transfer_test;
+mod status_page_test;
+mod support_access_test;
 mod test_utils;
+mod trial_suspension_test;
@@ -0,0 +1,66 @@
+//! Tests for origin switch progress
+
+use crate::services::origin_switch::{MIN_DRAIN, drain_complete, drain_totals, switch_progress};
+use chrono::{Duration, Utc};
+use pistonprotection_common::origin_switch::DrainReport;
+
+fn report(worker_id: &str, active_connections: u64) -> DrainReport {
+    DrainReport {
+        switch_id: "switch-1".to_string(),
+        worker_id: worker_id.to_string(),
+        active_connections,
+        reported_at: 0,
+    }
+}
+
+/// Test drain reports are summed over workers
+#[test]
+fn test_drain_totals() {
+    assert_eq!(drain_totals(&[]), (0, 0));
+    assert_eq!(
+        drain_totals(&[report("worker-1", 12), report("worker-2", 3)]),
+        (15, 2)
+    );
+}
+
+/// Test switches complete once drained or at the deadline
+#[test]
+fn test_drain_complete() {
+    let started = Utc::now();
+    let deadline = started + Duration::seconds(300);
+    let settled = started + Duration::from_std(MIN_DRAIN).unwrap();
+
+    // Connections left
+    assert!(!drain_complete(started, deadline, 4, 2, settled));
+    // Drained, but workers may not have applied the plan yet
+    assert!(!drain_complete(
+        started,
+        deadline,
+        0,
+        2,
+        started + Duration::seconds(1)
+    ));
+    // No worker reported
+    assert!(!drain_complete(started, deadline, 0, 0, settled));
+    // Drained
+    assert!(drain_complete(started, deadline, 0, 2, settled));
+    // Grace period over, whatever is left
+    assert!(drain_complete(started, deadline, 4, 2, deadline));
+}
+
+/// Test progress follows the grace period
+#[test]
+fn test_switch_progress() {
+    let started = Utc::now();
+    let deadline = started + Duration::seconds(100);
+
+    assert_eq!(switch_progress(started, deadline, started), 0.0);
+    assert!(
+        (switch_progress(started, deadline, started + Duration::seconds(25)) - 0.25).abs() < 1e-9
+    );
+    assert_eq!(
+        switch_progress(started, deadline, deadline + Duration::seconds(5)),
+        1.0
+    );
+    assert_eq!(switch_progress(started, started, started), 1.0);
+}
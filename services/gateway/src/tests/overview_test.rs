@@ -0,0 +1,191 @@
+//! Tests for the dashboard overview
+
+use crate::middleware::auth::{AuthContext, AuthMethod};
+use crate::services::overview::{
+    BackendSummary, OrgRole, OverviewAccess, OverviewService, apply_live_status, health_name,
+    severity_name, trial_status,
+};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use chrono::{Duration, Utc};
+use pistonprotection_common::error::Error;
+use pistonprotection_proto::backend::BackendStatus;
+use pistonprotection_proto::common::HealthStatus;
+use tower::ServiceExt;
+
+use super::test_utils::{constants::TEST_ORG_ID, create_test_app_state};
+
+fn context(role: &str, method: AuthMethod, organizations: &[&str]) -> AuthContext {
+    AuthContext {
+        user_id: "test-user-001".to_string(),
+        email: "user@example.com".to_string(),
+        role: role.to_string(),
+        organizations: organizations.iter().map(|org| org.to_string()).collect(),
+        auth_method: method,
+        api_key_id: None,
+        support_grant_id: None,
+    }
+}
+
+/// Test organization roles are parsed from their database values
+#[test]
+fn test_org_role_parse() {
+    assert_eq!(OrgRole::parse("owner"), Some(OrgRole::Owner));
+    assert_eq!(OrgRole::parse("viewer"), Some(OrgRole::Viewer));
+    assert_eq!(OrgRole::parse("superuser"), None);
+    assert!(OrgRole::Owner > OrgRole::Admin);
+    assert!(OrgRole::Member > OrgRole::Viewer);
+}
+
+/// Test the sections each role can see
+#[test]
+fn test_access_for_roles() {
+    let viewer = OverviewAccess::for_roles("user", Some(OrgRole::Viewer));
+    assert!(viewer.backends && viewer.incidents && viewer.trial);
+    assert!(!viewer.traffic && !viewer.quota);
+
+    let member = OverviewAccess::for_roles("user", Some(OrgRole::Member));
+    assert!(member.traffic && !member.quota);
+
+    let admin = OverviewAccess::for_roles("user", Some(OrgRole::Admin));
+    assert!(admin.traffic && admin.quota);
+    assert_eq!(
+        OverviewAccess::for_roles("user", Some(OrgRole::Owner)),
+        admin
+    );
+
+    let outsider = OverviewAccess::for_roles("user", None);
+    assert!(!outsider.backends && !outsider.incidents && !outsider.traffic && !outsider.quota);
+    assert!(!outsider.trial);
+
+    // Platform admins see every organization in full
+    assert_eq!(OverviewAccess::for_roles("admin", None), admin);
+}
+
+/// Test live statuses overlay traffic and raise attacks
+#[test]
+fn test_apply_live_status() {
+    let mut backend = BackendSummary {
+        id: "b1".to_string(),
+        name: "lobby".to_string(),
+        health: health_name(HealthStatus::Healthy),
+        healthy_origins: 2,
+        total_origins: 2,
+        under_attack: false,
+        requests_per_second: 0,
+    };
+    apply_live_status(
+        &mut backend,
+        &BackendStatus {
+            requests_per_second: 1200,
+            under_attack: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(backend.requests_per_second, 1200);
+    assert!(backend.under_attack);
+
+    // A stale cache entry does not clear an attack still open in the database
+    apply_live_status(&mut backend, &BackendStatus::default());
+    assert!(backend.under_attack);
+}
+
+/// Test the trial section follows the trial from its days left to its outcome
+#[test]
+fn test_trial_status() {
+    let now = Utc::now();
+
+    let trialing = trial_status(Some(now + Duration::hours(49)), None, None, false, now).unwrap();
+    assert_eq!(trialing.state, "trialing");
+    assert_eq!(trialing.days_remaining, 3);
+
+    let ended = trial_status(Some(now - Duration::minutes(5)), None, None, false, now).unwrap();
+    assert_eq!(ended.state, "ended");
+    assert_eq!(ended.days_remaining, 0);
+
+    let grace_ends_at = now + Duration::days(3);
+    let grace = trial_status(
+        Some(now - Duration::days(1)),
+        Some("grace"),
+        Some(grace_ends_at),
+        false,
+        now,
+    )
+    .unwrap();
+    assert_eq!(grace.state, "grace");
+    assert_eq!(grace.grace_ends_at, Some(grace_ends_at));
+
+    let suspended = trial_status(
+        Some(now - Duration::days(5)),
+        Some("suspended"),
+        None,
+        true,
+        now,
+    )
+    .unwrap();
+    assert!(suspended.protection_suspended);
+
+    // Nothing to show without a trial or once it was paid for
+    assert!(trial_status(None, None, None, false, now).is_none());
+    assert!(
+        trial_status(
+            Some(now - Duration::days(30)),
+            Some("converted"),
+            None,
+            false,
+            now
+        )
+        .is_none()
+    );
+}
+
+/// Test enum names shown on the dashboard
+#[test]
+fn test_names() {
+    assert_eq!(health_name(HealthStatus::Degraded), "degraded");
+    assert_eq!(severity_name(3), "high");
+    assert_eq!(severity_name(42), "unspecified");
+}
+
+/// Test an organization is required
+#[tokio::test]
+async fn test_overview_requires_organization() {
+    let service = OverviewService::new(create_test_app_state());
+    let result = service
+        .get(&context("user", AuthMethod::Jwt, &[]), None)
+        .await;
+    assert!(matches!(result, Err(Error::Validation(_))));
+}
+
+/// Test API keys are limited to their organization
+#[tokio::test]
+async fn test_overview_api_key_organization() {
+    let service = OverviewService::new(create_test_app_state());
+    let result = service
+        .get(
+            &context("user", AuthMethod::ApiKey, &[TEST_ORG_ID]),
+            Some("other-org"),
+        )
+        .await;
+    assert!(matches!(result, Err(Error::Forbidden(_))));
+}
+
+/// Test the endpoint rejects requests without credentials
+#[tokio::test]
+async fn test_overview_unauthorized() {
+    let app = crate::handlers::http::create_router(create_test_app_state());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/v1/overview")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
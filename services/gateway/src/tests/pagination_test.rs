@@ -0,0 +1,129 @@
+//! Conformance tests for the list APIs' pagination
+
+use super::test_utils::{
+    assert_grpc_status_code, constants, create_test_app_state, create_test_request,
+};
+use crate::services::approval::PENDING_ACTION_LIST;
+use crate::services::backend::BACKEND_LIST;
+use crate::services::config_history::REVISION_LIST;
+use crate::services::filter::RULE_LIST;
+use crate::services::metrics::ATTACK_EVENT_LIST;
+use crate::services::scoring::api::{BLOCKED_IP_LIST, BlockedIPEntry};
+use pistonprotection_common::pagination::{PageRequest, check_conformance};
+use pistonprotection_proto::Pagination;
+use pistonprotection_proto::backend::backend_service_server::BackendService;
+use pistonprotection_proto::backend::*;
+use pistonprotection_proto::filter::filter_service_server::FilterService;
+use pistonprotection_proto::filter::*;
+use pistonprotection_proto::metrics::metrics_service_server::MetricsService;
+use pistonprotection_proto::metrics::*;
+use tonic::Code;
+
+#[test]
+fn test_list_specs_conform() {
+    for (name, spec) in [
+        ("backends", &BACKEND_LIST),
+        ("rules", &RULE_LIST),
+        ("blocked IPs", &BLOCKED_IP_LIST),
+        ("config revisions", &REVISION_LIST),
+        ("pending actions", &PENDING_ACTION_LIST),
+        ("attack events", &ATTACK_EVENT_LIST),
+    ] {
+        if let Err(e) = check_conformance(spec) {
+            panic!("{} list spec: {}", name, e);
+        }
+    }
+}
+
+#[test]
+fn test_blocked_ips_pages() {
+    let entries = || {
+        (0..12u8).map(|i| BlockedIPEntry {
+            ip: format!("203.0.113.{}", i),
+            threat_score: 50 + i % 3,
+            block_reason: Some(if i % 2 == 0 { "flood" } else { "scanner" }.to_string()),
+            total_requests: u64::from(i) * 10,
+            blocked_requests: u64::from(i),
+        })
+    };
+
+    let mut seen = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let pagination = Pagination {
+            page_size: 4,
+            cursor: cursor.clone(),
+            filter: r#"block_reason = "flood""#.to_string(),
+            ..Default::default()
+        };
+        let request = PageRequest::new(&BLOCKED_IP_LIST, Some(&pagination)).unwrap();
+        let (page, total) = request.apply(entries());
+        assert_eq!(total, 6);
+        seen.extend(page.items);
+        match page.next_cursor {
+            Some(next) => cursor = next,
+            None => break,
+        }
+    }
+
+    // Highest threat score first by default
+    assert_eq!(seen.len(), 6);
+    assert!(
+        seen.windows(2)
+            .all(|pair| pair[0].threat_score >= pair[1].threat_score)
+    );
+}
+
+#[tokio::test]
+async fn test_list_backends_rejects_unknown_sort_field() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(ListBackendsRequest {
+        organization_id: constants::TEST_ORG_ID.to_string(),
+        pagination: Some(Pagination {
+            order_by: "secret_column".to_string(),
+            ..Default::default()
+        }),
+        type_filter: 0,
+    });
+
+    let status = service.list_backends(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_list_rules_rejects_invalid_filter() {
+    let service = crate::handlers::grpc::FilterGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(ListRulesRequest {
+        backend_id: constants::TEST_BACKEND_ID.to_string(),
+        pagination: Some(Pagination {
+            filter: "priority ~ 1".to_string(),
+            ..Default::default()
+        }),
+        include_disabled: true,
+    });
+
+    let status = service.list_rules(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_list_attack_events_rejects_invalid_cursor() {
+    let service = crate::handlers::grpc::MetricsGrpcService::new(create_test_app_state());
+
+    let now = chrono::Utc::now();
+    let request = create_test_request(ListAttackEventsRequest {
+        backend_id: constants::TEST_BACKEND_ID.to_string(),
+        start_time: Some((now - chrono::Duration::hours(1)).into()),
+        end_time: Some(now.into()),
+        pagination: Some(Pagination {
+            order_by: "-peak_pps".to_string(),
+            cursor: "not-a-cursor".to_string(),
+            ..Default::default()
+        }),
+    });
+
+    let status = service.list_attack_events(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
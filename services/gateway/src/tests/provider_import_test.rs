@@ -0,0 +1,184 @@
+//! Tests for importing configuration exported from other providers
+
+use super::test_utils::{
+    assert_grpc_status_code, constants, create_test_app_state, create_test_request,
+};
+use crate::services::provider_import::translate;
+use crate::services::rule_transfer::{RowError, RuleRow};
+use pistonprotection_proto::filter::filter_service_server::FilterService;
+use pistonprotection_proto::filter::*;
+use tonic::Code;
+
+fn valid_rows(rows: Vec<(usize, Result<RuleRow, RowError>)>) -> Vec<(usize, RuleRow)> {
+    rows.into_iter()
+        .filter_map(|(entry, row)| row.ok().map(|row| (entry, row)))
+        .collect()
+}
+
+/// Test IP access rules are merged into one rule per action and kind
+#[test]
+fn test_cloudflare_access_rules() {
+    let export = br#"{
+        "success": true,
+        "result": [
+            {"id": "a", "mode": "block", "configuration": {"target": "ip", "value": "198.51.100.4"}},
+            {"id": "b", "mode": "block", "configuration": {"target": "ip_range", "value": "203.0.113.0/24"}},
+            {"id": "c", "mode": "whitelist", "configuration": {"target": "ip6", "value": "2001:db8::1"}},
+            {"id": "d", "mode": "block", "configuration": {"target": "country", "value": "CN"}},
+            {"id": "e", "mode": "challenge", "configuration": {"target": "asn", "value": "64500"}},
+            {"id": "f", "mode": "block", "configuration": {"target": "country", "value": "T1"}},
+            {"id": "g", "mode": "block", "configuration": {"target": "ip", "value": "not-an-ip"}}
+        ]
+    }"#;
+    let translation = translate(ConfigProvider::Cloudflare, export).unwrap();
+    assert_eq!(translation.total_entries, 7);
+    assert_eq!(translation.unsupported.len(), 1);
+    assert!(translation.unsupported[0].starts_with("Entry 6:"));
+
+    let errors: Vec<(usize, &'static str)> = translation
+        .rows
+        .iter()
+        .filter_map(|(entry, row)| row.as_ref().err().map(|e| (*entry, e.field)))
+        .collect();
+    assert_eq!(errors, [(7, "value")]);
+
+    let rows = valid_rows(translation.rows);
+    assert_eq!(rows.len(), 4);
+
+    let (entry, blocks) = &rows[0];
+    assert_eq!(*entry, 1);
+    assert_eq!(blocks.action, "drop");
+    assert_eq!(blocks.source_ips, ["198.51.100.4", "203.0.113.0/24"]);
+
+    assert_eq!(rows[1].1.action, "allow");
+    assert_eq!(rows[2].1.source_countries, ["CN"]);
+    assert_eq!(rows[3].1.action, "challenge");
+    assert_eq!(rows[3].1.source_asns, ["AS64500"]);
+
+    for (_, row) in rows {
+        row.into_rule().unwrap();
+    }
+}
+
+/// Test custom rule expressions are split into one rule per alternative
+#[test]
+fn test_cloudflare_custom_rules() {
+    let export = br#"{
+        "result": {
+            "phase": "http_request_firewall_custom",
+            "rules": [
+                {
+                    "action": "block",
+                    "description": "Block scanners",
+                    "expression": "(ip.src in {192.0.2.0/24 198.51.100.7}) or (ip.geoip.country eq \"RU\" and tcp.dstport in {25565 27000..27015})"
+                },
+                {
+                    "action": "block",
+                    "expression": "cf.bot_management.score lt 30"
+                },
+                {
+                    "action": "managed_challenge",
+                    "expression": "not ip.src in {192.0.2.1}"
+                }
+            ]
+        }
+    }"#;
+    let translation = translate(ConfigProvider::Cloudflare, export).unwrap();
+    assert_eq!(translation.total_entries, 3);
+    assert_eq!(translation.unsupported.len(), 2);
+
+    let rows = valid_rows(translation.rows);
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].1.name, "Block scanners (1/2)");
+    assert_eq!(rows[0].1.source_ips, ["192.0.2.0/24", "198.51.100.7"]);
+    assert_eq!(rows[1].1.source_countries, ["RU"]);
+    assert_eq!(rows[1].1.destination_ports, ["25565", "27000-27015"]);
+    assert_eq!(rows[1].1.protocols, ["tcp"]);
+
+    for (_, row) in rows {
+        let rule = row.into_rule().unwrap();
+        assert_eq!(
+            rule.action,
+            pistonprotection_proto::common::Action::Drop as i32
+        );
+    }
+}
+
+/// Test rate limits keep their rate and report dropped features
+#[test]
+fn test_cloudflare_rate_limits() {
+    let export = br#"[
+        {
+            "id": "rl",
+            "description": "Login",
+            "match": {"request": {"methods": ["POST"], "schemes": ["HTTPS"], "url": "example.com/login*"}},
+            "threshold": 120,
+            "period": 60,
+            "action": {"mode": "ban", "timeout": 600}
+        },
+        {
+            "action": "block",
+            "expression": "http.request.uri.path eq \"/api\"",
+            "ratelimit": {"characteristics": ["ip.src", "cf.colo.id"], "period": 10, "requests_per_period": 50}
+        }
+    ]"#;
+    let translation = translate(ConfigProvider::Cloudflare, export).unwrap();
+    assert_eq!(translation.unsupported.len(), 1);
+    assert!(translation.unsupported[0].contains("600s"));
+
+    let rows = valid_rows(translation.rows);
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].1.rate_limit_rps, Some(2));
+    assert_eq!(rows[0].1.rate_limit_burst, Some(120));
+    assert_eq!(rows[1].1.rate_limit_rps, Some(5));
+
+    let rule = rows[0].1.clone().into_rule().unwrap();
+    let Some(l7_match::Match::Http(http)) = rule.r#match.unwrap().l7_match.unwrap().r#match else {
+        panic!("expected an HTTP match");
+    };
+    assert_eq!(http.methods, ["POST"]);
+    assert_eq!(http.hosts, ["example.com"]);
+    assert_eq!(http.paths, ["/login*"]);
+    assert!(http.require_tls);
+}
+
+/// Test generic entries and their validation
+#[test]
+fn test_generic_entries() {
+    let export = br#"[
+        {"type": "cidr", "value": "203.0.113.0/24", "action": "deny"},
+        {"type": "country", "value": "KP"},
+        {"type": "asn", "value": "AS64501", "action": "allow"},
+        {"type": "port", "value": "22"},
+        {"type": "ip", "value": "192.0.2.1", "action": "tarpit"},
+        {"type": "country", "value": "North Korea"}
+    ]"#;
+    let translation = translate(ConfigProvider::Generic, export).unwrap();
+    assert_eq!(translation.total_entries, 6);
+
+    let errors: Vec<(usize, &'static str)> = translation
+        .rows
+        .iter()
+        .filter_map(|(entry, row)| row.as_ref().err().map(|e| (*entry, e.field)))
+        .collect();
+    assert_eq!(errors, [(4, "type"), (5, "action"), (6, "value")]);
+    assert_eq!(valid_rows(translation.rows).len(), 3);
+
+    assert!(translate(ConfigProvider::Generic, b"{}").is_err());
+    assert!(translate(ConfigProvider::Unspecified, b"[]").is_err());
+}
+
+#[tokio::test]
+async fn test_import_provider_config_requires_backend() {
+    let service = crate::handlers::grpc::FilterGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(ImportProviderConfigRequest {
+        organization_id: constants::TEST_ORG_ID.to_string(),
+        provider: ConfigProvider::Cloudflare as i32,
+        data: b"[]".to_vec(),
+        ..Default::default()
+    });
+
+    let status = service.import_provider_config(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
@@ -0,0 +1,142 @@
+//! Tests for rate limit profiles
+
+use super::test_utils::{assert_grpc_status_code, create_test_app_state, create_test_request};
+use crate::services::rate_profile::{
+    MAX_PROFILES, rate_profiles_to_proto, validate_name, validate_profiles,
+};
+use pistonprotection_common::error::Error;
+use pistonprotection_common::rate_profile::ManualProfile;
+use pistonprotection_proto::backend::backend_service_server::BackendService;
+use pistonprotection_proto::backend::{
+    RateLimitProfile, RateProfileWindow, TriggerRateProfileRequest,
+};
+use tonic::Code;
+
+fn profile(name: &str, per_ip_pps: u64) -> RateLimitProfile {
+    RateLimitProfile {
+        name: name.to_string(),
+        per_ip_pps,
+        per_ip_burst: 0,
+    }
+}
+
+fn window(profile: &str, days: Vec<u32>, start_minute: u32, end_minute: u32) -> RateProfileWindow {
+    RateProfileWindow {
+        profile: profile.to_string(),
+        days,
+        start_minute,
+        end_minute,
+    }
+}
+
+/// Test profile names are normalized and restricted
+#[test]
+fn test_validate_name() {
+    assert_eq!(validate_name(" Restart-Storm ").unwrap(), "restart-storm");
+    assert!(validate_name("").is_err());
+    assert!(validate_name("night time").is_err());
+    assert!(validate_name(&"a".repeat(65)).is_err());
+}
+
+/// Test schedules can only refer to defined profiles
+#[test]
+fn test_validate_profiles() {
+    let profiles = validate_profiles(
+        "backend-1",
+        vec![profile("normal", 1000), profile("Event", 5000)],
+        vec![window("event", vec![5, 4, 5], 18 * 60, 2 * 60)],
+        "normal",
+        -300,
+    )
+    .unwrap();
+    assert_eq!(profiles.profiles[1].name, "event");
+    assert_eq!(profiles.windows[0].days, [4, 5]);
+    assert_eq!(profiles.default_profile, "normal");
+
+    let invalid = [
+        validate_profiles("b", vec![profile("a", 1), profile("A", 2)], vec![], "", 0),
+        validate_profiles("b", vec![profile("a", 0)], vec![], "", 0),
+        validate_profiles(
+            "b",
+            vec![profile("a", 1)],
+            vec![window("b", vec![], 0, 60)],
+            "",
+            0,
+        ),
+        validate_profiles(
+            "b",
+            vec![profile("a", 1)],
+            vec![window("a", vec![7], 0, 60)],
+            "",
+            0,
+        ),
+        validate_profiles(
+            "b",
+            vec![profile("a", 1)],
+            vec![window("a", vec![], 0, 1440)],
+            "",
+            0,
+        ),
+        validate_profiles("b", vec![profile("a", 1)], vec![], "b", 0),
+        validate_profiles("b", vec![profile("a", 1)], vec![], "", 15 * 60),
+        validate_profiles(
+            "b",
+            (0..=MAX_PROFILES)
+                .map(|i| profile(&format!("p{}", i), 1))
+                .collect(),
+            vec![],
+            "",
+            0,
+        ),
+    ];
+    for result in invalid {
+        assert!(matches!(result, Err(Error::Validation(_))));
+    }
+}
+
+/// Test the API reports the profile in effect and why
+#[test]
+fn test_rate_profiles_to_proto() {
+    let mut profiles = validate_profiles(
+        "backend-1",
+        vec![profile("normal", 1000), profile("restart-storm", 50)],
+        vec![],
+        "normal",
+        0,
+    )
+    .unwrap();
+
+    let proto = rate_profiles_to_proto(&profiles, None, 1000);
+    assert_eq!(proto.active_profile, "normal");
+    assert_eq!(proto.active_source, "default");
+    assert!(proto.manual_profile.is_empty());
+
+    profiles.manual = Some(ManualProfile {
+        profile: "restart-storm".to_string(),
+        triggered_by: "user-1".to_string(),
+        until: 2000,
+    });
+    let proto = rate_profiles_to_proto(&profiles, None, 1000);
+    assert_eq!(proto.active_profile, "restart-storm");
+    assert_eq!(proto.active_source, "manual");
+    assert_eq!(proto.triggered_by, "user-1");
+    assert_eq!(proto.manual_until.unwrap().seconds, 2000);
+
+    // Ended triggers are not reported
+    let proto = rate_profiles_to_proto(&profiles, None, 2000);
+    assert_eq!(proto.active_profile, "normal");
+    assert!(proto.manual_until.is_none());
+}
+
+#[tokio::test]
+async fn test_trigger_rate_profile_requires_backend() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(TriggerRateProfileRequest {
+        profile: "event".to_string(),
+        ..Default::default()
+    });
+
+    let status = service.trigger_rate_profile(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
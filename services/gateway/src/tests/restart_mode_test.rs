@@ -0,0 +1,72 @@
+//! Tests for restart modes
+
+use super::test_utils::{assert_grpc_status_code, create_test_app_state, create_test_request};
+use crate::services::restart_mode::{
+    DEFAULT_CONNECTION_MULTIPLIER, MAX_CONNECTION_MULTIPLIER, restart_mode_to_proto,
+    validate_connection_multiplier,
+};
+use pistonprotection_common::restart_mode::RestartMode;
+use pistonprotection_proto::backend::backend_service_server::BackendService;
+use pistonprotection_proto::backend::{StartRestartModeRequest, StopRestartModeRequest};
+use tonic::Code;
+
+/// Test the connection multiplier must actually relax the limits
+#[test]
+fn test_validate_connection_multiplier() {
+    assert_eq!(
+        validate_connection_multiplier(0).unwrap(),
+        DEFAULT_CONNECTION_MULTIPLIER
+    );
+    assert_eq!(validate_connection_multiplier(10).unwrap(), 10);
+    assert!(validate_connection_multiplier(1).is_err());
+    assert!(validate_connection_multiplier(MAX_CONNECTION_MULTIPLIER + 1).is_err());
+}
+
+/// Test ended restart modes are reported as inactive
+#[test]
+fn test_restart_mode_to_proto() {
+    let restart = RestartMode {
+        backend_id: "backend-1".to_string(),
+        connection_multiplier: 5,
+        reason: "Weekly restart".to_string(),
+        started_by: "user-1".to_string(),
+        started_at: 1000,
+        ends_at: 1600,
+    };
+
+    let proto = restart_mode_to_proto(Some(&restart), "backend-1", 1200);
+    assert!(proto.active);
+    assert_eq!(proto.connection_multiplier, 5);
+    assert_eq!(proto.started_by, "user-1");
+    assert_eq!(proto.ends_at.unwrap().seconds, 1600);
+
+    let proto = restart_mode_to_proto(Some(&restart), "backend-1", 1600);
+    assert!(!proto.active);
+    assert_eq!(proto.backend_id, "backend-1");
+    assert!(proto.ends_at.is_none());
+
+    assert!(!restart_mode_to_proto(None, "backend-1", 1200).active);
+}
+
+#[tokio::test]
+async fn test_start_restart_mode_requires_backend() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(StartRestartModeRequest {
+        duration_minutes: 10,
+        ..Default::default()
+    });
+
+    let status = service.start_restart_mode(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_stop_restart_mode_requires_backend() {
+    let service = crate::handlers::grpc::BackendGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(StopRestartModeRequest::default());
+
+    let status = service.stop_restart_mode(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
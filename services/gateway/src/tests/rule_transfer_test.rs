@@ -0,0 +1,188 @@
+//! Tests for rule import and export
+
+use super::mock_db::create_test_filter_rule;
+use super::test_utils::{
+    assert_grpc_status_code, constants, create_test_app_state, create_test_request,
+};
+use crate::services::rule_transfer::{RuleRow, decode, encode};
+use pistonprotection_proto::common::{
+    Action, IpAddress, IpNetwork, PortRange, Protocol, RateLimit,
+};
+use pistonprotection_proto::filter::filter_service_server::FilterService;
+use pistonprotection_proto::filter::*;
+use tonic::Code;
+
+fn network(addr: &str, prefix_length: u32) -> IpNetwork {
+    IpNetwork {
+        address: Some(IpAddress::from(addr.parse::<std::net::IpAddr>().unwrap())),
+        prefix_length,
+    }
+}
+
+fn sample_rule() -> FilterRule {
+    let mut rule = create_test_filter_rule("rule-1", "Limit game ports");
+    rule.action = Action::RateLimit as i32;
+    rule.rate_limit = Some(RateLimit {
+        requests_per_second: 200,
+        burst_size: 400,
+        window_seconds: 1,
+    });
+    rule.r#match = Some(FilterMatch {
+        source_ips: vec![network("198.51.100.0", 24), network("2001:db8::1", 128)],
+        source_countries: vec!["DE".to_string()],
+        source_asns: vec!["AS64500".to_string()],
+        destination_ports: vec![
+            PortRange {
+                start: 25565,
+                end: 25565,
+            },
+            PortRange {
+                start: 27000,
+                end: 27015,
+            },
+        ],
+        protocols: vec![Protocol::Tcp as i32, Protocol::Udp as i32],
+        time_match: Some(TimeMatch {
+            days_of_week: vec![0, 6],
+            time_ranges: vec![TimeRange {
+                start_minutes: 0,
+                end_minutes: 360,
+            }],
+        }),
+        ..Default::default()
+    });
+    rule
+}
+
+/// Test rules survive an export and import in both formats
+#[test]
+fn test_round_trip() {
+    let rule = sample_rule();
+    let row = RuleRow::from_rule(constants::TEST_BACKEND_ID, &rule);
+    assert_eq!(row.action, "rate_limit");
+    assert_eq!(row.source_ips, ["198.51.100.0/24", "2001:db8::1"]);
+    assert_eq!(row.destination_ports, ["25565", "27000-27015"]);
+    assert_eq!(row.protocols, ["tcp", "udp"]);
+
+    for format in [TransferFormat::Json, TransferFormat::Csv] {
+        let data = encode(format, std::slice::from_ref(&row)).unwrap();
+        let rows = decode(format, &data).unwrap();
+        assert_eq!(rows.len(), 1);
+
+        let (number, decoded) = rows.into_iter().next().unwrap();
+        assert_eq!(number, 1);
+        let decoded = decoded.unwrap();
+        assert_eq!(decoded, row, "{:?}", format);
+
+        let imported = decoded.into_rule().unwrap();
+        assert_eq!(imported.name, rule.name);
+        assert_eq!(imported.priority, rule.priority);
+        assert_eq!(imported.action, rule.action);
+        assert_eq!(imported.rate_limit, rule.rate_limit);
+        assert_eq!(imported.r#match, rule.r#match);
+    }
+}
+
+/// Test a blocklist from another provider imports as drop rules
+#[test]
+fn test_csv_blocklist() {
+    let csv = "Name,Source_IPs,Description\n\
+               scanners,203.0.113.0/24; 198.51.100.7,Known scanners\n\
+               botnet,192.0.2.0/25,\n";
+    let rows = decode(TransferFormat::Csv, csv.as_bytes()).unwrap();
+    assert_eq!(rows.len(), 2);
+
+    let rule = rows[0].1.clone().unwrap().into_rule().unwrap();
+    assert_eq!(rule.name, "scanners");
+    assert_eq!(rule.description, "Known scanners");
+    assert_eq!(rule.action, Action::Drop as i32);
+    assert!(rule.enabled);
+    assert_eq!(
+        rule.r#match.unwrap().source_ips,
+        [network("203.0.113.0", 24), network("198.51.100.7", 32)]
+    );
+}
+
+/// Test invalid rows are reported with the column at fault
+#[test]
+fn test_row_errors() {
+    let csv = "name,action,source_ips,destination_ports,source_countries,rate_limit_rps,priority\n\
+               ok,drop,203.0.113.1,,,,\n\
+               bad-network,drop,203.0.113.0/33,,,,\n\
+               bad-action,explode,,,,,\n\
+               bad-ports,drop,,90-80,,,\n\
+               bad-country,drop,,,Germany,,\n\
+               no-rate,rate_limit,,,,,\n\
+               bad-priority,drop,,,,,high\n\
+               ,drop,203.0.113.2,,,,\n";
+    let rows = decode(TransferFormat::Csv, csv.as_bytes()).unwrap();
+
+    let errors: Vec<(usize, &str)> = rows
+        .into_iter()
+        .filter_map(|(row, parsed)| {
+            parsed
+                .and_then(RuleRow::into_rule)
+                .err()
+                .map(|e| (row, e.field))
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (2, "source_ips"),
+            (3, "action"),
+            (4, "destination_ports"),
+            (5, "source_countries"),
+            (6, "rate_limit_rps"),
+            (7, "priority"),
+            (8, "name"),
+        ]
+    );
+}
+
+/// Test files that cannot be read as a whole are rejected
+#[test]
+fn test_invalid_files() {
+    assert!(decode(TransferFormat::Csv, b"name,colour\nrule,blue\n").is_err());
+    assert!(decode(TransferFormat::Csv, b"action\ndrop\n").is_err());
+    assert!(decode(TransferFormat::Json, b"{\"name\": \"rule\"}").is_err());
+    assert!(decode(TransferFormat::Unspecified, b"[]").is_err());
+
+    // Unknown fields of a JSON rule only fail that rule
+    let rows = decode(
+        TransferFormat::Json,
+        br#"[{"name": "a", "source_ips": ["192.0.2.1"]}, {"name": "b", "colour": "blue"}]"#,
+    )
+    .unwrap();
+    assert!(rows[0].1.is_ok());
+    assert!(rows[1].1.is_err());
+}
+
+#[tokio::test]
+async fn test_export_rules_requires_format() {
+    let service = crate::handlers::grpc::FilterGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(ExportRulesRequest {
+        organization_id: constants::TEST_ORG_ID.to_string(),
+        ..Default::default()
+    });
+
+    let status = service.export_rules(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_import_rules_rejects_invalid_file() {
+    let service = crate::handlers::grpc::FilterGrpcService::new(create_test_app_state());
+
+    let request = create_test_request(ImportRulesRequest {
+        organization_id: constants::TEST_ORG_ID.to_string(),
+        format: TransferFormat::Csv as i32,
+        data: b"name,colour\nrule,blue\n".to_vec(),
+        dry_run: true,
+        ..Default::default()
+    });
+
+    let status = service.import_rules(request).await.err().unwrap();
+    assert_grpc_status_code(&status, Code::InvalidArgument);
+}
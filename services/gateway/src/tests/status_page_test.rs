@@ -0,0 +1,133 @@
+//! Tests for public status pages
+
+use crate::services::status_page::{
+    ComponentState, ComponentStatus, DailyUptime, IncidentSummary, MAX_TITLE_LEN, PublicStatus,
+    component_state, escape_html, overall_state, render_html, uptime_percent, valid_token,
+    validate_title,
+};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use chrono::{NaiveDate, Utc};
+use pistonprotection_common::error::Error;
+use tower::ServiceExt;
+
+use super::test_utils::create_test_app_state;
+
+fn day(d: u32, availability: f64) -> DailyUptime {
+    DailyUptime {
+        date: NaiveDate::from_ymd_opt(2026, 1, d).unwrap(),
+        availability,
+    }
+}
+
+/// Test backend states from probe availability and mitigation
+#[test]
+fn test_component_state() {
+    assert_eq!(component_state(None, false), ComponentState::Unknown);
+    assert_eq!(component_state(None, true), ComponentState::Mitigating);
+    assert_eq!(
+        component_state(Some(1.0), false),
+        ComponentState::Operational
+    );
+    assert_eq!(component_state(Some(1.0), true), ComponentState::Mitigating);
+    assert_eq!(component_state(Some(0.5), true), ComponentState::Degraded);
+    assert_eq!(component_state(Some(0.0), false), ComponentState::Outage);
+}
+
+/// Test a page takes the worst state of its backends
+#[test]
+fn test_overall_state() {
+    assert_eq!(overall_state([]), ComponentState::Unknown);
+    assert_eq!(
+        overall_state([ComponentState::Unknown, ComponentState::Operational]),
+        ComponentState::Operational
+    );
+    assert_eq!(
+        overall_state([
+            ComponentState::Mitigating,
+            ComponentState::Outage,
+            ComponentState::Degraded
+        ]),
+        ComponentState::Outage
+    );
+}
+
+/// Test uptime is averaged over days with probe results
+#[test]
+fn test_uptime_percent() {
+    assert_eq!(uptime_percent(&[]), None);
+    let uptime = uptime_percent(&[day(1, 1.0), day(2, 0.5)]).unwrap();
+    assert!((uptime - 75.0).abs() < 1e-9);
+}
+
+/// Test tokens are checked before any lookup
+#[test]
+fn test_valid_token() {
+    assert!(valid_token("0123456789abcdef0123456789abcdef"));
+    assert!(!valid_token("0123456789abcdef"));
+    assert!(!valid_token("0123456789abcdef0123456789abcdeg"));
+    assert!(!valid_token("../../0123456789abcdef0123456789"));
+}
+
+/// Test titles are trimmed and bounded
+#[test]
+fn test_validate_title() {
+    assert_eq!(validate_title("  Acme  ").unwrap(), "Acme");
+    assert!(matches!(
+        validate_title(&"a".repeat(MAX_TITLE_LEN + 1)),
+        Err(Error::Validation(_))
+    ));
+}
+
+/// Test names from the organization are escaped in the page
+#[test]
+fn test_render_html_escapes() {
+    assert_eq!(
+        escape_html(r#"<a href="x">&'"#),
+        "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+    );
+
+    let status = PublicStatus {
+        title: "Acme <Status>".to_string(),
+        state: ComponentState::Mitigating,
+        components: vec![ComponentStatus {
+            backend_id: "backend-1".to_string(),
+            name: "<script>alert(1)</script>".to_string(),
+            state: ComponentState::Mitigating,
+            availability: Some(1.0),
+            uptime_percent: Some(99.5),
+            daily: vec![day(1, 1.0), day(2, 0.99)],
+        }],
+        incidents: vec![IncidentSummary {
+            backend_name: "<script>alert(1)</script>".to_string(),
+            attack_type: "syn_flood".to_string(),
+            started_at: Utc::now(),
+            ended_at: None,
+        }],
+        generated_at: Utc::now(),
+    };
+
+    let html = render_html(&status);
+    assert!(html.contains("<title>Acme &lt;Status&gt;</title>"));
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("99.50% uptime"));
+    assert!(html.contains("syn_flood attack"));
+    assert!(html.contains("being mitigated"));
+}
+
+/// Test malformed tokens are rejected without a database
+#[tokio::test]
+async fn test_unknown_status_page_not_found() {
+    let app = crate::handlers::http::create_router(create_test_app_state());
+
+    for uri in ["/status/not-a-token", "/status/not-a-token/json"] {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,200 @@
+//! Tests for read-only support access
+
+use super::test_utils::{constants::TEST_ORG_ID, create_test_app_state};
+use crate::middleware::auth::{
+    AuthContext, AuthError, AuthMethod, AuthMiddleware, AuthState, Claims, JwtValidator,
+    is_read_only_method,
+};
+use crate::services::overview::OverviewService;
+use bytes::Bytes;
+use http_body_util::combinators::UnsyncBoxBody;
+use jsonwebtoken::{EncodingKey, Header, encode};
+use pistonprotection_common::config::AuthConfig;
+use pistonprotection_common::error::Error;
+use std::convert::Infallible;
+use std::future::{Ready, ready};
+use std::task::{Context, Poll};
+use tower::Service;
+
+fn config() -> AuthConfig {
+    AuthConfig {
+        jwt_secret: "test-secret".to_string(),
+        jwt_issuer: "test-issuer".to_string(),
+        jwt_audience: "test-audience".to_string(),
+        skip_auth: false,
+        public_paths: vec![],
+    }
+}
+
+fn token(typ: &str, grant: Option<&str>) -> String {
+    let now = chrono::Utc::now().timestamp();
+    let claims = Claims {
+        sub: "staff-001".to_string(),
+        iss: "test-issuer".to_string(),
+        aud: "test-audience".to_string(),
+        exp: now + 600,
+        iat: now,
+        nbf: now,
+        jti: uuid::Uuid::new_v4().to_string(),
+        email: "staff@example.com".to_string(),
+        role: "support".to_string(),
+        orgs: vec![TEST_ORG_ID.to_string()],
+        sid: None,
+        typ: typ.to_string(),
+        grant: grant.map(str::to_string),
+    };
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(b"test-secret"),
+    )
+    .unwrap()
+}
+
+/// Service behind the middleware, answering every request it is given
+#[derive(Clone)]
+struct Handler;
+
+impl Service<http::Request<()>> for Handler {
+    type Response = http::Response<UnsyncBoxBody<Bytes, tonic::Status>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: http::Request<()>) -> Self::Future {
+        ready(Ok(http::Response::new(UnsyncBoxBody::default())))
+    }
+}
+
+/// gRPC status the middleware answered a support token call with
+async fn support_call_status(method: &str) -> Option<String> {
+    let mut middleware = AuthMiddleware::new(Handler, AuthState::new(Some(&config()), None, false));
+    let request = http::Request::builder()
+        .uri(method)
+        .header(
+            "authorization",
+            format!("Bearer {}", token("support", Some("grant-001"))),
+        )
+        .body(())
+        .unwrap();
+
+    let response = middleware.call(request).await.unwrap();
+    response
+        .headers()
+        .get("grpc-status")
+        .map(|status| status.to_str().unwrap().to_string())
+}
+
+fn support_context(organizations: &[&str]) -> AuthContext {
+    AuthContext {
+        user_id: "staff-001".to_string(),
+        email: "staff@example.com".to_string(),
+        role: "support".to_string(),
+        organizations: organizations.iter().map(|org| org.to_string()).collect(),
+        auth_method: AuthMethod::Jwt,
+        api_key_id: None,
+        support_grant_id: Some("grant-001".to_string()),
+    }
+}
+
+/// Test only reading methods are open to support tokens
+#[test]
+fn test_is_read_only_method() {
+    assert!(is_read_only_method(
+        "/pistonprotection.backend.BackendService/GetBackend"
+    ));
+    assert!(is_read_only_method(
+        "/pistonprotection.backend.BackendService/ListConfigRevisions"
+    ));
+    assert!(is_read_only_method(
+        "/pistonprotection.metrics.MetricsService/StreamMetrics"
+    ));
+    assert!(!is_read_only_method(
+        "/pistonprotection.backend.BackendService/DeleteBackend"
+    ));
+    assert!(!is_read_only_method(
+        "/pistonprotection.backend.BackendService/RollbackConfig"
+    ));
+    assert!(!is_read_only_method(
+        "/pistonprotection.backend.BackendService/ApprovePendingAction"
+    ));
+    assert!(!is_read_only_method(""));
+}
+
+/// Test support tokens are only accepted with their grant
+#[test]
+fn test_support_token_requires_grant() {
+    let validator = JwtValidator::new(&config());
+
+    let claims = validator
+        .validate(&token("support", Some("grant-001")))
+        .unwrap();
+    assert_eq!(claims.grant.as_deref(), Some("grant-001"));
+
+    assert!(matches!(
+        validator.validate(&token("support", None)),
+        Err(AuthError::InvalidTokenType)
+    ));
+    assert!(matches!(
+        validator.validate(&token("refresh", Some("grant-001"))),
+        Err(AuthError::InvalidTokenType)
+    ));
+}
+
+/// Test support tokens are refused when the grant cannot be checked
+#[tokio::test]
+async fn test_support_token_fails_closed() {
+    let state = AuthState::new(Some(&config()), None, false);
+    let mut headers = http::HeaderMap::new();
+    headers.insert(
+        "authorization",
+        format!("Bearer {}", token("support", Some("grant-001")))
+            .parse()
+            .unwrap(),
+    );
+
+    let result = state.authenticate(&headers, None).await;
+    assert!(matches!(result, Err(AuthError::DatabaseNotAvailable)));
+
+    let status: tonic::Status = AuthError::SupportAccessRevoked.into();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+/// Test support tokens are refused on mutating gRPC methods
+#[tokio::test]
+async fn test_support_token_refused_on_write() {
+    // PERMISSION_DENIED, before the grant is looked up
+    for method in [
+        "/pistonprotection.backend.BackendService/DeleteBackend",
+        "/pistonprotection.backend.BackendService/UpdateProtection",
+        "/pistonprotection.filter.FilterService/CreateRule",
+    ] {
+        assert_eq!(
+            support_call_status(method).await.as_deref(),
+            Some("7"),
+            "{}",
+            method
+        );
+    }
+
+    // Reads get as far as the grant check, which fails closed without a database
+    assert_eq!(
+        support_call_status("/pistonprotection.backend.BackendService/GetBackend")
+            .await
+            .as_deref(),
+        Some("16")
+    );
+}
+
+/// Test support staff only see the organization that granted access
+#[tokio::test]
+async fn test_overview_support_organization() {
+    let service = OverviewService::new(create_test_app_state());
+    let result = service
+        .get(&support_context(&[TEST_ORG_ID]), Some("other-org"))
+        .await;
+    assert!(matches!(result, Err(Error::Forbidden(_))));
+}
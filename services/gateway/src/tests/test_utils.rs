@@ -2,6 +2,7 @@
 
 use crate::services::AppState;
 use pistonprotection_common::config::Config;
+use sqlx::{PgPool, Postgres, Transaction};
 
 /// Test configuration constants
 pub mod constants {
@@ -85,6 +86,60 @@ pub fn assert_grpc_status_code(result: &tonic::Status, expected: tonic::Code) {
     );
 }
 
+/// Auth migrations, in order; the auth service owns the organization,
+/// billing and audit tables the gateway reads and writes
+const AUTH_MIGRATIONS: [&str; 9] = [
+    include_str!("../../../auth/migrations/0001_initial.up.sql"),
+    include_str!("../../../auth/migrations/0002_billing.up.sql"),
+    include_str!("../../../auth/migrations/0003_support_access.up.sql"),
+    include_str!("../../../auth/migrations/0004_session_devices.up.sql"),
+    include_str!("../../../auth/migrations/0005_refresh_token_rotation.up.sql"),
+    include_str!("../../../auth/migrations/0006_billing_event_processing.up.sql"),
+    include_str!("../../../auth/migrations/0007_plan_changes.up.sql"),
+    include_str!("../../../auth/migrations/0008_trial_expiry.up.sql"),
+    include_str!("../../../auth/migrations/0009_invoice_documents.up.sql"),
+];
+
+/// Gateway migrations, in order
+const GATEWAY_MIGRATIONS: [&str; 14] = [
+    include_str!("../../migrations/0001_gateway_schema.sql"),
+    include_str!("../../migrations/0002_alerts_table.sql"),
+    include_str!("../../migrations/0003_backend_onboarding.sql"),
+    include_str!("../../migrations/0004_origin_exposure.sql"),
+    include_str!("../../migrations/0005_origin_switches.sql"),
+    include_str!("../../migrations/0006_status_pages.sql"),
+    include_str!("../../migrations/0007_backend_modes.sql"),
+    include_str!("../../migrations/0008_ban_sync.sql"),
+    include_str!("../../migrations/0009_rate_profiles.sql"),
+    include_str!("../../migrations/0010_restart_modes.sql"),
+    include_str!("../../migrations/0011_bandwidth_quotas.sql"),
+    include_str!("../../migrations/0012_config_history.sql"),
+    include_str!("../../migrations/0013_approvals.sql"),
+    include_str!("../../migrations/0014_exemption_tokens.sql"),
+];
+
+/// Transaction on a throwaway schema holding the auth and gateway tables, in
+/// the database at `DATABASE_URL`; `None` without one
+///
+/// Roll the transaction back at the end of the test to drop the schema.
+pub async fn gateway_schema() -> Option<Transaction<'static, Postgres>> {
+    let url = std::env::var("DATABASE_URL").ok()?;
+    let pool = PgPool::connect(&url).await.unwrap();
+    let mut tx = pool.begin().await.unwrap();
+
+    let schema = format!("gateway_test_{}", uuid::Uuid::new_v4().simple());
+    sqlx::raw_sql(&format!(
+        "CREATE SCHEMA {schema}; SET LOCAL search_path TO {schema};"
+    ))
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+    for migration in AUTH_MIGRATIONS.into_iter().chain(GATEWAY_MIGRATIONS) {
+        sqlx::raw_sql(migration).execute(&mut *tx).await.unwrap();
+    }
+    Some(tx)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
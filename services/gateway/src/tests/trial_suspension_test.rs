@@ -0,0 +1,186 @@
+//! Tests for trial suspensions
+
+use super::test_utils::gateway_schema;
+use crate::services::backend_mode::SYSTEM_ACTOR;
+use crate::services::trial_suspension::{
+    BACKEND_IDS_QUERY, CLEAR_SUSPENSION_QUERY, RENEW_BEFORE_SECONDS, RESTORED_QUERY,
+    SUSPENDED_QUERY, SUSPENSION_REASON, is_suspension, needs_suspension,
+};
+use chrono::{DateTime, Duration, Utc};
+use pistonprotection_proto::backend::{BackendMode, BackendModeState};
+
+fn mode(
+    mode: BackendMode,
+    reason: &str,
+    changed_by: &str,
+    changed_at: Option<DateTime<Utc>>,
+    revert_at: Option<DateTime<Utc>>,
+) -> BackendModeState {
+    BackendModeState {
+        backend_id: "b1".to_string(),
+        mode: mode as i32,
+        reason: reason.to_string(),
+        changed_by: changed_by.to_string(),
+        changed_at: changed_at.map(Into::into),
+        revert_at: revert_at.map(Into::into),
+    }
+}
+
+/// Test only the system's passthrough for the trial counts as a suspension
+#[test]
+fn test_is_suspension() {
+    let now = Utc::now();
+    assert!(is_suspension(&mode(
+        BackendMode::Passthrough,
+        SUSPENSION_REASON,
+        SYSTEM_ACTOR,
+        Some(now),
+        None,
+    )));
+    assert!(!is_suspension(&mode(
+        BackendMode::Passthrough,
+        "Debugging origin timeouts",
+        "user-1",
+        Some(now),
+        None,
+    )));
+    assert!(!is_suspension(&mode(
+        BackendMode::Protect,
+        SUSPENSION_REASON,
+        SYSTEM_ACTOR,
+        Some(now),
+        None,
+    )));
+}
+
+/// Test suspensions are applied, renewed before they revert, and leave
+/// later operator choices alone
+#[test]
+fn test_needs_suspension() {
+    let now = Utc::now();
+    let suspended_at = now - Duration::days(2);
+
+    // Protected backends, including ones never switched, are suspended
+    let protected = mode(BackendMode::Protect, "", "", None, None);
+    assert!(needs_suspension(&protected, suspended_at, now));
+    let reverted = mode(
+        BackendMode::Protect,
+        "Mode expired",
+        SYSTEM_ACTOR,
+        Some(now),
+        None,
+    );
+    assert!(needs_suspension(&reverted, suspended_at, now));
+
+    // A suspension is renewed only when it reverts soon
+    let fresh = mode(
+        BackendMode::Passthrough,
+        SUSPENSION_REASON,
+        SYSTEM_ACTOR,
+        Some(suspended_at),
+        Some(now + Duration::days(5)),
+    );
+    assert!(!needs_suspension(&fresh, suspended_at, now));
+    let expiring = mode(
+        BackendMode::Passthrough,
+        SUSPENSION_REASON,
+        SYSTEM_ACTOR,
+        Some(suspended_at),
+        Some(now + Duration::seconds(RENEW_BEFORE_SECONDS - 60)),
+    );
+    assert!(needs_suspension(&expiring, suspended_at, now));
+
+    // Operators may override the suspension, but not predate it
+    let operator = mode(
+        BackendMode::BlockAll,
+        "Under attack",
+        "user-1",
+        Some(now - Duration::hours(1)),
+        Some(now + Duration::hours(1)),
+    );
+    assert!(!needs_suspension(&operator, suspended_at, now));
+    let earlier = mode(
+        BackendMode::Protect,
+        "",
+        "user-1",
+        Some(suspended_at - Duration::days(1)),
+        None,
+    );
+    assert!(needs_suspension(&earlier, suspended_at, now));
+}
+
+/// Test the suspension and reactivation queries against the schema; skipped
+/// without a `DATABASE_URL`
+#[tokio::test]
+async fn test_suspension_queries_against_schema() {
+    let Some(mut tx) = gateway_schema().await else {
+        return;
+    };
+
+    sqlx::raw_sql(
+        r#"
+        INSERT INTO organizations (id, name, slug) VALUES
+            ('suspended', 'Suspended', 'suspended'), ('paid', 'Paid', 'paid'),
+            ('grace', 'Grace', 'grace');
+        INSERT INTO subscriptions (
+            id, organization_id, plan_id, plan_name, current_period_start,
+            current_period_end, trial_outcome, protection_suspended_at
+        ) VALUES
+            ('s1', 'suspended', 'free', 'Free', NOW(), NOW(), 'suspended', '2024-03-01T00:00:00Z'),
+            ('s2', 'paid', 'pro', 'Pro', NOW(), NOW(), 'converted', '2024-03-01T00:00:00Z'),
+            ('s3', 'grace', 'free', 'Free', NOW(), NOW(), 'grace', NULL);
+        INSERT INTO backends (id, organization_id, name) VALUES
+            ('b1', 'suspended', 'one'), ('b2', 'suspended', 'two'), ('b3', 'paid', 'three');
+        "#,
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+
+    let suspended: Vec<(String, DateTime<Utc>)> = sqlx::query_as(SUSPENDED_QUERY)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(
+        suspended,
+        [(
+            "suspended".to_string(),
+            "2024-03-01T00:00:00Z".parse().unwrap()
+        )]
+    );
+
+    let mut backend_ids: Vec<(String,)> = sqlx::query_as(BACKEND_IDS_QUERY)
+        .bind("suspended")
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    backend_ids.sort();
+    assert_eq!(backend_ids, [("b1".to_string(),), ("b2".to_string(),)]);
+
+    let restored: Vec<(String,)> = sqlx::query_as(RESTORED_QUERY)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(restored, [("paid".to_string(),)]);
+
+    // Clearing leaves an organization that is still suspended alone
+    for organization_id in ["paid", "suspended"] {
+        sqlx::query(CLEAR_SUSPENSION_QUERY)
+            .bind(organization_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+    }
+    let restored: Vec<(String,)> = sqlx::query_as(RESTORED_QUERY)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert!(restored.is_empty());
+    let suspended: Vec<(String, DateTime<Utc>)> = sqlx::query_as(SUSPENDED_QUERY)
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(suspended.len(), 1);
+
+    tx.rollback().await.unwrap();
+}
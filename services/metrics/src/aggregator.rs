@@ -3,6 +3,7 @@
 //! This module handles collecting, aggregating, and caching metrics from
 //! multiple worker nodes to provide real-time and historical metrics.
 
+use crate::events::{EventFeed, EventKind};
 use crate::storage::TimeSeriesStorage;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
@@ -105,6 +106,10 @@ pub struct RawTrafficMetrics {
     pub new_connections: u64,
     pub closed_connections: u64,
     pub requests_by_protocol: HashMap<String, u64>,
+    /// The backend had an active load test exemption token, so the sample
+    /// is kept out of the attack detection baseline
+    #[serde(default)]
+    pub exempt: bool,
 }
 
 /// Raw attack metrics from workers
@@ -183,6 +188,9 @@ pub struct MetricsAggregator {
     /// Attack detection state
     attack_state: DashMap<String, AttackDetectionState>,
 
+    /// Feed of incident events
+    events: Arc<EventFeed>,
+
     /// Configuration
     config: AggregatorConfig,
 }
@@ -270,10 +278,17 @@ impl MetricsAggregator {
             traffic_updates,
             attack_updates,
             attack_state: DashMap::new(),
+            events: Arc::new(EventFeed::default()),
             config,
         }
     }
 
+    /// Publish incident events to the given feed
+    pub fn with_events(mut self, events: Arc<EventFeed>) -> Self {
+        self.events = events;
+        self
+    }
+
     /// Subscribe to traffic metrics updates
     pub fn subscribe_traffic(&self) -> broadcast::Receiver<TrafficMetrics> {
         self.traffic_updates.subscribe()
@@ -423,12 +438,14 @@ impl MetricsAggregator {
             warn!("Failed to store traffic metrics time-series: {}", e);
         }
 
-        // Update attack detection baseline
-        self.update_attack_baseline(
-            &raw.backend_id,
-            raw.requests_per_second,
-            raw.packets_per_second,
-        );
+        // Update attack detection baseline, unless a load test is running
+        if !raw.exempt {
+            self.update_attack_baseline(
+                &raw.backend_id,
+                raw.requests_per_second,
+                raw.packets_per_second,
+            );
+        }
 
         debug!(backend_id = %raw.backend_id, worker_id = %raw.worker_id, "Ingested traffic metrics");
         Ok(())
@@ -470,6 +487,7 @@ impl MetricsAggregator {
             requests_rate_limited: raw.requests_rate_limited,
             unique_attack_ips: raw.unique_attack_ips,
             top_sources,
+            ..Default::default()
         };
 
         // Store in cache
@@ -573,6 +591,18 @@ impl MetricsAggregator {
                     "Attack detected"
                 );
 
+                self.events.publish(
+                    backend_id,
+                    EventKind::IncidentStarted {
+                        attack_type: metrics.attack_type.clone(),
+                        severity: state
+                            .severity
+                            .as_str_name()
+                            .trim_start_matches("ATTACK_SEVERITY_")
+                            .to_lowercase(),
+                    },
+                );
+
                 // Record attack event start
                 if let Err(e) = self
                     .storage
@@ -597,6 +627,13 @@ impl MetricsAggregator {
                 "Attack ended"
             );
 
+            self.events.publish(
+                backend_id,
+                EventKind::IncidentEnded {
+                    duration_seconds: duration,
+                },
+            );
+
             // Record attack event end
             if let Err(e) = self.storage.end_attack_event(backend_id, duration).await {
                 warn!("Failed to record attack event end: {}", e);
@@ -3,6 +3,7 @@
 //! This module handles alert creation, evaluation, and notification dispatch
 //! for the metrics service.
 
+use crate::events::{EventFeed, EventKind};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use pistonprotection_proto::{
@@ -80,6 +81,14 @@ struct AlertNotificationPayload {
     message: String,
 }
 
+/// Lowercase name of an alert state, as published in events
+fn state_name(state: AlertState) -> String {
+    state
+        .as_str_name()
+        .trim_start_matches("ALERT_STATE_")
+        .to_lowercase()
+}
+
 /// Alert manager service
 pub struct AlertManager {
     /// Database pool for persistence
@@ -103,6 +112,9 @@ pub struct AlertManager {
     /// Channel for notification dispatch
     notification_tx: mpsc::Sender<AlertNotificationPayload>,
 
+    /// Feed of alert state changes
+    events: Arc<EventFeed>,
+
     /// Configuration
     config: AlertConfig,
 }
@@ -134,6 +146,15 @@ impl Default for AlertConfig {
 impl AlertManager {
     /// Create a new alert manager
     pub fn new(db_pool: Option<PgPool>, config: AlertConfig) -> Arc<Self> {
+        Self::with_events(db_pool, config, Arc::new(EventFeed::default()))
+    }
+
+    /// Create a new alert manager publishing state changes to a feed
+    pub fn with_events(
+        db_pool: Option<PgPool>,
+        config: AlertConfig,
+        events: Arc<EventFeed>,
+    ) -> Arc<Self> {
         let (eval_trigger, _) = broadcast::channel(100);
         let (notification_tx, notification_rx) = mpsc::channel(1000);
 
@@ -150,6 +171,7 @@ impl AlertManager {
             http_client,
             eval_trigger,
             notification_tx,
+            events,
             config,
         });
 
@@ -553,6 +575,7 @@ impl AlertManager {
                 });
 
         state.last_evaluated = now;
+        let previous_state = state.state;
 
         if condition_met {
             // Condition is met
@@ -593,6 +616,19 @@ impl AlertManager {
             state.state = AlertState::Ok;
         }
 
+        if state.state != previous_state {
+            self.events.publish(
+                &alert.backend_id,
+                EventKind::AlertState {
+                    alert_id: alert.id.clone(),
+                    alert_name: alert.name.clone(),
+                    state: state_name(state.state),
+                    previous_state: state_name(previous_state),
+                    value: current_value,
+                },
+            );
+        }
+
         // Update alert state in storage
         self.update_alert_state(&alert.id, state.state, state.last_triggered)
             .await?;
@@ -0,0 +1,261 @@
+//! Authentication of streaming clients
+//!
+//! The WebSocket bridge and the event feed are reached by browsers and CLIs
+//! directly rather than through the gateway, so they authenticate requests
+//! themselves: with a session JWT or an API key, from the usual headers or,
+//! where a browser cannot set headers, from the query string.
+
+use axum::{
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use jsonwebtoken::{DecodingKey, Validation, decode};
+use pistonprotection_common::config::AuthConfig;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{debug, warn};
+
+/// Authenticated user of a request
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub user_id: String,
+    pub role: String,
+    pub organizations: Vec<String>,
+}
+
+impl Identity {
+    pub fn is_admin(&self) -> bool {
+        self.role == "admin"
+    }
+}
+
+/// Session JWT claims used here (must match auth service claims)
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    role: String,
+    #[serde(default)]
+    orgs: Vec<String>,
+    typ: String,
+}
+
+/// Credentials passed in the query string, since browsers cannot set
+/// headers on WebSocket handshakes or `EventSource` requests
+#[derive(Debug, Default, Deserialize)]
+pub struct AuthParams {
+    access_token: Option<String>,
+    api_key: Option<String>,
+}
+
+/// Authentication failures
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthFailure {
+    Unauthorized,
+    Unavailable,
+}
+
+impl IntoResponse for AuthFailure {
+    fn into_response(self) -> Response {
+        match self {
+            AuthFailure::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
+            AuthFailure::Unavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Authentication unavailable",
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Authenticates streaming clients and authorizes the backends they watch
+pub struct ClientAuth {
+    jwt: Option<(DecodingKey, Validation)>,
+    db: Option<PgPool>,
+    skip_auth: bool,
+}
+
+impl ClientAuth {
+    pub fn new(config: Option<&AuthConfig>, db: Option<PgPool>, is_production: bool) -> Self {
+        let (jwt, skip_auth) = match config {
+            Some(cfg) => {
+                let mut validation = Validation::default();
+                validation.set_issuer(&[&cfg.jwt_issuer]);
+                validation.set_audience(&[&cfg.jwt_audience]);
+                validation.validate_nbf = true;
+                let key = DecodingKey::from_secret(cfg.jwt_secret.as_bytes());
+                (Some((key, validation)), cfg.skip_auth && !is_production)
+            }
+            None => {
+                warn!(
+                    "No auth configuration provided, stream authentication will be skipped in development"
+                );
+                (None, !is_production)
+            }
+        };
+
+        Self { jwt, db, skip_auth }
+    }
+
+    /// Authenticate a request by session JWT or API key
+    pub async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+        params: &AuthParams,
+    ) -> Result<Identity, AuthFailure> {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if let Some(token) = bearer.or(params.access_token.as_deref()) {
+            return self.validate_jwt(token);
+        }
+
+        let api_key = headers
+            .get("x-api-key")
+            .and_then(|v| v.to_str().ok())
+            .or(params.api_key.as_deref());
+        if let Some(api_key) = api_key {
+            return self.validate_api_key(api_key).await;
+        }
+
+        if self.skip_auth {
+            debug!("Skipping stream auth (development mode)");
+            return Ok(Identity {
+                user_id: "anonymous".to_string(),
+                role: "admin".to_string(),
+                organizations: Vec::new(),
+            });
+        }
+
+        Err(AuthFailure::Unauthorized)
+    }
+
+    fn validate_jwt(&self, token: &str) -> Result<Identity, AuthFailure> {
+        let (key, validation) = self.jwt.as_ref().ok_or(AuthFailure::Unauthorized)?;
+        let claims = decode::<Claims>(token, key, validation)
+            .map_err(|e| {
+                debug!(error = %e, "Stream JWT validation failed");
+                AuthFailure::Unauthorized
+            })?
+            .claims;
+
+        if claims.typ != "access" {
+            return Err(AuthFailure::Unauthorized);
+        }
+
+        Ok(Identity {
+            user_id: claims.sub,
+            role: claims.role,
+            organizations: claims.orgs,
+        })
+    }
+
+    async fn validate_api_key(&self, api_key: &str) -> Result<Identity, AuthFailure> {
+        let pool = self.db.as_ref().ok_or(AuthFailure::Unavailable)?;
+
+        let row = sqlx::query_as::<_, (String, Option<String>, String)>(
+            r#"
+            SELECT ak.user_id, ak.organization_id, u.role
+            FROM api_keys ak
+            JOIN users u ON ak.user_id = u.id
+            WHERE ak.key_hash = encode(sha256($1::bytea), 'hex')
+            AND ak.revoked_at IS NULL
+            AND (ak.expires_at IS NULL OR ak.expires_at > NOW())
+            "#,
+        )
+        .bind(api_key.as_bytes())
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            warn!(error = %e, "Database error validating API key");
+            AuthFailure::Unavailable
+        })?;
+
+        let (user_id, organization_id, role) = row.ok_or(AuthFailure::Unauthorized)?;
+        Ok(Identity {
+            user_id,
+            role,
+            organizations: organization_id.into_iter().collect(),
+        })
+    }
+
+    /// Whether the user may watch a backend
+    pub async fn can_view(&self, identity: &Identity, backend_id: &str) -> bool {
+        if identity.is_admin() {
+            return true;
+        }
+        let Some(pool) = &self.db else {
+            return false;
+        };
+
+        match sqlx::query_scalar::<_, String>("SELECT organization_id FROM backends WHERE id = $1")
+            .bind(backend_id)
+            .fetch_optional(pool)
+            .await
+        {
+            Ok(Some(organization_id)) => identity.organizations.contains(&organization_id),
+            Ok(None) => false,
+            Err(e) => {
+                warn!(error = %e, "Failed to look up backend organization");
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(role: &str, organizations: &[&str]) -> Identity {
+        Identity {
+            user_id: "user1".to_string(),
+            role: role.to_string(),
+            organizations: organizations.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let config = AuthConfig {
+            jwt_secret: "test-secret".to_string(),
+            jwt_issuer: "test-issuer".to_string(),
+            jwt_audience: "test-audience".to_string(),
+            skip_auth: false,
+            public_paths: vec![],
+        };
+        let auth = ClientAuth::new(Some(&config), None, true);
+        let headers = HeaderMap::new();
+
+        assert_eq!(
+            auth.authenticate(&headers, &AuthParams::default())
+                .await
+                .unwrap_err(),
+            AuthFailure::Unauthorized
+        );
+        let params = AuthParams {
+            access_token: Some("invalid".to_string()),
+            api_key: None,
+        };
+        assert_eq!(
+            auth.authenticate(&headers, &params).await.unwrap_err(),
+            AuthFailure::Unauthorized
+        );
+        // API keys need the database
+        let params = AuthParams {
+            access_token: None,
+            api_key: Some("key".to_string()),
+        };
+        assert_eq!(
+            auth.authenticate(&headers, &params).await.unwrap_err(),
+            AuthFailure::Unavailable
+        );
+    }
+
+    #[tokio::test]
+    async fn test_can_view_without_database() {
+        let auth = ClientAuth::new(None, None, false);
+        assert!(auth.can_view(&identity("admin", &[]), "b1").await);
+        assert!(!auth.can_view(&identity("user", &["org1"]), "b1").await);
+    }
+}
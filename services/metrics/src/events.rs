@@ -0,0 +1,448 @@
+//! Alert and incident event feed
+//!
+//! Alert state changes and incident (attack) lifecycle events are published
+//! to an in-memory journal and served as Server-Sent Events on
+//! `/api/v1/events`, a simpler alternative to gRPC streaming for dashboards
+//! and command line followers. Event IDs increase monotonically, so a client
+//! that reconnects with `Last-Event-ID` gets the events it missed from the
+//! journal before the live feed.
+//!
+//! Security alerts raised by other services (impossible travel, credential
+//! stuffing) are posted to `/api/v1/security/alerts` and published on the
+//! same feed. They belong to no backend, so only admins see them.
+
+use crate::AppState;
+use crate::auth::{AuthParams, Identity};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{
+        IntoResponse, Response,
+        sse::{Event as SseEvent, KeepAlive, Sse},
+    },
+};
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+/// Events kept for resuming clients
+const DEFAULT_JOURNAL_SIZE: usize = 1000;
+
+/// Event published on the feed
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    /// Monotonic event ID
+    pub id: u64,
+    pub time: DateTime<Utc>,
+    pub backend_id: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// What happened
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all_fields = "camelCase")]
+pub enum EventKind {
+    /// An alert moved between ok, pending and firing
+    #[serde(rename = "alert.state")]
+    AlertState {
+        alert_id: String,
+        alert_name: String,
+        state: String,
+        previous_state: String,
+        value: f64,
+    },
+    /// An attack on a backend was detected
+    #[serde(rename = "incident.started")]
+    IncidentStarted {
+        attack_type: String,
+        severity: String,
+    },
+    /// An attack on a backend ended
+    #[serde(rename = "incident.ended")]
+    IncidentEnded { duration_seconds: u32 },
+    /// Another service detected suspicious account activity
+    #[serde(rename = "security.alert")]
+    SecurityAlert {
+        alert_type: String,
+        severity: String,
+        source: String,
+        message: String,
+        ip_address: Option<String>,
+        user_id: Option<String>,
+        organization_id: Option<String>,
+        blocked: bool,
+    },
+}
+
+impl EventKind {
+    /// SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::AlertState { .. } => "alert.state",
+            EventKind::IncidentStarted { .. } => "incident.started",
+            EventKind::IncidentEnded { .. } => "incident.ended",
+            EventKind::SecurityAlert { .. } => "security.alert",
+        }
+    }
+}
+
+/// Journal of recent events, and their live feed
+pub struct EventFeed {
+    capacity: usize,
+    journal: Mutex<Journal>,
+    live: broadcast::Sender<Event>,
+    /// Bearer token required to post security alerts
+    report_token: Option<String>,
+}
+
+struct Journal {
+    events: VecDeque<Event>,
+    last_id: u64,
+}
+
+impl Default for EventFeed {
+    fn default() -> Self {
+        Self::new(DEFAULT_JOURNAL_SIZE)
+    }
+}
+
+impl EventFeed {
+    /// Create a feed keeping the given number of events
+    pub fn new(capacity: usize) -> Self {
+        let (live, _) = broadcast::channel(1000);
+        Self {
+            capacity: capacity.max(1),
+            journal: Mutex::new(Journal {
+                events: VecDeque::new(),
+                last_id: 0,
+            }),
+            live,
+            report_token: None,
+        }
+    }
+
+    /// Require a bearer token to post security alerts
+    pub fn with_report_token(mut self, token: Option<String>) -> Self {
+        self.report_token = token.filter(|t| !t.is_empty());
+        self
+    }
+
+    /// Publish an event
+    ///
+    /// IDs follow the wall clock in milliseconds, so they keep increasing
+    /// across restarts and a `Last-Event-ID` from before one stays valid.
+    pub fn publish(&self, backend_id: &str, kind: EventKind) -> u64 {
+        let mut journal = self.journal.lock();
+        let now = Utc::now();
+        let id = (now.timestamp_millis().max(0) as u64).max(journal.last_id + 1);
+        journal.last_id = id;
+
+        let event = Event {
+            id,
+            time: now,
+            backend_id: backend_id.to_string(),
+            kind,
+        };
+        debug!(id, event = event.kind.name(), backend_id = %backend_id, "Publishing event");
+
+        journal.events.push_back(event.clone());
+        while journal.events.len() > self.capacity {
+            journal.events.pop_front();
+        }
+
+        // Sent under the lock so subscribers see events in ID order
+        let _ = self.live.send(event);
+        id
+    }
+
+    /// Subscribe to live events, with the journaled events after an ID
+    ///
+    /// Both are taken under the journal lock, so no event is missed or
+    /// delivered twice between them.
+    pub fn subscribe(&self, after: Option<u64>) -> (broadcast::Receiver<Event>, Vec<Event>) {
+        let journal = self.journal.lock();
+        let rx = self.live.subscribe();
+        let backlog = match after {
+            Some(after) => journal
+                .events
+                .iter()
+                .filter(|event| event.id > after)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        (rx, backlog)
+    }
+
+    /// Journaled events after an ID
+    fn since(&self, after: u64) -> Vec<Event> {
+        self.journal
+            .lock()
+            .events
+            .iter()
+            .filter(|event| event.id > after)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Query parameters of the event stream
+#[derive(Debug, Default, Deserialize)]
+pub struct EventsQuery {
+    /// Only events of this backend
+    backend_id: Option<String>,
+    /// Resume point for clients that cannot set `Last-Event-ID`
+    last_event_id: Option<String>,
+    #[serde(flatten)]
+    auth: AuthParams,
+}
+
+/// Stream alert and incident events as Server-Sent Events
+pub async fn events_sse(
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let identity = match state.auth.authenticate(&headers, &query.auth).await {
+        Ok(identity) => identity,
+        Err(failure) => {
+            warn!(failure = ?failure, "Rejected event stream");
+            return failure.into_response();
+        }
+    };
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .or(query.last_event_id.as_deref())
+        .and_then(|v| v.trim().parse().ok());
+
+    let stream = event_stream(state, identity, query.backend_id, last_event_id);
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn event_stream(
+    state: AppState,
+    identity: Identity,
+    backend_id: Option<String>,
+    last_event_id: Option<u64>,
+) -> impl Stream<Item = Result<SseEvent, Infallible>> {
+    let events = state.events.clone();
+    let (mut rx, backlog) = events.subscribe(last_event_id);
+
+    async_stream::stream! {
+        let mut last = last_event_id.unwrap_or(0);
+        // Authorization by backend, looked up once per connection
+        let mut visible: HashMap<String, bool> = HashMap::new();
+
+        let mut pending = backlog;
+        loop {
+            for event in pending.drain(..) {
+                if event.id <= last {
+                    continue;
+                }
+                last = event.id;
+                if backend_id.as_ref().is_some_and(|id| *id != event.backend_id) {
+                    continue;
+                }
+                let allowed = match visible.get(&event.backend_id) {
+                    Some(&allowed) => allowed,
+                    None => {
+                        let allowed = state.auth.can_view(&identity, &event.backend_id).await;
+                        visible.insert(event.backend_id.clone(), allowed);
+                        allowed
+                    }
+                };
+                if allowed {
+                    yield Ok(to_sse(&event));
+                }
+            }
+
+            match rx.recv().await {
+                Ok(event) => pending.push(event),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    // Catch up from the journal rather than skip events
+                    warn!(lagged = n, "Event stream lagged, replaying");
+                    pending = events.since(last);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+/// Security alert reported by another service
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityAlertReport {
+    pub alert_type: String,
+    pub severity: String,
+    pub source: String,
+    pub message: String,
+    #[serde(default)]
+    pub ip_address: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub organization_id: Option<String>,
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+impl From<SecurityAlertReport> for EventKind {
+    fn from(report: SecurityAlertReport) -> Self {
+        EventKind::SecurityAlert {
+            alert_type: report.alert_type,
+            severity: report.severity,
+            source: report.source,
+            message: report.message,
+            ip_address: report.ip_address,
+            user_id: report.user_id,
+            organization_id: report.organization_id,
+            blocked: report.blocked,
+        }
+    }
+}
+
+/// Publish a security alert posted by another service
+pub async fn ingest_security_alert(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(report): Json<SecurityAlertReport>,
+) -> StatusCode {
+    if let Some(token) = &state.events.report_token {
+        let authorized = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|v| v == token);
+        if !authorized {
+            return StatusCode::UNAUTHORIZED;
+        }
+    }
+
+    warn!(
+        alert_type = %report.alert_type,
+        source = %report.source,
+        "Security alert: {}",
+        report.message
+    );
+    state.events.publish("", report.into());
+    StatusCode::ACCEPTED
+}
+
+fn to_sse(event: &Event) -> SseEvent {
+    let sse = SseEvent::default()
+        .id(event.id.to_string())
+        .event(event.kind.name());
+    match sse.json_data(event) {
+        Ok(sse) => sse,
+        Err(e) => {
+            warn!(error = %e, "Failed to encode event");
+            SseEvent::default()
+                .id(event.id.to_string())
+                .comment("encoding error")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started() -> EventKind {
+        EventKind::IncidentStarted {
+            attack_type: "syn_flood".to_string(),
+            severity: "high".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_publish_and_resume() {
+        let feed = EventFeed::new(2);
+        let first = feed.publish("b1", started());
+        let second = feed.publish(
+            "b1",
+            EventKind::IncidentEnded {
+                duration_seconds: 30,
+            },
+        );
+        assert!(second > first);
+
+        let (_rx, backlog) = feed.subscribe(None);
+        assert!(backlog.is_empty());
+        let (_rx, backlog) = feed.subscribe(Some(first));
+        assert_eq!(backlog.len(), 1);
+        assert_eq!(backlog[0].id, second);
+
+        // The journal keeps only the newest events
+        let third = feed.publish("b2", started());
+        let ids: Vec<u64> = feed.since(0).iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![second, third]);
+    }
+
+    #[tokio::test]
+    async fn test_live_after_subscribe() {
+        let feed = EventFeed::default();
+        let (mut rx, _) = feed.subscribe(None);
+        let id = feed.publish("b1", started());
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.id, id);
+        assert_eq!(event.kind, started());
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = Event {
+            id: 7,
+            time: Utc::now(),
+            backend_id: "b1".to_string(),
+            kind: EventKind::AlertState {
+                alert_id: "a1".to_string(),
+                alert_name: "High RPS".to_string(),
+                state: "firing".to_string(),
+                previous_state: "pending".to_string(),
+                value: 12.5,
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "alert.state");
+        assert_eq!(json["backendId"], "b1");
+        assert_eq!(json["alertName"], "High RPS");
+        assert_eq!(json["previousState"], "pending");
+        assert_eq!(event.kind.name(), "alert.state");
+    }
+
+    #[test]
+    fn test_security_alert_report() {
+        let report: SecurityAlertReport = serde_json::from_value(serde_json::json!({
+            "alertType": "credential_stuffing",
+            "severity": "high",
+            "source": "auth",
+            "message": "Failed logins for 25 accounts",
+            "ipAddress": "203.0.113.7",
+            "blocked": true,
+        }))
+        .unwrap();
+
+        let kind = EventKind::from(report);
+        assert_eq!(kind.name(), "security.alert");
+        let json = serde_json::to_value(&kind).unwrap();
+        assert_eq!(json["type"], "security.alert");
+        assert_eq!(json["alertType"], "credential_stuffing");
+        assert_eq!(json["ipAddress"], "203.0.113.7");
+        assert!(json["userId"].is_null());
+    }
+}
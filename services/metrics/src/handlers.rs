@@ -1,8 +1,10 @@
 //! gRPC handlers for the metrics service
 
 use crate::{
-    aggregator::MetricsAggregator, alerts::AlertManager, storage::TimeSeriesStorage,
-    streams::MetricsStreamer,
+    aggregator::MetricsAggregator,
+    alerts::AlertManager,
+    storage::TimeSeriesStorage,
+    streams::{MetricsStreamer, ResumeCursor},
 };
 use pistonprotection_proto::metrics::{metrics_service_server::MetricsService, *};
 use std::pin::Pin;
@@ -94,17 +96,21 @@ impl MetricsService for MetricsGrpcService {
         } else {
             req.interval_seconds
         };
+        let cursor = ResumeCursor {
+            after: req.resume_after,
+            token: req.resume_token,
+        };
 
         let stream = self
             .streamer
-            .stream_traffic_metrics(req.backend_id, interval)
+            .stream_traffic_metrics(req.backend_id, interval, cursor)
             .await
             .map_err(|e| {
                 error!("Failed to create traffic metrics stream: {}", e);
-                Status::internal(format!("Failed to create stream: {}", e))
+                Status::from(e)
             })?;
 
-        Ok(Response::new(Box::pin(stream)))
+        Ok(Response::new(stream))
     }
 
     // =========================================================================
@@ -168,17 +174,21 @@ impl MetricsService for MetricsGrpcService {
         } else {
             req.interval_seconds
         };
+        let cursor = ResumeCursor {
+            after: req.resume_after,
+            token: req.resume_token,
+        };
 
         let stream = self
             .streamer
-            .stream_attack_metrics(req.backend_id, interval)
+            .stream_attack_metrics(req.backend_id, interval, cursor)
             .await
             .map_err(|e| {
                 error!("Failed to create attack metrics stream: {}", e);
-                Status::internal(format!("Failed to create stream: {}", e))
+                Status::from(e)
             })?;
 
-        Ok(Response::new(Box::pin(stream)))
+        Ok(Response::new(stream))
     }
 
     // =========================================================================
@@ -5,24 +5,35 @@
 
 mod aggregator;
 mod alerts;
+mod auth;
 pub mod clickhouse;
+mod events;
 mod handlers;
+mod prober;
 mod storage;
 mod streams;
+mod websocket;
 
 use aggregator::{AggregatorConfig, MetricsAggregator};
 use alerts::{AlertConfig, AlertManager};
+use auth::ClientAuth;
 use clickhouse::{ClickHouseAnalytics, ClickHouseConfig};
+use events::EventFeed;
 use handlers::MetricsGrpcService;
 use pistonprotection_common::{
-    config::Config, geoip::GeoIpService, redis::CacheService, telemetry,
+    config::{Config, secrets::Secrets},
+    geoip::GeoIpService,
+    redis::CacheService,
+    telemetry,
 };
+use pistonprotection_proto::metrics::TimeGranularity;
 use pistonprotection_proto::metrics::metrics_service_server::MetricsServiceServer;
+use prober::{ProbeResult, Prober, ProberConfig};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use storage::{RetentionConfig, TimeSeriesStorage};
-use streams::MetricsStreamer;
+use streams::{MetricsStreamer, StreamerConfig};
 use tokio::signal;
 use tonic::transport::Server;
 use tonic_health::server::health_reporter;
@@ -31,8 +42,15 @@ use tower_http::{
     trace::TraceLayer,
 };
 use tracing::{error, info, warn};
-
-use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
+use websocket::{WsBridge, WsConfig};
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+    routing::{get, post},
+};
 use serde::{Deserialize, Serialize};
 
 const SERVICE_NAME: &str = "metrics";
@@ -44,17 +62,25 @@ pub struct AppState {
     pub storage: Arc<TimeSeriesStorage>,
     pub alerts: Arc<AlertManager>,
     pub streamer: Arc<MetricsStreamer>,
+    pub auth: Arc<ClientAuth>,
+    pub ws: Arc<WsBridge>,
+    pub events: Arc<EventFeed>,
     pub clickhouse: Option<Arc<ClickHouseAnalytics>>,
+    pub prober: Arc<Prober>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load configuration
-    let config = Config::load(SERVICE_NAME)?;
+    let mut config = Config::load(SERVICE_NAME)?;
 
     // Initialize telemetry
     telemetry::init(SERVICE_NAME, &config.telemetry)?;
 
+    // Resolve credentials kept in Kubernetes Secrets or Vault
+    let secrets = Arc::new(Secrets::from_env()?);
+    let db_credentials = config.resolve_secrets(&secrets).await?;
+
     info!(
         "Starting {} service v{}",
         SERVICE_NAME,
@@ -62,11 +88,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     // Initialize database connection
-    let db_pool = if let Some(ref db_config) = config.database {
-        match pistonprotection_common::db::create_pool(db_config).await {
-            Ok(pool) => {
+    let db_pools = if let Some(ref db_config) = config.database {
+        match pistonprotection_common::db::create_pools(db_config).await {
+            Ok(pools) => {
                 info!("Database connection established");
-                Some(pool)
+                if let Some(credentials) = db_credentials {
+                    pistonprotection_common::db::spawn_credential_renewal(
+                        pools.pools(),
+                        Arc::clone(&secrets),
+                        credentials,
+                    );
+                }
+                Some(pools)
             }
             Err(e) => {
                 warn!(
@@ -148,7 +181,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     let storage = Arc::new(TimeSeriesStorage::new(
-        db_pool.clone(),
+        db_pools.clone(),
         redis_pool.clone(),
         "piston:metrics",
         retention_config,
@@ -163,12 +196,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         baseline_window_size: 60,
     };
 
-    let aggregator = Arc::new(MetricsAggregator::new(
-        storage.clone(),
-        cache,
-        geoip,
-        aggregator_config,
-    ));
+    // Alert, incident and security events, served to dashboards over SSE
+    let events = Arc::new(
+        EventFeed::default().with_report_token(std::env::var("SECURITY_ALERT_TOKEN").ok()),
+    );
+
+    let aggregator = Arc::new(
+        MetricsAggregator::new(storage.clone(), cache, geoip, aggregator_config)
+            .with_events(events.clone()),
+    );
 
     // Create alert manager
     let alert_config = AlertConfig {
@@ -178,7 +214,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         notification_timeout: Duration::from_secs(10),
     };
 
-    let alerts = AlertManager::new(db_pool.clone(), alert_config);
+    let db_pool = db_pools.as_ref().map(|pools| pools.primary().clone());
+    let alerts = AlertManager::with_events(db_pool, alert_config, events.clone());
 
     // Load alerts from database
     if let Err(e) = alerts.load_alerts().await {
@@ -186,7 +223,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Create metrics streamer
-    let streamer = Arc::new(MetricsStreamer::new(aggregator.clone()));
+    let streamer = Arc::new(MetricsStreamer::with_config(
+        aggregator.clone(),
+        StreamerConfig::from_env(),
+    ));
+    // Sequence aggregator updates so reconnecting streams can resume
+    let recorder_handle = streams::start_recording_task(streamer.clone());
+
+    // Browsers and CLIs reach the streaming endpoints directly
+    let auth = Arc::new(ClientAuth::new(
+        config.auth.as_ref(),
+        db_pools.as_ref().map(|pools| pools.primary().clone()),
+        config.is_production(),
+    ));
+
+    // Bridge the streams to browsers over WebSocket
+    let ws = Arc::new(WsBridge::new(
+        streamer.clone(),
+        auth.clone(),
+        WsConfig::from_env(),
+    ));
 
     // Initialize ClickHouse for high-volume event analytics
     let clickhouse = if let Ok(clickhouse_url) = std::env::var("CLICKHOUSE_URL") {
@@ -237,13 +293,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
+    // Probe protected frontends, or collect results of regional probers
+    let prober = Arc::new(Prober::new(
+        ProberConfig::from_env(),
+        storage.clone(),
+        alerts.clone(),
+    ));
+    let probe_handle = prober::start_probe_task(prober.clone());
+
+    // Move read replicas in and out of rotation by replication lag
+    let replica_handle = db_pools
+        .as_ref()
+        .and_then(|pools| pools.spawn_replica_monitor());
+
     // Create application state
     let app_state = AppState {
         aggregator: aggregator.clone(),
         storage: storage.clone(),
         alerts: alerts.clone(),
         streamer: streamer.clone(),
+        auth,
+        ws,
+        events,
         clickhouse: clickhouse.clone(),
+        prober,
     };
 
     // Start background tasks
@@ -337,6 +410,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // abort these handles and wait for them to complete
     http_handle.abort();
     grpc_handle.abort();
+    recorder_handle.abort();
+    for handle in [probe_handle, replica_handle].into_iter().flatten() {
+        handle.abort();
+    }
 
     // Final flush
     if let Err(e) = aggregator.flush_to_storage().await {
@@ -394,6 +471,17 @@ fn create_http_router(state: AppState) -> Router {
             "/api/v1/analytics/filters/:backend_id",
             get(get_filter_analytics),
         )
+        // Synthetic probe endpoints
+        .route("/api/v1/probes", get(get_probe_status))
+        .route("/api/v1/probes/results", post(ingest_probe_results))
+        .route("/api/v1/probes/:backend_id/series", get(get_probe_series))
+        // Live metrics for browsers
+        .route("/api/v1/ws/metrics", get(websocket::metrics_ws))
+        .route("/api/v1/events", get(events::events_sse))
+        .route(
+            "/api/v1/security/alerts",
+            post(events::ingest_security_alert),
+        )
         .layer(TraceLayer::new_for_http())
         .layer(cors)
         .with_state(state)
@@ -475,6 +563,8 @@ struct AnalyticsQuery {
     limit: Option<u32>,
     /// Interval in seconds for time series (optional, defaults to 300)
     interval: Option<u32>,
+    /// Probe region (optional, probe series only; defaults to all regions)
+    region: Option<String>,
 }
 
 impl AnalyticsQuery {
@@ -706,3 +796,82 @@ async fn shutdown_signal() {
         _ = terminate => {},
     }
 }
+
+async fn get_probe_status(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.prober.status(Utc::now()))
+}
+
+/// Store the results pushed by a regional prober
+async fn ingest_probe_results(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(results): Json<Vec<ProbeResult>>,
+) -> impl IntoResponse {
+    if let Some(token) = state.prober.report_token() {
+        let authorized = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|v| v == token);
+        if !authorized {
+            return StatusCode::UNAUTHORIZED;
+        }
+    }
+
+    for result in results {
+        state.prober.ingest(result).await;
+    }
+    state.prober.evaluate(Utc::now()).await;
+    StatusCode::NO_CONTENT
+}
+
+async fn get_probe_series(
+    State(state): State<AppState>,
+    Path(backend_id): Path<String>,
+    axum::extract::Query(query): axum::extract::Query<AnalyticsQuery>,
+) -> impl IntoResponse {
+    let (start, end) = query.parse_times();
+    let granularity = match query.interval.unwrap_or(300) {
+        0..=60 => TimeGranularity::Minute,
+        61..=300 => TimeGranularity::FiveMinutes,
+        301..=900 => TimeGranularity::FifteenMinutes,
+        901..=3600 => TimeGranularity::Hour,
+        _ => TimeGranularity::Day,
+    };
+
+    match state
+        .storage
+        .query_probe_time_series(
+            &backend_id,
+            query.region.as_deref(),
+            start,
+            end,
+            granularity,
+        )
+        .await
+    {
+        Ok(series) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "backend_id": backend_id,
+                "region": query.region,
+                "series": series
+                    .iter()
+                    .map(|s| serde_json::json!({
+                        "metric": s.metric_name,
+                        "points": s.points.iter().map(|p| serde_json::json!({
+                            "timestamp": p.timestamp.as_ref().map(|t| t.seconds),
+                            "value": p.value,
+                        })).collect::<Vec<_>>(),
+                    }))
+                    .collect::<Vec<_>>(),
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": e.to_string()
+            })),
+        ),
+    }
+}
@@ -0,0 +1,665 @@
+//! Synthetic monitoring probes
+//!
+//! Checks the protected frontend address of backends from the outside, the
+//! way players and visitors reach them: a TCP connect, an HTTP request or a
+//! Minecraft Server List Ping. One prober runs per region. Regional probers
+//! started with `PROBE_REPORT_URL` push their results to the central metrics
+//! service, which stores the latency and availability series of every
+//! region, feeds them to the alert manager and flags backends whose
+//! protection endpoints are degraded across regions.
+
+use crate::alerts::AlertManager;
+use crate::storage::TimeSeriesStorage;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::metrics::{PROBE_DEGRADED, PROBE_LATENCY, PROBE_UP};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+/// Check performed against a frontend address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckKind {
+    /// TCP connect
+    Tcp,
+    /// HTTP GET, up unless the answer is a 5xx
+    Http,
+    /// Minecraft Java Server List Ping
+    Minecraft,
+}
+
+impl CheckKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckKind::Tcp => "tcp",
+            CheckKind::Http => "http",
+            CheckKind::Minecraft => "minecraft",
+        }
+    }
+}
+
+/// Frontend address of a backend to probe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeTarget {
+    pub backend_id: String,
+    pub kind: CheckKind,
+    /// `host:port` for TCP and Minecraft checks, the URL for HTTP checks
+    pub address: String,
+}
+
+impl ProbeTarget {
+    /// Parse `backend_id=scheme://address`, where the scheme is `tcp`,
+    /// `http`, `https` or `minecraft`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (backend_id, url) = value.trim().split_once('=')?;
+        let (scheme, rest) = url.split_once("://")?;
+        let (kind, address) = match scheme {
+            "tcp" => (CheckKind::Tcp, rest.to_string()),
+            "minecraft" => {
+                // The Minecraft default port is implied
+                let address = if rest.contains(':') {
+                    rest.to_string()
+                } else {
+                    format!("{}:25565", rest)
+                };
+                (CheckKind::Minecraft, address)
+            }
+            "http" | "https" => (CheckKind::Http, url.to_string()),
+            _ => return None,
+        };
+        if backend_id.is_empty() || rest.is_empty() {
+            return None;
+        }
+        Some(Self {
+            backend_id: backend_id.to_string(),
+            kind,
+            address,
+        })
+    }
+}
+
+/// Prober configuration
+#[derive(Debug, Clone)]
+pub struct ProberConfig {
+    /// Region this prober runs in
+    pub region: String,
+    /// Time between probe rounds
+    pub interval: Duration,
+    /// Time allowed for a single check
+    pub timeout: Duration,
+    /// Frontends to probe
+    pub targets: Vec<ProbeTarget>,
+    /// Central metrics service to push results to, instead of storing them
+    pub report_url: Option<String>,
+    /// Bearer token of result pushes
+    pub report_token: Option<String>,
+    /// Share of regions that must reach a backend for it to be healthy
+    pub min_availability: f64,
+    /// Mean latency across regions above which a backend is degraded
+    pub max_latency_ms: f64,
+}
+
+impl Default for ProberConfig {
+    fn default() -> Self {
+        Self {
+            region: "default".to_string(),
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(5),
+            targets: Vec::new(),
+            report_url: None,
+            report_token: None,
+            min_availability: 0.75,
+            max_latency_ms: 1000.0,
+        }
+    }
+}
+
+impl ProberConfig {
+    /// Load the prober configuration from `PROBE_*` environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(region) = std::env::var("PROBE_REGION") {
+            config.region = region;
+        }
+        if let Some(secs) = env_parse::<u64>("PROBE_INTERVAL_SECS") {
+            config.interval = Duration::from_secs(secs.max(1));
+        }
+        if let Some(ms) = env_parse::<u64>("PROBE_TIMEOUT_MS") {
+            config.timeout = Duration::from_millis(ms.max(1));
+        }
+        if let Ok(targets) = std::env::var("PROBE_TARGETS") {
+            config.targets = targets
+                .split(',')
+                .filter(|t| !t.trim().is_empty())
+                .filter_map(|t| {
+                    let target = ProbeTarget::parse(t);
+                    if target.is_none() {
+                        warn!(target = %t, "Ignoring invalid probe target");
+                    }
+                    target
+                })
+                .collect();
+        }
+        config.report_url = std::env::var("PROBE_REPORT_URL").ok();
+        config.report_token = std::env::var("PROBE_REPORT_TOKEN").ok();
+        if let Some(share) = env_parse::<f64>("PROBE_MIN_AVAILABILITY") {
+            config.min_availability = share.clamp(0.0, 1.0);
+        }
+        if let Some(ms) = env_parse("PROBE_MAX_LATENCY_MS") {
+            config.max_latency_ms = ms;
+        }
+
+        config
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Outcome of one check from one region
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeResult {
+    pub backend_id: String,
+    pub region: String,
+    pub kind: CheckKind,
+    pub success: bool,
+    /// Time to a successful answer
+    pub latency_ms: Option<f64>,
+    pub error: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Probe state of a backend across regions
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendProbeStatus {
+    pub backend_id: String,
+    /// Share of regions reaching the backend
+    pub availability: f64,
+    /// Mean latency of the regions reaching the backend
+    pub latency_ms: Option<f64>,
+    pub degraded: bool,
+    /// Latest result of each region
+    pub regions: Vec<ProbeResult>,
+}
+
+/// Runs checks and tracks the latest result of each backend and region
+pub struct Prober {
+    config: ProberConfig,
+    storage: Arc<TimeSeriesStorage>,
+    alerts: Arc<AlertManager>,
+    http: reqwest::Client,
+    /// Latest result by backend ID and region
+    results: RwLock<HashMap<(String, String), ProbeResult>>,
+    /// Backends currently degraded
+    degraded: RwLock<HashSet<String>>,
+}
+
+impl Prober {
+    /// Create a prober
+    pub fn new(
+        config: ProberConfig,
+        storage: Arc<TimeSeriesStorage>,
+        alerts: Arc<AlertManager>,
+    ) -> Self {
+        // Challenges and redirects of the protection layer count as answers
+        let http = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            config,
+            storage,
+            alerts,
+            http,
+            results: RwLock::new(HashMap::new()),
+            degraded: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Whether this prober pushes its results to a central service
+    pub fn is_regional(&self) -> bool {
+        self.config.report_url.is_some()
+    }
+
+    /// Bearer token result pushes must carry, if any
+    pub fn report_token(&self) -> Option<&str> {
+        self.config.report_token.as_deref()
+    }
+
+    /// Run every check once, concurrently
+    pub async fn run_checks(&self) -> Vec<ProbeResult> {
+        let checks = self.config.targets.iter().map(|target| self.check(target));
+        futures::future::join_all(checks).await
+    }
+
+    /// Run a check against one target
+    pub async fn check(&self, target: &ProbeTarget) -> ProbeResult {
+        let started = Instant::now();
+        let outcome = match target.kind {
+            CheckKind::Http => self.http_check(&target.address).await,
+            kind => tokio::time::timeout(self.config.timeout, async {
+                match kind {
+                    CheckKind::Minecraft => minecraft_check(&target.address).await,
+                    _ => TcpStream::connect(&target.address).await.map(drop),
+                }
+            })
+            .await
+            .unwrap_or_else(|_| Err(Error::new(ErrorKind::TimedOut, "check timed out"))),
+        };
+
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        ProbeResult {
+            backend_id: target.backend_id.clone(),
+            region: self.config.region.clone(),
+            kind: target.kind,
+            success: outcome.is_ok(),
+            latency_ms: outcome.is_ok().then_some(latency_ms),
+            error: outcome.err().map(|e| e.to_string()),
+            timestamp: Utc::now(),
+        }
+    }
+
+    async fn http_check(&self, url: &str) -> std::io::Result<()> {
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| Error::other(e.to_string()))?;
+        if response.status().is_server_error() {
+            return Err(Error::other(format!("status {}", response.status())));
+        }
+        Ok(())
+    }
+
+    /// Push results to the central metrics service
+    pub async fn report(&self, results: &[ProbeResult]) -> Result<(), reqwest::Error> {
+        let Some(ref url) = self.config.report_url else {
+            return Ok(());
+        };
+        let url = format!("{}/api/v1/probes/results", url.trim_end_matches('/'));
+        let mut request = self.http.post(url).json(results);
+        if let Some(ref token) = self.config.report_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Store a result of any region and keep it as the latest of its region
+    pub async fn ingest(&self, result: ProbeResult) {
+        PROBE_UP
+            .with_label_values(&[&result.backend_id, &result.region])
+            .set(if result.success { 1.0 } else { 0.0 });
+        if let Some(latency_ms) = result.latency_ms {
+            PROBE_LATENCY
+                .with_label_values(&[&result.backend_id, &result.region])
+                .set(latency_ms);
+        }
+
+        if let Err(e) = self.storage.store_probe_result(&result).await {
+            warn!(backend_id = %result.backend_id, "Failed to store probe result: {}", e);
+        }
+
+        let key = (result.backend_id.clone(), result.region.clone());
+        let mut results = self.results.write();
+        // Pushes can arrive out of order
+        if results
+            .get(&key)
+            .is_none_or(|latest| latest.timestamp <= result.timestamp)
+        {
+            results.insert(key, result);
+        }
+    }
+
+    /// Compute the state of every backend from the latest results that are
+    /// recent enough, update the degraded set and evaluate probe alerts
+    pub async fn evaluate(&self, now: DateTime<Utc>) -> Vec<BackendProbeStatus> {
+        let statuses = self.status(now);
+
+        for status in &statuses {
+            let was_degraded = if status.degraded {
+                !self.degraded.write().insert(status.backend_id.clone())
+            } else {
+                self.degraded.write().remove(&status.backend_id)
+            };
+            if status.degraded && !was_degraded {
+                warn!(
+                    backend_id = %status.backend_id,
+                    availability = status.availability,
+                    latency_ms = ?status.latency_ms,
+                    "Protection endpoint degraded"
+                );
+            } else if !status.degraded && was_degraded {
+                info!(backend_id = %status.backend_id, "Protection endpoint recovered");
+            }
+            PROBE_DEGRADED
+                .with_label_values(&[&status.backend_id])
+                .set(if status.degraded { 1.0 } else { 0.0 });
+
+            let mut metrics = HashMap::from([
+                ("probe_availability".to_string(), status.availability),
+                (
+                    "probe_regions_down".to_string(),
+                    status.regions.iter().filter(|r| !r.success).count() as f64,
+                ),
+            ]);
+            if let Some(latency_ms) = status.latency_ms {
+                metrics.insert("probe_latency_ms".to_string(), latency_ms);
+            }
+            if let Err(e) = self
+                .alerts
+                .evaluate_alerts(&status.backend_id, &metrics)
+                .await
+            {
+                warn!(backend_id = %status.backend_id, "Failed to evaluate probe alerts: {}", e);
+            }
+        }
+
+        statuses
+    }
+
+    /// State of every backend from results newer than three probe intervals
+    pub fn status(&self, now: DateTime<Utc>) -> Vec<BackendProbeStatus> {
+        let max_age = ChronoDuration::from_std(self.config.interval * 3)
+            .unwrap_or_else(|_| ChronoDuration::minutes(5));
+
+        let mut by_backend: HashMap<String, Vec<ProbeResult>> = HashMap::new();
+        for result in self.results.read().values() {
+            if now.signed_duration_since(result.timestamp) <= max_age {
+                by_backend
+                    .entry(result.backend_id.clone())
+                    .or_default()
+                    .push(result.clone());
+            }
+        }
+
+        let mut statuses: Vec<BackendProbeStatus> = by_backend
+            .into_iter()
+            .map(|(backend_id, mut regions)| {
+                regions.sort_by(|a, b| a.region.cmp(&b.region));
+                let up: Vec<f64> = regions
+                    .iter()
+                    .filter(|r| r.success)
+                    .filter_map(|r| r.latency_ms)
+                    .collect();
+                let availability = up.len() as f64 / regions.len() as f64;
+                let latency_ms = (!up.is_empty()).then(|| up.iter().sum::<f64>() / up.len() as f64);
+                let degraded = availability < self.config.min_availability
+                    || latency_ms.is_some_and(|ms| ms > self.config.max_latency_ms);
+
+                BackendProbeStatus {
+                    backend_id,
+                    availability,
+                    latency_ms,
+                    degraded,
+                    regions,
+                }
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.backend_id.cmp(&b.backend_id));
+        statuses
+    }
+}
+
+/// Run probe rounds until the process exits: regional probers push their
+/// results, the central prober stores and evaluates them
+pub fn start_probe_task(prober: Arc<Prober>) -> Option<tokio::task::JoinHandle<()>> {
+    if prober.config.targets.is_empty() {
+        info!("No probe targets configured, synthetic probes disabled");
+        return None;
+    }
+    info!(
+        region = %prober.config.region,
+        targets = prober.config.targets.len(),
+        "Starting synthetic probes"
+    );
+
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(prober.config.interval);
+        loop {
+            interval.tick().await;
+            let results = prober.run_checks().await;
+            debug!(results = results.len(), "Probe round finished");
+
+            if prober.is_regional() {
+                if let Err(e) = prober.report(&results).await {
+                    warn!("Failed to push probe results: {}", e);
+                }
+                continue;
+            }
+            for result in results {
+                prober.ingest(result).await;
+            }
+            prober.evaluate(Utc::now()).await;
+        }
+    }))
+}
+
+/// Server List Ping: handshake with next state 1, then a status request,
+/// answered by a status response packet (ID 0)
+async fn minecraft_check(address: &str) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(address).await?;
+    let (host, port) = address
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "address without port"))?;
+
+    // Protocol version -1 asks for the status of any version
+    let mut handshake = vec![0x00];
+    write_varint(&mut handshake, -1);
+    write_varint(&mut handshake, host.len() as i32);
+    handshake.extend_from_slice(host.as_bytes());
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, 1);
+
+    let mut request = Vec::new();
+    write_varint(&mut request, handshake.len() as i32);
+    request.extend(handshake);
+    request.extend_from_slice(&[0x01, 0x00]);
+    stream.write_all(&request).await?;
+
+    let len = read_varint(&mut stream).await?;
+    let id = read_varint(&mut stream).await?;
+    if len < 2 || id != 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "unexpected status response",
+        ));
+    }
+    Ok(())
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7f == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+async fn read_varint(stream: &mut TcpStream) -> std::io::Result<i32> {
+    let mut value: i32 = 0;
+    for i in 0..5 {
+        let byte = stream.read_u8().await?;
+        value |= ((byte & 0x7f) as i32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::new(ErrorKind::InvalidData, "VarInt too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertConfig;
+    use crate::storage::RetentionConfig;
+    use tokio::net::TcpListener;
+
+    fn prober(targets: Vec<ProbeTarget>) -> Prober {
+        let storage = Arc::new(TimeSeriesStorage::new(
+            None,
+            None,
+            "test",
+            RetentionConfig::default(),
+        ));
+        let config = ProberConfig {
+            region: "eu-west".to_string(),
+            timeout: Duration::from_secs(1),
+            targets,
+            ..Default::default()
+        };
+        Prober::new(
+            config,
+            storage,
+            AlertManager::new(None, AlertConfig::default()),
+        )
+    }
+
+    fn result(region: &str, success: bool, latency_ms: f64) -> ProbeResult {
+        ProbeResult {
+            backend_id: "backend-1".to_string(),
+            region: region.to_string(),
+            kind: CheckKind::Tcp,
+            success,
+            latency_ms: success.then_some(latency_ms),
+            error: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        let target = ProbeTarget::parse("backend-1=minecraft://mc.example.com").unwrap();
+        assert_eq!(target.kind, CheckKind::Minecraft);
+        assert_eq!(target.address, "mc.example.com:25565");
+
+        let target = ProbeTarget::parse(" web=https://example.com/health").unwrap();
+        assert_eq!(target.backend_id, "web");
+        assert_eq!(target.kind, CheckKind::Http);
+        assert_eq!(target.address, "https://example.com/health");
+
+        assert_eq!(
+            ProbeTarget::parse("game=tcp://10.0.0.1:7777")
+                .unwrap()
+                .address,
+            "10.0.0.1:7777"
+        );
+        assert!(ProbeTarget::parse("udp://10.0.0.1:1").is_none());
+        assert!(ProbeTarget::parse("x=ftp://10.0.0.1").is_none());
+        assert!(ProbeTarget::parse("=tcp://10.0.0.1:1").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_and_minecraft_checks() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 256];
+                    if stream.read(&mut buf).await.unwrap_or(0) > 0 {
+                        // Status response: length, packet ID 0, JSON string
+                        let json = br#"{"version":{"name":"1.21","protocol":767}}"#;
+                        let mut packet = vec![0x00, json.len() as u8];
+                        packet.extend_from_slice(json);
+                        let mut response = vec![packet.len() as u8];
+                        response.extend(packet);
+                        let _ = stream.write_all(&response).await;
+                    }
+                });
+            }
+        });
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let prober = prober(vec![
+            ProbeTarget::parse(&format!("mc=minecraft://{}", addr)).unwrap(),
+            ProbeTarget::parse(&format!("tcp=tcp://{}", addr)).unwrap(),
+            ProbeTarget::parse(&format!("down=tcp://{}", closed_addr)).unwrap(),
+        ]);
+        let results = prober.run_checks().await;
+        assert!(results[0].success, "{:?}", results[0]);
+        assert!(results[1].success, "{:?}", results[1]);
+        assert!(!results[2].success);
+        assert!(results[2].latency_ms.is_none());
+        assert!(results[2].error.is_some());
+        assert!(results.iter().all(|r| r.region == "eu-west"));
+    }
+
+    #[tokio::test]
+    async fn test_http_check() {
+        let app = axum::Router::new()
+            .route("/ok", axum::routing::get(|| async { "ok" }))
+            .route(
+                "/challenge",
+                axum::routing::get(|| async { axum::response::Redirect::temporary("/ok") }),
+            )
+            .route(
+                "/down",
+                axum::routing::get(|| async { axum::http::StatusCode::BAD_GATEWAY }),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let prober = prober(
+            ["ok", "challenge", "down"]
+                .iter()
+                .map(|path| {
+                    ProbeTarget::parse(&format!("{}=http://{}/{}", path, addr, path)).unwrap()
+                })
+                .collect(),
+        );
+        let results = prober.run_checks().await;
+        assert!(results[0].success);
+        assert!(results[1].success);
+        assert!(!results[2].success);
+    }
+
+    #[tokio::test]
+    async fn test_degraded_across_regions() {
+        let prober = prober(Vec::new());
+        prober.ingest(result("eu-west", true, 20.0)).await;
+        prober.ingest(result("us-east", true, 40.0)).await;
+
+        let statuses = prober.evaluate(Utc::now()).await;
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].availability, 1.0);
+        assert_eq!(statuses[0].latency_ms, Some(30.0));
+        assert!(!statuses[0].degraded);
+
+        prober.ingest(result("us-east", false, 0.0)).await;
+        let statuses = prober.evaluate(Utc::now()).await;
+        assert_eq!(statuses[0].availability, 0.5);
+        assert!(statuses[0].degraded);
+        assert!(prober.degraded.read().contains("backend-1"));
+
+        // Older pushes do not replace newer results
+        let mut late = result("us-east", true, 10.0);
+        late.timestamp -= ChronoDuration::seconds(10);
+        prober.ingest(late).await;
+        assert!(prober.evaluate(Utc::now()).await[0].degraded);
+
+        // Results older than three intervals are ignored
+        let later = Utc::now() + ChronoDuration::minutes(10);
+        assert!(prober.status(later).is_empty());
+    }
+}
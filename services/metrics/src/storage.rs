@@ -4,9 +4,11 @@
 //! analysis, including time-series queries and attack event logging.
 
 use crate::aggregator::{GeoTrafficData, RawAttackMetrics, RawTrafficMetrics, RawWorkerMetrics};
+use crate::prober::ProbeResult;
 use chrono::{DateTime, Duration as ChronoDuration, Utc};
-use deadpool_redis::Pool as RedisPool;
 use deadpool_redis::redis::AsyncCommands;
+use pistonprotection_common::db::DbPools;
+use pistonprotection_common::redis::RedisPool;
 use pistonprotection_proto::{
     common::{Pagination, PaginationInfo, Timestamp},
     metrics::*,
@@ -15,6 +17,7 @@ use serde::{Deserialize, Serialize};
 use sqlx::Row;
 use sqlx::postgres::PgPool;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tracing::{debug, info};
@@ -72,8 +75,8 @@ struct AttackEventRecord {
 
 /// Time-series storage service
 pub struct TimeSeriesStorage {
-    /// PostgreSQL connection pool for long-term storage
-    db_pool: Option<PgPool>,
+    /// PostgreSQL pools for long-term storage (primary and read replicas)
+    db: Option<Arc<DbPools>>,
 
     /// Redis connection pool for real-time time-series
     redis_pool: Option<RedisPool>,
@@ -112,19 +115,29 @@ impl Default for RetentionConfig {
 impl TimeSeriesStorage {
     /// Create a new time-series storage instance
     pub fn new(
-        db_pool: Option<PgPool>,
+        db: Option<Arc<DbPools>>,
         redis_pool: Option<RedisPool>,
         key_prefix: &str,
         retention: RetentionConfig,
     ) -> Self {
         Self {
-            db_pool,
+            db,
             redis_pool,
             key_prefix: key_prefix.to_string(),
             retention,
         }
     }
 
+    /// Primary pool, for writes
+    fn db_pool(&self) -> Option<&PgPool> {
+        self.db.as_ref().map(|db| db.primary())
+    }
+
+    /// Pool for dashboard reads, a read replica when one is in rotation
+    fn read_pool(&self) -> Option<&PgPool> {
+        self.db.as_ref().map(|db| db.reader())
+    }
+
     /// Build a Redis key with prefix
     fn redis_key(&self, parts: &[&str]) -> String {
         format!("{}:{}", self.key_prefix, parts.join(":"))
@@ -186,7 +199,7 @@ impl TimeSeriesStorage {
         }
 
         // Store in PostgreSQL for long-term storage
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.db_pool() {
             sqlx::query(
                 r#"
                 INSERT INTO worker_metrics_ts (
@@ -303,7 +316,7 @@ impl TimeSeriesStorage {
         }
 
         // Store in PostgreSQL
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.db_pool() {
             let protocols_json = serde_json::to_value(&raw.requests_by_protocol)
                 .map_err(|e| StorageError::Serialization(e.to_string()))?;
 
@@ -400,7 +413,7 @@ impl TimeSeriesStorage {
         }
 
         // Store in PostgreSQL
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.db_pool() {
             let top_sources_json = serde_json::to_value(&raw.top_sources)
                 .map_err(|e| StorageError::Serialization(e.to_string()))?;
 
@@ -454,7 +467,7 @@ impl TimeSeriesStorage {
         &self,
         metrics: &TrafficMetrics,
     ) -> Result<(), StorageError> {
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.db_pool() {
             let protocols_json = serde_json::to_value(&metrics.requests_by_protocol)
                 .map_err(|e| StorageError::Serialization(e.to_string()))?;
 
@@ -485,7 +498,7 @@ impl TimeSeriesStorage {
 
     /// Store attack snapshot (aggregated)
     pub async fn store_attack_snapshot(&self, metrics: &AttackMetrics) -> Result<(), StorageError> {
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.db_pool() {
             let top_sources_json = serde_json::to_value(&metrics.top_sources)
                 .map_err(|e| StorageError::Serialization(e.to_string()))?;
 
@@ -523,7 +536,7 @@ impl TimeSeriesStorage {
         country_code: &str,
         data: &GeoTrafficData,
     ) -> Result<(), StorageError> {
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.db_pool() {
             sqlx::query(
                 r#"
                 INSERT INTO geo_traffic (
@@ -549,6 +562,147 @@ impl TimeSeriesStorage {
         Ok(())
     }
 
+    /// Store the result of a synthetic probe, under its region and under
+    /// the backend across regions
+    pub async fn store_probe_result(&self, result: &ProbeResult) -> Result<(), StorageError> {
+        let timestamp = result.timestamp.timestamp();
+        let availability = if result.success { 1.0 } else { 0.0 };
+
+        // Scored by timestamp; the region keeps members of the same second
+        // apart on the cross-region key
+        if let Some(ref pool) = self.redis_pool {
+            let mut conn = pool
+                .get()
+                .await
+                .map_err(|e| StorageError::RedisPool(e.to_string()))?;
+
+            let samples = [
+                ("availability", Some(availability)),
+                ("latency_ms", result.latency_ms),
+            ];
+            for (metric, value) in samples {
+                let Some(value) = value else { continue };
+                let member = format!("{}:{}:{}", timestamp, result.region, value);
+                for key in [
+                    self.redis_key(&["probe", &result.backend_id, &result.region, metric]),
+                    self.redis_key(&["probe", &result.backend_id, metric]),
+                ] {
+                    let _: () = conn.zadd(&key, &member, timestamp).await?;
+                    let _: () = conn
+                        .expire(&key, self.retention.raw_retention.as_secs() as i64)
+                        .await?;
+                }
+            }
+        }
+
+        if let Some(pool) = self.db_pool() {
+            sqlx::query(
+                r#"
+                INSERT INTO probe_results_ts (
+                    backend_id, region, timestamp, check_kind, availability, latency_ms, error
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+            )
+            .bind(&result.backend_id)
+            .bind(&result.region)
+            .bind(result.timestamp)
+            .bind(result.kind.as_str())
+            .bind(availability)
+            .bind(result.latency_ms)
+            .bind(&result.error)
+            .execute(pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Query the availability and latency series of synthetic probes of a
+    /// backend, from one region or across regions
+    pub async fn query_probe_time_series(
+        &self,
+        backend_id: &str,
+        region: Option<&str>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        granularity: TimeGranularity,
+    ) -> Result<Vec<TimeSeries>, StorageError> {
+        let mut result = Vec::new();
+
+        for metric_name in ["availability", "latency_ms"] {
+            let mut points = Vec::new();
+
+            if let Some(ref pool) = self.redis_pool {
+                let mut conn = pool
+                    .get()
+                    .await
+                    .map_err(|e| StorageError::RedisPool(e.to_string()))?;
+
+                let key = match region {
+                    Some(region) => self.redis_key(&["probe", backend_id, region, metric_name]),
+                    None => self.redis_key(&["probe", backend_id, metric_name]),
+                };
+                let members: Vec<String> = conn
+                    .zrangebyscore(&key, start_time.timestamp(), end_time.timestamp())
+                    .await?;
+                let raw_points = members
+                    .iter()
+                    .filter_map(|member| {
+                        let (timestamp, _) = member.split_once(':')?;
+                        let (_, value) = member.rsplit_once(':')?;
+                        Some((timestamp.to_string(), value.parse().ok()?))
+                    })
+                    .collect::<Vec<_>>();
+                points = self.aggregate_points(raw_points, granularity);
+            }
+
+            if points.is_empty() {
+                if let Some(pool) = self.read_pool() {
+                    let query = format!(
+                        r#"
+                        SELECT
+                            (EXTRACT(EPOCH FROM timestamp)::bigint / {bucket}) * {bucket} as bucket_ts,
+                            AVG({column}) as value
+                        FROM probe_results_ts
+                        WHERE backend_id = $1 AND timestamp >= $2 AND timestamp <= $3
+                            AND ($4::text IS NULL OR region = $4) AND {column} IS NOT NULL
+                        GROUP BY bucket_ts
+                        ORDER BY bucket_ts
+                        "#,
+                        bucket = self.granularity_to_seconds(granularity),
+                        column = metric_name,
+                    );
+
+                    let rows = sqlx::query(&query)
+                        .bind(backend_id)
+                        .bind(start_time)
+                        .bind(end_time)
+                        .bind(region)
+                        .fetch_all(pool)
+                        .await?;
+
+                    points = rows
+                        .iter()
+                        .map(|row| DataPoint {
+                            timestamp: Some(Timestamp {
+                                seconds: row.get("bucket_ts"),
+                                nanos: 0,
+                            }),
+                            value: row.get("value"),
+                        })
+                        .collect();
+                }
+            }
+
+            result.push(TimeSeries {
+                metric_name: metric_name.to_string(),
+                points,
+            });
+        }
+
+        Ok(result)
+    }
+
     /// Query time-series data for traffic metrics
     pub async fn query_time_series(
         &self,
@@ -685,7 +839,7 @@ impl TimeSeriesStorage {
         }
 
         // Fall back to PostgreSQL for historical data
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.read_pool() {
             let table = match category {
                 "traffic" => "traffic_metrics_ts",
                 "attack" => "attack_metrics_ts",
@@ -816,7 +970,7 @@ impl TimeSeriesStorage {
     ) -> Result<String, StorageError> {
         let event_id = Uuid::new_v4().to_string();
 
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.db_pool() {
             sqlx::query(
                 r#"
                 INSERT INTO attack_events (
@@ -869,7 +1023,7 @@ impl TimeSeriesStorage {
             None
         };
 
-        if let (Some(pool), Some(event_id)) = (&self.db_pool, &event_id) {
+        if let (Some(pool), Some(event_id)) = (self.db_pool(), &event_id) {
             sqlx::query(
                 r#"
                 UPDATE attack_events
@@ -892,8 +1046,7 @@ impl TimeSeriesStorage {
     /// Get an attack event by ID
     pub async fn get_attack_event(&self, event_id: &str) -> Result<AttackEvent, StorageError> {
         let pool = self
-            .db_pool
-            .as_ref()
+            .read_pool()
             .ok_or_else(|| StorageError::Internal("Database not configured".to_string()))?;
 
         let row = sqlx::query(
@@ -923,8 +1076,7 @@ impl TimeSeriesStorage {
         pagination: Option<Pagination>,
     ) -> Result<(Vec<AttackEvent>, PaginationInfo), StorageError> {
         let pool = self
-            .db_pool
-            .as_ref()
+            .read_pool()
             .ok_or_else(|| StorageError::Internal("Database not configured".to_string()))?;
 
         let page = pagination.as_ref().map(|p| p.page).unwrap_or(1).max(1);
@@ -1036,8 +1188,7 @@ impl TimeSeriesStorage {
         end_time: Option<Timestamp>,
     ) -> Result<GeoMetrics, StorageError> {
         let pool = self
-            .db_pool
-            .as_ref()
+            .read_pool()
             .ok_or_else(|| StorageError::Internal("Database not configured".to_string()))?;
 
         let start = start_time
@@ -1109,7 +1260,7 @@ impl TimeSeriesStorage {
             let pattern = self.redis_key(&["traffic", "*", "*"]);
             let keys: Vec<String> = deadpool_redis::redis::cmd("KEYS")
                 .arg(&pattern)
-                .query_async(&mut *conn)
+                .query_async(&mut conn)
                 .await?;
 
             for key in keys {
@@ -1117,7 +1268,7 @@ impl TimeSeriesStorage {
                     .arg(&key)
                     .arg("-inf")
                     .arg(cutoff)
-                    .query_async(&mut *conn)
+                    .query_async(&mut conn)
                     .await?;
             }
 
@@ -1125,7 +1276,23 @@ impl TimeSeriesStorage {
             let pattern = self.redis_key(&["attack", "*", "*"]);
             let keys: Vec<String> = deadpool_redis::redis::cmd("KEYS")
                 .arg(&pattern)
-                .query_async(&mut *conn)
+                .query_async(&mut conn)
+                .await?;
+
+            for key in keys {
+                let _: () = deadpool_redis::redis::cmd("ZREMRANGEBYSCORE")
+                    .arg(&key)
+                    .arg("-inf")
+                    .arg(cutoff)
+                    .query_async(&mut conn)
+                    .await?;
+            }
+
+            // Clean up probe series, with or without a region
+            let pattern = self.redis_key(&["probe", "*"]);
+            let keys: Vec<String> = deadpool_redis::redis::cmd("KEYS")
+                .arg(&pattern)
+                .query_async(&mut conn)
                 .await?;
 
             for key in keys {
@@ -1133,13 +1300,13 @@ impl TimeSeriesStorage {
                     .arg(&key)
                     .arg("-inf")
                     .arg(cutoff)
-                    .query_async(&mut *conn)
+                    .query_async(&mut conn)
                     .await?;
             }
         }
 
         // Clean up PostgreSQL
-        if let Some(ref pool) = self.db_pool {
+        if let Some(pool) = self.db_pool() {
             let raw_cutoff =
                 Utc::now() - ChronoDuration::from_std(self.retention.raw_retention).unwrap();
 
@@ -1173,6 +1340,18 @@ impl TimeSeriesStorage {
                 result.rows_affected()
             );
 
+            // Clean probe results (few rows, kept as long as hourly aggregates)
+            let probe_cutoff =
+                Utc::now() - ChronoDuration::from_std(self.retention.hourly_retention).unwrap();
+            let result = sqlx::query("DELETE FROM probe_results_ts WHERE timestamp < $1")
+                .bind(probe_cutoff)
+                .execute(pool)
+                .await?;
+            debug!(
+                "Cleaned {} rows from probe_results_ts",
+                result.rows_affected()
+            );
+
             // Clean geo traffic (keep longer)
             let geo_cutoff =
                 Utc::now() - ChronoDuration::from_std(self.retention.daily_retention).unwrap();
@@ -4,10 +4,19 @@
 //! to connected clients via gRPC server-streaming RPCs.
 
 use crate::aggregator::MetricsAggregator;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use dashmap::DashMap;
 use futures::Stream;
+use hmac::{Hmac, Mac};
 use pistonprotection_proto::metrics::{AttackMetrics, TrafficMetrics};
+use rand::RngCore;
+use sha2::Sha256;
+use std::collections::VecDeque;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::task::{Context, Poll};
 use std::time::Duration;
 use thiserror::Error;
@@ -25,11 +34,83 @@ pub enum StreamError {
     #[error("Backend not found: {0}")]
     BackendNotFound(String),
 
+    #[error("Invalid resume token")]
+    InvalidResumeToken,
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
 
+impl From<StreamError> for Status {
+    fn from(err: StreamError) -> Self {
+        match err {
+            StreamError::Closed => Status::unavailable(err.to_string()),
+            StreamError::BackendNotFound(_) => Status::not_found(err.to_string()),
+            StreamError::InvalidResumeToken => Status::unauthenticated(err.to_string()),
+            StreamError::Internal(_) => Status::internal(err.to_string()),
+        }
+    }
+}
+
+/// Streamer configuration
+#[derive(Debug, Clone)]
+pub struct StreamerConfig {
+    /// Points kept per backend for replay to resuming clients
+    pub replay_points: usize,
+
+    /// Secret signing resume tokens. Shared by all replicas so tokens stay
+    /// valid when a client reconnects after a restart or to another replica.
+    pub token_secret: Option<String>,
+}
+
+impl Default for StreamerConfig {
+    fn default() -> Self {
+        Self {
+            replay_points: 512,
+            token_secret: None,
+        }
+    }
+}
+
+impl StreamerConfig {
+    /// Load streamer configuration from environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(points) = std::env::var("METRICS_STREAM_REPLAY_POINTS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            config.replay_points = points;
+        }
+
+        config.token_secret = std::env::var("METRICS_STREAM_TOKEN_SECRET")
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        config
+    }
+}
+
+/// Position a reconnecting client resumes from
+#[derive(Debug, Clone, Default)]
+pub struct ResumeCursor {
+    /// Sequence of the last point received, 0 for a fresh stream
+    pub after: u64,
+
+    /// Resume token that came with that point
+    pub token: String,
+}
+
+/// Boxed stream of metrics points
+pub type MetricsStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + 'static>>;
+
 /// Metrics streamer service
+///
+/// Every aggregator update is recorded with a sequence number into a bounded
+/// per-backend replay buffer. Streamed points carry their sequence and a
+/// resume token; a client that reconnects with both gets the points it
+/// missed from the buffer before the live feed, and never a point twice.
 pub struct MetricsStreamer {
     /// Reference to the aggregator for data and subscriptions
     aggregator: Arc<MetricsAggregator>,
@@ -39,15 +120,44 @@ pub struct MetricsStreamer {
 
     /// Stream buffer size
     buffer_size: usize,
+
+    /// Resume token signer
+    tokens: ResumeTokens,
+
+    /// Recorded traffic points
+    traffic_log: Arc<ReplayLog<TrafficMetrics>>,
+
+    /// Recorded attack points
+    attack_log: Arc<ReplayLog<AttackMetrics>>,
 }
 
 impl MetricsStreamer {
     /// Create a new metrics streamer
     pub fn new(aggregator: Arc<MetricsAggregator>) -> Self {
+        Self::with_config(aggregator, StreamerConfig::default())
+    }
+
+    /// Create a new metrics streamer with the given configuration
+    pub fn with_config(aggregator: Arc<MetricsAggregator>, config: StreamerConfig) -> Self {
+        let key = match config.token_secret {
+            Some(secret) => secret.into_bytes(),
+            None => {
+                warn!(
+                    "No stream token secret configured, resume tokens will not survive a restart"
+                );
+                let mut key = vec![0u8; 32];
+                rand::rng().fill_bytes(&mut key);
+                key
+            }
+        };
+
         Self {
             aggregator,
             max_streams_per_backend: 100,
             buffer_size: 100,
+            tokens: ResumeTokens::new(key, rand::random()),
+            traffic_log: Arc::new(ReplayLog::new(config.replay_points)),
+            attack_log: Arc::new(ReplayLog::new(config.replay_points)),
         }
     }
 
@@ -56,22 +166,41 @@ impl MetricsStreamer {
         &self,
         backend_id: String,
         interval_seconds: u32,
-    ) -> Result<TrafficMetricsStream, StreamError> {
-        let interval = Duration::from_secs(interval_seconds.max(1) as u64);
-
+        cursor: ResumeCursor,
+    ) -> Result<MetricsStream<TrafficMetrics>, StreamError> {
         info!(
             backend_id = %backend_id,
             interval_secs = %interval_seconds,
+            resume_after = cursor.after,
             "Creating traffic metrics stream"
         );
 
-        // Subscribe to real-time updates from aggregator
-        let rx = self.aggregator.subscribe_traffic();
+        let (rx, backlog) =
+            self.open(&self.traffic_log, StreamKind::Traffic, &backend_id, &cursor)?;
 
-        // Create filtered stream
-        let stream = TrafficMetricsStream::new(backend_id, rx, self.aggregator.clone(), interval);
+        // A fresh stream starts from the current state
+        let initial = if backlog.is_empty() && cursor.after == 0 {
+            match self.aggregator.get_traffic_metrics(&backend_id).await {
+                Ok(metrics) => Some(metrics),
+                Err(e) => {
+                    warn!(error = %e, "Failed to get initial traffic metrics");
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
-        Ok(stream)
+        let token = self.tokens.issue(StreamKind::Traffic, &backend_id);
+        Ok(resumable_stream(
+            self.traffic_log.clone(),
+            rx,
+            backend_id,
+            token,
+            cursor.after,
+            initial,
+            backlog,
+        ))
     }
 
     /// Create an attack metrics stream for a backend
@@ -79,198 +208,393 @@ impl MetricsStreamer {
         &self,
         backend_id: String,
         interval_seconds: u32,
-    ) -> Result<AttackMetricsStream, StreamError> {
-        let interval = Duration::from_secs(interval_seconds.max(1) as u64);
-
+        cursor: ResumeCursor,
+    ) -> Result<MetricsStream<AttackMetrics>, StreamError> {
         info!(
             backend_id = %backend_id,
             interval_secs = %interval_seconds,
+            resume_after = cursor.after,
             "Creating attack metrics stream"
         );
 
-        // Subscribe to real-time updates from aggregator
-        let rx = self.aggregator.subscribe_attack();
+        let (rx, backlog) =
+            self.open(&self.attack_log, StreamKind::Attack, &backend_id, &cursor)?;
 
-        // Create filtered stream
-        let stream = AttackMetricsStream::new(backend_id, rx, self.aggregator.clone(), interval);
+        // A fresh stream starts from the current state
+        let initial = if backlog.is_empty() && cursor.after == 0 {
+            match self.aggregator.get_attack_metrics(&backend_id).await {
+                Ok(metrics) => Some(metrics),
+                Err(e) => {
+                    warn!(error = %e, "Failed to get initial attack metrics");
+                    None
+                }
+            }
+        } else {
+            None
+        };
 
-        Ok(stream)
+        let token = self.tokens.issue(StreamKind::Attack, &backend_id);
+        Ok(resumable_stream(
+            self.attack_log.clone(),
+            rx,
+            backend_id,
+            token,
+            cursor.after,
+            initial,
+            backlog,
+        ))
     }
-}
 
-/// Traffic metrics stream
-pub struct TrafficMetricsStream {
-    /// Backend ID to filter for
-    backend_id: String,
+    /// Subscribe to a log and collect the points a stream starts with
+    ///
+    /// The subscription is taken before the buffer is read so no point falls
+    /// in between; points seen in both are dropped by sequence.
+    #[allow(clippy::type_complexity)]
+    fn open<T: StreamPoint>(
+        &self,
+        log: &ReplayLog<T>,
+        kind: StreamKind,
+        backend_id: &str,
+        cursor: &ResumeCursor,
+    ) -> Result<(broadcast::Receiver<(u64, T)>, Vec<(u64, T)>), StreamError> {
+        let rx = log.subscribe();
+
+        if cursor.after == 0 {
+            return Ok((rx, log.latest(backend_id).into_iter().collect()));
+        }
 
-    /// Broadcast receiver for updates
-    rx: broadcast::Receiver<TrafficMetrics>,
+        let epoch = self.tokens.verify(&cursor.token, kind, backend_id)?;
+        let (backlog, covered) = log.since(backend_id, cursor.after);
+        if epoch != self.tokens.epoch {
+            // Sequences follow the clock, so the points buffered here are
+            // still the ones after the cursor, but earlier ones are gone
+            info!(
+                backend_id = %backend_id,
+                resume_after = cursor.after,
+                replayed = backlog.len(),
+                "Resuming metrics stream issued by another process"
+            );
+        } else if !covered {
+            warn!(
+                backend_id = %backend_id,
+                resume_after = cursor.after,
+                replayed = backlog.len(),
+                "Resume cursor is older than the replay buffer, points were lost"
+            );
+        } else {
+            debug!(
+                backend_id = %backend_id,
+                resume_after = cursor.after,
+                replayed = backlog.len(),
+                "Resuming metrics stream"
+            );
+        }
 
-    /// Reference to aggregator for polling current state
-    aggregator: Arc<MetricsAggregator>,
+        Ok((rx, backlog))
+    }
+}
 
-    /// Interval timer for periodic updates
-    interval: Interval,
+/// Record aggregator updates into the replay buffers until the task is aborted
+pub fn start_recording_task(streamer: Arc<MetricsStreamer>) -> tokio::task::JoinHandle<()> {
+    let mut traffic = streamer.aggregator.subscribe_traffic();
+    let mut attack = streamer.aggregator.subscribe_attack();
 
-    /// Whether we've sent the initial value
-    sent_initial: bool,
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                update = traffic.recv() => match update {
+                    Ok(metrics) => {
+                        streamer.traffic_log.record(metrics);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(lagged = n, "Traffic recorder lagged, updates were not recorded");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                update = attack.recv() => match update {
+                    Ok(metrics) => {
+                        streamer.attack_log.record(metrics);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(lagged = n, "Attack recorder lagged, updates were not recorded");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+        debug!("Metrics recorder stopped");
+    })
 }
 
-impl Unpin for TrafficMetricsStream {}
+/// Kind of a metrics stream, bound into its resume tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamKind {
+    Traffic,
+    Attack,
+}
 
-impl TrafficMetricsStream {
-    fn new(
-        backend_id: String,
-        rx: broadcast::Receiver<TrafficMetrics>,
-        aggregator: Arc<MetricsAggregator>,
-        interval_duration: Duration,
-    ) -> Self {
-        Self {
-            backend_id,
-            rx,
-            aggregator,
-            interval: tokio::time::interval(interval_duration),
-            sent_initial: false,
+impl StreamKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            StreamKind::Traffic => "traffic",
+            StreamKind::Attack => "attack",
         }
     }
 }
 
-impl Stream for TrafficMetricsStream {
-    type Item = Result<TrafficMetrics, Status>;
+/// Metrics point carried by a resumable stream
+trait StreamPoint: Clone + Send + Sync + 'static {
+    fn backend_id(&self) -> &str;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // Send initial value on first poll
-        if !self.sent_initial {
-            self.sent_initial = true;
+    fn set_position(&mut self, sequence: u64, resume_token: &str);
+}
 
-            // Get current metrics synchronously
-            let _backend_id = self.backend_id.clone();
-            let _aggregator = self.aggregator.clone();
+impl StreamPoint for TrafficMetrics {
+    fn backend_id(&self) -> &str {
+        &self.backend_id
+    }
 
-            // We need to spawn a task to get the initial value since get_traffic_metrics is async
-            // For simplicity, we'll just wait for the first interval tick
-        }
+    fn set_position(&mut self, sequence: u64, resume_token: &str) {
+        self.sequence = sequence;
+        self.resume_token = resume_token.to_string();
+    }
+}
 
-        // Check for broadcast updates
-        match self.rx.try_recv() {
-            Ok(metrics) => {
-                // Filter for our backend
-                if metrics.backend_id == self.backend_id {
-                    return Poll::Ready(Some(Ok(metrics)));
-                }
-            }
-            Err(broadcast::error::TryRecvError::Empty) => {
-                // No updates, continue to interval check
-            }
-            Err(broadcast::error::TryRecvError::Lagged(n)) => {
-                warn!(
-                    backend_id = %self.backend_id,
-                    lagged = %n,
-                    "Traffic metrics stream lagged"
-                );
-            }
-            Err(broadcast::error::TryRecvError::Closed) => {
-                debug!(backend_id = %self.backend_id, "Traffic metrics stream closed");
-                return Poll::Ready(None);
-            }
-        }
+impl StreamPoint for AttackMetrics {
+    fn backend_id(&self) -> &str {
+        &self.backend_id
+    }
 
-        // Check interval timer
-        match self.interval.poll_tick(cx) {
-            Poll::Ready(_) => {
-                // Timer fired, fetch current metrics
-                let backend_id = self.backend_id.clone();
-                let aggregator = self.aggregator.clone();
+    fn set_position(&mut self, sequence: u64, resume_token: &str) {
+        self.sequence = sequence;
+        self.resume_token = resume_token.to_string();
+    }
+}
 
-                // Since we can't easily await here, we use a workaround:
-                // Create a future and poll it
-                let _fut = async move { aggregator.get_traffic_metrics(&backend_id).await };
+type HmacSha256 = Hmac<Sha256>;
 
-                // For a proper implementation, we'd use a pinned future
-                // For now, we'll rely on the broadcast updates which is the primary mechanism
-                cx.waker().wake_by_ref();
-                Poll::Pending
-            }
-            Poll::Pending => Poll::Pending,
+/// Signs and verifies resume tokens
+///
+/// A token binds the stream kind and backend to the epoch of the process
+/// that issued it, so a client can only resume the stream it was given and
+/// the streamer can tell whether its buffer may cover the cursor.
+struct ResumeTokens {
+    key: Vec<u8>,
+    epoch: u64,
+}
+
+impl ResumeTokens {
+    fn new(key: Vec<u8>, epoch: u64) -> Self {
+        Self { key, epoch }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC can take key of any size")
+    }
+
+    /// Issue a token for a stream
+    fn issue(&self, kind: StreamKind, backend_id: &str) -> String {
+        let payload = format!("{}:{}:{}", self.epoch, kind.as_str(), backend_id);
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let signature = mac.finalize().into_bytes();
+
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// Verify a token for a stream, returning the epoch it was issued in
+    fn verify(&self, token: &str, kind: StreamKind, backend_id: &str) -> Result<u64, StreamError> {
+        let (payload, signature) = token
+            .split_once('.')
+            .ok_or(StreamError::InvalidResumeToken)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| StreamError::InvalidResumeToken)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| StreamError::InvalidResumeToken)?;
+
+        let mut mac = self.mac();
+        mac.update(&payload);
+        mac.verify_slice(&signature)
+            .map_err(|_| StreamError::InvalidResumeToken)?;
+
+        let payload = String::from_utf8(payload).map_err(|_| StreamError::InvalidResumeToken)?;
+        let mut parts = payload.splitn(3, ':');
+        let epoch = parts
+            .next()
+            .and_then(|epoch| epoch.parse().ok())
+            .ok_or(StreamError::InvalidResumeToken)?;
+        if parts.next() != Some(kind.as_str()) || parts.next() != Some(backend_id) {
+            return Err(StreamError::InvalidResumeToken);
         }
+
+        Ok(epoch)
     }
 }
 
-/// Attack metrics stream
-pub struct AttackMetricsStream {
-    /// Backend ID to filter for
-    backend_id: String,
-
-    /// Broadcast receiver for updates
-    rx: broadcast::Receiver<AttackMetrics>,
+/// Bounded per-backend history of sequenced points, and their live feed
+struct ReplayLog<T> {
+    /// Points kept per backend
+    capacity: usize,
 
-    /// Reference to aggregator for polling current state
-    aggregator: Arc<MetricsAggregator>,
+    /// History by backend ID
+    backends: DashMap<String, BackendHistory<T>>,
 
-    /// Interval timer for periodic updates
-    interval: Interval,
+    /// Last sequence handed out
+    last_sequence: AtomicU64,
 
-    /// Whether we've sent the initial value
-    sent_initial: bool,
+    /// Live feed of recorded points
+    live: broadcast::Sender<(u64, T)>,
 }
 
-impl Unpin for AttackMetricsStream {}
+struct BackendHistory<T> {
+    points: VecDeque<(u64, T)>,
 
-impl AttackMetricsStream {
-    fn new(
-        backend_id: String,
-        rx: broadcast::Receiver<AttackMetrics>,
-        aggregator: Arc<MetricsAggregator>,
-        interval_duration: Duration,
-    ) -> Self {
+    /// Sequence of the newest point dropped from the buffer
+    evicted_through: u64,
+}
+
+impl<T> Default for BackendHistory<T> {
+    fn default() -> Self {
         Self {
-            backend_id,
-            rx,
-            aggregator,
-            interval: tokio::time::interval(interval_duration),
-            sent_initial: false,
+            points: VecDeque::new(),
+            evicted_through: 0,
         }
     }
 }
 
-impl Stream for AttackMetricsStream {
-    type Item = Result<AttackMetrics, Status>;
+impl<T: StreamPoint> ReplayLog<T> {
+    fn new(capacity: usize) -> Self {
+        let (live, _) = broadcast::channel(1000);
+        Self {
+            capacity: capacity.max(1),
+            backends: DashMap::new(),
+            last_sequence: AtomicU64::new(0),
+            live,
+        }
+    }
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // Check for broadcast updates
-        match self.rx.try_recv() {
-            Ok(metrics) => {
-                // Filter for our backend
-                if metrics.backend_id == self.backend_id {
-                    return Poll::Ready(Some(Ok(metrics)));
-                }
-            }
-            Err(broadcast::error::TryRecvError::Empty) => {
-                // No updates, continue to interval check
-            }
-            Err(broadcast::error::TryRecvError::Lagged(n)) => {
-                warn!(
-                    backend_id = %self.backend_id,
-                    lagged = %n,
-                    "Attack metrics stream lagged"
-                );
-            }
-            Err(broadcast::error::TryRecvError::Closed) => {
-                debug!(backend_id = %self.backend_id, "Attack metrics stream closed");
-                return Poll::Ready(None);
+    /// Hand out the next sequence
+    ///
+    /// Sequences follow the wall clock in milliseconds, so they keep
+    /// increasing across restarts and a cursor issued before one never hides
+    /// the points after it.
+    fn next_sequence(&self) -> u64 {
+        let now = Utc::now().timestamp_millis().max(0) as u64;
+        let previous = self
+            .last_sequence
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |last| {
+                Some(now.max(last + 1))
+            })
+            .unwrap_or_else(|last| last);
+        now.max(previous + 1)
+    }
+
+    /// Record a point, returning its sequence
+    fn record(&self, point: T) -> u64 {
+        let mut history = self
+            .backends
+            .entry(point.backend_id().to_string())
+            .or_default();
+
+        let sequence = self.next_sequence();
+        history.points.push_back((sequence, point.clone()));
+        while history.points.len() > self.capacity {
+            if let Some((evicted, _)) = history.points.pop_front() {
+                history.evicted_through = evicted;
             }
         }
 
-        // Check interval timer
-        match self.interval.poll_tick(cx) {
-            Poll::Ready(_) => {
-                // Timer fired, we could fetch current metrics here
-                cx.waker().wake_by_ref();
-                Poll::Pending
-            }
-            Poll::Pending => Poll::Pending,
+        // Sent while the entry is held, so a backend's points go out in
+        // sequence order
+        let _ = self.live.send((sequence, point));
+        sequence
+    }
+
+    /// Buffered points after a sequence, and whether none were evicted
+    fn since(&self, backend_id: &str, after: u64) -> (Vec<(u64, T)>, bool) {
+        match self.backends.get(backend_id) {
+            Some(history) => (
+                history
+                    .points
+                    .iter()
+                    .filter(|(sequence, _)| *sequence > after)
+                    .cloned()
+                    .collect(),
+                after >= history.evicted_through,
+            ),
+            None => (Vec::new(), true),
         }
     }
+
+    /// Newest buffered point
+    fn latest(&self, backend_id: &str) -> Option<(u64, T)> {
+        self.backends
+            .get(backend_id)
+            .and_then(|history| history.points.back().cloned())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<(u64, T)> {
+        self.live.subscribe()
+    }
+}
+
+/// Stream a backend's points: the backlog, then the live feed
+fn resumable_stream<T: StreamPoint>(
+    log: Arc<ReplayLog<T>>,
+    mut rx: broadcast::Receiver<(u64, T)>,
+    backend_id: String,
+    resume_token: String,
+    after: u64,
+    initial: Option<T>,
+    backlog: Vec<(u64, T)>,
+) -> MetricsStream<T> {
+    Box::pin(async_stream::stream! {
+        let mut last = after;
+
+        // The current state is not a recorded point, so it keeps the cursor
+        if let Some(mut point) = initial {
+            point.set_position(last, &resume_token);
+            yield Ok(point);
+        }
+
+        for (sequence, mut point) in backlog {
+            last = sequence;
+            point.set_position(sequence, &resume_token);
+            yield Ok(point);
+        }
+
+        loop {
+            match rx.recv().await {
+                Ok((sequence, mut point)) => {
+                    if point.backend_id() != backend_id || sequence <= last {
+                        continue;
+                    }
+                    last = sequence;
+                    point.set_position(sequence, &resume_token);
+                    yield Ok(point);
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    // Catch up from the buffer rather than skip points
+                    warn!(backend_id = %backend_id, lagged = n, "Metrics stream lagged, replaying");
+                    for (sequence, mut point) in log.since(&backend_id, last).0 {
+                        last = sequence;
+                        point.set_position(sequence, &resume_token);
+                        yield Ok(point);
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    debug!(backend_id = %backend_id, "Metrics stream closed");
+                    break;
+                }
+            }
+        }
+    })
 }
 
 /// A more sophisticated stream that properly handles async fetching
@@ -509,6 +833,7 @@ mod tests {
     use super::*;
     use crate::aggregator::AggregatorConfig;
     use crate::storage::{RetentionConfig, TimeSeriesStorage};
+    use futures::StreamExt;
     use pistonprotection_common::geoip::GeoIpService;
 
     fn create_test_aggregator() -> Arc<MetricsAggregator> {
@@ -534,13 +859,162 @@ mod tests {
 
         // Test that we can create streams without error
         let _traffic_stream = streamer
-            .stream_traffic_metrics("backend1".to_string(), 1)
+            .stream_traffic_metrics("backend1".to_string(), 1, ResumeCursor::default())
             .await
             .unwrap();
 
         let _attack_stream = streamer
-            .stream_attack_metrics("backend1".to_string(), 1)
+            .stream_attack_metrics("backend1".to_string(), 1, ResumeCursor::default())
             .await
             .unwrap();
     }
+
+    fn traffic(backend_id: &str, requests_total: u64) -> TrafficMetrics {
+        TrafficMetrics {
+            backend_id: backend_id.to_string(),
+            requests_total,
+            ..Default::default()
+        }
+    }
+
+    async fn next(stream: &mut MetricsStream<TrafficMetrics>) -> TrafficMetrics {
+        tokio::time::timeout(Duration::from_secs(1), stream.next())
+            .await
+            .expect("stream stalled")
+            .expect("stream ended")
+            .expect("stream failed")
+    }
+
+    #[test]
+    fn test_resume_token() {
+        let tokens = ResumeTokens::new(b"secret".to_vec(), 7);
+        let token = tokens.issue(StreamKind::Traffic, "backend:1");
+
+        assert_eq!(
+            tokens
+                .verify(&token, StreamKind::Traffic, "backend:1")
+                .unwrap(),
+            7
+        );
+        assert!(
+            tokens
+                .verify(&token, StreamKind::Attack, "backend:1")
+                .is_err()
+        );
+        assert!(
+            tokens
+                .verify(&token, StreamKind::Traffic, "backend")
+                .is_err()
+        );
+        assert!(
+            tokens
+                .verify("garbage", StreamKind::Traffic, "backend:1")
+                .is_err()
+        );
+
+        // Another secret does not accept it, another epoch does
+        let other = ResumeTokens::new(b"other".to_vec(), 7);
+        assert!(
+            other
+                .verify(&token, StreamKind::Traffic, "backend:1")
+                .is_err()
+        );
+        let restarted = ResumeTokens::new(b"secret".to_vec(), 8);
+        assert_eq!(
+            restarted
+                .verify(&token, StreamKind::Traffic, "backend:1")
+                .unwrap(),
+            7
+        );
+    }
+
+    #[test]
+    fn test_replay_log() {
+        let log = ReplayLog::new(2);
+        let first = log.record(traffic("backend1", 1));
+        let second = log.record(traffic("backend1", 2));
+        log.record(traffic("backend2", 1));
+        assert!(second > first);
+
+        let (points, covered) = log.since("backend1", first);
+        assert!(covered);
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].1.requests_total, 2);
+
+        // The first point is evicted by the third
+        let third = log.record(traffic("backend1", 3));
+        let (points, covered) = log.since("backend1", 0);
+        assert!(!covered);
+        assert_eq!(points.len(), 2);
+        assert!(log.since("backend1", first).1);
+        assert_eq!(log.latest("backend1").unwrap().0, third);
+        assert!(log.since("backend3", 0).0.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resume_stream() {
+        let streamer = MetricsStreamer::new(create_test_aggregator());
+        streamer.traffic_log.record(traffic("backend1", 1));
+
+        // A fresh stream starts at the newest point
+        let mut stream = streamer
+            .stream_traffic_metrics("backend1".to_string(), 1, ResumeCursor::default())
+            .await
+            .unwrap();
+        let first = next(&mut stream).await;
+        assert_eq!(first.requests_total, 1);
+        assert!(first.sequence > 0);
+        drop(stream);
+
+        // Points recorded while disconnected are replayed, then live ones follow
+        streamer.traffic_log.record(traffic("backend1", 2));
+        streamer.traffic_log.record(traffic("backend2", 1));
+        streamer.traffic_log.record(traffic("backend1", 3));
+        let mut stream = streamer
+            .stream_traffic_metrics(
+                "backend1".to_string(),
+                1,
+                ResumeCursor {
+                    after: first.sequence,
+                    token: first.resume_token.clone(),
+                },
+            )
+            .await
+            .unwrap();
+        streamer.traffic_log.record(traffic("backend1", 4));
+
+        let mut last = first.sequence;
+        for expected in 2..=4 {
+            let point = next(&mut stream).await;
+            assert_eq!(point.requests_total, expected);
+            assert!(point.sequence > last);
+            last = point.sequence;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_requires_token() {
+        let streamer = MetricsStreamer::new(create_test_aggregator());
+        let token = streamer.tokens.issue(StreamKind::Traffic, "backend1");
+
+        let cursor = ResumeCursor {
+            after: 1,
+            token: String::new(),
+        };
+        assert!(matches!(
+            streamer
+                .stream_traffic_metrics("backend1".to_string(), 1, cursor)
+                .await,
+            Err(StreamError::InvalidResumeToken)
+        ));
+
+        // A token only resumes the stream it was issued for
+        let cursor = ResumeCursor { after: 1, token };
+        assert!(matches!(
+            streamer
+                .stream_attack_metrics("backend1".to_string(), 1, cursor)
+                .await,
+            Err(StreamError::InvalidResumeToken)
+        ));
+    }
 }
@@ -0,0 +1,461 @@
+//! WebSocket bridge for browser dashboards
+//!
+//! Browsers cannot consume gRPC server streams, so `/api/v1/ws/metrics`
+//! bridges the [`MetricsStreamer`] over a WebSocket. A connection
+//! authenticates once, with a session JWT or an API key, and then manages
+//! its own subscriptions with JSON messages:
+//!
+//! ```json
+//! {"type": "subscribe", "id": "t1", "backendId": "...", "metrics": "traffic", "resolutionSecs": 5}
+//! {"type": "unsubscribe", "id": "t1"}
+//! ```
+//!
+//! Each subscription sends at most one frame per resolution interval. A
+//! client that reads slower than its frames are produced only ever gets the
+//! newest point; stale ones are dropped and counted in the next frame, so a
+//! slow browser tab cannot make the service buffer without bound.
+
+use crate::AppState;
+use crate::auth::{AuthParams, ClientAuth, Identity};
+use crate::streams::{MetricsStream, MetricsStreamer, ResumeCursor};
+use axum::{
+    extract::{
+        Query, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use pistonprotection_proto::metrics::{AttackMetrics, TrafficMetrics};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+/// Longest resolution a subscription may ask for
+const MAX_RESOLUTION_SECS: u32 = 300;
+
+/// WebSocket bridge configuration
+#[derive(Debug, Clone)]
+pub struct WsConfig {
+    /// Subscriptions a single connection may hold
+    pub max_subscriptions: usize,
+
+    /// Frames queued per connection before stale ones are dropped
+    pub send_buffer: usize,
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        Self {
+            max_subscriptions: 16,
+            send_buffer: 32,
+        }
+    }
+}
+
+impl WsConfig {
+    /// Load WebSocket configuration from environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(max) = std::env::var("METRICS_WS_MAX_SUBSCRIPTIONS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            config.max_subscriptions = max;
+        }
+
+        if let Some(buffer) = std::env::var("METRICS_WS_SEND_BUFFER")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            config.send_buffer = buffer;
+        }
+
+        config
+    }
+}
+
+/// Metric set of a subscription
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum MetricSet {
+    Traffic,
+    Attack,
+}
+
+/// Messages sent by the browser
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ClientMessage {
+    #[serde(rename_all = "camelCase")]
+    Subscribe {
+        id: String,
+        backend_id: String,
+        metrics: MetricSet,
+        #[serde(default)]
+        resolution_secs: u32,
+        #[serde(default)]
+        resume_after: u64,
+        #[serde(default)]
+        resume_token: String,
+    },
+    Unsubscribe {
+        id: String,
+    },
+}
+
+/// Messages sent to the browser
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ServerMessage {
+    Subscribed {
+        id: String,
+    },
+    Unsubscribed {
+        id: String,
+    },
+    Traffic {
+        id: String,
+        data: TrafficMetrics,
+        /// Stale frames dropped since the previous one
+        dropped: u64,
+    },
+    Attack {
+        id: String,
+        data: AttackMetrics,
+        dropped: u64,
+    },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        message: String,
+    },
+}
+
+/// Bridges metrics streams to WebSocket connections
+pub struct WsBridge {
+    streamer: Arc<MetricsStreamer>,
+    auth: Arc<ClientAuth>,
+    config: WsConfig,
+}
+
+impl WsBridge {
+    /// Create a new bridge
+    pub fn new(streamer: Arc<MetricsStreamer>, auth: Arc<ClientAuth>, config: WsConfig) -> Self {
+        Self {
+            streamer,
+            auth,
+            config,
+        }
+    }
+
+    /// Serve an upgraded connection until either side closes it
+    async fn serve(self: Arc<Self>, socket: WebSocket, identity: Identity) {
+        info!(user_id = %identity.user_id, "Metrics WebSocket connected");
+
+        let (mut sink, mut source) = socket.split();
+        let (tx, mut rx) = mpsc::channel(self.config.send_buffer.max(1));
+        let mut subscriptions: HashMap<String, JoinHandle<()>> = HashMap::new();
+
+        loop {
+            tokio::select! {
+                Some(frame) = rx.recv() => {
+                    if send_frame(&mut sink, &frame).await.is_err() {
+                        break;
+                    }
+                }
+                message = source.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        // Replies skip the queue, which only this loop drains
+                        let reply = self
+                            .handle(&identity, text.as_str(), &tx, &mut subscriptions)
+                            .await;
+                        if send_frame(&mut sink, &reply).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => break,
+                    // Pings are answered by axum, binary frames are not part of the protocol
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        debug!(error = %e, "Metrics WebSocket error");
+                        break;
+                    }
+                },
+            }
+        }
+
+        for (_, handle) in subscriptions {
+            handle.abort();
+        }
+        info!(user_id = %identity.user_id, "Metrics WebSocket disconnected");
+    }
+
+    /// Handle a client message, returning the reply
+    async fn handle(
+        &self,
+        identity: &Identity,
+        text: &str,
+        tx: &mpsc::Sender<ServerMessage>,
+        subscriptions: &mut HashMap<String, JoinHandle<()>>,
+    ) -> ServerMessage {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                return ServerMessage::Error {
+                    id: None,
+                    message: format!("Invalid message: {}", e),
+                };
+            }
+        };
+
+        match message {
+            ClientMessage::Subscribe {
+                id,
+                backend_id,
+                metrics,
+                resolution_secs,
+                resume_after,
+                resume_token,
+            } => {
+                subscriptions.retain(|_, handle| !handle.is_finished());
+                if !subscriptions.contains_key(&id)
+                    && subscriptions.len() >= self.config.max_subscriptions
+                {
+                    return ServerMessage::Error {
+                        id: Some(id),
+                        message: "Too many subscriptions".to_string(),
+                    };
+                }
+
+                // Unknown and foreign backends look the same
+                if !self.auth.can_view(identity, &backend_id).await {
+                    return ServerMessage::Error {
+                        id: Some(id),
+                        message: "Backend not found".to_string(),
+                    };
+                }
+
+                let resolution_secs = resolution_secs.clamp(1, MAX_RESOLUTION_SECS);
+                let resolution = Duration::from_secs(resolution_secs as u64);
+                let cursor = ResumeCursor {
+                    after: resume_after,
+                    token: resume_token,
+                };
+
+                let handle = match metrics {
+                    MetricSet::Traffic => self
+                        .streamer
+                        .stream_traffic_metrics(backend_id, resolution_secs, cursor)
+                        .await
+                        .map(|stream| {
+                            spawn_subscription(
+                                id.clone(),
+                                stream,
+                                resolution,
+                                tx.clone(),
+                                |id, data, dropped| ServerMessage::Traffic { id, data, dropped },
+                            )
+                        }),
+                    MetricSet::Attack => self
+                        .streamer
+                        .stream_attack_metrics(backend_id, resolution_secs, cursor)
+                        .await
+                        .map(|stream| {
+                            spawn_subscription(
+                                id.clone(),
+                                stream,
+                                resolution,
+                                tx.clone(),
+                                |id, data, dropped| ServerMessage::Attack { id, data, dropped },
+                            )
+                        }),
+                };
+
+                match handle {
+                    Ok(handle) => {
+                        if let Some(previous) = subscriptions.insert(id.clone(), handle) {
+                            previous.abort();
+                        }
+                        ServerMessage::Subscribed { id }
+                    }
+                    Err(e) => ServerMessage::Error {
+                        id: Some(id),
+                        message: e.to_string(),
+                    },
+                }
+            }
+            ClientMessage::Unsubscribe { id } => {
+                if let Some(handle) = subscriptions.remove(&id) {
+                    handle.abort();
+                }
+                ServerMessage::Unsubscribed { id }
+            }
+        }
+    }
+}
+
+/// Send a message as a text frame
+async fn send_frame(
+    sink: &mut SplitSink<WebSocket, Message>,
+    frame: &ServerMessage,
+) -> Result<(), axum::Error> {
+    match serde_json::to_string(frame) {
+        Ok(text) => sink.send(Message::Text(text.into())).await,
+        Err(e) => {
+            warn!(error = %e, "Failed to encode metrics frame");
+            Ok(())
+        }
+    }
+}
+
+/// Forward a stream to a connection at most once per resolution interval
+///
+/// Only the newest point is held; when the connection's queue is full the
+/// point waits for the next tick and is replaced by anything newer.
+fn spawn_subscription<T: Send + 'static>(
+    id: String,
+    mut stream: MetricsStream<T>,
+    resolution: Duration,
+    tx: mpsc::Sender<ServerMessage>,
+    frame: fn(String, T, u64) -> ServerMessage,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(resolution);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut pending: Option<T> = None;
+        let mut dropped = 0u64;
+
+        loop {
+            tokio::select! {
+                // Drain the stream first so a tick always sends the newest point
+                biased;
+
+                point = stream.next() => match point {
+                    Some(Ok(point)) => {
+                        if pending.replace(point).is_some() {
+                            dropped += 1;
+                        }
+                    }
+                    Some(Err(status)) => {
+                        let _ = tx
+                            .send(ServerMessage::Error {
+                                id: Some(id.clone()),
+                                message: status.message().to_string(),
+                            })
+                            .await;
+                        break;
+                    }
+                    None => break,
+                },
+                _ = ticker.tick(), if pending.is_some() => match tx.try_reserve() {
+                    Ok(permit) => {
+                        if let Some(point) = pending.take() {
+                            permit.send(frame(id.clone(), point, dropped));
+                            dropped = 0;
+                        }
+                    }
+                    Err(TrySendError::Full(())) => {}
+                    Err(TrySendError::Closed(())) => break,
+                },
+            }
+        }
+    })
+}
+
+/// Upgrade an authenticated request to a metrics WebSocket
+pub async fn metrics_ws(
+    State(state): State<AppState>,
+    Query(params): Query<AuthParams>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let bridge = state.ws.clone();
+    match state.auth.authenticate(&headers, &params).await {
+        Ok(identity) => ws.on_upgrade(move |socket| bridge.serve(socket, identity)),
+        Err(failure) => {
+            warn!(failure = ?failure, "Rejected metrics WebSocket");
+            failure.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_client_messages() {
+        let message: ClientMessage = serde_json::from_str(
+            r#"{"type":"subscribe","id":"t1","backendId":"b1","metrics":"attack","resolutionSecs":5}"#,
+        )
+        .unwrap();
+        match message {
+            ClientMessage::Subscribe {
+                id,
+                backend_id,
+                metrics,
+                resolution_secs,
+                resume_after,
+                ..
+            } => {
+                assert_eq!(id, "t1");
+                assert_eq!(backend_id, "b1");
+                assert_eq!(metrics, MetricSet::Attack);
+                assert_eq!(resolution_secs, 5);
+                assert_eq!(resume_after, 0);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        let message: ClientMessage =
+            serde_json::from_str(r#"{"type":"unsubscribe","id":"t1"}"#).unwrap();
+        assert!(matches!(message, ClientMessage::Unsubscribe { id } if id == "t1"));
+
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"type":"subscribe"}"#).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_subscription_drops_stale_frames() {
+        let (points_tx, points_rx) = mpsc::unbounded_channel::<u64>();
+        let stream: MetricsStream<u64> =
+            Box::pin(tokio_stream::wrappers::UnboundedReceiverStream::new(points_rx).map(Ok));
+        let (tx, mut rx) = mpsc::channel(1);
+        let frame: fn(String, u64, u64) -> ServerMessage =
+            |id, data, dropped| ServerMessage::Error {
+                id: Some(id),
+                message: format!("{}:{}", data, dropped),
+            };
+        let handle =
+            spawn_subscription("s1".to_string(), stream, Duration::from_secs(1), tx, frame);
+
+        // Points within one interval make one frame
+        for point in 1..=3 {
+            points_tx.send(point).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        // The queue is full, so newer points replace the held one
+        for point in 4..=6 {
+            points_tx.send(point).unwrap();
+        }
+        tokio::time::sleep(Duration::from_secs(3)).await;
+
+        let message = |frame| match frame {
+            Some(ServerMessage::Error { message, .. }) => message,
+            other => panic!("unexpected frame: {:?}", other),
+        };
+        assert_eq!(message(rx.recv().await), "3:2");
+        assert_eq!(message(rx.recv().await), "6:2");
+
+        handle.abort();
+    }
+}
@@ -110,6 +110,13 @@ pub struct Subscription {
     pub cancel_at_period_end: bool,
     #[prost(message, optional, tag = "12")]
     pub canceled_at: ::core::option::Option<super::common::Timestamp>,
+    /// Plan change taking effect at the end of the period (downgrades)
+    #[prost(string, tag = "13")]
+    pub scheduled_plan_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "BillingPeriod", tag = "14")]
+    pub scheduled_billing_period: i32,
+    #[prost(message, optional, tag = "15")]
+    pub scheduled_change_at: ::core::option::Option<super::common::Timestamp>,
 }
 /// Subscription plan definition
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -153,10 +160,26 @@ pub struct Plan {
     #[prost(bool, tag = "18")]
     pub is_active: bool,
 }
-/// Invoice
+/// Invoice line item
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct InvoiceLineItem {
+    #[prost(string, tag = "1")]
+    pub description: ::prost::alloc::string::String,
+    #[prost(enumeration = "InvoiceLineItemKind", tag = "2")]
+    pub kind: i32,
+    #[prost(int64, tag = "3")]
+    pub quantity: i64,
+    #[prost(int64, tag = "4")]
+    pub unit_amount_cents: i64,
+    #[prost(int64, tag = "5")]
+    pub amount_cents: i64,
+}
+/// Invoice
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Invoice {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
@@ -198,6 +221,18 @@ pub struct Invoice {
     pub paid_at: ::core::option::Option<super::common::Timestamp>,
     #[prost(message, optional, tag = "20")]
     pub created_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, repeated, tag = "21")]
+    pub line_items: ::prost::alloc::vec::Vec<InvoiceLineItem>,
+    #[prost(string, tag = "22")]
+    pub customer_country: ::prost::alloc::string::String,
+    /// VAT rate in basis points
+    #[prost(int32, tag = "23")]
+    pub vat_rate_bps: i32,
+    #[prost(bool, tag = "24")]
+    pub vat_reverse_charge: bool,
+    /// Invoice PDF rendered by PistonProtection; download through GetInvoicePdf
+    #[prost(string, tag = "25")]
+    pub pdf_url: ::prost::alloc::string::String,
 }
 /// Usage record
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -403,6 +438,12 @@ pub struct Session {
     pub created_at: ::core::option::Option<super::common::Timestamp>,
     #[prost(message, optional, tag = "10")]
     pub last_active_at: ::core::option::Option<super::common::Timestamp>,
+    /// Country the session was opened from (ISO 3166-1 alpha-2, empty if unknown)
+    #[prost(string, tag = "11")]
+    pub country_code: ::prost::alloc::string::String,
+    /// The session making the request
+    #[prost(bool, tag = "12")]
+    pub current: bool,
 }
 /// Audit log entry
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -465,6 +506,31 @@ pub struct Invitation {
     #[prost(message, optional, tag = "9")]
     pub created_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Read-only access to an organization granted to PistonProtection support
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SupportAccessGrant {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub granted_by_user_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub reason: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "5")]
+    pub expires_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "6")]
+    pub revoked_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(string, tag = "7")]
+    pub revoked_by_user_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "8")]
+    pub created_at: ::core::option::Option<super::common::Timestamp>,
+    /// Neither revoked nor expired
+    #[prost(bool, tag = "9")]
+    pub active: bool,
+}
 /// Request/Response messages
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -612,6 +678,51 @@ pub struct ValidateApiKeyResponse {
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ListSessionsRequest {
+    /// Session making the request, flagged as current in the listing
+    #[prost(string, tag = "1")]
+    pub current_session_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListSessionsResponse {
+    /// Active sessions, most recently used first
+    #[prost(message, repeated, tag = "1")]
+    pub sessions: ::prost::alloc::vec::Vec<Session>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RevokeSessionRequest {
+    #[prost(string, tag = "1")]
+    pub session_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RevokeSessionResponse {
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+}
+/// Sign out everywhere but the session making the request
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RevokeOtherSessionsRequest {
+    #[prost(string, tag = "1")]
+    pub current_session_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RevokeOtherSessionsResponse {
+    #[prost(uint32, tag = "1")]
+    pub revoked_count: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct CreateApiKeyRequest {
     #[prost(string, tag = "1")]
     pub organization_id: ::prost::alloc::string::String,
@@ -758,6 +869,79 @@ pub struct RevokeInvitationResponse {
     #[prost(bool, tag = "1")]
     pub success: bool,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GrantSupportAccessRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    /// Why support needs access, e.g. a ticket reference
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+    /// How long the grant lasts (default 24 hours, at most 7 days)
+    #[prost(uint64, tag = "3")]
+    pub duration_seconds: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GrantSupportAccessResponse {
+    #[prost(message, optional, tag = "1")]
+    pub grant: ::core::option::Option<SupportAccessGrant>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ListSupportAccessGrantsRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub active_only: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListSupportAccessGrantsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub grants: ::prost::alloc::vec::Vec<SupportAccessGrant>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RevokeSupportAccessRequest {
+    #[prost(string, tag = "1")]
+    pub grant_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RevokeSupportAccessResponse {
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+}
+/// Staff only: exchange an organization's active grant for a support token
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct IssueSupportTokenRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    /// Why the session is needed, recorded in the organization's audit log
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct IssueSupportTokenResponse {
+    /// Read-only token, refused by the gateway once the grant is revoked
+    #[prost(string, tag = "1")]
+    pub token: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub expires_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "3")]
+    pub grant: ::core::option::Option<SupportAccessGrant>,
+}
 /// Plans
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -804,6 +988,8 @@ pub struct GetSubscriptionResponse {
     #[prost(message, optional, tag = "2")]
     pub plan: ::core::option::Option<Plan>,
 }
+/// Upgrades take effect immediately and are prorated; downgrades are
+/// scheduled for the end of the current period
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
@@ -814,6 +1000,7 @@ pub struct UpdateSubscriptionRequest {
     pub plan_id: ::prost::alloc::string::String,
     #[prost(enumeration = "BillingPeriod", tag = "3")]
     pub billing_period: i32,
+    /// Upgrades only
     #[prost(enumeration = "ProrationBehavior", tag = "4")]
     pub proration_behavior: i32,
 }
@@ -824,6 +1011,21 @@ pub struct UpdateSubscriptionResponse {
     #[prost(message, optional, tag = "1")]
     pub subscription: ::core::option::Option<Subscription>,
 }
+/// Cancel a scheduled downgrade, staying on the current plan
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct CancelPlanChangeRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct CancelPlanChangeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub subscription: ::core::option::Option<Subscription>,
+}
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
@@ -928,11 +1130,30 @@ pub struct GetInvoiceRequest {
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetInvoiceResponse {
     #[prost(message, optional, tag = "1")]
     pub invoice: ::core::option::Option<Invoice>,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetInvoicePdfRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub invoice_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetInvoicePdfResponse {
+    /// Presigned URL of the PDF, valid until expires_at
+    #[prost(string, tag = "1")]
+    pub download_url: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub expires_at: ::core::option::Option<super::common::Timestamp>,
+}
 /// Usage
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -1216,6 +1437,44 @@ impl InvoiceStatus {
         }
     }
 }
+/// Invoice line item kind
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum InvoiceLineItemKind {
+    Unspecified = 0,
+    Subscription = 1,
+    Proration = 2,
+    Overage = 3,
+    Other = 4,
+}
+impl InvoiceLineItemKind {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "INVOICE_LINE_ITEM_KIND_UNSPECIFIED",
+            Self::Subscription => "INVOICE_LINE_ITEM_KIND_SUBSCRIPTION",
+            Self::Proration => "INVOICE_LINE_ITEM_KIND_PRORATION",
+            Self::Overage => "INVOICE_LINE_ITEM_KIND_OVERAGE",
+            Self::Other => "INVOICE_LINE_ITEM_KIND_OTHER",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "INVOICE_LINE_ITEM_KIND_UNSPECIFIED" => Some(Self::Unspecified),
+            "INVOICE_LINE_ITEM_KIND_SUBSCRIPTION" => Some(Self::Subscription),
+            "INVOICE_LINE_ITEM_KIND_PRORATION" => Some(Self::Proration),
+            "INVOICE_LINE_ITEM_KIND_OVERAGE" => Some(Self::Overage),
+            "INVOICE_LINE_ITEM_KIND_OTHER" => Some(Self::Other),
+            _ => None,
+        }
+    }
+}
 /// Usage metric type
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -1713,6 +1972,88 @@ pub mod auth_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        /// Sessions (the caller's own)
+        pub async fn list_sessions(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListSessionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListSessionsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/ListSessions",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("pistonprotection.auth.AuthService", "ListSessions"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn revoke_session(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RevokeSessionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RevokeSessionResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/RevokeSession",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("pistonprotection.auth.AuthService", "RevokeSession"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn revoke_other_sessions(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RevokeOtherSessionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RevokeOtherSessionsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/RevokeOtherSessions",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.auth.AuthService",
+                        "RevokeOtherSessions",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
         /// API Keys
         pub async fn create_api_key(
             &mut self,
@@ -1936,12 +2277,12 @@ pub mod auth_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
-        /// Billing - Plans
-        pub async fn list_plans(
+        /// Support access
+        pub async fn grant_support_access(
             &mut self,
-            request: impl tonic::IntoRequest<super::ListPlansRequest>,
+            request: impl tonic::IntoRequest<super::GrantSupportAccessRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::ListPlansResponse>,
+            tonic::Response<super::GrantSupportAccessResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1954,20 +2295,23 @@ pub mod auth_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.auth.AuthService/ListPlans",
+                "/pistonprotection.auth.AuthService/GrantSupportAccess",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("pistonprotection.auth.AuthService", "ListPlans"),
+                    GrpcMethod::new(
+                        "pistonprotection.auth.AuthService",
+                        "GrantSupportAccess",
+                    ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn get_plan(
+        pub async fn list_support_access_grants(
             &mut self,
-            request: impl tonic::IntoRequest<super::GetPlanRequest>,
+            request: impl tonic::IntoRequest<super::ListSupportAccessGrantsRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::GetPlanResponse>,
+            tonic::Response<super::ListSupportAccessGrantsResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1980,19 +2324,23 @@ pub mod auth_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.auth.AuthService/GetPlan",
+                "/pistonprotection.auth.AuthService/ListSupportAccessGrants",
             );
             let mut req = request.into_request();
             req.extensions_mut()
-                .insert(GrpcMethod::new("pistonprotection.auth.AuthService", "GetPlan"));
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.auth.AuthService",
+                        "ListSupportAccessGrants",
+                    ),
+                );
             self.inner.unary(req, path, codec).await
         }
-        /// Billing - Subscriptions
-        pub async fn get_subscription(
+        pub async fn revoke_support_access(
             &mut self,
-            request: impl tonic::IntoRequest<super::GetSubscriptionRequest>,
+            request: impl tonic::IntoRequest<super::RevokeSupportAccessRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::GetSubscriptionResponse>,
+            tonic::Response<super::RevokeSupportAccessResponse>,
             tonic::Status,
         > {
             self.inner
@@ -2005,7 +2353,117 @@ pub mod auth_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.auth.AuthService/GetSubscription",
+                "/pistonprotection.auth.AuthService/RevokeSupportAccess",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.auth.AuthService",
+                        "RevokeSupportAccess",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn issue_support_token(
+            &mut self,
+            request: impl tonic::IntoRequest<super::IssueSupportTokenRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::IssueSupportTokenResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/IssueSupportToken",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.auth.AuthService",
+                        "IssueSupportToken",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Billing - Plans
+        pub async fn list_plans(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListPlansRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListPlansResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/ListPlans",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("pistonprotection.auth.AuthService", "ListPlans"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_plan(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetPlanRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetPlanResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/GetPlan",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("pistonprotection.auth.AuthService", "GetPlan"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Billing - Subscriptions
+        pub async fn get_subscription(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetSubscriptionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetSubscriptionResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/GetSubscription",
             );
             let mut req = request.into_request();
             req.extensions_mut()
@@ -2104,6 +2562,35 @@ pub mod auth_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn cancel_plan_change(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CancelPlanChangeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelPlanChangeResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/CancelPlanChange",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.auth.AuthService",
+                        "CancelPlanChange",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
         /// Billing - Checkout
         pub async fn create_checkout_session(
             &mut self,
@@ -2216,6 +2703,32 @@ pub mod auth_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn get_invoice_pdf(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetInvoicePdfRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetInvoicePdfResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.auth.AuthService/GetInvoicePdf",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("pistonprotection.auth.AuthService", "GetInvoicePdf"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
         /// Billing - Usage
         pub async fn get_usage_summary(
             &mut self,
@@ -2377,6 +2890,28 @@ pub mod auth_service_server {
             tonic::Response<super::ValidateApiKeyResponse>,
             tonic::Status,
         >;
+        /// Sessions (the caller's own)
+        async fn list_sessions(
+            &self,
+            request: tonic::Request<super::ListSessionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListSessionsResponse>,
+            tonic::Status,
+        >;
+        async fn revoke_session(
+            &self,
+            request: tonic::Request<super::RevokeSessionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RevokeSessionResponse>,
+            tonic::Status,
+        >;
+        async fn revoke_other_sessions(
+            &self,
+            request: tonic::Request<super::RevokeOtherSessionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RevokeOtherSessionsResponse>,
+            tonic::Status,
+        >;
         /// API Keys
         async fn create_api_key(
             &self,
@@ -2436,6 +2971,35 @@ pub mod auth_service_server {
             tonic::Response<super::RevokeInvitationResponse>,
             tonic::Status,
         >;
+        /// Support access
+        async fn grant_support_access(
+            &self,
+            request: tonic::Request<super::GrantSupportAccessRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GrantSupportAccessResponse>,
+            tonic::Status,
+        >;
+        async fn list_support_access_grants(
+            &self,
+            request: tonic::Request<super::ListSupportAccessGrantsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListSupportAccessGrantsResponse>,
+            tonic::Status,
+        >;
+        async fn revoke_support_access(
+            &self,
+            request: tonic::Request<super::RevokeSupportAccessRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RevokeSupportAccessResponse>,
+            tonic::Status,
+        >;
+        async fn issue_support_token(
+            &self,
+            request: tonic::Request<super::IssueSupportTokenRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::IssueSupportTokenResponse>,
+            tonic::Status,
+        >;
         /// Billing - Plans
         async fn list_plans(
             &self,
@@ -2477,6 +3041,13 @@ pub mod auth_service_server {
             tonic::Response<super::ResumeSubscriptionResponse>,
             tonic::Status,
         >;
+        async fn cancel_plan_change(
+            &self,
+            request: tonic::Request<super::CancelPlanChangeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelPlanChangeResponse>,
+            tonic::Status,
+        >;
         /// Billing - Checkout
         async fn create_checkout_session(
             &self,
@@ -2507,6 +3078,13 @@ pub mod auth_service_server {
             tonic::Response<super::GetInvoiceResponse>,
             tonic::Status,
         >;
+        async fn get_invoice_pdf(
+            &self,
+            request: tonic::Request<super::GetInvoicePdfRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetInvoicePdfResponse>,
+            tonic::Status,
+        >;
         /// Billing - Usage
         async fn get_usage_summary(
             &self,
@@ -3021,25 +3599,25 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.auth.AuthService/CreateApiKey" => {
+                "/pistonprotection.auth.AuthService/ListSessions" => {
                     #[allow(non_camel_case_types)]
-                    struct CreateApiKeySvc<T: AuthService>(pub Arc<T>);
+                    struct ListSessionsSvc<T: AuthService>(pub Arc<T>);
                     impl<
                         T: AuthService,
-                    > tonic::server::UnaryService<super::CreateApiKeyRequest>
-                    for CreateApiKeySvc<T> {
-                        type Response = super::CreateApiKeyResponse;
+                    > tonic::server::UnaryService<super::ListSessionsRequest>
+                    for ListSessionsSvc<T> {
+                        type Response = super::ListSessionsResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CreateApiKeyRequest>,
+                            request: tonic::Request<super::ListSessionsRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as AuthService>::create_api_key(&inner, request).await
+                                <T as AuthService>::list_sessions(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -3050,7 +3628,7 @@ pub mod auth_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = CreateApiKeySvc(inner);
+                        let method = ListSessionsSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -3066,25 +3644,25 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.auth.AuthService/ListApiKeys" => {
+                "/pistonprotection.auth.AuthService/RevokeSession" => {
                     #[allow(non_camel_case_types)]
-                    struct ListApiKeysSvc<T: AuthService>(pub Arc<T>);
+                    struct RevokeSessionSvc<T: AuthService>(pub Arc<T>);
                     impl<
                         T: AuthService,
-                    > tonic::server::UnaryService<super::ListApiKeysRequest>
-                    for ListApiKeysSvc<T> {
-                        type Response = super::ListApiKeysResponse;
+                    > tonic::server::UnaryService<super::RevokeSessionRequest>
+                    for RevokeSessionSvc<T> {
+                        type Response = super::RevokeSessionResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ListApiKeysRequest>,
+                            request: tonic::Request<super::RevokeSessionRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as AuthService>::list_api_keys(&inner, request).await
+                                <T as AuthService>::revoke_session(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -3095,7 +3673,7 @@ pub mod auth_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = ListApiKeysSvc(inner);
+                        let method = RevokeSessionSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -3111,25 +3689,26 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.auth.AuthService/RevokeApiKey" => {
+                "/pistonprotection.auth.AuthService/RevokeOtherSessions" => {
                     #[allow(non_camel_case_types)]
-                    struct RevokeApiKeySvc<T: AuthService>(pub Arc<T>);
+                    struct RevokeOtherSessionsSvc<T: AuthService>(pub Arc<T>);
                     impl<
                         T: AuthService,
-                    > tonic::server::UnaryService<super::RevokeApiKeyRequest>
-                    for RevokeApiKeySvc<T> {
-                        type Response = super::RevokeApiKeyResponse;
+                    > tonic::server::UnaryService<super::RevokeOtherSessionsRequest>
+                    for RevokeOtherSessionsSvc<T> {
+                        type Response = super::RevokeOtherSessionsResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RevokeApiKeyRequest>,
+                            request: tonic::Request<super::RevokeOtherSessionsRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as AuthService>::revoke_api_key(&inner, request).await
+                                <T as AuthService>::revoke_other_sessions(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -3140,7 +3719,7 @@ pub mod auth_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = RevokeApiKeySvc(inner);
+                        let method = RevokeOtherSessionsSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -3156,25 +3735,25 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.auth.AuthService/CreateAuditLog" => {
+                "/pistonprotection.auth.AuthService/CreateApiKey" => {
                     #[allow(non_camel_case_types)]
-                    struct CreateAuditLogSvc<T: AuthService>(pub Arc<T>);
+                    struct CreateApiKeySvc<T: AuthService>(pub Arc<T>);
                     impl<
                         T: AuthService,
-                    > tonic::server::UnaryService<super::CreateAuditLogRequest>
-                    for CreateAuditLogSvc<T> {
-                        type Response = super::CreateAuditLogResponse;
+                    > tonic::server::UnaryService<super::CreateApiKeyRequest>
+                    for CreateApiKeySvc<T> {
+                        type Response = super::CreateApiKeyResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CreateAuditLogRequest>,
+                            request: tonic::Request<super::CreateApiKeyRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as AuthService>::create_audit_log(&inner, request).await
+                                <T as AuthService>::create_api_key(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -3185,7 +3764,7 @@ pub mod auth_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = CreateAuditLogSvc(inner);
+                        let method = CreateApiKeySvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -3201,25 +3780,25 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.auth.AuthService/ListAuditLogs" => {
+                "/pistonprotection.auth.AuthService/ListApiKeys" => {
                     #[allow(non_camel_case_types)]
-                    struct ListAuditLogsSvc<T: AuthService>(pub Arc<T>);
+                    struct ListApiKeysSvc<T: AuthService>(pub Arc<T>);
                     impl<
                         T: AuthService,
-                    > tonic::server::UnaryService<super::ListAuditLogsRequest>
-                    for ListAuditLogsSvc<T> {
-                        type Response = super::ListAuditLogsResponse;
+                    > tonic::server::UnaryService<super::ListApiKeysRequest>
+                    for ListApiKeysSvc<T> {
+                        type Response = super::ListApiKeysResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ListAuditLogsRequest>,
+                            request: tonic::Request<super::ListApiKeysRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as AuthService>::list_audit_logs(&inner, request).await
+                                <T as AuthService>::list_api_keys(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -3230,7 +3809,7 @@ pub mod auth_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = ListAuditLogsSvc(inner);
+                        let method = ListApiKeysSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -3246,25 +3825,160 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.auth.AuthService/CreateInvitation" => {
+                "/pistonprotection.auth.AuthService/RevokeApiKey" => {
                     #[allow(non_camel_case_types)]
-                    struct CreateInvitationSvc<T: AuthService>(pub Arc<T>);
+                    struct RevokeApiKeySvc<T: AuthService>(pub Arc<T>);
                     impl<
                         T: AuthService,
-                    > tonic::server::UnaryService<super::CreateInvitationRequest>
-                    for CreateInvitationSvc<T> {
-                        type Response = super::CreateInvitationResponse;
+                    > tonic::server::UnaryService<super::RevokeApiKeyRequest>
+                    for RevokeApiKeySvc<T> {
+                        type Response = super::RevokeApiKeyResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CreateInvitationRequest>,
+                            request: tonic::Request<super::RevokeApiKeyRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as AuthService>::create_invitation(&inner, request).await
+                                <T as AuthService>::revoke_api_key(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RevokeApiKeySvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.auth.AuthService/CreateAuditLog" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateAuditLogSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::CreateAuditLogRequest>
+                    for CreateAuditLogSvc<T> {
+                        type Response = super::CreateAuditLogResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateAuditLogRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::create_audit_log(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CreateAuditLogSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.auth.AuthService/ListAuditLogs" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListAuditLogsSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::ListAuditLogsRequest>
+                    for ListAuditLogsSvc<T> {
+                        type Response = super::ListAuditLogsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListAuditLogsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::list_audit_logs(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ListAuditLogsSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.auth.AuthService/CreateInvitation" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateInvitationSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::CreateInvitationRequest>
+                    for CreateInvitationSvc<T> {
+                        type Response = super::CreateInvitationResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateInvitationRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::create_invitation(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -3381,6 +4095,195 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/pistonprotection.auth.AuthService/GrantSupportAccess" => {
+                    #[allow(non_camel_case_types)]
+                    struct GrantSupportAccessSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::GrantSupportAccessRequest>
+                    for GrantSupportAccessSvc<T> {
+                        type Response = super::GrantSupportAccessResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GrantSupportAccessRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::grant_support_access(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GrantSupportAccessSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.auth.AuthService/ListSupportAccessGrants" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListSupportAccessGrantsSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::ListSupportAccessGrantsRequest>
+                    for ListSupportAccessGrantsSvc<T> {
+                        type Response = super::ListSupportAccessGrantsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::ListSupportAccessGrantsRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::list_support_access_grants(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ListSupportAccessGrantsSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.auth.AuthService/RevokeSupportAccess" => {
+                    #[allow(non_camel_case_types)]
+                    struct RevokeSupportAccessSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::RevokeSupportAccessRequest>
+                    for RevokeSupportAccessSvc<T> {
+                        type Response = super::RevokeSupportAccessResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RevokeSupportAccessRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::revoke_support_access(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RevokeSupportAccessSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.auth.AuthService/IssueSupportToken" => {
+                    #[allow(non_camel_case_types)]
+                    struct IssueSupportTokenSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::IssueSupportTokenRequest>
+                    for IssueSupportTokenSvc<T> {
+                        type Response = super::IssueSupportTokenResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::IssueSupportTokenRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::issue_support_token(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = IssueSupportTokenSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/pistonprotection.auth.AuthService/ListPlans" => {
                     #[allow(non_camel_case_types)]
                     struct ListPlansSvc<T: AuthService>(pub Arc<T>);
@@ -3654,6 +4557,52 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/pistonprotection.auth.AuthService/CancelPlanChange" => {
+                    #[allow(non_camel_case_types)]
+                    struct CancelPlanChangeSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::CancelPlanChangeRequest>
+                    for CancelPlanChangeSvc<T> {
+                        type Response = super::CancelPlanChangeResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CancelPlanChangeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::cancel_plan_change(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CancelPlanChangeSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/pistonprotection.auth.AuthService/CreateCheckoutSession" => {
                     #[allow(non_camel_case_types)]
                     struct CreateCheckoutSessionSvc<T: AuthService>(pub Arc<T>);
@@ -3842,6 +4791,51 @@ pub mod auth_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/pistonprotection.auth.AuthService/GetInvoicePdf" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetInvoicePdfSvc<T: AuthService>(pub Arc<T>);
+                    impl<
+                        T: AuthService,
+                    > tonic::server::UnaryService<super::GetInvoicePdfRequest>
+                    for GetInvoicePdfSvc<T> {
+                        type Response = super::GetInvoicePdfResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetInvoicePdfRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as AuthService>::get_invoice_pdf(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetInvoicePdfSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/pistonprotection.auth.AuthService/GetUsageSummary" => {
                     #[allow(non_camel_case_types)]
                     struct GetUsageSummarySvc<T: AuthService>(pub Arc<T>);
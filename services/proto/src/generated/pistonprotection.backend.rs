@@ -44,6 +44,9 @@ pub struct Backend {
         ::prost::alloc::string::String,
         ::prost::alloc::string::String,
     >,
+    /// Onboarding of a backend added by hostname
+    #[prost(message, optional, tag = "15")]
+    pub onboarding: ::core::option::Option<OnboardingStatus>,
 }
 /// Origin server
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -328,6 +331,21 @@ pub struct ProtectionSettings {
     /// L7 specific settings
     #[prost(message, optional, tag = "7")]
     pub l7_settings: ::core::option::Option<L7ProtectionSettings>,
+    /// Honeypot ports
+    #[prost(message, optional, tag = "8")]
+    pub honeypot: ::core::option::Option<HoneypotSettings>,
+    /// Allowlist learning mode
+    #[prost(message, optional, tag = "9")]
+    pub learning: ::core::option::Option<LearningSettings>,
+    /// Responses served to clients refused at L7
+    #[prost(message, optional, tag = "10")]
+    pub block_responses: ::core::option::Option<BlockResponseSettings>,
+    /// Per-connection bandwidth limit
+    #[prost(message, optional, tag = "11")]
+    pub connection_rate: ::core::option::Option<ConnectionRateSettings>,
+    /// Deprioritization of suspicious traffic
+    #[prost(message, optional, tag = "12")]
+    pub marking: ::core::option::Option<MarkingSettings>,
 }
 /// Challenge settings
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -355,6 +373,89 @@ pub struct GeoIpSettings {
     #[prost(string, repeated, tag = "2")]
     pub countries: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
+/// Honeypot settings: ports no real service of the backend listens on. Any
+/// source sending to them is flagged as an attacker.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct HoneypotSettings {
+    #[prost(uint32, repeated, tag = "1")]
+    pub ports: ::prost::alloc::vec::Vec<u32>,
+    /// Block flagged sources for every backend, not just flag them
+    #[prost(bool, tag = "2")]
+    pub auto_block: bool,
+    /// How long auto-blocked sources stay blocked (0 = until unblocked)
+    #[prost(uint32, tag = "3")]
+    pub block_duration_seconds: u32,
+}
+/// Allowlist learning settings: for a training window the workers record
+/// sources completing full, valid protocol handshakes (TCP connection with
+/// data, Minecraft login, QUIC handshake). Those sources become known-good and
+/// keep relaxed limits during later attacks.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct LearningSettings {
+    #[prost(bool, tag = "1")]
+    pub enabled: bool,
+    /// Length of the training window; changing it starts a new window
+    #[prost(uint32, tag = "2")]
+    pub training_seconds: u32,
+    /// Factor applied to the limits of known-good sources
+    #[prost(uint32, tag = "3")]
+    pub limit_multiplier: u32,
+    /// Completed handshakes before a source is known-good
+    #[prost(uint32, tag = "4")]
+    pub min_handshakes: u32,
+}
+/// Responses served to clients refused at L7, in place of the defaults. Empty
+/// fields keep the default response.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct BlockResponseSettings {
+    /// HTML page of refused HTTP requests (blocked, rate limited, challenged).
+    /// {{status}}, {{reason}}, {{host}} and {{client_ip}} are replaced with the
+    /// escaped values of the request.
+    #[prost(string, tag = "1")]
+    pub http_block_page: ::prost::alloc::string::String,
+    /// Disconnect message of refused Minecraft logins, as a JSON text component
+    #[prost(string, tag = "2")]
+    pub minecraft_kick_message: ::prost::alloc::string::String,
+}
+/// Per-connection bandwidth limit: every established TCP connection and UDP
+/// flow to the backend is held to this rate. Packets beyond it are dropped,
+/// so a single client cannot saturate the backend without being blocked.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ConnectionRateSettings {
+    /// Bytes per second of each flow (0 = unlimited)
+    #[prost(uint64, tag = "1")]
+    pub bytes_per_second: u64,
+    /// Bytes a flow may send at once (0 = one second of bytes_per_second)
+    #[prost(uint64, tag = "2")]
+    pub burst_bytes: u64,
+}
+/// Traffic marking: greylisted sources over their reduced limit are passed
+/// with a scavenger DSCP instead of being dropped, so QoS downstream of the
+/// workers sheds them first under congestion. Only the IP header changes.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct MarkingSettings {
+    #[prost(bool, tag = "1")]
+    pub enabled: bool,
+    /// DSCP written to marked packets, 0-63 (0 = lower effort, RFC 8622)
+    #[prost(uint32, tag = "2")]
+    pub dscp: u32,
+    /// Set ECN Congestion Experienced on ECN-capable marked packets
+    #[prost(bool, tag = "3")]
+    pub ecn_ce: bool,
+    /// Also mark greylisted traffic within its limit
+    #[prost(bool, tag = "4")]
+    pub mark_all_greylisted: bool,
+}
 /// L7 protection settings
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -449,192 +550,705 @@ pub struct BackendStatus {
     #[prost(message, optional, tag = "9")]
     pub last_updated: ::core::option::Option<super::common::Timestamp>,
 }
-/// Request/Response messages
+/// Onboarding status
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct CreateBackendRequest {
-    #[prost(string, tag = "1")]
-    pub organization_id: ::prost::alloc::string::String,
-    #[prost(message, optional, tag = "2")]
-    pub backend: ::core::option::Option<Backend>,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct OnboardingStatus {
+    #[prost(enumeration = "OnboardingState", tag = "1")]
+    pub state: i32,
+    /// Hostname players connect with
+    #[prost(string, tag = "2")]
+    pub hostname: ::prost::alloc::string::String,
+    /// Resolved origin: target of the \_minecraft.\_tcp SRV record, or the
+    /// hostname itself, and its addresses
+    #[prost(string, tag = "3")]
+    pub target: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "4")]
+    pub port: u32,
+    #[prost(string, repeated, tag = "5")]
+    pub addresses: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Ownership verification: the TXT record must contain the token
+    #[prost(string, tag = "6")]
+    pub verification_record: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub verification_token: ::prost::alloc::string::String,
+    #[prost(bool, tag = "8")]
+    pub ownership_verified: bool,
+    /// Status ping from a worker
+    #[prost(bool, tag = "9")]
+    pub origin_reachable: bool,
+    #[prost(string, tag = "10")]
+    pub origin_version: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "11")]
+    pub origin_latency_ms: u32,
+    /// Why the last check stopped short of active
+    #[prost(string, tag = "12")]
+    pub error: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "13")]
+    pub checked_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Switch of a backend from one origin to another without dropping
+/// connections
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct CreateBackendResponse {
-    #[prost(message, optional, tag = "1")]
-    pub backend: ::core::option::Option<Backend>,
-}
-#[derive(serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct GetBackendRequest {
+pub struct OriginSwitch {
     #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
     pub backend_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub from_origin_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub to_origin_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "OriginSwitchState", tag = "5")]
+    pub state: i32,
+    /// Grace period for connections to the old origin
+    #[prost(uint32, tag = "6")]
+    pub drain_seconds: u32,
+    /// Connections still open to the old origin, summed over the workers
+    /// reporting on the switch
+    #[prost(uint64, tag = "7")]
+    pub remaining_connections: u64,
+    #[prost(uint32, tag = "8")]
+    pub workers_reporting: u32,
+    /// Share of the grace period elapsed, 1 once completed
+    #[prost(double, tag = "9")]
+    pub progress: f64,
+    #[prost(message, optional, tag = "10")]
+    pub started_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "11")]
+    pub drain_deadline: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "12")]
+    pub completed_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Origin address found in public records
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct GetBackendResponse {
-    #[prost(message, optional, tag = "1")]
-    pub backend: ::core::option::Option<Backend>,
-}
-#[derive(serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct UpdateBackendRequest {
-    #[prost(message, optional, tag = "1")]
-    pub backend: ::core::option::Option<Backend>,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ExposureFinding {
+    #[prost(enumeration = "ExposureSource", tag = "1")]
+    pub source: i32,
+    /// Name the origin was found through
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+    /// Leaked origin address or hostname
+    #[prost(string, tag = "3")]
+    pub address: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub detail: ::prost::alloc::string::String,
 }
+/// Result of an origin concealment scan. Attackers who find the origin can
+/// bypass protection entirely.
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct UpdateBackendResponse {
-    #[prost(message, optional, tag = "1")]
-    pub backend: ::core::option::Option<Backend>,
+pub struct OriginExposureReport {
+    #[prost(bool, tag = "1")]
+    pub exposed: bool,
+    #[prost(message, repeated, tag = "2")]
+    pub findings: ::prost::alloc::vec::Vec<ExposureFinding>,
+    #[prost(uint32, tag = "3")]
+    pub names_checked: u32,
+    /// Checks that could not run
+    #[prost(string, repeated, tag = "4")]
+    pub errors: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "5")]
+    pub scanned_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Current mode of a backend
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct DeleteBackendRequest {
+pub struct BackendModeState {
     #[prost(string, tag = "1")]
     pub backend_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "BackendMode", tag = "2")]
+    pub mode: i32,
+    #[prost(string, tag = "3")]
+    pub reason: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub changed_by: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "5")]
+    pub changed_at: ::core::option::Option<super::common::Timestamp>,
+    /// Time the backend goes back to protection, unset while protected
+    #[prost(message, optional, tag = "6")]
+    pub revert_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Entry of the mode audit log of a backend
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct DeleteBackendResponse {
-    #[prost(bool, tag = "1")]
-    pub success: bool,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct BackendModeChange {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "BackendMode", tag = "3")]
+    pub from_mode: i32,
+    #[prost(enumeration = "BackendMode", tag = "4")]
+    pub to_mode: i32,
+    #[prost(string, tag = "5")]
+    pub reason: ::prost::alloc::string::String,
+    /// User who made the change, "system" for automatic reverts
+    #[prost(string, tag = "6")]
+    pub changed_by: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "7")]
+    pub changed_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "8")]
+    pub revert_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Public status page of an organization, served by the gateway at
+/// /status/{token}
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct ListBackendsRequest {
+pub struct StatusPage {
     #[prost(string, tag = "1")]
     pub organization_id: ::prost::alloc::string::String,
-    #[prost(message, optional, tag = "2")]
-    pub pagination: ::core::option::Option<super::common::Pagination>,
-    #[prost(enumeration = "BackendType", tag = "3")]
-    pub type_filter: i32,
-}
-#[derive(serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct ListBackendsResponse {
-    #[prost(message, repeated, tag = "1")]
-    pub backends: ::prost::alloc::vec::Vec<Backend>,
-    #[prost(message, optional, tag = "2")]
-    pub pagination: ::core::option::Option<super::common::PaginationInfo>,
+    #[prost(bool, tag = "2")]
+    pub enabled: bool,
+    #[prost(string, tag = "3")]
+    pub title: ::prost::alloc::string::String,
+    /// Secret part of the public URL
+    #[prost(string, tag = "4")]
+    pub token: ::prost::alloc::string::String,
+    /// Path of the page on the gateway
+    #[prost(string, tag = "5")]
+    pub path: ::prost::alloc::string::String,
+    /// Backends left off the page
+    #[prost(string, repeated, tag = "6")]
+    pub hidden_backend_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "7")]
+    pub created_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "8")]
+    pub updated_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Ban synchronization with game server plugins of a backend, served by the
+/// gateway under /plugin/v1 with the token as bearer credential
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct AddOriginRequest {
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct BanSync {
     #[prost(string, tag = "1")]
     pub backend_id: ::prost::alloc::string::String,
-    #[prost(message, optional, tag = "2")]
-    pub origin: ::core::option::Option<Origin>,
+    #[prost(bool, tag = "2")]
+    pub enabled: bool,
+    /// Secret the plugins authenticate with
+    #[prost(string, tag = "3")]
+    pub token: ::prost::alloc::string::String,
+    /// Bans currently in effect
+    #[prost(uint32, tag = "4")]
+    pub active_bans: u32,
+    /// Filter rule dropping the addresses of banned players, unset while no
+    /// ban carries an address
+    #[prost(string, tag = "5")]
+    pub rule_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "6")]
+    pub created_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "7")]
+    pub updated_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Named per-source limits of a backend, applied instead of those of the
+/// protection settings while active
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct AddOriginResponse {
-    #[prost(message, optional, tag = "1")]
-    pub origin: ::core::option::Option<Origin>,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RateLimitProfile {
+    /// Lowercase letters, digits, '-' and '\_', e.g. "restart-storm"
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub per_ip_pps: u64,
+    /// 0 = one second of per_ip_pps
+    #[prost(uint64, tag = "3")]
+    pub per_ip_burst: u64,
 }
+/// Weekly window during which a profile applies
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct UpdateOriginRequest {
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RateProfileWindow {
     #[prost(string, tag = "1")]
-    pub backend_id: ::prost::alloc::string::String,
-    #[prost(message, optional, tag = "2")]
-    pub origin: ::core::option::Option<Origin>,
+    pub profile: ::prost::alloc::string::String,
+    /// Days the window starts on, 0 = Monday; empty = every day
+    #[prost(uint32, repeated, tag = "2")]
+    pub days: ::prost::alloc::vec::Vec<u32>,
+    /// Minutes after local midnight. An end at or before the start runs past
+    /// midnight, an end equal to the start covers the whole day.
+    #[prost(uint32, tag = "3")]
+    pub start_minute: u32,
+    #[prost(uint32, tag = "4")]
+    pub end_minute: u32,
 }
+/// Rate limit profiles of a backend and the one in effect
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct UpdateOriginResponse {
-    #[prost(message, optional, tag = "1")]
-    pub origin: ::core::option::Option<Origin>,
+pub struct RateProfiles {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub profiles: ::prost::alloc::vec::Vec<RateLimitProfile>,
+    /// The first window covering the current time wins
+    #[prost(message, repeated, tag = "3")]
+    pub windows: ::prost::alloc::vec::Vec<RateProfileWindow>,
+    /// Profile outside every window; empty = the protection settings
+    #[prost(string, tag = "4")]
+    pub default_profile: ::prost::alloc::string::String,
+    /// Offset of the local time of the schedule from UTC
+    #[prost(int32, tag = "5")]
+    pub utc_offset_minutes: i32,
+    /// Profile triggered by hand, overriding the schedule until manual_until
+    #[prost(string, tag = "6")]
+    pub manual_profile: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "7")]
+    pub manual_until: ::core::option::Option<super::common::Timestamp>,
+    #[prost(string, tag = "8")]
+    pub triggered_by: ::prost::alloc::string::String,
+    /// Profile in effect; empty = the protection settings
+    #[prost(string, tag = "9")]
+    pub active_profile: ::prost::alloc::string::String,
+    /// "manual", "schedule" or "default"
+    #[prost(string, tag = "10")]
+    pub active_source: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "11")]
+    pub updated_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Restart mode of a backend: while a game server restarts and its players
+/// reconnect at once, per-source limits are relaxed and protocol validation
+/// runs at the strictest level, until ends_at
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct RemoveOriginRequest {
+pub struct RestartMode {
     #[prost(string, tag = "1")]
     pub backend_id: ::prost::alloc::string::String,
-    #[prost(string, tag = "2")]
-    pub origin_id: ::prost::alloc::string::String,
-}
-#[derive(serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct RemoveOriginResponse {
-    #[prost(bool, tag = "1")]
-    pub success: bool,
+    #[prost(bool, tag = "2")]
+    pub active: bool,
+    /// Factor applied to the per-source packet rate and burst
+    #[prost(uint32, tag = "3")]
+    pub connection_multiplier: u32,
+    #[prost(string, tag = "4")]
+    pub reason: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub started_by: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "6")]
+    pub started_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "7")]
+    pub ends_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Traffic of a backend in the current period
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, ::prost::Message)]
-pub struct UpdateProtectionRequest {
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct BackendBandwidth {
     #[prost(string, tag = "1")]
     pub backend_id: ::prost::alloc::string::String,
-    #[prost(message, optional, tag = "2")]
-    pub protection: ::core::option::Option<ProtectionSettings>,
+    #[prost(uint64, tag = "2")]
+    pub bytes_in: u64,
+    /// Part of bytes_in dropped by the filters, not counted towards the quota
+    #[prost(uint64, tag = "3")]
+    pub bytes_dropped: u64,
 }
+/// Monthly bandwidth quota of an organization: traffic passed to its
+/// backends counts towards the bandwidth of its plan, dropped traffic does not
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct UpdateProtectionResponse {
-    #[prost(message, optional, tag = "1")]
-    pub protection: ::core::option::Option<ProtectionSettings>,
+pub struct BandwidthQuota {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "BandwidthQuotaAction", tag = "2")]
+    pub action: i32,
+    /// Per-connection bandwidth while throttled
+    #[prost(uint64, tag = "3")]
+    pub throttle_bytes_per_second: u64,
+    /// Usage in percent of the limit logged as a warning; 0 = no warning
+    #[prost(uint32, tag = "4")]
+    pub warn_percent: u32,
+    /// Bandwidth of the plan
+    #[prost(uint64, tag = "5")]
+    pub limit_bytes: u64,
+    #[prost(uint64, tag = "6")]
+    pub used_bytes: u64,
+    /// Current UTC calendar month
+    #[prost(message, optional, tag = "7")]
+    pub period_start: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "8")]
+    pub period_end: ::core::option::Option<super::common::Timestamp>,
+    /// Action in effect, unspecified while under the limit
+    #[prost(enumeration = "BandwidthQuotaAction", tag = "9")]
+    pub applied_action: i32,
+    #[prost(message, repeated, tag = "10")]
+    pub backends: ::prost::alloc::vec::Vec<BackendBandwidth>,
+    #[prost(string, tag = "11")]
+    pub updated_by: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "12")]
+    pub updated_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Value changed between two configuration revisions
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct SetProtectionLevelRequest {
+pub struct ConfigChange {
+    /// Dotted path of the value, e.g. `rules.<rule id>.action` or
+    /// `protection.learning.enabled`
     #[prost(string, tag = "1")]
-    pub backend_id: ::prost::alloc::string::String,
-    #[prost(enumeration = "ProtectionLevel", tag = "2")]
-    pub level: i32,
-}
-#[derive(serde::Serialize, serde::Deserialize)]
-#[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct SetProtectionLevelResponse {
-    #[prost(enumeration = "ProtectionLevel", tag = "1")]
-    pub level: i32,
+    pub path: ::prost::alloc::string::String,
+    #[prost(enumeration = "ConfigChangeKind", tag = "2")]
+    pub kind: i32,
+    /// JSON encoded values, empty when absent
+    #[prost(string, tag = "3")]
+    pub before: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub after: ::prost::alloc::string::String,
 }
+/// Immutable revision of the protection settings and filter rules of a
+/// backend
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct GetBackendStatusRequest {
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConfigRevision {
     #[prost(string, tag = "1")]
     pub backend_id: ::prost::alloc::string::String,
+    /// Increases by one with every change of the backend
+    #[prost(uint64, tag = "2")]
+    pub revision: u64,
+    /// What changed, e.g. "rule updated"
+    #[prost(string, tag = "3")]
+    pub summary: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub changed_by: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "5")]
+    pub created_at: ::core::option::Option<super::common::Timestamp>,
+    /// Revision restored by a rollback, 0 otherwise
+    #[prost(uint64, tag = "6")]
+    pub restored_from: u64,
+    /// Changes from the previous revision, left empty in lists
+    #[prost(message, repeated, tag = "7")]
+    pub changes: ::prost::alloc::vec::Vec<ConfigChange>,
+    #[prost(uint32, tag = "8")]
+    pub change_count: u32,
 }
+/// Two-person approval policy of an organization
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct GetBackendStatusResponse {
-    #[prost(message, optional, tag = "1")]
-    pub status: ::core::option::Option<BackendStatus>,
+pub struct ApprovalPolicy {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub enabled: bool,
+    /// Least organization role of approvers: "member", "admin" or "owner"
+    #[prost(string, tag = "3")]
+    pub approver_role: ::prost::alloc::string::String,
+    /// How long an action waits for a decision
+    #[prost(uint32, tag = "4")]
+    pub expiry_seconds: u32,
+    #[prost(string, tag = "5")]
+    pub updated_by: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "6")]
+    pub updated_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Action waiting for or decided on by a second member
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct WatchBackendStatusRequest {
+pub struct PendingAction {
     #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
     pub backend_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "ApprovalActionKind", tag = "4")]
+    pub kind: i32,
+    /// What the action does, e.g. "Delete backend lobby"
+    #[prost(string, tag = "5")]
+    pub description: ::prost::alloc::string::String,
+    #[prost(enumeration = "PendingActionStatus", tag = "6")]
+    pub status: i32,
+    #[prost(string, tag = "7")]
+    pub requested_by: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub decided_by: ::prost::alloc::string::String,
+    /// Reason given with the decision
+    #[prost(string, tag = "9")]
+    pub reason: ::prost::alloc::string::String,
+    /// Why applying an approved action failed
+    #[prost(string, tag = "10")]
+    pub error: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "11")]
+    pub created_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "12")]
+    pub expires_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "13")]
+    pub decided_at: ::core::option::Option<super::common::Timestamp>,
 }
+/// Exemption token of a backend: the tester addresses of a load test bypass
+/// filtering until expires_at, and the backend's traffic is kept out of the
+/// attack detection baseline meanwhile
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
-pub struct AddDomainRequest {
+pub struct ExemptionToken {
     #[prost(string, tag = "1")]
-    pub backend_id: ::prost::alloc::string::String,
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(bool, tag = "3")]
+    pub active: bool,
+    /// Exempted source addresses
+    #[prost(string, repeated, tag = "4")]
+    pub ips: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, tag = "5")]
+    pub reason: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub created_by: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "7")]
+    pub created_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "8")]
+    pub expires_at: ::core::option::Option<super::common::Timestamp>,
+}
+/// Audit log entry of an exemption token
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ExemptionTokenEvent {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub token_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub backend_id: ::prost::alloc::string::String,
+    /// "created", "revoked" or "expired"
+    #[prost(string, tag = "4")]
+    pub action: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub actor: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "6")]
+    pub created_at: ::core::option::Option<super::common::Timestamp>,
+}
+/// Request/Response messages
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreateBackendRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub backend: ::core::option::Option<Backend>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreateBackendResponse {
+    #[prost(message, optional, tag = "1")]
+    pub backend: ::core::option::Option<Backend>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetBackendRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetBackendResponse {
+    #[prost(message, optional, tag = "1")]
+    pub backend: ::core::option::Option<Backend>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateBackendRequest {
+    #[prost(message, optional, tag = "1")]
+    pub backend: ::core::option::Option<Backend>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateBackendResponse {
+    #[prost(message, optional, tag = "1")]
+    pub backend: ::core::option::Option<Backend>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct DeleteBackendRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct DeleteBackendResponse {
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ListBackendsRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub pagination: ::core::option::Option<super::common::Pagination>,
+    #[prost(enumeration = "BackendType", tag = "3")]
+    pub type_filter: i32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListBackendsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub backends: ::prost::alloc::vec::Vec<Backend>,
+    #[prost(message, optional, tag = "2")]
+    pub pagination: ::core::option::Option<super::common::PaginationInfo>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddOriginRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub origin: ::core::option::Option<Origin>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddOriginResponse {
+    #[prost(message, optional, tag = "1")]
+    pub origin: ::core::option::Option<Origin>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateOriginRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub origin: ::core::option::Option<Origin>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateOriginResponse {
+    #[prost(message, optional, tag = "1")]
+    pub origin: ::core::option::Option<Origin>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RemoveOriginRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub origin_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RemoveOriginResponse {
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateProtectionRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub protection: ::core::option::Option<ProtectionSettings>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateProtectionResponse {
+    #[prost(message, optional, tag = "1")]
+    pub protection: ::core::option::Option<ProtectionSettings>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetProtectionLevelRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "ProtectionLevel", tag = "2")]
+    pub level: i32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetProtectionLevelResponse {
+    #[prost(enumeration = "ProtectionLevel", tag = "1")]
+    pub level: i32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetHoneypotRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub honeypot: ::core::option::Option<HoneypotSettings>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetHoneypotResponse {
+    #[prost(message, optional, tag = "1")]
+    pub honeypot: ::core::option::Option<HoneypotSettings>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetBlockResponsesRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub block_responses: ::core::option::Option<BlockResponseSettings>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetBlockResponsesResponse {
+    #[prost(message, optional, tag = "1")]
+    pub block_responses: ::core::option::Option<BlockResponseSettings>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetBackendStatusRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetBackendStatusResponse {
+    #[prost(message, optional, tag = "1")]
+    pub status: ::core::option::Option<BackendStatus>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct WatchBackendStatusRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct AddDomainRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub domain: ::prost::alloc::string::String,
 }
@@ -683,505 +1297,1430 @@ pub struct VerifyDomainResponse {
     #[prost(string, tag = "2")]
     pub error: ::prost::alloc::string::String,
 }
-/// Backend type
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum BackendType {
-    Unspecified = 0,
-    Http = 1,
-    Https = 2,
-    Tcp = 3,
-    Udp = 4,
-    MinecraftJava = 5,
-    MinecraftBedrock = 6,
-    Quic = 7,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct StartOnboardingRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub hostname: ::prost::alloc::string::String,
+    /// 0 = from the SRV record, or the default port of the backend type
+    #[prost(uint32, tag = "3")]
+    pub port: u32,
 }
-impl BackendType {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "BACKEND_TYPE_UNSPECIFIED",
-            Self::Http => "BACKEND_TYPE_HTTP",
-            Self::Https => "BACKEND_TYPE_HTTPS",
-            Self::Tcp => "BACKEND_TYPE_TCP",
-            Self::Udp => "BACKEND_TYPE_UDP",
-            Self::MinecraftJava => "BACKEND_TYPE_MINECRAFT_JAVA",
-            Self::MinecraftBedrock => "BACKEND_TYPE_MINECRAFT_BEDROCK",
-            Self::Quic => "BACKEND_TYPE_QUIC",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "BACKEND_TYPE_UNSPECIFIED" => Some(Self::Unspecified),
-            "BACKEND_TYPE_HTTP" => Some(Self::Http),
-            "BACKEND_TYPE_HTTPS" => Some(Self::Https),
-            "BACKEND_TYPE_TCP" => Some(Self::Tcp),
-            "BACKEND_TYPE_UDP" => Some(Self::Udp),
-            "BACKEND_TYPE_MINECRAFT_JAVA" => Some(Self::MinecraftJava),
-            "BACKEND_TYPE_MINECRAFT_BEDROCK" => Some(Self::MinecraftBedrock),
-            "BACKEND_TYPE_QUIC" => Some(Self::Quic),
-            _ => None,
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct StartOnboardingResponse {
+    #[prost(message, optional, tag = "1")]
+    pub onboarding: ::core::option::Option<OnboardingStatus>,
 }
-/// Geographic routing strategy
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum GeoRoutingStrategy {
-    Unspecified = 0,
-    /// Route to geographically closest origin based on country/region
-    Proximity = 1,
-    /// Route based on explicit region-to-origin mappings
-    Mapping = 2,
-    /// Route based on measured latency (requires latency probing)
-    Latency = 3,
-    /// Route to origin in same continent, fallback to closest
-    Continent = 4,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct CheckOnboardingRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
 }
-impl GeoRoutingStrategy {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "GEO_ROUTING_STRATEGY_UNSPECIFIED",
-            Self::Proximity => "GEO_ROUTING_STRATEGY_PROXIMITY",
-            Self::Mapping => "GEO_ROUTING_STRATEGY_MAPPING",
-            Self::Latency => "GEO_ROUTING_STRATEGY_LATENCY",
-            Self::Continent => "GEO_ROUTING_STRATEGY_CONTINENT",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "GEO_ROUTING_STRATEGY_UNSPECIFIED" => Some(Self::Unspecified),
-            "GEO_ROUTING_STRATEGY_PROXIMITY" => Some(Self::Proximity),
-            "GEO_ROUTING_STRATEGY_MAPPING" => Some(Self::Mapping),
-            "GEO_ROUTING_STRATEGY_LATENCY" => Some(Self::Latency),
-            "GEO_ROUTING_STRATEGY_CONTINENT" => Some(Self::Continent),
-            _ => None,
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct CheckOnboardingResponse {
+    #[prost(message, optional, tag = "1")]
+    pub onboarding: ::core::option::Option<OnboardingStatus>,
 }
-/// Type of geographic region
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum GeoRegionType {
-    Unspecified = 0,
-    Country = 1,
-    Continent = 2,
-    Custom = 3,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetOriginExposureRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
 }
-impl GeoRegionType {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "GEO_REGION_TYPE_UNSPECIFIED",
-            Self::Country => "GEO_REGION_TYPE_COUNTRY",
-            Self::Continent => "GEO_REGION_TYPE_CONTINENT",
-            Self::Custom => "GEO_REGION_TYPE_CUSTOM",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "GEO_REGION_TYPE_UNSPECIFIED" => Some(Self::Unspecified),
-            "GEO_REGION_TYPE_COUNTRY" => Some(Self::Country),
-            "GEO_REGION_TYPE_CONTINENT" => Some(Self::Continent),
-            "GEO_REGION_TYPE_CUSTOM" => Some(Self::Custom),
-            _ => None,
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetOriginExposureResponse {
+    /// Unset if the backend was never scanned
+    #[prost(message, optional, tag = "1")]
+    pub report: ::core::option::Option<OriginExposureReport>,
 }
-/// Load balancing algorithm
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum LoadBalancerAlgorithm {
-    Unspecified = 0,
-    RoundRobin = 1,
-    LeastConnections = 2,
-    Random = 3,
-    IpHash = 4,
-    Weighted = 5,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ScanOriginExposureRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
 }
-impl LoadBalancerAlgorithm {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "LOAD_BALANCER_ALGORITHM_UNSPECIFIED",
-            Self::RoundRobin => "LOAD_BALANCER_ALGORITHM_ROUND_ROBIN",
-            Self::LeastConnections => "LOAD_BALANCER_ALGORITHM_LEAST_CONNECTIONS",
-            Self::Random => "LOAD_BALANCER_ALGORITHM_RANDOM",
-            Self::IpHash => "LOAD_BALANCER_ALGORITHM_IP_HASH",
-            Self::Weighted => "LOAD_BALANCER_ALGORITHM_WEIGHTED",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "LOAD_BALANCER_ALGORITHM_UNSPECIFIED" => Some(Self::Unspecified),
-            "LOAD_BALANCER_ALGORITHM_ROUND_ROBIN" => Some(Self::RoundRobin),
-            "LOAD_BALANCER_ALGORITHM_LEAST_CONNECTIONS" => Some(Self::LeastConnections),
-            "LOAD_BALANCER_ALGORITHM_RANDOM" => Some(Self::Random),
-            "LOAD_BALANCER_ALGORITHM_IP_HASH" => Some(Self::IpHash),
-            "LOAD_BALANCER_ALGORITHM_WEIGHTED" => Some(Self::Weighted),
-            _ => None,
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScanOriginExposureResponse {
+    #[prost(message, optional, tag = "1")]
+    pub report: ::core::option::Option<OriginExposureReport>,
 }
-/// Protection level
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum ProtectionLevel {
-    Unspecified = 0,
-    Off = 1,
-    Low = 2,
-    Medium = 3,
-    High = 4,
-    UnderAttack = 5,
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SwitchOriginRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub from_origin_id: ::prost::alloc::string::String,
+    /// 0 = default grace period (5 minutes)
+    #[prost(uint32, tag = "5")]
+    pub drain_seconds: u32,
+    /// Origin to switch to: an existing origin, or a new one added to the
+    /// backend
+    #[prost(oneof = "switch_origin_request::To", tags = "3, 4")]
+    pub to: ::core::option::Option<switch_origin_request::To>,
 }
-impl ProtectionLevel {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "PROTECTION_LEVEL_UNSPECIFIED",
-            Self::Off => "PROTECTION_LEVEL_OFF",
-            Self::Low => "PROTECTION_LEVEL_LOW",
-            Self::Medium => "PROTECTION_LEVEL_MEDIUM",
-            Self::High => "PROTECTION_LEVEL_HIGH",
-            Self::UnderAttack => "PROTECTION_LEVEL_UNDER_ATTACK",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "PROTECTION_LEVEL_UNSPECIFIED" => Some(Self::Unspecified),
-            "PROTECTION_LEVEL_OFF" => Some(Self::Off),
-            "PROTECTION_LEVEL_LOW" => Some(Self::Low),
-            "PROTECTION_LEVEL_MEDIUM" => Some(Self::Medium),
-            "PROTECTION_LEVEL_HIGH" => Some(Self::High),
-            "PROTECTION_LEVEL_UNDER_ATTACK" => Some(Self::UnderAttack),
-            _ => None,
-        }
+/// Nested message and enum types in `SwitchOriginRequest`.
+pub mod switch_origin_request {
+    /// Origin to switch to: an existing origin, or a new one added to the
+    /// backend
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum To {
+        #[prost(string, tag = "3")]
+        ToOriginId(::prost::alloc::string::String),
+        #[prost(message, tag = "4")]
+        NewOrigin(super::Origin),
     }
 }
-/// Challenge types
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum ChallengeType {
-    Unspecified = 0,
-    Javascript = 1,
-    Captcha = 2,
-    /// Proof of work
-    Pow = 3,
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SwitchOriginResponse {
+    #[prost(message, optional, tag = "1")]
+    pub origin_switch: ::core::option::Option<OriginSwitch>,
 }
-impl ChallengeType {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "CHALLENGE_TYPE_UNSPECIFIED",
-            Self::Javascript => "CHALLENGE_TYPE_JAVASCRIPT",
-            Self::Captcha => "CHALLENGE_TYPE_CAPTCHA",
-            Self::Pow => "CHALLENGE_TYPE_POW",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "CHALLENGE_TYPE_UNSPECIFIED" => Some(Self::Unspecified),
-            "CHALLENGE_TYPE_JAVASCRIPT" => Some(Self::Javascript),
-            "CHALLENGE_TYPE_CAPTCHA" => Some(Self::Captcha),
-            "CHALLENGE_TYPE_POW" => Some(Self::Pow),
-            _ => None,
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetOriginSwitchRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
 }
-/// GeoIP filtering mode
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum GeoIpMode {
-    Unspecified = 0,
-    Disabled = 1,
-    AllowList = 2,
-    BlockList = 3,
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetOriginSwitchResponse {
+    /// Latest switch of the backend, unset if it never switched origin
+    #[prost(message, optional, tag = "1")]
+    pub origin_switch: ::core::option::Option<OriginSwitch>,
 }
-impl GeoIpMode {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "GEO_IP_MODE_UNSPECIFIED",
-            Self::Disabled => "GEO_IP_MODE_DISABLED",
-            Self::AllowList => "GEO_IP_MODE_ALLOW_LIST",
-            Self::BlockList => "GEO_IP_MODE_BLOCK_LIST",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "GEO_IP_MODE_UNSPECIFIED" => Some(Self::Unspecified),
-            "GEO_IP_MODE_DISABLED" => Some(Self::Disabled),
-            "GEO_IP_MODE_ALLOW_LIST" => Some(Self::AllowList),
-            "GEO_IP_MODE_BLOCK_LIST" => Some(Self::BlockList),
-            _ => None,
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct CancelOriginSwitchRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
 }
-/// WAF mode
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum WafMode {
-    Unspecified = 0,
-    Detect = 1,
-    Block = 2,
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelOriginSwitchResponse {
+    #[prost(message, optional, tag = "1")]
+    pub origin_switch: ::core::option::Option<OriginSwitch>,
 }
-impl WafMode {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "WAF_MODE_UNSPECIFIED",
-            Self::Detect => "WAF_MODE_DETECT",
-            Self::Block => "WAF_MODE_BLOCK",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "WAF_MODE_UNSPECIFIED" => Some(Self::Unspecified),
-            "WAF_MODE_DETECT" => Some(Self::Detect),
-            "WAF_MODE_BLOCK" => Some(Self::Block),
-            _ => None,
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetStatusPageRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
 }
-/// Bot protection mode
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
-#[repr(i32)]
-pub enum BotProtectionMode {
-    Unspecified = 0,
-    AllowAll = 1,
-    ChallengeSuspicious = 2,
-    BlockAllBots = 3,
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetStatusPageResponse {
+    /// Unset if the organization never configured a status page
+    #[prost(message, optional, tag = "1")]
+    pub status_page: ::core::option::Option<StatusPage>,
 }
-impl BotProtectionMode {
-    /// String value of the enum field names used in the ProtoBuf definition.
-    ///
-    /// The values are not transformed in any way and thus are considered stable
-    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
-    pub fn as_str_name(&self) -> &'static str {
-        match self {
-            Self::Unspecified => "BOT_PROTECTION_MODE_UNSPECIFIED",
-            Self::AllowAll => "BOT_PROTECTION_MODE_ALLOW_ALL",
-            Self::ChallengeSuspicious => "BOT_PROTECTION_MODE_CHALLENGE_SUSPICIOUS",
-            Self::BlockAllBots => "BOT_PROTECTION_MODE_BLOCK_ALL_BOTS",
-        }
-    }
-    /// Creates an enum from field names used in the ProtoBuf definition.
-    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
-        match value {
-            "BOT_PROTECTION_MODE_UNSPECIFIED" => Some(Self::Unspecified),
-            "BOT_PROTECTION_MODE_ALLOW_ALL" => Some(Self::AllowAll),
-            "BOT_PROTECTION_MODE_CHALLENGE_SUSPICIOUS" => Some(Self::ChallengeSuspicious),
-            "BOT_PROTECTION_MODE_BLOCK_ALL_BOTS" => Some(Self::BlockAllBots),
-            _ => None,
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct UpdateStatusPageRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub enabled: bool,
+    /// Empty = "Service Status"
+    #[prost(string, tag = "3")]
+    pub title: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "4")]
+    pub hidden_backend_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
-/// Generated client implementations.
-pub mod backend_service_client {
-    #![allow(
-        unused_variables,
-        dead_code,
-        missing_docs,
-        clippy::wildcard_imports,
-        clippy::let_unit_value,
-    )]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
-    /// Backend service
-    #[derive(Debug, Clone)]
-    pub struct BackendServiceClient<T> {
-        inner: tonic::client::Grpc<T>,
-    }
-    impl BackendServiceClient<tonic::transport::Channel> {
-        /// Attempt to create a new client by connecting to a given endpoint.
-        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
-        where
-            D: TryInto<tonic::transport::Endpoint>,
-            D::Error: Into<StdError>,
-        {
-            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
-            Ok(Self::new(conn))
-        }
-    }
-    impl<T> BackendServiceClient<T>
-    where
-        T: tonic::client::GrpcService<tonic::body::Body>,
-        T::Error: Into<StdError>,
-        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
-        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
-    {
-        pub fn new(inner: T) -> Self {
-            let inner = tonic::client::Grpc::new(inner);
-            Self { inner }
-        }
-        pub fn with_origin(inner: T, origin: Uri) -> Self {
-            let inner = tonic::client::Grpc::with_origin(inner, origin);
-            Self { inner }
-        }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> BackendServiceClient<InterceptedService<T, F>>
-        where
-            F: tonic::service::Interceptor,
-            T::ResponseBody: Default,
-            T: tonic::codegen::Service<
-                http::Request<tonic::body::Body>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::Body>>::ResponseBody,
-                >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::Body>,
-            >>::Error: Into<StdError> + std::marker::Send + std::marker::Sync,
-        {
-            BackendServiceClient::new(InterceptedService::new(inner, interceptor))
-        }
-        /// Compress requests with the given encoding.
-        ///
-        /// This requires the server to support it otherwise it might respond with an
-        /// error.
-        #[must_use]
-        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.send_compressed(encoding);
-            self
-        }
-        /// Enable decompressing responses.
-        #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.accept_compressed(encoding);
-            self
-        }
-        /// Limits the maximum size of a decoded message.
-        ///
-        /// Default: `4MB`
-        #[must_use]
-        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_decoding_message_size(limit);
-            self
-        }
-        /// Limits the maximum size of an encoded message.
-        ///
-        /// Default: `usize::MAX`
-        #[must_use]
-        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_encoding_message_size(limit);
-            self
-        }
-        /// Backend management
-        pub async fn create_backend(
-            &mut self,
-            request: impl tonic::IntoRequest<super::CreateBackendRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::CreateBackendResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::unknown(
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
-            let codec = tonic_prost::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/CreateBackend",
-            );
-            let mut req = request.into_request();
-            req.extensions_mut()
-                .insert(
-                    GrpcMethod::new(
-                        "pistonprotection.backend.BackendService",
-                        "CreateBackend",
-                    ),
-                );
-            self.inner.unary(req, path, codec).await
-        }
-        pub async fn get_backend(
-            &mut self,
-            request: impl tonic::IntoRequest<super::GetBackendRequest>,
-        ) -> std::result::Result<
-            tonic::Response<super::GetBackendResponse>,
-            tonic::Status,
-        > {
-            self.inner
-                .ready()
-                .await
-                .map_err(|e| {
-                    tonic::Status::unknown(
-                        format!("Service was not ready: {}", e.into()),
-                    )
-                })?;
-            let codec = tonic_prost::ProstCodec::default();
-            let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/GetBackend",
-            );
-            let mut req = request.into_request();
-            req.extensions_mut()
-                .insert(
-                    GrpcMethod::new(
-                        "pistonprotection.backend.BackendService",
-                        "GetBackend",
-                    ),
-                );
-            self.inner.unary(req, path, codec).await
-        }
-        pub async fn update_backend(
-            &mut self,
-            request: impl tonic::IntoRequest<super::UpdateBackendRequest>,
-        ) -> std::result::Result<
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct UpdateStatusPageResponse {
+    #[prost(message, optional, tag = "1")]
+    pub status_page: ::core::option::Option<StatusPage>,
+}
+/// Replace the token of a status page, invalidating the old URL
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RotateStatusPageTokenRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RotateStatusPageTokenResponse {
+    #[prost(message, optional, tag = "1")]
+    pub status_page: ::core::option::Option<StatusPage>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetBackendModeRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "BackendMode", tag = "2")]
+    pub mode: i32,
+    /// Time until the backend goes back to protection; 0 = default (1 hour).
+    /// Ignored when switching back to protection.
+    #[prost(uint32, tag = "3")]
+    pub revert_after_seconds: u32,
+    #[prost(string, tag = "4")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetBackendModeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub state: ::core::option::Option<BackendModeState>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetBackendModeRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    /// Audit log entries returned, newest first; 0 = 20
+    #[prost(uint32, tag = "2")]
+    pub history_limit: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetBackendModeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub state: ::core::option::Option<BackendModeState>,
+    #[prost(message, repeated, tag = "2")]
+    pub history: ::prost::alloc::vec::Vec<BackendModeChange>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetBanSyncRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetBanSyncResponse {
+    /// Unset if ban synchronization was never configured for the backend
+    #[prost(message, optional, tag = "1")]
+    pub ban_sync: ::core::option::Option<BanSync>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct UpdateBanSyncRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub enabled: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct UpdateBanSyncResponse {
+    #[prost(message, optional, tag = "1")]
+    pub ban_sync: ::core::option::Option<BanSync>,
+}
+/// Replace the token of the plugins of a backend, locking out the old one
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RotateBanSyncTokenRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RotateBanSyncTokenResponse {
+    #[prost(message, optional, tag = "1")]
+    pub ban_sync: ::core::option::Option<BanSync>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetRateProfilesRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetRateProfilesResponse {
+    #[prost(message, optional, tag = "1")]
+    pub rate_profiles: ::core::option::Option<RateProfiles>,
+}
+/// Replace the profiles and schedule of a backend; no profiles removes them
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateRateProfilesRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub profiles: ::prost::alloc::vec::Vec<RateLimitProfile>,
+    #[prost(message, repeated, tag = "3")]
+    pub windows: ::prost::alloc::vec::Vec<RateProfileWindow>,
+    #[prost(string, tag = "4")]
+    pub default_profile: ::prost::alloc::string::String,
+    #[prost(int32, tag = "5")]
+    pub utc_offset_minutes: i32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateRateProfilesResponse {
+    #[prost(message, optional, tag = "1")]
+    pub rate_profiles: ::core::option::Option<RateProfiles>,
+}
+/// Apply a profile by hand, e.g. ahead of an event or a restart
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct TriggerRateProfileRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    /// Empty = back to the schedule
+    #[prost(string, tag = "2")]
+    pub profile: ::prost::alloc::string::String,
+    /// Time until the schedule applies again; 0 = default (1 hour)
+    #[prost(uint32, tag = "3")]
+    pub duration_seconds: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TriggerRateProfileResponse {
+    #[prost(message, optional, tag = "1")]
+    pub rate_profiles: ::core::option::Option<RateProfiles>,
+}
+/// Put a backend in restart mode, or extend it if already active
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct StartRestartModeRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    /// Time until the mode ends; 0 = default (10 minutes)
+    #[prost(uint32, tag = "2")]
+    pub duration_minutes: u32,
+    /// 0 = default (5)
+    #[prost(uint32, tag = "3")]
+    pub connection_multiplier: u32,
+    #[prost(string, tag = "4")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct StartRestartModeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub restart_mode: ::core::option::Option<RestartMode>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct StopRestartModeRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct StopRestartModeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub restart_mode: ::core::option::Option<RestartMode>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetRestartModeRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetRestartModeResponse {
+    #[prost(message, optional, tag = "1")]
+    pub restart_mode: ::core::option::Option<RestartMode>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetBandwidthQuotaRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetBandwidthQuotaResponse {
+    #[prost(message, optional, tag = "1")]
+    pub bandwidth_quota: ::core::option::Option<BandwidthQuota>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct UpdateBandwidthQuotaRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "BandwidthQuotaAction", tag = "2")]
+    pub action: i32,
+    /// 0 = default (1 MiB/s)
+    #[prost(uint64, tag = "3")]
+    pub throttle_bytes_per_second: u64,
+    #[prost(uint32, tag = "4")]
+    pub warn_percent: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateBandwidthQuotaResponse {
+    #[prost(message, optional, tag = "1")]
+    pub bandwidth_quota: ::core::option::Option<BandwidthQuota>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ListConfigRevisionsRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub pagination: ::core::option::Option<super::common::Pagination>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListConfigRevisionsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub revisions: ::prost::alloc::vec::Vec<ConfigRevision>,
+    #[prost(message, optional, tag = "2")]
+    pub pagination: ::core::option::Option<super::common::PaginationInfo>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetConfigRevisionRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub revision: u64,
+    /// Revision the changes are computed from; 0 = the previous revision
+    #[prost(uint64, tag = "3")]
+    pub compare_to: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetConfigRevisionResponse {
+    #[prost(message, optional, tag = "1")]
+    pub revision: ::core::option::Option<ConfigRevision>,
+    /// JSON encoded configuration of the revision
+    #[prost(string, tag = "2")]
+    pub snapshot: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RollbackConfigRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    /// Revision to restore
+    #[prost(uint64, tag = "2")]
+    pub revision: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RollbackConfigResponse {
+    /// Revision recording the rollback
+    #[prost(message, optional, tag = "1")]
+    pub revision: ::core::option::Option<ConfigRevision>,
+    /// Conflicts between the restored rules
+    #[prost(string, repeated, tag = "2")]
+    pub warnings: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetApprovalPolicyRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetApprovalPolicyResponse {
+    #[prost(message, optional, tag = "1")]
+    pub approval_policy: ::core::option::Option<ApprovalPolicy>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct UpdateApprovalPolicyRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub enabled: bool,
+    /// Empty = "admin"
+    #[prost(string, tag = "3")]
+    pub approver_role: ::prost::alloc::string::String,
+    /// 0 = default (24 hours)
+    #[prost(uint32, tag = "4")]
+    pub expiry_seconds: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct UpdateApprovalPolicyResponse {
+    #[prost(message, optional, tag = "1")]
+    pub approval_policy: ::core::option::Option<ApprovalPolicy>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ListPendingActionsRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub pagination: ::core::option::Option<super::common::Pagination>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListPendingActionsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub actions: ::prost::alloc::vec::Vec<PendingAction>,
+    #[prost(message, optional, tag = "2")]
+    pub pagination: ::core::option::Option<super::common::PaginationInfo>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ApprovePendingActionRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ApprovePendingActionResponse {
+    #[prost(message, optional, tag = "1")]
+    pub action: ::core::option::Option<PendingAction>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RejectPendingActionRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RejectPendingActionResponse {
+    #[prost(message, optional, tag = "1")]
+    pub action: ::core::option::Option<PendingAction>,
+}
+/// Exempt tester addresses of a backend for the length of a load test
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct CreateExemptionTokenRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub ips: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Time until the token expires; 0 = default (1 hour)
+    #[prost(uint32, tag = "3")]
+    pub duration_minutes: u32,
+    #[prost(string, tag = "4")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct CreateExemptionTokenResponse {
+    #[prost(message, optional, tag = "1")]
+    pub token: ::core::option::Option<ExemptionToken>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RevokeExemptionTokenRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct RevokeExemptionTokenResponse {
+    #[prost(message, optional, tag = "1")]
+    pub token: ::core::option::Option<ExemptionToken>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ListExemptionTokensRequest {
+    #[prost(string, tag = "1")]
+    pub backend_id: ::prost::alloc::string::String,
+    /// Audit log entries returned, newest first; 0 = 20
+    #[prost(uint32, tag = "2")]
+    pub history_limit: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListExemptionTokensResponse {
+    /// Tokens not yet expired or revoked
+    #[prost(message, repeated, tag = "1")]
+    pub tokens: ::prost::alloc::vec::Vec<ExemptionToken>,
+    #[prost(message, repeated, tag = "2")]
+    pub history: ::prost::alloc::vec::Vec<ExemptionTokenEvent>,
+}
+/// Backend type
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum BackendType {
+    Unspecified = 0,
+    Http = 1,
+    Https = 2,
+    Tcp = 3,
+    Udp = 4,
+    MinecraftJava = 5,
+    MinecraftBedrock = 6,
+    Quic = 7,
+}
+impl BackendType {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "BACKEND_TYPE_UNSPECIFIED",
+            Self::Http => "BACKEND_TYPE_HTTP",
+            Self::Https => "BACKEND_TYPE_HTTPS",
+            Self::Tcp => "BACKEND_TYPE_TCP",
+            Self::Udp => "BACKEND_TYPE_UDP",
+            Self::MinecraftJava => "BACKEND_TYPE_MINECRAFT_JAVA",
+            Self::MinecraftBedrock => "BACKEND_TYPE_MINECRAFT_BEDROCK",
+            Self::Quic => "BACKEND_TYPE_QUIC",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "BACKEND_TYPE_UNSPECIFIED" => Some(Self::Unspecified),
+            "BACKEND_TYPE_HTTP" => Some(Self::Http),
+            "BACKEND_TYPE_HTTPS" => Some(Self::Https),
+            "BACKEND_TYPE_TCP" => Some(Self::Tcp),
+            "BACKEND_TYPE_UDP" => Some(Self::Udp),
+            "BACKEND_TYPE_MINECRAFT_JAVA" => Some(Self::MinecraftJava),
+            "BACKEND_TYPE_MINECRAFT_BEDROCK" => Some(Self::MinecraftBedrock),
+            "BACKEND_TYPE_QUIC" => Some(Self::Quic),
+            _ => None,
+        }
+    }
+}
+/// Geographic routing strategy
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum GeoRoutingStrategy {
+    Unspecified = 0,
+    /// Route to geographically closest origin based on country/region
+    Proximity = 1,
+    /// Route based on explicit region-to-origin mappings
+    Mapping = 2,
+    /// Route based on measured latency (requires latency probing)
+    Latency = 3,
+    /// Route to origin in same continent, fallback to closest
+    Continent = 4,
+}
+impl GeoRoutingStrategy {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "GEO_ROUTING_STRATEGY_UNSPECIFIED",
+            Self::Proximity => "GEO_ROUTING_STRATEGY_PROXIMITY",
+            Self::Mapping => "GEO_ROUTING_STRATEGY_MAPPING",
+            Self::Latency => "GEO_ROUTING_STRATEGY_LATENCY",
+            Self::Continent => "GEO_ROUTING_STRATEGY_CONTINENT",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GEO_ROUTING_STRATEGY_UNSPECIFIED" => Some(Self::Unspecified),
+            "GEO_ROUTING_STRATEGY_PROXIMITY" => Some(Self::Proximity),
+            "GEO_ROUTING_STRATEGY_MAPPING" => Some(Self::Mapping),
+            "GEO_ROUTING_STRATEGY_LATENCY" => Some(Self::Latency),
+            "GEO_ROUTING_STRATEGY_CONTINENT" => Some(Self::Continent),
+            _ => None,
+        }
+    }
+}
+/// Type of geographic region
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum GeoRegionType {
+    Unspecified = 0,
+    Country = 1,
+    Continent = 2,
+    Custom = 3,
+}
+impl GeoRegionType {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "GEO_REGION_TYPE_UNSPECIFIED",
+            Self::Country => "GEO_REGION_TYPE_COUNTRY",
+            Self::Continent => "GEO_REGION_TYPE_CONTINENT",
+            Self::Custom => "GEO_REGION_TYPE_CUSTOM",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GEO_REGION_TYPE_UNSPECIFIED" => Some(Self::Unspecified),
+            "GEO_REGION_TYPE_COUNTRY" => Some(Self::Country),
+            "GEO_REGION_TYPE_CONTINENT" => Some(Self::Continent),
+            "GEO_REGION_TYPE_CUSTOM" => Some(Self::Custom),
+            _ => None,
+        }
+    }
+}
+/// Load balancing algorithm
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum LoadBalancerAlgorithm {
+    Unspecified = 0,
+    RoundRobin = 1,
+    LeastConnections = 2,
+    Random = 3,
+    IpHash = 4,
+    Weighted = 5,
+}
+impl LoadBalancerAlgorithm {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "LOAD_BALANCER_ALGORITHM_UNSPECIFIED",
+            Self::RoundRobin => "LOAD_BALANCER_ALGORITHM_ROUND_ROBIN",
+            Self::LeastConnections => "LOAD_BALANCER_ALGORITHM_LEAST_CONNECTIONS",
+            Self::Random => "LOAD_BALANCER_ALGORITHM_RANDOM",
+            Self::IpHash => "LOAD_BALANCER_ALGORITHM_IP_HASH",
+            Self::Weighted => "LOAD_BALANCER_ALGORITHM_WEIGHTED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "LOAD_BALANCER_ALGORITHM_UNSPECIFIED" => Some(Self::Unspecified),
+            "LOAD_BALANCER_ALGORITHM_ROUND_ROBIN" => Some(Self::RoundRobin),
+            "LOAD_BALANCER_ALGORITHM_LEAST_CONNECTIONS" => Some(Self::LeastConnections),
+            "LOAD_BALANCER_ALGORITHM_RANDOM" => Some(Self::Random),
+            "LOAD_BALANCER_ALGORITHM_IP_HASH" => Some(Self::IpHash),
+            "LOAD_BALANCER_ALGORITHM_WEIGHTED" => Some(Self::Weighted),
+            _ => None,
+        }
+    }
+}
+/// Protection level
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ProtectionLevel {
+    Unspecified = 0,
+    Off = 1,
+    Low = 2,
+    Medium = 3,
+    High = 4,
+    UnderAttack = 5,
+}
+impl ProtectionLevel {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "PROTECTION_LEVEL_UNSPECIFIED",
+            Self::Off => "PROTECTION_LEVEL_OFF",
+            Self::Low => "PROTECTION_LEVEL_LOW",
+            Self::Medium => "PROTECTION_LEVEL_MEDIUM",
+            Self::High => "PROTECTION_LEVEL_HIGH",
+            Self::UnderAttack => "PROTECTION_LEVEL_UNDER_ATTACK",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "PROTECTION_LEVEL_UNSPECIFIED" => Some(Self::Unspecified),
+            "PROTECTION_LEVEL_OFF" => Some(Self::Off),
+            "PROTECTION_LEVEL_LOW" => Some(Self::Low),
+            "PROTECTION_LEVEL_MEDIUM" => Some(Self::Medium),
+            "PROTECTION_LEVEL_HIGH" => Some(Self::High),
+            "PROTECTION_LEVEL_UNDER_ATTACK" => Some(Self::UnderAttack),
+            _ => None,
+        }
+    }
+}
+/// Challenge types
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ChallengeType {
+    Unspecified = 0,
+    Javascript = 1,
+    Captcha = 2,
+    /// Proof of work
+    Pow = 3,
+}
+impl ChallengeType {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "CHALLENGE_TYPE_UNSPECIFIED",
+            Self::Javascript => "CHALLENGE_TYPE_JAVASCRIPT",
+            Self::Captcha => "CHALLENGE_TYPE_CAPTCHA",
+            Self::Pow => "CHALLENGE_TYPE_POW",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CHALLENGE_TYPE_UNSPECIFIED" => Some(Self::Unspecified),
+            "CHALLENGE_TYPE_JAVASCRIPT" => Some(Self::Javascript),
+            "CHALLENGE_TYPE_CAPTCHA" => Some(Self::Captcha),
+            "CHALLENGE_TYPE_POW" => Some(Self::Pow),
+            _ => None,
+        }
+    }
+}
+/// GeoIP filtering mode
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum GeoIpMode {
+    Unspecified = 0,
+    Disabled = 1,
+    AllowList = 2,
+    BlockList = 3,
+}
+impl GeoIpMode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "GEO_IP_MODE_UNSPECIFIED",
+            Self::Disabled => "GEO_IP_MODE_DISABLED",
+            Self::AllowList => "GEO_IP_MODE_ALLOW_LIST",
+            Self::BlockList => "GEO_IP_MODE_BLOCK_LIST",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GEO_IP_MODE_UNSPECIFIED" => Some(Self::Unspecified),
+            "GEO_IP_MODE_DISABLED" => Some(Self::Disabled),
+            "GEO_IP_MODE_ALLOW_LIST" => Some(Self::AllowList),
+            "GEO_IP_MODE_BLOCK_LIST" => Some(Self::BlockList),
+            _ => None,
+        }
+    }
+}
+/// WAF mode
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum WafMode {
+    Unspecified = 0,
+    Detect = 1,
+    Block = 2,
+}
+impl WafMode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "WAF_MODE_UNSPECIFIED",
+            Self::Detect => "WAF_MODE_DETECT",
+            Self::Block => "WAF_MODE_BLOCK",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "WAF_MODE_UNSPECIFIED" => Some(Self::Unspecified),
+            "WAF_MODE_DETECT" => Some(Self::Detect),
+            "WAF_MODE_BLOCK" => Some(Self::Block),
+            _ => None,
+        }
+    }
+}
+/// Bot protection mode
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum BotProtectionMode {
+    Unspecified = 0,
+    AllowAll = 1,
+    ChallengeSuspicious = 2,
+    BlockAllBots = 3,
+}
+impl BotProtectionMode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "BOT_PROTECTION_MODE_UNSPECIFIED",
+            Self::AllowAll => "BOT_PROTECTION_MODE_ALLOW_ALL",
+            Self::ChallengeSuspicious => "BOT_PROTECTION_MODE_CHALLENGE_SUSPICIOUS",
+            Self::BlockAllBots => "BOT_PROTECTION_MODE_BLOCK_ALL_BOTS",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "BOT_PROTECTION_MODE_UNSPECIFIED" => Some(Self::Unspecified),
+            "BOT_PROTECTION_MODE_ALLOW_ALL" => Some(Self::AllowAll),
+            "BOT_PROTECTION_MODE_CHALLENGE_SUSPICIOUS" => Some(Self::ChallengeSuspicious),
+            "BOT_PROTECTION_MODE_BLOCK_ALL_BOTS" => Some(Self::BlockAllBots),
+            _ => None,
+        }
+    }
+}
+/// Onboarding state of a backend added by hostname. Protection cannot be
+/// enabled before the backend is active.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum OnboardingState {
+    Unspecified = 0,
+    /// The hostname does not resolve yet
+    PendingDns = 1,
+    /// The ownership TXT record is missing
+    PendingVerification = 2,
+    /// The origin did not answer a status ping from a worker
+    PendingProbe = 3,
+    Active = 4,
+}
+impl OnboardingState {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "ONBOARDING_STATE_UNSPECIFIED",
+            Self::PendingDns => "ONBOARDING_STATE_PENDING_DNS",
+            Self::PendingVerification => "ONBOARDING_STATE_PENDING_VERIFICATION",
+            Self::PendingProbe => "ONBOARDING_STATE_PENDING_PROBE",
+            Self::Active => "ONBOARDING_STATE_ACTIVE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ONBOARDING_STATE_UNSPECIFIED" => Some(Self::Unspecified),
+            "ONBOARDING_STATE_PENDING_DNS" => Some(Self::PendingDns),
+            "ONBOARDING_STATE_PENDING_VERIFICATION" => Some(Self::PendingVerification),
+            "ONBOARDING_STATE_PENDING_PROBE" => Some(Self::PendingProbe),
+            "ONBOARDING_STATE_ACTIVE" => Some(Self::Active),
+            _ => None,
+        }
+    }
+}
+/// State of an origin switch
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum OriginSwitchState {
+    Unspecified = 0,
+    /// New connections go to the new origin, the old one keeps its players
+    Draining = 1,
+    /// The old origin drained or the grace period ended; it is disabled
+    Completed = 2,
+    Cancelled = 3,
+}
+impl OriginSwitchState {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "ORIGIN_SWITCH_STATE_UNSPECIFIED",
+            Self::Draining => "ORIGIN_SWITCH_STATE_DRAINING",
+            Self::Completed => "ORIGIN_SWITCH_STATE_COMPLETED",
+            Self::Cancelled => "ORIGIN_SWITCH_STATE_CANCELLED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ORIGIN_SWITCH_STATE_UNSPECIFIED" => Some(Self::Unspecified),
+            "ORIGIN_SWITCH_STATE_DRAINING" => Some(Self::Draining),
+            "ORIGIN_SWITCH_STATE_COMPLETED" => Some(Self::Completed),
+            "ORIGIN_SWITCH_STATE_CANCELLED" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+}
+/// Where an origin address was found exposed
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ExposureSource {
+    Unspecified = 0,
+    /// A domain or common subdomain resolves to the origin
+    DnsRecord = 1,
+    /// A name or IP logged in certificate transparency points to the origin
+    CertificateTransparency = 2,
+    /// An HTTP response header contains the origin address
+    HttpHeader = 3,
+}
+impl ExposureSource {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "EXPOSURE_SOURCE_UNSPECIFIED",
+            Self::DnsRecord => "EXPOSURE_SOURCE_DNS_RECORD",
+            Self::CertificateTransparency => "EXPOSURE_SOURCE_CERTIFICATE_TRANSPARENCY",
+            Self::HttpHeader => "EXPOSURE_SOURCE_HTTP_HEADER",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "EXPOSURE_SOURCE_UNSPECIFIED" => Some(Self::Unspecified),
+            "EXPOSURE_SOURCE_DNS_RECORD" => Some(Self::DnsRecord),
+            "EXPOSURE_SOURCE_CERTIFICATE_TRANSPARENCY" => {
+                Some(Self::CertificateTransparency)
+            }
+            "EXPOSURE_SOURCE_HTTP_HEADER" => Some(Self::HttpHeader),
+            _ => None,
+        }
+    }
+}
+/// How workers treat the traffic of a backend
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum BackendMode {
+    Unspecified = 0,
+    /// Filter traffic with the configured protection
+    Protect = 1,
+    /// Forward all traffic without filtering, to rule out protection as the
+    /// cause of a problem
+    Passthrough = 2,
+    /// Drop all traffic
+    BlockAll = 3,
+}
+impl BackendMode {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "BACKEND_MODE_UNSPECIFIED",
+            Self::Protect => "BACKEND_MODE_PROTECT",
+            Self::Passthrough => "BACKEND_MODE_PASSTHROUGH",
+            Self::BlockAll => "BACKEND_MODE_BLOCK_ALL",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "BACKEND_MODE_UNSPECIFIED" => Some(Self::Unspecified),
+            "BACKEND_MODE_PROTECT" => Some(Self::Protect),
+            "BACKEND_MODE_PASSTHROUGH" => Some(Self::Passthrough),
+            "BACKEND_MODE_BLOCK_ALL" => Some(Self::BlockAll),
+            _ => None,
+        }
+    }
+}
+/// What happens to the backends of an organization once their traffic of the
+/// month exceeds the bandwidth of its plan
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum BandwidthQuotaAction {
+    Unspecified = 0,
+    /// Record the overage in the audit log only
+    Notify = 1,
+    /// Cap the bandwidth of every connection to the backends
+    Throttle = 2,
+    /// Drop all traffic to the backends until the next period
+    Suspend = 3,
+}
+impl BandwidthQuotaAction {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "BANDWIDTH_QUOTA_ACTION_UNSPECIFIED",
+            Self::Notify => "BANDWIDTH_QUOTA_ACTION_NOTIFY",
+            Self::Throttle => "BANDWIDTH_QUOTA_ACTION_THROTTLE",
+            Self::Suspend => "BANDWIDTH_QUOTA_ACTION_SUSPEND",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "BANDWIDTH_QUOTA_ACTION_UNSPECIFIED" => Some(Self::Unspecified),
+            "BANDWIDTH_QUOTA_ACTION_NOTIFY" => Some(Self::Notify),
+            "BANDWIDTH_QUOTA_ACTION_THROTTLE" => Some(Self::Throttle),
+            "BANDWIDTH_QUOTA_ACTION_SUSPEND" => Some(Self::Suspend),
+            _ => None,
+        }
+    }
+}
+/// Kind of a configuration change
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ConfigChangeKind {
+    Unspecified = 0,
+    Added = 1,
+    Removed = 2,
+    Modified = 3,
+}
+impl ConfigChangeKind {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "CONFIG_CHANGE_KIND_UNSPECIFIED",
+            Self::Added => "CONFIG_CHANGE_KIND_ADDED",
+            Self::Removed => "CONFIG_CHANGE_KIND_REMOVED",
+            Self::Modified => "CONFIG_CHANGE_KIND_MODIFIED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CONFIG_CHANGE_KIND_UNSPECIFIED" => Some(Self::Unspecified),
+            "CONFIG_CHANGE_KIND_ADDED" => Some(Self::Added),
+            "CONFIG_CHANGE_KIND_REMOVED" => Some(Self::Removed),
+            "CONFIG_CHANGE_KIND_MODIFIED" => Some(Self::Modified),
+            _ => None,
+        }
+    }
+}
+/// High-impact action held for a second member's approval
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ApprovalActionKind {
+    Unspecified = 0,
+    DeleteBackend = 1,
+    /// Protection disabled, turned off or bypassed with passthrough mode
+    DisableProtection = 2,
+    /// Allow rule for a network of a /8 (IPv4) or /32 (IPv6) or larger
+    WideAllowlist = 3,
+}
+impl ApprovalActionKind {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "APPROVAL_ACTION_KIND_UNSPECIFIED",
+            Self::DeleteBackend => "APPROVAL_ACTION_KIND_DELETE_BACKEND",
+            Self::DisableProtection => "APPROVAL_ACTION_KIND_DISABLE_PROTECTION",
+            Self::WideAllowlist => "APPROVAL_ACTION_KIND_WIDE_ALLOWLIST",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "APPROVAL_ACTION_KIND_UNSPECIFIED" => Some(Self::Unspecified),
+            "APPROVAL_ACTION_KIND_DELETE_BACKEND" => Some(Self::DeleteBackend),
+            "APPROVAL_ACTION_KIND_DISABLE_PROTECTION" => Some(Self::DisableProtection),
+            "APPROVAL_ACTION_KIND_WIDE_ALLOWLIST" => Some(Self::WideAllowlist),
+            _ => None,
+        }
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum PendingActionStatus {
+    Unspecified = 0,
+    Pending = 1,
+    /// Approved and applied
+    Approved = 2,
+    Rejected = 3,
+    /// Not decided on in time
+    Expired = 4,
+    /// Approved, but applying it failed
+    Failed = 5,
+}
+impl PendingActionStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "PENDING_ACTION_STATUS_UNSPECIFIED",
+            Self::Pending => "PENDING_ACTION_STATUS_PENDING",
+            Self::Approved => "PENDING_ACTION_STATUS_APPROVED",
+            Self::Rejected => "PENDING_ACTION_STATUS_REJECTED",
+            Self::Expired => "PENDING_ACTION_STATUS_EXPIRED",
+            Self::Failed => "PENDING_ACTION_STATUS_FAILED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "PENDING_ACTION_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "PENDING_ACTION_STATUS_PENDING" => Some(Self::Pending),
+            "PENDING_ACTION_STATUS_APPROVED" => Some(Self::Approved),
+            "PENDING_ACTION_STATUS_REJECTED" => Some(Self::Rejected),
+            "PENDING_ACTION_STATUS_EXPIRED" => Some(Self::Expired),
+            "PENDING_ACTION_STATUS_FAILED" => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+/// Generated client implementations.
+pub mod backend_service_client {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// Backend service
+    #[derive(Debug, Clone)]
+    pub struct BackendServiceClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl BackendServiceClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> BackendServiceClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::Body>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> BackendServiceClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::Body>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::Body>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::Body>,
+            >>::Error: Into<StdError> + std::marker::Send + std::marker::Sync,
+        {
+            BackendServiceClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
+        }
+        /// Backend management
+        pub async fn create_backend(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CreateBackendRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CreateBackendResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/CreateBackend",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "CreateBackend",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_backend(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetBackendRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetBackendResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetBackend",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetBackend",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_backend(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateBackendRequest>,
+        ) -> std::result::Result<
             tonic::Response<super::UpdateBackendResponse>,
             tonic::Status,
         > {
@@ -1195,23 +2734,816 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/UpdateBackend",
+                "/pistonprotection.backend.BackendService/UpdateBackend",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "UpdateBackend",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn delete_backend(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DeleteBackendRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::DeleteBackendResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/DeleteBackend",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "DeleteBackend",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn list_backends(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListBackendsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListBackendsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/ListBackends",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "ListBackends",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Origin management
+        pub async fn add_origin(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AddOriginRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::AddOriginResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/AddOrigin",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "AddOrigin",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_origin(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateOriginRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateOriginResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/UpdateOrigin",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "UpdateOrigin",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn remove_origin(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemoveOriginRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveOriginResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/RemoveOrigin",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "RemoveOrigin",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Protection settings
+        pub async fn update_protection(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateProtectionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateProtectionResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/UpdateProtection",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "UpdateProtection",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn set_protection_level(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetProtectionLevelRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetProtectionLevelResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/SetProtectionLevel",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "SetProtectionLevel",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn set_honeypot(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetHoneypotRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetHoneypotResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/SetHoneypot",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "SetHoneypot",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn set_block_responses(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetBlockResponsesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetBlockResponsesResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/SetBlockResponses",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "SetBlockResponses",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Status
+        pub async fn get_backend_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetBackendStatusRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetBackendStatusResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetBackendStatus",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetBackendStatus",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn watch_backend_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::WatchBackendStatusRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::BackendStatus>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/WatchBackendStatus",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "WatchBackendStatus",
+                    ),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// Domain management
+        pub async fn add_domain(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AddDomainRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::AddDomainResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/AddDomain",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "AddDomain",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn remove_domain(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemoveDomainRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveDomainResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/RemoveDomain",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "RemoveDomain",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn verify_domain(
+            &mut self,
+            request: impl tonic::IntoRequest<super::VerifyDomainRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::VerifyDomainResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/VerifyDomain",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "VerifyDomain",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Onboarding by hostname
+        pub async fn start_onboarding(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StartOnboardingRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::StartOnboardingResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/StartOnboarding",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "StartOnboarding",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn check_onboarding(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CheckOnboardingRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CheckOnboardingResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/CheckOnboarding",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "CheckOnboarding",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Zero-downtime origin switch
+        pub async fn switch_origin(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SwitchOriginRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SwitchOriginResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/SwitchOrigin",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "SwitchOrigin",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_origin_switch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetOriginSwitchRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetOriginSwitchResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetOriginSwitch",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetOriginSwitch",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn cancel_origin_switch(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CancelOriginSwitchRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelOriginSwitchResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/CancelOriginSwitch",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "CancelOriginSwitch",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Origin concealment
+        pub async fn get_origin_exposure(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetOriginExposureRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetOriginExposureResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetOriginExposure",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetOriginExposure",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn scan_origin_exposure(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ScanOriginExposureRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ScanOriginExposureResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/ScanOriginExposure",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "ScanOriginExposure",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Public status page
+        pub async fn get_status_page(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetStatusPageRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetStatusPageResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetStatusPage",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetStatusPage",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_status_page(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateStatusPageRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateStatusPageResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/UpdateStatusPage",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "UpdateStatusPage",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn rotate_status_page_token(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RotateStatusPageTokenRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RotateStatusPageTokenResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/RotateStatusPageToken",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "RotateStatusPageToken",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Maintenance and passthrough mode
+        pub async fn set_backend_mode(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetBackendModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetBackendModeResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/SetBackendMode",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "SetBackendMode",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_backend_mode(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetBackendModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetBackendModeResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetBackendMode",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetBackendMode",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Ban list synchronization with game server plugins
+        pub async fn get_ban_sync(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetBanSyncRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetBanSyncResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetBanSync",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "UpdateBackend",
+                        "GetBanSync",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn delete_backend(
+        pub async fn update_ban_sync(
             &mut self,
-            request: impl tonic::IntoRequest<super::DeleteBackendRequest>,
+            request: impl tonic::IntoRequest<super::UpdateBanSyncRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::DeleteBackendResponse>,
+            tonic::Response<super::UpdateBanSyncResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1224,23 +3556,23 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/DeleteBackend",
+                "/pistonprotection.backend.BackendService/UpdateBanSync",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "DeleteBackend",
+                        "UpdateBanSync",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn list_backends(
+        pub async fn rotate_ban_sync_token(
             &mut self,
-            request: impl tonic::IntoRequest<super::ListBackendsRequest>,
+            request: impl tonic::IntoRequest<super::RotateBanSyncTokenRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::ListBackendsResponse>,
+            tonic::Response<super::RotateBanSyncTokenResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1253,24 +3585,24 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/ListBackends",
+                "/pistonprotection.backend.BackendService/RotateBanSyncToken",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "ListBackends",
+                        "RotateBanSyncToken",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        /// Origin management
-        pub async fn add_origin(
+        /// Scheduled rate limit profiles
+        pub async fn get_rate_profiles(
             &mut self,
-            request: impl tonic::IntoRequest<super::AddOriginRequest>,
+            request: impl tonic::IntoRequest<super::GetRateProfilesRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::AddOriginResponse>,
+            tonic::Response<super::GetRateProfilesResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1283,23 +3615,23 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/AddOrigin",
+                "/pistonprotection.backend.BackendService/GetRateProfiles",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "AddOrigin",
+                        "GetRateProfiles",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn update_origin(
+        pub async fn update_rate_profiles(
             &mut self,
-            request: impl tonic::IntoRequest<super::UpdateOriginRequest>,
+            request: impl tonic::IntoRequest<super::UpdateRateProfilesRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::UpdateOriginResponse>,
+            tonic::Response<super::UpdateRateProfilesResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1312,23 +3644,23 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/UpdateOrigin",
+                "/pistonprotection.backend.BackendService/UpdateRateProfiles",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "UpdateOrigin",
+                        "UpdateRateProfiles",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn remove_origin(
+        pub async fn trigger_rate_profile(
             &mut self,
-            request: impl tonic::IntoRequest<super::RemoveOriginRequest>,
+            request: impl tonic::IntoRequest<super::TriggerRateProfileRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::RemoveOriginResponse>,
+            tonic::Response<super::TriggerRateProfileResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1341,24 +3673,288 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/RemoveOrigin",
+                "/pistonprotection.backend.BackendService/TriggerRateProfile",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "RemoveOrigin",
+                        "TriggerRateProfile",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        /// Protection settings
-        pub async fn update_protection(
+        /// Restart-storm protection
+        pub async fn start_restart_mode(
             &mut self,
-            request: impl tonic::IntoRequest<super::UpdateProtectionRequest>,
+            request: impl tonic::IntoRequest<super::StartRestartModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::StartRestartModeResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/StartRestartMode",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "StartRestartMode",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn stop_restart_mode(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StopRestartModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::StopRestartModeResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/StopRestartMode",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "StopRestartMode",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_restart_mode(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetRestartModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetRestartModeResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetRestartMode",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetRestartMode",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Monthly bandwidth quota
+        pub async fn get_bandwidth_quota(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetBandwidthQuotaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetBandwidthQuotaResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetBandwidthQuota",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetBandwidthQuota",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_bandwidth_quota(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateBandwidthQuotaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateBandwidthQuotaResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/UpdateBandwidthQuota",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "UpdateBandwidthQuota",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Configuration history
+        pub async fn list_config_revisions(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListConfigRevisionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListConfigRevisionsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/ListConfigRevisions",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "ListConfigRevisions",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_config_revision(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetConfigRevisionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetConfigRevisionResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetConfigRevision",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetConfigRevision",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn rollback_config(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RollbackConfigRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RollbackConfigResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/RollbackConfig",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "RollbackConfig",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Two-person approval
+        pub async fn get_approval_policy(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetApprovalPolicyRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetApprovalPolicyResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.backend.BackendService/GetApprovalPolicy",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.backend.BackendService",
+                        "GetApprovalPolicy",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_approval_policy(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateApprovalPolicyRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::UpdateProtectionResponse>,
+            tonic::Response<super::UpdateApprovalPolicyResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1371,23 +3967,23 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/UpdateProtection",
+                "/pistonprotection.backend.BackendService/UpdateApprovalPolicy",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "UpdateProtection",
+                        "UpdateApprovalPolicy",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn set_protection_level(
+        pub async fn list_pending_actions(
             &mut self,
-            request: impl tonic::IntoRequest<super::SetProtectionLevelRequest>,
+            request: impl tonic::IntoRequest<super::ListPendingActionsRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::SetProtectionLevelResponse>,
+            tonic::Response<super::ListPendingActionsResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1400,24 +3996,23 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/SetProtectionLevel",
+                "/pistonprotection.backend.BackendService/ListPendingActions",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "SetProtectionLevel",
+                        "ListPendingActions",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        /// Status
-        pub async fn get_backend_status(
+        pub async fn approve_pending_action(
             &mut self,
-            request: impl tonic::IntoRequest<super::GetBackendStatusRequest>,
+            request: impl tonic::IntoRequest<super::ApprovePendingActionRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::GetBackendStatusResponse>,
+            tonic::Response<super::ApprovePendingActionResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1430,23 +4025,23 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/GetBackendStatus",
+                "/pistonprotection.backend.BackendService/ApprovePendingAction",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "GetBackendStatus",
+                        "ApprovePendingAction",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn watch_backend_status(
+        pub async fn reject_pending_action(
             &mut self,
-            request: impl tonic::IntoRequest<super::WatchBackendStatusRequest>,
+            request: impl tonic::IntoRequest<super::RejectPendingActionRequest>,
         ) -> std::result::Result<
-            tonic::Response<tonic::codec::Streaming<super::BackendStatus>>,
+            tonic::Response<super::RejectPendingActionResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1459,24 +4054,24 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/WatchBackendStatus",
+                "/pistonprotection.backend.BackendService/RejectPendingAction",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "WatchBackendStatus",
+                        "RejectPendingAction",
                     ),
                 );
-            self.inner.server_streaming(req, path, codec).await
+            self.inner.unary(req, path, codec).await
         }
-        /// Domain management
-        pub async fn add_domain(
+        /// Load test exemption tokens
+        pub async fn create_exemption_token(
             &mut self,
-            request: impl tonic::IntoRequest<super::AddDomainRequest>,
+            request: impl tonic::IntoRequest<super::CreateExemptionTokenRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::AddDomainResponse>,
+            tonic::Response<super::CreateExemptionTokenResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1489,23 +4084,23 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/AddDomain",
+                "/pistonprotection.backend.BackendService/CreateExemptionToken",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "AddDomain",
+                        "CreateExemptionToken",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn remove_domain(
+        pub async fn revoke_exemption_token(
             &mut self,
-            request: impl tonic::IntoRequest<super::RemoveDomainRequest>,
+            request: impl tonic::IntoRequest<super::RevokeExemptionTokenRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::RemoveDomainResponse>,
+            tonic::Response<super::RevokeExemptionTokenResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1518,23 +4113,23 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/RemoveDomain",
+                "/pistonprotection.backend.BackendService/RevokeExemptionToken",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "RemoveDomain",
+                        "RevokeExemptionToken",
                     ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn verify_domain(
+        pub async fn list_exemption_tokens(
             &mut self,
-            request: impl tonic::IntoRequest<super::VerifyDomainRequest>,
+            request: impl tonic::IntoRequest<super::ListExemptionTokensRequest>,
         ) -> std::result::Result<
-            tonic::Response<super::VerifyDomainResponse>,
+            tonic::Response<super::ListExemptionTokensResponse>,
             tonic::Status,
         > {
             self.inner
@@ -1547,14 +4142,14 @@ pub mod backend_service_client {
                 })?;
             let codec = tonic_prost::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/pistonprotection.backend.BackendService/VerifyDomain",
+                "/pistonprotection.backend.BackendService/ListExemptionTokens",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
                     GrpcMethod::new(
                         "pistonprotection.backend.BackendService",
-                        "VerifyDomain",
+                        "ListExemptionTokens",
                     ),
                 );
             self.inner.unary(req, path, codec).await
@@ -1647,6 +4242,20 @@ pub mod backend_service_server {
             tonic::Response<super::SetProtectionLevelResponse>,
             tonic::Status,
         >;
+        async fn set_honeypot(
+            &self,
+            request: tonic::Request<super::SetHoneypotRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetHoneypotResponse>,
+            tonic::Status,
+        >;
+        async fn set_block_responses(
+            &self,
+            request: tonic::Request<super::SetBlockResponsesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetBlockResponsesResponse>,
+            tonic::Status,
+        >;
         /// Status
         async fn get_backend_status(
             &self,
@@ -1690,6 +4299,256 @@ pub mod backend_service_server {
             tonic::Response<super::VerifyDomainResponse>,
             tonic::Status,
         >;
+        /// Onboarding by hostname
+        async fn start_onboarding(
+            &self,
+            request: tonic::Request<super::StartOnboardingRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::StartOnboardingResponse>,
+            tonic::Status,
+        >;
+        async fn check_onboarding(
+            &self,
+            request: tonic::Request<super::CheckOnboardingRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CheckOnboardingResponse>,
+            tonic::Status,
+        >;
+        /// Zero-downtime origin switch
+        async fn switch_origin(
+            &self,
+            request: tonic::Request<super::SwitchOriginRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SwitchOriginResponse>,
+            tonic::Status,
+        >;
+        async fn get_origin_switch(
+            &self,
+            request: tonic::Request<super::GetOriginSwitchRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetOriginSwitchResponse>,
+            tonic::Status,
+        >;
+        async fn cancel_origin_switch(
+            &self,
+            request: tonic::Request<super::CancelOriginSwitchRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelOriginSwitchResponse>,
+            tonic::Status,
+        >;
+        /// Origin concealment
+        async fn get_origin_exposure(
+            &self,
+            request: tonic::Request<super::GetOriginExposureRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetOriginExposureResponse>,
+            tonic::Status,
+        >;
+        async fn scan_origin_exposure(
+            &self,
+            request: tonic::Request<super::ScanOriginExposureRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ScanOriginExposureResponse>,
+            tonic::Status,
+        >;
+        /// Public status page
+        async fn get_status_page(
+            &self,
+            request: tonic::Request<super::GetStatusPageRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetStatusPageResponse>,
+            tonic::Status,
+        >;
+        async fn update_status_page(
+            &self,
+            request: tonic::Request<super::UpdateStatusPageRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateStatusPageResponse>,
+            tonic::Status,
+        >;
+        async fn rotate_status_page_token(
+            &self,
+            request: tonic::Request<super::RotateStatusPageTokenRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RotateStatusPageTokenResponse>,
+            tonic::Status,
+        >;
+        /// Maintenance and passthrough mode
+        async fn set_backend_mode(
+            &self,
+            request: tonic::Request<super::SetBackendModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetBackendModeResponse>,
+            tonic::Status,
+        >;
+        async fn get_backend_mode(
+            &self,
+            request: tonic::Request<super::GetBackendModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetBackendModeResponse>,
+            tonic::Status,
+        >;
+        /// Ban list synchronization with game server plugins
+        async fn get_ban_sync(
+            &self,
+            request: tonic::Request<super::GetBanSyncRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetBanSyncResponse>,
+            tonic::Status,
+        >;
+        async fn update_ban_sync(
+            &self,
+            request: tonic::Request<super::UpdateBanSyncRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateBanSyncResponse>,
+            tonic::Status,
+        >;
+        async fn rotate_ban_sync_token(
+            &self,
+            request: tonic::Request<super::RotateBanSyncTokenRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RotateBanSyncTokenResponse>,
+            tonic::Status,
+        >;
+        /// Scheduled rate limit profiles
+        async fn get_rate_profiles(
+            &self,
+            request: tonic::Request<super::GetRateProfilesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetRateProfilesResponse>,
+            tonic::Status,
+        >;
+        async fn update_rate_profiles(
+            &self,
+            request: tonic::Request<super::UpdateRateProfilesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateRateProfilesResponse>,
+            tonic::Status,
+        >;
+        async fn trigger_rate_profile(
+            &self,
+            request: tonic::Request<super::TriggerRateProfileRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::TriggerRateProfileResponse>,
+            tonic::Status,
+        >;
+        /// Restart-storm protection
+        async fn start_restart_mode(
+            &self,
+            request: tonic::Request<super::StartRestartModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::StartRestartModeResponse>,
+            tonic::Status,
+        >;
+        async fn stop_restart_mode(
+            &self,
+            request: tonic::Request<super::StopRestartModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::StopRestartModeResponse>,
+            tonic::Status,
+        >;
+        async fn get_restart_mode(
+            &self,
+            request: tonic::Request<super::GetRestartModeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetRestartModeResponse>,
+            tonic::Status,
+        >;
+        /// Monthly bandwidth quota
+        async fn get_bandwidth_quota(
+            &self,
+            request: tonic::Request<super::GetBandwidthQuotaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetBandwidthQuotaResponse>,
+            tonic::Status,
+        >;
+        async fn update_bandwidth_quota(
+            &self,
+            request: tonic::Request<super::UpdateBandwidthQuotaRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateBandwidthQuotaResponse>,
+            tonic::Status,
+        >;
+        /// Configuration history
+        async fn list_config_revisions(
+            &self,
+            request: tonic::Request<super::ListConfigRevisionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListConfigRevisionsResponse>,
+            tonic::Status,
+        >;
+        async fn get_config_revision(
+            &self,
+            request: tonic::Request<super::GetConfigRevisionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetConfigRevisionResponse>,
+            tonic::Status,
+        >;
+        async fn rollback_config(
+            &self,
+            request: tonic::Request<super::RollbackConfigRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RollbackConfigResponse>,
+            tonic::Status,
+        >;
+        /// Two-person approval
+        async fn get_approval_policy(
+            &self,
+            request: tonic::Request<super::GetApprovalPolicyRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetApprovalPolicyResponse>,
+            tonic::Status,
+        >;
+        async fn update_approval_policy(
+            &self,
+            request: tonic::Request<super::UpdateApprovalPolicyRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateApprovalPolicyResponse>,
+            tonic::Status,
+        >;
+        async fn list_pending_actions(
+            &self,
+            request: tonic::Request<super::ListPendingActionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListPendingActionsResponse>,
+            tonic::Status,
+        >;
+        async fn approve_pending_action(
+            &self,
+            request: tonic::Request<super::ApprovePendingActionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ApprovePendingActionResponse>,
+            tonic::Status,
+        >;
+        async fn reject_pending_action(
+            &self,
+            request: tonic::Request<super::RejectPendingActionRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RejectPendingActionResponse>,
+            tonic::Status,
+        >;
+        /// Load test exemption tokens
+        async fn create_exemption_token(
+            &self,
+            request: tonic::Request<super::CreateExemptionTokenRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::CreateExemptionTokenResponse>,
+            tonic::Status,
+        >;
+        async fn revoke_exemption_token(
+            &self,
+            request: tonic::Request<super::RevokeExemptionTokenRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RevokeExemptionTokenResponse>,
+            tonic::Status,
+        >;
+        async fn list_exemption_tokens(
+            &self,
+            request: tonic::Request<super::ListExemptionTokensRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListExemptionTokensResponse>,
+            tonic::Status,
+        >;
     }
     /// Backend service
     #[derive(Debug)]
@@ -1770,23 +4629,1536 @@ pub mod backend_service_server {
             match req.uri().path() {
                 "/pistonprotection.backend.BackendService/CreateBackend" => {
                     #[allow(non_camel_case_types)]
-                    struct CreateBackendSvc<T: BackendService>(pub Arc<T>);
+                    struct CreateBackendSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::CreateBackendRequest>
+                    for CreateBackendSvc<T> {
+                        type Response = super::CreateBackendResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateBackendRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::create_backend(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CreateBackendSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/GetBackend" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetBackendSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::GetBackendRequest>
+                    for GetBackendSvc<T> {
+                        type Response = super::GetBackendResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetBackendRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::get_backend(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetBackendSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/UpdateBackend" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateBackendSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::UpdateBackendRequest>
+                    for UpdateBackendSvc<T> {
+                        type Response = super::UpdateBackendResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateBackendRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::update_backend(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdateBackendSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/DeleteBackend" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeleteBackendSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::DeleteBackendRequest>
+                    for DeleteBackendSvc<T> {
+                        type Response = super::DeleteBackendResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DeleteBackendRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::delete_backend(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = DeleteBackendSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/ListBackends" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListBackendsSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::ListBackendsRequest>
+                    for ListBackendsSvc<T> {
+                        type Response = super::ListBackendsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListBackendsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::list_backends(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ListBackendsSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/AddOrigin" => {
+                    #[allow(non_camel_case_types)]
+                    struct AddOriginSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::AddOriginRequest>
+                    for AddOriginSvc<T> {
+                        type Response = super::AddOriginResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AddOriginRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::add_origin(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = AddOriginSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/UpdateOrigin" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateOriginSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::UpdateOriginRequest>
+                    for UpdateOriginSvc<T> {
+                        type Response = super::UpdateOriginResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateOriginRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::update_origin(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdateOriginSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/RemoveOrigin" => {
+                    #[allow(non_camel_case_types)]
+                    struct RemoveOriginSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::RemoveOriginRequest>
+                    for RemoveOriginSvc<T> {
+                        type Response = super::RemoveOriginResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RemoveOriginRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::remove_origin(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RemoveOriginSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/UpdateProtection" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateProtectionSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::UpdateProtectionRequest>
+                    for UpdateProtectionSvc<T> {
+                        type Response = super::UpdateProtectionResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateProtectionRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::update_protection(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdateProtectionSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/SetProtectionLevel" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetProtectionLevelSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::SetProtectionLevelRequest>
+                    for SetProtectionLevelSvc<T> {
+                        type Response = super::SetProtectionLevelResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetProtectionLevelRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::set_protection_level(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SetProtectionLevelSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/SetHoneypot" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetHoneypotSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::SetHoneypotRequest>
+                    for SetHoneypotSvc<T> {
+                        type Response = super::SetHoneypotResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetHoneypotRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::set_honeypot(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SetHoneypotSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/SetBlockResponses" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetBlockResponsesSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::SetBlockResponsesRequest>
+                    for SetBlockResponsesSvc<T> {
+                        type Response = super::SetBlockResponsesResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetBlockResponsesRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::set_block_responses(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SetBlockResponsesSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/GetBackendStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetBackendStatusSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::GetBackendStatusRequest>
+                    for GetBackendStatusSvc<T> {
+                        type Response = super::GetBackendStatusResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetBackendStatusRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::get_backend_status(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetBackendStatusSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/WatchBackendStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchBackendStatusSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::ServerStreamingService<
+                        super::WatchBackendStatusRequest,
+                    > for WatchBackendStatusSvc<T> {
+                        type Response = super::BackendStatus;
+                        type ResponseStream = T::WatchBackendStatusStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::WatchBackendStatusRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::watch_backend_status(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = WatchBackendStatusSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/AddDomain" => {
+                    #[allow(non_camel_case_types)]
+                    struct AddDomainSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::AddDomainRequest>
+                    for AddDomainSvc<T> {
+                        type Response = super::AddDomainResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AddDomainRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::add_domain(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = AddDomainSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/RemoveDomain" => {
+                    #[allow(non_camel_case_types)]
+                    struct RemoveDomainSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::RemoveDomainRequest>
+                    for RemoveDomainSvc<T> {
+                        type Response = super::RemoveDomainResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RemoveDomainRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::remove_domain(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RemoveDomainSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/VerifyDomain" => {
+                    #[allow(non_camel_case_types)]
+                    struct VerifyDomainSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::VerifyDomainRequest>
+                    for VerifyDomainSvc<T> {
+                        type Response = super::VerifyDomainResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::VerifyDomainRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::verify_domain(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = VerifyDomainSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/StartOnboarding" => {
+                    #[allow(non_camel_case_types)]
+                    struct StartOnboardingSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::StartOnboardingRequest>
+                    for StartOnboardingSvc<T> {
+                        type Response = super::StartOnboardingResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StartOnboardingRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::start_onboarding(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = StartOnboardingSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/CheckOnboarding" => {
+                    #[allow(non_camel_case_types)]
+                    struct CheckOnboardingSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::CheckOnboardingRequest>
+                    for CheckOnboardingSvc<T> {
+                        type Response = super::CheckOnboardingResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CheckOnboardingRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::check_onboarding(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CheckOnboardingSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/SwitchOrigin" => {
+                    #[allow(non_camel_case_types)]
+                    struct SwitchOriginSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::SwitchOriginRequest>
+                    for SwitchOriginSvc<T> {
+                        type Response = super::SwitchOriginResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SwitchOriginRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::switch_origin(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SwitchOriginSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/GetOriginSwitch" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetOriginSwitchSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::GetOriginSwitchRequest>
+                    for GetOriginSwitchSvc<T> {
+                        type Response = super::GetOriginSwitchResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetOriginSwitchRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::get_origin_switch(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetOriginSwitchSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/CancelOriginSwitch" => {
+                    #[allow(non_camel_case_types)]
+                    struct CancelOriginSwitchSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::CancelOriginSwitchRequest>
+                    for CancelOriginSwitchSvc<T> {
+                        type Response = super::CancelOriginSwitchResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CancelOriginSwitchRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::cancel_origin_switch(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CancelOriginSwitchSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/GetOriginExposure" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetOriginExposureSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::GetOriginExposureRequest>
+                    for GetOriginExposureSvc<T> {
+                        type Response = super::GetOriginExposureResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetOriginExposureRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::get_origin_exposure(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetOriginExposureSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/ScanOriginExposure" => {
+                    #[allow(non_camel_case_types)]
+                    struct ScanOriginExposureSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::ScanOriginExposureRequest>
+                    for ScanOriginExposureSvc<T> {
+                        type Response = super::ScanOriginExposureResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ScanOriginExposureRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::scan_origin_exposure(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ScanOriginExposureSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/GetStatusPage" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetStatusPageSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::GetStatusPageRequest>
+                    for GetStatusPageSvc<T> {
+                        type Response = super::GetStatusPageResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetStatusPageRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::get_status_page(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetStatusPageSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/UpdateStatusPage" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateStatusPageSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::UpdateStatusPageRequest>
+                    for UpdateStatusPageSvc<T> {
+                        type Response = super::UpdateStatusPageResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateStatusPageRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::update_status_page(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdateStatusPageSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/RotateStatusPageToken" => {
+                    #[allow(non_camel_case_types)]
+                    struct RotateStatusPageTokenSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::RotateStatusPageTokenRequest>
+                    for RotateStatusPageTokenSvc<T> {
+                        type Response = super::RotateStatusPageTokenResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RotateStatusPageTokenRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::rotate_status_page_token(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RotateStatusPageTokenSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/SetBackendMode" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetBackendModeSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::SetBackendModeRequest>
+                    for SetBackendModeSvc<T> {
+                        type Response = super::SetBackendModeResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetBackendModeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::set_backend_mode(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SetBackendModeSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/GetBackendMode" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetBackendModeSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::GetBackendModeRequest>
+                    for GetBackendModeSvc<T> {
+                        type Response = super::GetBackendModeResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetBackendModeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::get_backend_mode(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetBackendModeSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/GetBanSync" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetBanSyncSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::GetBanSyncRequest>
+                    for GetBanSyncSvc<T> {
+                        type Response = super::GetBanSyncResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetBanSyncRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::get_ban_sync(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetBanSyncSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/UpdateBanSync" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateBanSyncSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::UpdateBanSyncRequest>
+                    for UpdateBanSyncSvc<T> {
+                        type Response = super::UpdateBanSyncResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateBanSyncRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::update_ban_sync(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = UpdateBanSyncSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/RotateBanSyncToken" => {
+                    #[allow(non_camel_case_types)]
+                    struct RotateBanSyncTokenSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::RotateBanSyncTokenRequest>
+                    for RotateBanSyncTokenSvc<T> {
+                        type Response = super::RotateBanSyncTokenResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RotateBanSyncTokenRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::rotate_ban_sync_token(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RotateBanSyncTokenSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/GetRateProfiles" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetRateProfilesSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::GetRateProfilesRequest>
+                    for GetRateProfilesSvc<T> {
+                        type Response = super::GetRateProfilesResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetRateProfilesRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::get_rate_profiles(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetRateProfilesSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/UpdateRateProfiles" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateRateProfilesSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::CreateBackendRequest>
-                    for CreateBackendSvc<T> {
-                        type Response = super::CreateBackendResponse;
+                    > tonic::server::UnaryService<super::UpdateRateProfilesRequest>
+                    for UpdateRateProfilesSvc<T> {
+                        type Response = super::UpdateRateProfilesResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CreateBackendRequest>,
+                            request: tonic::Request<super::UpdateRateProfilesRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::create_backend(&inner, request).await
+                                <T as BackendService>::update_rate_profiles(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -1797,7 +6169,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = CreateBackendSvc(inner);
+                        let method = UpdateRateProfilesSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1813,25 +6185,26 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/GetBackend" => {
+                "/pistonprotection.backend.BackendService/TriggerRateProfile" => {
                     #[allow(non_camel_case_types)]
-                    struct GetBackendSvc<T: BackendService>(pub Arc<T>);
+                    struct TriggerRateProfileSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::GetBackendRequest>
-                    for GetBackendSvc<T> {
-                        type Response = super::GetBackendResponse;
+                    > tonic::server::UnaryService<super::TriggerRateProfileRequest>
+                    for TriggerRateProfileSvc<T> {
+                        type Response = super::TriggerRateProfileResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::GetBackendRequest>,
+                            request: tonic::Request<super::TriggerRateProfileRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::get_backend(&inner, request).await
+                                <T as BackendService>::trigger_rate_profile(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -1842,7 +6215,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = GetBackendSvc(inner);
+                        let method = TriggerRateProfileSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1858,25 +6231,26 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/UpdateBackend" => {
+                "/pistonprotection.backend.BackendService/StartRestartMode" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateBackendSvc<T: BackendService>(pub Arc<T>);
+                    struct StartRestartModeSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::UpdateBackendRequest>
-                    for UpdateBackendSvc<T> {
-                        type Response = super::UpdateBackendResponse;
+                    > tonic::server::UnaryService<super::StartRestartModeRequest>
+                    for StartRestartModeSvc<T> {
+                        type Response = super::StartRestartModeResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpdateBackendRequest>,
+                            request: tonic::Request<super::StartRestartModeRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::update_backend(&inner, request).await
+                                <T as BackendService>::start_restart_mode(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -1887,7 +6261,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = UpdateBackendSvc(inner);
+                        let method = StartRestartModeSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1903,25 +6277,26 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/DeleteBackend" => {
+                "/pistonprotection.backend.BackendService/StopRestartMode" => {
                     #[allow(non_camel_case_types)]
-                    struct DeleteBackendSvc<T: BackendService>(pub Arc<T>);
+                    struct StopRestartModeSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::DeleteBackendRequest>
-                    for DeleteBackendSvc<T> {
-                        type Response = super::DeleteBackendResponse;
+                    > tonic::server::UnaryService<super::StopRestartModeRequest>
+                    for StopRestartModeSvc<T> {
+                        type Response = super::StopRestartModeResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::DeleteBackendRequest>,
+                            request: tonic::Request<super::StopRestartModeRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::delete_backend(&inner, request).await
+                                <T as BackendService>::stop_restart_mode(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -1932,7 +6307,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = DeleteBackendSvc(inner);
+                        let method = StopRestartModeSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1948,25 +6323,26 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/ListBackends" => {
+                "/pistonprotection.backend.BackendService/GetRestartMode" => {
                     #[allow(non_camel_case_types)]
-                    struct ListBackendsSvc<T: BackendService>(pub Arc<T>);
+                    struct GetRestartModeSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::ListBackendsRequest>
-                    for ListBackendsSvc<T> {
-                        type Response = super::ListBackendsResponse;
+                    > tonic::server::UnaryService<super::GetRestartModeRequest>
+                    for GetRestartModeSvc<T> {
+                        type Response = super::GetRestartModeResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ListBackendsRequest>,
+                            request: tonic::Request<super::GetRestartModeRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::list_backends(&inner, request).await
+                                <T as BackendService>::get_restart_mode(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -1977,7 +6353,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = ListBackendsSvc(inner);
+                        let method = GetRestartModeSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1993,25 +6369,26 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/AddOrigin" => {
+                "/pistonprotection.backend.BackendService/GetBandwidthQuota" => {
                     #[allow(non_camel_case_types)]
-                    struct AddOriginSvc<T: BackendService>(pub Arc<T>);
+                    struct GetBandwidthQuotaSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::AddOriginRequest>
-                    for AddOriginSvc<T> {
-                        type Response = super::AddOriginResponse;
+                    > tonic::server::UnaryService<super::GetBandwidthQuotaRequest>
+                    for GetBandwidthQuotaSvc<T> {
+                        type Response = super::GetBandwidthQuotaResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::AddOriginRequest>,
+                            request: tonic::Request<super::GetBandwidthQuotaRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::add_origin(&inner, request).await
+                                <T as BackendService>::get_bandwidth_quota(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2022,7 +6399,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = AddOriginSvc(inner);
+                        let method = GetBandwidthQuotaSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2038,25 +6415,29 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/UpdateOrigin" => {
+                "/pistonprotection.backend.BackendService/UpdateBandwidthQuota" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateOriginSvc<T: BackendService>(pub Arc<T>);
+                    struct UpdateBandwidthQuotaSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::UpdateOriginRequest>
-                    for UpdateOriginSvc<T> {
-                        type Response = super::UpdateOriginResponse;
+                    > tonic::server::UnaryService<super::UpdateBandwidthQuotaRequest>
+                    for UpdateBandwidthQuotaSvc<T> {
+                        type Response = super::UpdateBandwidthQuotaResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpdateOriginRequest>,
+                            request: tonic::Request<super::UpdateBandwidthQuotaRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::update_origin(&inner, request).await
+                                <T as BackendService>::update_bandwidth_quota(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2067,7 +6448,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = UpdateOriginSvc(inner);
+                        let method = UpdateBandwidthQuotaSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2083,25 +6464,29 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/RemoveOrigin" => {
+                "/pistonprotection.backend.BackendService/ListConfigRevisions" => {
                     #[allow(non_camel_case_types)]
-                    struct RemoveOriginSvc<T: BackendService>(pub Arc<T>);
+                    struct ListConfigRevisionsSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::RemoveOriginRequest>
-                    for RemoveOriginSvc<T> {
-                        type Response = super::RemoveOriginResponse;
+                    > tonic::server::UnaryService<super::ListConfigRevisionsRequest>
+                    for ListConfigRevisionsSvc<T> {
+                        type Response = super::ListConfigRevisionsResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RemoveOriginRequest>,
+                            request: tonic::Request<super::ListConfigRevisionsRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::remove_origin(&inner, request).await
+                                <T as BackendService>::list_config_revisions(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2112,7 +6497,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = RemoveOriginSvc(inner);
+                        let method = ListConfigRevisionsSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2128,25 +6513,25 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/UpdateProtection" => {
+                "/pistonprotection.backend.BackendService/GetConfigRevision" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateProtectionSvc<T: BackendService>(pub Arc<T>);
+                    struct GetConfigRevisionSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::UpdateProtectionRequest>
-                    for UpdateProtectionSvc<T> {
-                        type Response = super::UpdateProtectionResponse;
+                    > tonic::server::UnaryService<super::GetConfigRevisionRequest>
+                    for GetConfigRevisionSvc<T> {
+                        type Response = super::GetConfigRevisionResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpdateProtectionRequest>,
+                            request: tonic::Request<super::GetConfigRevisionRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::update_protection(&inner, request)
+                                <T as BackendService>::get_config_revision(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -2158,7 +6543,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = UpdateProtectionSvc(inner);
+                        let method = GetConfigRevisionSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2174,25 +6559,25 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/SetProtectionLevel" => {
+                "/pistonprotection.backend.BackendService/RollbackConfig" => {
                     #[allow(non_camel_case_types)]
-                    struct SetProtectionLevelSvc<T: BackendService>(pub Arc<T>);
+                    struct RollbackConfigSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::SetProtectionLevelRequest>
-                    for SetProtectionLevelSvc<T> {
-                        type Response = super::SetProtectionLevelResponse;
+                    > tonic::server::UnaryService<super::RollbackConfigRequest>
+                    for RollbackConfigSvc<T> {
+                        type Response = super::RollbackConfigResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::SetProtectionLevelRequest>,
+                            request: tonic::Request<super::RollbackConfigRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::set_protection_level(&inner, request)
+                                <T as BackendService>::rollback_config(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -2204,7 +6589,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = SetProtectionLevelSvc(inner);
+                        let method = RollbackConfigSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2220,25 +6605,25 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/GetBackendStatus" => {
+                "/pistonprotection.backend.BackendService/GetApprovalPolicy" => {
                     #[allow(non_camel_case_types)]
-                    struct GetBackendStatusSvc<T: BackendService>(pub Arc<T>);
+                    struct GetApprovalPolicySvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::GetBackendStatusRequest>
-                    for GetBackendStatusSvc<T> {
-                        type Response = super::GetBackendStatusResponse;
+                    > tonic::server::UnaryService<super::GetApprovalPolicyRequest>
+                    for GetApprovalPolicySvc<T> {
+                        type Response = super::GetApprovalPolicyResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::GetBackendStatusRequest>,
+                            request: tonic::Request<super::GetApprovalPolicyRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::get_backend_status(&inner, request)
+                                <T as BackendService>::get_approval_policy(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -2250,7 +6635,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = GetBackendStatusSvc(inner);
+                        let method = GetApprovalPolicySvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2266,27 +6651,28 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/WatchBackendStatus" => {
+                "/pistonprotection.backend.BackendService/UpdateApprovalPolicy" => {
                     #[allow(non_camel_case_types)]
-                    struct WatchBackendStatusSvc<T: BackendService>(pub Arc<T>);
+                    struct UpdateApprovalPolicySvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::ServerStreamingService<
-                        super::WatchBackendStatusRequest,
-                    > for WatchBackendStatusSvc<T> {
-                        type Response = super::BackendStatus;
-                        type ResponseStream = T::WatchBackendStatusStream;
+                    > tonic::server::UnaryService<super::UpdateApprovalPolicyRequest>
+                    for UpdateApprovalPolicySvc<T> {
+                        type Response = super::UpdateApprovalPolicyResponse;
                         type Future = BoxFuture<
-                            tonic::Response<Self::ResponseStream>,
+                            tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::WatchBackendStatusRequest>,
+                            request: tonic::Request<super::UpdateApprovalPolicyRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::watch_backend_status(&inner, request)
+                                <T as BackendService>::update_approval_policy(
+                                        &inner,
+                                        request,
+                                    )
                                     .await
                             };
                             Box::pin(fut)
@@ -2298,7 +6684,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = WatchBackendStatusSvc(inner);
+                        let method = UpdateApprovalPolicySvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2309,30 +6695,31 @@ pub mod backend_service_server {
                                 max_decoding_message_size,
                                 max_encoding_message_size,
                             );
-                        let res = grpc.server_streaming(method, req).await;
+                        let res = grpc.unary(method, req).await;
                         Ok(res)
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/AddDomain" => {
+                "/pistonprotection.backend.BackendService/ListPendingActions" => {
                     #[allow(non_camel_case_types)]
-                    struct AddDomainSvc<T: BackendService>(pub Arc<T>);
+                    struct ListPendingActionsSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::AddDomainRequest>
-                    for AddDomainSvc<T> {
-                        type Response = super::AddDomainResponse;
+                    > tonic::server::UnaryService<super::ListPendingActionsRequest>
+                    for ListPendingActionsSvc<T> {
+                        type Response = super::ListPendingActionsResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::AddDomainRequest>,
+                            request: tonic::Request<super::ListPendingActionsRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::add_domain(&inner, request).await
+                                <T as BackendService>::list_pending_actions(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2343,7 +6730,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = AddDomainSvc(inner);
+                        let method = ListPendingActionsSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2359,25 +6746,29 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/RemoveDomain" => {
+                "/pistonprotection.backend.BackendService/ApprovePendingAction" => {
                     #[allow(non_camel_case_types)]
-                    struct RemoveDomainSvc<T: BackendService>(pub Arc<T>);
+                    struct ApprovePendingActionSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::RemoveDomainRequest>
-                    for RemoveDomainSvc<T> {
-                        type Response = super::RemoveDomainResponse;
+                    > tonic::server::UnaryService<super::ApprovePendingActionRequest>
+                    for ApprovePendingActionSvc<T> {
+                        type Response = super::ApprovePendingActionResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RemoveDomainRequest>,
+                            request: tonic::Request<super::ApprovePendingActionRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::remove_domain(&inner, request).await
+                                <T as BackendService>::approve_pending_action(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2388,7 +6779,7 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = RemoveDomainSvc(inner);
+                        let method = ApprovePendingActionSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2404,25 +6795,29 @@ pub mod backend_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.backend.BackendService/VerifyDomain" => {
+                "/pistonprotection.backend.BackendService/RejectPendingAction" => {
                     #[allow(non_camel_case_types)]
-                    struct VerifyDomainSvc<T: BackendService>(pub Arc<T>);
+                    struct RejectPendingActionSvc<T: BackendService>(pub Arc<T>);
                     impl<
                         T: BackendService,
-                    > tonic::server::UnaryService<super::VerifyDomainRequest>
-                    for VerifyDomainSvc<T> {
-                        type Response = super::VerifyDomainResponse;
+                    > tonic::server::UnaryService<super::RejectPendingActionRequest>
+                    for RejectPendingActionSvc<T> {
+                        type Response = super::RejectPendingActionResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::VerifyDomainRequest>,
+                            request: tonic::Request<super::RejectPendingActionRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as BackendService>::verify_domain(&inner, request).await
+                                <T as BackendService>::reject_pending_action(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2433,7 +6828,154 @@ pub mod backend_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = VerifyDomainSvc(inner);
+                        let method = RejectPendingActionSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/CreateExemptionToken" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateExemptionTokenSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::CreateExemptionTokenRequest>
+                    for CreateExemptionTokenSvc<T> {
+                        type Response = super::CreateExemptionTokenResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateExemptionTokenRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::create_exemption_token(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CreateExemptionTokenSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/RevokeExemptionToken" => {
+                    #[allow(non_camel_case_types)]
+                    struct RevokeExemptionTokenSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::RevokeExemptionTokenRequest>
+                    for RevokeExemptionTokenSvc<T> {
+                        type Response = super::RevokeExemptionTokenResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RevokeExemptionTokenRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::revoke_exemption_token(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RevokeExemptionTokenSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.backend.BackendService/ListExemptionTokens" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListExemptionTokensSvc<T: BackendService>(pub Arc<T>);
+                    impl<
+                        T: BackendService,
+                    > tonic::server::UnaryService<super::ListExemptionTokensRequest>
+                    for ListExemptionTokensSvc<T> {
+                        type Response = super::ListExemptionTokensResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListExemptionTokensRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as BackendService>::list_exemption_tokens(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ListExemptionTokensSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -89,6 +89,12 @@ pub struct Pagination {
     pub page_size: u32,
     #[prost(string, tag = "3")]
     pub cursor: ::prost::alloc::string::String,
+    /// Field to sort by, prefixed with "-" for descending order
+    #[prost(string, tag = "4")]
+    pub order_by: ::prost::alloc::string::String,
+    /// Conjunction of comparisons, e.g. `name ~ "api" AND created_at >= 2024-01-01T00:00:00Z`
+    #[prost(string, tag = "5")]
+    pub filter: ::prost::alloc::string::String,
 }
 /// Pagination response
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -267,6 +267,9 @@ pub struct CreateRuleRequest {
 pub struct CreateRuleResponse {
     #[prost(message, optional, tag = "1")]
     pub rule: ::core::option::Option<FilterRule>,
+    /// Conflicts with other rules of the backend, resolved by priority
+    #[prost(string, repeated, tag = "2")]
+    pub warnings: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -295,6 +298,9 @@ pub struct UpdateRuleRequest {
 pub struct UpdateRuleResponse {
     #[prost(message, optional, tag = "1")]
     pub rule: ::core::option::Option<FilterRule>,
+    /// Conflicts with other rules of the backend, resolved by priority
+    #[prost(string, repeated, tag = "2")]
+    pub warnings: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -347,6 +353,9 @@ pub struct BulkCreateRulesResponse {
     pub rules: ::prost::alloc::vec::Vec<FilterRule>,
     #[prost(message, repeated, tag = "2")]
     pub errors: ::prost::alloc::vec::Vec<super::common::Error>,
+    /// Conflicts of the created rules, resolved by priority
+    #[prost(string, repeated, tag = "3")]
+    pub warnings: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -367,6 +376,118 @@ pub struct BulkDeleteRulesResponse {
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ExportRulesRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    /// Limit the export to one backend of the organization
+    #[prost(string, tag = "2")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "TransferFormat", tag = "3")]
+    pub format: i32,
+    #[prost(bool, tag = "4")]
+    pub include_disabled: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ExportRulesResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub content_type: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "3")]
+    pub rule_count: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ImportRulesRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    /// Backend of rows that do not name one
+    #[prost(string, tag = "2")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "TransferFormat", tag = "3")]
+    pub format: i32,
+    #[prost(bytes = "vec", tag = "4")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    /// Validate the rows without creating any rules
+    #[prost(bool, tag = "5")]
+    pub dry_run: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportRulesResponse {
+    #[prost(uint32, tag = "1")]
+    pub total_rows: u32,
+    /// Rules created, or that would be created in a dry run
+    #[prost(uint32, tag = "2")]
+    pub imported: u32,
+    #[prost(message, repeated, tag = "3")]
+    pub errors: ::prost::alloc::vec::Vec<ImportRowError>,
+    /// Conflicts of the imported rules, resolved by priority
+    #[prost(string, repeated, tag = "4")]
+    pub warnings: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(bool, tag = "5")]
+    pub dry_run: bool,
+}
+/// A row that was not imported
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ImportRowError {
+    /// 1-based position of the row among the data rows
+    #[prost(uint32, tag = "1")]
+    pub row: u32,
+    /// Column at fault, if known
+    #[prost(string, tag = "2")]
+    pub field: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub message: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ImportProviderConfigRequest {
+    #[prost(string, tag = "1")]
+    pub organization_id: ::prost::alloc::string::String,
+    /// Backend the imported rules protect
+    #[prost(string, tag = "2")]
+    pub backend_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "ConfigProvider", tag = "3")]
+    pub provider: i32,
+    #[prost(bytes = "vec", tag = "4")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    /// Translate and validate without creating any rules
+    #[prost(bool, tag = "5")]
+    pub dry_run: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportProviderConfigResponse {
+    /// Entries read from the export
+    #[prost(uint32, tag = "1")]
+    pub total_entries: u32,
+    /// Rules created, or that would be created in a dry run
+    #[prost(uint32, tag = "2")]
+    pub imported: u32,
+    /// Entries that could not be translated, numbered by position in the export
+    #[prost(message, repeated, tag = "3")]
+    pub errors: ::prost::alloc::vec::Vec<ImportRowError>,
+    /// Conflicts of the imported rules, resolved by priority
+    #[prost(string, repeated, tag = "4")]
+    pub warnings: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Provider features that were dropped or have no equivalent
+    #[prost(string, repeated, tag = "5")]
+    pub unsupported: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(bool, tag = "6")]
+    pub dry_run: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct ReorderRulesRequest {
     #[prost(string, tag = "1")]
     pub backend_id: ::prost::alloc::string::String,
@@ -599,6 +720,72 @@ impl MinecraftBedrockPacketType {
         }
     }
 }
+/// File format of rule imports and exports
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum TransferFormat {
+    Unspecified = 0,
+    Json = 1,
+    Csv = 2,
+}
+impl TransferFormat {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "TRANSFER_FORMAT_UNSPECIFIED",
+            Self::Json => "TRANSFER_FORMAT_JSON",
+            Self::Csv => "TRANSFER_FORMAT_CSV",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "TRANSFER_FORMAT_UNSPECIFIED" => Some(Self::Unspecified),
+            "TRANSFER_FORMAT_JSON" => Some(Self::Json),
+            "TRANSFER_FORMAT_CSV" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+/// Provider whose exported configuration is imported
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ConfigProvider {
+    Unspecified = 0,
+    /// IP access rules, rate limits and custom rules of the Cloudflare API
+    Cloudflare = 1,
+    /// JSON array of {type, value, action, note} entries
+    Generic = 2,
+}
+impl ConfigProvider {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "CONFIG_PROVIDER_UNSPECIFIED",
+            Self::Cloudflare => "CONFIG_PROVIDER_CLOUDFLARE",
+            Self::Generic => "CONFIG_PROVIDER_GENERIC",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CONFIG_PROVIDER_UNSPECIFIED" => Some(Self::Unspecified),
+            "CONFIG_PROVIDER_CLOUDFLARE" => Some(Self::Cloudflare),
+            "CONFIG_PROVIDER_GENERIC" => Some(Self::Generic),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod filter_service_client {
     #![allow(
@@ -890,6 +1077,95 @@ pub mod filter_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        /// Import and export of an organization's rules and blocks as CSV or JSON
+        pub async fn export_rules(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExportRulesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExportRulesResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.filter.FilterService/ExportRules",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.filter.FilterService",
+                        "ExportRules",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn import_rules(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImportRulesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ImportRulesResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.filter.FilterService/ImportRules",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.filter.FilterService",
+                        "ImportRules",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Import of configuration exported from another protection provider
+        pub async fn import_provider_config(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImportProviderConfigRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ImportProviderConfigResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.filter.FilterService/ImportProviderConfig",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.filter.FilterService",
+                        "ImportProviderConfig",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
         /// Rule ordering
         pub async fn reorder_rules(
             &mut self,
@@ -1043,6 +1319,29 @@ pub mod filter_service_server {
             tonic::Response<super::BulkDeleteRulesResponse>,
             tonic::Status,
         >;
+        /// Import and export of an organization's rules and blocks as CSV or JSON
+        async fn export_rules(
+            &self,
+            request: tonic::Request<super::ExportRulesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExportRulesResponse>,
+            tonic::Status,
+        >;
+        async fn import_rules(
+            &self,
+            request: tonic::Request<super::ImportRulesRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ImportRulesResponse>,
+            tonic::Status,
+        >;
+        /// Import of configuration exported from another protection provider
+        async fn import_provider_config(
+            &self,
+            request: tonic::Request<super::ImportProviderConfigRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ImportProviderConfigResponse>,
+            tonic::Status,
+        >;
         /// Rule ordering
         async fn reorder_rules(
             &self,
@@ -1465,6 +1764,145 @@ pub mod filter_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/pistonprotection.filter.FilterService/ExportRules" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExportRulesSvc<T: FilterService>(pub Arc<T>);
+                    impl<
+                        T: FilterService,
+                    > tonic::server::UnaryService<super::ExportRulesRequest>
+                    for ExportRulesSvc<T> {
+                        type Response = super::ExportRulesResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExportRulesRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as FilterService>::export_rules(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ExportRulesSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.filter.FilterService/ImportRules" => {
+                    #[allow(non_camel_case_types)]
+                    struct ImportRulesSvc<T: FilterService>(pub Arc<T>);
+                    impl<
+                        T: FilterService,
+                    > tonic::server::UnaryService<super::ImportRulesRequest>
+                    for ImportRulesSvc<T> {
+                        type Response = super::ImportRulesResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ImportRulesRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as FilterService>::import_rules(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ImportRulesSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.filter.FilterService/ImportProviderConfig" => {
+                    #[allow(non_camel_case_types)]
+                    struct ImportProviderConfigSvc<T: FilterService>(pub Arc<T>);
+                    impl<
+                        T: FilterService,
+                    > tonic::server::UnaryService<super::ImportProviderConfigRequest>
+                    for ImportProviderConfigSvc<T> {
+                        type Response = super::ImportProviderConfigResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ImportProviderConfigRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as FilterService>::import_provider_config(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ImportProviderConfigSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/pistonprotection.filter.FilterService/ReorderRules" => {
                     #[allow(non_camel_case_types)]
                     struct ReorderRulesSvc<T: FilterService>(pub Arc<T>);
@@ -42,6 +42,11 @@ pub struct TrafficMetrics {
         ::prost::alloc::string::String,
         u64,
     >,
+    /// Stream position, only set on streamed updates
+    #[prost(uint64, tag = "16")]
+    pub sequence: u64,
+    #[prost(string, tag = "17")]
+    pub resume_token: ::prost::alloc::string::String,
 }
 /// Attack metrics
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -82,6 +87,11 @@ pub struct AttackMetrics {
     pub unique_attack_ips: u32,
     #[prost(message, repeated, tag = "14")]
     pub top_sources: ::prost::alloc::vec::Vec<AttackSource>,
+    /// Stream position, only set on streamed updates
+    #[prost(uint64, tag = "15")]
+    pub sequence: u64,
+    #[prost(string, tag = "16")]
+    pub resume_token: ::prost::alloc::string::String,
 }
 /// Attack source information
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -389,6 +399,11 @@ pub struct StreamTrafficMetricsRequest {
     pub backend_id: ::prost::alloc::string::String,
     #[prost(uint32, tag = "2")]
     pub interval_seconds: u32,
+    /// Resume after the last sequence received, with the token it came with
+    #[prost(uint64, tag = "3")]
+    pub resume_after: u64,
+    #[prost(string, tag = "4")]
+    pub resume_token: ::prost::alloc::string::String,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -412,6 +427,11 @@ pub struct StreamAttackMetricsRequest {
     pub backend_id: ::prost::alloc::string::String,
     #[prost(uint32, tag = "2")]
     pub interval_seconds: u32,
+    /// Resume after the last sequence received, with the token it came with
+    #[prost(uint64, tag = "3")]
+    pub resume_after: u64,
+    #[prost(string, tag = "4")]
+    pub resume_token: ::prost::alloc::string::String,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
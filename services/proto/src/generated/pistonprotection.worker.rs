@@ -30,6 +30,71 @@ pub struct Worker {
     pub registered_at: ::core::option::Option<super::common::Timestamp>,
     #[prost(message, optional, tag = "9")]
     pub last_heartbeat: ::core::option::Option<super::common::Timestamp>,
+    /// Deployment region (e.g. "eu-west")
+    #[prost(string, tag = "10")]
+    pub region: ::prost::alloc::string::String,
+    /// Loaded eBPF programs
+    #[prost(message, repeated, tag = "11")]
+    pub programs: ::prost::alloc::vec::Vec<ProgramVersion>,
+    /// Backends placed on this worker (set by the control plane)
+    #[prost(string, repeated, tag = "12")]
+    pub assigned_backends: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Version of a loaded eBPF program
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ProgramVersion {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// Worker build the program object shipped with
+    #[prost(string, tag = "2")]
+    pub version: ::prost::alloc::string::String,
+    /// Load generation, bumped on every (re)load
+    #[prost(uint64, tag = "3")]
+    pub generation: u64,
+    /// SHA-256 of the loaded object file (hex)
+    #[prost(string, tag = "4")]
+    pub digest: ::prost::alloc::string::String,
+    /// Error from the last attempt to load a rollout target, if it failed
+    #[prost(string, tag = "5")]
+    pub load_error: ::prost::alloc::string::String,
+    /// Why the last load or attach of the program failed, if it did
+    #[prost(message, optional, tag = "6")]
+    pub diagnostic: ::core::option::Option<LoadDiagnostic>,
+    /// Fingerprint of the key that signed the loaded object, empty when
+    /// artifact signatures are not enforced
+    #[prost(string, tag = "7")]
+    pub signed_by: ::prost::alloc::string::String,
+}
+/// Failure to load an eBPF program on a worker, for remote diagnosis
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct LoadDiagnostic {
+    /// Program (object) name
+    #[prost(string, tag = "1")]
+    pub program: ::prost::alloc::string::String,
+    /// Section of the program in the object (e.g. "xdp")
+    #[prost(string, tag = "2")]
+    pub section: ::prost::alloc::string::String,
+    #[prost(enumeration = "LoadStage", tag = "3")]
+    pub stage: i32,
+    #[prost(string, tag = "4")]
+    pub error: ::prost::alloc::string::String,
+    /// Line of the verifier log explaining the rejection
+    #[prost(string, tag = "5")]
+    pub summary: ::prost::alloc::string::String,
+    /// Tail of the verifier log
+    #[prost(string, tag = "6")]
+    pub verifier_log: ::prost::alloc::string::String,
+    /// Kernel release and program variant the load was attempted with
+    #[prost(string, tag = "7")]
+    pub kernel_release: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub variant: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "9")]
+    pub failed_at: ::core::option::Option<super::common::Timestamp>,
 }
 /// Network interface on worker
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -108,6 +173,14 @@ pub struct WorkerCapabilities {
     pub kernel_major: u32,
     #[prost(uint32, tag = "12")]
     pub kernel_minor: u32,
+    /// Probed BPF features (e.g. "ringbuf", "lpm_trie") and whether kernel BTF is present
+    #[prost(string, repeated, tag = "13")]
+    pub bpf_features: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(bool, tag = "14")]
+    pub btf: bool,
+    /// Program build the kernel can load ("full", "compat"; empty if none)
+    #[prost(string, tag = "15")]
+    pub program_variant: ::prost::alloc::string::String,
 }
 /// Filter configuration for worker
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -127,6 +200,38 @@ pub struct FilterConfig {
     /// Generated at
     #[prost(message, optional, tag = "5")]
     pub generated_at: ::core::option::Option<super::common::Timestamp>,
+    /// When set, evaluate this configuration as a canary alongside the active
+    /// one (counters only) instead of enforcing it
+    #[prost(message, optional, tag = "6")]
+    pub canary: ::core::option::Option<CanarySettings>,
+    /// eBPF program versions this worker should run (set by program rollouts)
+    #[prost(message, repeated, tag = "7")]
+    pub programs: ::prost::alloc::vec::Vec<ProgramTarget>,
+}
+/// eBPF program version a worker should load
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ProgramTarget {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub version: ::prost::alloc::string::String,
+    /// Expected SHA-256 of the object file (hex)
+    #[prost(string, tag = "3")]
+    pub digest: ::prost::alloc::string::String,
+}
+/// Canary evaluation settings
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct CanarySettings {
+    /// How long to evaluate the candidate before the canary expires
+    #[prost(uint32, tag = "1")]
+    pub duration_seconds: u32,
+    /// Enforce the candidate automatically once the period has elapsed
+    #[prost(bool, tag = "2")]
+    pub auto_promote: bool,
 }
 /// Backend-specific filter configuration
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -149,6 +254,13 @@ pub struct BackendFilter {
     /// Filter rules
     #[prost(message, repeated, tag = "6")]
     pub rules: ::prost::alloc::vec::Vec<super::filter::FilterRule>,
+    /// Organization owning the backend; workers keep a separate map namespace
+    /// (blocklists, rate limits, config) per organization
+    #[prost(string, tag = "7")]
+    pub organization_id: ::prost::alloc::string::String,
+    /// Hostnames of the backend, for L7 responses
+    #[prost(string, repeated, tag = "8")]
+    pub domains: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// Protection configuration
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -177,6 +289,96 @@ pub struct ProtectionConfig {
     pub challenge_enabled: bool,
     #[prost(uint32, tag = "8")]
     pub challenge_threshold: u32,
+    /// Honeypot ports
+    #[prost(message, optional, tag = "9")]
+    pub honeypot: ::core::option::Option<HoneypotConfig>,
+    /// Allowlist learning mode
+    #[prost(message, optional, tag = "10")]
+    pub learning: ::core::option::Option<LearningConfig>,
+    /// Responses served to clients refused at L7
+    #[prost(message, optional, tag = "11")]
+    pub block_responses: ::core::option::Option<BlockResponseConfig>,
+    /// Per-connection bandwidth of established flows
+    #[prost(message, optional, tag = "12")]
+    pub connection_rate: ::core::option::Option<ConnectionRateConfig>,
+    /// Marking of greylisted traffic
+    #[prost(message, optional, tag = "13")]
+    pub marking: ::core::option::Option<MarkingConfig>,
+}
+/// Honeypot ports of a backend; sources sending to them are flagged
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct HoneypotConfig {
+    #[prost(uint32, repeated, tag = "1")]
+    pub ports: ::prost::alloc::vec::Vec<u32>,
+    /// Block flagged sources in the global blocklist
+    #[prost(bool, tag = "2")]
+    pub auto_block: bool,
+    /// 0 = until unblocked
+    #[prost(uint32, tag = "3")]
+    pub block_duration_seconds: u32,
+}
+/// Allowlist learning of a backend; during the training window sources that
+/// complete full protocol handshakes become known-good and get relaxed limits
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct LearningConfig {
+    #[prost(bool, tag = "1")]
+    pub enabled: bool,
+    #[prost(uint32, tag = "2")]
+    pub training_seconds: u32,
+    /// Applied to the limits of known-good sources
+    #[prost(uint32, tag = "3")]
+    pub limit_multiplier: u32,
+    /// Handshakes before a source is known-good
+    #[prost(uint32, tag = "4")]
+    pub min_handshakes: u32,
+}
+/// Responses served to clients refused at L7; empty fields keep the defaults
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct BlockResponseConfig {
+    /// HTML page of refused HTTP requests, with {{status}}, {{reason}},
+    /// {{host}} and {{client_ip}} placeholders
+    #[prost(string, tag = "1")]
+    pub http_block_page: ::prost::alloc::string::String,
+    /// Disconnect message of refused Minecraft logins (JSON text component)
+    #[prost(string, tag = "2")]
+    pub minecraft_kick_message: ::prost::alloc::string::String,
+}
+/// Per-connection bandwidth of a backend, enforced on established TCP
+/// connections and UDP flows
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ConnectionRateConfig {
+    /// 0 = unlimited
+    #[prost(uint64, tag = "1")]
+    pub bytes_per_second: u64,
+    /// 0 = one second of bytes_per_second
+    #[prost(uint64, tag = "2")]
+    pub burst_bytes: u64,
+}
+/// Marking of a backend's greylisted traffic: packets beyond the greylist
+/// limit get a scavenger DSCP and pass instead of being dropped
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct MarkingConfig {
+    #[prost(bool, tag = "1")]
+    pub enabled: bool,
+    /// 0 = lower effort
+    #[prost(uint32, tag = "2")]
+    pub dscp: u32,
+    /// Set CE on ECN-capable packets
+    #[prost(bool, tag = "3")]
+    pub ecn_ce: bool,
+    /// Also mark traffic within the limit
+    #[prost(bool, tag = "4")]
+    pub mark_all_greylisted: bool,
 }
 /// Rate limit config for XDP
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -191,7 +393,7 @@ pub struct RateLimitConfig {
 /// Global filter settings
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GlobalFilterSettings {
     /// Default action for unmatched traffic
     #[prost(enumeration = "super::common::Action", tag = "1")]
@@ -204,6 +406,37 @@ pub struct GlobalFilterSettings {
     pub emergency_mode: bool,
     #[prost(uint64, tag = "4")]
     pub emergency_pps_threshold: u64,
+    /// Penalty ladders replacing the worker defaults, at most one per level
+    #[prost(message, repeated, tag = "5")]
+    pub penalty_ladder: ::prost::alloc::vec::Vec<PenaltyLevel>,
+}
+/// Escalating penalties for repeat offenders at one protection level.
+/// Thresholds count violations (after decay), including the current one.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct PenaltyLevel {
+    /// Protection level (0-4)
+    #[prost(uint32, tag = "1")]
+    pub protection_level: u32,
+    /// Violations before the source's limits are divided by rate_limit_divisor
+    #[prost(uint32, tag = "2")]
+    pub rate_limit_threshold: u32,
+    #[prost(uint32, tag = "3")]
+    pub rate_limit_divisor: u32,
+    /// Violations before a short block
+    #[prost(uint32, tag = "4")]
+    pub short_block_threshold: u32,
+    #[prost(uint32, tag = "5")]
+    pub short_block_seconds: u32,
+    /// Violations before a long block
+    #[prost(uint32, tag = "6")]
+    pub long_block_threshold: u32,
+    #[prost(uint32, tag = "7")]
+    pub long_block_seconds: u32,
+    /// Seconds for one violation to decay (0 = never)
+    #[prost(uint32, tag = "8")]
+    pub decay_seconds: u32,
 }
 /// eBPF map update
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -284,6 +517,9 @@ pub struct RegisterResponse {
     pub worker_id: ::prost::alloc::string::String,
     #[prost(message, optional, tag = "2")]
     pub initial_config: ::core::option::Option<FilterConfig>,
+    /// Log filter directives of the worker's node, if any
+    #[prost(message, optional, tag = "3")]
+    pub log_level: ::core::option::Option<LogLevel>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -298,6 +534,9 @@ pub struct HeartbeatRequest {
     /// Current config version for checking if update is needed
     #[prost(uint32, tag = "4")]
     pub current_config_version: u32,
+    /// Currently loaded eBPF programs
+    #[prost(message, repeated, tag = "5")]
+    pub programs: ::prost::alloc::vec::Vec<ProgramVersion>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -333,12 +572,18 @@ pub struct InterfaceMetrics {
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Copy, PartialEq, Eq, Hash, ::prost::Message)]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct HeartbeatResponse {
     #[prost(bool, tag = "1")]
     pub config_update_available: bool,
     #[prost(uint32, tag = "2")]
     pub latest_config_version: u32,
+    /// Worker is unknown to the control plane and must register again
+    #[prost(bool, tag = "3")]
+    pub reregister_required: bool,
+    /// Log filter directives of the worker's node, if any
+    #[prost(message, optional, tag = "4")]
+    pub log_level: ::core::option::Option<LogLevel>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -357,6 +602,24 @@ pub struct DeregisterResponse {
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ListWorkersRequest {
+    /// Only workers in this region (empty = all)
+    #[prost(string, tag = "1")]
+    pub region: ::prost::alloc::string::String,
+    /// Only workers with this status (unspecified = all)
+    #[prost(enumeration = "WorkerStatus", tag = "2")]
+    pub status: i32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListWorkersResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub workers: ::prost::alloc::vec::Vec<Worker>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct GetConfigRequest {
     #[prost(string, tag = "1")]
     pub worker_id: ::prost::alloc::string::String,
@@ -429,6 +692,9 @@ pub struct BackendMetrics {
         ::prost::alloc::string::String,
         u64,
     >,
+    /// Part of bytes_in dropped by the filters
+    #[prost(uint64, tag = "9")]
+    pub bytes_dropped: u64,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -639,6 +905,227 @@ pub struct MapEntry {
     #[prost(bytes = "vec", tag = "2")]
     pub value: ::prost::alloc::vec::Vec<u8>,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetLoadDiagnosticsRequest {
+    /// Empty for all workers and programs
+    #[prost(string, tag = "1")]
+    pub worker_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub program: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetLoadDiagnosticsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub diagnostics: ::prost::alloc::vec::Vec<WorkerLoadDiagnostic>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct WorkerLoadDiagnostic {
+    #[prost(string, tag = "1")]
+    pub worker_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub node_name: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub diagnostic: ::core::option::Option<LoadDiagnostic>,
+}
+/// Fleet-wide rollout of an eBPF program version in waves
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RolloutSpec {
+    #[prost(string, tag = "1")]
+    pub program: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub version: ::prost::alloc::string::String,
+    /// Expected SHA-256 of the object file (hex)
+    #[prost(string, tag = "3")]
+    pub digest: ::prost::alloc::string::String,
+    /// Cumulative share of workers updated by the end of each wave, in percent
+    /// (e.g. 10, 50, 100)
+    #[prost(uint32, repeated, tag = "4")]
+    pub wave_percentages: ::prost::alloc::vec::Vec<u32>,
+    /// How long to observe each wave before moving on
+    #[prost(uint32, tag = "5")]
+    pub observe_seconds: u32,
+    /// Roll back when the average drop rate of updated workers rises by more
+    /// than this (absolute, 0.0 - 1.0) over their pre-update baseline
+    #[prost(double, tag = "6")]
+    pub max_drop_rate_increase: f64,
+    /// Roll back when more workers than this fail to load the new version
+    #[prost(uint32, tag = "7")]
+    pub max_failed_workers: u32,
+    /// Program variant the object was built as ("full" if empty); only
+    /// workers reporting this variant take part
+    #[prost(string, tag = "8")]
+    pub variant: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WorkerRollout {
+    #[prost(string, tag = "1")]
+    pub worker_id: ::prost::alloc::string::String,
+    /// Wave the worker was selected in (0 = not selected yet)
+    #[prost(uint32, tag = "2")]
+    pub wave: u32,
+    #[prost(enumeration = "WorkerRolloutState", tag = "3")]
+    pub state: i32,
+    #[prost(string, tag = "4")]
+    pub previous_version: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub previous_digest: ::prost::alloc::string::String,
+    /// Drop rate when the worker was selected, and the latest one
+    #[prost(double, tag = "6")]
+    pub baseline_drop_rate: f64,
+    #[prost(double, tag = "7")]
+    pub drop_rate: f64,
+    #[prost(string, tag = "8")]
+    pub error: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Rollout {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub spec: ::core::option::Option<RolloutSpec>,
+    #[prost(enumeration = "RolloutPhase", tag = "3")]
+    pub phase: i32,
+    /// 1-based index of the wave in progress
+    #[prost(uint32, tag = "4")]
+    pub current_wave: u32,
+    #[prost(message, repeated, tag = "5")]
+    pub workers: ::prost::alloc::vec::Vec<WorkerRollout>,
+    /// Why the rollout stopped, for rolled back and aborted rollouts
+    #[prost(string, tag = "6")]
+    pub message: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "7")]
+    pub started_at: ::core::option::Option<super::common::Timestamp>,
+    #[prost(message, optional, tag = "8")]
+    pub updated_at: ::core::option::Option<super::common::Timestamp>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StartRolloutRequest {
+    #[prost(message, optional, tag = "1")]
+    pub spec: ::core::option::Option<RolloutSpec>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetRolloutRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct ListRolloutsRequest {
+    /// Only rollouts of this program (empty = all)
+    #[prost(string, tag = "1")]
+    pub program: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListRolloutsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub rollouts: ::prost::alloc::vec::Vec<Rollout>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct AbortRolloutRequest {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    /// Send updated workers back to their previous version
+    #[prost(bool, tag = "2")]
+    pub rollback: bool,
+}
+/// Log filter directives layered over the startup filter of a node's worker
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct LogLevel {
+    #[prost(string, tag = "1")]
+    pub node_name: ::prost::alloc::string::String,
+    /// tracing filter directives, e.g. "ebpf=debug" or "worker::config_sync=trace"
+    #[prost(string, repeated, tag = "2")]
+    pub directives: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Changes with every update of the node's directives
+    #[prost(uint64, tag = "3")]
+    pub revision: u64,
+    #[prost(message, optional, tag = "4")]
+    pub updated_at: ::core::option::Option<super::common::Timestamp>,
+    /// When the directives lapse (unset = kept until cleared)
+    #[prost(message, optional, tag = "5")]
+    pub expires_at: ::core::option::Option<super::common::Timestamp>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct GetLogLevelRequest {
+    #[prost(string, tag = "1")]
+    pub node_name: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
+pub struct SetLogLevelRequest {
+    #[prost(string, tag = "1")]
+    pub node_name: ::prost::alloc::string::String,
+    /// Replaces the node's directives; empty clears them
+    #[prost(string, repeated, tag = "2")]
+    pub directives: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Lifetime of the directives (0 = until cleared)
+    #[prost(uint32, tag = "3")]
+    pub ttl_seconds: u32,
+}
+/// Step of loading an eBPF program that failed
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum LoadStage {
+    Unspecified = 0,
+    /// Parsing the object file and creating its maps
+    Object = 1,
+    /// BPF_PROG_LOAD: the kernel verifier rejected the program
+    Verifier = 2,
+    /// Attaching the loaded program to an interface
+    Attach = 3,
+}
+impl LoadStage {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "LOAD_STAGE_UNSPECIFIED",
+            Self::Object => "LOAD_STAGE_OBJECT",
+            Self::Verifier => "LOAD_STAGE_VERIFIER",
+            Self::Attach => "LOAD_STAGE_ATTACH",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "LOAD_STAGE_UNSPECIFIED" => Some(Self::Unspecified),
+            "LOAD_STAGE_OBJECT" => Some(Self::Object),
+            "LOAD_STAGE_VERIFIER" => Some(Self::Verifier),
+            "LOAD_STAGE_ATTACH" => Some(Self::Attach),
+            _ => None,
+        }
+    }
+}
 /// XDP attachment mode
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -794,30 +1281,117 @@ impl ConnTrackState {
         }
     }
 }
-/// Generated client implementations.
-pub mod worker_service_client {
-    #![allow(
-        unused_variables,
-        dead_code,
-        missing_docs,
-        clippy::wildcard_imports,
-        clippy::let_unit_value,
-    )]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
-    /// Worker service for control plane communication
-    #[derive(Debug, Clone)]
-    pub struct WorkerServiceClient<T> {
-        inner: tonic::client::Grpc<T>,
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum RolloutPhase {
+    Unspecified = 0,
+    /// Waiting for the current wave's workers to report the new digest
+    Rolling = 1,
+    /// Current wave is updated, watching error and drop deltas
+    Observing = 2,
+    Completed = 3,
+    RolledBack = 4,
+    Aborted = 5,
+}
+impl RolloutPhase {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "ROLLOUT_PHASE_UNSPECIFIED",
+            Self::Rolling => "ROLLOUT_PHASE_ROLLING",
+            Self::Observing => "ROLLOUT_PHASE_OBSERVING",
+            Self::Completed => "ROLLOUT_PHASE_COMPLETED",
+            Self::RolledBack => "ROLLOUT_PHASE_ROLLED_BACK",
+            Self::Aborted => "ROLLOUT_PHASE_ABORTED",
+        }
     }
-    impl WorkerServiceClient<tonic::transport::Channel> {
-        /// Attempt to create a new client by connecting to a given endpoint.
-        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
-        where
-            D: TryInto<tonic::transport::Endpoint>,
-            D::Error: Into<StdError>,
-        {
-            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ROLLOUT_PHASE_UNSPECIFIED" => Some(Self::Unspecified),
+            "ROLLOUT_PHASE_ROLLING" => Some(Self::Rolling),
+            "ROLLOUT_PHASE_OBSERVING" => Some(Self::Observing),
+            "ROLLOUT_PHASE_COMPLETED" => Some(Self::Completed),
+            "ROLLOUT_PHASE_ROLLED_BACK" => Some(Self::RolledBack),
+            "ROLLOUT_PHASE_ABORTED" => Some(Self::Aborted),
+            _ => None,
+        }
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum WorkerRolloutState {
+    Unspecified = 0,
+    /// Not yet selected for a wave
+    Pending = 1,
+    /// Told to load the new version, not yet confirmed
+    Updating = 2,
+    /// Reports the new digest
+    Updated = 3,
+    /// Reported a load error or timed out
+    Failed = 4,
+    /// Sent back to its previous version
+    Reverted = 5,
+}
+impl WorkerRolloutState {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Unspecified => "WORKER_ROLLOUT_STATE_UNSPECIFIED",
+            Self::Pending => "WORKER_ROLLOUT_STATE_PENDING",
+            Self::Updating => "WORKER_ROLLOUT_STATE_UPDATING",
+            Self::Updated => "WORKER_ROLLOUT_STATE_UPDATED",
+            Self::Failed => "WORKER_ROLLOUT_STATE_FAILED",
+            Self::Reverted => "WORKER_ROLLOUT_STATE_REVERTED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "WORKER_ROLLOUT_STATE_UNSPECIFIED" => Some(Self::Unspecified),
+            "WORKER_ROLLOUT_STATE_PENDING" => Some(Self::Pending),
+            "WORKER_ROLLOUT_STATE_UPDATING" => Some(Self::Updating),
+            "WORKER_ROLLOUT_STATE_UPDATED" => Some(Self::Updated),
+            "WORKER_ROLLOUT_STATE_FAILED" => Some(Self::Failed),
+            "WORKER_ROLLOUT_STATE_REVERTED" => Some(Self::Reverted),
+            _ => None,
+        }
+    }
+}
+/// Generated client implementations.
+pub mod worker_service_client {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// Worker service for control plane communication
+    #[derive(Debug, Clone)]
+    pub struct WorkerServiceClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl WorkerServiceClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
             Ok(Self::new(conn))
         }
     }
@@ -968,6 +1542,143 @@ pub mod worker_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn list_workers(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListWorkersRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListWorkersResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.worker.WorkerService/ListWorkers",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.worker.WorkerService",
+                        "ListWorkers",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// eBPF program rollouts
+        pub async fn start_rollout(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StartRolloutRequest>,
+        ) -> std::result::Result<tonic::Response<super::Rollout>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.worker.WorkerService/StartRollout",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.worker.WorkerService",
+                        "StartRollout",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_rollout(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetRolloutRequest>,
+        ) -> std::result::Result<tonic::Response<super::Rollout>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.worker.WorkerService/GetRollout",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.worker.WorkerService",
+                        "GetRollout",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn list_rollouts(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListRolloutsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListRolloutsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.worker.WorkerService/ListRollouts",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.worker.WorkerService",
+                        "ListRollouts",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn abort_rollout(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AbortRolloutRequest>,
+        ) -> std::result::Result<tonic::Response<super::Rollout>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.worker.WorkerService/AbortRollout",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.worker.WorkerService",
+                        "AbortRollout",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
         /// Configuration
         pub async fn get_config(
             &mut self,
@@ -1281,6 +1992,88 @@ pub mod worker_service_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        pub async fn get_load_diagnostics(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetLoadDiagnosticsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetLoadDiagnosticsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.worker.WorkerService/GetLoadDiagnostics",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.worker.WorkerService",
+                        "GetLoadDiagnostics",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Runtime log filter of a node
+        pub async fn get_log_level(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetLogLevelRequest>,
+        ) -> std::result::Result<tonic::Response<super::LogLevel>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.worker.WorkerService/GetLogLevel",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.worker.WorkerService",
+                        "GetLogLevel",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn set_log_level(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetLogLevelRequest>,
+        ) -> std::result::Result<tonic::Response<super::LogLevel>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/pistonprotection.worker.WorkerService/SetLogLevel",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "pistonprotection.worker.WorkerService",
+                        "SetLogLevel",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -1318,6 +2111,33 @@ pub mod worker_service_server {
             tonic::Response<super::DeregisterResponse>,
             tonic::Status,
         >;
+        async fn list_workers(
+            &self,
+            request: tonic::Request<super::ListWorkersRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListWorkersResponse>,
+            tonic::Status,
+        >;
+        /// eBPF program rollouts
+        async fn start_rollout(
+            &self,
+            request: tonic::Request<super::StartRolloutRequest>,
+        ) -> std::result::Result<tonic::Response<super::Rollout>, tonic::Status>;
+        async fn get_rollout(
+            &self,
+            request: tonic::Request<super::GetRolloutRequest>,
+        ) -> std::result::Result<tonic::Response<super::Rollout>, tonic::Status>;
+        async fn list_rollouts(
+            &self,
+            request: tonic::Request<super::ListRolloutsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListRolloutsResponse>,
+            tonic::Status,
+        >;
+        async fn abort_rollout(
+            &self,
+            request: tonic::Request<super::AbortRolloutRequest>,
+        ) -> std::result::Result<tonic::Response<super::Rollout>, tonic::Status>;
         /// Configuration
         async fn get_config(
             &self,
@@ -1410,6 +2230,22 @@ pub mod worker_service_server {
             tonic::Response<super::DumpMapsResponse>,
             tonic::Status,
         >;
+        async fn get_load_diagnostics(
+            &self,
+            request: tonic::Request<super::GetLoadDiagnosticsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetLoadDiagnosticsResponse>,
+            tonic::Status,
+        >;
+        /// Runtime log filter of a node
+        async fn get_log_level(
+            &self,
+            request: tonic::Request<super::GetLogLevelRequest>,
+        ) -> std::result::Result<tonic::Response<super::LogLevel>, tonic::Status>;
+        async fn set_log_level(
+            &self,
+            request: tonic::Request<super::SetLogLevelRequest>,
+        ) -> std::result::Result<tonic::Response<super::LogLevel>, tonic::Status>;
     }
     /// Worker service for control plane communication
     #[derive(Debug)]
@@ -1623,25 +2459,25 @@ pub mod worker_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.worker.WorkerService/GetConfig" => {
+                "/pistonprotection.worker.WorkerService/ListWorkers" => {
                     #[allow(non_camel_case_types)]
-                    struct GetConfigSvc<T: WorkerService>(pub Arc<T>);
+                    struct ListWorkersSvc<T: WorkerService>(pub Arc<T>);
                     impl<
                         T: WorkerService,
-                    > tonic::server::UnaryService<super::GetConfigRequest>
-                    for GetConfigSvc<T> {
-                        type Response = super::GetConfigResponse;
+                    > tonic::server::UnaryService<super::ListWorkersRequest>
+                    for ListWorkersSvc<T> {
+                        type Response = super::ListWorkersResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::GetConfigRequest>,
+                            request: tonic::Request<super::ListWorkersRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as WorkerService>::get_config(&inner, request).await
+                                <T as WorkerService>::list_workers(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -1652,7 +2488,7 @@ pub mod worker_service_server {
                     let max_encoding_message_size = self.max_encoding_message_size;
                     let inner = self.inner.clone();
                     let fut = async move {
-                        let method = GetConfigSvc(inner);
+                        let method = ListWorkersSvc(inner);
                         let codec = tonic_prost::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1668,9 +2504,234 @@ pub mod worker_service_server {
                     };
                     Box::pin(fut)
                 }
-                "/pistonprotection.worker.WorkerService/StreamConfig" => {
+                "/pistonprotection.worker.WorkerService/StartRollout" => {
                     #[allow(non_camel_case_types)]
-                    struct StreamConfigSvc<T: WorkerService>(pub Arc<T>);
+                    struct StartRolloutSvc<T: WorkerService>(pub Arc<T>);
+                    impl<
+                        T: WorkerService,
+                    > tonic::server::UnaryService<super::StartRolloutRequest>
+                    for StartRolloutSvc<T> {
+                        type Response = super::Rollout;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StartRolloutRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerService>::start_rollout(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = StartRolloutSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.worker.WorkerService/GetRollout" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetRolloutSvc<T: WorkerService>(pub Arc<T>);
+                    impl<
+                        T: WorkerService,
+                    > tonic::server::UnaryService<super::GetRolloutRequest>
+                    for GetRolloutSvc<T> {
+                        type Response = super::Rollout;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetRolloutRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerService>::get_rollout(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetRolloutSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.worker.WorkerService/ListRollouts" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListRolloutsSvc<T: WorkerService>(pub Arc<T>);
+                    impl<
+                        T: WorkerService,
+                    > tonic::server::UnaryService<super::ListRolloutsRequest>
+                    for ListRolloutsSvc<T> {
+                        type Response = super::ListRolloutsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListRolloutsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerService>::list_rollouts(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = ListRolloutsSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.worker.WorkerService/AbortRollout" => {
+                    #[allow(non_camel_case_types)]
+                    struct AbortRolloutSvc<T: WorkerService>(pub Arc<T>);
+                    impl<
+                        T: WorkerService,
+                    > tonic::server::UnaryService<super::AbortRolloutRequest>
+                    for AbortRolloutSvc<T> {
+                        type Response = super::Rollout;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AbortRolloutRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerService>::abort_rollout(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = AbortRolloutSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.worker.WorkerService/GetConfig" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetConfigSvc<T: WorkerService>(pub Arc<T>);
+                    impl<
+                        T: WorkerService,
+                    > tonic::server::UnaryService<super::GetConfigRequest>
+                    for GetConfigSvc<T> {
+                        type Response = super::GetConfigResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetConfigRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerService>::get_config(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetConfigSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.worker.WorkerService/StreamConfig" => {
+                    #[allow(non_camel_case_types)]
+                    struct StreamConfigSvc<T: WorkerService>(pub Arc<T>);
                     impl<
                         T: WorkerService,
                     > tonic::server::ServerStreamingService<super::StreamConfigRequest>
@@ -2123,6 +3184,142 @@ pub mod worker_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/pistonprotection.worker.WorkerService/GetLoadDiagnostics" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetLoadDiagnosticsSvc<T: WorkerService>(pub Arc<T>);
+                    impl<
+                        T: WorkerService,
+                    > tonic::server::UnaryService<super::GetLoadDiagnosticsRequest>
+                    for GetLoadDiagnosticsSvc<T> {
+                        type Response = super::GetLoadDiagnosticsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetLoadDiagnosticsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerService>::get_load_diagnostics(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetLoadDiagnosticsSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.worker.WorkerService/GetLogLevel" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetLogLevelSvc<T: WorkerService>(pub Arc<T>);
+                    impl<
+                        T: WorkerService,
+                    > tonic::server::UnaryService<super::GetLogLevelRequest>
+                    for GetLogLevelSvc<T> {
+                        type Response = super::LogLevel;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetLogLevelRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerService>::get_log_level(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetLogLevelSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/pistonprotection.worker.WorkerService/SetLogLevel" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetLogLevelSvc<T: WorkerService>(pub Arc<T>);
+                    impl<
+                        T: WorkerService,
+                    > tonic::server::UnaryService<super::SetLogLevelRequest>
+                    for SetLogLevelSvc<T> {
+                        type Response = super::LogLevel;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetLogLevelRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerService>::set_log_level(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SetLogLevelSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         let mut response = http::Response::new(
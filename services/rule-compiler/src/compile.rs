@@ -0,0 +1,488 @@
+//! Rule compilation
+//!
+//! Rules are compiled in precedence order (priority, then id). What each
+//! rule compiles into:
+//!
+//! | Rule                                         | Entries                        |
+//! |----------------------------------------------|--------------------------------|
+//! | `source_ip_blacklist` (any action)           | `xdp_filter` blocked IPs       |
+//! | drop from `source_ips`                       | `xdp_filter` blocked IPs       |
+//! | drop to `destination_ports`                  | `xdp_udp` blocked ports        |
+//! | allow from `source_ips` (optionally per protocol) | TCP/HTTP/UDP/QUIC allowlists |
+//!
+//! Conflicting rules are resolved in precedence order: a source allowed by a
+//! rule is not blocked by a later rule, and UDP allowlist entries are withheld
+//! when an earlier rule drops UDP ports (the allowlist is checked first). Networks are expanded into one entry per address, since the maps
+//! are hash maps rather than LPM tries; networks wider than
+//! [`MIN_PREFIX_V4`] / [`MIN_PREFIX_V6`] are left to userspace.
+
+use crate::conflict::{self, Conflict};
+use crate::error::{CompileError, Result};
+use crate::rule::{Action, Cidr, Protocol, Rule};
+use crate::target::{self, MapTarget};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+
+/// Shortest IPv4 prefix expanded into per-address entries
+pub const MIN_PREFIX_V4: u8 = 16;
+
+/// Shortest IPv6 prefix expanded into per-address entries
+pub const MIN_PREFIX_V6: u8 = 112;
+
+/// Map entry produced by one or more rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapEntry {
+    pub target: MapTarget,
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    /// Rules that produced the entry, sorted
+    pub rules: Vec<String>,
+}
+
+impl fmt::Display for MapEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} = {} ({})",
+            self.target,
+            hex(&self.key),
+            hex(&self.value),
+            self.rules.join(", ")
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MapOp {
+    Update,
+    Delete,
+}
+
+/// Change to apply to a map
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapMutation {
+    pub target: MapTarget,
+    pub op: MapOp,
+    pub key: Vec<u8>,
+    /// Empty for deletes
+    pub value: Vec<u8>,
+}
+
+impl fmt::Display for MapMutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.op {
+            MapOp::Update => write!(
+                f,
+                "update {} {} = {}",
+                self.target,
+                hex(&self.key),
+                hex(&self.value)
+            ),
+            MapOp::Delete => write!(f, "delete {} {}", self.target, hex(&self.key)),
+        }
+    }
+}
+
+/// Part of a rule that was not compiled into map entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    pub rule: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.reason)
+    }
+}
+
+/// Map entries of a rule set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompiledRules {
+    entries: BTreeMap<(MapTarget, Vec<u8>), MapEntry>,
+    map_sizes: BTreeMap<MapTarget, usize>,
+    skipped: Vec<Skipped>,
+    conflicts: Vec<Conflict>,
+}
+
+impl CompiledRules {
+    /// Entries sorted by program, map and key
+    pub fn entries(&self) -> impl Iterator<Item = &MapEntry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Rule parts left to userspace, in compilation order
+    pub fn skipped(&self) -> &[Skipped] {
+        &self.skipped
+    }
+
+    /// Conflicting rule pairs, in precedence order
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Conflicts a rule is part of
+    pub fn conflicts_of<'a>(&'a self, rule: &'a str) -> impl Iterator<Item = &'a Conflict> {
+        self.conflicts.iter().filter(move |c| c.involves(rule))
+    }
+
+    /// Programs with at least one entry
+    pub fn programs(&self) -> BTreeSet<&'static str> {
+        self.entries.keys().map(|(t, _)| t.program).collect()
+    }
+
+    /// Updates installing every entry
+    pub fn mutations(&self) -> Vec<MapMutation> {
+        self.diff(&CompiledRules::default())
+    }
+
+    /// Mutations turning the maps of `previous` into these
+    ///
+    /// Deletes come first so a map never has to hold both sets at once.
+    pub fn diff(&self, previous: &CompiledRules) -> Vec<MapMutation> {
+        let deletes = previous
+            .entries
+            .iter()
+            .filter(|(k, _)| !self.entries.contains_key(*k))
+            .map(|((target, key), _)| MapMutation {
+                target: *target,
+                op: MapOp::Delete,
+                key: key.clone(),
+                value: Vec::new(),
+            });
+        let updates = self
+            .entries
+            .iter()
+            .filter(|(k, e)| previous.entries.get(*k).map(|p| &p.value) != Some(&e.value))
+            .map(|((target, key), e)| MapMutation {
+                target: *target,
+                op: MapOp::Update,
+                key: key.clone(),
+                value: e.value.clone(),
+            });
+        deletes.chain(updates).collect()
+    }
+
+    fn skip(&mut self, rule: &Rule, reason: impl Into<String>) {
+        self.skipped.push(Skipped {
+            rule: rule.id.clone(),
+            reason: reason.into(),
+        });
+    }
+
+    fn insert(
+        &mut self,
+        rule: &Rule,
+        target: MapTarget,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Result<()> {
+        if let Some(entry) = self.entries.get_mut(&(target, key.clone())) {
+            if !entry.rules.contains(&rule.id) {
+                entry.rules.push(rule.id.clone());
+                entry.rules.sort();
+            }
+            return Ok(());
+        }
+
+        let in_map = self.map_sizes.entry(target).or_default();
+        if *in_map >= target.max_entries {
+            return Err(CompileError::MapFull {
+                rule: rule.id.clone(),
+                program: target.program,
+                map: target.map,
+                max_entries: target.max_entries,
+            });
+        }
+        *in_map += 1;
+        self.entries.insert(
+            (target, key.clone()),
+            MapEntry {
+                target,
+                key,
+                value,
+                rules: vec![rule.id.clone()],
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Compile rules into map entries
+///
+/// Disabled rules are ignored. Fails on malformed networks or ports, and
+/// when a map would overflow.
+pub fn compile(rules: &[Rule]) -> Result<CompiledRules> {
+    let mut ordered: Vec<&Rule> = rules.iter().filter(|r| r.enabled).collect();
+    ordered.sort_by(|a, b| (a.priority, &a.id).cmp(&(b.priority, &b.id)));
+
+    let mut compiler = Compiler::default();
+    for rule in &ordered {
+        compiler.rule(rule)?;
+    }
+    compiler.out.conflicts = conflict::find_conflicts(&ordered);
+    Ok(compiler.out)
+}
+
+#[derive(Default)]
+struct Compiler<'a> {
+    out: CompiledRules,
+    /// Sources allowed so far, with the rule allowing them
+    allowed: HashMap<IpAddr, &'a str>,
+    /// Rules that dropped UDP ports so far
+    udp_port_drops: BTreeSet<&'a str>,
+}
+
+impl<'a> Compiler<'a> {
+    fn rule(&mut self, rule: &'a Rule) -> Result<()> {
+        validate(rule)?;
+
+        self.block_sources(rule, &rule.source_ip_blacklist)?;
+        if !rule.has_match_criteria() {
+            return Ok(());
+        }
+        if rule.has_userspace_criteria() {
+            self.out.skip(
+                rule,
+                "matches on GeoIP, ASN, L7 or schedule criteria, which are evaluated in userspace",
+            );
+            return Ok(());
+        }
+
+        match rule.action {
+            Action::Allow => self.allow(rule),
+            Action::Drop => self.drop(rule),
+            action => {
+                self.out
+                    .skip(rule, format!("{} actions are applied in userspace", action));
+                Ok(())
+            }
+        }
+    }
+
+    fn allow(&mut self, rule: &'a Rule) -> Result<()> {
+        if rule.source_ips.is_empty() {
+            self.out.skip(
+                rule,
+                "allow rules compile only when scoped to source networks",
+            );
+            return Ok(());
+        }
+        if !rule.destination_ips.is_empty() || !rule.destination_ports.is_empty() {
+            self.out
+                .skip(rule, "allowlists cannot be scoped to destinations");
+            return Ok(());
+        }
+
+        let mut protocols: BTreeSet<Protocol> = if rule.protocols.is_empty() {
+            Protocol::ALL.into_iter().collect()
+        } else {
+            rule.protocols.iter().copied().collect()
+        };
+        // A UDP allowlist entry would let the source past the port drops of
+        // higher-priority rules
+        if !self.udp_port_drops.is_empty() && protocols.remove(&Protocol::Udp) {
+            let rules: Vec<&str> = self.udp_port_drops.iter().copied().collect();
+            self.out.skip(
+                rule,
+                format!(
+                    "UDP sources are not allowlisted as higher-priority rules drop UDP ports: {}",
+                    rules.join(", ")
+                ),
+            );
+        }
+
+        let mut uncovered = BTreeSet::new();
+        for cidr in &rule.source_ips {
+            let Some(addrs) = self.expand(rule, cidr) else {
+                continue;
+            };
+            for addr in addrs {
+                for protocol in &protocols {
+                    let targets = match addr {
+                        IpAddr::V4(_) => target::allowlists_v4(*protocol),
+                        IpAddr::V6(_) => target::allowlists_v6(*protocol),
+                    };
+                    if targets.is_empty() {
+                        uncovered.insert((family(&addr), *protocol));
+                    }
+                    for target in targets {
+                        self.out.insert(
+                            rule,
+                            *target,
+                            address_key(&addr),
+                            target::flag_value(target::PRESENT),
+                        )?;
+                    }
+                }
+                self.allowed.entry(addr).or_insert(&rule.id);
+            }
+        }
+
+        for (family, protocol) in uncovered {
+            self.out.skip(
+                rule,
+                format!("no allowlist for {} {} traffic", family, protocol),
+            );
+        }
+        Ok(())
+    }
+
+    fn drop(&mut self, rule: &'a Rule) -> Result<()> {
+        let scoped = !rule.destination_ips.is_empty()
+            || !rule.destination_ports.is_empty()
+            || !rule.protocols.is_empty();
+
+        if !rule.source_ips.is_empty() {
+            if scoped {
+                self.out.skip(
+                    rule,
+                    "drops from sources scoped to destinations or protocols are applied in userspace",
+                );
+                return Ok(());
+            }
+            return self.block_sources(rule, &rule.source_ips);
+        }
+
+        if rule.destination_ports.is_empty() || !rule.destination_ips.is_empty() {
+            self.out.skip(
+                rule,
+                "drop rules compile only when scoped to source networks or destination ports",
+            );
+            return Ok(());
+        }
+        if !rule.protocols.is_empty() && !rule.protocols.contains(&Protocol::Udp) {
+            self.out
+                .skip(rule, "only UDP destination ports can be blocked in XDP");
+            return Ok(());
+        }
+        if rule.protocols.iter().any(|p| *p != Protocol::Udp) || rule.protocols.is_empty() {
+            self.out
+                .skip(rule, "only UDP traffic to the ports is dropped in XDP");
+        }
+
+        for range in &rule.destination_ports {
+            for port in range.start..=range.end {
+                self.out.insert(
+                    rule,
+                    target::UDP_BLOCKED_PORTS,
+                    target::port_key(port as u16),
+                    target::flag_value(target::PRESENT),
+                )?;
+            }
+        }
+        self.udp_port_drops.insert(&rule.id);
+        Ok(())
+    }
+
+    /// Addresses of a network, if it is narrow enough to expand
+    fn expand(&mut self, rule: &Rule, cidr: &Cidr) -> Option<impl Iterator<Item = IpAddr> + use<>> {
+        let min_prefix = match cidr.addr {
+            IpAddr::V4(_) => MIN_PREFIX_V4,
+            IpAddr::V6(_) => MIN_PREFIX_V6,
+        };
+        if cidr.prefix < min_prefix {
+            self.out.skip(
+                rule,
+                format!(
+                    "network {} is wider than /{} and is not expanded into map entries",
+                    cidr, min_prefix
+                ),
+            );
+            return None;
+        }
+        Some(cidr.addresses())
+    }
+
+    fn block_sources(&mut self, rule: &Rule, cidrs: &[Cidr]) -> Result<()> {
+        let mut allowed_by = BTreeSet::new();
+        for cidr in cidrs {
+            let Some(addrs) = self.expand(rule, cidr) else {
+                continue;
+            };
+            for addr in addrs {
+                if let Some(allowing) = self.allowed.get(&addr) {
+                    allowed_by.insert(*allowing);
+                    continue;
+                }
+                let target = match addr {
+                    IpAddr::V4(_) => target::BLOCKED_IPS_V4,
+                    IpAddr::V6(_) => target::BLOCKED_IPS_V6,
+                };
+                self.out.insert(
+                    rule,
+                    target,
+                    address_key(&addr),
+                    target::blocked_ip_value(target::BLOCK_REASON_MANUAL),
+                )?;
+            }
+        }
+
+        if !allowed_by.is_empty() {
+            let rules: Vec<&str> = allowed_by.into_iter().collect();
+            self.out.skip(
+                rule,
+                format!(
+                    "sources allowed by higher-priority rules are not blocked: {}",
+                    rules.join(", ")
+                ),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn validate(rule: &Rule) -> Result<()> {
+    let networks = rule
+        .source_ips
+        .iter()
+        .chain(&rule.source_ip_blacklist)
+        .chain(&rule.destination_ips);
+    for cidr in networks {
+        if !cidr.is_valid() {
+            return Err(CompileError::InvalidNetwork {
+                rule: rule.id.clone(),
+                network: cidr.to_string(),
+                reason: format!("prefix exceeds /{}", cidr.max_prefix()),
+            });
+        }
+    }
+
+    for range in &rule.destination_ports {
+        if !range.is_valid() {
+            return Err(CompileError::InvalidPortRange {
+                rule: rule.id.clone(),
+                start: range.start,
+                end: range.end,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn address_key(addr: &IpAddr) -> Vec<u8> {
+    match addr {
+        IpAddr::V4(v4) => target::ipv4_key(*v4),
+        IpAddr::V6(v6) => target::ipv6_key(*v6),
+    }
+}
+
+fn family(addr: &IpAddr) -> &'static str {
+    match addr {
+        IpAddr::V4(_) => "IPv4",
+        IpAddr::V6(_) => "IPv6",
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
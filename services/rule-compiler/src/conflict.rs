@@ -0,0 +1,143 @@
+//! Conflict detection between rules
+//!
+//! Two rules conflict when some traffic matches both and they disagree on
+//! what to do with it. Conflicts never make compilation fail: the rule that
+//! comes first in precedence order (priority, then id) wins, and the
+//! conflict is reported so the author can make the intent explicit.
+
+use crate::rule::{Action, Cidr, PortRange, Rule};
+use std::fmt;
+
+/// What two rules disagree on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictKind {
+    /// The same sources have different actions
+    Sources,
+    /// Overlapping destination ports have different actions
+    Ports,
+    /// Other overlapping traffic has different actions, e.g. a source
+    /// allowed by one rule sending to a port dropped by the other
+    Traffic,
+}
+
+/// Pair of conflicting rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Rule that takes precedence
+    pub winner: String,
+    pub loser: String,
+    pub kind: ConflictKind,
+    /// Both rules have the same priority and the winner was picked by id
+    pub tie: bool,
+}
+
+impl Conflict {
+    pub fn involves(&self, rule: &str) -> bool {
+        self.winner == rule || self.loser == rule
+    }
+
+    /// The rule in conflict with `rule`, if `rule` is part of the conflict
+    pub fn other(&self, rule: &str) -> Option<&str> {
+        if self.winner == rule {
+            Some(&self.loser)
+        } else if self.loser == rule {
+            Some(&self.winner)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            ConflictKind::Sources => "apply different actions to the same sources",
+            ConflictKind::Ports => "apply different actions to the same destination ports",
+            ConflictKind::Traffic => "apply different actions to overlapping traffic",
+        };
+        write!(
+            f,
+            "{} and {} {}; {} takes precedence",
+            self.winner, self.loser, what, self.winner
+        )?;
+        if self.tie {
+            write!(f, " by rule id as both have the same priority")?;
+        }
+        Ok(())
+    }
+}
+
+/// Conflicts between rules given in precedence order
+pub(crate) fn find_conflicts(ordered: &[&Rule]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for (i, winner) in ordered.iter().enumerate() {
+        for loser in &ordered[i + 1..] {
+            let Some(kind) = conflict_kind(winner, loser) else {
+                continue;
+            };
+            conflicts.push(Conflict {
+                winner: winner.id.clone(),
+                loser: loser.id.clone(),
+                kind,
+                tie: winner.priority == loser.priority,
+            });
+        }
+    }
+    conflicts
+}
+
+fn conflict_kind(a: &Rule, b: &Rule) -> Option<ConflictKind> {
+    // Blacklists drop their sources whatever else the rule matches on
+    if networks_overlap(&a.source_ip_blacklist, allowed_sources(b))
+        || networks_overlap(allowed_sources(a), &b.source_ip_blacklist)
+    {
+        return Some(ConflictKind::Sources);
+    }
+
+    // Rules narrowed by GeoIP, ASN, L7 or schedule criteria are not compared,
+    // as whether their traffic overlaps is only known at runtime. Logging
+    // does not decide the fate of traffic.
+    let comparable =
+        |r: &Rule| r.action != Action::Log && r.has_match_criteria() && !r.has_userspace_criteria();
+    if a.action == b.action || !comparable(a) || !comparable(b) {
+        return None;
+    }
+    let overlap = any_overlap(&a.source_ips, &b.source_ips, networks_overlap)
+        && any_overlap(&a.destination_ips, &b.destination_ips, networks_overlap)
+        && any_overlap(&a.protocols, &b.protocols, |x, y| {
+            x.iter().any(|p| y.contains(p))
+        })
+        && any_overlap(&a.destination_ports, &b.destination_ports, ports_overlap);
+    if !overlap {
+        return None;
+    }
+
+    Some(if !a.source_ips.is_empty() && !b.source_ips.is_empty() {
+        ConflictKind::Sources
+    } else if !a.destination_ports.is_empty() && !b.destination_ports.is_empty() {
+        ConflictKind::Ports
+    } else {
+        ConflictKind::Traffic
+    })
+}
+
+/// Whether two criteria overlap, where an empty list matches anything
+fn any_overlap<T>(a: &[T], b: &[T], overlaps: impl Fn(&[T], &[T]) -> bool) -> bool {
+    a.is_empty() || b.is_empty() || overlaps(a, b)
+}
+
+fn allowed_sources(rule: &Rule) -> &[Cidr] {
+    if rule.action == Action::Allow {
+        &rule.source_ips
+    } else {
+        &[]
+    }
+}
+
+fn networks_overlap(a: &[Cidr], b: &[Cidr]) -> bool {
+    a.iter().any(|x| b.iter().any(|y| x.overlaps(y)))
+}
+
+fn ports_overlap(a: &[PortRange], b: &[PortRange]) -> bool {
+    a.iter().any(|x| b.iter().any(|y| x.overlaps(y)))
+}
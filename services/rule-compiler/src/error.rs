@@ -0,0 +1,32 @@
+//! Compilation errors
+
+use thiserror::Error;
+
+/// Why a rule cannot be compiled
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CompileError {
+    #[error("rule {rule}: invalid network {network}: {reason}")]
+    InvalidNetwork {
+        rule: String,
+        network: String,
+        reason: String,
+    },
+
+    #[error("rule {rule}: invalid port range {start}-{end}")]
+    InvalidPortRange { rule: String, start: u32, end: u32 },
+
+    #[error("rule {rule}: {program}/{map} cannot hold more than {max_entries} entries")]
+    MapFull {
+        rule: String,
+        program: &'static str,
+        map: &'static str,
+        max_entries: usize,
+    },
+}
+
+/// Error parsing an address or network in CIDR notation
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid network {0}")]
+pub struct ParseCidrError(pub String);
+
+pub type Result<T> = std::result::Result<T, CompileError>;
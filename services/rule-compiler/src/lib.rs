@@ -0,0 +1,29 @@
+//! PistonProtection Rule Compiler
+//!
+//! Translates filter rules into the entries of the eBPF maps the XDP programs
+//! consult. Both the gateway (API-created rules) and the operator (`FilterRule`
+//! resources) convert their rule representation into the canonical [`Rule`]
+//! and compile it here, so a rule is enforced the same way whichever path it
+//! came from.
+//!
+//! Compilation is deterministic: the same rules always produce the same
+//! entries in the same order, so a compiled rule set can be diffed against
+//! the previous one to get the minimal set of [`MapMutation`]s to apply.
+//!
+//! Only criteria that a hash map lookup can enforce without widening the rule
+//! are compiled. Everything else (GeoIP, ASN and L7 matching, port-scoped
+//! allows, ...) is left to userspace and reported as [`Skipped`]. Rules that
+//! disagree on the same traffic are resolved by priority and reported as
+//! [`Conflict`]s.
+
+pub mod compile;
+pub mod conflict;
+pub mod error;
+pub mod rule;
+pub mod target;
+
+pub use compile::{CompiledRules, MapEntry, MapMutation, MapOp, Skipped, compile};
+pub use conflict::{Conflict, ConflictKind};
+pub use error::CompileError;
+pub use rule::{Action, Cidr, PortRange, Protocol, Rule};
+pub use target::MapTarget;
@@ -0,0 +1,317 @@
+//! Canonical rule model
+
+use crate::error::ParseCidrError;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Action taken on matching traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Allow,
+    Drop,
+    RateLimit,
+    Challenge,
+    Log,
+    Redirect,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Action::Allow => "allow",
+            Action::Drop => "drop",
+            Action::RateLimit => "rate_limit",
+            Action::Challenge => "challenge",
+            Action::Log => "log",
+            Action::Redirect => "redirect",
+        })
+    }
+}
+
+/// Transport protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 3] = [Protocol::Tcp, Protocol::Udp, Protocol::Icmp];
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+            Protocol::Icmp => "ICMP",
+        })
+    }
+}
+
+/// Inclusive port range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl PortRange {
+    pub const fn new(start: u32, end: u32) -> Self {
+        Self { start, end }
+    }
+
+    pub const fn single(port: u16) -> Self {
+        Self::new(port as u32, port as u32)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.start <= self.end && self.end <= u16::MAX as u32
+    }
+
+    pub fn overlaps(&self, other: &PortRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+/// Address or network in CIDR notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+
+impl Cidr {
+    pub const fn new(addr: IpAddr, prefix: u8) -> Self {
+        Self { addr, prefix }
+    }
+
+    /// Single-address network
+    pub const fn host(addr: IpAddr) -> Self {
+        let prefix = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        Self { addr, prefix }
+    }
+
+    pub fn max_prefix(&self) -> u8 {
+        match self.addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.prefix <= self.max_prefix()
+    }
+
+    /// Whether the networks share any address
+    pub fn overlaps(&self, other: &Cidr) -> bool {
+        let prefix = self.prefix.min(other.prefix);
+        match (self.addr, other.addr) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => {
+                let mask = u32::MAX
+                    .checked_shl(32u32.saturating_sub(prefix.into()))
+                    .unwrap_or(0);
+                u32::from(a) & mask == u32::from(b) & mask
+            }
+            (IpAddr::V6(a), IpAddr::V6(b)) => {
+                let mask = u128::MAX
+                    .checked_shl(128u32.saturating_sub(prefix.into()))
+                    .unwrap_or(0);
+                u128::from(a) & mask == u128::from(b) & mask
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of addresses in the network, saturating at `u128::MAX`
+    pub fn size(&self) -> u128 {
+        let host_bits = u32::from(self.max_prefix().saturating_sub(self.prefix));
+        1u128.checked_shl(host_bits).unwrap_or(u128::MAX)
+    }
+
+    /// Every address in the network, lowest first (host bits of `addr` are
+    /// ignored)
+    pub fn addresses(&self) -> impl Iterator<Item = IpAddr> + use<> {
+        let host_bits = u32::from(self.max_prefix().saturating_sub(self.prefix));
+        let (base, v4) = match self.addr {
+            IpAddr::V4(addr) => (u128::from(u32::from(addr)), true),
+            IpAddr::V6(addr) => (u128::from(addr), false),
+        };
+        let mask = u128::MAX.checked_shl(host_bits).unwrap_or(0);
+        let base = base & mask;
+        let count = self.size();
+        (0..count).map(move |offset| {
+            let addr = base + offset;
+            if v4 {
+                IpAddr::V4(Ipv4Addr::from(addr as u32))
+            } else {
+                IpAddr::V6(Ipv6Addr::from(addr))
+            }
+        })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl From<IpAddr> for Cidr {
+    fn from(addr: IpAddr) -> Self {
+        Self::host(addr)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = ParseCidrError;
+
+    /// Parse `10.0.0.0/24`, `2001:db8::/64` or a bare address
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseCidrError(s.to_string());
+        let cidr = match s.split_once('/') {
+            Some((addr, prefix)) => Cidr::new(
+                addr.parse().map_err(|_| err())?,
+                prefix.parse().map_err(|_| err())?,
+            ),
+            None => Cidr::host(s.parse().map_err(|_| err())?),
+        };
+        if !cidr.is_valid() {
+            return Err(err());
+        }
+        Ok(cidr)
+    }
+}
+
+/// Filter rule in the form the compiler understands
+///
+/// Frontends (the gateway's API model and the operator's `FilterRule`
+/// resource) convert into this. Criteria within a rule are ANDed; the
+/// entries of each list are ORed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub id: String,
+    /// Lower values take precedence
+    pub priority: i32,
+    pub action: Action,
+    pub enabled: bool,
+    /// Sources the action applies to
+    pub source_ips: Vec<Cidr>,
+    /// Sources that are always dropped, whatever the action
+    pub source_ip_blacklist: Vec<Cidr>,
+    pub destination_ips: Vec<Cidr>,
+    pub destination_ports: Vec<PortRange>,
+    pub protocols: Vec<Protocol>,
+    pub countries: Vec<String>,
+    pub asns: Vec<String>,
+    /// The rule matches on L7 protocols or payload
+    pub l7: bool,
+    /// The rule only applies at certain times
+    pub scheduled: bool,
+}
+
+impl Rule {
+    pub fn new(id: impl Into<String>, priority: i32, action: Action) -> Self {
+        Self {
+            id: id.into(),
+            priority,
+            action,
+            enabled: true,
+            source_ips: Vec::new(),
+            source_ip_blacklist: Vec::new(),
+            destination_ips: Vec::new(),
+            destination_ports: Vec::new(),
+            protocols: Vec::new(),
+            countries: Vec::new(),
+            asns: Vec::new(),
+            l7: false,
+            scheduled: false,
+        }
+    }
+
+    /// Whether the rule narrows matches with criteria the XDP programs
+    /// cannot see
+    pub(crate) fn has_userspace_criteria(&self) -> bool {
+        !self.countries.is_empty() || !self.asns.is_empty() || self.l7 || self.scheduled
+    }
+
+    /// Whether the rule matches on anything besides its blacklist
+    pub(crate) fn has_match_criteria(&self) -> bool {
+        !self.source_ips.is_empty()
+            || !self.destination_ips.is_empty()
+            || !self.destination_ports.is_empty()
+            || !self.protocols.is_empty()
+            || self.has_userspace_criteria()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cidr() {
+        let cidr: Cidr = "10.0.0.0/24".parse().unwrap();
+        assert_eq!(cidr.addr, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)));
+        assert_eq!(cidr.prefix, 24);
+
+        let host: Cidr = "2001:db8::1".parse().unwrap();
+        assert_eq!(host.prefix, 128);
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/24".parse::<Cidr>().is_err());
+        assert!("2001:db8::/129".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_cidr_addresses() {
+        let cidr: Cidr = "192.168.1.5/30".parse().unwrap();
+        let addrs: Vec<String> = cidr.addresses().map(|a| a.to_string()).collect();
+        assert_eq!(
+            addrs,
+            ["192.168.1.4", "192.168.1.5", "192.168.1.6", "192.168.1.7"]
+        );
+
+        let cidr: Cidr = "2001:db8::ff/127".parse().unwrap();
+        assert_eq!(cidr.size(), 2);
+        assert_eq!(
+            cidr.addresses().next(),
+            Some("2001:db8::fe".parse().unwrap())
+        );
+
+        let all: Cidr = "::/0".parse().unwrap();
+        assert_eq!(all.size(), u128::MAX);
+    }
+
+    #[test]
+    fn test_cidr_overlaps() {
+        let net: Cidr = "10.0.0.0/16".parse().unwrap();
+        assert!(net.overlaps(&"10.0.5.1".parse().unwrap()));
+        assert!(net.overlaps(&"10.0.0.0/8".parse().unwrap()));
+        assert!(!net.overlaps(&"10.1.0.0/16".parse().unwrap()));
+        assert!(!net.overlaps(&"::/0".parse().unwrap()));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.overlaps(&"192.0.2.1".parse().unwrap()));
+
+        let v6: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(v6.overlaps(&"2001:db8:1::1".parse().unwrap()));
+        assert!(!v6.overlaps(&"2001:db9::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_port_range_validity() {
+        assert!(PortRange::new(80, 80).is_valid());
+        assert!(PortRange::new(0, 65535).is_valid());
+        assert!(!PortRange::new(443, 80).is_valid());
+        assert!(!PortRange::new(1, 70000).is_valid());
+
+        assert!(PortRange::new(80, 90).overlaps(&PortRange::single(90)));
+        assert!(!PortRange::new(80, 90).overlaps(&PortRange::new(91, 100)));
+    }
+}
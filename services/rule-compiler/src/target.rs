@@ -0,0 +1,134 @@
+//! eBPF maps rules compile into, and their key and value encodings
+//!
+//! Encodings mirror the map definitions in the `ebpf` crate. The programs
+//! run on little-endian (`bpfel`) targets:
+//!
+//! | Key           | Encoding                                            |
+//! |---------------|-----------------------------------------------------|
+//! | IPv4 address  | `u32` in host order (`u32::from_be(saddr)`), LE     |
+//! | IPv6 address  | the 16 address bytes as on the wire                 |
+//! | Port          | `u16` in host order, LE                             |
+
+use crate::rule::Protocol;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Map of an XDP program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MapTarget {
+    pub program: &'static str,
+    pub map: &'static str,
+    pub max_entries: usize,
+}
+
+impl fmt::Display for MapTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.program, self.map)
+    }
+}
+
+pub const BLOCKED_IPS_V4: MapTarget = MapTarget {
+    program: "xdp_filter",
+    map: "BLOCKED_IPS_V4",
+    max_entries: 1_000_000,
+};
+
+pub const BLOCKED_IPS_V6: MapTarget = MapTarget {
+    program: "xdp_filter",
+    map: "BLOCKED_IPS_V6",
+    max_entries: 500_000,
+};
+
+pub const TCP_WHITELIST: MapTarget = MapTarget {
+    program: "xdp_tcp",
+    map: "TCP_WHITELIST",
+    max_entries: 10_000,
+};
+
+pub const TCP_WHITELIST_V6: MapTarget = MapTarget {
+    program: "xdp_tcp",
+    map: "TCP_WHITELIST_V6",
+    max_entries: 10_000,
+};
+
+pub const HTTP_WHITELIST: MapTarget = MapTarget {
+    program: "xdp_http",
+    map: "HTTP_WHITELIST",
+    max_entries: 10_000,
+};
+
+pub const HTTP_WHITELIST_V6: MapTarget = MapTarget {
+    program: "xdp_http",
+    map: "HTTP_WHITELIST_V6",
+    max_entries: 10_000,
+};
+
+pub const UDP_WHITELIST: MapTarget = MapTarget {
+    program: "xdp_udp",
+    map: "UDP_WHITELIST",
+    max_entries: 10_000,
+};
+
+pub const QUIC_WHITELIST: MapTarget = MapTarget {
+    program: "xdp_quic",
+    map: "QUIC_WHITELIST",
+    max_entries: 10_000,
+};
+
+pub const UDP_BLOCKED_PORTS: MapTarget = MapTarget {
+    program: "xdp_udp",
+    map: "BLOCKED_PORTS",
+    max_entries: 1000,
+};
+
+/// Source allowlists of the programs handling a protocol
+pub fn allowlists_v4(protocol: Protocol) -> &'static [MapTarget] {
+    match protocol {
+        Protocol::Tcp => &[TCP_WHITELIST, HTTP_WHITELIST],
+        Protocol::Udp => &[UDP_WHITELIST, QUIC_WHITELIST],
+        Protocol::Icmp => &[],
+    }
+}
+
+/// IPv6 source allowlists of the programs handling a protocol
+pub fn allowlists_v6(protocol: Protocol) -> &'static [MapTarget] {
+    match protocol {
+        Protocol::Tcp => &[TCP_WHITELIST_V6, HTTP_WHITELIST_V6],
+        Protocol::Udp | Protocol::Icmp => &[],
+    }
+}
+
+/// `BlockReason::Manual` in the `ebpf` crate
+pub const BLOCK_REASON_MANUAL: u32 = 0;
+
+/// Value of allowlist and port map entries (only presence is checked)
+pub const PRESENT: u32 = 1;
+
+pub fn ipv4_key(addr: Ipv4Addr) -> Vec<u8> {
+    u32::from(addr).to_le_bytes().to_vec()
+}
+
+pub fn ipv6_key(addr: Ipv6Addr) -> Vec<u8> {
+    addr.octets().to_vec()
+}
+
+pub fn port_key(port: u16) -> Vec<u8> {
+    port.to_le_bytes().to_vec()
+}
+
+pub fn flag_value(value: u32) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+/// `BlockedIpEntry` with no expiry
+///
+/// `#[repr(C)]` `{ reason: u32, expires_at: u64, packets_blocked: u64 }`,
+/// 24 bytes with 4 bytes of padding after `reason`.
+pub fn blocked_ip_value(reason: u32) -> Vec<u8> {
+    let mut value = Vec::with_capacity(24);
+    value.extend_from_slice(&reason.to_le_bytes());
+    value.extend_from_slice(&[0; 4]);
+    value.extend_from_slice(&0u64.to_le_bytes());
+    value.extend_from_slice(&0u64.to_le_bytes());
+    value
+}
@@ -0,0 +1,232 @@
+//! Golden tests for rule compilation
+//!
+//! Each case compiles a rule set and compares the rendered entries (or
+//! error) with `tests/golden/<case>.txt`. Run with `UPDATE_GOLDEN=1` to
+//! rewrite the files after an intended change, then review the diff.
+
+use pistonprotection_rule_compiler::{Action, Cidr, PortRange, Protocol, Rule, compile};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+// =============================================================================
+// Helpers
+// =============================================================================
+
+fn cidrs(networks: &[&str]) -> Vec<Cidr> {
+    networks.iter().map(|n| n.parse().unwrap()).collect()
+}
+
+fn render(rules: &[Rule]) -> String {
+    let compiled = match compile(rules) {
+        Ok(compiled) => compiled,
+        Err(err) => return format!("error: {}\n", err),
+    };
+
+    let mut out = String::new();
+    for entry in compiled.entries() {
+        writeln!(out, "{}", entry).unwrap();
+    }
+    for skipped in compiled.skipped() {
+        writeln!(out, "skipped {}", skipped).unwrap();
+    }
+    for conflict in compiled.conflicts() {
+        writeln!(out, "conflict {}", conflict).unwrap();
+    }
+    out
+}
+
+fn assert_golden(case: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", case));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("reading {}: {}", path.display(), e));
+    assert_eq!(
+        actual, expected,
+        "{} differs from the golden output; rerun with UPDATE_GOLDEN=1 if intended",
+        case
+    );
+}
+
+// =============================================================================
+// Cases
+// =============================================================================
+
+#[test]
+fn golden_blocklist() {
+    let mut rule = Rule::new("blocklist", 10, Action::Log);
+    rule.source_ip_blacklist = cidrs(&["203.0.113.7", "198.51.100.0/30", "2001:db8::1"]);
+
+    assert_golden("blocklist", &render(&[rule]));
+}
+
+#[test]
+fn golden_drop_sources() {
+    let mut rule = Rule::new("drop-sources", 10, Action::Drop);
+    rule.source_ips = cidrs(&["192.0.2.128/31", "2001:db8::/127"]);
+
+    let mut scoped = Rule::new("drop-scoped", 20, Action::Drop);
+    scoped.source_ips = cidrs(&["192.0.2.200"]);
+    scoped.protocols = vec![Protocol::Tcp];
+
+    assert_golden("drop_sources", &render(&[rule, scoped]));
+}
+
+#[test]
+fn golden_allowlist() {
+    let mut tcp = Rule::new("allow-tcp", 10, Action::Allow);
+    tcp.source_ips = cidrs(&["192.0.2.10"]);
+    tcp.protocols = vec![Protocol::Tcp];
+
+    let mut any = Rule::new("allow-any", 20, Action::Allow);
+    any.source_ips = cidrs(&["10.1.0.0/31", "2001:db8::5"]);
+
+    let mut scoped = Rule::new("allow-port", 30, Action::Allow);
+    scoped.source_ips = cidrs(&["10.9.9.9"]);
+    scoped.destination_ports = vec![PortRange::single(443)];
+
+    assert_golden("allowlist", &render(&[tcp, any, scoped]));
+}
+
+#[test]
+fn golden_udp_ports() {
+    let mut udp = Rule::new("drop-udp", 10, Action::Drop);
+    udp.destination_ports = vec![PortRange::single(53), PortRange::new(1900, 1902)];
+    udp.protocols = vec![Protocol::Udp];
+
+    let mut any = Rule::new("drop-memcached", 20, Action::Drop);
+    any.destination_ports = vec![PortRange::single(11211)];
+
+    let mut tcp = Rule::new("drop-ssh", 30, Action::Drop);
+    tcp.destination_ports = vec![PortRange::single(22)];
+    tcp.protocols = vec![Protocol::Tcp];
+
+    assert_golden("udp_ports", &render(&[udp, any, tcp]));
+}
+
+#[test]
+fn golden_precedence() {
+    let mut allow = Rule::new("allow-monitoring", 10, Action::Allow);
+    allow.source_ips = cidrs(&["192.0.2.1"]);
+
+    let mut drop = Rule::new("drop-range", 20, Action::Drop);
+    drop.source_ips = cidrs(&["192.0.2.0/30"]);
+
+    let mut disabled = Rule::new("disabled", 5, Action::Drop);
+    disabled.enabled = false;
+    disabled.source_ips = cidrs(&["192.0.2.3"]);
+
+    // Input order does not matter
+    assert_golden("precedence", &render(&[drop, disabled, allow]));
+}
+
+#[test]
+fn golden_conflicts() {
+    // Higher priority drop of UDP ports keeps a later UDP allow out of the maps
+    let mut dns = Rule::new("drop-dns", 10, Action::Drop);
+    dns.destination_ports = vec![PortRange::single(53)];
+    dns.protocols = vec![Protocol::Udp];
+
+    let mut resolvers = Rule::new("allow-resolvers", 20, Action::Allow);
+    resolvers.source_ips = cidrs(&["198.51.100.53"]);
+
+    // Same priority: resolved by id
+    let mut allow = Rule::new("a-allow-office", 30, Action::Allow);
+    allow.source_ips = cidrs(&["203.0.113.0/30"]);
+    let mut block = Rule::new("b-block-office", 30, Action::Drop);
+    block.source_ips = cidrs(&["203.0.113.2"]);
+
+    // Different ports and protocols do not conflict
+    let mut ssh = Rule::new("allow-ssh", 40, Action::Allow);
+    ssh.source_ips = cidrs(&["192.0.2.10"]);
+    ssh.protocols = vec![Protocol::Tcp];
+    let mut games = Rule::new("drop-games", 50, Action::Drop);
+    games.destination_ports = vec![PortRange::new(27015, 27020)];
+    games.protocols = vec![Protocol::Udp];
+
+    assert_golden(
+        "conflicts",
+        &render(&[games, ssh, block, allow, resolvers, dns]),
+    );
+}
+
+#[test]
+fn golden_userspace_only() {
+    let mut geo = Rule::new("geo", 10, Action::Drop);
+    geo.source_ips = cidrs(&["198.51.100.1"]);
+    geo.countries = vec!["XX".to_string()];
+    geo.source_ip_blacklist = cidrs(&["198.51.100.2"]);
+
+    let mut limit = Rule::new("limit", 20, Action::RateLimit);
+    limit.source_ips = cidrs(&["198.51.100.3"]);
+
+    let mut http = Rule::new("http", 30, Action::Drop);
+    http.destination_ports = vec![PortRange::single(80)];
+    http.l7 = true;
+
+    assert_golden("userspace_only", &render(&[geo, limit, http]));
+}
+
+#[test]
+fn golden_wide_network() {
+    let mut rule = Rule::new("too-wide", 10, Action::Drop);
+    rule.source_ips = cidrs(&["10.0.0.0/8", "192.0.2.1"]);
+
+    assert_golden("wide_network", &render(&[rule]));
+}
+
+#[test]
+fn golden_invalid_port_range() {
+    let mut rule = Rule::new("bad-ports", 10, Action::Drop);
+    rule.destination_ports = vec![PortRange::new(9000, 8000)];
+
+    assert_golden("invalid_port_range", &render(&[rule]));
+}
+
+#[test]
+fn golden_port_map_full() {
+    let mut rule = Rule::new("all-ports", 10, Action::Drop);
+    rule.destination_ports = vec![PortRange::new(1, 65535)];
+    rule.protocols = vec![Protocol::Udp];
+
+    assert_golden("port_map_full", &render(&[rule]));
+}
+
+#[test]
+fn golden_diff() {
+    let mut before = Rule::new("rule", 10, Action::Drop);
+    before.source_ips = cidrs(&["192.0.2.1", "192.0.2.2"]);
+    let mut after = before.clone();
+    after.source_ips = cidrs(&["192.0.2.2", "192.0.2.3"]);
+
+    let previous = compile(&[before]).unwrap();
+    let current = compile(&[after]).unwrap();
+
+    let mut out = String::new();
+    for mutation in current.diff(&previous) {
+        writeln!(out, "{}", mutation).unwrap();
+    }
+    assert_golden("diff", &out);
+
+    assert!(current.diff(&current).is_empty());
+    assert_eq!(current.mutations().len(), current.len());
+}
+
+#[test]
+fn compile_is_deterministic() {
+    let mut a = Rule::new("a", 10, Action::Allow);
+    a.source_ips = cidrs(&["192.0.2.0/29"]);
+    let mut b = Rule::new("b", 10, Action::Drop);
+    b.source_ips = cidrs(&["192.0.2.4/30", "2001:db8::/126"]);
+    b.source_ip_blacklist = cidrs(&["198.51.100.9"]);
+
+    let forward = compile(&[a.clone(), b.clone()]).unwrap();
+    let reverse = compile(&[b, a]).unwrap();
+    assert_eq!(forward, reverse);
+}
@@ -0,0 +1,388 @@
+//! Control plane client of the simulated worker
+//!
+//! Registers with the gateway's WorkerService like the worker does, with the
+//! same environment variables, and keeps the registration alive with
+//! heartbeats carrying the counters of the simulated interface. Newer
+//! configurations announced by heartbeats are fetched and applied to the
+//! simulated data plane. Connection loss and re-registration requests start
+//! over from registration; shutdown deregisters.
+
+use crate::sim::{INTERFACE, SimDataPlane, SimStats};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::worker::{
+    DeregisterRequest, GetConfigRequest, HeartbeatRequest, InterfaceMetrics, RegisterRequest,
+    Worker, WorkerCapabilities, WorkerMetrics, WorkerStatus,
+    worker_service_client::WorkerServiceClient,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout};
+use tonic::transport::{Channel, Endpoint};
+use tracing::{debug, info, warn};
+
+/// Label marking the worker as simulated in the control plane
+const SIMULATED_LABEL: &str = "pistonprotection.io/simulated";
+
+/// Configuration of the control plane client
+#[derive(Debug, Clone)]
+pub struct ControlConfig {
+    /// Control plane address (e.g., "http://localhost:50051")
+    pub address: String,
+    pub node_name: String,
+    pub region: String,
+    pub labels: HashMap<String, String>,
+    pub heartbeat_interval: Duration,
+    pub request_timeout: Duration,
+    /// Delay before registering again after a failure
+    pub reconnect_delay: Duration,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            address: "http://localhost:50051".to_string(),
+            node_name: hostname::get()
+                .map(|h| h.to_string_lossy().to_string())
+                .unwrap_or_else(|_| "worker-sim".to_string()),
+            region: String::new(),
+            labels: HashMap::new(),
+            heartbeat_interval: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(10),
+            reconnect_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ControlConfig {
+    /// Create from the worker's environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(addr) = std::env::var("PISTON_CONTROL_PLANE_ADDR") {
+            config.address = addr;
+        }
+        if let Ok(name) = std::env::var("NODE_NAME") {
+            config.node_name = name;
+        }
+        if let Ok(region) = std::env::var("PISTON_WORKER_REGION") {
+            config.region = region;
+        }
+        if let Some(secs) = std::env::var("PISTON_HEARTBEAT_INTERVAL")
+            .ok()
+            .and_then(|secs| secs.parse::<u64>().ok())
+        {
+            config.heartbeat_interval = Duration::from_secs(secs.max(1));
+        }
+
+        // Format: key1=value1,key2=value2
+        if let Ok(labels) = std::env::var("PISTON_WORKER_LABELS") {
+            for pair in labels.split(',') {
+                if let Some((key, value)) = pair.split_once('=') {
+                    config
+                        .labels
+                        .insert(key.trim().to_string(), value.trim().to_string());
+                }
+            }
+        }
+        config
+            .labels
+            .insert(SIMULATED_LABEL.to_string(), "true".to_string());
+
+        config
+    }
+}
+
+/// Registration state reported by the control API
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ControlStatus {
+    pub address: String,
+    pub connected: bool,
+    pub worker_id: Option<String>,
+    pub last_heartbeat: Option<DateTime<Utc>>,
+}
+
+/// Why a registration ended
+enum SessionEnd {
+    Shutdown,
+    Reconnect,
+}
+
+/// Control plane client of the simulated worker
+pub struct ControlClient {
+    config: ControlConfig,
+    sim: Arc<SimDataPlane>,
+    status: RwLock<ControlStatus>,
+}
+
+impl ControlClient {
+    pub fn new(config: ControlConfig, sim: Arc<SimDataPlane>) -> Self {
+        let status = ControlStatus {
+            address: config.address.clone(),
+            ..Default::default()
+        };
+        Self {
+            config,
+            sim,
+            status: RwLock::new(status),
+        }
+    }
+
+    pub fn status(&self) -> ControlStatus {
+        self.status.read().clone()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.status.read().connected
+    }
+
+    /// Register and heartbeat until shutdown, then deregister
+    pub async fn run(self: Arc<Self>, mut shutdown_rx: watch::Receiver<bool>) {
+        loop {
+            let end = match self.session(&mut shutdown_rx).await {
+                Ok(end) => end,
+                Err(e) => {
+                    warn!("Control plane session failed: {}", e);
+                    SessionEnd::Reconnect
+                }
+            };
+            self.status.write().connected = false;
+
+            if matches!(end, SessionEnd::Shutdown) {
+                return;
+            }
+            tokio::select! {
+                _ = shutdown_rx.changed() => return,
+                _ = sleep(self.config.reconnect_delay) => {}
+            }
+        }
+    }
+
+    /// One registration: register, then heartbeat until it has to end
+    async fn session(&self, shutdown_rx: &mut watch::Receiver<bool>) -> Result<SessionEnd> {
+        let endpoint = Endpoint::from_shared(self.config.address.clone())
+            .map_err(|e| Error::Internal(format!("Invalid control plane address: {}", e)))?
+            .connect_timeout(self.config.request_timeout)
+            .timeout(self.config.request_timeout);
+        let channel = endpoint
+            .connect()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to connect to control plane: {}", e)))?;
+        let mut client = WorkerServiceClient::new(channel);
+
+        let worker_id = self.register(&mut client).await?;
+        let mut interval = tokio::time::interval(self.config.heartbeat_interval);
+        let mut rates = RateMeter::new(self.sim.stats());
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    self.deregister(&mut client, &worker_id).await;
+                    return Ok(SessionEnd::Shutdown);
+                }
+                _ = interval.tick() => {
+                    if !self.heartbeat(&mut client, &worker_id, &mut rates).await? {
+                        warn!("Control plane does not know this worker, re-registering");
+                        return Ok(SessionEnd::Reconnect);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn register(&self, client: &mut WorkerServiceClient<Channel>) -> Result<String> {
+        let previous_id = self.status.read().worker_id.clone();
+        let request = RegisterRequest {
+            worker: Some(build_worker_info(&self.config, previous_id)),
+        };
+        let response = timeout(self.config.request_timeout, client.register(request))
+            .await
+            .map_err(|_| Error::Internal("Registration request timeout".to_string()))?
+            .map_err(|e| Error::Internal(format!("Registration failed: {}", e)))?
+            .into_inner();
+
+        info!(
+            "Registered with control plane as simulated worker {}",
+            response.worker_id
+        );
+        if let Some(config) = response.initial_config {
+            info!("Applying initial configuration version {}", config.version);
+            self.sim.apply_config(&config);
+        }
+
+        let mut status = self.status.write();
+        status.worker_id = Some(response.worker_id.clone());
+        status.connected = true;
+        status.last_heartbeat = Some(Utc::now());
+        Ok(response.worker_id)
+    }
+
+    /// Send a heartbeat and fetch the configuration it announces; false if
+    /// the control plane asks for a new registration
+    async fn heartbeat(
+        &self,
+        client: &mut WorkerServiceClient<Channel>,
+        worker_id: &str,
+        rates: &mut RateMeter,
+    ) -> Result<bool> {
+        let current_version = self.sim.config_version();
+        let request = HeartbeatRequest {
+            worker_id: worker_id.to_string(),
+            status: WorkerStatus::Ready.into(),
+            metrics: Some(WorkerMetrics {
+                cpu_percent: 0.0,
+                memory_percent: 0.0,
+                interfaces: vec![rates.sample(self.sim.stats())],
+            }),
+            current_config_version: current_version,
+            programs: vec![],
+        };
+        let response = timeout(self.config.request_timeout, client.heartbeat(request))
+            .await
+            .map_err(|_| Error::Internal("Heartbeat timeout".to_string()))?
+            .map_err(|e| Error::Internal(format!("Heartbeat failed: {}", e)))?
+            .into_inner();
+        self.status.write().last_heartbeat = Some(Utc::now());
+
+        if response.reregister_required {
+            return Ok(false);
+        }
+        if response.config_update_available && response.latest_config_version > current_version {
+            debug!(
+                "Configuration update available: {} -> {}",
+                current_version, response.latest_config_version
+            );
+            self.fetch_config(client, worker_id, current_version)
+                .await?;
+        }
+        Ok(true)
+    }
+
+    async fn fetch_config(
+        &self,
+        client: &mut WorkerServiceClient<Channel>,
+        worker_id: &str,
+        current_version: u32,
+    ) -> Result<()> {
+        let request = GetConfigRequest {
+            worker_id: worker_id.to_string(),
+            current_version,
+        };
+        let response = timeout(self.config.request_timeout, client.get_config(request))
+            .await
+            .map_err(|_| Error::Internal("Get config timeout".to_string()))?
+            .map_err(|e| Error::Internal(format!("Failed to get config: {}", e)))?
+            .into_inner();
+
+        if let Some(config) = response.config
+            && !response.up_to_date
+        {
+            info!("Applying configuration version {}", config.version);
+            self.sim.apply_config(&config);
+        }
+        Ok(())
+    }
+
+    async fn deregister(&self, client: &mut WorkerServiceClient<Channel>, worker_id: &str) {
+        let request = DeregisterRequest {
+            worker_id: worker_id.to_string(),
+        };
+        match timeout(self.config.request_timeout, client.deregister(request)).await {
+            Ok(Ok(_)) => info!("Deregistered from control plane"),
+            Ok(Err(e)) => warn!("Failed to deregister: {}", e),
+            Err(_) => warn!("Deregister request timeout"),
+        }
+    }
+}
+
+/// Packet rates of the simulated interface between heartbeats
+struct RateMeter {
+    last: SimStats,
+    at: Instant,
+}
+
+impl RateMeter {
+    fn new(stats: SimStats) -> Self {
+        Self {
+            last: stats,
+            at: Instant::now(),
+        }
+    }
+
+    fn sample(&mut self, stats: SimStats) -> InterfaceMetrics {
+        let elapsed = self.at.elapsed().as_secs_f64().max(1e-3);
+        let packets =
+            (stats.passed + stats.dropped).saturating_sub(self.last.passed + self.last.dropped);
+        self.last = stats;
+        self.at = Instant::now();
+
+        InterfaceMetrics {
+            name: INTERFACE.to_string(),
+            rx_pps: (packets as f64 / elapsed) as u64,
+            xdp_pass: stats.passed,
+            xdp_drop: stats.dropped,
+            ..Default::default()
+        }
+    }
+}
+
+/// Registration of the simulated worker: one simulated interface and no
+/// XDP capabilities, so the control plane never expects a program rollout
+/// to load on it
+fn build_worker_info(config: &ControlConfig, worker_id: Option<String>) -> Worker {
+    Worker {
+        id: worker_id.unwrap_or_default(),
+        node_name: config.node_name.clone(),
+        hostname: hostname::get()
+            .map(|h| h.to_string_lossy().to_string())
+            .unwrap_or_else(|_| "unknown".to_string()),
+        interfaces: vec![pistonprotection_proto::worker::NetworkInterface {
+            name: INTERFACE.to_string(),
+            ..Default::default()
+        }],
+        capabilities: Some(WorkerCapabilities {
+            program_variant: "simulated".to_string(),
+            ..Default::default()
+        }),
+        status: WorkerStatus::Registering.into(),
+        labels: config.labels.clone(),
+        region: config.region.clone(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_worker_info() {
+        let mut config = ControlConfig::default();
+        config
+            .labels
+            .insert(SIMULATED_LABEL.to_string(), "true".to_string());
+
+        let worker = build_worker_info(&config, Some("worker-1".to_string()));
+        assert_eq!(worker.id, "worker-1");
+        assert_eq!(worker.interfaces[0].name, INTERFACE);
+        assert_eq!(worker.labels[SIMULATED_LABEL], "true");
+        assert!(!worker.capabilities.unwrap().xdp_native);
+    }
+
+    #[test]
+    fn test_rate_meter_reports_totals() {
+        let mut meter = RateMeter::new(SimStats::default());
+        let metrics = meter.sample(SimStats {
+            passed: 40,
+            dropped: 60,
+            ..Default::default()
+        });
+        assert_eq!(metrics.name, INTERFACE);
+        assert_eq!(metrics.xdp_pass, 40);
+        assert_eq!(metrics.xdp_drop, 60);
+        assert!(metrics.rx_pps > 0);
+    }
+}
@@ -0,0 +1,326 @@
+//! HTTP control API of the simulated worker
+//!
+//! Serves the worker's health, metrics, status and blocklist endpoints with
+//! the same paths and payloads, so dashboards and scripts written against a
+//! worker work against the simulation, plus:
+//! - `POST /sim/packets`: generate packets and run them through the
+//!   simulated data plane, returning the verdict totals
+//! - `GET /sim/stats`: counters of the simulated data plane
+
+use crate::control::{ControlClient, ControlStatus};
+use crate::sim::{AppliedConfig, PacketResult, SimDataPlane, SimStats};
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, post},
+};
+use pistonprotection_ebpf_tests::scenario::PacketSpec;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Shared state of the handlers
+#[derive(Clone)]
+pub struct SimState {
+    pub sim: Arc<SimDataPlane>,
+    /// Absent in standalone mode
+    pub control: Option<Arc<ControlClient>>,
+}
+
+impl SimState {
+    fn is_connected(&self) -> bool {
+        self.control
+            .as_ref()
+            .is_none_or(|control| control.is_connected())
+    }
+}
+
+/// Create the HTTP router with all endpoints
+pub fn create_router(state: SimState) -> Router {
+    Router::new()
+        .route("/health", get(health_check))
+        .route("/health/live", get(liveness_check))
+        .route("/health/ready", get(readiness_check))
+        .route("/metrics", get(metrics))
+        .route("/status", get(detailed_status))
+        .route("/status/config", get(config_status))
+        .route("/admin/blocked-ips", get(list_blocked_ips))
+        .route("/admin/blocked-ips", post(block_ip))
+        .route("/admin/blocked-ips/{ip}", delete(unblock_ip))
+        .route("/sim/packets", post(inject_packets))
+        .route("/sim/stats", get(sim_stats))
+        .with_state(state)
+}
+
+// ============================================================================
+// Health Check Handlers
+// ============================================================================
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    service: &'static str,
+    version: &'static str,
+    worker_id: Option<String>,
+    connection_state: String,
+    config_version: u32,
+}
+
+async fn health_check(State(state): State<SimState>) -> impl IntoResponse {
+    let control = state.control.as_ref().map(|control| control.status());
+    let healthy = state.is_connected();
+
+    let response = HealthResponse {
+        status: if healthy { "healthy" } else { "unhealthy" },
+        service: "worker-sim",
+        version: env!("CARGO_PKG_VERSION"),
+        worker_id: control.as_ref().and_then(|c| c.worker_id.clone()),
+        connection_state: match &control {
+            None => "standalone".to_string(),
+            Some(c) if c.connected => "connected".to_string(),
+            Some(_) => "disconnected".to_string(),
+        },
+        config_version: state.sim.config_version(),
+    };
+
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(response))
+}
+
+async fn liveness_check() -> impl IntoResponse {
+    (StatusCode::OK, "OK")
+}
+
+/// Ready once connected with a configuration applied (always in standalone
+/// mode)
+async fn readiness_check(State(state): State<SimState>) -> impl IntoResponse {
+    let ready = state.control.is_none() || (state.is_connected() && state.sim.config().is_some());
+    if ready {
+        (StatusCode::OK, "READY")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "NOT_READY")
+    }
+}
+
+// ============================================================================
+// Metrics Handler
+// ============================================================================
+
+async fn metrics(State(state): State<SimState>) -> impl IntoResponse {
+    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
+        .with_label_values(&["worker", "blocked_ips"])
+        .set(state.sim.list_blocked_ips().len() as f64);
+
+    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
+        .with_label_values(&["worker", "backends"])
+        .set(state.sim.config().map_or(0, |c| c.backends.len()) as f64);
+
+    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
+        .with_label_values(&["worker", "control_plane"])
+        .set(if state.is_connected() { 1.0 } else { 0.0 });
+
+    let metrics = pistonprotection_common::metrics::encode_metrics();
+    (
+        StatusCode::OK,
+        [("content-type", "text/plain; version=0.0.4")],
+        metrics,
+    )
+}
+
+// ============================================================================
+// Status Handlers
+// ============================================================================
+
+#[derive(Serialize)]
+struct StatusResponse {
+    version: &'static str,
+    simulated: bool,
+    connection: Option<ControlStatus>,
+    configuration: Option<AppliedConfig>,
+    stats: SimStats,
+    tracked_sources: usize,
+}
+
+async fn detailed_status(State(state): State<SimState>) -> impl IntoResponse {
+    let response = StatusResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        simulated: true,
+        connection: state.control.as_ref().map(|control| control.status()),
+        configuration: state.sim.config(),
+        stats: state.sim.stats(),
+        tracked_sources: state.sim.tracked_sources(),
+    };
+    (StatusCode::OK, Json(response))
+}
+
+#[derive(Serialize)]
+struct ConfigStatusResponse {
+    version: u32,
+    config_id: Option<String>,
+    backends_count: usize,
+    backends: Vec<String>,
+    last_sync: Option<String>,
+}
+
+async fn config_status(State(state): State<SimState>) -> impl IntoResponse {
+    let config = state.sim.config();
+    let response = ConfigStatusResponse {
+        version: config.as_ref().map_or(0, |c| c.version),
+        config_id: config.as_ref().map(|c| c.config_id.clone()),
+        backends_count: config.as_ref().map_or(0, |c| c.backends.len()),
+        backends: config
+            .as_ref()
+            .map(|c| c.backends.clone())
+            .unwrap_or_default(),
+        last_sync: config.map(|c| c.applied_at.to_rfc3339()),
+    };
+    (StatusCode::OK, Json(response))
+}
+
+// ============================================================================
+// Admin Handlers
+// ============================================================================
+
+#[derive(Serialize)]
+struct BlockedIpResponse {
+    ip: String,
+    reason: String,
+    blocked_at: String,
+    expires_at: Option<String>,
+}
+
+async fn list_blocked_ips(State(state): State<SimState>) -> impl IntoResponse {
+    let response: Vec<BlockedIpResponse> = state
+        .sim
+        .list_blocked_ips()
+        .into_iter()
+        .map(|entry| BlockedIpResponse {
+            ip: entry.ip.to_string(),
+            reason: entry.reason,
+            blocked_at: entry.blocked_at.to_rfc3339(),
+            expires_at: entry.expires_at.map(|t| t.to_rfc3339()),
+        })
+        .collect();
+    (StatusCode::OK, Json(response))
+}
+
+#[derive(Deserialize)]
+struct BlockIpRequest {
+    ip: String,
+    reason: String,
+    #[serde(default)]
+    duration_secs: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct BlockIpSuccessResponse {
+    success: bool,
+    message: String,
+}
+
+fn block_response(status: StatusCode, success: bool, message: String) -> impl IntoResponse {
+    (status, Json(BlockIpSuccessResponse { success, message }))
+}
+
+async fn block_ip(
+    State(state): State<SimState>,
+    Json(request): Json<BlockIpRequest>,
+) -> impl IntoResponse {
+    let Ok(ip) = request.ip.parse::<IpAddr>() else {
+        return block_response(
+            StatusCode::BAD_REQUEST,
+            false,
+            format!("Invalid IP address: {}", request.ip),
+        );
+    };
+
+    match state
+        .sim
+        .block_ip(ip, &request.reason, request.duration_secs)
+    {
+        Ok(()) => block_response(
+            StatusCode::OK,
+            true,
+            format!("IP {} blocked successfully", ip),
+        ),
+        Err(e) => block_response(
+            StatusCode::BAD_REQUEST,
+            false,
+            format!("Failed to block IP: {}", e),
+        ),
+    }
+}
+
+async fn unblock_ip(
+    State(state): State<SimState>,
+    Path(ip_str): Path<String>,
+) -> impl IntoResponse {
+    let Ok(ip) = ip_str.parse::<IpAddr>() else {
+        return block_response(
+            StatusCode::BAD_REQUEST,
+            false,
+            format!("Invalid IP address: {}", ip_str),
+        );
+    };
+
+    match state.sim.unblock_ip(&ip) {
+        Ok(()) => block_response(
+            StatusCode::OK,
+            true,
+            format!("IP {} unblocked successfully", ip),
+        ),
+        Err(e) => block_response(
+            StatusCode::NOT_FOUND,
+            false,
+            format!("Failed to unblock IP: {}", e),
+        ),
+    }
+}
+
+// ============================================================================
+// Simulation Handlers
+// ============================================================================
+
+/// Packets to generate, in the packet format of the filter test scenarios
+#[derive(Deserialize)]
+struct InjectRequest {
+    packet: PacketSpec,
+    #[serde(default = "default_count")]
+    count: u32,
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum InjectResponse {
+    Verdicts(PacketResult),
+    Error { error: String },
+}
+
+async fn inject_packets(
+    State(state): State<SimState>,
+    Json(request): Json<InjectRequest>,
+) -> impl IntoResponse {
+    match state.sim.inject(&request.packet, request.count) {
+        Ok(result) => (StatusCode::OK, Json(InjectResponse::Verdicts(result))),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(InjectResponse::Error {
+                error: e.to_string(),
+            }),
+        ),
+    }
+}
+
+async fn sim_stats(State(state): State<SimState>) -> impl IntoResponse {
+    (StatusCode::OK, Json(state.sim.stats()))
+}
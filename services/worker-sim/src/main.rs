@@ -0,0 +1,123 @@
+//! PistonProtection Simulated Worker
+//!
+//! Runs the worker's control surface without eBPF, for developing the
+//! control plane on machines that cannot load XDP programs (macOS, Windows,
+//! containers without `CAP_BPF`). It registers with the gateway like a
+//! worker, heartbeats, applies the filter configurations it is sent, and
+//! serves the worker's HTTP API. The data plane is the userspace reference
+//! filter of ebpf-tests; `POST /sim/packets` feeds it generated packets in
+//! the packet format of the filter test scenarios, e.g.
+//!
+//! ```text
+//! curl -X POST localhost:8081/sim/packets -H 'content-type: application/json' \
+//!   -d '{"packet": {"protocol": "udp", "src": "198.51.100.7", "dst": "10.0.0.1",
+//!        "src_port": 53, "dst_port": 40000}, "count": 100}'
+//! ```
+//!
+//! Usage: `worker-sim`, configured through the worker's environment
+//! variables (`PISTON_CONTROL_PLANE_ADDR`, `NODE_NAME`,
+//! `PISTON_WORKER_REGION`, `PISTON_HEARTBEAT_INTERVAL`,
+//! `PISTON_WORKER_LABELS`, `PISTON_STANDALONE`) and
+//! `PISTON_SIM_HTTP_ADDR` (default `127.0.0.1:8081`). Verdicts follow the
+//! reference filter, not the full set of XDP programs; use it to exercise
+//! the control plane, not to measure mitigation.
+
+mod control;
+mod http;
+mod sim;
+
+use control::{ControlClient, ControlConfig};
+use http::SimState;
+use sim::SimDataPlane;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::signal;
+use tokio::sync::watch;
+use tracing::{error, info};
+
+const DEFAULT_HTTP_ADDR: &str = "127.0.0.1:8081";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    let http_addr: SocketAddr = std::env::var("PISTON_SIM_HTTP_ADDR")
+        .unwrap_or_else(|_| DEFAULT_HTTP_ADDR.to_string())
+        .parse()?;
+    let standalone = std::env::var("PISTON_STANDALONE").is_ok();
+
+    let sim = Arc::new(SimDataPlane::new());
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    let control = (!standalone).then(|| {
+        let config = ControlConfig::from_env();
+        info!(
+            "Simulated worker {} registering with {}",
+            config.node_name, config.address
+        );
+        Arc::new(ControlClient::new(config, Arc::clone(&sim)))
+    });
+    let control_handle = control
+        .clone()
+        .map(|control| tokio::spawn(control.run(shutdown_rx)));
+    if standalone {
+        info!("Running standalone, not registering with a control plane");
+    }
+
+    let router = http::create_router(SimState {
+        sim: Arc::clone(&sim),
+        control,
+    });
+    let listener = tokio::net::TcpListener::bind(http_addr).await?;
+    info!(addr = %http_addr, "HTTP server listening");
+    let http_handle = tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!(error = %e, "HTTP server error");
+        }
+    });
+
+    shutdown_signal().await;
+    info!("Shutting down");
+
+    // Deregister before the API goes away
+    let _ = shutdown_tx.send(true);
+    if let Some(handle) = control_handle {
+        let _ = handle.await;
+    }
+    http_handle.abort();
+
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        match signal::ctrl_c().await {
+            Ok(()) => info!("Received Ctrl+C signal"),
+            Err(e) => error!(error = %e, "Failed to listen for Ctrl+C signal"),
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+                info!("Received SIGTERM signal");
+            }
+            Err(e) => error!(error = %e, "Failed to listen for SIGTERM signal"),
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
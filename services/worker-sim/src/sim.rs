@@ -0,0 +1,381 @@
+//! Simulated data plane
+//!
+//! Stands in for the XDP programs and their maps. Packets run through the
+//! userspace `ReferenceFilter` of ebpf-tests, which chains the blocklist,
+//! per-source rate limit and UDP amplification checks in the same order as
+//! the kernel programs. Filter configurations from the control plane set
+//! the protection level and which checks run; blocked IPs are kept here
+//! with their reason and expiry so the control API can list them the way
+//! the worker lists its blocklist map.
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_ebpf_tests::clock::SystemClock;
+use pistonprotection_ebpf_tests::scenario::{
+    FilterStats, PacketSpec, ReferenceFilter, ScenarioConfig, Verdict,
+};
+use pistonprotection_proto::worker::FilterConfig;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Name of the simulated interface reported to the control plane
+pub const INTERFACE: &str = "sim0";
+
+/// Highest protection level of the XDP programs
+const MAX_PROTECTION_LEVEL: u32 = 5;
+
+/// Most packets a single request may generate
+pub const MAX_PACKETS: u32 = 1_000_000;
+
+/// Entry of the simulated blocklist
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockedIp {
+    pub ip: IpAddr,
+    pub reason: String,
+    pub blocked_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl BlockedIp {
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Configuration currently applied
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedConfig {
+    pub version: u32,
+    pub config_id: String,
+    pub backends: Vec<String>,
+    pub protection_level: u8,
+    pub rate_limit: bool,
+    pub amplification: bool,
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Counters of the simulated data plane since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SimStats {
+    pub passed: u64,
+    pub dropped: u64,
+    pub blocked: u64,
+    pub rate_limited: u64,
+    pub amplification: u64,
+}
+
+impl SimStats {
+    fn add(&self, stats: &FilterStats) -> Self {
+        Self {
+            passed: self.passed + stats.passed,
+            dropped: self.dropped + stats.dropped,
+            blocked: self.blocked + stats.blocked,
+            rate_limited: self.rate_limited + stats.rate_limited,
+            amplification: self.amplification + stats.amplification,
+        }
+    }
+}
+
+/// Verdict totals of generated packets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PacketResult {
+    pub pass: u32,
+    pub drop: u32,
+}
+
+struct State {
+    filter: ReferenceFilter<SystemClock>,
+    /// Counters of the filters replaced by configuration changes
+    carried: SimStats,
+    blocked: HashMap<IpAddr, BlockedIp>,
+    config: Option<AppliedConfig>,
+}
+
+/// Userspace data plane of the simulated worker
+pub struct SimDataPlane {
+    state: Mutex<State>,
+}
+
+impl Default for SimDataPlane {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimDataPlane {
+    /// Data plane with the reference filter defaults, until a configuration
+    /// is applied
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                filter: ReferenceFilter::new(ScenarioConfig::default(), SystemClock::new()),
+                carried: SimStats::default(),
+                blocked: HashMap::new(),
+                config: None,
+            }),
+        }
+    }
+
+    /// Apply a filter configuration from the control plane
+    ///
+    /// The reference filter is rebuilt with the settings of the
+    /// configuration: rate limit buckets start over, counters and blocked
+    /// IPs carry over.
+    pub fn apply_config(&self, config: &FilterConfig) {
+        let settings = filter_settings(config);
+        let mut state = self.state.lock();
+
+        let mut filter = ReferenceFilter::new(settings.clone(), SystemClock::new());
+        let now = Utc::now();
+        state.blocked.retain(|_, entry| !entry.is_expired(now));
+        for entry in state.blocked.values() {
+            filter.block_ip(entry.ip, remaining(entry, now));
+        }
+        state.carried = state.carried.add(&state.filter.stats());
+        state.filter = filter;
+
+        state.config = Some(AppliedConfig {
+            version: config.version,
+            config_id: config.config_id.clone(),
+            backends: config
+                .backends
+                .iter()
+                .map(|b| b.backend_id.clone())
+                .collect(),
+            protection_level: settings.protection_level,
+            rate_limit: settings.rate_limit,
+            amplification: settings.amplification,
+            applied_at: now,
+        });
+    }
+
+    /// Configuration currently applied, if any
+    pub fn config(&self) -> Option<AppliedConfig> {
+        self.state.lock().config.clone()
+    }
+
+    /// Version of the applied configuration (0 before the first one)
+    pub fn config_version(&self) -> u32 {
+        self.state
+            .lock()
+            .config
+            .as_ref()
+            .map_or(0, |config| config.version)
+    }
+
+    /// Block `ip`, permanently or for `duration_secs`
+    pub fn block_ip(&self, ip: IpAddr, reason: &str, duration_secs: Option<u32>) -> Result<()> {
+        if duration_secs == Some(0) {
+            return Err(Error::validation("Block duration must be positive"));
+        }
+
+        let now = Utc::now();
+        let duration = duration_secs.map(|secs| Duration::from_secs(secs.into()));
+        let mut state = self.state.lock();
+        state.filter.block_ip(ip, duration);
+        state.blocked.insert(
+            ip,
+            BlockedIp {
+                ip,
+                reason: reason.to_string(),
+                blocked_at: now,
+                expires_at: duration_secs.map(|secs| now + chrono::Duration::seconds(secs.into())),
+            },
+        );
+        Ok(())
+    }
+
+    /// Lift the block of `ip`
+    pub fn unblock_ip(&self, ip: &IpAddr) -> Result<()> {
+        let mut state = self.state.lock();
+        state.filter.unblock_ip(ip);
+        match state.blocked.remove(ip) {
+            Some(_) => Ok(()),
+            None => Err(Error::not_found("Blocked IP", ip.to_string())),
+        }
+    }
+
+    /// Blocked IPs, expired entries left out
+    pub fn list_blocked_ips(&self) -> Vec<BlockedIp> {
+        let now = Utc::now();
+        let mut state = self.state.lock();
+        state.blocked.retain(|_, entry| !entry.is_expired(now));
+        state.filter.cleanup_expired();
+
+        let mut blocked: Vec<_> = state.blocked.values().cloned().collect();
+        blocked.sort_by_key(|entry| entry.blocked_at);
+        blocked
+    }
+
+    /// Generate `count` copies of `spec` and run them through the filter
+    pub fn inject(&self, spec: &PacketSpec, count: u32) -> Result<PacketResult> {
+        if count == 0 || count > MAX_PACKETS {
+            return Err(Error::validation(format!(
+                "Packet count must be between 1 and {}",
+                MAX_PACKETS
+            )));
+        }
+        let frame = spec.build().map_err(Error::validation)?;
+
+        let mut state = self.state.lock();
+        let mut result = PacketResult::default();
+        for _ in 0..count {
+            match state.filter.process(&frame) {
+                Verdict::Pass => result.pass += 1,
+                Verdict::Drop => result.drop += 1,
+            }
+        }
+        Ok(result)
+    }
+
+    /// Counters since startup
+    pub fn stats(&self) -> SimStats {
+        let state = self.state.lock();
+        state.carried.add(&state.filter.stats())
+    }
+
+    /// Sources with a rate limit bucket
+    pub fn tracked_sources(&self) -> usize {
+        self.state.lock().filter.tracked_sources()
+    }
+}
+
+/// Reference filter settings of a configuration: the highest protection
+/// level of the protected backends, the per-source rate limit when one of
+/// them sets it, and amplification checks when any backend is protected
+pub fn filter_settings(config: &FilterConfig) -> ScenarioConfig {
+    let protected: Vec<_> = config
+        .backends
+        .iter()
+        .filter_map(|backend| backend.protection.as_ref())
+        .filter(|protection| protection.enabled)
+        .collect();
+
+    let level = protected
+        .iter()
+        .map(|protection| protection.level)
+        .max()
+        .unwrap_or(0)
+        .min(MAX_PROTECTION_LEVEL);
+
+    ScenarioConfig {
+        protection_level: level as u8,
+        rate_limit: protected
+            .iter()
+            .any(|protection| protection.per_ip_rate.is_some()),
+        amplification: !protected.is_empty(),
+        blocked_ips: Vec::new(),
+    }
+}
+
+/// Time left of a block, `None` for permanent blocks
+fn remaining(entry: &BlockedIp, now: DateTime<Utc>) -> Option<Duration> {
+    entry
+        .expires_at
+        .map(|expires_at| (expires_at - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pistonprotection_proto::worker::{BackendFilter, ProtectionConfig, RateLimitConfig};
+
+    fn spec(src: &str) -> PacketSpec {
+        serde_json::from_value(serde_json::json!({
+            "protocol": "tcp",
+            "src": src,
+            "dst": "10.0.0.1",
+            "dst_port": 25565,
+            "flags": ["syn"],
+        }))
+        .unwrap()
+    }
+
+    fn backend(id: &str, level: u32, per_ip_rate: bool) -> BackendFilter {
+        BackendFilter {
+            backend_id: id.to_string(),
+            protection: Some(ProtectionConfig {
+                enabled: true,
+                level,
+                per_ip_rate: per_ip_rate.then_some(RateLimitConfig {
+                    tokens_per_second: 100,
+                    bucket_size: 1000,
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filter_settings() {
+        let config = FilterConfig {
+            version: 3,
+            backends: vec![backend("a", 2, false), backend("b", 9, true)],
+            ..Default::default()
+        };
+        let settings = filter_settings(&config);
+        assert_eq!(settings.protection_level, 5);
+        assert!(settings.rate_limit);
+        assert!(settings.amplification);
+
+        let settings = filter_settings(&FilterConfig::default());
+        assert_eq!(settings.protection_level, 0);
+        assert!(!settings.rate_limit);
+        assert!(!settings.amplification);
+    }
+
+    #[test]
+    fn test_block_and_unblock() {
+        let sim = SimDataPlane::new();
+        let attacker: IpAddr = "198.51.100.7".parse().unwrap();
+
+        sim.block_ip(attacker, "test", None).unwrap();
+        let result = sim.inject(&spec("198.51.100.7"), 10).unwrap();
+        assert_eq!(result, PacketResult { pass: 0, drop: 10 });
+        assert_eq!(sim.list_blocked_ips().len(), 1);
+
+        sim.unblock_ip(&attacker).unwrap();
+        let result = sim.inject(&spec("198.51.100.7"), 10).unwrap();
+        assert_eq!(result, PacketResult { pass: 10, drop: 0 });
+        assert!(sim.unblock_ip(&attacker).is_err());
+        assert!(sim.block_ip(attacker, "test", Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_apply_config_keeps_blocks_and_counters() {
+        let sim = SimDataPlane::new();
+        let attacker: IpAddr = "198.51.100.7".parse().unwrap();
+        sim.block_ip(attacker, "test", Some(600)).unwrap();
+        sim.inject(&spec("198.51.100.7"), 5).unwrap();
+
+        sim.apply_config(&FilterConfig {
+            version: 7,
+            config_id: "cfg-7".to_string(),
+            backends: vec![backend("a", 3, true)],
+            ..Default::default()
+        });
+
+        assert_eq!(sim.config_version(), 7);
+        let config = sim.config().unwrap();
+        assert_eq!(config.backends, vec!["a".to_string()]);
+        assert_eq!(config.protection_level, 3);
+
+        let result = sim.inject(&spec("198.51.100.7"), 5).unwrap();
+        assert_eq!(result.drop, 5);
+        assert_eq!(sim.stats().dropped, 10);
+    }
+
+    #[test]
+    fn test_inject_rejects_bad_requests() {
+        let sim = SimDataPlane::new();
+        assert!(sim.inject(&spec("198.51.100.7"), 0).is_err());
+        assert!(sim.inject(&spec("198.51.100.7"), MAX_PACKETS + 1).is_err());
+
+        // Mixed address families cannot be built
+        assert!(sim.inject(&spec("2001:db8::1"), 1).is_err());
+    }
+}
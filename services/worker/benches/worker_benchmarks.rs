@@ -1,4 +1,190 @@
-//! worker benchmarks placeholder
+//! Worker benchmarks
+//!
+//! Measures XDP program throughput when several CPUs see the same source,
+//! which is where shared per-source map entries contend. Each object is
+//! loaded (not attached) and one thread per CPU runs a UDP packet from a
+//! single source through it with `BPF_PROG_TEST_RUN`, at 1 thread and then
+//! at every thread count up to the CPU count. Comparing the objects built
+//! before and after a change to the maps shows what the change buys.
+//!
+//! Requires root (or CAP_BPF and CAP_NET_ADMIN) and built eBPF objects:
+//!
+//! ```text
+//! PISTON_BENCH_OBJECTS=before=/tmp/old/xdp_filter,after=target/bpfel-unknown-none/release/xdp_filter \
+//!     cargo bench -p pistonprotection-worker --bench worker_benchmarks
+//! ```
+//!
+//! - `PISTON_BENCH_OBJECTS`: `label=path` pairs, compared in order
+//! - `PISTON_BENCH_PROGRAM`: program to run (default `xdp_filter`;
+//!   `xdp_ratelimit` is configured with its default limits)
+//! - `PISTON_BENCH_THREADS`: highest thread count (default: every CPU)
+//! - `PISTON_BENCH_REPEAT`: runs per thread (default 1,000,000)
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/ebpf/selftest.rs"]
+mod selftest;
+
+use aya::maps::{PerCpuArray, PerCpuValues};
+use aya::programs::Xdp;
+use std::net::Ipv4Addr;
+use std::os::fd::AsFd;
+use std::time::Instant;
+
+/// bpffs directory for the pinned maps of benchmarked objects, kept apart
+/// from the worker's
+const PIN_PATH: &str = "/sys/fs/bpf/pistonprotection-bench";
+
+/// Mirrors `RateLimitConfig` in `xdp_ratelimit`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RateLimitConfig {
+    tokens_per_second: u64,
+    bucket_size: u64,
+    enabled: u32,
+    level: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u64` and two `u32` fields, no padding.
+unsafe impl aya::Pod for RateLimitConfig {}
+
+/// Throughput of one object at one thread count
+struct Measurement {
+    threads: usize,
+    /// Average run time reported by the kernel, across threads
+    ns_per_packet: f64,
+    /// Packets per second across all threads, by wall clock
+    pps: f64,
+}
+
 fn main() {
-    println!("Run with: cargo bench");
+    let Ok(objects) = std::env::var("PISTON_BENCH_OBJECTS") else {
+        println!("Set PISTON_BENCH_OBJECTS=label=path,... to benchmark eBPF objects");
+        return;
+    };
+    let program = std::env::var("PISTON_BENCH_PROGRAM").unwrap_or_else(|_| "xdp_filter".into());
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let max_threads = env_number("PISTON_BENCH_THREADS").map_or(cpus, |n| n as usize);
+    let repeat = env_number("PISTON_BENCH_REPEAT").unwrap_or(1_000_000);
+
+    let packet = selftest::udp_packet(
+        Ipv4Addr::new(198, 18, 0, 7),
+        Ipv4Addr::new(192, 0, 2, 1),
+        40000,
+        25565,
+        &[0u8; 64],
+    );
+
+    println!(
+        "{:<12} {:>8} {:>12} {:>14}",
+        "object", "threads", "ns/packet", "packets/s"
+    );
+    for (label, path) in objects.split(',').filter_map(|pair| pair.split_once('=')) {
+        match bench_object(path, &program, &packet, max_threads, repeat) {
+            Ok(measurements) => {
+                for m in measurements {
+                    println!(
+                        "{:<12} {:>8} {:>12.1} {:>14.0}",
+                        label, m.threads, m.ns_per_packet, m.pps
+                    );
+                }
+            }
+            Err(e) => eprintln!("{}: {}", label, e),
+        }
+    }
+}
+
+fn env_number(name: &str) -> Option<u32> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+fn bench_object(
+    path: &str,
+    program: &str,
+    packet: &[u8],
+    max_threads: usize,
+    repeat: u32,
+) -> Result<Vec<Measurement>, String> {
+    let data = std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    std::fs::create_dir_all(PIN_PATH)
+        .map_err(|e| format!("failed to create {}: {}", PIN_PATH, e))?;
+    let mut ebpf = aya::EbpfLoader::new()
+        .map_pin_path(PIN_PATH)
+        .load(&data)
+        .map_err(|e| format!("failed to load {}: {}", path, e))?;
+
+    if program == "xdp_ratelimit" {
+        let mut config: PerCpuArray<_, RateLimitConfig> = ebpf
+            .map_mut("RATELIMIT_CONFIG")
+            .ok_or("RATELIMIT_CONFIG not found")?
+            .try_into()
+            .map_err(|e| format!("invalid RATELIMIT_CONFIG: {}", e))?;
+        let value = RateLimitConfig {
+            tokens_per_second: 1000,
+            bucket_size: 2000,
+            enabled: 1,
+            level: 1,
+        };
+        let cpus = aya::util::nr_cpus().map_err(|(_, e)| e.to_string())?;
+        config
+            .set(0, PerCpuValues::try_from(vec![value; cpus]).unwrap(), 0)
+            .map_err(|e| format!("failed to configure {}: {}", program, e))?;
+    }
+
+    let xdp: &mut Xdp = ebpf
+        .program_mut(program)
+        .ok_or_else(|| format!("program {} not found", program))?
+        .try_into()
+        .map_err(|e| format!("{} is not an XDP program: {}", program, e))?;
+    xdp.load()
+        .map_err(|e| format!("failed to load {}: {}", program, e))?;
+    let fd = xdp.fd().map_err(|e| e.to_string())?.as_fd();
+
+    let mut thread_counts = vec![1];
+    let mut threads = 2;
+    while threads < max_threads {
+        thread_counts.push(threads);
+        threads *= 2;
+    }
+    if max_threads > 1 {
+        thread_counts.push(max_threads);
+    }
+
+    let mut measurements = Vec::new();
+    for threads in thread_counts {
+        let started = Instant::now();
+        let runs: Vec<Result<u32, String>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|cpu| {
+                    scope.spawn(move || {
+                        pin_to_cpu(cpu);
+                        selftest::test_run(fd, packet, repeat)
+                            .map(|run| run.duration_ns)
+                            .map_err(|e| format!("test run failed: {}", e))
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let elapsed = started.elapsed().as_secs_f64();
+        let durations = runs.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        measurements.push(Measurement {
+            threads,
+            ns_per_packet: durations.iter().map(|&ns| ns as f64).sum::<f64>() / threads as f64,
+            pps: (threads as u64 * repeat as u64) as f64 / elapsed,
+        });
+    }
+
+    Ok(measurements)
+}
+
+/// Keep the calling thread on one CPU so each thread runs the program on
+/// its own CPU
+fn pin_to_cpu(cpu: usize) {
+    // SAFETY: cpu_set_t is a plain bit set, zeroed before use
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+    }
 }
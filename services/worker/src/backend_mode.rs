@@ -0,0 +1,324 @@
+//! Backend Modes
+//!
+//! Applies the mode overrides published by the gateway (see
+//! `pistonprotection_common::backend_mode`). Destinations of a backend in
+//! passthrough or block-all get an entry in the xdp_filter mode maps, and
+//! its hostnames the same mode in the HTTP proxy. The proxy only sees a new
+//! set of modes once the XDP maps took it, so both enforce the same modes.
+//!
+//! Overrides past their revert time are dropped here even while the gateway
+//! still publishes them, so a backend never stays unprotected because the
+//! gateway is unreachable.
+
+use crate::ebpf::tenants::TenantDestination;
+use parking_lot::RwLock;
+use pistonprotection_common::backend_mode::{BackendMode, BackendModeOverride};
+use pistonprotection_common::error::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Mode map value passing all traffic (mirrors `backend_mode::PASSTHROUGH`)
+pub const XDP_PASSTHROUGH: u32 = 1;
+
+/// Mode map value dropping all traffic (mirrors `backend_mode::BLOCK_ALL`)
+pub const XDP_BLOCK_ALL: u32 = 2;
+
+/// Mode of a backend changed on this worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeChange {
+    pub backend_id: String,
+    pub from: BackendMode,
+    pub to: BackendMode,
+    /// The override reached its revert time
+    pub expired: bool,
+}
+
+/// Override applied on this worker
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendModeStatus {
+    #[serde(flatten)]
+    pub mode: BackendModeOverride,
+    /// XDP destinations the mode applies to
+    pub destinations: usize,
+    /// Seconds until the backend goes back to protection
+    pub reverts_in_secs: i64,
+}
+
+#[derive(Default)]
+struct Applied {
+    /// Overrides keyed by backend ID
+    overrides: HashMap<String, BackendModeOverride>,
+    /// Modes keyed by lowercase hostname
+    hosts: HashMap<String, BackendMode>,
+    /// XDP destinations per backend ID
+    destinations: HashMap<String, usize>,
+}
+
+/// Mode overrides applied to the XDP maps and the HTTP proxy.
+#[derive(Default)]
+pub struct BackendModes {
+    applied: RwLock<Applied>,
+}
+
+impl BackendModes {
+    /// Create a tracker without overrides: every backend is protected.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the applied overrides.
+    ///
+    /// Overrides past their revert time at `now` are dropped. The XDP map
+    /// entries of the remaining ones are handed to `write_xdp`; if it fails
+    /// the previous overrides stay in place and the error is returned.
+    pub fn apply<D, W>(
+        &self,
+        overrides: Vec<BackendModeOverride>,
+        now: i64,
+        destinations: D,
+        write_xdp: W,
+    ) -> Result<Vec<ModeChange>>
+    where
+        D: Fn(&str) -> Vec<TenantDestination>,
+        W: FnOnce(&[(TenantDestination, u32)]) -> Result<()>,
+    {
+        let mut next = Applied::default();
+        let mut expired = Vec::new();
+        for mode in overrides {
+            if mode.mode == BackendMode::Protect {
+                continue;
+            }
+            if mode.expired(now) {
+                expired.push(mode.backend_id);
+                continue;
+            }
+            next.overrides.insert(mode.backend_id.clone(), mode);
+        }
+
+        let mut entries = Vec::new();
+        for (backend_id, mode) in &next.overrides {
+            let value = xdp_mode(mode.mode);
+            let backend_destinations = destinations(backend_id);
+            next.destinations
+                .insert(backend_id.clone(), backend_destinations.len());
+            entries.extend(backend_destinations.into_iter().map(|d| (d, value)));
+            for host in &mode.hosts {
+                next.hosts.insert(host.to_ascii_lowercase(), mode.mode);
+            }
+        }
+
+        let mut applied = self.applied.write();
+        write_xdp(&entries)?;
+
+        let mut changes = Vec::new();
+        for (backend_id, current) in &applied.overrides {
+            if !next.overrides.contains_key(backend_id) {
+                changes.push(ModeChange {
+                    backend_id: backend_id.clone(),
+                    from: current.mode,
+                    to: BackendMode::Protect,
+                    expired: expired.contains(backend_id),
+                });
+            }
+        }
+        for (backend_id, mode) in &next.overrides {
+            let from = applied
+                .overrides
+                .get(backend_id)
+                .map(|current| current.mode)
+                .unwrap_or_default();
+            if from != mode.mode {
+                changes.push(ModeChange {
+                    backend_id: backend_id.clone(),
+                    from,
+                    to: mode.mode,
+                    expired: false,
+                });
+            }
+        }
+
+        *applied = next;
+        Ok(changes)
+    }
+
+    /// Overrides currently applied.
+    pub fn overrides(&self) -> Vec<BackendModeOverride> {
+        self.applied.read().overrides.values().cloned().collect()
+    }
+
+    /// Mode of a backend.
+    pub fn mode(&self, backend_id: &str) -> BackendMode {
+        self.applied
+            .read()
+            .overrides
+            .get(backend_id)
+            .map(|mode| mode.mode)
+            .unwrap_or_default()
+    }
+
+    /// Mode of the backend serving a hostname.
+    pub fn host_mode(&self, host: &str) -> BackendMode {
+        let applied = self.applied.read();
+        if applied.hosts.is_empty() {
+            return BackendMode::Protect;
+        }
+        applied
+            .hosts
+            .get(&host.to_ascii_lowercase())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Overrides applied on this worker.
+    pub fn status(&self, now: i64) -> Vec<BackendModeStatus> {
+        let applied = self.applied.read();
+        let mut status: Vec<BackendModeStatus> = applied
+            .overrides
+            .values()
+            .map(|mode| BackendModeStatus {
+                destinations: applied
+                    .destinations
+                    .get(&mode.backend_id)
+                    .copied()
+                    .unwrap_or(0),
+                reverts_in_secs: (mode.revert_at - now).max(0),
+                mode: mode.clone(),
+            })
+            .collect();
+        status.sort_by(|a, b| a.mode.backend_id.cmp(&b.mode.backend_id));
+        status
+    }
+}
+
+/// Mode map value of a mode; protected backends have no entry.
+pub fn xdp_mode(mode: BackendMode) -> u32 {
+    match mode {
+        BackendMode::Protect => 0,
+        BackendMode::Passthrough => XDP_PASSTHROUGH,
+        BackendMode::BlockAll => XDP_BLOCK_ALL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pistonprotection_common::error::Error;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn mode(backend_id: &str, mode: BackendMode, revert_at: i64) -> BackendModeOverride {
+        BackendModeOverride {
+            backend_id: backend_id.to_string(),
+            mode,
+            hosts: vec![format!("{}.example.com", backend_id)],
+            reason: "debugging".to_string(),
+            changed_by: "user-1".to_string(),
+            changed_at: 0,
+            revert_at,
+        }
+    }
+
+    fn destinations(backend_id: &str) -> Vec<TenantDestination> {
+        let last = backend_id.len() as u8;
+        vec![TenantDestination {
+            addr: IpAddr::V4(Ipv4Addr::new(10, 0, 0, last)),
+            port: 25565,
+        }]
+    }
+
+    #[test]
+    fn test_applies_modes_to_xdp_and_hosts() {
+        let modes = BackendModes::new();
+        let mut written = Vec::new();
+
+        let changes = modes
+            .apply(
+                vec![
+                    mode("a", BackendMode::Passthrough, 100),
+                    mode("bb", BackendMode::BlockAll, 100),
+                ],
+                10,
+                destinations,
+                |entries| {
+                    written = entries.to_vec();
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        assert_eq!(changes.len(), 2);
+        written.sort_by_key(|(_, value)| *value);
+        assert_eq!(
+            written,
+            vec![
+                (destinations("a")[0], XDP_PASSTHROUGH),
+                (destinations("bb")[0], XDP_BLOCK_ALL)
+            ]
+        );
+        assert_eq!(modes.mode("a"), BackendMode::Passthrough);
+        assert_eq!(modes.host_mode("BB.example.com"), BackendMode::BlockAll);
+        assert_eq!(modes.host_mode("other.example.com"), BackendMode::Protect);
+        assert_eq!(modes.status(40)[0].reverts_in_secs, 60);
+    }
+
+    #[test]
+    fn test_expired_modes_revert_locally() {
+        let modes = BackendModes::new();
+        modes
+            .apply(
+                vec![mode("a", BackendMode::Passthrough, 100)],
+                10,
+                destinations,
+                |_| Ok(()),
+            )
+            .unwrap();
+
+        // The gateway still publishes the override past its revert time
+        let changes = modes
+            .apply(
+                vec![mode("a", BackendMode::Passthrough, 100)],
+                100,
+                destinations,
+                |entries| {
+                    assert!(entries.is_empty());
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            changes,
+            vec![ModeChange {
+                backend_id: "a".to_string(),
+                from: BackendMode::Passthrough,
+                to: BackendMode::Protect,
+                expired: true,
+            }]
+        );
+        assert_eq!(modes.mode("a"), BackendMode::Protect);
+        assert_eq!(modes.host_mode("a.example.com"), BackendMode::Protect);
+    }
+
+    #[test]
+    fn test_failed_xdp_write_keeps_previous_modes() {
+        let modes = BackendModes::new();
+        modes
+            .apply(
+                vec![mode("a", BackendMode::Passthrough, 100)],
+                10,
+                destinations,
+                |_| Ok(()),
+            )
+            .unwrap();
+
+        let result = modes.apply(
+            vec![mode("a", BackendMode::BlockAll, 100)],
+            20,
+            destinations,
+            |_| Err(Error::Internal("map full".to_string())),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(modes.mode("a"), BackendMode::Passthrough);
+        assert_eq!(modes.host_mode("a.example.com"), BackendMode::Passthrough);
+    }
+}
@@ -0,0 +1,193 @@
+//! Block Responses
+//!
+//! Responses served to clients refused at L7, configured per backend with
+//! `BlockResponseConfig` and applied with the filter configuration. The HTTP
+//! proxy serves the block page of the backend owning the requested hostname,
+//! and refused Minecraft logins to a destination of the backend get its kick
+//! message. Backends without a custom response keep the defaults.
+
+use crate::ebpf::tenants::TenantDestination;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Custom responses of a backend
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackendBlockResponses {
+    pub backend_id: String,
+    /// Hostnames of the backend
+    pub hosts: Vec<String>,
+    /// Destinations of the backend
+    pub destinations: Vec<TenantDestination>,
+    /// HTML block page template, if customized
+    pub http_block_page: Option<String>,
+    /// Kick message as a JSON text component, if customized
+    pub minecraft_kick_message: Option<String>,
+}
+
+#[derive(Default)]
+struct Applied {
+    /// Responses keyed by lowercase hostname
+    hosts: HashMap<String, Arc<BackendBlockResponses>>,
+    /// Responses keyed by destination (port 0 matches any port)
+    destinations: HashMap<TenantDestination, Arc<BackendBlockResponses>>,
+}
+
+/// Custom block responses of the configured backends.
+#[derive(Default)]
+pub struct BlockResponses {
+    applied: RwLock<Applied>,
+}
+
+impl BlockResponses {
+    /// Create a store without custom responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the custom responses of all backends.
+    pub fn replace(&self, backends: Vec<BackendBlockResponses>) {
+        let mut next = Applied::default();
+        for backend in backends {
+            if backend.http_block_page.is_none() && backend.minecraft_kick_message.is_none() {
+                continue;
+            }
+            let backend = Arc::new(backend);
+            for host in &backend.hosts {
+                next.hosts
+                    .insert(host.to_ascii_lowercase(), Arc::clone(&backend));
+            }
+            for destination in &backend.destinations {
+                next.destinations.insert(*destination, Arc::clone(&backend));
+            }
+        }
+        *self.applied.write() = next;
+    }
+
+    /// Block page for a refused request to a hostname, if the backend
+    /// serving it has one.
+    pub fn block_page(
+        &self,
+        host: &str,
+        status: u16,
+        reason: &str,
+        client_ip: IpAddr,
+    ) -> Option<String> {
+        let applied = self.applied.read();
+        let template = applied
+            .hosts
+            .get(&host.to_ascii_lowercase())?
+            .http_block_page
+            .as_deref()?;
+        Some(render_block_page(template, status, reason, host, client_ip))
+    }
+
+    /// Kick message for refused logins to a destination, if the backend
+    /// behind it has one.
+    pub fn kick_message(&self, addr: IpAddr, port: u16) -> Option<String> {
+        let applied = self.applied.read();
+        [port, 0]
+            .into_iter()
+            .find_map(|port| applied.destinations.get(&TenantDestination { addr, port }))
+            .and_then(|backend| backend.minecraft_kick_message.clone())
+    }
+}
+
+/// Fill in the placeholders of a block page template.
+pub fn render_block_page(
+    template: &str,
+    status: u16,
+    reason: &str,
+    host: &str,
+    client_ip: IpAddr,
+) -> String {
+    template
+        .replace("{{status}}", &status.to_string())
+        .replace("{{reason}}", &escape_html(reason))
+        .replace("{{host}}", &escape_html(host))
+        .replace("{{client_ip}}", &client_ip.to_string())
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn backend(page: Option<&str>, kick: Option<&str>) -> BackendBlockResponses {
+        BackendBlockResponses {
+            backend_id: "backend-1".to_string(),
+            hosts: vec!["Play.Example.com".to_string()],
+            destinations: vec![TenantDestination {
+                addr: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+                port: 0,
+            }],
+            http_block_page: page.map(str::to_string),
+            minecraft_kick_message: kick.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_block_page_placeholders() {
+        let responses = BlockResponses::new();
+        responses.replace(vec![backend(
+            Some("<h1>{{status}}</h1><p>{{reason}} on {{host}} for {{client_ip}}</p>"),
+            None,
+        )]);
+        let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+
+        let page = responses
+            .block_page("play.example.com", 429, "Too <many> requests", client)
+            .unwrap();
+        assert_eq!(
+            page,
+            "<h1>429</h1><p>Too &lt;many&gt; requests on play.example.com for 192.0.2.7</p>"
+        );
+        assert!(
+            responses
+                .block_page("other.example.com", 429, "", client)
+                .is_none()
+        );
+        assert!(responses.kick_message(client, 25565).is_none());
+    }
+
+    #[test]
+    fn test_kick_message_by_destination() {
+        let responses = BlockResponses::new();
+        responses.replace(vec![
+            backend(None, Some(r#"{"text":"Banned"}"#)),
+            BackendBlockResponses {
+                backend_id: "backend-2".to_string(),
+                ..Default::default()
+            },
+        ]);
+        let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(
+            responses.kick_message(addr, 25565).as_deref(),
+            Some(r#"{"text":"Banned"}"#)
+        );
+        assert!(
+            responses
+                .kick_message(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 25565)
+                .is_none()
+        );
+
+        responses.replace(Vec::new());
+        assert!(responses.kick_message(addr, 25565).is_none());
+    }
+}
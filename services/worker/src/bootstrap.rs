@@ -0,0 +1,815 @@
+//! Worker Bootstrap
+//!
+//! Without a control plane pushing program rollouts, the worker can bring up
+//! its XDP programs from a bootstrap file rendered by Helm into a ConfigMap.
+//! The file lists node pools, each with:
+//! - a node selector matched against the labels of the worker's node
+//! - the programs to load, their attach mode and initial sampling and latency
+//!   rates
+//! - the interfaces each program is attached to, by exact name, name regex
+//!   or a node label holding the interface name
+//! - optionally the initial protected ports
+//! - optionally the conntrack bypass: tc_flowmark attached next to xdp_tcp,
+//!   marking the flows it validated
+//!
+//! The first pool matching the node applies. The file is read at startup and
+//! re-read whenever the mounted ConfigMap changes; interfaces no longer
+//! selected are detached. Protected ports set here are only a starting point:
+//! the operator's protected port discovery replaces them when enabled.
+//!
+//! When artifact signatures are enforced, the file must come with its
+//! detached signature (`bootstrap.json.sig`, see `ebpf::artifacts`), and so
+//! must every object file it loads.
+
+use crate::ebpf::flow_mark::{self, FlowMarkConfig, FlowMarkSpec};
+use crate::ebpf::interface::NetworkInterface;
+use crate::ebpf::loader::{EbpfLoader, XdpMode, object_digest};
+use crate::ebpf::protected_ports::ProtectedPorts;
+
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::signing::read_signed;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Default path of the bootstrap file (mounted from the bootstrap ConfigMap)
+pub const DEFAULT_BOOTSTRAP_PATH: &str = "/etc/pistonprotection/bootstrap/bootstrap.json";
+
+/// Name the bootstrap file is reported under in artifact checks
+pub const ARTIFACT: &str = "bootstrap";
+
+/// Default interval between checks of the bootstrap file
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 10;
+
+/// Default interval between refreshes of the node labels
+pub const DEFAULT_LABEL_REFRESH_SECS: u64 = 300;
+
+/// Directory of the service account credentials in a pod
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Upper bound on a node lookup against the Kubernetes API
+const NODE_LOOKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bootstrap settings
+#[derive(Debug, Clone)]
+pub struct BootstrapSettings {
+    /// Path of the bootstrap file
+    pub path: PathBuf,
+    /// Interval between checks of the bootstrap file
+    pub poll_interval: Duration,
+    /// Interval between refreshes of the node labels
+    pub label_refresh: Duration,
+    /// Name of the node the worker runs on
+    pub node_name: Option<String>,
+    /// Static node labels, used instead of the Kubernetes API when set
+    pub node_labels: Option<BTreeMap<String, String>>,
+}
+
+impl Default for BootstrapSettings {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_BOOTSTRAP_PATH),
+            poll_interval: Duration::from_secs(DEFAULT_POLL_INTERVAL_SECS),
+            label_refresh: Duration::from_secs(DEFAULT_LABEL_REFRESH_SECS),
+            node_name: None,
+            node_labels: None,
+        }
+    }
+}
+
+impl BootstrapSettings {
+    /// Load bootstrap settings from environment variables
+    pub fn from_env() -> Self {
+        let mut settings = Self::default();
+
+        if let Ok(path) = std::env::var("PISTON_BOOTSTRAP_CONFIG") {
+            settings.path = PathBuf::from(path);
+        }
+
+        if let Ok(secs) = std::env::var("PISTON_BOOTSTRAP_POLL_SECS") {
+            if let Ok(secs) = secs.parse::<u64>() {
+                settings.poll_interval = Duration::from_secs(secs.max(1));
+            }
+        }
+
+        if let Ok(secs) = std::env::var("PISTON_BOOTSTRAP_LABEL_REFRESH_SECS") {
+            if let Ok(secs) = secs.parse::<u64>() {
+                settings.label_refresh = Duration::from_secs(secs.max(1));
+            }
+        }
+
+        settings.node_name = std::env::var("NODE_NAME").ok().filter(|n| !n.is_empty());
+
+        // Static labels (format: pool=edge,topology.kubernetes.io/zone=eu-1)
+        if let Ok(labels) = std::env::var("PISTON_NODE_LABELS") {
+            settings.node_labels = Some(parse_labels(&labels));
+        }
+
+        settings
+    }
+}
+
+/// Parse `key=value` pairs separated by commas
+fn parse_labels(labels: &str) -> BTreeMap<String, String> {
+    labels
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+/// Contents of the bootstrap file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BootstrapConfig {
+    #[serde(default)]
+    pub pools: Vec<NodePool>,
+}
+
+/// Programs and their configuration for the nodes of a pool
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodePool {
+    pub name: String,
+    /// Labels the node must carry; empty matches every node
+    #[serde(default)]
+    pub node_selector: BTreeMap<String, String>,
+    #[serde(default)]
+    pub programs: Vec<ProgramSpec>,
+    /// Initial protected ports
+    #[serde(default)]
+    pub protected_ports: Option<ProtectedPorts>,
+    /// Conntrack bypass of the flows xdp_tcp validated
+    #[serde(default)]
+    pub flow_mark: Option<FlowMarkSpec>,
+}
+
+/// A program to load and where to attach it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramSpec {
+    pub name: String,
+    /// Version of the object file in the program directory
+    pub version: String,
+    /// Expected SHA-256 of the object file (hex); not checked when unset
+    #[serde(default)]
+    pub digest: Option<String>,
+    #[serde(default)]
+    pub mode: AttachMode,
+    #[serde(default)]
+    pub sampling_rate: Option<u32>,
+    #[serde(default)]
+    pub latency_rate: Option<u32>,
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceSelector>,
+}
+
+/// XDP attach mode of a bootstrapped program
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachMode {
+    Offload,
+    #[default]
+    Driver,
+    Generic,
+}
+
+impl From<AttachMode> for XdpMode {
+    fn from(mode: AttachMode) -> Self {
+        match mode {
+            AttachMode::Offload => XdpMode::Offload,
+            AttachMode::Driver => XdpMode::Driver,
+            AttachMode::Generic => XdpMode::Generic,
+        }
+    }
+}
+
+/// Interface selection rule; a rule with several fields needs all to match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceSelector {
+    /// Exact interface name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Regex the whole interface name must match
+    #[serde(default)]
+    pub name_regex: Option<String>,
+    /// Node label whose value is the interface name
+    #[serde(default)]
+    pub node_label: Option<String>,
+}
+
+impl InterfaceSelector {
+    fn matches(
+        &self,
+        interface: &str,
+        regex: Option<&Regex>,
+        labels: &BTreeMap<String, String>,
+    ) -> bool {
+        if self.name.is_none() && regex.is_none() && self.node_label.is_none() {
+            return false;
+        }
+        if self.name.as_deref().is_some_and(|name| name != interface) {
+            return false;
+        }
+        if regex.is_some_and(|regex| !regex.is_match(interface)) {
+            return false;
+        }
+        if let Some(label) = &self.node_label {
+            if labels.get(label).map(String::as_str) != Some(interface) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn regex(&self) -> Result<Option<Regex>> {
+        self.name_regex
+            .as_deref()
+            .map(|pattern| {
+                Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| {
+                    Error::validation(format!("Invalid interface regex {:?}: {}", pattern, e))
+                })
+            })
+            .transpose()
+    }
+}
+
+impl BootstrapConfig {
+    /// Parse and validate a bootstrap file
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = serde_json::from_str(contents)
+            .map_err(|e| Error::validation(format!("Invalid bootstrap config: {}", e)))?;
+
+        for pool in &config.pools {
+            for program in &pool.programs {
+                if program.name.is_empty() || program.version.is_empty() {
+                    return Err(Error::validation(format!(
+                        "Program in pool {} needs a name and a version",
+                        pool.name
+                    )));
+                }
+                for selector in &program.interfaces {
+                    selector.regex()?;
+                }
+            }
+            if let Some(ports) = &pool.protected_ports {
+                ports.validate()?;
+            }
+            if let Some(spec) = &pool.flow_mark {
+                spec.validate()?;
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// First pool whose node selector matches the labels
+    pub fn select_pool(&self, labels: &BTreeMap<String, String>) -> Option<&NodePool> {
+        self.pools.iter().find(|pool| {
+            pool.node_selector
+                .iter()
+                .all(|(key, value)| labels.get(key) == Some(value))
+        })
+    }
+}
+
+/// Program attached to an interface by the bootstrap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub program: String,
+    pub mode: AttachMode,
+}
+
+/// What a pool resolves to on this node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BootstrapPlan {
+    pub pool: Option<String>,
+    pub programs: Vec<ProgramSpec>,
+    /// Program of each selected interface, by interface name
+    pub attachments: BTreeMap<String, Attachment>,
+    pub protected_ports: Option<ProtectedPorts>,
+    pub flow_mark: Option<FlowMarkSpec>,
+    /// Interfaces tc_flowmark is attached to: those running xdp_tcp
+    pub flow_mark_interfaces: BTreeSet<String>,
+}
+
+/// Resolve the pool matching the node against its interfaces
+///
+/// Only XDP-capable interfaces are selected. An interface selected by
+/// several programs keeps the first one, as it can only hold one.
+pub fn plan(
+    config: &BootstrapConfig,
+    labels: &BTreeMap<String, String>,
+    interfaces: &[NetworkInterface],
+) -> Result<BootstrapPlan> {
+    let Some(pool) = config.select_pool(labels) else {
+        return Ok(BootstrapPlan::default());
+    };
+
+    let mut attachments = BTreeMap::new();
+    for program in &pool.programs {
+        for selector in &program.interfaces {
+            let regex = selector.regex()?;
+            for interface in interfaces.iter().filter(|i| i.supports_xdp()) {
+                if !selector.matches(&interface.name, regex.as_ref(), labels) {
+                    continue;
+                }
+                match attachments.get(&interface.name) {
+                    Some(Attachment { program: other, .. }) if *other != program.name => warn!(
+                        "Interface {} selected by {} and {}, keeping {}",
+                        interface.name, other, program.name, other
+                    ),
+                    Some(_) => {}
+                    None => {
+                        attachments.insert(
+                            interface.name.clone(),
+                            Attachment {
+                                program: program.name.clone(),
+                                mode: program.mode,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Flows are only recorded where xdp_tcp sees their handshake
+    let flow_mark_interfaces = match &pool.flow_mark {
+        Some(_) => attachments
+            .iter()
+            .filter(|(_, attachment)| attachment.program == "xdp_tcp")
+            .map(|(interface, _)| interface.clone())
+            .collect(),
+        None => BTreeSet::new(),
+    };
+
+    Ok(BootstrapPlan {
+        pool: Some(pool.name.clone()),
+        programs: pool.programs.clone(),
+        attachments,
+        protected_ports: pool.protected_ports.clone(),
+        flow_mark: pool.flow_mark.clone(),
+        flow_mark_interfaces,
+    })
+}
+
+/// Interfaces attached by a previous plan that the new one drops or moves
+pub fn stale_attachments(
+    previous: &BTreeMap<String, Attachment>,
+    next: &BTreeMap<String, Attachment>,
+) -> BTreeSet<String> {
+    previous
+        .iter()
+        .filter(|(interface, attachment)| next.get(*interface) != Some(attachment))
+        .map(|(interface, _)| interface.clone())
+        .collect()
+}
+
+/// Apply a plan to the loader
+///
+/// Failures are logged and skipped, so one broken program does not keep
+/// the others from coming up. Returns the attachments that took effect.
+pub fn apply(
+    loader: &mut EbpfLoader,
+    program_dir: &Path,
+    interfaces: &[NetworkInterface],
+    previous: &BTreeMap<String, Attachment>,
+    plan: &BootstrapPlan,
+) -> BTreeMap<String, Attachment> {
+    for interface in stale_attachments(previous, &plan.attachments) {
+        match loader.detach_xdp(&interface) {
+            Ok(()) => info!("Detached bootstrapped program from {}", interface),
+            Err(e) => warn!("Failed to detach {}: {}", interface, e),
+        }
+    }
+
+    for program in &plan.programs {
+        if let Err(e) = load_program(loader, program_dir, program) {
+            error!(
+                "Failed to load {} version {}: {}",
+                program.name, program.version, e
+            );
+            loader.record_load_error(&program.name, e.to_string());
+            continue;
+        }
+        if let Some(rate) = program.sampling_rate {
+            if let Err(e) = loader.set_sampling_rate(&program.name, rate) {
+                warn!("Failed to set sampling rate of {}: {}", program.name, e);
+            }
+        }
+        if let Some(rate) = program.latency_rate {
+            if let Err(e) = loader.set_latency_rate(&program.name, rate) {
+                warn!("Failed to set latency rate of {}: {}", program.name, e);
+            }
+        }
+    }
+
+    let mut attached = BTreeMap::new();
+    for (name, attachment) in &plan.attachments {
+        if previous.get(name) == Some(attachment) && loader.is_attached(name) {
+            attached.insert(name.clone(), attachment.clone());
+            continue;
+        }
+        let Some(interface) = interfaces.iter().find(|i| i.name == *name) else {
+            continue;
+        };
+        match loader.attach_xdp(&attachment.program, interface, attachment.mode.into()) {
+            Ok(()) => {
+                attached.insert(name.clone(), attachment.clone());
+            }
+            Err(e) => error!("Failed to attach {} to {}: {}", attachment.program, name, e),
+        }
+    }
+
+    if let Some(ports) = &plan.protected_ports {
+        if let Err(e) = loader.set_protected_ports(ports.clone()) {
+            warn!("Failed to set bootstrap protected ports: {}", e);
+        }
+    }
+
+    apply_flow_mark(loader, program_dir, plan);
+
+    attached
+}
+
+/// Attach tc_flowmark to the interfaces of the plan and switch the
+/// conntrack bypass on, or detach it and switch it off
+///
+/// The bypass is switched on last, so xdp_tcp only records flows once their
+/// packets can be marked.
+fn apply_flow_mark(loader: &mut EbpfLoader, program_dir: &Path, plan: &BootstrapPlan) {
+    let stale: Vec<String> = loader
+        .list_tc_attached()
+        .into_iter()
+        .filter(|a| a.program_name == flow_mark::PROGRAM)
+        .filter(|a| {
+            !plan.flow_mark_interfaces.contains(&a.interface)
+                || plan.flow_mark.as_ref().map(|spec| spec.direction) != Some(a.direction)
+        })
+        .map(|a| a.interface.clone())
+        .collect();
+    let Some(spec) = &plan.flow_mark else {
+        if let Err(e) = loader.set_flow_mark(FlowMarkConfig::default()) {
+            warn!("Failed to switch conntrack bypass off: {}", e);
+        }
+        detach_tc(loader, &stale);
+        return;
+    };
+    detach_tc(loader, &stale);
+
+    let program = ProgramSpec {
+        name: flow_mark::PROGRAM.to_string(),
+        version: spec.version.clone(),
+        digest: spec.digest.clone(),
+        ..Default::default()
+    };
+    if let Err(e) = load_program(loader, program_dir, &program) {
+        error!(
+            "Failed to load {} version {}: {}",
+            program.name, program.version, e
+        );
+        loader.record_load_error(&program.name, e.to_string());
+        return;
+    }
+
+    let mut attached = 0;
+    for interface in &plan.flow_mark_interfaces {
+        let current = loader
+            .list_tc_attached()
+            .into_iter()
+            .any(|a| a.interface == *interface && a.program_name == flow_mark::PROGRAM);
+        if current {
+            attached += 1;
+            continue;
+        }
+        match loader.attach_tc(flow_mark::PROGRAM, interface, spec.direction) {
+            Ok(()) => attached += 1,
+            Err(e) => error!(
+                "Failed to attach {} to {}: {}",
+                flow_mark::PROGRAM,
+                interface,
+                e
+            ),
+        }
+    }
+
+    let config = if attached > 0 {
+        spec.config()
+    } else {
+        warn!("No interface runs xdp_tcp, leaving conntrack bypass off");
+        FlowMarkConfig::default()
+    };
+    if let Err(e) = loader.set_flow_mark(config) {
+        warn!("Failed to set conntrack bypass: {}", e);
+    }
+}
+
+fn detach_tc(loader: &mut EbpfLoader, interfaces: &[String]) {
+    for interface in interfaces {
+        match loader.detach_tc(interface) {
+            Ok(()) => info!("Detached {} from {}", flow_mark::PROGRAM, interface),
+            Err(e) => warn!(
+                "Failed to detach {} from {}: {}",
+                flow_mark::PROGRAM,
+                interface,
+                e
+            ),
+        }
+    }
+}
+
+/// Load a program's object file unless that version is already loaded
+fn load_program(loader: &mut EbpfLoader, program_dir: &Path, program: &ProgramSpec) -> Result<()> {
+    if loader
+        .program_version(&program.name)
+        .is_some_and(|v| v.version == program.version)
+    {
+        return Ok(());
+    }
+
+    let variant = loader.program_variant().ok_or_else(|| {
+        Error::internal("Kernel lacks required BPF features for any program variant")
+    })?;
+    let path = program_dir.join(variant.object_file(&program.name, &program.version));
+    let (data, signature) = read_signed(&path)?;
+    let digest = program
+        .digest
+        .clone()
+        .unwrap_or_else(|| object_digest(&data));
+
+    loader.load_version(
+        &program.name,
+        &program.version,
+        &digest,
+        &data,
+        signature.as_deref(),
+    )?;
+    info!(
+        "Loaded {} version {} from bootstrap config",
+        program.name, program.version
+    );
+    Ok(())
+}
+
+/// Labels of the worker's node
+///
+/// Static labels take precedence; otherwise the node is looked up with the
+/// pod's service account, which needs `get` on nodes.
+pub async fn node_labels(settings: &BootstrapSettings) -> Result<BTreeMap<String, String>> {
+    if let Some(labels) = &settings.node_labels {
+        return Ok(labels.clone());
+    }
+    let node = settings
+        .node_name
+        .as_deref()
+        .ok_or_else(|| Error::validation("NODE_NAME is not set"))?;
+    fetch_node_labels(node).await
+}
+
+/// Fetch the labels of a node from the Kubernetes API
+async fn fetch_node_labels(node: &str) -> Result<BTreeMap<String, String>> {
+    #[derive(Deserialize)]
+    struct Metadata {
+        #[serde(default)]
+        labels: BTreeMap<String, String>,
+    }
+    #[derive(Deserialize)]
+    struct Node {
+        metadata: Metadata,
+    }
+
+    let host = std::env::var("KUBERNETES_SERVICE_HOST")
+        .map_err(|_| Error::validation("Not running in Kubernetes and PISTON_NODE_LABELS unset"))?;
+    let port = std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+    let dir = Path::new(SERVICE_ACCOUNT_DIR);
+    let token = std::fs::read_to_string(dir.join("token"))
+        .map_err(|e| Error::internal(format!("Failed to read service account token: {}", e)))?;
+    let ca = std::fs::read(dir.join("ca.crt"))
+        .map_err(|e| Error::internal(format!("Failed to read cluster CA: {}", e)))?;
+    let ca = reqwest::Certificate::from_pem(&ca)
+        .map_err(|e| Error::internal(format!("Invalid cluster CA: {}", e)))?;
+
+    let client = reqwest::Client::builder()
+        .timeout(NODE_LOOKUP_TIMEOUT)
+        .add_root_certificate(ca)
+        .build()
+        .map_err(|e| Error::internal(format!("Failed to create Kubernetes client: {}", e)))?;
+
+    // IPv6 service hosts need brackets in the URL
+    let host = if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host
+    };
+    let node: Node = client
+        .get(format!("https://{}:{}/api/v1/nodes/{}", host, port, node))
+        .bearer_auth(token.trim())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| Error::external_service("kubernetes", e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| Error::external_service("kubernetes", e.to_string()))?;
+
+    Ok(node.metadata.labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interface(name: &str) -> NetworkInterface {
+        NetworkInterface {
+            name: name.to_string(),
+            index: 2,
+            mac_address: None,
+            ip_address: None,
+            is_up: true,
+            is_loopback: false,
+            mtu: 1500,
+        }
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    const CONFIG: &str = r#"{
+        "pools": [
+            {
+                "name": "edge",
+                "nodeSelector": {"pistonprotection.io/pool": "edge"},
+                "programs": [
+                    {
+                        "name": "xdp_filter",
+                        "version": "1.4.0",
+                        "mode": "generic",
+                        "samplingRate": 256,
+                        "interfaces": [{"nameRegex": "eth[0-9]+"}]
+                    },
+                    {
+                        "name": "xdp_udp",
+                        "version": "1.4.0",
+                        "interfaces": [
+                            {"nodeLabel": "pistonprotection.io/public-interface"},
+                            {"name": "eth0"}
+                        ]
+                    }
+                ],
+                "protectedPorts": {"udp": [19132]}
+            },
+            {
+                "name": "proxy",
+                "nodeSelector": {"pistonprotection.io/pool": "proxy"},
+                "programs": [
+                    {"name": "xdp_tcp", "version": "1.4.0", "interfaces": [{"name": "eth0"}]},
+                    {"name": "xdp_udp", "version": "1.4.0", "interfaces": [{"name": "eth1"}]}
+                ],
+                "flowMark": {"version": "1.4.0", "mark": 256, "direction": "both"}
+            },
+            {
+                "name": "default",
+                "programs": [
+                    {"name": "xdp_filter", "version": "1.3.0", "interfaces": [{"name": "eth0"}]}
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let config = BootstrapConfig::parse(CONFIG).unwrap();
+        assert_eq!(config.pools.len(), 3);
+        let filter = &config.pools[0].programs[0];
+        assert_eq!(filter.mode, AttachMode::Generic);
+        assert_eq!(filter.sampling_rate, Some(256));
+        assert_eq!(config.pools[0].programs[1].mode, AttachMode::Driver);
+        assert_eq!(
+            config.pools[0].protected_ports.as_ref().unwrap().udp,
+            [19132].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_regex() {
+        let config = r#"{"pools": [{"name": "p", "programs": [
+            {"name": "xdp_filter", "version": "1", "interfaces": [{"nameRegex": "eth["}]}
+        ]}]}"#;
+        assert!(BootstrapConfig::parse(config).is_err());
+    }
+
+    #[test]
+    fn test_select_pool() {
+        let config = BootstrapConfig::parse(CONFIG).unwrap();
+        let edge = labels(&[("pistonprotection.io/pool", "edge")]);
+        assert_eq!(config.select_pool(&edge).unwrap().name, "edge");
+        let other = labels(&[("pistonprotection.io/pool", "core")]);
+        assert_eq!(config.select_pool(&other).unwrap().name, "default");
+        assert!(BootstrapConfig::default().select_pool(&edge).is_none());
+    }
+
+    #[test]
+    fn test_plan_interfaces() {
+        let config = BootstrapConfig::parse(CONFIG).unwrap();
+        let node = labels(&[
+            ("pistonprotection.io/pool", "edge"),
+            ("pistonprotection.io/public-interface", "bond0"),
+        ]);
+        let mut lo = interface("lo");
+        lo.is_loopback = true;
+        let interfaces = vec![interface("eth0"), interface("eth1"), interface("bond0"), lo];
+
+        let plan = plan(&config, &node, &interfaces).unwrap();
+        assert_eq!(plan.pool.as_deref(), Some("edge"));
+        // eth0 is selected by both programs and keeps the first
+        assert_eq!(plan.attachments["eth0"].program, "xdp_filter");
+        assert_eq!(plan.attachments["eth0"].mode, AttachMode::Generic);
+        assert_eq!(plan.attachments["eth1"].program, "xdp_filter");
+        assert_eq!(plan.attachments["bond0"].program, "xdp_udp");
+        assert_eq!(plan.attachments["bond0"].mode, AttachMode::Driver);
+        assert!(!plan.attachments.contains_key("lo"));
+    }
+
+    #[test]
+    fn test_plan_flow_mark() {
+        let config = BootstrapConfig::parse(CONFIG).unwrap();
+        let interfaces = vec![interface("eth0"), interface("eth1")];
+
+        let proxy = labels(&[("pistonprotection.io/pool", "proxy")]);
+        let marked = plan(&config, &proxy, &interfaces).unwrap();
+        assert_eq!(marked.flow_mark.as_ref().unwrap().mark, 256);
+        // Only the interface running xdp_tcp is marked
+        assert_eq!(
+            marked.flow_mark_interfaces,
+            ["eth0".to_string()].into_iter().collect()
+        );
+
+        let edge = labels(&[("pistonprotection.io/pool", "edge")]);
+        let unmarked = plan(&config, &edge, &interfaces).unwrap();
+        assert!(unmarked.flow_mark.is_none());
+        assert!(unmarked.flow_mark_interfaces.is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_flow_mark() {
+        let config = r#"{"pools": [{"name": "p", "flowMark": {"version": "1", "mark": 0}}]}"#;
+        assert!(BootstrapConfig::parse(config).is_err());
+        let config = r#"{"pools": [{"name": "p",
+            "flowMark": {"version": "1", "mark": 256, "mask": 255}}]}"#;
+        assert!(BootstrapConfig::parse(config).is_err());
+    }
+
+    #[test]
+    fn test_empty_selector_matches_nothing() {
+        let selector = InterfaceSelector::default();
+        assert!(!selector.matches("eth0", None, &BTreeMap::new()));
+    }
+
+    #[test]
+    fn test_stale_attachments() {
+        let filter = Attachment {
+            program: "xdp_filter".to_string(),
+            mode: AttachMode::Driver,
+        };
+        let previous: BTreeMap<_, _> = [
+            ("eth0".to_string(), filter.clone()),
+            ("eth1".to_string(), filter.clone()),
+        ]
+        .into_iter()
+        .collect();
+        let next: BTreeMap<_, _> = [
+            ("eth0".to_string(), filter),
+            (
+                "eth1".to_string(),
+                Attachment {
+                    program: "xdp_udp".to_string(),
+                    mode: AttachMode::Driver,
+                },
+            ),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            stale_attachments(&previous, &next),
+            ["eth1".to_string()].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_parse_labels() {
+        assert_eq!(
+            parse_labels("pool=edge, zone = eu-1,invalid,=x"),
+            labels(&[("pool", "edge"), ("zone", "eu-1")])
+        );
+    }
+}
@@ -0,0 +1,452 @@
+//! Canary Rule Evaluation
+//!
+//! Evaluates a candidate filter configuration alongside the active one
+//! without enforcing it. Every source whose verdict differs between the two
+//! rule sets is written to the xdp_filter canary maps, where the data path
+//! only counts its traffic. The counters are turned into a comparison report
+//! of what the candidate would start dropping and what it would stop dropping.
+
+use crate::ebpf::stats::CanaryEntry;
+use pistonprotection_common::duration::bounded_duration;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::worker::{CanarySettings, FilterConfig};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+/// Evaluation period used when the control plane does not specify one
+pub const DEFAULT_DURATION_SECS: u32 = 300;
+
+/// Longest allowed evaluation period
+pub const MAX_DURATION_SECS: u32 = 86_400;
+
+/// Maximum number of sources tracked per address family (matches the eBPF maps)
+pub const MAX_TRACKED_SOURCES: usize = 65_536;
+
+/// How the candidate rule set would treat a source compared to the active one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryVerdict {
+    /// Candidate would drop traffic the active set passes
+    WouldDrop,
+    /// Candidate would pass traffic the active set drops
+    WouldPass,
+}
+
+impl CanaryVerdict {
+    /// Verdict value stored in the eBPF canary maps
+    pub fn as_raw(self) -> u32 {
+        match self {
+            CanaryVerdict::WouldDrop => 1,
+            CanaryVerdict::WouldPass => 2,
+        }
+    }
+}
+
+/// A source whose verdict differs between the active and candidate rule sets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanaryChange {
+    pub ip: IpAddr,
+    /// Rule responsible for the change (from the candidate for `WouldDrop`,
+    /// from the active set for `WouldPass`)
+    pub rule_id: String,
+    pub verdict: CanaryVerdict,
+}
+
+/// Sources blocked by a configuration's rules, mapped to the first rule
+/// blocking each of them
+pub fn blocked_sources(config: &FilterConfig) -> HashMap<IpAddr, String> {
+    let mut sources = HashMap::new();
+
+    for backend in &config.backends {
+        for rule in &backend.rules {
+            let Some(ref filter_match) = rule.r#match else {
+                continue;
+            };
+
+            for ip_network in &filter_match.source_ip_blacklist {
+                if let Some(ref addr) = ip_network.address {
+                    if let Ok(ip) = IpAddr::try_from(addr) {
+                        sources.entry(ip).or_insert_with(|| rule.id.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    sources
+}
+
+/// Compute the sources whose verdict would change if `candidate` replaced `active`
+pub fn diff_rule_sets(
+    active: Option<&FilterConfig>,
+    candidate: &FilterConfig,
+) -> Vec<CanaryChange> {
+    let active = active.map(blocked_sources).unwrap_or_default();
+    let candidate = blocked_sources(candidate);
+
+    let mut changes: Vec<CanaryChange> = candidate
+        .iter()
+        .filter(|(ip, _)| !active.contains_key(ip))
+        .map(|(ip, rule_id)| CanaryChange {
+            ip: *ip,
+            rule_id: rule_id.clone(),
+            verdict: CanaryVerdict::WouldDrop,
+        })
+        .chain(
+            active
+                .iter()
+                .filter(|(ip, _)| !candidate.contains_key(ip))
+                .map(|(ip, rule_id)| CanaryChange {
+                    ip: *ip,
+                    rule_id: rule_id.clone(),
+                    verdict: CanaryVerdict::WouldPass,
+                }),
+        )
+        .collect();
+
+    changes.sort_by(|a, b| a.ip.cmp(&b.ip));
+    changes
+}
+
+/// A candidate configuration under evaluation
+#[derive(Debug, Clone)]
+pub struct CanaryEvaluation {
+    candidate: FilterConfig,
+    active_version: Option<u32>,
+    duration_secs: u32,
+    auto_promote: bool,
+    started_at: chrono::DateTime<chrono::Utc>,
+    changes: Vec<CanaryChange>,
+    truncated: bool,
+    counters: HashMap<IpAddr, CanaryEntry>,
+}
+
+impl CanaryEvaluation {
+    /// Start evaluating `candidate` against the active configuration
+    ///
+    /// Fails if the requested evaluation period is longer than allowed.
+    pub fn new(
+        candidate: &FilterConfig,
+        active: Option<&FilterConfig>,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Self> {
+        let settings = candidate.canary.unwrap_or_default();
+        let duration_secs = effective_duration(&settings)?;
+        let mut changes = diff_rule_sets(active, candidate);
+
+        // Bound each address family to what the canary maps can hold
+        let (mut v4, mut v6) = (0usize, 0usize);
+        let before = changes.len();
+        changes.retain(|change| {
+            let count = if change.ip.is_ipv4() {
+                &mut v4
+            } else {
+                &mut v6
+            };
+            *count += 1;
+            *count <= MAX_TRACKED_SOURCES
+        });
+        let truncated = changes.len() != before;
+
+        Ok(Self {
+            candidate: FilterConfig {
+                canary: None,
+                ..candidate.clone()
+            },
+            active_version: active.map(|config| config.version),
+            duration_secs,
+            auto_promote: settings.auto_promote,
+            started_at: now,
+            changes,
+            truncated,
+            counters: HashMap::new(),
+        })
+    }
+
+    /// Candidate configuration, with canary settings stripped so it can be enforced
+    pub fn candidate(&self) -> &FilterConfig {
+        &self.candidate
+    }
+
+    /// Whether the candidate should be enforced once the period elapses
+    pub fn auto_promote(&self) -> bool {
+        self.auto_promote
+    }
+
+    /// Sources whose verdict would change
+    pub fn changes(&self) -> &[CanaryChange] {
+        &self.changes
+    }
+
+    /// Source verdicts to program into the eBPF canary maps
+    pub fn map_entries(&self) -> Vec<(IpAddr, u32)> {
+        self.changes
+            .iter()
+            .map(|change| (change.ip, change.verdict.as_raw()))
+            .collect()
+    }
+
+    /// When the evaluation period ends
+    pub fn expires_at(&self) -> chrono::DateTime<chrono::Utc> {
+        self.started_at + chrono::Duration::seconds(self.duration_secs as i64)
+    }
+
+    /// Check whether the evaluation period has ended
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        now >= self.expires_at()
+    }
+
+    /// Replace the traffic counters with a fresh reading of the canary maps
+    pub fn record_counters(&mut self, counters: &[(IpAddr, CanaryEntry)]) {
+        self.counters = counters.iter().copied().collect();
+    }
+
+    /// Build the comparison report
+    pub fn report(&self, now: chrono::DateTime<chrono::Utc>) -> CanaryReport {
+        let mut would_drop = ImpactBuilder::default();
+        let mut would_pass = ImpactBuilder::default();
+
+        for change in &self.changes {
+            let counter = self.counters.get(&change.ip).copied().unwrap_or_default();
+            let builder = match change.verdict {
+                CanaryVerdict::WouldDrop => &mut would_drop,
+                CanaryVerdict::WouldPass => &mut would_pass,
+            };
+            builder.add(&change.rule_id, &counter);
+        }
+
+        CanaryReport {
+            candidate_config_id: self.candidate.config_id.clone(),
+            candidate_version: self.candidate.version,
+            active_version: self.active_version,
+            started_at: self.started_at,
+            expires_at: self.expires_at(),
+            expired: self.is_expired(now),
+            auto_promote: self.auto_promote,
+            truncated: self.truncated,
+            would_drop: would_drop.finish(),
+            would_pass: would_pass.finish(),
+        }
+    }
+}
+
+/// Comparison of would-be-dropped traffic between the active and candidate rule sets
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryReport {
+    pub candidate_config_id: String,
+    pub candidate_version: u32,
+    pub active_version: Option<u32>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub expired: bool,
+    pub auto_promote: bool,
+    /// Some changed sources were not tracked because the canary maps were full
+    pub truncated: bool,
+    /// Traffic the candidate would start dropping
+    pub would_drop: CanaryImpact,
+    /// Traffic the candidate would stop dropping
+    pub would_pass: CanaryImpact,
+}
+
+/// Aggregated impact for one verdict
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CanaryImpact {
+    pub sources: usize,
+    pub packets: u64,
+    pub bytes: u64,
+    /// Per-rule breakdown, highest packet count first
+    pub rules: Vec<CanaryRuleImpact>,
+}
+
+/// Impact attributed to a single rule
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CanaryRuleImpact {
+    pub rule_id: String,
+    pub sources: usize,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+#[derive(Default)]
+struct ImpactBuilder {
+    impact: CanaryImpact,
+    rules: BTreeMap<String, CanaryRuleImpact>,
+}
+
+impl ImpactBuilder {
+    fn add(&mut self, rule_id: &str, counter: &CanaryEntry) {
+        self.impact.sources += 1;
+        self.impact.packets = self.impact.packets.saturating_add(counter.packets);
+        self.impact.bytes = self.impact.bytes.saturating_add(counter.bytes);
+
+        let rule = self
+            .rules
+            .entry(rule_id.to_string())
+            .or_insert_with(|| CanaryRuleImpact {
+                rule_id: rule_id.to_string(),
+                ..Default::default()
+            });
+        rule.sources += 1;
+        rule.packets = rule.packets.saturating_add(counter.packets);
+        rule.bytes = rule.bytes.saturating_add(counter.bytes);
+    }
+
+    fn finish(mut self) -> CanaryImpact {
+        let mut rules: Vec<_> = self.rules.into_values().collect();
+        rules.sort_by(|a, b| b.packets.cmp(&a.packets).then(a.rule_id.cmp(&b.rule_id)));
+        self.impact.rules = rules;
+        self.impact
+    }
+}
+
+/// Evaluation period for the given settings, defaulted when unset
+fn effective_duration(settings: &CanarySettings) -> Result<u32> {
+    bounded_duration(
+        settings.duration_seconds,
+        DEFAULT_DURATION_SECS,
+        MAX_DURATION_SECS,
+    )
+    .ok_or_else(|| {
+        Error::validation(format!(
+            "Canary duration cannot exceed {} seconds",
+            MAX_DURATION_SECS
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pistonprotection_proto::common::{IpAddress, IpNetwork};
+    use pistonprotection_proto::filter::{FilterMatch, FilterRule};
+    use pistonprotection_proto::worker::BackendFilter;
+
+    fn config(version: u32, rules: &[(&str, &[&str])]) -> FilterConfig {
+        let rules = rules
+            .iter()
+            .map(|(id, ips)| FilterRule {
+                id: id.to_string(),
+                r#match: Some(FilterMatch {
+                    source_ip_blacklist: ips
+                        .iter()
+                        .map(|ip| IpNetwork {
+                            address: Some(IpAddress::from(ip.parse::<IpAddr>().unwrap())),
+                            prefix_length: 32,
+                        })
+                        .collect(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect();
+
+        FilterConfig {
+            config_id: format!("cfg-{}", version),
+            version,
+            backends: vec![BackendFilter {
+                backend_id: "backend-1".to_string(),
+                rules,
+                ..Default::default()
+            }],
+            global: None,
+            generated_at: None,
+            canary: None,
+            programs: vec![],
+        }
+    }
+
+    #[test]
+    fn test_diff_rule_sets() {
+        let active = config(1, &[("old", &["10.0.0.1", "10.0.0.2"])]);
+        let candidate = config(2, &[("new", &["10.0.0.2", "10.0.0.3"])]);
+
+        let changes = diff_rule_sets(Some(&active), &candidate);
+        assert_eq!(
+            changes,
+            vec![
+                CanaryChange {
+                    ip: "10.0.0.1".parse().unwrap(),
+                    rule_id: "old".to_string(),
+                    verdict: CanaryVerdict::WouldPass,
+                },
+                CanaryChange {
+                    ip: "10.0.0.3".parse().unwrap(),
+                    rule_id: "new".to_string(),
+                    verdict: CanaryVerdict::WouldDrop,
+                },
+            ]
+        );
+
+        // Without an active configuration everything the candidate blocks is new
+        let changes = diff_rule_sets(None, &candidate);
+        assert_eq!(changes.len(), 2);
+        assert!(
+            changes
+                .iter()
+                .all(|c| c.verdict == CanaryVerdict::WouldDrop)
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let active = config(1, &[("old", &["10.0.0.1"])]);
+        let mut candidate = config(2, &[("a", &["10.0.0.2", "10.0.0.3"]), ("b", &["10.0.0.4"])]);
+        candidate.canary = Some(CanarySettings {
+            duration_seconds: 60,
+            auto_promote: true,
+        });
+
+        let now = chrono::Utc::now();
+        let mut evaluation = CanaryEvaluation::new(&candidate, Some(&active), now).unwrap();
+        assert!(evaluation.candidate().canary.is_none());
+        assert_eq!(evaluation.map_entries().len(), 4);
+
+        let counter = |packets, bytes| CanaryEntry {
+            packets,
+            bytes,
+            ..Default::default()
+        };
+        evaluation.record_counters(&[
+            ("10.0.0.1".parse().unwrap(), counter(5, 500)),
+            ("10.0.0.2".parse().unwrap(), counter(1, 100)),
+            ("10.0.0.3".parse().unwrap(), counter(2, 200)),
+            ("10.0.0.4".parse().unwrap(), counter(10, 1000)),
+        ]);
+
+        let report = evaluation.report(now);
+        assert_eq!(report.active_version, Some(1));
+        assert_eq!(report.candidate_version, 2);
+        assert!(report.auto_promote);
+        assert!(!report.expired);
+        assert_eq!(report.would_drop.sources, 3);
+        assert_eq!(report.would_drop.packets, 13);
+        assert_eq!(report.would_drop.rules[0].rule_id, "b");
+        assert_eq!(report.would_drop.rules[1].packets, 3);
+        assert_eq!(report.would_pass.packets, 5);
+        assert_eq!(report.would_pass.rules[0].rule_id, "old");
+
+        assert!(evaluation.is_expired(now + chrono::Duration::seconds(60)));
+    }
+
+    #[test]
+    fn test_effective_duration() {
+        let settings = |duration_seconds| CanarySettings {
+            duration_seconds,
+            auto_promote: false,
+        };
+        assert_eq!(
+            effective_duration(&settings(0)).unwrap(),
+            DEFAULT_DURATION_SECS
+        );
+        assert_eq!(effective_duration(&settings(120)).unwrap(), 120);
+        assert_eq!(
+            effective_duration(&settings(MAX_DURATION_SECS)).unwrap(),
+            MAX_DURATION_SECS
+        );
+        assert!(matches!(
+            effective_duration(&settings(MAX_DURATION_SECS + 1)),
+            Err(Error::Validation(_))
+        ));
+    }
+}
@@ -4,22 +4,39 @@
 //! applying them to eBPF maps. Manages version tracking and ensures
 //! atomic updates where possible.
 
+use crate::block_response::{BackendBlockResponses, BlockResponses};
+use crate::canary::{CanaryEvaluation, CanaryReport};
 use crate::ebpf::{
+    flow_rate::{BackendFlowRate, FlowRateConfig},
+    honeypot::{BackendHoneypot, HoneypotPorts},
+    learning::{BackendLearning, LearningSettings},
     loader::EbpfLoader,
     maps::{BackendConfig, MapManager},
+    marking::{BackendMarking, DSCP_LE, DSCP_MAX, MarkingSettings},
+    penalty::PenaltyLadder,
+    stats::CanaryEntry,
+    tenants::{TenantDestination, TenantLimits},
 };
 use parking_lot::RwLock;
 use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::signing::read_signed;
 use pistonprotection_proto::worker::{
-    BackendFilter, FilterConfig, GlobalFilterSettings, MapOperation, MapUpdate,
+    BackendFilter, FilterConfig, GlobalFilterSettings, MapOperation, MapUpdate, ProgramTarget,
 };
 use std::collections::{HashMap, HashSet};
 use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::Notify;
 use tracing::{debug, error, info, warn};
 
+/// Port ranges wider than this are routed to a tenant for every port
+const MAX_TENANT_PORTS_PER_RANGE: u32 = 64;
+
+/// Directory holding versioned eBPF objects for rollouts (see `ProgramVariant::object_file`)
+const DEFAULT_PROGRAM_DIR: &str = "/opt/pistonprotection/ebpf";
+
 /// Configuration version tracking
 #[derive(Debug, Clone)]
 pub struct ConfigVersion {
@@ -70,6 +87,12 @@ pub struct ConfigSyncManager {
     sync_in_progress: Arc<AtomicBool>,
     /// Statistics
     stats: Arc<RwLock<SyncStats>>,
+    /// Candidate configuration under canary evaluation
+    canary: Arc<RwLock<Option<CanaryEvaluation>>>,
+    /// Directory with versioned program objects for rollouts
+    program_dir: PathBuf,
+    /// Custom L7 block responses of the configured backends
+    block_responses: Arc<BlockResponses>,
 }
 
 /// Synchronization statistics
@@ -104,9 +127,19 @@ impl ConfigSyncManager {
             pending_updates: Arc::new(RwLock::new(Vec::new())),
             sync_in_progress: Arc::new(AtomicBool::new(false)),
             stats: Arc::new(RwLock::new(SyncStats::default())),
+            canary: Arc::new(RwLock::new(None)),
+            program_dir: std::env::var("PISTON_PROGRAM_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from(DEFAULT_PROGRAM_DIR)),
+            block_responses: Arc::new(BlockResponses::new()),
         }
     }
 
+    /// Directory holding the program object files
+    pub fn program_dir(&self) -> &Path {
+        &self.program_dir
+    }
+
     /// Get the current configuration version
     pub fn current_version(&self) -> Option<ConfigVersion> {
         self.current_version.read().clone()
@@ -117,6 +150,21 @@ impl ConfigSyncManager {
         self.current_config.read().clone()
     }
 
+    /// Destinations of a backend in the current configuration
+    pub fn backend_destinations(&self, backend_id: &str) -> Vec<TenantDestination> {
+        self.current_config
+            .read()
+            .as_ref()
+            .and_then(|config| config.backends.iter().find(|b| b.backend_id == backend_id))
+            .map(tenant_destinations)
+            .unwrap_or_default()
+    }
+
+    /// Custom L7 block responses of the current configuration
+    pub fn block_responses(&self) -> Arc<BlockResponses> {
+        Arc::clone(&self.block_responses)
+    }
+
     /// Get applied backends
     pub fn applied_backends(&self) -> HashMap<String, AppliedBackendFilter> {
         self.applied_backends.read().clone()
@@ -124,7 +172,7 @@ impl ConfigSyncManager {
 
     /// Get global settings
     pub fn global_settings(&self) -> Option<GlobalFilterSettings> {
-        *self.global_settings.read()
+        self.global_settings.read().clone()
     }
 
     /// Get sync statistics
@@ -193,8 +241,23 @@ impl ConfigSyncManager {
             }
         }
 
+        // Candidate configurations are evaluated, not enforced
+        if config.canary.is_some() {
+            return self.start_canary(config);
+        }
+
+        // A new active configuration invalidates the canary baseline
+        let abandoned = self.canary.write().take();
+        if let Some(evaluation) = abandoned {
+            warn!(
+                "Active configuration changed, abandoning canary evaluation of version {}",
+                evaluation.candidate().version
+            );
+            self.clear_canary_maps();
+        }
+
         // Get loader and map manager
-        let loader = self.loader.write();
+        let mut loader = self.loader.write();
         let maps = loader.maps();
         let mut map_manager = maps.write();
 
@@ -221,9 +284,61 @@ impl ConfigSyncManager {
         // Apply global settings
         if let Some(ref global) = config.global {
             self.apply_global_settings(&mut map_manager, global)?;
-            *self.global_settings.write() = Some(*global);
+            match PenaltyLadder::from_proto(&global.penalty_ladder) {
+                Ok(ladder) => {
+                    if let Err(e) = loader.set_penalty_ladder(ladder) {
+                        warn!("Failed to update penalty ladder: {}", e);
+                    }
+                }
+                Err(e) => warn!("Ignoring invalid penalty ladder: {}", e),
+            }
+            *self.global_settings.write() = Some(global.clone());
+        }
+
+        // Sync tenant namespaces of the backends still configured
+        let configured: HashSet<String> = config
+            .backends
+            .iter()
+            .map(|b| b.backend_id.clone())
+            .collect();
+        map_manager.prune_tenants(&configured);
+        map_manager.prune_honeypots(&configured);
+        map_manager.prune_learning(&configured);
+        map_manager.prune_flow_rates(&configured);
+        map_manager.prune_traffic(&configured);
+        map_manager.prune_marking(&configured);
+        let tenant_entries = map_manager.tenant_map_entries();
+        let honeypot_entries = map_manager.honeypot_map_entries();
+        let flow_rate_entries = map_manager.flow_rate_entries();
+        let traffic_keys = map_manager
+            .traffic_entries()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        let marking_entries = map_manager.marking_entries();
+        drop(map_manager);
+        if let Err(e) = loader.set_tenant_entries(&tenant_entries) {
+            warn!("Failed to update tenant maps: {}", e);
+        }
+        if let Err(e) = loader.set_honeypot_entries(&honeypot_entries) {
+            warn!("Failed to update honeypot maps: {}", e);
+        }
+        if let Err(e) = loader.set_flow_rates(flow_rate_entries) {
+            warn!("Failed to update per-connection bandwidth limits: {}", e);
+        }
+        if let Err(e) = loader.set_traffic_keys(traffic_keys) {
+            warn!("Failed to update traffic accounting: {}", e);
+        }
+        if let Err(e) = loader.set_marking_entries(marking_entries) {
+            warn!("Failed to update traffic marking: {}", e);
         }
 
+        // Load program versions requested by rollouts
+        for target in &config.programs {
+            apply_program_target(&mut loader, &self.program_dir, target);
+        }
+        drop(loader);
+
         // Update version tracking
         let config_hash = calculate_config_hash(config);
         *self.current_version.write() = Some(ConfigVersion {
@@ -236,6 +351,15 @@ impl ConfigSyncManager {
         // Cache the configuration
         *self.current_config.write() = Some(config.clone());
 
+        // Serve the custom block responses of the configured backends
+        self.block_responses.replace(
+            config
+                .backends
+                .iter()
+                .map(backend_block_responses)
+                .collect(),
+        );
+
         // Update stats
         {
             let mut stats = self.stats.write();
@@ -280,9 +404,50 @@ impl ConfigSyncManager {
 
         map_manager.update_backend(backend_config);
 
+        // Backends with an owner get their own map namespace
+        if !backend.organization_id.is_empty() {
+            let limits = TenantLimits {
+                protection_level: protection.map(|p| p.level as u8).unwrap_or(0),
+                per_ip_pps: protection
+                    .and_then(|p| p.per_ip_rate.as_ref())
+                    .map(|r| r.tokens_per_second)
+                    .unwrap_or(0),
+                per_ip_burst: protection
+                    .and_then(|p| p.per_ip_rate.as_ref())
+                    .map(|r| r.bucket_size)
+                    .unwrap_or(0),
+            };
+            let tenant_id = map_manager.register_backend_tenant(
+                &backend.organization_id,
+                &backend.backend_id,
+                tenant_destinations(backend),
+                limits,
+            );
+            debug!(
+                "Backend {} mapped to tenant {} (organization {})",
+                backend.backend_id, tenant_id, backend.organization_id
+            );
+        }
+
+        // Honeypot ports flag sources for the backend's tenant
+        let tenant_id = map_manager.tenant_id(&backend.organization_id).unwrap_or(0);
+        map_manager.set_backend_honeypot(&backend.backend_id, backend_honeypot(backend, tenant_id));
+
+        // Learning mode trains the backend's known-good sources
+        map_manager.set_backend_learning(&backend.backend_id, backend_learning(backend));
+
+        // Established flows to the backend are held to its per-connection bandwidth
+        map_manager.set_backend_flow_rate(&backend.backend_id, backend_flow_rate(backend));
+
+        // Traffic to the backend counts towards its organization's bandwidth
+        map_manager.set_backend_traffic(&backend.backend_id, tenant_destinations(backend));
+
+        // Greylisted traffic to the backend is deprioritized instead of dropped
+        map_manager.set_backend_marking(&backend.backend_id, backend_marking(backend));
+
         // Apply filter rules
         for rule in &backend.rules {
-            self.apply_filter_rule(map_manager, &backend.backend_id, rule)?;
+            self.apply_filter_rule(map_manager, backend, rule)?;
         }
 
         // Track applied backend
@@ -305,12 +470,12 @@ impl ConfigSyncManager {
     fn apply_filter_rule(
         &self,
         map_manager: &mut MapManager,
-        backend_id: &str,
+        backend: &BackendFilter,
         rule: &pistonprotection_proto::filter::FilterRule,
     ) -> Result<()> {
         debug!(
             "Applying filter rule: {} for backend {}",
-            rule.id, backend_id
+            rule.id, backend.backend_id
         );
 
         // Extract matching criteria
@@ -319,11 +484,18 @@ impl ConfigSyncManager {
             for ip_network in &filter_match.source_ip_blacklist {
                 if let Some(ref addr) = ip_network.address {
                     if let Ok(ip) = std::net::IpAddr::try_from(addr) {
-                        map_manager.block_ip(
-                            ip,
-                            &format!("rule:{}", rule.id),
-                            None, // Permanent block from rule
-                        )?;
+                        let reason = format!("rule:{}", rule.id);
+                        // Rules of an owned backend only block within its organization
+                        if backend.organization_id.is_empty() {
+                            map_manager.block_ip(ip, &reason, None)?; // Permanent block from rule
+                        } else {
+                            map_manager.block_ip_in_tenant(
+                                &backend.organization_id,
+                                ip,
+                                &reason,
+                                None,
+                            )?;
+                        }
                     }
                 }
             }
@@ -516,6 +688,137 @@ impl ConfigSyncManager {
         Ok(())
     }
 
+    /// Start evaluating a candidate configuration alongside the active one
+    ///
+    /// The canary lock is never held while taking the loader lock.
+    fn start_canary(&self, config: &FilterConfig) -> Result<()> {
+        let running = self.canary.read().as_ref().is_some_and(|existing| {
+            existing.candidate().config_id == config.config_id
+                && existing.candidate().version == config.version
+        });
+        if running {
+            debug!(
+                "Canary evaluation of version {} already running",
+                config.version
+            );
+            return Ok(());
+        }
+
+        let evaluation = CanaryEvaluation::new(
+            config,
+            self.current_config.read().as_ref(),
+            chrono::Utc::now(),
+        )?;
+
+        info!(
+            "Starting canary evaluation: id={}, version={}, changed_sources={}, expires_at={}",
+            config.config_id,
+            config.version,
+            evaluation.changes().len(),
+            evaluation.expires_at()
+        );
+
+        if let Err(e) = self
+            .loader
+            .write()
+            .set_canary_entries(&evaluation.map_entries())
+        {
+            warn!("Canary traffic counters unavailable: {}", e);
+        }
+
+        *self.canary.write() = Some(evaluation);
+        Ok(())
+    }
+
+    /// Get the comparison report of the running canary evaluation
+    pub fn canary_report(&self) -> Option<CanaryReport> {
+        let counters = self.read_canary_counters();
+        let mut canary = self.canary.write();
+        let evaluation = canary.as_mut()?;
+        if let Some(counters) = counters {
+            evaluation.record_counters(&counters);
+        }
+        Some(evaluation.report(chrono::Utc::now()))
+    }
+
+    /// Enforce the candidate configuration under evaluation
+    pub async fn promote_canary(&self) -> Result<CanaryReport> {
+        let (evaluation, report) = self.finish_canary()?;
+
+        info!(
+            "Promoting canary configuration version {}: would_drop={} packets, would_pass={} packets",
+            report.candidate_version, report.would_drop.packets, report.would_pass.packets
+        );
+
+        self.apply_config(evaluation.candidate()).await?;
+        Ok(report)
+    }
+
+    /// Stop the running canary evaluation without enforcing it
+    pub fn abort_canary(&self) -> Result<CanaryReport> {
+        let (_, report) = self.finish_canary()?;
+
+        info!(
+            "Canary evaluation of version {} ended without promotion",
+            report.candidate_version
+        );
+
+        Ok(report)
+    }
+
+    /// Conclude the canary evaluation once its period has elapsed
+    pub async fn check_canary_expiry(&self) -> Result<()> {
+        let auto_promote = match self.canary.read().as_ref() {
+            Some(evaluation) if evaluation.is_expired(chrono::Utc::now()) => {
+                evaluation.auto_promote()
+            }
+            _ => return Ok(()),
+        };
+
+        if auto_promote {
+            self.promote_canary().await?;
+        } else {
+            self.abort_canary()?;
+        }
+
+        Ok(())
+    }
+
+    /// Take the running canary evaluation and its final report
+    fn finish_canary(&self) -> Result<(CanaryEvaluation, CanaryReport)> {
+        let mut evaluation = self
+            .canary
+            .write()
+            .take()
+            .ok_or_else(|| Error::not_found("Canary evaluation", "current"))?;
+
+        if let Some(counters) = self.read_canary_counters() {
+            evaluation.record_counters(&counters);
+        }
+        self.clear_canary_maps();
+
+        let report = evaluation.report(chrono::Utc::now());
+        Ok((evaluation, report))
+    }
+
+    /// Read canary counters from the eBPF maps
+    fn read_canary_counters(&self) -> Option<Vec<(IpAddr, CanaryEntry)>> {
+        match self.loader.read().read_canary_counters() {
+            Ok(counters) => Some(counters),
+            Err(e) => {
+                debug!("Failed to read canary counters: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Remove all canary entries from the eBPF maps
+    fn clear_canary_maps(&self) {
+        if let Err(e) = self.loader.write().clear_canary_entries() {
+            debug!("Failed to clear canary maps: {}", e);
+        }
+    }
+
     /// Get pending updates that couldn't be applied
     pub fn pending_updates(&self) -> Vec<MapUpdate> {
         self.pending_updates.read().clone()
@@ -563,7 +866,7 @@ impl ConfigSyncManager {
         ConfigSyncState {
             version: self.current_version.read().clone(),
             backends: self.applied_backends.read().clone(),
-            global_settings: *self.global_settings.read(),
+            global_settings: self.global_settings.read().clone(),
             stats: self.stats.read().clone(),
             pending_updates_count: self.pending_updates.read().len(),
         }
@@ -580,6 +883,58 @@ pub struct ConfigSyncState {
     pub pending_updates_count: usize,
 }
 
+/// Load the program version a rollout asks for, unless it is already running
+///
+/// Failures are recorded on the loader and reported with the next heartbeat;
+/// the previous version keeps running.
+fn apply_program_target(loader: &mut EbpfLoader, program_dir: &Path, target: &ProgramTarget) {
+    if loader
+        .program_version(&target.name)
+        .is_some_and(|v| v.digest.eq_ignore_ascii_case(&target.digest))
+    {
+        return;
+    }
+
+    let Some(variant) = loader.program_variant() else {
+        let missing: Vec<String> = loader
+            .capabilities()
+            .missing_baseline()
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        loader.record_load_error(
+            &target.name,
+            format!("Kernel lacks required BPF features: {}", missing.join(", ")),
+        );
+        return;
+    };
+
+    let path = program_dir.join(variant.object_file(&target.name, &target.version));
+    let result = read_signed(&path).and_then(|(data, signature)| {
+        loader.load_version(
+            &target.name,
+            &target.version,
+            &target.digest,
+            &data,
+            signature.as_deref(),
+        )
+    });
+
+    match result {
+        Ok(()) => info!(
+            "Loaded {} version {} for rollout",
+            target.name, target.version
+        ),
+        Err(e) => {
+            error!(
+                "Failed to load {} version {}: {}",
+                target.name, target.version, e
+            );
+            loader.record_load_error(&target.name, e.to_string());
+        }
+    }
+}
+
 /// Calculate a hash of the configuration for integrity checking
 fn calculate_config_hash(config: &FilterConfig) -> u64 {
     use std::collections::hash_map::DefaultHasher;
@@ -603,9 +958,178 @@ fn calculate_config_hash(config: &FilterConfig) -> u64 {
         }
     }
 
+    for program in &config.programs {
+        program.name.hash(&mut hasher);
+        program.digest.hash(&mut hasher);
+    }
+
     hasher.finish()
 }
 
+/// Destinations routed to the tenant of a backend
+///
+/// Each destination address is paired with every port of narrow port
+/// ranges; wide or missing ranges match any port (port 0).
+fn tenant_destinations(backend: &BackendFilter) -> Vec<TenantDestination> {
+    let mut ports = Vec::new();
+    for range in &backend.destination_ports {
+        let end = range.end.max(range.start);
+        if range.start == 0
+            || end > u16::MAX as u32
+            || end - range.start >= MAX_TENANT_PORTS_PER_RANGE
+        {
+            ports = vec![0];
+            break;
+        }
+        ports.extend((range.start..=end).map(|port| port as u16));
+    }
+    if ports.is_empty() {
+        ports.push(0);
+    }
+
+    let mut destinations = Vec::new();
+    for network in &backend.destination_ips {
+        let Some(addr) = network
+            .address
+            .as_ref()
+            .and_then(|a| IpAddr::try_from(a).ok())
+        else {
+            continue;
+        };
+        destinations.extend(ports.iter().map(|&port| TenantDestination { addr, port }));
+    }
+    destinations
+}
+
+/// Honeypot ports of a backend, on every destination address of the backend
+///
+/// Backends without destination addresses get their honeypot ports on any
+/// address. Ports outside the u16 range are ignored.
+fn backend_honeypot(backend: &BackendFilter, tenant_id: u32) -> Option<BackendHoneypot> {
+    let honeypot = backend.protection.as_ref()?.honeypot.as_ref()?;
+
+    let mut ports: Vec<u16> = honeypot
+        .ports
+        .iter()
+        .filter_map(|&port| u16::try_from(port).ok())
+        .filter(|&port| port != 0)
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    if ports.is_empty() {
+        return None;
+    }
+
+    let addrs = backend
+        .destination_ips
+        .iter()
+        .filter_map(|network| network.address.as_ref())
+        .filter_map(|addr| IpAddr::try_from(addr).ok())
+        .collect();
+
+    Some(BackendHoneypot {
+        tenant_id,
+        addrs,
+        settings: HoneypotPorts {
+            ports,
+            auto_block: honeypot.auto_block,
+            block_duration_secs: honeypot.block_duration_seconds,
+        },
+    })
+}
+
+/// Learning mode of a backend, if enabled
+///
+/// Sources are credited to the backend when they complete handshakes with
+/// one of its destination addresses and ports; backends without either
+/// learn from any.
+fn backend_learning(backend: &BackendFilter) -> Option<BackendLearning> {
+    let learning = backend.protection.as_ref()?.learning.as_ref()?;
+    if !learning.enabled || learning.training_seconds == 0 {
+        return None;
+    }
+
+    let addrs = backend
+        .destination_ips
+        .iter()
+        .filter_map(|network| network.address.as_ref())
+        .filter_map(|addr| IpAddr::try_from(addr).ok())
+        .collect();
+    let ports = backend
+        .destination_ports
+        .iter()
+        .filter_map(|range| {
+            let start = u16::try_from(range.start).ok()?;
+            let end = u16::try_from(range.end.max(range.start)).unwrap_or(u16::MAX);
+            Some((start, end))
+        })
+        .collect();
+
+    Some(BackendLearning {
+        addrs,
+        ports,
+        settings: LearningSettings {
+            training_secs: learning.training_seconds,
+            limit_multiplier: learning.limit_multiplier.max(1),
+            min_handshakes: learning.min_handshakes.max(1),
+        },
+    })
+}
+
+/// Per-connection bandwidth limit of a backend, if it sets one
+fn backend_flow_rate(backend: &BackendFilter) -> Option<BackendFlowRate> {
+    let rate = backend.protection.as_ref()?.connection_rate.as_ref()?;
+    if rate.bytes_per_second == 0 {
+        return None;
+    }
+
+    Some(BackendFlowRate {
+        destinations: tenant_destinations(backend),
+        config: FlowRateConfig {
+            bytes_per_sec: rate.bytes_per_second,
+            burst_bytes: rate.burst_bytes,
+        },
+    })
+}
+
+/// Marking policy of a backend, if it enables one
+fn backend_marking(backend: &BackendFilter) -> Option<BackendMarking> {
+    let marking = backend.protection.as_ref()?.marking.as_ref()?;
+    if !marking.enabled {
+        return None;
+    }
+
+    let dscp = match marking.dscp {
+        0 => DSCP_LE,
+        dscp => dscp.min(DSCP_MAX as u32) as u8,
+    };
+    Some(BackendMarking {
+        destinations: tenant_destinations(backend),
+        settings: MarkingSettings {
+            dscp,
+            ecn_ce: marking.ecn_ce,
+            mark_within_limit: marking.mark_all_greylisted,
+        },
+    })
+}
+
+/// Custom block responses of a backend; empty fields keep the defaults
+fn backend_block_responses(backend: &BackendFilter) -> BackendBlockResponses {
+    let config = backend
+        .protection
+        .as_ref()
+        .and_then(|p| p.block_responses.as_ref());
+    let custom = |value: Option<&String>| value.filter(|v| !v.is_empty()).cloned();
+
+    BackendBlockResponses {
+        backend_id: backend.backend_id.clone(),
+        hosts: backend.domains.clone(),
+        destinations: tenant_destinations(backend),
+        http_block_page: custom(config.map(|c| &c.http_block_page)),
+        minecraft_kick_message: custom(config.map(|c| &c.minecraft_kick_message)),
+    }
+}
+
 /// Parse IP address from bytes
 fn parse_ip_from_bytes(bytes: &[u8]) -> Result<IpAddr> {
     match bytes.len() {
@@ -717,6 +1241,8 @@ mod tests {
             backends: vec![],
             global: None,
             generated_at: None,
+            canary: None,
+            programs: vec![],
         };
 
         let config2 = FilterConfig {
@@ -725,6 +1251,8 @@ mod tests {
             backends: vec![],
             global: None,
             generated_at: None,
+            canary: None,
+            programs: vec![],
         };
 
         let config3 = FilterConfig {
@@ -733,6 +1261,8 @@ mod tests {
             backends: vec![],
             global: None,
             generated_at: None,
+            canary: None,
+            programs: vec![],
         };
 
         assert_eq!(
@@ -797,4 +1327,190 @@ mod tests {
         assert_eq!(config.rate_limit_pps, 5000);
         assert_eq!(config.blocked_countries, vec![1, 2]);
     }
+
+    #[test]
+    fn test_tenant_destinations() {
+        use pistonprotection_proto::common::{IpAddress, IpNetwork, PortRange, ip_address};
+
+        let mut backend = BackendFilter {
+            backend_id: "b1".to_string(),
+            organization_id: "org-1".to_string(),
+            destination_ips: vec![IpNetwork {
+                address: Some(IpAddress {
+                    address: Some(ip_address::Address::Ipv4(0x0a00_0001)),
+                }),
+                prefix_length: 32,
+            }],
+            destination_ports: vec![PortRange {
+                start: 25565,
+                end: 25566,
+            }],
+            ..Default::default()
+        };
+
+        let destinations = tenant_destinations(&backend);
+        assert_eq!(destinations.len(), 2);
+        assert_eq!(destinations[0].addr, "10.0.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(destinations[1].port, 25566);
+
+        // Wide ranges match any port
+        backend.destination_ports = vec![PortRange {
+            start: 1000,
+            end: 60000,
+        }];
+        let destinations = tenant_destinations(&backend);
+        assert_eq!(destinations.len(), 1);
+        assert_eq!(destinations[0].port, 0);
+    }
+
+    #[test]
+    fn test_backend_honeypot() {
+        use pistonprotection_proto::worker::{HoneypotConfig, ProtectionConfig};
+
+        let mut backend = BackendFilter {
+            backend_id: "b1".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(backend_honeypot(&backend, 0), None);
+
+        backend.protection = Some(ProtectionConfig {
+            honeypot: Some(HoneypotConfig {
+                ports: vec![2323, 23, 0, 70000, 23],
+                auto_block: true,
+                block_duration_seconds: 300,
+            }),
+            ..Default::default()
+        });
+        let honeypot = backend_honeypot(&backend, 7).unwrap();
+        assert_eq!(honeypot.tenant_id, 7);
+        assert!(honeypot.addrs.is_empty());
+        assert_eq!(honeypot.settings.ports, vec![23, 2323]);
+        assert!(honeypot.settings.auto_block);
+        assert_eq!(honeypot.settings.block_duration_secs, 300);
+    }
+
+    #[test]
+    fn test_backend_learning() {
+        use pistonprotection_proto::common::PortRange;
+        use pistonprotection_proto::worker::{LearningConfig, ProtectionConfig};
+
+        let mut backend = BackendFilter {
+            backend_id: "b1".to_string(),
+            destination_ports: vec![PortRange {
+                start: 25565,
+                end: 25566,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(backend_learning(&backend), None);
+
+        let mut config = LearningConfig {
+            enabled: false,
+            training_seconds: 3600,
+            limit_multiplier: 0,
+            min_handshakes: 0,
+        };
+        backend.protection = Some(ProtectionConfig {
+            learning: Some(config),
+            ..Default::default()
+        });
+        assert_eq!(backend_learning(&backend), None);
+
+        config.enabled = true;
+        backend.protection.as_mut().unwrap().learning = Some(config);
+        let learning = backend_learning(&backend).unwrap();
+        assert!(learning.addrs.is_empty());
+        assert_eq!(learning.ports, vec![(25565, 25566)]);
+        assert_eq!(learning.settings.training_secs, 3600);
+        assert_eq!(learning.settings.limit_multiplier, 1);
+        assert_eq!(learning.settings.min_handshakes, 1);
+    }
+
+    #[test]
+    fn test_backend_flow_rate() {
+        use pistonprotection_proto::common::IpNetwork;
+        use pistonprotection_proto::worker::{ConnectionRateConfig, ProtectionConfig};
+
+        let mut backend = BackendFilter {
+            backend_id: "b1".to_string(),
+            destination_ips: vec![IpNetwork {
+                address: Some("192.0.2.10".parse::<IpAddr>().unwrap().into()),
+                prefix_length: 32,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(backend_flow_rate(&backend), None);
+
+        backend.protection = Some(ProtectionConfig {
+            connection_rate: Some(ConnectionRateConfig {
+                bytes_per_second: 0,
+                burst_bytes: 0,
+            }),
+            ..Default::default()
+        });
+        assert_eq!(backend_flow_rate(&backend), None);
+
+        backend.protection.as_mut().unwrap().connection_rate = Some(ConnectionRateConfig {
+            bytes_per_second: 1_000_000,
+            burst_bytes: 250_000,
+        });
+        let flow_rate = backend_flow_rate(&backend).unwrap();
+        assert_eq!(flow_rate.destinations.len(), 1);
+        assert_eq!(flow_rate.destinations[0].port, 0);
+        assert_eq!(flow_rate.config.bytes_per_sec, 1_000_000);
+        assert_eq!(flow_rate.config.burst_bytes, 250_000);
+    }
+
+    #[test]
+    fn test_backend_marking() {
+        use pistonprotection_proto::worker::{MarkingConfig, ProtectionConfig};
+
+        let mut backend = BackendFilter {
+            backend_id: "b1".to_string(),
+            protection: Some(ProtectionConfig {
+                marking: Some(MarkingConfig {
+                    enabled: false,
+                    dscp: 8,
+                    ecn_ce: true,
+                    mark_all_greylisted: false,
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(backend_marking(&backend), None);
+
+        let marking = backend
+            .protection
+            .as_mut()
+            .unwrap()
+            .marking
+            .as_mut()
+            .unwrap();
+        marking.enabled = true;
+        let settings = backend_marking(&backend).unwrap().settings;
+        assert_eq!(settings.dscp, 8);
+        assert!(settings.ecn_ce);
+        assert!(!settings.mark_within_limit);
+
+        // No DSCP means lower effort, out of range ones are clamped
+        let marking = backend
+            .protection
+            .as_mut()
+            .unwrap()
+            .marking
+            .as_mut()
+            .unwrap();
+        marking.dscp = 0;
+        assert_eq!(backend_marking(&backend).unwrap().settings.dscp, DSCP_LE);
+        let marking = backend
+            .protection
+            .as_mut()
+            .unwrap()
+            .marking
+            .as_mut()
+            .unwrap();
+        marking.dscp = 255;
+        assert_eq!(backend_marking(&backend).unwrap().settings.dscp, DSCP_MAX);
+    }
 }
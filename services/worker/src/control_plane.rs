@@ -5,14 +5,21 @@
 //! metrics reporting, and automatic reconnection.
 
 use crate::config_sync::ConfigSyncManager;
-use crate::ebpf::{interface::NetworkInterface, loader::EbpfLoader};
+use crate::ebpf::{
+    interface::NetworkInterface,
+    loader::EbpfLoader,
+    probe::{BpfFeature, KernelCapabilities},
+    traffic::TrafficMeter,
+};
+use crate::log_levels::LogLevels;
 use parking_lot::RwLock;
 use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::resilience::{ResilienceConfig, ResilienceLayer, Resilient};
 use pistonprotection_proto::worker::{
     BackendMetrics, DeregisterRequest, FilterConfig, GetConfigRequest, HeartbeatRequest,
-    InterfaceMetrics, RegisterRequest, ReportAttackRequest, ReportMetricsRequest,
-    StreamConfigRequest, Worker, WorkerCapabilities, WorkerStatus,
-    worker_service_client::WorkerServiceClient,
+    InterfaceMetrics, LogLevel, ProgramVersion, RegisterRequest, ReportAttackRequest,
+    ReportMetricsRequest, SetLogLevelRequest, StreamConfigRequest, Worker, WorkerCapabilities,
+    WorkerStatus, worker_service_client::WorkerServiceClient,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -21,8 +28,12 @@ use std::time::Duration;
 use tokio::sync::{Mutex, broadcast, watch};
 use tokio::time::{interval, sleep, timeout};
 use tonic::transport::{Channel, Endpoint};
+use tower::Layer;
 use tracing::{debug, error, info, warn};
 
+/// gRPC client of the control plane, behind the resilience layer
+type ControlPlaneGrpc = WorkerServiceClient<Resilient<Channel>>;
+
 /// Control plane connection state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectionState {
@@ -73,8 +84,12 @@ pub struct ControlPlaneConfig {
     pub enable_config_stream: bool,
     /// Worker node name (for identification)
     pub node_name: String,
+    /// Deployment region reported at registration
+    pub region: String,
     /// Worker labels
     pub labels: HashMap<String, String>,
+    /// Timeouts, retries and circuit breaking of control plane calls
+    pub resilience: ResilienceConfig,
 }
 
 impl Default for ControlPlaneConfig {
@@ -92,7 +107,9 @@ impl Default for ControlPlaneConfig {
             node_name: hostname::get()
                 .map(|h| h.to_string_lossy().to_string())
                 .unwrap_or_else(|_| "unknown".to_string()),
+            region: String::new(),
             labels: HashMap::new(),
+            resilience: ResilienceConfig::default(),
         }
     }
 }
@@ -110,6 +127,10 @@ impl ControlPlaneConfig {
             config.node_name = name;
         }
 
+        if let Ok(region) = std::env::var("PISTON_WORKER_REGION") {
+            config.region = region;
+        }
+
         if let Ok(timeout_secs) = std::env::var("PISTON_CONNECT_TIMEOUT") {
             if let Ok(secs) = timeout_secs.parse::<u64>() {
                 config.connect_timeout = Duration::from_secs(secs);
@@ -139,6 +160,8 @@ impl ControlPlaneConfig {
             }
         }
 
+        config.resilience = ResilienceConfig::from_env();
+
         config
     }
 }
@@ -173,6 +196,7 @@ pub struct BackendMetricsSnapshot {
     pub bytes_in: u64,
     pub bytes_out: u64,
     pub packets_dropped: u64,
+    pub bytes_dropped: u64,
     pub packets_challenged: u64,
     pub drops_by_reason: HashMap<String, u64>,
 }
@@ -219,8 +243,13 @@ pub struct ControlPlaneClient {
     loader: Arc<RwLock<EbpfLoader>>,
     /// Configuration sync manager
     config_sync: Arc<ConfigSyncManager>,
+    /// Runtime log directives
+    log_levels: Arc<LogLevels>,
     /// gRPC client (wrapped in mutex for exclusive access during reconnection)
-    client: Arc<Mutex<Option<WorkerServiceClient<Channel>>>>,
+    client: Arc<Mutex<Option<ControlPlaneGrpc>>>,
+    /// Breaker and retry budget of control plane calls, kept across
+    /// reconnections
+    resilience: ResilienceLayer,
     /// Shutdown signal sender
     shutdown_tx: broadcast::Sender<()>,
     /// State change notification
@@ -245,6 +274,7 @@ impl ControlPlaneClient {
     ) -> Self {
         let (shutdown_tx, _) = broadcast::channel(1);
         let (state_tx, state_rx) = watch::channel(ConnectionState::Disconnected);
+        let resilience = ResilienceLayer::new("control-plane", config.resilience.clone());
 
         Self {
             config,
@@ -254,7 +284,9 @@ impl ControlPlaneClient {
             interfaces: Arc::new(interfaces),
             loader,
             config_sync,
+            log_levels: Arc::new(LogLevels::new()),
             client: Arc::new(Mutex::new(None)),
+            resilience,
             shutdown_tx,
             state_tx,
             state_rx,
@@ -279,6 +311,11 @@ impl ControlPlaneClient {
         self.config_version.load(Ordering::SeqCst)
     }
 
+    /// Runtime log directives of the worker
+    pub fn log_levels(&self) -> &LogLevels {
+        &self.log_levels
+    }
+
     /// Subscribe to state changes
     pub fn subscribe_state_changes(&self) -> watch::Receiver<ConnectionState> {
         self.state_rx.clone()
@@ -354,22 +391,12 @@ impl ControlPlaneClient {
     async fn connect_and_register(&self) -> Result<()> {
         self.set_state(ConnectionState::Connecting);
 
-        // Create channel with configuration
         let endpoint = Endpoint::from_shared(self.config.address.clone())
             .map_err(|e| Error::Internal(format!("Invalid control plane address: {}", e)))?
-            .connect_timeout(self.config.connect_timeout)
-            .timeout(self.config.request_timeout)
             .tcp_keepalive(Some(Duration::from_secs(30)))
             .http2_keep_alive_interval(Duration::from_secs(30))
             .keep_alive_while_idle(true);
-
-        // Connect with timeout
-        let channel = timeout(self.config.connect_timeout, endpoint.connect())
-            .await
-            .map_err(|_| Error::Internal("Connection timeout".to_string()))?
-            .map_err(|e| Error::Internal(format!("Failed to connect: {}", e)))?;
-
-        let mut client = WorkerServiceClient::new(channel);
+        let mut client = connect_client(&self.config, endpoint, &self.resilience).await?;
 
         // Build worker info
         let worker_info = self.build_worker_info();
@@ -405,6 +432,7 @@ impl ControlPlaneClient {
             );
             self.apply_configuration(&initial_config).await?;
         }
+        self.log_levels.apply_remote(response.log_level.as_ref());
 
         // Store client
         *self.client.lock().await = Some(client);
@@ -419,62 +447,12 @@ impl ControlPlaneClient {
 
     /// Build worker information for registration
     fn build_worker_info(&self) -> Worker {
-        let mut sys = sysinfo::System::new_all();
-        sys.refresh_all();
-
-        // Parse kernel version
-        let kernel_version = sysinfo::System::kernel_version().unwrap_or_default();
-        let (kernel_major, kernel_minor) = parse_kernel_version(&kernel_version);
-
-        Worker {
-            id: String::new(), // Assigned by control plane
-            node_name: self.config.node_name.clone(),
-            hostname: hostname::get()
-                .map(|h| h.to_string_lossy().to_string())
-                .unwrap_or_else(|_| "unknown".to_string()),
-            interfaces: self
-                .interfaces
-                .iter()
-                .map(|iface| pistonprotection_proto::worker::NetworkInterface {
-                    name: iface.name.clone(),
-                    ip_address: iface.ip_address.map(|ip| ip.into()),
-                    mac_address: iface
-                        .mac_address
-                        .map(|mac| {
-                            mac.iter()
-                                .map(|b| format!("{:02x}", b))
-                                .collect::<Vec<_>>()
-                                .join(":")
-                        })
-                        .unwrap_or_default(),
-                    xdp_status: None,
-                    rx_bytes: 0,
-                    tx_bytes: 0,
-                    rx_packets: 0,
-                    tx_packets: 0,
-                    rx_dropped: 0,
-                    tx_dropped: 0,
-                })
-                .collect(),
-            capabilities: Some(WorkerCapabilities {
-                xdp_native: check_xdp_support(XdpSupportLevel::Native),
-                xdp_driver: check_xdp_support(XdpSupportLevel::Driver),
-                xdp_offload: check_xdp_support(XdpSupportLevel::Offload),
-                bpf_helpers: get_available_bpf_helpers(),
-                max_bpf_stack_size: 512,
-                max_map_entries: 1_000_000,
-                cpu_cores: sys.cpus().len() as u32,
-                memory_bytes: sys.total_memory(),
-                network_drivers: get_network_drivers(&self.interfaces),
-                kernel_version,
-                kernel_major,
-                kernel_minor,
-            }),
-            status: WorkerStatus::Registering.into(),
-            labels: self.config.labels.clone(),
-            registered_at: None,
-            last_heartbeat: None,
-        }
+        build_worker_info(
+            &self.config,
+            &self.interfaces,
+            &self.loader,
+            self.worker_id.read().clone(),
+        )
     }
 
     /// Spawn heartbeat task
@@ -482,6 +460,7 @@ impl ControlPlaneClient {
         let client = Arc::clone(&self.client);
         let worker_id = Arc::clone(&self.worker_id);
         let state = Arc::clone(&self.state);
+        let state_tx = self.state_tx.clone();
         let config_version = Arc::clone(&self.config_version);
         let last_heartbeat = Arc::clone(&self.last_heartbeat);
         let loader = Arc::clone(&self.loader);
@@ -489,6 +468,7 @@ impl ControlPlaneClient {
         let heartbeat_interval = self.config.heartbeat_interval;
         let request_timeout = self.config.request_timeout;
         let config_sync = Arc::clone(&self.config_sync);
+        let log_levels = Arc::clone(&self.log_levels);
         let mut shutdown_rx = self.shutdown_tx.subscribe();
 
         tokio::spawn(async move {
@@ -539,6 +519,7 @@ impl ControlPlaneClient {
                                     .collect(),
                             }),
                             current_config_version: current_version,
+                            programs: program_versions(&loader.read()),
                         };
 
                         let mut client_guard = client.lock().await;
@@ -554,6 +535,16 @@ impl ControlPlaneClient {
                                         .as_secs();
                                     last_heartbeat.store(now, Ordering::SeqCst);
 
+                                    // The control plane forgot us (restart or expiry); register again
+                                    if resp.reregister_required {
+                                        warn!("Control plane does not know this worker, re-registering");
+                                        *state.write() = ConnectionState::Reconnecting;
+                                        let _ = state_tx.send(ConnectionState::Reconnecting);
+                                        continue;
+                                    }
+
+                                    log_levels.apply_remote(resp.log_level.as_ref());
+
                                     // Check for config update
                                     if resp.config_update_available {
                                         let current_version = config_version.load(Ordering::SeqCst);
@@ -593,6 +584,7 @@ impl ControlPlaneClient {
 
         tokio::spawn(async move {
             let mut interval_timer = interval(metrics_interval);
+            let mut meter = TrafficMeter::new();
 
             loop {
                 tokio::select! {
@@ -612,7 +604,7 @@ impl ControlPlaneClient {
                         };
 
                         // Collect backend metrics
-                        let backend_metrics = collect_backend_metrics(&loader);
+                        let backend_metrics = collect_backend_metrics(&loader, &mut meter);
 
                         if backend_metrics.is_empty() {
                             continue;
@@ -631,6 +623,7 @@ impl ControlPlaneClient {
                                     packets_dropped: m.packets_dropped,
                                     packets_challenged: m.packets_challenged,
                                     drops_by_reason: m.drops_by_reason,
+                                    bytes_dropped: m.bytes_dropped,
                                 })
                                 .collect(),
                         };
@@ -639,6 +632,7 @@ impl ControlPlaneClient {
                         if let Some(ref mut grpc_client) = *client_guard {
                             match timeout(request_timeout, grpc_client.report_metrics(request)).await {
                                 Ok(Ok(_)) => {
+                                    meter.commit();
                                     debug!("Metrics reported successfully");
                                 }
                                 Ok(Err(e)) => {
@@ -754,6 +748,9 @@ impl ControlPlaneClient {
         let this_interfaces = Arc::clone(&self.interfaces);
         let this_config_version = Arc::clone(&self.config_version);
         let this_config_sync = Arc::clone(&self.config_sync);
+        let this_log_levels = Arc::clone(&self.log_levels);
+        let this_loader = Arc::clone(&self.loader);
+        let resilience = self.resilience.clone();
 
         tokio::spawn(async move {
             let mut check_interval = interval(Duration::from_secs(5));
@@ -828,10 +825,13 @@ impl ControlPlaneClient {
                         match reconnect(
                             &this_config,
                             &this_interfaces,
+                            &this_loader,
                             &client,
                             &worker_id,
                             &this_config_version,
                             &this_config_sync,
+                            &this_log_levels,
+                            &resilience,
                         )
                         .await
                         {
@@ -976,6 +976,34 @@ impl ControlPlaneClient {
         Err(Error::Internal("No client connection".to_string()))
     }
 
+    /// Store log directives for this node on the control plane, for
+    /// `ttl_seconds` (0 = until cleared)
+    pub async fn set_log_level(
+        &self,
+        directives: Vec<String>,
+        ttl_seconds: u32,
+    ) -> Result<LogLevel> {
+        let request = SetLogLevelRequest {
+            node_name: self.config.node_name.clone(),
+            directives,
+            ttl_seconds,
+        };
+
+        let mut client_guard = self.client.lock().await;
+        let grpc_client = client_guard
+            .as_mut()
+            .ok_or_else(|| Error::Internal("No client connection".to_string()))?;
+        let response = timeout(
+            self.config.request_timeout,
+            grpc_client.set_log_level(request),
+        )
+        .await
+        .map_err(|_| Error::Internal("Set log level timeout".to_string()))?
+        .map_err(|e| Error::Internal(format!("Failed to set log level: {}", e)))?;
+
+        Ok(response.into_inner())
+    }
+
     /// Gracefully shutdown the control plane client
     pub async fn shutdown(&self) -> Result<()> {
         info!("Shutting down control plane client");
@@ -1001,37 +1029,95 @@ impl ControlPlaneClient {
     }
 }
 
+/// Connect a channel to `endpoint` and wrap it in the resilience layer
+async fn connect_client(
+    config: &ControlPlaneConfig,
+    endpoint: Endpoint,
+    resilience: &ResilienceLayer,
+) -> Result<ControlPlaneGrpc> {
+    let endpoint = endpoint
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.request_timeout);
+
+    // Connect with timeout
+    let channel = timeout(config.connect_timeout, endpoint.connect())
+        .await
+        .map_err(|_| Error::Internal("Connection timeout".to_string()))?
+        .map_err(|e| Error::Internal(format!("Failed to connect: {}", e)))?;
+
+    Ok(WorkerServiceClient::new(resilience.layer(channel)))
+}
+
 /// Reconnect to control plane
+#[allow(clippy::too_many_arguments)]
 async fn reconnect(
     config: &ControlPlaneConfig,
     interfaces: &[NetworkInterface],
-    client: &Arc<Mutex<Option<WorkerServiceClient<Channel>>>>,
+    loader: &Arc<RwLock<EbpfLoader>>,
+    client: &Arc<Mutex<Option<ControlPlaneGrpc>>>,
     worker_id: &Arc<RwLock<Option<String>>>,
     config_version: &Arc<AtomicU32>,
     config_sync: &Arc<ConfigSyncManager>,
+    log_levels: &LogLevels,
+    resilience: &ResilienceLayer,
 ) -> Result<()> {
     // Create new channel
     let endpoint = Endpoint::from_shared(config.address.clone())
-        .map_err(|e| Error::Internal(format!("Invalid control plane address: {}", e)))?
-        .connect_timeout(config.connect_timeout)
-        .timeout(config.request_timeout);
+        .map_err(|e| Error::Internal(format!("Invalid control plane address: {}", e)))?;
+    let mut new_client = connect_client(config, endpoint, resilience).await?;
 
-    let channel = timeout(config.connect_timeout, endpoint.connect())
-        .await
-        .map_err(|_| Error::Internal("Connection timeout".to_string()))?
-        .map_err(|e| Error::Internal(format!("Failed to connect: {}", e)))?;
+    // Re-register (in case we were removed from control plane)
+    let worker_info = build_worker_info(config, interfaces, loader, worker_id.read().clone());
 
-    let mut new_client = WorkerServiceClient::new(channel);
+    let register_request = RegisterRequest {
+        worker: Some(worker_info),
+    };
 
-    // Re-register (in case we were removed from control plane)
+    let response = timeout(
+        config.request_timeout,
+        new_client.register(register_request),
+    )
+    .await
+    .map_err(|_| Error::Internal("Registration timeout".to_string()))?
+    .map_err(|e| Error::Internal(format!("Registration failed: {}", e)))?;
+
+    let response = response.into_inner();
+
+    // Update worker ID (might be different on re-registration)
+    *worker_id.write() = Some(response.worker_id.clone());
+
+    // Apply configuration if provided
+    if let Some(initial_config) = response.initial_config {
+        config_sync.apply_config(&initial_config).await?;
+        config_version.store(initial_config.version, Ordering::SeqCst);
+    }
+    log_levels.apply_remote(response.log_level.as_ref());
+
+    // Store new client
+    *client.lock().await = Some(new_client);
+
+    Ok(())
+}
+
+/// Build worker information for (re-)registration
+///
+/// A previously assigned `worker_id` is sent along so the control plane keeps
+/// the worker's identity and backend placement across reconnects.
+fn build_worker_info(
+    config: &ControlPlaneConfig,
+    interfaces: &[NetworkInterface],
+    loader: &Arc<RwLock<EbpfLoader>>,
+    worker_id: Option<String>,
+) -> Worker {
     let mut sys = sysinfo::System::new_all();
     sys.refresh_all();
 
-    let kernel_version = sysinfo::System::kernel_version().unwrap_or_default();
-    let (kernel_major, kernel_minor) = parse_kernel_version(&kernel_version);
+    // Kernel capabilities probed at startup
+    let loader = loader.read();
+    let kernel = loader.capabilities();
 
-    let worker_info = Worker {
-        id: worker_id.read().clone().unwrap_or_default(),
+    Worker {
+        id: worker_id.unwrap_or_default(), // Assigned by control plane on first registration
         node_name: config.node_name.clone(),
         hostname: hostname::get()
             .map(|h| h.to_string_lossy().to_string())
@@ -1063,49 +1149,65 @@ async fn reconnect(
             xdp_native: check_xdp_support(XdpSupportLevel::Native),
             xdp_driver: check_xdp_support(XdpSupportLevel::Driver),
             xdp_offload: check_xdp_support(XdpSupportLevel::Offload),
-            bpf_helpers: get_available_bpf_helpers(),
+            bpf_helpers: get_available_bpf_helpers(kernel),
             max_bpf_stack_size: 512,
             max_map_entries: 1_000_000,
             cpu_cores: sys.cpus().len() as u32,
             memory_bytes: sys.total_memory(),
             network_drivers: get_network_drivers(interfaces),
-            kernel_version,
-            kernel_major,
-            kernel_minor,
+            kernel_version: kernel.release.clone(),
+            kernel_major: kernel.version.major,
+            kernel_minor: kernel.version.minor,
+            bpf_features: kernel.feature_names(),
+            btf: kernel.supports(BpfFeature::Btf),
+            program_variant: loader
+                .program_variant()
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
         }),
         status: WorkerStatus::Registering.into(),
         labels: config.labels.clone(),
         registered_at: None,
         last_heartbeat: None,
-    };
-
-    let register_request = RegisterRequest {
-        worker: Some(worker_info),
-    };
-
-    let response = timeout(
-        config.request_timeout,
-        new_client.register(register_request),
-    )
-    .await
-    .map_err(|_| Error::Internal("Registration timeout".to_string()))?
-    .map_err(|e| Error::Internal(format!("Registration failed: {}", e)))?;
-
-    let response = response.into_inner();
-
-    // Update worker ID (might be different on re-registration)
-    *worker_id.write() = Some(response.worker_id.clone());
-
-    // Apply configuration if provided
-    if let Some(initial_config) = response.initial_config {
-        config_sync.apply_config(&initial_config).await?;
-        config_version.store(initial_config.version, Ordering::SeqCst);
+        region: config.region.clone(),
+        programs: program_versions(&loader),
+        assigned_backends: vec![],
     }
+}
 
-    // Store new client
-    *client.lock().await = Some(new_client);
-
-    Ok(())
+/// Versions of the loaded eBPF programs, sorted by name
+///
+/// Programs whose last version change or load failed are included with the
+/// error and its diagnostic, even if no version of them is loaded.
+fn program_versions(loader: &EbpfLoader) -> Vec<ProgramVersion> {
+    let mut names = loader.loaded_programs();
+    names.extend(loader.failed_programs());
+    names.extend(loader.load_diagnostics().into_iter().map(|d| d.program));
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let (version, digest, signed_by) = match loader.program_version(&name) {
+                Some(info) => (
+                    info.version.clone(),
+                    info.digest.clone(),
+                    info.signed_by.clone().unwrap_or_default(),
+                ),
+                None => (String::new(), String::new(), String::new()),
+            };
+            ProgramVersion {
+                generation: loader.program_generation(&name),
+                version,
+                digest,
+                signed_by,
+                load_error: loader.load_error(&name).unwrap_or_default().to_string(),
+                diagnostic: loader.load_diagnostic(&name).map(|d| d.to_proto()),
+                name,
+            }
+        })
+        .collect()
 }
 
 /// Calculate exponential backoff delay
@@ -1133,14 +1235,6 @@ fn rand_u64() -> u64 {
         .wrapping_add(1)
 }
 
-/// Parse kernel version string (e.g., "5.15.0" -> (5, 15))
-fn parse_kernel_version(version: &str) -> (u32, u32) {
-    let parts: Vec<&str> = version.split('.').collect();
-    let major = parts.first().and_then(|s| s.parse().ok()).unwrap_or(0);
-    let minor = parts.get(1).and_then(|s| s.parse().ok()).unwrap_or(0);
-    (major, minor)
-}
-
 /// XDP support level
 enum XdpSupportLevel {
     Native,
@@ -1155,19 +1249,31 @@ fn check_xdp_support(_level: XdpSupportLevel) -> bool {
     true
 }
 
-/// Get available BPF helpers
-fn get_available_bpf_helpers() -> Vec<String> {
-    // In a real implementation, this would probe the kernel
-    vec![
-        "bpf_map_lookup_elem".to_string(),
-        "bpf_map_update_elem".to_string(),
-        "bpf_map_delete_elem".to_string(),
-        "bpf_ktime_get_ns".to_string(),
-        "bpf_get_prandom_u32".to_string(),
-        "bpf_xdp_adjust_head".to_string(),
-        "bpf_xdp_adjust_tail".to_string(),
-        "bpf_fib_lookup".to_string(),
-    ]
+/// Get the BPF helpers the programs use that the kernel provides
+fn get_available_bpf_helpers(kernel: &KernelCapabilities) -> Vec<String> {
+    let mut helpers = vec![
+        "bpf_map_lookup_elem",
+        "bpf_map_update_elem",
+        "bpf_map_delete_elem",
+        "bpf_ktime_get_ns",
+        "bpf_get_prandom_u32",
+        "bpf_xdp_adjust_head",
+    ];
+
+    let gated = [
+        (BpfFeature::XdpAdjustTail, "bpf_xdp_adjust_tail"),
+        (BpfFeature::FibLookup, "bpf_fib_lookup"),
+        (BpfFeature::RingBuf, "bpf_ringbuf_output"),
+        (BpfFeature::BpfLoop, "bpf_loop"),
+    ];
+    helpers.extend(
+        gated
+            .into_iter()
+            .filter(|(feature, _)| kernel.supports(*feature))
+            .map(|(_, helper)| helper),
+    );
+
+    helpers.into_iter().map(String::from).collect()
 }
 
 /// Get network drivers for interfaces
@@ -1217,14 +1323,43 @@ fn collect_worker_metrics(
 }
 
 /// Collect backend-specific metrics
-fn collect_backend_metrics(loader: &Arc<RwLock<EbpfLoader>>) -> Vec<BackendMetricsSnapshot> {
+///
+/// Traffic comes from the xdp_filter destination counters, as the traffic
+/// since the last report committed to `meter`.
+fn collect_backend_metrics(
+    loader: &Arc<RwLock<EbpfLoader>>,
+    meter: &mut TrafficMeter,
+) -> Vec<BackendMetricsSnapshot> {
     let loader_guard = loader.read();
-    let maps = loader_guard.maps();
-    let _map_manager = maps.read();
-
-    // In a real implementation, read from eBPF maps
-    // For now, return empty
-    vec![]
+    let counters = match loader_guard.read_dst_traffic() {
+        Ok(counters) => counters,
+        Err(e) => {
+            debug!("Destination traffic unavailable: {}", e);
+            return vec![];
+        }
+    };
+    let owners: HashMap<_, _> = loader_guard
+        .maps()
+        .read()
+        .traffic_entries()
+        .into_iter()
+        .collect();
+    drop(loader_guard);
+
+    let mut metrics: Vec<BackendMetricsSnapshot> = meter
+        .observe(&counters, &owners)
+        .into_iter()
+        .map(|(backend_id, traffic)| BackendMetricsSnapshot {
+            backend_id,
+            packets_in: traffic.packets,
+            bytes_in: traffic.bytes,
+            packets_dropped: traffic.dropped_packets,
+            bytes_dropped: traffic.dropped_bytes,
+            ..Default::default()
+        })
+        .collect();
+    metrics.sort_by(|a, b| a.backend_id.cmp(&b.backend_id));
+    metrics
 }
 
 #[cfg(test)]
@@ -1232,11 +1367,26 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_parse_kernel_version() {
-        assert_eq!(parse_kernel_version("5.15.0-generic"), (5, 15));
-        assert_eq!(parse_kernel_version("6.1.21"), (6, 1));
-        assert_eq!(parse_kernel_version("4.19"), (4, 19));
-        assert_eq!(parse_kernel_version("invalid"), (0, 0));
+    fn test_kernel_version_in_capabilities() {
+        let major_minor = |release| {
+            let version = KernelCapabilities::from_release(release, false).version;
+            (version.major, version.minor)
+        };
+        assert_eq!(major_minor("5.15.0-generic"), (5, 15));
+        assert_eq!(major_minor("6.1.21"), (6, 1));
+        assert_eq!(major_minor("4.19"), (4, 19));
+        assert_eq!(major_minor("invalid"), (0, 0));
+    }
+
+    #[test]
+    fn test_bpf_helpers_follow_kernel() {
+        let old = get_available_bpf_helpers(&KernelCapabilities::from_release("4.19.0", false));
+        assert!(old.contains(&"bpf_fib_lookup".to_string()));
+        assert!(!old.contains(&"bpf_ringbuf_output".to_string()));
+
+        let new = get_available_bpf_helpers(&KernelCapabilities::from_release("6.1.0", true));
+        assert!(new.contains(&"bpf_ringbuf_output".to_string()));
+        assert!(new.contains(&"bpf_loop".to_string()));
     }
 
     #[test]
@@ -1259,6 +1409,23 @@ mod tests {
         assert_eq!(config.heartbeat_interval, Duration::from_secs(10));
     }
 
+    #[test]
+    fn test_build_worker_info_keeps_identity() {
+        let config = ControlPlaneConfig {
+            region: "eu-west".to_string(),
+            ..Default::default()
+        };
+        let loader = Arc::new(RwLock::new(EbpfLoader::new().unwrap()));
+
+        let first = build_worker_info(&config, &[], &loader, None);
+        assert!(first.id.is_empty());
+        assert_eq!(first.region, "eu-west");
+        assert!(first.programs.is_empty());
+
+        let again = build_worker_info(&config, &[], &loader, Some("worker-1".to_string()));
+        assert_eq!(again.id, "worker-1");
+    }
+
     #[test]
     fn test_connection_state_display() {
         assert_eq!(format!("{}", ConnectionState::Connected), "connected");
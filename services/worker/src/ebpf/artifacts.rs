@@ -0,0 +1,190 @@
+//! Artifact verification
+//!
+//! eBPF object files and the bootstrap config are checked against their
+//! ed25519 detached signatures (see `pistonprotection_common::signing`)
+//! before the worker uses them, so whoever controls the control channel or
+//! the program directory can only pick between artifacts that were signed,
+//! not push their own. Verification is enforced once signing keys are
+//! trusted (`PISTON_ARTIFACT_KEYS`): unsigned artifacts and bad signatures
+//! are then refused. Without keys artifacts are used unchecked, which the
+//! status report shows.
+
+use chrono::{DateTime, Utc};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::signing::TrustedKeys;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Trusted artifact signing keys (comma separated, base64)
+pub const ARTIFACT_KEYS_ENV: &str = "PISTON_ARTIFACT_KEYS";
+
+/// Outcome of the last check of an artifact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verification {
+    /// Signature matches a trusted key
+    Verified,
+    /// Used without a check, no keys are trusted
+    Unchecked,
+    /// Unsigned or badly signed, refused
+    Rejected,
+}
+
+/// Last check of an artifact
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactCheck {
+    /// Program name, or `bootstrap` for the bootstrap config
+    pub artifact: String,
+    /// SHA-256 of the checked content (hex)
+    pub digest: String,
+    pub verification: Verification,
+    /// Fingerprint of the key that signed the artifact
+    pub signed_by: Option<String>,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Verification settings and the last checks, for status reports
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactStatus {
+    /// Unsigned artifacts are refused
+    pub enforced: bool,
+    /// Fingerprints of the trusted keys
+    pub trusted_keys: Vec<String>,
+    pub artifacts: Vec<ArtifactCheck>,
+}
+
+/// Checks artifacts against the trusted keys
+#[derive(Debug, Default)]
+pub struct ArtifactVerifier {
+    keys: TrustedKeys,
+    checks: BTreeMap<String, ArtifactCheck>,
+}
+
+impl ArtifactVerifier {
+    pub fn new(keys: TrustedKeys) -> Self {
+        Self {
+            keys,
+            checks: BTreeMap::new(),
+        }
+    }
+
+    /// Verifier trusting the keys in `PISTON_ARTIFACT_KEYS`
+    pub fn from_env() -> Result<Self> {
+        TrustedKeys::from_env(ARTIFACT_KEYS_ENV).map(Self::new)
+    }
+
+    /// Unsigned artifacts are refused
+    pub fn enforced(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Check `data` against its detached `signature` (base64)
+    ///
+    /// Returns the fingerprint of the signing key, or `None` when no keys are
+    /// trusted and the artifact is used unchecked. The outcome is kept for
+    /// [`ArtifactVerifier::status`].
+    pub fn verify(
+        &mut self,
+        artifact: &str,
+        data: &[u8],
+        signature: Option<&str>,
+    ) -> Result<Option<String>> {
+        let result = if !self.enforced() {
+            Ok(None)
+        } else {
+            match signature {
+                Some(signature) => self.keys.verify(data, signature).map(Some),
+                None => Err(Error::Unauthorized(format!(
+                    "{} is not signed and artifact signatures are enforced",
+                    artifact
+                ))),
+            }
+        };
+
+        let (verification, signed_by, error) = match &result {
+            Ok(Some(key_id)) => (Verification::Verified, Some(key_id.clone()), None),
+            Ok(None) => (Verification::Unchecked, None, None),
+            Err(e) => (Verification::Rejected, None, Some(e.to_string())),
+        };
+        self.checks.insert(
+            artifact.to_string(),
+            ArtifactCheck {
+                artifact: artifact.to_string(),
+                digest: super::loader::object_digest(data),
+                verification,
+                signed_by,
+                error,
+                checked_at: Utc::now(),
+            },
+        );
+        result
+    }
+
+    /// Last check of an artifact
+    pub fn check(&self, artifact: &str) -> Option<&ArtifactCheck> {
+        self.checks.get(artifact)
+    }
+
+    pub fn status(&self) -> ArtifactStatus {
+        ArtifactStatus {
+            enforced: self.enforced(),
+            trusted_keys: self.keys.ids(),
+            artifacts: self.checks.values().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pistonprotection_common::signing::SigningKey;
+
+    #[test]
+    fn test_enforced_verification() {
+        let (signer, _) = SigningKey::generate().unwrap();
+        let (other, _) = SigningKey::generate().unwrap();
+        let keys = TrustedKeys::parse(&signer.public_key()).unwrap();
+        let mut verifier = ArtifactVerifier::new(keys.clone());
+        assert!(verifier.enforced());
+
+        let signature = signer.sign(b"object");
+        let key_id = verifier
+            .verify("xdp_filter", b"object", Some(&signature))
+            .unwrap();
+        assert_eq!(key_id, Some(keys.ids()[0].clone()));
+        assert_eq!(
+            verifier.check("xdp_filter").unwrap().verification,
+            Verification::Verified
+        );
+
+        // Unsigned, tampered and foreign-signed objects are refused
+        assert!(verifier.verify("xdp_filter", b"object", None).is_err());
+        assert!(
+            verifier
+                .verify("xdp_filter", b"tampered", Some(&signature))
+                .is_err()
+        );
+        let foreign = other.sign(b"object");
+        assert!(
+            verifier
+                .verify("xdp_filter", b"object", Some(&foreign))
+                .is_err()
+        );
+        let check = verifier.check("xdp_filter").unwrap();
+        assert_eq!(check.verification, Verification::Rejected);
+        assert!(check.error.is_some());
+    }
+
+    #[test]
+    fn test_unchecked_without_keys() {
+        let mut verifier = ArtifactVerifier::default();
+        assert!(!verifier.enforced());
+        assert_eq!(verifier.verify("bootstrap", b"{}", None).unwrap(), None);
+
+        let status = verifier.status();
+        assert!(!status.enforced);
+        assert_eq!(status.artifacts.len(), 1);
+        assert_eq!(status.artifacts[0].verification, Verification::Unchecked);
+    }
+}
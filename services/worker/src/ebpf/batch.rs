@@ -0,0 +1,853 @@
+//! Batched map operations
+//!
+//! Threat intelligence pushes write hundreds of thousands of prefixes, and
+//! one `bpf()` syscall per entry keeps the loader locked for seconds.
+//! `BPF_MAP_UPDATE_BATCH`, `BPF_MAP_LOOKUP_BATCH` and `BPF_MAP_DELETE_BATCH`
+//! (5.6+) move a whole chunk of entries per syscall. Kernels without the
+//! commands, and map types without batch support (LPM tries before 6.x),
+//! refuse the first chunk; the operation then falls back to one syscall per
+//! entry and remembers not to try batching that map again.
+//!
+//! Operations work on raw key and value bytes laid out back to back, so the
+//! value size of per-CPU maps is not handled here.
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::time::Instant;
+use tracing::{debug, info};
+
+const BPF_MAP_LOOKUP_ELEM: libc::c_int = 1;
+const BPF_MAP_UPDATE_ELEM: libc::c_int = 2;
+const BPF_MAP_DELETE_ELEM: libc::c_int = 3;
+const BPF_MAP_GET_NEXT_KEY: libc::c_int = 4;
+const BPF_MAP_LOOKUP_BATCH: libc::c_int = 24;
+const BPF_MAP_UPDATE_BATCH: libc::c_int = 26;
+const BPF_MAP_DELETE_BATCH: libc::c_int = 27;
+
+/// Kernel-internal "operation not supported", returned by map types without
+/// batch callbacks
+const ENOTSUPP: i32 = 524;
+
+/// Entries per batch syscall (`PISTON_MAP_BATCH_SIZE`)
+pub const DEFAULT_CHUNK_SIZE: usize = 8192;
+
+/// Pushes at least this large log their progress at info level
+const PROGRESS_LOG_ENTRIES: usize = 50_000;
+
+/// Batched operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOp {
+    Update,
+    Lookup,
+    Delete,
+}
+
+impl BatchOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BatchOp::Update => "update",
+            BatchOp::Lookup => "lookup",
+            BatchOp::Delete => "delete",
+        }
+    }
+}
+
+/// Failed batch syscall
+#[derive(Debug)]
+pub struct BatchError {
+    /// Entries the kernel processed before failing
+    pub processed: usize,
+    pub error: io::Error,
+}
+
+impl BatchError {
+    /// The kernel or map type has no batch support
+    pub fn unsupported(&self) -> bool {
+        matches!(
+            self.error.raw_os_error(),
+            Some(libc::EINVAL | libc::EOPNOTSUPP | ENOTSUPP)
+        )
+    }
+}
+
+/// Raw operations on one map, one entry or one chunk per call
+pub trait MapOps {
+    fn key_size(&self) -> usize;
+    fn value_size(&self) -> usize;
+
+    /// Write `keys.len() / key_size` entries, returning how many were written
+    fn update_batch(&self, keys: &[u8], values: &[u8]) -> Result<usize, BatchError>;
+
+    /// Delete `keys.len() / key_size` keys, returning how many were deleted
+    fn delete_batch(&self, keys: &[u8]) -> Result<usize, BatchError>;
+
+    /// Read up to `keys.len() / key_size` entries from the position `token`
+    /// (the start when `None`), storing the next position in `next_token`
+    ///
+    /// Returns the entries read and whether the walk reached the end.
+    fn lookup_batch(
+        &self,
+        token: Option<&[u8]>,
+        next_token: &mut [u8],
+        keys: &mut [u8],
+        values: &mut [u8],
+    ) -> Result<(usize, bool), BatchError>;
+
+    fn update_elem(&self, key: &[u8], value: &[u8]) -> io::Result<()>;
+
+    /// Delete one key, `false` if it was not present
+    fn delete_elem(&self, key: &[u8]) -> io::Result<bool>;
+
+    /// Key following `key` (the first key when `None`), `false` at the end
+    fn next_key(&self, key: Option<&[u8]>, next: &mut [u8]) -> io::Result<bool>;
+
+    /// Value of `key`, `false` if it was not present
+    fn lookup_elem(&self, key: &[u8], value: &mut [u8]) -> io::Result<bool>;
+}
+
+/// `batch` member of `union bpf_attr`
+#[repr(C)]
+#[derive(Default)]
+struct BatchAttr {
+    in_batch: u64,
+    out_batch: u64,
+    keys: u64,
+    values: u64,
+    count: u32,
+    map_fd: u32,
+    elem_flags: u64,
+    flags: u64,
+}
+
+/// `map_elem` member of `union bpf_attr`
+#[repr(C)]
+#[derive(Default)]
+struct MapElemAttr {
+    map_fd: u32,
+    _pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+/// Issue a `bpf()` command on an attr
+///
+/// # Safety
+///
+/// `attr` must be a zeroed `bpf_attr` prefix valid for `cmd`, and every
+/// buffer it points to must be large enough for the command and outlive
+/// the call.
+unsafe fn bpf<T>(cmd: libc::c_int, attr: &mut T) -> io::Result<()> {
+    // SAFETY: upheld by the caller
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *mut T,
+            std::mem::size_of::<T>() as u32,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// A kernel map of plain (not per-CPU) values, by file descriptor
+pub struct BpfMap<'a> {
+    fd: BorrowedFd<'a>,
+    key_size: usize,
+    value_size: usize,
+}
+
+impl<'a> BpfMap<'a> {
+    pub fn new(fd: BorrowedFd<'a>, key_size: usize, value_size: usize) -> Self {
+        Self {
+            fd,
+            key_size,
+            value_size,
+        }
+    }
+
+    fn batch_attr(&self, keys: &[u8], values: &[u8]) -> BatchAttr {
+        BatchAttr {
+            keys: keys.as_ptr() as u64,
+            values: if values.is_empty() {
+                0
+            } else {
+                values.as_ptr() as u64
+            },
+            count: (keys.len() / self.key_size) as u32,
+            map_fd: self.fd.as_raw_fd() as u32,
+            ..Default::default()
+        }
+    }
+
+    fn elem_attr(&self, key: &[u8], value: u64) -> MapElemAttr {
+        MapElemAttr {
+            map_fd: self.fd.as_raw_fd() as u32,
+            key: key.as_ptr() as u64,
+            value,
+            ..Default::default()
+        }
+    }
+}
+
+impl MapOps for BpfMap<'_> {
+    fn key_size(&self) -> usize {
+        self.key_size
+    }
+
+    fn value_size(&self) -> usize {
+        self.value_size
+    }
+
+    fn update_batch(&self, keys: &[u8], values: &[u8]) -> Result<usize, BatchError> {
+        let mut attr = self.batch_attr(keys, values);
+        // SAFETY: keys and values hold `count` entries of the map's key and
+        // value size and outlive the call
+        match unsafe { bpf(BPF_MAP_UPDATE_BATCH, &mut attr) } {
+            Ok(()) => Ok(attr.count as usize),
+            Err(error) => Err(BatchError {
+                processed: attr.count as usize,
+                error,
+            }),
+        }
+    }
+
+    fn delete_batch(&self, keys: &[u8]) -> Result<usize, BatchError> {
+        let mut attr = self.batch_attr(keys, &[]);
+        // SAFETY: keys holds `count` keys of the map's key size and outlives
+        // the call
+        match unsafe { bpf(BPF_MAP_DELETE_BATCH, &mut attr) } {
+            Ok(()) => Ok(attr.count as usize),
+            Err(error) => Err(BatchError {
+                processed: attr.count as usize,
+                error,
+            }),
+        }
+    }
+
+    fn lookup_batch(
+        &self,
+        token: Option<&[u8]>,
+        next_token: &mut [u8],
+        keys: &mut [u8],
+        values: &mut [u8],
+    ) -> Result<(usize, bool), BatchError> {
+        let mut attr = self.batch_attr(keys, values);
+        attr.in_batch = token.map_or(0, |token| token.as_ptr() as u64);
+        attr.out_batch = next_token.as_mut_ptr() as u64;
+        // SAFETY: keys and values have room for `count` entries, and both
+        // tokens are at least as large as a key (the kernel stores either a
+        // key or a u32 bucket index there); all outlive the call
+        match unsafe { bpf(BPF_MAP_LOOKUP_BATCH, &mut attr) } {
+            Ok(()) => Ok((attr.count as usize, false)),
+            // ENOENT marks the end of the walk, possibly with a last chunk
+            Err(error) if error.raw_os_error() == Some(libc::ENOENT) => {
+                Ok((attr.count as usize, true))
+            }
+            Err(error) => Err(BatchError {
+                processed: attr.count as usize,
+                error,
+            }),
+        }
+    }
+
+    fn update_elem(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let mut attr = self.elem_attr(key, value.as_ptr() as u64);
+        // SAFETY: key and value are one entry of the map's sizes
+        unsafe { bpf(BPF_MAP_UPDATE_ELEM, &mut attr) }
+    }
+
+    fn delete_elem(&self, key: &[u8]) -> io::Result<bool> {
+        let mut attr = self.elem_attr(key, 0);
+        // SAFETY: key is one key of the map's key size
+        match unsafe { bpf(BPF_MAP_DELETE_ELEM, &mut attr) } {
+            Ok(()) => Ok(true),
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn next_key(&self, key: Option<&[u8]>, next: &mut [u8]) -> io::Result<bool> {
+        let mut attr = self.elem_attr(key.unwrap_or(&[]), next.as_mut_ptr() as u64);
+        if key.is_none() {
+            attr.key = 0;
+        }
+        // SAFETY: key (if any) and next are the map's key size
+        match unsafe { bpf(BPF_MAP_GET_NEXT_KEY, &mut attr) } {
+            Ok(()) => Ok(true),
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn lookup_elem(&self, key: &[u8], value: &mut [u8]) -> io::Result<bool> {
+        let mut attr = self.elem_attr(key, value.as_mut_ptr() as u64);
+        // SAFETY: key and value are one entry of the map's sizes
+        match unsafe { bpf(BPF_MAP_LOOKUP_ELEM, &mut attr) } {
+            Ok(()) => Ok(true),
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Progress of one batched operation, reported after every chunk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchProgress {
+    pub map: String,
+    pub op: BatchOp,
+    pub done: usize,
+    pub total: usize,
+    /// Whether the chunk went through a batch syscall
+    pub batched: bool,
+}
+
+/// Result of one batched operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BatchOutcome {
+    pub entries: usize,
+    pub syscalls: usize,
+    /// Whether every chunk went through batch syscalls
+    pub batched: bool,
+}
+
+/// Entries read from a map, keys and values laid out back to back
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawEntries {
+    key_size: usize,
+    value_size: usize,
+    keys: Vec<u8>,
+    values: Vec<u8>,
+}
+
+impl RawEntries {
+    fn new(key_size: usize, value_size: usize) -> Self {
+        Self {
+            key_size,
+            value_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len() / self.key_size.max(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.keys
+            .chunks_exact(self.key_size.max(1))
+            .zip(self.values.chunks_exact(self.value_size.max(1)))
+    }
+}
+
+/// Runs map operations in chunks, falling back to per-entry syscalls where
+/// batching is unsupported
+#[derive(Debug)]
+pub struct Batcher {
+    chunk_size: usize,
+    /// Operations the kernel refused to batch, per map
+    unsupported: parking_lot::Mutex<HashSet<(String, BatchOp)>>,
+}
+
+impl Default for Batcher {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_SIZE)
+    }
+}
+
+impl Batcher {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            unsupported: parking_lot::Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Chunk size from `PISTON_MAP_BATCH_SIZE`
+    pub fn from_env() -> Self {
+        let chunk_size = std::env::var("PISTON_MAP_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CHUNK_SIZE);
+        Self::new(chunk_size)
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Whether `op` is still tried as a batch on `map`
+    pub fn batches(&self, map: &str, op: BatchOp) -> bool {
+        !self.unsupported.lock().contains(&(map.to_string(), op))
+    }
+
+    fn mark_unsupported(&self, map: &str, op: BatchOp, error: &io::Error) {
+        debug!(
+            "Batch {} unsupported on map {} ({}), using per-entry syscalls",
+            op.as_str(),
+            map,
+            error
+        );
+        self.unsupported.lock().insert((map.to_string(), op));
+    }
+
+    /// Write entries, `keys` and `values` laid out back to back
+    pub fn update(
+        &self,
+        name: &str,
+        map: &impl MapOps,
+        keys: &[u8],
+        values: &[u8],
+        progress: &mut dyn FnMut(&BatchProgress),
+    ) -> io::Result<BatchOutcome> {
+        let (ks, vs) = (map.key_size(), map.value_size());
+        let total = keys.len() / ks;
+        let mut batched = self.batches(name, BatchOp::Update);
+        let mut outcome = BatchOutcome {
+            entries: total,
+            batched,
+            ..Default::default()
+        };
+
+        let mut done = 0;
+        while done < total {
+            let end = (done + self.chunk_size).min(total);
+            if batched {
+                match map.update_batch(&keys[done * ks..end * ks], &values[done * vs..end * vs]) {
+                    Ok(_) => outcome.syscalls += 1,
+                    Err(e) if e.processed == 0 && e.unsupported() => {
+                        self.mark_unsupported(name, BatchOp::Update, &e.error);
+                        batched = false;
+                        outcome.batched = false;
+                        continue;
+                    }
+                    Err(e) => return Err(e.error),
+                }
+            } else {
+                for i in done..end {
+                    map.update_elem(&keys[i * ks..(i + 1) * ks], &values[i * vs..(i + 1) * vs])?;
+                }
+                outcome.syscalls += end - done;
+            }
+            done = end;
+            progress(&BatchProgress {
+                map: name.to_string(),
+                op: BatchOp::Update,
+                done,
+                total,
+                batched,
+            });
+        }
+
+        Ok(outcome)
+    }
+
+    /// Delete keys laid out back to back; keys already gone are skipped
+    pub fn delete(
+        &self,
+        name: &str,
+        map: &impl MapOps,
+        keys: &[u8],
+        progress: &mut dyn FnMut(&BatchProgress),
+    ) -> io::Result<BatchOutcome> {
+        let ks = map.key_size();
+        let total = keys.len() / ks;
+        let mut batched = self.batches(name, BatchOp::Delete);
+        let mut outcome = BatchOutcome {
+            entries: total,
+            batched,
+            ..Default::default()
+        };
+
+        let mut done = 0;
+        while done < total {
+            let end = (done + self.chunk_size).min(total);
+            if batched {
+                outcome.syscalls += 1;
+                match map.delete_batch(&keys[done * ks..end * ks]) {
+                    Ok(_) => done = end,
+                    // The batch stops at the first missing key; skip it
+                    Err(e) if e.error.raw_os_error() == Some(libc::ENOENT) => {
+                        done += e.processed + 1;
+                        continue;
+                    }
+                    Err(e) if e.processed == 0 && e.unsupported() => {
+                        self.mark_unsupported(name, BatchOp::Delete, &e.error);
+                        outcome.syscalls -= 1;
+                        batched = false;
+                        outcome.batched = false;
+                        continue;
+                    }
+                    Err(e) => return Err(e.error),
+                }
+            } else {
+                for i in done..end {
+                    map.delete_elem(&keys[i * ks..(i + 1) * ks])?;
+                }
+                outcome.syscalls += end - done;
+                done = end;
+            }
+            progress(&BatchProgress {
+                map: name.to_string(),
+                op: BatchOp::Delete,
+                done,
+                total,
+                batched,
+            });
+        }
+
+        Ok(outcome)
+    }
+
+    /// Read every entry of a map
+    pub fn lookup(&self, name: &str, map: &impl MapOps) -> io::Result<RawEntries> {
+        let (ks, vs) = (map.key_size(), map.value_size());
+        let mut entries = RawEntries::new(ks, vs);
+
+        if self.batches(name, BatchOp::Lookup) {
+            // Hash maps use a u32 bucket index as the position, others a key
+            let token_size = ks.max(std::mem::size_of::<u32>());
+            let mut token = vec![0u8; token_size];
+            let mut next_token = vec![0u8; token_size];
+            let mut chunk = self.chunk_size;
+            let mut started = false;
+
+            loop {
+                let mut keys = vec![0u8; chunk * ks];
+                let mut values = vec![0u8; chunk * vs];
+                let result = map.lookup_batch(
+                    started.then_some(token.as_slice()),
+                    &mut next_token,
+                    &mut keys,
+                    &mut values,
+                );
+                match result {
+                    Ok((count, end)) => {
+                        entries.keys.extend_from_slice(&keys[..count * ks]);
+                        entries.values.extend_from_slice(&values[..count * vs]);
+                        if end {
+                            return Ok(entries);
+                        }
+                        std::mem::swap(&mut token, &mut next_token);
+                        started = true;
+                    }
+                    // A hash bucket holds more entries than the chunk
+                    Err(e) if e.error.raw_os_error() == Some(libc::ENOSPC) => chunk *= 2,
+                    Err(e) if !started && e.unsupported() => {
+                        self.mark_unsupported(name, BatchOp::Lookup, &e.error);
+                        break;
+                    }
+                    Err(e) => return Err(e.error),
+                }
+            }
+        }
+
+        let mut key = vec![0u8; ks];
+        let mut next = vec![0u8; ks];
+        let mut value = vec![0u8; vs];
+        let mut started = false;
+        while map.next_key(started.then_some(key.as_slice()), &mut next)? {
+            // Entries deleted during the walk are skipped
+            if map.lookup_elem(&next, &mut value)? {
+                entries.keys.extend_from_slice(&next);
+                entries.values.extend_from_slice(&value);
+            }
+            std::mem::swap(&mut key, &mut next);
+            started = true;
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Progress reporter logging large operations
+///
+/// Chunks are logged at debug level; operations of at least
+/// `PROGRESS_LOG_ENTRIES` entries also log every tenth at info level.
+pub fn log_progress() -> impl FnMut(&BatchProgress) {
+    let started = Instant::now();
+    let mut logged_tenth = 0;
+    move |progress| {
+        debug!(
+            "Map {} {}: {}/{} entries",
+            progress.map,
+            progress.op.as_str(),
+            progress.done,
+            progress.total
+        );
+        if progress.total < PROGRESS_LOG_ENTRIES {
+            return;
+        }
+        let tenth = progress.done * 10 / progress.total;
+        if tenth > logged_tenth {
+            logged_tenth = tenth;
+            info!(
+                "Map {} {}: {}/{} entries in {:.1}s{}",
+                progress.map,
+                progress.op.as_str(),
+                progress.done,
+                progress.total,
+                started.elapsed().as_secs_f64(),
+                if progress.batched { "" } else { " (per-entry)" }
+            );
+        }
+    }
+}
+
+/// Bytes of a plain-data value
+pub fn pod_bytes<T: aya::Pod>(value: &T) -> &[u8] {
+    // SAFETY: `Pod` types are plain data without padding, so every byte of
+    // the value is initialized
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, std::mem::size_of::<T>()) }
+}
+
+/// Plain-data value read from the start of `bytes`
+pub fn pod_from_bytes<T: aya::Pod>(bytes: &[u8]) -> Option<T> {
+    if bytes.len() < std::mem::size_of::<T>() {
+        return None;
+    }
+    // SAFETY: bytes holds at least size_of::<T>() bytes and any bit pattern
+    // is a valid `Pod` value
+    Some(unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::collections::BTreeMap;
+
+    /// In-memory map of u32 keys and values
+    #[derive(Default)]
+    struct FakeMap {
+        entries: RefCell<BTreeMap<u32, u32>>,
+        batch_error: Option<i32>,
+        batch_calls: Cell<usize>,
+        elem_calls: Cell<usize>,
+    }
+
+    impl FakeMap {
+        fn unsupported() -> Self {
+            Self {
+                batch_error: Some(libc::EINVAL),
+                ..Default::default()
+            }
+        }
+
+        fn refuse(&self) -> Result<(), BatchError> {
+            self.batch_calls.set(self.batch_calls.get() + 1);
+            match self.batch_error {
+                Some(errno) => Err(BatchError {
+                    processed: 0,
+                    error: io::Error::from_raw_os_error(errno),
+                }),
+                None => Ok(()),
+            }
+        }
+    }
+
+    fn word(bytes: &[u8]) -> u32 {
+        u32::from_ne_bytes(bytes.try_into().unwrap())
+    }
+
+    fn words(values: impl IntoIterator<Item = u32>) -> Vec<u8> {
+        values.into_iter().flat_map(u32::to_ne_bytes).collect()
+    }
+
+    impl MapOps for FakeMap {
+        fn key_size(&self) -> usize {
+            4
+        }
+
+        fn value_size(&self) -> usize {
+            4
+        }
+
+        fn update_batch(&self, keys: &[u8], values: &[u8]) -> Result<usize, BatchError> {
+            self.refuse()?;
+            let mut entries = self.entries.borrow_mut();
+            for (key, value) in keys.chunks_exact(4).zip(values.chunks_exact(4)) {
+                entries.insert(word(key), word(value));
+            }
+            Ok(keys.len() / 4)
+        }
+
+        fn delete_batch(&self, keys: &[u8]) -> Result<usize, BatchError> {
+            self.refuse()?;
+            let mut entries = self.entries.borrow_mut();
+            for (i, key) in keys.chunks_exact(4).enumerate() {
+                if entries.remove(&word(key)).is_none() {
+                    return Err(BatchError {
+                        processed: i,
+                        error: io::Error::from_raw_os_error(libc::ENOENT),
+                    });
+                }
+            }
+            Ok(keys.len() / 4)
+        }
+
+        fn lookup_batch(
+            &self,
+            token: Option<&[u8]>,
+            next_token: &mut [u8],
+            keys: &mut [u8],
+            values: &mut [u8],
+        ) -> Result<(usize, bool), BatchError> {
+            self.refuse()?;
+            let start = token.map_or(0, word);
+            let entries = self.entries.borrow();
+            let chunk: Vec<_> = entries
+                .iter()
+                .skip(start as usize)
+                .take(keys.len() / 4)
+                .collect();
+            for (i, (key, value)) in chunk.iter().enumerate() {
+                keys[i * 4..(i + 1) * 4].copy_from_slice(&key.to_ne_bytes());
+                values[i * 4..(i + 1) * 4].copy_from_slice(&value.to_ne_bytes());
+            }
+            let next = start as usize + chunk.len();
+            next_token[..4].copy_from_slice(&(next as u32).to_ne_bytes());
+            Ok((chunk.len(), next >= entries.len()))
+        }
+
+        fn update_elem(&self, key: &[u8], value: &[u8]) -> io::Result<()> {
+            self.elem_calls.set(self.elem_calls.get() + 1);
+            self.entries.borrow_mut().insert(word(key), word(value));
+            Ok(())
+        }
+
+        fn delete_elem(&self, key: &[u8]) -> io::Result<bool> {
+            self.elem_calls.set(self.elem_calls.get() + 1);
+            Ok(self.entries.borrow_mut().remove(&word(key)).is_some())
+        }
+
+        fn next_key(&self, key: Option<&[u8]>, next: &mut [u8]) -> io::Result<bool> {
+            self.elem_calls.set(self.elem_calls.get() + 1);
+            let entries = self.entries.borrow();
+            let found = match key {
+                None => entries.keys().next(),
+                Some(key) => entries.range(word(key) + 1..).map(|(k, _)| k).next(),
+            };
+            match found {
+                Some(found) => {
+                    next.copy_from_slice(&found.to_ne_bytes());
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+
+        fn lookup_elem(&self, key: &[u8], value: &mut [u8]) -> io::Result<bool> {
+            self.elem_calls.set(self.elem_calls.get() + 1);
+            match self.entries.borrow().get(&word(key)) {
+                Some(found) => {
+                    value.copy_from_slice(&found.to_ne_bytes());
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_in_chunks() {
+        let map = FakeMap::default();
+        let batcher = Batcher::new(4);
+        let mut reports = Vec::new();
+
+        let outcome = batcher
+            .update(
+                "BLOCKED",
+                &map,
+                &words(0..10),
+                &words((0..10).map(|i| i * 2)),
+                &mut |p| reports.push((p.done, p.total)),
+            )
+            .unwrap();
+
+        assert_eq!(outcome.entries, 10);
+        assert_eq!(outcome.syscalls, 3);
+        assert!(outcome.batched);
+        assert_eq!(reports, vec![(4, 10), (8, 10), (10, 10)]);
+        assert_eq!(map.entries.borrow().get(&9), Some(&18));
+        assert_eq!(map.elem_calls.get(), 0);
+    }
+
+    #[test]
+    fn test_fallback_when_unsupported() {
+        let map = FakeMap::unsupported();
+        let batcher = Batcher::new(4);
+
+        let outcome = batcher
+            .update("TRIE", &map, &words(0..6), &words(0..6), &mut |_| {})
+            .unwrap();
+        assert!(!outcome.batched);
+        assert_eq!(outcome.syscalls, 6);
+        assert_eq!(map.entries.borrow().len(), 6);
+        assert_eq!(map.batch_calls.get(), 1);
+        assert!(!batcher.batches("TRIE", BatchOp::Update));
+
+        // Later operations on the map skip the batch attempt
+        batcher
+            .update("TRIE", &map, &words(6..8), &words(6..8), &mut |_| {})
+            .unwrap();
+        assert_eq!(map.batch_calls.get(), 1);
+
+        let entries = batcher.lookup("TRIE", &map).unwrap();
+        assert_eq!(entries.len(), 8);
+
+        let outcome = batcher
+            .delete("TRIE", &map, &words([1, 3]), &mut |_| {})
+            .unwrap();
+        assert_eq!(outcome.syscalls, 2);
+        assert_eq!(map.entries.borrow().len(), 6);
+    }
+
+    #[test]
+    fn test_delete_skips_missing_keys() {
+        let map = FakeMap::default();
+        map.entries.borrow_mut().extend((0..8).map(|i| (i, i)));
+        let batcher = Batcher::new(8);
+
+        let outcome = batcher
+            .delete("BLOCKED", &map, &words([1, 2, 42, 5, 6]), &mut |_| {})
+            .unwrap();
+        assert!(outcome.batched);
+        assert_eq!(outcome.syscalls, 2);
+        let left: Vec<u32> = map.entries.borrow().keys().copied().collect();
+        assert_eq!(left, vec![0, 3, 4, 7]);
+    }
+
+    #[test]
+    fn test_lookup_in_chunks() {
+        let map = FakeMap::default();
+        map.entries
+            .borrow_mut()
+            .extend((0..10).map(|i| (i, i + 100)));
+        let batcher = Batcher::new(3);
+
+        let entries = batcher.lookup("BLOCKED", &map).unwrap();
+        assert_eq!(entries.len(), 10);
+        let (key, value) = entries.iter().last().unwrap();
+        assert_eq!((word(key), word(value)), (9, 109));
+        assert_eq!(map.batch_calls.get(), 4);
+        assert_eq!(map.elem_calls.get(), 0);
+    }
+
+    #[test]
+    fn test_pod_round_trip() {
+        let bytes = pod_bytes(&0x0102_0304u32).to_vec();
+        assert_eq!(pod_from_bytes::<u32>(&bytes), Some(0x0102_0304));
+        assert_eq!(pod_from_bytes::<u64>(&bytes), None);
+    }
+}
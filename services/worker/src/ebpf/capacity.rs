@@ -0,0 +1,847 @@
+//! Map memory budgeting and capacity planning
+//!
+//! The eBPF programs declare their maps with fixed sizes (2M TCP connections,
+//! 1M blocked IPs, ...). Before a program is loaded its object is parsed and
+//! the keyed maps are resized so the program fits its memory budget, or to
+//! the exact sizes set per map. Once loaded, the live maps are counted to
+//! report memory use, occupancy and headroom, and to warn about maps close
+//! to full: LRU maps past that point evict entries still in use, other
+//! hash maps start rejecting inserts.
+
+use super::loader::{EbpfLoader, possible_cpus};
+use aya::maps::{Map, MapData, MapInfo};
+use aya_obj::generated::bpf_map_type;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::error::{Error, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::sync::Arc;
+
+/// `bpf(2)` command returning the key following another
+const BPF_MAP_GET_NEXT_KEY: libc::c_int = 4;
+
+/// Occupancy above which a map is reported as near eviction
+pub const DEFAULT_WARN_OCCUPANCY: f64 = 0.85;
+
+/// Keyed maps smaller than this keep their declared size
+pub const MIN_SCALABLE_ENTRIES: u32 = 4096;
+
+/// Smallest size a budget may shrink a map to
+pub const MIN_ENTRIES: u32 = 1024;
+
+/// Largest factor a budget may grow a map by
+pub const MAX_GROWTH: f64 = 4.0;
+
+/// Approximate kernel overhead of a hash map element (`htab_elem` and bucket)
+const HASH_ELEM_OVERHEAD: u64 = 64;
+
+/// Approximate kernel overhead of an LPM trie node
+const LPM_NODE_OVERHEAD: u64 = 48;
+
+/// Map types the planner tells apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapKind {
+    Array,
+    PerCpuArray,
+    Hash,
+    PerCpuHash,
+    LruHash,
+    LruPerCpuHash,
+    LpmTrie,
+    RingBuf,
+    PerfEventArray,
+    Other,
+}
+
+impl MapKind {
+    /// Kind of a raw `bpf_map_type`
+    pub fn from_raw(map_type: u32) -> Self {
+        match map_type {
+            t if t == bpf_map_type::BPF_MAP_TYPE_ARRAY as u32 => Self::Array,
+            t if t == bpf_map_type::BPF_MAP_TYPE_PERCPU_ARRAY as u32 => Self::PerCpuArray,
+            t if t == bpf_map_type::BPF_MAP_TYPE_HASH as u32 => Self::Hash,
+            t if t == bpf_map_type::BPF_MAP_TYPE_PERCPU_HASH as u32 => Self::PerCpuHash,
+            t if t == bpf_map_type::BPF_MAP_TYPE_LRU_HASH as u32 => Self::LruHash,
+            t if t == bpf_map_type::BPF_MAP_TYPE_LRU_PERCPU_HASH as u32 => Self::LruPerCpuHash,
+            t if t == bpf_map_type::BPF_MAP_TYPE_LPM_TRIE as u32 => Self::LpmTrie,
+            t if t == bpf_map_type::BPF_MAP_TYPE_RINGBUF as u32 => Self::RingBuf,
+            t if t == bpf_map_type::BPF_MAP_TYPE_PERF_EVENT_ARRAY as u32 => Self::PerfEventArray,
+            _ => Self::Other,
+        }
+    }
+
+    /// Kind of a loaded map
+    pub fn of(map: &Map) -> Self {
+        match map {
+            Map::Array(_) => Self::Array,
+            Map::PerCpuArray(_) => Self::PerCpuArray,
+            Map::HashMap(_) => Self::Hash,
+            Map::PerCpuHashMap(_) => Self::PerCpuHash,
+            Map::LruHashMap(_) => Self::LruHash,
+            Map::PerCpuLruHashMap(_) => Self::LruPerCpuHash,
+            Map::LpmTrie(_) => Self::LpmTrie,
+            Map::RingBuf(_) => Self::RingBuf,
+            Map::PerfEventArray(_) => Self::PerfEventArray,
+            _ => Self::Other,
+        }
+    }
+
+    /// Maps holding a variable number of keyed entries
+    pub fn is_keyed(self) -> bool {
+        matches!(
+            self,
+            Self::Hash | Self::PerCpuHash | Self::LruHash | Self::LruPerCpuHash | Self::LpmTrie
+        )
+    }
+
+    /// Maps evicting their least recently used entries when full
+    pub fn evicts(self) -> bool {
+        matches!(self, Self::LruHash | Self::LruPerCpuHash)
+    }
+
+    fn is_per_cpu(self) -> bool {
+        matches!(
+            self,
+            Self::PerCpuArray | Self::PerCpuHash | Self::LruPerCpuHash
+        )
+    }
+}
+
+/// Definition of a map
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MapSpec {
+    pub name: String,
+    pub kind: MapKind,
+    pub key_size: u32,
+    pub value_size: u32,
+    pub max_entries: u32,
+    /// Pinned by name and shared with the other programs
+    pub pinned: bool,
+}
+
+impl MapSpec {
+    /// Maps declared by a program object
+    pub fn from_object(data: &[u8]) -> Result<Vec<Self>> {
+        let object = aya_obj::Object::parse(data)
+            .map_err(|e| Error::Internal(format!("Failed to parse eBPF object: {}", e)))?;
+
+        let mut specs: Vec<Self> = object
+            .maps
+            .iter()
+            .map(|(name, map)| Self {
+                name: name.clone(),
+                kind: MapKind::from_raw(map.map_type()),
+                key_size: map.key_size(),
+                value_size: map.value_size(),
+                max_entries: map.max_entries(),
+                pinned: map.pinning() == aya_obj::maps::PinningType::ByName,
+            })
+            .collect();
+        specs.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(specs)
+    }
+
+    /// Definition of a loaded map
+    pub fn from_info(name: &str, kind: MapKind, info: &MapInfo, pinned: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            key_size: info.key_size(),
+            value_size: info.value_size(),
+            max_entries: info.max_entries(),
+            pinned,
+        }
+    }
+
+    /// Whether a budget may resize the map
+    ///
+    /// Arrays are indexed by position and pinned maps must keep the size
+    /// the other programs share, so only large private keyed maps qualify.
+    pub fn is_scalable(&self) -> bool {
+        self.kind.is_keyed() && !self.pinned && self.max_entries >= MIN_SCALABLE_ENTRIES
+    }
+
+    /// Approximate kernel memory of the map with `entries` entries
+    pub fn memory_bytes(&self, entries: u32, cpus: u32) -> u64 {
+        let key = round_up(self.key_size);
+        let value = round_up(self.value_size);
+        let cpus = cpus.max(1) as u64;
+        let per_entry = match self.kind {
+            MapKind::Array => value,
+            MapKind::PerCpuArray => value * cpus,
+            MapKind::Hash | MapKind::LruHash => key + value + HASH_ELEM_OVERHEAD,
+            MapKind::PerCpuHash | MapKind::LruPerCpuHash => key + value * cpus + HASH_ELEM_OVERHEAD,
+            MapKind::LpmTrie => key + value + LPM_NODE_OVERHEAD,
+            // Ring buffers are sized in bytes
+            MapKind::RingBuf => return entries as u64,
+            MapKind::PerfEventArray => return 0,
+            MapKind::Other => key + value,
+        };
+        per_entry * entries as u64
+    }
+}
+
+fn round_up(size: u32) -> u64 {
+    (size as u64).div_ceil(8) * 8
+}
+
+/// Map sizing configuration
+#[derive(Debug, Clone)]
+pub struct MapBudgets {
+    /// Memory budget of programs without their own (bytes)
+    pub default_budget: Option<u64>,
+    /// Per-program budgets, keyed by program name
+    pub program_budgets: HashMap<String, u64>,
+    /// Exact sizes, keyed by map name; these win over the budget
+    pub max_entries: HashMap<String, u32>,
+    /// Occupancy above which a map is reported as near eviction
+    pub warn_occupancy: f64,
+}
+
+impl Default for MapBudgets {
+    fn default() -> Self {
+        Self {
+            default_budget: None,
+            program_budgets: HashMap::new(),
+            max_entries: HashMap::new(),
+            warn_occupancy: DEFAULT_WARN_OCCUPANCY,
+        }
+    }
+}
+
+impl MapBudgets {
+    /// Load map sizing from environment variables
+    ///
+    /// - `PISTON_MAP_MEMORY_BUDGET`: budget of every program (e.g. 512M)
+    /// - `PISTON_MAP_MEMORY_BUDGETS`: per program (xdp_tcp=1G,xdp_udp=256M)
+    /// - `PISTON_MAP_MAX_ENTRIES`: per map (TCP_CONNECTIONS=4000000)
+    /// - `PISTON_MAP_WARN_OCCUPANCY`: warning threshold (0-1)
+    pub fn from_env() -> Self {
+        let mut budgets = Self::default();
+
+        if let Ok(budget) = std::env::var("PISTON_MAP_MEMORY_BUDGET") {
+            budgets.default_budget = parse_bytes(&budget);
+        }
+        if let Ok(pairs) = std::env::var("PISTON_MAP_MEMORY_BUDGETS") {
+            for (program, budget) in parse_pairs(&pairs) {
+                if let Some(budget) = parse_bytes(budget) {
+                    budgets.program_budgets.insert(program.to_string(), budget);
+                }
+            }
+        }
+        if let Ok(pairs) = std::env::var("PISTON_MAP_MAX_ENTRIES") {
+            for (map, entries) in parse_pairs(&pairs) {
+                if let Ok(entries) = entries.parse::<u32>() {
+                    budgets.max_entries.insert(map.to_string(), entries);
+                }
+            }
+        }
+        if let Ok(threshold) = std::env::var("PISTON_MAP_WARN_OCCUPANCY") {
+            if let Ok(threshold) = threshold.parse::<f64>() {
+                if (0.0..=1.0).contains(&threshold) {
+                    budgets.warn_occupancy = threshold;
+                }
+            }
+        }
+
+        budgets
+    }
+
+    /// Memory budget of a program
+    pub fn budget_for(&self, program: &str) -> Option<u64> {
+        self.program_budgets
+            .get(program)
+            .copied()
+            .or(self.default_budget)
+    }
+
+    /// Size the maps of a program
+    ///
+    /// Maps with an explicit size get it. With a budget, the scalable maps
+    /// are scaled by a common factor so the whole program fits, never below
+    /// `MIN_ENTRIES` nor above `MAX_GROWTH` times their declared size.
+    pub fn plan(&self, program: &str, specs: &[MapSpec], cpus: u32) -> SizingPlan {
+        let budget = self.budget_for(program);
+
+        let mut fixed_bytes = 0u64;
+        let mut scalable_bytes = 0u64;
+        for spec in specs {
+            match self.max_entries.get(&spec.name) {
+                Some(&entries) => fixed_bytes += spec.memory_bytes(entries, cpus),
+                None if budget.is_some() && spec.is_scalable() => {
+                    scalable_bytes += spec.memory_bytes(spec.max_entries, cpus)
+                }
+                None => fixed_bytes += spec.memory_bytes(spec.max_entries, cpus),
+            }
+        }
+
+        let factor = match budget {
+            Some(budget) if scalable_bytes > 0 => {
+                (budget.saturating_sub(fixed_bytes) as f64 / scalable_bytes as f64).min(MAX_GROWTH)
+            }
+            _ => 1.0,
+        };
+
+        let maps: Vec<PlannedMap> = specs
+            .iter()
+            .map(|spec| {
+                let entries = match self.max_entries.get(&spec.name) {
+                    Some(&entries) => entries,
+                    None if budget.is_some() && spec.is_scalable() => {
+                        ((spec.max_entries as f64 * factor) as u32).max(MIN_ENTRIES)
+                    }
+                    None => spec.max_entries,
+                };
+                PlannedMap {
+                    name: spec.name.clone(),
+                    pinned: spec.pinned,
+                    declared_entries: spec.max_entries,
+                    entries,
+                    memory_bytes: spec.memory_bytes(entries, cpus),
+                }
+            })
+            .collect();
+
+        let memory_bytes = maps.iter().map(|m| m.memory_bytes).sum();
+        SizingPlan {
+            program: program.to_string(),
+            budget,
+            memory_bytes,
+            over_budget: budget.is_some_and(|budget| memory_bytes > budget),
+            maps,
+        }
+    }
+}
+
+fn parse_pairs(pairs: &str) -> impl Iterator<Item = (&str, &str)> {
+    pairs
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+/// Parse a size in bytes with an optional K, M or G suffix (powers of 1024)
+pub fn parse_bytes(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.chars().last()?.to_ascii_uppercase() {
+        'K' => (&value[..value.len() - 1], 1u64 << 10),
+        'M' => (&value[..value.len() - 1], 1 << 20),
+        'G' => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Planned size of one map
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlannedMap {
+    pub name: String,
+    pub pinned: bool,
+    /// Size declared by the program
+    pub declared_entries: u32,
+    /// Size the map is created with
+    pub entries: u32,
+    pub memory_bytes: u64,
+}
+
+/// Sizes of the maps of one program
+#[derive(Debug, Clone, Serialize)]
+pub struct SizingPlan {
+    pub program: String,
+    pub budget: Option<u64>,
+    /// Estimated memory of all maps as planned
+    pub memory_bytes: u64,
+    /// The budget is too small even with every scalable map at its minimum
+    pub over_budget: bool,
+    pub maps: Vec<PlannedMap>,
+}
+
+impl SizingPlan {
+    /// Maps created with a size other than the declared one
+    pub fn resized(&self) -> impl Iterator<Item = &PlannedMap> {
+        self.maps.iter().filter(|m| m.entries != m.declared_entries)
+    }
+
+    /// Planned size of a map
+    pub fn get(&self, map: &str) -> Option<&PlannedMap> {
+        self.maps.iter().find(|m| m.name == map)
+    }
+}
+
+// ============================================================================
+// Usage
+// ============================================================================
+
+/// A loaded map, detached from the loader so it can be counted without
+/// holding the loader lock
+pub struct MapHandle {
+    pub program: String,
+    pub spec: MapSpec,
+    pub declared_entries: Option<u32>,
+    pub fd: OwnedFd,
+}
+
+impl MapHandle {
+    /// Handle of a loaded map
+    pub fn new(program: &str, name: &str, map: &Map, plan: Option<&SizingPlan>) -> Result<Self> {
+        let data = map_data(map);
+        let info = data
+            .info()
+            .map_err(|e| Error::Internal(format!("Failed to read map info: {}", e)))?;
+        let fd = data
+            .fd()
+            .as_fd()
+            .try_clone_to_owned()
+            .map_err(|e| Error::Internal(format!("Failed to duplicate map fd: {}", e)))?;
+
+        let planned = plan.and_then(|plan| plan.get(name));
+        let pinned = planned.is_some_and(|planned| planned.pinned);
+
+        Ok(Self {
+            program: program.to_string(),
+            spec: MapSpec::from_info(name, MapKind::of(map), &info, pinned),
+            declared_entries: planned.map(|planned| planned.declared_entries),
+            fd,
+        })
+    }
+}
+
+/// Kernel map behind a map of any type
+pub fn map_data(map: &Map) -> &MapData {
+    match map {
+        Map::Array(data)
+        | Map::BloomFilter(data)
+        | Map::CpuMap(data)
+        | Map::DevMap(data)
+        | Map::DevMapHash(data)
+        | Map::HashMap(data)
+        | Map::LpmTrie(data)
+        | Map::LruHashMap(data)
+        | Map::PerCpuArray(data)
+        | Map::PerCpuHashMap(data)
+        | Map::PerCpuLruHashMap(data)
+        | Map::PerfEventArray(data)
+        | Map::ProgramArray(data)
+        | Map::Queue(data)
+        | Map::RingBuf(data)
+        | Map::SockHash(data)
+        | Map::SockMap(data)
+        | Map::Stack(data)
+        | Map::StackTraceMap(data)
+        | Map::Unsupported(data)
+        | Map::XskMap(data) => data,
+    }
+}
+
+/// `map_elem` member of `union bpf_attr`
+#[repr(C)]
+#[derive(Default)]
+struct MapElemAttr {
+    map_fd: u32,
+    _pad: u32,
+    key: u64,
+    next_key: u64,
+    flags: u64,
+}
+
+/// Count the entries of a keyed map, stopping at `limit`
+///
+/// Walks the keys with `BPF_MAP_GET_NEXT_KEY`, one syscall per entry.
+pub fn count_entries(map: BorrowedFd<'_>, key_size: u32, limit: u64) -> io::Result<u64> {
+    let mut key = vec![0u8; key_size as usize];
+    let mut next_key = vec![0u8; key_size as usize];
+    let mut count = 0;
+    let mut first = true;
+
+    while count < limit {
+        let mut attr = MapElemAttr {
+            map_fd: map.as_raw_fd() as u32,
+            key: if first { 0 } else { key.as_ptr() as u64 },
+            next_key: next_key.as_mut_ptr() as u64,
+            ..Default::default()
+        };
+
+        // SAFETY: attr is a zeroed `bpf_attr` prefix valid for
+        // BPF_MAP_GET_NEXT_KEY, and both key buffers are `key_size` bytes
+        // long and outlive the call
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_bpf,
+                BPF_MAP_GET_NEXT_KEY,
+                &mut attr as *mut MapElemAttr,
+                std::mem::size_of::<MapElemAttr>() as u32,
+            )
+        };
+        if ret < 0 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() == Some(libc::ENOENT) {
+                break;
+            }
+            return Err(error);
+        }
+
+        count += 1;
+        first = false;
+        std::mem::swap(&mut key, &mut next_key);
+    }
+
+    Ok(count)
+}
+
+/// Memory use and occupancy of one map
+#[derive(Debug, Clone, Serialize)]
+pub struct MapUsage {
+    pub name: String,
+    pub kind: MapKind,
+    pub key_size: u32,
+    pub value_size: u32,
+    pub max_entries: u32,
+    /// Size declared by the program, if the map was resized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declared_entries: Option<u32>,
+    pub pinned: bool,
+    /// Entries in use (keyed maps only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occupancy: Option<f64>,
+    /// Estimated memory at `max_entries`
+    pub memory_bytes: u64,
+    /// Entries that can be added before the map is full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headroom_entries: Option<u64>,
+    /// Occupancy is past the warning threshold
+    pub near_eviction: bool,
+}
+
+impl MapUsage {
+    /// Usage of a map with `entries` entries in use
+    pub fn new(
+        spec: &MapSpec,
+        declared_entries: Option<u32>,
+        entries: Option<u64>,
+        cpus: u32,
+        warn_occupancy: f64,
+    ) -> Self {
+        let occupancy = entries
+            .filter(|_| spec.max_entries > 0)
+            .map(|entries| entries as f64 / spec.max_entries as f64);
+
+        Self {
+            name: spec.name.clone(),
+            kind: spec.kind,
+            key_size: spec.key_size,
+            value_size: spec.value_size,
+            max_entries: spec.max_entries,
+            declared_entries: declared_entries.filter(|&declared| declared != spec.max_entries),
+            pinned: spec.pinned,
+            entries,
+            occupancy,
+            memory_bytes: spec.memory_bytes(spec.max_entries, cpus),
+            headroom_entries: entries
+                .map(|entries| (spec.max_entries as u64).saturating_sub(entries)),
+            near_eviction: occupancy.is_some_and(|occupancy| occupancy >= warn_occupancy),
+        }
+    }
+}
+
+/// Map memory of one program
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramCapacity {
+    pub program: String,
+    pub budget: Option<u64>,
+    pub memory_bytes: u64,
+    /// Budget left, negative when the maps exceed it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headroom_bytes: Option<i64>,
+    pub maps: Vec<MapUsage>,
+}
+
+/// Map memory of every loaded program
+#[derive(Debug, Clone, Serialize)]
+pub struct CapacityReport {
+    pub generated_at: DateTime<Utc>,
+    pub warn_occupancy: f64,
+    /// Estimated memory of all maps; pinned maps are counted once
+    pub memory_bytes: u64,
+    pub programs: Vec<ProgramCapacity>,
+}
+
+impl CapacityReport {
+    /// Report on the maps of every program loaded by `loader`
+    ///
+    /// Counting walks every key of every map, so it runs on a blocking
+    /// thread without the loader lock.
+    pub async fn from_loader(loader: Arc<RwLock<EbpfLoader>>) -> Result<Self> {
+        let (handles, budgets) = {
+            let loader = loader.read();
+            (loader.map_handles(), loader.map_budgets().clone())
+        };
+        tokio::task::spawn_blocking(move || Self::collect(&handles, &budgets, possible_cpus()))
+            .await
+            .map_err(|e| Error::Internal(format!("Map capacity task failed: {}", e)))
+    }
+
+    /// Count the maps and build the report
+    pub fn collect(handles: &[MapHandle], budgets: &MapBudgets, cpus: u32) -> Self {
+        let mut programs: Vec<ProgramCapacity> = Vec::new();
+        let mut pinned_seen = HashSet::new();
+        let mut memory_bytes = 0;
+
+        for handle in handles {
+            let entries = if handle.spec.kind.is_keyed() {
+                match count_entries(
+                    handle.fd.as_fd(),
+                    handle.spec.key_size,
+                    handle.spec.max_entries as u64,
+                ) {
+                    Ok(entries) => Some(entries),
+                    Err(e) => {
+                        tracing::debug!(map = %handle.spec.name, error = %e, "Failed to count map entries");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            let usage = MapUsage::new(
+                &handle.spec,
+                handle.declared_entries,
+                entries,
+                cpus,
+                budgets.warn_occupancy,
+            );
+
+            if !handle.spec.pinned || pinned_seen.insert(handle.spec.name.clone()) {
+                memory_bytes += usage.memory_bytes;
+            }
+
+            let position = match programs.iter().position(|p| p.program == handle.program) {
+                Some(position) => position,
+                None => {
+                    programs.push(ProgramCapacity {
+                        program: handle.program.clone(),
+                        budget: budgets.budget_for(&handle.program),
+                        memory_bytes: 0,
+                        headroom_bytes: None,
+                        maps: Vec::new(),
+                    });
+                    programs.len() - 1
+                }
+            };
+            let program = &mut programs[position];
+            program.memory_bytes += usage.memory_bytes;
+            program.maps.push(usage);
+        }
+
+        for program in &mut programs {
+            program.headroom_bytes = program
+                .budget
+                .map(|budget| budget as i64 - program.memory_bytes as i64);
+            program.maps.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        programs.sort_by(|a, b| a.program.cmp(&b.program));
+
+        Self {
+            generated_at: Utc::now(),
+            warn_occupancy: budgets.warn_occupancy,
+            memory_bytes,
+            programs,
+        }
+    }
+
+    /// Export map sizes and occupancy as Prometheus gauges
+    pub fn export_metrics(&self) {
+        use pistonprotection_common::metrics::{
+            EBPF_MAP_ENTRIES, EBPF_MAP_MAX_ENTRIES, EBPF_MAP_MEMORY_BYTES,
+        };
+
+        for program in &self.programs {
+            for map in &program.maps {
+                let labels = [program.program.as_str(), map.name.as_str()];
+                EBPF_MAP_MAX_ENTRIES
+                    .with_label_values(&labels)
+                    .set(map.max_entries as f64);
+                EBPF_MAP_MEMORY_BYTES
+                    .with_label_values(&labels)
+                    .set(map.memory_bytes as f64);
+                if let Some(entries) = map.entries {
+                    EBPF_MAP_ENTRIES
+                        .with_label_values(&labels)
+                        .set(entries as f64);
+                }
+            }
+        }
+    }
+}
+
+/// Warns once when a map crosses the occupancy threshold, and again only
+/// after it dropped back below it
+#[derive(Debug, Default)]
+pub struct OccupancyWatch {
+    warned: HashSet<(String, String)>,
+}
+
+impl OccupancyWatch {
+    /// Log maps newly past the threshold and return them as (program, map)
+    pub fn check(&mut self, report: &CapacityReport) -> Vec<(String, String)> {
+        let mut newly_full = Vec::new();
+        for program in &report.programs {
+            for map in &program.maps {
+                let key = (program.program.clone(), map.name.clone());
+                if !map.near_eviction {
+                    self.warned.remove(&key);
+                    continue;
+                }
+                if self.warned.insert(key.clone()) {
+                    tracing::warn!(
+                        program = %program.program,
+                        map = %map.name,
+                        entries = map.entries.unwrap_or(0),
+                        max_entries = map.max_entries,
+                        "eBPF map is {:.0}% full, {}",
+                        map.occupancy.unwrap_or(0.0) * 100.0,
+                        if map.kind.evicts() {
+                            "entries in use are being evicted"
+                        } else {
+                            "new entries will be rejected"
+                        }
+                    );
+                    newly_full.push(key);
+                }
+            }
+        }
+        newly_full
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(name: &str, kind: MapKind, max_entries: u32) -> MapSpec {
+        MapSpec {
+            name: name.to_string(),
+            kind,
+            key_size: 16,
+            value_size: 32,
+            max_entries,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("4096"), Some(4096));
+        assert_eq!(parse_bytes("512K"), Some(512 * 1024));
+        assert_eq!(parse_bytes("256m"), Some(256 << 20));
+        assert_eq!(parse_bytes("2G"), Some(2 << 30));
+        assert_eq!(parse_bytes("lots"), None);
+        assert_eq!(parse_bytes(""), None);
+    }
+
+    #[test]
+    fn test_memory_estimate() {
+        let hash = spec("CONNS", MapKind::LruHash, 1000);
+        assert_eq!(hash.memory_bytes(1000, 4), (16 + 32 + 64) * 1000);
+
+        let per_cpu = spec("STATS", MapKind::PerCpuArray, 1);
+        assert_eq!(per_cpu.memory_bytes(1, 4), 32 * 4);
+    }
+
+    #[test]
+    fn test_plan_without_budget_keeps_sizes() {
+        let specs = vec![spec("CONNS", MapKind::LruHash, 2_000_000)];
+        let plan = MapBudgets::default().plan("xdp_tcp", &specs, 4);
+        assert_eq!(plan.resized().count(), 0);
+        assert!(!plan.over_budget);
+    }
+
+    #[test]
+    fn test_plan_scales_to_budget() {
+        let specs = vec![
+            spec("CONNS", MapKind::LruHash, 2_000_000),
+            spec("IPS", MapKind::Hash, 1_000_000),
+            spec("STATS", MapKind::PerCpuArray, 1),
+            MapSpec {
+                pinned: true,
+                ..spec("PENALTY", MapKind::LruHash, 100_000)
+            },
+        ];
+        let budgets = MapBudgets {
+            default_budget: Some(64 << 20),
+            ..Default::default()
+        };
+        let plan = budgets.plan("xdp_tcp", &specs, 4);
+
+        let entries = |name: &str| plan.maps.iter().find(|m| m.name == name).unwrap().entries;
+        // Both scalable maps shrink by the same factor
+        assert!(entries("CONNS") < 2_000_000);
+        assert_eq!(entries("CONNS") / 2, entries("IPS"));
+        // Arrays and pinned maps keep their size
+        assert_eq!(entries("STATS"), 1);
+        assert_eq!(entries("PENALTY"), 100_000);
+        assert!(plan.memory_bytes <= 64 << 20);
+        assert!(!plan.over_budget);
+    }
+
+    #[test]
+    fn test_plan_growth_is_capped() {
+        let specs = vec![spec("CONNS", MapKind::LruHash, 10_000)];
+        let budgets = MapBudgets {
+            default_budget: Some(1 << 30),
+            ..Default::default()
+        };
+        let plan = budgets.plan("xdp_tcp", &specs, 4);
+        assert_eq!(plan.maps[0].entries, 40_000);
+    }
+
+    #[test]
+    fn test_plan_explicit_sizes_and_over_budget() {
+        let specs = vec![
+            spec("CONNS", MapKind::LruHash, 2_000_000),
+            spec("IPS", MapKind::Hash, 1_000_000),
+        ];
+        let budgets = MapBudgets {
+            default_budget: Some(1 << 20),
+            max_entries: [("IPS".to_string(), 500_000)].into_iter().collect(),
+            ..Default::default()
+        };
+        let plan = budgets.plan("xdp_tcp", &specs, 4);
+
+        assert_eq!(plan.maps[0].entries, MIN_ENTRIES);
+        assert_eq!(plan.maps[1].entries, 500_000);
+        assert!(plan.over_budget);
+    }
+
+    #[test]
+    fn test_usage_and_warnings() {
+        let lru = spec("CONNS", MapKind::LruHash, 1000);
+        let usage = MapUsage::new(&lru, Some(2000), Some(900), 4, 0.85);
+        assert_eq!(usage.occupancy, Some(0.9));
+        assert_eq!(usage.headroom_entries, Some(100));
+        assert_eq!(usage.declared_entries, Some(2000));
+        assert!(usage.near_eviction);
+
+        let report = CapacityReport {
+            generated_at: Utc::now(),
+            warn_occupancy: 0.85,
+            memory_bytes: usage.memory_bytes,
+            programs: vec![ProgramCapacity {
+                program: "xdp_tcp".to_string(),
+                budget: None,
+                memory_bytes: usage.memory_bytes,
+                headroom_bytes: None,
+                maps: vec![usage],
+            }],
+        };
+        let mut watch = OccupancyWatch::default();
+        assert_eq!(watch.check(&report).len(), 1);
+        // Still full: no repeated warning
+        assert!(watch.check(&report).is_empty());
+    }
+}
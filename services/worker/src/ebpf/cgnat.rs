@@ -0,0 +1,440 @@
+//! Carrier-grade NAT
+//!
+//! Mobile carriers and many ISPs put thousands of users behind one address
+//! (CGNAT), so a busy carrier address trips the per-IP limits of xdp_tcp,
+//! xdp_minecraft and xdp_quic although every user behind it behaves. The
+//! worker writes the known CGNAT ranges to the shared `CGNAT_RANGES` trie,
+//! where the programs multiply the per-IP limits of matching sources.
+//!
+//! Ranges come from configuration (the RFC 6598 shared address space by
+//! default) and, optionally, from detection: while it is on, xdp_tcp
+//! records for every source completing a handshake a sketch of the SYN
+//! shapes (window and TCP options, which differ between operating systems)
+//! and TCP timestamp clocks (which differ between devices) in
+//! `CGNAT_SIGNALS`. One host shows one clock and one shape; an address
+//! showing many of both is shared. Detected addresses are reported, and in
+//! `apply` mode get the multiplier too until they stop looking shared.
+
+use super::learning::addr_key;
+use chrono::{DateTime, Utc};
+use ipnetwork::IpNetwork;
+use parking_lot::RwLock;
+use pistonprotection_common::metrics::CGNAT_RANGES;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Maximum number of CGNAT ranges (mirrors `cgnat::MAX_RANGES`)
+pub const MAX_RANGES: usize = 16384;
+
+/// Limit multiplier when the configuration leaves it unset
+pub const DEFAULT_MULTIPLIER: u32 = 8;
+
+/// Largest limit multiplier
+pub const MAX_MULTIPLIER: u32 = 100;
+
+/// Distinct timestamp clocks per interval from which a source counts as
+/// shared
+pub const DEFAULT_MIN_CLOCKS: u32 = 16;
+
+/// Distinct SYN shapes per interval from which a source counts as shared
+pub const DEFAULT_MIN_FINGERPRINTS: u32 = 3;
+
+/// Time a detected address keeps its multiplier after it last looked shared
+pub const DETECTION_TTL: chrono::Duration = chrono::Duration::hours(24);
+
+/// RFC 6598 shared address space, reserved for CGNAT
+pub const SHARED_ADDRESS_SPACE: &str = "100.64.0.0/10";
+
+/// Value of `CGNAT_SIGNALS` (mirrors `CgnatSignals`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CgnatSignals {
+    pub first_seen_ns: u64,
+    pub last_seen_ns: u64,
+    pub syns: u32,
+    pub timestamped: u32,
+    pub fingerprints: u64,
+    pub clocks: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of integer fields, no implicit padding.
+unsafe impl aya::Pod for CgnatSignals {}
+
+/// Number of distinct values behind a 64-bit sketch (linear counting)
+pub fn estimate_distinct(sketch: u64) -> u32 {
+    let empty = sketch.count_zeros();
+    if empty == 0 {
+        // Saturated: at least as many as a sketch with one empty bit
+        return (64.0 * (64.0f64).ln()).round() as u32;
+    }
+    (-64.0 * (f64::from(empty) / 64.0).ln()).round() as u32
+}
+
+/// What detection does with the addresses it finds shared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionMode {
+    /// SYN signals are not recorded
+    Off,
+    /// Shared addresses are reported only
+    #[default]
+    Monitor,
+    /// Shared addresses get the multiplier
+    Apply,
+}
+
+impl FromStr for DetectionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(DetectionMode::Off),
+            "monitor" => Ok(DetectionMode::Monitor),
+            "apply" => Ok(DetectionMode::Apply),
+            other => Err(format!("unknown CGNAT detection mode '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for DetectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DetectionMode::Off => "off",
+            DetectionMode::Monitor => "monitor",
+            DetectionMode::Apply => "apply",
+        };
+        f.write_str(name)
+    }
+}
+
+/// CGNAT configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgnatConfig {
+    pub ranges: Vec<IpNetwork>,
+    pub multiplier: u32,
+    pub detection: DetectionMode,
+    pub min_clocks: u32,
+    pub min_fingerprints: u32,
+}
+
+impl Default for CgnatConfig {
+    fn default() -> Self {
+        Self {
+            ranges: vec![SHARED_ADDRESS_SPACE.parse().expect("valid network")],
+            multiplier: DEFAULT_MULTIPLIER,
+            detection: DetectionMode::default(),
+            min_clocks: DEFAULT_MIN_CLOCKS,
+            min_fingerprints: DEFAULT_MIN_FINGERPRINTS,
+        }
+    }
+}
+
+impl CgnatConfig {
+    /// Load CGNAT configuration from environment variables
+    ///
+    /// `PISTON_CGNAT_RANGES` lists the carrier ranges, comma separated
+    /// (default the RFC 6598 shared address space, empty for none);
+    /// `PISTON_CGNAT_MULTIPLIER` sets their limit multiplier,
+    /// `PISTON_CGNAT_DETECTION` the detection mode (`off`, `monitor` or
+    /// `apply`) and `PISTON_CGNAT_MIN_CLOCKS`/`PISTON_CGNAT_MIN_FINGERPRINTS`
+    /// its thresholds.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(ranges) = std::env::var("PISTON_CGNAT_RANGES") {
+            config.ranges = ranges
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .filter_map(|s| match s.trim().parse() {
+                    Ok(range) => Some(range),
+                    Err(e) => {
+                        tracing::warn!(range = s, error = %e, "Ignoring CGNAT range");
+                        None
+                    }
+                })
+                .collect();
+        }
+        if let Ok(multiplier) = std::env::var("PISTON_CGNAT_MULTIPLIER") {
+            if let Ok(multiplier) = multiplier.parse::<u32>() {
+                config.multiplier = multiplier.clamp(1, MAX_MULTIPLIER);
+            }
+        }
+        if let Ok(mode) = std::env::var("PISTON_CGNAT_DETECTION") {
+            match mode.parse() {
+                Ok(mode) => config.detection = mode,
+                Err(e) => tracing::warn!(error = %e, "Ignoring PISTON_CGNAT_DETECTION"),
+            }
+        }
+        if let Ok(min) = std::env::var("PISTON_CGNAT_MIN_CLOCKS") {
+            if let Ok(min) = min.parse::<u32>() {
+                config.min_clocks = min.max(2);
+            }
+        }
+        if let Ok(min) = std::env::var("PISTON_CGNAT_MIN_FINGERPRINTS") {
+            if let Ok(min) = min.parse::<u32>() {
+                config.min_fingerprints = min.max(1);
+            }
+        }
+
+        config.ranges.truncate(MAX_RANGES);
+        config
+    }
+}
+
+/// Address found shared by detection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DetectedSource {
+    pub ip: IpAddr,
+    /// Estimated distinct timestamp clocks in the last interval it looked
+    /// shared
+    pub clocks: u32,
+    /// Estimated distinct SYN shapes in that interval
+    pub fingerprints: u32,
+    pub first_detected: DateTime<Utc>,
+    pub last_detected: DateTime<Utc>,
+}
+
+/// CGNAT ranges and detection
+#[derive(Debug, Clone, Serialize)]
+pub struct CgnatStatus {
+    pub multiplier: u32,
+    pub detection: DetectionMode,
+    pub ranges: Vec<String>,
+    pub detected: Vec<DetectedSource>,
+}
+
+/// Known and detected CGNAT addresses
+pub struct CgnatDetector {
+    config: CgnatConfig,
+    /// Detected addresses keyed by address
+    detected: RwLock<HashMap<IpAddr, DetectedSource>>,
+}
+
+impl CgnatDetector {
+    pub fn new(config: CgnatConfig) -> Self {
+        Self {
+            config,
+            detected: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Whether xdp_tcp should record SYN signals
+    pub fn detecting(&self) -> bool {
+        self.config.detection != DetectionMode::Off
+    }
+
+    /// Evaluate the SYN signals recorded over the last interval
+    ///
+    /// Returns the addresses newly found shared. Addresses in a configured
+    /// range already get the multiplier and are skipped; detected addresses
+    /// not found shared for `DETECTION_TTL` are dropped.
+    pub fn record(
+        &self,
+        signals: &[(IpAddr, CgnatSignals)],
+        now: DateTime<Utc>,
+    ) -> Vec<DetectedSource> {
+        let mut detected = self.detected.write();
+        let mut found = Vec::new();
+
+        for (ip, signal) in signals {
+            if self.config.ranges.iter().any(|range| range.contains(*ip)) {
+                continue;
+            }
+            let clocks = estimate_distinct(signal.clocks);
+            let fingerprints = estimate_distinct(signal.fingerprints);
+            if clocks < self.config.min_clocks || fingerprints < self.config.min_fingerprints {
+                continue;
+            }
+
+            match detected.get_mut(ip) {
+                Some(source) => {
+                    source.clocks = clocks;
+                    source.fingerprints = fingerprints;
+                    source.last_detected = now;
+                }
+                None => {
+                    let source = DetectedSource {
+                        ip: *ip,
+                        clocks,
+                        fingerprints,
+                        first_detected: now,
+                        last_detected: now,
+                    };
+                    found.push(source.clone());
+                    detected.insert(*ip, source);
+                }
+            }
+        }
+
+        detected.retain(|_, source| now - source.last_detected < DETECTION_TTL);
+        found
+    }
+
+    /// `CGNAT_RANGES` entries: (prefix length, IPv4-mapped or IPv6 network,
+    /// multiplier)
+    pub fn map_entries(&self) -> Vec<(u32, [u8; 16], u32)> {
+        let mut entries: Vec<(u32, [u8; 16], u32)> = self
+            .config
+            .ranges
+            .iter()
+            .map(|range| {
+                let prefix = match range {
+                    IpNetwork::V4(net) => 96 + u32::from(net.prefix()),
+                    IpNetwork::V6(net) => u32::from(net.prefix()),
+                };
+                (prefix, addr_key(range.network()), self.config.multiplier)
+            })
+            .collect();
+
+        let detected = self.detected.read();
+        CGNAT_RANGES
+            .with_label_values(&["configured"])
+            .set(entries.len() as f64);
+        CGNAT_RANGES
+            .with_label_values(&["detected"])
+            .set(detected.len() as f64);
+        if self.config.detection == DetectionMode::Apply {
+            entries.extend(
+                detected
+                    .keys()
+                    .map(|ip| (128, addr_key(*ip), self.config.multiplier)),
+            );
+        }
+
+        entries.sort_unstable();
+        entries.truncate(MAX_RANGES);
+        entries
+    }
+
+    /// CGNAT ranges and detected addresses
+    pub fn status(&self) -> CgnatStatus {
+        let mut detected: Vec<DetectedSource> = self.detected.read().values().cloned().collect();
+        detected.sort_by(|a, b| b.clocks.cmp(&a.clocks).then(a.ip.cmp(&b.ip)));
+
+        CgnatStatus {
+            multiplier: self.config.multiplier,
+            detection: self.config.detection,
+            ranges: self.config.ranges.iter().map(|r| r.to_string()).collect(),
+            detected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sketch with one bit per value, as the kernel sets them
+    fn sketch(values: impl Iterator<Item = u32>) -> u64 {
+        values.fold(0, |acc, v| {
+            acc | 1u64 << (v.wrapping_mul(0x9e37_79b1) >> 26)
+        })
+    }
+
+    fn signals(devices: u32, shapes: u32) -> CgnatSignals {
+        CgnatSignals {
+            syns: devices * 4,
+            timestamped: devices * 4,
+            fingerprints: sketch(0..shapes),
+            clocks: sketch((0..devices).map(|d| d.wrapping_mul(7919))),
+            ..Default::default()
+        }
+    }
+
+    fn detector(detection: DetectionMode) -> CgnatDetector {
+        CgnatDetector::new(CgnatConfig {
+            detection,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_layout_matches_kernel() {
+        assert_eq!(std::mem::size_of::<CgnatSignals>(), 40);
+    }
+
+    #[test]
+    fn test_estimate_distinct() {
+        assert_eq!(estimate_distinct(0), 0);
+        assert_eq!(estimate_distinct(1), 1);
+        // Values sharing a bit make the bits set undercount them
+        assert_eq!(estimate_distinct(u64::from(u32::MAX)), 44);
+        assert_eq!(estimate_distinct(u64::MAX), 266);
+    }
+
+    #[test]
+    fn test_detects_shared_address() {
+        let detector = detector(DetectionMode::Apply);
+        let shared: IpAddr = "198.51.100.7".parse().unwrap();
+        let host: IpAddr = "203.0.113.9".parse().unwrap();
+        let now = Utc::now();
+
+        let found = detector.record(&[(shared, signals(40, 5)), (host, signals(1, 1))], now);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].ip, shared);
+
+        // Seen again: not new, but kept
+        assert!(detector.record(&[(shared, signals(40, 5))], now).is_empty());
+        assert!(
+            detector
+                .map_entries()
+                .contains(&(128, addr_key(shared), DEFAULT_MULTIPLIER))
+        );
+    }
+
+    #[test]
+    fn test_one_os_is_not_enough() {
+        // Many clocks from a single SYN shape: one machine opening
+        // connections from many containers, not a carrier
+        let detector = detector(DetectionMode::Apply);
+        let ip: IpAddr = "198.51.100.7".parse().unwrap();
+        assert!(
+            detector
+                .record(&[(ip, signals(40, 1))], Utc::now())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_monitor_mode_does_not_apply() {
+        let detector = detector(DetectionMode::Monitor);
+        let ip: IpAddr = "198.51.100.7".parse().unwrap();
+        detector.record(&[(ip, signals(40, 5))], Utc::now());
+
+        assert_eq!(detector.status().detected.len(), 1);
+        // Only the shared address space
+        assert_eq!(
+            detector.map_entries(),
+            vec![(
+                106,
+                addr_key("100.64.0.0".parse().unwrap()),
+                DEFAULT_MULTIPLIER
+            )]
+        );
+    }
+
+    #[test]
+    fn test_detection_expires() {
+        let detector = detector(DetectionMode::Apply);
+        let ip: IpAddr = "198.51.100.7".parse().unwrap();
+        let now = Utc::now();
+        detector.record(&[(ip, signals(40, 5))], now);
+
+        detector.record(&[], now + DETECTION_TTL);
+        assert!(detector.status().detected.is_empty());
+    }
+
+    #[test]
+    fn test_configured_range_not_detected() {
+        let detector = detector(DetectionMode::Apply);
+        let ip: IpAddr = "100.64.1.1".parse().unwrap();
+        assert!(
+            detector
+                .record(&[(ip, signals(40, 5))], Utc::now())
+                .is_empty()
+        );
+    }
+}
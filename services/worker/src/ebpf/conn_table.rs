@@ -0,0 +1,579 @@
+//! Connection table export
+//!
+//! During an incident support needs to see what the XDP programs think is
+//! happening: which connections they track, in which state, and how busy
+//! they are. This module mirrors the kernel connection entries of xdp_tcp,
+//! xdp_quic and xdp_minecraft, decodes them into readable entries and pages
+//! through them in a stable order, so a dump taken while traffic flows does
+//! not repeat or skip entries between pages.
+
+use super::connections::TcpConnectionState;
+use super::learning::key_addr;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::common::L7Protocol;
+use serde::Serialize;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Entries returned per page when the request leaves it unset
+pub const DEFAULT_DUMP_LIMIT: usize = 100;
+
+/// Most entries returned per page
+pub const MAX_DUMP_LIMIT: usize = 1000;
+
+/// Value of `QUIC_CONNECTIONS` (mirrors `QuicConnectionState`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuicConnectionState {
+    pub state: u8,
+    pub _pad0: [u8; 3],
+    pub version: u32,
+    pub packets: u64,
+    pub bytes: u64,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub initial_packets: u32,
+    pub _pad1: u32,
+    pub response_bytes: u64,
+    pub flags: u32,
+    pub _pad2: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for QuicConnectionState {}
+
+/// Value of `MC_JAVA_CONNECTIONS` (mirrors `McConnectionState`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct McConnectionState {
+    pub state: u8,
+    pub _pad0: [u8; 3],
+    pub protocol_version: u32,
+    pub packets: u64,
+    pub bytes: u64,
+    pub last_seen: u64,
+    pub flags: u32,
+    pub pending_packet_bytes: u32,
+    pub pending_seq: u32,
+    pub _pad1: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for McConnectionState {}
+
+/// Value of `MC_BEDROCK_CONNECTIONS` (mirrors `BedrockConnectionState`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BedrockConnectionState {
+    pub state: u8,
+    pub protocol_version: u8,
+    pub mtu_size: u16,
+    pub security_cookie: u32,
+    pub client_guid: u64,
+    pub packets: u64,
+    pub bytes_in: u64,
+    pub bytes_out_estimate: u64,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub ping_count: u32,
+    pub conn_req_count: u32,
+    pub window_start: u64,
+    pub flags: u32,
+    pub _pad0: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for BedrockConnectionState {}
+
+/// Kernel connection table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionTable {
+    Tcp,
+    Quic,
+    MinecraftJava,
+    MinecraftBedrock,
+}
+
+impl ConnectionTable {
+    pub const ALL: [ConnectionTable; 4] = [
+        ConnectionTable::Tcp,
+        ConnectionTable::Quic,
+        ConnectionTable::MinecraftJava,
+        ConnectionTable::MinecraftBedrock,
+    ];
+
+    /// Program owning the table
+    pub fn program(self) -> &'static str {
+        match self {
+            ConnectionTable::Tcp => "xdp_tcp",
+            ConnectionTable::Quic => "xdp_quic",
+            ConnectionTable::MinecraftJava | ConnectionTable::MinecraftBedrock => "xdp_minecraft",
+        }
+    }
+
+    /// Name of the kernel map
+    pub fn map_name(self) -> &'static str {
+        match self {
+            ConnectionTable::Tcp => "TCP_CONNECTIONS",
+            ConnectionTable::Quic => "QUIC_CONNECTIONS",
+            ConnectionTable::MinecraftJava => "MC_JAVA_CONNECTIONS",
+            ConnectionTable::MinecraftBedrock => "MC_BEDROCK_CONNECTIONS",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tcp" => Some(ConnectionTable::Tcp),
+            "quic" => Some(ConnectionTable::Quic),
+            "minecraft_java" => Some(ConnectionTable::MinecraftJava),
+            "minecraft_bedrock" => Some(ConnectionTable::MinecraftBedrock),
+            _ => None,
+        }
+    }
+
+    /// Tables tracking the connections of a backend speaking `protocol`
+    ///
+    /// Kernel entries do not record their destination, so a backend's
+    /// connections are the ones in the tables of its protocol.
+    pub fn for_protocol(protocol: L7Protocol) -> Vec<ConnectionTable> {
+        match protocol {
+            L7Protocol::Http | L7Protocol::Http2 | L7Protocol::GenericTcp => {
+                vec![ConnectionTable::Tcp]
+            }
+            L7Protocol::Http3 | L7Protocol::Quic => vec![ConnectionTable::Quic],
+            L7Protocol::MinecraftJava => {
+                vec![ConnectionTable::Tcp, ConnectionTable::MinecraftJava]
+            }
+            L7Protocol::MinecraftBedrock => vec![ConnectionTable::MinecraftBedrock],
+            L7Protocol::GenericUdp => Vec::new(),
+            L7Protocol::Unspecified => ConnectionTable::ALL.to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for ConnectionTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConnectionTable::Tcp => "tcp",
+            ConnectionTable::Quic => "quic",
+            ConnectionTable::MinecraftJava => "minecraft_java",
+            ConnectionTable::MinecraftBedrock => "minecraft_bedrock",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Decoded connection entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionEntry {
+    pub table: ConnectionTable,
+    /// Key of the entry in its kernel map
+    pub key: u64,
+    /// Client address; QUIC keys only hold the low 32 bits of IPv6 clients
+    pub source: IpAddr,
+    /// Client port, unknown for TCP
+    pub source_port: Option<u16>,
+    pub state: &'static str,
+    pub flags: Vec<&'static str>,
+    pub packets: u64,
+    pub bytes: u64,
+    /// Time since the first packet, unknown for Minecraft Java
+    pub age_ms: Option<u64>,
+    /// Time since the last packet
+    pub idle_ms: u64,
+}
+
+/// Decode a `TCP_CONNECTIONS` entry at `now_ns` (kernel monotonic clock)
+pub fn decode_tcp(key: u64, conn: &TcpConnectionState, now_ns: u64) -> ConnectionEntry {
+    let state = match conn.state {
+        1 => "syn_sent",
+        2 => "syn_recv",
+        3 => "established",
+        4 => "fin_wait",
+        5 => "close_wait",
+        6 => "closing",
+        _ => "none",
+    };
+    ConnectionEntry {
+        table: ConnectionTable::Tcp,
+        key,
+        source: key_addr(conn.src_addr),
+        source_port: None,
+        state,
+        flags: flag_names(
+            u32::from(conn.flags),
+            &[
+                (0x01, "syn_cookie"),
+                (0x02, "validated"),
+                (0x04, "handshake_recorded"),
+                (0x08, "counted"),
+                (0x10, "timestamp"),
+            ],
+        ),
+        packets: conn.packets,
+        bytes: conn.bytes,
+        age_ms: Some(elapsed_ms(conn.first_seen, now_ns)),
+        idle_ms: elapsed_ms(conn.last_seen, now_ns),
+    }
+}
+
+/// Decode a `QUIC_CONNECTIONS` entry at `now_ns`
+pub fn decode_quic(key: u64, conn: &QuicConnectionState, now_ns: u64) -> ConnectionEntry {
+    let state = match conn.state {
+        1 => "initial",
+        2 => "handshake",
+        3 => "established",
+        4 => "closing",
+        _ => "none",
+    };
+    ConnectionEntry {
+        table: ConnectionTable::Quic,
+        key,
+        source: IpAddr::V4(Ipv4Addr::from((key >> 32) as u32)),
+        source_port: Some((key >> 16) as u16),
+        state,
+        flags: flag_names(
+            conn.flags,
+            &[
+                (0x01, "version_validated"),
+                (0x02, "address_validated"),
+                (0x04, "retry_sent"),
+                (0x08, "suspicious"),
+            ],
+        ),
+        packets: conn.packets,
+        bytes: conn.bytes,
+        age_ms: Some(elapsed_ms(conn.first_seen, now_ns)),
+        idle_ms: elapsed_ms(conn.last_seen, now_ns),
+    }
+}
+
+/// Decode a `MC_JAVA_CONNECTIONS` entry at `now_ns`
+pub fn decode_minecraft_java(key: u64, conn: &McConnectionState, now_ns: u64) -> ConnectionEntry {
+    let state = match conn.state {
+        1 => "status",
+        2 => "login",
+        3 => "configuration",
+        4 => "play",
+        5 => "transfer",
+        _ => "none",
+    };
+    ConnectionEntry {
+        table: ConnectionTable::MinecraftJava,
+        key,
+        source: IpAddr::V4(Ipv4Addr::from((key >> 32) as u32)),
+        source_port: Some(key as u16),
+        state,
+        flags: flag_names(
+            conn.flags,
+            &[
+                (0x0001, "encryption_pending"),
+                (0x0002, "encryption_enabled"),
+                (0x0004, "compression_enabled"),
+                (0x0008, "validated"),
+                (0x0010, "fragmented_pending"),
+                (0x0020, "login_start_received"),
+            ],
+        ),
+        packets: conn.packets,
+        bytes: conn.bytes,
+        age_ms: None,
+        idle_ms: elapsed_ms(conn.last_seen, now_ns),
+    }
+}
+
+/// Decode a `MC_BEDROCK_CONNECTIONS` entry at `now_ns`
+pub fn decode_minecraft_bedrock(
+    key: u64,
+    conn: &BedrockConnectionState,
+    now_ns: u64,
+) -> ConnectionEntry {
+    let state = match conn.state {
+        1 => "ping_sent",
+        2 => "connection_request_1",
+        3 => "connection_request_2",
+        4 => "connected",
+        5 => "established",
+        _ => "none",
+    };
+    ConnectionEntry {
+        table: ConnectionTable::MinecraftBedrock,
+        key,
+        source: IpAddr::V4(Ipv4Addr::from((key >> 32) as u32)),
+        source_port: Some(key as u16),
+        state,
+        flags: flag_names(
+            conn.flags,
+            &[
+                (0x0001, "guid_validated"),
+                (0x0002, "mtu_negotiated"),
+                (0x0004, "suspicious"),
+                (0x0008, "amplification_detected"),
+                (0x0010, "cookie_validated"),
+            ],
+        ),
+        packets: conn.packets,
+        bytes: conn.bytes_in,
+        age_ms: Some(elapsed_ms(conn.first_seen, now_ns)),
+        idle_ms: elapsed_ms(conn.last_seen, now_ns),
+    }
+}
+
+fn flag_names(flags: u32, names: &[(u32, &'static str)]) -> Vec<&'static str> {
+    names
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|&(_, name)| name)
+        .collect()
+}
+
+fn elapsed_ms(since_ns: u64, now_ns: u64) -> u64 {
+    now_ns.saturating_sub(since_ns) / 1_000_000
+}
+
+/// Conditions an entry must meet to be dumped
+///
+/// Parsed from space separated `name:value` terms, all of which must
+/// match: `table:quic state:established source:203.0.113.7 flag:validated`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionFilter {
+    pub tables: Vec<ConnectionTable>,
+    pub states: Vec<String>,
+    pub sources: Vec<IpAddr>,
+    pub flags: Vec<String>,
+}
+
+impl ConnectionFilter {
+    pub fn parse(filter: &str) -> Result<Self> {
+        let mut parsed = Self::default();
+        for term in filter.split_whitespace() {
+            let (name, value) = term.split_once(':').ok_or_else(|| {
+                Error::validation(format!("Filter term '{}' is not name:value", term))
+            })?;
+            match name {
+                "table" => parsed.tables.push(
+                    ConnectionTable::parse(value)
+                        .ok_or_else(|| Error::validation(format!("Unknown table '{}'", value)))?,
+                ),
+                "state" => parsed.states.push(value.to_string()),
+                "source" => parsed.sources.push(value.parse().map_err(|_| {
+                    Error::validation(format!("Invalid source address '{}'", value))
+                })?),
+                "flag" => parsed.flags.push(value.to_string()),
+                _ => {
+                    return Err(Error::validation(format!("Unknown filter term '{}'", name)));
+                }
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Whether the filter allows entries of `table`
+    pub fn allows_table(&self, table: ConnectionTable) -> bool {
+        self.tables.is_empty() || self.tables.contains(&table)
+    }
+
+    pub fn matches(&self, entry: &ConnectionEntry) -> bool {
+        self.allows_table(entry.table)
+            && (self.states.is_empty() || self.states.iter().any(|s| s == entry.state))
+            && (self.sources.is_empty() || self.sources.contains(&entry.source))
+            && self
+                .flags
+                .iter()
+                .all(|flag| entry.flags.contains(&flag.as_str()))
+    }
+}
+
+/// Position after the last entry of a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DumpCursor {
+    pub table: ConnectionTable,
+    pub key: u64,
+}
+
+impl DumpCursor {
+    pub fn parse(cursor: &str) -> Result<Self> {
+        cursor
+            .split_once(':')
+            .and_then(|(table, key)| {
+                Some(Self {
+                    table: ConnectionTable::parse(table)?,
+                    key: key.parse().ok()?,
+                })
+            })
+            .ok_or_else(|| Error::validation(format!("Invalid cursor '{}'", cursor)))
+    }
+}
+
+impl fmt::Display for DumpCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.table, self.key)
+    }
+}
+
+/// One page of a connection dump
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionPage {
+    pub entries: Vec<ConnectionEntry>,
+    /// Entries matching the filter across all pages
+    pub matched: usize,
+    /// Cursor of the next page, unset on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// Page of the entries matching `filter` that come after `cursor`
+///
+/// Entries are ordered by table and key. Connections opened between two
+/// pages show up on a later page if their key sorts after the cursor.
+pub fn page(
+    mut entries: Vec<ConnectionEntry>,
+    filter: &ConnectionFilter,
+    cursor: Option<DumpCursor>,
+    limit: usize,
+) -> ConnectionPage {
+    entries.retain(|entry| filter.matches(entry));
+    entries.sort_by_key(|entry| (entry.table, entry.key));
+    let matched = entries.len();
+
+    let start = cursor.map_or(0, |cursor| {
+        entries.partition_point(|entry| {
+            DumpCursor {
+                table: entry.table,
+                key: entry.key,
+            } <= cursor
+        })
+    });
+    let limit = limit.clamp(1, MAX_DUMP_LIMIT);
+    let end = (start + limit).min(entries.len());
+    let next_cursor = (end < entries.len()).then(|| {
+        DumpCursor {
+            table: entries[end - 1].table,
+            key: entries[end - 1].key,
+        }
+        .to_string()
+    });
+
+    ConnectionPage {
+        entries: entries.drain(start..end).collect(),
+        matched,
+        next_cursor,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    fn java_key(ip: Ipv4Addr, port: u16) -> u64 {
+        (u64::from(u32::from(ip)) << 32) | u64::from(port)
+    }
+
+    fn entries(count: u64) -> Vec<ConnectionEntry> {
+        (1..=count)
+            .map(|key| {
+                let conn = TcpConnectionState {
+                    state: if key % 2 == 0 { 3 } else { 1 },
+                    flags: 0x02,
+                    ..Default::default()
+                };
+                decode_tcp(key, &conn, 0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_layouts_match_kernel() {
+        assert_eq!(std::mem::size_of::<QuicConnectionState>(), 64);
+        assert_eq!(
+            std::mem::offset_of!(QuicConnectionState, response_bytes),
+            48
+        );
+        assert_eq!(std::mem::size_of::<McConnectionState>(), 48);
+        assert_eq!(std::mem::offset_of!(McConnectionState, flags), 32);
+        assert_eq!(std::mem::size_of::<BedrockConnectionState>(), 80);
+        assert_eq!(std::mem::offset_of!(BedrockConnectionState, flags), 72);
+    }
+
+    #[test]
+    fn test_decodes_entries() {
+        let src = Ipv4Addr::new(203, 0, 113, 7);
+        let conn = McConnectionState {
+            state: 4,
+            flags: 0x0002 | 0x0008,
+            packets: 12,
+            bytes: 4096,
+            last_seen: 1000 * MS,
+            ..Default::default()
+        };
+        let entry = decode_minecraft_java(java_key(src, 51234), &conn, 1500 * MS);
+        assert_eq!(entry.source, IpAddr::V4(src));
+        assert_eq!(entry.source_port, Some(51234));
+        assert_eq!(entry.state, "play");
+        assert_eq!(entry.flags, vec!["encryption_enabled", "validated"]);
+        assert_eq!(entry.idle_ms, 500);
+        assert_eq!(entry.age_ms, None);
+
+        let quic = QuicConnectionState {
+            state: 2,
+            first_seen: 100 * MS,
+            last_seen: 200 * MS,
+            ..Default::default()
+        };
+        let key = (u64::from(u32::from(src)) << 32) | (443 << 16) | 0xbeef;
+        let entry = decode_quic(key, &quic, 300 * MS);
+        assert_eq!(entry.source_port, Some(443));
+        assert_eq!(entry.state, "handshake");
+        assert_eq!(entry.age_ms, Some(200));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = ConnectionFilter::parse("table:tcp state:established flag:validated").unwrap();
+        let all = entries(4);
+        assert!(!filter.matches(&all[0]));
+        assert!(filter.matches(&all[1]));
+
+        assert!(ConnectionFilter::parse("state").is_err());
+        assert!(ConnectionFilter::parse("table:udp").is_err());
+        assert!(ConnectionFilter::parse("source:not-an-ip").is_err());
+        assert!(ConnectionFilter::parse("port:25565").is_err());
+        assert_eq!(
+            ConnectionFilter::parse("").unwrap(),
+            ConnectionFilter::default()
+        );
+    }
+
+    #[test]
+    fn test_pages_cover_every_entry_once() {
+        let filter = ConnectionFilter::default();
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = page(entries(25), &filter, cursor, 10);
+            assert_eq!(page.matched, 25);
+            seen.extend(page.entries.iter().map(|entry| entry.key));
+            match page.next_cursor {
+                Some(next) => cursor = Some(DumpCursor::parse(&next).unwrap()),
+                None => break,
+            }
+        }
+        assert_eq!(seen, (1..=25).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_closed_connection_does_not_shift_pages() {
+        let filter = ConnectionFilter::default();
+        let first = page(entries(20), &filter, None, 10);
+        let cursor = DumpCursor::parse(first.next_cursor.as_deref().unwrap()).unwrap();
+
+        // Connection 3 closed before the second page was requested
+        let mut remaining = entries(20);
+        remaining.remove(2);
+        let second = page(remaining, &filter, Some(cursor), 10);
+        assert_eq!(second.entries[0].key, 11);
+        assert!(second.next_cursor.is_none());
+    }
+}
@@ -0,0 +1,283 @@
+//! Per-source connection budget
+//!
+//! xdp_tcp gives every source a budget of `max_connections_per_ip` open
+//! connections: a SYN takes a slot in `TcpIpState::active_connections` and
+//! a FIN or RST from the client returns it. Connections that end without
+//! either, because the client vanished or the `TCP_CONNECTIONS` LRU evicted
+//! their entry, would hold their slot forever, so the worker regularly
+//! counts the connections still open per source and lowers the counters
+//! that are too high. This module mirrors the kernel layouts and holds the
+//! reconciliation, which never raises a counter: a SYN racing the sweep
+//! at worst gets its slot back early.
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+/// `TcpConnectionState::flags`: holds a slot of its source's budget
+/// (mirrors `CONN_FLAG_COUNTED`)
+pub const CONN_FLAG_COUNTED: u8 = 0x08;
+
+/// Time without packets after which an open connection is considered gone
+pub const DEFAULT_IDLE_TIMEOUT_NS: u64 = 15 * 60 * 1_000_000_000;
+
+/// Value of `TCP_CONNECTIONS` (mirrors `TcpConnectionState`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpConnectionState {
+    pub state: u8,
+    pub flags: u8,
+    pub _pad0: [u8; 2],
+    pub initial_seq: u32,
+    pub expected_ack: u32,
+    pub syn_fingerprint: u32,
+    pub packets: u64,
+    pub bytes: u64,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub window_scale: u8,
+    pub _pad2: u8,
+    pub mss: u16,
+    /// IPv4-mapped for IPv4
+    pub src_addr: [u8; 16],
+    /// Valid with the `CONN_FLAG_TIMESTAMP` flag (0x10)
+    pub syn_tsval: u32,
+    /// Bandwidth bucket (mirrors `FlowBucket`)
+    pub rate_tokens: u64,
+    pub rate_refilled_ns: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for TcpConnectionState {}
+
+/// Value of `TCP_IP_STATE_V*` (mirrors `TcpIpState`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpIpState {
+    pub packets: u64,
+    pub syn_packets: u64,
+    pub ack_packets: u64,
+    pub rst_packets: u64,
+    pub invalid_packets: u64,
+    pub window_start: u64,
+    pub last_seen: u64,
+    pub active_connections: u32,
+    pub _pad0: u32,
+    pub blocked_until: u64,
+    pub flags: u32,
+    pub _pad1: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for TcpIpState {}
+
+/// Changes of one reconciliation sweep
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    /// New `active_connections` of sources whose counter was too high
+    pub counters: Vec<([u8; 16], u32)>,
+    /// `TCP_CONNECTIONS` keys of counted connections idle for too long
+    pub stale: Vec<u64>,
+    /// Slots returned across all sources
+    pub returned: u64,
+}
+
+/// Outcome of a sweep over the kernel maps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReconcileReport {
+    /// Counted connections found open
+    pub open_connections: usize,
+    /// Sources whose counter was lowered
+    pub sources_lowered: usize,
+    pub slots_returned: u64,
+    /// Idle connections removed from the table
+    pub stale_removed: usize,
+}
+
+/// Count the connections still open per source and lower the counters
+/// above it
+///
+/// A counted connection without packets for `idle_timeout_ns` at `now_ns`
+/// (both on the kernel's monotonic clock) is gone and returns its slot.
+/// `counters` holds the `active_connections` of each source.
+pub fn reconcile(
+    connections: &[(u64, TcpConnectionState)],
+    counters: &[([u8; 16], u32)],
+    now_ns: u64,
+    idle_timeout_ns: u64,
+) -> Reconciliation {
+    let mut result = Reconciliation::default();
+
+    let open = open_connections(connections, now_ns, idle_timeout_ns);
+    for &(key, conn) in connections {
+        if conn.flags & CONN_FLAG_COUNTED != 0
+            && now_ns.saturating_sub(conn.last_seen) > idle_timeout_ns
+        {
+            result.stale.push(key);
+        }
+    }
+
+    for &(addr, active) in counters {
+        let counted = open.get(&addr).copied().unwrap_or(0);
+        if active > counted {
+            result.counters.push((addr, counted));
+            result.returned += u64::from(active - counted);
+        }
+    }
+
+    result
+}
+
+/// Counted connections still open per source
+pub fn open_connections(
+    connections: &[(u64, TcpConnectionState)],
+    now_ns: u64,
+    idle_timeout_ns: u64,
+) -> HashMap<[u8; 16], u32> {
+    let mut open: HashMap<[u8; 16], u32> = HashMap::new();
+    for (_, conn) in connections {
+        if conn.flags & CONN_FLAG_COUNTED != 0
+            && now_ns.saturating_sub(conn.last_seen) <= idle_timeout_ns
+        {
+            *open.entry(conn.src_addr).or_default() += 1;
+        }
+    }
+    open
+}
+
+/// Source key of an IPv4 `TCP_IP_STATE_V4` key (host byte order)
+pub fn v4_key(addr: u32) -> [u8; 16] {
+    Ipv4Addr::from(addr).to_ipv6_mapped().octets()
+}
+
+/// Time on the clock of `bpf_ktime_get_ns`
+pub fn monotonic_ns() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid, writable timespec.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+    const IDLE: u64 = 60 * SECOND;
+
+    fn addr(last: u8) -> [u8; 16] {
+        v4_key(u32::from(Ipv4Addr::new(203, 0, 113, last)))
+    }
+
+    fn conn(src: [u8; 16], last_seen: u64, counted: bool) -> TcpConnectionState {
+        TcpConnectionState {
+            state: 3,
+            flags: if counted { CONN_FLAG_COUNTED } else { 0 },
+            last_seen,
+            src_addr: src,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_layouts_match_kernel() {
+        assert_eq!(std::mem::size_of::<TcpConnectionState>(), 88);
+        assert_eq!(std::mem::offset_of!(TcpConnectionState, src_addr), 52);
+        assert_eq!(std::mem::offset_of!(TcpConnectionState, rate_tokens), 72);
+        assert_eq!(std::mem::size_of::<TcpIpState>(), 80);
+        assert_eq!(std::mem::offset_of!(TcpIpState, active_connections), 56);
+        assert_eq!(std::mem::offset_of!(TcpIpState, blocked_until), 64);
+    }
+
+    #[test]
+    fn test_lowers_leaked_counters() {
+        let now = 1000 * SECOND;
+        let connections = vec![
+            (1, conn(addr(1), now, true)),
+            (2, conn(addr(1), now - SECOND, true)),
+            // Vanished client, never closed
+            (3, conn(addr(1), now - 2 * IDLE, true)),
+            // Its slot was returned by a FIN already
+            (4, conn(addr(1), now, false)),
+        ];
+        // Five slots taken, two connections still open
+        let result = reconcile(&connections, &[(addr(1), 5)], now, IDLE);
+
+        assert_eq!(result.counters, vec![(addr(1), 2)]);
+        assert_eq!(result.stale, vec![3]);
+        assert_eq!(result.returned, 3);
+    }
+
+    #[test]
+    fn test_never_raises_counters() {
+        let now = 1000 * SECOND;
+        let connections = vec![
+            (1, conn(addr(1), now, true)),
+            (2, conn(addr(1), now, true)),
+            (3, conn(addr(2), now, true)),
+        ];
+        // The SYN of connection 2 raced the sweep; addr(2) returned its slot
+        // between the two reads
+        let result = reconcile(&connections, &[(addr(1), 1), (addr(2), 0)], now, IDLE);
+
+        assert!(result.counters.is_empty());
+        assert_eq!(result.returned, 0);
+    }
+
+    /// Many short connections per source, most closed cleanly and some
+    /// lost: the counters settle on the connections actually open
+    #[test]
+    fn test_churn_settles_on_open_connections() {
+        let mut now = 0;
+        let mut connections: Vec<(u64, TcpConnectionState)> = Vec::new();
+        let mut counters: HashMap<[u8; 16], u32> = HashMap::new();
+        let mut key = 0u64;
+
+        for round in 0..50u64 {
+            now += 10 * SECOND;
+            for source in 1..=20u8 {
+                for _ in 0..10 {
+                    key += 1;
+                    *counters.entry(addr(source)).or_default() += 1;
+                    let mut entry = conn(addr(source), now, true);
+                    match key % 10 {
+                        // Clean close: FIN returned the slot
+                        0..=6 => {
+                            entry.flags &= !CONN_FLAG_COUNTED;
+                            *counters.get_mut(&addr(source)).unwrap() -= 1;
+                        }
+                        // Client vanished without FIN or RST
+                        7 | 8 => entry.last_seen = now.saturating_sub(round * SECOND),
+                        // Still open
+                        _ => {}
+                    }
+                    connections.push((key, entry));
+                }
+            }
+
+            // The LRU evicts the oldest entries without telling anyone
+            if connections.len() > 5000 {
+                connections.drain(..connections.len() - 5000);
+            }
+
+            let snapshot: Vec<([u8; 16], u32)> = counters.iter().map(|(&a, &c)| (a, c)).collect();
+            let result = reconcile(&connections, &snapshot, now, IDLE);
+            for (addr, count) in result.counters {
+                counters.insert(addr, count);
+            }
+            connections.retain(|(key, _)| !result.stale.contains(key));
+        }
+
+        let open = open_connections(&connections, now, IDLE);
+        for source in 1..=20u8 {
+            assert_eq!(
+                counters[&addr(source)],
+                open.get(&addr(source)).copied().unwrap_or(0)
+            );
+            // Far below the 1500 connections opened per source
+            assert!(counters[&addr(source)] < 100);
+        }
+    }
+}
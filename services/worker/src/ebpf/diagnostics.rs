@@ -0,0 +1,198 @@
+//! Load diagnostics
+//!
+//! Programs can fail to load on some kernels: the object may need a map type
+//! the kernel lacks, or the verifier may reject a program it cannot prove
+//! safe. The loader keeps the last failure of each program with the step
+//! that failed, the section of the program and the tail of the verifier log,
+//! so support can read them from the status API or the control plane instead
+//! of the worker's logs.
+
+use aya::programs::{Program, ProgramError};
+use chrono::{DateTime, Utc};
+use pistonprotection_proto::worker;
+use serde::Serialize;
+
+/// Bytes of the verifier log kept; the explanation is at its end
+pub const MAX_VERIFIER_LOG_BYTES: usize = 16 * 1024;
+
+/// Verifier log lines that report statistics rather than the rejection
+const STATS_PREFIXES: &[&str] = &[
+    "processed ",
+    "verification time",
+    "stack depth",
+    "max_states_per_insn",
+];
+
+/// Step of loading a program that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadStage {
+    /// Parsing the object and creating its maps
+    Object,
+    /// The verifier rejected the program
+    Verifier,
+    /// Attaching the program to an interface
+    Attach,
+}
+
+impl LoadStage {
+    pub fn to_proto(self) -> worker::LoadStage {
+        match self {
+            LoadStage::Object => worker::LoadStage::Object,
+            LoadStage::Verifier => worker::LoadStage::Verifier,
+            LoadStage::Attach => worker::LoadStage::Attach,
+        }
+    }
+}
+
+/// Last failure to load a program
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadDiagnostic {
+    pub program: String,
+    /// Section of the program in the object, empty if the object failed
+    pub section: String,
+    pub stage: LoadStage,
+    pub error: String,
+    /// Verifier log line explaining the rejection
+    pub summary: String,
+    /// Tail of the verifier log
+    pub verifier_log: String,
+    pub kernel_release: String,
+    pub variant: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+impl LoadDiagnostic {
+    /// Diagnostic of a failure at `stage`; the verifier log is taken from
+    /// `error` if the kernel rejected the program.
+    pub fn new(
+        program: &str,
+        section: &str,
+        stage: LoadStage,
+        error: String,
+        verifier_log: Option<&str>,
+    ) -> Self {
+        let verifier_log = verifier_log.map(log_tail).unwrap_or_default();
+        Self {
+            program: program.to_string(),
+            section: section.to_string(),
+            stage,
+            summary: verifier_summary(&verifier_log),
+            error,
+            verifier_log,
+            kernel_release: String::new(),
+            variant: String::new(),
+            failed_at: Utc::now(),
+        }
+    }
+
+    pub fn to_proto(&self) -> worker::LoadDiagnostic {
+        worker::LoadDiagnostic {
+            program: self.program.clone(),
+            section: self.section.clone(),
+            stage: self.stage.to_proto().into(),
+            error: self.error.clone(),
+            summary: self.summary.clone(),
+            verifier_log: self.verifier_log.clone(),
+            kernel_release: self.kernel_release.clone(),
+            variant: self.variant.clone(),
+            failed_at: Some(self.failed_at.into()),
+        }
+    }
+}
+
+/// Verifier log of a program the kernel refused to load
+pub fn verifier_log(error: &ProgramError) -> Option<String> {
+    match error {
+        ProgramError::LoadError { verifier_log, .. } => Some(format!("{:?}", verifier_log)),
+        _ => None,
+    }
+}
+
+/// Section name of a program type
+pub fn program_section(program: &Program) -> &'static str {
+    match program {
+        Program::Xdp(_) => "xdp",
+        Program::SchedClassifier(_) => "classifier",
+        Program::SocketFilter(_) => "socket",
+        Program::TracePoint(_) => "tracepoint",
+        Program::KProbe(_) => "kprobe",
+        _ => "other",
+    }
+}
+
+/// Last line of a verifier log that is not a statistic, which is where the
+/// verifier explains a rejection
+pub fn verifier_summary(log: &str) -> String {
+    log.lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| {
+            !line.is_empty()
+                && !line.starts_with("...")
+                && !STATS_PREFIXES.iter().any(|prefix| line.starts_with(prefix))
+        })
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// End of a verifier log, cut at a line start to fit `MAX_VERIFIER_LOG_BYTES`
+pub fn log_tail(log: &str) -> String {
+    let log = log.trim_end_matches(['\0', '\n']);
+    if log.len() <= MAX_VERIFIER_LOG_BYTES {
+        return log.to_string();
+    }
+    let mut start = log.len() - MAX_VERIFIER_LOG_BYTES;
+    while !log.is_char_boundary(start) {
+        start += 1;
+    }
+    let tail = &log[start..];
+    let tail = tail.split_once('\n').map_or(tail, |(_, rest)| rest);
+    format!("...\n{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "func#0 @0\n\
+        0: (61) r2 = *(u32 *)(r1 +0)\n\
+        1: (71) r3 = *(u8 *)(r2 +12)\n\
+        R2 invalid mem access 'scalar'\n\
+        processed 2 insns (limit 1000000) max_states_per_insn 0 total_states 0\n\
+        verification time 17 usec\n\
+        stack depth 0\n";
+
+    #[test]
+    fn test_verifier_summary() {
+        assert_eq!(verifier_summary(LOG), "R2 invalid mem access 'scalar'");
+        assert_eq!(verifier_summary(""), "");
+    }
+
+    #[test]
+    fn test_log_tail_keeps_end() {
+        assert_eq!(log_tail(LOG), LOG.trim_end());
+
+        let long = format!("{}{}", "0: (b7) r0 = 2\n".repeat(2000), LOG);
+        let tail = log_tail(&long);
+        assert!(tail.len() <= MAX_VERIFIER_LOG_BYTES + 4);
+        assert!(tail.starts_with("...\n0: (b7)"));
+        assert!(tail.ends_with("stack depth 0"));
+        assert_eq!(verifier_summary(&tail), "R2 invalid mem access 'scalar'");
+    }
+
+    #[test]
+    fn test_diagnostic_to_proto() {
+        let diagnostic = LoadDiagnostic::new(
+            "xdp_filter",
+            "xdp",
+            LoadStage::Verifier,
+            "the BPF_PROG_LOAD syscall failed".to_string(),
+            Some(LOG),
+        );
+        let proto = diagnostic.to_proto();
+        assert_eq!(proto.stage(), worker::LoadStage::Verifier);
+        assert_eq!(proto.summary, "R2 invalid mem access 'scalar'");
+        assert!(proto.failed_at.is_some());
+    }
+}
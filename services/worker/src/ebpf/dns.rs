@@ -0,0 +1,148 @@
+//! DNS query budgets of protected resolvers
+//!
+//! Customers protecting DNS resolvers need limits per client rather than per
+//! connection: xdp_tcp counts the DNS-over-TCP queries sent to protected TCP
+//! ports flagged as DNS, and xdp_http the DNS-over-HTTPS requests for
+//! `/dns-query`, each against a per-second budget of the client. The DNS
+//! ports are only flagged while they are also protected, so the budget
+//! follows the protected ports set by the operator. Queries and drops are
+//! counted apart from the generic TCP and HTTP counters. This module mirrors
+//! the kernel layout of `DNS_BUDGET`.
+
+use super::protected_ports::PROTECTED;
+use pistonprotection_common::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Flag of protected TCP ports serving DNS (mirrors `dns::PORT_DNS`)
+pub const PORT_DNS: u32 = 0x2;
+
+/// Default DNS-over-TCP port
+pub const DNS_PORT: u16 = 53;
+
+/// Value of `DNS_BUDGET` (mirrors `DnsBudgetConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DnsBudgetConfig {
+    pub tcp_qps: u32,
+    pub doh_qps: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u32` fields, no padding.
+unsafe impl aya::Pod for DnsBudgetConfig {}
+
+/// Per-client DNS query budgets of the worker
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsBudget {
+    /// Protected TCP ports whose payload is DNS-over-TCP
+    #[serde(default = "default_tcp_ports")]
+    pub tcp_ports: BTreeSet<u16>,
+    /// DNS-over-TCP queries per second of each client (0 = unlimited)
+    #[serde(default)]
+    pub tcp_qps: u32,
+    /// DNS-over-HTTPS requests per second of each client (0 = unlimited)
+    #[serde(default)]
+    pub doh_qps: u32,
+}
+
+fn default_tcp_ports() -> BTreeSet<u16> {
+    [DNS_PORT].into_iter().collect()
+}
+
+impl Default for DnsBudget {
+    fn default() -> Self {
+        Self {
+            tcp_ports: default_tcp_ports(),
+            tcp_qps: 0,
+            doh_qps: 0,
+        }
+    }
+}
+
+impl DnsBudget {
+    /// Load the budgets from environment variables
+    ///
+    /// `PISTON_DNS_TCP_QPS` and `PISTON_DNS_DOH_QPS` set the per-client
+    /// budgets, `PISTON_DNS_TCP_PORTS` the comma-separated DNS ports (53 by
+    /// default).
+    pub fn from_env() -> Self {
+        let mut budget = Self::default();
+
+        let var = |name: &str| std::env::var(name).ok()?.parse::<u32>().ok();
+        if let Some(qps) = var("PISTON_DNS_TCP_QPS") {
+            budget.tcp_qps = qps;
+        }
+        if let Some(qps) = var("PISTON_DNS_DOH_QPS") {
+            budget.doh_qps = qps;
+        }
+        if let Ok(ports) = std::env::var("PISTON_DNS_TCP_PORTS") {
+            budget.tcp_ports = ports
+                .split(',')
+                .filter_map(|port| port.trim().parse().ok())
+                .filter(|&port| port != 0)
+                .collect();
+        }
+
+        budget
+    }
+
+    /// Check the DNS ports can be flagged in the protected port map
+    pub fn validate(&self) -> Result<()> {
+        if self.tcp_ports.contains(&0) {
+            return Err(Error::validation("Port 0 cannot serve DNS"));
+        }
+        Ok(())
+    }
+
+    /// `DNS_BUDGET` contents of the budget
+    pub fn config(&self) -> DnsBudgetConfig {
+        DnsBudgetConfig {
+            tcp_qps: self.tcp_qps,
+            doh_qps: self.doh_qps,
+        }
+    }
+
+    /// `TCP_PROTECTED_PORTS` value of a protected port
+    pub fn port_flags(&self, port: u16) -> u32 {
+        if self.tcp_ports.contains(&port) {
+            PROTECTED | PORT_DNS
+        } else {
+            PROTECTED
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_flags() {
+        let budget = DnsBudget::default();
+        assert_eq!(budget.port_flags(53), PROTECTED | PORT_DNS);
+        assert_eq!(budget.port_flags(443), PROTECTED);
+
+        let budget = DnsBudget {
+            tcp_ports: BTreeSet::new(),
+            ..budget
+        };
+        assert_eq!(budget.port_flags(53), PROTECTED);
+    }
+
+    #[test]
+    fn test_parse() {
+        let budget: DnsBudget = serde_json::from_str(r#"{"tcpQps": 20, "dohQps": 10}"#).unwrap();
+        assert_eq!(budget.tcp_ports, default_tcp_ports());
+        assert_eq!(
+            budget.config(),
+            DnsBudgetConfig {
+                tcp_qps: 20,
+                doh_qps: 10
+            }
+        );
+
+        let budget: DnsBudget = serde_json::from_str(r#"{"tcpPorts": [0, 5353]}"#).unwrap();
+        assert!(budget.validate().is_err());
+    }
+}
@@ -0,0 +1,471 @@
+//! Drop event aggregation
+//!
+//! Every XDP program submits each packet it drops to its `DROP_EVENTS` ring
+//! buffer (perf event array in the compat variant) with the source, drop
+//! reason and size. During a flood that is one event per packet, far more
+//! than anything downstream can use, so the worker coalesces the events by
+//! (source, reason) and flushes one summary per key with packet and byte
+//! totals every flush interval. Summaries are counted in Prometheus and the
+//! most recent ones are kept for `/status/drop-events`.
+//!
+//! The pending keys are capped at a high-water mark: once it is reached,
+//! events of keys not seen in the current window are shed, counted per
+//! reason only, while known keys keep aggregating. A flood of spoofed
+//! sources thus costs bounded memory and still shows up in the totals.
+
+use super::stats::drop_reason_name;
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use pistonprotection_common::metrics::{DROP_EVENT_SUMMARIES_TOTAL, DROP_EVENTS_TOTAL};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Duration;
+
+/// Default interval between flushes
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default cap on the (source, reason) keys aggregated between flushes
+pub const DEFAULT_HIGH_WATER_MARK: usize = 65_536;
+
+/// Default number of flushed summaries kept for the status endpoint
+pub const DEFAULT_RECENT_SUMMARIES: usize = 1024;
+
+/// Bounds of the flush interval
+pub const MIN_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+pub const MAX_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Destination ports listed per summary
+pub const MAX_SUMMARY_PORTS: usize = 8;
+
+/// Dropped packet (mirrors `DropEvent`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DropEvent {
+    pub timestamp_ns: u64,
+    /// Source address, IPv4 as an IPv4-mapped IPv6 address
+    pub src_addr: [u8; 16],
+    pub bytes: u32,
+    pub reason: u32,
+    pub dst_port: u16,
+    pub protocol: u8,
+    pub _pad: [u8; 5],
+}
+
+// SAFETY: `#[repr(C)]` struct of integer fields and byte arrays, with the
+// padding spelled out.
+unsafe impl aya::Pod for DropEvent {}
+
+impl DropEvent {
+    /// Decode an event from a ring buffer or perf event record
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < std::mem::size_of::<Self>() {
+            return None;
+        }
+        // SAFETY: length checked above and every bit pattern is a valid `DropEvent`.
+        Some(unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Self) })
+    }
+
+    /// Source address of the dropped packet (unspecified if the program
+    /// dropped it before parsing the IP header)
+    pub fn src(&self) -> IpAddr {
+        Ipv6Addr::from(self.src_addr).to_canonical()
+    }
+}
+
+/// Flush interval, high-water mark and summaries kept
+#[derive(Debug, Clone)]
+pub struct DropEventConfig {
+    pub flush_interval: Duration,
+    pub high_water_mark: usize,
+    pub recent_summaries: usize,
+}
+
+impl Default for DropEventConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            high_water_mark: DEFAULT_HIGH_WATER_MARK,
+            recent_summaries: DEFAULT_RECENT_SUMMARIES,
+        }
+    }
+}
+
+impl DropEventConfig {
+    /// Load from `PISTON_DROP_EVENT_FLUSH_MS`, `PISTON_DROP_EVENT_HIGH_WATER`
+    /// and `PISTON_DROP_EVENT_RECENT`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(ms) = std::env::var("PISTON_DROP_EVENT_FLUSH_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            config.flush_interval =
+                Duration::from_millis(ms).clamp(MIN_FLUSH_INTERVAL, MAX_FLUSH_INTERVAL);
+        }
+        if let Some(keys) = std::env::var("PISTON_DROP_EVENT_HIGH_WATER")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            config.high_water_mark = keys.max(1);
+        }
+        if let Some(recent) = std::env::var("PISTON_DROP_EVENT_RECENT")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            config.recent_summaries = recent;
+        }
+
+        config
+    }
+}
+
+/// Drops of one source for one reason over a flush window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DropSummary {
+    pub src: IpAddr,
+    pub reason: &'static str,
+    pub packets: u64,
+    pub bytes: u64,
+    /// First destination ports seen, up to `MAX_SUMMARY_PORTS`
+    pub dst_ports: Vec<u16>,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+}
+
+/// Drops shed for one reason over a flush window
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShedSummary {
+    pub reason: &'static str,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Output of a flush
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DropFlush {
+    /// Ordered by packets, largest first
+    pub summaries: Vec<DropSummary>,
+    pub shed: Vec<ShedSummary>,
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    packets: u64,
+    bytes: u64,
+    dst_ports: Vec<u16>,
+}
+
+/// Coalesces drop events by (source, reason) between flushes
+pub struct DropAggregator {
+    high_water_mark: usize,
+    pending: HashMap<(IpAddr, u32), Pending>,
+    /// Packets and bytes shed per reason
+    shed: BTreeMap<u32, (u64, u64)>,
+    window_start: DateTime<Utc>,
+}
+
+impl DropAggregator {
+    pub fn new(high_water_mark: usize, now: DateTime<Utc>) -> Self {
+        Self {
+            high_water_mark,
+            pending: HashMap::new(),
+            shed: BTreeMap::new(),
+            window_start: now,
+        }
+    }
+
+    /// Add an event; false if it was shed
+    pub fn record(&mut self, event: &DropEvent) -> bool {
+        let key = (event.src(), event.reason);
+        if self.pending.len() >= self.high_water_mark && !self.pending.contains_key(&key) {
+            let shed = self.shed.entry(event.reason).or_default();
+            shed.0 += 1;
+            shed.1 += u64::from(event.bytes);
+            return false;
+        }
+
+        let pending = self.pending.entry(key).or_default();
+        pending.packets += 1;
+        pending.bytes += u64::from(event.bytes);
+        if pending.dst_ports.len() < MAX_SUMMARY_PORTS
+            && !pending.dst_ports.contains(&event.dst_port)
+        {
+            pending.dst_ports.push(event.dst_port);
+        }
+        true
+    }
+
+    /// Keys aggregated since the last flush
+    pub fn pending_keys(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Summarize the window ending at `now` and start a new one
+    pub fn flush(&mut self, now: DateTime<Utc>) -> DropFlush {
+        let window_start = std::mem::replace(&mut self.window_start, now);
+
+        let mut summaries: Vec<DropSummary> = self
+            .pending
+            .drain()
+            .map(|((src, reason), pending)| DropSummary {
+                src,
+                reason: drop_reason_name(reason),
+                packets: pending.packets,
+                bytes: pending.bytes,
+                dst_ports: pending.dst_ports,
+                window_start,
+                window_end: now,
+            })
+            .collect();
+        summaries.sort_by(|a, b| b.packets.cmp(&a.packets).then(a.src.cmp(&b.src)));
+
+        let shed = std::mem::take(&mut self.shed)
+            .into_iter()
+            .map(|(reason, (packets, bytes))| ShedSummary {
+                reason: drop_reason_name(reason),
+                packets,
+                bytes,
+            })
+            .collect();
+
+        DropFlush { summaries, shed }
+    }
+}
+
+/// Drop event status for the HTTP API
+#[derive(Debug, Clone, Serialize)]
+pub struct DropEventStatus {
+    pub flush_interval_ms: u64,
+    pub high_water_mark: usize,
+    pub pending_keys: usize,
+    pub events_total: u64,
+    pub shed_total: u64,
+    /// Summaries of the most recent flushes, newest first
+    pub recent: Vec<DropSummary>,
+    /// Shed drops of the last flush that shed any
+    pub last_shed: Vec<ShedSummary>,
+}
+
+#[derive(Default)]
+struct Recent {
+    summaries: VecDeque<DropSummary>,
+    last_shed: Vec<ShedSummary>,
+    events_total: u64,
+    shed_total: u64,
+}
+
+/// Drop event aggregation shared by the drain task and the HTTP API
+pub struct DropEvents {
+    config: DropEventConfig,
+    aggregator: Mutex<DropAggregator>,
+    recent: RwLock<Recent>,
+}
+
+impl DropEvents {
+    pub fn new(config: DropEventConfig) -> Self {
+        Self {
+            aggregator: Mutex::new(DropAggregator::new(config.high_water_mark, Utc::now())),
+            recent: RwLock::new(Recent::default()),
+            config,
+        }
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        self.config.flush_interval
+    }
+
+    /// Aggregate events drained from a program
+    pub fn ingest(&self, events: &[DropEvent]) {
+        if events.is_empty() {
+            return;
+        }
+
+        let shed = {
+            let mut aggregator = self.aggregator.lock();
+            events
+                .iter()
+                .filter(|event| !aggregator.record(event))
+                .count() as u64
+        };
+        let aggregated = events.len() as u64 - shed;
+
+        DROP_EVENTS_TOTAL
+            .with_label_values(&["aggregated"])
+            .inc_by(aggregated as f64);
+        if shed > 0 {
+            DROP_EVENTS_TOTAL
+                .with_label_values(&["shed"])
+                .inc_by(shed as f64);
+        }
+
+        let mut recent = self.recent.write();
+        recent.events_total += events.len() as u64;
+        recent.shed_total += shed;
+    }
+
+    /// Flush the current window and export its summaries
+    pub fn flush(&self, now: DateTime<Utc>) -> DropFlush {
+        let flush = self.aggregator.lock().flush(now);
+
+        for summary in &flush.summaries {
+            DROP_EVENT_SUMMARIES_TOTAL
+                .with_label_values(&[summary.reason])
+                .inc();
+        }
+
+        let mut recent = self.recent.write();
+        for summary in flush.summaries.iter().rev() {
+            recent.summaries.push_front(summary.clone());
+        }
+        let limit = self.config.recent_summaries;
+        recent.summaries.truncate(limit);
+        if !flush.shed.is_empty() {
+            recent.last_shed = flush.shed.clone();
+        }
+
+        flush
+    }
+
+    pub fn status(&self) -> DropEventStatus {
+        let pending_keys = self.aggregator.lock().pending_keys();
+        let recent = self.recent.read();
+        DropEventStatus {
+            flush_interval_ms: self.config.flush_interval.as_millis() as u64,
+            high_water_mark: self.config.high_water_mark,
+            pending_keys,
+            events_total: recent.events_total,
+            shed_total: recent.shed_total,
+            recent: recent.summaries.iter().cloned().collect(),
+            last_shed: recent.last_shed.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn t0() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+    }
+
+    fn event(src: &str, reason: u32, bytes: u32, dst_port: u16) -> DropEvent {
+        let src_addr = match src.parse::<IpAddr>().unwrap() {
+            IpAddr::V4(v4) => v4.to_ipv6_mapped().octets(),
+            IpAddr::V6(v6) => v6.octets(),
+        };
+        DropEvent {
+            timestamp_ns: 0,
+            src_addr,
+            bytes,
+            reason,
+            dst_port,
+            protocol: 17,
+            _pad: [0; 5],
+        }
+    }
+
+    #[test]
+    fn test_event_layout() {
+        assert_eq!(std::mem::size_of::<DropEvent>(), 40);
+
+        let event = event("198.51.100.7", 5, 1200, 25565);
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &event as *const DropEvent as *const u8,
+                std::mem::size_of::<DropEvent>(),
+            )
+        };
+        let decoded = DropEvent::from_bytes(bytes).unwrap();
+        assert_eq!(decoded, event);
+        assert_eq!(decoded.src(), "198.51.100.7".parse::<IpAddr>().unwrap());
+        assert!(DropEvent::from_bytes(&bytes[..39]).is_none());
+    }
+
+    #[test]
+    fn test_coalesces_by_source_and_reason() {
+        let mut aggregator = DropAggregator::new(16, t0());
+        for _ in 0..1000 {
+            assert!(aggregator.record(&event("198.51.100.7", 5, 100, 53)));
+        }
+        aggregator.record(&event("198.51.100.7", 21, 60, 53));
+        aggregator.record(&event("2001:db8::1", 5, 80, 443));
+        aggregator.record(&event("2001:db8::1", 5, 80, 8443));
+        assert_eq!(aggregator.pending_keys(), 3);
+
+        let end = t0() + chrono::Duration::seconds(1);
+        let flush = aggregator.flush(end);
+        assert_eq!(flush.summaries.len(), 3);
+        assert!(flush.shed.is_empty());
+
+        let top = &flush.summaries[0];
+        assert_eq!(top.src, "198.51.100.7".parse::<IpAddr>().unwrap());
+        assert_eq!(top.reason, "udp_flood");
+        assert_eq!((top.packets, top.bytes), (1000, 100_000));
+        assert_eq!(top.dst_ports, vec![53]);
+        assert_eq!((top.window_start, top.window_end), (t0(), end));
+
+        let v6 = flush.summaries.iter().find(|s| s.src.is_ipv6()).unwrap();
+        assert_eq!(v6.dst_ports, vec![443, 8443]);
+
+        // The next window starts empty
+        assert_eq!(aggregator.pending_keys(), 0);
+        assert!(aggregator.flush(end).summaries.is_empty());
+    }
+
+    #[test]
+    fn test_sheds_new_keys_past_high_water_mark() {
+        let mut aggregator = DropAggregator::new(2, t0());
+        assert!(aggregator.record(&event("198.51.100.1", 5, 100, 53)));
+        assert!(aggregator.record(&event("198.51.100.2", 5, 100, 53)));
+
+        // New sources are shed, known ones keep aggregating
+        assert!(!aggregator.record(&event("198.51.100.3", 5, 100, 53)));
+        assert!(!aggregator.record(&event("198.51.100.4", 2, 60, 80)));
+        assert!(aggregator.record(&event("198.51.100.1", 5, 100, 53)));
+        assert_eq!(aggregator.pending_keys(), 2);
+
+        let flush = aggregator.flush(t0());
+        assert_eq!(flush.summaries[0].packets, 2);
+        assert_eq!(
+            flush.shed,
+            vec![
+                ShedSummary {
+                    reason: "syn_flood",
+                    packets: 1,
+                    bytes: 60,
+                },
+                ShedSummary {
+                    reason: "udp_flood",
+                    packets: 1,
+                    bytes: 100,
+                },
+            ]
+        );
+
+        // Shedding ends with the window
+        assert!(aggregator.record(&event("198.51.100.3", 5, 100, 53)));
+    }
+
+    #[test]
+    fn test_recent_summaries_bounded() {
+        let events = DropEvents::new(DropEventConfig {
+            recent_summaries: 3,
+            ..Default::default()
+        });
+        let batch: Vec<_> = (1..=5)
+            .map(|i| event(&format!("198.51.100.{}", i), 5, 100, 53))
+            .collect();
+        events.ingest(&batch);
+        events.flush(t0());
+
+        let status = events.status();
+        assert_eq!(status.events_total, 5);
+        assert_eq!(status.shed_total, 0);
+        assert_eq!(status.pending_keys, 0);
+        assert_eq!(status.recent.len(), 3);
+    }
+}
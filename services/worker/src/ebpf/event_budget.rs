@@ -0,0 +1,204 @@
+//! In-kernel event budget
+//!
+//! The XDP programs rate limit the drop events and flow samples they submit
+//! with a token bucket per CPU (`EVENT_BUDGET`), complementing the drop event
+//! aggregation in userspace: an attack cannot flood the event buffers, and
+//! what is shed is chosen by priority. Drops that decided a new block always
+//! get through, other drop events need a token and samples need more than
+//! the low reserve, so they go first. This module writes the budget to
+//! `EVENT_BUDGET_CONFIG` and reads the per-class counts of emitted and
+//! suppressed events.
+
+use pistonprotection_common::metrics::XDP_EVENT_BUDGET_EVENTS;
+use serde::Serialize;
+
+/// Priority classes (mirrors `event_budget::CLASSES`)
+pub const CLASSES: usize = 3;
+
+/// Names of the priority classes, by index (mirrors
+/// `event_budget::PRIORITY_*`)
+pub const PRIORITIES: [&str; CLASSES] = ["low", "normal", "high"];
+
+/// Event budget of each CPU (mirrors `EventBudgetConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EventBudgetConfig {
+    /// Tokens added per second
+    pub rate: u32,
+    /// Bucket capacity
+    pub burst: u32,
+    /// Tokens kept back from low-priority events
+    pub low_reserve: u32,
+    #[serde(skip)]
+    pub _pad: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of four `u32` fields, no padding.
+unsafe impl aya::Pod for EventBudgetConfig {}
+
+impl Default for EventBudgetConfig {
+    /// Same budget as the kernel fallback (`EventBudgetConfig::DEFAULT`)
+    fn default() -> Self {
+        Self {
+            rate: 4096,
+            burst: 8192,
+            low_reserve: 4096,
+            _pad: 0,
+        }
+    }
+}
+
+impl EventBudgetConfig {
+    /// Load the budget from environment variables
+    ///
+    /// `PISTON_EVENT_RATE` and `PISTON_EVENT_BURST` set the tokens per second
+    /// and the capacity of each CPU's bucket, `PISTON_EVENT_LOW_RESERVE` the
+    /// tokens samples may not take.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        let var = |name: &str| std::env::var(name).ok()?.parse::<u32>().ok();
+        if let Some(rate) = var("PISTON_EVENT_RATE") {
+            config.rate = rate;
+        }
+        if let Some(burst) = var("PISTON_EVENT_BURST") {
+            config.burst = burst;
+        }
+        if let Some(reserve) = var("PISTON_EVENT_LOW_RESERVE") {
+            config.low_reserve = reserve;
+        }
+
+        config.sanitized()
+    }
+
+    /// Budget the kernel can apply: a rate of 0 would select the kernel
+    /// defaults, and a reserve of the whole bucket would shed every sample
+    pub fn sanitized(mut self) -> Self {
+        self.rate = self.rate.max(1);
+        self.burst = self.burst.max(1);
+        self.low_reserve = self.low_reserve.min(self.burst - 1);
+        self
+    }
+}
+
+/// Token bucket and event counters of one CPU (mirrors `EventBudget`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventBudget {
+    pub tokens: u64,
+    pub last_refill_ns: u64,
+    pub emitted: [u64; CLASSES],
+    pub suppressed: [u64; CLASSES],
+}
+
+// SAFETY: `#[repr(C)]` struct of `u64` fields and arrays, no padding.
+unsafe impl aya::Pod for EventBudget {}
+
+/// Events of one priority class since the program was loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EventClassCounts {
+    pub priority: &'static str,
+    pub emitted: u64,
+    pub suppressed: u64,
+}
+
+/// Event budget of one program
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EventBudgetReport {
+    pub program: String,
+    pub config: EventBudgetConfig,
+    pub classes: Vec<EventClassCounts>,
+}
+
+impl EventBudgetReport {
+    /// Sum the per-CPU counters of a program's `EVENT_BUDGET` map
+    pub fn from_map(program: &str, config: EventBudgetConfig, per_cpu: &[EventBudget]) -> Self {
+        let classes = PRIORITIES
+            .iter()
+            .enumerate()
+            .map(|(class, priority)| EventClassCounts {
+                priority,
+                emitted: per_cpu.iter().map(|cpu| cpu.emitted[class]).sum(),
+                suppressed: per_cpu.iter().map(|cpu| cpu.suppressed[class]).sum(),
+            })
+            .collect();
+
+        Self {
+            program: program.to_string(),
+            config,
+            classes,
+        }
+    }
+
+    /// Export the counts as the `xdp_event_budget_events` gauges
+    pub fn export_metrics(&self) {
+        for class in &self.classes {
+            for (outcome, count) in [("emitted", class.emitted), ("suppressed", class.suppressed)] {
+                XDP_EVENT_BUDGET_EVENTS
+                    .with_label_values(&[&self.program, class.priority, outcome])
+                    .set(count as f64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitized() {
+        let config = EventBudgetConfig {
+            rate: 0,
+            burst: 100,
+            low_reserve: 500,
+            _pad: 0,
+        }
+        .sanitized();
+        assert_eq!(config.rate, 1);
+        assert_eq!(config.low_reserve, 99);
+
+        let config = EventBudgetConfig {
+            burst: 0,
+            ..Default::default()
+        }
+        .sanitized();
+        assert_eq!(config.burst, 1);
+        assert_eq!(config.low_reserve, 0);
+    }
+
+    #[test]
+    fn test_report_from_map() {
+        let cpu = |emitted, suppressed| EventBudget {
+            emitted,
+            suppressed,
+            ..Default::default()
+        };
+        let report = EventBudgetReport::from_map(
+            "xdp_udp",
+            EventBudgetConfig::default(),
+            &[cpu([10, 20, 1], [5, 0, 0]), cpu([0, 7, 2], [40, 3, 0])],
+        );
+
+        assert_eq!(
+            report.classes,
+            vec![
+                EventClassCounts {
+                    priority: "low",
+                    emitted: 10,
+                    suppressed: 45,
+                },
+                EventClassCounts {
+                    priority: "normal",
+                    emitted: 27,
+                    suppressed: 3,
+                },
+                EventClassCounts {
+                    priority: "high",
+                    emitted: 3,
+                    suppressed: 0,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Conntrack bypass of validated flows
+//!
+//! On proxy nodes every forwarded packet pays for conntrack and the stateful
+//! iptables chains, even when xdp_tcp already saw its connection complete a
+//! handshake. XDP runs before the skb exists and cannot mark packets itself,
+//! so xdp_tcp records established flows in the pinned `FLOW_MARKS` map and
+//! the tc_flowmark classifier, attached to the clsact hooks of the same
+//! interfaces, sets the configured mark bits on their packets. A raw table
+//! rule on the node then skips conntrack for them, e.g.
+//!
+//! ```text
+//! iptables -t raw -A PREROUTING -m mark --mark 0x100/0x100 -j NOTRACK
+//! ```
+//!
+//! The rule is the operator's to install, as only they know whether the
+//! node NATs these flows (NAT needs conntrack; the bypass is for routed
+//! flows). Ingress marking is what conntrack sees; egress marking is for
+//! qdiscs and filters after the stack. This module mirrors the kernel
+//! layouts and validates the bypass settings of a node pool.
+
+use aya::programs::TcAttachType;
+use pistonprotection_common::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Name of the TC companion program
+pub const PROGRAM: &str = "tc_flowmark";
+
+/// Value of `FLOW_MARK_CONFIG` (mirrors `FlowMarkConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlowMarkConfig {
+    /// Record and mark validated flows (0 = off)
+    pub enabled: u32,
+    /// Mark bits set on the packets of recorded flows
+    pub mark: u32,
+    /// Bits of the skb mark owned by the bypass
+    pub mask: u32,
+    pub _pad: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of four `u32` fields, no padding.
+unsafe impl aya::Pod for FlowMarkConfig {}
+
+impl FlowMarkConfig {
+    /// Bypass switched on with `mark` within `mask`
+    pub fn enabled(mark: u32, mask: u32) -> Self {
+        Self {
+            enabled: 1,
+            mark,
+            mask,
+            _pad: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != 0
+    }
+}
+
+/// Value of `FLOW_MARK_STATS` (mirrors `FlowMarkStats`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct FlowMarkStats {
+    /// TCP packets looked up
+    pub packets: u64,
+    /// Packets of recorded flows, which were marked
+    pub marked: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u64` fields, no padding.
+unsafe impl aya::Pod for FlowMarkStats {}
+
+impl FlowMarkStats {
+    /// Sum of two counters
+    pub fn add(&self, other: &Self) -> Self {
+        Self {
+            packets: self.packets.wrapping_add(other.packets),
+            marked: self.marked.wrapping_add(other.marked),
+        }
+    }
+}
+
+/// clsact hooks tc_flowmark is attached to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TcDirection {
+    #[default]
+    Ingress,
+    Egress,
+    Both,
+}
+
+impl TcDirection {
+    pub fn attach_types(&self) -> &'static [TcAttachType] {
+        match self {
+            TcDirection::Ingress => &[TcAttachType::Ingress],
+            TcDirection::Egress => &[TcAttachType::Egress],
+            TcDirection::Both => &[TcAttachType::Ingress, TcAttachType::Egress],
+        }
+    }
+}
+
+/// Conntrack bypass of a node pool: tc_flowmark attached next to xdp_tcp
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowMarkSpec {
+    /// Version of the tc_flowmark object file in the program directory
+    pub version: String,
+    /// Expected SHA-256 of the object file (hex); not checked when unset
+    #[serde(default)]
+    pub digest: Option<String>,
+    /// Mark bits set on the packets of validated flows
+    pub mark: u32,
+    /// Bits of the skb mark owned by the bypass; the mark bits when unset
+    #[serde(default)]
+    pub mask: Option<u32>,
+    #[serde(default)]
+    pub direction: TcDirection,
+}
+
+impl FlowMarkSpec {
+    /// Check the mark can be told apart from unmarked packets and stays
+    /// within its mask, leaving other users of the skb mark alone
+    pub fn validate(&self) -> Result<()> {
+        if self.version.is_empty() {
+            return Err(Error::validation("Flow mark needs a program version"));
+        }
+        if self.mark == 0 {
+            return Err(Error::validation("Flow mark must set at least one bit"));
+        }
+        if self.mark & !self.mask() != 0 {
+            return Err(Error::validation(format!(
+                "Flow mark {:#x} has bits outside its mask {:#x}",
+                self.mark,
+                self.mask()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn mask(&self) -> u32 {
+        self.mask.unwrap_or(self.mark)
+    }
+
+    /// `FLOW_MARK_CONFIG` contents of the spec
+    pub fn config(&self) -> FlowMarkConfig {
+        FlowMarkConfig::enabled(self.mark, self.mask())
+    }
+}
+
+/// Interface tc_flowmark is attached to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TcAttachmentStatus {
+    pub interface: String,
+    pub direction: TcDirection,
+}
+
+/// Conntrack bypass state of the worker
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FlowMarkStatus {
+    pub enabled: bool,
+    pub mark: u32,
+    pub mask: u32,
+    pub attachments: Vec<TcAttachmentStatus>,
+    /// Counters of tc_flowmark since it was loaded
+    pub stats: FlowMarkStats,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(mark: u32, mask: Option<u32>) -> FlowMarkSpec {
+        FlowMarkSpec {
+            version: "1.4.0".to_string(),
+            mark,
+            mask,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_spec_validate() {
+        assert!(spec(0x100, None).validate().is_ok());
+        assert!(spec(0x100, Some(0xf00)).validate().is_ok());
+        assert!(spec(0, None).validate().is_err());
+        assert!(spec(0x100, Some(0x0ff)).validate().is_err());
+
+        let mut unversioned = spec(0x100, None);
+        unversioned.version.clear();
+        assert!(unversioned.validate().is_err());
+    }
+
+    #[test]
+    fn test_spec_config() {
+        assert_eq!(
+            spec(0x100, None).config(),
+            FlowMarkConfig::enabled(0x100, 0x100)
+        );
+        assert_eq!(spec(0x100, Some(0xf00)).config().mask, 0xf00);
+        assert!(!FlowMarkConfig::default().is_enabled());
+    }
+
+    #[test]
+    fn test_spec_parse() {
+        let spec: FlowMarkSpec =
+            serde_json::from_str(r#"{"version": "1.4.0", "mark": 256, "direction": "both"}"#)
+                .unwrap();
+        assert_eq!(spec.direction, TcDirection::Both);
+        assert_eq!(spec.direction.attach_types().len(), 2);
+        assert_eq!(spec.mask(), 256);
+
+        let spec: FlowMarkSpec =
+            serde_json::from_str(r#"{"version": "1.4.0", "mark": 256}"#).unwrap();
+        assert_eq!(spec.direction, TcDirection::Ingress);
+    }
+
+    #[test]
+    fn test_stats_add() {
+        let a = FlowMarkStats {
+            packets: 10,
+            marked: 4,
+        };
+        assert_eq!(
+            a.add(&a),
+            FlowMarkStats {
+                packets: 20,
+                marked: 8
+            }
+        );
+    }
+}
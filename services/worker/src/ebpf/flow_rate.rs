@@ -0,0 +1,170 @@
+//! Per-connection bandwidth limits
+//!
+//! Backends can cap the bytes per second of every established flow, so one
+//! client cannot saturate a backend without being blocked outright. xdp_tcp
+//! keeps a token bucket in each established connection and xdp_udp in each
+//! tracked flow; packets beyond the bucket are dropped with the
+//! `flow_rate_limit` reason. The limits are looked up by destination in the
+//! pinned `FLOW_RATES` map, which both programs share. This module mirrors
+//! the kernel layouts and builds the map contents from the backends' limits.
+//!
+//! Backends of organizations over their monthly bandwidth may be throttled
+//! by the gateway (see `pistonprotection_common::bandwidth_quota`); a
+//! throttle caps the limit of every destination of the backend, whether or
+//! not it has one configured.
+
+use super::tenants::{TenantDestination, TenantDstV6Key, destination_entries};
+use std::collections::HashMap;
+
+/// Maximum number of limited destinations (mirrors `flow_rate::MAX_DESTINATIONS`)
+pub const MAX_DESTINATIONS: usize = 65536;
+
+/// Value of `FLOW_RATES` (mirrors `FlowRateConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlowRateConfig {
+    pub bytes_per_sec: u64,
+    /// Bucket size (0 = one second of `bytes_per_sec`)
+    pub burst_bytes: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u64` fields, no padding.
+unsafe impl aya::Pod for FlowRateConfig {}
+
+/// Per-connection bandwidth of a backend on this worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendFlowRate {
+    /// Destinations of the backend; backends without destination addresses
+    /// cannot be limited
+    pub destinations: Vec<TenantDestination>,
+    pub config: FlowRateConfig,
+}
+
+/// Apply bandwidth throttles to the per-connection limits of the backends
+///
+/// `destinations` are the accounted destinations of every backend, used for
+/// throttled backends without a limit of their own. A configured limit below
+/// the throttle is kept.
+pub fn throttled_flow_rates(
+    flow_rates: &HashMap<String, BackendFlowRate>,
+    destinations: &HashMap<String, Vec<TenantDestination>>,
+    throttles: &HashMap<String, u64>,
+) -> HashMap<String, BackendFlowRate> {
+    let mut throttled = flow_rates.clone();
+
+    for (backend_id, &bytes_per_sec) in throttles {
+        match throttled.get_mut(backend_id) {
+            Some(flow_rate) if flow_rate.config.bytes_per_sec <= bytes_per_sec => {}
+            Some(flow_rate) => {
+                flow_rate.config = FlowRateConfig {
+                    bytes_per_sec,
+                    burst_bytes: flow_rate.config.burst_bytes.min(bytes_per_sec),
+                };
+            }
+            None => {
+                let Some(destinations) = destinations.get(backend_id) else {
+                    continue;
+                };
+                throttled.insert(
+                    backend_id.clone(),
+                    BackendFlowRate {
+                        destinations: destinations.clone(),
+                        config: FlowRateConfig {
+                            bytes_per_sec,
+                            burst_bytes: 0,
+                        },
+                    },
+                );
+            }
+        }
+    }
+
+    throttled
+}
+
+/// Build the contents of `FLOW_RATES`
+pub fn flow_rate_map_entries(
+    backends: &HashMap<String, BackendFlowRate>,
+) -> Vec<(TenantDstV6Key, FlowRateConfig)> {
+    destination_entries(backends, MAX_DESTINATIONS, |_, backend| {
+        Some((backend.destinations.as_slice(), backend.config))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(addrs: &[&str], port: u16, bytes_per_sec: u64) -> BackendFlowRate {
+        BackendFlowRate {
+            destinations: addrs
+                .iter()
+                .map(|addr| TenantDestination {
+                    addr: addr.parse().unwrap(),
+                    port,
+                })
+                .collect(),
+            config: FlowRateConfig {
+                bytes_per_sec,
+                burst_bytes: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_map_entries() {
+        let mut backends = HashMap::new();
+        backends.insert(
+            "a".to_string(),
+            backend(&["192.0.2.10", "2001:db8::10"], 25565, 1_000_000),
+        );
+        backends.insert("b".to_string(), backend(&[], 0, 5_000));
+
+        let entries = flow_rate_map_entries(&backends);
+        assert_eq!(entries.len(), 2);
+
+        let (key, config) = entries[0];
+        assert_eq!(
+            key.addr,
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 192, 0, 2, 10]
+        );
+        assert_eq!(key.port, 25565);
+        assert_eq!(config.bytes_per_sec, 1_000_000);
+        assert_eq!(entries[1].0.addr[0], 0x20);
+    }
+
+    #[test]
+    fn test_throttled_flow_rates() {
+        let mut flow_rates = HashMap::new();
+        flow_rates.insert("fast".to_string(), backend(&["192.0.2.10"], 0, 10_000_000));
+        flow_rates.insert("slow".to_string(), backend(&["192.0.2.11"], 0, 1_000));
+        let mut destinations = HashMap::new();
+        destinations.insert(
+            "open".to_string(),
+            backend(&["192.0.2.12"], 25565, 0).destinations,
+        );
+        let throttles: HashMap<_, _> = [
+            ("fast".to_string(), 1_000_000),
+            ("slow".to_string(), 1_000_000),
+            ("open".to_string(), 1_000_000),
+            ("gone".to_string(), 1_000_000),
+        ]
+        .into_iter()
+        .collect();
+
+        let throttled = throttled_flow_rates(&flow_rates, &destinations, &throttles);
+        assert_eq!(throttled.len(), 3);
+        assert_eq!(throttled["fast"].config.bytes_per_sec, 1_000_000);
+        // A stricter configured limit stays
+        assert_eq!(throttled["slow"].config.bytes_per_sec, 1_000);
+        // Backends without a limit get one
+        assert_eq!(throttled["open"].config.bytes_per_sec, 1_000_000);
+        assert_eq!(throttled["open"].destinations[0].port, 25565);
+    }
+
+    #[test]
+    fn test_layout_matches_kernel() {
+        assert_eq!(std::mem::size_of::<FlowRateConfig>(), 16);
+        assert_eq!(std::mem::size_of::<TenantDstV6Key>(), 20);
+    }
+}
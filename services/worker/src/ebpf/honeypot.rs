@@ -0,0 +1,300 @@
+//! Honeypot ports
+//!
+//! Backends can name ports no real service of theirs listens on. xdp_filter
+//! drops everything sent to them and records the source in the
+//! `HONEYPOT_HITS_V*` maps; if the backend asks for it, the source is also
+//! added to the global blocklist right away, so it is cut off from every
+//! backend and program before userspace sees the hit. This module mirrors
+//! the kernel layouts and keeps the honeypots configured per backend and the
+//! sources flagged recently.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::IpAddr;
+
+/// `HoneypotConfig::flags`: block sources touching the port (mirrors `honeypot::FLAG_AUTO_BLOCK`)
+pub const FLAG_AUTO_BLOCK: u32 = 1;
+
+/// Maximum number of honeypot destinations per address family (mirrors `honeypot::MAX_PORTS`)
+pub const MAX_PORTS: usize = 65536;
+
+/// Number of flagged sources kept for the status API
+pub const MAX_FLAGGED: usize = 1000;
+
+/// Destination key of `HONEYPOT_PORTS_V4` (mirrors `HoneypotV4Key`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct HoneypotV4Key {
+    pub addr: u32,
+    pub port: u16,
+    pub _pad: u16,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for HoneypotV4Key {}
+
+/// Destination key of `HONEYPOT_PORTS_V6` (mirrors `HoneypotV6Key`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct HoneypotV6Key {
+    pub addr: [u8; 16],
+    pub port: u16,
+    pub _pad: u16,
+}
+
+// SAFETY: `#[repr(C)]` struct of a byte array and explicit padding, no implicit padding.
+unsafe impl aya::Pod for HoneypotV6Key {}
+
+/// Value of `HONEYPOT_PORTS_V*` (mirrors `HoneypotConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HoneypotConfig {
+    pub tenant_id: u32,
+    pub flags: u32,
+    pub block_duration_ns: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u32` and a `u64` field, no padding.
+unsafe impl aya::Pod for HoneypotConfig {}
+
+/// Value of `HONEYPOT_HITS_V*` (mirrors `HoneypotHit`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HoneypotHit {
+    pub first_seen_ns: u64,
+    pub last_seen_ns: u64,
+    pub packets: u64,
+    pub tenant_id: u32,
+    pub port: u16,
+    pub protocol: u8,
+    pub blocked: u8,
+}
+
+// SAFETY: `#[repr(C)]` struct of integer fields laid out without padding.
+unsafe impl aya::Pod for HoneypotHit {}
+
+/// Honeypot settings of a backend
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HoneypotPorts {
+    pub ports: Vec<u16>,
+    /// Block flagged sources in the global blocklist
+    pub auto_block: bool,
+    /// Seconds auto-blocked sources stay blocked (0 = until unblocked)
+    pub block_duration_secs: u32,
+}
+
+/// Honeypot ports of a backend on this worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendHoneypot {
+    pub tenant_id: u32,
+    /// Destination addresses of the backend (empty = any address)
+    pub addrs: Vec<IpAddr>,
+    pub settings: HoneypotPorts,
+}
+
+/// Contents of the xdp_filter honeypot maps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HoneypotMapEntries {
+    pub v4: Vec<(HoneypotV4Key, HoneypotConfig)>,
+    pub v6: Vec<(HoneypotV6Key, HoneypotConfig)>,
+}
+
+/// Build the contents of the honeypot maps
+///
+/// Backends are visited in id order, so when two claim the same destination
+/// the result does not depend on hash order. Backends without destination
+/// addresses claim the port on any address of both families.
+pub fn honeypot_map_entries(backends: &HashMap<String, BackendHoneypot>) -> HoneypotMapEntries {
+    let ordered: BTreeMap<&String, &BackendHoneypot> = backends.iter().collect();
+    let mut v4 = BTreeMap::new();
+    let mut v6 = BTreeMap::new();
+
+    for backend in ordered.values() {
+        let config = HoneypotConfig {
+            tenant_id: backend.tenant_id,
+            flags: if backend.settings.auto_block {
+                FLAG_AUTO_BLOCK
+            } else {
+                0
+            },
+            block_duration_ns: backend.settings.block_duration_secs as u64 * 1_000_000_000,
+        };
+
+        let (addrs_v4, addrs_v6): (Vec<Option<u32>>, Vec<Option<[u8; 16]>>) =
+            if backend.addrs.is_empty() {
+                (vec![Some(0)], vec![Some([0; 16])])
+            } else {
+                backend
+                    .addrs
+                    .iter()
+                    .map(|addr| match addr {
+                        IpAddr::V4(addr) => (Some(u32::from(*addr)), None),
+                        IpAddr::V6(addr) => (None, Some(addr.octets())),
+                    })
+                    .unzip()
+            };
+
+        for &port in &backend.settings.ports {
+            for addr in addrs_v4.iter().flatten() {
+                v4.entry((*addr, port)).or_insert(config);
+            }
+            for addr in addrs_v6.iter().flatten() {
+                v6.entry((*addr, port)).or_insert(config);
+            }
+        }
+    }
+
+    HoneypotMapEntries {
+        v4: v4
+            .into_iter()
+            .take(MAX_PORTS)
+            .map(|((addr, port), config)| {
+                let key = HoneypotV4Key {
+                    addr,
+                    port,
+                    _pad: 0,
+                };
+                (key, config)
+            })
+            .collect(),
+        v6: v6
+            .into_iter()
+            .take(MAX_PORTS)
+            .map(|((addr, port), config)| {
+                let key = HoneypotV6Key {
+                    addr,
+                    port,
+                    _pad: 0,
+                };
+                (key, config)
+            })
+            .collect(),
+    }
+}
+
+/// Source that touched a honeypot port
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlaggedSource {
+    pub ip: IpAddr,
+    pub tenant_id: u32,
+    /// Last honeypot port touched
+    pub port: u16,
+    pub protocol: u8,
+    /// Packets sent to honeypot ports since the last drain
+    pub packets: u64,
+    /// The kernel added the source to the global blocklist
+    pub blocked: bool,
+    pub flagged_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl FlaggedSource {
+    pub fn from_hit(ip: IpAddr, hit: &HoneypotHit, now: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            ip,
+            tenant_id: hit.tenant_id,
+            port: hit.port,
+            protocol: hit.protocol,
+            packets: hit.packets,
+            blocked: hit.blocked != 0,
+            flagged_at: now,
+        }
+    }
+}
+
+/// Most recently flagged sources, newest last
+#[derive(Debug, Default)]
+pub struct FlaggedLog {
+    entries: VecDeque<FlaggedSource>,
+}
+
+impl FlaggedLog {
+    pub fn push(&mut self, source: FlaggedSource) {
+        if self.entries.len() == MAX_FLAGGED {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(source);
+    }
+
+    pub fn recent(&self) -> Vec<FlaggedSource> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(tenant_id: u32, addrs: &[&str], ports: &[u16], auto_block: bool) -> BackendHoneypot {
+        BackendHoneypot {
+            tenant_id,
+            addrs: addrs.iter().map(|a| a.parse().unwrap()).collect(),
+            settings: HoneypotPorts {
+                ports: ports.to_vec(),
+                auto_block,
+                block_duration_secs: 60,
+            },
+        }
+    }
+
+    #[test]
+    fn test_map_entries() {
+        let mut backends = HashMap::new();
+        backends.insert(
+            "a".to_string(),
+            backend(1, &["192.0.2.10", "2001:db8::10"], &[23, 2323], true),
+        );
+        backends.insert("b".to_string(), backend(2, &[], &[445], false));
+
+        let entries = honeypot_map_entries(&backends);
+        let v4: Vec<(u32, u16, u32)> = entries
+            .v4
+            .iter()
+            .map(|(key, config)| (key.addr, key.port, config.tenant_id))
+            .collect();
+        assert_eq!(
+            v4,
+            [(0, 445, 2), (0xc000_020a, 23, 1), (0xc000_020a, 2323, 1)]
+        );
+        assert_eq!(entries.v6.len(), 3);
+
+        let (_, config) = entries.v4[1];
+        assert_eq!(config.flags, FLAG_AUTO_BLOCK);
+        assert_eq!(config.block_duration_ns, 60_000_000_000);
+        assert_eq!(entries.v4[0].1.flags, 0);
+    }
+
+    #[test]
+    fn test_map_entries_shared_destination() {
+        // The backend with the lowest id keeps a destination claimed twice
+        let mut backends = HashMap::new();
+        backends.insert("z".to_string(), backend(2, &[], &[23], false));
+        backends.insert("a".to_string(), backend(1, &[], &[23], true));
+
+        let entries = honeypot_map_entries(&backends);
+        assert_eq!(entries.v4.len(), 1);
+        assert_eq!(entries.v4[0].1.tenant_id, 1);
+        assert_eq!(entries.v4[0].1.flags, FLAG_AUTO_BLOCK);
+    }
+
+    #[test]
+    fn test_flagged_log_bounded() {
+        let mut log = FlaggedLog::default();
+        let now = chrono::Utc::now();
+        for i in 0..MAX_FLAGGED as u32 + 5 {
+            let hit = HoneypotHit {
+                packets: 1,
+                port: 23,
+                protocol: 6,
+                ..Default::default()
+            };
+            log.push(FlaggedSource::from_hit(
+                IpAddr::from(i.to_be_bytes()),
+                &hit,
+                now,
+            ));
+        }
+        let recent = log.recent();
+        assert_eq!(recent.len(), MAX_FLAGGED);
+        assert_eq!(recent[0].ip, IpAddr::from(5u32.to_be_bytes()));
+    }
+}
@@ -0,0 +1,194 @@
+//! XDP processing latency
+//!
+//! When enabled, every XDP program times 1 in N packets from entry to
+//! verdict with `bpf_ktime_get_ns` and counts them in a log2 histogram per
+//! protection level (`LATENCY` map). This module mirrors the kernel layout,
+//! sums the per-CPU buckets and exports them as the
+//! `xdp_processing_seconds` Prometheus histogram, so filter overhead can be
+//! compared across programs and protection levels.
+
+use pistonprotection_common::metrics::XDP_PROCESSING_SECONDS;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Histogram buckets per protection level (mirrors `latency::BUCKETS`)
+pub const BUCKETS: usize = 16;
+
+/// Bucket `i` counts packets under `2^(MIN_SHIFT + i)` ns (mirrors
+/// `latency::MIN_SHIFT`)
+pub const MIN_SHIFT: u32 = 6;
+
+/// Protection levels with their own histogram (mirrors `latency::LEVELS`)
+pub const LEVELS: usize = 5;
+
+/// Latency measurement configuration (mirrors `LatencyConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyConfig {
+    pub rate: u32,
+    pub _pad: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u32` fields, no padding.
+unsafe impl aya::Pod for LatencyConfig {}
+
+/// One histogram bucket (mirrors `LatencyBucket`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyBucket {
+    pub count: u64,
+    pub sum_ns: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u64` fields, no padding.
+unsafe impl aya::Pod for LatencyBucket {}
+
+/// Per-program measurement rates
+#[derive(Debug, Clone, Default)]
+pub struct LatencyRates {
+    /// Rate used by programs without an override (0 disables measurement)
+    pub default_rate: u32,
+    /// Per-program overrides, keyed by program name
+    pub program_rates: HashMap<String, u32>,
+}
+
+impl LatencyRates {
+    /// Load measurement rates from environment variables
+    ///
+    /// Measurement is off unless `PISTON_LATENCY_RATE` or
+    /// `PISTON_LATENCY_RATES` (format: xdp_filter=64,xdp_udp=0) is set.
+    pub fn from_env() -> Self {
+        let mut rates = Self::default();
+
+        if let Ok(rate) = std::env::var("PISTON_LATENCY_RATE") {
+            if let Ok(rate) = rate.parse::<u32>() {
+                rates.default_rate = rate;
+            }
+        }
+
+        if let Ok(overrides) = std::env::var("PISTON_LATENCY_RATES") {
+            for pair in overrides.split(',') {
+                if let Some((program, rate)) = pair.split_once('=') {
+                    if let Ok(rate) = rate.trim().parse::<u32>() {
+                        rates.program_rates.insert(program.trim().to_string(), rate);
+                    }
+                }
+            }
+        }
+
+        rates
+    }
+
+    /// Measurement rate for a program
+    pub fn rate_for(&self, program: &str) -> u32 {
+        self.program_rates
+            .get(program)
+            .copied()
+            .unwrap_or(self.default_rate)
+    }
+}
+
+/// Upper bound of a bucket in seconds (`+Inf` for the last one)
+pub fn bucket_upper_bound_secs(bucket: usize) -> f64 {
+    if bucket + 1 >= BUCKETS {
+        return f64::INFINITY;
+    }
+    (1u64 << (MIN_SHIFT as usize + bucket)) as f64 / 1e9
+}
+
+/// Processing time histogram of one program at one protection level
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyHistogram {
+    pub program: String,
+    /// Protection level the packets were filtered at (0 = program without one)
+    pub level: u32,
+    /// Packets per bucket, not cumulative
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum_ns: u64,
+}
+
+impl LatencyHistogram {
+    /// Build the histograms of a program from the `LATENCY` map
+    ///
+    /// `entries` holds the per-CPU values of each map index in order. Levels
+    /// without a timed packet are left out.
+    pub fn from_map(program: &str, entries: &[Vec<LatencyBucket>]) -> Vec<Self> {
+        entries
+            .chunks(BUCKETS)
+            .take(LEVELS)
+            .enumerate()
+            .filter_map(|(level, level_entries)| {
+                let mut histogram = Self {
+                    program: program.to_string(),
+                    level: level as u32,
+                    buckets: vec![0; BUCKETS],
+                    count: 0,
+                    sum_ns: 0,
+                };
+                for (bucket, per_cpu) in level_entries.iter().enumerate() {
+                    for value in per_cpu {
+                        histogram.buckets[bucket] += value.count;
+                        histogram.count += value.count;
+                        histogram.sum_ns += value.sum_ns;
+                    }
+                }
+                (histogram.count > 0).then_some(histogram)
+            })
+            .collect()
+    }
+
+    /// Mean processing time in nanoseconds
+    pub fn mean_ns(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.sum_ns as f64 / self.count as f64
+    }
+
+    /// Export as the `xdp_processing_seconds` histogram
+    pub fn export_metrics(&self) {
+        // The last bucket is `+Inf`, which the exporter adds from the count
+        let buckets: Vec<(f64, u64)> = self.buckets[..BUCKETS - 1]
+            .iter()
+            .enumerate()
+            .map(|(bucket, count)| (bucket_upper_bound_secs(bucket), *count))
+            .collect();
+        XDP_PROCESSING_SECONDS.set(
+            &[&self.program, &self.level.to_string()],
+            &buckets,
+            self.count,
+            self.sum_ns as f64 / 1e9,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bounds() {
+        assert_eq!(bucket_upper_bound_secs(0), 64e-9);
+        assert_eq!(bucket_upper_bound_secs(1), 128e-9);
+        assert_eq!(bucket_upper_bound_secs(BUCKETS - 1), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_histograms_from_map() {
+        let bucket = |count, sum_ns| LatencyBucket { count, sum_ns };
+        let mut entries = vec![vec![LatencyBucket::default(); 2]; BUCKETS * LEVELS];
+        // Level 2: three packets under 128 ns on two CPUs, one over 256 ns
+        entries[2 * BUCKETS + 1] = vec![bucket(2, 200), bucket(1, 90)];
+        entries[2 * BUCKETS + 3] = vec![bucket(1, 300), LatencyBucket::default()];
+
+        let histograms = LatencyHistogram::from_map("xdp_filter", &entries);
+        assert_eq!(histograms.len(), 1);
+        let histogram = &histograms[0];
+        assert_eq!(histogram.level, 2);
+        assert_eq!(histogram.count, 4);
+        assert_eq!(histogram.buckets[1], 3);
+        assert_eq!(histogram.buckets[3], 1);
+        assert_eq!(histogram.mean_ns(), 147.5);
+    }
+}
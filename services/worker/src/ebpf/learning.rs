@@ -0,0 +1,502 @@
+//! Allowlist learning
+//!
+//! A backend in learning mode trains for a window. While any backend trains,
+//! the `LEARNING` flag is set and xdp_tcp, xdp_minecraft and xdp_quic record
+//! every source completing a full, valid handshake (TCP connection carrying
+//! data, Minecraft login, QUIC handshake) in the shared `HANDSHAKES` map.
+//! The worker drains that map, credits each source to the training backends
+//! it talked to and keeps the sources with enough handshakes. Once a
+//! backend's window closes, its sources are written to `KNOWN_GOOD`, where
+//! the programs multiply their limits so regular clients keep their service
+//! during later attacks.
+//!
+//! What a backend learned can be exported and imported into another worker
+//! (see `crate::warmup`), so a node joining the fleet starts from the
+//! fleet's window instead of training from scratch.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::{IpAddr, Ipv6Addr};
+
+/// `HandshakeRecord::protocols`: TCP connection established and carrying data
+pub const PROTO_TCP: u32 = 1;
+/// `HandshakeRecord::protocols`: Minecraft login completed
+pub const PROTO_MINECRAFT: u32 = 2;
+/// `HandshakeRecord::protocols`: QUIC handshake completed
+pub const PROTO_QUIC: u32 = 4;
+
+/// Maximum number of known-good sources (mirrors `known_good::MAX_SOURCES`)
+pub const MAX_SOURCES: usize = 65536;
+
+/// Maximum number of sources tracked per training backend
+pub const MAX_CANDIDATES: usize = 262144;
+
+/// Value of `HANDSHAKES` (mirrors `HandshakeRecord`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandshakeRecord {
+    pub first_seen_ns: u64,
+    pub last_seen_ns: u64,
+    pub completions: u32,
+    pub protocols: u32,
+    pub dst_addr: [u8; 16],
+    pub dst_port: u16,
+    pub _pad: [u8; 6],
+}
+
+// SAFETY: `#[repr(C)]` struct of integer fields and byte arrays with explicit padding, no implicit padding.
+unsafe impl aya::Pod for HandshakeRecord {}
+
+/// Key of the learning maps: IPv6 addresses as is, IPv4 addresses IPv4-mapped
+pub fn addr_key(addr: IpAddr) -> [u8; 16] {
+    match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped().octets(),
+        IpAddr::V6(addr) => addr.octets(),
+    }
+}
+
+/// Address of a learning map key
+pub fn key_addr(key: [u8; 16]) -> IpAddr {
+    let addr = Ipv6Addr::from(key);
+    match addr.to_ipv4_mapped() {
+        Some(addr) => IpAddr::V4(addr),
+        None => IpAddr::V6(addr),
+    }
+}
+
+/// Learning settings of a backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LearningSettings {
+    /// Length of the training window
+    pub training_secs: u32,
+    /// Factor applied to the limits of known-good sources
+    pub limit_multiplier: u32,
+    /// Completed handshakes before a source is known-good
+    pub min_handshakes: u32,
+}
+
+/// Learning mode of a backend on this worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendLearning {
+    /// Destination addresses of the backend (empty = any address)
+    pub addrs: Vec<IpAddr>,
+    /// Destination port ranges of the backend (empty = any port)
+    pub ports: Vec<(u16, u16)>,
+    pub settings: LearningSettings,
+}
+
+impl BackendLearning {
+    fn serves(&self, addr: IpAddr, port: u16) -> bool {
+        (self.addrs.is_empty() || self.addrs.contains(&addr))
+            && (self.ports.is_empty()
+                || self
+                    .ports
+                    .iter()
+                    .any(|&(start, end)| start <= port && port <= end))
+    }
+
+    /// Whether a change to `other` needs a new training window
+    fn retrains(&self, other: &BackendLearning) -> bool {
+        self.addrs != other.addrs
+            || self.ports != other.ports
+            || self.settings.training_secs != other.settings.training_secs
+    }
+}
+
+/// Training window of a backend and what it learned
+#[derive(Debug)]
+struct Training {
+    backend: BackendLearning,
+    started_at: DateTime<Utc>,
+    /// Handshakes completed per source
+    handshakes: HashMap<IpAddr, u32>,
+}
+
+impl Training {
+    fn ends_at(&self) -> DateTime<Utc> {
+        self.started_at + chrono::Duration::seconds(self.backend.settings.training_secs as i64)
+    }
+
+    fn is_training(&self, now: DateTime<Utc>) -> bool {
+        now < self.ends_at()
+    }
+
+    /// Merge imported handshakes, keeping the earlier window start
+    fn merge(&mut self, learned: &LearnedBackend) {
+        if learned.training_secs != self.backend.settings.training_secs {
+            return;
+        }
+        self.started_at = self.started_at.min(learned.started_at);
+        for &(addr, count) in &learned.sources {
+            let tracked = self.handshakes.len();
+            match self.handshakes.get_mut(&addr) {
+                Some(current) => *current = (*current).max(count),
+                None if tracked < MAX_CANDIDATES => {
+                    self.handshakes.insert(addr, count);
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn known_good(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        let min = self.backend.settings.min_handshakes.max(1);
+        self.handshakes
+            .iter()
+            .filter(move |&(_, &count)| count >= min)
+            .map(|(&addr, _)| addr)
+    }
+}
+
+/// Handshakes a backend learned, as exported to other workers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LearnedBackend {
+    pub backend_id: String,
+    pub started_at: DateTime<Utc>,
+    /// Length of the training window the handshakes were counted in
+    pub training_secs: u32,
+    /// Handshakes completed per source
+    pub sources: Vec<(IpAddr, u32)>,
+}
+
+/// Learning state of a backend, for the status API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LearningStatus {
+    pub backend_id: String,
+    #[serde(flatten)]
+    pub settings: LearningSettings,
+    /// The training window is still open
+    pub training: bool,
+    pub started_at: DateTime<Utc>,
+    pub training_ends_at: DateTime<Utc>,
+    /// Sources that completed at least one handshake
+    pub candidates: usize,
+    /// Sources with enough handshakes to be known-good
+    pub known_good: usize,
+}
+
+/// Learning mode of every backend
+#[derive(Debug, Default)]
+pub struct AllowlistLearner {
+    backends: HashMap<String, Training>,
+    /// Imported state of backends not configured yet, applied once they are
+    imported: HashMap<String, LearnedBackend>,
+}
+
+impl AllowlistLearner {
+    /// Set or clear the learning mode of a backend
+    ///
+    /// Enabling learning, or changing the training window or destinations,
+    /// starts a new window from scratch; other changes keep what was learned.
+    pub fn configure(
+        &mut self,
+        backend_id: &str,
+        learning: Option<BackendLearning>,
+        now: DateTime<Utc>,
+    ) {
+        let Some(learning) = learning else {
+            self.backends.remove(backend_id);
+            return;
+        };
+
+        match self.backends.get_mut(backend_id) {
+            Some(training) if !training.backend.retrains(&learning) => {
+                training.backend = learning;
+            }
+            _ => {
+                let mut training = Training {
+                    backend: learning,
+                    started_at: now,
+                    handshakes: HashMap::new(),
+                };
+                if let Some(learned) = self.imported.remove(backend_id) {
+                    training.merge(&learned);
+                }
+                self.backends.insert(backend_id.to_string(), training);
+            }
+        }
+    }
+
+    /// What every backend learned so far
+    pub fn export(&self) -> Vec<LearnedBackend> {
+        let mut learned: Vec<LearnedBackend> = self
+            .backends
+            .iter()
+            .map(|(id, training)| LearnedBackend {
+                backend_id: id.clone(),
+                started_at: training.started_at,
+                training_secs: training.backend.settings.training_secs,
+                sources: training
+                    .handshakes
+                    .iter()
+                    .map(|(&addr, &count)| (addr, count))
+                    .collect(),
+            })
+            .collect();
+        learned.sort_by(|a, b| a.backend_id.cmp(&b.backend_id));
+        learned
+    }
+
+    /// Merge what another worker learned
+    ///
+    /// Handshake counts are merged and the earlier window start kept, so a
+    /// backend whose window closed elsewhere has known-good sources right
+    /// away. State learned with another training window is skipped; state of
+    /// backends not configured yet is kept until they are. Returns the
+    /// number of backends imported.
+    pub fn import(&mut self, learned: Vec<LearnedBackend>) -> usize {
+        let mut imported = 0;
+        for backend in learned {
+            match self.backends.get_mut(&backend.backend_id) {
+                Some(training)
+                    if training.backend.settings.training_secs != backend.training_secs => {}
+                Some(training) => {
+                    training.merge(&backend);
+                    imported += 1;
+                }
+                None => {
+                    self.imported.insert(backend.backend_id.clone(), backend);
+                    imported += 1;
+                }
+            }
+        }
+        imported
+    }
+
+    /// Drop the learning state of backends no longer in the configuration
+    pub fn prune(&mut self, active_backends: &HashSet<String>) {
+        self.backends.retain(|id, _| active_backends.contains(id));
+        self.imported.retain(|id, _| active_backends.contains(id));
+    }
+
+    /// Number of backends in learning mode
+    pub fn backend_count(&self) -> usize {
+        self.backends.len()
+    }
+
+    /// Whether any backend is in its training window
+    pub fn is_training(&self, now: DateTime<Utc>) -> bool {
+        self.backends.values().any(|t| t.is_training(now))
+    }
+
+    /// Credit the handshakes of a source to the training backends serving
+    /// the destination it completed them with
+    ///
+    /// Returns the number of backends credited.
+    pub fn record(&mut self, src: IpAddr, record: &HandshakeRecord, now: DateTime<Utc>) -> usize {
+        let dst = key_addr(record.dst_addr);
+        let mut credited = 0;
+
+        for training in self.backends.values_mut() {
+            if !training.is_training(now) || !training.backend.serves(dst, record.dst_port) {
+                continue;
+            }
+            let tracked = training.handshakes.len();
+            match training.handshakes.get_mut(&src) {
+                Some(count) => *count = count.saturating_add(record.completions),
+                None if tracked < MAX_CANDIDATES => {
+                    training.handshakes.insert(src, record.completions);
+                }
+                None => continue,
+            }
+            credited += 1;
+        }
+
+        credited
+    }
+
+    /// Contents of `KNOWN_GOOD`: the sources of backends done training
+    ///
+    /// A source known to several backends gets the largest multiplier.
+    pub fn known_good_entries(&self, now: DateTime<Utc>) -> Vec<([u8; 16], u32)> {
+        let mut entries: BTreeMap<[u8; 16], u32> = BTreeMap::new();
+        for training in self.backends.values() {
+            if training.is_training(now) {
+                continue;
+            }
+            let multiplier = training.backend.settings.limit_multiplier;
+            for addr in training.known_good() {
+                let entry = entries.entry(addr_key(addr)).or_insert(multiplier);
+                *entry = (*entry).max(multiplier);
+            }
+        }
+        entries.into_iter().take(MAX_SOURCES).collect()
+    }
+
+    /// Learning state of every backend, sorted by backend ID
+    pub fn status(&self, now: DateTime<Utc>) -> Vec<LearningStatus> {
+        let mut status: Vec<LearningStatus> = self
+            .backends
+            .iter()
+            .map(|(id, training)| LearningStatus {
+                backend_id: id.clone(),
+                settings: training.backend.settings,
+                training: training.is_training(now),
+                started_at: training.started_at,
+                training_ends_at: training.ends_at(),
+                candidates: training.handshakes.len(),
+                known_good: training.known_good().count(),
+            })
+            .collect();
+        status.sort_by(|a, b| a.backend_id.cmp(&b.backend_id));
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn learning(addrs: &[&str], ports: &[(u16, u16)], min_handshakes: u32) -> BackendLearning {
+        BackendLearning {
+            addrs: addrs.iter().map(|a| a.parse().unwrap()).collect(),
+            ports: ports.to_vec(),
+            settings: LearningSettings {
+                training_secs: 3600,
+                limit_multiplier: 4,
+                min_handshakes,
+            },
+        }
+    }
+
+    fn handshake(dst: &str, dst_port: u16, completions: u32) -> HandshakeRecord {
+        HandshakeRecord {
+            completions,
+            protocols: PROTO_TCP,
+            dst_addr: addr_key(dst.parse().unwrap()),
+            dst_port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_addr_key_roundtrip() {
+        for addr in ["192.0.2.1", "2001:db8::1"] {
+            let addr: IpAddr = addr.parse().unwrap();
+            assert_eq!(key_addr(addr_key(addr)), addr);
+        }
+        assert_eq!(
+            &addr_key("192.0.2.1".parse().unwrap())[10..],
+            [0xff, 0xff, 192, 0, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_known_good_after_training() {
+        let start = Utc::now();
+        let mut learner = AllowlistLearner::default();
+        learner.configure(
+            "b1",
+            Some(learning(&["192.0.2.10"], &[(25565, 25565)], 2)),
+            start,
+        );
+        assert!(learner.is_training(start));
+
+        let regular: IpAddr = "198.51.100.1".parse().unwrap();
+        let once: IpAddr = "198.51.100.2".parse().unwrap();
+        let elsewhere: IpAddr = "198.51.100.3".parse().unwrap();
+        assert_eq!(
+            learner.record(regular, &handshake("192.0.2.10", 25565, 1), start),
+            1
+        );
+        assert_eq!(
+            learner.record(regular, &handshake("192.0.2.10", 25565, 1), start),
+            1
+        );
+        assert_eq!(
+            learner.record(once, &handshake("192.0.2.10", 25565, 1), start),
+            1
+        );
+        assert_eq!(
+            learner.record(elsewhere, &handshake("192.0.2.11", 25565, 5), start),
+            0
+        );
+        assert_eq!(
+            learner.record(elsewhere, &handshake("192.0.2.10", 80, 5), start),
+            0
+        );
+
+        // Nothing is relaxed while the window is open
+        assert!(learner.known_good_entries(start).is_empty());
+
+        let after = start + chrono::Duration::seconds(3600);
+        assert!(!learner.is_training(after));
+        assert_eq!(
+            learner.known_good_entries(after),
+            vec![(addr_key(regular), 4)]
+        );
+
+        // Handshakes after the window teach nothing
+        assert_eq!(
+            learner.record(once, &handshake("192.0.2.10", 25565, 1), after),
+            0
+        );
+
+        let status = learner.status(after);
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].candidates, 2);
+        assert_eq!(status[0].known_good, 1);
+    }
+
+    #[test]
+    fn test_configure_retrains_on_window_change() {
+        let start = Utc::now();
+        let source: IpAddr = "198.51.100.1".parse().unwrap();
+        let mut learner = AllowlistLearner::default();
+        learner.configure("b1", Some(learning(&[], &[], 1)), start);
+        learner.record(source, &handshake("192.0.2.10", 443, 1), start);
+
+        // A new multiplier keeps what was learned
+        let mut relaxed = learning(&[], &[], 1);
+        relaxed.settings.limit_multiplier = 8;
+        learner.configure("b1", Some(relaxed.clone()), start);
+        let after = start + chrono::Duration::seconds(3600);
+        assert_eq!(
+            learner.known_good_entries(after),
+            vec![(addr_key(source), 8)]
+        );
+
+        // A new window starts over
+        relaxed.settings.training_secs = 60;
+        learner.configure("b1", Some(relaxed), after);
+        assert!(learner.is_training(after));
+        assert_eq!(learner.status(after)[0].candidates, 0);
+
+        learner.prune(&HashSet::new());
+        assert_eq!(learner.backend_count(), 0);
+    }
+
+    #[test]
+    fn test_import_skips_training() {
+        let start = Utc::now();
+        let source: IpAddr = "198.51.100.1".parse().unwrap();
+        let mut seasoned = AllowlistLearner::default();
+        seasoned.configure(
+            "b1",
+            Some(learning(&["192.0.2.10"], &[(443, 443)], 2)),
+            start,
+        );
+        seasoned.record(source, &handshake("192.0.2.10", 443, 3), start);
+
+        // A worker joining after the window closed elsewhere
+        let later = start + chrono::Duration::seconds(3600);
+        let mut fresh = AllowlistLearner::default();
+        assert_eq!(fresh.import(seasoned.export()), 1);
+        fresh.configure(
+            "b1",
+            Some(learning(&["192.0.2.10"], &[(443, 443)], 2)),
+            later,
+        );
+        assert!(!fresh.is_training(later));
+        assert_eq!(fresh.known_good_entries(later), vec![(addr_key(source), 4)]);
+
+        // State of another training window is not merged
+        let mut other = AllowlistLearner::default();
+        let mut shorter = learning(&["192.0.2.10"], &[(443, 443)], 2);
+        shorter.settings.training_secs = 60;
+        other.configure("b1", Some(shorter), later);
+        assert_eq!(other.import(seasoned.export()), 0);
+        assert_eq!(other.status(later)[0].candidates, 0);
+    }
+}
@@ -1,15 +1,65 @@
 //! eBPF program loader and manager
 
+use super::artifacts::{ArtifactStatus, ArtifactVerifier};
+use super::batch::{self, Batcher, BpfMap, pod_bytes, pod_from_bytes};
+use super::capacity::{MapBudgets, MapHandle, MapSpec, SizingPlan, map_data};
+use super::cgnat::CgnatSignals;
+use super::conn_table::{
+    self, BedrockConnectionState, ConnectionEntry, ConnectionTable, McConnectionState,
+    QuicConnectionState,
+};
+use super::connections::{self, ReconcileReport, TcpConnectionState, TcpIpState};
+use super::diagnostics::{LoadDiagnostic, LoadStage, program_section, verifier_log};
+use super::dns::{DnsBudget, DnsBudgetConfig};
+use super::drop_events::DropEvent;
+use super::event_budget::{EventBudget, EventBudgetConfig, EventBudgetReport};
+use super::flow_mark::{
+    FlowMarkConfig, FlowMarkStats, FlowMarkStatus, TcAttachmentStatus, TcDirection,
+};
+use super::flow_rate::FlowRateConfig;
+use super::honeypot::{HoneypotHit, HoneypotMapEntries};
 use super::interface::NetworkInterface;
+use super::latency::{LatencyBucket, LatencyConfig, LatencyHistogram, LatencyRates};
+use super::learning::{HandshakeRecord, key_addr};
 use super::maps::MapManager;
+use super::marking::{MarkingCounter, MarkingPolicy};
+use super::penalty::{PenaltyConfig, PenaltyLadder};
+use super::probe::{KernelCapabilities, ProgramVariant};
+use super::protected_ports::{PROTECTED, ProtectedPorts};
+use super::quic_cid::QuicCidLimit;
+use super::sampling::{PacketSample, SampleConfig, SamplingConfig};
+use super::selftest::{self, TestRun};
+use super::sources::{self, SourceCounters, SourceLedger, SourceTraffic};
+use super::stats::{
+    CanaryEntry, DropBreakdown, DropCounter, ProgramStats, StatsReader, StatsSnapshot,
+};
+use super::tenants::{
+    REASON_MANUAL, TenantDestination, TenantDstV4Key, TenantDstV6Key, TenantIpV4Key, TenantIpV6Key,
+    TenantMapEntries,
+};
+use super::threat_intel::{ThreatFeedConfig, ThreatIntelMapEntries};
+use super::traffic::DstTraffic;
+use crate::reputation::GreylistEntry;
 use aya::Ebpf;
-use aya::programs::{Xdp, XdpFlags};
-use parking_lot::RwLock;
+use aya::maps::lpm_trie::{Key as LpmKey, LpmTrie};
+use aya::maps::perf::PerfEventArrayBuffer;
+use aya::maps::{
+    Array, HashMap as BpfHashMap, Map, MapData, PerCpuArray, PerCpuHashMap, PerCpuValues,
+    PerfEventArray, RingBuf,
+};
+use aya::programs::tc::{self, SchedClassifierLinkId};
+use aya::programs::{SchedClassifier, Xdp, XdpFlags};
+use bytes::BytesMut;
+use parking_lot::{Mutex, RwLock};
 use pistonprotection_common::error::{Error, Result};
-use std::collections::HashMap;
-use std::path::Path;
+use pistonprotection_common::signing::read_signed;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tracing::{info, warn};
+use std::time::Instant;
+use tracing::{debug, info, warn};
 
 /// XDP attachment mode
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -40,23 +90,257 @@ pub struct AttachedProgram {
     pub program_name: String,
 }
 
+/// Attached TC classifier info
+#[derive(Debug)]
+pub struct TcAttachment {
+    pub interface: String,
+    pub direction: TcDirection,
+    pub program_name: String,
+    /// Links to detach the classifier with, one per clsact hook
+    links: Vec<SchedClassifierLinkId>,
+}
+
+/// Version and object digest of a loaded program
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramVersionInfo {
+    pub version: String,
+    /// SHA-256 of the object file (hex)
+    pub digest: String,
+    /// Fingerprint of the key that signed the object, if it was verified
+    pub signed_by: Option<String>,
+}
+
+/// Perf event records read per call
+const PERF_READ_BATCH: usize = 64;
+
+/// Where a program's flow samples or drop events are read from
+enum EventSource {
+    /// Ring buffer (full variant)
+    Ring(RingBuf<MapData>),
+    /// Per-CPU buffers of a perf event array (compat variant)
+    Perf(Vec<PerfEventArrayBuffer<MapData>>),
+}
+
+impl EventSource {
+    fn from_map(map: Map) -> std::result::Result<Self, String> {
+        match map {
+            Map::RingBuf(_) => RingBuf::try_from(map)
+                .map(EventSource::Ring)
+                .map_err(|e| e.to_string()),
+            Map::PerfEventArray(_) => {
+                let mut array = PerfEventArray::try_from(map).map_err(|e| e.to_string())?;
+                let cpus = aya::util::online_cpus().map_err(|(_, e)| e.to_string())?;
+                cpus.into_iter()
+                    .map(|cpu| array.open(cpu, None).map_err(|e| e.to_string()))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map(EventSource::Perf)
+            }
+            _ => Err("not a ring buffer or perf event array".to_string()),
+        }
+    }
+
+    /// Read up to `max` records, decoding ring buffer records with `ring`
+    /// and perf event records with `perf`
+    fn drain<T>(
+        &mut self,
+        max: usize,
+        ring_decode: fn(&[u8]) -> Option<T>,
+        perf_decode: fn(&[u8]) -> Option<T>,
+    ) -> Vec<T> {
+        let mut samples = Vec::new();
+        match self {
+            EventSource::Ring(ring) => {
+                while samples.len() < max {
+                    let Some(item) = ring.next() else {
+                        break;
+                    };
+                    if let Some(sample) = ring_decode(&item) {
+                        samples.push(sample);
+                    }
+                }
+            }
+            EventSource::Perf(buffers) => {
+                let mut records = vec![BytesMut::new(); PERF_READ_BATCH];
+                for buffer in buffers.iter_mut() {
+                    while samples.len() < max && buffer.readable() {
+                        let events = match buffer.read_events(&mut records) {
+                            Ok(events) if events.read > 0 => events,
+                            Ok(_) => break,
+                            Err(e) => {
+                                warn!("Failed to read perf event buffer: {}", e);
+                                break;
+                            }
+                        };
+                        samples.extend(
+                            records[..events.read]
+                                .iter()
+                                .filter_map(|record| perf_decode(record)),
+                        );
+                    }
+                }
+                samples.truncate(max);
+            }
+        }
+        samples
+    }
+}
+
 /// eBPF program loader and manager
 pub struct EbpfLoader {
     /// Loaded eBPF objects
     objects: HashMap<String, Ebpf>,
     /// Attached XDP programs
     attached: HashMap<String, AttachedProgram>,
+    /// Attached TC classifiers
+    tc_attached: HashMap<String, TcAttachment>,
     /// Map manager
     maps: Arc<RwLock<MapManager>>,
+    /// Load generation per program, bumped on every (re)load
+    generations: HashMap<String, u64>,
+    /// Version and digest per program
+    versions: HashMap<String, ProgramVersionInfo>,
+    /// Error from the last failed versioned load per program
+    load_errors: HashMap<String, String>,
+    /// Last load or attach failure per program
+    diagnostics: HashMap<String, LoadDiagnostic>,
+    /// Per-CPU stats aggregation state
+    stats_reader: Mutex<StatsReader>,
+    /// Per-source traffic merged from the per-CPU source counters
+    sources: Mutex<SourceLedger>,
+    /// Sample ring buffers (or perf buffers) taken from each loaded program
+    sample_rings: HashMap<String, Mutex<EventSource>>,
+    /// Drop event ring buffers (or perf buffers) taken from each loaded
+    /// program
+    drop_event_rings: HashMap<String, Mutex<EventSource>>,
+    /// Flow sampling rates
+    sampling: SamplingConfig,
+    /// Processing latency measurement rates
+    latency: LatencyRates,
+    /// Event budget written to every loaded program
+    event_budget: EventBudgetConfig,
+    /// Map memory budgets and size overrides
+    budgets: MapBudgets,
+    /// Map sizes each program was loaded with
+    sizing: HashMap<String, SizingPlan>,
+    /// Chunked map writes for large pushes
+    batcher: Batcher,
+    /// Kernel capabilities, used to pick program variants
+    capabilities: KernelCapabilities,
+    /// Penalty ladders written to every loaded program
+    penalty: PenaltyLadder,
+    /// Learning flag and known-good sources written to every loaded program
+    learning: LearningMaps,
+    /// CGNAT detection flag and ranges written to every loaded program
+    cgnat: CgnatMaps,
+    /// Per-connection bandwidth limits written to every loaded program
+    flow_rates: Vec<(TenantDstV6Key, FlowRateConfig)>,
+    /// Backend destinations counted in xdp_filter
+    traffic_keys: Vec<TenantDstV6Key>,
+    /// Marking policies of backend destinations written to xdp_filter
+    marking: Vec<(TenantDstV6Key, MarkingPolicy)>,
+    /// Protected ports written to xdp_tcp and xdp_udp
+    protected_ports: ProtectedPorts,
+    /// DNS query budgets written to xdp_tcp and xdp_http
+    dns_budget: DnsBudget,
+    /// Observe mode flag written to every loaded program
+    observe: bool,
+    /// 0-RTT rejection flag written to xdp_quic
+    reject_0rtt: bool,
+    /// Short header rate limit per DCID written to xdp_quic
+    quic_cid_limit: QuicCidLimit,
+    /// Source addresses bypassing filtering in the layer 4 programs
+    whitelist: Whitelist,
+    /// Conntrack bypass settings written to xdp_tcp and tc_flowmark
+    flow_mark: FlowMarkConfig,
+    /// bpffs directory holding maps shared between programs
+    map_pin_path: PathBuf,
+    /// Signature checks of object files and the bootstrap config
+    artifacts: ArtifactVerifier,
+}
+
+/// Contents of the shared learning maps
+#[derive(Debug, Default, PartialEq, Eq)]
+struct LearningMaps {
+    /// Some backend is in its training window
+    training: bool,
+    /// Known-good sources and their limit multiplier
+    known_good: Vec<([u8; 16], u32)>,
+}
+
+/// Contents of the shared CGNAT maps
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CgnatMaps {
+    /// xdp_tcp records SYN signals
+    detect: bool,
+    /// (prefix length, network, limit multiplier)
+    ranges: Vec<(u32, [u8; 16], u32)>,
+}
+
+/// Contents of the whitelist maps
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Whitelist {
+    /// IPv4 addresses in host byte order
+    v4: Vec<u32>,
+    v6: Vec<[u8; 16]>,
+}
+
+/// Whitelist maps of IPv4 sources, keyed in host byte order
+const WHITELIST_MAPS: [&str; 4] = [
+    "TCP_WHITELIST",
+    "UDP_WHITELIST",
+    "QUIC_WHITELIST",
+    "HTTP_WHITELIST",
+];
+
+/// Whitelist maps of IPv6 sources
+const WHITELIST_V6_MAPS: [&str; 2] = ["TCP_WHITELIST_V6", "HTTP_WHITELIST_V6"];
+
+/// Number of possible CPUs, which per-CPU maps hold a value for
+pub fn possible_cpus() -> u32 {
+    aya::util::nr_cpus().map(|cpus| cpus as u32).unwrap_or(1)
 }
 
+/// Default bpffs directory of shared maps (`PISTON_BPF_PIN_PATH`)
+pub const DEFAULT_MAP_PIN_PATH: &str = "/sys/fs/bpf/pistonprotection";
+
 impl EbpfLoader {
     /// Create a new eBPF loader
     pub fn new() -> Result<Self> {
         Ok(Self {
             objects: HashMap::new(),
             attached: HashMap::new(),
+            tc_attached: HashMap::new(),
             maps: Arc::new(RwLock::new(MapManager::new())),
+            generations: HashMap::new(),
+            versions: HashMap::new(),
+            load_errors: HashMap::new(),
+            diagnostics: HashMap::new(),
+            stats_reader: Mutex::new(StatsReader::new()),
+            sources: Mutex::new(SourceLedger::new()),
+            sample_rings: HashMap::new(),
+            drop_event_rings: HashMap::new(),
+            sampling: SamplingConfig::default(),
+            latency: LatencyRates::default(),
+            event_budget: EventBudgetConfig::default(),
+            budgets: MapBudgets::default(),
+            sizing: HashMap::new(),
+            batcher: Batcher::default(),
+            capabilities: KernelCapabilities::default(),
+            penalty: PenaltyLadder::default(),
+            learning: LearningMaps::default(),
+            cgnat: CgnatMaps::default(),
+            flow_rates: Vec::new(),
+            traffic_keys: Vec::new(),
+            marking: Vec::new(),
+            protected_ports: ProtectedPorts::default(),
+            dns_budget: DnsBudget::default(),
+            observe: false,
+            reject_0rtt: false,
+            quic_cid_limit: QuicCidLimit::default(),
+            whitelist: Whitelist::default(),
+            flow_mark: FlowMarkConfig::default(),
+            map_pin_path: PathBuf::from(DEFAULT_MAP_PIN_PATH),
+            artifacts: ArtifactVerifier::default(),
         })
     }
 
@@ -64,22 +348,266 @@ impl EbpfLoader {
     pub fn load_from_bytes(&mut self, name: &str, data: &[u8]) -> Result<()> {
         info!("Loading eBPF program: {}", name);
 
-        let ebpf = Ebpf::load(data)
-            .map_err(|e| Error::Internal(format!("Failed to load eBPF program: {}", e)))?;
+        // Maps pinned by name (e.g. PENALTY) are shared with the other programs
+        if let Err(e) = std::fs::create_dir_all(&self.map_pin_path) {
+            warn!(
+                "Failed to create map pin directory {:?}: {}",
+                self.map_pin_path, e
+            );
+        }
+        let plan = self.plan_map_sizes(name, data);
+
+        let mut loader = aya::EbpfLoader::new();
+        loader.map_pin_path(&self.map_pin_path);
+        if let Some(plan) = &plan {
+            for map in plan.resized() {
+                loader.set_max_entries(&map.name, map.entries);
+            }
+        }
+        let mut ebpf = match loader.load(data) {
+            Ok(ebpf) => ebpf,
+            Err(e) => {
+                let log = match &e {
+                    aya::EbpfError::ProgramError(e) => verifier_log(e),
+                    _ => None,
+                };
+                self.record_diagnostic(LoadDiagnostic::new(
+                    name,
+                    "",
+                    LoadStage::Object,
+                    e.to_string(),
+                    log.as_deref(),
+                ));
+                return Err(Error::Internal(format!(
+                    "Failed to load eBPF program: {}",
+                    e
+                )));
+            }
+        };
+        self.diagnostics.remove(name);
+
+        // Keep the sample and drop event buffers so they can be drained
+        // without the object
+        if let Some(map) = ebpf.take_map("SAMPLES") {
+            match EventSource::from_map(map) {
+                Ok(source) => {
+                    self.sample_rings
+                        .insert(name.to_string(), Mutex::new(source));
+                }
+                Err(e) => warn!("Invalid sample map in {}: {}", name, e),
+            }
+        }
+        if let Some(map) = ebpf.take_map("DROP_EVENTS") {
+            match EventSource::from_map(map) {
+                Ok(source) => {
+                    self.drop_event_rings
+                        .insert(name.to_string(), Mutex::new(source));
+                }
+                Err(e) => warn!("Invalid drop event map in {}: {}", name, e),
+            }
+        }
 
         self.objects.insert(name.to_string(), ebpf);
+        match plan {
+            Some(plan) => {
+                self.sizing.insert(name.to_string(), plan);
+            }
+            None => {
+                self.sizing.remove(name);
+            }
+        }
+        *self.generations.entry(name.to_string()).or_insert(0) += 1;
+        self.versions.insert(
+            name.to_string(),
+            ProgramVersionInfo {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                digest: object_digest(data),
+                signed_by: None,
+            },
+        );
+
+        let rate = self.sampling.rate_for(name);
+        if let Err(e) = self.write_sampling_rate(name, rate) {
+            warn!("Failed to configure sampling for {}: {}", name, e);
+        }
+        let rate = self.latency.rate_for(name);
+        if let Err(e) = self.write_latency_rate(name, rate) {
+            warn!(
+                "Failed to configure latency measurement for {}: {}",
+                name, e
+            );
+        }
+        if let Err(e) = self.write_event_budget(name) {
+            warn!("Failed to configure event budget for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_penalty_ladder(name) {
+            warn!("Failed to configure penalty ladder for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_learning(name) {
+            warn!("Failed to configure allowlist learning for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_cgnat(name) {
+            warn!("Failed to configure CGNAT ranges for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_flow_rates(name) {
+            warn!(
+                "Failed to configure per-connection bandwidth for {}: {}",
+                name, e
+            );
+        }
+        if let Err(e) = self.write_traffic_keys(name) {
+            warn!("Failed to configure traffic accounting for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_marking(name) {
+            warn!("Failed to configure traffic marking for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_protected_ports(name) {
+            warn!("Failed to configure protected ports for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_dns_budget(name) {
+            warn!("Failed to configure DNS query budgets for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_observe_mode(name) {
+            warn!("Failed to configure observe mode for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_reject_0rtt(name) {
+            warn!("Failed to configure 0-RTT rejection for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_whitelist(name) {
+            warn!("Failed to configure whitelist for {}: {}", name, e);
+        }
+        if let Err(e) = self.write_quic_cid_limit(name) {
+            warn!(
+                "Failed to configure QUIC connection ID limit for {}: {}",
+                name, e
+            );
+        }
+        if let Err(e) = self.write_flow_mark(name) {
+            warn!("Failed to configure conntrack bypass for {}: {}", name, e);
+        }
 
         Ok(())
     }
 
-    /// Load an eBPF program from a file
+    /// Size the maps of a program from the map budgets
+    fn plan_map_sizes(&self, name: &str, data: &[u8]) -> Option<SizingPlan> {
+        let specs = match MapSpec::from_object(data) {
+            Ok(specs) => specs,
+            Err(e) => {
+                warn!("Failed to read map definitions of {}: {}", name, e);
+                return None;
+            }
+        };
+        let plan = self.budgets.plan(name, &specs, possible_cpus());
+
+        for map in plan.resized() {
+            info!(
+                "Sizing map {} of {} to {} entries (declared {})",
+                map.name, name, map.entries, map.declared_entries
+            );
+        }
+        if plan.over_budget {
+            warn!(
+                "Maps of {} need about {} bytes, over the budget of {} bytes",
+                name,
+                plan.memory_bytes,
+                plan.budget.unwrap_or(0)
+            );
+        }
+        Some(plan)
+    }
+
+    /// Load an eBPF program from a file, checked against its signature
     pub fn load_from_file(&mut self, name: &str, path: &Path) -> Result<()> {
         info!("Loading eBPF program from {:?}: {}", path, name);
 
-        let data = std::fs::read(path)
-            .map_err(|e| Error::Internal(format!("Failed to read eBPF file: {}", e)))?;
+        let (data, signature) = read_signed(path)?;
+        let signed_by = self.artifacts.verify(name, &data, signature.as_deref())?;
+
+        self.load_from_bytes(name, &data)?;
+        if let Some(info) = self.versions.get_mut(name) {
+            info.signed_by = signed_by;
+        }
+        Ok(())
+    }
+
+    /// Replace a program with a specific version of its object file
+    ///
+    /// The object must match `expected_digest` and, when artifact signatures
+    /// are enforced, its detached `signature`. Interfaces the previous
+    /// object was attached to are re-attached in the same mode. On failure
+    /// the previous object stays loaded and the error is kept for reporting.
+    pub fn load_version(
+        &mut self,
+        name: &str,
+        version: &str,
+        expected_digest: &str,
+        data: &[u8],
+        signature: Option<&str>,
+    ) -> Result<()> {
+        let result = self.try_load_version(name, version, expected_digest, data, signature);
+        match &result {
+            Ok(()) => {
+                self.load_errors.remove(name);
+            }
+            Err(e) => {
+                self.load_errors.insert(name.to_string(), e.to_string());
+            }
+        }
+        result
+    }
+
+    fn try_load_version(
+        &mut self,
+        name: &str,
+        version: &str,
+        expected_digest: &str,
+        data: &[u8],
+        signature: Option<&str>,
+    ) -> Result<()> {
+        let digest = object_digest(data);
+        if !digest.eq_ignore_ascii_case(expected_digest) {
+            return Err(Error::Internal(format!(
+                "Digest mismatch for {} {}: expected {}, got {}",
+                name, version, expected_digest, digest
+            )));
+        }
+        let signed_by = self.artifacts.verify(name, data, signature)?;
+
+        let reattach: Vec<(String, XdpMode)> = self
+            .attached
+            .values()
+            .filter(|a| a.program_name == name)
+            .map(|a| (a.interface.clone(), a.mode))
+            .collect();
+        let reattach_tc: Vec<(String, TcDirection)> = self
+            .tc_attached
+            .values()
+            .filter(|a| a.program_name == name)
+            .map(|a| (a.interface.clone(), a.direction))
+            .collect();
+        // The links belong to the object being replaced
+        self.tc_attached.retain(|_, a| a.program_name != name);
+
+        info!("Upgrading eBPF program {} to version {}", name, version);
+        self.load_from_bytes(name, data)?;
+        self.versions.insert(
+            name.to_string(),
+            ProgramVersionInfo {
+                version: version.to_string(),
+                digest,
+                signed_by,
+            },
+        );
+
+        for (interface, mode) in reattach {
+            self.attach_xdp_to(name, &interface, mode)?;
+        }
+        for (interface, direction) in reattach_tc {
+            self.attach_tc(name, &interface, direction)?;
+        }
 
-        self.load_from_bytes(name, &data)
+        Ok(())
     }
 
     /// Attach XDP program to an interface
@@ -88,10 +616,19 @@ impl EbpfLoader {
         program_name: &str,
         interface: &NetworkInterface,
         preferred_mode: XdpMode,
+    ) -> Result<()> {
+        self.attach_xdp_to(program_name, &interface.name, preferred_mode)
+    }
+
+    fn attach_xdp_to(
+        &mut self,
+        program_name: &str,
+        interface_name: &str,
+        preferred_mode: XdpMode,
     ) -> Result<()> {
         info!(
             "Attaching XDP program {} to interface {} (mode: {:?})",
-            program_name, interface.name, preferred_mode
+            program_name, interface_name, preferred_mode
         );
 
         let ebpf = self
@@ -100,65 +637,90 @@ impl EbpfLoader {
             .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
 
         // Get the XDP program
-        let program: &mut Xdp = ebpf
-            .program_mut(program_name)
-            .ok_or_else(|| {
-                Error::Internal(format!("Program {} not found in object", program_name))
-            })?
+        let program = ebpf.program_mut(program_name).ok_or_else(|| {
+            Error::Internal(format!("Program {} not found in object", program_name))
+        })?;
+        let section = program_section(program);
+        let program: &mut Xdp = program
             .try_into()
             .map_err(|e| Error::Internal(format!("Not an XDP program: {}", e)))?;
 
-        // Load the program
-        program
-            .load()
-            .map_err(|e| Error::Internal(format!("Failed to load XDP program: {}", e)))?;
+        // Load the program; this is where the verifier runs
+        if let Err(e) = program.load() {
+            let log = verifier_log(&e);
+            let stage = if log.is_some() {
+                LoadStage::Verifier
+            } else {
+                LoadStage::Object
+            };
+            self.record_diagnostic(LoadDiagnostic::new(
+                program_name,
+                section,
+                stage,
+                e.to_string(),
+                log.as_deref(),
+            ));
+            return Err(Error::Internal(format!(
+                "Failed to load XDP program: {}",
+                e
+            )));
+        }
 
         // Try to attach with preferred mode, falling back to generic
         // Note: try_attach_program is a standalone function to avoid borrow issues
-        let (mode, _flags) = match preferred_mode {
+        let attached = match preferred_mode {
             XdpMode::Offload => {
                 // Try offload, fall back to driver, then generic
-                if try_attach_program(program, &interface.name, XdpFlags::HW_MODE) {
-                    (XdpMode::Offload, XdpFlags::HW_MODE)
-                } else if try_attach_program(program, &interface.name, XdpFlags::DRV_MODE) {
+                if try_attach_program(program, interface_name, XdpFlags::HW_MODE) {
+                    Ok((XdpMode::Offload, XdpFlags::HW_MODE))
+                } else if try_attach_program(program, interface_name, XdpFlags::DRV_MODE) {
                     warn!("Offload mode not supported, using driver mode");
-                    (XdpMode::Driver, XdpFlags::DRV_MODE)
+                    Ok((XdpMode::Driver, XdpFlags::DRV_MODE))
                 } else {
                     warn!("Driver mode not supported, using generic mode");
                     program
-                        .attach(&interface.name, XdpFlags::SKB_MODE)
-                        .map_err(|e| Error::Internal(format!("Failed to attach XDP: {}", e)))?;
-                    (XdpMode::Generic, XdpFlags::SKB_MODE)
+                        .attach(interface_name, XdpFlags::SKB_MODE)
+                        .map(|_| (XdpMode::Generic, XdpFlags::SKB_MODE))
                 }
             }
             XdpMode::Driver => {
-                if try_attach_program(program, &interface.name, XdpFlags::DRV_MODE) {
-                    (XdpMode::Driver, XdpFlags::DRV_MODE)
+                if try_attach_program(program, interface_name, XdpFlags::DRV_MODE) {
+                    Ok((XdpMode::Driver, XdpFlags::DRV_MODE))
                 } else {
                     warn!("Driver mode not supported, using generic mode");
                     program
-                        .attach(&interface.name, XdpFlags::SKB_MODE)
-                        .map_err(|e| Error::Internal(format!("Failed to attach XDP: {}", e)))?;
-                    (XdpMode::Generic, XdpFlags::SKB_MODE)
+                        .attach(interface_name, XdpFlags::SKB_MODE)
+                        .map(|_| (XdpMode::Generic, XdpFlags::SKB_MODE))
                 }
             }
-            XdpMode::Generic => {
-                program
-                    .attach(&interface.name, XdpFlags::SKB_MODE)
-                    .map_err(|e| Error::Internal(format!("Failed to attach XDP: {}", e)))?;
-                (XdpMode::Generic, XdpFlags::SKB_MODE)
+            XdpMode::Generic => program
+                .attach(interface_name, XdpFlags::SKB_MODE)
+                .map(|_| (XdpMode::Generic, XdpFlags::SKB_MODE)),
+        };
+        let (mode, _flags) = match attached {
+            Ok(attached) => attached,
+            Err(e) => {
+                self.record_diagnostic(LoadDiagnostic::new(
+                    program_name,
+                    section,
+                    LoadStage::Attach,
+                    format!("{} on {}", e, interface_name),
+                    None,
+                ));
+                return Err(Error::Internal(format!("Failed to attach XDP: {}", e)));
             }
         };
+        self.diagnostics.remove(program_name);
 
         info!(
             "Attached XDP program {} to {} with mode {:?}",
-            program_name, interface.name, mode
+            program_name, interface_name, mode
         );
 
         self.attached.insert(
-            interface.name.clone(),
+            interface_name.to_string(),
             AttachedProgram {
-                interface: interface.name.clone(),
+                interface: interface_name.to_string(),
                 mode,
                 program_name: program_name.to_string(),
             },
@@ -180,6 +742,132 @@ impl EbpfLoader {
         Ok(())
     }
 
+    /// Attach a TC classifier to the clsact hooks of an interface
+    ///
+    /// The clsact qdisc is added when the interface has none yet. A
+    /// classifier already attached to the interface is detached first.
+    pub fn attach_tc(
+        &mut self,
+        program_name: &str,
+        interface_name: &str,
+        direction: TcDirection,
+    ) -> Result<()> {
+        info!(
+            "Attaching TC program {} to interface {} ({:?})",
+            program_name, interface_name, direction
+        );
+        self.detach_tc(interface_name)?;
+
+        // Fails with EEXIST when the interface already has the qdisc
+        if let Err(e) = tc::qdisc_add_clsact(interface_name) {
+            debug!("clsact qdisc not added to {}: {}", interface_name, e);
+        }
+
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+        let program = ebpf.program_mut(program_name).ok_or_else(|| {
+            Error::Internal(format!("Program {} not found in object", program_name))
+        })?;
+        let section = program_section(program);
+        let program: &mut SchedClassifier = program
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Not a TC program: {}", e)))?;
+
+        // Loaded once per object, then attached to every interface
+        if program.fd().is_err()
+            && let Err(e) = program.load()
+        {
+            let log = verifier_log(&e);
+            let stage = if log.is_some() {
+                LoadStage::Verifier
+            } else {
+                LoadStage::Object
+            };
+            self.record_diagnostic(LoadDiagnostic::new(
+                program_name,
+                section,
+                stage,
+                e.to_string(),
+                log.as_deref(),
+            ));
+            return Err(Error::Internal(format!("Failed to load TC program: {}", e)));
+        }
+
+        let mut links = Vec::new();
+        for attach_type in direction.attach_types() {
+            match program.attach(interface_name, *attach_type) {
+                Ok(link) => links.push(link),
+                Err(e) => {
+                    for link in links {
+                        let _ = program.detach(link);
+                    }
+                    self.record_diagnostic(LoadDiagnostic::new(
+                        program_name,
+                        section,
+                        LoadStage::Attach,
+                        format!("{} on {} {:?}", e, interface_name, attach_type),
+                        None,
+                    ));
+                    return Err(Error::Internal(format!("Failed to attach TC: {}", e)));
+                }
+            }
+        }
+        self.diagnostics.remove(program_name);
+
+        info!(
+            "Attached TC program {} to {} ({:?})",
+            program_name, interface_name, direction
+        );
+
+        self.tc_attached.insert(
+            interface_name.to_string(),
+            TcAttachment {
+                interface: interface_name.to_string(),
+                direction,
+                program_name: program_name.to_string(),
+                links,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Detach the TC classifier from an interface
+    ///
+    /// The clsact qdisc stays, as other classifiers may be attached to it.
+    pub fn detach_tc(&mut self, interface_name: &str) -> Result<()> {
+        let Some(attached) = self.tc_attached.remove(interface_name) else {
+            return Ok(());
+        };
+        info!(
+            "Detaching TC program {} from {}",
+            attached.program_name, interface_name
+        );
+
+        let program = self
+            .objects
+            .get_mut(&attached.program_name)
+            .and_then(|ebpf| ebpf.program_mut(&attached.program_name))
+            .and_then(|program| <&mut SchedClassifier>::try_from(program).ok());
+        // Without the object the links went with it
+        let Some(program) = program else {
+            return Ok(());
+        };
+        for link in attached.links {
+            program
+                .detach(link)
+                .map_err(|e| Error::Internal(format!("Failed to detach TC: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Get list of attached TC classifiers
+    pub fn list_tc_attached(&self) -> Vec<&TcAttachment> {
+        self.tc_attached.values().collect()
+    }
+
     /// Get the map manager
     pub fn maps(&self) -> Arc<RwLock<MapManager>> {
         Arc::clone(&self.maps)
@@ -210,33 +898,1951 @@ impl EbpfLoader {
         Ok(())
     }
 
-    /// Get list of attached programs
-    pub fn list_attached(&self) -> Vec<&AttachedProgram> {
-        self.attached.values().collect()
+    /// Read a program's per-CPU stats map and compute deltas since the last read
+    pub fn read_stats<T: ProgramStats>(&self) -> Result<StatsSnapshot<T>> {
+        let ebpf = self
+            .objects
+            .get(T::PROGRAM)
+            .ok_or_else(|| Error::not_found("eBPF program", T::PROGRAM))?;
+
+        let map: PerCpuArray<_, T> = ebpf
+            .map(T::MAP_NAME)
+            .ok_or_else(|| Error::Internal(format!("Map {} not found", T::MAP_NAME)))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let values = map
+            .get(&0, 0)
+            .map_err(|e| Error::Internal(format!("Failed to read stats map: {}", e)))?;
+
+        let generation = self.generations.get(T::PROGRAM).copied().unwrap_or(0);
+
+        Ok(self
+            .stats_reader
+            .lock()
+            .observe(&values, generation, Instant::now()))
     }
 
-    /// Check if a program is attached to an interface
-    pub fn is_attached(&self, interface_name: &str) -> bool {
-        self.attached.contains_key(interface_name)
+    /// Read a program's per-port and per-reason drop breakdown maps
+    pub fn read_drop_breakdown(&self, program_name: &str) -> Result<DropBreakdown> {
+        let ebpf = self
+            .objects
+            .get(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        let by_port: PerCpuHashMap<_, u32, DropCounter> = ebpf
+            .map("DROPS_BY_DST_PORT")
+            .ok_or_else(|| Error::Internal("Map DROPS_BY_DST_PORT not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let mut ports = Vec::new();
+        for entry in by_port.iter() {
+            let (key, values) =
+                entry.map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+            ports.push((key, values.iter().copied().collect()));
+        }
+
+        let by_reason: PerCpuArray<_, DropCounter> = ebpf
+            .map("DROPS_BY_REASON")
+            .ok_or_else(|| Error::Internal("Map DROPS_BY_REASON not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let mut reasons = Vec::new();
+        for index in 0..by_reason.len() {
+            let values = by_reason
+                .get(&index, 0)
+                .map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+            reasons.push(values.iter().copied().collect());
+        }
+
+        let generation = self.generations.get(program_name).copied().unwrap_or(0);
+
+        Ok(self
+            .stats_reader
+            .lock()
+            .observe_breakdown(program_name, generation, &ports, &reasons))
     }
-}
 
-/// Try to attach XDP program with specified flags
-/// Returns true if attachment succeeded, false otherwise
-fn try_attach_program(program: &mut Xdp, interface_name: &str, flags: XdpFlags) -> bool {
-    // Try to attach with the specified flags
-    // Returns false to fall back to next mode (this is a placeholder implementation)
-    // In a real implementation, we would try to attach and check for errors
-    program.attach(interface_name, flags).is_ok()
-}
+    /// Replace the xdp_filter canary entries with the given source verdicts
+    ///
+    /// Counters start from zero for every entry written.
+    pub fn set_canary_entries(&mut self, entries: &[(IpAddr, u32)]) -> Result<()> {
+        self.clear_canary_entries()?;
 
-impl Drop for EbpfLoader {
-    fn drop(&mut self) {
-        info!("Cleaning up eBPF programs");
-        // Programs are automatically detached when dropped
-        self.attached.clear();
-        self.objects.clear();
+        let nr_cpus = aya::util::nr_cpus()
+            .map_err(|(_, e)| Error::Internal(format!("Failed to count CPUs: {}", e)))?;
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let values = |verdict: u32| {
+            PerCpuValues::try_from(vec![
+                CanaryEntry {
+                    verdict,
+                    ..Default::default()
+                };
+                nr_cpus
+            ])
+            .map_err(|e| Error::Internal(format!("Invalid per-CPU values: {}", e)))
+        };
+
+        let mut v4: PerCpuHashMap<_, u32, CanaryEntry> = canary_map(ebpf, "CANARY_IPS_V4")?;
+        for (ip, verdict) in entries {
+            if let IpAddr::V4(addr) = ip {
+                v4.insert(u32::from(*addr), values(*verdict)?, 0)
+                    .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+            }
+        }
+
+        let mut v6: PerCpuHashMap<_, [u8; 16], CanaryEntry> = canary_map(ebpf, "CANARY_IPS_V6")?;
+        for (ip, verdict) in entries {
+            if let IpAddr::V6(addr) = ip {
+                v6.insert(addr.octets(), values(*verdict)?, 0)
+                    .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove all xdp_filter canary entries
+    pub fn clear_canary_entries(&mut self) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let mut v4: PerCpuHashMap<_, u32, CanaryEntry> = canary_map(ebpf, "CANARY_IPS_V4")?;
+        let keys: Vec<u32> = v4.keys().filter_map(|k| k.ok()).collect();
+        for key in keys {
+            let _ = v4.remove(&key);
+        }
+
+        let mut v6: PerCpuHashMap<_, [u8; 16], CanaryEntry> = canary_map(ebpf, "CANARY_IPS_V6")?;
+        let keys: Vec<[u8; 16]> = v6.keys().filter_map(|k| k.ok()).collect();
+        for key in keys {
+            let _ = v6.remove(&key);
+        }
+
+        Ok(())
+    }
+
+    /// Replace the contents of the xdp_filter tenant maps
+    ///
+    /// Stale keys are removed after the new entries are written, so sources
+    /// never fall back to the global namespace while the update runs.
+    pub fn set_tenant_entries(&mut self, entries: &TenantMapEntries) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let mut dst_v4 = HashMap::new();
+        let mut dst_v6 = HashMap::new();
+        for (destination, tenant_id) in &entries.destinations {
+            match destination.addr {
+                IpAddr::V4(addr) => {
+                    dst_v4.insert(
+                        TenantDstV4Key {
+                            addr: u32::from(addr),
+                            port: destination.port,
+                            _pad: 0,
+                        },
+                        *tenant_id,
+                    );
+                }
+                IpAddr::V6(addr) => {
+                    dst_v6.insert(
+                        TenantDstV6Key {
+                            addr: addr.octets(),
+                            port: destination.port,
+                            _pad: 0,
+                        },
+                        *tenant_id,
+                    );
+                }
+            }
+        }
+
+        let mut blocked_v4 = HashSet::new();
+        let mut blocked_v6 = HashSet::new();
+        for (tenant_id, ip) in &entries.blocked {
+            match ip {
+                IpAddr::V4(addr) => {
+                    blocked_v4.insert(TenantIpV4Key {
+                        tenant_id: *tenant_id,
+                        addr: u32::from(*addr),
+                    });
+                }
+                IpAddr::V6(addr) => {
+                    blocked_v6.insert(TenantIpV6Key {
+                        tenant_id: *tenant_id,
+                        addr: addr.octets(),
+                    });
+                }
+            }
+        }
+
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "TENANT_CONFIG",
+            entries.configs.iter().copied(),
+        )?;
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "TENANT_DESTINATIONS_V4",
+            dst_v4.into_iter(),
+        )?;
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "TENANT_DESTINATIONS_V6",
+            dst_v6.into_iter(),
+        )?;
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "TENANT_BLOCKED_V4",
+            blocked_v4.into_iter().map(|key| (key, REASON_MANUAL)),
+        )?;
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "TENANT_BLOCKED_V6",
+            blocked_v6.into_iter().map(|key| (key, REASON_MANUAL)),
+        )?;
+
+        Ok(())
     }
+
+    /// Replace the contents of the xdp_filter threat intelligence maps
+    ///
+    /// Feed settings are written first and stale prefixes removed last, so
+    /// networks listed before and after the update stay blocked throughout.
+    pub fn set_threat_intel(&mut self, entries: &ThreatIntelMapEntries) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let mut feeds: Array<_, ThreatFeedConfig> = ebpf
+            .map_mut("THREAT_FEEDS")
+            .ok_or_else(|| Error::Internal("Map THREAT_FEEDS not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        for index in 0..feeds.len() {
+            let config = entries
+                .feeds
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default();
+            feeds
+                .set(index, config, 0)
+                .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+        }
+
+        replace_lpm_trie(ebpf, &self.batcher, "THREAT_INTEL_V4", &entries.v4)?;
+        replace_lpm_trie(ebpf, &self.batcher, "THREAT_INTEL_V6", &entries.v6)?;
+
+        Ok(())
+    }
+
+    /// Replace the contents of the xdp_filter greylist maps
+    pub fn set_greylist(&mut self, entries: &[(IpAddr, GreylistEntry)]) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "GREYLIST_V4",
+            entries.iter().filter_map(|(addr, entry)| match addr {
+                IpAddr::V4(addr) => Some((u32::from(*addr), *entry)),
+                IpAddr::V6(_) => None,
+            }),
+        )?;
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "GREYLIST_V6",
+            entries.iter().filter_map(|(addr, entry)| match addr {
+                IpAddr::V6(addr) => Some((addr.octets(), *entry)),
+                IpAddr::V4(_) => None,
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    /// Replace the contents of the xdp_filter backend mode maps
+    ///
+    /// Takes the destinations of backends switched out of protection, with
+    /// their mode (`backend_mode::*` of the eBPF crate).
+    pub fn set_backend_mode_entries(&mut self, entries: &[(TenantDestination, u32)]) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "BACKEND_MODES_V4",
+            entries
+                .iter()
+                .filter_map(|(destination, mode)| match destination.addr {
+                    IpAddr::V4(addr) => Some((
+                        TenantDstV4Key {
+                            addr: u32::from(addr),
+                            port: destination.port,
+                            _pad: 0,
+                        },
+                        *mode,
+                    )),
+                    IpAddr::V6(_) => None,
+                }),
+        )?;
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "BACKEND_MODES_V6",
+            entries
+                .iter()
+                .filter_map(|(destination, mode)| match destination.addr {
+                    IpAddr::V6(addr) => Some((
+                        TenantDstV6Key {
+                            addr: addr.octets(),
+                            port: destination.port,
+                            _pad: 0,
+                        },
+                        *mode,
+                    )),
+                    IpAddr::V4(_) => None,
+                }),
+        )?;
+
+        Ok(())
+    }
+
+    /// Replace the contents of the xdp_filter honeypot port maps
+    pub fn set_honeypot_entries(&mut self, entries: &HoneypotMapEntries) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "HONEYPOT_PORTS_V4",
+            entries.v4.iter().copied(),
+        )?;
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "HONEYPOT_PORTS_V6",
+            entries.v6.iter().copied(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Read and clear the sources xdp_filter saw on honeypot ports
+    ///
+    /// A source hitting a honeypot again after the read shows up in the next
+    /// one, so every drain reports only new activity.
+    pub fn take_honeypot_hits(&mut self) -> Result<Vec<(IpAddr, HoneypotHit)>> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let mut hits = Vec::new();
+
+        let mut v4: BpfHashMap<_, u32, HoneypotHit> = ebpf
+            .map_mut("HONEYPOT_HITS_V4")
+            .ok_or_else(|| Error::Internal("Map HONEYPOT_HITS_V4 not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        let entries: Vec<(u32, HoneypotHit)> = v4.iter().filter_map(|e| e.ok()).collect();
+        for (key, hit) in entries {
+            let _ = v4.remove(&key);
+            hits.push((IpAddr::from(std::net::Ipv4Addr::from(key)), hit));
+        }
+
+        let mut v6: BpfHashMap<_, [u8; 16], HoneypotHit> = ebpf
+            .map_mut("HONEYPOT_HITS_V6")
+            .ok_or_else(|| Error::Internal("Map HONEYPOT_HITS_V6 not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        let entries: Vec<([u8; 16], HoneypotHit)> = v6.iter().filter_map(|e| e.ok()).collect();
+        for (key, hit) in entries {
+            let _ = v6.remove(&key);
+            hits.push((IpAddr::from(std::net::Ipv6Addr::from(key)), hit));
+        }
+
+        Ok(hits)
+    }
+
+    /// Lower the per-source connection counters of xdp_tcp to the
+    /// connections still open, removing those idle for `idle_timeout_ns`
+    ///
+    /// A counter is re-read right before it is written and only ever
+    /// lowered, so SYNs and FINs handled during the sweep are not undone.
+    pub fn reconcile_connections(&mut self, idle_timeout_ns: u64) -> Result<ReconcileReport> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_tcp")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_tcp"))?;
+        let now_ns = connections::monotonic_ns();
+
+        let table: BpfHashMap<_, u64, TcpConnectionState> = ebpf
+            .map("TCP_CONNECTIONS")
+            .ok_or_else(|| Error::Internal("Map TCP_CONNECTIONS not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        let open: Vec<(u64, TcpConnectionState)> = table.iter().filter_map(|e| e.ok()).collect();
+
+        let v4: BpfHashMap<_, u32, TcpIpState> = ebpf
+            .map("TCP_IP_STATE_V4")
+            .ok_or_else(|| Error::Internal("Map TCP_IP_STATE_V4 not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        let mut counters: Vec<([u8; 16], u32)> = v4
+            .iter()
+            .filter_map(|e| e.ok())
+            .filter(|(_, state)| state.active_connections > 0)
+            .map(|(key, state)| (connections::v4_key(key), state.active_connections))
+            .collect();
+        let v6: BpfHashMap<_, [u8; 16], TcpIpState> = ebpf
+            .map("TCP_IP_STATE_V6")
+            .ok_or_else(|| Error::Internal("Map TCP_IP_STATE_V6 not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        counters.extend(
+            v6.iter()
+                .filter_map(|e| e.ok())
+                .filter(|(_, state)| state.active_connections > 0)
+                .map(|(key, state)| (key, state.active_connections)),
+        );
+
+        let result = connections::reconcile(&open, &counters, now_ns, idle_timeout_ns);
+        let mut report = ReconcileReport {
+            open_connections: connections::open_connections(&open, now_ns, idle_timeout_ns)
+                .values()
+                .map(|&count| count as usize)
+                .sum(),
+            ..Default::default()
+        };
+
+        let mut table: BpfHashMap<_, u64, TcpConnectionState> = ebpf
+            .map_mut("TCP_CONNECTIONS")
+            .ok_or_else(|| Error::Internal("Map TCP_CONNECTIONS not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        for key in &result.stale {
+            if table.remove(key).is_ok() {
+                report.stale_removed += 1;
+            }
+        }
+
+        for (addr, count) in result.counters {
+            let lowered = match Ipv6Addr::from(addr).to_ipv4_mapped() {
+                Some(v4_addr) => {
+                    let mut map: BpfHashMap<_, u32, TcpIpState> = ebpf
+                        .map_mut("TCP_IP_STATE_V4")
+                        .ok_or_else(|| {
+                            Error::Internal("Map TCP_IP_STATE_V4 not found".to_string())
+                        })?
+                        .try_into()
+                        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+                    lower_active_connections(&mut map, u32::from(v4_addr), count)
+                }
+                None => {
+                    let mut map: BpfHashMap<_, [u8; 16], TcpIpState> = ebpf
+                        .map_mut("TCP_IP_STATE_V6")
+                        .ok_or_else(|| {
+                            Error::Internal("Map TCP_IP_STATE_V6 not found".to_string())
+                        })?
+                        .try_into()
+                        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+                    lower_active_connections(&mut map, addr, count)
+                }
+            };
+            if lowered > 0 {
+                report.sources_lowered += 1;
+                report.slots_returned += u64::from(lowered);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Read and decode the entries of the connection tables, skipping the
+    /// tables of programs that are not loaded
+    pub fn read_connections(&self, tables: &[ConnectionTable]) -> Result<Vec<ConnectionEntry>> {
+        let now_ns = connections::monotonic_ns();
+        let mut entries = Vec::new();
+
+        for &table in tables {
+            let Some(ebpf) = self.objects.get(table.program()) else {
+                continue;
+            };
+            let map = ebpf
+                .map(table.map_name())
+                .ok_or_else(|| Error::Internal(format!("Map {} not found", table.map_name())))?;
+
+            match table {
+                ConnectionTable::Tcp => {
+                    let map: BpfHashMap<_, u64, TcpConnectionState> = map
+                        .try_into()
+                        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+                    entries.extend(
+                        map.iter()
+                            .filter_map(|e| e.ok())
+                            .map(|(key, conn)| conn_table::decode_tcp(key, &conn, now_ns)),
+                    );
+                }
+                ConnectionTable::Quic => {
+                    let map: BpfHashMap<_, u64, QuicConnectionState> = map
+                        .try_into()
+                        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+                    entries.extend(
+                        map.iter()
+                            .filter_map(|e| e.ok())
+                            .map(|(key, conn)| conn_table::decode_quic(key, &conn, now_ns)),
+                    );
+                }
+                ConnectionTable::MinecraftJava => {
+                    let map: BpfHashMap<_, u64, McConnectionState> = map
+                        .try_into()
+                        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+                    entries.extend(
+                        map.iter().filter_map(|e| e.ok()).map(|(key, conn)| {
+                            conn_table::decode_minecraft_java(key, &conn, now_ns)
+                        }),
+                    );
+                }
+                ConnectionTable::MinecraftBedrock => {
+                    let map: BpfHashMap<_, u64, BedrockConnectionState> = map
+                        .try_into()
+                        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+                    entries.extend(map.iter().filter_map(|e| e.ok()).map(|(key, conn)| {
+                        conn_table::decode_minecraft_bedrock(key, &conn, now_ns)
+                    }));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Read the xdp_filter threat feed hit counters, summed across CPUs and
+    /// indexed by feed id
+    pub fn read_threat_feed_hits(&self) -> Result<Vec<DropCounter>> {
+        let ebpf = self
+            .objects
+            .get("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let hits: PerCpuArray<_, DropCounter> = ebpf
+            .map("THREAT_FEED_HITS")
+            .ok_or_else(|| Error::Internal("Map THREAT_FEED_HITS not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        (0..hits.len())
+            .map(|index| {
+                let values = hits
+                    .get(&index, 0)
+                    .map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+                Ok(values
+                    .iter()
+                    .fold(DropCounter::default(), |acc, v| DropCounter {
+                        packets: acc.packets.wrapping_add(v.packets),
+                        bytes: acc.bytes.wrapping_add(v.bytes),
+                    }))
+            })
+            .collect()
+    }
+
+    /// Read the xdp_filter canary counters, summed across CPUs
+    pub fn read_canary_counters(&self) -> Result<Vec<(IpAddr, CanaryEntry)>> {
+        let ebpf = self
+            .objects
+            .get("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let mut counters = Vec::new();
+
+        let v4: PerCpuHashMap<_, u32, CanaryEntry> = ebpf
+            .map("CANARY_IPS_V4")
+            .ok_or_else(|| Error::Internal("Map CANARY_IPS_V4 not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        for entry in v4.iter() {
+            let (key, values) =
+                entry.map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+            counters.push((
+                IpAddr::from(std::net::Ipv4Addr::from(key)),
+                sum_canary(&values),
+            ));
+        }
+
+        let v6: PerCpuHashMap<_, [u8; 16], CanaryEntry> = ebpf
+            .map("CANARY_IPS_V6")
+            .ok_or_else(|| Error::Internal("Map CANARY_IPS_V6 not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        for entry in v6.iter() {
+            let (key, values) =
+                entry.map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+            counters.push((
+                IpAddr::from(std::net::Ipv6Addr::from(key)),
+                sum_canary(&values),
+            ));
+        }
+
+        Ok(counters)
+    }
+
+    /// Set the kernel capabilities probed at startup
+    pub fn set_capabilities(&mut self, capabilities: KernelCapabilities) {
+        self.capabilities = capabilities;
+    }
+
+    /// Kernel capabilities probed at startup
+    pub fn capabilities(&self) -> &KernelCapabilities {
+        &self.capabilities
+    }
+
+    /// Program variant to load on this kernel, if any
+    pub fn program_variant(&self) -> Option<ProgramVariant> {
+        self.capabilities.select_variant()
+    }
+
+    /// Set the sampling configuration used for programs loaded from now on
+    pub fn set_sampling_config(&mut self, sampling: SamplingConfig) {
+        self.sampling = sampling;
+    }
+
+    /// Set the map budgets used for programs loaded from now on
+    pub fn set_map_budgets(&mut self, budgets: MapBudgets) {
+        self.budgets = budgets;
+    }
+
+    /// Set the batcher used for map pushes
+    pub fn set_batcher(&mut self, batcher: Batcher) {
+        self.batcher = batcher;
+    }
+
+    /// Map budgets and size overrides
+    pub fn map_budgets(&self) -> &MapBudgets {
+        &self.budgets
+    }
+
+    /// Map sizes a program was loaded with
+    pub fn sizing_plan(&self, program_name: &str) -> Option<&SizingPlan> {
+        self.sizing.get(program_name)
+    }
+
+    /// Handles on the maps of every loaded program, to count their entries
+    /// without holding the loader
+    pub fn map_handles(&self) -> Vec<MapHandle> {
+        let mut handles = Vec::new();
+        for (program, ebpf) in &self.objects {
+            let plan = self.sizing.get(program);
+            for (name, map) in ebpf.maps() {
+                match MapHandle::new(program, name, map, plan) {
+                    Ok(handle) => handles.push(handle),
+                    Err(e) => debug!("Skipping map {} of {}: {}", name, program, e),
+                }
+            }
+        }
+        handles
+    }
+
+    /// Set the latency measurement rates used for programs loaded from now on
+    pub fn set_latency_rates(&mut self, latency: LatencyRates) {
+        self.latency = latency;
+    }
+
+    /// Set the event budget used for programs loaded from now on
+    pub fn set_event_budget(&mut self, budget: EventBudgetConfig) {
+        self.event_budget = budget;
+    }
+
+    /// Set the bpffs directory of shared maps used for programs loaded from
+    /// now on
+    pub fn set_map_pin_path(&mut self, path: impl Into<PathBuf>) {
+        self.map_pin_path = path.into();
+    }
+
+    /// Current penalty ladders
+    pub fn penalty_ladder(&self) -> &PenaltyLadder {
+        &self.penalty
+    }
+
+    /// Replace the penalty ladders
+    ///
+    /// `PENALTY_CONFIG` is pinned and shared, but it is written through every
+    /// loaded program so programs loaded without a pin directory see it too.
+    pub fn set_penalty_ladder(&mut self, ladder: PenaltyLadder) -> Result<()> {
+        if self.penalty == ladder {
+            return Ok(());
+        }
+        self.penalty = ladder;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_penalty_ladder(&name)?;
+        }
+        Ok(())
+    }
+
+    fn write_penalty_ladder(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // xdp_filter and xdp_ratelimit escalate nothing
+        let Some(map) = ebpf.map_mut("PENALTY_CONFIG") else {
+            return Ok(());
+        };
+        let mut map: Array<_, PenaltyConfig> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        for (level, config) in self.penalty.levels.iter().enumerate() {
+            map.set(level as u32, config, 0)
+                .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the learning flag and the known-good sources
+    ///
+    /// Like the penalty ladder, the shared learning maps are written through
+    /// every loaded program, and only when they change.
+    pub fn set_learning(&mut self, training: bool, known_good: Vec<([u8; 16], u32)>) -> Result<()> {
+        let learning = LearningMaps {
+            training,
+            known_good,
+        };
+        if self.learning == learning {
+            return Ok(());
+        }
+        self.learning = learning;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_learning(&name)?;
+        }
+        Ok(())
+    }
+
+    fn write_learning(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Only xdp_tcp, xdp_minecraft and xdp_quic see handshakes
+        let Some(map) = ebpf.map_mut("LEARNING") else {
+            return Ok(());
+        };
+        let mut map: Array<_, u32> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        map.set(0, self.learning.training as u32, 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "KNOWN_GOOD",
+            self.learning.known_good.iter().copied(),
+        )
+    }
+
+    /// Set the CGNAT detection flag and ranges
+    ///
+    /// Like the learning maps, the shared CGNAT maps are written through
+    /// every loaded program, and only when they change.
+    pub fn set_cgnat(&mut self, detect: bool, ranges: Vec<(u32, [u8; 16], u32)>) -> Result<()> {
+        let cgnat = CgnatMaps { detect, ranges };
+        if self.cgnat == cgnat {
+            return Ok(());
+        }
+        self.cgnat = cgnat;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_cgnat(&name)?;
+        }
+        Ok(())
+    }
+
+    fn write_cgnat(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Only xdp_tcp records SYN signals
+        if let Some(map) = ebpf.map_mut("CGNAT_DETECT") {
+            let mut map: Array<_, u32> = map
+                .try_into()
+                .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+            map.set(0, self.cgnat.detect as u32, 0)
+                .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+        }
+
+        // Only xdp_tcp, xdp_minecraft and xdp_quic apply per-IP limits
+        if ebpf.map("CGNAT_RANGES").is_none() {
+            return Ok(());
+        }
+        replace_lpm_trie(ebpf, &self.batcher, "CGNAT_RANGES", &self.cgnat.ranges)
+    }
+
+    /// Set the per-connection bandwidth limits of the backend destinations
+    ///
+    /// Like the CGNAT maps, the shared `FLOW_RATES` map is written through
+    /// every loaded program, and only when it changes.
+    pub fn set_flow_rates(&mut self, entries: Vec<(TenantDstV6Key, FlowRateConfig)>) -> Result<()> {
+        if self.flow_rates == entries {
+            return Ok(());
+        }
+        self.flow_rates = entries;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_flow_rates(&name)?;
+        }
+        Ok(())
+    }
+
+    fn write_flow_rates(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Only xdp_tcp and xdp_udp limit established flows
+        if ebpf.map("FLOW_RATES").is_none() {
+            return Ok(());
+        }
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "FLOW_RATES",
+            self.flow_rates.iter().copied(),
+        )
+    }
+
+    /// Set the backend destinations whose traffic xdp_filter counts
+    ///
+    /// Destinations already counted keep their counters, new ones start
+    /// from zero and removed ones are dropped.
+    pub fn set_traffic_keys(&mut self, keys: Vec<TenantDstV6Key>) -> Result<()> {
+        if self.traffic_keys == keys {
+            return Ok(());
+        }
+        self.traffic_keys = keys;
+        if self.objects.contains_key("xdp_filter") {
+            self.write_traffic_keys("xdp_filter")?;
+        }
+        Ok(())
+    }
+
+    fn write_traffic_keys(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+        let Some(map) = ebpf.map_mut("DST_TRAFFIC") else {
+            return Ok(());
+        };
+        let mut map: PerCpuHashMap<_, TenantDstV6Key, DstTraffic> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let keep: HashSet<TenantDstV6Key> = self.traffic_keys.iter().copied().collect();
+        let existing: HashSet<TenantDstV6Key> = map.keys().filter_map(|k| k.ok()).collect();
+        for key in existing.difference(&keep) {
+            let _ = map.remove(key);
+        }
+
+        let nr_cpus = aya::util::nr_cpus()
+            .map_err(|(_, e)| Error::Internal(format!("Failed to count CPUs: {}", e)))?;
+        for key in keep.difference(&existing) {
+            let values = PerCpuValues::try_from(vec![DstTraffic::default(); nr_cpus])
+                .map_err(|e| Error::Internal(format!("Invalid per-CPU values: {}", e)))?;
+            map.insert(*key, values, 0)
+                .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Set the protected ports of xdp_tcp and xdp_udp
+    pub fn set_protected_ports(&mut self, ports: ProtectedPorts) -> Result<()> {
+        ports.validate()?;
+        if self.protected_ports == ports {
+            return Ok(());
+        }
+        self.protected_ports = ports;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_protected_ports(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Protected ports currently set
+    pub fn protected_ports(&self) -> &ProtectedPorts {
+        &self.protected_ports
+    }
+
+    fn write_protected_ports(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Protected TCP ports serving DNS are flagged for the query budget
+        if ebpf.map("TCP_PROTECTED_PORTS").is_some() {
+            replace_hash_map(
+                ebpf,
+                &self.batcher,
+                "TCP_PROTECTED_PORTS",
+                self.protected_ports
+                    .tcp
+                    .iter()
+                    .map(|&port| (port, self.dns_budget.port_flags(port))),
+            )?;
+        }
+        if ebpf.map("PROTECTED_PORTS").is_some() {
+            replace_hash_map(
+                ebpf,
+                &self.batcher,
+                "PROTECTED_PORTS",
+                self.protected_ports
+                    .udp
+                    .iter()
+                    .map(|&port| (port, PROTECTED)),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Set the per-client DNS query budgets
+    ///
+    /// The DNS ports are flagged in `TCP_PROTECTED_PORTS`, so they are
+    /// rewritten along with `DNS_BUDGET`.
+    pub fn set_dns_budget(&mut self, budget: DnsBudget) -> Result<()> {
+        budget.validate()?;
+        if self.dns_budget == budget {
+            return Ok(());
+        }
+        self.dns_budget = budget;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_protected_ports(&name)?;
+            self.write_dns_budget(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Current per-client DNS query budgets
+    pub fn dns_budget(&self) -> &DnsBudget {
+        &self.dns_budget
+    }
+
+    fn write_dns_budget(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Only xdp_tcp and xdp_http have the map
+        let Some(map) = ebpf.map_mut("DNS_BUDGET") else {
+            return Ok(());
+        };
+        let mut map: Array<_, DnsBudgetConfig> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        map.set(0, self.dns_budget.config(), 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))
+    }
+
+    /// Switch observe mode on or off
+    ///
+    /// In observe mode the programs account their drops but pass the
+    /// packets. `OBSERVE` is pinned and shared, but it is written through
+    /// every loaded program like the other shared flags.
+    pub fn set_observe_mode(&mut self, observe: bool) -> Result<()> {
+        if self.observe == observe {
+            return Ok(());
+        }
+        self.observe = observe;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_observe_mode(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Whether observe mode is on
+    pub fn observe_mode(&self) -> bool {
+        self.observe
+    }
+
+    fn write_observe_mode(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Objects built before observe mode lack the map
+        let Some(map) = ebpf.map_mut("OBSERVE") else {
+            return Ok(());
+        };
+        let mut map: Array<_, u32> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        map.set(0, self.observe as u32, 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))
+    }
+
+    /// Switch 0-RTT rejection on or off
+    ///
+    /// 0-RTT data can be replayed, so under attack xdp_quic may drop it and
+    /// leave clients to resend it once the handshake completes.
+    pub fn set_reject_0rtt(&mut self, reject: bool) -> Result<()> {
+        if self.reject_0rtt == reject {
+            return Ok(());
+        }
+        self.reject_0rtt = reject;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_reject_0rtt(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Whether 0-RTT rejection is on
+    pub fn reject_0rtt(&self) -> bool {
+        self.reject_0rtt
+    }
+
+    fn write_reject_0rtt(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Only xdp_quic has the map
+        let Some(map) = ebpf.map_mut("QUIC_REJECT_0RTT") else {
+            return Ok(());
+        };
+        let mut map: Array<_, u32> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        map.set(0, self.reject_0rtt as u32, 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))
+    }
+
+    /// Set the short header rate limit per QUIC connection ID
+    ///
+    /// Lets xdp_quic throttle one abusive HTTP/3 connection without hurting
+    /// the other clients sharing its address.
+    pub fn set_quic_cid_limit(&mut self, limit: QuicCidLimit) -> Result<()> {
+        limit.validate()?;
+        if self.quic_cid_limit == limit {
+            return Ok(());
+        }
+        self.quic_cid_limit = limit;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_quic_cid_limit(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Current short header rate limit per QUIC connection ID
+    pub fn quic_cid_limit(&self) -> QuicCidLimit {
+        self.quic_cid_limit
+    }
+
+    fn write_quic_cid_limit(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Only xdp_quic has the map
+        let Some(map) = ebpf.map_mut("QUIC_CID_LIMIT") else {
+            return Ok(());
+        };
+        let mut map: Array<_, QuicCidLimit> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        map.set(0, self.quic_cid_limit, 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))
+    }
+
+    /// Set the source addresses that bypass filtering
+    ///
+    /// Written to the whitelist maps of xdp_tcp, xdp_udp, xdp_quic and
+    /// xdp_http, replacing the previous addresses, and only when they change.
+    pub fn set_whitelist(&mut self, ips: &BTreeSet<IpAddr>) -> Result<()> {
+        let mut whitelist = Whitelist::default();
+        for ip in ips {
+            match ip {
+                IpAddr::V4(ip) => whitelist.v4.push(u32::from(*ip)),
+                IpAddr::V6(ip) => whitelist.v6.push(ip.octets()),
+            }
+        }
+        if self.whitelist == whitelist {
+            return Ok(());
+        }
+        self.whitelist = whitelist;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_whitelist(&name)?;
+        }
+        Ok(())
+    }
+
+    fn write_whitelist(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        for name in WHITELIST_MAPS {
+            if ebpf.map(name).is_some() {
+                replace_hash_map(
+                    ebpf,
+                    &self.batcher,
+                    name,
+                    self.whitelist.v4.iter().map(|&ip| (ip, 1u32)),
+                )?;
+            }
+        }
+        for name in WHITELIST_V6_MAPS {
+            if ebpf.map(name).is_some() {
+                replace_hash_map(
+                    ebpf,
+                    &self.batcher,
+                    name,
+                    self.whitelist.v6.iter().map(|&ip| (ip, 1u32)),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the conntrack bypass settings
+    ///
+    /// `FLOW_MARK_CONFIG` is pinned and shared by xdp_tcp, which records
+    /// validated flows only while it is enabled, and tc_flowmark, which
+    /// marks their packets.
+    pub fn set_flow_mark(&mut self, config: FlowMarkConfig) -> Result<()> {
+        if self.flow_mark == config {
+            return Ok(());
+        }
+        self.flow_mark = config;
+
+        let names: Vec<String> = self.objects.keys().cloned().collect();
+        for name in names {
+            self.write_flow_mark(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Current conntrack bypass settings
+    pub fn flow_mark(&self) -> FlowMarkConfig {
+        self.flow_mark
+    }
+
+    fn write_flow_mark(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Only xdp_tcp and tc_flowmark have the map
+        let Some(map) = ebpf.map_mut("FLOW_MARK_CONFIG") else {
+            return Ok(());
+        };
+        let mut map: Array<_, FlowMarkConfig> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        map.set(0, self.flow_mark, 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))
+    }
+
+    /// Conntrack bypass settings, attachments and counters
+    pub fn flow_mark_status(&self) -> FlowMarkStatus {
+        let mut attachments: Vec<TcAttachmentStatus> = self
+            .tc_attached
+            .values()
+            .filter(|a| a.program_name == super::flow_mark::PROGRAM)
+            .map(|a| TcAttachmentStatus {
+                interface: a.interface.clone(),
+                direction: a.direction,
+            })
+            .collect();
+        attachments.sort_by(|a, b| a.interface.cmp(&b.interface));
+        let stats = self.read_flow_mark_stats().unwrap_or_else(|e| {
+            debug!("Failed to read conntrack bypass counters: {}", e);
+            FlowMarkStats::default()
+        });
+
+        FlowMarkStatus {
+            enabled: self.flow_mark.is_enabled(),
+            mark: self.flow_mark.mark,
+            mask: self.flow_mark.mask,
+            attachments,
+            stats,
+        }
+    }
+
+    /// Read the tc_flowmark counters, summed over the CPUs
+    pub fn read_flow_mark_stats(&self) -> Result<FlowMarkStats> {
+        let Some(ebpf) = self.objects.get(super::flow_mark::PROGRAM) else {
+            return Ok(FlowMarkStats::default());
+        };
+        let map = ebpf
+            .map("FLOW_MARK_STATS")
+            .ok_or_else(|| Error::not_found("eBPF map", "FLOW_MARK_STATS"))?;
+        let map: PerCpuArray<_, FlowMarkStats> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+        let values = map
+            .get(&0, 0)
+            .map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+        Ok(values
+            .iter()
+            .fold(FlowMarkStats::default(), |sum, stats| sum.add(stats)))
+    }
+
+    /// Set the marking policies of the backend destinations
+    ///
+    /// Counters of policy ids newly in use are reset, so a reused id does
+    /// not carry the marks of its previous backend.
+    pub fn set_marking_entries(
+        &mut self,
+        entries: Vec<(TenantDstV6Key, MarkingPolicy)>,
+    ) -> Result<()> {
+        if self.marking == entries {
+            return Ok(());
+        }
+        let previous: HashSet<u32> = self.marking.iter().map(|(_, p)| p.policy_id).collect();
+        let assigned: HashSet<u32> = entries
+            .iter()
+            .map(|(_, p)| p.policy_id)
+            .filter(|id| !previous.contains(id))
+            .collect();
+        self.marking = entries;
+        if self.objects.contains_key("xdp_filter") {
+            self.reset_marking_counters(&assigned)?;
+            self.write_marking("xdp_filter")?;
+        }
+        Ok(())
+    }
+
+    fn write_marking(&mut self, program_name: &str) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // Only xdp_filter marks greylisted traffic
+        if ebpf.map("MARKING_POLICIES").is_none() {
+            return Ok(());
+        }
+        replace_hash_map(
+            ebpf,
+            &self.batcher,
+            "MARKING_POLICIES",
+            self.marking.iter().copied(),
+        )
+    }
+
+    fn reset_marking_counters(&mut self, policy_ids: &HashSet<u32>) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+        let Some(map) = ebpf.map_mut("MARKING_COUNTERS") else {
+            return Ok(());
+        };
+        let mut counters: PerCpuArray<_, MarkingCounter> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let nr_cpus = aya::util::nr_cpus()
+            .map_err(|(_, e)| Error::Internal(format!("Failed to count CPUs: {}", e)))?;
+        for &policy_id in policy_ids {
+            let values = PerCpuValues::try_from(vec![MarkingCounter::default(); nr_cpus])
+                .map_err(|e| Error::Internal(format!("Invalid per-CPU values: {}", e)))?;
+            counters
+                .set(policy_id, values, 0)
+                .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Read the xdp_filter marking counters, summed across CPUs and indexed
+    /// by policy id
+    pub fn read_marking_counters(&self) -> Result<Vec<MarkingCounter>> {
+        let ebpf = self
+            .objects
+            .get("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let counters: PerCpuArray<_, MarkingCounter> = ebpf
+            .map("MARKING_COUNTERS")
+            .ok_or_else(|| Error::Internal("Map MARKING_COUNTERS not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        (0..counters.len())
+            .map(|index| {
+                let values = counters
+                    .get(&index, 0)
+                    .map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+                Ok(values
+                    .iter()
+                    .fold(MarkingCounter::default(), |total, value| total.add(value)))
+            })
+            .collect()
+    }
+
+    /// Read the xdp_filter destination traffic counters, summed across CPUs
+    pub fn read_dst_traffic(&self) -> Result<Vec<(TenantDstV6Key, DstTraffic)>> {
+        let ebpf = self
+            .objects
+            .get("xdp_filter")
+            .ok_or_else(|| Error::not_found("eBPF program", "xdp_filter"))?;
+
+        let map: PerCpuHashMap<_, TenantDstV6Key, DstTraffic> = ebpf
+            .map("DST_TRAFFIC")
+            .ok_or_else(|| Error::Internal("Map DST_TRAFFIC not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let mut counters = Vec::new();
+        for entry in map.iter() {
+            let (key, values) =
+                entry.map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+            let total = values
+                .iter()
+                .fold(DstTraffic::default(), |total, value| total.add(value));
+            counters.push((key, total));
+        }
+        Ok(counters)
+    }
+
+    /// Read and clear the SYN signals recorded for CGNAT detection
+    ///
+    /// `CGNAT_SIGNALS` is pinned and shared, so usually the first program
+    /// holding it returns every record and the others none.
+    pub fn take_cgnat_signals(&mut self) -> Result<Vec<(IpAddr, CgnatSignals)>> {
+        let mut signals = Vec::new();
+
+        for ebpf in self.objects.values_mut() {
+            let Some(map) = ebpf.map_mut("CGNAT_SIGNALS") else {
+                continue;
+            };
+            let mut map: BpfHashMap<_, [u8; 16], CgnatSignals> = map
+                .try_into()
+                .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+            let entries: Vec<([u8; 16], CgnatSignals)> =
+                map.iter().filter_map(|e| e.ok()).collect();
+            for (key, record) in entries {
+                let _ = map.remove(&key);
+                signals.push((key_addr(key), record));
+            }
+        }
+
+        Ok(signals)
+    }
+
+    /// Read and clear the handshakes recorded for allowlist learning
+    ///
+    /// `HANDSHAKES` is pinned and shared, so usually the first program
+    /// holding it returns every record and the others none.
+    pub fn take_handshakes(&mut self) -> Result<Vec<(IpAddr, HandshakeRecord)>> {
+        let mut handshakes = Vec::new();
+
+        for ebpf in self.objects.values_mut() {
+            let Some(map) = ebpf.map_mut("HANDSHAKES") else {
+                continue;
+            };
+            let mut map: BpfHashMap<_, [u8; 16], HandshakeRecord> = map
+                .try_into()
+                .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+            let entries: Vec<([u8; 16], HandshakeRecord)> =
+                map.iter().filter_map(|e| e.ok()).collect();
+            for (key, record) in entries {
+                let _ = map.remove(&key);
+                handshakes.push((key_addr(key), record));
+            }
+        }
+
+        Ok(handshakes)
+    }
+
+    /// Current sampling rate of a program
+    pub fn sampling_rate(&self, program_name: &str) -> u32 {
+        self.sampling.rate_for(program_name)
+    }
+
+    /// Change the sampling rate of a loaded program (0 disables sampling)
+    pub fn set_sampling_rate(&mut self, program_name: &str, rate: u32) -> Result<()> {
+        self.write_sampling_rate(program_name, rate)?;
+        self.sampling
+            .program_rates
+            .insert(program_name.to_string(), rate);
+        Ok(())
+    }
+
+    fn write_sampling_rate(&mut self, program_name: &str, rate: u32) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        let mut map: PerCpuArray<_, SampleConfig> = ebpf
+            .map_mut("SAMPLE_CONFIG")
+            .ok_or_else(|| Error::Internal("Map SAMPLE_CONFIG not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let nr_cpus = aya::util::nr_cpus()
+            .map_err(|(_, e)| Error::Internal(format!("Failed to count CPUs: {}", e)))?;
+        let values = PerCpuValues::try_from(vec![SampleConfig { rate, _pad: 0 }; nr_cpus])
+            .map_err(|e| Error::Internal(format!("Invalid per-CPU values: {}", e)))?;
+
+        map.set(0, values, 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Current latency measurement rate of a program
+    pub fn latency_rate(&self, program_name: &str) -> u32 {
+        self.latency.rate_for(program_name)
+    }
+
+    /// Change the latency measurement rate of a loaded program (0 disables it)
+    pub fn set_latency_rate(&mut self, program_name: &str, rate: u32) -> Result<()> {
+        self.write_latency_rate(program_name, rate)?;
+        self.latency
+            .program_rates
+            .insert(program_name.to_string(), rate);
+        Ok(())
+    }
+
+    fn write_latency_rate(&mut self, program_name: &str, rate: u32) -> Result<()> {
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        let mut map: PerCpuArray<_, LatencyConfig> = ebpf
+            .map_mut("LATENCY_CONFIG")
+            .ok_or_else(|| Error::Internal("Map LATENCY_CONFIG not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let nr_cpus = aya::util::nr_cpus()
+            .map_err(|(_, e)| Error::Internal(format!("Failed to count CPUs: {}", e)))?;
+        let values = PerCpuValues::try_from(vec![LatencyConfig { rate, _pad: 0 }; nr_cpus])
+            .map_err(|e| Error::Internal(format!("Invalid per-CPU values: {}", e)))?;
+
+        map.set(0, values, 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Read a program's processing time histograms
+    pub fn read_latency(&self, program_name: &str) -> Result<Vec<LatencyHistogram>> {
+        let ebpf = self
+            .objects
+            .get(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        let map: PerCpuArray<_, LatencyBucket> = ebpf
+            .map("LATENCY")
+            .ok_or_else(|| Error::Internal("Map LATENCY not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let mut entries = Vec::new();
+        for index in 0..map.len() {
+            let values = map
+                .get(&index, 0)
+                .map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+            entries.push(values.iter().copied().collect());
+        }
+
+        Ok(LatencyHistogram::from_map(program_name, &entries))
+    }
+
+    fn write_event_budget(&mut self, program_name: &str) -> Result<()> {
+        let budget = self.event_budget;
+        let ebpf = self
+            .objects
+            .get_mut(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        // tc_flowmark submits no events
+        let Some(map) = ebpf.map_mut("EVENT_BUDGET_CONFIG") else {
+            return Ok(());
+        };
+        let mut map: PerCpuArray<_, EventBudgetConfig> = map
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let nr_cpus = aya::util::nr_cpus()
+            .map_err(|(_, e)| Error::Internal(format!("Failed to count CPUs: {}", e)))?;
+        let values = PerCpuValues::try_from(vec![budget; nr_cpus])
+            .map_err(|e| Error::Internal(format!("Invalid per-CPU values: {}", e)))?;
+
+        map.set(0, values, 0)
+            .map_err(|e| Error::Internal(format!("Failed to update map: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Read a program's emitted and suppressed event counts
+    pub fn read_event_budget(&self, program_name: &str) -> Result<EventBudgetReport> {
+        let ebpf = self
+            .objects
+            .get(program_name)
+            .ok_or_else(|| Error::not_found("eBPF program", program_name))?;
+
+        let map: PerCpuArray<_, EventBudget> = ebpf
+            .map("EVENT_BUDGET")
+            .ok_or_else(|| Error::Internal("Map EVENT_BUDGET not found".to_string()))?
+            .try_into()
+            .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+        let values = map
+            .get(&0, 0)
+            .map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+        let per_cpu: Vec<EventBudget> = values.iter().copied().collect();
+
+        Ok(EventBudgetReport::from_map(
+            program_name,
+            self.event_budget,
+            &per_cpu,
+        ))
+    }
+
+    /// Sum the per-CPU source counters of every loaded program that keeps
+    /// them into the source ledger
+    pub fn merge_source_counters(&self) -> Result<()> {
+        let now = Instant::now();
+        for program in sources::PROGRAMS {
+            let Some(ebpf) = self.objects.get(program) else {
+                continue;
+            };
+
+            let mut entries: Vec<(IpAddr, Vec<SourceCounters>)> = Vec::new();
+            if let Some(map) = ebpf.map(sources::MAP_V4) {
+                let map: PerCpuHashMap<_, u32, SourceCounters> = map
+                    .try_into()
+                    .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+                for entry in map.iter() {
+                    let (addr, values) =
+                        entry.map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+                    entries.push((
+                        Ipv4Addr::from(addr).into(),
+                        values.iter().copied().collect(),
+                    ));
+                }
+            }
+            if let Some(map) = ebpf.map(sources::MAP_V6) {
+                let map: PerCpuHashMap<_, [u8; 16], SourceCounters> = map
+                    .try_into()
+                    .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+                for entry in map.iter() {
+                    let (addr, values) =
+                        entry.map_err(|e| Error::Internal(format!("Failed to read map: {}", e)))?;
+                    entries.push((
+                        Ipv6Addr::from(addr).into(),
+                        values.iter().copied().collect(),
+                    ));
+                }
+            }
+
+            let generation = self.generations.get(program).copied().unwrap_or(0);
+            self.sources
+                .lock()
+                .merge(program, generation, now, &entries);
+        }
+        Ok(())
+    }
+
+    /// Sources sending the most packets per second as of the last merge
+    pub fn top_sources(&self, limit: usize) -> Vec<SourceTraffic> {
+        self.sources.lock().top(limit)
+    }
+
+    /// Sources tracked per program as of the last merge
+    pub fn tracked_sources(&self) -> Vec<(String, usize)> {
+        self.sources.lock().tracked()
+    }
+
+    /// Names of programs with a sample ring buffer
+    pub fn sampled_programs(&self) -> Vec<String> {
+        self.sample_rings.keys().cloned().collect()
+    }
+    /// Drain up to `max` samples from a program's sample buffers
+    /// Drain up to `max` samples from a program's ring buffer
+    pub fn drain_samples(&self, program_name: &str, max: usize) -> Vec<PacketSample> {
+        let Some(source) = self.sample_rings.get(program_name) else {
+            return Vec::new();
+        };
+
+        source.lock().drain(
+            max,
+            PacketSample::from_bytes,
+            PacketSample::from_perf_record,
+        )
+    }
+
+    /// Names of programs with a drop event buffer
+    pub fn drop_event_programs(&self) -> Vec<String> {
+        self.drop_event_rings.keys().cloned().collect()
+    }
+
+    /// Drain up to `max` drop events from a program's drop event buffers
+    pub fn drain_drop_events(&self, program_name: &str, max: usize) -> Vec<DropEvent> {
+        let Some(source) = self.drop_event_rings.get(program_name) else {
+            return Vec::new();
+        };
+
+        source
+            .lock()
+            .drain(max, DropEvent::from_bytes, DropEvent::from_bytes)
+    }
+
+    /// Run a packet through a loaded program with `BPF_PROG_TEST_RUN`
+    pub fn test_run(&self, name: &str, packet: &[u8], repeat: u32) -> Result<TestRun> {
+        let program = self
+            .objects
+            .get(name)
+            .and_then(|ebpf| ebpf.program(name))
+            .ok_or_else(|| Error::not_found("eBPF program", name))?;
+        let fd = program
+            .fd()
+            .map_err(|e| Error::Internal(format!("Program {} is not loaded: {}", name, e)))?;
+
+        selftest::test_run(fd.as_fd(), packet, repeat)
+            .map_err(|e| Error::Internal(format!("Test run of {} failed: {}", name, e)))
+    }
+
+    /// Get names of all loaded programs
+    pub fn loaded_programs(&self) -> Vec<String> {
+        self.objects.keys().cloned().collect()
+    }
+
+    /// Load generation of a program (0 if never loaded)
+    pub fn program_generation(&self, name: &str) -> u64 {
+        self.generations.get(name).copied().unwrap_or(0)
+    }
+
+    /// Version and digest of a loaded program
+    pub fn program_version(&self, name: &str) -> Option<&ProgramVersionInfo> {
+        self.versions.get(name)
+    }
+
+    /// Trust the keys of a verifier for object files and the bootstrap config
+    pub fn set_artifact_verifier(&mut self, verifier: ArtifactVerifier) {
+        self.artifacts = verifier;
+    }
+
+    /// Check an artifact against its detached signature
+    ///
+    /// Returns the fingerprint of the signing key, or `None` when signatures
+    /// are not enforced.
+    pub fn verify_artifact(
+        &mut self,
+        artifact: &str,
+        data: &[u8],
+        signature: Option<&str>,
+    ) -> Result<Option<String>> {
+        self.artifacts.verify(artifact, data, signature)
+    }
+
+    /// Trusted keys and the last check of each artifact
+    pub fn artifact_status(&self) -> ArtifactStatus {
+        self.artifacts.status()
+    }
+
+    /// Error from the last failed versioned load of a program
+    pub fn load_error(&self, name: &str) -> Option<&str> {
+        self.load_errors.get(name).map(String::as_str)
+    }
+
+    /// Record a versioned load that failed before reaching the loader
+    pub fn record_load_error(&mut self, name: &str, error: String) {
+        self.load_errors.insert(name.to_string(), error);
+    }
+
+    /// Programs whose last version change failed
+    pub fn failed_programs(&self) -> Vec<String> {
+        self.load_errors.keys().cloned().collect()
+    }
+
+    /// Keep the diagnostic of a failed load, with the kernel it happened on
+    fn record_diagnostic(&mut self, mut diagnostic: LoadDiagnostic) {
+        diagnostic.kernel_release = self.capabilities.release.clone();
+        diagnostic.variant = self
+            .program_variant()
+            .map(|variant| variant.to_string())
+            .unwrap_or_default();
+        warn!(
+            program = %diagnostic.program,
+            section = %diagnostic.section,
+            stage = ?diagnostic.stage,
+            kernel = %diagnostic.kernel_release,
+            summary = %diagnostic.summary,
+            "eBPF program failed to load: {}",
+            diagnostic.error
+        );
+        self.diagnostics
+            .insert(diagnostic.program.clone(), diagnostic);
+    }
+
+    /// Last load failure of a program, cleared once it loads
+    pub fn load_diagnostic(&self, name: &str) -> Option<&LoadDiagnostic> {
+        self.diagnostics.get(name)
+    }
+
+    /// Load failures of all programs, sorted by program
+    pub fn load_diagnostics(&self) -> Vec<LoadDiagnostic> {
+        let mut diagnostics: Vec<LoadDiagnostic> = self.diagnostics.values().cloned().collect();
+        diagnostics.sort_by(|a, b| a.program.cmp(&b.program));
+        diagnostics
+    }
+
+    /// Get list of attached programs
+    pub fn list_attached(&self) -> Vec<&AttachedProgram> {
+        self.attached.values().collect()
+    }
+
+    /// Check if a program is attached to an interface
+    pub fn is_attached(&self, interface_name: &str) -> bool {
+        self.attached.contains_key(interface_name)
+    }
+}
+
+/// Open a canary map of xdp_filter for writing
+/// Lower the `active_connections` of a source to at most `count`, giving
+/// the number of slots returned
+fn lower_active_connections<K: aya::Pod>(
+    map: &mut BpfHashMap<&mut MapData, K, TcpIpState>,
+    key: K,
+    count: u32,
+) -> u32 {
+    let Ok(mut state) = map.get(&key, 0) else {
+        return 0;
+    };
+    if state.active_connections <= count {
+        return 0;
+    }
+    let returned = state.active_connections - count;
+    state.active_connections = count;
+    match map.insert(key, state, 0) {
+        Ok(()) => returned,
+        Err(_) => 0,
+    }
+}
+
+fn canary_map<'a, K: aya::Pod>(
+    ebpf: &'a mut Ebpf,
+    name: &str,
+) -> Result<PerCpuHashMap<&'a mut MapData, K, CanaryEntry>> {
+    ebpf.map_mut(name)
+        .ok_or_else(|| Error::Internal(format!("Map {} not found", name)))?
+        .try_into()
+        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))
+}
+
+/// Write `entries` into a hash map of xdp_filter, then drop every other key
+fn replace_hash_map<K, V>(
+    ebpf: &mut Ebpf,
+    batcher: &Batcher,
+    name: &str,
+    entries: impl Iterator<Item = (K, V)>,
+) -> Result<()>
+where
+    K: aya::Pod + Eq + std::hash::Hash,
+    V: aya::Pod,
+{
+    let map = ebpf
+        .map(name)
+        .ok_or_else(|| Error::Internal(format!("Map {} not found", name)))?;
+    // Checks the map type and key and value sizes
+    let _: BpfHashMap<&MapData, K, V> = map
+        .try_into()
+        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+    let mut keep = HashSet::new();
+    for (key, value) in entries {
+        keys.extend_from_slice(pod_bytes(&key));
+        values.extend_from_slice(pod_bytes(&value));
+        keep.insert(key);
+    }
+
+    let map = BpfMap::new(
+        map_data(map).fd().as_fd(),
+        std::mem::size_of::<K>(),
+        std::mem::size_of::<V>(),
+    );
+    replace_map_entries(batcher, name, &map, &keys, &values, |key| {
+        pod_from_bytes::<K>(key).is_some_and(|key| keep.contains(&key))
+    })
+}
+
+/// Write prefixes into an LPM trie, then drop every other key
+fn replace_lpm_trie<K, V>(
+    ebpf: &mut Ebpf,
+    batcher: &Batcher,
+    name: &str,
+    entries: &[(u32, K, V)],
+) -> Result<()>
+where
+    K: aya::Pod + Eq + std::hash::Hash,
+    V: aya::Pod,
+{
+    let map = ebpf
+        .map(name)
+        .ok_or_else(|| Error::Internal(format!("Map {} not found", name)))?;
+    let _: LpmTrie<&MapData, K, V> = map
+        .try_into()
+        .map_err(|e| Error::Internal(format!("Invalid map type: {}", e)))?;
+
+    let mut keys = Vec::with_capacity(entries.len() * std::mem::size_of::<LpmKey<K>>());
+    let mut values = Vec::with_capacity(entries.len() * std::mem::size_of::<V>());
+    let mut keep = HashSet::new();
+    for (prefix_len, data, value) in entries {
+        keys.extend_from_slice(pod_bytes(&LpmKey::new(*prefix_len, *data)));
+        values.extend_from_slice(pod_bytes(value));
+        keep.insert((*prefix_len, *data));
+    }
+
+    let map = BpfMap::new(
+        map_data(map).fd().as_fd(),
+        std::mem::size_of::<LpmKey<K>>(),
+        std::mem::size_of::<V>(),
+    );
+    replace_map_entries(batcher, name, &map, &keys, &values, |key| {
+        pod_from_bytes::<LpmKey<K>>(key)
+            .is_some_and(|key| keep.contains(&(key.prefix_len(), key.data())))
+    })
+}
+
+/// Write entries in chunks, then delete the keys `keep` rejects
+///
+/// New entries are written before stale ones are removed, so keys present
+/// before and after the push stay in the map throughout.
+fn replace_map_entries(
+    batcher: &Batcher,
+    name: &str,
+    map: &BpfMap<'_>,
+    keys: &[u8],
+    values: &[u8],
+    keep: impl Fn(&[u8]) -> bool,
+) -> Result<()> {
+    let started = Instant::now();
+    let written = batcher
+        .update(name, map, keys, values, &mut batch::log_progress())
+        .map_err(|e| Error::Internal(format!("Failed to update map {}: {}", name, e)))?;
+
+    let stale: Vec<u8> = batcher
+        .lookup(name, map)
+        .map_err(|e| Error::Internal(format!("Failed to read map {}: {}", name, e)))?
+        .iter()
+        .filter(|(key, _)| !keep(key))
+        .flat_map(|(key, _)| key.iter().copied())
+        .collect();
+    let removed = batcher
+        .delete(name, map, &stale, &mut batch::log_progress())
+        .map_err(|e| Error::Internal(format!("Failed to update map {}: {}", name, e)))?;
+
+    debug!(
+        "Replaced map {}: {} entries written, {} removed, {} syscalls{}, {:.1}ms",
+        name,
+        written.entries,
+        removed.entries,
+        written.syscalls + removed.syscalls,
+        if written.batched { "" } else { " (per-entry)" },
+        started.elapsed().as_secs_f64() * 1000.0
+    );
+    Ok(())
+}
+
+/// Sum the per-CPU copies of a canary entry
+fn sum_canary(values: &[CanaryEntry]) -> CanaryEntry {
+    values
+        .iter()
+        .fold(CanaryEntry::default(), |mut acc, value| {
+            acc.verdict = acc.verdict.max(value.verdict);
+            acc.packets = acc.packets.wrapping_add(value.packets);
+            acc.bytes = acc.bytes.wrapping_add(value.bytes);
+            acc
+        })
+}
+
+/// Try to attach XDP program with specified flags
+/// Returns true if attachment succeeded, false otherwise
+fn try_attach_program(program: &mut Xdp, interface_name: &str, flags: XdpFlags) -> bool {
+    // Try to attach with the specified flags
+    // Returns false to fall back to next mode (this is a placeholder implementation)
+    // In a real implementation, we would try to attach and check for errors
+    program.attach(interface_name, flags).is_ok()
+}
+
+impl Drop for EbpfLoader {
+    fn drop(&mut self) {
+        info!("Cleaning up eBPF programs");
+        // Programs are automatically detached when dropped
+        self.attached.clear();
+        self.tc_attached.clear();
+        self.objects.clear();
+    }
+}
+
+/// SHA-256 of an eBPF object file (hex)
+pub fn object_digest(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(data))
 }
 
 #[cfg(test)]
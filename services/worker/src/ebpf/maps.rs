@@ -1,7 +1,27 @@
 //! eBPF map management
 
+use super::flow_rate::{
+    BackendFlowRate, FlowRateConfig, flow_rate_map_entries, throttled_flow_rates,
+};
+use super::honeypot::{
+    BackendHoneypot, FlaggedLog, FlaggedSource, HoneypotHit, HoneypotMapEntries,
+    honeypot_map_entries,
+};
+use super::learning::{
+    AllowlistLearner, BackendLearning, HandshakeRecord, LearnedBackend, LearningStatus,
+};
+use super::marking::{
+    BackendMarking, MarkingCounter, MarkingPolicy, MarkingStatus, assign_policy_ids,
+    marking_map_entries, marking_status,
+};
+use super::tenants::{
+    DEFAULT_PPS_LIMIT, ProfileLimits, TenantBlock, TenantConfig, TenantDestination, TenantDstV6Key,
+    TenantLimits, TenantMapEntries, TenantNamespace,
+};
+use super::traffic::traffic_map_keys;
 use pistonprotection_common::error::{Error, Result};
-use std::collections::HashMap;
+use pistonprotection_common::restart_mode::RestartMode;
+use std::collections::{HashMap, HashSet};
 use std::net::IpAddr;
 use tracing::{debug, info};
 
@@ -15,8 +35,38 @@ pub struct MapManager {
     conntrack: HashMap<ConnTrackKey, ConnTrackEntry>,
     /// Backend configurations
     backends: HashMap<String, BackendConfig>,
+    /// Map namespaces keyed by organization ID
+    tenants: HashMap<String, TenantNamespace>,
+    /// Next tenant id to hand out (0 is the global namespace)
+    next_tenant_id: u32,
+    /// Newly blocked IPs not yet taken by the reputation engine
+    block_events: Vec<IpAddr>,
+    /// Honeypot ports keyed by backend ID
+    honeypots: HashMap<String, BackendHoneypot>,
+    /// Sources recently seen on honeypot ports
+    flagged: FlaggedLog,
+    /// Per-connection bandwidth limits keyed by backend ID
+    flow_rates: HashMap<String, BackendFlowRate>,
+    /// Per-connection bandwidth of backends over their quota, keyed by
+    /// backend ID
+    bandwidth_throttles: HashMap<String, u64>,
+    /// Accounted destinations keyed by backend ID
+    traffic: HashMap<String, Vec<TenantDestination>>,
+    /// Marking policies keyed by backend ID
+    marking: HashMap<String, BackendMarking>,
+    /// Marking policy ids keyed by backend ID
+    marking_ids: HashMap<String, u32>,
+    /// Allowlist learning keyed by backend ID
+    learning: AllowlistLearner,
+    /// Limits of active rate limit profiles keyed by backend ID
+    profile_limits: HashMap<String, ProfileLimits>,
+    /// Active restart modes keyed by backend ID
+    restarts: HashMap<String, RestartMode>,
 }
 
+/// Maximum number of block events kept between two reads
+pub const MAX_BLOCK_EVENTS: usize = 10_000;
+
 /// Blocked IP entry
 #[derive(Debug, Clone)]
 pub struct BlockedIpEntry {
@@ -89,6 +139,19 @@ impl MapManager {
             rate_limits: HashMap::new(),
             conntrack: HashMap::new(),
             backends: HashMap::new(),
+            tenants: HashMap::new(),
+            next_tenant_id: 1,
+            block_events: Vec::new(),
+            honeypots: HashMap::new(),
+            flagged: FlaggedLog::default(),
+            flow_rates: HashMap::new(),
+            bandwidth_throttles: HashMap::new(),
+            traffic: HashMap::new(),
+            marking: HashMap::new(),
+            marking_ids: HashMap::new(),
+            learning: AllowlistLearner::default(),
+            profile_limits: HashMap::new(),
+            restarts: HashMap::new(),
         }
     }
 
@@ -99,7 +162,7 @@ impl MapManager {
 
         info!(ip = %ip, reason = %reason, "Blocking IP");
 
-        self.blocked_ips.insert(
+        let previous = self.blocked_ips.insert(
             ip,
             BlockedIpEntry {
                 ip,
@@ -109,10 +172,21 @@ impl MapManager {
                 packets_blocked: 0,
             },
         );
+        if previous.is_none() && self.block_events.len() < MAX_BLOCK_EVENTS {
+            self.block_events.push(ip);
+        }
 
         Ok(())
     }
 
+    /// Take the IPs blocked since the last call
+    ///
+    /// Re-blocking an already blocked IP (e.g. on every config sync) is not
+    /// an event.
+    pub fn take_block_events(&mut self) -> Vec<IpAddr> {
+        std::mem::take(&mut self.block_events)
+    }
+
     /// Unblock an IP address
     pub fn unblock_ip(&mut self, ip: &IpAddr) -> Result<()> {
         if self.blocked_ips.remove(ip).is_some() {
@@ -162,6 +236,13 @@ impl MapManager {
             .unwrap_or(0) as u64;
         self.conntrack
             .retain(|_, entry| entry.last_seen > five_mins_ago);
+
+        // Clean expired tenant blocks
+        for namespace in self.tenants.values_mut() {
+            namespace
+                .blocked_ips
+                .retain(|_, block| !block.is_expired(now));
+        }
     }
 
     /// Update rate limit for an IP
@@ -224,6 +305,21 @@ impl MapManager {
         self.conntrack.get(key)
     }
 
+    /// Count the open connections to a destination (port 0: any port)
+    pub fn count_connections(&self, dst_ip: IpAddr, dst_port: u16) -> u64 {
+        self.conntrack
+            .iter()
+            .filter(|(key, entry)| {
+                key.dst_ip == dst_ip
+                    && (dst_port == 0 || key.dst_port == dst_port)
+                    && !matches!(
+                        entry.state,
+                        ConnTrackState::Closing | ConnTrackState::Closed
+                    )
+            })
+            .count() as u64
+    }
+
     /// Update backend configuration
     pub fn update_backend(&mut self, config: BackendConfig) {
         debug!(backend_id = %config.id, "Updating backend config");
@@ -235,6 +331,437 @@ impl MapManager {
         self.backends.get(id)
     }
 
+    /// Register a backend in the namespace of its organization
+    ///
+    /// Creates the namespace on first use. Tenant ids are stable for the
+    /// lifetime of the namespace so kernel entries keyed by them stay valid.
+    pub fn register_backend_tenant(
+        &mut self,
+        organization_id: &str,
+        backend_id: &str,
+        destinations: Vec<TenantDestination>,
+        limits: TenantLimits,
+    ) -> u32 {
+        // A backend moved between organizations leaves its old namespace
+        for namespace in self.tenants.values_mut() {
+            if namespace.organization_id != organization_id {
+                namespace.backends.remove(backend_id);
+                namespace.destinations.remove(backend_id);
+            }
+        }
+
+        if !self.tenants.contains_key(organization_id) {
+            let tenant_id = self.next_tenant_id;
+            self.next_tenant_id += 1;
+            info!(organization_id = %organization_id, tenant_id, "Creating tenant namespace");
+            self.tenants.insert(
+                organization_id.to_string(),
+                TenantNamespace::new(tenant_id, organization_id),
+            );
+        }
+
+        let namespace = self
+            .tenants
+            .get_mut(organization_id)
+            .expect("inserted above");
+        namespace.backends.insert(backend_id.to_string());
+        namespace
+            .destinations
+            .insert(backend_id.to_string(), destinations);
+        namespace.limits = limits;
+        namespace.tenant_id
+    }
+
+    /// Get the tenant id of an organization
+    pub fn tenant_id(&self, organization_id: &str) -> Option<u32> {
+        self.tenants.get(organization_id).map(|n| n.tenant_id)
+    }
+
+    /// Get the namespace of an organization
+    pub fn get_tenant(&self, organization_id: &str) -> Option<&TenantNamespace> {
+        self.tenants.get(organization_id)
+    }
+
+    /// Block an IP address for the backends of one organization only
+    pub fn block_ip_in_tenant(
+        &mut self,
+        organization_id: &str,
+        ip: IpAddr,
+        reason: &str,
+        duration_secs: Option<u32>,
+    ) -> Result<()> {
+        let namespace = self
+            .tenants
+            .get_mut(organization_id)
+            .ok_or_else(|| Error::not_found("Tenant namespace", organization_id))?;
+
+        let now = chrono::Utc::now();
+        let expires_at = duration_secs.map(|d| now + chrono::Duration::seconds(d as i64));
+
+        info!(
+            ip = %ip,
+            organization_id = %organization_id,
+            reason = %reason,
+            "Blocking IP in tenant namespace"
+        );
+
+        namespace.blocked_ips.insert(
+            ip,
+            TenantBlock {
+                reason: reason.to_string(),
+                blocked_at: now,
+                expires_at,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Unblock an IP address in the namespace of one organization
+    pub fn unblock_ip_in_tenant(&mut self, organization_id: &str, ip: &IpAddr) -> Result<()> {
+        let removed = self
+            .tenants
+            .get_mut(organization_id)
+            .and_then(|namespace| namespace.blocked_ips.remove(ip));
+
+        if removed.is_some() {
+            info!(ip = %ip, organization_id = %organization_id, "Unblocked IP in tenant namespace");
+            Ok(())
+        } else {
+            Err(Error::not_found("Blocked IP", ip.to_string()))
+        }
+    }
+
+    /// Check if an IP is blocked for the backends of an organization
+    ///
+    /// Global blocks apply to every organization.
+    pub fn is_blocked_in_tenant(&self, organization_id: &str, ip: &IpAddr) -> bool {
+        if self.is_blocked(ip) {
+            return true;
+        }
+        self.tenants
+            .get(organization_id)
+            .and_then(|namespace| namespace.blocked_ips.get(ip))
+            .is_some_and(|block| !block.is_expired(chrono::Utc::now()))
+    }
+
+    /// Drop backends no longer in the configuration and remove empty namespaces
+    pub fn prune_tenants(&mut self, active_backends: &HashSet<String>) {
+        for namespace in self.tenants.values_mut() {
+            namespace.backends.retain(|id| active_backends.contains(id));
+            let backends = &namespace.backends;
+            namespace.destinations.retain(|id, _| backends.contains(id));
+        }
+        self.tenants.retain(|organization_id, namespace| {
+            if namespace.backends.is_empty() {
+                debug!(organization_id = %organization_id, "Removing empty tenant namespace");
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Replace the limits of the active rate limit profiles, keyed by
+    /// backend ID
+    pub fn set_profile_limits(&mut self, limits: HashMap<String, ProfileLimits>) {
+        self.profile_limits = limits;
+    }
+
+    /// Replace the active restart modes, keyed by backend ID
+    pub fn set_restarts(&mut self, restarts: HashMap<String, RestartMode>) {
+        self.restarts = restarts;
+    }
+
+    /// Limits of a namespace, with those of the active rate limit profile
+    /// of its first backend (by ID) that has one, relaxed and validated
+    /// more strictly while one of its backends restarts
+    fn effective_limits(&self, namespace: &TenantNamespace) -> TenantLimits {
+        let mut backends: Vec<&String> = namespace.backends.iter().collect();
+        backends.sort();
+
+        let limits = match backends
+            .iter()
+            .find_map(|&backend_id| self.profile_limits.get(backend_id))
+        {
+            Some(profile) => TenantLimits {
+                per_ip_pps: profile.per_ip_pps,
+                per_ip_burst: profile.per_ip_burst,
+                ..namespace.limits
+            },
+            None => namespace.limits,
+        };
+
+        match backends
+            .iter()
+            .find_map(|&backend_id| self.restarts.get(backend_id))
+        {
+            Some(restart) => {
+                let per_ip_pps = match limits.per_ip_pps {
+                    0 => DEFAULT_PPS_LIMIT,
+                    pps => pps,
+                };
+                TenantLimits {
+                    protection_level: restart.protection_level(limits.protection_level),
+                    per_ip_pps: restart.relax(per_ip_pps),
+                    per_ip_burst: restart.relax(limits.per_ip_burst),
+                }
+            }
+            None => limits,
+        }
+    }
+
+    /// Build the contents of the xdp_filter tenant maps
+    pub fn tenant_map_entries(&self) -> TenantMapEntries {
+        let now = chrono::Utc::now();
+        let mut entries = TenantMapEntries::default();
+
+        for namespace in self.tenants.values() {
+            let tenant_id = namespace.tenant_id;
+
+            for destinations in namespace.destinations.values() {
+                entries
+                    .destinations
+                    .extend(destinations.iter().map(|&d| (d, tenant_id)));
+            }
+
+            let limits = self.effective_limits(namespace);
+            entries.configs.push((
+                tenant_id,
+                TenantConfig {
+                    protection_level: limits.protection_level as u32,
+                    _pad: 0,
+                    per_ip_pps_limit: limits.per_ip_pps,
+                    per_ip_burst: limits.per_ip_burst,
+                },
+            ));
+
+            entries.blocked.extend(
+                namespace
+                    .blocked_ips
+                    .iter()
+                    .filter(|(_, block)| !block.is_expired(now))
+                    .map(|(&ip, _)| (tenant_id, ip)),
+            );
+        }
+
+        entries
+    }
+
+    /// Set or clear the honeypot ports of a backend
+    pub fn set_backend_honeypot(&mut self, backend_id: &str, honeypot: Option<BackendHoneypot>) {
+        match honeypot {
+            Some(honeypot) if !honeypot.settings.ports.is_empty() => {
+                debug!(
+                    backend_id = %backend_id,
+                    ports = ?honeypot.settings.ports,
+                    "Updating honeypot ports"
+                );
+                self.honeypots.insert(backend_id.to_string(), honeypot);
+            }
+            _ => {
+                self.honeypots.remove(backend_id);
+            }
+        }
+    }
+
+    /// Drop honeypots of backends no longer in the configuration
+    pub fn prune_honeypots(&mut self, active_backends: &HashSet<String>) {
+        self.honeypots.retain(|id, _| active_backends.contains(id));
+    }
+
+    /// Honeypots keyed by backend ID
+    pub fn honeypots(&self) -> &HashMap<String, BackendHoneypot> {
+        &self.honeypots
+    }
+
+    /// Build the contents of the xdp_filter honeypot maps
+    pub fn honeypot_map_entries(&self) -> HoneypotMapEntries {
+        honeypot_map_entries(&self.honeypots)
+    }
+
+    /// Record a source the kernel saw on a honeypot port
+    ///
+    /// Sources the kernel already blocked are mirrored into the blocklist
+    /// with the duration of the honeypot that caught them, so they show up in
+    /// the block APIs and reputation events like any other block.
+    pub fn record_honeypot_hit(&mut self, ip: IpAddr, hit: &HoneypotHit) -> Result<FlaggedSource> {
+        let source = FlaggedSource::from_hit(ip, hit, chrono::Utc::now());
+        self.flagged.push(source.clone());
+
+        if source.blocked {
+            let duration_secs = self
+                .honeypots
+                .values()
+                .find(|h| h.tenant_id == hit.tenant_id && h.settings.ports.contains(&hit.port))
+                .map(|h| h.settings.block_duration_secs)
+                .unwrap_or(0);
+            let reason = format!("honeypot:{}", hit.port);
+            self.block_ip(ip, &reason, (duration_secs > 0).then_some(duration_secs))?;
+        }
+
+        Ok(source)
+    }
+
+    /// Sources recently seen on honeypot ports, newest last
+    pub fn flagged_sources(&self) -> Vec<FlaggedSource> {
+        self.flagged.recent()
+    }
+
+    /// Set or clear the per-connection bandwidth limit of a backend
+    pub fn set_backend_flow_rate(&mut self, backend_id: &str, flow_rate: Option<BackendFlowRate>) {
+        match flow_rate {
+            Some(flow_rate) if !flow_rate.destinations.is_empty() => {
+                debug!(
+                    backend_id = %backend_id,
+                    bytes_per_sec = flow_rate.config.bytes_per_sec,
+                    "Updating per-connection bandwidth limit"
+                );
+                self.flow_rates.insert(backend_id.to_string(), flow_rate);
+            }
+            _ => {
+                self.flow_rates.remove(backend_id);
+            }
+        }
+    }
+
+    /// Drop bandwidth limits of backends no longer in the configuration
+    pub fn prune_flow_rates(&mut self, active_backends: &HashSet<String>) {
+        self.flow_rates.retain(|id, _| active_backends.contains(id));
+    }
+
+    /// Replace the bandwidth throttles of backends over their quota
+    ///
+    /// Returns whether they changed.
+    pub fn set_bandwidth_throttles(&mut self, throttles: HashMap<String, u64>) -> bool {
+        if self.bandwidth_throttles == throttles {
+            return false;
+        }
+        self.bandwidth_throttles = throttles;
+        true
+    }
+
+    /// Bandwidth throttles currently applied
+    pub fn bandwidth_throttles(&self) -> &HashMap<String, u64> {
+        &self.bandwidth_throttles
+    }
+
+    /// Build the contents of the shared `FLOW_RATES` map, throttles included
+    pub fn flow_rate_entries(&self) -> Vec<(TenantDstV6Key, FlowRateConfig)> {
+        if self.bandwidth_throttles.is_empty() {
+            return flow_rate_map_entries(&self.flow_rates);
+        }
+        flow_rate_map_entries(&throttled_flow_rates(
+            &self.flow_rates,
+            &self.traffic,
+            &self.bandwidth_throttles,
+        ))
+    }
+
+    /// Set the destinations whose traffic is counted for a backend
+    pub fn set_backend_traffic(&mut self, backend_id: &str, destinations: Vec<TenantDestination>) {
+        if destinations.is_empty() {
+            self.traffic.remove(backend_id);
+        } else {
+            self.traffic.insert(backend_id.to_string(), destinations);
+        }
+    }
+
+    /// Stop counting the traffic of backends no longer in the configuration
+    pub fn prune_traffic(&mut self, active_backends: &HashSet<String>) {
+        self.traffic.retain(|id, _| active_backends.contains(id));
+    }
+
+    /// Build the keys of `DST_TRAFFIC` and the backend each one belongs to
+    pub fn traffic_entries(&self) -> Vec<(TenantDstV6Key, String)> {
+        traffic_map_keys(&self.traffic)
+    }
+
+    /// Set or clear the marking policy of a backend
+    pub fn set_backend_marking(&mut self, backend_id: &str, marking: Option<BackendMarking>) {
+        match marking {
+            Some(marking) if !marking.destinations.is_empty() => {
+                debug!(
+                    backend_id = %backend_id,
+                    dscp = marking.settings.dscp,
+                    "Updating traffic marking policy"
+                );
+                self.marking.insert(backend_id.to_string(), marking);
+            }
+            _ => {
+                self.marking.remove(backend_id);
+            }
+        }
+        assign_policy_ids(&mut self.marking_ids, &self.marking);
+    }
+
+    /// Drop marking policies of backends no longer in the configuration
+    pub fn prune_marking(&mut self, active_backends: &HashSet<String>) {
+        self.marking.retain(|id, _| active_backends.contains(id));
+        assign_policy_ids(&mut self.marking_ids, &self.marking);
+    }
+
+    /// Build the contents of `MARKING_POLICIES`
+    pub fn marking_entries(&self) -> Vec<(TenantDstV6Key, MarkingPolicy)> {
+        marking_map_entries(&self.marking, &self.marking_ids)
+    }
+
+    /// Marking activity by backend ID, from the per-policy counters
+    pub fn marking_status(&self, counters: &[MarkingCounter]) -> Vec<MarkingStatus> {
+        marking_status(&self.marking, &self.marking_ids, counters)
+    }
+
+    /// Set or clear the learning mode of a backend
+    pub fn set_backend_learning(&mut self, backend_id: &str, learning: Option<BackendLearning>) {
+        if let Some(ref learning) = learning {
+            debug!(
+                backend_id = %backend_id,
+                training_secs = learning.settings.training_secs,
+                "Updating allowlist learning"
+            );
+        }
+        self.learning
+            .configure(backend_id, learning, chrono::Utc::now());
+    }
+
+    /// Drop the learning state of backends no longer in the configuration
+    pub fn prune_learning(&mut self, active_backends: &HashSet<String>) {
+        self.learning.prune(active_backends);
+    }
+
+    /// Credit handshakes drained from the kernel to the training backends
+    pub fn record_handshakes(&mut self, handshakes: &[(IpAddr, HandshakeRecord)]) {
+        let now = chrono::Utc::now();
+        for (ip, record) in handshakes {
+            self.learning.record(*ip, record, now);
+        }
+    }
+
+    /// Whether any backend is in its training window
+    pub fn learning_active(&self) -> bool {
+        self.learning.is_training(chrono::Utc::now())
+    }
+
+    /// Build the contents of the shared `KNOWN_GOOD` map
+    pub fn known_good_entries(&self) -> Vec<([u8; 16], u32)> {
+        self.learning.known_good_entries(chrono::Utc::now())
+    }
+
+    /// Learning state of every backend, sorted by backend ID
+    pub fn learning_status(&self) -> Vec<LearningStatus> {
+        self.learning.status(chrono::Utc::now())
+    }
+
+    /// What every backend learned, for other workers to warm up from
+    pub fn export_learning(&self) -> Vec<LearnedBackend> {
+        self.learning.export()
+    }
+
+    /// Merge what another worker learned
+    pub fn import_learning(&mut self, learned: Vec<LearnedBackend>) -> usize {
+        self.learning.import(learned)
+    }
+
     /// Get statistics
     pub fn stats(&self) -> MapStats {
         MapStats {
@@ -242,6 +769,9 @@ impl MapManager {
             rate_limits: self.rate_limits.len(),
             conntrack_entries: self.conntrack.len(),
             backends: self.backends.len(),
+            tenants: self.tenants.len(),
+            honeypots: self.honeypots.len(),
+            learning: self.learning.backend_count(),
         }
     }
 }
@@ -253,6 +783,9 @@ pub struct MapStats {
     pub rate_limits: usize,
     pub conntrack_entries: usize,
     pub backends: usize,
+    pub tenants: usize,
+    pub honeypots: usize,
+    pub learning: usize,
 }
 
 #[cfg(test)]
@@ -286,5 +819,216 @@ mod tests {
         let entry = manager.get_conntrack(&key).unwrap();
         assert_eq!(entry.state, ConnTrackState::New);
         assert_eq!(entry.packets, 1);
+
+        let dst: IpAddr = "10.0.0.2".parse().unwrap();
+        assert_eq!(manager.count_connections(dst, 80), 1);
+        assert_eq!(manager.count_connections(dst, 0), 1);
+        assert_eq!(manager.count_connections(dst, 443), 0);
+        manager.update_conntrack(key, ConnTrackState::Closed, 2, 128);
+        assert_eq!(manager.count_connections(dst, 80), 0);
+    }
+
+    fn destination(addr: &str, port: u16) -> Vec<TenantDestination> {
+        vec![TenantDestination {
+            addr: addr.parse().unwrap(),
+            port,
+        }]
+    }
+
+    #[test]
+    fn test_tenant_block_isolated() {
+        let mut manager = MapManager::new();
+        let ip: IpAddr = "192.168.1.1".parse().unwrap();
+
+        manager.register_backend_tenant(
+            "org-a",
+            "backend-a",
+            destination("10.0.0.1", 25565),
+            TenantLimits::default(),
+        );
+        manager.register_backend_tenant(
+            "org-b",
+            "backend-b",
+            destination("10.0.0.2", 25565),
+            TenantLimits::default(),
+        );
+
+        manager
+            .block_ip_in_tenant("org-a", ip, "Test block", None)
+            .unwrap();
+        assert!(manager.is_blocked_in_tenant("org-a", &ip));
+        assert!(!manager.is_blocked_in_tenant("org-b", &ip));
+        assert!(!manager.is_blocked(&ip));
+
+        // Global blocks apply to every tenant
+        manager.block_ip(ip, "Global block", None).unwrap();
+        assert!(manager.is_blocked_in_tenant("org-b", &ip));
+    }
+
+    #[test]
+    fn test_tenant_ids_stable() {
+        let mut manager = MapManager::new();
+
+        let a = manager.register_backend_tenant("org-a", "b1", vec![], TenantLimits::default());
+        let b = manager.register_backend_tenant("org-b", "b2", vec![], TenantLimits::default());
+        let a2 = manager.register_backend_tenant("org-a", "b3", vec![], TenantLimits::default());
+
+        assert_ne!(a, 0);
+        assert_ne!(a, b);
+        assert_eq!(a, a2);
+        assert_eq!(manager.stats().tenants, 2);
+    }
+
+    #[test]
+    fn test_prune_tenants() {
+        let mut manager = MapManager::new();
+        manager.register_backend_tenant("org-a", "b1", vec![], TenantLimits::default());
+        manager.register_backend_tenant("org-b", "b2", vec![], TenantLimits::default());
+
+        let active: HashSet<String> = ["b1".to_string()].into_iter().collect();
+        manager.prune_tenants(&active);
+
+        assert!(manager.get_tenant("org-a").is_some());
+        assert!(manager.get_tenant("org-b").is_none());
+    }
+
+    #[test]
+    fn test_tenant_map_entries() {
+        let mut manager = MapManager::new();
+        let ip: IpAddr = "192.168.1.1".parse().unwrap();
+        let limits = TenantLimits {
+            protection_level: 3,
+            per_ip_pps: 500,
+            per_ip_burst: 1000,
+        };
+
+        let tenant_id =
+            manager.register_backend_tenant("org-a", "b1", destination("10.0.0.1", 0), limits);
+        manager
+            .block_ip_in_tenant("org-a", ip, "Test block", None)
+            .unwrap();
+
+        let entries = manager.tenant_map_entries();
+        assert_eq!(entries.destinations.len(), 1);
+        assert_eq!(entries.destinations[0].1, tenant_id);
+        assert_eq!(entries.configs[0].1.per_ip_pps_limit, 500);
+        assert_eq!(entries.configs[0].1.per_ip_burst, 1000);
+        assert_eq!(entries.blocked, vec![(tenant_id, ip)]);
+    }
+
+    #[test]
+    fn test_profile_limits_override_tenant_limits() {
+        let mut manager = MapManager::new();
+        let limits = TenantLimits {
+            protection_level: 3,
+            per_ip_pps: 500,
+            per_ip_burst: 1000,
+        };
+        manager.register_backend_tenant("org-a", "b1", destination("10.0.0.1", 0), limits);
+
+        manager.set_profile_limits(HashMap::from([(
+            "b1".to_string(),
+            ProfileLimits {
+                per_ip_pps: 50,
+                per_ip_burst: 0,
+            },
+        )]));
+        let config = manager.tenant_map_entries().configs[0].1;
+        assert_eq!(config.protection_level, 3);
+        assert_eq!(config.per_ip_pps_limit, 50);
+        assert_eq!(config.per_ip_burst, 0);
+
+        manager.set_profile_limits(HashMap::new());
+        assert_eq!(
+            manager.tenant_map_entries().configs[0].1.per_ip_pps_limit,
+            500
+        );
+    }
+
+    #[test]
+    fn test_restart_relaxes_limits() {
+        let mut manager = MapManager::new();
+        let limits = TenantLimits {
+            protection_level: 2,
+            per_ip_pps: 0,
+            per_ip_burst: 0,
+        };
+        manager.register_backend_tenant("org-a", "b1", destination("10.0.0.1", 0), limits);
+        manager.set_profile_limits(HashMap::from([(
+            "b1".to_string(),
+            ProfileLimits {
+                per_ip_pps: 200,
+                per_ip_burst: 400,
+            },
+        )]));
+
+        manager.set_restarts(HashMap::from([(
+            "b1".to_string(),
+            RestartMode {
+                backend_id: "b1".to_string(),
+                connection_multiplier: 5,
+                reason: String::new(),
+                started_by: "user-1".to_string(),
+                started_at: 0,
+                ends_at: 600,
+            },
+        )]));
+        let config = manager.tenant_map_entries().configs[0].1;
+        assert_eq!(config.protection_level, 4);
+        assert_eq!(config.per_ip_pps_limit, 1000);
+        assert_eq!(config.per_ip_burst, 2000);
+
+        // Without a profile the kernel default is relaxed
+        manager.set_profile_limits(HashMap::new());
+        let config = manager.tenant_map_entries().configs[0].1;
+        assert_eq!(config.per_ip_pps_limit, 5 * DEFAULT_PPS_LIMIT);
+        assert_eq!(config.per_ip_burst, 0);
+
+        manager.set_restarts(HashMap::new());
+        let config = manager.tenant_map_entries().configs[0].1;
+        assert_eq!(config.protection_level, 2);
+        assert_eq!(config.per_ip_pps_limit, 0);
+    }
+
+    #[test]
+    fn test_record_honeypot_hit() {
+        use crate::ebpf::honeypot::HoneypotPorts;
+
+        let mut manager = MapManager::new();
+        manager.set_backend_honeypot(
+            "b1",
+            Some(BackendHoneypot {
+                tenant_id: 0,
+                addrs: vec![],
+                settings: HoneypotPorts {
+                    ports: vec![23],
+                    auto_block: true,
+                    block_duration_secs: 600,
+                },
+            }),
+        );
+
+        let flagged: IpAddr = "198.51.100.1".parse().unwrap();
+        let blocked: IpAddr = "198.51.100.2".parse().unwrap();
+        let mut hit = HoneypotHit {
+            packets: 3,
+            port: 23,
+            protocol: 6,
+            ..Default::default()
+        };
+        manager.record_honeypot_hit(flagged, &hit).unwrap();
+        hit.blocked = 1;
+        manager.record_honeypot_hit(blocked, &hit).unwrap();
+
+        assert!(!manager.is_blocked(&flagged));
+        assert!(manager.is_blocked(&blocked));
+        let entry = &manager.blocked_ips[&blocked];
+        assert_eq!(entry.reason, "honeypot:23");
+        assert!(entry.expires_at.is_some());
+        assert_eq!(manager.take_block_events(), vec![blocked]);
+        assert_eq!(manager.flagged_sources().len(), 2);
+
+        manager.prune_honeypots(&HashSet::new());
+        assert!(manager.honeypot_map_entries().v4.is_empty());
     }
 }
@@ -0,0 +1,299 @@
+//! Traffic marking
+//!
+//! Greylisted sources exceeding their reduced bucket are dropped by
+//! xdp_filter, unless the destination backend has a marking policy: then
+//! their packets are rewritten to a scavenger DSCP (and, if asked, ECN CE)
+//! and passed, so QoS downstream of the worker sheds them first instead of
+//! the worker refusing traffic that may well be legitimate. Policies can also
+//! mark greylisted traffic within its bucket. This module mirrors the kernel
+//! layouts, builds the `MARKING_POLICIES` contents from the backends'
+//! settings and attributes the per-policy `MARKING_COUNTERS` to backends.
+//!
+//! Policy ids index the counters, so a backend keeps its id for as long as
+//! it has a policy; ids are reused once freed, after their counter is reset.
+
+use super::tenants::{TenantDestination, TenantDstV6Key, destination_entries};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Maximum number of destinations with a policy (mirrors `marking::MAX_DESTINATIONS`)
+pub const MAX_DESTINATIONS: usize = 65536;
+
+/// Number of policy counters (mirrors `marking::MAX_POLICIES`)
+pub const MAX_POLICIES: u32 = 4096;
+
+/// Mark and pass greylisted packets beyond their bucket
+pub const FLAG_GREYLIST_EXCESS: u32 = 1 << 0;
+/// Also mark greylisted packets within their bucket
+pub const FLAG_GREYLIST_WITHIN: u32 = 1 << 1;
+/// Set ECN CE on ECN-capable packets
+pub const FLAG_ECN_CE: u32 = 1 << 2;
+
+/// Lower-effort per-hop behavior (RFC 8622), used when no DSCP is set
+pub const DSCP_LE: u8 = 1;
+/// Largest DSCP value
+pub const DSCP_MAX: u8 = 63;
+
+/// Value of `MARKING_POLICIES` (mirrors `MarkingPolicy`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MarkingPolicy {
+    /// Index into `MARKING_COUNTERS`
+    pub policy_id: u32,
+    pub flags: u32,
+    pub dscp: u8,
+    pub _pad: [u8; 7],
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u32`, one `u8` and explicit padding
+// up to 16 bytes.
+unsafe impl aya::Pod for MarkingPolicy {}
+
+/// Value of `MARKING_COUNTERS` (mirrors `MarkingCounter`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MarkingCounter {
+    pub packets: u64,
+    pub bytes: u64,
+    /// Part of `packets` marked CE
+    pub ce_marked: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of three `u64` fields, no padding.
+unsafe impl aya::Pod for MarkingCounter {}
+
+impl MarkingCounter {
+    /// Sum of two counters
+    pub fn add(&self, other: &Self) -> Self {
+        Self {
+            packets: self.packets.wrapping_add(other.packets),
+            bytes: self.bytes.wrapping_add(other.bytes),
+            ce_marked: self.ce_marked.wrapping_add(other.ce_marked),
+        }
+    }
+}
+
+/// Marking settings of a backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MarkingSettings {
+    pub dscp: u8,
+    pub ecn_ce: bool,
+    /// Mark greylisted traffic within its bucket too
+    pub mark_within_limit: bool,
+}
+
+impl MarkingSettings {
+    /// `FLAG_*` bits of the kernel policy
+    pub fn flags(&self) -> u32 {
+        let mut flags = FLAG_GREYLIST_EXCESS;
+        if self.mark_within_limit {
+            flags |= FLAG_GREYLIST_WITHIN;
+        }
+        if self.ecn_ce {
+            flags |= FLAG_ECN_CE;
+        }
+        flags
+    }
+}
+
+/// Marking policy of a backend on this worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendMarking {
+    /// Destinations of the backend; backends without destination addresses
+    /// cannot be marked
+    pub destinations: Vec<TenantDestination>,
+    pub settings: MarkingSettings,
+}
+
+/// Marking activity of a backend
+#[derive(Debug, Clone, Serialize)]
+pub struct MarkingStatus {
+    pub backend_id: String,
+    pub policy_id: u32,
+    pub settings: MarkingSettings,
+    /// Marked since the policy was assigned or xdp_filter loaded
+    pub counters: MarkingCounter,
+}
+
+/// Keep the policy ids of `backends` up to date
+///
+/// Backends keep their id, removed backends free theirs and new ones get the
+/// lowest free id in backend id order. Backends left without an id once all
+/// `MAX_POLICIES` are taken are not marked.
+pub fn assign_policy_ids(
+    ids: &mut HashMap<String, u32>,
+    backends: &HashMap<String, BackendMarking>,
+) {
+    ids.retain(|backend_id, _| backends.contains_key(backend_id));
+
+    let used: BTreeSet<u32> = ids.values().copied().collect();
+    let mut free = (0..MAX_POLICIES).filter(|id| !used.contains(id));
+    let ordered: BTreeMap<&String, &BackendMarking> = backends.iter().collect();
+    for backend_id in ordered.keys() {
+        if ids.contains_key(*backend_id) {
+            continue;
+        }
+        let Some(id) = free.next() else {
+            break;
+        };
+        ids.insert((*backend_id).clone(), id);
+    }
+}
+
+/// Build the contents of `MARKING_POLICIES`
+///
+/// Backends without a policy id are skipped.
+pub fn marking_map_entries(
+    backends: &HashMap<String, BackendMarking>,
+    ids: &HashMap<String, u32>,
+) -> Vec<(TenantDstV6Key, MarkingPolicy)> {
+    destination_entries(backends, MAX_DESTINATIONS, |backend_id, backend| {
+        let policy = MarkingPolicy {
+            policy_id: *ids.get(backend_id)?,
+            flags: backend.settings.flags(),
+            dscp: backend.settings.dscp.min(DSCP_MAX),
+            _pad: [0; 7],
+        };
+        Some((backend.destinations.as_slice(), policy))
+    })
+}
+
+/// Marking activity of every backend with a policy, by backend id
+///
+/// `counters` is indexed by policy id.
+pub fn marking_status(
+    backends: &HashMap<String, BackendMarking>,
+    ids: &HashMap<String, u32>,
+    counters: &[MarkingCounter],
+) -> Vec<MarkingStatus> {
+    let ordered: BTreeMap<&String, &BackendMarking> = backends.iter().collect();
+    ordered
+        .into_iter()
+        .filter_map(|(backend_id, backend)| {
+            let policy_id = *ids.get(backend_id)?;
+            Some(MarkingStatus {
+                backend_id: backend_id.clone(),
+                policy_id,
+                settings: backend.settings,
+                counters: counters
+                    .get(policy_id as usize)
+                    .copied()
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(addrs: &[&str], port: u16, dscp: u8) -> BackendMarking {
+        BackendMarking {
+            destinations: addrs
+                .iter()
+                .map(|addr| TenantDestination {
+                    addr: addr.parse().unwrap(),
+                    port,
+                })
+                .collect(),
+            settings: MarkingSettings {
+                dscp,
+                ecn_ce: false,
+                mark_within_limit: false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_assign_policy_ids() {
+        let mut backends = HashMap::new();
+        backends.insert("b".to_string(), backend(&["192.0.2.11"], 0, DSCP_LE));
+        backends.insert("a".to_string(), backend(&["192.0.2.10"], 0, DSCP_LE));
+
+        let mut ids = HashMap::new();
+        assign_policy_ids(&mut ids, &backends);
+        assert_eq!(ids["a"], 0);
+        assert_eq!(ids["b"], 1);
+
+        // Removed backends free their id, existing ones keep theirs
+        backends.remove("a");
+        backends.insert("c".to_string(), backend(&["192.0.2.12"], 0, DSCP_LE));
+        assign_policy_ids(&mut ids, &backends);
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids["b"], 1);
+        assert_eq!(ids["c"], 0);
+    }
+
+    #[test]
+    fn test_map_entries() {
+        let mut backends = HashMap::new();
+        backends.insert(
+            "a".to_string(),
+            backend(&["192.0.2.10", "2001:db8::10"], 25565, 8),
+        );
+        backends.insert("b".to_string(), backend(&["192.0.2.20"], 0, 200));
+        backends.insert("c".to_string(), backend(&["192.0.2.30"], 0, DSCP_LE));
+
+        let mut ids = HashMap::new();
+        assign_policy_ids(&mut ids, &backends);
+        // Backends without a policy id are not marked
+        ids.remove("c");
+        let entries = marking_map_entries(&backends, &ids);
+        assert_eq!(entries.len(), 3);
+
+        let (key, policy) = entries[0];
+        assert_eq!(
+            key.addr,
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 192, 0, 2, 10]
+        );
+        assert_eq!(key.port, 25565);
+        assert_eq!(policy.policy_id, ids["a"]);
+        assert_eq!(policy.dscp, 8);
+        assert_eq!(policy.flags, FLAG_GREYLIST_EXCESS);
+
+        // Out of range DSCPs are clamped
+        assert_eq!(entries[1].1.policy_id, ids["b"]);
+        assert_eq!(entries[1].1.dscp, DSCP_MAX);
+        assert_eq!(entries[2].0.addr[0], 0x20);
+    }
+
+    #[test]
+    fn test_flags() {
+        let settings = MarkingSettings {
+            dscp: DSCP_LE,
+            ecn_ce: true,
+            mark_within_limit: true,
+        };
+        assert_eq!(
+            settings.flags(),
+            FLAG_GREYLIST_EXCESS | FLAG_GREYLIST_WITHIN | FLAG_ECN_CE
+        );
+    }
+
+    #[test]
+    fn test_marking_status() {
+        let mut backends = HashMap::new();
+        backends.insert("a".to_string(), backend(&["192.0.2.10"], 0, DSCP_LE));
+        backends.insert("b".to_string(), backend(&["192.0.2.11"], 0, DSCP_LE));
+        let mut ids = HashMap::new();
+        assign_policy_ids(&mut ids, &backends);
+
+        let counters = [MarkingCounter {
+            packets: 10,
+            bytes: 15_000,
+            ce_marked: 4,
+        }];
+        let status = marking_status(&backends, &ids, &counters);
+        assert_eq!(status.len(), 2);
+        assert_eq!(status[0].backend_id, "a");
+        assert_eq!(status[0].counters.packets, 10);
+        assert_eq!(status[1].counters, MarkingCounter::default());
+    }
+
+    #[test]
+    fn test_layout_matches_kernel() {
+        assert_eq!(std::mem::size_of::<MarkingPolicy>(), 16);
+        assert_eq!(std::mem::size_of::<MarkingCounter>(), 24);
+    }
+}
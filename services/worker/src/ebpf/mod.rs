@@ -1,6 +1,34 @@
 //! eBPF/XDP management module
 
+pub mod artifacts;
+pub mod batch;
+pub mod capacity;
+pub mod cgnat;
+pub mod conn_table;
+pub mod connections;
+pub mod diagnostics;
+pub mod dns;
+pub mod drop_events;
+pub mod event_budget;
+pub mod flow_mark;
+pub mod flow_rate;
+pub mod honeypot;
 pub mod interface;
+pub mod latency;
+pub mod learning;
 pub mod loader;
 pub mod maps;
+pub mod marking;
+pub mod offender_bundle;
+pub mod penalty;
+pub mod probe;
 pub mod programs;
+pub mod protected_ports;
+pub mod quic_cid;
+pub mod sampling;
+pub mod selftest;
+pub mod sources;
+pub mod stats;
+pub mod tenants;
+pub mod threat_intel;
+pub mod traffic;
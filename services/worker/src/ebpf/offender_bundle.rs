@@ -0,0 +1,746 @@
+//! Offender bundles
+//!
+//! Cold-start protection: a curated bundle of known attack infrastructure
+//! (booter and stresser source ranges) is loaded into the threat
+//! intelligence maps at startup, before any feed could be fetched. Bundles
+//! are JSON files signed with ed25519 (see
+//! `pistonprotection_common::signing`), kept as `offenders-<version>.json`
+//! next to their `.sig` in the bundle directory, so a node drops known
+//! attack sources from its first packet even without network access.
+//!
+//! A configured URL is polled for newer bundles, which are only saved once
+//! their signature checks out. The newest bundle is loaded unless a version
+//! is pinned. A newly loaded version is on probation: if it drops far more
+//! packets than the version before it, it is taken for a false-positive
+//! spike and rolled back, and it is not loaded again unless pinned.
+
+use super::threat_intel::{BUNDLE_FEED, ThreatIntelManager};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::signing::{TrustedKeys, signature_path};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Trusted bundle signing keys (comma separated, base64); bundles are only
+/// loaded when set
+pub const BUNDLE_KEYS_ENV: &str = "PISTON_OFFENDER_BUNDLE_KEYS";
+
+/// Default directory of the verified bundles
+pub const DEFAULT_BUNDLE_DIR: &str = "/var/lib/pistonprotection/bundles";
+
+/// Default interval between checks for a newer bundle
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+
+/// Default time a new version is watched for false-positive spikes
+pub const DEFAULT_PROBATION: Duration = Duration::from_secs(1800);
+
+/// Default factor over the drop rate of the previous version that counts as
+/// a false-positive spike
+pub const DEFAULT_SPIKE_FACTOR: f64 = 10.0;
+
+/// Default drop rate below which a version is never rolled back
+pub const DEFAULT_MIN_SPIKE_PPS: f64 = 5000.0;
+
+/// Default number of bundle versions kept on disk
+pub const DEFAULT_KEEP_VERSIONS: usize = 5;
+
+/// File keeping the pinned and rejected versions across restarts
+const STATE_FILE: &str = "state.json";
+
+/// Bundle settings
+#[derive(Debug, Clone)]
+pub struct BundleConfig {
+    pub dir: PathBuf,
+    /// URL of the latest bundle; its signature is fetched from `<url>.sig`
+    pub url: Option<String>,
+    pub keys: TrustedKeys,
+    /// Version loaded whatever is newer
+    pub pin: Option<u64>,
+    pub refresh_interval: Duration,
+    pub probation: Duration,
+    pub spike_factor: f64,
+    pub min_spike_pps: f64,
+    pub keep_versions: usize,
+}
+
+impl Default for BundleConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from(DEFAULT_BUNDLE_DIR),
+            url: None,
+            keys: TrustedKeys::default(),
+            pin: None,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            probation: DEFAULT_PROBATION,
+            spike_factor: DEFAULT_SPIKE_FACTOR,
+            min_spike_pps: DEFAULT_MIN_SPIKE_PPS,
+            keep_versions: DEFAULT_KEEP_VERSIONS,
+        }
+    }
+}
+
+impl BundleConfig {
+    /// Load bundle settings from `PISTON_OFFENDER_BUNDLE_*` environment
+    /// variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        match TrustedKeys::from_env(BUNDLE_KEYS_ENV) {
+            Ok(keys) => config.keys = keys,
+            Err(e) => tracing::error!(error = %e, "Invalid offender bundle keys, bundles disabled"),
+        }
+        if let Ok(dir) = std::env::var("PISTON_OFFENDER_BUNDLE_DIR") {
+            config.dir = PathBuf::from(dir);
+        }
+        config.url = std::env::var("PISTON_OFFENDER_BUNDLE_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        config.pin = env_parse("PISTON_OFFENDER_BUNDLE_PIN");
+        if let Some(secs) = env_parse::<u64>("PISTON_OFFENDER_BUNDLE_REFRESH_SECS") {
+            config.refresh_interval = Duration::from_secs(secs.max(60));
+        }
+        if let Some(secs) = env_parse("PISTON_OFFENDER_BUNDLE_PROBATION_SECS") {
+            config.probation = Duration::from_secs(secs);
+        }
+        if let Some(factor) = env_parse::<f64>("PISTON_OFFENDER_BUNDLE_SPIKE_FACTOR") {
+            config.spike_factor = factor.max(1.0);
+        }
+        if let Some(pps) = env_parse::<f64>("PISTON_OFFENDER_BUNDLE_MIN_SPIKE_PPS") {
+            config.min_spike_pps = pps.max(0.0);
+        }
+        if let Some(keep) = env_parse::<usize>("PISTON_OFFENDER_BUNDLE_KEEP") {
+            config.keep_versions = keep.max(2);
+        }
+
+        config
+    }
+
+    /// Whether bundles can be verified, and so loaded
+    pub fn enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Path of a bundle version in `dir`
+pub fn bundle_path(dir: &Path, version: u64) -> PathBuf {
+    dir.join(format!("offenders-{}.json", version))
+}
+
+/// Version of a bundle file name
+fn path_version(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("offenders-")?
+        .strip_suffix(".json")?
+        .parse()
+        .ok()
+}
+
+/// Metadata of a bundle; the networks are parsed by the threat feed
+#[derive(Debug, Clone, Deserialize)]
+struct BundleHeader {
+    version: u64,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    networks: Vec<serde::de::IgnoredAny>,
+}
+
+/// Verified bundle version available on disk
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BundleInfo {
+    pub version: u64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub description: String,
+    pub networks: usize,
+    /// Fingerprint of the key that signed the bundle
+    pub key_id: String,
+}
+
+/// Change of the loaded bundle
+#[derive(Debug, Clone, PartialEq)]
+pub enum BundleChange {
+    Loaded {
+        version: u64,
+        previous: Option<u64>,
+    },
+    /// No version is left to load, the bundle feed was emptied
+    Unloaded {
+        previous: u64,
+    },
+}
+
+/// State of the bundles
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleStatus {
+    pub enabled: bool,
+    pub trusted_keys: Vec<String>,
+    pub url: Option<String>,
+    pub pinned: Option<u64>,
+    pub active: Option<u64>,
+    pub activated_at: Option<DateTime<Utc>>,
+    /// Whether the active version can still be rolled back automatically
+    pub on_probation: bool,
+    /// Drop rate of the active version
+    pub drop_pps: Option<f64>,
+    /// Drop rate of the version before it
+    pub previous_drop_pps: Option<f64>,
+    pub available: Vec<BundleInfo>,
+    /// Versions rolled back, with the reason
+    pub rejected: BTreeMap<u64, String>,
+    pub last_fetch: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// Pinned and rejected versions, persisted in the bundle directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PersistedState {
+    pin: Option<u64>,
+    rejected: BTreeMap<u64, String>,
+}
+
+#[derive(Debug, Default)]
+struct BundleState {
+    available: BTreeMap<u64, BundleInfo>,
+    persisted: PersistedState,
+    active: Option<u64>,
+    activated_at: Option<DateTime<Utc>>,
+    /// Drop rate of the previous version when the active one was loaded
+    previous_pps: Option<f64>,
+    current_pps: Option<f64>,
+    /// Last reading of the bundle feed's drop counter
+    last_packets: Option<(u64, DateTime<Utc>)>,
+    last_fetch: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+/// Verified offender bundles and the version loaded into the threat feed
+pub struct OffenderBundles {
+    config: BundleConfig,
+    state: RwLock<BundleState>,
+}
+
+impl OffenderBundles {
+    pub fn new(config: BundleConfig) -> Self {
+        let mut state = BundleState::default();
+        state.persisted.pin = config.pin;
+        Self {
+            config,
+            state: RwLock::new(state),
+        }
+    }
+
+    pub fn config(&self) -> &BundleConfig {
+        &self.config
+    }
+
+    /// Check a bundle against its signature
+    fn verify(&self, content: &[u8], signature: &str) -> Result<BundleInfo> {
+        let key_id = self.config.keys.verify(content, signature)?;
+        let header: BundleHeader = serde_json::from_slice(content)
+            .map_err(|e| Error::validation(format!("Invalid offender bundle: {}", e)))?;
+        Ok(BundleInfo {
+            version: header.version,
+            created_at: header.created_at,
+            description: header.description,
+            networks: header.networks.len(),
+            key_id,
+        })
+    }
+
+    /// Read a bundle version from disk, checking its signature again
+    fn read(&self, version: u64) -> Result<(Vec<u8>, BundleInfo)> {
+        let path = bundle_path(&self.config.dir, version);
+        let content = std::fs::read(&path)
+            .map_err(|e| Error::Internal(format!("Failed to read {}: {}", path.display(), e)))?;
+        let signature = std::fs::read_to_string(signature_path(&path))
+            .map_err(|e| Error::Unauthorized(format!("Missing signature: {}", e)))?;
+        let info = self.verify(&content, &signature)?;
+        if info.version != version {
+            return Err(Error::validation(format!(
+                "{} holds version {}",
+                path.display(),
+                info.version
+            )));
+        }
+        Ok((content, info))
+    }
+
+    /// Load the persisted state and verify every bundle in the directory
+    ///
+    /// Files that fail verification are skipped and reported.
+    pub fn scan(&self) -> Vec<(PathBuf, Error)> {
+        let mut failures = Vec::new();
+        let mut available = BTreeMap::new();
+
+        if let Ok(data) = std::fs::read(self.config.dir.join(STATE_FILE)) {
+            match serde_json::from_slice::<PersistedState>(&data) {
+                Ok(persisted) => {
+                    let mut state = self.state.write();
+                    state.persisted.rejected = persisted.rejected;
+                    // A pin set in the environment wins over one set at runtime
+                    if self.config.pin.is_none() {
+                        state.persisted.pin = persisted.pin;
+                    }
+                }
+                Err(e) => failures.push((
+                    self.config.dir.join(STATE_FILE),
+                    Error::validation(e.to_string()),
+                )),
+            }
+        }
+
+        let entries = match std::fs::read_dir(&self.config.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                failures.push((self.config.dir.clone(), Error::Internal(e.to_string())));
+                self.state.write().available = available;
+                return failures;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(version) = path_version(&path) else {
+                continue;
+            };
+            match self.read(version) {
+                Ok((_, info)) => {
+                    available.insert(version, info);
+                }
+                Err(e) => failures.push((path, e)),
+            }
+        }
+
+        self.state.write().available = available;
+        failures
+    }
+
+    /// Verify a downloaded bundle and save it to the directory
+    ///
+    /// Returns the version, or `None` if it was already available. Only the
+    /// newest versions are kept, besides the active and pinned ones.
+    pub fn store(&self, content: &[u8], signature: &str) -> Result<Option<u64>> {
+        let info = self.verify(content, signature)?;
+        let version = info.version;
+        if self.state.read().available.contains_key(&version) {
+            return Ok(None);
+        }
+
+        let path = bundle_path(&self.config.dir, version);
+        std::fs::create_dir_all(&self.config.dir)
+            .and_then(|_| write_atomic(&signature_path(&path), signature.as_bytes()))
+            .and_then(|_| write_atomic(&path, content))
+            .map_err(|e| Error::Internal(format!("Failed to save offender bundle: {}", e)))?;
+
+        let mut state = self.state.write();
+        state.available.insert(version, info);
+        let keep: Vec<u64> = state
+            .available
+            .keys()
+            .rev()
+            .take(self.config.keep_versions)
+            .copied()
+            .chain(state.active)
+            .chain(state.persisted.pin)
+            .collect();
+        let dropped: Vec<u64> = state
+            .available
+            .keys()
+            .filter(|v| !keep.contains(v))
+            .copied()
+            .collect();
+        for old in dropped {
+            state.available.remove(&old);
+            let old_path = bundle_path(&self.config.dir, old);
+            let _ = std::fs::remove_file(signature_path(&old_path));
+            let _ = std::fs::remove_file(old_path);
+        }
+        Ok(Some(version))
+    }
+
+    /// Version that should be loaded: the pinned one, else the newest one
+    /// not rejected
+    pub fn select(&self) -> Option<u64> {
+        let state = self.state.read();
+        if let Some(pin) = state.persisted.pin {
+            return state.available.contains_key(&pin).then_some(pin);
+        }
+        state
+            .available
+            .keys()
+            .rev()
+            .find(|version| !state.persisted.rejected.contains_key(version))
+            .copied()
+    }
+
+    /// Version currently loaded
+    pub fn active(&self) -> Option<u64> {
+        self.state.read().active
+    }
+
+    /// Load the selected version into the bundle feed if it is not loaded
+    pub fn reconcile(
+        &self,
+        threat_intel: &ThreatIntelManager,
+        now: DateTime<Utc>,
+    ) -> Result<Option<BundleChange>> {
+        let target = self.select();
+        let previous = self.active();
+        if target == previous {
+            return Ok(None);
+        }
+        let feed_id = threat_intel
+            .feed_id(BUNDLE_FEED)
+            .ok_or_else(|| Error::Internal("Offender bundle feed not configured".to_string()))?;
+
+        let Some(version) = target else {
+            threat_intel.record_fetch(
+                feed_id,
+                Ok(r#"{"networks":[]}"#.to_string()),
+                std::time::Instant::now(),
+            );
+            let mut state = self.state.write();
+            state.active = None;
+            state.activated_at = None;
+            return Ok(previous.map(|previous| BundleChange::Unloaded { previous }));
+        };
+
+        let (content, _) = self.read(version)?;
+        let body = String::from_utf8(content)
+            .map_err(|e| Error::validation(format!("Offender bundle is not UTF-8: {}", e)))?;
+        threat_intel.record_fetch(feed_id, Ok(body), std::time::Instant::now());
+
+        let mut state = self.state.write();
+        state.active = Some(version);
+        state.activated_at = Some(now);
+        state.previous_pps = previous.and(state.current_pps);
+        state.current_pps = None;
+        Ok(Some(BundleChange::Loaded { version, previous }))
+    }
+
+    /// Whether the active version can still be rolled back automatically
+    fn on_probation(&self, state: &BundleState, now: DateTime<Utc>) -> bool {
+        state.active.is_some()
+            && state.persisted.pin.is_none()
+            && state
+                .activated_at
+                .is_some_and(|at| (now - at).num_seconds() < self.config.probation.as_secs() as i64)
+    }
+
+    /// Record the packets dropped by the bundle feed so far
+    ///
+    /// Returns the reason if the active version is on probation and drops
+    /// far more than the previous one did, after rejecting it; the caller
+    /// then reconciles to roll it back.
+    pub fn observe_drops(&self, packets: u64, now: DateTime<Utc>) -> Option<String> {
+        let mut state = self.state.write();
+        let last = state.last_packets.replace((packets, now));
+        let (last_packets, last_at) = last?;
+        let elapsed = (now - last_at).num_milliseconds() as f64 / 1000.0;
+        if elapsed <= 0.0 {
+            return None;
+        }
+        // The counters restart from zero when xdp_filter is reloaded
+        let delta = packets.checked_sub(last_packets).unwrap_or(packets);
+        let pps = delta as f64 / elapsed;
+        state.current_pps = Some(pps);
+
+        if !self.on_probation(&state, now) {
+            return None;
+        }
+        let previous = state.previous_pps?;
+        let limit = previous.max(self.config.min_spike_pps) * self.config.spike_factor;
+        if pps <= limit {
+            return None;
+        }
+
+        let version = state.active?;
+        let reason = format!(
+            "drop rate spiked to {:.0} pps from {:.0} pps under the previous version",
+            pps, previous
+        );
+        state.persisted.rejected.insert(version, reason.clone());
+        drop(state);
+        self.persist();
+        Some(reason)
+    }
+
+    /// Reject the active version, so the previous one is loaded again
+    ///
+    /// Returns the rejected version, `None` if nothing is loaded.
+    pub fn rollback(&self, reason: &str) -> Option<u64> {
+        let mut state = self.state.write();
+        let version = state.active?;
+        state.persisted.rejected.insert(version, reason.to_string());
+        if state.persisted.pin == Some(version) {
+            state.persisted.pin = None;
+        }
+        drop(state);
+        self.persist();
+        Some(version)
+    }
+
+    /// Pin a version, or unpin with `None`
+    ///
+    /// Pinning a rejected version clears its rejection.
+    pub fn pin(&self, version: Option<u64>) -> Result<()> {
+        let mut state = self.state.write();
+        if let Some(version) = version {
+            if !state.available.contains_key(&version) {
+                return Err(Error::not_found("offender bundle", version.to_string()));
+            }
+            state.persisted.rejected.remove(&version);
+        }
+        state.persisted.pin = version;
+        drop(state);
+        self.persist();
+        Ok(())
+    }
+
+    fn persist(&self) {
+        let data = match serde_json::to_vec_pretty(&self.state.read().persisted) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to encode offender bundle state");
+                return;
+            }
+        };
+        let written = std::fs::create_dir_all(&self.config.dir)
+            .and_then(|_| write_atomic(&self.config.dir.join(STATE_FILE), &data));
+        if let Err(e) = written {
+            tracing::warn!(error = %e, "Failed to save offender bundle state");
+        }
+    }
+
+    /// Record the outcome of checking the URL for a newer bundle
+    pub fn record_fetch(&self, result: &Result<Option<u64>>, now: DateTime<Utc>) {
+        let mut state = self.state.write();
+        state.last_fetch = Some(now);
+        state.last_error = result.as_ref().err().map(|e| e.to_string());
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> BundleStatus {
+        let state = self.state.read();
+        BundleStatus {
+            enabled: self.config.enabled(),
+            trusted_keys: self.config.keys.ids(),
+            url: self.config.url.clone(),
+            pinned: state.persisted.pin,
+            active: state.active,
+            activated_at: state.activated_at,
+            on_probation: self.on_probation(&state, now),
+            drop_pps: state.current_pps,
+            previous_drop_pps: state.previous_pps,
+            available: state.available.values().rev().cloned().collect(),
+            rejected: state.persisted.rejected.clone(),
+            last_fetch: state.last_fetch,
+            last_error: state.last_error.clone(),
+        }
+    }
+}
+
+/// Write through a temporary file, so a crash never leaves half a file
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Download the latest bundle and its signature
+pub async fn fetch_bundle(client: &reqwest::Client, url: &str) -> Result<(Vec<u8>, String)> {
+    let get = |url: String| async move {
+        let response = client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| Error::ExternalService {
+                service: "offender bundle".to_string(),
+                message: e.to_string(),
+            })?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::ExternalService {
+                service: "offender bundle".to_string(),
+                message: format!("HTTP {} for {}", status, url),
+            });
+        }
+        response.bytes().await.map_err(|e| Error::ExternalService {
+            service: "offender bundle".to_string(),
+            message: e.to_string(),
+        })
+    };
+
+    let content = get(url.to_string()).await?;
+    let signature = get(format!("{}.sig", url)).await?;
+    let signature = String::from_utf8(signature.to_vec())
+        .map_err(|_| Error::Unauthorized("Bundle signature is not text".to_string()))?;
+    Ok((content.to_vec(), signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ebpf::threat_intel::{ThreatFeed, ThreatIntelConfig};
+    use pistonprotection_common::signing::SigningKey;
+
+    struct Signer(SigningKey);
+
+    impl Signer {
+        fn new() -> Self {
+            Self(SigningKey::generate().unwrap().0)
+        }
+
+        fn keys(&self) -> TrustedKeys {
+            TrustedKeys::parse(&self.0.public_key()).unwrap()
+        }
+
+        fn bundle(&self, version: u64, networks: &[&str]) -> (Vec<u8>, String) {
+            let networks: Vec<_> = networks
+                .iter()
+                .map(|network| serde_json::json!({ "network": network }))
+                .collect();
+            let content = serde_json::to_vec(&serde_json::json!({
+                "version": version,
+                "description": "Booter infrastructure",
+                "networks": networks,
+            }))
+            .unwrap();
+            let signature = self.0.sign(&content);
+            (content, signature)
+        }
+    }
+
+    fn setup(signer: &Signer) -> (tempfile::TempDir, OffenderBundles, ThreatIntelManager) {
+        let dir = tempfile::tempdir().unwrap();
+        let bundles = OffenderBundles::new(BundleConfig {
+            dir: dir.path().to_path_buf(),
+            keys: signer.keys(),
+            min_spike_pps: 100.0,
+            ..Default::default()
+        });
+        let threat_intel = ThreatIntelManager::new(ThreatIntelConfig {
+            feeds: vec![ThreatFeed::bundle()],
+        });
+        (dir, bundles, threat_intel)
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let signer = Signer::new();
+        let (dir, bundles, threat_intel) = setup(&signer);
+        let now = Utc::now();
+
+        let (content, signature) = signer.bundle(1, &["203.0.113.0/24", "198.51.100.7"]);
+        assert_eq!(bundles.store(&content, &signature).unwrap(), Some(1));
+        assert_eq!(bundles.store(&content, &signature).unwrap(), None);
+
+        // Bundles signed by another key are refused
+        let (content, signature) = Signer::new().bundle(2, &["192.0.2.0/24"]);
+        assert!(bundles.store(&content, &signature).is_err());
+
+        let change = bundles.reconcile(&threat_intel, now).unwrap();
+        assert_eq!(
+            change,
+            Some(BundleChange::Loaded {
+                version: 1,
+                previous: None
+            })
+        );
+        assert_eq!(threat_intel.status()[0].entries, 2);
+        assert_eq!(bundles.reconcile(&threat_intel, now).unwrap(), None);
+
+        // A restarted worker finds the verified bundle on disk
+        let restarted = OffenderBundles::new(BundleConfig {
+            dir: dir.path().to_path_buf(),
+            keys: signer.keys(),
+            ..Default::default()
+        });
+        assert!(restarted.scan().is_empty());
+        assert_eq!(restarted.select(), Some(1));
+    }
+
+    #[test]
+    fn test_tampered_bundle_skipped() {
+        let signer = Signer::new();
+        let (dir, bundles, _) = setup(&signer);
+        let (content, signature) = signer.bundle(1, &["203.0.113.0/24"]);
+        bundles.store(&content, &signature).unwrap();
+
+        let path = bundle_path(dir.path(), 1);
+        std::fs::write(&path, signer.bundle(1, &["0.0.0.0/1"]).0).unwrap();
+        let failures = bundles.scan();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(bundles.select(), None);
+    }
+
+    #[test]
+    fn test_spike_rolls_back() {
+        let signer = Signer::new();
+        let (_dir, bundles, threat_intel) = setup(&signer);
+        let start = Utc::now();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+
+        let (content, signature) = signer.bundle(1, &["203.0.113.0/24"]);
+        bundles.store(&content, &signature).unwrap();
+        bundles.reconcile(&threat_intel, at(0)).unwrap();
+        assert_eq!(bundles.observe_drops(0, at(0)), None);
+        assert_eq!(bundles.observe_drops(500, at(10)), None);
+
+        let (content, signature) = signer.bundle(2, &["203.0.113.0/24", "100.0.0.0/8"]);
+        bundles.store(&content, &signature).unwrap();
+        let change = bundles.reconcile(&threat_intel, at(10)).unwrap();
+        assert_eq!(
+            change,
+            Some(BundleChange::Loaded {
+                version: 2,
+                previous: Some(1)
+            })
+        );
+
+        // 50 pps before, 20000 pps now
+        assert!(bundles.observe_drops(200_500, at(20)).is_some());
+        assert_eq!(bundles.select(), Some(1));
+        assert_eq!(
+            bundles.reconcile(&threat_intel, at(20)).unwrap(),
+            Some(BundleChange::Loaded {
+                version: 1,
+                previous: Some(2)
+            })
+        );
+        assert!(bundles.status(at(20)).rejected.contains_key(&2));
+
+        // Pinning brings the rejected version back
+        bundles.pin(Some(2)).unwrap();
+        assert_eq!(bundles.select(), Some(2));
+        assert!(bundles.pin(Some(7)).is_err());
+    }
+
+    #[test]
+    fn test_manual_rollback_without_previous() {
+        let signer = Signer::new();
+        let (_dir, bundles, threat_intel) = setup(&signer);
+        let now = Utc::now();
+
+        let (content, signature) = signer.bundle(1, &["203.0.113.0/24"]);
+        bundles.store(&content, &signature).unwrap();
+        bundles.reconcile(&threat_intel, now).unwrap();
+
+        assert_eq!(bundles.rollback("false positives"), Some(1));
+        assert_eq!(
+            bundles.reconcile(&threat_intel, now).unwrap(),
+            Some(BundleChange::Unloaded { previous: 1 })
+        );
+        assert_eq!(threat_intel.status()[0].entries, 0);
+        assert_eq!(bundles.rollback("again"), None);
+    }
+}
@@ -0,0 +1,184 @@
+//! Penalty ladder
+//!
+//! The XDP programs no longer block a source outright when it trips a
+//! detection. Each violation is recorded in the shared, pinned `PENALTY` map
+//! and escalates the source: first its limits are divided, repeated
+//! violations earn a short block and persistent ones a long block.
+//! Violations decay over time. This module holds the ladder of every
+//! protection level, written to the `PENALTY_CONFIG` array.
+
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_proto::worker::PenaltyLevel;
+
+/// Entries of `PENALTY_CONFIG` (mirrors `penalty::LEVELS`)
+pub const LEVELS: usize = 5;
+
+/// Ladder of one protection level (mirrors `PenaltyConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PenaltyConfig {
+    pub rate_limit_threshold: u32,
+    pub rate_limit_divisor: u32,
+    pub short_block_threshold: u32,
+    pub long_block_threshold: u32,
+    pub short_block_ns: u64,
+    pub long_block_ns: u64,
+    pub decay_ns: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of four `u32` and three `u64` fields, no padding.
+unsafe impl aya::Pod for PenaltyConfig {}
+
+const SECOND_NS: u64 = 1_000_000_000;
+
+impl PenaltyConfig {
+    const fn new(
+        thresholds: (u32, u32, u32),
+        rate_limit_divisor: u32,
+        block_secs: (u64, u64),
+        decay_secs: u64,
+    ) -> Self {
+        Self {
+            rate_limit_threshold: thresholds.0,
+            rate_limit_divisor,
+            short_block_threshold: thresholds.1,
+            long_block_threshold: thresholds.2,
+            short_block_ns: block_secs.0 * SECOND_NS,
+            long_block_ns: block_secs.1 * SECOND_NS,
+            decay_ns: decay_secs * SECOND_NS,
+        }
+    }
+
+    /// Convert and validate a ladder sent by the control plane
+    pub fn from_proto(level: &PenaltyLevel) -> Result<Self> {
+        if level.rate_limit_divisor == 0 {
+            return Err(Error::validation(
+                "Penalty rate limit divisor must be at least 1",
+            ));
+        }
+        if level.rate_limit_threshold == 0
+            || level.rate_limit_threshold > level.short_block_threshold
+            || level.short_block_threshold > level.long_block_threshold
+        {
+            return Err(Error::validation(
+                "Penalty thresholds must satisfy 1 <= rate limit <= short block <= long block",
+            ));
+        }
+        if level.short_block_seconds == 0 || level.long_block_seconds < level.short_block_seconds {
+            return Err(Error::validation(
+                "Penalty block durations must satisfy 0 < short block <= long block",
+            ));
+        }
+
+        Ok(Self::new(
+            (
+                level.rate_limit_threshold,
+                level.short_block_threshold,
+                level.long_block_threshold,
+            ),
+            level.rate_limit_divisor,
+            (
+                level.short_block_seconds as u64,
+                level.long_block_seconds as u64,
+            ),
+            level.decay_seconds as u64,
+        ))
+    }
+}
+
+/// Default ladders, indexed by protection level
+///
+/// Higher levels escalate after fewer violations, block longer and forget
+/// more slowly. Level 2 matches the kernel fallback (`PenaltyConfig::DEFAULT`).
+pub const DEFAULT_LADDER: [PenaltyConfig; LEVELS] = [
+    PenaltyConfig::new((2, 5, 10), 2, (30, 600), 120),
+    PenaltyConfig::new((2, 5, 10), 2, (30, 600), 120),
+    PenaltyConfig::new((1, 3, 6), 4, (60, 3600), 300),
+    PenaltyConfig::new((1, 2, 4), 8, (300, 6 * 3600), 900),
+    PenaltyConfig::new((1, 2, 3), 16, (900, 24 * 3600), 3600),
+];
+
+/// Ladders of every protection level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PenaltyLadder {
+    pub levels: [PenaltyConfig; LEVELS],
+}
+
+impl Default for PenaltyLadder {
+    fn default() -> Self {
+        Self {
+            levels: DEFAULT_LADDER,
+        }
+    }
+}
+
+impl PenaltyLadder {
+    /// Defaults with the levels sent by the control plane replaced
+    pub fn from_proto(levels: &[PenaltyLevel]) -> Result<Self> {
+        let mut ladder = Self::default();
+        for level in levels {
+            let index = level.protection_level as usize;
+            if index >= LEVELS {
+                return Err(Error::validation(format!(
+                    "Penalty ladder for unknown protection level {}",
+                    level.protection_level
+                )));
+            }
+            ladder.levels[index] = PenaltyConfig::from_proto(level)?;
+        }
+        Ok(ladder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(protection_level: u32) -> PenaltyLevel {
+        PenaltyLevel {
+            protection_level,
+            rate_limit_threshold: 1,
+            rate_limit_divisor: 10,
+            short_block_threshold: 2,
+            short_block_seconds: 10,
+            long_block_threshold: 5,
+            long_block_seconds: 100,
+            decay_seconds: 60,
+        }
+    }
+
+    #[test]
+    fn test_default_ladder() {
+        for config in DEFAULT_LADDER {
+            assert!(config.rate_limit_divisor >= 1);
+            assert!(config.rate_limit_threshold <= config.short_block_threshold);
+            assert!(config.short_block_threshold <= config.long_block_threshold);
+            assert!(config.short_block_ns <= config.long_block_ns);
+        }
+    }
+
+    #[test]
+    fn test_from_proto_replaces_level() {
+        let ladder = PenaltyLadder::from_proto(&[level(3)]).unwrap();
+        assert_eq!(ladder.levels[3].rate_limit_divisor, 10);
+        assert_eq!(ladder.levels[3].long_block_ns, 100 * SECOND_NS);
+        assert_eq!(ladder.levels[2], DEFAULT_LADDER[2]);
+    }
+
+    #[test]
+    fn test_from_proto_rejects_invalid() {
+        assert!(PenaltyLadder::from_proto(&[level(5)]).is_err());
+
+        let mut unordered = level(1);
+        unordered.short_block_threshold = 6;
+        assert!(PenaltyLadder::from_proto(&[unordered]).is_err());
+
+        let mut no_divisor = level(1);
+        no_divisor.rate_limit_divisor = 0;
+        assert!(PenaltyLadder::from_proto(&[no_divisor]).is_err());
+
+        let mut no_block = level(1);
+        no_block.short_block_seconds = 0;
+        assert!(PenaltyLadder::from_proto(&[no_block]).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Kernel capability probing
+//!
+//! Detects the running kernel version and the BPF features the XDP programs
+//! rely on, and picks the program variant the kernel can load. Features are
+//! derived from the kernel version (the release that introduced each map
+//! type or helper) plus a check for kernel BTF; distribution kernels that
+//! backport features are reported conservatively.
+//!
+//! Programs are built in two variants (see the `ebpf` crate features):
+//!
+//! | Variant  | Frames               | Flow samples      | Kernel |
+//! |----------|----------------------|-------------------|--------|
+//! | `full`   | `xdp.frags`          | ring buffer       | 5.18+  |
+//! | `compat` | first buffer only    | perf event array  | 5.4+   |
+//!
+//! Both variants need LPM tries (4.11) for the threat intelligence maps of
+//! xdp_filter. Objects carry BTF; aya relocates them against the kernel BTF
+//! when `/sys/kernel/btf/vmlinux` exists (CO-RE).
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+
+/// Kernel BTF exported by kernels built with `CONFIG_DEBUG_INFO_BTF`
+const VMLINUX_BTF_PATH: &str = "/sys/kernel/btf/vmlinux";
+
+/// Running kernel version
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct KernelVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl KernelVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse a release string such as `6.1.0-18-amd64` or `5.15.0-generic`
+    pub fn parse(release: &str) -> Option<Self> {
+        let mut parts = release.split(['.', '-', '+', '_']);
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let patch = parts
+            .next()
+            .and_then(|p| {
+                let digits: String = p.chars().take_while(|c| c.is_ascii_digit()).collect();
+                digits.parse().ok()
+            })
+            .unwrap_or(0);
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for KernelVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// BPF features the programs depend on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BpfFeature {
+    Xdp,
+    PerCpuMaps,
+    LruHash,
+    LpmTrie,
+    XdpAdjustTail,
+    FibLookup,
+    GlobalData,
+    RingBuf,
+    BpfLoop,
+    XdpFrags,
+    Btf,
+}
+
+impl BpfFeature {
+    pub const ALL: [BpfFeature; 11] = [
+        BpfFeature::Xdp,
+        BpfFeature::PerCpuMaps,
+        BpfFeature::LruHash,
+        BpfFeature::LpmTrie,
+        BpfFeature::XdpAdjustTail,
+        BpfFeature::FibLookup,
+        BpfFeature::GlobalData,
+        BpfFeature::RingBuf,
+        BpfFeature::BpfLoop,
+        BpfFeature::XdpFrags,
+        BpfFeature::Btf,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BpfFeature::Xdp => "xdp",
+            BpfFeature::PerCpuMaps => "percpu_maps",
+            BpfFeature::LruHash => "lru_hash",
+            BpfFeature::LpmTrie => "lpm_trie",
+            BpfFeature::XdpAdjustTail => "xdp_adjust_tail",
+            BpfFeature::FibLookup => "fib_lookup",
+            BpfFeature::GlobalData => "global_data",
+            BpfFeature::RingBuf => "ringbuf",
+            BpfFeature::BpfLoop => "bpf_loop",
+            BpfFeature::XdpFrags => "xdp_frags",
+            BpfFeature::Btf => "btf",
+        }
+    }
+
+    /// First kernel release with the feature (`None` if not version-gated)
+    pub fn min_kernel(&self) -> Option<KernelVersion> {
+        let (major, minor) = match self {
+            BpfFeature::Xdp => (4, 8),
+            BpfFeature::PerCpuMaps => (4, 6),
+            BpfFeature::LruHash => (4, 10),
+            BpfFeature::LpmTrie => (4, 11),
+            BpfFeature::XdpAdjustTail => (4, 18),
+            BpfFeature::FibLookup => (4, 18),
+            BpfFeature::GlobalData => (5, 2),
+            BpfFeature::RingBuf => (5, 8),
+            BpfFeature::BpfLoop => (5, 17),
+            BpfFeature::XdpFrags => (5, 18),
+            BpfFeature::Btf => return None,
+        };
+        Some(KernelVersion::new(major, minor, 0))
+    }
+}
+
+impl fmt::Display for BpfFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Build of the XDP programs to load
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramVariant {
+    /// Multi-buffer frames and ring buffer flow sampling
+    Full,
+    /// Single-buffer frames and perf event flow sampling
+    Compat,
+}
+
+impl ProgramVariant {
+    /// Variants in order of preference
+    pub const ALL: [ProgramVariant; 2] = [ProgramVariant::Full, ProgramVariant::Compat];
+
+    /// Features every variant needs
+    pub const BASELINE: [BpfFeature; 4] = [
+        BpfFeature::Xdp,
+        BpfFeature::PerCpuMaps,
+        BpfFeature::LruHash,
+        BpfFeature::LpmTrie,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProgramVariant::Full => "full",
+            ProgramVariant::Compat => "compat",
+        }
+    }
+
+    /// Features the variant needs on top of the baseline
+    pub fn required_features(&self) -> &'static [BpfFeature] {
+        match self {
+            ProgramVariant::Full => &[BpfFeature::XdpFrags, BpfFeature::RingBuf],
+            ProgramVariant::Compat => &[],
+        }
+    }
+
+    /// Object file of a program version for this variant
+    ///
+    /// The full variant uses the plain `<name>-<version>.o`; other variants
+    /// add their name, e.g. `<name>-<version>.compat.o`.
+    pub fn object_file(&self, program: &str, version: &str) -> String {
+        match self {
+            ProgramVariant::Full => format!("{}-{}.o", program, version),
+            variant => format!("{}-{}.{}.o", program, version, variant.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for ProgramVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the running kernel supports
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KernelCapabilities {
+    pub release: String,
+    pub version: KernelVersion,
+    pub features: BTreeSet<BpfFeature>,
+}
+
+impl KernelCapabilities {
+    /// Probe the running kernel
+    pub fn probe() -> Self {
+        let release = sysinfo::System::kernel_version().unwrap_or_default();
+        let btf = Path::new(VMLINUX_BTF_PATH).exists();
+        Self::from_release(&release, btf)
+    }
+
+    /// Capabilities of a kernel release, given whether kernel BTF is present
+    pub fn from_release(release: &str, btf: bool) -> Self {
+        let version = KernelVersion::parse(release).unwrap_or_default();
+        let mut features: BTreeSet<BpfFeature> = BpfFeature::ALL
+            .into_iter()
+            .filter(|f| f.min_kernel().is_some_and(|min| version >= min))
+            .collect();
+        if btf {
+            features.insert(BpfFeature::Btf);
+        }
+
+        Self {
+            release: release.to_string(),
+            version,
+            features,
+        }
+    }
+
+    pub fn supports(&self, feature: BpfFeature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// Baseline features the kernel lacks
+    pub fn missing_baseline(&self) -> Vec<BpfFeature> {
+        ProgramVariant::BASELINE
+            .into_iter()
+            .filter(|f| !self.supports(*f))
+            .collect()
+    }
+
+    /// Most capable variant the kernel can load, if any
+    pub fn select_variant(&self) -> Option<ProgramVariant> {
+        if !self.missing_baseline().is_empty() {
+            return None;
+        }
+        ProgramVariant::ALL
+            .into_iter()
+            .find(|v| v.required_features().iter().all(|f| self.supports(*f)))
+    }
+
+    /// Feature names, sorted
+    pub fn feature_names(&self) -> Vec<String> {
+        self.features.iter().map(|f| f.to_string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kernel_version() {
+        assert_eq!(
+            KernelVersion::parse("6.1.0-18-amd64"),
+            Some(KernelVersion::new(6, 1, 0))
+        );
+        assert_eq!(
+            KernelVersion::parse("5.15.153-generic"),
+            Some(KernelVersion::new(5, 15, 153))
+        );
+        assert_eq!(
+            KernelVersion::parse("4.19"),
+            Some(KernelVersion::new(4, 19, 0))
+        );
+        assert_eq!(
+            KernelVersion::parse("6.8.0rc1"),
+            Some(KernelVersion::new(6, 8, 0))
+        );
+        assert_eq!(KernelVersion::parse("invalid"), None);
+    }
+
+    #[test]
+    fn test_features_follow_kernel_version() {
+        let caps = KernelCapabilities::from_release("5.10.0", false);
+        assert!(caps.supports(BpfFeature::LpmTrie));
+        assert!(caps.supports(BpfFeature::RingBuf));
+        assert!(!caps.supports(BpfFeature::BpfLoop));
+        assert!(!caps.supports(BpfFeature::XdpFrags));
+        assert!(!caps.supports(BpfFeature::Btf));
+
+        let caps = KernelCapabilities::from_release("6.1.0", true);
+        assert!(caps.supports(BpfFeature::XdpFrags));
+        assert!(caps.supports(BpfFeature::Btf));
+    }
+
+    #[test]
+    fn test_select_variant() {
+        let full = KernelCapabilities::from_release("6.1.0", true);
+        assert_eq!(full.select_variant(), Some(ProgramVariant::Full));
+
+        // Ring buffers arrived in 5.8, but xdp.frags only in 5.18
+        let compat = KernelCapabilities::from_release("5.15.0", true);
+        assert_eq!(compat.select_variant(), Some(ProgramVariant::Compat));
+        let compat = KernelCapabilities::from_release("5.4.0", false);
+        assert_eq!(compat.select_variant(), Some(ProgramVariant::Compat));
+
+        // LRU hash maps arrived in 4.10, LPM tries in 4.11
+        let old = KernelCapabilities::from_release("4.9.0", false);
+        assert_eq!(old.select_variant(), None);
+        assert_eq!(
+            old.missing_baseline(),
+            [BpfFeature::LruHash, BpfFeature::LpmTrie]
+        );
+        let old = KernelCapabilities::from_release("4.10.0", false);
+        assert_eq!(old.missing_baseline(), [BpfFeature::LpmTrie]);
+
+        let unknown = KernelCapabilities::from_release("", false);
+        assert_eq!(unknown.select_variant(), None);
+    }
+
+    #[test]
+    fn test_object_file() {
+        assert_eq!(
+            ProgramVariant::Full.object_file("xdp_filter", "0.2.0"),
+            "xdp_filter-0.2.0.o"
+        );
+        assert_eq!(
+            ProgramVariant::Compat.object_file("xdp_filter", "0.2.0"),
+            "xdp_filter-0.2.0.compat.o"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Protected ports
+//!
+//! xdp_tcp and xdp_udp keep the destination ports of protected services in
+//! `TCP_PROTECTED_PORTS` and `PROTECTED_PORTS`. The operator discovers them
+//! from the Kubernetes Services labeled for protection and sets them on the
+//! workers receiving their traffic through the admin API; the worker writes
+//! them to every loaded program and again whenever one is reloaded.
+
+use pistonprotection_common::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Capacity of each protected port map
+pub const MAX_PROTECTED_PORTS: usize = 1000;
+
+/// Value of the protected port map entries
+pub const PROTECTED: u32 = 1;
+
+/// Protected destination ports by protocol
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectedPorts {
+    #[serde(default)]
+    pub tcp: BTreeSet<u16>,
+    #[serde(default)]
+    pub udp: BTreeSet<u16>,
+}
+
+impl ProtectedPorts {
+    /// Check the ports fit the kernel maps
+    pub fn validate(&self) -> Result<()> {
+        for (protocol, ports) in [("TCP", &self.tcp), ("UDP", &self.udp)] {
+            if ports.contains(&0) {
+                return Err(Error::validation(format!(
+                    "Port 0 cannot be protected ({})",
+                    protocol
+                )));
+            }
+            if ports.len() > MAX_PROTECTED_PORTS {
+                return Err(Error::validation(format!(
+                    "{} {} ports exceed the limit of {}",
+                    ports.len(),
+                    protocol,
+                    MAX_PROTECTED_PORTS
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut ports = ProtectedPorts {
+            tcp: [25565, 443].into_iter().collect(),
+            udp: [19132].into_iter().collect(),
+        };
+        assert!(ports.validate().is_ok());
+
+        ports.udp.insert(0);
+        assert!(ports.validate().is_err());
+
+        let ports = ProtectedPorts {
+            tcp: (1..=MAX_PROTECTED_PORTS as u16 + 1).collect(),
+            udp: BTreeSet::new(),
+        };
+        assert!(ports.validate().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_missing_protocol() {
+        let ports: ProtectedPorts = serde_json::from_str(r#"{"tcp":[80,443]}"#).unwrap();
+        assert_eq!(ports.tcp.len(), 2);
+        assert!(ports.udp.is_empty());
+    }
+}
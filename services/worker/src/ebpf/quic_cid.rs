@@ -0,0 +1,113 @@
+//! Per-connection ID rate limits of HTTP/3 traffic
+//!
+//! Rate limiting QUIC per source address is weak behind CGNAT, where many
+//! clients share one. xdp_quic therefore also counts the short header
+//! packets of each destination connection ID and throttles a DCID over its
+//! ceiling for a while, leaving the other connections of the address alone.
+//! Short headers do not carry the DCID length, so it is configured to match
+//! the connection IDs the servers issue. This module mirrors the kernel
+//! layout of `QUIC_CID_LIMIT` and validates the ceilings pushed to it.
+
+use pistonprotection_common::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// Longest QUIC connection ID (RFC 9000 Section 17.2)
+pub const MAX_CID_LENGTH: u32 = 20;
+
+/// Value of `QUIC_CID_LIMIT` (mirrors `QuicCidLimit`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuicCidLimit {
+    /// Maximum short header packets per DCID per window (0 = off)
+    pub max_packets_per_window: u32,
+    /// DCID length of the servers' connection IDs (0 = 8 bytes)
+    #[serde(default)]
+    pub cid_len: u32,
+    /// Rate limit window in nanoseconds (0 = 1 second)
+    #[serde(default)]
+    pub window_ns: u64,
+    /// How long a DCID over the limit stays throttled in nanoseconds
+    /// (0 = 10 seconds)
+    #[serde(default)]
+    pub block_duration_ns: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u32` and two `u64` fields, no padding.
+unsafe impl aya::Pod for QuicCidLimit {}
+
+impl QuicCidLimit {
+    /// Load the limit from environment variables
+    ///
+    /// `PISTON_QUIC_CID_MAX_PACKETS` sets the packets per window and enables
+    /// the limit, `PISTON_QUIC_CID_LEN`, `PISTON_QUIC_CID_WINDOW_MS` and
+    /// `PISTON_QUIC_CID_BLOCK_MS` the DCID length, window and throttle time.
+    /// Invalid settings leave the limit off.
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok()?.parse::<u64>().ok();
+        let ms = |name: &str| var(name).map(|ms| ms.saturating_mul(1_000_000));
+
+        let limit = Self {
+            max_packets_per_window: var("PISTON_QUIC_CID_MAX_PACKETS")
+                .map_or(0, |max| max.min(u32::MAX as u64) as u32),
+            cid_len: var("PISTON_QUIC_CID_LEN").map_or(0, |len| len.min(u32::MAX as u64) as u32),
+            window_ns: ms("PISTON_QUIC_CID_WINDOW_MS").unwrap_or(0),
+            block_duration_ns: ms("PISTON_QUIC_CID_BLOCK_MS").unwrap_or(0),
+        };
+
+        match limit.validate() {
+            Ok(()) => limit,
+            Err(e) => {
+                tracing::warn!("Ignoring QUIC connection ID limit: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_packets_per_window != 0
+    }
+
+    /// Check the DCID length is one QUIC allows
+    pub fn validate(&self) -> Result<()> {
+        if self.cid_len > MAX_CID_LENGTH {
+            return Err(Error::validation(format!(
+                "Connection ID length {} exceeds {} bytes",
+                self.cid_len, MAX_CID_LENGTH
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let limit = QuicCidLimit {
+            max_packets_per_window: 500,
+            cid_len: 8,
+            ..Default::default()
+        };
+        assert!(limit.validate().is_ok());
+        assert!(limit.is_enabled());
+        assert!(!QuicCidLimit::default().is_enabled());
+
+        let limit = QuicCidLimit {
+            cid_len: 21,
+            ..limit
+        };
+        assert!(limit.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let limit: QuicCidLimit =
+            serde_json::from_str(r#"{"maxPacketsPerWindow": 200, "cidLen": 16}"#).unwrap();
+        assert_eq!(limit.max_packets_per_window, 200);
+        assert_eq!(limit.cid_len, 16);
+        assert_eq!(limit.window_ns, 0);
+    }
+}
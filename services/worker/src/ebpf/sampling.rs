@@ -0,0 +1,726 @@
+//! Flow sampling analysis
+//!
+//! Every XDP program copies the leading bytes of 1 in N passed packets into
+//! its `SAMPLES` ring buffer (perf event array in the compat variant). This module mirrors the kernel sample layout,
+//! classifies samples into a per-program protocol mix, extrapolates packet
+//! and byte counts from the sample rate, and records on-demand captures that
+//! can be exported as pcap.
+
+use parking_lot::{Mutex, RwLock};
+use pistonprotection_common::metrics::{
+    XDP_ESTIMATED_BYTES, XDP_ESTIMATED_PACKETS, XDP_SAMPLED_PACKETS,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default sampling rate (mirrors `sampling::DEFAULT_RATE`)
+pub const DEFAULT_RATE: u32 = 1024;
+
+/// Bytes of each packet copied by the kernel (mirrors `sampling::CAPTURE_BYTES`)
+pub const CAPTURE_BYTES: usize = 128;
+
+/// Size of the header of a perf event sample (mirrors `SampleHeader`)
+pub const PERF_HEADER_BYTES: usize = 24;
+
+/// Maximum number of (protocol, port) pairs tracked per program
+pub const MAX_TRACKED_PORTS: usize = 4096;
+
+/// Number of ports listed in a sampling report
+pub const TOP_PORTS: usize = 20;
+
+/// Upper bound on samples kept by a single capture
+pub const MAX_CAPTURE_SAMPLES: usize = 100_000;
+
+/// Sampling configuration (mirrors `SampleConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SampleConfig {
+    pub rate: u32,
+    pub _pad: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u32` fields, no padding.
+unsafe impl aya::Pod for SampleConfig {}
+
+/// Sampled packet headers (mirrors `PacketSample`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PacketSample {
+    pub timestamp_ns: u64,
+    pub packet_len: u32,
+    pub captured_len: u32,
+    pub rate: u32,
+    pub _pad: u32,
+    pub data: [u8; CAPTURE_BYTES],
+}
+
+// SAFETY: `#[repr(C)]` struct of integer fields and a byte array, no padding.
+unsafe impl aya::Pod for PacketSample {}
+
+impl PacketSample {
+    /// Decode a sample from a ring buffer record
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < std::mem::size_of::<Self>() {
+            return None;
+        }
+        // SAFETY: length checked above and every bit pattern is a valid `PacketSample`.
+        Some(unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Self) })
+    }
+
+    /// Decode a perf event record of the compat variant
+    ///
+    /// The record holds the header fields followed by the captured bytes the
+    /// kernel appended, padded to 8 bytes.
+    pub fn from_perf_record(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < PERF_HEADER_BYTES {
+            return None;
+        }
+        let u32_at = |offset: usize| {
+            u32::from_ne_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"))
+        };
+
+        let payload = &bytes[PERF_HEADER_BYTES..];
+        let captured = (u32_at(12) as usize).min(payload.len()).min(CAPTURE_BYTES);
+        let mut data = [0u8; CAPTURE_BYTES];
+        data[..captured].copy_from_slice(&payload[..captured]);
+
+        Some(Self {
+            timestamp_ns: u64::from_ne_bytes(bytes[..8].try_into().expect("8 bytes")),
+            packet_len: u32_at(8),
+            captured_len: captured as u32,
+            rate: u32_at(16),
+            _pad: 0,
+            data,
+        })
+    }
+
+    /// Captured leading bytes of the packet
+    pub fn captured(&self) -> &[u8] {
+        &self.data[..(self.captured_len as usize).min(CAPTURE_BYTES)]
+    }
+}
+
+/// Per-program sampling rates
+#[derive(Debug, Clone)]
+pub struct SamplingConfig {
+    /// Rate used by programs without an override (0 disables sampling)
+    pub default_rate: u32,
+    /// Per-program overrides, keyed by program name
+    pub program_rates: HashMap<String, u32>,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            default_rate: DEFAULT_RATE,
+            program_rates: HashMap::new(),
+        }
+    }
+}
+
+impl SamplingConfig {
+    /// Load sampling configuration from environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(rate) = std::env::var("PISTON_SAMPLE_RATE") {
+            if let Ok(rate) = rate.parse::<u32>() {
+                config.default_rate = rate;
+            }
+        }
+
+        // Per-program rates (format: xdp_http=256,xdp_udp=0)
+        if let Ok(rates) = std::env::var("PISTON_SAMPLE_RATES") {
+            for pair in rates.split(',') {
+                if let Some((program, rate)) = pair.split_once('=') {
+                    if let Ok(rate) = rate.trim().parse::<u32>() {
+                        config
+                            .program_rates
+                            .insert(program.trim().to_string(), rate);
+                    }
+                }
+            }
+        }
+
+        config
+    }
+
+    /// Sampling rate for a program
+    pub fn rate_for(&self, program: &str) -> u32 {
+        self.program_rates
+            .get(program)
+            .copied()
+            .unwrap_or(self.default_rate)
+    }
+}
+
+/// Transport-level classification of a sampled packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlowClass {
+    pub protocol: &'static str,
+    pub dst_port: Option<u16>,
+}
+
+/// Classify a sample by its Ethernet, IP and transport headers
+pub fn classify(data: &[u8]) -> FlowClass {
+    let unclassified = |protocol| FlowClass {
+        protocol,
+        dst_port: None,
+    };
+    let be16 = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+
+    let Some((ethertype, offset)) = network_header(data) else {
+        return unclassified("truncated");
+    };
+
+    let (ip_protocol, l4_offset, first_fragment) = match ethertype {
+        0x0800 => {
+            if data.len() < offset + 20 {
+                return unclassified("truncated");
+            }
+            let ihl = (data[offset] & 0x0f) as usize * 4;
+            let fragment_offset = be16(offset + 6) & 0x1fff;
+            (data[offset + 9], offset + ihl, fragment_offset == 0)
+        }
+        0x86dd => {
+            if data.len() < offset + 40 {
+                return unclassified("truncated");
+            }
+            let mut next = data[offset + 6];
+            let mut l4 = offset + 40;
+            let mut first_fragment = true;
+            // Walk a bounded number of extension headers
+            for _ in 0..4 {
+                match next {
+                    0 | 43 | 60 if data.len() >= l4 + 2 => {
+                        next = data[l4];
+                        l4 += (data[l4 + 1] as usize + 1) * 8;
+                    }
+                    44 if data.len() >= l4 + 8 => {
+                        first_fragment = be16(l4 + 2) & 0xfff8 == 0;
+                        next = data[l4];
+                        l4 += 8;
+                    }
+                    51 if data.len() >= l4 + 2 => {
+                        next = data[l4];
+                        l4 += (data[l4 + 1] as usize + 2) * 4;
+                    }
+                    _ => break,
+                }
+            }
+            (next, l4, first_fragment)
+        }
+        0x0806 => return unclassified("arp"),
+        _ => return unclassified("non_ip"),
+    };
+
+    let protocol = match ip_protocol {
+        1 => "icmp",
+        6 => "tcp",
+        17 => "udp",
+        47 => "gre",
+        50 => "esp",
+        58 => "icmpv6",
+        _ => "other",
+    };
+
+    let dst_port = match ip_protocol {
+        6 | 17 if first_fragment && data.len() >= l4_offset + 4 => Some(be16(l4_offset + 2)),
+        _ => None,
+    };
+
+    FlowClass { protocol, dst_port }
+}
+
+/// Source address of a sampled IPv4 or IPv6 packet
+pub fn source_addr(data: &[u8]) -> Option<IpAddr> {
+    let (ethertype, offset) = network_header(data)?;
+    match ethertype {
+        0x0800 if data.len() >= offset + 20 => {
+            let octets: [u8; 4] = data[offset + 12..offset + 16].try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        0x86dd if data.len() >= offset + 40 => {
+            let octets: [u8; 16] = data[offset + 8..offset + 24].try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        _ => None,
+    }
+}
+
+/// Ethertype and offset of the network header, after up to two VLAN tags
+/// (802.1Q / QinQ)
+fn network_header(data: &[u8]) -> Option<(u16, usize)> {
+    let be16 = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+
+    if data.len() < 14 {
+        return None;
+    }
+
+    let mut ethertype = be16(12);
+    let mut offset = 14;
+    for _ in 0..2 {
+        if ethertype != 0x8100 && ethertype != 0x88a8 {
+            break;
+        }
+        if data.len() < offset + 4 {
+            return None;
+        }
+        ethertype = be16(offset + 2);
+        offset += 4;
+    }
+    Some((ethertype, offset))
+}
+
+/// Sample counts and extrapolated totals for one bucket
+#[derive(Debug, Clone, Copy, Default)]
+struct Tally {
+    samples: u64,
+    estimated_packets: u64,
+    estimated_bytes: u64,
+}
+
+impl Tally {
+    fn add(&mut self, sample: &PacketSample) {
+        let rate = sample.rate.max(1) as u64;
+        self.samples += 1;
+        self.estimated_packets = self.estimated_packets.saturating_add(rate);
+        self.estimated_bytes = self
+            .estimated_bytes
+            .saturating_add(rate.saturating_mul(sample.packet_len as u64));
+    }
+}
+
+/// Accumulated samples for one program
+#[derive(Debug, Default)]
+struct ProgramSamples {
+    rate: u32,
+    total: Tally,
+    protocols: HashMap<&'static str, Tally>,
+    ports: HashMap<(&'static str, u16), Tally>,
+}
+
+/// Share of a program's estimated traffic for one protocol
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtocolShare {
+    pub protocol: &'static str,
+    pub samples: u64,
+    pub estimated_packets: u64,
+    pub estimated_bytes: u64,
+    /// Fraction of the program's estimated packets
+    pub share: f64,
+}
+
+/// Share of a program's estimated traffic for one destination port
+#[derive(Debug, Clone, Serialize)]
+pub struct PortShare {
+    pub protocol: &'static str,
+    pub port: u16,
+    pub samples: u64,
+    pub estimated_packets: u64,
+    pub estimated_bytes: u64,
+    /// Fraction of the program's estimated packets
+    pub share: f64,
+}
+
+/// Protocol mix of a program's passed traffic
+#[derive(Debug, Clone, Serialize)]
+pub struct SamplingReport {
+    pub program: String,
+    /// Most recent sampling rate seen
+    pub sample_rate: u32,
+    pub samples: u64,
+    pub estimated_packets: u64,
+    pub estimated_bytes: u64,
+    /// Protocols, highest estimated packet count first
+    pub protocols: Vec<ProtocolShare>,
+    /// Destination ports, highest estimated packet count first
+    pub top_ports: Vec<PortShare>,
+}
+
+/// A sample kept by a capture
+#[derive(Debug, Clone)]
+pub struct CapturedSample {
+    pub program: String,
+    pub received_at: SystemTime,
+    pub sample: PacketSample,
+}
+
+/// On-demand capture of raw samples
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// Only keep samples from this program (all programs if `None`)
+    pub program: Option<String>,
+    pub max_samples: usize,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub samples: Vec<CapturedSample>,
+}
+
+/// Capture progress
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStatus {
+    pub program: Option<String>,
+    pub max_samples: usize,
+    pub captured: usize,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Capture {
+    fn status(&self) -> CaptureStatus {
+        CaptureStatus {
+            program: self.program.clone(),
+            max_samples: self.max_samples,
+            captured: self.samples.len(),
+            started_at: self.started_at,
+        }
+    }
+
+    /// Encode the captured samples as a pcap file (Ethernet link type)
+    pub fn to_pcap(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(24 + self.samples.len() * (16 + CAPTURE_BYTES));
+
+        // Global header
+        out.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        out.extend_from_slice(&2u16.to_le_bytes());
+        out.extend_from_slice(&4u16.to_le_bytes());
+        out.extend_from_slice(&0i32.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(CAPTURE_BYTES as u32).to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+
+        for captured in &self.samples {
+            let timestamp = captured
+                .received_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let data = captured.sample.captured();
+
+            out.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+            out.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&captured.sample.packet_len.to_le_bytes());
+            out.extend_from_slice(data);
+        }
+
+        out
+    }
+}
+
+/// Worker-side analyzer for sampled packets
+#[derive(Default)]
+pub struct SampleAnalyzer {
+    programs: RwLock<HashMap<String, ProgramSamples>>,
+    capture: Mutex<Option<Capture>>,
+}
+
+impl SampleAnalyzer {
+    /// Create a new analyzer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account a batch of samples drained from a program's ring buffer
+    pub fn ingest(&self, program: &str, samples: &[PacketSample]) {
+        if samples.is_empty() {
+            return;
+        }
+
+        {
+            let mut programs = self.programs.write();
+            let state = programs.entry(program.to_string()).or_default();
+
+            for sample in samples {
+                let class = classify(sample.captured());
+                let rate = sample.rate.max(1) as f64;
+
+                state.rate = sample.rate;
+                state.total.add(sample);
+                state
+                    .protocols
+                    .entry(class.protocol)
+                    .or_default()
+                    .add(sample);
+
+                if let Some(port) = class.dst_port {
+                    let key = (class.protocol, port);
+                    if state.ports.len() < MAX_TRACKED_PORTS || state.ports.contains_key(&key) {
+                        state.ports.entry(key).or_default().add(sample);
+                    }
+                }
+
+                XDP_SAMPLED_PACKETS
+                    .with_label_values(&[program, class.protocol])
+                    .inc();
+                XDP_ESTIMATED_PACKETS
+                    .with_label_values(&[program, class.protocol])
+                    .inc_by(rate);
+                XDP_ESTIMATED_BYTES
+                    .with_label_values(&[program, class.protocol])
+                    .inc_by(rate * sample.packet_len as f64);
+            }
+        }
+
+        let mut capture = self.capture.lock();
+        if let Some(capture) = capture.as_mut() {
+            if capture.program.as_deref().is_none_or(|p| p == program) {
+                let received_at = SystemTime::now();
+                let room = capture.max_samples.saturating_sub(capture.samples.len());
+                capture
+                    .samples
+                    .extend(samples.iter().take(room).map(|sample| CapturedSample {
+                        program: program.to_string(),
+                        received_at,
+                        sample: *sample,
+                    }));
+            }
+        }
+    }
+
+    /// Protocol mix report for every program that produced samples
+    pub fn reports(&self) -> Vec<SamplingReport> {
+        let programs = self.programs.read();
+        let mut reports: Vec<_> = programs
+            .iter()
+            .map(|(program, state)| build_report(program, state))
+            .collect();
+        reports.sort_by(|a, b| a.program.cmp(&b.program));
+        reports
+    }
+
+    /// Protocol mix report for a single program
+    pub fn report(&self, program: &str) -> Option<SamplingReport> {
+        self.programs
+            .read()
+            .get(program)
+            .map(|state| build_report(program, state))
+    }
+
+    /// Forget accumulated samples for all programs
+    pub fn reset(&self) {
+        self.programs.write().clear();
+    }
+
+    /// Start capturing raw samples, replacing any capture in progress
+    pub fn start_capture(&self, program: Option<String>, max_samples: usize) -> CaptureStatus {
+        let capture = Capture {
+            program,
+            max_samples: max_samples.clamp(1, MAX_CAPTURE_SAMPLES),
+            started_at: chrono::Utc::now(),
+            samples: Vec::new(),
+        };
+        let status = capture.status();
+        *self.capture.lock() = Some(capture);
+        status
+    }
+
+    /// Progress of the capture in progress
+    pub fn capture_status(&self) -> Option<CaptureStatus> {
+        self.capture.lock().as_ref().map(Capture::status)
+    }
+
+    /// Stop the capture in progress and return what it collected
+    pub fn finish_capture(&self) -> Option<Capture> {
+        self.capture.lock().take()
+    }
+}
+
+fn build_report(program: &str, state: &ProgramSamples) -> SamplingReport {
+    let total = state.total.estimated_packets.max(1) as f64;
+
+    let mut protocols: Vec<_> = state
+        .protocols
+        .iter()
+        .map(|(protocol, tally)| ProtocolShare {
+            protocol,
+            samples: tally.samples,
+            estimated_packets: tally.estimated_packets,
+            estimated_bytes: tally.estimated_bytes,
+            share: tally.estimated_packets as f64 / total,
+        })
+        .collect();
+    protocols.sort_by(|a, b| {
+        b.estimated_packets
+            .cmp(&a.estimated_packets)
+            .then(a.protocol.cmp(b.protocol))
+    });
+
+    let mut top_ports: Vec<_> = state
+        .ports
+        .iter()
+        .map(|((protocol, port), tally)| PortShare {
+            protocol,
+            port: *port,
+            samples: tally.samples,
+            estimated_packets: tally.estimated_packets,
+            estimated_bytes: tally.estimated_bytes,
+            share: tally.estimated_packets as f64 / total,
+        })
+        .collect();
+    top_ports.sort_by(|a, b| {
+        b.estimated_packets
+            .cmp(&a.estimated_packets)
+            .then(a.port.cmp(&b.port))
+    });
+    top_ports.truncate(TOP_PORTS);
+
+    SamplingReport {
+        program: program.to_string(),
+        sample_rate: state.rate,
+        samples: state.total.samples,
+        estimated_packets: state.total.estimated_packets,
+        estimated_bytes: state.total.estimated_bytes,
+        protocols,
+        top_ports,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(packet: &[u8], rate: u32) -> PacketSample {
+        let mut data = [0u8; CAPTURE_BYTES];
+        let captured = packet.len().min(CAPTURE_BYTES);
+        data[..captured].copy_from_slice(&packet[..captured]);
+        PacketSample {
+            timestamp_ns: 0,
+            packet_len: packet.len() as u32,
+            captured_len: captured as u32,
+            rate,
+            _pad: 0,
+            data,
+        }
+    }
+
+    fn ipv4_packet(protocol: u8, dst_port: u16, vlan: bool) -> Vec<u8> {
+        let mut packet = vec![0u8; 12];
+        if vlan {
+            packet.extend_from_slice(&[0x81, 0x00, 0x00, 0x0a]);
+        }
+        packet.extend_from_slice(&[0x08, 0x00]);
+        let mut ip = [0u8; 20];
+        ip[0] = 0x45;
+        ip[9] = protocol;
+        packet.extend_from_slice(&ip);
+        packet.extend_from_slice(&40000u16.to_be_bytes());
+        packet.extend_from_slice(&dst_port.to_be_bytes());
+        packet.resize(packet.len() + 16, 0);
+        packet
+    }
+
+    #[test]
+    fn test_classify() {
+        let class = classify(&ipv4_packet(6, 443, false));
+        assert_eq!(class.protocol, "tcp");
+        assert_eq!(class.dst_port, Some(443));
+
+        let class = classify(&ipv4_packet(17, 53, true));
+        assert_eq!(class.protocol, "udp");
+        assert_eq!(class.dst_port, Some(53));
+
+        // IPv6 UDP behind a hop-by-hop options header
+        let mut packet = vec![0u8; 12];
+        packet.extend_from_slice(&[0x86, 0xdd]);
+        let mut ip6 = [0u8; 40];
+        ip6[0] = 0x60;
+        ip6[6] = 0; // hop-by-hop
+        packet.extend_from_slice(&ip6);
+        packet.extend_from_slice(&[17, 0, 0, 0, 0, 0, 0, 0]);
+        packet.extend_from_slice(&[0x9c, 0x40, 0x63, 0xdd]);
+        let class = classify(&packet);
+        assert_eq!(class.protocol, "udp");
+        assert_eq!(class.dst_port, Some(25565));
+
+        assert_eq!(classify(&[0u8; 10]).protocol, "truncated");
+    }
+
+    #[test]
+    fn test_source_addr() {
+        let mut packet = ipv4_packet(6, 443, true);
+        packet[18 + 12..18 + 16].copy_from_slice(&[198, 51, 100, 7]);
+        assert_eq!(source_addr(&packet), Some("198.51.100.7".parse().unwrap()));
+
+        let mut packet = vec![0u8; 12];
+        packet.extend_from_slice(&[0x86, 0xdd]);
+        let mut ip6 = [0u8; 40];
+        ip6[8..24].copy_from_slice(
+            &"2001:db8::1"
+                .parse::<std::net::Ipv6Addr>()
+                .unwrap()
+                .octets(),
+        );
+        packet.extend_from_slice(&ip6);
+        assert_eq!(source_addr(&packet), Some("2001:db8::1".parse().unwrap()));
+
+        assert_eq!(source_addr(&packet[..30]), None);
+    }
+
+    #[test]
+    fn test_perf_record() {
+        let packet = ipv4_packet(17, 53, false);
+        let mut record = Vec::new();
+        record.extend_from_slice(&7u64.to_ne_bytes());
+        record.extend_from_slice(&1500u32.to_ne_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_ne_bytes());
+        record.extend_from_slice(&64u32.to_ne_bytes());
+        record.extend_from_slice(&0u32.to_ne_bytes());
+        record.extend_from_slice(&packet);
+        record.resize(record.len().next_multiple_of(8), 0);
+
+        let sample = PacketSample::from_perf_record(&record).unwrap();
+        assert_eq!(sample.timestamp_ns, 7);
+        assert_eq!(sample.packet_len, 1500);
+        assert_eq!(sample.rate, 64);
+        assert_eq!(sample.captured(), &packet[..]);
+        assert_eq!(classify(sample.captured()).dst_port, Some(53));
+
+        // Truncated record: only the bytes present are kept
+        let sample = PacketSample::from_perf_record(&record[..PERF_HEADER_BYTES + 10]).unwrap();
+        assert_eq!(sample.captured(), &packet[..10]);
+
+        assert!(PacketSample::from_perf_record(&record[..10]).is_none());
+    }
+
+    #[test]
+    fn test_extrapolation() {
+        let analyzer = SampleAnalyzer::new();
+        let tcp = sample(&ipv4_packet(6, 443, false), 1024);
+        let udp = sample(&ipv4_packet(17, 53, false), 1024);
+        analyzer.ingest("xdp_test", &[tcp, tcp, tcp, udp]);
+
+        let report = analyzer.report("xdp_test").unwrap();
+        assert_eq!(report.samples, 4);
+        assert_eq!(report.estimated_packets, 4096);
+        assert_eq!(report.estimated_bytes, 1024 * 4 * tcp.packet_len as u64);
+        assert_eq!(report.protocols[0].protocol, "tcp");
+        assert_eq!(report.protocols[0].estimated_packets, 3072);
+        assert!((report.protocols[0].share - 0.75).abs() < f64::EPSILON);
+        assert_eq!(report.top_ports[0].port, 443);
+    }
+
+    #[test]
+    fn test_capture_pcap() {
+        let analyzer = SampleAnalyzer::new();
+        let packet = ipv4_packet(6, 80, false);
+        analyzer.start_capture(Some("xdp_http".to_string()), 2);
+
+        analyzer.ingest("xdp_udp", &[sample(&packet, 1)]);
+        analyzer.ingest("xdp_http", &[sample(&packet, 1); 3]);
+        assert_eq!(analyzer.capture_status().unwrap().captured, 2);
+
+        let pcap = analyzer.finish_capture().unwrap().to_pcap();
+        assert_eq!(&pcap[..4], &0xa1b2_c3d4u32.to_le_bytes());
+        assert_eq!(pcap.len(), 24 + 2 * (16 + packet.len()));
+        assert!(analyzer.capture_status().is_none());
+    }
+
+    #[test]
+    fn test_sampling_config_rates() {
+        let mut config = SamplingConfig::default();
+        config.program_rates.insert("xdp_udp".to_string(), 0);
+        assert_eq!(config.rate_for("xdp_udp"), 0);
+        assert_eq!(config.rate_for("xdp_tcp"), DEFAULT_RATE);
+    }
+}
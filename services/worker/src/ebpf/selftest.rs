@@ -0,0 +1,455 @@
+//! Self test
+//!
+//! Runs a battery of synthetic packets through the programs loaded on this
+//! node with `BPF_PROG_TEST_RUN` and checks their verdicts. A node whose
+//! filters are misconfigured (rate limits off, protection level too low, a
+//! program passing everything) is caught in production without sending
+//! real traffic.
+//!
+//! Test runs go through the live maps. Sources are taken from the
+//! benchmarking range 198.18.0.0/15 (RFC 2544), which no real client uses,
+//! and their verdicts show up in the drop counters like any other traffic.
+
+use chrono::{DateTime, Utc};
+use pistonprotection_common::error::Result;
+use serde::Serialize;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::time::Instant;
+
+/// `bpf(2)` command running a program on a test input
+const BPF_PROG_TEST_RUN: libc::c_int = 10;
+
+pub const XDP_ABORTED: u32 = 0;
+pub const XDP_DROP: u32 = 1;
+pub const XDP_PASS: u32 = 2;
+pub const XDP_TX: u32 = 3;
+pub const XDP_REDIRECT: u32 = 4;
+
+/// Destination of the checks when the request leaves it unset
+pub const DEFAULT_TARGET: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 0, 2, 1), 25565);
+
+/// SYNs sent by the flood check, several times the per-source burst
+pub const SYN_FLOOD_PACKETS: u32 = 5000;
+
+/// `test` member of `union bpf_attr`
+#[repr(C)]
+#[derive(Default)]
+struct TestRunAttr {
+    prog_fd: u32,
+    retval: u32,
+    data_size_in: u32,
+    data_size_out: u32,
+    data_in: u64,
+    data_out: u64,
+    repeat: u32,
+    duration: u32,
+    ctx_size_in: u32,
+    ctx_size_out: u32,
+    ctx_in: u64,
+    ctx_out: u64,
+    flags: u32,
+    cpu: u32,
+    batch_size: u32,
+    /// Keeps the trailing padding zeroed, as the kernel requires
+    _pad: u32,
+}
+
+/// Verdict of a test run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestRun {
+    /// XDP action returned by the last run
+    pub action: u32,
+    /// Average run time
+    pub duration_ns: u32,
+}
+
+/// Run a program `repeat` times on a packet
+pub fn test_run(program: BorrowedFd<'_>, packet: &[u8], repeat: u32) -> io::Result<TestRun> {
+    let mut attr = TestRunAttr {
+        prog_fd: program.as_raw_fd() as u32,
+        data_size_in: packet.len() as u32,
+        data_in: packet.as_ptr() as u64,
+        repeat,
+        ..Default::default()
+    };
+
+    // SAFETY: attr is a zeroed `bpf_attr` prefix valid for BPF_PROG_TEST_RUN,
+    // and the packet it points to outlives the call
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_PROG_TEST_RUN,
+            &mut attr as *mut TestRunAttr,
+            std::mem::size_of::<TestRunAttr>(),
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(TestRun {
+        action: attr.retval,
+        duration_ns: attr.duration,
+    })
+}
+
+/// Name of an XDP action
+pub fn action_name(action: u32) -> &'static str {
+    match action {
+        XDP_ABORTED => "aborted",
+        XDP_DROP => "drop",
+        XDP_PASS => "pass",
+        XDP_TX => "tx",
+        XDP_REDIRECT => "redirect",
+        _ => "unknown",
+    }
+}
+
+/// Verdict a check expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Expectation {
+    /// The packet reaches the backend (pass, tx or redirect)
+    Forward,
+    /// The packet is dropped
+    Drop,
+}
+
+impl Expectation {
+    fn met_by(self, action: u32) -> bool {
+        match self {
+            Expectation::Forward => matches!(action, XDP_PASS | XDP_TX | XDP_REDIRECT),
+            Expectation::Drop => action == XDP_DROP,
+        }
+    }
+}
+
+/// Synthetic packet and the verdict a program must return for it
+#[derive(Debug, Clone)]
+pub struct SelfTestCheck {
+    pub name: &'static str,
+    pub program: &'static str,
+    pub description: &'static str,
+    /// Ethernet frame
+    pub packet: Vec<u8>,
+    /// Runs of the packet back to back; the verdict of the last one counts
+    pub repeat: u32,
+    pub expect: Expectation,
+}
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// The program is not loaded on this node
+    Skipped,
+    /// The kernel could not run the program
+    Error,
+}
+
+/// Result of a check
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub program: &'static str,
+    pub description: &'static str,
+    pub status: CheckStatus,
+    pub expected: Expectation,
+    /// Verdict of the last run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<&'static str>,
+    pub packets: u32,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+/// Results of a self test
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestReport {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub target: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    /// Whether every check that ran met its expectation
+    pub fn success(&self) -> bool {
+        self.failed == 0 && self.errors == 0
+    }
+}
+
+/// Checks against a protected destination
+pub fn checks(target: SocketAddrV4) -> Vec<SelfTestCheck> {
+    let source = |last| Ipv4Addr::new(198, 18, 0, last);
+    let dst = *target.ip();
+    let port = target.port();
+
+    vec![
+        SelfTestCheck {
+            name: "clean_syn",
+            program: "xdp_filter",
+            description: "A single SYN from a new source is forwarded",
+            packet: tcp_packet(source(1), dst, port, TCP_SYN, &[]),
+            repeat: 1,
+            expect: Expectation::Forward,
+        },
+        SelfTestCheck {
+            name: "invalid_tcp_flags",
+            program: "xdp_filter",
+            description: "A segment with both SYN and RST set is dropped",
+            packet: tcp_packet(source(2), dst, port, TCP_SYN | TCP_RST, &[]),
+            repeat: 1,
+            expect: Expectation::Drop,
+        },
+        SelfTestCheck {
+            name: "syn_flood_burst",
+            program: "xdp_filter",
+            description: "A SYN burst from one source is rate limited",
+            packet: tcp_packet(source(3), dst, port, TCP_SYN, &[]),
+            repeat: SYN_FLOOD_PACKETS,
+            expect: Expectation::Drop,
+        },
+        SelfTestCheck {
+            name: "dns_amplification",
+            program: "xdp_udp",
+            description: "A large DNS response with many answers is dropped",
+            packet: udp_packet(source(4), dst, 53, port, &dns_amplification_response()),
+            repeat: 1,
+            expect: Expectation::Drop,
+        },
+        SelfTestCheck {
+            name: "malformed_minecraft_handshake",
+            program: "xdp_minecraft",
+            description: "A Minecraft handshake with an invalid length VarInt is dropped",
+            packet: tcp_packet(
+                source(5),
+                dst,
+                port,
+                TCP_PSH | TCP_ACK,
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00],
+            ),
+            repeat: 1,
+            expect: Expectation::Drop,
+        },
+    ]
+}
+
+/// Run checks with `test_run`, skipping those of programs not in `loaded`
+pub fn run<F>(
+    target: SocketAddrV4,
+    checks: Vec<SelfTestCheck>,
+    loaded: &[String],
+    mut test_run: F,
+) -> SelfTestReport
+where
+    F: FnMut(&SelfTestCheck) -> Result<TestRun>,
+{
+    let started_at = Utc::now();
+    let start = Instant::now();
+
+    let results: Vec<CheckResult> = checks
+        .into_iter()
+        .map(|check| {
+            let mut result = CheckResult {
+                name: check.name,
+                program: check.program,
+                description: check.description,
+                status: CheckStatus::Skipped,
+                expected: check.expect,
+                action: None,
+                packets: check.repeat,
+                detail: String::new(),
+            };
+            if !loaded.iter().any(|name| name == check.program) {
+                result.detail = format!("{} is not loaded", check.program);
+                return result;
+            }
+            match test_run(&check) {
+                Ok(run) => {
+                    result.action = Some(action_name(run.action));
+                    result.status = if check.expect.met_by(run.action) {
+                        CheckStatus::Passed
+                    } else {
+                        CheckStatus::Failed
+                    };
+                }
+                Err(e) => {
+                    result.status = CheckStatus::Error;
+                    result.detail = e.to_string();
+                }
+            }
+            result
+        })
+        .collect();
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    SelfTestReport {
+        started_at,
+        duration_ms: start.elapsed().as_millis() as u64,
+        target: target.to_string(),
+        passed: count(CheckStatus::Passed),
+        failed: count(CheckStatus::Failed),
+        skipped: count(CheckStatus::Skipped),
+        errors: count(CheckStatus::Error),
+        checks: results,
+    }
+}
+
+const ETH_P_IP: u16 = 0x0800;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// Source port of the synthetic packets
+const SOURCE_PORT: u16 = 40000;
+
+/// Ethernet frame carrying an IPv4 packet
+fn ipv4_frame(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, transport: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(34 + transport.len());
+    frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]);
+    frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x02]);
+    frame.extend_from_slice(&ETH_P_IP.to_be_bytes());
+
+    let mut ip = [0u8; 20];
+    ip[0] = 0x45;
+    ip[2..4].copy_from_slice(&((20 + transport.len()) as u16).to_be_bytes());
+    ip[6] = 0x40; // Don't fragment
+    ip[8] = 64;
+    ip[9] = protocol;
+    ip[12..16].copy_from_slice(&src.octets());
+    ip[16..20].copy_from_slice(&dst.octets());
+    let checksum = ipv4_checksum(&ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    frame.extend_from_slice(&ip);
+    frame.extend_from_slice(transport);
+    frame
+}
+
+fn tcp_packet(src: Ipv4Addr, dst: Ipv4Addr, port: u16, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let mut tcp = vec![0u8; 20];
+    tcp[0..2].copy_from_slice(&SOURCE_PORT.to_be_bytes());
+    tcp[2..4].copy_from_slice(&port.to_be_bytes());
+    tcp[4..8].copy_from_slice(&1u32.to_be_bytes());
+    tcp[12] = 5 << 4;
+    tcp[13] = flags;
+    tcp[14..16].copy_from_slice(&64240u16.to_be_bytes());
+    tcp.extend_from_slice(payload);
+    ipv4_frame(src, dst, IPPROTO_TCP, &tcp)
+}
+
+/// Ethernet frame carrying a UDP datagram
+pub fn udp_packet(
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    src_port: u16,
+    dst_port: u16,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut udp = vec![0u8; 8];
+    udp[0..2].copy_from_slice(&src_port.to_be_bytes());
+    udp[2..4].copy_from_slice(&dst_port.to_be_bytes());
+    udp[4..6].copy_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    udp.extend_from_slice(payload);
+    ipv4_frame(src, dst, IPPROTO_UDP, &udp)
+}
+
+/// DNS response with one question and many answers, padded past 1 KiB
+fn dns_amplification_response() -> Vec<u8> {
+    let mut dns = vec![0u8; 12];
+    dns[0..2].copy_from_slice(&0x1234u16.to_be_bytes());
+    dns[2..4].copy_from_slice(&0x8180u16.to_be_bytes()); // Response, recursion available
+    dns[4..6].copy_from_slice(&1u16.to_be_bytes());
+    dns[6..8].copy_from_slice(&40u16.to_be_bytes());
+    dns.resize(1200, 0);
+    dns
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pistonprotection_common::error::Error;
+
+    #[test]
+    fn test_packets_are_well_formed() {
+        for check in checks(DEFAULT_TARGET) {
+            let packet = &check.packet;
+            assert_eq!(u16::from_be_bytes([packet[12], packet[13]]), ETH_P_IP);
+            let ip = &packet[14..34];
+            assert_eq!(ipv4_checksum(ip), 0, "{}", check.name);
+            assert_eq!(
+                u16::from_be_bytes([ip[2], ip[3]]) as usize,
+                packet.len() - 14
+            );
+            assert_eq!(ip[12..14], [198, 18]);
+        }
+    }
+
+    #[test]
+    fn test_run_reports_each_check() {
+        let loaded = vec!["xdp_filter".to_string(), "xdp_udp".to_string()];
+        let report = run(DEFAULT_TARGET, checks(DEFAULT_TARGET), &loaded, |check| {
+            match check.name {
+                // Rate limiting is off on this node
+                "syn_flood_burst" => Ok(TestRun {
+                    action: XDP_PASS,
+                    duration_ns: 10,
+                }),
+                "dns_amplification" => Err(Error::Internal("EPERM".to_string())),
+                _ => Ok(TestRun {
+                    action: if check.expect == Expectation::Drop {
+                        XDP_DROP
+                    } else {
+                        XDP_PASS
+                    },
+                    duration_ns: 10,
+                }),
+            }
+        });
+
+        assert_eq!(report.passed, 2);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.skipped, 1);
+        assert!(!report.success());
+
+        let flood = report
+            .checks
+            .iter()
+            .find(|c| c.name == "syn_flood_burst")
+            .unwrap();
+        assert_eq!(flood.status, CheckStatus::Failed);
+        assert_eq!(flood.action, Some("pass"));
+        assert_eq!(flood.packets, SYN_FLOOD_PACKETS);
+
+        let minecraft = report
+            .checks
+            .iter()
+            .find(|c| c.program == "xdp_minecraft")
+            .unwrap();
+        assert_eq!(minecraft.status, CheckStatus::Skipped);
+    }
+}
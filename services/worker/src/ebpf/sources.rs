@@ -0,0 +1,243 @@
+//! Per-source traffic merged from per-CPU counters
+//!
+//! `xdp_filter` and `xdp_ratelimit` count every source in
+//! `SOURCE_COUNTERS_*` maps holding one value per CPU, so counting a packet
+//! never writes a cache line another CPU uses. This module mirrors the
+//! kernel counters, sums the CPUs of each source and turns successive merges
+//! into per-source rates.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Instant;
+
+/// Programs keeping per-CPU source counters
+pub const PROGRAMS: [&str; 2] = ["xdp_filter", "xdp_ratelimit"];
+
+/// Per-CPU source counter maps
+pub const MAP_V4: &str = "SOURCE_COUNTERS_V4";
+pub const MAP_V6: &str = "SOURCE_COUNTERS_V6";
+
+/// Traffic of one source on one CPU (mirrors `SourceCounters`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SourceCounters {
+    pub packets: u64,
+    pub bytes: u64,
+    pub dropped: u64,
+    pub last_seen_ns: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct made only of `u64` fields, no padding.
+unsafe impl aya::Pod for SourceCounters {}
+
+impl SourceCounters {
+    /// Sum the values of every CPU
+    pub fn sum(per_cpu: &[Self]) -> Self {
+        per_cpu.iter().fold(Self::default(), |total, value| Self {
+            packets: total.packets.wrapping_add(value.packets),
+            bytes: total.bytes.wrapping_add(value.bytes),
+            dropped: total.dropped.wrapping_add(value.dropped),
+            last_seen_ns: total.last_seen_ns.max(value.last_seen_ns),
+        })
+    }
+
+    /// Counter-wise difference from a previous merge, `None` if the source
+    /// was evicted and counted again from zero
+    fn delta_since(&self, previous: &Self) -> Option<Self> {
+        Some(Self {
+            packets: self.packets.checked_sub(previous.packets)?,
+            bytes: self.bytes.checked_sub(previous.bytes)?,
+            dropped: self.dropped.checked_sub(previous.dropped)?,
+            last_seen_ns: self.last_seen_ns,
+        })
+    }
+}
+
+/// Traffic of one source seen by one program
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SourceTraffic {
+    pub ip: IpAddr,
+    pub program: String,
+    /// Totals since the source was first counted
+    pub total: SourceCounters,
+    /// Packets per second since the previous merge
+    pub pps: f64,
+    /// Bytes per second since the previous merge
+    pub bps: f64,
+    /// Dropped packets per second since the previous merge
+    pub dropped_pps: f64,
+}
+
+/// Last merge of one program
+#[derive(Debug)]
+struct ProgramSources {
+    generation: u64,
+    merged_at: Instant,
+    totals: HashMap<IpAddr, SourceCounters>,
+    traffic: Vec<SourceTraffic>,
+}
+
+/// Merged source counters of every program
+#[derive(Debug, Default)]
+pub struct SourceLedger {
+    programs: HashMap<String, ProgramSources>,
+}
+
+impl SourceLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge a poll of a program's counter maps
+    ///
+    /// `entries` holds each source with its per-CPU values. Rates are
+    /// computed against the previous merge of the same program load; the
+    /// first merge after a (re)load only records totals.
+    pub fn merge(
+        &mut self,
+        program: &str,
+        generation: u64,
+        now: Instant,
+        entries: &[(IpAddr, Vec<SourceCounters>)],
+    ) {
+        let previous = self
+            .programs
+            .get(program)
+            .filter(|p| p.generation == generation);
+        let elapsed = previous
+            .map(|p| now.duration_since(p.merged_at).as_secs_f64())
+            .filter(|secs| *secs > 0.0);
+
+        let mut totals = HashMap::with_capacity(entries.len());
+        let mut traffic = Vec::with_capacity(entries.len());
+        for (ip, per_cpu) in entries {
+            let total = SourceCounters::sum(per_cpu);
+            let delta = match (previous, elapsed) {
+                (Some(previous), Some(_)) => match previous.totals.get(ip) {
+                    Some(before) => total.delta_since(before).unwrap_or(total),
+                    None => total,
+                },
+                _ => SourceCounters::default(),
+            };
+            let rate = |count: u64| elapsed.map_or(0.0, |secs| count as f64 / secs);
+
+            traffic.push(SourceTraffic {
+                ip: *ip,
+                program: program.to_string(),
+                total,
+                pps: rate(delta.packets),
+                bps: rate(delta.bytes),
+                dropped_pps: rate(delta.dropped),
+            });
+            totals.insert(*ip, total);
+        }
+
+        self.programs.insert(
+            program.to_string(),
+            ProgramSources {
+                generation,
+                merged_at: now,
+                totals,
+                traffic,
+            },
+        );
+    }
+
+    /// Sources sending the most packets per second, across programs
+    pub fn top(&self, limit: usize) -> Vec<SourceTraffic> {
+        let mut top: Vec<SourceTraffic> = self
+            .programs
+            .values()
+            .flat_map(|p| p.traffic.iter().cloned())
+            .collect();
+        top.sort_by(|a, b| {
+            b.pps
+                .total_cmp(&a.pps)
+                .then(b.total.packets.cmp(&a.total.packets))
+        });
+        top.truncate(limit);
+        top
+    }
+
+    /// Sources tracked per program
+    pub fn tracked(&self) -> Vec<(String, usize)> {
+        let mut tracked: Vec<_> = self
+            .programs
+            .iter()
+            .map(|(program, p)| (program.clone(), p.totals.len()))
+            .collect();
+        tracked.sort();
+        tracked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn counters(packets: u64, dropped: u64, last_seen_ns: u64) -> SourceCounters {
+        SourceCounters {
+            packets,
+            bytes: packets * 100,
+            dropped,
+            last_seen_ns,
+        }
+    }
+
+    #[test]
+    fn test_sum_per_cpu() {
+        let total = SourceCounters::sum(&[counters(3, 1, 50), counters(4, 0, 90)]);
+        assert_eq!(total.packets, 7);
+        assert_eq!(total.bytes, 700);
+        assert_eq!(total.dropped, 1);
+        assert_eq!(total.last_seen_ns, 90);
+    }
+
+    #[test]
+    fn test_merge_rates() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let other: IpAddr = "203.0.113.8".parse().unwrap();
+        let start = Instant::now();
+        let mut ledger = SourceLedger::new();
+
+        ledger.merge("xdp_filter", 1, start, &[(ip, vec![counters(10, 0, 1)])]);
+        // The first merge only records totals
+        assert_eq!(ledger.top(10)[0].pps, 0.0);
+
+        ledger.merge(
+            "xdp_filter",
+            1,
+            start + Duration::from_secs(10),
+            &[
+                (ip, vec![counters(60, 10, 2), counters(50, 0, 3)]),
+                (other, vec![counters(20, 0, 3)]),
+            ],
+        );
+        let top = ledger.top(10);
+        assert_eq!(top[0].ip, ip);
+        assert_eq!(top[0].pps, 10.0);
+        assert_eq!(top[0].dropped_pps, 1.0);
+        // New sources count from zero
+        assert_eq!(top[1].pps, 2.0);
+        assert_eq!(ledger.top(1).len(), 1);
+    }
+
+    #[test]
+    fn test_reload_resets_rates() {
+        let ip: IpAddr = "2001:db8::1".parse().unwrap();
+        let start = Instant::now();
+        let mut ledger = SourceLedger::new();
+
+        ledger.merge("xdp_ratelimit", 1, start, &[(ip, vec![counters(10, 0, 1)])]);
+        ledger.merge(
+            "xdp_ratelimit",
+            2,
+            start + Duration::from_secs(1),
+            &[(ip, vec![counters(5, 0, 2)])],
+        );
+        assert_eq!(ledger.top(1)[0].pps, 0.0);
+        assert_eq!(ledger.tracked(), vec![("xdp_ratelimit".to_string(), 1)]);
+    }
+}
@@ -0,0 +1,712 @@
+//! Per-CPU statistics aggregation for XDP programs
+//!
+//! Every XDP program keeps its counters in a single-entry `PerCpuArray`, so the
+//! kernel holds one monotonically increasing copy per CPU. This module mirrors
+//! the kernel-side stats structs, sums the per-CPU values, and turns successive
+//! polls into deltas and per-second rates.
+
+use serde::Serialize;
+use std::any::Any;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Counter struct read from an XDP program's per-CPU stats map
+pub trait ProgramStats: aya::Pod + Default + Send + Sync + 'static {
+    /// Name of the program object in the loader
+    const PROGRAM: &'static str;
+    /// Name of the `PerCpuArray` stats map inside the program
+    const MAP_NAME: &'static str;
+
+    /// All counters as `(name, value)` pairs, in struct order
+    fn counters(&self) -> Vec<(&'static str, u64)>;
+
+    /// Add another set of counters to this one (used to sum per-CPU values)
+    fn accumulate(&mut self, other: &Self);
+
+    /// Counter-wise difference from a previous reading.
+    ///
+    /// Returns `None` if any counter went backwards, which means the
+    /// underlying map was recreated and the baseline is no longer valid.
+    fn delta_since(&self, previous: &Self) -> Option<Self>;
+}
+
+/// Declare a userspace mirror of a kernel-side stats struct.
+///
+/// The field list must match the `#[repr(C)]` struct in the eBPF program
+/// exactly (same order, all `u64`).
+macro_rules! program_stats {
+    (
+        $(#[$meta:meta])*
+        $name:ident, program = $program:expr, map = $map:expr,
+        { $($field:ident),+ $(,)? }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+        pub struct $name {
+            $(pub $field: u64,)+
+        }
+
+        // SAFETY: `#[repr(C)]` struct made only of `u64` fields, so it has no
+        // padding and every bit pattern is valid.
+        unsafe impl aya::Pod for $name {}
+
+        impl ProgramStats for $name {
+            const PROGRAM: &'static str = $program;
+            const MAP_NAME: &'static str = $map;
+
+            fn counters(&self) -> Vec<(&'static str, u64)> {
+                vec![$((stringify!($field), self.$field)),+]
+            }
+
+            fn accumulate(&mut self, other: &Self) {
+                $(self.$field = self.$field.wrapping_add(other.$field);)+
+            }
+
+            fn delta_since(&self, previous: &Self) -> Option<Self> {
+                Some(Self {
+                    $($field: self.$field.checked_sub(previous.$field)?,)+
+                })
+            }
+        }
+    };
+}
+
+program_stats! {
+    /// Counters from `xdp_filter`
+    FilterStats, program = "xdp_filter", map = "STATS",
+    {
+        packets_total,
+        packets_passed,
+        packets_dropped,
+        packets_rate_limited,
+        bytes_total,
+    }
+}
+
+program_stats! {
+    /// Counters from `xdp_ratelimit`
+    RateLimitStats, program = "xdp_ratelimit", map = "RATELIMIT_STATS",
+    {
+        total_packets,
+        passed_packets,
+        dropped_packets,
+        limited_ips,
+    }
+}
+
+program_stats! {
+    /// Counters from `xdp_http`
+    HttpStats, program = "xdp_http", map = "HTTP_STATS",
+    {
+        total_requests,
+        passed_requests,
+        dropped_invalid_method,
+        dropped_rate_limited,
+        dropped_slow_loris,
+        dropped_invalid_request,
+        dropped_blocked_ip,
+        http2_requests,
+        dropped_slow_post,
+        dropped_http2_rapid_reset,
+        dropped_http2_control_flood,
+        http2_rst_stream_frames,
+        http2_headers_frames,
+        http2_data_frames,
+        dropped_request_smuggling,
+        dropped_header_injection,
+        doh_requests,
+        dropped_doh_rate_limited,
+    }
+}
+
+program_stats! {
+    /// Counters from `xdp_quic`
+    QuicStats, program = "xdp_quic", map = "QUIC_STATS",
+    {
+        total_packets,
+        passed_packets,
+        dropped_invalid_header,
+        dropped_invalid_version,
+        dropped_amplification,
+        dropped_rate_limited,
+        dropped_blocked_ip,
+        initial_packets,
+        handshake_packets,
+        short_header_packets,
+        dropped_0rtt,
+        dropped_cid_rate_limited,
+    }
+}
+
+program_stats! {
+    /// Counters from `xdp_udp`
+    UdpStats, program = "xdp_udp", map = "UDP_STATS",
+    {
+        total_packets,
+        passed_packets,
+        dropped_rate_limited,
+        dropped_invalid_size,
+        dropped_amplification,
+        dropped_port_scan,
+        dropped_blocked_ip,
+        dropped_blocked_port,
+        dropped_fragmented,
+        dns_packets,
+        ntp_packets,
+        ssdp_packets,
+        memcached_packets,
+        dropped_flow_rate,
+    }
+}
+
+program_stats! {
+    /// Counters from `xdp_tcp`
+    TcpStats, program = "xdp_tcp", map = "TCP_STATS",
+    {
+        total_packets,
+        passed_packets,
+        dropped_syn_flood,
+        dropped_ack_flood,
+        dropped_rst_flood,
+        dropped_invalid_flags,
+        dropped_blocked_ip,
+        dropped_connection_limit,
+        syn_cookies_issued,
+        syn_cookies_validated,
+        syn_cookies_failed,
+        window_probe_detected,
+        dropped_fragments,
+        dropped_invalid_ack,
+        dropped_handshake_timeout,
+        incomplete_handshakes_detected,
+        dropped_flow_rate,
+        dns_queries,
+        dropped_dns_rate_limited,
+    }
+}
+
+/// Rate of a single counter between two polls
+#[derive(Debug, Clone, Serialize)]
+pub struct CounterRate {
+    pub name: &'static str,
+    pub total: u64,
+    pub delta: u64,
+    pub per_second: f64,
+}
+
+/// Aggregated stats for one program at one poll
+#[derive(Debug, Clone)]
+pub struct StatsSnapshot<T> {
+    /// Program the counters were read from
+    pub program: &'static str,
+    /// Counters summed across all CPUs
+    pub totals: T,
+    /// Change since the previous poll (zero on the first poll)
+    pub delta: T,
+    /// Time elapsed since the previous poll (zero on the first poll)
+    pub interval: Duration,
+    /// Number of CPUs the values were summed over
+    pub cpus: usize,
+    /// Program load generation the counters belong to
+    pub generation: u64,
+    /// True if the counters were reset since the previous poll
+    pub reset_detected: bool,
+}
+
+impl<T: ProgramStats> StatsSnapshot<T> {
+    /// Per-second rate for every counter
+    pub fn rates(&self) -> Vec<CounterRate> {
+        let secs = self.interval.as_secs_f64();
+        self.totals
+            .counters()
+            .into_iter()
+            .zip(self.delta.counters())
+            .map(|((name, total), (_, delta))| CounterRate {
+                name,
+                total,
+                delta,
+                per_second: if secs > 0.0 { delta as f64 / secs } else { 0.0 },
+            })
+            .collect()
+    }
+
+    /// Per-second rate for a single counter
+    pub fn rate(&self, counter: &str) -> Option<f64> {
+        self.rates()
+            .into_iter()
+            .find(|r| r.name == counter)
+            .map(|r| r.per_second)
+    }
+}
+
+// ============================================================================
+// Drop Breakdowns
+// ============================================================================
+
+/// Maximum number of destination ports exported to Prometheus per program
+/// and poll, to keep label cardinality bounded.
+pub const MAX_EXPORTED_PORTS: usize = 50;
+
+/// Names of `BlockReason` values, indexed by discriminant
+pub const DROP_REASONS: &[&str] = &[
+    "manual",
+    "rate_limit",
+    "syn_flood",
+    "ack_flood",
+    "rst_flood",
+    "udp_flood",
+    "icmp_flood",
+    "dns_amplification",
+    "ntp_amplification",
+    "ssdp_amplification",
+    "memcached_amplification",
+    "invalid_protocol",
+    "port_scan",
+    "http_slow_attack",
+    "http_rate_limit",
+    "quic_amplification",
+    "invalid_quic_version",
+    "connection_limit",
+    "invalid_minecraft",
+    "minecraft_bot",
+    "generic_ddos",
+    "blocked_ip",
+    "fragmentation",
+    "threat_intel",
+    "reputation",
+    "honeypot",
+    "backend_blocked",
+    "flow_rate_limit",
+];
+
+/// Get the label for a `BlockReason` discriminant
+pub fn drop_reason_name(reason: u32) -> &'static str {
+    DROP_REASONS
+        .get(reason as usize)
+        .copied()
+        .unwrap_or("unknown")
+}
+
+/// Packet and byte counter for one breakdown bucket (mirrors `DropCounter`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DropCounter {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u64` fields, no padding.
+unsafe impl aya::Pod for DropCounter {}
+
+impl DropCounter {
+    fn add(&mut self, other: &Self) {
+        self.packets = self.packets.wrapping_add(other.packets);
+        self.bytes = self.bytes.wrapping_add(other.bytes);
+    }
+
+    /// Difference from a previous reading, or `self` if the counter was reset
+    fn delta_since(&self, previous: &Self) -> Self {
+        match (
+            self.packets.checked_sub(previous.packets),
+            self.bytes.checked_sub(previous.bytes),
+        ) {
+            (Some(packets), Some(bytes)) => Self { packets, bytes },
+            _ => *self,
+        }
+    }
+}
+
+/// Canary counters for one source address (mirrors `CanaryEntry`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CanaryEntry {
+    pub verdict: u32,
+    pub _pad: u32,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for CanaryEntry {}
+
+/// Decode a `DROPS_BY_DST_PORT` key into `(protocol, port)`
+pub fn decode_dst_port_key(key: u32) -> (u8, u16) {
+    ((key >> 16) as u8, key as u16)
+}
+
+/// Drops towards one destination port
+#[derive(Debug, Clone, Serialize)]
+pub struct PortDrops {
+    pub protocol: u8,
+    pub port: u16,
+    pub total: DropCounter,
+    pub delta: DropCounter,
+}
+
+/// Drops for one block reason
+#[derive(Debug, Clone, Serialize)]
+pub struct ReasonDrops {
+    pub reason: &'static str,
+    pub total: DropCounter,
+    pub delta: DropCounter,
+}
+
+/// Per-port and per-reason drop breakdown for one program
+#[derive(Debug, Clone, Serialize)]
+pub struct DropBreakdown {
+    pub program: String,
+    /// Ports sorted by packets dropped since the previous poll, highest first
+    pub by_dst_port: Vec<PortDrops>,
+    /// Reasons with at least one recorded drop
+    pub by_reason: Vec<ReasonDrops>,
+}
+
+impl DropBreakdown {
+    /// Increment the Prometheus breakdown counters by this poll's deltas
+    pub fn export_metrics(&self) {
+        use pistonprotection_common::metrics::{XDP_DROPS_BY_DST_PORT, XDP_DROPS_BY_REASON};
+
+        for entry in self.by_dst_port.iter().take(MAX_EXPORTED_PORTS) {
+            if entry.delta.packets == 0 {
+                continue;
+            }
+            let protocol = match entry.protocol {
+                6 => "tcp".to_string(),
+                17 => "udp".to_string(),
+                other => other.to_string(),
+            };
+            XDP_DROPS_BY_DST_PORT
+                .with_label_values(&[&self.program, &protocol, &entry.port.to_string()])
+                .inc_by(entry.delta.packets as f64);
+        }
+
+        for entry in &self.by_reason {
+            if entry.delta.packets == 0 {
+                continue;
+            }
+            XDP_DROPS_BY_REASON
+                .with_label_values(&[self.program.as_str(), entry.reason])
+                .inc_by(entry.delta.packets as f64);
+        }
+    }
+}
+
+/// Previous breakdown poll for a program
+struct BreakdownBaseline {
+    generation: u64,
+    ports: HashMap<u32, DropCounter>,
+    reasons: Vec<DropCounter>,
+}
+
+// ============================================================================
+// Reader
+// ============================================================================
+
+/// Previous poll kept as the baseline for the next delta
+struct Baseline {
+    totals: Box<dyn Any + Send + Sync>,
+    generation: u64,
+    at: Instant,
+}
+
+/// Sums per-CPU stats and tracks deltas between polls
+#[derive(Default)]
+pub struct StatsReader {
+    baselines: HashMap<&'static str, Baseline>,
+    breakdown_baselines: HashMap<String, BreakdownBaseline>,
+}
+
+impl StatsReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sum per-CPU values into a single set of counters
+    pub fn sum_per_cpu<T: ProgramStats>(per_cpu: &[T]) -> T {
+        let mut total = T::default();
+        for value in per_cpu {
+            total.accumulate(value);
+        }
+        total
+    }
+
+    /// Record a new poll of per-CPU values and compute the snapshot.
+    ///
+    /// `generation` must change whenever the program is reloaded. A new
+    /// generation, or any counter going backwards, is treated as a reset: the
+    /// delta is the full new total since the counters restarted from zero.
+    pub fn observe<T: ProgramStats>(
+        &mut self,
+        per_cpu: &[T],
+        generation: u64,
+        now: Instant,
+    ) -> StatsSnapshot<T> {
+        let totals = Self::sum_per_cpu(per_cpu);
+
+        let (delta, interval, reset_detected) = match self.baselines.get(T::PROGRAM) {
+            Some(baseline) => {
+                let interval = now.saturating_duration_since(baseline.at);
+                let previous = baseline.totals.downcast_ref::<T>();
+                match previous {
+                    Some(previous) if baseline.generation == generation => {
+                        match totals.delta_since(previous) {
+                            Some(delta) => (delta, interval, false),
+                            None => (totals, interval, true),
+                        }
+                    }
+                    _ => (totals, interval, true),
+                }
+            }
+            None => (T::default(), Duration::ZERO, false),
+        };
+
+        self.baselines.insert(
+            T::PROGRAM,
+            Baseline {
+                totals: Box::new(totals),
+                generation,
+                at: now,
+            },
+        );
+
+        StatsSnapshot {
+            program: T::PROGRAM,
+            totals,
+            delta,
+            interval,
+            cpus: per_cpu.len(),
+            generation,
+            reset_detected,
+        }
+    }
+
+    /// Record a new poll of the breakdown maps and compute per-bucket deltas.
+    ///
+    /// `ports` holds `(key, per-CPU values)` pairs from `DROPS_BY_DST_PORT`;
+    /// `reasons` holds per-CPU values for each `DROPS_BY_REASON` index. Ports
+    /// that were evicted from the LRU map and reinserted count as a reset.
+    pub fn observe_breakdown(
+        &mut self,
+        program: &str,
+        generation: u64,
+        ports: &[(u32, Vec<DropCounter>)],
+        reasons: &[Vec<DropCounter>],
+    ) -> DropBreakdown {
+        let sum = |values: &[DropCounter]| {
+            let mut total = DropCounter::default();
+            for value in values {
+                total.add(value);
+            }
+            total
+        };
+
+        let port_totals: HashMap<u32, DropCounter> =
+            ports.iter().map(|(key, v)| (*key, sum(v))).collect();
+        let reason_totals: Vec<DropCounter> = reasons.iter().map(|v| sum(v)).collect();
+
+        let baseline = self
+            .breakdown_baselines
+            .get(program)
+            .filter(|b| b.generation == generation);
+        let first_poll = !self.breakdown_baselines.contains_key(program);
+
+        let delta_for = |total: &DropCounter, previous: Option<&DropCounter>| {
+            if first_poll {
+                return DropCounter::default();
+            }
+            match previous {
+                Some(previous) => total.delta_since(previous),
+                None => *total,
+            }
+        };
+
+        let mut by_dst_port: Vec<PortDrops> = port_totals
+            .iter()
+            .map(|(key, total)| {
+                let (protocol, port) = decode_dst_port_key(*key);
+                PortDrops {
+                    protocol,
+                    port,
+                    total: *total,
+                    delta: delta_for(total, baseline.and_then(|b| b.ports.get(key))),
+                }
+            })
+            .collect();
+        by_dst_port.sort_by(|a, b| {
+            b.delta
+                .packets
+                .cmp(&a.delta.packets)
+                .then(b.total.packets.cmp(&a.total.packets))
+        });
+
+        let by_reason = reason_totals
+            .iter()
+            .enumerate()
+            .filter(|(_, total)| total.packets > 0)
+            .map(|(index, total)| ReasonDrops {
+                reason: drop_reason_name(index as u32),
+                total: *total,
+                delta: delta_for(total, baseline.and_then(|b| b.reasons.get(index))),
+            })
+            .collect();
+
+        self.breakdown_baselines.insert(
+            program.to_string(),
+            BreakdownBaseline {
+                generation,
+                ports: port_totals,
+                reasons: reason_totals,
+            },
+        );
+
+        DropBreakdown {
+            program: program.to_string(),
+            by_dst_port,
+            by_reason,
+        }
+    }
+
+    /// Forget the baseline for a program (e.g. when it is unloaded)
+    pub fn forget(&mut self, program: &str) {
+        self.baselines.remove(program);
+        self.breakdown_baselines.remove(program);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tcp(total: u64, passed: u64) -> TcpStats {
+        TcpStats {
+            total_packets: total,
+            passed_packets: passed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sum_per_cpu() {
+        let total = StatsReader::sum_per_cpu(&[tcp(10, 8), tcp(5, 5), tcp(0, 0)]);
+        assert_eq!(total.total_packets, 15);
+        assert_eq!(total.passed_packets, 13);
+    }
+
+    #[test]
+    fn test_delta_and_rate() {
+        let mut reader = StatsReader::new();
+        let start = Instant::now();
+
+        let first = reader.observe(&[tcp(100, 90), tcp(100, 90)], 1, start);
+        assert_eq!(first.totals.total_packets, 200);
+        assert_eq!(first.delta.total_packets, 0);
+        assert_eq!(first.cpus, 2);
+
+        let second = reader.observe(
+            &[tcp(150, 120), tcp(250, 200)],
+            1,
+            start + Duration::from_secs(2),
+        );
+        assert!(!second.reset_detected);
+        assert_eq!(second.delta.total_packets, 200);
+        assert_eq!(second.delta.passed_packets, 140);
+        assert_eq!(second.rate("total_packets"), Some(100.0));
+        assert_eq!(second.rate("passed_packets"), Some(70.0));
+        assert_eq!(second.rate("unknown"), None);
+    }
+
+    #[test]
+    fn test_reset_on_reload() {
+        let mut reader = StatsReader::new();
+        let start = Instant::now();
+
+        reader.observe(&[tcp(1000, 900)], 1, start);
+        let after_reload = reader.observe(&[tcp(30, 30)], 2, start + Duration::from_secs(1));
+
+        assert!(after_reload.reset_detected);
+        assert_eq!(after_reload.delta.total_packets, 30);
+        assert_eq!(after_reload.generation, 2);
+    }
+
+    #[test]
+    fn test_reset_on_counter_decrease() {
+        let mut reader = StatsReader::new();
+        let start = Instant::now();
+
+        reader.observe(&[tcp(1000, 900)], 1, start);
+        let snapshot = reader.observe(&[tcp(10, 10)], 1, start + Duration::from_secs(1));
+
+        assert!(snapshot.reset_detected);
+        assert_eq!(snapshot.delta.total_packets, 10);
+    }
+
+    #[test]
+    fn test_breakdown_deltas() {
+        let mut reader = StatsReader::new();
+        let counter = |packets, bytes| DropCounter { packets, bytes };
+        let tcp_80 = (6u32 << 16) | 80;
+        let udp_53 = (17u32 << 16) | 53;
+
+        let first = reader.observe_breakdown(
+            "xdp_tcp",
+            1,
+            &[(tcp_80, vec![counter(10, 600), counter(5, 300)])],
+            &[vec![], vec![counter(15, 900)]],
+        );
+        assert_eq!(first.by_dst_port[0].total.packets, 15);
+        assert_eq!(first.by_dst_port[0].delta.packets, 0);
+
+        let second = reader.observe_breakdown(
+            "xdp_tcp",
+            1,
+            &[
+                (tcp_80, vec![counter(12, 720), counter(5, 300)]),
+                (udp_53, vec![counter(40, 4000)]),
+            ],
+            &[vec![], vec![counter(57, 5020)]],
+        );
+
+        // Newly seen port counts fully, and sorts first by delta
+        assert_eq!(second.by_dst_port[0].protocol, 17);
+        assert_eq!(second.by_dst_port[0].port, 53);
+        assert_eq!(second.by_dst_port[0].delta.packets, 40);
+        assert_eq!(second.by_dst_port[1].port, 80);
+        assert_eq!(second.by_dst_port[1].delta, counter(2, 120));
+
+        assert_eq!(second.by_reason.len(), 1);
+        assert_eq!(second.by_reason[0].reason, "rate_limit");
+        assert_eq!(second.by_reason[0].delta.packets, 42);
+    }
+
+    #[test]
+    fn test_drop_reason_names() {
+        assert_eq!(drop_reason_name(0), "manual");
+        assert_eq!(drop_reason_name(21), "blocked_ip");
+        assert_eq!(drop_reason_name(23), "threat_intel");
+        assert_eq!(drop_reason_name(24), "reputation");
+        assert_eq!(drop_reason_name(25), "honeypot");
+        assert_eq!(drop_reason_name(27), "flow_rate_limit");
+        assert_eq!(drop_reason_name(99), "unknown");
+        assert_eq!(decode_dst_port_key((6 << 16) | 25565), (6, 25565));
+    }
+
+    #[test]
+    fn test_programs_tracked_independently() {
+        let mut reader = StatsReader::new();
+        let start = Instant::now();
+
+        reader.observe(&[tcp(10, 10)], 1, start);
+        let udp = reader.observe(
+            &[UdpStats {
+                total_packets: 5,
+                ..Default::default()
+            }],
+            1,
+            start,
+        );
+        assert_eq!(udp.program, "xdp_udp");
+        assert_eq!(udp.delta.total_packets, 0);
+        assert!(!udp.reset_detected);
+    }
+}
@@ -0,0 +1,270 @@
+//! Per-organization map namespaces
+//!
+//! Backends owned by different organizations share one worker. xdp_filter
+//! resolves the destination of each packet to a tenant id and keys its
+//! blocklist and rate limit lookups by that id, so a block or limit set for
+//! one organization never affects another. This module mirrors the kernel
+//! key layouts and holds the userspace state of each namespace.
+
+use super::learning::addr_key;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
+
+/// Tenant id of traffic that matches no registered backend (mirrors `tenant::GLOBAL`)
+pub const GLOBAL_TENANT: u32 = 0;
+
+/// Per-source packets per second of namespaces without a limit (mirrors
+/// `tenant::DEFAULT_PPS_LIMIT`)
+pub const DEFAULT_PPS_LIMIT: u64 = 1000;
+
+/// Block reason written for tenant blocks (mirrors `BlockReason::Manual`)
+pub const REASON_MANUAL: u32 = 0;
+
+/// Destination key of `TENANT_DESTINATIONS_V4` (mirrors `TenantDstV4Key`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TenantDstV4Key {
+    pub addr: u32,
+    pub port: u16,
+    pub _pad: u16,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for TenantDstV4Key {}
+
+/// Destination key of `TENANT_DESTINATIONS_V6` (mirrors `TenantDstV6Key`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TenantDstV6Key {
+    pub addr: [u8; 16],
+    pub port: u16,
+    pub _pad: u16,
+}
+
+// SAFETY: `#[repr(C)]` struct of a byte array and explicit padding, no implicit padding.
+unsafe impl aya::Pod for TenantDstV6Key {}
+
+/// Source key of the tenant IPv4 maps (mirrors `TenantIpV4Key`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TenantIpV4Key {
+    pub tenant_id: u32,
+    pub addr: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u32` fields, no padding.
+unsafe impl aya::Pod for TenantIpV4Key {}
+
+/// Source key of the tenant IPv6 maps (mirrors `TenantIpV6Key`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TenantIpV6Key {
+    pub tenant_id: u32,
+    pub addr: [u8; 16],
+}
+
+// SAFETY: `#[repr(C)]` struct of a `u32` and a byte array, no padding.
+unsafe impl aya::Pod for TenantIpV6Key {}
+
+/// Value of `TENANT_CONFIG` (mirrors `TenantConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TenantConfig {
+    pub protection_level: u32,
+    pub _pad: u32,
+    pub per_ip_pps_limit: u64,
+    pub per_ip_burst: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct with explicit padding, no implicit padding.
+unsafe impl aya::Pod for TenantConfig {}
+
+/// Limits applied to every source within a namespace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TenantLimits {
+    pub protection_level: u8,
+    /// Per-source packets per second (0 = kernel default)
+    pub per_ip_pps: u64,
+    /// Per-source bucket size (0 = one second of `per_ip_pps`)
+    pub per_ip_burst: u64,
+}
+
+/// Per-source limits of an active rate limit profile, replacing those of
+/// the protection settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProfileLimits {
+    pub per_ip_pps: u64,
+    pub per_ip_burst: u64,
+}
+
+/// Backend destination routed to a namespace (port 0 matches any port)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TenantDestination {
+    pub addr: IpAddr,
+    pub port: u16,
+}
+
+/// Build the contents of a map keyed by backend destination
+///
+/// `entry` gives the destinations of a backend and the value stored for each
+/// of them, or `None` to skip the backend. Backends are visited in id order,
+/// so when two claim the same destination the lowest id keeps it whatever
+/// the hash order. Addresses are IPv4-mapped and at most `max` destinations
+/// are kept, lowest first.
+pub fn destination_entries<'a, B, T: Clone>(
+    backends: &'a HashMap<String, B>,
+    max: usize,
+    mut entry: impl FnMut(&'a String, &'a B) -> Option<(&'a [TenantDestination], T)>,
+) -> Vec<(TenantDstV6Key, T)> {
+    let ordered: BTreeMap<&String, &B> = backends.iter().collect();
+    let mut entries = BTreeMap::new();
+
+    for (backend_id, backend) in ordered {
+        let Some((destinations, value)) = entry(backend_id, backend) else {
+            continue;
+        };
+        for destination in destinations {
+            entries
+                .entry((addr_key(destination.addr), destination.port))
+                .or_insert_with(|| value.clone());
+        }
+    }
+
+    entries
+        .into_iter()
+        .take(max)
+        .map(|((addr, port), value)| {
+            let key = TenantDstV6Key {
+                addr,
+                port,
+                _pad: 0,
+            };
+            (key, value)
+        })
+        .collect()
+}
+
+/// Tenant-scoped blocked source
+#[derive(Debug, Clone)]
+pub struct TenantBlock {
+    pub reason: String,
+    pub blocked_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TenantBlock {
+    /// Whether the block has passed its expiry
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| now > expires_at)
+    }
+}
+
+/// Map namespace of one organization
+#[derive(Debug, Clone)]
+pub struct TenantNamespace {
+    pub tenant_id: u32,
+    pub organization_id: String,
+    /// Backends of the organization on this worker
+    pub backends: HashSet<String>,
+    /// Destinations per backend
+    pub destinations: HashMap<String, Vec<TenantDestination>>,
+    pub blocked_ips: HashMap<IpAddr, TenantBlock>,
+    pub limits: TenantLimits,
+}
+
+impl TenantNamespace {
+    pub fn new(tenant_id: u32, organization_id: &str) -> Self {
+        Self {
+            tenant_id,
+            organization_id: organization_id.to_string(),
+            backends: HashSet::new(),
+            destinations: HashMap::new(),
+            blocked_ips: HashMap::new(),
+            limits: TenantLimits::default(),
+        }
+    }
+}
+
+/// Contents of the xdp_filter tenant maps
+#[derive(Debug, Clone, Default)]
+pub struct TenantMapEntries {
+    pub destinations: Vec<(TenantDestination, u32)>,
+    pub configs: Vec<(u32, TenantConfig)>,
+    pub blocked: Vec<(u32, IpAddr)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ebpf::learning::key_addr;
+
+    fn destinations(addrs: &[&str], port: u16) -> Vec<TenantDestination> {
+        addrs
+            .iter()
+            .map(|addr| TenantDestination {
+                addr: addr.parse().unwrap(),
+                port,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_destination_entries() {
+        let mut backends = HashMap::new();
+        backends.insert(
+            "a".to_string(),
+            destinations(&["192.0.2.10", "2001:db8::10"], 25565),
+        );
+        backends.insert("b".to_string(), destinations(&["192.0.2.11"], 0));
+        backends.insert("c".to_string(), destinations(&["192.0.2.12"], 0));
+
+        let entries = destination_entries(&backends, 16, |backend_id, destinations| {
+            (backend_id != "c").then(|| (destinations.as_slice(), backend_id.clone()))
+        });
+        let entries: Vec<(IpAddr, u16, &str)> = entries
+            .iter()
+            .map(|(key, backend_id)| (key_addr(key.addr), key.port, backend_id.as_str()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                ("192.0.2.10".parse().unwrap(), 25565, "a"),
+                ("192.0.2.11".parse().unwrap(), 0, "b"),
+                ("2001:db8::10".parse().unwrap(), 25565, "a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_destination_entries_shared_destination() {
+        // The backend with the lowest id keeps a destination claimed twice,
+        // whatever order the backends were inserted in
+        for order in [["z", "a"], ["a", "z"]] {
+            let mut backends = HashMap::new();
+            for backend_id in order {
+                backends.insert(backend_id.to_string(), destinations(&["192.0.2.10"], 0));
+            }
+
+            let entries = destination_entries(&backends, 16, |backend_id, destinations| {
+                Some((destinations.as_slice(), backend_id.clone()))
+            });
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].1, "a");
+        }
+    }
+
+    #[test]
+    fn test_destination_entries_bounded() {
+        let mut backends = HashMap::new();
+        backends.insert(
+            "a".to_string(),
+            destinations(&["192.0.2.12", "192.0.2.10", "192.0.2.11"], 0),
+        );
+
+        let entries = destination_entries(&backends, 2, |_, destinations| {
+            Some((destinations.as_slice(), ()))
+        });
+        let last: Vec<u8> = entries.iter().map(|(key, _)| key.addr[15]).collect();
+        assert_eq!(last, [10, 11]);
+    }
+}
@@ -0,0 +1,1084 @@
+//! Threat intelligence feeds
+//!
+//! Periodically imports third-party blocklists (Spamhaus DROP, FireHOL
+//! netsets, the AbuseIPDB blacklist, ...), normalizes every feed into a set
+//! of networks with a category and a confidence, and merges the feeds into
+//! the `THREAT_INTEL_V4`/`THREAT_INTEL_V6` LPM tries of xdp_filter. Each
+//! entry records the feed it came from, so feeds can be switched between
+//! enforcing and count-only without rewriting the tries, and the kernel keeps
+//! per-feed hit counters in `THREAT_FEED_HITS`.
+//!
+//! The signed offender bundle (see `offender_bundle`) is one more feed,
+//! loaded from disk instead of fetched, so known attack infrastructure is
+//! dropped before any feed could be downloaded.
+
+use super::stats::DropCounter;
+use ipnetwork::IpNetwork;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Maximum number of IPv4 prefixes (mirrors `threat_intel::MAX_ENTRIES_V4`)
+pub const MAX_ENTRIES_V4: usize = 1_000_000;
+
+/// Maximum number of IPv6 prefixes (mirrors `threat_intel::MAX_ENTRIES_V6`)
+pub const MAX_ENTRIES_V6: usize = 250_000;
+
+/// Maximum number of feeds (mirrors `threat_intel::MAX_FEEDS`)
+pub const MAX_FEEDS: usize = 64;
+
+/// Shortest IPv4 prefix accepted from a feed
+pub const MIN_PREFIX_V4: u8 = 8;
+
+/// Shortest IPv6 prefix accepted from a feed
+pub const MIN_PREFIX_V6: u8 = 16;
+
+/// Default time between two fetches of a feed
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Time before a failed fetch is retried
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Name of the feed holding the active offender bundle
+pub const BUNDLE_FEED: &str = "offender_bundle";
+
+/// Networks that are never imported, whatever a feed lists. Some feeds
+/// (FireHOL level 1 among them) include bogons, and dropping private or
+/// loopback sources would cut the worker off from its own infrastructure.
+const RESERVED_NETWORKS: &[&str] = &[
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "224.0.0.0/3",
+    "::/8",
+    "fc00::/7",
+    "fe80::/10",
+    "ff00::/8",
+];
+
+/// Value of the `THREAT_INTEL_V*` tries (mirrors `ThreatIntelEntry`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreatIntelEntry {
+    pub feed_id: u32,
+    pub category: u32,
+    pub confidence: u32,
+    pub _pad: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of four `u32` fields, no padding.
+unsafe impl aya::Pod for ThreatIntelEntry {}
+
+/// Value of `THREAT_FEEDS` (mirrors `ThreatFeedConfig`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreatFeedConfig {
+    pub enabled: u32,
+    pub min_confidence: u32,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u32` fields, no padding.
+unsafe impl aya::Pod for ThreatFeedConfig {}
+
+/// What a listed network is known for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum ThreatCategory {
+    /// Hijacked or leased to criminal operations
+    Hijacked = 0,
+    Malware = 1,
+    Botnet = 2,
+    /// Reported for abusive traffic (scanning, brute force, attacks)
+    Abuse = 3,
+    Spam = 4,
+    Other = 5,
+}
+
+impl ThreatCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThreatCategory::Hijacked => "hijacked",
+            ThreatCategory::Malware => "malware",
+            ThreatCategory::Botnet => "botnet",
+            ThreatCategory::Abuse => "abuse",
+            ThreatCategory::Spam => "spam",
+            ThreatCategory::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ThreatCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Format of a feed's content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedFormat {
+    /// Spamhaus DROP: `network ; SBL id` lines, or the JSON lines format
+    SpamhausDrop,
+    /// One network or address per line with `#` comments (FireHOL netsets)
+    Netset,
+    /// AbuseIPDB blacklist API response (JSON), scored per address
+    AbuseIpDb,
+    /// Signed offender bundle (JSON), loaded by the bundle task
+    Bundle,
+}
+
+impl FromStr for FeedFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spamhaus" | "spamhaus_drop" => Ok(FeedFormat::SpamhausDrop),
+            "netset" | "firehol" | "plain" => Ok(FeedFormat::Netset),
+            "abuseipdb" => Ok(FeedFormat::AbuseIpDb),
+            other => Err(format!("unknown feed format '{}'", other)),
+        }
+    }
+}
+
+/// Configured feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreatFeed {
+    pub name: String,
+    pub url: String,
+    pub format: FeedFormat,
+    pub category: ThreatCategory,
+    /// Confidence given to entries of feeds that do not score them
+    pub confidence: u8,
+    /// Entries below this confidence are ignored
+    pub min_confidence: u8,
+    /// Drop matching traffic; disabled feeds only count hits
+    pub enabled: bool,
+    pub refresh_interval: Duration,
+    /// Credential sent in the `Key` header (AbuseIPDB)
+    pub api_key: Option<String>,
+}
+
+impl ThreatFeed {
+    /// Feed shipped with the worker, by name
+    pub fn builtin(name: &str) -> Option<Self> {
+        let feed = |url: &str, format, category, confidence| Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            format,
+            category,
+            confidence,
+            min_confidence: 0,
+            enabled: true,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            api_key: None,
+        };
+
+        Some(match name {
+            "spamhaus_drop" => feed(
+                "https://www.spamhaus.org/drop/drop_v4.json",
+                FeedFormat::SpamhausDrop,
+                ThreatCategory::Hijacked,
+                100,
+            ),
+            "spamhaus_drop_v6" => feed(
+                "https://www.spamhaus.org/drop/drop_v6.json",
+                FeedFormat::SpamhausDrop,
+                ThreatCategory::Hijacked,
+                100,
+            ),
+            "firehol_level1" => feed(
+                "https://iplists.firehol.org/files/firehol_level1.netset",
+                FeedFormat::Netset,
+                ThreatCategory::Malware,
+                90,
+            ),
+            "firehol_level2" => feed(
+                "https://iplists.firehol.org/files/firehol_level2.netset",
+                FeedFormat::Netset,
+                ThreatCategory::Abuse,
+                75,
+            ),
+            "abuseipdb" => Self {
+                min_confidence: 90,
+                refresh_interval: Duration::from_secs(6 * 3600),
+                ..feed(
+                    "https://api.abuseipdb.com/api/v2/blacklist?confidenceMinimum=75",
+                    FeedFormat::AbuseIpDb,
+                    ThreatCategory::Abuse,
+                    0,
+                )
+            },
+            _ => return None,
+        })
+    }
+
+    /// Feed of the offender bundle: booter and stresser infrastructure
+    pub fn bundle() -> Self {
+        Self {
+            name: BUNDLE_FEED.to_string(),
+            url: String::new(),
+            format: FeedFormat::Bundle,
+            category: ThreatCategory::Botnet,
+            confidence: 100,
+            min_confidence: 0,
+            enabled: true,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            api_key: None,
+        }
+    }
+
+    /// Parse a feed setting: a built-in name or `name=format:url`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let Some((name, source)) = spec.split_once('=') else {
+            return Self::builtin(spec).ok_or_else(|| format!("unknown feed '{}'", spec));
+        };
+        let (format, url) = source
+            .split_once(':')
+            .ok_or_else(|| format!("feed '{}' must be given as name=format:url", name))?;
+
+        Ok(Self {
+            name: name.trim().to_string(),
+            url: url.trim().to_string(),
+            format: format.trim().parse()?,
+            category: ThreatCategory::Other,
+            confidence: 100,
+            min_confidence: 0,
+            enabled: true,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            api_key: None,
+        })
+    }
+}
+
+/// Threat intelligence configuration
+#[derive(Debug, Clone, Default)]
+pub struct ThreatIntelConfig {
+    pub feeds: Vec<ThreatFeed>,
+}
+
+impl ThreatIntelConfig {
+    /// Load feed configuration from environment variables
+    ///
+    /// `PISTON_THREAT_FEEDS` lists enforced feeds and
+    /// `PISTON_THREAT_FEEDS_MONITOR` count-only feeds, comma separated, each
+    /// a built-in name (`spamhaus_drop`, `firehol_level1`, `abuseipdb`, ...)
+    /// or `name=format:url`. No feed is fetched unless configured.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        let lists = [
+            ("PISTON_THREAT_FEEDS", true),
+            ("PISTON_THREAT_FEEDS_MONITOR", false),
+        ];
+        for (var, enabled) in lists {
+            let Ok(specs) = std::env::var(var) else {
+                continue;
+            };
+            for spec in specs.split(',').filter(|s| !s.trim().is_empty()) {
+                match ThreatFeed::parse(spec) {
+                    Ok(feed) => config.feeds.push(ThreatFeed { enabled, ..feed }),
+                    Err(e) => tracing::warn!(var, error = %e, "Ignoring threat feed"),
+                }
+            }
+        }
+
+        if let Ok(secs) = std::env::var("PISTON_THREAT_FEED_REFRESH_SECS") {
+            if let Ok(secs) = secs.parse::<u64>() {
+                for feed in &mut config.feeds {
+                    feed.refresh_interval = Duration::from_secs(secs.max(60));
+                }
+            }
+        }
+
+        if let Ok(key) = std::env::var("PISTON_ABUSEIPDB_KEY") {
+            for feed in &mut config.feeds {
+                if feed.format == FeedFormat::AbuseIpDb {
+                    feed.api_key = Some(key.clone());
+                }
+            }
+        }
+        config.feeds.retain(|feed| {
+            let usable = feed.format != FeedFormat::AbuseIpDb || feed.api_key.is_some();
+            if !usable {
+                tracing::warn!(feed = %feed.name, "PISTON_ABUSEIPDB_KEY not set, ignoring feed");
+            }
+            usable
+        });
+
+        // Loaded first, so the bundle keeps its feed id
+        if std::env::var(super::offender_bundle::BUNDLE_KEYS_ENV).is_ok() {
+            let enabled = std::env::var("PISTON_OFFENDER_BUNDLE_MONITOR").is_err();
+            config.feeds.insert(
+                0,
+                ThreatFeed {
+                    enabled,
+                    ..ThreatFeed::bundle()
+                },
+            );
+        }
+
+        config.feeds.truncate(MAX_FEEDS);
+        config
+    }
+}
+
+/// Network listed by a feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeedEntry {
+    pub network: IpNetwork,
+    pub confidence: u8,
+}
+
+/// Normalized content of a feed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedFeed {
+    /// Deduplicated networks with host bits cleared
+    pub entries: Vec<FeedEntry>,
+    /// Lines that could not be parsed
+    pub invalid: usize,
+    /// Networks dropped as too wide or reserved
+    pub skipped: usize,
+}
+
+/// Parse and normalize the content of a feed
+pub fn parse_feed(format: FeedFormat, body: &str, confidence: u8) -> ParsedFeed {
+    let mut parsed = ParsedFeed::default();
+    let raw: Vec<(IpNetwork, u8)> = match format {
+        FeedFormat::SpamhausDrop => body
+            .lines()
+            .filter_map(|line| parse_spamhaus_line(line, &mut parsed.invalid))
+            .map(|network| (network, confidence))
+            .collect(),
+        FeedFormat::Netset => body
+            .lines()
+            .filter_map(|line| {
+                let line = line.split('#').next().unwrap_or("").trim();
+                parse_network(line, &mut parsed.invalid)
+            })
+            .map(|network| (network, confidence))
+            .collect(),
+        FeedFormat::AbuseIpDb => parse_abuseipdb(body, &mut parsed.invalid),
+        FeedFormat::Bundle => parse_bundle(body, confidence, &mut parsed.invalid),
+    };
+
+    let reserved: Vec<IpNetwork> = RESERVED_NETWORKS
+        .iter()
+        .map(|n| n.parse().expect("valid reserved network"))
+        .collect();
+
+    let mut networks: BTreeMap<(IpAddr, u8), u8> = BTreeMap::new();
+    for (network, confidence) in raw {
+        let min_prefix = match network {
+            IpNetwork::V4(_) => MIN_PREFIX_V4,
+            IpNetwork::V6(_) => MIN_PREFIX_V6,
+        };
+        let is_reserved = reserved
+            .iter()
+            .any(|r| r.contains(network.network()) || network.contains(r.network()));
+        if network.prefix() < min_prefix || is_reserved {
+            parsed.skipped += 1;
+            continue;
+        }
+
+        let best = networks
+            .entry((network.network(), network.prefix()))
+            .or_insert(confidence);
+        *best = (*best).max(confidence);
+    }
+
+    parsed.entries = networks
+        .into_iter()
+        .map(|((addr, prefix), confidence)| FeedEntry {
+            network: IpNetwork::new(addr, prefix).expect("prefix from a valid network"),
+            confidence,
+        })
+        .collect();
+    parsed
+}
+
+fn parse_network(s: &str, invalid: &mut usize) -> Option<IpNetwork> {
+    if s.is_empty() {
+        return None;
+    }
+    match s.parse() {
+        Ok(network) => Some(network),
+        Err(_) => {
+            *invalid += 1;
+            None
+        }
+    }
+}
+
+/// `1.10.16.0/20 ; SBL256894`, or `{"cidr":"1.10.16.0/20","sblid":...}`
+fn parse_spamhaus_line(line: &str, invalid: &mut usize) -> Option<IpNetwork> {
+    #[derive(Deserialize)]
+    struct DropRecord {
+        cidr: Option<String>,
+    }
+
+    let line = line.trim();
+    if line.starts_with('{') {
+        // The trailing metadata record has no network
+        return match serde_json::from_str::<DropRecord>(line) {
+            Ok(record) => parse_network(record.cidr.as_deref().unwrap_or(""), invalid),
+            Err(_) => {
+                *invalid += 1;
+                None
+            }
+        };
+    }
+    parse_network(line.split(';').next().unwrap_or("").trim(), invalid)
+}
+
+/// Scored addresses of an AbuseIPDB blacklist response
+fn parse_abuseipdb(body: &str, invalid: &mut usize) -> Vec<(IpNetwork, u8)> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Record {
+        ip_address: String,
+        abuse_confidence_score: u8,
+    }
+    #[derive(Deserialize)]
+    struct Response {
+        data: Vec<Record>,
+    }
+
+    let Ok(response) = serde_json::from_str::<Response>(body) else {
+        *invalid += 1;
+        return Vec::new();
+    };
+    response
+        .data
+        .into_iter()
+        .filter_map(|record| {
+            let network = parse_network(&record.ip_address, invalid)?;
+            Some((network, record.abuse_confidence_score.min(100)))
+        })
+        .collect()
+}
+
+/// Networks of an offender bundle, scored per network
+fn parse_bundle(body: &str, confidence: u8, invalid: &mut usize) -> Vec<(IpNetwork, u8)> {
+    #[derive(Deserialize)]
+    struct Record {
+        network: String,
+        confidence: Option<u8>,
+    }
+    #[derive(Deserialize)]
+    struct Bundle {
+        networks: Vec<Record>,
+    }
+
+    let Ok(bundle) = serde_json::from_str::<Bundle>(body) else {
+        *invalid += 1;
+        return Vec::new();
+    };
+    bundle
+        .networks
+        .into_iter()
+        .filter_map(|record| {
+            let network = parse_network(record.network.trim(), invalid)?;
+            Some((network, record.confidence.unwrap_or(confidence).min(100)))
+        })
+        .collect()
+}
+
+/// Download the content of a feed
+pub async fn fetch_feed(client: &reqwest::Client, feed: &ThreatFeed) -> Result<String, String> {
+    let mut request = client.get(&feed.url);
+    if let Some(key) = &feed.api_key {
+        request = request
+            .header("Key", key)
+            .header("Accept", "application/json");
+    }
+
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status));
+    }
+    response.text().await.map_err(|e| e.to_string())
+}
+
+/// Contents of the xdp_filter threat intelligence maps
+#[derive(Debug, Clone, Default)]
+pub struct ThreatIntelMapEntries {
+    /// IPv4 prefixes (network byte order) with their entry
+    pub v4: Vec<(u32, [u8; 4], ThreatIntelEntry)>,
+    pub v6: Vec<(u32, [u8; 16], ThreatIntelEntry)>,
+    /// Settings per feed id
+    pub feeds: Vec<ThreatFeedConfig>,
+}
+
+/// Fetch outcome and statistics of a feed
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedStatus {
+    pub id: u32,
+    pub name: String,
+    pub url: String,
+    pub format: FeedFormat,
+    pub category: ThreatCategory,
+    pub enabled: bool,
+    pub min_confidence: u8,
+    pub entries: usize,
+    pub invalid: usize,
+    pub skipped: usize,
+    pub last_fetch: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_error: Option<String>,
+    /// Packets and bytes matched since the feed was programmed
+    pub hits: DropCounter,
+}
+
+/// State of a configured feed
+#[derive(Debug)]
+struct FeedState {
+    feed: ThreatFeed,
+    entries: Vec<FeedEntry>,
+    invalid: usize,
+    skipped: usize,
+    last_fetch: Option<chrono::DateTime<chrono::Utc>>,
+    last_error: Option<String>,
+    next_fetch: Instant,
+    hits: DropCounter,
+}
+
+/// Feed data and statistics shared by the import task and the HTTP API
+#[derive(Debug, Default)]
+pub struct ThreatIntelManager {
+    feeds: RwLock<Vec<FeedState>>,
+    /// Bumped whenever the map contents change
+    revision: RwLock<u64>,
+}
+
+impl ThreatIntelManager {
+    pub fn new(config: ThreatIntelConfig) -> Self {
+        let now = Instant::now();
+        let feeds = config
+            .feeds
+            .into_iter()
+            .take(MAX_FEEDS)
+            .map(|feed| FeedState {
+                feed,
+                entries: Vec::new(),
+                invalid: 0,
+                skipped: 0,
+                last_fetch: None,
+                last_error: None,
+                next_fetch: now,
+                hits: DropCounter::default(),
+            })
+            .collect();
+
+        Self {
+            feeds: RwLock::new(feeds),
+            revision: RwLock::new(0),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.feeds.read().is_empty()
+    }
+
+    /// Revision of the map contents, bumped on every change
+    pub fn revision(&self) -> u64 {
+        *self.revision.read()
+    }
+
+    fn bump_revision(&self) {
+        *self.revision.write() += 1;
+    }
+
+    /// Feeds whose refresh is due, with their id
+    ///
+    /// The offender bundle is never due: it is not fetched like a feed.
+    pub fn due_feeds(&self, now: Instant) -> Vec<(u32, ThreatFeed)> {
+        self.feeds
+            .read()
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| state.feed.format != FeedFormat::Bundle)
+            .filter(|(_, state)| state.next_fetch <= now)
+            .map(|(id, state)| (id as u32, state.feed.clone()))
+            .collect()
+    }
+
+    /// Record the outcome of fetching a feed
+    ///
+    /// A failed fetch keeps the previous entries, so an unreachable feed does
+    /// not unblock the networks it listed.
+    pub fn record_fetch(&self, feed_id: u32, result: Result<String, String>, now: Instant) {
+        let mut feeds = self.feeds.write();
+        let Some(state) = feeds.get_mut(feed_id as usize) else {
+            return;
+        };
+
+        match result {
+            Ok(body) => {
+                let parsed = parse_feed(state.feed.format, &body, state.feed.confidence);
+                let changed = parsed.entries != state.entries;
+                state.entries = parsed.entries;
+                state.invalid = parsed.invalid;
+                state.skipped = parsed.skipped;
+                state.last_fetch = Some(chrono::Utc::now());
+                state.last_error = None;
+                state.next_fetch = now + state.feed.refresh_interval;
+                drop(feeds);
+                if changed {
+                    self.bump_revision();
+                }
+            }
+            Err(e) => {
+                state.last_error = Some(e);
+                state.next_fetch = now + RETRY_INTERVAL.min(state.feed.refresh_interval);
+            }
+        }
+    }
+
+    /// Id of the feed named `name`
+    pub fn feed_id(&self, name: &str) -> Option<u32> {
+        self.feeds
+            .read()
+            .iter()
+            .position(|state| state.feed.name == name)
+            .map(|id| id as u32)
+    }
+
+    /// Packets and bytes the feed named `name` matched
+    pub fn feed_hits(&self, name: &str) -> Option<DropCounter> {
+        self.feeds
+            .read()
+            .iter()
+            .find(|state| state.feed.name == name)
+            .map(|state| state.hits)
+    }
+
+    /// Switch a feed between enforcing and count-only
+    ///
+    /// Returns `false` if no feed has that name.
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        let mut feeds = self.feeds.write();
+        let Some(state) = feeds.iter_mut().find(|state| state.feed.name == name) else {
+            return false;
+        };
+        let changed = state.feed.enabled != enabled;
+        state.feed.enabled = enabled;
+        drop(feeds);
+        if changed {
+            self.bump_revision();
+        }
+        true
+    }
+
+    /// Store the per-feed hit counters read from the kernel
+    pub fn record_hits(&self, hits: &[DropCounter]) {
+        for (state, hits) in self.feeds.write().iter_mut().zip(hits) {
+            state.hits = *hits;
+        }
+    }
+
+    /// Merge all feeds into the contents of the threat intelligence maps
+    ///
+    /// A network listed by several feeds keeps the entry with the highest
+    /// confidence (the lowest feed id on a tie). If a family exceeds its map
+    /// size, the lowest-confidence networks are left out.
+    pub fn map_entries(&self) -> ThreatIntelMapEntries {
+        let feeds = self.feeds.read();
+        let mut merged: BTreeMap<(IpAddr, u8), ThreatIntelEntry> = BTreeMap::new();
+
+        for (id, state) in feeds.iter().enumerate() {
+            for entry in &state.entries {
+                let value = ThreatIntelEntry {
+                    feed_id: id as u32,
+                    category: state.feed.category as u32,
+                    confidence: entry.confidence as u32,
+                    _pad: 0,
+                };
+                merged
+                    .entry((entry.network.network(), entry.network.prefix()))
+                    .and_modify(|current| {
+                        if value.confidence > current.confidence {
+                            *current = value;
+                        }
+                    })
+                    .or_insert(value);
+            }
+        }
+
+        let mut entries = ThreatIntelMapEntries {
+            feeds: feeds
+                .iter()
+                .map(|state| ThreatFeedConfig {
+                    enabled: state.feed.enabled as u32,
+                    min_confidence: state.feed.min_confidence as u32,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        for ((addr, prefix), value) in merged {
+            match addr {
+                IpAddr::V4(addr) => entries.v4.push((prefix as u32, addr.octets(), value)),
+                IpAddr::V6(addr) => entries.v6.push((prefix as u32, addr.octets(), value)),
+            }
+        }
+
+        let truncated = keep_most_confident(&mut entries.v4, MAX_ENTRIES_V4)
+            + keep_most_confident(&mut entries.v6, MAX_ENTRIES_V6);
+        if truncated > 0 {
+            tracing::warn!(truncated, "Threat intelligence maps full, dropping entries");
+        }
+
+        entries
+    }
+
+    /// Status of every feed
+    pub fn status(&self) -> Vec<FeedStatus> {
+        self.feeds
+            .read()
+            .iter()
+            .enumerate()
+            .map(|(id, state)| FeedStatus {
+                id: id as u32,
+                name: state.feed.name.clone(),
+                url: state.feed.url.clone(),
+                format: state.feed.format,
+                category: state.feed.category,
+                enabled: state.feed.enabled,
+                min_confidence: state.feed.min_confidence,
+                entries: state.entries.len(),
+                invalid: state.invalid,
+                skipped: state.skipped,
+                last_fetch: state.last_fetch,
+                last_error: state.last_error.clone(),
+                hits: state.hits,
+            })
+            .collect()
+    }
+
+    /// Highest confidence with which a count-only feed lists `ip`
+    ///
+    /// Enforcing feeds are not consulted: their sources never get past
+    /// xdp_filter.
+    pub fn monitored_listing(&self, ip: IpAddr) -> Option<u8> {
+        self.feeds
+            .read()
+            .iter()
+            .filter(|state| !state.feed.enabled)
+            .filter_map(|state| {
+                lookup(&state.entries, ip)
+                    .filter(|confidence| *confidence >= state.feed.min_confidence)
+            })
+            .max()
+    }
+
+    /// Hit counters per category, summed over feeds
+    pub fn hits_by_category(&self) -> HashMap<ThreatCategory, DropCounter> {
+        let mut hits: HashMap<ThreatCategory, DropCounter> = HashMap::new();
+        for state in self.feeds.read().iter() {
+            let counter = hits.entry(state.feed.category).or_default();
+            counter.packets = counter.packets.wrapping_add(state.hits.packets);
+            counter.bytes = counter.bytes.wrapping_add(state.hits.bytes);
+        }
+        hits
+    }
+}
+
+/// Confidence of the most specific entry containing `ip`
+///
+/// `entries` must be sorted by network address and prefix, as produced by
+/// `parse_feed`.
+fn lookup(entries: &[FeedEntry], ip: IpAddr) -> Option<u8> {
+    let (min_prefix, max_prefix) = match ip {
+        IpAddr::V4(_) => (MIN_PREFIX_V4, 32),
+        IpAddr::V6(_) => (MIN_PREFIX_V6, 128),
+    };
+    (min_prefix..=max_prefix).rev().find_map(|prefix| {
+        let network = IpNetwork::new(ip, prefix).ok()?.network();
+        entries
+            .binary_search_by(|entry| {
+                (entry.network.network(), entry.network.prefix()).cmp(&(network, prefix))
+            })
+            .ok()
+            .map(|index| entries[index].confidence)
+    })
+}
+
+/// Keep the `max` most confident entries, returning how many were dropped
+fn keep_most_confident<K>(entries: &mut Vec<(u32, K, ThreatIntelEntry)>, max: usize) -> usize {
+    if entries.len() <= max {
+        return 0;
+    }
+    let dropped = entries.len() - max;
+    entries.sort_by(|a, b| b.2.confidence.cmp(&a.2.confidence));
+    entries.truncate(max);
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks(parsed: &ParsedFeed) -> Vec<String> {
+        parsed
+            .entries
+            .iter()
+            .map(|e| e.network.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_spamhaus_drop() {
+        let text = "; Spamhaus DROP List 2026/10/16\n\
+                    ; Last-Modified: Fri, 16 Oct 2026 10:00:00 GMT\n\
+                    1.10.16.0/20 ; SBL256894\n\
+                    2.56.192.0/22 ; SBL459831\n\
+                    garbage ; SBL1\n";
+        let parsed = parse_feed(FeedFormat::SpamhausDrop, text, 100);
+        assert_eq!(networks(&parsed), ["1.10.16.0/20", "2.56.192.0/22"]);
+        assert_eq!(parsed.invalid, 1);
+
+        let json = "{\"cidr\":\"1.10.16.0/20\",\"sblid\":\"SBL256894\",\"rir\":\"apnic\"}\n\
+                    {\"cidr\":\"2001:db8:4000::/34\",\"sblid\":\"SBL1\",\"rir\":\"ripencc\"}\n\
+                    {\"type\":\"metadata\",\"timestamp\":1760608800,\"size\":2}\n";
+        let parsed = parse_feed(FeedFormat::SpamhausDrop, json, 100);
+        assert_eq!(networks(&parsed), ["1.10.16.0/20", "2001:db8:4000::/34"]);
+        assert_eq!(parsed.invalid, 0);
+    }
+
+    #[test]
+    fn test_parse_netset_normalizes() {
+        let text = "#\n# firehol_level1\n#\n\
+                    0.0.0.0/8\n\
+                    10.0.0.0/8\n\
+                    5.188.10.7/24\n\
+                    5.188.10.0/24\n\
+                    45.9.20.17\n\
+                    1.0.0.0/4\n\
+                    192.0.2.1 # inline comment\n";
+        let parsed = parse_feed(FeedFormat::Netset, text, 90);
+        assert_eq!(
+            networks(&parsed),
+            ["5.188.10.0/24", "45.9.20.17/32", "192.0.2.1/32"]
+        );
+        // Bogons and the /4 are dropped, the duplicate /24 is merged
+        assert_eq!(parsed.skipped, 3);
+        assert!(parsed.entries.iter().all(|e| e.confidence == 90));
+    }
+
+    #[test]
+    fn test_parse_abuseipdb() {
+        let body = r#"{
+            "meta": {"generatedAt": "2026-10-16T10:00:00+00:00"},
+            "data": [
+                {"ipAddress": "198.51.100.7", "countryCode": "XX", "abuseConfidenceScore": 100},
+                {"ipAddress": "2001:db8::7", "countryCode": "XX", "abuseConfidenceScore": 80},
+                {"ipAddress": "not-an-ip", "countryCode": "XX", "abuseConfidenceScore": 90}
+            ]
+        }"#;
+        let parsed = parse_feed(FeedFormat::AbuseIpDb, body, 0);
+        assert_eq!(networks(&parsed), ["198.51.100.7/32", "2001:db8::7/128"]);
+        assert_eq!(parsed.entries[1].confidence, 80);
+        assert_eq!(parsed.invalid, 1);
+
+        assert_eq!(parse_feed(FeedFormat::AbuseIpDb, "<html>", 0).invalid, 1);
+    }
+
+    #[test]
+    fn test_parse_feed_spec() {
+        let feed = ThreatFeed::parse("spamhaus_drop").unwrap();
+        assert_eq!(feed.format, FeedFormat::SpamhausDrop);
+        assert_eq!(feed.category, ThreatCategory::Hijacked);
+
+        let custom = ThreatFeed::parse("corp=netset:https://example.com/block.netset").unwrap();
+        assert_eq!(custom.name, "corp");
+        assert_eq!(custom.url, "https://example.com/block.netset");
+        assert_eq!(custom.format, FeedFormat::Netset);
+
+        assert!(ThreatFeed::parse("nope").is_err());
+        assert!(ThreatFeed::parse("corp=https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_map_entries_merge_feeds() {
+        let drop = ThreatFeed::builtin("spamhaus_drop").unwrap();
+        let firehol = ThreatFeed {
+            enabled: false,
+            ..ThreatFeed::builtin("firehol_level1").unwrap()
+        };
+        let manager = ThreatIntelManager::new(ThreatIntelConfig {
+            feeds: vec![drop, firehol],
+        });
+        let now = Instant::now();
+        assert_eq!(manager.due_feeds(now).len(), 2);
+
+        manager.record_fetch(0, Ok("198.51.100.0/24 ; SBL1\n".to_string()), now);
+        manager.record_fetch(
+            1,
+            Ok("198.51.100.0/24\n203.0.113.9\n2001:db8::/32\n".to_string()),
+            now,
+        );
+        assert!(manager.due_feeds(now).is_empty());
+        let revision = manager.revision();
+
+        let entries = manager.map_entries();
+        assert_eq!(
+            entries.v4,
+            [
+                (
+                    24,
+                    [198, 51, 100, 0],
+                    ThreatIntelEntry {
+                        feed_id: 0,
+                        category: ThreatCategory::Hijacked as u32,
+                        confidence: 100,
+                        _pad: 0,
+                    }
+                ),
+                (
+                    32,
+                    [203, 0, 113, 9],
+                    ThreatIntelEntry {
+                        feed_id: 1,
+                        category: ThreatCategory::Malware as u32,
+                        confidence: 90,
+                        _pad: 0,
+                    }
+                ),
+            ]
+        );
+        assert_eq!(entries.v6.len(), 1);
+        assert_eq!(
+            entries.feeds,
+            [
+                ThreatFeedConfig {
+                    enabled: 1,
+                    min_confidence: 0
+                },
+                ThreatFeedConfig {
+                    enabled: 0,
+                    min_confidence: 0
+                },
+            ]
+        );
+
+        // Toggling a feed changes the maps, refetching the same data does not
+        assert!(manager.set_enabled("firehol_level1", true));
+        assert!(!manager.set_enabled("unknown", true));
+        assert_eq!(manager.revision(), revision + 1);
+        manager.record_fetch(0, Ok("198.51.100.0/24 ; SBL1\n".to_string()), now);
+        assert_eq!(manager.revision(), revision + 1);
+    }
+
+    #[test]
+    fn test_bundle_feed() {
+        let manager = ThreatIntelManager::new(ThreatIntelConfig {
+            feeds: vec![ThreatFeed::bundle()],
+        });
+        let now = Instant::now();
+        // Bundles are loaded by the bundle task, not fetched
+        assert!(manager.due_feeds(now).is_empty());
+
+        let bundle = r#"{"version":3,"networks":[
+            {"network":"203.0.113.0/24","confidence":80},
+            {"network":"198.51.100.7"},
+            {"network":"10.0.0.0/8"},
+            {"network":"bogus"}]}"#;
+        let feed_id = manager.feed_id(BUNDLE_FEED).unwrap();
+        manager.record_fetch(feed_id, Ok(bundle.to_string()), now);
+
+        let status = &manager.status()[0];
+        assert_eq!(status.entries, 2);
+        assert_eq!(status.skipped, 1);
+        assert_eq!(status.invalid, 1);
+        assert_eq!(status.category, ThreatCategory::Botnet);
+        let confidences: Vec<u32> = manager
+            .map_entries()
+            .v4
+            .iter()
+            .map(|(_, _, entry)| entry.confidence)
+            .collect();
+        assert_eq!(confidences, [100, 80]);
+        assert_eq!(manager.feed_hits(BUNDLE_FEED), Some(DropCounter::default()));
+    }
+
+    #[test]
+    fn test_failed_fetch_keeps_entries() {
+        let manager = ThreatIntelManager::new(ThreatIntelConfig {
+            feeds: vec![ThreatFeed::builtin("firehol_level1").unwrap()],
+        });
+        let now = Instant::now();
+        manager.record_fetch(0, Ok("203.0.113.0/24\n".to_string()), now);
+        manager.record_fetch(0, Err("connection refused".to_string()), now);
+
+        let status = manager.status();
+        assert_eq!(status[0].entries, 1);
+        assert_eq!(status[0].last_error.as_deref(), Some("connection refused"));
+        assert_eq!(manager.due_feeds(now + RETRY_INTERVAL).len(), 1);
+
+        manager.record_hits(&[DropCounter {
+            packets: 3,
+            bytes: 180,
+        }]);
+        assert_eq!(
+            manager.hits_by_category()[&ThreatCategory::Malware].packets,
+            3
+        );
+    }
+
+    #[test]
+    fn test_monitored_listing() {
+        let feed = ThreatFeed::builtin("firehol_level1").unwrap();
+        let confidence = feed.confidence;
+        let manager = ThreatIntelManager::new(ThreatIntelConfig { feeds: vec![feed] });
+        let now = Instant::now();
+        manager.record_fetch(
+            0,
+            Ok("203.0.113.0/24\n198.51.100.7\n2001:db8::/32\n".to_string()),
+            now,
+        );
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        // Enforcing feeds are not consulted
+        assert_eq!(manager.monitored_listing(ip("203.0.113.9")), None);
+
+        assert!(manager.set_enabled("firehol_level1", false));
+        assert_eq!(
+            manager.monitored_listing(ip("203.0.113.9")),
+            Some(confidence)
+        );
+        assert_eq!(
+            manager.monitored_listing(ip("198.51.100.7")),
+            Some(confidence)
+        );
+        assert_eq!(
+            manager.monitored_listing(ip("2001:db8:5::1")),
+            Some(confidence)
+        );
+        assert_eq!(manager.monitored_listing(ip("198.51.100.8")), None);
+        assert_eq!(manager.monitored_listing(ip("2001:db9::1")), None);
+    }
+
+    #[test]
+    fn test_keep_most_confident() {
+        let entry = |confidence| ThreatIntelEntry {
+            confidence,
+            ..Default::default()
+        };
+        let mut entries = vec![
+            (32, 1u8, entry(50)),
+            (32, 2, entry(100)),
+            (32, 3, entry(75)),
+        ];
+        assert_eq!(keep_most_confident(&mut entries, 2), 1);
+        assert_eq!(entries.iter().map(|e| e.1).collect::<Vec<_>>(), [2, 3]);
+    }
+}
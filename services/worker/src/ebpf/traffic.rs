@@ -0,0 +1,230 @@
+//! Destination traffic accounting
+//!
+//! xdp_filter counts the packets and bytes sent to every backend destination
+//! in `DST_TRAFFIC`, and how many of them it dropped. Userspace inserts a
+//! zeroed entry per destination and the program only bumps existing ones.
+//! The counters only grow, so the metrics task keeps the last reported read
+//! in a [`TrafficMeter`] and reports the traffic since then per backend; the
+//! control plane adds these up into the monthly bandwidth usage of each
+//! organization. Traffic of a report that did not reach the control plane
+//! is reported again with the next one.
+
+use super::tenants::{TenantDestination, TenantDstV6Key, destination_entries};
+use std::collections::HashMap;
+
+/// Maximum number of accounted destinations (mirrors `traffic::MAX_DESTINATIONS`)
+pub const MAX_DESTINATIONS: usize = 65536;
+
+/// Value of `DST_TRAFFIC` (mirrors `DstTraffic`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DstTraffic {
+    /// Every packet to the destination, dropped or not
+    pub packets: u64,
+    pub bytes: u64,
+    pub dropped_packets: u64,
+    pub dropped_bytes: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of four `u64` fields, no padding.
+unsafe impl aya::Pod for DstTraffic {}
+
+impl DstTraffic {
+    /// Sum of two counters
+    pub fn add(&self, other: &Self) -> Self {
+        Self {
+            packets: self.packets.saturating_add(other.packets),
+            bytes: self.bytes.saturating_add(other.bytes),
+            dropped_packets: self.dropped_packets.saturating_add(other.dropped_packets),
+            dropped_bytes: self.dropped_bytes.saturating_add(other.dropped_bytes),
+        }
+    }
+
+    /// Traffic counted since `previous`
+    ///
+    /// A counter below its previous value belongs to a recreated entry (the
+    /// program was reloaded or the destination re-added), so it is taken as
+    /// a whole.
+    pub fn since(&self, previous: &Self) -> Self {
+        if self.packets < previous.packets
+            || self.bytes < previous.bytes
+            || self.dropped_packets < previous.dropped_packets
+            || self.dropped_bytes < previous.dropped_bytes
+        {
+            return *self;
+        }
+        Self {
+            packets: self.packets - previous.packets,
+            bytes: self.bytes - previous.bytes,
+            dropped_packets: self.dropped_packets - previous.dropped_packets,
+            dropped_bytes: self.dropped_bytes - previous.dropped_bytes,
+        }
+    }
+
+    /// Bytes passed to the destination
+    pub fn clean_bytes(&self) -> u64 {
+        self.bytes.saturating_sub(self.dropped_bytes)
+    }
+}
+
+/// Build the keys of `DST_TRAFFIC` and the backend each one is counted for
+pub fn traffic_map_keys(
+    backends: &HashMap<String, Vec<TenantDestination>>,
+) -> Vec<(TenantDstV6Key, String)> {
+    destination_entries(backends, MAX_DESTINATIONS, |backend_id, destinations| {
+        Some((destinations.as_slice(), backend_id.clone()))
+    })
+}
+
+/// Turns the cumulative `DST_TRAFFIC` counters into traffic per backend
+/// between two reports
+#[derive(Debug, Default)]
+pub struct TrafficMeter {
+    /// Counters as of the last committed report
+    last: HashMap<TenantDstV6Key, DstTraffic>,
+    /// Counters of the report being sent
+    pending: HashMap<TenantDstV6Key, DstTraffic>,
+}
+
+impl TrafficMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Traffic per backend since the last committed report
+    ///
+    /// Destinations without an owner are remembered but not reported, and
+    /// destinations no longer read are forgotten once committed.
+    pub fn observe(
+        &mut self,
+        counters: &[(TenantDstV6Key, DstTraffic)],
+        owners: &HashMap<TenantDstV6Key, String>,
+    ) -> HashMap<String, DstTraffic> {
+        let mut per_backend: HashMap<String, DstTraffic> = HashMap::new();
+        let mut pending = HashMap::with_capacity(counters.len());
+
+        for (key, current) in counters {
+            let previous = self.last.get(key).copied().unwrap_or_default();
+            let delta = current.since(&previous);
+            if let Some(backend_id) = owners.get(key)
+                && delta.packets > 0
+            {
+                let total = per_backend.entry(backend_id.clone()).or_default();
+                *total = total.add(&delta);
+            }
+            pending.insert(*key, *current);
+        }
+
+        self.pending = pending;
+        per_backend
+    }
+
+    /// Take the last observed counters as reported
+    pub fn commit(&mut self) {
+        self.last = std::mem::take(&mut self.pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(last: u8, port: u16) -> TenantDstV6Key {
+        TenantDstV6Key {
+            addr: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 192, 0, 2, last],
+            port,
+            _pad: 0,
+        }
+    }
+
+    fn traffic(packets: u64, bytes: u64, dropped_bytes: u64) -> DstTraffic {
+        DstTraffic {
+            packets,
+            bytes,
+            dropped_packets: u64::from(dropped_bytes > 0),
+            dropped_bytes,
+        }
+    }
+
+    #[test]
+    fn test_map_keys() {
+        let mut backends = HashMap::new();
+        let destination = |addr: &str| TenantDestination {
+            addr: addr.parse().unwrap(),
+            port: 25565,
+        };
+        backends.insert("a".to_string(), vec![destination("192.0.2.10")]);
+        backends.insert("b".to_string(), vec![destination("192.0.2.11")]);
+
+        let keys = traffic_map_keys(&backends);
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0], (key(10, 25565), "a".to_string()));
+        assert_eq!(keys[1], (key(11, 25565), "b".to_string()));
+    }
+
+    #[test]
+    fn test_meter_reports_deltas() {
+        let owners: HashMap<_, _> = [(key(10, 0), "a".to_string()), (key(11, 0), "a".to_string())]
+            .into_iter()
+            .collect();
+        let mut meter = TrafficMeter::new();
+
+        let usage = meter.observe(
+            &[
+                (key(10, 0), traffic(10, 1000, 0)),
+                (key(11, 0), traffic(5, 500, 100)),
+            ],
+            &owners,
+        );
+        assert_eq!(usage["a"].bytes, 1500);
+        assert_eq!(usage["a"].clean_bytes(), 1400);
+        meter.commit();
+
+        let usage = meter.observe(
+            &[
+                (key(10, 0), traffic(12, 1200, 0)),
+                (key(11, 0), traffic(5, 500, 100)),
+            ],
+            &owners,
+        );
+        assert_eq!(usage["a"].packets, 2);
+        assert_eq!(usage["a"].bytes, 200);
+        meter.commit();
+
+        // Idle backends are not reported
+        let usage = meter.observe(&[(key(10, 0), traffic(12, 1200, 0))], &owners);
+        assert!(usage.is_empty());
+    }
+
+    #[test]
+    fn test_meter_reports_again_until_committed() {
+        let owners: HashMap<_, _> = [(key(10, 0), "a".to_string())].into_iter().collect();
+        let mut meter = TrafficMeter::new();
+        meter.observe(&[(key(10, 0), traffic(10, 1000, 0))], &owners);
+
+        // The first report failed, so the second one carries both
+        let usage = meter.observe(&[(key(10, 0), traffic(15, 1500, 0))], &owners);
+        assert_eq!(usage["a"].bytes, 1500);
+        meter.commit();
+
+        let usage = meter.observe(&[(key(10, 0), traffic(16, 1600, 0))], &owners);
+        assert_eq!(usage["a"].bytes, 100);
+    }
+
+    #[test]
+    fn test_meter_recreated_entry() {
+        let owners: HashMap<_, _> = [(key(10, 0), "a".to_string())].into_iter().collect();
+        let mut meter = TrafficMeter::new();
+        meter.observe(&[(key(10, 0), traffic(100, 10_000, 0))], &owners);
+        meter.commit();
+
+        // The program was reloaded and counts from zero again
+        let usage = meter.observe(&[(key(10, 0), traffic(3, 300, 0))], &owners);
+        assert_eq!(usage["a"].bytes, 300);
+    }
+
+    #[test]
+    fn test_layout_matches_kernel() {
+        assert_eq!(std::mem::size_of::<DstTraffic>(), 32);
+    }
+}
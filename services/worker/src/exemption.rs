@@ -0,0 +1,239 @@
+//! Exemption Tokens
+//!
+//! Applies the load test exemption tokens published by the gateway (see
+//! `pistonprotection_common::exemption`). The tester addresses of every
+//! active token are written to the whitelist maps of the layer 4 XDP
+//! programs, and each token is exported in the `exemption_token_active`
+//! metric, so dashboards and the attack detector can tell the test traffic
+//! of its backend apart.
+//!
+//! Expired tokens are dropped here even while the gateway still publishes
+//! them, so an address is never whitelisted past its token because the
+//! gateway is unreachable.
+
+use parking_lot::RwLock;
+use pistonprotection_common::error::Result;
+use pistonprotection_common::exemption::{ExemptionToken, exempt_backends, exempt_ips};
+use pistonprotection_common::metrics::EXEMPTION_TOKEN_ACTIVE;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+
+/// Exemption token applied or dropped on this worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExemptionChange {
+    pub token_id: String,
+    pub backend_id: String,
+    /// Addresses now exempted, unset once the token was dropped
+    pub ips: Option<usize>,
+    /// The token reached its expiry
+    pub expired: bool,
+}
+
+/// Exemption token applied on this worker
+#[derive(Debug, Clone, Serialize)]
+pub struct ExemptionStatus {
+    #[serde(flatten)]
+    pub token: ExemptionToken,
+    /// Seconds until the addresses are filtered again
+    pub expires_in_secs: i64,
+}
+
+/// Exemption tokens applied to the XDP maps.
+#[derive(Default)]
+pub struct ExemptionTokens {
+    /// Tokens keyed by token ID
+    applied: RwLock<HashMap<String, ExemptionToken>>,
+}
+
+impl ExemptionTokens {
+    /// Create a tracker without tokens: every source is filtered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the applied tokens.
+    ///
+    /// Tokens expired at `now` are dropped. The addresses of the remaining
+    /// ones are handed to `write_xdp`; if it fails the previous tokens stay
+    /// in place and the error is returned.
+    pub fn apply<W>(
+        &self,
+        published: Vec<ExemptionToken>,
+        now: i64,
+        write_xdp: W,
+    ) -> Result<Vec<ExemptionChange>>
+    where
+        W: FnOnce(&BTreeSet<IpAddr>) -> Result<()>,
+    {
+        let mut next = HashMap::new();
+        let mut expired = Vec::new();
+        for token in published {
+            if token.expired(now) {
+                expired.push(token.id);
+                continue;
+            }
+            next.insert(token.id.clone(), token);
+        }
+
+        let mut applied = self.applied.write();
+        let tokens: Vec<ExemptionToken> = next.values().cloned().collect();
+        write_xdp(&exempt_ips(&tokens, now))?;
+
+        let mut changes = Vec::new();
+        for (token_id, token) in applied.iter() {
+            if !next.contains_key(token_id) {
+                changes.push(ExemptionChange {
+                    token_id: token_id.clone(),
+                    backend_id: token.backend_id.clone(),
+                    ips: None,
+                    expired: expired.contains(token_id) || token.expired(now),
+                });
+            }
+        }
+        for (token_id, token) in &next {
+            if applied.get(token_id).map(|current| &current.ips) != Some(&token.ips) {
+                changes.push(ExemptionChange {
+                    token_id: token_id.clone(),
+                    backend_id: token.backend_id.clone(),
+                    ips: Some(token.ips.len()),
+                    expired: false,
+                });
+            }
+        }
+
+        EXEMPTION_TOKEN_ACTIVE.reset();
+        for token in next.values() {
+            EXEMPTION_TOKEN_ACTIVE
+                .with_label_values(&[token.backend_id.as_str(), token.id.as_str()])
+                .set(token.ips.len() as f64);
+        }
+
+        *applied = next;
+        Ok(changes)
+    }
+
+    /// Tokens currently applied.
+    pub fn tokens(&self) -> Vec<ExemptionToken> {
+        self.applied.read().values().cloned().collect()
+    }
+
+    /// Backends whose traffic is kept out of attack detection baselines.
+    pub fn exempt_backends(&self, now: i64) -> BTreeSet<String> {
+        exempt_backends(&self.tokens(), now)
+    }
+
+    /// Tokens applied on this worker.
+    pub fn status(&self, now: i64) -> Vec<ExemptionStatus> {
+        let mut status: Vec<ExemptionStatus> = self
+            .applied
+            .read()
+            .values()
+            .map(|token| ExemptionStatus {
+                expires_in_secs: (token.expires_at - now).max(0),
+                token: token.clone(),
+            })
+            .collect();
+        status.sort_by(|a, b| {
+            (&a.token.backend_id, &a.token.id).cmp(&(&b.token.backend_id, &b.token.id))
+        });
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pistonprotection_common::error::Error;
+
+    fn token(id: &str, ips: &[&str], expires_at: i64) -> ExemptionToken {
+        ExemptionToken {
+            id: id.to_string(),
+            backend_id: "b1".to_string(),
+            ips: ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+            reason: "Load test".to_string(),
+            created_by: "user-1".to_string(),
+            created_at: 0,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_applies_tokens() {
+        let tracker = ExemptionTokens::new();
+        let mut written = BTreeSet::new();
+
+        let changes = tracker
+            .apply(
+                vec![
+                    token("t1", &["203.0.113.7", "2001:db8::1"], 600),
+                    token("t2", &["203.0.113.7"], 900),
+                ],
+                100,
+                |ips| {
+                    written = ips.clone();
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert_eq!(written.len(), 2);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(tracker.status(100)[0].expires_in_secs, 500);
+        assert_eq!(
+            tracker.exempt_backends(100),
+            BTreeSet::from(["b1".to_string()])
+        );
+
+        // Republishing the same tokens is not a change
+        let changes = tracker
+            .apply(
+                vec![
+                    token("t1", &["203.0.113.7", "2001:db8::1"], 600),
+                    token("t2", &["203.0.113.7"], 900),
+                ],
+                200,
+                |_| Ok(()),
+            )
+            .unwrap();
+        assert!(changes.is_empty());
+
+        let changes = tracker
+            .apply(vec![token("t2", &["203.0.113.7"], 900)], 200, |_| Ok(()))
+            .unwrap();
+        assert_eq!(changes[0].token_id, "t1");
+        assert_eq!(changes[0].ips, None);
+        assert!(!changes[0].expired);
+    }
+
+    #[test]
+    fn test_expired_token_dropped() {
+        let tracker = ExemptionTokens::new();
+        tracker
+            .apply(vec![token("t1", &["203.0.113.7"], 600)], 100, |_| Ok(()))
+            .unwrap();
+
+        // The gateway still publishes it, but its time is up
+        let mut written = None;
+        let changes = tracker
+            .apply(vec![token("t1", &["203.0.113.7"], 600)], 600, |ips| {
+                written = Some(ips.len());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(written, Some(0));
+        assert!(changes[0].expired);
+        assert!(tracker.exempt_backends(600).is_empty());
+    }
+
+    #[test]
+    fn test_failed_write_keeps_tokens() {
+        let tracker = ExemptionTokens::new();
+        tracker
+            .apply(vec![token("t1", &["203.0.113.7"], 600)], 100, |_| Ok(()))
+            .unwrap();
+
+        let result = tracker.apply(vec![], 200, |_| Err(Error::Internal("map".to_string())));
+        assert!(result.is_err());
+        assert_eq!(tracker.tokens().len(), 1);
+    }
+}
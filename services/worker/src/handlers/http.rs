@@ -4,15 +4,60 @@
 //! - Health checks (liveness and readiness probes)
 //! - Prometheus metrics
 //! - Worker status and configuration information
-//! - Administrative operations (IP blocking, config refresh)
+//! - Administrative operations (IP blocking, config refresh, canary evaluation,
+//!   flow sampling, drop event summaries, the event budget, processing
+//!   latency, map capacity, top sources, packet capture, threat intelligence
+//!   feeds, offender bundles, source reputation, honeypot ports, allowlist
+//!   learning, Minecraft identity limits, origin switches, origin connection
+//!   pools, origin response anomalies, backend modes, rate limit profiles,
+//!   CGNAT ranges, kernel SYN pressure, conntrack bypass marking, the
+//!   enforcement backend, connection table dumps, observe mode, QUIC 0-RTT
+//!   rejection, QUIC connection ID rate limits, DNS query budgets, warm-up
+//!   snapshots and runtime log directives)
 
 use super::WorkerState;
+use crate::backend_mode::BackendModeStatus;
+use crate::canary::CanaryReport;
+use crate::ebpf::artifacts::ArtifactStatus;
+use crate::ebpf::capacity::CapacityReport;
+use crate::ebpf::cgnat::CgnatStatus;
+use crate::ebpf::conn_table::{
+    self, ConnectionFilter, ConnectionTable, DEFAULT_DUMP_LIMIT, DumpCursor,
+};
+use crate::ebpf::diagnostics::LoadDiagnostic;
+use crate::ebpf::dns::DnsBudget;
+use crate::ebpf::drop_events::DropEventStatus;
+use crate::ebpf::event_budget::EventBudgetReport;
+use crate::ebpf::flow_mark::FlowMarkStatus;
+use crate::ebpf::honeypot::{FlaggedSource, HoneypotPorts};
+use crate::ebpf::latency::LatencyHistogram;
+use crate::ebpf::learning::LearningStatus;
+use crate::ebpf::marking::MarkingStatus;
+use crate::ebpf::offender_bundle::{BundleChange, BundleStatus};
+use crate::ebpf::protected_ports::ProtectedPorts;
+use crate::ebpf::quic_cid::QuicCidLimit;
+use crate::ebpf::sampling::{CaptureStatus, SamplingReport};
+use crate::ebpf::selftest::{self, SelfTestReport};
+use crate::ebpf::sources::SourceTraffic;
+use crate::ebpf::threat_intel::FeedStatus;
+use crate::exemption::ExemptionStatus;
+use crate::kernel_tcp::SynFloodReport;
+use crate::log_levels::LogLevelStatus;
+use crate::nftables::EnforcementStatus;
+use crate::protocol::minecraft_identity::IdentityThrottleStatus;
+use crate::proxy::anomaly::AnomalyStatus;
+use crate::rate_profile::ActiveProfile;
+use crate::reputation::{ReputationEvent, ReputationStatus};
+use crate::restart_mode::RestartStatus;
+use crate::routing::origin_switch::OriginSwitchStatus;
+use crate::routing::pool::OriginPoolStats;
+use crate::warmup::{WarmupReport, WarmupSnapshot, WarmupStatus};
 use axum::{
     Json, Router,
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{delete, get, post},
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
 };
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
@@ -40,11 +85,73 @@ pub fn create_router(state: WorkerState) -> Router {
         .route("/status/connection", get(connection_status))
         .route("/status/config", get(config_status))
         .route("/status/interfaces", get(interfaces_status))
+        .route("/status/sampling", get(sampling_status))
+        .route("/status/drop-events", get(drop_event_status))
+        .route("/status/event-budget", get(event_budget_status))
+        .route("/status/latency", get(latency_status))
+        .route("/status/map-capacity", get(map_capacity_status))
+        .route("/status/top-sources", get(top_sources_status))
+        .route("/status/threat-intel", get(threat_intel_status))
+        .route("/status/offender-bundles", get(offender_bundle_status))
+        .route("/status/reputation", get(reputation_status))
+        .route("/status/honeypot", get(honeypot_status))
+        .route("/status/learning", get(learning_status))
+        .route("/status/marking", get(marking_status))
+        .route("/status/protected-ports", get(protected_ports_status))
+        .route("/status/dns-budget", get(dns_budget_status))
+        .route("/status/observe", get(observe_status))
+        .route("/status/quic-0rtt", get(quic_0rtt_status))
+        .route("/status/quic-cid-limit", get(quic_cid_limit_status))
+        .route(
+            "/status/minecraft-identities",
+            get(minecraft_identity_status),
+        )
+        .route("/status/origin-switches", get(origin_switch_status))
+        .route("/status/origin-pools", get(origin_pool_status))
+        .route("/status/origin-anomalies", get(origin_anomaly_status))
+        .route("/status/backend-modes", get(backend_mode_status))
+        .route("/status/rate-profiles", get(rate_profile_status))
+        .route("/status/restart-modes", get(restart_mode_status))
+        .route("/status/exemption-tokens", get(exemption_token_status))
+        .route("/status/cgnat", get(cgnat_status))
+        .route("/status/syn-flood", get(syn_flood_status))
+        .route("/status/flow-marks", get(flow_mark_status))
+        .route("/status/enforcement", get(enforcement_status))
+        .route("/status/load-diagnostics", get(load_diagnostics_status))
+        .route("/status/artifacts", get(artifact_status))
+        .route("/status/warmup", get(warmup_status))
         // Admin endpoints
         .route("/admin/blocked-ips", get(list_blocked_ips))
         .route("/admin/blocked-ips", post(block_ip))
         .route("/admin/blocked-ips/:ip", delete(unblock_ip))
         .route("/admin/refresh-config", post(refresh_config))
+        .route("/admin/canary", get(canary_report))
+        .route("/admin/canary", delete(abort_canary))
+        .route("/admin/canary/promote", post(promote_canary))
+        .route("/admin/sampling/:program", put(set_sampling_rate))
+        .route("/admin/latency/:program", put(set_latency_rate))
+        .route("/admin/capture", post(start_capture))
+        .route("/admin/capture", get(download_capture))
+        .route("/admin/selftest", post(run_selftest))
+        .route("/admin/threat-intel/:feed", put(set_threat_feed))
+        .route("/admin/offender-bundles/pin", put(pin_offender_bundle))
+        .route(
+            "/admin/offender-bundles/rollback",
+            post(rollback_offender_bundle),
+        )
+        .route("/admin/reputation/events", post(record_reputation_event))
+        .route("/admin/reputation/:ip", get(reputation_score))
+        .route("/admin/connections", get(dump_connections))
+        .route("/admin/protected-ports", put(set_protected_ports))
+        .route("/admin/dns-budget", put(set_dns_budget))
+        .route("/admin/observe", put(set_observe_mode))
+        .route("/admin/quic-0rtt", put(set_quic_0rtt))
+        .route("/admin/quic-cid-limit", put(set_quic_cid_limit))
+        .route("/admin/warmup-snapshot", get(export_warmup_snapshot))
+        .route("/admin/warmup-snapshot", put(import_warmup_snapshot))
+        .route("/admin/log-level", get(log_level_status))
+        .route("/admin/log-level", put(set_log_level))
+        .route("/admin/log-level", delete(clear_log_level))
         // Add middleware layers
         .layer(TraceLayer::new_for_http())
         .layer(cors)
@@ -516,6 +623,461 @@ async fn refresh_config(State(state): State<WorkerState>) -> impl IntoResponse {
     )
 }
 
+// ============================================================================
+// Canary Evaluation Handlers
+// ============================================================================
+
+/// Canary evaluation response
+#[derive(Serialize)]
+struct CanaryResponse {
+    success: bool,
+    message: String,
+    report: Option<CanaryReport>,
+}
+
+/// Get the comparison report of the running canary evaluation
+async fn canary_report(State(state): State<WorkerState>) -> impl IntoResponse {
+    match state.config_sync.canary_report() {
+        Some(report) => (
+            StatusCode::OK,
+            Json(CanaryResponse {
+                success: true,
+                message: format!("Canary evaluation of version {}", report.candidate_version),
+                report: Some(report),
+            }),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(CanaryResponse {
+                success: false,
+                message: "No canary evaluation running".to_string(),
+                report: None,
+            }),
+        ),
+    }
+}
+
+/// Enforce the candidate configuration under evaluation
+async fn promote_canary(State(state): State<WorkerState>) -> impl IntoResponse {
+    match state.config_sync.promote_canary().await {
+        Ok(report) => (
+            StatusCode::OK,
+            Json(CanaryResponse {
+                success: true,
+                message: format!(
+                    "Configuration version {} promoted",
+                    report.candidate_version
+                ),
+                report: Some(report),
+            }),
+        ),
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(CanaryResponse {
+                success: false,
+                message: format!("Failed to promote canary: {}", e),
+                report: None,
+            }),
+        ),
+    }
+}
+
+/// Stop the running canary evaluation without enforcing it
+async fn abort_canary(State(state): State<WorkerState>) -> impl IntoResponse {
+    match state.config_sync.abort_canary() {
+        Ok(report) => (
+            StatusCode::OK,
+            Json(CanaryResponse {
+                success: true,
+                message: format!(
+                    "Canary evaluation of version {} aborted",
+                    report.candidate_version
+                ),
+                report: Some(report),
+            }),
+        ),
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(CanaryResponse {
+                success: false,
+                message: format!("Failed to abort canary: {}", e),
+                report: None,
+            }),
+        ),
+    }
+}
+
+// ============================================================================
+// Flow Sampling Handlers
+// ============================================================================
+
+/// Sampling rate of a loaded program
+#[derive(Serialize)]
+struct ProgramSamplingRate {
+    program: String,
+    rate: u32,
+}
+
+/// Flow sampling status response
+#[derive(Serialize)]
+struct SamplingStatusResponse {
+    rates: Vec<ProgramSamplingRate>,
+    reports: Vec<SamplingReport>,
+    capture: Option<CaptureStatus>,
+}
+
+/// Get sampling rates, protocol mix reports and capture progress
+async fn sampling_status(State(state): State<WorkerState>) -> impl IntoResponse {
+    let mut rates: Vec<_> = {
+        let loader = state.loader.read();
+        loader
+            .sampled_programs()
+            .into_iter()
+            .map(|program| ProgramSamplingRate {
+                rate: loader.sampling_rate(&program),
+                program,
+            })
+            .collect()
+    };
+    rates.sort_by(|a, b| a.program.cmp(&b.program));
+
+    (
+        StatusCode::OK,
+        Json(SamplingStatusResponse {
+            rates,
+            reports: state.sampler.reports(),
+            capture: state.sampler.capture_status(),
+        }),
+    )
+}
+
+/// Get the drop event aggregation settings and the most recent summaries
+async fn drop_event_status(State(state): State<WorkerState>) -> Json<DropEventStatus> {
+    Json(state.drop_events.status())
+}
+
+/// Get the event budget of each program and the events it suppressed
+async fn event_budget_status(State(state): State<WorkerState>) -> Json<Vec<EventBudgetReport>> {
+    let loader = state.loader.read();
+    let mut reports: Vec<_> = loader
+        .loaded_programs()
+        .iter()
+        .filter_map(|program| loader.read_event_budget(program).ok())
+        .collect();
+    reports.sort_by(|a, b| a.program.cmp(&b.program));
+    Json(reports)
+}
+
+/// Set sampling rate request
+#[derive(Deserialize)]
+struct SetSamplingRateRequest {
+    /// Sample 1 in `rate` passed packets (0 disables sampling)
+    rate: u32,
+}
+
+/// Sampling response
+#[derive(Serialize)]
+struct SamplingResponse {
+    success: bool,
+    message: String,
+}
+
+/// Change the sampling rate of a program
+async fn set_sampling_rate(
+    State(state): State<WorkerState>,
+    Path(program): Path<String>,
+    Json(request): Json<SetSamplingRateRequest>,
+) -> impl IntoResponse {
+    match state
+        .loader
+        .write()
+        .set_sampling_rate(&program, request.rate)
+    {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(SamplingResponse {
+                success: true,
+                message: format!("Sampling rate of {} set to 1/{}", program, request.rate),
+            }),
+        ),
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(SamplingResponse {
+                success: false,
+                message: format!("Failed to set sampling rate: {}", e),
+            }),
+        ),
+    }
+}
+
+/// Latency measurement of one program
+#[derive(Serialize)]
+struct ProgramLatency {
+    program: String,
+    rate: u32,
+    histograms: Vec<LatencyHistogram>,
+}
+
+/// Get latency measurement rates and processing time histograms
+async fn latency_status(State(state): State<WorkerState>) -> impl IntoResponse {
+    let loader = state.loader.read();
+    let mut programs: Vec<_> = loader
+        .loaded_programs()
+        .into_iter()
+        .map(|program| ProgramLatency {
+            rate: loader.latency_rate(&program),
+            histograms: loader.read_latency(&program).unwrap_or_default(),
+            program,
+        })
+        .collect();
+    programs.sort_by(|a, b| a.program.cmp(&b.program));
+
+    (StatusCode::OK, Json(programs))
+}
+
+/// Get memory use, occupancy and headroom of every eBPF map
+async fn map_capacity_status(State(state): State<WorkerState>) -> Response {
+    match CapacityReport::from_loader(state.loader.clone()).await {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(MapCapacityError {
+                success: false,
+                message: format!("Failed to collect map capacity: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Map capacity failure response
+#[derive(Serialize)]
+struct MapCapacityError {
+    success: bool,
+    message: String,
+}
+
+/// Top sources query
+#[derive(Deserialize)]
+struct TopSourcesQuery {
+    #[serde(default = "default_top_sources")]
+    limit: usize,
+}
+
+fn default_top_sources() -> usize {
+    50
+}
+
+/// Top sources response
+#[derive(Serialize)]
+struct TopSourcesResponse {
+    /// Sources counted per program
+    tracked: Vec<TrackedSources>,
+    sources: Vec<SourceTraffic>,
+}
+
+/// Sources counted by one program
+#[derive(Serialize)]
+struct TrackedSources {
+    program: String,
+    sources: usize,
+}
+
+/// Get the sources sending the most packets, from the last merge of the
+/// per-CPU source counters
+async fn top_sources_status(
+    State(state): State<WorkerState>,
+    Query(query): Query<TopSourcesQuery>,
+) -> impl IntoResponse {
+    let loader = state.loader.read();
+    let tracked = loader
+        .tracked_sources()
+        .into_iter()
+        .map(|(program, sources)| TrackedSources { program, sources })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(TopSourcesResponse {
+            tracked,
+            sources: loader.top_sources(query.limit),
+        }),
+    )
+}
+
+/// Set latency measurement rate request
+#[derive(Deserialize)]
+struct SetLatencyRateRequest {
+    /// Time 1 in `rate` packets (0 disables measurement)
+    rate: u32,
+}
+
+/// Latency measurement response
+#[derive(Serialize)]
+struct LatencyResponse {
+    success: bool,
+    message: String,
+}
+
+/// Change the latency measurement rate of a program
+async fn set_latency_rate(
+    State(state): State<WorkerState>,
+    Path(program): Path<String>,
+    Json(request): Json<SetLatencyRateRequest>,
+) -> impl IntoResponse {
+    match state
+        .loader
+        .write()
+        .set_latency_rate(&program, request.rate)
+    {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(LatencyResponse {
+                success: true,
+                message: format!(
+                    "Latency measurement rate of {} set to 1/{}",
+                    program, request.rate
+                ),
+            }),
+        ),
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(LatencyResponse {
+                success: false,
+                message: format!("Failed to set latency measurement rate: {}", e),
+            }),
+        ),
+    }
+}
+
+/// Start capture request
+#[derive(Deserialize)]
+struct StartCaptureRequest {
+    /// Only capture samples from this program
+    #[serde(default)]
+    program: Option<String>,
+    #[serde(default = "default_capture_samples")]
+    max_samples: usize,
+}
+
+fn default_capture_samples() -> usize {
+    1000
+}
+
+/// Start capturing sampled packets, replacing any capture in progress
+async fn start_capture(
+    State(state): State<WorkerState>,
+    Json(request): Json<StartCaptureRequest>,
+) -> impl IntoResponse {
+    let status = state
+        .sampler
+        .start_capture(request.program, request.max_samples);
+
+    (StatusCode::OK, Json(status))
+}
+
+/// Stop the capture in progress and download it as pcap
+async fn download_capture(State(state): State<WorkerState>) -> Response {
+    match state.sampler.finish_capture() {
+        Some(capture) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "application/vnd.tcpdump.pcap"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"capture.pcap\"",
+                ),
+            ],
+            capture.to_pcap(),
+        )
+            .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(SamplingResponse {
+                success: false,
+                message: "No capture in progress".to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Self test request
+#[derive(Deserialize, Default)]
+struct SelfTestRequest {
+    /// Protected destination (`ip:port`) the synthetic packets are sent to
+    #[serde(default)]
+    target: Option<String>,
+}
+
+/// Self test response
+#[derive(Serialize)]
+struct SelfTestResponse {
+    success: bool,
+    message: String,
+    report: Option<SelfTestReport>,
+}
+
+/// Run synthetic packets through the loaded programs and report whether
+/// each verdict is the expected one
+///
+/// Responds 200 if every check that ran passed, 409 otherwise.
+async fn run_selftest(
+    State(state): State<WorkerState>,
+    request: Option<Json<SelfTestRequest>>,
+) -> impl IntoResponse {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let target = match request.target.as_deref().map(str::parse).transpose() {
+        Ok(target) => target.unwrap_or(selftest::DEFAULT_TARGET),
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(SelfTestResponse {
+                    success: false,
+                    message: format!("Invalid target: {}", e),
+                    report: None,
+                }),
+            );
+        }
+    };
+
+    let report = {
+        let loader = state.loader.read();
+        selftest::run(
+            target,
+            selftest::checks(target),
+            &loader.loaded_programs(),
+            |check| loader.test_run(check.program, &check.packet, check.repeat),
+        )
+    };
+
+    tracing::info!(
+        passed = report.passed,
+        failed = report.failed,
+        skipped = report.skipped,
+        errors = report.errors,
+        "Self test finished"
+    );
+
+    let message = format!(
+        "{} passed, {} failed, {} skipped, {} errors",
+        report.passed, report.failed, report.skipped, report.errors
+    );
+    let (status, success) = if report.success() {
+        (StatusCode::OK, true)
+    } else {
+        (StatusCode::CONFLICT, false)
+    };
+    (
+        status,
+        Json(SelfTestResponse {
+            success,
+            message,
+            report: Some(report),
+        }),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -536,3 +1098,902 @@ mod tests {
         assert_eq!(request.duration_secs, None);
     }
 }
+
+/// Threat intelligence status response
+#[derive(Serialize)]
+struct ThreatIntelStatusResponse {
+    feeds: Vec<FeedStatus>,
+}
+
+/// Get fetch status, entry counts and hit counters of the threat feeds
+async fn threat_intel_status(State(state): State<WorkerState>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(ThreatIntelStatusResponse {
+            feeds: state.threat_intel.status(),
+        }),
+    )
+}
+
+/// Update threat feed request
+#[derive(Deserialize)]
+struct SetThreatFeedRequest {
+    /// Drop traffic from the feed's networks (false = count only)
+    enabled: bool,
+}
+
+/// Threat feed response
+#[derive(Serialize)]
+struct ThreatFeedResponse {
+    success: bool,
+    message: String,
+}
+
+/// Switch a threat feed between enforcing and count-only
+async fn set_threat_feed(
+    State(state): State<WorkerState>,
+    Path(feed): Path<String>,
+    Json(request): Json<SetThreatFeedRequest>,
+) -> impl IntoResponse {
+    if !state.threat_intel.set_enabled(&feed, request.enabled) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ThreatFeedResponse {
+                success: false,
+                message: format!("Threat feed {} is not configured", feed),
+            }),
+        );
+    }
+
+    // Apply right away rather than on the next import tick
+    let entries = state.threat_intel.map_entries();
+    if let Err(e) = state.loader.write().set_threat_intel(&entries) {
+        tracing::debug!(error = %e, "Threat feed change not programmed yet");
+    }
+
+    let mode = if request.enabled {
+        "enforcing"
+    } else {
+        "count-only"
+    };
+    (
+        StatusCode::OK,
+        Json(ThreatFeedResponse {
+            success: true,
+            message: format!("Threat feed {} is now {}", feed, mode),
+        }),
+    )
+}
+
+/// Get the verified offender bundles, the loaded version and its drop rate
+async fn offender_bundle_status(State(state): State<WorkerState>) -> Json<BundleStatus> {
+    Json(state.bundles.status(chrono::Utc::now()))
+}
+
+/// Pin offender bundle request
+#[derive(Deserialize)]
+struct PinOffenderBundleRequest {
+    /// Version to load whatever is newer, unset to follow the newest again
+    version: Option<u64>,
+}
+
+/// Offender bundle response
+#[derive(Serialize)]
+struct OffenderBundleResponse {
+    success: bool,
+    message: String,
+    /// Version loaded after the change
+    active: Option<u64>,
+}
+
+/// Load the selected offender bundle and program it right away
+fn apply_offender_bundle(state: &WorkerState) -> (StatusCode, Json<OffenderBundleResponse>) {
+    match state
+        .bundles
+        .reconcile(&state.threat_intel, chrono::Utc::now())
+    {
+        Ok(change) => {
+            let entries = state.threat_intel.map_entries();
+            if let Err(e) = state.loader.write().set_threat_intel(&entries) {
+                tracing::debug!(error = %e, "Offender bundle change not programmed yet");
+            }
+            let message = match change {
+                Some(BundleChange::Loaded { version, .. }) => {
+                    format!("Loaded offender bundle version {}", version)
+                }
+                Some(BundleChange::Unloaded { previous }) => format!(
+                    "Unloaded offender bundle version {}, no other version to load",
+                    previous
+                ),
+                None => "Loaded offender bundle unchanged".to_string(),
+            };
+            (
+                StatusCode::OK,
+                Json(OffenderBundleResponse {
+                    success: true,
+                    message,
+                    active: state.bundles.active(),
+                }),
+            )
+        }
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(OffenderBundleResponse {
+                success: false,
+                message: format!("Failed to load offender bundle: {}", e),
+                active: state.bundles.active(),
+            }),
+        ),
+    }
+}
+
+/// Pin an offender bundle version, e.g. to hold back a new bundle, or unpin
+async fn pin_offender_bundle(
+    State(state): State<WorkerState>,
+    Json(request): Json<PinOffenderBundleRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = state.bundles.pin(request.version) {
+        return (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(OffenderBundleResponse {
+                success: false,
+                message: format!("Failed to pin offender bundle: {}", e),
+                active: state.bundles.active(),
+            }),
+        );
+    }
+    tracing::warn!(version = ?request.version, "Offender bundle pinned through the admin API");
+    apply_offender_bundle(&state)
+}
+
+/// Roll back offender bundle request
+#[derive(Deserialize, Default)]
+struct RollbackOffenderBundleRequest {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Reject the loaded offender bundle and load the previous version
+async fn rollback_offender_bundle(
+    State(state): State<WorkerState>,
+    request: Option<Json<RollbackOffenderBundleRequest>>,
+) -> impl IntoResponse {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let reason = request
+        .reason
+        .unwrap_or_else(|| "rolled back through the admin API".to_string());
+    let Some(version) = state.bundles.rollback(&reason) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(OffenderBundleResponse {
+                success: false,
+                message: "No offender bundle is loaded".to_string(),
+                active: None,
+            }),
+        );
+    };
+    tracing::warn!(version, reason = %reason, "Offender bundle rolled back through the admin API");
+    apply_offender_bundle(&state)
+}
+
+/// Get the reputation store, recent activity and greylisted sources
+async fn reputation_status(State(state): State<WorkerState>) -> Json<ReputationStatus> {
+    Json(state.reputation.status())
+}
+
+/// Honeypot ports of a backend
+#[derive(Serialize)]
+struct HoneypotBackendStatus {
+    backend_id: String,
+    #[serde(flatten)]
+    settings: HoneypotPorts,
+}
+
+/// Honeypot status response
+#[derive(Serialize)]
+struct HoneypotStatusResponse {
+    backends: Vec<HoneypotBackendStatus>,
+    /// Sources seen on honeypot ports, newest last
+    flagged: Vec<FlaggedSource>,
+}
+
+/// Get the configured honeypot ports and the sources they caught
+async fn honeypot_status(State(state): State<WorkerState>) -> Json<HoneypotStatusResponse> {
+    let (backends, flagged) = state.honeypots();
+    Json(HoneypotStatusResponse {
+        backends: backends
+            .into_iter()
+            .map(|(backend_id, settings)| HoneypotBackendStatus {
+                backend_id,
+                settings,
+            })
+            .collect(),
+        flagged,
+    })
+}
+
+/// Get the allowlist learning state of every backend in learning mode
+async fn learning_status(State(state): State<WorkerState>) -> Json<Vec<LearningStatus>> {
+    Json(state.learning_status())
+}
+
+/// Get the marking policy and marked traffic of every backend marking its
+/// greylisted traffic
+async fn marking_status(State(state): State<WorkerState>) -> Json<Vec<MarkingStatus>> {
+    Json(state.marking_status())
+}
+
+/// Get the protected ports set by the operator
+async fn protected_ports_status(State(state): State<WorkerState>) -> Json<ProtectedPorts> {
+    Json(state.loader.read().protected_ports().clone())
+}
+
+/// Protected ports response
+#[derive(Serialize)]
+struct ProtectedPortsResponse {
+    success: bool,
+    message: String,
+}
+
+/// Replace the protected ports of xdp_tcp and xdp_udp
+async fn set_protected_ports(
+    State(state): State<WorkerState>,
+    Json(request): Json<ProtectedPorts>,
+) -> impl IntoResponse {
+    let (tcp, udp) = (request.tcp.len(), request.udp.len());
+    match state.loader.write().set_protected_ports(request) {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ProtectedPortsResponse {
+                success: true,
+                message: format!("Protecting {} TCP and {} UDP ports", tcp, udp),
+            }),
+        ),
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ProtectedPortsResponse {
+                success: false,
+                message: format!("Failed to set protected ports: {}", e),
+            }),
+        ),
+    }
+}
+
+/// Get the per-client DNS query budgets of protected resolvers
+async fn dns_budget_status(State(state): State<WorkerState>) -> Json<DnsBudget> {
+    Json(state.loader.read().dns_budget().clone())
+}
+
+/// DNS query budget response
+#[derive(Serialize)]
+struct DnsBudgetResponse {
+    success: bool,
+    message: String,
+}
+
+/// Set the per-client DNS-over-TCP and DNS-over-HTTPS budgets
+async fn set_dns_budget(
+    State(state): State<WorkerState>,
+    Json(request): Json<DnsBudget>,
+) -> impl IntoResponse {
+    let (tcp_qps, doh_qps) = (request.tcp_qps, request.doh_qps);
+    match state.loader.write().set_dns_budget(request) {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(DnsBudgetResponse {
+                success: true,
+                message: format!(
+                    "DNS budgets set to {} TCP and {} DoH queries per second per client",
+                    tcp_qps, doh_qps
+                ),
+            }),
+        ),
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(DnsBudgetResponse {
+                success: false,
+                message: format!("Failed to set DNS budgets: {}", e),
+            }),
+        ),
+    }
+}
+
+/// Observe mode of the worker
+#[derive(Serialize, Deserialize)]
+struct ObserveMode {
+    enabled: bool,
+}
+
+/// Get whether the worker is in observe mode
+async fn observe_status(State(state): State<WorkerState>) -> Json<ObserveMode> {
+    Json(ObserveMode {
+        enabled: state.loader.read().observe_mode(),
+    })
+}
+
+/// Observe mode response
+#[derive(Serialize)]
+struct ObserveModeResponse {
+    success: bool,
+    message: String,
+}
+
+/// Switch observe mode, passing the packets the programs would drop
+async fn set_observe_mode(
+    State(state): State<WorkerState>,
+    Json(request): Json<ObserveMode>,
+) -> impl IntoResponse {
+    match state.loader.write().set_observe_mode(request.enabled) {
+        Ok(()) => {
+            tracing::warn!(
+                enabled = request.enabled,
+                "Observe mode switched through the admin API"
+            );
+            (
+                StatusCode::OK,
+                Json(ObserveModeResponse {
+                    success: true,
+                    message: if request.enabled {
+                        "Observe mode on, drops are only counted".to_string()
+                    } else {
+                        "Observe mode off, drops are enforced".to_string()
+                    },
+                }),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ObserveModeResponse {
+                success: false,
+                message: format!("Failed to switch observe mode: {}", e),
+            }),
+        ),
+    }
+}
+
+/// 0-RTT policy of xdp_quic
+#[derive(Serialize, Deserialize)]
+struct Quic0RttPolicy {
+    reject: bool,
+}
+
+/// Get whether xdp_quic rejects 0-RTT packets
+async fn quic_0rtt_status(State(state): State<WorkerState>) -> Json<Quic0RttPolicy> {
+    Json(Quic0RttPolicy {
+        reject: state.loader.read().reject_0rtt(),
+    })
+}
+
+/// 0-RTT policy response
+#[derive(Serialize)]
+struct Quic0RttResponse {
+    success: bool,
+    message: String,
+}
+
+/// Switch 0-RTT rejection, e.g. while under a replay flood
+async fn set_quic_0rtt(
+    State(state): State<WorkerState>,
+    Json(request): Json<Quic0RttPolicy>,
+) -> impl IntoResponse {
+    match state.loader.write().set_reject_0rtt(request.reject) {
+        Ok(()) => {
+            tracing::warn!(
+                reject = request.reject,
+                "0-RTT rejection switched through the admin API"
+            );
+            (
+                StatusCode::OK,
+                Json(Quic0RttResponse {
+                    success: true,
+                    message: if request.reject {
+                        "0-RTT packets are rejected".to_string()
+                    } else {
+                        "0-RTT packets are accepted".to_string()
+                    },
+                }),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(Quic0RttResponse {
+                success: false,
+                message: format!("Failed to switch 0-RTT rejection: {}", e),
+            }),
+        ),
+    }
+}
+
+/// Get the short header rate limit per QUIC connection ID
+async fn quic_cid_limit_status(State(state): State<WorkerState>) -> Json<QuicCidLimit> {
+    Json(state.loader.read().quic_cid_limit())
+}
+
+/// QUIC connection ID limit response
+#[derive(Serialize)]
+struct QuicCidLimitResponse {
+    success: bool,
+    message: String,
+}
+
+/// Set the short header rate limit per QUIC connection ID, e.g. to throttle
+/// abusive HTTP/3 connections behind a CGNAT address
+async fn set_quic_cid_limit(
+    State(state): State<WorkerState>,
+    Json(request): Json<QuicCidLimit>,
+) -> impl IntoResponse {
+    if let Err(e) = request.validate() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(QuicCidLimitResponse {
+                success: false,
+                message: e.to_string(),
+            }),
+        );
+    }
+
+    match state.loader.write().set_quic_cid_limit(request) {
+        Ok(()) => {
+            tracing::warn!(
+                max_packets = request.max_packets_per_window,
+                cid_len = request.cid_len,
+                "QUIC connection ID limit set through the admin API"
+            );
+            (
+                StatusCode::OK,
+                Json(QuicCidLimitResponse {
+                    success: true,
+                    message: if request.is_enabled() {
+                        format!(
+                            "Connection IDs are limited to {} short header packets per window",
+                            request.max_packets_per_window
+                        )
+                    } else {
+                        "Connection IDs are not limited".to_string()
+                    },
+                }),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(QuicCidLimitResponse {
+                success: false,
+                message: format!("Failed to set QUIC connection ID limit: {}", e),
+            }),
+        ),
+    }
+}
+
+/// Get the worker's base log filter and the runtime directives over it
+async fn log_level_status(State(state): State<WorkerState>) -> Json<LogLevelStatus> {
+    Json(state.control_plane.log_levels().status())
+}
+
+/// Runtime log directives to apply
+#[derive(Deserialize)]
+struct SetLogLevelRequest {
+    directives: Vec<String>,
+    /// Lifetime kept by the control plane; directives applied while
+    /// disconnected stay until cleared
+    #[serde(default)]
+    ttl_secs: Option<u32>,
+}
+
+/// Log directives response
+#[derive(Serialize)]
+struct LogLevelResponse {
+    success: bool,
+    message: String,
+    /// Whether the control plane stored the directives for this node
+    persisted: bool,
+    status: Option<LogLevelStatus>,
+}
+
+/// Replace the runtime log directives, e.g. `["config_sync=debug"]`
+async fn set_log_level(
+    State(state): State<WorkerState>,
+    Json(request): Json<SetLogLevelRequest>,
+) -> impl IntoResponse {
+    apply_log_level(&state, request.directives, request.ttl_secs.unwrap_or(0)).await
+}
+
+/// Drop the runtime log directives, back to the base filter
+async fn clear_log_level(State(state): State<WorkerState>) -> impl IntoResponse {
+    apply_log_level(&state, Vec::new(), 0).await
+}
+
+async fn apply_log_level(
+    state: &WorkerState,
+    directives: Vec<String>,
+    ttl_secs: u32,
+) -> (StatusCode, Json<LogLevelResponse>) {
+    if let Err(e) = directives
+        .iter()
+        .try_for_each(|directive| pistonprotection_common::telemetry::validate_directive(directive))
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(LogLevelResponse {
+                success: false,
+                message: e.to_string(),
+                persisted: false,
+                status: None,
+            }),
+        );
+    }
+
+    // Store on the control plane first so the next heartbeat carries the
+    // same revision instead of undoing the change
+    let persisted = if state.control_plane.is_connected() {
+        match state
+            .control_plane
+            .set_log_level(directives.clone(), ttl_secs)
+            .await
+        {
+            Ok(level) => Some(level),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to persist log directives, applying locally");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let log_levels = state.control_plane.log_levels();
+    match log_levels.apply_local(directives, persisted.as_ref()) {
+        Ok(()) => {
+            let status = log_levels.status();
+            tracing::info!(
+                directives = ?status.directives,
+                persisted = persisted.is_some(),
+                "Log directives changed through the admin API"
+            );
+            (
+                StatusCode::OK,
+                Json(LogLevelResponse {
+                    success: true,
+                    message: if status.directives.is_empty() {
+                        "Log filter reset to the base directives".to_string()
+                    } else {
+                        format!("Applied {} log directives", status.directives.len())
+                    },
+                    persisted: persisted.is_some(),
+                    status: Some(status),
+                }),
+            )
+        }
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(LogLevelResponse {
+                success: false,
+                message: format!("Failed to apply log directives: {}", e),
+                persisted: persisted.is_some(),
+                status: None,
+            }),
+        ),
+    }
+}
+
+/// Get the Minecraft identity limits activity and blocked identities
+async fn minecraft_identity_status(
+    State(state): State<WorkerState>,
+) -> Json<IdentityThrottleStatus> {
+    Json(state.identities.status(std::time::Instant::now()))
+}
+
+/// Get the origin switches in progress and the connections left on the
+/// old origins
+async fn origin_switch_status(State(state): State<WorkerState>) -> Json<Vec<OriginSwitchStatus>> {
+    let maps = state.loader.read().maps();
+    let status = state.switches.status(&maps.read());
+    Json(status)
+}
+
+/// Get the utilization of the origin connection pools
+async fn origin_pool_status(State(state): State<WorkerState>) -> Json<Vec<OriginPoolStats>> {
+    Json(state.pools.stats())
+}
+
+/// Get the mitigations started by origin response anomalies and the recent
+/// incident records
+async fn origin_anomaly_status(State(state): State<WorkerState>) -> Json<AnomalyStatus> {
+    Json(state.anomalies.status())
+}
+
+/// Get the backends switched to passthrough or block-all on this worker
+async fn backend_mode_status(State(state): State<WorkerState>) -> Json<Vec<BackendModeStatus>> {
+    Json(state.modes.status(chrono::Utc::now().timestamp()))
+}
+
+/// Get the rate limit profile in effect for each backend with profiles
+async fn rate_profile_status(State(state): State<WorkerState>) -> Json<Vec<ActiveProfile>> {
+    Json(state.rate_profiles.status())
+}
+
+/// Get the backends in restart mode on this worker
+async fn restart_mode_status(State(state): State<WorkerState>) -> Json<Vec<RestartStatus>> {
+    Json(state.restarts.status(chrono::Utc::now().timestamp()))
+}
+
+/// Get the CGNAT ranges and the addresses detection found shared
+async fn cgnat_status(State(state): State<WorkerState>) -> Json<CgnatStatus> {
+    Json(state.cgnat.status())
+}
+
+/// Get the last correlation of kernel SYN and accept queue pressure with
+/// the SYN floods seen at XDP
+async fn syn_flood_status(State(state): State<WorkerState>) -> Json<Option<SynFloodReport>> {
+    Json(state.kernel_tcp.status())
+}
+
+/// Get the conntrack bypass settings and how many packets were marked
+async fn flow_mark_status(State(state): State<WorkerState>) -> Json<FlowMarkStatus> {
+    Json(state.loader.read().flow_mark_status())
+}
+
+/// Get the enforcement backend (XDP or the nftables fallback), why it was
+/// picked and what the nftables table holds
+async fn enforcement_status(State(state): State<WorkerState>) -> Json<EnforcementStatus> {
+    Json(state.enforcement.status())
+}
+
+/// Get the load test exemption tokens applied on this worker
+async fn exemption_token_status(State(state): State<WorkerState>) -> Json<Vec<ExemptionStatus>> {
+    Json(state.exemptions.status(chrono::Utc::now().timestamp()))
+}
+
+/// Get the warm-up state of the worker: the snapshot it imported and when
+/// it last published its own
+async fn warmup_status(State(state): State<WorkerState>) -> Json<WarmupStatus> {
+    Json(state.warmup.status(chrono::Utc::now()))
+}
+
+/// Export the learned state of the worker, to warm up another one
+async fn export_warmup_snapshot(State(state): State<WorkerState>) -> Json<WarmupSnapshot> {
+    Json(state.warmup_snapshot())
+}
+
+/// Warm-up snapshot import response
+#[derive(Serialize)]
+struct WarmupImportResponse {
+    success: bool,
+    message: String,
+    report: Option<WarmupReport>,
+}
+
+/// Merge the learned state exported by another worker
+async fn import_warmup_snapshot(
+    State(state): State<WorkerState>,
+    Json(snapshot): Json<WarmupSnapshot>,
+) -> impl IntoResponse {
+    match state.import_warmup_snapshot(snapshot) {
+        Ok(report) => {
+            tracing::info!(
+                source = ?report.source_worker_id,
+                learning = report.learning_backends,
+                reputation = report.reputation_sources,
+                baselines = report.baselines,
+                "Warm-up snapshot imported through the admin API"
+            );
+            (
+                StatusCode::OK,
+                Json(WarmupImportResponse {
+                    success: true,
+                    message: format!(
+                        "Imported learning of {} backends, {} reputation scores and {} baselines",
+                        report.learning_backends, report.reputation_sources, report.baselines
+                    ),
+                    report: Some(report),
+                }),
+            )
+        }
+        Err(e) => (
+            StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(WarmupImportResponse {
+                success: false,
+                message: format!("Failed to import warm-up snapshot: {}", e),
+                report: None,
+            }),
+        ),
+    }
+}
+
+/// Get the last load failure of each eBPF program that failed to load, with
+/// the tail of its verifier log
+async fn load_diagnostics_status(State(state): State<WorkerState>) -> Json<Vec<LoadDiagnostic>> {
+    Json(state.loader.read().load_diagnostics())
+}
+
+/// Get the trusted artifact signing keys and the last signature check of
+/// each object file and the bootstrap config
+async fn artifact_status(State(state): State<WorkerState>) -> Json<ArtifactStatus> {
+    Json(state.loader.read().artifact_status())
+}
+
+/// Reputation event request, e.g. from the challenge service
+#[derive(Deserialize)]
+struct ReputationEventRequest {
+    ip: String,
+    /// `blocked`, `challenge_failed`, `threat_intel` or `honeypot`
+    event: String,
+    /// Listing confidence of `threat_intel` events (0-100)
+    confidence: Option<u8>,
+}
+
+/// Reputation response
+#[derive(Serialize)]
+struct ReputationResponse {
+    success: bool,
+    message: String,
+}
+
+/// Record an event worsening the reputation of a source
+///
+/// Events are scored on the next reputation tick.
+async fn record_reputation_event(
+    State(state): State<WorkerState>,
+    Json(request): Json<ReputationEventRequest>,
+) -> impl IntoResponse {
+    let ip: IpAddr = match request.ip.parse() {
+        Ok(ip) => ip,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ReputationResponse {
+                    success: false,
+                    message: format!("Invalid IP address: {}", request.ip),
+                }),
+            );
+        }
+    };
+    let Some(event) = ReputationEvent::parse(&request.event, request.confidence) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ReputationResponse {
+                success: false,
+                message: format!("Unknown reputation event: {}", request.event),
+            }),
+        );
+    };
+
+    state.reputation.record(ip, event);
+    (
+        StatusCode::ACCEPTED,
+        Json(ReputationResponse {
+            success: true,
+            message: format!("Recorded {} for {}", request.event, ip),
+        }),
+    )
+}
+
+/// Reputation score response
+#[derive(Serialize)]
+struct ReputationScoreResponse {
+    ip: IpAddr,
+    score: f64,
+    greylisted: bool,
+}
+
+/// Get the current reputation score of a source
+async fn reputation_score(State(state): State<WorkerState>, Path(ip): Path<String>) -> Response {
+    let ip: IpAddr = match ip.parse() {
+        Ok(ip) => ip,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ReputationResponse {
+                    success: false,
+                    message: format!("Invalid IP address: {}", ip),
+                }),
+            )
+                .into_response();
+        }
+    };
+
+    match state
+        .reputation
+        .score(&ip, chrono::Utc::now().timestamp())
+        .await
+    {
+        Ok(score) => Json(ReputationScoreResponse {
+            ip,
+            score,
+            greylisted: state.reputation.greylist().iter().any(|s| s.ip == ip),
+        })
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ReputationResponse {
+                success: false,
+                message: e.to_string(),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+/// Connection dump query
+#[derive(Deserialize)]
+struct ConnectionsQuery {
+    /// Only dump the tables of this backend's protocol
+    #[serde(default)]
+    backend: Option<String>,
+    #[serde(default = "default_dump_limit")]
+    limit: usize,
+    /// Space separated `name:value` terms, see `ConnectionFilter`
+    #[serde(default)]
+    filter: String,
+    /// `next_cursor` of the previous page
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+fn default_dump_limit() -> usize {
+    DEFAULT_DUMP_LIMIT
+}
+
+/// Connection dump error response
+#[derive(Serialize)]
+struct ConnectionsResponse {
+    success: bool,
+    message: String,
+}
+
+/// Dump the decoded entries of the kernel connection tables, one page at
+/// a time
+async fn dump_connections(
+    State(state): State<WorkerState>,
+    Query(query): Query<ConnectionsQuery>,
+) -> Response {
+    let error = |status: StatusCode, message: String| {
+        (
+            status,
+            Json(ConnectionsResponse {
+                success: false,
+                message,
+            }),
+        )
+            .into_response()
+    };
+
+    let filter = match ConnectionFilter::parse(&query.filter) {
+        Ok(filter) => filter,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let cursor = match query.cursor.as_deref().map(DumpCursor::parse).transpose() {
+        Ok(cursor) => cursor,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    let mut tables = ConnectionTable::ALL.to_vec();
+    if let Some(backend_id) = &query.backend {
+        let protocol = state.config_sync.current_config().and_then(|config| {
+            config
+                .backends
+                .iter()
+                .find(|backend| &backend.backend_id == backend_id)
+                .map(|backend| backend.protocol())
+        });
+        match protocol {
+            Some(protocol) => tables = ConnectionTable::for_protocol(protocol),
+            None => {
+                return error(
+                    StatusCode::NOT_FOUND,
+                    format!("Backend {} is not configured on this worker", backend_id),
+                );
+            }
+        }
+    }
+    tables.retain(|&table| filter.allows_table(table));
+
+    match state.loader.read().read_connections(&tables) {
+        Ok(entries) => {
+            Json(conn_table::page(entries, &filter, cursor, query.limit)).into_response()
+        }
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read connection tables: {}", e),
+        ),
+    }
+}
@@ -0,0 +1,196 @@
+//! Node-local admin API
+//!
+//! Serves the worker's HTTP API on a unix domain socket too, so operators on
+//! the node can query stats and switch observe mode while the network path
+//! to the worker or the control plane is degraded. Connections are
+//! authorized by the peer credentials of the socket (`SO_PEERCRED`): root,
+//! the worker's own user and the configured users and groups are let in,
+//! every other peer is disconnected before a request is read.
+
+use axum::Router;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, info, warn};
+
+/// Default path of the socket, on a host directory mounted into the pod
+pub const DEFAULT_SOCKET_PATH: &str = "/run/pistonprotection/worker.sock";
+
+/// Local API configuration
+#[derive(Debug, Clone)]
+pub struct LocalApiConfig {
+    /// Serve the local API
+    pub enabled: bool,
+    /// Path of the unix socket
+    pub socket_path: PathBuf,
+    /// Users allowed besides root and the worker's own user
+    pub allowed_uids: Vec<u32>,
+    /// Groups whose members are allowed
+    pub allowed_gids: Vec<u32>,
+}
+
+impl Default for LocalApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            socket_path: PathBuf::from(DEFAULT_SOCKET_PATH),
+            allowed_uids: Vec::new(),
+            allowed_gids: Vec::new(),
+        }
+    }
+}
+
+impl LocalApiConfig {
+    /// Load local API configuration from environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(enabled) = std::env::var("PISTON_LOCAL_API") {
+            config.enabled = !matches!(enabled.as_str(), "0" | "false" | "off");
+        }
+
+        if let Ok(path) = std::env::var("PISTON_LOCAL_API_SOCKET") {
+            config.socket_path = PathBuf::from(path);
+        }
+
+        // Numeric ids (format: 1000,1001)
+        if let Ok(uids) = std::env::var("PISTON_LOCAL_API_UIDS") {
+            config.allowed_uids = parse_ids(&uids);
+        }
+        if let Ok(gids) = std::env::var("PISTON_LOCAL_API_GIDS") {
+            config.allowed_gids = parse_ids(&gids);
+        }
+
+        config
+    }
+
+    /// Whether a peer with these credentials may use the API
+    pub fn is_authorized(&self, uid: u32, gid: u32, own_uid: u32) -> bool {
+        uid == 0
+            || uid == own_uid
+            || self.allowed_uids.contains(&uid)
+            || self.allowed_gids.contains(&gid)
+    }
+
+    /// Mode of the socket file
+    ///
+    /// Without extra users or groups only the owner can connect at all;
+    /// otherwise anyone can connect and the peer credentials decide.
+    fn socket_mode(&self) -> u32 {
+        if self.allowed_uids.is_empty() && self.allowed_gids.is_empty() {
+            0o600
+        } else {
+            0o666
+        }
+    }
+}
+
+fn parse_ids(ids: &str) -> Vec<u32> {
+    ids.split(',')
+        .filter_map(|id| id.trim().parse().ok())
+        .collect()
+}
+
+/// Unix listener handing only authorized connections to the server
+struct AuthorizedListener {
+    listener: UnixListener,
+    config: Arc<LocalApiConfig>,
+    own_uid: u32,
+}
+
+impl axum::serve::Listener for AuthorizedListener {
+    type Io = UnixStream;
+    type Addr = tokio::net::unix::SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            let (stream, addr) = axum::serve::Listener::accept(&mut self.listener).await;
+            match stream.peer_cred() {
+                Ok(cred)
+                    if self
+                        .config
+                        .is_authorized(cred.uid(), cred.gid(), self.own_uid) =>
+                {
+                    debug!(
+                        uid = cred.uid(),
+                        gid = cred.gid(),
+                        pid = ?cred.pid(),
+                        "Local API connection"
+                    );
+                    return (stream, addr);
+                }
+                Ok(cred) => warn!(
+                    uid = cred.uid(),
+                    gid = cred.gid(),
+                    pid = ?cred.pid(),
+                    "Rejected unauthorized local API connection"
+                ),
+                Err(e) => warn!(error = %e, "Failed to read local API peer credentials"),
+            }
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        self.listener.local_addr()
+    }
+}
+
+/// Serve the router on the configured socket until the task is aborted
+pub async fn serve(router: Router, config: LocalApiConfig) -> std::io::Result<()> {
+    let path = config.socket_path.clone();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // A socket left behind by a previous run makes bind fail
+    match std::fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let listener = UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(config.socket_mode()))?;
+    info!(path = %path.display(), "Local API listening");
+
+    let listener = AuthorizedListener {
+        listener,
+        config: Arc::new(config),
+        own_uid: nix::unistd::geteuid().as_raw(),
+    };
+    axum::serve(listener, router).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_authorized() {
+        let config = LocalApiConfig {
+            allowed_uids: vec![1000],
+            allowed_gids: vec![2000],
+            ..Default::default()
+        };
+        assert!(config.is_authorized(0, 0, 500));
+        assert!(config.is_authorized(500, 500, 500));
+        assert!(config.is_authorized(1000, 1000, 500));
+        assert!(config.is_authorized(1001, 2000, 500));
+        assert!(!config.is_authorized(1001, 1001, 500));
+    }
+
+    #[test]
+    fn test_socket_mode() {
+        assert_eq!(LocalApiConfig::default().socket_mode(), 0o600);
+        let config = LocalApiConfig {
+            allowed_gids: vec![2000],
+            ..Default::default()
+        };
+        assert_eq!(config.socket_mode(), 0o666);
+    }
+
+    #[test]
+    fn test_parse_ids() {
+        assert_eq!(parse_ids("1000, 1001,x,"), vec![1000, 1001]);
+    }
+}
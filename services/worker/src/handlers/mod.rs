@@ -4,12 +4,27 @@
 //! as well as the worker state management.
 
 pub mod http;
+pub mod local;
 
+use crate::backend_mode::BackendModes;
 use crate::config_sync::ConfigSyncManager;
 use crate::control_plane::{ConnectionState, ControlPlaneClient};
-use crate::ebpf::{interface::NetworkInterface, loader::EbpfLoader};
-use deadpool_redis::Pool as RedisPool;
+use crate::ebpf::{
+    cgnat::CgnatDetector, drop_events::DropEvents, interface::NetworkInterface, loader::EbpfLoader,
+    offender_bundle::OffenderBundles, sampling::SampleAnalyzer, threat_intel::ThreatIntelManager,
+};
+use crate::exemption::ExemptionTokens;
+use crate::kernel_tcp::KernelTcpMonitor;
+use crate::nftables::NftEnforcer;
+use crate::protocol::minecraft_identity::IdentityThrottle;
+use crate::proxy::anomaly::OriginAnomalyDetector;
+use crate::rate_profile::RateProfiles;
+use crate::reputation::ReputationEngine;
+use crate::restart_mode::RestartModes;
+use crate::routing::{OriginPools, OriginSwitches};
+use crate::warmup::{Warmup, WarmupReport, WarmupSnapshot};
 use parking_lot::RwLock;
+use pistonprotection_common::redis::RedisPool;
 use pistonprotection_common::{config::Config, error::Result, redis::CacheService};
 use std::sync::Arc;
 
@@ -28,10 +43,45 @@ pub struct WorkerState {
     pub config: Arc<Config>,
     /// Network interfaces on this worker
     pub interfaces: Arc<Vec<NetworkInterface>>,
+    /// Flow sample analyzer
+    pub sampler: Arc<SampleAnalyzer>,
+    /// Threat intelligence feeds
+    pub threat_intel: Arc<ThreatIntelManager>,
+    /// Signed offender bundles loaded into the threat feeds
+    pub bundles: Arc<OffenderBundles>,
+    /// Source reputation scores
+    pub reputation: Arc<ReputationEngine>,
+    /// Minecraft player identity limits
+    pub identities: Arc<IdentityThrottle>,
+    /// Origin switches in progress
+    pub switches: Arc<OriginSwitches>,
+    /// Connection pools of proxied TCP origins
+    pub pools: Arc<OriginPools>,
+    /// Origin response anomalies of HTTP proxy hosts
+    pub anomalies: Arc<OriginAnomalyDetector>,
+    /// Backends switched to passthrough or block-all
+    pub modes: Arc<BackendModes>,
+    /// Rate limit profiles of backends
+    pub rate_profiles: Arc<RateProfiles>,
+    /// Backends in restart mode
+    pub restarts: Arc<RestartModes>,
+    /// Load test exemption tokens
+    pub exemptions: Arc<ExemptionTokens>,
+    /// Known and detected CGNAT addresses
+    pub cgnat: Arc<CgnatDetector>,
+    /// Kernel TCP pressure correlated with XDP SYN-flood stats
+    pub kernel_tcp: Arc<KernelTcpMonitor>,
+    /// nftables fallback enforcement
+    pub enforcement: Arc<NftEnforcer>,
+    /// Drop events aggregated by source and reason
+    pub drop_events: Arc<DropEvents>,
+    /// Regional warm-up snapshots of learned state
+    pub warmup: Arc<Warmup>,
 }
 
 impl WorkerState {
     /// Create a new worker state
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         loader: Arc<RwLock<EbpfLoader>>,
         config_sync: Arc<ConfigSyncManager>,
@@ -39,6 +89,23 @@ impl WorkerState {
         redis: Option<RedisPool>,
         config: Arc<Config>,
         interfaces: Arc<Vec<NetworkInterface>>,
+        sampler: Arc<SampleAnalyzer>,
+        threat_intel: Arc<ThreatIntelManager>,
+        bundles: Arc<OffenderBundles>,
+        reputation: Arc<ReputationEngine>,
+        identities: Arc<IdentityThrottle>,
+        switches: Arc<OriginSwitches>,
+        pools: Arc<OriginPools>,
+        anomalies: Arc<OriginAnomalyDetector>,
+        modes: Arc<BackendModes>,
+        rate_profiles: Arc<RateProfiles>,
+        restarts: Arc<RestartModes>,
+        exemptions: Arc<ExemptionTokens>,
+        cgnat: Arc<CgnatDetector>,
+        kernel_tcp: Arc<KernelTcpMonitor>,
+        enforcement: Arc<NftEnforcer>,
+        drop_events: Arc<DropEvents>,
+        warmup: Arc<Warmup>,
     ) -> Self {
         let cache = redis.map(|pool| CacheService::new(pool, "piston:worker"));
 
@@ -49,6 +116,23 @@ impl WorkerState {
             cache,
             config,
             interfaces,
+            sampler,
+            threat_intel,
+            bundles,
+            reputation,
+            identities,
+            switches,
+            pools,
+            anomalies,
+            modes,
+            rate_profiles,
+            restarts,
+            exemptions,
+            cgnat,
+            kernel_tcp,
+            enforcement,
+            drop_events,
+            warmup,
         }
     }
 
@@ -188,6 +272,78 @@ impl WorkerState {
             .cloned()
             .collect()
     }
+
+    /// Get the honeypot ports by backend ID (sorted) and the recently
+    /// flagged sources
+    pub fn honeypots(
+        &self,
+    ) -> (
+        Vec<(String, crate::ebpf::honeypot::HoneypotPorts)>,
+        Vec<crate::ebpf::honeypot::FlaggedSource>,
+    ) {
+        let loader = self.loader.read();
+        let maps = loader.maps();
+        let map_manager = maps.read();
+        let mut backends: Vec<_> = map_manager
+            .honeypots()
+            .iter()
+            .map(|(id, honeypot)| (id.clone(), honeypot.settings.clone()))
+            .collect();
+        backends.sort_by(|a, b| a.0.cmp(&b.0));
+        (backends, map_manager.flagged_sources())
+    }
+
+    /// Get the allowlist learning state by backend ID (sorted)
+    pub fn learning_status(&self) -> Vec<crate::ebpf::learning::LearningStatus> {
+        let loader = self.loader.read();
+        let maps = loader.maps();
+        let map_manager = maps.read();
+        map_manager.learning_status()
+    }
+
+    /// Take a warm-up snapshot of the learned state
+    pub fn warmup_snapshot(&self) -> WarmupSnapshot {
+        let learning = {
+            let loader = self.loader.read();
+            let maps = loader.maps();
+            let map_manager = maps.read();
+            map_manager.export_learning()
+        };
+        WarmupSnapshot::collect(
+            &self.warmup.config().region,
+            self.worker_id(),
+            learning,
+            &self.reputation,
+            &self.anomalies,
+            chrono::Utc::now(),
+        )
+    }
+
+    /// Merge a warm-up snapshot into the learned state
+    pub fn import_warmup_snapshot(&self, snapshot: WarmupSnapshot) -> Result<WarmupReport> {
+        self.warmup.import(
+            snapshot,
+            &self.reputation,
+            &self.anomalies,
+            chrono::Utc::now(),
+            |learning| {
+                let loader = self.loader.read();
+                let maps = loader.maps();
+                let mut map_manager = maps.write();
+                map_manager.import_learning(learning)
+            },
+        )
+    }
+
+    /// Get the marking activity by backend ID (sorted)
+    pub fn marking_status(&self) -> Vec<crate::ebpf::marking::MarkingStatus> {
+        let loader = self.loader.read();
+        // Counters are unavailable until xdp_filter is loaded
+        let counters = loader.read_marking_counters().unwrap_or_default();
+        let maps = loader.maps();
+        let map_manager = maps.read();
+        map_manager.marking_status(&counters)
+    }
 }
 
 /// Extended health check response
@@ -0,0 +1,604 @@
+//! Kernel TCP pressure and SYN flood correlation
+//!
+//! The XDP TCP filter counts the SYNs it drops and the SYN cookies it
+//! answers with, but it cannot see what got past it. The kernel can: when
+//! SYNs reach a listener faster than handshakes complete, the SYN queue fills
+//! up and the kernel falls back to its own SYN cookies (`SyncookiesSent`,
+//! `TCPReqQFullDoCookies`) or drops (`TCPReqQFullDrop`), and when the
+//! application does not accept fast enough the accept queue overflows
+//! (`ListenOverflows`, `ListenDrops`). This module reads those counters from
+//! `/proc/net/netstat`, and summarises `/proc/net/tcp{,6}` the way `ss` does
+//! (half-open connections and accept queues of listeners), for the node the
+//! worker runs on.
+//!
+//! Each poll compares the kernel deltas with the XDP SYN-flood deltas of the
+//! same interval:
+//!
+//! - no SYN flood at XDP and no kernel pressure is quiet;
+//! - a SYN flood the kernel does not feel is contained by the filter;
+//! - a SYN flood with kernel pressure is leaking past the filter;
+//! - kernel pressure without a flood at XDP is unfiltered, e.g. a flood
+//!   below the thresholds, on ports the filter does not protect, or an
+//!   application that cannot keep up.
+//!
+//! The worker runs in the host network namespace, so these are the counters
+//! of the node, not of a container.
+
+use crate::ebpf::stats::{StatsSnapshot, TcpStats};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::metrics::{KERNEL_TCP_EVENTS, KERNEL_TCP_SOCKETS, SYN_FLOOD_STATE};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// SYN drops per second at XDP that count as a flood
+pub const DEFAULT_FLOOD_RATE: f64 = 1000.0;
+
+/// Kernel SYN and accept queue events per second that count as pressure
+pub const DEFAULT_PRESSURE_RATE: f64 = 1.0;
+
+/// TCP socket states in `/proc/net/tcp`
+const TCP_SYN_RECV: u8 = 0x03;
+const TCP_LISTEN: u8 = 0x0A;
+
+/// SYN flood correlation configuration
+#[derive(Debug, Clone)]
+pub struct KernelTcpConfig {
+    /// Root of the proc filesystem
+    pub proc_root: PathBuf,
+    /// SYN drops and cookies per second at XDP that count as a flood
+    pub flood_rate: f64,
+    /// Kernel SYN and accept queue events per second that count as pressure
+    pub pressure_rate: f64,
+}
+
+impl Default for KernelTcpConfig {
+    fn default() -> Self {
+        Self {
+            proc_root: PathBuf::from("/proc"),
+            flood_rate: DEFAULT_FLOOD_RATE,
+            pressure_rate: DEFAULT_PRESSURE_RATE,
+        }
+    }
+}
+
+impl KernelTcpConfig {
+    /// Load configuration from environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(root) = std::env::var("PISTON_PROC_ROOT") {
+            config.proc_root = PathBuf::from(root);
+        }
+        if let Ok(rate) = std::env::var("PISTON_SYN_FLOOD_RATE") {
+            if let Ok(rate) = rate.parse::<f64>() {
+                config.flood_rate = rate.max(1.0);
+            }
+        }
+        if let Ok(rate) = std::env::var("PISTON_KERNEL_PRESSURE_RATE") {
+            if let Ok(rate) = rate.parse::<f64>() {
+                config.pressure_rate = rate.max(0.1);
+            }
+        }
+
+        config
+    }
+}
+
+/// Listen and SYN queue counters of the kernel (`TcpExt` in
+/// `/proc/net/netstat`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct KernelTcpCounters {
+    /// SYN cookies sent because a SYN queue was full
+    pub syncookies_sent: u64,
+    /// Valid SYN cookies received
+    pub syncookies_recv: u64,
+    /// Invalid SYN cookies received
+    pub syncookies_failed: u64,
+    /// Connections dropped because an accept queue was full
+    pub listen_overflows: u64,
+    /// Connections dropped by listeners for any reason
+    pub listen_drops: u64,
+    /// SYNs answered with a cookie because the SYN queue was full
+    pub req_q_full_do_cookies: u64,
+    /// SYNs dropped because the SYN queue was full and cookies are off
+    pub req_q_full_drop: u64,
+}
+
+impl KernelTcpCounters {
+    /// Counters with their Prometheus event names
+    fn events(&self) -> [(&'static str, u64); 7] {
+        [
+            ("syncookies_sent", self.syncookies_sent),
+            ("syncookies_recv", self.syncookies_recv),
+            ("syncookies_failed", self.syncookies_failed),
+            ("listen_overflows", self.listen_overflows),
+            ("listen_drops", self.listen_drops),
+            ("req_q_full_do_cookies", self.req_q_full_do_cookies),
+            ("req_q_full_drop", self.req_q_full_drop),
+        ]
+    }
+
+    /// Counter increments since `previous`, or `None` if any went backwards
+    pub fn delta_since(&self, previous: &Self) -> Option<Self> {
+        Some(Self {
+            syncookies_sent: self.syncookies_sent.checked_sub(previous.syncookies_sent)?,
+            syncookies_recv: self.syncookies_recv.checked_sub(previous.syncookies_recv)?,
+            syncookies_failed: self
+                .syncookies_failed
+                .checked_sub(previous.syncookies_failed)?,
+            listen_overflows: self
+                .listen_overflows
+                .checked_sub(previous.listen_overflows)?,
+            listen_drops: self.listen_drops.checked_sub(previous.listen_drops)?,
+            req_q_full_do_cookies: self
+                .req_q_full_do_cookies
+                .checked_sub(previous.req_q_full_do_cookies)?,
+            req_q_full_drop: self.req_q_full_drop.checked_sub(previous.req_q_full_drop)?,
+        })
+    }
+
+    /// Events showing SYNs or connections piling up in the kernel
+    ///
+    /// `ListenDrops` includes `ListenOverflows`, and `SyncookiesSent`
+    /// includes `TCPReqQFullDoCookies`, so only the larger of each is counted.
+    pub fn pressure_events(&self) -> u64 {
+        self.syncookies_sent.max(self.req_q_full_do_cookies)
+            + self.req_q_full_drop
+            + self.listen_drops.max(self.listen_overflows)
+    }
+}
+
+/// Parse the `TcpExt` counters of `/proc/net/netstat`
+///
+/// The file holds pairs of lines, a header naming the counters of a group
+/// and a row with their values. Counters the kernel does not have are 0.
+pub fn parse_netstat(content: &str) -> KernelTcpCounters {
+    let mut values: HashMap<&str, u64> = HashMap::new();
+    let mut lines = content.lines();
+
+    while let (Some(header), Some(row)) = (lines.next(), lines.next()) {
+        let (Some(("TcpExt", names)), Some(("TcpExt", numbers))) =
+            (header.split_once(':'), row.split_once(':'))
+        else {
+            continue;
+        };
+        for (name, value) in names.split_whitespace().zip(numbers.split_whitespace()) {
+            if let Ok(value) = value.parse() {
+                values.insert(name, value);
+            }
+        }
+    }
+
+    let value = |name: &str| values.get(name).copied().unwrap_or(0);
+    KernelTcpCounters {
+        syncookies_sent: value("SyncookiesSent"),
+        syncookies_recv: value("SyncookiesRecv"),
+        syncookies_failed: value("SyncookiesFailed"),
+        listen_overflows: value("ListenOverflows"),
+        listen_drops: value("ListenDrops"),
+        req_q_full_do_cookies: value("TCPReqQFullDoCookies"),
+        req_q_full_drop: value("TCPReqQFullDrop"),
+    }
+}
+
+/// Summary of the TCP sockets of the node, like `ss -s` plus the accept
+/// queues of listeners
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SocketSummary {
+    /// Half-open connections (SYN_RECV)
+    pub syn_recv: u64,
+    /// Listening sockets
+    pub listeners: u64,
+    /// Connections waiting in accept queues, summed over listeners
+    pub accept_queued: u64,
+    /// Longest accept queue of a listener
+    pub max_accept_queue: u64,
+}
+
+impl SocketSummary {
+    /// Add the sockets of a `/proc/net/tcp` or `/proc/net/tcp6` table
+    ///
+    /// For listeners the kernel reports the accept queue length as
+    /// `rx_queue`.
+    pub fn add_table(&mut self, content: &str) {
+        for line in content.lines().skip(1) {
+            let mut fields = line.split_whitespace().skip(3);
+            let (Some(state), Some(queues)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let Ok(state) = u8::from_str_radix(state, 16) else {
+                continue;
+            };
+            match state {
+                TCP_SYN_RECV => self.syn_recv += 1,
+                TCP_LISTEN => {
+                    let queued = queues
+                        .split_once(':')
+                        .and_then(|(_, rx)| u64::from_str_radix(rx, 16).ok())
+                        .unwrap_or(0);
+                    self.listeners += 1;
+                    self.accept_queued += queued;
+                    self.max_accept_queue = self.max_accept_queue.max(queued);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Kernel TCP state read in one poll
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KernelTcpReading {
+    pub counters: KernelTcpCounters,
+    pub sockets: SocketSummary,
+    /// `net.ipv4.tcp_max_syn_backlog`, if readable
+    pub max_syn_backlog: Option<u64>,
+}
+
+/// How the kernel fares against SYN floods seen at XDP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SynFloodVerdict {
+    /// No flood and no kernel pressure
+    Quiet,
+    /// Flood dropped at XDP without kernel pressure
+    Contained,
+    /// Flood at XDP with kernel pressure: SYNs leak past the filter
+    Leaking,
+    /// Kernel pressure the filter does not see a flood for
+    Unfiltered,
+}
+
+impl SynFloodVerdict {
+    pub const ALL: [SynFloodVerdict; 4] = [
+        SynFloodVerdict::Quiet,
+        SynFloodVerdict::Contained,
+        SynFloodVerdict::Leaking,
+        SynFloodVerdict::Unfiltered,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SynFloodVerdict::Quiet => "quiet",
+            SynFloodVerdict::Contained => "contained",
+            SynFloodVerdict::Leaking => "leaking",
+            SynFloodVerdict::Unfiltered => "unfiltered",
+        }
+    }
+}
+
+/// XDP SYN-flood rates of an interval
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct XdpSynRates {
+    /// SYNs dropped as a flood per second
+    pub syn_flood_drops: f64,
+    /// SYN cookies issued per second
+    pub syn_cookies_issued: f64,
+}
+
+impl XdpSynRates {
+    /// Rates from a TCP stats snapshot, `None` on the first poll or after the
+    /// counters were reset
+    pub fn from_snapshot(snapshot: &StatsSnapshot<TcpStats>) -> Option<Self> {
+        if snapshot.reset_detected || snapshot.interval.is_zero() {
+            return None;
+        }
+        Some(Self {
+            syn_flood_drops: snapshot.rate("dropped_syn_flood")?,
+            syn_cookies_issued: snapshot.rate("syn_cookies_issued")?,
+        })
+    }
+
+    /// SYNs the filter handled instead of the kernel, per second
+    pub fn filtered(&self) -> f64 {
+        self.syn_flood_drops + self.syn_cookies_issued
+    }
+}
+
+/// Correlation of one poll
+#[derive(Debug, Clone, Serialize)]
+pub struct SynFloodReport {
+    pub verdict: SynFloodVerdict,
+    /// Seconds between this poll and the previous one
+    pub interval_secs: f64,
+    /// XDP rates, absent while xdp_tcp is not loaded
+    pub xdp: Option<XdpSynRates>,
+    /// Kernel SYN and accept queue events per second
+    pub kernel_pressure_per_second: f64,
+    /// Kernel counter increments of the interval
+    pub kernel_delta: KernelTcpCounters,
+    /// Kernel counter totals
+    pub kernel_totals: KernelTcpCounters,
+    pub sockets: SocketSummary,
+    /// Half-open connections as a fraction of `tcp_max_syn_backlog`
+    pub syn_backlog_fill: Option<f64>,
+    pub observed_at: DateTime<Utc>,
+}
+
+/// Correlate the rates of an interval into a verdict
+pub fn correlate(
+    xdp: Option<&XdpSynRates>,
+    kernel_pressure_per_second: f64,
+    config: &KernelTcpConfig,
+) -> SynFloodVerdict {
+    let flood = xdp.is_some_and(|rates| rates.filtered() >= config.flood_rate);
+    let pressure = kernel_pressure_per_second >= config.pressure_rate;
+
+    match (flood, pressure) {
+        (false, false) => SynFloodVerdict::Quiet,
+        (true, false) => SynFloodVerdict::Contained,
+        (true, true) => SynFloodVerdict::Leaking,
+        (false, true) => SynFloodVerdict::Unfiltered,
+    }
+}
+
+struct Baseline {
+    counters: KernelTcpCounters,
+    at: Instant,
+}
+
+/// Reads kernel TCP state and correlates it with XDP SYN-flood stats
+pub struct KernelTcpMonitor {
+    config: KernelTcpConfig,
+    baseline: RwLock<Option<Baseline>>,
+    last: RwLock<Option<SynFloodReport>>,
+}
+
+impl KernelTcpMonitor {
+    /// Create a new monitor
+    pub fn new(config: KernelTcpConfig) -> Self {
+        Self {
+            config,
+            baseline: RwLock::new(None),
+            last: RwLock::new(None),
+        }
+    }
+
+    /// Read the kernel counters and socket tables
+    pub fn read(&self) -> io::Result<KernelTcpReading> {
+        let root = &self.config.proc_root;
+        let netstat = std::fs::read_to_string(root.join("net/netstat"))?;
+
+        let mut sockets = SocketSummary::default();
+        sockets.add_table(&std::fs::read_to_string(root.join("net/tcp"))?);
+        // IPv6 may be disabled on the node
+        if let Ok(table) = std::fs::read_to_string(root.join("net/tcp6")) {
+            sockets.add_table(&table);
+        }
+
+        let max_syn_backlog =
+            std::fs::read_to_string(root.join("sys/net/ipv4/tcp_max_syn_backlog"))
+                .ok()
+                .and_then(|value| value.trim().parse().ok());
+
+        Ok(KernelTcpReading {
+            counters: parse_netstat(&netstat),
+            sockets,
+            max_syn_backlog,
+        })
+    }
+
+    /// Record a reading with the XDP rates of the same interval
+    ///
+    /// The first reading only sets the baseline.
+    pub fn observe(
+        &self,
+        reading: KernelTcpReading,
+        xdp: Option<XdpSynRates>,
+        now: Instant,
+    ) -> Option<SynFloodReport> {
+        let previous = self.baseline.write().replace(Baseline {
+            counters: reading.counters,
+            at: now,
+        })?;
+
+        let interval = now.saturating_duration_since(previous.at).as_secs_f64();
+        // Kernel counters only go back on reboot, which restarts the worker
+        let delta = reading
+            .counters
+            .delta_since(&previous.counters)
+            .unwrap_or_default();
+        let pressure = if interval > 0.0 {
+            delta.pressure_events() as f64 / interval
+        } else {
+            0.0
+        };
+
+        let report = SynFloodReport {
+            verdict: correlate(xdp.as_ref(), pressure, &self.config),
+            interval_secs: interval,
+            xdp,
+            kernel_pressure_per_second: pressure,
+            kernel_delta: delta,
+            kernel_totals: reading.counters,
+            sockets: reading.sockets,
+            syn_backlog_fill: reading
+                .max_syn_backlog
+                .filter(|max| *max > 0)
+                .map(|max| reading.sockets.syn_recv as f64 / max as f64),
+            observed_at: Utc::now(),
+        };
+
+        export_metrics(&report);
+        *self.last.write() = Some(report.clone());
+        Some(report)
+    }
+
+    /// Last correlation, if any
+    pub fn status(&self) -> Option<SynFloodReport> {
+        self.last.read().clone()
+    }
+}
+
+fn export_metrics(report: &SynFloodReport) {
+    for (event, delta) in report.kernel_delta.events() {
+        KERNEL_TCP_EVENTS
+            .with_label_values(&[event])
+            .inc_by(delta as f64);
+    }
+
+    let sockets = &report.sockets;
+    for (kind, value) in [
+        ("syn_recv", sockets.syn_recv),
+        ("listen", sockets.listeners),
+        ("accept_queued", sockets.accept_queued),
+        ("max_accept_queue", sockets.max_accept_queue),
+    ] {
+        KERNEL_TCP_SOCKETS
+            .with_label_values(&[kind])
+            .set(value as f64);
+    }
+
+    for verdict in SynFloodVerdict::ALL {
+        let current = if verdict == report.verdict { 1.0 } else { 0.0 };
+        SYN_FLOOD_STATE
+            .with_label_values(&[verdict.as_str()])
+            .set(current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const NETSTAT: &str = "\
+TcpExt: SyncookiesSent SyncookiesRecv SyncookiesFailed EmbryonicRsts ListenOverflows ListenDrops TCPReqQFullDoCookies TCPReqQFullDrop
+TcpExt: 120 80 3 0 7 9 100 2
+IpExt: InNoRoutes InTruncatedPkts
+IpExt: 0 0
+";
+
+    const TCP: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:63DD 00000000:0000 0A 00000000:00000004 00:00000000 00000000     0        0 1001 1 0 100 0 0 10 0
+   1: 00000000:0050 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1002 1 0 100 0 0 10 0
+   2: 0100007F:63DD 0200A8C0:D431 03 00000000:00000000 02:00000064 00000000     0        0 0 1 0 0 0 0 0
+   3: 0100007F:63DD 0300A8C0:D432 03 00000000:00000000 02:00000064 00000000     0        0 0 1 0 0 0 0 0
+   4: 0100007F:63DD 0400A8C0:D433 01 00000000:00000000 00:00000000 00000000     0        0 1003 1 0 20 4 30 10 -1
+";
+
+    fn config() -> KernelTcpConfig {
+        KernelTcpConfig::default()
+    }
+
+    #[test]
+    fn test_parse_netstat() {
+        let counters = parse_netstat(NETSTAT);
+        assert_eq!(counters.syncookies_sent, 120);
+        assert_eq!(counters.syncookies_recv, 80);
+        assert_eq!(counters.syncookies_failed, 3);
+        assert_eq!(counters.listen_overflows, 7);
+        assert_eq!(counters.listen_drops, 9);
+        assert_eq!(counters.req_q_full_do_cookies, 100);
+        assert_eq!(counters.req_q_full_drop, 2);
+
+        assert_eq!(parse_netstat(""), KernelTcpCounters::default());
+    }
+
+    #[test]
+    fn test_pressure_events_not_double_counted() {
+        let counters = parse_netstat(NETSTAT);
+        // max(120, 100) + 2 + max(9, 7)
+        assert_eq!(counters.pressure_events(), 131);
+    }
+
+    #[test]
+    fn test_delta_since() {
+        let previous = parse_netstat(NETSTAT);
+        let mut current = previous;
+        current.syncookies_sent += 50;
+        current.listen_overflows += 1;
+
+        let delta = current.delta_since(&previous).unwrap();
+        assert_eq!(delta.syncookies_sent, 50);
+        assert_eq!(delta.listen_overflows, 1);
+        assert_eq!(delta.req_q_full_drop, 0);
+
+        assert!(previous.delta_since(&current).is_none());
+    }
+
+    #[test]
+    fn test_socket_summary() {
+        let mut summary = SocketSummary::default();
+        summary.add_table(TCP);
+        assert_eq!(
+            summary,
+            SocketSummary {
+                syn_recv: 2,
+                listeners: 2,
+                accept_queued: 4,
+                max_accept_queue: 4,
+            }
+        );
+
+        // Headers and blank tables add nothing
+        summary.add_table("  sl  local_address rem_address   st\n");
+        assert_eq!(summary.listeners, 2);
+    }
+
+    #[test]
+    fn test_correlate() {
+        let config = config();
+        let flood = XdpSynRates {
+            syn_flood_drops: 50_000.0,
+            syn_cookies_issued: 0.0,
+        };
+        let trickle = XdpSynRates {
+            syn_flood_drops: 10.0,
+            syn_cookies_issued: 5.0,
+        };
+
+        assert_eq!(correlate(None, 0.0, &config), SynFloodVerdict::Quiet);
+        assert_eq!(
+            correlate(Some(&trickle), 0.0, &config),
+            SynFloodVerdict::Quiet
+        );
+        assert_eq!(
+            correlate(Some(&flood), 0.0, &config),
+            SynFloodVerdict::Contained
+        );
+        assert_eq!(
+            correlate(Some(&flood), 200.0, &config),
+            SynFloodVerdict::Leaking
+        );
+        assert_eq!(
+            correlate(Some(&trickle), 200.0, &config),
+            SynFloodVerdict::Unfiltered
+        );
+        assert_eq!(correlate(None, 200.0, &config), SynFloodVerdict::Unfiltered);
+    }
+
+    #[test]
+    fn test_observe_needs_baseline() {
+        let monitor = KernelTcpMonitor::new(config());
+        let start = Instant::now();
+        let mut reading = KernelTcpReading {
+            counters: parse_netstat(NETSTAT),
+            max_syn_backlog: Some(4),
+            ..Default::default()
+        };
+        reading.sockets.add_table(TCP);
+
+        assert!(monitor.observe(reading, None, start).is_none());
+        assert!(monitor.status().is_none());
+
+        reading.counters.syncookies_sent += 1000;
+        let flood = XdpSynRates {
+            syn_flood_drops: 20_000.0,
+            syn_cookies_issued: 0.0,
+        };
+        let report = monitor
+            .observe(reading, Some(flood), start + Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(report.verdict, SynFloodVerdict::Leaking);
+        assert_eq!(report.kernel_delta.syncookies_sent, 1000);
+        assert!((report.kernel_pressure_per_second - 100.0).abs() < 1e-9);
+        assert_eq!(report.syn_backlog_fill, Some(0.5));
+        assert_eq!(monitor.status().unwrap().verdict, SynFloodVerdict::Leaking);
+    }
+}
@@ -0,0 +1,220 @@
+//! Runtime log filter directives
+//!
+//! Directives such as `config_sync=debug` are layered over the worker's base
+//! filter (`RUST_LOG` or the configured log level) without a restart. The
+//! control plane keeps the directives of each node and sends them with the
+//! register and heartbeat responses; they are applied whenever their
+//! revision changes, so they come back after a worker restart and lapse
+//! when their lifetime ends. Directives set through the admin API apply
+//! immediately and are persisted through the control plane when connected;
+//! otherwise they stay until the control plane sends a new revision.
+//!
+//! Targets without a path (`config_sync`) also match the worker module of
+//! that name (`worker::config_sync`), so operators need not know the crate
+//! name.
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::telemetry;
+use pistonprotection_proto::worker::LogLevel;
+use serde::Serialize;
+use tracing::{info, warn};
+
+/// Crate name of the worker binary, the root of its tracing targets
+const CRATE_TARGET: &str = "worker";
+
+/// Where the applied directives came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevelSource {
+    /// Base filter only
+    Base,
+    /// Directives of the control plane
+    ControlPlane,
+    /// Set through the admin API and not persisted
+    Local,
+}
+
+/// Log filter of the worker
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLevelStatus {
+    pub base: String,
+    pub directives: Vec<String>,
+    pub source: LogLevelSource,
+    /// Control plane revision last applied (0 = none)
+    pub revision: u64,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+struct State {
+    directives: Vec<String>,
+    source: LogLevelSource,
+    revision: u64,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Applies runtime directives to the worker's log filter
+pub struct LogLevels {
+    state: RwLock<State>,
+}
+
+impl Default for LogLevels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogLevels {
+    pub fn new() -> Self {
+        Self {
+            state: RwLock::new(State {
+                directives: Vec::new(),
+                source: LogLevelSource::Base,
+                revision: 0,
+                expires_at: None,
+            }),
+        }
+    }
+
+    /// Apply the directives the control plane holds for this node, from a
+    /// register or heartbeat response (`None` when it holds none)
+    pub fn apply_remote(&self, level: Option<&LogLevel>) {
+        let revision = level.map_or(0, |level| level.revision);
+        let directives = level
+            .map(|level| level.directives.clone())
+            .unwrap_or_default();
+        {
+            let mut state = self.state.write();
+            if state.revision == revision {
+                return;
+            }
+            state.revision = revision;
+        }
+
+        match apply(&directives) {
+            Ok(()) => {
+                let mut state = self.state.write();
+                state.source = if directives.is_empty() {
+                    LogLevelSource::Base
+                } else {
+                    LogLevelSource::ControlPlane
+                };
+                state.expires_at = level
+                    .and_then(|level| level.expires_at.as_ref())
+                    .map(Into::into);
+                state.directives = directives;
+                info!(
+                    revision,
+                    directives = ?state.directives,
+                    "Applied log directives from control plane"
+                );
+            }
+            Err(e) => warn!(revision, error = %e, "Failed to apply log directives"),
+        }
+    }
+
+    /// Apply directives set on this worker; `persisted` is the control
+    /// plane's copy when they were stored there
+    pub fn apply_local(&self, directives: Vec<String>, persisted: Option<&LogLevel>) -> Result<()> {
+        apply(&directives)?;
+
+        let mut state = self.state.write();
+        state.source = match persisted {
+            _ if directives.is_empty() => LogLevelSource::Base,
+            Some(_) => LogLevelSource::ControlPlane,
+            None => LogLevelSource::Local,
+        };
+        if let Some(level) = persisted {
+            state.revision = level.revision;
+            state.expires_at = level.expires_at.as_ref().map(Into::into);
+        } else {
+            state.expires_at = None;
+        }
+        state.directives = directives;
+        Ok(())
+    }
+
+    pub fn status(&self) -> LogLevelStatus {
+        let state = self.state.read();
+        LogLevelStatus {
+            base: telemetry::log_filter()
+                .map(|filter| filter.base().to_string())
+                .unwrap_or_default(),
+            directives: state.directives.clone(),
+            source: state.source,
+            revision: state.revision,
+            expires_at: state.expires_at,
+        }
+    }
+}
+
+/// Replace the runtime directives of the log filter
+fn apply(directives: &[String]) -> Result<()> {
+    let filter = telemetry::log_filter()
+        .ok_or_else(|| Error::Internal("Log filter is not initialized".to_string()))?;
+    filter.set_overrides(qualify(directives))
+}
+
+/// Directives with a worker-qualified copy of each one naming a bare target
+pub fn qualify(directives: &[String]) -> Vec<String> {
+    let mut qualified = Vec::with_capacity(directives.len());
+    for directive in directives {
+        qualified.push(directive.clone());
+
+        let target = directive
+            .find(['[', '='])
+            .map_or("", |end| &directive[..end]);
+        if !target.is_empty() && !target.contains("::") && target != CRATE_TARGET {
+            qualified.push(format!("{}::{}", CRATE_TARGET, directive));
+        }
+    }
+    qualified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directives(list: &[&str]) -> Vec<String> {
+        list.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_qualify() {
+        assert_eq!(
+            qualify(&directives(&["config_sync=debug"])),
+            directives(&["config_sync=debug", "worker::config_sync=debug"])
+        );
+        assert_eq!(
+            qualify(&directives(&["ebpf[load]=trace"])),
+            directives(&["ebpf[load]=trace", "worker::ebpf[load]=trace"])
+        );
+
+        // Bare levels, paths and the crate itself are left alone
+        let unchanged = directives(&["debug", "worker::ebpf=trace", "worker=debug", "[span]=info"]);
+        assert_eq!(qualify(&unchanged), unchanged);
+    }
+
+    #[test]
+    fn test_same_revision_not_reapplied() {
+        let levels = LogLevels::new();
+
+        // Revision 0 is the initial state: nothing to apply
+        levels.apply_remote(None);
+        let status = levels.status();
+        assert_eq!(status.revision, 0);
+        assert_eq!(status.source, LogLevelSource::Base);
+
+        // Without an initialized filter the revision is still recorded, so
+        // a failing directive set is not retried on every heartbeat
+        let level = LogLevel {
+            directives: directives(&["debug"]),
+            revision: 7,
+            ..Default::default()
+        };
+        levels.apply_remote(Some(&level));
+        assert_eq!(levels.status().revision, 7);
+        assert!(levels.status().directives.is_empty());
+    }
+}
@@ -4,19 +4,51 @@
 //! Connects to the control plane gateway for configuration and coordination.
 
 use parking_lot::RwLock;
-use pistonprotection_common::{config::Config, telemetry};
+use pistonprotection_common::backend_mode::{
+    ACTIVE_MODES_KEY, BackendModeOverride, backend_mode_key,
+};
+use pistonprotection_common::bandwidth_quota::{
+    BandwidthThrottle, THROTTLED_BACKENDS_KEY, bandwidth_throttle_key,
+};
+use pistonprotection_common::exemption::{
+    ACTIVE_EXEMPTIONS_KEY, ExemptionToken, exemption_token_key,
+};
+use pistonprotection_common::rate_profile::{
+    ACTIVE_PROFILES_KEY, BackendRateProfiles, rate_profiles_key,
+};
+use pistonprotection_common::restart_mode::{ACTIVE_RESTARTS_KEY, RestartMode, restart_mode_key};
+use pistonprotection_common::{
+    config::{Config, secrets::Secrets},
+    origin_switch, probe,
+    redis::{CacheService, RedisPool},
+    telemetry,
+};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::signal;
 use tokio::sync::watch;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
+mod backend_mode;
+mod block_response;
+mod bootstrap;
+mod canary;
 mod config_sync;
 mod control_plane;
 pub mod ebpf;
+mod exemption;
 mod handlers;
+mod kernel_tcp;
+mod log_levels;
+mod nftables;
+mod origin_probe;
 pub mod protocol;
+mod proxy;
+mod rate_profile;
+mod reputation;
+mod restart_mode;
 pub mod routing;
+mod warmup;
 
 // Tests temporarily disabled - requires refactoring to library crate
 // #[cfg(test)]
@@ -37,6 +69,45 @@ pub struct WorkerRuntime {
     pub control_plane: Arc<ControlPlaneClient>,
     /// Network interfaces
     pub interfaces: Arc<Vec<ebpf::interface::NetworkInterface>>,
+    /// Flow sample analyzer
+    pub sampler: Arc<ebpf::sampling::SampleAnalyzer>,
+    /// Drop events aggregated by source and reason
+    pub drop_events: Arc<ebpf::drop_events::DropEvents>,
+    /// Threat intelligence feeds
+    pub threat_intel: Arc<ebpf::threat_intel::ThreatIntelManager>,
+    /// Signed offender bundles loaded into the threat feeds
+    pub bundles: Arc<ebpf::offender_bundle::OffenderBundles>,
+    /// Source reputation scores
+    pub reputation: Arc<reputation::ReputationEngine>,
+    /// Minecraft player identity limits
+    pub identities: Arc<protocol::minecraft_identity::IdentityThrottle>,
+    /// Keys shared with the gateway: origin probes and switches (requires
+    /// Redis)
+    pub gateway_cache: Option<CacheService>,
+    /// Origin switches in progress
+    pub switches: Arc<routing::OriginSwitches>,
+    /// Backends switched to passthrough or block-all
+    pub modes: Arc<backend_mode::BackendModes>,
+    /// Rate limit profiles of backends
+    pub rate_profiles: Arc<rate_profile::RateProfiles>,
+    /// Backends in restart mode
+    pub restarts: Arc<restart_mode::RestartModes>,
+    /// Load test exemption tokens
+    pub exemptions: Arc<exemption::ExemptionTokens>,
+    /// Known and detected CGNAT addresses
+    pub cgnat: Arc<ebpf::cgnat::CgnatDetector>,
+    /// Kernel TCP pressure correlated with XDP SYN-flood stats
+    pub kernel_tcp: Arc<kernel_tcp::KernelTcpMonitor>,
+    /// nftables fallback where XDP does not run natively
+    pub enforcement: Arc<nftables::NftEnforcer>,
+    /// UDP session affinity table
+    pub affinity: Arc<routing::SessionAffinity>,
+    /// Connection pools of proxied TCP origins
+    pub pools: Arc<routing::OriginPools>,
+    /// Origin response anomalies of HTTP proxy hosts
+    pub origin_anomalies: Arc<proxy::anomaly::OriginAnomalyDetector>,
+    /// Regional warm-up snapshots of learned state
+    pub warmup: Arc<warmup::Warmup>,
     /// Application configuration
     pub config: Arc<Config>,
     /// Shutdown signal sender
@@ -52,7 +123,13 @@ impl WorkerRuntime {
         interfaces: Vec<ebpf::interface::NetworkInterface>,
         config: Config,
         control_plane_config: ControlPlaneConfig,
+        redis: Option<RedisPool>,
     ) -> Self {
+        let enforcement = nftables::NftEnforcer::probe(
+            nftables::NftConfig::from_env(),
+            loader.capabilities(),
+            &interfaces,
+        );
         let loader = Arc::new(RwLock::new(loader));
         let interfaces = Arc::new(interfaces);
 
@@ -69,11 +146,57 @@ impl WorkerRuntime {
 
         let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
+        // Probe, switch and mode keys share the `piston` prefix
+        let gateway_cache = redis
+            .clone()
+            .map(|pool| CacheService::new(pool, probe::PROBE_CACHE_PREFIX));
+
         Self {
             loader,
             config_sync,
             control_plane,
             interfaces,
+            sampler: Arc::new(ebpf::sampling::SampleAnalyzer::new()),
+            drop_events: Arc::new(ebpf::drop_events::DropEvents::new(
+                ebpf::drop_events::DropEventConfig::from_env(),
+            )),
+            threat_intel: Arc::new(ebpf::threat_intel::ThreatIntelManager::new(
+                ebpf::threat_intel::ThreatIntelConfig::from_env(),
+            )),
+            bundles: Arc::new(ebpf::offender_bundle::OffenderBundles::new(
+                ebpf::offender_bundle::BundleConfig::from_env(),
+            )),
+            reputation: Arc::new(reputation::ReputationEngine::new(
+                reputation::ReputationConfig::from_env(),
+                redis,
+            )),
+            identities: Arc::new(protocol::minecraft_identity::IdentityThrottle::new(
+                protocol::minecraft_identity::IdentityPolicy::from_env(),
+            )),
+            gateway_cache,
+            switches: Arc::new(routing::OriginSwitches::new()),
+            modes: Arc::new(backend_mode::BackendModes::new()),
+            rate_profiles: Arc::new(rate_profile::RateProfiles::new()),
+            restarts: Arc::new(restart_mode::RestartModes::new()),
+            exemptions: Arc::new(exemption::ExemptionTokens::new()),
+            cgnat: Arc::new(ebpf::cgnat::CgnatDetector::new(
+                ebpf::cgnat::CgnatConfig::from_env(),
+            )),
+            kernel_tcp: Arc::new(kernel_tcp::KernelTcpMonitor::new(
+                kernel_tcp::KernelTcpConfig::from_env(),
+            )),
+            enforcement: Arc::new(enforcement),
+            affinity: Arc::new(routing::SessionAffinity::new(
+                routing::AffinityConfig::from_env(),
+            )),
+            pools: Arc::new(routing::OriginPools::new(routing::PoolConfig::from_env())),
+            origin_anomalies: Arc::new(proxy::anomaly::OriginAnomalyDetector::new(
+                proxy::anomaly::AnomalyConfig::from_env(),
+            )),
+            warmup: Arc::new(warmup::Warmup::new(
+                warmup::WarmupConfig::from_env(),
+                chrono::Utc::now(),
+            )),
             config: Arc::new(config),
             shutdown_tx,
             shutdown_rx,
@@ -123,11 +246,15 @@ impl WorkerRuntime {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load configuration
-    let config = Config::load(SERVICE_NAME)?;
+    let mut config = Config::load(SERVICE_NAME)?;
 
     // Initialize telemetry
     telemetry::init(SERVICE_NAME, &config.telemetry)?;
 
+    // Resolve credentials kept in Kubernetes Secrets or Vault
+    let secrets = Secrets::from_env()?;
+    config.resolve_secrets(&secrets).await?;
+
     info!(
         "Starting {} service v{}",
         SERVICE_NAME,
@@ -156,7 +283,51 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Initialize eBPF loader
-    let ebpf_loader = ebpf::loader::EbpfLoader::new()?;
+    let mut ebpf_loader = ebpf::loader::EbpfLoader::new()?;
+    ebpf_loader.set_sampling_config(ebpf::sampling::SamplingConfig::from_env());
+    ebpf_loader.set_latency_rates(ebpf::latency::LatencyRates::from_env());
+    ebpf_loader.set_event_budget(ebpf::event_budget::EventBudgetConfig::from_env());
+    ebpf_loader.set_map_budgets(ebpf::capacity::MapBudgets::from_env());
+    ebpf_loader.set_batcher(ebpf::batch::Batcher::from_env());
+    if let Ok(reject) = std::env::var("PISTON_QUIC_REJECT_0RTT") {
+        ebpf_loader.set_reject_0rtt(matches!(reject.as_str(), "1" | "true" | "on"))?;
+    }
+    ebpf_loader.set_quic_cid_limit(ebpf::quic_cid::QuicCidLimit::from_env())?;
+    ebpf_loader.set_dns_budget(ebpf::dns::DnsBudget::from_env())?;
+    if let Ok(path) = std::env::var("PISTON_BPF_PIN_PATH") {
+        ebpf_loader.set_map_pin_path(path);
+    }
+    let artifacts = ebpf::artifacts::ArtifactVerifier::from_env()?;
+    if artifacts.enforced() {
+        info!(
+            "Artifact signatures enforced, trusted keys: {}",
+            artifacts.status().trusted_keys.join(", ")
+        );
+    } else {
+        warn!("No artifact signing keys trusted, object files and bootstrap config are unchecked");
+    }
+    ebpf_loader.set_artifact_verifier(artifacts);
+
+    // Probe kernel features to pick the program variant
+    let kernel = ebpf::probe::KernelCapabilities::probe();
+    info!(
+        "Kernel {} BPF features: {}",
+        kernel.version,
+        kernel.feature_names().join(", ")
+    );
+    match kernel.select_variant() {
+        Some(variant) => info!("Using {} program variant", variant),
+        None => warn!(
+            "Kernel lacks required BPF features ({}), programs cannot be loaded",
+            kernel
+                .missing_baseline()
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+    ebpf_loader.set_capabilities(kernel);
 
     // Load control plane configuration from environment
     let control_plane_config = ControlPlaneConfig::from_env();
@@ -171,14 +342,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         control_plane_config.metrics_interval
     );
 
-    // Create worker runtime
-    let runtime = Arc::new(WorkerRuntime::new(
-        ebpf_loader,
-        interfaces.clone(),
-        config.clone(),
-        control_plane_config.clone(),
-    ));
-
     // Initialize Redis connection for config updates (optional)
     let redis_pool = if let Some(ref redis_config) = config.redis {
         match pistonprotection_common::redis::create_pool(redis_config).await {
@@ -199,6 +362,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
+    // Create worker runtime
+    let runtime = Arc::new(WorkerRuntime::new(
+        ebpf_loader,
+        interfaces.clone(),
+        config.clone(),
+        control_plane_config.clone(),
+        redis_pool.clone(),
+    ));
+
+    let choice = runtime.enforcement.choice();
+    match choice.backend {
+        nftables::Enforcement::Xdp => info!("Enforcing with XDP: {}", choice.reason),
+        nftables::Enforcement::Nftables => {
+            warn!("Enforcing with the nftables fallback: {}", choice.reason)
+        }
+    }
+
     // Create worker state for HTTP handlers
     let worker_state = handlers::WorkerState::new(
         Arc::clone(&runtime.loader),
@@ -207,6 +387,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         redis_pool,
         Arc::clone(&runtime.config),
         Arc::clone(&runtime.interfaces),
+        Arc::clone(&runtime.sampler),
+        Arc::clone(&runtime.threat_intel),
+        Arc::clone(&runtime.bundles),
+        Arc::clone(&runtime.reputation),
+        Arc::clone(&runtime.identities),
+        Arc::clone(&runtime.switches),
+        Arc::clone(&runtime.pools),
+        Arc::clone(&runtime.origin_anomalies),
+        Arc::clone(&runtime.modes),
+        Arc::clone(&runtime.rate_profiles),
+        Arc::clone(&runtime.restarts),
+        Arc::clone(&runtime.exemptions),
+        Arc::clone(&runtime.cgnat),
+        Arc::clone(&runtime.kernel_tcp),
+        Arc::clone(&runtime.enforcement),
+        Arc::clone(&runtime.drop_events),
+        Arc::clone(&runtime.warmup),
     );
 
     // Start HTTP server (health checks, metrics)
@@ -227,6 +424,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    // Serve the same API on a unix socket for operators on the node
+    let local_api_handle = spawn_local_api(worker_state.clone());
+
     // Start control plane connection (unless in standalone mode)
     let is_standalone = std::env::var("PISTON_STANDALONE").is_ok();
     let control_plane_handle = if !is_standalone {
@@ -247,6 +447,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
+    // Load and attach programs from the bootstrap config (if mounted)
+    let bootstrap_handle = spawn_bootstrap_task(Arc::clone(&runtime));
+
     // Start periodic tasks
     let periodic_handle = spawn_periodic_tasks(Arc::clone(&runtime));
 
@@ -256,6 +459,78 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Monitor control plane state changes
     let state_monitor_handle = spawn_state_monitor(Arc::clone(&runtime));
 
+    // Drain flow samples from the XDP programs
+    let sampling_handle = spawn_sampling_task(Arc::clone(&runtime));
+
+    // Aggregate XDP drop events by source and reason
+    let drop_events_handle = spawn_drop_event_task(Arc::clone(&runtime));
+
+    // Import threat intelligence feeds
+    let threat_intel_handle = spawn_threat_intel_task(Arc::clone(&runtime));
+
+    // Load the signed offender bundle and check for newer ones
+    let bundle_handle = spawn_bundle_task(Arc::clone(&runtime));
+
+    // Score source reputation and program the greylist
+    let reputation_handle = spawn_reputation_task(Arc::clone(&runtime));
+
+    // Collect sources caught on honeypot ports
+    let honeypot_handle = spawn_honeypot_task(Arc::clone(&runtime));
+
+    // Learn known-good sources of backends in learning mode
+    let learning_handle = spawn_learning_task(Arc::clone(&runtime));
+
+    // Relax per-IP limits of CGNAT addresses and detect new ones
+    let cgnat_handle = spawn_cgnat_task(Arc::clone(&runtime));
+
+    // Correlate kernel SYN and accept queue pressure with XDP SYN floods
+    let kernel_tcp_handle = spawn_kernel_tcp_task(Arc::clone(&runtime));
+
+    // Program the nftables fallback where XDP does not run natively
+    let enforcement_handle = spawn_enforcement_task(Arc::clone(&runtime));
+
+    // Block the addresses of repeat-offending Minecraft identities
+    let identity_handle = spawn_identity_task(Arc::clone(&runtime));
+
+    // Ping origins for backends being onboarded
+    let probe_handle = spawn_probe_task(Arc::clone(&runtime));
+
+    // Drain old origins of backends switching origin
+    let switch_handle = spawn_switch_task(Arc::clone(&runtime));
+
+    // Apply passthrough and block-all modes of backends
+    let mode_handle = spawn_mode_task(Arc::clone(&runtime));
+
+    // Switch rate limit profiles on schedule
+    let profile_handle = spawn_profile_task(Arc::clone(&runtime));
+
+    // Relax limits of restarting backends until their restart mode ends
+    let restart_handle = spawn_restart_task(Arc::clone(&runtime));
+
+    // Whitelist the tester addresses of load test exemption tokens
+    let exemption_handle = spawn_exemption_task(Arc::clone(&runtime));
+
+    // Cap connections to backends over their monthly bandwidth
+    let throttle_handle = spawn_throttle_task(Arc::clone(&runtime));
+
+    // Merge the per-CPU source counters of the XDP programs
+    let sources_handle = spawn_sources_task(Arc::clone(&runtime));
+
+    // Return connection slots xdp_tcp never saw closed
+    let connections_handle = spawn_connections_task(Arc::clone(&runtime));
+
+    // Expire idle UDP sessions pinned to origins
+    let affinity_handle = spawn_affinity_task(Arc::clone(&runtime));
+
+    // Mitigate origin error spikes of HTTP proxy hosts
+    let anomaly_handle = spawn_anomaly_task(Arc::clone(&runtime));
+
+    // Warm up from the learned state of the region and share our own
+    let warmup_handle = spawn_warmup_task(Arc::clone(&runtime));
+
+    // Serve HTTP backends through the caching reverse proxy (if configured)
+    let http_proxy_handle = spawn_http_proxy(&runtime).await;
+
     // Wait for shutdown signal
     shutdown_signal().await;
     info!("Shutdown signal received");
@@ -281,18 +556,55 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             warn!("Shutdown timeout reached, forcing exit");
         }
         _ = async {
+            bootstrap_handle.abort();
             periodic_handle.abort();
             cleanup_handle.abort();
             state_monitor_handle.abort();
+            sampling_handle.abort();
+            drop_events_handle.abort();
+            threat_intel_handle.abort();
+            bundle_handle.abort();
+            reputation_handle.abort();
+            honeypot_handle.abort();
+            learning_handle.abort();
+            cgnat_handle.abort();
+            kernel_tcp_handle.abort();
+            enforcement_handle.abort();
+            identity_handle.abort();
+            probe_handle.abort();
+            switch_handle.abort();
+            mode_handle.abort();
+            profile_handle.abort();
+            restart_handle.abort();
+            exemption_handle.abort();
+            throttle_handle.abort();
+            sources_handle.abort();
+            connections_handle.abort();
+            affinity_handle.abort();
+            anomaly_handle.abort();
+            warmup_handle.abort();
             if let Some(h) = control_plane_handle {
                 h.abort();
             }
+            if let Some(h) = http_proxy_handle {
+                h.abort();
+            }
+            if let Some(h) = local_api_handle {
+                h.abort();
+            }
             http_handle.abort();
         } => {
             info!("All tasks terminated");
         }
     }
 
+    // Remove the nftables fallback like the XDP programs are detached
+    if runtime.enforcement.active() {
+        if let Err(e) = runtime.enforcement.remove().await {
+            warn!("Failed to remove nftables table: {}", e);
+        }
+    }
+
     // Cleanup eBPF programs
     info!("Cleaning up eBPF programs...");
     // Note: Programs are automatically detached when the loader is dropped
@@ -304,12 +616,140 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Spawn the node-local admin API on a unix socket (if enabled)
+fn spawn_local_api(state: handlers::WorkerState) -> Option<tokio::task::JoinHandle<()>> {
+    let config = handlers::local::LocalApiConfig::from_env();
+    if !config.enabled {
+        info!("Local API disabled");
+        return None;
+    }
+
+    let router = handlers::http::create_router(state);
+    Some(tokio::spawn(async move {
+        if let Err(e) = handlers::local::serve(router, config).await {
+            error!(error = %e, "Local API error");
+        }
+    }))
+}
+
+/// Spawn task applying the bootstrap config and following changes to it
+///
+/// The config is re-applied when the mounted ConfigMap changes or the node
+/// labels selecting the pool do.
+fn spawn_bootstrap_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let settings = bootstrap::BootstrapSettings::from_env();
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        if runtime.enforcement.active() {
+            info!("nftables enforces on this node, bootstrap programs are not attached");
+            return;
+        }
+        if !settings.path.exists() {
+            info!(
+                "No bootstrap config at {}, programs are left to the control plane",
+                settings.path.display()
+            );
+            return;
+        }
+
+        let mut interval = tokio::time::interval(settings.poll_interval);
+        let mut applied_contents: Option<(String, Option<String>)> = None;
+        let mut labels = None;
+        let mut labels_fetched: Option<std::time::Instant> = None;
+        let mut attached = std::collections::BTreeMap::new();
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Bootstrap task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let contents = match tokio::fs::read_to_string(&settings.path).await {
+                        Ok(contents) => contents,
+                        Err(e) => {
+                            warn!("Failed to read bootstrap config: {}", e);
+                            continue;
+                        }
+                    };
+                    let signature = tokio::fs::read_to_string(
+                        pistonprotection_common::signing::signature_path(&settings.path),
+                    )
+                    .await
+                    .ok();
+                    let contents = (contents, signature);
+                    let mut changed = applied_contents.as_ref() != Some(&contents);
+
+                    if labels_fetched.is_none_or(|at| at.elapsed() >= settings.label_refresh) {
+                        match bootstrap::node_labels(&settings).await {
+                            Ok(fetched) => {
+                                changed |= labels.as_ref() != Some(&fetched);
+                                labels = Some(fetched);
+                                labels_fetched = Some(std::time::Instant::now());
+                            }
+                            Err(e) => warn!("Failed to get node labels: {}", e),
+                        }
+                    }
+                    let Some(node_labels) = &labels else {
+                        continue;
+                    };
+                    if !changed {
+                        continue;
+                    }
+
+                    // Invalid contents are not retried until the ConfigMap changes
+                    let verified = runtime.loader.write().verify_artifact(
+                        bootstrap::ARTIFACT,
+                        contents.0.as_bytes(),
+                        contents.1.as_deref(),
+                    );
+                    let plan = verified
+                        .and_then(|_| bootstrap::BootstrapConfig::parse(&contents.0))
+                        .and_then(|config| {
+                            bootstrap::plan(&config, node_labels, &runtime.interfaces)
+                        });
+                    applied_contents = Some(contents);
+                    let plan = match plan {
+                        Ok(plan) => plan,
+                        Err(e) => {
+                            error!("Ignoring bootstrap config: {}", e);
+                            continue;
+                        }
+                    };
+
+                    match &plan.pool {
+                        Some(pool) => info!(
+                            "Applying bootstrap pool {} ({} programs on {} interfaces)",
+                            pool,
+                            plan.programs.len(),
+                            plan.attachments.len()
+                        ),
+                        None => info!("No bootstrap pool matches this node"),
+                    }
+                    let mut loader = runtime.loader.write();
+                    attached = bootstrap::apply(
+                        &mut loader,
+                        runtime.config_sync.program_dir(),
+                        &runtime.interfaces,
+                        &attached,
+                        &plan,
+                    );
+                }
+            }
+        }
+    })
+}
+
 /// Spawn periodic tasks (metrics collection, health checks)
 fn spawn_periodic_tasks(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
     let mut shutdown_rx = runtime.shutdown_receiver();
 
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        let mut occupancy = ebpf::capacity::OccupancyWatch::default();
 
         loop {
             tokio::select! {
@@ -335,6 +775,15 @@ fn spawn_periodic_tasks(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<
 
                     // Update Prometheus metrics
                     update_prometheus_metrics(&runtime);
+
+                    // Export map usage and warn about maps close to full
+                    match ebpf::capacity::CapacityReport::from_loader(Arc::clone(&runtime.loader)).await {
+                        Ok(report) => {
+                            report.export_metrics();
+                            occupancy.check(&report);
+                        }
+                        Err(e) => warn!("Failed to collect map capacity: {}", e),
+                    }
                 }
             }
         }
@@ -359,88 +808,1603 @@ fn spawn_cleanup_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()
                 }
                 _ = interval.tick() => {
                     // Cleanup expired entries in eBPF maps
-                    let loader = runtime.loader.read();
-                    let maps = loader.maps();
-                    let mut map_manager = maps.write();
-                    map_manager.cleanup_expired();
+                    {
+                        let loader = runtime.loader.read();
+                        let maps = loader.maps();
+                        let mut map_manager = maps.write();
+                        map_manager.cleanup_expired();
+                    }
+
+                    // Drop connection pools of origins without connections
+                    runtime.pools.prune();
+
+                    // Conclude canary evaluations whose period has elapsed
+                    if let Err(e) = runtime.config_sync.check_canary_expiry().await {
+                        error!("Failed to conclude canary evaluation: {}", e);
+                    }
                 }
             }
         }
     })
 }
 
-/// Spawn control plane state monitor
-fn spawn_state_monitor(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
-    let mut state_rx = runtime.control_plane.subscribe_state_changes();
+/// Spawn task merging the per-CPU source counters into per-source rates
+fn spawn_sources_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
     let mut shutdown_rx = runtime.shutdown_receiver();
 
     tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(10));
+
         loop {
             tokio::select! {
                 _ = shutdown_rx.changed() => {
                     if *shutdown_rx.borrow() {
-                        info!("State monitor shutting down");
+                        info!("Source counter task shutting down");
                         break;
                     }
                 }
-                result = state_rx.changed() => {
-                    if result.is_err() {
+                _ = interval.tick() => {
+                    if let Err(e) = runtime.loader.read().merge_source_counters() {
+                        debug!(error = %e, "Failed to merge source counters");
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn task reconciling the per-source connection counters of xdp_tcp
+/// with its connection table
+fn spawn_connections_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Connection budget task shutting down");
                         break;
                     }
+                }
+                _ = interval.tick() => {
+                    let mut loader = runtime.loader.write();
+                    if loader.program_generation("xdp_tcp") == 0 {
+                        continue;
+                    }
+                    match loader.reconcile_connections(ebpf::connections::DEFAULT_IDLE_TIMEOUT_NS) {
+                        Ok(report) if report.slots_returned > 0 || report.stale_removed > 0 => info!(
+                            open_connections = report.open_connections,
+                            sources = report.sources_lowered,
+                            slots_returned = report.slots_returned,
+                            stale_removed = report.stale_removed,
+                            "Reconciled per-source connection counters"
+                        ),
+                        Ok(_) => {}
+                        Err(e) => warn!(error = %e, "Failed to reconcile connection counters"),
+                    }
+                }
+            }
+        }
+    })
+}
 
-                    let state = *state_rx.borrow();
-                    match state {
-                        ConnectionState::Connected => {
-                            info!("Control plane connection established");
-                            // Trigger config sync on reconnection
-                            runtime.config_sync.trigger_sync();
-                        }
-                        ConnectionState::Disconnected => {
-                            warn!("Control plane connection lost");
-                        }
-                        ConnectionState::Reconnecting => {
-                            info!("Attempting to reconnect to control plane...");
-                        }
-                        ConnectionState::ShuttingDown => {
-                            info!("Control plane connection shutting down");
-                        }
-                        ConnectionState::Connecting => {
-                            info!("Connecting to control plane...");
+/// Spawn affinity task expiring idle UDP sessions and exporting the table
+/// occupancy
+fn spawn_affinity_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(10));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Affinity task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let expired = runtime.affinity.prune(std::time::Instant::now());
+                    if expired > 0 {
+                        debug!(expired, "Expired idle UDP sessions");
+                    }
+                    runtime.affinity.record_metrics();
+                }
+            }
+        }
+    })
+}
+
+/// Spawn anomaly task evaluating origin responses of HTTP proxy hosts and
+/// reporting the mitigations it starts to the control plane
+fn spawn_anomaly_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(runtime.origin_anomalies.window());
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Anomaly task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let events = runtime
+                        .origin_anomalies
+                        .evaluate(std::time::Instant::now());
+                    for event in events {
+                        let incident = match event {
+                            proxy::anomaly::AnomalyEvent::Opened(incident)
+                            | proxy::anomaly::AnomalyEvent::Escalated(incident) => incident,
+                            proxy::anomaly::AnomalyEvent::Resolved(incident) => {
+                                info!(
+                                    host = %incident.host,
+                                    incident = %incident.id,
+                                    "Origin anomaly resolved, mitigation lifted"
+                                );
+                                continue;
+                            }
+                        };
+                        warn!(
+                            host = %incident.host,
+                            incident = %incident.id,
+                            mitigation = incident.mitigation.as_str(),
+                            rps = incident.peak_rps,
+                            error_rate = incident.peak_error_rate,
+                            "Origin failing under request spike, mitigating"
+                        );
+
+                        if runtime.control_plane.is_connected() {
+                            let attack = control_plane::AttackInfo {
+                                backend_id: incident.host.clone(),
+                                attack_type: format!(
+                                    "l7_origin_errors_{}",
+                                    incident.mitigation.as_str()
+                                ),
+                                attack_pps: incident.peak_rps as u64,
+                                attack_bps: 0,
+                                sources: Vec::new(),
+                            };
+                            if let Err(e) = runtime.control_plane.report_attack(attack).await {
+                                warn!(incident = %incident.id, "Failed to report origin anomaly: {}", e);
+                            }
                         }
                     }
+                }
+            }
+        }
+    })
+}
 
-                    // Update connection state metric
-                    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
-                        .with_label_values(&["worker", "control_plane"])
-                        .set(if state == ConnectionState::Connected { 1.0 } else { 0.0 });
+/// Start the caching HTTP reverse proxy if origins are configured
+async fn spawn_http_proxy(runtime: &Arc<WorkerRuntime>) -> Option<tokio::task::JoinHandle<()>> {
+    let proxy = Arc::new(proxy::HttpProxy::new(
+        proxy::HttpProxyConfig::from_env()?,
+        Arc::clone(&runtime.pools),
+        Arc::clone(&runtime.origin_anomalies),
+        Arc::clone(&runtime.modes),
+        runtime.config_sync.block_responses(),
+    ));
+
+    let addr = proxy.listen_addr();
+    match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => Some(tokio::spawn(
+            proxy.serve(listener, runtime.shutdown_receiver()),
+        )),
+        Err(e) => {
+            error!(error = %e, addr = %addr, "Failed to bind HTTP proxy");
+            None
+        }
+    }
+}
+
+/// Spawn flow sampling task draining sample ring buffers into the analyzer
+fn spawn_sampling_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    /// Upper bound on samples drained per program per tick
+    const MAX_SAMPLES_PER_TICK: usize = 4096;
+
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(250));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Sampling task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let drained: Vec<_> = {
+                        let loader = runtime.loader.read();
+                        loader
+                            .sampled_programs()
+                            .into_iter()
+                            .map(|program| {
+                                let samples = loader.drain_samples(&program, MAX_SAMPLES_PER_TICK);
+                                (program, samples)
+                            })
+                            .collect()
+                    };
+
+                    for (program, samples) in drained {
+                        runtime.sampler.ingest(&program, &samples);
+                        record_threat_intel_sightings(&runtime, &samples);
+                    }
                 }
             }
         }
     })
 }
 
-/// Update Prometheus metrics
-fn update_prometheus_metrics(runtime: &WorkerRuntime) {
-    let loader = runtime.loader.read();
-    let maps = loader.maps();
-    let map_stats = maps.read().stats();
+/// Spawn drop event task draining the drop event rings and flushing the
+/// aggregated summaries every flush interval
+fn spawn_drop_event_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    /// Upper bound on events drained per program per tick
+    const MAX_EVENTS_PER_TICK: usize = 65_536;
 
-    // Update eBPF map metrics
-    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
-        .with_label_values(&["worker", "conntrack"])
-        .set(map_stats.conntrack_entries as f64);
+    let mut shutdown_rx = runtime.shutdown_receiver();
 
-    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
-        .with_label_values(&["worker", "blocked_ips"])
-        .set(map_stats.blocked_ips as f64);
+    tokio::spawn(async move {
+        let mut drain = tokio::time::interval(tokio::time::Duration::from_millis(100));
+        let mut flush = tokio::time::interval(runtime.drop_events.flush_interval());
+        flush.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
-    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
-        .with_label_values(&["worker", "rate_limits"])
-        .set(map_stats.rate_limits as f64);
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Drop event task shutting down");
+                        break;
+                    }
+                }
+                _ = drain.tick() => {
+                    let drained: Vec<_> = {
+                        let loader = runtime.loader.read();
+                        loader
+                            .drop_event_programs()
+                            .into_iter()
+                            .map(|program| loader.drain_drop_events(&program, MAX_EVENTS_PER_TICK))
+                            .collect()
+                    };
+
+                    for events in drained {
+                        runtime.drop_events.ingest(&events);
+                    }
+                }
+                _ = flush.tick() => {
+                    let flushed = runtime.drop_events.flush(chrono::Utc::now());
+                    if let Some(top) = flushed.summaries.iter().max_by_key(|s| s.packets) {
+                        debug!(
+                            summaries = flushed.summaries.len(),
+                            top_src = %top.src,
+                            top_reason = top.reason,
+                            top_packets = top.packets,
+                            "Flushed drop event summaries"
+                        );
+                    }
+                    let shed: u64 = flushed.shed.iter().map(|s| s.packets).sum();
+                    if shed > 0 {
+                        warn!(
+                            shed,
+                            "Drop event high-water mark reached, shed events of new sources"
+                        );
+                    }
+                }
+            }
+        }
+    })
+}
 
-    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
-        .with_label_values(&["worker", "backends"])
-        .set(map_stats.backends as f64);
+/// Spawn threat intelligence task fetching due feeds and programming
+/// xdp_filter
+fn spawn_threat_intel_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    /// Upper bound on a single feed download
+    const FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        if runtime.threat_intel.is_empty() {
+            info!("No threat intelligence feeds configured");
+            return;
+        }
+
+        let client = match reqwest::Client::builder().timeout(FETCH_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to create threat feed HTTP client: {}", e);
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+        // Map contents last written: (manager revision, xdp_filter generation)
+        let mut programmed = None;
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Threat intelligence task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let threat_intel = &runtime.threat_intel;
+                    for (feed_id, feed) in threat_intel.due_feeds(std::time::Instant::now()) {
+                        let result = ebpf::threat_intel::fetch_feed(&client, &feed).await;
+                        if let Err(e) = &result {
+                            warn!(feed = %feed.name, error = %e, "Failed to fetch threat feed");
+                        }
+                        threat_intel.record_fetch(feed_id, result, std::time::Instant::now());
+                    }
+
+                    // Reprogram on feed changes and after xdp_filter is reloaded
+                    let mut loader = runtime.loader.write();
+                    let generation = loader.program_generation("xdp_filter");
+                    let current = (threat_intel.revision(), generation);
+                    if generation > 0 && programmed != Some(current) {
+                        match loader.set_threat_intel(&threat_intel.map_entries()) {
+                            Ok(()) => {
+                                debug!(revision = current.0, "Programmed threat intelligence maps");
+                                programmed = Some(current);
+                            }
+                            Err(e) => error!("Failed to program threat intelligence maps: {}", e),
+                        }
+                    }
+
+                    if let Ok(hits) = loader.read_threat_feed_hits() {
+                        threat_intel.record_hits(&hits);
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn offender bundle task loading the verified bundle into the threat
+/// feeds, saving newer bundles and rolling back versions that cause
+/// false-positive spikes
+fn spawn_bundle_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    /// Upper bound on a single bundle download
+    const FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let bundles = Arc::clone(&runtime.bundles);
+        if !bundles.config().enabled() {
+            debug!("No offender bundle keys configured");
+            return;
+        }
+        for (path, e) in bundles.scan() {
+            warn!(path = %path.display(), "Skipping offender bundle: {}", e);
+        }
+
+        let client = match reqwest::Client::builder().timeout(FETCH_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to create offender bundle HTTP client: {}", e);
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+        let mut next_fetch = std::time::Instant::now();
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Offender bundle task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let now = chrono::Utc::now();
+
+                    if let Some(url) = &bundles.config().url
+                        && next_fetch <= std::time::Instant::now()
+                    {
+                        let result = match ebpf::offender_bundle::fetch_bundle(&client, url).await {
+                            Ok((content, signature)) => bundles.store(&content, &signature),
+                            Err(e) => Err(e),
+                        };
+                        let retry = match &result {
+                            Ok(Some(version)) => {
+                                info!(version, "Saved new offender bundle");
+                                bundles.config().refresh_interval
+                            }
+                            Ok(None) => bundles.config().refresh_interval,
+                            Err(e) => {
+                                warn!("Failed to refresh offender bundle: {}", e);
+                                ebpf::threat_intel::RETRY_INTERVAL
+                                    .min(bundles.config().refresh_interval)
+                            }
+                        };
+                        bundles.record_fetch(&result, now);
+                        next_fetch = std::time::Instant::now() + retry;
+                    }
+
+                    if let Some(hits) = runtime.threat_intel.feed_hits(ebpf::threat_intel::BUNDLE_FEED)
+                        && let Some(reason) = bundles.observe_drops(hits.packets, now)
+                    {
+                        warn!(version = ?bundles.active(), "Rolling back offender bundle: {}", reason);
+                    }
+
+                    match bundles.reconcile(&runtime.threat_intel, now) {
+                        Ok(Some(ebpf::offender_bundle::BundleChange::Loaded { version, previous })) => {
+                            info!(version, previous = ?previous, "Loaded offender bundle");
+                        }
+                        Ok(Some(ebpf::offender_bundle::BundleChange::Unloaded { previous })) => {
+                            warn!(previous, "No offender bundle left to load, bundle feed emptied");
+                        }
+                        Ok(None) => {}
+                        Err(e) => error!("Failed to load offender bundle: {}", e),
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Feed sources listed by count-only threat feeds into their reputation
+///
+/// Samples are only taken of packets xdp_filter passed, so sources of
+/// enforcing feeds never show up here.
+fn record_threat_intel_sightings(
+    runtime: &WorkerRuntime,
+    samples: &[ebpf::sampling::PacketSample],
+) {
+    for sample in samples {
+        let Some(ip) = ebpf::sampling::source_addr(sample.captured()) else {
+            continue;
+        };
+        if let Some(confidence) = runtime.threat_intel.monitored_listing(ip) {
+            runtime
+                .reputation
+                .record(ip, reputation::ReputationEvent::ThreatIntel { confidence });
+        }
+    }
+}
+
+/// Spawn reputation task scoring recorded events and programming the
+/// xdp_filter greylist
+fn spawn_reputation_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(10));
+        // Greylist last written and the xdp_filter generation it was written to
+        let mut programmed: Option<(Vec<std::net::IpAddr>, u64)> = None;
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Reputation task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let reputation = &runtime.reputation;
+                    let blocked = runtime.loader.read().maps().write().take_block_events();
+                    for ip in blocked {
+                        reputation.record(ip, reputation::ReputationEvent::Blocked);
+                    }
+
+                    let now = chrono::Utc::now().timestamp();
+                    if let Err(e) = reputation.flush(now).await {
+                        warn!("Failed to update reputation scores: {}", e);
+                    }
+                    let greylist = match reputation.refresh_greylist(now).await {
+                        Ok(greylist) => greylist,
+                        Err(e) => {
+                            warn!("Failed to load reputation greylist: {}", e);
+                            continue;
+                        }
+                    };
+
+                    let mut loader = runtime.loader.write();
+                    let generation = loader.program_generation("xdp_filter");
+                    if generation == 0 {
+                        continue;
+                    }
+                    let current = (greylist.iter().map(|source| source.ip).collect(), generation);
+                    if programmed.as_ref() != Some(&current) {
+                        match loader.set_greylist(&reputation.map_entries()) {
+                            Ok(()) => {
+                                debug!(sources = greylist.len(), "Programmed reputation greylist");
+                                programmed = Some(current);
+                            }
+                            Err(e) => error!("Failed to program reputation greylist: {}", e),
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn honeypot task collecting the sources xdp_filter saw on honeypot
+/// ports
+fn spawn_honeypot_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Honeypot task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let mut loader = runtime.loader.write();
+                    if loader.program_generation("xdp_filter") == 0 {
+                        continue;
+                    }
+                    let hits = match loader.take_honeypot_hits() {
+                        Ok(hits) => hits,
+                        Err(e) => {
+                            warn!("Failed to read honeypot hits: {}", e);
+                            continue;
+                        }
+                    };
+                    let maps = loader.maps();
+                    drop(loader);
+
+                    let mut maps = maps.write();
+                    for (ip, hit) in hits {
+                        match maps.record_honeypot_hit(ip, &hit) {
+                            Ok(source) => info!(
+                                ip = %ip,
+                                port = source.port,
+                                blocked = source.blocked,
+                                "Source touched honeypot port"
+                            ),
+                            Err(e) => warn!("Failed to record honeypot hit from {}: {}", ip, e),
+                        }
+                        runtime
+                            .reputation
+                            .record(ip, reputation::ReputationEvent::Honeypot);
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn learning task crediting recorded handshakes to training backends
+/// and programming the known-good sources
+fn spawn_learning_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Learning task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let mut loader = runtime.loader.write();
+                    let handshakes = match loader.take_handshakes() {
+                        Ok(handshakes) => handshakes,
+                        Err(e) => {
+                            warn!("Failed to read recorded handshakes: {}", e);
+                            continue;
+                        }
+                    };
+
+                    let maps = loader.maps();
+                    let (training, known_good) = {
+                        let mut maps = maps.write();
+                        maps.record_handshakes(&handshakes);
+                        (maps.learning_active(), maps.known_good_entries())
+                    };
+                    if let Err(e) = loader.set_learning(training, known_good) {
+                        warn!("Failed to update known-good sources: {}", e);
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn warm-up task importing the snapshot of the worker's region until one
+/// is imported or the worker has learned enough itself, then publishing the
+/// worker's own learned state to it
+fn spawn_warmup_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let Some(cache) = runtime.gateway_cache.clone() else {
+            return;
+        };
+        let warmup = Arc::clone(&runtime.warmup);
+        let key = warmup.key();
+        let mut interval = tokio::time::interval(warmup.config().publish_interval);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Warm-up task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let now = chrono::Utc::now();
+
+                    if !warmup.imported() && !warmup.seasoned(now) {
+                        match cache.get::<warmup::WarmupSnapshot>(&key).await {
+                            Ok(Some(snapshot)) => {
+                                let imported = warmup.import(
+                                    snapshot,
+                                    &runtime.reputation,
+                                    &runtime.origin_anomalies,
+                                    now,
+                                    |learning| {
+                                        let loader = runtime.loader.read();
+                                        let maps = loader.maps();
+                                        let mut maps = maps.write();
+                                        maps.import_learning(learning)
+                                    },
+                                );
+                                match imported {
+                                    Ok(report) => info!(
+                                        region = %warmup.config().region,
+                                        source = ?report.source_worker_id,
+                                        learning = report.learning_backends,
+                                        reputation = report.reputation_sources,
+                                        baselines = report.baselines,
+                                        "Imported warm-up snapshot"
+                                    ),
+                                    Err(e) => warn!("Ignoring warm-up snapshot: {}", e),
+                                }
+                            }
+                            Ok(None) => debug!(
+                                region = %warmup.config().region,
+                                "No warm-up snapshot for region"
+                            ),
+                            Err(e) => warn!("Failed to read warm-up snapshot: {}", e),
+                        }
+                        continue;
+                    }
+
+                    let snapshot = warmup::WarmupSnapshot::collect(
+                        &warmup.config().region,
+                        runtime.control_plane.worker_id(),
+                        runtime.loader.read().maps().read().export_learning(),
+                        &runtime.reputation,
+                        &runtime.origin_anomalies,
+                        now,
+                    );
+                    if !warmup.should_publish(&snapshot, now) {
+                        continue;
+                    }
+                    match cache.set(&key, &snapshot, warmup.config().max_age).await {
+                        Ok(()) => {
+                            warmup.published(&snapshot);
+                            debug!(region = %warmup.config().region, "Published warm-up snapshot");
+                        }
+                        Err(e) => warn!("Failed to publish warm-up snapshot: {}", e),
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn CGNAT task evaluating the recorded SYN signals and programming
+/// the CGNAT ranges
+fn spawn_cgnat_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("CGNAT task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let cgnat = &runtime.cgnat;
+                    let mut loader = runtime.loader.write();
+                    match loader.take_cgnat_signals() {
+                        Ok(signals) => {
+                            for source in cgnat.record(&signals, chrono::Utc::now()) {
+                                info!(
+                                    ip = %source.ip,
+                                    clocks = source.clocks,
+                                    fingerprints = source.fingerprints,
+                                    "Source looks like a CGNAT address"
+                                );
+                            }
+                        }
+                        Err(e) => warn!("Failed to read CGNAT signals: {}", e),
+                    }
+
+                    if let Err(e) = loader.set_cgnat(cgnat.detecting(), cgnat.map_entries()) {
+                        warn!("Failed to update CGNAT ranges: {}", e);
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn kernel TCP task correlating the node's SYN and accept queue
+/// counters with the SYN-flood stats of xdp_tcp
+fn spawn_kernel_tcp_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(10));
+        let mut last_verdict = kernel_tcp::SynFloodVerdict::Quiet;
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Kernel TCP task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let reading = match runtime.kernel_tcp.read() {
+                        Ok(reading) => reading,
+                        Err(e) => {
+                            warn!("Failed to read kernel TCP counters: {}", e);
+                            continue;
+                        }
+                    };
+
+                    let xdp = {
+                        let loader = runtime.loader.read();
+                        if loader.program_generation("xdp_tcp") == 0 {
+                            None
+                        } else {
+                            loader
+                                .read_stats::<ebpf::stats::TcpStats>()
+                                .ok()
+                                .and_then(|snapshot| {
+                                    kernel_tcp::XdpSynRates::from_snapshot(&snapshot)
+                                })
+                        }
+                    };
+
+                    let now = std::time::Instant::now();
+                    let Some(report) = runtime.kernel_tcp.observe(reading, xdp, now) else {
+                        continue;
+                    };
+                    if report.verdict == last_verdict {
+                        continue;
+                    }
+                    last_verdict = report.verdict;
+
+                    match report.verdict {
+                        kernel_tcp::SynFloodVerdict::Leaking
+                        | kernel_tcp::SynFloodVerdict::Unfiltered => warn!(
+                            verdict = report.verdict.as_str(),
+                            kernel_pressure = report.kernel_pressure_per_second,
+                            syn_recv = report.sockets.syn_recv,
+                            "SYN pressure reaching the kernel"
+                        ),
+                        _ => info!(verdict = report.verdict.as_str(), "SYN flood verdict changed"),
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn task programming the worker's blocklists and rate limits into
+/// nftables, when nftables is the enforcement backend
+///
+/// The table is rebuilt from the map state on every tick and only committed
+/// when it changed.
+fn spawn_enforcement_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        if !runtime.enforcement.active() {
+            return;
+        }
+        let mut interval = tokio::time::interval(runtime.enforcement.sync_interval());
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Enforcement task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let threat = runtime.threat_intel.map_entries();
+                    let ruleset = {
+                        let maps = runtime.loader.read().maps();
+                        let maps = maps.read();
+                        nftables::Ruleset::from_maps(&maps, &threat)
+                    };
+
+                    match runtime.enforcement.sync(&ruleset).await {
+                        Ok(true) => debug!(
+                            blocked = ruleset.blocked.len(),
+                            threat_networks = ruleset.threat.len(),
+                            destinations = ruleset.destination_count(),
+                            "Updated nftables enforcement"
+                        ),
+                        Ok(false) => {}
+                        Err(e) => error!("Failed to program nftables: {}", e),
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spawn identity task applying Minecraft identity blocks to the IP
+/// blocklist
+fn spawn_identity_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Identity task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    runtime.identities.prune(std::time::Instant::now());
+                    let blocks = runtime.identities.take_blocks();
+                    if blocks.is_empty() {
+                        continue;
+                    }
+
+                    let maps = runtime.loader.read().maps();
+                    let mut maps = maps.write();
+                    for block in blocks {
+                        let duration = block.duration.as_secs().min(u32::MAX as u64) as u32;
+                        for ip in &block.addrs {
+                            if let Err(e) = maps.block_ip(*ip, &block.reason(), Some(duration)) {
+                                warn!("Failed to block {} of {}: {}", ip, block.username, e);
+                            }
+                        }
+                        info!(
+                            username = %block.username,
+                            addrs = block.addrs.len(),
+                            "Blocked repeat-offending Minecraft identity"
+                        );
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Maximum number of origin probes started per tick
+const MAX_PROBES_PER_TICK: usize = 16;
+
+/// Spawn probe task running the origin status pings queued by the gateway
+fn spawn_probe_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let Some(probes) = runtime.gateway_cache.clone() else {
+            return;
+        };
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Probe task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    for _ in 0..MAX_PROBES_PER_TICK {
+                        let request = match probes
+                            .pop::<probe::OriginProbeRequest>(
+                                probe::PROBE_QUEUE_KEY,
+                            )
+                            .await
+                        {
+                            Ok(Some(request)) => request,
+                            Ok(None) => break,
+                            Err(e) => {
+                                warn!("Failed to read origin probes: {}", e);
+                                break;
+                            }
+                        };
+
+                        let probes = probes.clone();
+                        let worker_id = runtime.control_plane.worker_id();
+                        tokio::spawn(async move {
+                            let result = origin_probe::run(&request, worker_id).await;
+                            debug!(
+                                probe = %request.id,
+                                address = %request.address,
+                                reachable = result.reachable,
+                                "Origin probe finished"
+                            );
+                            let key = probe::probe_result_key(&request.id);
+                            if let Err(e) = probes
+                                .set(&key, &result, probe::PROBE_TTL)
+                                .await
+                            {
+                                warn!("Failed to store origin probe {}: {}", request.id, e);
+                            }
+                        });
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Interval between drain reports of origin switches
+const SWITCH_REPORT_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(5);
+
+/// Spawn switch task applying the origin switch plans of the gateway and
+/// reporting the connections left on old origins
+fn spawn_switch_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let Some(cache) = runtime.gateway_cache.clone() else {
+            return;
+        };
+        let mut interval = tokio::time::interval(SWITCH_REPORT_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Switch task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let backend_ids = match cache.smembers(origin_switch::ACTIVE_SWITCHES_KEY).await {
+                        Ok(ids) => ids,
+                        Err(e) => {
+                            warn!("Failed to read origin switches: {}", e);
+                            continue;
+                        }
+                    };
+                    let mut plans = Vec::with_capacity(backend_ids.len());
+                    for backend_id in backend_ids {
+                        match cache
+                            .get::<origin_switch::OriginSwitchPlan>(
+                                &origin_switch::switch_plan_key(&backend_id),
+                            )
+                            .await
+                        {
+                            Ok(Some(plan)) => plans.push(plan),
+                            Ok(None) => {}
+                            Err(e) => warn!("Failed to read origin switch of {}: {}", backend_id, e),
+                        }
+                    }
+                    runtime.switches.update(plans.clone());
+
+                    // Reports need the ID registered with the control plane
+                    let Some(worker_id) = runtime.control_plane.worker_id() else {
+                        continue;
+                    };
+                    let maps = runtime.loader.read().maps();
+                    let now = chrono::Utc::now().timestamp();
+                    for plan in plans {
+                        let report = origin_switch::DrainReport {
+                            switch_id: plan.id.clone(),
+                            worker_id: worker_id.clone(),
+                            active_connections: runtime
+                                .switches
+                                .remaining_connections(&plan, &maps.read()),
+                            reported_at: now,
+                        };
+                        let key = origin_switch::drain_report_key(&plan.id, &worker_id);
+                        let stored = cache
+                            .set(&key, &report, origin_switch::DRAIN_REPORT_TTL)
+                            .await;
+                        let registered = cache
+                            .sadd(&origin_switch::switch_workers_key(&plan.id), &worker_id)
+                            .await;
+                        if let Err(e) = stored.and(registered.map(|_| ())) {
+                            warn!("Failed to report drain of switch {}: {}", plan.id, e);
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Interval between applications of backend modes
+const MODE_SYNC_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(5);
+
+/// Spawn mode task applying the passthrough and block-all modes of the
+/// gateway to the XDP maps and the HTTP proxy
+///
+/// Modes are reapplied on every tick, so they follow destination changes of
+/// the filter configuration and expire on time even while Redis is down.
+fn spawn_mode_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let Some(cache) = runtime.gateway_cache.clone() else {
+            return;
+        };
+        let mut interval = tokio::time::interval(MODE_SYNC_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Mode task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let overrides = match read_backend_modes(&cache).await {
+                        Ok(overrides) => overrides,
+                        Err(e) => {
+                            warn!("Failed to read backend modes, keeping current ones: {}", e);
+                            runtime.modes.overrides()
+                        }
+                    };
+                    let published: std::collections::HashMap<String, BackendModeOverride> =
+                        overrides
+                            .iter()
+                            .map(|mode| (mode.backend_id.clone(), mode.clone()))
+                            .collect();
+
+                    let applied = runtime.modes.apply(
+                        overrides,
+                        chrono::Utc::now().timestamp(),
+                        |backend_id| runtime.config_sync.backend_destinations(backend_id),
+                        |entries| {
+                            let mut loader = runtime.loader.write();
+                            if loader.program_generation("xdp_filter") == 0 {
+                                return Ok(());
+                            }
+                            loader.set_backend_mode_entries(entries)
+                        },
+                    );
+                    let changes = match applied {
+                        Ok(changes) => changes,
+                        Err(e) => {
+                            error!("Failed to apply backend modes: {}", e);
+                            continue;
+                        }
+                    };
+
+                    for change in changes {
+                        let mode = published.get(&change.backend_id);
+                        if change.expired {
+                            warn!(
+                                backend = %change.backend_id,
+                                from = %change.from,
+                                "Backend mode reached its revert time, protecting again"
+                            );
+                        } else {
+                            info!(
+                                backend = %change.backend_id,
+                                from = %change.from,
+                                to = %change.to,
+                                changed_by = mode.map(|m| m.changed_by.as_str()).unwrap_or(""),
+                                reason = mode.map(|m| m.reason.as_str()).unwrap_or(""),
+                                revert_at = mode.map(|m| m.revert_at).unwrap_or(0),
+                                "Applied backend mode"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Read the mode overrides published by the gateway
+async fn read_backend_modes(
+    cache: &CacheService,
+) -> pistonprotection_common::Result<Vec<BackendModeOverride>> {
+    let mut overrides = Vec::new();
+    for backend_id in cache.smembers(ACTIVE_MODES_KEY).await? {
+        if let Some(mode) = cache
+            .get::<BackendModeOverride>(&backend_mode_key(&backend_id))
+            .await?
+        {
+            overrides.push(mode);
+        }
+    }
+    Ok(overrides)
+}
+
+/// Interval between applications of rate limit profiles
+const PROFILE_SYNC_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(15);
+
+/// Spawn profile task applying the rate limit profiles of the gateway to the
+/// XDP tenant maps
+///
+/// The active profiles are resolved on every tick, so schedules switch and
+/// manual triggers end on time even while Redis is down.
+fn spawn_profile_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let Some(cache) = runtime.gateway_cache.clone() else {
+            return;
+        };
+        let mut interval = tokio::time::interval(PROFILE_SYNC_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Profile task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let published = match read_rate_profiles(&cache).await {
+                        Ok(published) => published,
+                        Err(e) => {
+                            warn!("Failed to read rate limit profiles, keeping current ones: {}", e);
+                            runtime.rate_profiles.published()
+                        }
+                    };
+
+                    let applied = runtime.rate_profiles.apply(
+                        published,
+                        chrono::Utc::now().timestamp(),
+                        |limits| {
+                            let mut loader = runtime.loader.write();
+                            let maps = loader.maps();
+                            let mut map_manager = maps.write();
+                            map_manager.set_profile_limits(limits.clone());
+                            let entries = map_manager.tenant_map_entries();
+                            drop(map_manager);
+                            if loader.program_generation("xdp_filter") == 0 {
+                                return Ok(());
+                            }
+                            loader.set_tenant_entries(&entries)
+                        },
+                    );
+                    let changes = match applied {
+                        Ok(changes) => changes,
+                        Err(e) => {
+                            error!("Failed to apply rate limit profiles: {}", e);
+                            continue;
+                        }
+                    };
+
+                    for change in changes {
+                        info!(
+                            backend = %change.backend_id,
+                            from = %change.from,
+                            to = %change.to,
+                            source = %change.source,
+                            "Switched rate limit profile"
+                        );
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Read the rate limit profiles published by the gateway
+async fn read_rate_profiles(
+    cache: &CacheService,
+) -> pistonprotection_common::Result<Vec<BackendRateProfiles>> {
+    let mut published = Vec::new();
+    for backend_id in cache.smembers(ACTIVE_PROFILES_KEY).await? {
+        if let Some(profiles) = cache
+            .get::<BackendRateProfiles>(&rate_profiles_key(&backend_id))
+            .await?
+        {
+            published.push(profiles);
+        }
+    }
+    Ok(published)
+}
+
+/// Interval between applications of restart modes
+const RESTART_SYNC_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(5);
+
+/// Spawn restart task applying the restart modes of the gateway to the XDP
+/// tenant maps
+///
+/// Ended restart modes are dropped on every tick, so limits go back to
+/// normal on time even while Redis is down.
+fn spawn_restart_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let Some(cache) = runtime.gateway_cache.clone() else {
+            return;
+        };
+        let mut interval = tokio::time::interval(RESTART_SYNC_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Restart task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let published = match read_restart_modes(&cache).await {
+                        Ok(published) => published,
+                        Err(e) => {
+                            warn!("Failed to read restart modes, keeping current ones: {}", e);
+                            runtime.restarts.restarts()
+                        }
+                    };
+
+                    let applied = runtime.restarts.apply(
+                        published,
+                        chrono::Utc::now().timestamp(),
+                        |restarts| {
+                            let mut loader = runtime.loader.write();
+                            let maps = loader.maps();
+                            let mut map_manager = maps.write();
+                            map_manager.set_restarts(restarts.clone());
+                            let entries = map_manager.tenant_map_entries();
+                            drop(map_manager);
+                            if loader.program_generation("xdp_filter") == 0 {
+                                return Ok(());
+                            }
+                            loader.set_tenant_entries(&entries)
+                        },
+                    );
+                    let changes = match applied {
+                        Ok(changes) => changes,
+                        Err(e) => {
+                            error!("Failed to apply restart modes: {}", e);
+                            continue;
+                        }
+                    };
+
+                    for change in changes {
+                        match change.connection_multiplier {
+                            Some(connection_multiplier) => info!(
+                                backend = %change.backend_id,
+                                connection_multiplier,
+                                "Applied restart mode"
+                            ),
+                            None if change.expired => info!(
+                                backend = %change.backend_id,
+                                "Restart mode ended, limits back to normal"
+                            ),
+                            None => info!(
+                                backend = %change.backend_id,
+                                "Restart mode stopped"
+                            ),
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Read the restart modes published by the gateway
+async fn read_restart_modes(
+    cache: &CacheService,
+) -> pistonprotection_common::Result<Vec<RestartMode>> {
+    let mut published = Vec::new();
+    for backend_id in cache.smembers(ACTIVE_RESTARTS_KEY).await? {
+        if let Some(restart) = cache
+            .get::<RestartMode>(&restart_mode_key(&backend_id))
+            .await?
+        {
+            published.push(restart);
+        }
+    }
+    Ok(published)
+}
+
+/// Interval between applications of exemption tokens
+const EXEMPTION_SYNC_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(5);
+
+/// Spawn exemption task writing the tester addresses of the gateway's
+/// exemption tokens to the XDP whitelist maps
+///
+/// Expired tokens are dropped on every tick, so their addresses are
+/// filtered again on time even while Redis is down.
+fn spawn_exemption_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let Some(cache) = runtime.gateway_cache.clone() else {
+            return;
+        };
+        let mut interval = tokio::time::interval(EXEMPTION_SYNC_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Exemption task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    let published = match read_exemption_tokens(&cache).await {
+                        Ok(published) => published,
+                        Err(e) => {
+                            warn!("Failed to read exemption tokens, keeping current ones: {}", e);
+                            runtime.exemptions.tokens()
+                        }
+                    };
+
+                    let applied = runtime.exemptions.apply(
+                        published,
+                        chrono::Utc::now().timestamp(),
+                        |ips| runtime.loader.write().set_whitelist(ips),
+                    );
+                    let changes = match applied {
+                        Ok(changes) => changes,
+                        Err(e) => {
+                            error!("Failed to apply exemption tokens: {}", e);
+                            continue;
+                        }
+                    };
+
+                    for change in changes {
+                        match change.ips {
+                            Some(ips) => info!(
+                                backend = %change.backend_id,
+                                token = %change.token_id,
+                                ips,
+                                "Applied exemption token"
+                            ),
+                            None if change.expired => info!(
+                                backend = %change.backend_id,
+                                token = %change.token_id,
+                                "Exemption token expired, addresses filtered again"
+                            ),
+                            None => info!(
+                                backend = %change.backend_id,
+                                token = %change.token_id,
+                                "Exemption token revoked"
+                            ),
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Read the exemption tokens published by the gateway
+async fn read_exemption_tokens(
+    cache: &CacheService,
+) -> pistonprotection_common::Result<Vec<ExemptionToken>> {
+    let mut published = Vec::new();
+    for token_id in cache.smembers(ACTIVE_EXEMPTIONS_KEY).await? {
+        if let Some(token) = cache
+            .get::<ExemptionToken>(&exemption_token_key(&token_id))
+            .await?
+        {
+            published.push(token);
+        }
+    }
+    Ok(published)
+}
+
+/// Interval between applications of bandwidth throttles
+const THROTTLE_SYNC_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(30);
+
+/// Spawn throttle task capping the per-connection bandwidth of backends
+/// whose organization exceeded its monthly bandwidth
+///
+/// Ended throttles are dropped on every tick, so they lift at the end of
+/// the period even while Redis is down.
+fn spawn_throttle_task(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        let Some(cache) = runtime.gateway_cache.clone() else {
+            return;
+        };
+        let mut interval = tokio::time::interval(THROTTLE_SYNC_INTERVAL);
+        let mut published = Vec::new();
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("Throttle task shutting down");
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    match read_bandwidth_throttles(&cache).await {
+                        Ok(throttles) => published = throttles,
+                        Err(e) => warn!("Failed to read bandwidth throttles, keeping current ones: {}", e),
+                    }
+
+                    let now = chrono::Utc::now().timestamp();
+                    let throttles: std::collections::HashMap<String, u64> = published
+                        .iter()
+                        .filter(|throttle| !throttle.expired(now))
+                        .map(|throttle| (throttle.backend_id.clone(), throttle.bytes_per_second))
+                        .collect();
+
+                    let mut loader = runtime.loader.write();
+                    let maps = loader.maps();
+                    let mut map_manager = maps.write();
+                    let previous = map_manager.bandwidth_throttles().clone();
+                    if !map_manager.set_bandwidth_throttles(throttles.clone()) {
+                        continue;
+                    }
+                    let entries = map_manager.flow_rate_entries();
+                    drop(map_manager);
+                    if let Err(e) = loader.set_flow_rates(entries) {
+                        error!("Failed to apply bandwidth throttles: {}", e);
+                        continue;
+                    }
+                    drop(loader);
+
+                    for (backend_id, bytes_per_second) in &throttles {
+                        if previous.get(backend_id) != Some(bytes_per_second) {
+                            info!(
+                                backend = %backend_id,
+                                bytes_per_second,
+                                "Throttled backend over its bandwidth quota"
+                            );
+                        }
+                    }
+                    for backend_id in previous.keys() {
+                        if !throttles.contains_key(backend_id) {
+                            info!(backend = %backend_id, "Bandwidth throttle lifted");
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Read the bandwidth throttles published by the gateway
+async fn read_bandwidth_throttles(
+    cache: &CacheService,
+) -> pistonprotection_common::Result<Vec<BandwidthThrottle>> {
+    let mut published = Vec::new();
+    for backend_id in cache.smembers(THROTTLED_BACKENDS_KEY).await? {
+        if let Some(throttle) = cache
+            .get::<BandwidthThrottle>(&bandwidth_throttle_key(&backend_id))
+            .await?
+        {
+            published.push(throttle);
+        }
+    }
+    Ok(published)
+}
+
+/// Spawn control plane state monitor
+fn spawn_state_monitor(runtime: Arc<WorkerRuntime>) -> tokio::task::JoinHandle<()> {
+    let mut state_rx = runtime.control_plane.subscribe_state_changes();
+    let mut shutdown_rx = runtime.shutdown_receiver();
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("State monitor shutting down");
+                        break;
+                    }
+                }
+                result = state_rx.changed() => {
+                    if result.is_err() {
+                        break;
+                    }
+
+                    let state = *state_rx.borrow();
+                    match state {
+                        ConnectionState::Connected => {
+                            info!("Control plane connection established");
+                            // Trigger config sync on reconnection
+                            runtime.config_sync.trigger_sync();
+                        }
+                        ConnectionState::Disconnected => {
+                            warn!("Control plane connection lost");
+                        }
+                        ConnectionState::Reconnecting => {
+                            info!("Attempting to reconnect to control plane...");
+                        }
+                        ConnectionState::ShuttingDown => {
+                            info!("Control plane connection shutting down");
+                        }
+                        ConnectionState::Connecting => {
+                            info!("Connecting to control plane...");
+                        }
+                    }
+
+                    // Update connection state metric
+                    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
+                        .with_label_values(&["worker", "control_plane"])
+                        .set(if state == ConnectionState::Connected { 1.0 } else { 0.0 });
+                }
+            }
+        }
+    })
+}
+
+/// Update Prometheus metrics
+fn update_prometheus_metrics(runtime: &WorkerRuntime) {
+    let loader = runtime.loader.read();
+    let maps = loader.maps();
+    let map_stats = maps.read().stats();
+
+    // Update eBPF map metrics
+    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
+        .with_label_values(&["worker", "conntrack"])
+        .set(map_stats.conntrack_entries as f64);
+
+    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
+        .with_label_values(&["worker", "blocked_ips"])
+        .set(map_stats.blocked_ips as f64);
+
+    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
+        .with_label_values(&["worker", "rate_limits"])
+        .set(map_stats.rate_limits as f64);
+
+    pistonprotection_common::metrics::ACTIVE_CONNECTIONS
+        .with_label_values(&["worker", "backends"])
+        .set(map_stats.backends as f64);
+
+    // Export per-port and per-reason drop breakdowns
+    for program in loader.loaded_programs() {
+        match loader.read_drop_breakdown(&program) {
+            Ok(breakdown) => breakdown.export_metrics(),
+            Err(e) => debug!(program = %program, error = %e, "No drop breakdown available"),
+        }
+        match loader.read_event_budget(&program) {
+            Ok(report) => report.export_metrics(),
+            Err(e) => debug!(program = %program, error = %e, "No event budget available"),
+        }
+        if loader.latency_rate(&program) > 0 {
+            match loader.read_latency(&program) {
+                Ok(histograms) => histograms.iter().for_each(|h| h.export_metrics()),
+                Err(e) => debug!(program = %program, error = %e, "No latency histograms available"),
+            }
+        }
+    }
 
     // Update sync stats
     let _sync_stats = runtime.config_sync.stats();
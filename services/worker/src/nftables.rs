@@ -0,0 +1,1107 @@
+//! nftables fallback enforcement
+//!
+//! Some nodes cannot run the XDP programs well: virtualized NICs whose driver
+//! has no native XDP only get generic mode, which runs after the skb is
+//! built and is slower than the stack it is meant to protect, and old or
+//! locked-down kernels cannot load the programs at all. On those nodes the
+//! worker programs the same enforcement into an nftables table instead:
+//!
+//! - the global blocklist and the enabled threat intelligence networks are
+//!   dropped in a raw-priority prerouting chain, before conntrack;
+//! - packets to backend destinations jump, through a verdict map, to the
+//!   chain of their tenant, which drops the tenant's blocked sources and
+//!   rate limits each source with the tenant's per-IP limits.
+//!
+//! The backend is picked at startup from the kernel capability probe and
+//! the drivers of the XDP-capable interfaces (`PISTON_ENFORCEMENT` = `auto`,
+//! `xdp` or `nftables`). While nftables enforces, the bootstrap programs are
+//! not attached, so packets are not limited twice.
+//!
+//! The table is rendered from the worker's map state on every sync and
+//! handed to `nft -f -`, which commits each script over netlink as one
+//! transaction, so there is no window without rules. Nothing is sent while
+//! the ruleset is unchanged. When only set and map elements changed (blocked
+//! sources, threat networks, destinations), those sets and maps are flushed
+//! and refilled in place. When the layout changed (tenants, their limits,
+//! interfaces or meter timeout), the previous table is deleted and the new
+//! one created. That reload drops the rate limit meters, so every source
+//! starts over with a full bucket.
+
+use crate::ebpf::interface::NetworkInterface;
+use crate::ebpf::maps::MapManager;
+use crate::ebpf::probe::KernelCapabilities;
+use crate::ebpf::tenants::{DEFAULT_PPS_LIMIT, TenantMapEntries};
+use crate::ebpf::threat_intel::ThreatIntelMapEntries;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::metrics::{ENFORCEMENT_BACKEND, NFT_SET_ELEMENTS};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Default name of the inet table
+pub const DEFAULT_TABLE: &str = "pistonprotection";
+
+/// Default interval between syncs of the table
+pub const DEFAULT_SYNC_SECS: u64 = 5;
+
+/// Default lifetime of an idle source in a rate limit meter
+pub const DEFAULT_METER_TIMEOUT_SECS: u64 = 60;
+
+/// Sources tracked per rate limit meter
+const METER_SIZE: u32 = 65_536;
+
+/// Verdict maps dispatching packets to the chains of their tenants: by
+/// destination address and port, then by address alone
+const DISPATCH_MAPS: [&str; 4] = [
+    "tenants_v4",
+    "tenants_v6",
+    "tenants_any_v4",
+    "tenants_any_v6",
+];
+
+/// Where the interface drivers are linked
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// Drivers with native (driver mode) XDP support
+const NATIVE_XDP_DRIVERS: &[&str] = &[
+    "bnxt_en",
+    "ena",
+    "hv_netvsc",
+    "i40e",
+    "ice",
+    "igb",
+    "igc",
+    "ixgbe",
+    "ixgbevf",
+    "mlx4_core",
+    "mlx5_core",
+    "nfp",
+    "qede",
+    "sfc",
+    "stmmac",
+    "thunder_nicvf",
+    "tun",
+    "virtio_net",
+    "vmxnet3",
+    "xen-netfront",
+];
+
+/// Which enforcement backend to use (`PISTON_ENFORCEMENT`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnforcementMode {
+    /// XDP where it runs natively, nftables otherwise
+    #[default]
+    Auto,
+    Xdp,
+    Nftables,
+}
+
+impl EnforcementMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "xdp" => Some(Self::Xdp),
+            "nftables" | "nft" => Some(Self::Nftables),
+            _ => None,
+        }
+    }
+}
+
+/// Enforcement backend in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Enforcement {
+    Xdp,
+    Nftables,
+}
+
+impl Enforcement {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Enforcement::Xdp => "xdp",
+            Enforcement::Nftables => "nftables",
+        }
+    }
+}
+
+/// Backend picked for the node and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EnforcementChoice {
+    pub backend: Enforcement,
+    pub reason: String,
+}
+
+/// Pick the enforcement backend
+///
+/// `drivers` are the XDP-capable interfaces with the name of their driver,
+/// if it could be read.
+pub fn select(
+    mode: EnforcementMode,
+    capabilities: &KernelCapabilities,
+    drivers: &[(String, Option<String>)],
+) -> EnforcementChoice {
+    let choice = |backend, reason: String| EnforcementChoice { backend, reason };
+
+    match mode {
+        EnforcementMode::Xdp => return choice(Enforcement::Xdp, "set by configuration".into()),
+        EnforcementMode::Nftables => {
+            return choice(Enforcement::Nftables, "set by configuration".into());
+        }
+        EnforcementMode::Auto => {}
+    }
+
+    if capabilities.select_variant().is_none() {
+        return choice(
+            Enforcement::Nftables,
+            format!(
+                "kernel {} cannot load the XDP programs",
+                capabilities.version
+            ),
+        );
+    }
+    if drivers.is_empty() {
+        return choice(
+            Enforcement::Nftables,
+            "no interface supports XDP".to_string(),
+        );
+    }
+    match drivers
+        .iter()
+        .find(|(_, driver)| driver.as_deref().is_some_and(native_xdp_driver))
+    {
+        Some((interface, driver)) => choice(
+            Enforcement::Xdp,
+            format!(
+                "{} ({}) runs XDP natively",
+                interface,
+                driver.as_deref().unwrap_or_default()
+            ),
+        ),
+        None => choice(
+            Enforcement::Nftables,
+            "no interface driver runs XDP natively, generic mode only".to_string(),
+        ),
+    }
+}
+
+pub fn native_xdp_driver(driver: &str) -> bool {
+    NATIVE_XDP_DRIVERS.contains(&driver)
+}
+
+/// Driver bound to an interface, from `/sys/class/net/<name>/device/driver`
+pub fn interface_driver(sys_class_net: &Path, interface: &str) -> Option<String> {
+    let driver = std::fs::read_link(sys_class_net.join(interface).join("device/driver")).ok()?;
+    driver
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// nftables enforcement configuration
+#[derive(Debug, Clone)]
+pub struct NftConfig {
+    pub mode: EnforcementMode,
+    /// Name of the inet table owned by the worker
+    pub table: String,
+    /// Interval between syncs of the table
+    pub sync_interval: Duration,
+    /// Lifetime of an idle source in a rate limit meter
+    pub meter_timeout: Duration,
+    /// Path of the nft binary
+    pub nft_path: PathBuf,
+}
+
+impl Default for NftConfig {
+    fn default() -> Self {
+        Self {
+            mode: EnforcementMode::Auto,
+            table: DEFAULT_TABLE.to_string(),
+            sync_interval: Duration::from_secs(DEFAULT_SYNC_SECS),
+            meter_timeout: Duration::from_secs(DEFAULT_METER_TIMEOUT_SECS),
+            nft_path: PathBuf::from("nft"),
+        }
+    }
+}
+
+impl NftConfig {
+    /// Load configuration from environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(mode) = std::env::var("PISTON_ENFORCEMENT") {
+            match EnforcementMode::parse(&mode) {
+                Some(mode) => config.mode = mode,
+                None => warn!("Unknown PISTON_ENFORCEMENT {:?}, using auto", mode),
+            }
+        }
+        if let Ok(table) = std::env::var("PISTON_NFT_TABLE") {
+            if valid_identifier(&table) {
+                config.table = table;
+            }
+        }
+        if let Ok(secs) = std::env::var("PISTON_NFT_SYNC_SECS") {
+            if let Ok(secs) = secs.parse::<u64>() {
+                config.sync_interval = Duration::from_secs(secs.max(1));
+            }
+        }
+        if let Ok(secs) = std::env::var("PISTON_NFT_METER_TIMEOUT_SECS") {
+            if let Ok(secs) = secs.parse::<u64>() {
+                config.meter_timeout = Duration::from_secs(secs.max(1));
+            }
+        }
+        if let Ok(path) = std::env::var("PISTON_NFT_PATH") {
+            config.nft_path = PathBuf::from(path);
+        }
+
+        config
+    }
+}
+
+/// Names nft accepts unquoted (tables, sets and chains)
+fn valid_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Enforcement of one tenant
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TenantRules {
+    /// Backend destinations (port 0 matches any port)
+    pub destinations: BTreeSet<(IpAddr, u16)>,
+    pub blocked: BTreeSet<IpAddr>,
+    /// Per-source packets per second and bucket size
+    pub pps: u64,
+    pub burst: u64,
+}
+
+/// Everything the table enforces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ruleset {
+    /// Globally blocked sources
+    pub blocked: BTreeSet<IpAddr>,
+    /// Networks of enabled threat intelligence feeds
+    pub threat: BTreeSet<(IpAddr, u8)>,
+    /// Rules per tenant id
+    pub tenants: BTreeMap<u32, TenantRules>,
+}
+
+impl Ruleset {
+    /// Collect the enforcement the XDP maps would hold
+    pub fn from_maps(maps: &MapManager, threat: &ThreatIntelMapEntries) -> Self {
+        let now = Utc::now();
+        let blocked = maps
+            .list_blocked_ips()
+            .into_iter()
+            .filter(|entry| entry.expires_at.is_none_or(|expires_at| expires_at > now))
+            .map(|entry| entry.ip);
+        Self::build(blocked, &maps.tenant_map_entries(), threat)
+    }
+
+    pub fn build(
+        blocked: impl IntoIterator<Item = IpAddr>,
+        tenants: &TenantMapEntries,
+        threat: &ThreatIntelMapEntries,
+    ) -> Self {
+        let mut ruleset = Self {
+            blocked: blocked.into_iter().map(|ip| ip.to_canonical()).collect(),
+            ..Default::default()
+        };
+
+        // Same checks as `check_threat_intel`: disabled feeds never drop
+        let enforced = |feed_id: u32, confidence: u32| {
+            threat
+                .feeds
+                .get(feed_id as usize)
+                .is_some_and(|feed| feed.enabled != 0 && confidence >= feed.min_confidence)
+        };
+        for (prefix, addr, entry) in &threat.v4 {
+            if enforced(entry.feed_id, entry.confidence) {
+                let network = IpAddr::V4(Ipv4Addr::from(*addr));
+                ruleset.threat.insert((network, *prefix as u8));
+            }
+        }
+        for (prefix, addr, entry) in &threat.v6 {
+            if enforced(entry.feed_id, entry.confidence) {
+                let network = IpAddr::V6(Ipv6Addr::from(*addr));
+                ruleset.threat.insert((network, *prefix as u8));
+            }
+        }
+
+        // Same limits as `check_tenant_rate_limit`
+        for (tenant_id, config) in &tenants.configs {
+            let tenant = ruleset.tenants.entry(*tenant_id).or_default();
+            (tenant.pps, tenant.burst) = match config.per_ip_pps_limit {
+                0 => (DEFAULT_PPS_LIMIT, DEFAULT_PPS_LIMIT),
+                pps if config.per_ip_burst > 0 => (pps, config.per_ip_burst),
+                pps => (pps, pps),
+            };
+        }
+        for (destination, tenant_id) in &tenants.destinations {
+            if let Some(tenant) = ruleset.tenants.get_mut(tenant_id) {
+                tenant
+                    .destinations
+                    .insert((destination.addr.to_canonical(), destination.port));
+            }
+        }
+        for (tenant_id, ip) in &tenants.blocked {
+            if let Some(tenant) = ruleset.tenants.get_mut(tenant_id) {
+                tenant.blocked.insert(ip.to_canonical());
+            }
+        }
+        // Tenants without destinations on this worker see no packets
+        ruleset
+            .tenants
+            .retain(|_, tenant| !tenant.destinations.is_empty());
+
+        ruleset
+    }
+
+    /// Destinations over all tenants
+    pub fn destination_count(&self) -> usize {
+        self.tenants.values().map(|t| t.destinations.len()).sum()
+    }
+
+    /// Tenant-blocked sources over all tenants
+    pub fn tenant_blocked_count(&self) -> usize {
+        self.tenants.values().map(|t| t.blocked.len()).sum()
+    }
+
+    /// The ruleset without set and map elements: its tenants and their
+    /// limits, which the chains and meters of the table are made of
+    pub fn layout(&self) -> Self {
+        Self {
+            tenants: self
+                .tenants
+                .iter()
+                .map(|(tenant_id, tenant)| {
+                    let limits = TenantRules {
+                        pps: tenant.pps,
+                        burst: tenant.burst,
+                        ..Default::default()
+                    };
+                    (*tenant_id, limits)
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Elements of the table's sets and verdict maps by name. The rate limit
+    /// meters fill themselves and are not listed.
+    fn elements(&self) -> BTreeMap<String, Vec<String>> {
+        let mut elements = BTreeMap::new();
+
+        let (v4, v6) = split(self.blocked.iter().map(|ip| (*ip, ip.to_string())));
+        elements.insert("blocked_v4".to_string(), v4);
+        elements.insert("blocked_v6".to_string(), v6);
+
+        let (v4, v6) = split(
+            self.threat
+                .iter()
+                .map(|(network, prefix)| (*network, format!("{}/{}", network, prefix))),
+        );
+        elements.insert("threat_v4".to_string(), v4);
+        elements.insert("threat_v6".to_string(), v6);
+
+        for map in DISPATCH_MAPS {
+            elements.insert(map.to_string(), Vec::new());
+        }
+        for (tenant_id, tenant) in &self.tenants {
+            let chain = format!("tenant_{}", tenant_id);
+            let (v4, v6) = split(tenant.blocked.iter().map(|ip| (*ip, ip.to_string())));
+            elements.insert(format!("{}_blocked_v4", chain), v4);
+            elements.insert(format!("{}_blocked_v6", chain), v6);
+
+            for (addr, port) in &tenant.destinations {
+                let (element, map) = match (addr, port) {
+                    (IpAddr::V4(_), 0) => (addr.to_string(), "tenants_any_v4"),
+                    (IpAddr::V6(_), 0) => (addr.to_string(), "tenants_any_v6"),
+                    (IpAddr::V4(_), port) => (format!("{} . {}", addr, port), "tenants_v4"),
+                    (IpAddr::V6(_), port) => (format!("{} . {}", addr, port), "tenants_v6"),
+                };
+                elements
+                    .entry(map.to_string())
+                    .or_default()
+                    .push(format!("{} : jump {}", element, chain));
+            }
+        }
+
+        elements
+    }
+
+    /// nft script setting the elements of a table rendered from a ruleset
+    /// with the same layout, leaving its rate limit meters untouched
+    pub fn render_update(&self, table: &str) -> String {
+        let mut out = String::new();
+        for (name, elements) in self.elements() {
+            let kind = if DISPATCH_MAPS.contains(&name.as_str()) {
+                "map"
+            } else {
+                "set"
+            };
+            let _ = writeln!(out, "flush {} inet {} {}", kind, table, name);
+            if !elements.is_empty() {
+                let _ = writeln!(
+                    out,
+                    "add element inet {} {} {{ {} }}",
+                    table,
+                    name,
+                    elements.join(", ")
+                );
+            }
+        }
+        out
+    }
+
+    /// nft script replacing the table with this ruleset
+    ///
+    /// Only packets arriving on `interfaces` are enforced (all when empty).
+    pub fn render(&self, table: &str, interfaces: &[String], meter_timeout: Duration) -> String {
+        let mut elements = self.elements();
+        let mut take = |name: &str| elements.remove(name).unwrap_or_default();
+
+        let mut out = String::new();
+        // Declaring the table first lets the delete succeed on the first sync
+        let _ = writeln!(out, "table inet {}", table);
+        let _ = writeln!(out, "delete table inet {}", table);
+        let _ = writeln!(out, "table inet {} {{", table);
+
+        render_set(&mut out, "blocked_v4", "ipv4_addr", "", &take("blocked_v4"));
+        render_set(&mut out, "blocked_v6", "ipv6_addr", "", &take("blocked_v6"));
+
+        let interval = "\t\tflags interval\n\t\tauto-merge\n";
+        render_set(
+            &mut out,
+            "threat_v4",
+            "ipv4_addr",
+            interval,
+            &take("threat_v4"),
+        );
+        render_set(
+            &mut out,
+            "threat_v6",
+            "ipv6_addr",
+            interval,
+            &take("threat_v6"),
+        );
+
+        let meter = format!(
+            "\t\tsize {}\n\t\tflags dynamic,timeout\n\t\ttimeout {}s\n",
+            METER_SIZE,
+            meter_timeout.as_secs().max(1)
+        );
+        for (tenant_id, tenant) in &self.tenants {
+            let chain = format!("tenant_{}", tenant_id);
+            let blocked_v4 = format!("{}_blocked_v4", chain);
+            render_set(&mut out, &blocked_v4, "ipv4_addr", "", &take(&blocked_v4));
+            let blocked_v6 = format!("{}_blocked_v6", chain);
+            render_set(&mut out, &blocked_v6, "ipv6_addr", "", &take(&blocked_v6));
+            render_set(
+                &mut out,
+                &format!("{}_meter_v4", chain),
+                "ipv4_addr",
+                &meter,
+                &[],
+            );
+            render_set(
+                &mut out,
+                &format!("{}_meter_v6", chain),
+                "ipv6_addr",
+                &meter,
+                &[],
+            );
+
+            let limit = format!(
+                "limit rate over {}/second burst {} packets",
+                tenant.pps, tenant.burst
+            );
+            let _ = writeln!(out, "\tchain {} {{", chain);
+            let _ = writeln!(out, "\t\tip saddr @{}_blocked_v4 counter drop", chain);
+            let _ = writeln!(out, "\t\tip6 saddr @{}_blocked_v6 counter drop", chain);
+            let _ = writeln!(
+                out,
+                "\t\tmeta nfproto ipv4 add @{}_meter_v4 {{ ip saddr {} }} counter drop",
+                chain, limit
+            );
+            let _ = writeln!(
+                out,
+                "\t\tmeta nfproto ipv6 add @{}_meter_v6 {{ ip6 saddr {} }} counter drop",
+                chain, limit
+            );
+            let _ = writeln!(out, "\t}}");
+        }
+
+        let types = [
+            "ipv4_addr . inet_service : verdict",
+            "ipv6_addr . inet_service : verdict",
+            "ipv4_addr : verdict",
+            "ipv6_addr : verdict",
+        ];
+        for (&name, typ) in DISPATCH_MAPS.iter().zip(types) {
+            render_map(&mut out, name, typ, &take(name));
+        }
+
+        // Raw priority: before conntrack, like XDP before the stack
+        let _ = writeln!(out, "\tchain prerouting {{");
+        let _ = writeln!(
+            out,
+            "\t\ttype filter hook prerouting priority raw; policy accept;"
+        );
+        if !interfaces.is_empty() {
+            let names: Vec<String> = interfaces.iter().map(|i| format!("\"{}\"", i)).collect();
+            let _ = writeln!(out, "\t\tiifname != {{ {} }} accept", names.join(", "));
+        }
+        let _ = writeln!(out, "\t\tip saddr @blocked_v4 counter drop");
+        let _ = writeln!(out, "\t\tip6 saddr @blocked_v6 counter drop");
+        let _ = writeln!(out, "\t\tip saddr @threat_v4 counter drop");
+        let _ = writeln!(out, "\t\tip6 saddr @threat_v6 counter drop");
+        let _ = writeln!(
+            out,
+            "\t\tmeta l4proto {{ tcp, udp }} ip daddr . th dport vmap @tenants_v4"
+        );
+        let _ = writeln!(
+            out,
+            "\t\tmeta l4proto {{ tcp, udp }} ip6 daddr . th dport vmap @tenants_v6"
+        );
+        let _ = writeln!(out, "\t\tip daddr vmap @tenants_any_v4");
+        let _ = writeln!(out, "\t\tip6 daddr vmap @tenants_any_v6");
+        let _ = writeln!(out, "\t}}");
+        let _ = writeln!(out, "}}");
+        out
+    }
+}
+
+/// Split elements by address family
+fn split(elements: impl Iterator<Item = (IpAddr, String)>) -> (Vec<String>, Vec<String>) {
+    let (v4, v6): (Vec<_>, Vec<_>) = elements.partition(|(ip, _)| ip.is_ipv4());
+    (
+        v4.into_iter().map(|(_, e)| e).collect(),
+        v6.into_iter().map(|(_, e)| e).collect(),
+    )
+}
+
+fn render_set(out: &mut String, name: &str, typ: &str, flags: &str, elements: &[String]) {
+    let _ = writeln!(out, "\tset {} {{", name);
+    let _ = writeln!(out, "\t\ttype {}", typ);
+    out.push_str(flags);
+    render_elements(out, elements);
+    let _ = writeln!(out, "\t}}");
+}
+
+fn render_map(out: &mut String, name: &str, typ: &str, elements: &[String]) {
+    let _ = writeln!(out, "\tmap {} {{", name);
+    let _ = writeln!(out, "\t\ttype {}", typ);
+    render_elements(out, elements);
+    let _ = writeln!(out, "\t}}");
+}
+
+/// nft rejects an empty element list, so empty sets have none
+fn render_elements(out: &mut String, elements: &[String]) {
+    if !elements.is_empty() {
+        let _ = writeln!(out, "\t\telements = {{ {} }}", elements.join(", "));
+    }
+}
+
+/// Ruleset last committed to the kernel
+#[derive(Debug, Clone)]
+struct AppliedRuleset {
+    script: String,
+    /// Script of the ruleset's layout, see [`Ruleset::layout`]
+    layout: String,
+    blocked: usize,
+    threat_networks: usize,
+    tenants: usize,
+    destinations: usize,
+    tenant_blocked: usize,
+    applied_at: DateTime<Utc>,
+}
+
+/// Status of the enforcement backend
+#[derive(Debug, Clone, Serialize)]
+pub struct EnforcementStatus {
+    pub backend: Enforcement,
+    pub reason: String,
+    /// nftables table, when nftables enforces
+    pub table: Option<String>,
+    pub interfaces: Vec<String>,
+    pub applied_at: Option<DateTime<Utc>>,
+    pub blocked: usize,
+    pub threat_networks: usize,
+    pub tenants: usize,
+    pub destinations: usize,
+    pub tenant_blocked: usize,
+    pub last_error: Option<String>,
+}
+
+/// Programs the ruleset into nftables when it is the enforcement backend
+pub struct NftEnforcer {
+    config: NftConfig,
+    choice: EnforcementChoice,
+    /// Interfaces enforced on
+    interfaces: Vec<String>,
+    applied: RwLock<Option<AppliedRuleset>>,
+    last_error: RwLock<Option<String>>,
+}
+
+impl NftEnforcer {
+    pub fn new(config: NftConfig, choice: EnforcementChoice, interfaces: Vec<String>) -> Self {
+        for backend in [Enforcement::Xdp, Enforcement::Nftables] {
+            ENFORCEMENT_BACKEND
+                .with_label_values(&[backend.as_str()])
+                .set(if backend == choice.backend { 1.0 } else { 0.0 });
+        }
+        Self {
+            config,
+            choice,
+            interfaces,
+            applied: RwLock::new(None),
+            last_error: RwLock::new(None),
+        }
+    }
+
+    /// Probe the node and pick the backend for the XDP-capable interfaces
+    pub fn probe(
+        config: NftConfig,
+        capabilities: &KernelCapabilities,
+        interfaces: &[NetworkInterface],
+    ) -> Self {
+        let sys_class_net = Path::new(SYS_CLASS_NET);
+        let drivers: Vec<(String, Option<String>)> = interfaces
+            .iter()
+            .filter(|i| i.supports_xdp())
+            .map(|i| (i.name.clone(), interface_driver(sys_class_net, &i.name)))
+            .collect();
+        let choice = select(config.mode, capabilities, &drivers);
+        let names = drivers.into_iter().map(|(name, _)| name).collect();
+        Self::new(config, choice, names)
+    }
+
+    pub fn choice(&self) -> &EnforcementChoice {
+        &self.choice
+    }
+
+    /// Whether nftables is the enforcement backend
+    pub fn active(&self) -> bool {
+        self.choice.backend == Enforcement::Nftables
+    }
+
+    pub fn sync_interval(&self) -> Duration {
+        self.config.sync_interval
+    }
+
+    /// Commit the ruleset unless the kernel already has it
+    ///
+    /// Only the elements are updated while the layout stays the same, which
+    /// keeps the rate limit meters; otherwise the table is replaced. Returns
+    /// whether anything was committed.
+    pub async fn sync(&self, ruleset: &Ruleset) -> Result<bool> {
+        let table = &self.config.table;
+        let render =
+            |ruleset: &Ruleset| ruleset.render(table, &self.interfaces, self.config.meter_timeout);
+        let script = render(ruleset);
+        let layout = render(&ruleset.layout());
+        let update = match self.applied.read().as_ref() {
+            Some(applied) if applied.script == script => return Ok(false),
+            Some(applied) if applied.layout == layout => Some(ruleset.render_update(table)),
+            _ => None,
+        };
+
+        if let Err(e) = self.run(update.as_deref().unwrap_or(&script)).await {
+            *self.last_error.write() = Some(e.to_string());
+            return Err(e);
+        }
+        *self.last_error.write() = None;
+
+        let applied = AppliedRuleset {
+            script,
+            layout,
+            blocked: ruleset.blocked.len(),
+            threat_networks: ruleset.threat.len(),
+            tenants: ruleset.tenants.len(),
+            destinations: ruleset.destination_count(),
+            tenant_blocked: ruleset.tenant_blocked_count(),
+            applied_at: Utc::now(),
+        };
+        for (set, count) in [
+            ("blocked", applied.blocked),
+            ("threat", applied.threat_networks),
+            ("destinations", applied.destinations),
+            ("tenant_blocked", applied.tenant_blocked),
+        ] {
+            NFT_SET_ELEMENTS.with_label_values(&[set]).set(count as f64);
+        }
+        *self.applied.write() = Some(applied);
+        Ok(true)
+    }
+
+    /// Delete the table
+    pub async fn remove(&self) -> Result<()> {
+        let table = &self.config.table;
+        self.run(&format!(
+            "table inet {}\ndelete table inet {}\n",
+            table, table
+        ))
+        .await?;
+        *self.applied.write() = None;
+        Ok(())
+    }
+
+    /// Run an nft script as one transaction
+    async fn run(&self, script: &str) -> Result<()> {
+        let mut child = tokio::process::Command::new(&self.config.nft_path)
+            .args(["-f", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                Error::internal(format!(
+                    "Failed to run {}: {}",
+                    self.config.nft_path.display(),
+                    e
+                ))
+            })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(script.as_bytes())
+                .await
+                .map_err(|e| Error::internal(format!("Failed to write nft script: {}", e)))?;
+        }
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| Error::internal(format!("Failed to wait for nft: {}", e)))?;
+        if !output.status.success() {
+            return Err(Error::internal(format!(
+                "nft failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> EnforcementStatus {
+        let applied = self.applied.read();
+        let applied = applied.as_ref();
+        EnforcementStatus {
+            backend: self.choice.backend,
+            reason: self.choice.reason.clone(),
+            table: self.active().then(|| self.config.table.clone()),
+            interfaces: self.interfaces.clone(),
+            applied_at: applied.map(|a| a.applied_at),
+            blocked: applied.map_or(0, |a| a.blocked),
+            threat_networks: applied.map_or(0, |a| a.threat_networks),
+            tenants: applied.map_or(0, |a| a.tenants),
+            destinations: applied.map_or(0, |a| a.destinations),
+            tenant_blocked: applied.map_or(0, |a| a.tenant_blocked),
+            last_error: self.last_error.read().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ebpf::tenants::{TenantConfig, TenantDestination};
+    use crate::ebpf::threat_intel::{ThreatFeedConfig, ThreatIntelEntry};
+
+    fn drivers(pairs: &[(&str, Option<&str>)]) -> Vec<(String, Option<String>)> {
+        pairs
+            .iter()
+            .map(|(name, driver)| (name.to_string(), driver.map(str::to_string)))
+            .collect()
+    }
+
+    fn tenant_entries() -> TenantMapEntries {
+        let destination = |addr: &str, port| TenantDestination {
+            addr: addr.parse().unwrap(),
+            port,
+        };
+        TenantMapEntries {
+            destinations: vec![
+                (destination("203.0.113.10", 25565), 1),
+                (destination("2001:db8::10", 0), 1),
+                (destination("203.0.113.20", 19132), 2),
+            ],
+            configs: vec![
+                (
+                    1,
+                    TenantConfig {
+                        per_ip_pps_limit: 500,
+                        ..Default::default()
+                    },
+                ),
+                (2, TenantConfig::default()),
+                (3, TenantConfig::default()),
+            ],
+            blocked: vec![(1, "198.51.100.7".parse().unwrap())],
+        }
+    }
+
+    fn threat_entries() -> ThreatIntelMapEntries {
+        let entry = |feed_id, confidence| ThreatIntelEntry {
+            feed_id,
+            category: 0,
+            confidence,
+            _pad: 0,
+        };
+        ThreatIntelMapEntries {
+            v4: vec![
+                (24, [192, 0, 2, 0], entry(0, 90)),
+                (16, [10, 1, 0, 0], entry(0, 10)),
+                (8, [100, 0, 0, 0], entry(1, 90)),
+            ],
+            v6: vec![],
+            feeds: vec![
+                ThreatFeedConfig {
+                    enabled: 1,
+                    min_confidence: 50,
+                },
+                ThreatFeedConfig {
+                    enabled: 0,
+                    min_confidence: 0,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(EnforcementMode::parse("auto"), Some(EnforcementMode::Auto));
+        assert_eq!(
+            EnforcementMode::parse(" NFTables "),
+            Some(EnforcementMode::Nftables)
+        );
+        assert_eq!(EnforcementMode::parse("xdp"), Some(EnforcementMode::Xdp));
+        assert_eq!(EnforcementMode::parse("iptables"), None);
+    }
+
+    #[test]
+    fn test_select() {
+        let kernel = KernelCapabilities::from_release("6.1.0", true);
+        let native = drivers(&[("eth0", Some("virtio_net"))]);
+        let generic = drivers(&[("eth0", Some("e1000")), ("eth1", None)]);
+
+        let choice = select(EnforcementMode::Auto, &kernel, &native);
+        assert_eq!(choice.backend, Enforcement::Xdp);
+        assert_eq!(
+            select(EnforcementMode::Auto, &kernel, &generic).backend,
+            Enforcement::Nftables
+        );
+        assert_eq!(
+            select(EnforcementMode::Auto, &kernel, &[]).backend,
+            Enforcement::Nftables
+        );
+
+        let old = KernelCapabilities::from_release("4.9.0", false);
+        assert_eq!(
+            select(EnforcementMode::Auto, &old, &native).backend,
+            Enforcement::Nftables
+        );
+
+        // Configuration wins over probing
+        assert_eq!(
+            select(EnforcementMode::Xdp, &old, &generic).backend,
+            Enforcement::Xdp
+        );
+        assert_eq!(
+            select(EnforcementMode::Nftables, &kernel, &native).backend,
+            Enforcement::Nftables
+        );
+    }
+
+    #[test]
+    fn test_build_ruleset() {
+        let blocked = [
+            "192.0.2.1".parse().unwrap(),
+            "::ffff:192.0.2.2".parse().unwrap(),
+        ];
+        let ruleset = Ruleset::build(blocked, &tenant_entries(), &threat_entries());
+
+        assert!(
+            ruleset
+                .blocked
+                .contains(&"192.0.2.2".parse::<IpAddr>().unwrap())
+        );
+        // Below the confidence threshold or from a disabled feed
+        assert_eq!(
+            ruleset.threat,
+            [("192.0.2.0".parse().unwrap(), 24)].into_iter().collect()
+        );
+
+        // Tenant 3 has no destinations on this worker
+        assert_eq!(ruleset.tenants.len(), 2);
+        let tenant = &ruleset.tenants[&1];
+        assert_eq!((tenant.pps, tenant.burst), (500, 500));
+        assert_eq!(tenant.destinations.len(), 2);
+        assert_eq!(tenant.blocked.len(), 1);
+        let tenant = &ruleset.tenants[&2];
+        assert_eq!(
+            (tenant.pps, tenant.burst),
+            (DEFAULT_PPS_LIMIT, DEFAULT_PPS_LIMIT)
+        );
+        assert_eq!(ruleset.destination_count(), 3);
+        assert_eq!(ruleset.tenant_blocked_count(), 1);
+    }
+
+    #[test]
+    fn test_render() {
+        let ruleset = Ruleset::build(
+            ["192.0.2.1".parse().unwrap()],
+            &tenant_entries(),
+            &threat_entries(),
+        );
+        let script = ruleset.render(
+            "pistonprotection",
+            &["eth0".to_string()],
+            Duration::from_secs(60),
+        );
+
+        assert!(
+            script.starts_with("table inet pistonprotection\ndelete table inet pistonprotection\n")
+        );
+        assert!(script.contains("\t\telements = { 192.0.2.1 }\n"));
+        assert!(script.contains("\t\telements = { 192.0.2.0/24 }\n"));
+        assert!(script.contains("203.0.113.10 . 25565 : jump tenant_1"));
+        assert!(script.contains("2001:db8::10 : jump tenant_1"));
+        assert!(script.contains("ip saddr @tenant_1_blocked_v4 counter drop"));
+        assert!(script.contains("limit rate over 500/second burst 500 packets"));
+        assert!(script.contains("iifname != { \"eth0\" } accept"));
+
+        // Chains are declared before the maps jumping to them
+        let chain = script.find("chain tenant_1 {").unwrap();
+        let map = script.find("map tenants_v4 {").unwrap();
+        assert!(chain < map);
+
+        // Empty sets have no element list
+        assert!(!script.contains("elements = {  }"));
+        let empty = Ruleset::default().render("t", &[], Duration::from_secs(60));
+        assert!(!empty.contains("elements"));
+        assert!(!empty.contains("iifname"));
+    }
+
+    #[test]
+    fn test_render_update() {
+        let ruleset = Ruleset::build(
+            ["192.0.2.1".parse().unwrap()],
+            &tenant_entries(),
+            &threat_entries(),
+        );
+        let update = ruleset.render_update("pistonprotection");
+
+        assert!(update.contains(
+            "flush set inet pistonprotection blocked_v4\n\
+             add element inet pistonprotection blocked_v4 { 192.0.2.1 }\n"
+        ));
+        // Emptied sets are flushed only
+        assert!(update.contains("flush set inet pistonprotection blocked_v6\n"));
+        assert!(!update.contains("add element inet pistonprotection blocked_v6"));
+        assert!(update.contains("flush set inet pistonprotection tenant_1_blocked_v4\n"));
+        assert!(update.contains("flush map inet pistonprotection tenants_v4\n"));
+        assert!(update.contains("203.0.113.10 . 25565 : jump tenant_1"));
+        // The meters and the table itself are left alone
+        assert!(!update.contains("meter"));
+        assert!(!update.contains("delete"));
+
+        let layout = ruleset.layout();
+        assert!(layout.blocked.is_empty() && layout.threat.is_empty());
+        assert_eq!(layout.tenants.len(), 2);
+        assert_eq!(
+            (layout.tenants[&1].pps, layout.tenants[&1].burst),
+            (500, 500)
+        );
+        assert_eq!(layout.destination_count(), 0);
+        assert_eq!(layout.tenant_blocked_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sync_replaces_table_only_when_layout_changes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stand-in for nft recording the scripts it is given
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("scripts");
+        let nft = dir.path().join("nft");
+        std::fs::write(
+            &nft,
+            format!(
+                "#!/bin/sh\ncat >> '{0}'\necho '# end' >> '{0}'\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&nft, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = NftConfig {
+            nft_path: nft,
+            ..Default::default()
+        };
+        let choice = EnforcementChoice {
+            backend: Enforcement::Nftables,
+            reason: String::new(),
+        };
+        let enforcer = NftEnforcer::new(config, choice, vec![]);
+
+        let mut ruleset = Ruleset::build(
+            ["192.0.2.1".parse().unwrap()],
+            &tenant_entries(),
+            &threat_entries(),
+        );
+        assert!(enforcer.sync(&ruleset).await.unwrap());
+        // Nothing changed
+        assert!(!enforcer.sync(&ruleset).await.unwrap());
+        // A new blocked source
+        ruleset.blocked.insert("192.0.2.9".parse().unwrap());
+        assert!(enforcer.sync(&ruleset).await.unwrap());
+        // A new limit
+        ruleset.tenants.get_mut(&1).unwrap().pps = 1000;
+        assert!(enforcer.sync(&ruleset).await.unwrap());
+
+        let scripts = std::fs::read_to_string(&log).unwrap();
+        let scripts: Vec<&str> = scripts.split("# end\n").filter(|s| !s.is_empty()).collect();
+        assert_eq!(scripts.len(), 3);
+
+        // Element changes keep the meters and their rate limit state
+        assert!(!scripts[1].contains("delete table"));
+        assert!(!scripts[1].contains("meter"));
+        assert!(
+            scripts[1]
+                .contains("add element inet pistonprotection blocked_v4 { 192.0.2.1, 192.0.2.9 }")
+        );
+
+        // A reload deletes the table, and with it every meter: all sources
+        // start over with a full bucket
+        for script in [scripts[0], scripts[2]] {
+            assert!(
+                script.starts_with(
+                    "table inet pistonprotection\ndelete table inet pistonprotection\n"
+                )
+            );
+            assert!(script.contains("set tenant_1_meter_v4 {"));
+            assert!(!script.contains("add element"));
+        }
+        assert!(scripts[2].contains("limit rate over 1000/second"));
+    }
+
+    #[test]
+    fn test_valid_identifier() {
+        assert!(valid_identifier("pistonprotection"));
+        assert!(valid_identifier("piston_v2"));
+        assert!(!valid_identifier(""));
+        assert!(!valid_identifier("2fast"));
+        assert!(!valid_identifier("a b"));
+    }
+}
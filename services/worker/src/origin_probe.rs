@@ -0,0 +1,252 @@
+//! Origin probes
+//!
+//! Runs the status pings the gateway queues while onboarding a backend (see
+//! `pistonprotection_common::probe`): a Server List Ping for Java origins
+//! and a RakNet unconnected ping for Bedrock origins.
+
+use crate::protocol::minecraft::RAKNET_MAGIC;
+use crate::protocol::minecraft_fallback::MinecraftPacketBuilder;
+use pistonprotection_common::probe::{OriginProbeRequest, OriginProbeResult, ProbeKind};
+use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// Time allowed for a whole probe
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest status response accepted (favicons make them large)
+const MAX_STATUS_LEN: usize = 256 * 1024;
+
+/// Server status reported by an origin
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerStatus {
+    pub version: String,
+    pub protocol: i32,
+    pub players_online: u32,
+    pub players_max: u32,
+}
+
+/// Ping the origin of a probe request
+pub async fn run(request: &OriginProbeRequest, worker_id: Option<String>) -> OriginProbeResult {
+    let started = Instant::now();
+    let status = tokio::time::timeout(PROBE_TIMEOUT, ping(request))
+        .await
+        .unwrap_or_else(|_| Err(Error::new(ErrorKind::TimedOut, "status ping timed out")));
+
+    let mut result = OriginProbeResult {
+        id: request.id.clone(),
+        worker_id,
+        latency_ms: started.elapsed().as_millis() as u64,
+        ..Default::default()
+    };
+    match status {
+        Ok(status) => {
+            result.reachable = true;
+            result.version = status.version;
+            result.protocol = status.protocol;
+            result.players_online = status.players_online;
+            result.players_max = status.players_max;
+        }
+        Err(e) => result.error = Some(e.to_string()),
+    }
+    result
+}
+
+async fn ping(request: &OriginProbeRequest) -> Result<ServerStatus> {
+    let addr = tokio::net::lookup_host((request.address.as_str(), request.port))
+        .await?
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "origin address does not resolve"))?;
+
+    match request.kind {
+        ProbeKind::MinecraftJava => java_status(addr, &request.hostname).await,
+        ProbeKind::MinecraftBedrock => bedrock_status(addr).await,
+    }
+}
+
+/// Server List Ping: handshake with next state 1, then a status request
+async fn java_status(addr: SocketAddr, hostname: &str) -> Result<ServerStatus> {
+    let mut stream = TcpStream::connect(addr).await?;
+
+    // Protocol version -1 asks for the status of any version
+    let mut handshake = MinecraftPacketBuilder::write_varint(-1);
+    handshake.extend(MinecraftPacketBuilder::write_string(hostname));
+    handshake.extend_from_slice(&addr.port().to_be_bytes());
+    handshake.extend(MinecraftPacketBuilder::write_varint(1));
+
+    let mut request = MinecraftPacketBuilder::build_packet(0x00, &handshake);
+    request.extend(MinecraftPacketBuilder::build_packet(0x00, &[]));
+    stream.write_all(&request).await?;
+
+    let len = read_varint(&mut stream).await?;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|len| (1..=MAX_STATUS_LEN).contains(len))
+        .ok_or_else(|| invalid_data(format!("invalid status response length {}", len)))?;
+    let mut packet = vec![0; len];
+    stream.read_exact(&mut packet).await?;
+
+    parse_java_status(&packet).ok_or_else(|| invalid_data("malformed status response"))
+}
+
+async fn read_varint(stream: &mut TcpStream) -> Result<i32> {
+    let mut value: i32 = 0;
+    for i in 0..5 {
+        let byte = stream.read_u8().await?;
+        value |= ((byte & 0x7f) as i32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("VarInt too long"))
+}
+
+fn invalid_data(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+fn decode_varint(buf: &[u8]) -> Option<(i32, &[u8])> {
+    let mut value: i32 = 0;
+    for (i, &byte) in buf.iter().take(5).enumerate() {
+        value |= ((byte & 0x7f) as i32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &buf[i + 1..]));
+        }
+    }
+    None
+}
+
+/// Parse a Status Response packet (ID 0x00 and a JSON string)
+fn parse_java_status(packet: &[u8]) -> Option<ServerStatus> {
+    let (id, rest) = decode_varint(packet)?;
+    if id != 0x00 {
+        return None;
+    }
+    let (len, rest) = decode_varint(rest)?;
+    let json = rest.get(..usize::try_from(len).ok()?)?;
+    let status: serde_json::Value = serde_json::from_slice(json).ok()?;
+
+    let count = |value: &serde_json::Value| value.as_u64().unwrap_or(0).min(u32::MAX as u64) as u32;
+    Some(ServerStatus {
+        version: status["version"]["name"].as_str()?.to_string(),
+        protocol: status["version"]["protocol"].as_i64()? as i32,
+        players_online: count(&status["players"]["online"]),
+        players_max: count(&status["players"]["max"]),
+    })
+}
+
+/// RakNet unconnected ping, answered by an unconnected pong
+async fn bedrock_status(addr: SocketAddr) -> Result<ServerStatus> {
+    let bind: SocketAddr = if addr.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(addr).await?;
+
+    let time = chrono::Utc::now().timestamp_millis();
+    let mut ping = Vec::with_capacity(33);
+    ping.push(0x01);
+    ping.extend_from_slice(&time.to_be_bytes());
+    ping.extend_from_slice(&RAKNET_MAGIC);
+    ping.extend_from_slice(&rand::random::<i64>().to_be_bytes());
+    socket.send(&ping).await?;
+
+    let mut buf = vec![0; 2048];
+    let len = socket.recv(&mut buf).await?;
+    parse_bedrock_pong(&buf[..len]).ok_or_else(|| invalid_data("malformed unconnected pong"))
+}
+
+/// Parse an Unconnected Pong (0x1c): `MCPE;motd;protocol;version;online;max;...`
+fn parse_bedrock_pong(packet: &[u8]) -> Option<ServerStatus> {
+    if packet.first() != Some(&0x1c) || packet.get(17..33)? != RAKNET_MAGIC {
+        return None;
+    }
+    let len = u16::from_be_bytes(packet.get(33..35)?.try_into().ok()?) as usize;
+    let motd = std::str::from_utf8(packet.get(35..35 + len)?).ok()?;
+
+    let fields: Vec<&str> = motd.split(';').collect();
+    if fields.len() < 6 {
+        return None;
+    }
+    Some(ServerStatus {
+        version: fields[3].to_string(),
+        protocol: fields[2].parse().ok()?,
+        players_online: fields[4].parse().unwrap_or(0),
+        players_max: fields[5].parse().unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::minecraft_fallback::{BedrockPacketBuilder, FallbackConfig};
+
+    #[test]
+    fn test_parse_java_status() {
+        let config = FallbackConfig {
+            version_name: "Paper 1.21".to_string(),
+            max_players: 100,
+            online_players: 7,
+            ..Default::default()
+        };
+        let response = MinecraftPacketBuilder::build_status_response(&config);
+        // Strip the length prefix
+        let (_, packet) = decode_varint(&response).unwrap();
+
+        let status = parse_java_status(packet).unwrap();
+        assert_eq!(status.version, "Paper 1.21");
+        assert_eq!(status.protocol, config.protocol_version);
+        assert_eq!(status.players_online, 7);
+        assert_eq!(status.players_max, 100);
+
+        assert_eq!(parse_java_status(&[0x01, 0x00]), None);
+    }
+
+    #[test]
+    fn test_parse_bedrock_pong() {
+        let pong = BedrockPacketBuilder::build_unconnected_pong(1, 2, "Lobby", 50, 3, "World", 712);
+        let status = parse_bedrock_pong(&pong).unwrap();
+        assert_eq!(status.protocol, 712);
+        assert_eq!(status.players_online, 3);
+        assert_eq!(status.players_max, 50);
+
+        let mut wrong_magic = pong.clone();
+        wrong_magic[20] ^= 0xff;
+        assert_eq!(parse_bedrock_pong(&wrong_magic), None);
+    }
+
+    #[tokio::test]
+    async fn test_java_status_ping() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 256];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let response =
+                MinecraftPacketBuilder::build_status_response(&FallbackConfig::default());
+            socket.write_all(&response).await.unwrap();
+        });
+
+        let request = OriginProbeRequest {
+            id: "probe".to_string(),
+            kind: ProbeKind::MinecraftJava,
+            address: addr.ip().to_string(),
+            port: addr.port(),
+            hostname: "play.example.com".to_string(),
+        };
+        let result = run(&request, Some("worker-1".to_string())).await;
+        assert!(result.reachable, "{:?}", result.error);
+        assert_eq!(result.id, "probe");
+        assert_eq!(result.version, FallbackConfig::default().version_name);
+
+        // Nothing listens on the port any more
+        let result = run(&request, None).await;
+        assert!(!result.reachable);
+        assert!(result.error.is_some());
+    }
+}
@@ -1,8 +1,15 @@
 //! Minecraft protocol analysis and filtering
 
+use super::minecraft_fallback::{MinecraftPacketBuilder, MinecraftState};
+use super::minecraft_identity::{IdentityThrottle, IdentityVerdict, LoginIdentity, PlayPackets};
 use super::{AnalyzerStats, L7Protocol, PacketMeta, ProtocolAnalyzer, Verdict};
+use crate::block_response::BlockResponses;
 use parking_lot::RwLock;
 use pistonprotection_common::error::Result;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
 use tracing::debug;
 
 /// Minecraft Java Edition packet types
@@ -139,6 +146,172 @@ fn read_varint(buf: &[u8]) -> Option<(i32, usize)> {
     None // Incomplete VarInt (ran out of bytes before finding terminator)
 }
 
+/// Split the first length-prefixed frame off a payload
+fn split_frame(payload: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, len_size) = read_varint(payload)?;
+    if len <= 0 {
+        return None;
+    }
+    let end = len_size.checked_add(len as usize)?;
+    if end > payload.len() {
+        return None;
+    }
+    Some((&payload[len_size..end], &payload[end..]))
+}
+
+/// Packet ID and data of a frame, `None` for zlib-compressed frames
+fn frame_packet(frame: &[u8], compressed: bool) -> Option<(i32, &[u8])> {
+    let frame = if compressed {
+        let (data_len, size) = read_varint(frame)?;
+        if data_len != 0 {
+            return None;
+        }
+        &frame[size..]
+    } else {
+        frame
+    };
+    let (id, size) = read_varint(frame)?;
+    Some((id, &frame[size..]))
+}
+
+/// Read a length-prefixed string of at most `max_len` bytes
+fn read_string(buf: &[u8], max_len: usize) -> Option<(&str, &[u8])> {
+    let (len, size) = read_varint(buf)?;
+    let len = usize::try_from(len).ok()?;
+    if len > max_len || buf.len() < size + len {
+        return None;
+    }
+    let value = std::str::from_utf8(&buf[size..size + len]).ok()?;
+    Some((value, &buf[size + len..]))
+}
+
+/// Skip a length-prefixed byte array of at most `max_len` bytes
+fn skip_bytes(buf: &[u8], max_len: usize) -> Option<&[u8]> {
+    let (len, size) = read_varint(buf)?;
+    let len = usize::try_from(len).ok()?;
+    if len > max_len || buf.len() < size + len {
+        return None;
+    }
+    Some(&buf[size + len..])
+}
+
+fn read_uuid(buf: &[u8]) -> Option<(String, &[u8])> {
+    let bytes: [u8; 16] = buf.get(..16)?.try_into().ok()?;
+    Some((uuid::Uuid::from_bytes(bytes).to_string(), &buf[16..]))
+}
+
+/// Maximum username length accepted by vanilla servers
+pub const MAX_USERNAME_LEN: usize = 16;
+
+/// Parse the data of a Login Start packet (ID 0x00 in login state)
+///
+/// The layout depends on the protocol version: 1.19 adds signature data,
+/// 1.19.1 an optional UUID, 1.19.3 drops the signature data and 1.20.2 makes
+/// the UUID mandatory.
+pub fn parse_login_start(data: &[u8], protocol_version: u32) -> Option<LoginIdentity> {
+    let (username, mut rest) = read_string(data, MAX_USERNAME_LEN)?;
+    if username.is_empty()
+        || !username
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_')
+    {
+        return None;
+    }
+
+    let mut uuid = None;
+    if (759..=760).contains(&protocol_version) {
+        // Signature data: timestamp, public key and signature
+        if *rest.first()? != 0 {
+            rest = rest.get(9..)?;
+            rest = skip_bytes(rest, 512)?;
+            rest = skip_bytes(rest, 4096)?;
+        } else {
+            rest = &rest[1..];
+        }
+    }
+    if (760..764).contains(&protocol_version) {
+        if *rest.first()? != 0 {
+            uuid = Some(read_uuid(&rest[1..])?.0);
+        }
+    } else if protocol_version >= 764 {
+        uuid = Some(read_uuid(rest)?.0);
+    }
+
+    Some(LoginIdentity {
+        username: username.to_string(),
+        uuid,
+    })
+}
+
+/// Serverbound Chat Message packet ID in play state
+///
+/// Only connections of 1.20.2+ clients are inspected: their configuration
+/// phase shows whether the server enabled compression.
+fn chat_message_id(protocol_version: u32) -> Option<i32> {
+    match protocol_version {
+        764..=765 => Some(0x05),
+        766..=767 => Some(0x06),
+        768.. => Some(0x07),
+        _ => None,
+    }
+}
+
+/// Serverbound Chat Command and Signed Chat Command packet IDs in play state
+fn chat_command_ids(protocol_version: u32) -> [i32; 2] {
+    match protocol_version {
+        ..=765 => [0x04, 0x04],
+        766..=767 => [0x04, 0x05],
+        _ => [0x05, 0x06],
+    }
+}
+
+/// Serverbound Client Status packet ID in play state, whose action 0 is a
+/// respawn
+fn client_status_id(protocol_version: u32) -> i32 {
+    match protocol_version {
+        ..=765 => 0x08,
+        766..=767 => 0x09,
+        _ => 0x0a,
+    }
+}
+
+/// Count a play packet among the chat messages, commands and respawns
+fn count_play_packet(protocol_version: u32, id: i32, data: &[u8], play: &mut PlayPackets) {
+    if Some(id) == chat_message_id(protocol_version) {
+        play.chat += 1;
+    } else if chat_command_ids(protocol_version).contains(&id) {
+        play.commands += 1;
+    } else if id == client_status_id(protocol_version) && data.first() == Some(&0) {
+        play.respawns += 1;
+    }
+}
+
+/// Serverbound Acknowledge Finish Configuration packet ID
+fn finish_configuration_id(protocol_version: u32) -> i32 {
+    if protocol_version >= 766 { 0x03 } else { 0x02 }
+}
+
+/// Maximum number of logins tracked for identity limits
+pub const MAX_TRACKED_CONNECTIONS: usize = 65_536;
+
+/// Progress of a login tracked for identity limits
+#[derive(Debug)]
+struct JavaConnection {
+    protocol_version: u32,
+    state: MinecraftState,
+    /// Key of the identity that logged in
+    identity: Option<String>,
+    /// The client sent Encryption Response: everything after is opaque
+    encrypted: bool,
+    /// The server enabled compression
+    compressed: bool,
+    /// The login was refused: the rest of the connection is dropped
+    refused: bool,
+    /// The player was kicked for a packet flood
+    kicked: bool,
+    last_seen: Instant,
+}
+
 /// Minecraft Java protocol analyzer
 pub struct MinecraftJavaAnalyzer {
     stats: RwLock<AnalyzerStats>,
@@ -148,6 +321,12 @@ pub struct MinecraftJavaAnalyzer {
     status_rate_limit: u32,
     /// Validate handshake packets
     validate_handshake: bool,
+    /// Per-identity limits, when enabled
+    identities: Option<Arc<IdentityThrottle>>,
+    /// Logins tracked for identity limits, by client address
+    connections: RwLock<HashMap<SocketAddr, JavaConnection>>,
+    /// Custom kick messages of refused logins, when enabled
+    block_responses: Option<Arc<BlockResponses>>,
 }
 
 impl Default for MinecraftJavaAnalyzer {
@@ -163,9 +342,57 @@ impl MinecraftJavaAnalyzer {
             max_connections_per_ip: 10,
             status_rate_limit: 5,
             validate_handshake: true,
+            identities: None,
+            connections: RwLock::new(HashMap::new()),
+            block_responses: None,
+        }
+    }
+
+    /// Create an analyzer enforcing per-identity limits
+    pub fn with_identity_throttle(identities: Arc<IdentityThrottle>) -> Self {
+        Self {
+            identities: Some(identities),
+            ..Self::new()
         }
     }
 
+    /// Kick refused logins with the message of the backend they target
+    pub fn with_block_responses(mut self, block_responses: Arc<BlockResponses>) -> Self {
+        self.block_responses = Some(block_responses);
+        self
+    }
+
+    /// Disconnect packet to send a client whose login was refused, if the
+    /// backend it connected to has a kick message. Clients of backends
+    /// without one are dropped silently. Players kicked for a packet flood
+    /// always get the flood kick message.
+    pub fn disconnect_packet(&self, meta: &PacketMeta) -> Option<Vec<u8>> {
+        let addr = SocketAddr::new(meta.src_ip, meta.src_port);
+        let (refused_login, kicked) = match self.connections.read().get(&addr) {
+            Some(conn) => (
+                conn.refused && conn.state == MinecraftState::Login,
+                conn.kicked,
+            ),
+            None => (false, false),
+        };
+        if kicked {
+            let message = &self.identities.as_ref()?.policy().flood_kick_message;
+            return Some(MinecraftPacketBuilder::build_disconnect_packet(
+                message, false,
+            ));
+        }
+        if !refused_login {
+            return None;
+        }
+        let message = self
+            .block_responses
+            .as_ref()?
+            .kick_message(meta.dst_ip, meta.dst_port)?;
+        Some(MinecraftPacketBuilder::build_disconnect_component_packet(
+            &message, true,
+        ))
+    }
+
     /// Parse a Minecraft Java handshake packet
     fn parse_handshake(&self, payload: &[u8]) -> Option<MinecraftJavaHandshake> {
         // Skip packet length
@@ -238,6 +465,147 @@ impl MinecraftJavaAnalyzer {
     }
 }
 
+impl MinecraftJavaAnalyzer {
+    /// Follow a login through handshake, login, configuration and play,
+    /// applying the limits of the identity that logged in
+    fn track_identity(
+        &self,
+        identities: &IdentityThrottle,
+        meta: &PacketMeta,
+        payload: &[u8],
+        now: Instant,
+    ) -> Verdict {
+        let addr = SocketAddr::new(meta.src_ip, meta.src_port);
+        let mut connections = self.connections.write();
+        let mut rest = payload;
+
+        if !connections.contains_key(&addr) {
+            // Only logins are tracked; status pings are left alone
+            let Some(handshake) = self.parse_handshake(payload) else {
+                return Verdict::Pass;
+            };
+            if !matches!(handshake.next_state, 2 | 3) {
+                return Verdict::Pass;
+            }
+            if connections.len() >= MAX_TRACKED_CONNECTIONS {
+                connections.retain(|_, conn| {
+                    now.saturating_duration_since(conn.last_seen)
+                        < super::minecraft_identity::CONNECTION_IDLE
+                });
+                if connections.len() >= MAX_TRACKED_CONNECTIONS {
+                    return Verdict::Pass;
+                }
+            }
+            connections.insert(
+                addr,
+                JavaConnection {
+                    protocol_version: handshake.protocol_version,
+                    state: MinecraftState::Login,
+                    identity: None,
+                    encrypted: false,
+                    compressed: false,
+                    refused: false,
+                    kicked: false,
+                    last_seen: now,
+                },
+            );
+            // Login Start usually shares the segment of the handshake
+            rest = split_frame(payload).map_or(&[], |(_, rest)| rest);
+        }
+
+        let Some(conn) = connections.get_mut(&addr) else {
+            return Verdict::Pass;
+        };
+        conn.last_seen = now;
+        if conn.refused {
+            return Verdict::Drop;
+        }
+
+        let mut play = PlayPackets::default();
+        while !conn.encrypted {
+            let Some((frame, next)) = split_frame(rest) else {
+                break;
+            };
+            rest = next;
+
+            // No uncompressed login packet after Login Start has ID 0x00, so
+            // a leading zero is the data length of a compressed frame
+            if conn.state == MinecraftState::Login
+                && conn.identity.is_some()
+                && !conn.compressed
+                && frame.first() == Some(&0)
+            {
+                conn.compressed = true;
+            }
+            let Some((id, data)) = frame_packet(frame, conn.compressed) else {
+                continue;
+            };
+
+            match conn.state {
+                MinecraftState::Login if conn.identity.is_none() => {
+                    if id != 0x00 {
+                        continue;
+                    }
+                    let Some(identity) = parse_login_start(data, conn.protocol_version) else {
+                        debug!(src = %meta.src_ip, "Malformed Minecraft Login Start");
+                        conn.refused = true;
+                        return Verdict::Drop;
+                    };
+                    match identities.login(&identity, addr, now) {
+                        IdentityVerdict::Allow => conn.identity = Some(identity.key()),
+                        verdict => {
+                            debug!(
+                                src = %meta.src_ip,
+                                username = %identity.username,
+                                ?verdict,
+                                "Refused Minecraft login"
+                            );
+                            conn.refused = true;
+                            return Verdict::Drop;
+                        }
+                    }
+                }
+                MinecraftState::Login => match id {
+                    0x01 => conn.encrypted = true,
+                    0x03 if conn.protocol_version >= 764 => {
+                        conn.state = MinecraftState::Configuration
+                    }
+                    _ => {}
+                },
+                MinecraftState::Configuration => {
+                    if id == finish_configuration_id(conn.protocol_version) {
+                        conn.state = MinecraftState::Play;
+                    }
+                }
+                MinecraftState::Play => {
+                    count_play_packet(conn.protocol_version, id, data, &mut play);
+                }
+                _ => {}
+            }
+        }
+
+        let Some(key) = conn.identity.clone() else {
+            return Verdict::Pass;
+        };
+        match identities.packet(&key, addr, play, now) {
+            IdentityVerdict::Allow => Verdict::Pass,
+            IdentityVerdict::Throttle => Verdict::RateLimit,
+            IdentityVerdict::Kick => {
+                debug!(
+                    src = %meta.src_ip,
+                    identity = %key,
+                    ?play,
+                    "Kicked Minecraft player for a packet flood"
+                );
+                conn.refused = true;
+                conn.kicked = true;
+                Verdict::Drop
+            }
+            IdentityVerdict::Block => Verdict::Drop,
+        }
+    }
+}
+
 /// Parsed Minecraft Java handshake
 #[derive(Debug)]
 struct MinecraftJavaHandshake {
@@ -276,6 +644,14 @@ impl ProtocolAnalyzer for MinecraftJavaAnalyzer {
             }
         }
 
+        if let Some(identities) = &self.identities {
+            let verdict = self.track_identity(identities, meta, payload, Instant::now());
+            if verdict != Verdict::Pass {
+                stats.packets_dropped += 1;
+                return Ok(verdict);
+            }
+        }
+
         stats.packets_passed += 1;
         Ok(Verdict::Pass)
     }
@@ -383,6 +759,7 @@ impl ProtocolAnalyzer for MinecraftBedrockAnalyzer {
 
 #[cfg(test)]
 mod tests {
+    use super::super::minecraft_identity::IdentityPolicy;
     use super::*;
 
     #[test]
@@ -450,6 +827,217 @@ mod tests {
         assert!(!is_minecraft_bedrock(&invalid));
     }
 
+    fn frame(id: i32, data: &[u8]) -> Vec<u8> {
+        let mut body = MinecraftPacketBuilder::write_varint(id);
+        body.extend_from_slice(data);
+        let mut frame = MinecraftPacketBuilder::write_varint(body.len() as i32);
+        frame.extend(body);
+        frame
+    }
+
+    fn handshake(protocol_version: i32) -> Vec<u8> {
+        let mut data = MinecraftPacketBuilder::write_varint(protocol_version);
+        data.extend(MinecraftPacketBuilder::write_string("play.example.com"));
+        data.extend_from_slice(&25565u16.to_be_bytes());
+        data.push(2);
+        frame(0x00, &data)
+    }
+
+    fn login_start(username: &str) -> Vec<u8> {
+        let mut data = MinecraftPacketBuilder::write_string(username);
+        data.extend_from_slice(&[0xab; 16]);
+        frame(0x00, &data)
+    }
+
+    fn meta(src: &str) -> PacketMeta {
+        let src: SocketAddr = src.parse().unwrap();
+        PacketMeta {
+            src_ip: src.ip(),
+            dst_ip: "10.0.0.1".parse().unwrap(),
+            src_port: src.port(),
+            dst_port: 25565,
+            protocol: super::super::Protocol::Tcp,
+            l7_protocol: None,
+            payload_len: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_login_start_versions() {
+        let mut data = MinecraftPacketBuilder::write_string("Steve");
+        assert_eq!(
+            parse_login_start(&data, 758),
+            Some(LoginIdentity {
+                username: "Steve".to_string(),
+                uuid: None,
+            })
+        );
+
+        // 1.19.3: optional UUID
+        let mut optional = data.clone();
+        optional.push(1);
+        optional.extend_from_slice(&[0x11; 16]);
+        let identity = parse_login_start(&optional, 762).unwrap();
+        assert_eq!(
+            identity.uuid.as_deref(),
+            Some("11111111-1111-1111-1111-111111111111")
+        );
+
+        // 1.20.2: mandatory UUID
+        assert_eq!(parse_login_start(&data, 765), None);
+        data.extend_from_slice(&[0x11; 16]);
+        assert_eq!(parse_login_start(&data, 765), Some(identity));
+
+        let invalid = MinecraftPacketBuilder::write_string("not a name");
+        assert_eq!(parse_login_start(&invalid, 758), None);
+        let too_long = MinecraftPacketBuilder::write_string("abcdefghijklmnopq");
+        assert_eq!(parse_login_start(&too_long, 758), None);
+    }
+
+    #[test]
+    fn test_identity_chat_limit() {
+        let throttle = Arc::new(IdentityThrottle::new(IdentityPolicy {
+            max_chat_per_minute: 2,
+            ..Default::default()
+        }));
+        let analyzer = MinecraftJavaAnalyzer::with_identity_throttle(Arc::clone(&throttle));
+        let client = meta("192.0.2.1:40000");
+
+        let mut login = handshake(767);
+        login.extend(login_start("Steve"));
+        assert_eq!(analyzer.analyze(&client, &login).unwrap(), Verdict::Pass);
+
+        // Compressed Login Acknowledged, then Acknowledge Finish Configuration
+        assert_eq!(
+            analyzer.analyze(&client, &frame(0x00, &[0x03])).unwrap(),
+            Verdict::Pass
+        );
+        assert_eq!(
+            analyzer.analyze(&client, &frame(0x00, &[0x03])).unwrap(),
+            Verdict::Pass
+        );
+
+        let chat = frame(0x00, &[0x06, 0x02, b'h', b'i']);
+        assert_eq!(analyzer.analyze(&client, &chat).unwrap(), Verdict::Pass);
+        assert_eq!(analyzer.analyze(&client, &chat).unwrap(), Verdict::Pass);
+        assert_eq!(
+            analyzer.analyze(&client, &chat).unwrap(),
+            Verdict::RateLimit
+        );
+
+        // Another player behind the same address is not affected
+        let neighbour = meta("192.0.2.1:40001");
+        let mut login = handshake(767);
+        login.extend(login_start("Alex"));
+        assert_eq!(analyzer.analyze(&neighbour, &login).unwrap(), Verdict::Pass);
+    }
+
+    #[test]
+    fn test_command_flood_kicks_player() {
+        let throttle = Arc::new(IdentityThrottle::new(IdentityPolicy {
+            max_commands_per_second: 2,
+            ..Default::default()
+        }));
+        let analyzer = MinecraftJavaAnalyzer::with_identity_throttle(Arc::clone(&throttle));
+        let client = meta("192.0.2.2:40000");
+
+        let mut login = handshake(767);
+        login.extend(login_start("Spammer"));
+        assert_eq!(analyzer.analyze(&client, &login).unwrap(), Verdict::Pass);
+        let mut configure = frame(0x00, &[0x03]);
+        configure.extend(frame(0x00, &[0x03]));
+        assert_eq!(
+            analyzer.analyze(&client, &configure).unwrap(),
+            Verdict::Pass
+        );
+
+        // Respawning is not a command
+        let respawn = frame(0x00, &[0x09, 0x00]);
+        assert_eq!(analyzer.analyze(&client, &respawn).unwrap(), Verdict::Pass);
+        assert_eq!(analyzer.disconnect_packet(&client), None);
+
+        // Three commands in one segment exceed the limit of two per second
+        let mut commands = frame(0x00, &[0x04, 0x01, b'a']);
+        commands.extend(frame(0x00, &[0x05, 0x01, b'b']));
+        commands.extend(frame(0x00, &[0x04, 0x01, b'c']));
+        assert_eq!(analyzer.analyze(&client, &commands).unwrap(), Verdict::Drop);
+        assert_eq!(
+            analyzer.disconnect_packet(&client),
+            Some(MinecraftPacketBuilder::build_disconnect_packet(
+                &throttle.policy().flood_kick_message,
+                false
+            ))
+        );
+
+        // The rest of the connection is dropped
+        assert_eq!(analyzer.analyze(&client, &respawn).unwrap(), Verdict::Drop);
+        assert_eq!(throttle.status(Instant::now()).flood_kicks, 1);
+    }
+
+    #[test]
+    fn test_blocked_identity_login_dropped() {
+        let throttle = Arc::new(IdentityThrottle::new(IdentityPolicy {
+            max_logins_per_minute: 1,
+            block_strikes: 1,
+            ..Default::default()
+        }));
+        let analyzer = MinecraftJavaAnalyzer::with_identity_throttle(Arc::clone(&throttle));
+
+        for (port, verdict) in [(1, Verdict::Pass), (2, Verdict::Drop), (3, Verdict::Drop)] {
+            let client = meta(&format!("198.51.100.{}:50000", port));
+            let mut login = handshake(765);
+            login.extend(login_start("Bot"));
+            assert_eq!(analyzer.analyze(&client, &login).unwrap(), verdict);
+            // The rest of a refused connection is dropped too
+            if verdict == Verdict::Drop {
+                assert_eq!(
+                    analyzer.analyze(&client, &frame(0x03, &[])).unwrap(),
+                    verdict
+                );
+            }
+        }
+
+        let blocks = throttle.take_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].addrs.len(), 2);
+    }
+
+    #[test]
+    fn test_refused_login_kick_message() {
+        let throttle = Arc::new(IdentityThrottle::new(IdentityPolicy {
+            max_logins_per_minute: 1,
+            ..Default::default()
+        }));
+        let responses = Arc::new(BlockResponses::new());
+        responses.replace(vec![crate::block_response::BackendBlockResponses {
+            backend_id: "backend-1".to_string(),
+            destinations: vec![crate::ebpf::tenants::TenantDestination {
+                addr: "10.0.0.1".parse().unwrap(),
+                port: 25565,
+            }],
+            minecraft_kick_message: Some(r#"{"text":"Slow down"}"#.to_string()),
+            ..Default::default()
+        }]);
+        let analyzer =
+            MinecraftJavaAnalyzer::with_identity_throttle(throttle).with_block_responses(responses);
+
+        let mut login = handshake(765);
+        login.extend(login_start("Steve"));
+        let first = meta("192.0.2.1:40000");
+        assert_eq!(analyzer.analyze(&first, &login).unwrap(), Verdict::Pass);
+        assert_eq!(analyzer.disconnect_packet(&first), None);
+
+        let second = meta("192.0.2.1:40001");
+        assert_eq!(analyzer.analyze(&second, &login).unwrap(), Verdict::Drop);
+        assert_eq!(
+            analyzer.disconnect_packet(&second),
+            Some(MinecraftPacketBuilder::build_disconnect_component_packet(
+                r#"{"text":"Slow down"}"#,
+                true
+            ))
+        );
+    }
+
     #[test]
     fn test_validate_handshake_next_state() {
         let analyzer = MinecraftJavaAnalyzer::new();
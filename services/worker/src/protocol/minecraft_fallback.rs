@@ -137,7 +137,12 @@ impl MinecraftPacketBuilder {
         let json_str = serde_json::to_string(&json_message)
             .unwrap_or_else(|_| format!(r#"{{"text":"{}"}}"#, message));
 
-        let data = Self::write_string(&json_str);
+        Self::build_disconnect_component_packet(&json_str, in_login_state)
+    }
+
+    /// Build a Disconnect packet from a JSON Chat Component.
+    pub fn build_disconnect_component_packet(component: &str, in_login_state: bool) -> Vec<u8> {
+        let data = Self::write_string(component);
 
         // Packet ID: 0x00 for login disconnect, 0x1D for play disconnect (1.20+)
         let packet_id = if in_login_state { 0x00 } else { 0x1D };
@@ -0,0 +1,689 @@
+//! Minecraft player identity throttling
+//!
+//! IP limits punish every player behind a shared address (campus networks,
+//! carrier-grade NAT). The Java analyzer extracts the username and UUID from
+//! Login Start and this module limits each identity instead: open
+//! connections, logins per minute, packets per second and chat messages per
+//! minute. Every limit exceeded is a strike; an identity collecting enough
+//! strikes within the strike window is blocked, and the addresses it logged
+//! in from are queued for the XDP IP blocklist.
+//!
+//! Bots that get past login are held to per-second limits on chat messages,
+//! commands and respawns. Exceeding one is a flood: the player is kicked and
+//! earns a strike, so repeat offenders end up blocked like any other.
+//!
+//! Identities are keyed by lowercase username. The UUID sent in Login Start
+//! is not authenticated at that point, so it is only reported.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// Maximum number of tracked identities
+pub const MAX_IDENTITIES: usize = 100_000;
+
+/// Addresses remembered per identity
+pub const MAX_ADDRS_PER_IDENTITY: usize = 8;
+
+/// Connections silent for longer than this no longer count as open
+pub const CONNECTION_IDLE: Duration = Duration::from_secs(30);
+
+/// Identities silent for longer than this are forgotten
+pub const IDENTITY_IDLE: Duration = Duration::from_secs(600);
+
+/// Prefix of the reason of IP blocks caused by an identity
+pub const BLOCK_REASON_PREFIX: &str = "mc-identity:";
+
+const SECOND: Duration = Duration::from_secs(1);
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Player identity sent in Login Start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginIdentity {
+    pub username: String,
+    /// Hyphenated UUID, sent by 1.19.1+ clients
+    pub uuid: Option<String>,
+}
+
+impl LoginIdentity {
+    /// Key the identity is throttled under
+    pub fn key(&self) -> String {
+        self.username.to_ascii_lowercase()
+    }
+}
+
+/// Per-identity limits (0 = unlimited)
+#[derive(Debug, Clone)]
+pub struct IdentityPolicy {
+    pub max_connections: u32,
+    pub max_logins_per_minute: u32,
+    pub max_packets_per_second: u32,
+    pub max_chat_per_minute: u32,
+    /// Post-login packet floods, which kick the player
+    pub max_chat_per_second: u32,
+    pub max_commands_per_second: u32,
+    pub max_respawns_per_second: u32,
+    /// Disconnect message of players kicked for a flood
+    pub flood_kick_message: String,
+    /// Strikes within `strike_window` after which the identity is blocked
+    pub block_strikes: u32,
+    pub strike_window: Duration,
+    pub block_duration: Duration,
+}
+
+impl Default for IdentityPolicy {
+    fn default() -> Self {
+        Self {
+            max_connections: 3,
+            max_logins_per_minute: 10,
+            max_packets_per_second: 500,
+            max_chat_per_minute: 30,
+            max_chat_per_second: 5,
+            max_commands_per_second: 5,
+            max_respawns_per_second: 2,
+            flood_kick_message: "You are sending packets too quickly".to_string(),
+            block_strikes: 5,
+            strike_window: Duration::from_secs(300),
+            block_duration: Duration::from_secs(900),
+        }
+    }
+}
+
+impl IdentityPolicy {
+    /// Load identity limits from environment variables
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+
+        if let Some(max) = env_u32("PISTON_MC_IDENTITY_MAX_CONNECTIONS") {
+            policy.max_connections = max;
+        }
+        if let Some(max) = env_u32("PISTON_MC_IDENTITY_MAX_LOGINS_PER_MINUTE") {
+            policy.max_logins_per_minute = max;
+        }
+        if let Some(max) = env_u32("PISTON_MC_IDENTITY_MAX_PACKETS_PER_SECOND") {
+            policy.max_packets_per_second = max;
+        }
+        if let Some(max) = env_u32("PISTON_MC_IDENTITY_MAX_CHAT_PER_MINUTE") {
+            policy.max_chat_per_minute = max;
+        }
+        if let Some(max) = env_u32("PISTON_MC_IDENTITY_MAX_CHAT_PER_SECOND") {
+            policy.max_chat_per_second = max;
+        }
+        if let Some(max) = env_u32("PISTON_MC_IDENTITY_MAX_COMMANDS_PER_SECOND") {
+            policy.max_commands_per_second = max;
+        }
+        if let Some(max) = env_u32("PISTON_MC_IDENTITY_MAX_RESPAWNS_PER_SECOND") {
+            policy.max_respawns_per_second = max;
+        }
+        if let Ok(message) = std::env::var("PISTON_MC_IDENTITY_FLOOD_KICK_MESSAGE") {
+            policy.flood_kick_message = message;
+        }
+        if let Some(strikes) = env_u32("PISTON_MC_IDENTITY_BLOCK_STRIKES") {
+            policy.block_strikes = strikes.max(1);
+        }
+        if let Some(secs) = env_u32("PISTON_MC_IDENTITY_STRIKE_WINDOW_SECS") {
+            policy.strike_window = Duration::from_secs(secs.max(1) as u64);
+        }
+        if let Some(secs) = env_u32("PISTON_MC_IDENTITY_BLOCK_SECS") {
+            policy.block_duration = Duration::from_secs(secs.max(1) as u64);
+        }
+
+        policy
+    }
+}
+
+fn env_u32(name: &str) -> Option<u32> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Outcome of checking an identity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityVerdict {
+    Allow,
+    /// A limit was exceeded
+    Throttle,
+    /// A post-login packet flood: the player is disconnected
+    Kick,
+    /// The identity is blocked
+    Block,
+}
+
+/// Post-login packets of interest in one segment of a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayPackets {
+    pub chat: u32,
+    pub commands: u32,
+    pub respawns: u32,
+}
+
+/// Identity block waiting to be applied to the IP blocklist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityBlock {
+    pub username: String,
+    /// Addresses the identity logged in from
+    pub addrs: Vec<IpAddr>,
+    pub duration: Duration,
+}
+
+impl IdentityBlock {
+    /// Reason recorded with the IP blocks
+    pub fn reason(&self) -> String {
+        format!("{}{}", BLOCK_REASON_PREFIX, self.username)
+    }
+}
+
+/// Blocked identity
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockedIdentity {
+    pub username: String,
+    pub uuid: Option<String>,
+    pub addrs: Vec<IpAddr>,
+    pub remaining_secs: u64,
+}
+
+/// Identity throttle status
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentityThrottleStatus {
+    pub tracked_identities: usize,
+    pub logins_refused: u64,
+    pub packets_throttled: u64,
+    /// Players disconnected for a post-login packet flood
+    pub flood_kicks: u64,
+    pub blocked: Vec<BlockedIdentity>,
+}
+
+/// Event counter over fixed windows
+#[derive(Debug, Clone, Copy)]
+struct RateWindow {
+    start: Instant,
+    count: u32,
+}
+
+impl RateWindow {
+    fn new(now: Instant) -> Self {
+        Self {
+            start: now,
+            count: 0,
+        }
+    }
+
+    /// Count `events` and return how far the window is over `limit`
+    fn hit(&mut self, now: Instant, period: Duration, limit: u32, events: u32) -> u32 {
+        if now.saturating_duration_since(self.start) >= period {
+            self.start = now;
+            self.count = 0;
+        }
+        self.count = self.count.saturating_add(events);
+        if limit == 0 {
+            0
+        } else {
+            self.count.saturating_sub(limit)
+        }
+    }
+
+    /// Count `events` and return whether they exceed `limit` per second
+    fn flooded(&mut self, now: Instant, limit: u32, events: u32) -> bool {
+        events > 0 && self.hit(now, SECOND, limit, events) > 0
+    }
+}
+
+#[derive(Debug)]
+struct IdentityRecord {
+    username: String,
+    uuid: Option<String>,
+    /// Open connections and when they were last seen
+    connections: HashMap<SocketAddr, Instant>,
+    /// Addresses logged in from, most recent last
+    addrs: VecDeque<IpAddr>,
+    logins: RateWindow,
+    packets: RateWindow,
+    chat: RateWindow,
+    chat_burst: RateWindow,
+    commands: RateWindow,
+    respawns: RateWindow,
+    strikes: u32,
+    last_strike: Instant,
+    last_seen: Instant,
+}
+
+impl IdentityRecord {
+    fn new(identity: &LoginIdentity, now: Instant) -> Self {
+        Self {
+            username: identity.username.clone(),
+            uuid: identity.uuid.clone(),
+            connections: HashMap::new(),
+            addrs: VecDeque::new(),
+            logins: RateWindow::new(now),
+            packets: RateWindow::new(now),
+            chat: RateWindow::new(now),
+            chat_burst: RateWindow::new(now),
+            commands: RateWindow::new(now),
+            respawns: RateWindow::new(now),
+            strikes: 0,
+            last_strike: now,
+            last_seen: now,
+        }
+    }
+
+    fn remember_addr(&mut self, ip: IpAddr) {
+        self.addrs.retain(|addr| *addr != ip);
+        if self.addrs.len() >= MAX_ADDRS_PER_IDENTITY {
+            self.addrs.pop_front();
+        }
+        self.addrs.push_back(ip);
+    }
+}
+
+#[derive(Debug)]
+struct Blocked {
+    username: String,
+    uuid: Option<String>,
+    addrs: Vec<IpAddr>,
+    until: Instant,
+}
+
+#[derive(Debug, Default)]
+struct ThrottleState {
+    identities: HashMap<String, IdentityRecord>,
+    blocked: HashMap<String, Blocked>,
+    pending: Vec<IdentityBlock>,
+    logins_refused: u64,
+    packets_throttled: u64,
+    flood_kicks: u64,
+}
+
+impl ThrottleState {
+    fn is_blocked(&mut self, key: &str, now: Instant) -> bool {
+        match self.blocked.get(key) {
+            Some(blocked) if blocked.until > now => true,
+            Some(_) => {
+                self.blocked.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Record a strike, blocking the identity once it has enough
+    fn strike(&mut self, policy: &IdentityPolicy, key: &str, now: Instant) -> IdentityVerdict {
+        let Some(record) = self.identities.get_mut(key) else {
+            return IdentityVerdict::Throttle;
+        };
+        if now.saturating_duration_since(record.last_strike) >= policy.strike_window {
+            record.strikes = 0;
+        }
+        record.strikes += 1;
+        record.last_strike = now;
+        if record.strikes < policy.block_strikes {
+            return IdentityVerdict::Throttle;
+        }
+
+        record.strikes = 0;
+        record.connections.clear();
+        let addrs: Vec<IpAddr> = record.addrs.iter().copied().collect();
+        self.pending.push(IdentityBlock {
+            username: record.username.clone(),
+            addrs: addrs.clone(),
+            duration: policy.block_duration,
+        });
+        self.blocked.insert(
+            key.to_string(),
+            Blocked {
+                username: record.username.clone(),
+                uuid: record.uuid.clone(),
+                addrs,
+                until: now + policy.block_duration,
+            },
+        );
+        IdentityVerdict::Block
+    }
+}
+
+/// Per-identity limits shared by every Java analyzer
+pub struct IdentityThrottle {
+    policy: IdentityPolicy,
+    state: Mutex<ThrottleState>,
+}
+
+impl IdentityThrottle {
+    pub fn new(policy: IdentityPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(ThrottleState::default()),
+        }
+    }
+
+    pub fn policy(&self) -> &IdentityPolicy {
+        &self.policy
+    }
+
+    /// Check a login of `identity` from `addr`
+    ///
+    /// New identities are not tracked, and so not limited, while
+    /// `MAX_IDENTITIES` identities are.
+    pub fn login(
+        &self,
+        identity: &LoginIdentity,
+        addr: SocketAddr,
+        now: Instant,
+    ) -> IdentityVerdict {
+        let key = identity.key();
+        let mut state = self.state.lock();
+        if state.is_blocked(&key, now) {
+            state.logins_refused += 1;
+            return IdentityVerdict::Block;
+        }
+        if state.identities.len() >= MAX_IDENTITIES && !state.identities.contains_key(&key) {
+            return IdentityVerdict::Allow;
+        }
+
+        let policy = &self.policy;
+        let record = state
+            .identities
+            .entry(key.clone())
+            .or_insert_with(|| IdentityRecord::new(identity, now));
+        record.uuid = identity.uuid.clone();
+        record.last_seen = now;
+        record.remember_addr(addr.ip());
+        record
+            .connections
+            .retain(|_, seen| now.saturating_duration_since(*seen) < CONNECTION_IDLE);
+
+        let too_many_connections = policy.max_connections != 0
+            && record.connections.len() >= policy.max_connections as usize
+            && !record.connections.contains_key(&addr);
+        let too_many_logins = record
+            .logins
+            .hit(now, MINUTE, policy.max_logins_per_minute, 1)
+            > 0;
+        if too_many_connections || too_many_logins {
+            state.logins_refused += 1;
+            return state.strike(policy, &key, now);
+        }
+
+        record.connections.insert(addr, now);
+        IdentityVerdict::Allow
+    }
+
+    /// Count a packet, carrying the chat messages, commands and respawns in
+    /// `play`, of a logged-in connection
+    ///
+    /// A limit exceeded throttles every further packet of its window but is
+    /// a single strike. A flood of play packets kicks the player, also for a
+    /// single strike, since the connection ends with it.
+    pub fn packet(
+        &self,
+        key: &str,
+        addr: SocketAddr,
+        play: PlayPackets,
+        now: Instant,
+    ) -> IdentityVerdict {
+        let mut state = self.state.lock();
+        if state.is_blocked(key, now) {
+            state.packets_throttled += 1;
+            return IdentityVerdict::Block;
+        }
+
+        let policy = &self.policy;
+        let Some(record) = state.identities.get_mut(key) else {
+            return IdentityVerdict::Allow;
+        };
+        record.last_seen = now;
+        record.connections.insert(addr, now);
+
+        let chat_flood = record
+            .chat_burst
+            .flooded(now, policy.max_chat_per_second, play.chat);
+        let command_flood =
+            record
+                .commands
+                .flooded(now, policy.max_commands_per_second, play.commands);
+        let respawn_flood =
+            record
+                .respawns
+                .flooded(now, policy.max_respawns_per_second, play.respawns);
+        if chat_flood || command_flood || respawn_flood {
+            record.connections.remove(&addr);
+            state.packets_throttled += 1;
+            state.flood_kicks += 1;
+            return match state.strike(policy, key, now) {
+                IdentityVerdict::Block => IdentityVerdict::Block,
+                _ => IdentityVerdict::Kick,
+            };
+        }
+
+        let packets_over = record
+            .packets
+            .hit(now, SECOND, policy.max_packets_per_second, 1);
+        let chat = play.chat;
+        let chat_over = if chat > 0 {
+            record
+                .chat
+                .hit(now, MINUTE, policy.max_chat_per_minute, chat)
+        } else {
+            0
+        };
+        if packets_over == 0 && chat_over == 0 {
+            return IdentityVerdict::Allow;
+        }
+
+        state.packets_throttled += 1;
+        // Strike only on the packet crossing a limit
+        if packets_over == 1 || (chat_over > 0 && chat_over <= chat) {
+            state.strike(policy, key, now)
+        } else {
+            IdentityVerdict::Throttle
+        }
+    }
+
+    /// Take the identity blocks not yet applied to the IP blocklist
+    pub fn take_blocks(&self) -> Vec<IdentityBlock> {
+        std::mem::take(&mut self.state.lock().pending)
+    }
+
+    /// Forget silent identities and expired blocks
+    pub fn prune(&self, now: Instant) {
+        let mut state = self.state.lock();
+        state
+            .identities
+            .retain(|_, record| now.saturating_duration_since(record.last_seen) < IDENTITY_IDLE);
+        state.blocked.retain(|_, blocked| blocked.until > now);
+    }
+
+    pub fn status(&self, now: Instant) -> IdentityThrottleStatus {
+        let state = self.state.lock();
+        let mut blocked: Vec<BlockedIdentity> = state
+            .blocked
+            .values()
+            .filter(|blocked| blocked.until > now)
+            .map(|blocked| BlockedIdentity {
+                username: blocked.username.clone(),
+                uuid: blocked.uuid.clone(),
+                addrs: blocked.addrs.clone(),
+                remaining_secs: blocked.until.saturating_duration_since(now).as_secs(),
+            })
+            .collect();
+        blocked.sort_by(|a, b| a.username.cmp(&b.username));
+
+        IdentityThrottleStatus {
+            tracked_identities: state.identities.len(),
+            logins_refused: state.logins_refused,
+            packets_throttled: state.packets_throttled,
+            flood_kicks: state.flood_kicks,
+            blocked,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(username: &str) -> LoginIdentity {
+        LoginIdentity {
+            username: username.to_string(),
+            uuid: None,
+        }
+    }
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    fn chat(chat: u32) -> PlayPackets {
+        PlayPackets {
+            chat,
+            ..Default::default()
+        }
+    }
+
+    fn policy() -> IdentityPolicy {
+        IdentityPolicy {
+            max_connections: 2,
+            max_logins_per_minute: 100,
+            max_packets_per_second: 10,
+            max_chat_per_minute: 3,
+            block_strikes: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_connection_limit_is_per_identity() {
+        let throttle = IdentityThrottle::new(policy());
+        let now = Instant::now();
+
+        // Players sharing an address are limited separately
+        for (i, name) in ["alice", "bob", "carol"].iter().enumerate() {
+            let client = addr(&format!("10.0.0.1:{}", 40000 + i));
+            assert_eq!(
+                throttle.login(&identity(name), client, now),
+                IdentityVerdict::Allow
+            );
+        }
+
+        assert_eq!(
+            throttle.login(&identity("Alice"), addr("10.0.0.2:1"), now),
+            IdentityVerdict::Allow
+        );
+        assert_eq!(
+            throttle.login(&identity("alice"), addr("10.0.0.3:1"), now),
+            IdentityVerdict::Throttle
+        );
+
+        // Idle connections stop counting
+        let later = now + CONNECTION_IDLE;
+        assert_eq!(
+            throttle.login(&identity("alice"), addr("10.0.0.3:1"), later),
+            IdentityVerdict::Allow
+        );
+    }
+
+    #[test]
+    fn test_repeat_offender_is_blocked() {
+        let throttle = IdentityThrottle::new(policy());
+        let start = Instant::now();
+        let client = addr("192.0.2.7:50000");
+        assert_eq!(
+            throttle.login(&identity("spammer"), client, start),
+            IdentityVerdict::Allow
+        );
+
+        // Flooding chat earns one strike per window crossed
+        let mut verdict = IdentityVerdict::Allow;
+        for minute in 0..3 {
+            let now = start + MINUTE * minute;
+            for _ in 0..5 {
+                verdict = throttle.packet("spammer", client, chat(1), now);
+            }
+        }
+        assert_eq!(verdict, IdentityVerdict::Block);
+
+        let blocks = throttle.take_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].reason(), "mc-identity:spammer");
+        assert_eq!(blocks[0].addrs, vec![client.ip()]);
+        assert!(throttle.take_blocks().is_empty());
+
+        // The identity stays blocked from every address until the block ends
+        let now = start + MINUTE * 3;
+        assert_eq!(
+            throttle.login(&identity("Spammer"), addr("198.51.100.1:1"), now),
+            IdentityVerdict::Block
+        );
+        let status = throttle.status(now);
+        assert_eq!(status.blocked.len(), 1);
+        assert_eq!(status.blocked[0].username, "spammer");
+
+        let expired = start + MINUTE * 2 + policy().block_duration;
+        throttle.prune(expired);
+        assert!(throttle.status(expired).blocked.is_empty());
+    }
+
+    #[test]
+    fn test_packet_flood_strikes_once_per_window() {
+        let throttle = IdentityThrottle::new(policy());
+        let now = Instant::now();
+        let client = addr("192.0.2.8:50000");
+        throttle.login(&identity("flooder"), client, now);
+
+        let verdicts: Vec<IdentityVerdict> = (0..50)
+            .map(|_| throttle.packet("flooder", client, PlayPackets::default(), now))
+            .collect();
+        assert!(verdicts[..10].iter().all(|v| *v == IdentityVerdict::Allow));
+        assert!(
+            verdicts[10..]
+                .iter()
+                .all(|v| *v == IdentityVerdict::Throttle)
+        );
+        assert!(throttle.take_blocks().is_empty());
+    }
+
+    #[test]
+    fn test_play_flood_kicks_and_escalates() {
+        let throttle = IdentityThrottle::new(policy());
+        let start = Instant::now();
+        let client = addr("203.0.113.9:50000");
+        throttle.login(&identity("bot"), client, start);
+
+        // Within the per-second limits nothing happens
+        let commands = PlayPackets {
+            commands: 5,
+            respawns: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            throttle.packet("bot", client, commands, start),
+            IdentityVerdict::Allow
+        );
+
+        // One command too many kicks the player
+        let command = PlayPackets {
+            commands: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            throttle.packet("bot", client, command, start),
+            IdentityVerdict::Kick
+        );
+        assert_eq!(throttle.status(start).flood_kicks, 1);
+
+        // Every kick is a strike: the third one blocks the identity
+        for second in 1..3 {
+            let now = start + SECOND * second;
+            throttle.login(&identity("bot"), client, now);
+            let verdict = throttle.packet("bot", client, chat(6), now);
+            let expected = if second == 2 {
+                IdentityVerdict::Block
+            } else {
+                IdentityVerdict::Kick
+            };
+            assert_eq!(verdict, expected);
+        }
+        let blocks = throttle.take_blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].addrs, vec![client.ip()]);
+    }
+}
@@ -4,6 +4,7 @@ pub mod haproxy;
 pub mod http;
 pub mod minecraft;
 pub mod minecraft_fallback;
+pub mod minecraft_identity;
 pub mod quic;
 pub mod tcp;
 pub mod udp;
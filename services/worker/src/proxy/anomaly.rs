@@ -0,0 +1,627 @@
+//! Origin response anomaly detection.
+//!
+//! Watches what the origin answers to proxied requests. A surge of inbound
+//! requests that coincides with the origin returning 5xx or failing to
+//! accept connections means a flood is getting through to the origin, so
+//! the host is switched to stricter layer 7 handling until the surge ends:
+//! per-client request limits, or a cookie challenge when most origin
+//! requests fail. Every mitigation opens an incident record that tracks the
+//! peak rates and when the mitigation was lifted.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use pistonprotection_common::metrics::{
+    L7_MITIGATED_REQUESTS, L7_MITIGATION_ACTIVE, ORIGIN_ERROR_RATE,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::cache::unix_now;
+
+/// Name of the cookie proving a client passed the challenge.
+pub const CHALLENGE_COOKIE: &str = "piston_challenge";
+
+/// Incident records kept for the status endpoint
+const MAX_INCIDENTS: usize = 100;
+
+/// Thresholds of the detector.
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    /// Length of an evaluation window
+    pub window: Duration,
+    /// Origin requests a window needs before its error rate counts
+    pub min_origin_requests: u64,
+    /// Origin error rate that, with an inbound spike, triggers strict limits
+    pub error_rate: f64,
+    /// Origin error rate that triggers the challenge instead
+    pub challenge_error_rate: f64,
+    /// Inbound rate over the baseline that counts as a spike
+    pub spike_factor: f64,
+    /// Floor of the baseline rate, so idle hosts do not spike on a handful
+    /// of requests
+    pub min_baseline_rps: f64,
+    /// Time a mitigation stays active after the last triggering window
+    pub hold: Duration,
+    /// Requests per second per client IP under strict limits
+    pub strict_rps: u32,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(10),
+            min_origin_requests: 20,
+            error_rate: 0.25,
+            challenge_error_rate: 0.5,
+            spike_factor: 3.0,
+            min_baseline_rps: 5.0,
+            hold: Duration::from_secs(300),
+            strict_rps: 10,
+        }
+    }
+}
+
+impl AnomalyConfig {
+    /// Load the thresholds from `PISTON_HTTP_ANOMALY_*` environment
+    /// variables.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(secs) = env_parse::<u64>("PISTON_HTTP_ANOMALY_WINDOW_SECS") {
+            config.window = Duration::from_secs(secs.max(1));
+        }
+        if let Some(min) = env_parse("PISTON_HTTP_ANOMALY_MIN_REQUESTS") {
+            config.min_origin_requests = min;
+        }
+        if let Some(rate) = env_parse::<f64>("PISTON_HTTP_ANOMALY_ERROR_RATE") {
+            config.error_rate = rate.clamp(0.0, 1.0);
+        }
+        if let Some(rate) = env_parse::<f64>("PISTON_HTTP_ANOMALY_CHALLENGE_ERROR_RATE") {
+            config.challenge_error_rate = rate.clamp(0.0, 1.0);
+        }
+        if let Some(factor) = env_parse::<f64>("PISTON_HTTP_ANOMALY_SPIKE_FACTOR") {
+            config.spike_factor = factor.max(1.0);
+        }
+        if let Some(rps) = env_parse::<f64>("PISTON_HTTP_ANOMALY_MIN_BASELINE_RPS") {
+            config.min_baseline_rps = rps.max(0.0);
+        }
+        if let Some(secs) = env_parse("PISTON_HTTP_ANOMALY_HOLD_SECS") {
+            config.hold = Duration::from_secs(secs);
+        }
+        if let Some(rps) = env_parse::<u32>("PISTON_HTTP_ANOMALY_STRICT_RPS") {
+            config.strict_rps = rps.max(1);
+        }
+
+        config
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Stricter layer 7 handling of a host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mitigation {
+    /// Per-client request limits
+    StrictLimits,
+    /// Cookie challenge on top of the per-client limits
+    Challenge,
+}
+
+impl Mitigation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mitigation::StrictLimits => "strict_limits",
+            Mitigation::Challenge => "challenge",
+        }
+    }
+}
+
+/// Outcome of a request forwarded to the origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OriginOutcome {
+    Success,
+    /// 5xx response
+    ServerError,
+    /// Connection failure or timeout
+    ConnectFailure,
+}
+
+/// What the proxy does with a request under mitigation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Allow,
+    /// The client exceeded the strict per-client limit
+    RateLimited,
+    /// The client has to pass the challenge first
+    Challenge,
+}
+
+/// Record of a mitigation, from the triggering window until it was lifted.
+#[derive(Debug, Clone, Serialize)]
+pub struct Incident {
+    pub id: String,
+    pub host: String,
+    pub mitigation: Mitigation,
+    /// Unix seconds
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    /// Inbound rate before the spike
+    pub baseline_rps: f64,
+    pub peak_rps: f64,
+    pub peak_error_rate: f64,
+    pub origin_errors: u64,
+    pub connect_failures: u64,
+}
+
+/// Change of a host's mitigation found by an evaluation.
+#[derive(Debug, Clone)]
+pub enum AnomalyEvent {
+    Opened(Incident),
+    Escalated(Incident),
+    Resolved(Incident),
+}
+
+/// Mitigations and incidents of the detector.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnomalyStatus {
+    /// Active mitigation per host
+    pub mitigations: HashMap<String, Mitigation>,
+    /// Recent incidents, newest first
+    pub incidents: Vec<Incident>,
+}
+
+/// Counters of the current window and the state of one host.
+#[derive(Debug, Default)]
+struct HostWindow {
+    inbound: u64,
+    origin_requests: u64,
+    origin_errors: u64,
+    connect_failures: u64,
+    /// Smoothed inbound rate of windows without an anomaly
+    baseline_rps: Option<f64>,
+    active: Option<ActiveMitigation>,
+}
+
+#[derive(Debug)]
+struct ActiveMitigation {
+    level: Mitigation,
+    until: Instant,
+    incident: Incident,
+}
+
+/// Weight of the newest window in the baseline
+const BASELINE_ALPHA: f64 = 0.2;
+
+/// Detects origin error spikes under inbound surges and decides the
+/// mitigation of each host.
+pub struct OriginAnomalyDetector {
+    config: AnomalyConfig,
+    hosts: Mutex<HashMap<String, HostWindow>>,
+    /// Requests of each client in the current second, under strict limits
+    clients: Mutex<HashMap<(String, IpAddr), (Instant, u32)>>,
+    incidents: Mutex<VecDeque<Incident>>,
+    /// Key of the challenge cookies, regenerated on restart
+    challenge_secret: [u8; 32],
+}
+
+impl OriginAnomalyDetector {
+    /// Create a detector without history.
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self {
+            config,
+            hosts: Mutex::new(HashMap::new()),
+            clients: Mutex::new(HashMap::new()),
+            incidents: Mutex::new(VecDeque::new()),
+            challenge_secret: rand::random(),
+        }
+    }
+
+    /// Length of an evaluation window.
+    pub fn window(&self) -> Duration {
+        self.config.window
+    }
+
+    /// Count a request received for `host`.
+    pub fn record_inbound(&self, host: &str) {
+        self.with_host(host, |window| window.inbound += 1);
+    }
+
+    /// Count the outcome of a request forwarded to the origin of `host`.
+    pub fn record_origin(&self, host: &str, outcome: OriginOutcome) {
+        self.with_host(host, |window| {
+            window.origin_requests += 1;
+            match outcome {
+                OriginOutcome::Success => {}
+                OriginOutcome::ServerError => window.origin_errors += 1,
+                OriginOutcome::ConnectFailure => window.connect_failures += 1,
+            }
+        });
+    }
+
+    fn with_host(&self, host: &str, f: impl FnOnce(&mut HostWindow)) {
+        let mut hosts = self.hosts.lock();
+        match hosts.get_mut(host) {
+            Some(window) => f(window),
+            None => f(hosts.entry(host.to_string()).or_default()),
+        }
+    }
+
+    /// Active mitigation of `host`.
+    pub fn mitigation(&self, host: &str) -> Option<Mitigation> {
+        let hosts = self.hosts.lock();
+        hosts.get(host)?.active.as_ref().map(|active| active.level)
+    }
+
+    /// Decide whether a request of `client` to `host` goes through, given
+    /// the challenge cookie it presented.
+    pub fn admit(
+        &self,
+        host: &str,
+        client: IpAddr,
+        cookie: Option<&str>,
+        now: Instant,
+    ) -> Admission {
+        let Some(level) = self.mitigation(host) else {
+            return Admission::Allow;
+        };
+
+        let admission = if level == Mitigation::Challenge
+            && cookie != Some(self.challenge_token(host, client).as_str())
+        {
+            Admission::Challenge
+        } else if !self.within_strict_limit(host, client, now) {
+            Admission::RateLimited
+        } else {
+            Admission::Allow
+        };
+
+        let action = match admission {
+            Admission::Allow => "allowed",
+            Admission::RateLimited => "rate_limited",
+            Admission::Challenge => "challenged",
+        };
+        L7_MITIGATED_REQUESTS
+            .with_label_values(&[host, action])
+            .inc();
+        admission
+    }
+
+    fn within_strict_limit(&self, host: &str, client: IpAddr, now: Instant) -> bool {
+        let mut clients = self.clients.lock();
+        let (start, count) = clients
+            .entry((host.to_string(), client))
+            .or_insert((now, 0));
+        if now.saturating_duration_since(*start) >= Duration::from_secs(1) {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.config.strict_rps
+    }
+
+    /// Cookie value proving `client` passed the challenge of `host`.
+    pub fn challenge_token(&self, host: &str, client: IpAddr) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.challenge_secret);
+        hasher.update(host.as_bytes());
+        hasher.update(client.to_string().as_bytes());
+        hex::encode(&hasher.finalize()[..16])
+    }
+
+    /// Close the current window of every host: compare its inbound rate and
+    /// origin error rate with the thresholds, apply, escalate or lift
+    /// mitigations, and return the changes.
+    pub fn evaluate(&self, now: Instant) -> Vec<AnomalyEvent> {
+        let window_secs = self.config.window.as_secs_f64();
+        let mut events = Vec::new();
+        let mut lifted = Vec::new();
+
+        let mut hosts = self.hosts.lock();
+        for (host, window) in hosts.iter_mut() {
+            let rps = window.inbound as f64 / window_secs;
+            let failures = window.origin_errors + window.connect_failures;
+            let error_rate = if window.origin_requests >= self.config.min_origin_requests {
+                failures as f64 / window.origin_requests as f64
+            } else {
+                0.0
+            };
+            let spike = window.baseline_rps.is_some_and(|baseline| {
+                rps >= baseline.max(self.config.min_baseline_rps) * self.config.spike_factor
+            });
+            ORIGIN_ERROR_RATE.with_label_values(&[host]).set(error_rate);
+
+            let triggered = (spike && error_rate >= self.config.error_rate).then_some(
+                if error_rate >= self.config.challenge_error_rate {
+                    Mitigation::Challenge
+                } else {
+                    Mitigation::StrictLimits
+                },
+            );
+
+            match (triggered, window.active.as_mut()) {
+                (Some(level), Some(active)) => {
+                    let incident = &mut active.incident;
+                    incident.peak_rps = incident.peak_rps.max(rps);
+                    incident.peak_error_rate = incident.peak_error_rate.max(error_rate);
+                    incident.origin_errors += window.origin_errors;
+                    incident.connect_failures += window.connect_failures;
+                    active.until = now + self.config.hold;
+                    if level > active.level {
+                        active.level = level;
+                        incident.mitigation = level;
+                        events.push(AnomalyEvent::Escalated(incident.clone()));
+                    }
+                }
+                (Some(level), None) => {
+                    let incident = Incident {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        host: host.clone(),
+                        mitigation: level,
+                        started_at: unix_now(),
+                        ended_at: None,
+                        baseline_rps: window.baseline_rps.unwrap_or_default(),
+                        peak_rps: rps,
+                        peak_error_rate: error_rate,
+                        origin_errors: window.origin_errors,
+                        connect_failures: window.connect_failures,
+                    };
+                    events.push(AnomalyEvent::Opened(incident.clone()));
+                    window.active = Some(ActiveMitigation {
+                        level,
+                        until: now + self.config.hold,
+                        incident,
+                    });
+                }
+                (None, Some(active)) => {
+                    active.incident.origin_errors += window.origin_errors;
+                    active.incident.connect_failures += window.connect_failures;
+                    if now >= active.until {
+                        let mut incident = window.active.take().unwrap().incident;
+                        incident.ended_at = Some(unix_now());
+                        events.push(AnomalyEvent::Resolved(incident));
+                        lifted.push(host.clone());
+                    }
+                }
+                (None, None) => {
+                    // Only traffic without an anomaly moves the baseline
+                    window.baseline_rps = Some(match window.baseline_rps {
+                        Some(baseline) => baseline + BASELINE_ALPHA * (rps - baseline),
+                        None => rps,
+                    });
+                }
+            }
+
+            window.inbound = 0;
+            window.origin_requests = 0;
+            window.origin_errors = 0;
+            window.connect_failures = 0;
+        }
+
+        L7_MITIGATION_ACTIVE.reset();
+        for (host, window) in hosts.iter() {
+            if let Some(active) = &window.active {
+                L7_MITIGATION_ACTIVE
+                    .with_label_values(&[host.as_str(), active.level.as_str()])
+                    .set(1.0);
+            }
+        }
+        drop(hosts);
+
+        // Client windows older than a second no longer limit anything
+        self.clients.lock().retain(|(host, _), (start, _)| {
+            !lifted.contains(host) && now.saturating_duration_since(*start) < Duration::from_secs(1)
+        });
+
+        let mut incidents = self.incidents.lock();
+        for event in &events {
+            let incident = match event {
+                AnomalyEvent::Opened(incident)
+                | AnomalyEvent::Escalated(incident)
+                | AnomalyEvent::Resolved(incident) => incident,
+            };
+            incidents.retain(|i| i.id != incident.id);
+            incidents.push_front(incident.clone());
+        }
+        incidents.truncate(MAX_INCIDENTS);
+
+        events
+    }
+
+    /// Active mitigations and recent incidents.
+    pub fn status(&self) -> AnomalyStatus {
+        let mitigations = self
+            .hosts
+            .lock()
+            .iter()
+            .filter_map(|(host, window)| Some((host.clone(), window.active.as_ref()?.level)))
+            .collect();
+
+        AnomalyStatus {
+            mitigations,
+            incidents: self.incidents.lock().iter().cloned().collect(),
+        }
+    }
+
+    /// Baseline inbound rate of every host that has one, sorted by host.
+    pub fn baselines(&self) -> Vec<(String, f64)> {
+        let mut baselines: Vec<(String, f64)> = self
+            .hosts
+            .lock()
+            .iter()
+            .filter_map(|(host, window)| Some((host.clone(), window.baseline_rps?)))
+            .collect();
+        baselines.sort_by(|a, b| a.0.cmp(&b.0));
+        baselines
+    }
+
+    /// Start hosts without a baseline from the baselines of another worker.
+    ///
+    /// Baselines this worker observed itself are kept. Returns the number of
+    /// hosts seeded.
+    pub fn seed_baselines(&self, baselines: &[(String, f64)]) -> usize {
+        let mut seeded = 0;
+        for (host, rps) in baselines {
+            if !rps.is_finite() || *rps < 0.0 {
+                continue;
+            }
+            self.with_host(host, |window| {
+                if window.baseline_rps.is_none() {
+                    window.baseline_rps = Some(*rps);
+                    seeded += 1;
+                }
+            });
+        }
+        seeded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST: &str = "example.com";
+
+    fn detector() -> OriginAnomalyDetector {
+        OriginAnomalyDetector::new(AnomalyConfig {
+            window: Duration::from_secs(1),
+            min_origin_requests: 10,
+            hold: Duration::from_secs(30),
+            strict_rps: 3,
+            ..Default::default()
+        })
+    }
+
+    /// Record one window of traffic and evaluate it.
+    fn window(
+        detector: &OriginAnomalyDetector,
+        inbound: u64,
+        errors: u64,
+        now: Instant,
+    ) -> Vec<AnomalyEvent> {
+        for i in 0..inbound {
+            detector.record_inbound(HOST);
+            let outcome = if i < errors {
+                OriginOutcome::ServerError
+            } else {
+                OriginOutcome::Success
+            };
+            detector.record_origin(HOST, outcome);
+        }
+        detector.evaluate(now)
+    }
+
+    #[test]
+    fn test_errors_without_spike_do_not_mitigate() {
+        let detector = detector();
+        let now = Instant::now();
+
+        assert!(window(&detector, 20, 0, now).is_empty());
+        // A failing origin under normal traffic is an outage, not an attack
+        assert!(window(&detector, 20, 20, now).is_empty());
+        // A spike the origin handles is not an anomaly either
+        assert!(window(&detector, 200, 0, now).is_empty());
+        assert_eq!(detector.mitigation(HOST), None);
+    }
+
+    #[test]
+    fn test_spike_with_errors_mitigates_and_escalates() {
+        let detector = detector();
+        let now = Instant::now();
+        window(&detector, 20, 0, now);
+
+        let events = window(&detector, 100, 30, now);
+        let AnomalyEvent::Opened(incident) = &events[0] else {
+            panic!("expected an incident, got {:?}", events);
+        };
+        assert_eq!(incident.mitigation, Mitigation::StrictLimits);
+        assert_eq!(incident.baseline_rps, 20.0);
+        assert_eq!(detector.mitigation(HOST), Some(Mitigation::StrictLimits));
+
+        let events = window(&detector, 150, 120, now);
+        assert!(
+            matches!(&events[0], AnomalyEvent::Escalated(i) if i.mitigation == Mitigation::Challenge)
+        );
+        assert_eq!(detector.mitigation(HOST), Some(Mitigation::Challenge));
+
+        let status = detector.status();
+        assert_eq!(status.incidents.len(), 1);
+        assert_eq!(status.incidents[0].peak_rps, 150.0);
+        assert_eq!(status.incidents[0].origin_errors, 150);
+    }
+
+    #[test]
+    fn test_mitigation_lifted_after_hold() {
+        let detector = detector();
+        let now = Instant::now();
+        window(&detector, 20, 0, now);
+        window(&detector, 100, 30, now);
+
+        assert!(window(&detector, 20, 0, now + Duration::from_secs(10)).is_empty());
+        let events = window(&detector, 20, 0, now + Duration::from_secs(31));
+        assert!(matches!(&events[0], AnomalyEvent::Resolved(i) if i.ended_at.is_some()));
+        assert_eq!(detector.mitigation(HOST), None);
+
+        // The baseline did not absorb the spike
+        assert_eq!(detector.hosts.lock()[HOST].baseline_rps, Some(20.0));
+    }
+
+    #[test]
+    fn test_seeded_baseline() {
+        let seasoned = detector();
+        let now = Instant::now();
+        window(&seasoned, 20, 0, now);
+        assert_eq!(seasoned.baselines(), vec![(HOST.to_string(), 20.0)]);
+
+        // A fresh worker detects the spike in its first window
+        let fresh = detector();
+        assert_eq!(fresh.seed_baselines(&seasoned.baselines()), 1);
+        let events = window(&fresh, 100, 30, now);
+        assert!(matches!(&events[0], AnomalyEvent::Opened(_)));
+
+        // What a worker observed itself is not overwritten
+        assert_eq!(fresh.seed_baselines(&[(HOST.to_string(), 500.0)]), 0);
+        assert_eq!(fresh.baselines(), vec![(HOST.to_string(), 20.0)]);
+    }
+
+    #[test]
+    fn test_admission() {
+        let detector = detector();
+        let now = Instant::now();
+        let client: IpAddr = "198.51.100.7".parse().unwrap();
+        assert_eq!(detector.admit(HOST, client, None, now), Admission::Allow);
+
+        window(&detector, 20, 0, now);
+        window(&detector, 100, 30, now);
+        let admitted = (0..5)
+            .filter(|_| detector.admit(HOST, client, None, now) == Admission::Allow)
+            .count();
+        assert_eq!(admitted, 3);
+        assert_eq!(
+            detector.admit(HOST, client, None, now + Duration::from_secs(1)),
+            Admission::Allow
+        );
+
+        window(&detector, 150, 120, now);
+        assert_eq!(
+            detector.admit(HOST, client, None, now + Duration::from_secs(2)),
+            Admission::Challenge
+        );
+        let token = detector.challenge_token(HOST, client);
+        assert_eq!(
+            detector.admit(HOST, client, Some(&token), now + Duration::from_secs(2)),
+            Admission::Allow
+        );
+        // Tokens are bound to the client address
+        let other: IpAddr = "198.51.100.8".parse().unwrap();
+        assert_eq!(
+            detector.admit(HOST, other, Some(&token), now + Duration::from_secs(2)),
+            Admission::Challenge
+        );
+    }
+}
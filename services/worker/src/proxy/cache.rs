@@ -0,0 +1,507 @@
+//! Response cache of the HTTP reverse proxy.
+//!
+//! Static assets are kept in a size-limited in-memory LRU tier and, when a
+//! cache directory is configured, a size-limited disk tier that entries
+//! evicted from memory fall back to. Freshness follows the `Cache-Control`
+//! header of the origin response; stale entries are still served while they
+//! are revalidated (`stale-while-revalidate`) and while the origin is
+//! failing (`stale-if-error`, at least the configured outage grace period).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use lru::LruCache;
+use parking_lot::Mutex;
+use pistonprotection_common::metrics::HTTP_CACHE_BYTES;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+/// File extensions of static assets
+const STATIC_EXTENSIONS: &[&str] = &[
+    "css", "js", "mjs", "map", "png", "jpg", "jpeg", "gif", "webp", "avif", "svg", "ico", "bmp",
+    "woff", "woff2", "ttf", "otf", "eot", "mp3", "mp4", "webm", "ogg", "wasm", "pdf", "zip",
+];
+
+/// Content types of static assets
+const STATIC_CONTENT_TYPES: &[&str] = &[
+    "text/css",
+    "text/javascript",
+    "application/javascript",
+    "application/wasm",
+    "image/",
+    "font/",
+    "audio/",
+    "video/",
+];
+
+/// Parsed `Cache-Control` header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub max_age: Option<u64>,
+    pub s_maxage: Option<u64>,
+    pub stale_while_revalidate: Option<u64>,
+    pub stale_if_error: Option<u64>,
+}
+
+impl CacheControl {
+    /// Parse the directives of a `Cache-Control` header value.
+    pub fn parse(value: &str) -> Self {
+        let mut control = Self::default();
+        for directive in value.split(',') {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name, Some(arg.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = arg.and_then(|arg| arg.parse::<u64>().ok());
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-store" => control.no_store = true,
+                "no-cache" => control.no_cache = true,
+                "private" => control.private = true,
+                "max-age" => control.max_age = seconds,
+                "s-maxage" => control.s_maxage = seconds,
+                "stale-while-revalidate" => control.stale_while_revalidate = seconds,
+                "stale-if-error" => control.stale_if_error = seconds,
+                _ => {}
+            }
+        }
+        control
+    }
+
+    /// Whether a shared cache may store the response.
+    pub fn storable(&self) -> bool {
+        !self.no_store && !self.no_cache && !self.private
+    }
+
+    /// Freshness lifetime for a shared cache, if the origin set one.
+    pub fn ttl(&self) -> Option<u64> {
+        self.s_maxage.or(self.max_age)
+    }
+}
+
+/// Whether a request path or response content type is a static asset.
+pub fn is_static_asset(path: &str, content_type: Option<&str>) -> bool {
+    let extension = path
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    if extension.is_some_and(|ext| STATIC_EXTENSIONS.contains(&ext.as_str())) {
+        return true;
+    }
+
+    content_type.is_some_and(|content_type| {
+        let content_type = content_type.to_ascii_lowercase();
+        STATIC_CONTENT_TYPES
+            .iter()
+            .any(|prefix| content_type.starts_with(prefix))
+    })
+}
+
+/// Cache key of a request.
+pub fn cache_key(host: &str, path_and_query: &str, accept_encoding: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(host.to_ascii_lowercase().as_bytes());
+    hasher.update(b"\0");
+    hasher.update(path_and_query.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(accept_encoding.unwrap_or_default().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Current UNIX time in seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Response stored in the cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub status: u16,
+    /// End-to-end headers of the origin response
+    pub headers: Vec<(String, String)>,
+    #[serde(skip)]
+    pub body: Bytes,
+    /// When the response was stored or last revalidated (UNIX seconds)
+    pub stored_at: u64,
+    /// Freshness lifetime in seconds
+    pub ttl: u64,
+    /// Seconds after expiry the response is served while revalidating
+    pub stale_while_revalidate: u64,
+    /// Seconds after expiry the response is served while the origin fails
+    pub stale_if_error: u64,
+}
+
+/// Freshness of a cached response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    Fresh,
+    /// Expired, but may be served while it is revalidated
+    StaleWhileRevalidate,
+    /// Expired, may only be served while the origin fails
+    StaleIfError,
+    Expired,
+}
+
+impl CachedResponse {
+    /// Freshness at UNIX time `now`.
+    pub fn freshness(&self, now: u64) -> Freshness {
+        let age = now.saturating_sub(self.stored_at);
+        if age < self.ttl {
+            Freshness::Fresh
+        } else if age < self.ttl + self.stale_while_revalidate {
+            Freshness::StaleWhileRevalidate
+        } else if age < self.ttl + self.stale_if_error {
+            Freshness::StaleIfError
+        } else {
+            Freshness::Expired
+        }
+    }
+
+    /// Whether the response may still be served in some way.
+    fn usable(&self, now: u64) -> bool {
+        self.freshness(now) != Freshness::Expired
+    }
+
+    /// Value of a stored header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn size(&self) -> u64 {
+        let headers: usize = self.headers.iter().map(|(n, v)| n.len() + v.len()).sum();
+        (self.body.len() + headers) as u64
+    }
+}
+
+/// Size limits of the cache tiers.
+#[derive(Debug, Clone)]
+pub struct CacheLimits {
+    /// Bytes kept in memory
+    pub memory_bytes: u64,
+    /// Directory of the disk tier (disabled when unset)
+    pub disk_dir: Option<PathBuf>,
+    /// Bytes kept on disk
+    pub disk_bytes: u64,
+    /// Largest response body cached
+    pub max_object_bytes: u64,
+}
+
+/// Entry of the disk tier index.
+struct DiskEntry {
+    size: u64,
+    expires_at: u64,
+    last_access: u64,
+}
+
+struct Tiers {
+    memory: LruCache<String, CachedResponse>,
+    memory_bytes: u64,
+    disk: HashMap<String, DiskEntry>,
+    disk_bytes: u64,
+}
+
+/// Two-tier response cache.
+pub struct ResponseCache {
+    limits: CacheLimits,
+    tiers: Mutex<Tiers>,
+}
+
+impl ResponseCache {
+    /// Create an empty cache, creating the disk directory if needed.
+    pub fn new(limits: CacheLimits) -> Self {
+        let mut limits = limits;
+        if let Some(dir) = &limits.disk_dir {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                warn!(dir = %dir.display(), "Disabling disk cache: {}", e);
+                limits.disk_dir = None;
+            }
+        }
+
+        Self {
+            limits,
+            tiers: Mutex::new(Tiers {
+                memory: LruCache::unbounded(),
+                memory_bytes: 0,
+                disk: HashMap::new(),
+                disk_bytes: 0,
+            }),
+        }
+    }
+
+    /// Largest response body cached.
+    pub fn max_object_bytes(&self) -> u64 {
+        self.limits.max_object_bytes
+    }
+
+    /// Look up a response that may still be served.
+    pub fn get(&self, key: &str, now: u64) -> Option<CachedResponse> {
+        let mut tiers = self.tiers.lock();
+        if let Some(response) = tiers.memory.get(key) {
+            if response.usable(now) {
+                return Some(response.clone());
+            }
+            if let Some(expired) = tiers.memory.pop(key) {
+                tiers.memory_bytes -= expired.size();
+            }
+        }
+
+        let entry = tiers.disk.get_mut(key)?;
+        if entry.expires_at <= now {
+            self.remove_disk(&mut tiers, key);
+            return None;
+        }
+        entry.last_access = now;
+
+        match self.read_disk(key) {
+            Some(response) if response.usable(now) => {
+                // Promote to memory; the disk copy stays as a fallback
+                self.insert_memory(&mut tiers, key, response.clone());
+                self.record_sizes(&tiers);
+                Some(response)
+            }
+            _ => {
+                self.remove_disk(&mut tiers, key);
+                None
+            }
+        }
+    }
+
+    /// Store a response.
+    pub fn insert(&self, key: &str, response: CachedResponse) {
+        if response.body.len() as u64 > self.limits.max_object_bytes {
+            return;
+        }
+
+        let mut tiers = self.tiers.lock();
+        if let Some(old) = tiers.memory.pop(key) {
+            tiers.memory_bytes -= old.size();
+        }
+        if tiers.disk.contains_key(key) {
+            self.remove_disk(&mut tiers, key);
+        }
+        self.insert_memory(&mut tiers, key, response);
+        self.record_sizes(&tiers);
+    }
+
+    /// Bytes held by the memory and disk tiers.
+    pub fn sizes(&self) -> (u64, u64) {
+        let tiers = self.tiers.lock();
+        (tiers.memory_bytes, tiers.disk_bytes)
+    }
+
+    fn insert_memory(&self, tiers: &mut Tiers, key: &str, response: CachedResponse) {
+        tiers.memory_bytes += response.size();
+        tiers.memory.put(key.to_string(), response);
+
+        // Evict least recently used entries to disk
+        while tiers.memory_bytes > self.limits.memory_bytes {
+            let Some((evicted_key, evicted)) = tiers.memory.pop_lru() else {
+                break;
+            };
+            tiers.memory_bytes -= evicted.size();
+            if !tiers.disk.contains_key(&evicted_key) {
+                self.write_disk(tiers, &evicted_key, &evicted);
+            }
+        }
+    }
+
+    fn disk_path(&self, key: &str, extension: &str) -> Option<PathBuf> {
+        let dir = self.limits.disk_dir.as_ref()?;
+        Some(dir.join(format!("{}.{}", key, extension)))
+    }
+
+    fn write_disk(&self, tiers: &mut Tiers, key: &str, response: &CachedResponse) {
+        let (Some(meta_path), Some(body_path)) =
+            (self.disk_path(key, "meta"), self.disk_path(key, "body"))
+        else {
+            return;
+        };
+        let size = response.size();
+        if size > self.limits.disk_bytes {
+            return;
+        }
+
+        // Evict least recently used files to make room
+        while tiers.disk_bytes + size > self.limits.disk_bytes {
+            let Some(oldest) = tiers
+                .disk
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_access)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove_disk(tiers, &oldest);
+        }
+
+        let meta = match serde_json::to_vec(response) {
+            Ok(meta) => meta,
+            Err(e) => {
+                warn!("Failed to encode cached response: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = std::fs::write(&body_path, &response.body)
+            .and_then(|_| std::fs::write(&meta_path, meta))
+        {
+            warn!(path = %body_path.display(), "Failed to write cached response: {}", e);
+            let _ = std::fs::remove_file(&body_path);
+            return;
+        }
+
+        tiers.disk_bytes += size;
+        tiers.disk.insert(
+            key.to_string(),
+            DiskEntry {
+                size,
+                expires_at: response.stored_at
+                    + response.ttl
+                    + response.stale_while_revalidate.max(response.stale_if_error),
+                last_access: unix_now(),
+            },
+        );
+    }
+
+    fn read_disk(&self, key: &str) -> Option<CachedResponse> {
+        let meta = std::fs::read(self.disk_path(key, "meta")?).ok()?;
+        let body = std::fs::read(self.disk_path(key, "body")?).ok()?;
+        let mut response: CachedResponse = serde_json::from_slice(&meta).ok()?;
+        response.body = Bytes::from(body);
+        Some(response)
+    }
+
+    fn remove_disk(&self, tiers: &mut Tiers, key: &str) {
+        if let Some(entry) = tiers.disk.remove(key) {
+            tiers.disk_bytes -= entry.size;
+        }
+        for extension in ["meta", "body"] {
+            if let Some(path) = self.disk_path(key, extension) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    fn record_sizes(&self, tiers: &Tiers) {
+        HTTP_CACHE_BYTES
+            .with_label_values(&["memory"])
+            .set(tiers.memory_bytes as f64);
+        HTTP_CACHE_BYTES
+            .with_label_values(&["disk"])
+            .set(tiers.disk_bytes as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &'static [u8], stored_at: u64) -> CachedResponse {
+        CachedResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/css".to_string())],
+            body: Bytes::from_static(body),
+            stored_at,
+            ttl: 60,
+            stale_while_revalidate: 30,
+            stale_if_error: 600,
+        }
+    }
+
+    fn limits(memory_bytes: u64, disk_dir: Option<PathBuf>) -> CacheLimits {
+        CacheLimits {
+            memory_bytes,
+            disk_dir,
+            disk_bytes: 1 << 20,
+            max_object_bytes: 1 << 16,
+        }
+    }
+
+    #[test]
+    fn test_parse_cache_control() {
+        let control = CacheControl::parse(
+            "public, max-age=300, s-maxage=\"600\", stale-while-revalidate=30, stale-if-error=86400",
+        );
+        assert!(control.storable());
+        assert_eq!(control.ttl(), Some(600));
+        assert_eq!(control.stale_while_revalidate, Some(30));
+        assert_eq!(control.stale_if_error, Some(86400));
+
+        assert!(!CacheControl::parse("no-store").storable());
+        assert!(!CacheControl::parse("private, max-age=60").storable());
+        assert!(!CacheControl::parse("No-Cache").storable());
+        assert_eq!(CacheControl::parse("max-age=abc").ttl(), None);
+    }
+
+    #[test]
+    fn test_static_assets() {
+        assert!(is_static_asset("/assets/app.3f2a.JS", None));
+        assert!(is_static_asset("/logo", Some("image/png")));
+        assert!(!is_static_asset("/api/users", Some("application/json")));
+        assert!(!is_static_asset("/v1.2/status", None));
+    }
+
+    #[test]
+    fn test_freshness() {
+        let response = response(b"body", 1000);
+        assert_eq!(response.freshness(1059), Freshness::Fresh);
+        assert_eq!(response.freshness(1060), Freshness::StaleWhileRevalidate);
+        assert_eq!(response.freshness(1100), Freshness::StaleIfError);
+        assert_eq!(response.freshness(1660), Freshness::Expired);
+    }
+
+    #[test]
+    fn test_memory_tier() {
+        let cache = ResponseCache::new(limits(1 << 20, None));
+        cache.insert("a", response(b"body", 1000));
+
+        assert_eq!(
+            cache.get("a", 1010).unwrap().body,
+            Bytes::from_static(b"body")
+        );
+        assert!(cache.get("a", 5000).is_none());
+        assert_eq!(cache.sizes().0, 0);
+
+        // Replacing an entry keeps the size accounting straight
+        cache.insert("b", response(b"body", 1000));
+        cache.insert("b", response(b"longer body", 1070));
+        assert_eq!(cache.sizes().0, response(b"longer body", 0).size());
+    }
+
+    #[test]
+    fn test_oversized_objects_are_not_cached() {
+        let mut limits = limits(1 << 20, None);
+        limits.max_object_bytes = 2;
+        let cache = ResponseCache::new(limits);
+        cache.insert("a", response(b"body", 1000));
+        assert!(cache.get("a", 1000).is_none());
+    }
+
+    #[test]
+    fn test_eviction_to_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry_size = response(b"0123456789", 0).size();
+        let cache = ResponseCache::new(limits(entry_size, Some(dir.path().to_path_buf())));
+
+        cache.insert("a", response(b"0123456789", 1000));
+        cache.insert("b", response(b"abcdefghij", 1000));
+        assert_eq!(cache.sizes(), (entry_size, entry_size));
+
+        // "a" was evicted to disk and is read back from there
+        let a = cache.get("a", 1010).unwrap();
+        assert_eq!(a.body, Bytes::from_static(b"0123456789"));
+        assert_eq!(a.header("Content-Type"), Some("text/css"));
+        assert!(cache.get("b", 1010).is_some());
+    }
+}
@@ -0,0 +1,1304 @@
+//! Layer 7 HTTP reverse proxy
+//!
+//! Optional caching reverse proxy for HTTP backends, enabled by configuring
+//! origins in `PISTON_HTTP_PROXY_ORIGINS`. Requests are forwarded to the
+//! origin of their `Host` through the origin connection pools, so saturated
+//! origins shed load instead of queueing without bound. Static assets are
+//! cached (see [`cache`]) and served from the cache while they are fresh,
+//! while they are revalidated in the background, and while the origin is
+//! down or shedding load, which keeps request floods for assets off the
+//! origin. WebSocket upgrades are relayed with per-connection limits (see
+//! [`websocket`]). Origin responses feed the anomaly detector (see
+//! [`anomaly`]), whose mitigations are enforced here before a request is
+//! served.
+
+pub mod anomaly;
+pub mod cache;
+pub mod websocket;
+
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full, Limited, combinators::BoxBody};
+use hyper::body::Incoming;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Method, Request, Response, StatusCode, Uri};
+use hyper_util::client::legacy::{Client, connect::HttpConnector};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use parking_lot::Mutex;
+use pistonprotection_common::backend_mode::BackendMode;
+use pistonprotection_common::metrics::HTTP_CACHE_REQUESTS;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::backend_mode::BackendModes;
+use crate::block_response::BlockResponses;
+use crate::routing::OriginPools;
+use crate::routing::pool::PoolPermit;
+use anomaly::{Admission, CHALLENGE_COOKIE, OriginAnomalyDetector, OriginOutcome};
+use cache::{CacheControl, CacheLimits, CachedResponse, Freshness, ResponseCache};
+use websocket::WebSocketLimits;
+
+/// Headers that only apply to a single connection
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+const MIB: u64 = 1024 * 1024;
+
+type ProxyBody = BoxBody<Bytes, hyper::Error>;
+
+/// Configuration of the HTTP reverse proxy.
+#[derive(Debug, Clone)]
+pub struct HttpProxyConfig {
+    /// Address the proxy listens on
+    pub listen: SocketAddr,
+    /// Origin base URI per host; `*` matches any other host
+    pub origins: HashMap<String, Uri>,
+    /// Freshness lifetime of static assets without `max-age` (0 = not cached)
+    pub default_ttl: u64,
+    /// Seconds an expired asset is still served while the origin fails,
+    /// unless the origin allows longer with `stale-if-error`
+    pub stale_if_error: u64,
+    /// Cache tier sizes
+    pub cache: CacheLimits,
+    /// Largest request body forwarded
+    pub max_request_bytes: usize,
+    /// Time allowed for the origin to answer
+    pub origin_timeout: Duration,
+    /// Limits of proxied WebSocket connections
+    pub websocket: WebSocketLimits,
+}
+
+impl HttpProxyConfig {
+    /// Load the configuration from `PISTON_HTTP_PROXY_*` and
+    /// `PISTON_HTTP_CACHE_*` environment variables. The proxy is disabled
+    /// (`None`) unless origins are configured.
+    ///
+    /// Origins are given as `host=http://address:port` pairs separated by
+    /// commas, e.g. `example.com=http://10.0.0.2:8080,*=http://10.0.0.3`.
+    pub fn from_env() -> Option<Self> {
+        let origins = parse_origins(&std::env::var("PISTON_HTTP_PROXY_ORIGINS").ok()?);
+        if origins.is_empty() {
+            warn!("PISTON_HTTP_PROXY_ORIGINS has no valid origins, HTTP proxy disabled");
+            return None;
+        }
+
+        let listen = std::env::var("PISTON_HTTP_PROXY_LISTEN")
+            .ok()
+            .and_then(|addr| addr.parse().ok())
+            .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 8080)));
+
+        Some(Self {
+            listen,
+            origins,
+            default_ttl: env_u64("PISTON_HTTP_CACHE_DEFAULT_TTL_SECS").unwrap_or(0),
+            stale_if_error: env_u64("PISTON_HTTP_CACHE_STALE_IF_ERROR_SECS").unwrap_or(3600),
+            cache: CacheLimits {
+                memory_bytes: env_u64("PISTON_HTTP_CACHE_MEMORY_MB").unwrap_or(64) * MIB,
+                disk_dir: std::env::var("PISTON_HTTP_CACHE_DIR")
+                    .ok()
+                    .map(PathBuf::from),
+                disk_bytes: env_u64("PISTON_HTTP_CACHE_DISK_MB").unwrap_or(1024) * MIB,
+                max_object_bytes: env_u64("PISTON_HTTP_CACHE_MAX_OBJECT_MB").unwrap_or(8) * MIB,
+            },
+            max_request_bytes: (env_u64("PISTON_HTTP_PROXY_MAX_REQUEST_MB").unwrap_or(16) * MIB)
+                as usize,
+            origin_timeout: Duration::from_secs(
+                env_u64("PISTON_HTTP_PROXY_ORIGIN_TIMEOUT_SECS").unwrap_or(30),
+            ),
+            websocket: WebSocketLimits::from_env(),
+        })
+    }
+
+    /// Origin base URI serving a host.
+    pub fn origin_for(&self, host: &str) -> Option<&Uri> {
+        self.origins
+            .get(&host.to_ascii_lowercase())
+            .or_else(|| self.origins.get("*"))
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Parse `host=http://origin` pairs, skipping invalid ones.
+fn parse_origins(value: &str) -> HashMap<String, Uri> {
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let (host, origin) = pair.split_once('=')?;
+            let uri: Uri = origin.trim().parse().ok()?;
+            if uri.scheme_str() != Some("http") || uri.authority().is_none() {
+                warn!(
+                    origin = %origin.trim(),
+                    "Ignoring HTTP proxy origin, only http:// origins are supported"
+                );
+                return None;
+            }
+            Some((host.trim().to_ascii_lowercase(), uri))
+        })
+        .collect()
+}
+
+/// Host of a request, lowercased and without the port.
+fn request_host<B>(req: &Request<B>) -> Option<String> {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .or_else(|| req.uri().host())?;
+    let host = match host.strip_prefix('[').and_then(|rest| rest.find(']')) {
+        Some(end) => &host[..end + 2],
+        None => host.split(':').next().unwrap_or(host),
+    };
+    Some(host.to_ascii_lowercase())
+}
+
+/// Copy end-to-end headers, dropping hop-by-hop ones and those listed in
+/// `Connection`.
+fn end_to_end_headers(headers: &HeaderMap) -> HeaderMap {
+    let listed: HashSet<String> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .collect();
+
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            !HOP_BY_HOP_HEADERS.contains(&name.as_str()) && !listed.contains(name.as_str())
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+fn full(body: Bytes) -> ProxyBody {
+    Full::new(body).map_err(|never| match never {}).boxed()
+}
+
+fn error_response(status: StatusCode, message: &'static str) -> Response<ProxyBody> {
+    let mut response = Response::new(full(Bytes::from_static(message.as_bytes())));
+    *response.status_mut() = status;
+    response
+}
+
+/// Response for clients shed because the origin is saturated.
+fn shed_response() -> Response<ProxyBody> {
+    let mut response = error_response(StatusCode::SERVICE_UNAVAILABLE, "origin saturated");
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+    response
+}
+
+/// Redirect back to the requested URL setting the challenge cookie. Clients
+/// that keep cookies and follow redirects pass on the second request.
+fn challenge_response(uri: &Uri, token: &str) -> Response<ProxyBody> {
+    let mut response = error_response(StatusCode::TEMPORARY_REDIRECT, "challenge");
+    let location = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let cookie = format!(
+        "{}={}; Path=/; Max-Age=3600; HttpOnly; SameSite=Lax",
+        CHALLENGE_COOKIE, token
+    );
+    let headers = response.headers_mut();
+    if let (Ok(location), Ok(cookie)) = (
+        HeaderValue::from_str(location),
+        HeaderValue::from_str(&cookie),
+    ) {
+        headers.insert(header::LOCATION, location);
+        headers.insert(header::SET_COOKIE, cookie);
+    }
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+/// Value of the challenge cookie of a request.
+fn challenge_cookie<B>(req: &Request<B>) -> Option<&str> {
+    req.headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == CHALLENGE_COOKIE).then_some(value)
+        })
+}
+
+/// Build a response from the cache.
+fn cached_response(
+    cached: &CachedResponse,
+    now: u64,
+    head: bool,
+    cache_status: &'static str,
+) -> Response<ProxyBody> {
+    let body = if head {
+        Bytes::new()
+    } else {
+        cached.body.clone()
+    };
+    let mut response = Response::new(full(body));
+    *response.status_mut() = StatusCode::from_u16(cached.status).unwrap_or(StatusCode::OK);
+
+    let headers = response.headers_mut();
+    for (name, value) in &cached.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(
+        header::AGE,
+        HeaderValue::from(now.saturating_sub(cached.stored_at)),
+    );
+    headers.insert("x-cache", HeaderValue::from_static(cache_status));
+    response
+}
+
+/// Why the origin could not answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OriginError {
+    /// The origin connection pool shed the request
+    Shed,
+    /// Connection failure or timeout
+    Unreachable,
+}
+
+impl OriginError {
+    fn response(self) -> Response<ProxyBody> {
+        match self {
+            OriginError::Shed => shed_response(),
+            OriginError::Unreachable => {
+                error_response(StatusCode::BAD_GATEWAY, "origin unreachable")
+            }
+        }
+    }
+}
+
+/// Request that may be answered from the cache.
+#[derive(Clone)]
+struct CacheableRequest {
+    key: String,
+    host: String,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+}
+
+/// Origin response to a cacheable request.
+enum Fetched {
+    /// Stored in the cache
+    Stored(CachedResponse),
+    /// The cached copy is still current (304 Not Modified)
+    Revalidated(CachedResponse),
+    /// Passed through without caching
+    Uncacheable(Response<ProxyBody>),
+}
+
+/// Caching HTTP reverse proxy.
+pub struct HttpProxy {
+    config: HttpProxyConfig,
+    cache: ResponseCache,
+    pools: Arc<OriginPools>,
+    anomalies: Arc<OriginAnomalyDetector>,
+    modes: Arc<BackendModes>,
+    block_responses: Arc<BlockResponses>,
+    client: Client<HttpConnector, Full<Bytes>>,
+    /// Cache keys being revalidated in the background
+    revalidating: Mutex<HashSet<String>>,
+}
+
+impl HttpProxy {
+    /// Create the proxy.
+    pub fn new(
+        config: HttpProxyConfig,
+        pools: Arc<OriginPools>,
+        anomalies: Arc<OriginAnomalyDetector>,
+        modes: Arc<BackendModes>,
+        block_responses: Arc<BlockResponses>,
+    ) -> Self {
+        let cache = ResponseCache::new(config.cache.clone());
+        let client = Client::builder(TokioExecutor::new()).build_http();
+        Self {
+            config,
+            cache,
+            pools,
+            anomalies,
+            modes,
+            block_responses,
+            client,
+            revalidating: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Address the proxy is configured to listen on.
+    pub fn listen_addr(&self) -> SocketAddr {
+        self.config.listen
+    }
+
+    /// Serve connections accepted on `listener` until shutdown.
+    pub async fn serve(
+        self: Arc<Self>,
+        listener: TcpListener,
+        mut shutdown_rx: watch::Receiver<bool>,
+    ) {
+        info!(
+            addr = ?listener.local_addr().ok(),
+            origins = self.config.origins.len(),
+            "HTTP proxy listening"
+        );
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        info!("HTTP proxy shutting down");
+                        return;
+                    }
+                }
+                accepted = listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!("Failed to accept HTTP proxy connection: {}", e);
+                            continue;
+                        }
+                    };
+                    let proxy = Arc::clone(&self);
+                    tokio::spawn(async move {
+                        let service = hyper::service::service_fn(move |req| {
+                            let proxy = Arc::clone(&proxy);
+                            async move { Ok::<_, Infallible>(proxy.handle(req, peer).await) }
+                        });
+                        if let Err(e) = hyper::server::conn::http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service)
+                            .with_upgrades()
+                            .await
+                        {
+                            debug!(peer = %peer, "HTTP proxy connection error: {}", e);
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    /// Replace the body of a refusal with the block page of the backend
+    /// serving `host`, if it has one.
+    fn with_block_page(
+        &self,
+        mut response: Response<ProxyBody>,
+        host: &str,
+        peer: SocketAddr,
+        reason: &str,
+    ) -> Response<ProxyBody> {
+        let status = response.status().as_u16();
+        if let Some(page) = self
+            .block_responses
+            .block_page(host, status, reason, peer.ip())
+        {
+            *response.body_mut() = full(Bytes::from(page));
+            let headers = response.headers_mut();
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/html; charset=utf-8"),
+            );
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        }
+        response
+    }
+
+    /// Proxy a request.
+    async fn handle(
+        self: Arc<Self>,
+        req: Request<Incoming>,
+        peer: SocketAddr,
+    ) -> Response<ProxyBody> {
+        let Some(host) = request_host(&req) else {
+            return error_response(StatusCode::BAD_REQUEST, "missing Host header");
+        };
+        let Some(origin) = self.config.origin_for(&host).cloned() else {
+            return error_response(StatusCode::MISDIRECTED_REQUEST, "unknown host");
+        };
+
+        // Backends being debugged skip mitigation and caching
+        let passthrough = match self.modes.host_mode(&host) {
+            BackendMode::BlockAll => {
+                let response = error_response(StatusCode::SERVICE_UNAVAILABLE, "backend blocked");
+                return self.with_block_page(
+                    response,
+                    &host,
+                    peer,
+                    "Access to this site is blocked",
+                );
+            }
+            BackendMode::Passthrough => true,
+            BackendMode::Protect => false,
+        };
+
+        if !passthrough {
+            self.anomalies.record_inbound(&host);
+            match self
+                .anomalies
+                .admit(&host, peer.ip(), challenge_cookie(&req), Instant::now())
+            {
+                Admission::Allow => {}
+                Admission::RateLimited => {
+                    let mut response =
+                        error_response(StatusCode::TOO_MANY_REQUESTS, "too many requests");
+                    response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+                    return self.with_block_page(response, &host, peer, "Too many requests");
+                }
+                Admission::Challenge => {
+                    let token = self.anomalies.challenge_token(&host, peer.ip());
+                    let response = challenge_response(req.uri(), &token);
+                    return self.with_block_page(response, &host, peer, "Checking your browser");
+                }
+            }
+        }
+
+        if websocket::is_upgrade(&req) {
+            return self.proxy_websocket(req, host, origin, peer).await;
+        }
+
+        let Some(request) = cacheable_request(&req, &host).filter(|_| !passthrough) else {
+            HTTP_CACHE_REQUESTS.with_label_values(&["bypass"]).inc();
+            let (parts, body) = req.into_parts();
+            let body = match Limited::new(body, self.config.max_request_bytes)
+                .collect()
+                .await
+            {
+                Ok(body) => body.to_bytes(),
+                Err(_) => {
+                    return error_response(StatusCode::PAYLOAD_TOO_LARGE, "request body too large");
+                }
+            };
+            return self
+                .forward(
+                    &host,
+                    &origin,
+                    parts.method,
+                    &parts.uri,
+                    &parts.headers,
+                    body,
+                    peer,
+                )
+                .await
+                .unwrap_or_else(OriginError::response);
+        };
+
+        let head = request.method == Method::HEAD;
+        let now = cache::unix_now();
+        let cached = self.cache.get(&request.key, now);
+        match cached.as_ref().map(|cached| cached.freshness(now)) {
+            Some(Freshness::Fresh) => {
+                HTTP_CACHE_REQUESTS.with_label_values(&["hit"]).inc();
+                return cached_response(cached.as_ref().unwrap(), now, head, "HIT");
+            }
+            Some(Freshness::StaleWhileRevalidate) => {
+                let cached = cached.unwrap();
+                HTTP_CACHE_REQUESTS.with_label_values(&["stale"]).inc();
+                self.spawn_revalidation(request, origin, cached.clone(), peer);
+                return cached_response(&cached, now, head, "STALE");
+            }
+            _ => {}
+        }
+
+        match self.fetch(&request, &origin, cached.as_ref(), peer).await {
+            Ok(Fetched::Stored(response)) => {
+                HTTP_CACHE_REQUESTS.with_label_values(&["miss"]).inc();
+                cached_response(&response, now, head, "MISS")
+            }
+            Ok(Fetched::Revalidated(response)) => {
+                HTTP_CACHE_REQUESTS
+                    .with_label_values(&["revalidated"])
+                    .inc();
+                cached_response(&response, now, head, "REVALIDATED")
+            }
+            // Serve the stale copy while the origin fails
+            Ok(Fetched::Uncacheable(response))
+                if cached.is_none() || !response.status().is_server_error() =>
+            {
+                HTTP_CACHE_REQUESTS.with_label_values(&["miss"]).inc();
+                response
+            }
+            Err(e) if cached.is_none() => {
+                HTTP_CACHE_REQUESTS.with_label_values(&["miss"]).inc();
+                e.response()
+            }
+            _ => {
+                HTTP_CACHE_REQUESTS
+                    .with_label_values(&["stale_if_error"])
+                    .inc();
+                cached_response(cached.as_ref().unwrap(), now, head, "STALE")
+            }
+        }
+    }
+
+    /// Revalidate a stale response in the background, once per key.
+    fn spawn_revalidation(
+        self: &Arc<Self>,
+        mut request: CacheableRequest,
+        origin: Uri,
+        cached: CachedResponse,
+        peer: SocketAddr,
+    ) {
+        if !self.revalidating.lock().insert(request.key.clone()) {
+            return;
+        }
+        // Revalidate with GET so HEAD requests refresh the body too
+        request.method = Method::GET;
+
+        let proxy = Arc::clone(self);
+        tokio::spawn(async move {
+            if let Err(e) = proxy.fetch(&request, &origin, Some(&cached), peer).await {
+                debug!(host = %request.host, uri = %request.uri, "Revalidation failed: {:?}", e);
+            }
+            proxy.revalidating.lock().remove(&request.key);
+        });
+    }
+
+    /// Fetch a cacheable request from the origin, revalidating the cached
+    /// copy if there is one, and store the response if it may be cached.
+    async fn fetch(
+        &self,
+        request: &CacheableRequest,
+        origin: &Uri,
+        cached: Option<&CachedResponse>,
+        peer: SocketAddr,
+    ) -> Result<Fetched, OriginError> {
+        let mut headers = request.headers.clone();
+        if let Some(cached) = cached {
+            let validators = [
+                ("etag", header::IF_NONE_MATCH),
+                ("last-modified", header::IF_MODIFIED_SINCE),
+            ];
+            for (stored, condition) in validators {
+                if let Some(value) = cached
+                    .header(stored)
+                    .and_then(|v| HeaderValue::from_str(v).ok())
+                {
+                    headers.insert(condition, value);
+                }
+            }
+        }
+
+        let response = self
+            .forward(
+                &request.host,
+                origin,
+                request.method.clone(),
+                &request.uri,
+                &headers,
+                Bytes::new(),
+                peer,
+            )
+            .await?;
+        let now = cache::unix_now();
+
+        if let Some(cached) = cached.filter(|_| response.status() == StatusCode::NOT_MODIFIED) {
+            let mut cached = cached.clone();
+            cached.stored_at = now;
+            self.cache.insert(&request.key, cached.clone());
+            return Ok(Fetched::Revalidated(cached));
+        }
+
+        let Some((ttl, control)) = self.storable(request, &response) else {
+            return Ok(Fetched::Uncacheable(response));
+        };
+
+        let (parts, body) = response.into_parts();
+        let body = Limited::new(body, self.cache.max_object_bytes() as usize)
+            .collect()
+            .await
+            .map_err(|_| OriginError::Unreachable)?
+            .to_bytes();
+        let stored = CachedResponse {
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .filter_map(|(name, value)| {
+                    Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+            body,
+            stored_at: now,
+            ttl,
+            stale_while_revalidate: control.stale_while_revalidate.unwrap_or(0),
+            stale_if_error: control
+                .stale_if_error
+                .unwrap_or(0)
+                .max(self.config.stale_if_error),
+        };
+        self.cache.insert(&request.key, stored.clone());
+        Ok(Fetched::Stored(stored))
+    }
+
+    /// Freshness lifetime and cache directives of a response that may be
+    /// cached: a complete (200) static asset the origin allows shared caches
+    /// to store, small enough for the cache.
+    fn storable<B>(
+        &self,
+        request: &CacheableRequest,
+        response: &Response<B>,
+    ) -> Option<(u64, CacheControl)> {
+        if request.method != Method::GET || response.status() != StatusCode::OK {
+            return None;
+        }
+
+        let headers = response.headers();
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+        };
+        if headers.contains_key(header::SET_COOKIE) {
+            return None;
+        }
+        // The key only varies on Accept-Encoding
+        if header(header::VARY).is_some_and(|vary| {
+            vary.split(',')
+                .any(|name| !name.trim().eq_ignore_ascii_case("accept-encoding"))
+        }) {
+            return None;
+        }
+        let length: u64 = header(header::CONTENT_LENGTH)?.parse().ok()?;
+        if length > self.cache.max_object_bytes() {
+            return None;
+        }
+        if !cache::is_static_asset(request.uri.path(), header(header::CONTENT_TYPE)) {
+            return None;
+        }
+
+        let control = header(header::CACHE_CONTROL)
+            .map(CacheControl::parse)
+            .unwrap_or_default();
+        if !control.storable() {
+            return None;
+        }
+        let ttl = control.ttl().unwrap_or(self.config.default_ttl);
+        (ttl > 0).then_some((ttl, control))
+    }
+
+    /// Send a request to the origin, holding a slot of the origin connection
+    /// pool until the response body is consumed.
+    #[allow(clippy::too_many_arguments)]
+    async fn forward(
+        &self,
+        host: &str,
+        origin: &Uri,
+        method: Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        body: Bytes,
+        peer: SocketAddr,
+    ) -> Result<Response<ProxyBody>, OriginError> {
+        let headers = end_to_end_headers(headers);
+        let (response, permit) = self
+            .send(host, origin, method, uri, headers, body, peer)
+            .await?;
+
+        let (mut parts, body) = response.into_parts();
+        parts.headers = end_to_end_headers(&parts.headers);
+        let body = body
+            .map_frame(move |frame| {
+                let _ = &permit;
+                frame
+            })
+            .boxed();
+        Ok(Response::from_parts(parts, body))
+    }
+
+    /// Send a request with end-to-end `headers` to the origin through its
+    /// connection pool, returning the response and the pool slot it holds.
+    #[allow(clippy::too_many_arguments)]
+    async fn send(
+        &self,
+        host: &str,
+        origin: &Uri,
+        method: Method,
+        uri: &Uri,
+        mut headers: HeaderMap,
+        body: Bytes,
+        peer: SocketAddr,
+    ) -> Result<(Response<Incoming>, PoolPermit), OriginError> {
+        let authority = origin.authority().map(|a| a.as_str()).unwrap_or_default();
+        let permit = self
+            .pools
+            .acquire(host, authority)
+            .await
+            .map_err(|_| OriginError::Shed)?;
+
+        let path_and_query = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        let target: Uri = format!(
+            "http://{}{}{}",
+            authority,
+            origin.path().trim_end_matches('/'),
+            path_and_query
+        )
+        .parse()
+        .map_err(|_| OriginError::Unreachable)?;
+
+        let forwarded_for = match headers.get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+            Some(existing) => format!("{}, {}", existing, peer.ip()),
+            None => peer.ip().to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+            headers.insert("x-forwarded-for", value);
+        }
+
+        let mut request = Request::new(Full::new(body));
+        *request.method_mut() = method;
+        *request.uri_mut() = target;
+        *request.headers_mut() = headers;
+
+        let response =
+            tokio::time::timeout(self.config.origin_timeout, self.client.request(request))
+                .await
+                .map_err(|_| OriginError::Unreachable)
+                .and_then(|result| {
+                    result.map_err(|e| {
+                        debug!(origin = %authority, "Origin request failed: {}", e);
+                        OriginError::Unreachable
+                    })
+                });
+
+        let outcome = match &response {
+            Ok(response) if response.status().is_server_error() => OriginOutcome::ServerError,
+            Ok(_) => OriginOutcome::Success,
+            Err(_) => OriginOutcome::ConnectFailure,
+        };
+        self.anomalies.record_origin(host, outcome);
+
+        Ok((response?, permit))
+    }
+
+    /// Proxy a WebSocket upgrade: forward the handshake, then relay the
+    /// upgraded connections with the WebSocket limits applied.
+    async fn proxy_websocket(
+        &self,
+        mut req: Request<Incoming>,
+        host: String,
+        origin: Uri,
+        peer: SocketAddr,
+    ) -> Response<ProxyBody> {
+        let client_upgrade = hyper::upgrade::on(&mut req);
+        let mut headers = end_to_end_headers(req.headers());
+        headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+
+        let (mut response, permit) = match self
+            .send(
+                &host,
+                &origin,
+                Method::GET,
+                req.uri(),
+                headers,
+                Bytes::new(),
+                peer,
+            )
+            .await
+        {
+            Ok(sent) => sent,
+            Err(e) => return e.response(),
+        };
+        if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+            // The origin refused the upgrade; pass its answer on
+            let (mut parts, body) = response.into_parts();
+            parts.headers = end_to_end_headers(&parts.headers);
+            let body = body
+                .map_frame(move |frame| {
+                    let _ = &permit;
+                    frame
+                })
+                .boxed();
+            return Response::from_parts(parts, body);
+        }
+
+        let origin_upgrade = hyper::upgrade::on(&mut response);
+        let limits = self.config.websocket.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            match tokio::try_join!(client_upgrade, origin_upgrade) {
+                Ok((client, origin)) => {
+                    let reason = websocket::relay(
+                        TokioIo::new(client),
+                        TokioIo::new(origin),
+                        &limits,
+                        &host,
+                    )
+                    .await;
+                    debug!(host = %host, peer = %peer, reason = reason.as_str(), "WebSocket closed");
+                }
+                Err(e) => debug!(host = %host, peer = %peer, "WebSocket upgrade failed: {}", e),
+            }
+        });
+
+        let mut switching = Response::new(full(Bytes::new()));
+        *switching.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+        let headers = switching.headers_mut();
+        *headers = end_to_end_headers(response.headers());
+        headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        switching
+    }
+}
+
+/// Cache key and parts of a GET or HEAD request without credentials or
+/// `no-store`.
+fn cacheable_request<B>(req: &Request<B>, host: &str) -> Option<CacheableRequest> {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return None;
+    }
+    let headers = req.headers();
+    if headers.contains_key(header::AUTHORIZATION) {
+        return None;
+    }
+    let no_store = headers
+        .get(header::CACHE_CONTROL)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| CacheControl::parse(v).no_store);
+    if no_store {
+        return None;
+    }
+
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let accept_encoding = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok());
+    Some(CacheableRequest {
+        key: cache::cache_key(host, path_and_query, accept_encoding),
+        host: host.to_string(),
+        method: req.method().clone(),
+        uri: req.uri().clone(),
+        headers: headers.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_response::BackendBlockResponses;
+    use crate::routing::PoolConfig;
+    use anomaly::{AnomalyConfig, Mitigation};
+    use pistonprotection_common::backend_mode::BackendModeOverride;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn config(origin: SocketAddr) -> HttpProxyConfig {
+        HttpProxyConfig {
+            listen: SocketAddr::from(([127, 0, 0, 1], 0)),
+            origins: parse_origins(&format!("*=http://{}", origin)),
+            default_ttl: 0,
+            stale_if_error: 3600,
+            cache: CacheLimits {
+                memory_bytes: MIB,
+                disk_dir: None,
+                disk_bytes: 0,
+                max_object_bytes: MIB,
+            },
+            max_request_bytes: MIB as usize,
+            origin_timeout: Duration::from_secs(5),
+            websocket: WebSocketLimits::default(),
+        }
+    }
+
+    async fn start_proxy(
+        config: HttpProxyConfig,
+    ) -> (Arc<HttpProxy>, SocketAddr, watch::Sender<bool>) {
+        let pools = Arc::new(OriginPools::new(PoolConfig::default()));
+        let anomalies = Arc::new(OriginAnomalyDetector::new(AnomalyConfig {
+            window: Duration::from_secs(1),
+            min_origin_requests: 1,
+            ..Default::default()
+        }));
+        let modes = Arc::new(BackendModes::new());
+        let block_responses = Arc::new(BlockResponses::new());
+        let proxy = Arc::new(HttpProxy::new(
+            config,
+            pools,
+            anomalies,
+            modes,
+            block_responses,
+        ));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        tokio::spawn(Arc::clone(&proxy).serve(listener, shutdown_rx));
+        (proxy, addr, shutdown_tx)
+    }
+
+    /// Send a GET request and return the response head and body.
+    async fn get(proxy: SocketAddr, path: &str) -> (String, String) {
+        let mut stream = tokio::net::TcpStream::connect(proxy).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n",
+            path
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.to_ascii_lowercase(), body.to_string())
+    }
+
+    #[test]
+    fn test_parse_origins() {
+        let origins =
+            parse_origins("Example.com=http://10.0.0.2:8080, *=http://10.0.0.3,bad=https://x,junk");
+        assert_eq!(origins.len(), 2);
+        assert_eq!(origins["example.com"].authority().unwrap(), "10.0.0.2:8080");
+
+        let config = HttpProxyConfig {
+            origins,
+            ..config(SocketAddr::from(([127, 0, 0, 1], 1)))
+        };
+        assert_eq!(
+            config.origin_for("EXAMPLE.com").unwrap().host(),
+            Some("10.0.0.2")
+        );
+        assert_eq!(
+            config.origin_for("other.com").unwrap().host(),
+            Some("10.0.0.3")
+        );
+    }
+
+    #[test]
+    fn test_request_host() {
+        let host = |value: &str| {
+            let req = Request::builder()
+                .header(header::HOST, value)
+                .body(())
+                .unwrap();
+            request_host(&req).unwrap()
+        };
+        assert_eq!(host("Example.com:8080"), "example.com");
+        assert_eq!(host("example.com"), "example.com");
+        assert_eq!(host("[2001:db8::1]:443"), "[2001:db8::1]");
+    }
+
+    #[test]
+    fn test_end_to_end_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONNECTION,
+            HeaderValue::from_static("close, x-secret"),
+        );
+        headers.insert("x-secret", HeaderValue::from_static("1"));
+        headers.insert(
+            header::TRANSFER_ENCODING,
+            HeaderValue::from_static("chunked"),
+        );
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/css"));
+
+        let headers = end_to_end_headers(&headers);
+        assert_eq!(headers.len(), 1);
+        assert!(headers.contains_key(header::CONTENT_TYPE));
+    }
+
+    #[tokio::test]
+    async fn test_caches_static_assets() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let origin = {
+            let hits = Arc::clone(&hits);
+            let counter = move || {
+                hits.fetch_add(1, Ordering::SeqCst);
+            };
+            let css = counter.clone();
+            axum::Router::new()
+                .route(
+                    "/app.css",
+                    axum::routing::get(move || async move {
+                        css();
+                        (
+                            [
+                                (header::CONTENT_TYPE, "text/css"),
+                                (header::CACHE_CONTROL, "public, max-age=60"),
+                            ],
+                            "body { color: red }",
+                        )
+                    }),
+                )
+                .route(
+                    "/api",
+                    axum::routing::get(move || async move {
+                        counter();
+                        axum::Json(serde_json::json!({ "ok": true }))
+                    }),
+                )
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let origin_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, origin).await });
+
+        let (_proxy, addr, _shutdown) = start_proxy(config(origin_addr)).await;
+
+        let (head, body) = get(addr, "/app.css").await;
+        assert!(head.contains("x-cache: miss"), "{}", head);
+        assert_eq!(body, "body { color: red }");
+
+        let (head, body) = get(addr, "/app.css").await;
+        assert!(head.contains("x-cache: hit"), "{}", head);
+        assert_eq!(body, "body { color: red }");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // Dynamic responses are not cached
+        get(addr, "/api").await;
+        let (head, _) = get(addr, "/api").await;
+        assert!(!head.contains("x-cache"), "{}", head);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_serves_stale_during_outage() {
+        // Nothing listens on the origin port
+        let origin_addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (proxy, addr, _shutdown) = start_proxy(config(origin_addr)).await;
+
+        let (head, _) = get(addr, "/logo.png").await;
+        assert!(head.starts_with("http/1.1 502"), "{}", head);
+
+        proxy.cache.insert(
+            &cache::cache_key("example.com", "/logo.png", None),
+            CachedResponse {
+                status: 200,
+                headers: vec![("content-type".to_string(), "image/png".to_string())],
+                body: Bytes::from_static(b"png"),
+                stored_at: cache::unix_now() - 120,
+                ttl: 60,
+                stale_while_revalidate: 0,
+                stale_if_error: 3600,
+            },
+        );
+        let (head, body) = get(addr, "/logo.png").await;
+        assert!(head.starts_with("http/1.1 200"), "{}", head);
+        assert!(head.contains("x-cache: stale"), "{}", head);
+        assert_eq!(body, "png");
+    }
+
+    #[tokio::test]
+    async fn test_relays_websocket_upgrade() {
+        // Origin accepting the upgrade and echoing everything afterwards
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let origin_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                head.push(stream.read_u8().await.unwrap());
+            }
+            let head = String::from_utf8(head).unwrap().to_ascii_lowercase();
+            assert!(head.contains("upgrade: websocket"), "{}", head);
+            stream
+                .write_all(
+                    b"HTTP/1.1 101 Switching Protocols\r\n\
+                      Connection: Upgrade\r\nUpgrade: websocket\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            let (mut rx, mut tx) = stream.split();
+            let _ = tokio::io::copy(&mut rx, &mut tx).await;
+        });
+
+        let mut config = config(origin_addr);
+        config.websocket.max_frame_bytes = 16;
+        let (_proxy, addr, _shutdown) = start_proxy(config).await;
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /ws HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade\r\n\
+                  Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        assert!(head.starts_with("HTTP/1.1 101"), "{}", head);
+
+        // Masked "hi" text frame comes back from the echoing origin
+        let ping = [0x81, 0x82, 1, 2, 3, 4, b'h' ^ 1, b'i' ^ 2];
+        stream.write_all(&ping).await.unwrap();
+        let mut echoed = [0u8; 8];
+        stream.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(echoed, ping);
+
+        // A frame above the limit closes the connection with 1009
+        stream
+            .write_all(&[0x82, 0x80 | 100, 1, 2, 3, 4])
+            .await
+            .unwrap();
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, [0x88, 2, 0x03, 0xf1]);
+    }
+
+    #[tokio::test]
+    async fn test_challenge_under_mitigation() {
+        let origin = axum::Router::new().route("/", axum::routing::get(|| async { "origin" }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let origin_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, origin).await });
+
+        let (proxy, addr, _shutdown) = start_proxy(config(origin_addr)).await;
+        let (head, body) = get(addr, "/").await;
+        assert!(head.starts_with("http/1.1 200"), "{}", head);
+        assert_eq!(body, "origin");
+
+        // A request surge the origin fails to answer
+        let now = Instant::now();
+        proxy.anomalies.evaluate(now);
+        for _ in 0..100 {
+            proxy.anomalies.record_inbound("example.com");
+            proxy
+                .anomalies
+                .record_origin("example.com", OriginOutcome::ConnectFailure);
+        }
+        proxy.anomalies.evaluate(now);
+        assert_eq!(
+            proxy.anomalies.mitigation("example.com"),
+            Some(Mitigation::Challenge)
+        );
+
+        let (head, _) = get(addr, "/").await;
+        assert!(head.starts_with("http/1.1 307"), "{}", head);
+        let cookie = head
+            .lines()
+            .find_map(|line| line.strip_prefix("set-cookie: "))
+            .and_then(|cookie| cookie.split(';').next())
+            .unwrap()
+            .to_string();
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET / HTTP/1.1\r\nHost: example.com\r\nCookie: {}\r\nConnection: close\r\n\r\n",
+            cookie
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.ends_with("origin"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_backend_modes() {
+        let origin = axum::Router::new().route("/", axum::routing::get(|| async { "origin" }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let origin_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, origin).await });
+
+        let (proxy, addr, _shutdown) = start_proxy(config(origin_addr)).await;
+        let set_mode = |mode| {
+            let overrides = vec![BackendModeOverride {
+                backend_id: "backend-1".to_string(),
+                mode,
+                hosts: vec!["Example.com".to_string()],
+                reason: String::new(),
+                changed_by: "user-1".to_string(),
+                changed_at: 0,
+                revert_at: i64::MAX,
+            }];
+            proxy
+                .modes
+                .apply(overrides, 0, |_| Vec::new(), |_| Ok(()))
+                .unwrap();
+        };
+
+        let (head, _) = get(addr, "/").await;
+        assert!(head.starts_with("http/1.1 200"), "{}", head);
+
+        // A request surge the origin fails to answer
+        let now = Instant::now();
+        proxy.anomalies.evaluate(now);
+        for _ in 0..100 {
+            proxy.anomalies.record_inbound("example.com");
+            proxy
+                .anomalies
+                .record_origin("example.com", OriginOutcome::ConnectFailure);
+        }
+        proxy.anomalies.evaluate(now);
+        assert!(proxy.anomalies.mitigation("example.com").is_some());
+
+        set_mode(BackendMode::BlockAll);
+        let (head, _) = get(addr, "/").await;
+        assert!(head.starts_with("http/1.1 503"), "{}", head);
+
+        // Passthrough skips the challenge of the mitigation
+        set_mode(BackendMode::Passthrough);
+        let (head, body) = get(addr, "/").await;
+        assert!(head.starts_with("http/1.1 200"), "{}", head);
+        assert_eq!(body, "origin");
+    }
+
+    #[tokio::test]
+    async fn test_block_page() {
+        let (proxy, addr, _shutdown) = start_proxy(config("127.0.0.1:9".parse().unwrap())).await;
+        proxy.block_responses.replace(vec![BackendBlockResponses {
+            backend_id: "backend-1".to_string(),
+            hosts: vec!["example.com".to_string()],
+            http_block_page: Some("<p>{{status}}: {{reason}} ({{host}})</p>".to_string()),
+            ..Default::default()
+        }]);
+        proxy
+            .modes
+            .apply(
+                vec![BackendModeOverride {
+                    backend_id: "backend-1".to_string(),
+                    mode: BackendMode::BlockAll,
+                    hosts: vec!["example.com".to_string()],
+                    reason: String::new(),
+                    changed_by: "user-1".to_string(),
+                    changed_at: 0,
+                    revert_at: i64::MAX,
+                }],
+                0,
+                |_| Vec::new(),
+                |_| Ok(()),
+            )
+            .unwrap();
+
+        let (head, body) = get(addr, "/").await;
+        assert!(head.starts_with("http/1.1 503"), "{}", head);
+        assert!(head.contains("content-type: text/html"), "{}", head);
+        assert_eq!(
+            body,
+            "<p>503: Access to this site is blocked (example.com)</p>"
+        );
+    }
+}
@@ -0,0 +1,462 @@
+//! WebSocket relaying for the HTTP reverse proxy.
+//!
+//! After the upgrade handshake the proxy relays the connection frame by
+//! frame in the client to origin direction, so every client frame is
+//! checked against the per-connection limits: frame size, frames and
+//! messages per second. Origin frames are copied as they are. Connections
+//! without frames in either direction for the idle timeout are closed. On a
+//! violation the client gets a close frame with the matching status code
+//! (1009 for oversized frames, 1008 for rate limits, 1001 for idle
+//! timeouts) and both sides are disconnected.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use hyper::Request;
+use hyper::header::{self, HeaderMap};
+use pistonprotection_common::metrics::{WEBSOCKET_ACTIVE_CONNECTIONS, WEBSOCKET_CLOSED};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Opcode of close frames
+const OPCODE_CLOSE: u8 = 0x8;
+
+/// Per-connection WebSocket limits.
+#[derive(Debug, Clone)]
+pub struct WebSocketLimits {
+    /// Largest client frame payload
+    pub max_frame_bytes: u64,
+    /// Client frames per second, control frames included (0 = unlimited)
+    pub max_frames_per_second: u32,
+    /// Complete client messages per second (0 = unlimited)
+    pub max_messages_per_second: u32,
+    /// Time without frames in either direction before closing
+    pub idle_timeout: Duration,
+}
+
+impl Default for WebSocketLimits {
+    fn default() -> Self {
+        Self {
+            max_frame_bytes: 1024 * 1024,
+            max_frames_per_second: 200,
+            max_messages_per_second: 100,
+            idle_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+impl WebSocketLimits {
+    /// Load the limits from `PISTON_WS_*` environment variables.
+    pub fn from_env() -> Self {
+        let mut limits = Self::default();
+
+        if let Some(max) = env_u64("PISTON_WS_MAX_FRAME_BYTES") {
+            limits.max_frame_bytes = max;
+        }
+        if let Some(max) = env_u64("PISTON_WS_MAX_FRAMES_PER_SECOND") {
+            limits.max_frames_per_second = max.min(u32::MAX as u64) as u32;
+        }
+        if let Some(max) = env_u64("PISTON_WS_MAX_MESSAGES_PER_SECOND") {
+            limits.max_messages_per_second = max.min(u32::MAX as u64) as u32;
+        }
+        if let Some(secs) = env_u64("PISTON_WS_IDLE_TIMEOUT_SECS") {
+            limits.idle_timeout = Duration::from_secs(secs.max(1));
+        }
+
+        limits
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Whether a request asks to upgrade to WebSocket.
+pub fn is_upgrade<B>(req: &Request<B>) -> bool {
+    header_has_token(req.headers(), header::CONNECTION, "upgrade")
+        && header_has_token(req.headers(), header::UPGRADE, "websocket")
+}
+
+fn header_has_token(headers: &HeaderMap, name: header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case(token))
+}
+
+/// Why a relayed connection ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    ClientClosed,
+    OriginClosed,
+    FrameTooLarge,
+    RateLimited,
+    IdleTimeout,
+    Error,
+}
+
+impl CloseReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CloseReason::ClientClosed => "client_closed",
+            CloseReason::OriginClosed => "origin_closed",
+            CloseReason::FrameTooLarge => "frame_too_large",
+            CloseReason::RateLimited => "rate_limited",
+            CloseReason::IdleTimeout => "idle_timeout",
+            CloseReason::Error => "error",
+        }
+    }
+
+    /// Close status code sent to the client when the proxy ends the
+    /// connection.
+    fn close_code(&self) -> Option<u16> {
+        match self {
+            CloseReason::FrameTooLarge => Some(1009),
+            CloseReason::RateLimited => Some(1008),
+            CloseReason::IdleTimeout => Some(1001),
+            _ => None,
+        }
+    }
+}
+
+/// Header of a WebSocket frame.
+#[derive(Debug)]
+struct FrameHeader {
+    fin: bool,
+    opcode: u8,
+    payload_len: u64,
+    /// Header bytes as received (including the masking key)
+    raw: Vec<u8>,
+}
+
+async fn read_frame_header<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<FrameHeader> {
+    let mut raw = vec![0; 2];
+    reader.read_exact(&mut raw).await?;
+    let fin = raw[0] & 0x80 != 0;
+    let opcode = raw[0] & 0x0f;
+    let masked = raw[1] & 0x80 != 0;
+
+    let payload_len = match raw[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len).await?;
+            raw.extend_from_slice(&len);
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len).await?;
+            raw.extend_from_slice(&len);
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if masked {
+        let mut mask = [0; 4];
+        reader.read_exact(&mut mask).await?;
+        raw.extend_from_slice(&mask);
+    }
+
+    Ok(FrameHeader {
+        fin,
+        opcode,
+        payload_len,
+        raw,
+    })
+}
+
+/// Frames counted in the current one-second window.
+struct RateWindow {
+    started: Instant,
+    count: u32,
+}
+
+impl RateWindow {
+    fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            count: 0,
+        }
+    }
+
+    /// Count an event, returning whether it is within `limit` per second.
+    fn hit(&mut self, now: Instant, limit: u32) -> bool {
+        if limit == 0 {
+            return true;
+        }
+        if now.duration_since(self.started) >= Duration::from_secs(1) {
+            self.started = now;
+            self.count = 0;
+        }
+        self.count += 1;
+        self.count <= limit
+    }
+}
+
+/// Time of the last frame, in milliseconds since the relay started.
+struct Activity {
+    started: Instant,
+    last: AtomicU64,
+}
+
+impl Activity {
+    fn touch(&self) {
+        self.last
+            .store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.started + Duration::from_millis(self.last.load(Ordering::Relaxed))
+    }
+}
+
+/// Relay client frames to the origin, enforcing the limits.
+async fn client_to_origin<R, W>(
+    client: &mut R,
+    origin: &mut W,
+    limits: &WebSocketLimits,
+    activity: &Activity,
+) -> CloseReason
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut frames = RateWindow::new(Instant::now());
+    let mut messages = RateWindow::new(Instant::now());
+
+    loop {
+        let frame = match read_frame_header(client).await {
+            Ok(frame) => frame,
+            Err(_) => return CloseReason::ClientClosed,
+        };
+        activity.touch();
+
+        if frame.payload_len > limits.max_frame_bytes {
+            return CloseReason::FrameTooLarge;
+        }
+        let now = Instant::now();
+        if !frames.hit(now, limits.max_frames_per_second) {
+            return CloseReason::RateLimited;
+        }
+        // Data frames (continuation, text, binary) with FIN end a message
+        if frame.fin
+            && frame.opcode < OPCODE_CLOSE
+            && !messages.hit(now, limits.max_messages_per_second)
+        {
+            return CloseReason::RateLimited;
+        }
+
+        if origin.write_all(&frame.raw).await.is_err() {
+            return CloseReason::OriginClosed;
+        }
+        let mut payload = client.take(frame.payload_len);
+        match tokio::io::copy(&mut payload, origin).await {
+            Ok(copied) if copied == frame.payload_len => {}
+            Ok(_) => return CloseReason::ClientClosed,
+            Err(_) => return CloseReason::Error,
+        }
+        activity.touch();
+    }
+}
+
+/// Copy origin frames to the client.
+async fn origin_to_client<R, W>(origin: &mut R, client: &mut W, activity: &Activity) -> CloseReason
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0; 16 * 1024];
+    loop {
+        let n = match origin.read(&mut buf).await {
+            Ok(0) | Err(_) => return CloseReason::OriginClosed,
+            Ok(n) => n,
+        };
+        activity.touch();
+        if client.write_all(&buf[..n]).await.is_err() {
+            return CloseReason::ClientClosed;
+        }
+    }
+}
+
+async fn idle_watchdog(activity: &Activity, idle_timeout: Duration) -> CloseReason {
+    loop {
+        let deadline = activity.last() + idle_timeout;
+        if Instant::now() >= deadline {
+            return CloseReason::IdleTimeout;
+        }
+        tokio::time::sleep_until(deadline.into()).await;
+    }
+}
+
+/// Relay an upgraded WebSocket connection between client and origin until
+/// either side closes or a limit is hit.
+pub async fn relay<C, O>(
+    client: C,
+    origin: O,
+    limits: &WebSocketLimits,
+    backend_id: &str,
+) -> CloseReason
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    O: AsyncRead + AsyncWrite + Unpin,
+{
+    let gauge = WEBSOCKET_ACTIVE_CONNECTIONS.with_label_values(&[backend_id]);
+    gauge.inc();
+
+    let (mut client_rx, mut client_tx) = tokio::io::split(client);
+    let (mut origin_rx, mut origin_tx) = tokio::io::split(origin);
+    let activity = Activity {
+        started: Instant::now(),
+        last: AtomicU64::new(0),
+    };
+
+    let reason = tokio::select! {
+        reason = client_to_origin(&mut client_rx, &mut origin_tx, limits, &activity) => reason,
+        reason = origin_to_client(&mut origin_rx, &mut client_tx, &activity) => reason,
+        reason = idle_watchdog(&activity, limits.idle_timeout) => reason,
+    };
+
+    if let Some(code) = reason.close_code() {
+        let [hi, lo] = code.to_be_bytes();
+        let _ = client_tx.write_all(&[0x80 | OPCODE_CLOSE, 2, hi, lo]).await;
+    }
+    let _ = client_tx.shutdown().await;
+    let _ = origin_tx.shutdown().await;
+
+    gauge.dec();
+    WEBSOCKET_CLOSED
+        .with_label_values(&[backend_id, reason.as_str()])
+        .inc();
+    reason
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{DuplexStream, duplex};
+
+    /// Masked client frame.
+    fn frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+        match payload.len() {
+            len if len < 126 => frame.push(0x80 | len as u8),
+            len => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+        }
+        let mask = [1, 2, 3, 4];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    fn limits() -> WebSocketLimits {
+        WebSocketLimits {
+            max_frame_bytes: 256,
+            max_frames_per_second: 10,
+            max_messages_per_second: 3,
+            idle_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Start a relay; returns the client and origin ends.
+    fn start(
+        limits: WebSocketLimits,
+    ) -> (
+        DuplexStream,
+        DuplexStream,
+        tokio::task::JoinHandle<CloseReason>,
+    ) {
+        let (client, client_proxy) = duplex(64 * 1024);
+        let (origin_proxy, origin) = duplex(64 * 1024);
+        let handle =
+            tokio::spawn(
+                async move { relay(client_proxy, origin_proxy, &limits, "backend-1").await },
+            );
+        (client, origin, handle)
+    }
+
+    async fn read_close_code(client: &mut DuplexStream) -> u16 {
+        let mut close = [0; 4];
+        client.read_exact(&mut close).await.unwrap();
+        assert_eq!(close[0], 0x88);
+        u16::from_be_bytes([close[2], close[3]])
+    }
+
+    #[tokio::test]
+    async fn test_relays_frames_both_ways() {
+        let (mut client, mut origin, handle) = start(limits());
+
+        let sent = frame(true, 0x1, b"hello");
+        client.write_all(&sent).await.unwrap();
+        let mut received = vec![0; sent.len()];
+        origin.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, sent);
+
+        // Origin frames are unmasked and copied as they are
+        origin.write_all(&[0x81, 2, b'h', b'i']).await.unwrap();
+        let mut received = [0; 4];
+        client.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received[2..], b"hi");
+
+        drop(client);
+        assert_eq!(handle.await.unwrap(), CloseReason::ClientClosed);
+    }
+
+    #[tokio::test]
+    async fn test_frame_too_large() {
+        let (mut client, _origin, handle) = start(limits());
+
+        client
+            .write_all(&frame(true, 0x2, &[0; 300]))
+            .await
+            .unwrap();
+        assert_eq!(read_close_code(&mut client).await, 1009);
+        assert_eq!(handle.await.unwrap(), CloseReason::FrameTooLarge);
+    }
+
+    #[tokio::test]
+    async fn test_message_rate_limit() {
+        let (mut client, _origin, handle) = start(limits());
+
+        // Fragments only count as one message once the final frame arrives
+        for _ in 0..4 {
+            client.write_all(&frame(false, 0x1, b"part")).await.unwrap();
+        }
+        for _ in 0..4 {
+            client.write_all(&frame(true, 0x1, b"msg")).await.unwrap();
+        }
+        assert_eq!(read_close_code(&mut client).await, 1008);
+        assert_eq!(handle.await.unwrap(), CloseReason::RateLimited);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let limits = WebSocketLimits {
+            idle_timeout: Duration::from_millis(50),
+            ..limits()
+        };
+        let (mut client, _origin, handle) = start(limits);
+
+        assert_eq!(read_close_code(&mut client).await, 1001);
+        assert_eq!(handle.await.unwrap(), CloseReason::IdleTimeout);
+    }
+
+    #[test]
+    fn test_is_upgrade() {
+        let req = Request::builder()
+            .header(header::CONNECTION, "keep-alive, Upgrade")
+            .header(header::UPGRADE, "WebSocket")
+            .body(())
+            .unwrap();
+        assert!(is_upgrade(&req));
+
+        let req = Request::builder()
+            .header(header::UPGRADE, "websocket")
+            .body(())
+            .unwrap();
+        assert!(!is_upgrade(&req));
+    }
+}
@@ -0,0 +1,292 @@
+//! Rate Limit Profiles
+//!
+//! Applies the rate limit profiles published by the gateway (see
+//! `pistonprotection_common::rate_profile`). The active profile of each
+//! backend is resolved here from its schedule and manual trigger, so
+//! profiles switch on time even while the gateway is unreachable. Its
+//! limits replace the per-source limits of the backend's namespace in the
+//! xdp_filter tenant config map, and the active profile is exported in
+//! the `rate_profile_active` metric.
+
+use crate::ebpf::tenants::ProfileLimits;
+use parking_lot::RwLock;
+use pistonprotection_common::error::Result;
+use pistonprotection_common::metrics::RATE_PROFILE_ACTIVE;
+use pistonprotection_common::rate_profile::{
+    BackendRateProfiles, CONFIGURED_PROFILE, ProfileSource,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Profile in effect for a backend on this worker
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActiveProfile {
+    pub backend_id: String,
+    /// `configured` when the limits of the protection settings apply
+    pub profile: String,
+    pub source: ProfileSource,
+    /// Limits written to the XDP maps, unset for the protection settings
+    pub per_ip_pps: Option<u64>,
+    pub per_ip_burst: Option<u64>,
+    /// End of the manual trigger (Unix seconds)
+    pub manual_until: Option<i64>,
+}
+
+/// Active profile of a backend changed on this worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileChange {
+    pub backend_id: String,
+    pub from: String,
+    pub to: String,
+    pub source: ProfileSource,
+}
+
+#[derive(Default)]
+struct Applied {
+    /// Profiles as last published
+    published: Vec<BackendRateProfiles>,
+    /// Active profiles keyed by backend ID
+    active: HashMap<String, ActiveProfile>,
+}
+
+/// Rate limit profiles applied to the XDP maps.
+#[derive(Default)]
+pub struct RateProfiles {
+    applied: RwLock<Applied>,
+}
+
+impl RateProfiles {
+    /// Create a tracker without profiles: every backend uses its
+    /// protection settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve the active profiles at `now` and apply them.
+    ///
+    /// The limits of the active profiles are handed to `write_xdp`; if it
+    /// fails the previous profiles stay in place and the error is returned.
+    pub fn apply<W>(
+        &self,
+        published: Vec<BackendRateProfiles>,
+        now: i64,
+        write_xdp: W,
+    ) -> Result<Vec<ProfileChange>>
+    where
+        W: FnOnce(&HashMap<String, ProfileLimits>) -> Result<()>,
+    {
+        let mut active = HashMap::new();
+        let mut limits = HashMap::new();
+        for profiles in &published {
+            let current = match profiles.active(now) {
+                Some((profile, source)) => {
+                    limits.insert(
+                        profiles.backend_id.clone(),
+                        ProfileLimits {
+                            per_ip_pps: profile.per_ip_pps,
+                            per_ip_burst: profile.per_ip_burst,
+                        },
+                    );
+                    ActiveProfile {
+                        backend_id: profiles.backend_id.clone(),
+                        profile: profile.name.clone(),
+                        source,
+                        per_ip_pps: Some(profile.per_ip_pps),
+                        per_ip_burst: Some(profile.per_ip_burst),
+                        manual_until: (source == ProfileSource::Manual)
+                            .then(|| profiles.manual.as_ref().map(|manual| manual.until))
+                            .flatten(),
+                    }
+                }
+                None => ActiveProfile {
+                    backend_id: profiles.backend_id.clone(),
+                    profile: CONFIGURED_PROFILE.to_string(),
+                    source: ProfileSource::Default,
+                    per_ip_pps: None,
+                    per_ip_burst: None,
+                    manual_until: None,
+                },
+            };
+            active.insert(profiles.backend_id.clone(), current);
+        }
+
+        let mut applied = self.applied.write();
+        write_xdp(&limits)?;
+
+        let profile_of = |profiles: &HashMap<String, ActiveProfile>, backend_id: &str| {
+            profiles
+                .get(backend_id)
+                .map(|active| active.profile.clone())
+                .unwrap_or_else(|| CONFIGURED_PROFILE.to_string())
+        };
+        let mut changes = Vec::new();
+        for backend_id in applied.active.keys() {
+            if !active.contains_key(backend_id) {
+                let from = profile_of(&applied.active, backend_id);
+                if from != CONFIGURED_PROFILE {
+                    changes.push(ProfileChange {
+                        backend_id: backend_id.clone(),
+                        from,
+                        to: CONFIGURED_PROFILE.to_string(),
+                        source: ProfileSource::Default,
+                    });
+                }
+            }
+        }
+        for (backend_id, current) in &active {
+            let from = profile_of(&applied.active, backend_id);
+            if from != current.profile {
+                changes.push(ProfileChange {
+                    backend_id: backend_id.clone(),
+                    from,
+                    to: current.profile.clone(),
+                    source: current.source,
+                });
+            }
+        }
+
+        RATE_PROFILE_ACTIVE.reset();
+        for current in active.values() {
+            RATE_PROFILE_ACTIVE
+                .with_label_values(&[
+                    current.backend_id.as_str(),
+                    current.profile.as_str(),
+                    current.source.as_str(),
+                ])
+                .set(1.0);
+        }
+
+        *applied = Applied { published, active };
+        Ok(changes)
+    }
+
+    /// Profiles as last published, to reapply while the gateway cache is
+    /// unreachable.
+    pub fn published(&self) -> Vec<BackendRateProfiles> {
+        self.applied.read().published.clone()
+    }
+
+    /// Profiles in effect on this worker.
+    pub fn status(&self) -> Vec<ActiveProfile> {
+        let mut status: Vec<ActiveProfile> = self.applied.read().active.values().cloned().collect();
+        status.sort_by(|a, b| a.backend_id.cmp(&b.backend_id));
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pistonprotection_common::error::Error;
+    use pistonprotection_common::rate_profile::{ManualProfile, ProfileWindow, RateLimitProfile};
+
+    fn profiles(backend_id: &str) -> BackendRateProfiles {
+        BackendRateProfiles {
+            backend_id: backend_id.to_string(),
+            profiles: vec![
+                RateLimitProfile {
+                    name: "night".to_string(),
+                    per_ip_pps: 100,
+                    per_ip_burst: 200,
+                },
+                RateLimitProfile {
+                    name: "restart-storm".to_string(),
+                    per_ip_pps: 20,
+                    per_ip_burst: 0,
+                },
+            ],
+            // 00:00 to 06:00 UTC every day
+            windows: vec![ProfileWindow {
+                profile: "night".to_string(),
+                days: vec![],
+                start_minute: 0,
+                end_minute: 6 * 60,
+            }],
+            ..Default::default()
+        }
+    }
+
+    const NIGHT: i64 = 3 * 3600;
+    const DAY: i64 = 12 * 3600;
+
+    #[test]
+    fn test_applies_scheduled_profile() {
+        let tracker = RateProfiles::new();
+        let mut written = HashMap::new();
+
+        let changes = tracker
+            .apply(vec![profiles("b1")], NIGHT, |limits| {
+                written = limits.clone();
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(written["b1"].per_ip_pps, 100);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].from, CONFIGURED_PROFILE);
+        assert_eq!(changes[0].to, "night");
+
+        let changes = tracker
+            .apply(vec![profiles("b1")], DAY, |limits| {
+                written = limits.clone();
+                Ok(())
+            })
+            .unwrap();
+        assert!(written.is_empty());
+        assert_eq!(changes[0].to, CONFIGURED_PROFILE);
+        assert_eq!(tracker.status()[0].per_ip_pps, None);
+    }
+
+    #[test]
+    fn test_manual_trigger_overrides_schedule() {
+        let tracker = RateProfiles::new();
+        let mut triggered = profiles("b1");
+        triggered.manual = Some(ManualProfile {
+            profile: "restart-storm".to_string(),
+            triggered_by: "user-1".to_string(),
+            until: NIGHT + 600,
+        });
+
+        tracker
+            .apply(vec![triggered.clone()], NIGHT, |_| Ok(()))
+            .unwrap();
+        let status = tracker.status();
+        assert_eq!(status[0].profile, "restart-storm");
+        assert_eq!(status[0].source, ProfileSource::Manual);
+        assert_eq!(status[0].manual_until, Some(NIGHT + 600));
+
+        // The trigger ends without the gateway
+        let changes = tracker
+            .apply(vec![triggered], NIGHT + 601, |_| Ok(()))
+            .unwrap();
+        assert_eq!(changes[0].to, "night");
+        assert_eq!(changes[0].source, ProfileSource::Schedule);
+    }
+
+    #[test]
+    fn test_removed_profiles_revert() {
+        let tracker = RateProfiles::new();
+        tracker
+            .apply(vec![profiles("b1")], NIGHT, |_| Ok(()))
+            .unwrap();
+
+        let changes = tracker.apply(vec![], NIGHT, |_| Ok(())).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].from, "night");
+        assert!(tracker.status().is_empty());
+    }
+
+    #[test]
+    fn test_failed_write_keeps_profiles() {
+        let tracker = RateProfiles::new();
+        tracker
+            .apply(vec![profiles("b1")], NIGHT, |_| Ok(()))
+            .unwrap();
+
+        let result = tracker.apply(vec![], NIGHT, |_| {
+            Err(Error::Internal("map update failed".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(tracker.status()[0].profile, "night");
+        assert_eq!(tracker.published().len(), 1);
+    }
+}
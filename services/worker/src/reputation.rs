@@ -0,0 +1,646 @@
+//! Source Reputation
+//!
+//! Keeps a per-source reputation score fed by local block events, failed
+//! challenges and sightings of sources listed by count-only threat feeds.
+//! Scores decay exponentially with a configurable half-life. With Redis they
+//! are shared by every worker (each update is applied atomically by a Lua
+//! script); without it each worker scores on its own.
+//!
+//! Sources scoring above the greylist threshold are not blocked: they are
+//! written to the xdp_filter greylist maps, where they get a much smaller
+//! token bucket than regular sources until their score decays.
+//!
+//! Local scores can be exported to warm up a new worker (see
+//! `crate::warmup`); shared scores need no warm-up.
+
+use deadpool_redis::{redis, redis::AsyncCommands};
+use parking_lot::{Mutex, RwLock};
+use pistonprotection_common::error::{Error, Result};
+use pistonprotection_common::redis::RedisPool;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Default time for a score to halve
+pub const DEFAULT_HALF_LIFE_SECS: u64 = 3600;
+
+/// Default score from which a source is greylisted
+pub const DEFAULT_GREYLIST_THRESHOLD: f64 = 50.0;
+
+/// Scores are capped here so a source recovers within a bounded time
+pub const MAX_SCORE: f64 = 1000.0;
+
+/// Maximum number of greylisted sources (mirrors `greylist::MAX_ENTRIES`)
+pub const MAX_GREYLISTED: usize = 65_536;
+
+/// Maximum number of sources with events waiting to be flushed
+pub const MAX_PENDING_SOURCES: usize = 10_000;
+
+/// Maximum number of scores exported to other workers
+pub const MAX_EXPORTED_SCORES: usize = 65_536;
+
+/// Prefix of the Redis keys, shared by all workers
+const KEY_PREFIX: &str = "piston:reputation";
+
+/// Decay the stored score, add the delta and index the time the source
+/// leaves the greylist.
+///
+/// KEYS: score hash, greylist sorted set
+/// ARGV: delta, now, half-life, threshold, max score, ttl, member
+const UPDATE_SCRIPT: &str = r#"
+local now = tonumber(ARGV[2])
+local half_life = tonumber(ARGV[3])
+local threshold = tonumber(ARGV[4])
+local state = redis.call('HMGET', KEYS[1], 'score', 'updated')
+local score = tonumber(state[1]) or 0
+local updated = tonumber(state[2]) or now
+if now > updated then
+  score = score * math.pow(0.5, (now - updated) / half_life)
+end
+score = math.min(score + tonumber(ARGV[1]), tonumber(ARGV[5]))
+redis.call('HSET', KEYS[1], 'score', tostring(score), 'updated', tostring(now))
+redis.call('EXPIRE', KEYS[1], ARGV[6])
+if score >= threshold then
+  local until_ts = now + half_life * math.log(score / threshold) / math.log(2)
+  redis.call('ZADD', KEYS[2], until_ts, ARGV[7])
+else
+  redis.call('ZREM', KEYS[2], ARGV[7])
+end
+return tostring(score)
+"#;
+
+/// Value of the `GREYLIST_V*` maps (mirrors `GreylistEntry`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GreylistEntry {
+    pub pps_limit: u64,
+    pub burst: u64,
+}
+
+// SAFETY: `#[repr(C)]` struct of two `u64` fields, no padding.
+unsafe impl aya::Pod for GreylistEntry {}
+
+/// Behaviour that worsens the reputation of a source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReputationEvent {
+    /// The worker blocked the source
+    Blocked,
+    /// The source failed a challenge
+    ChallengeFailed,
+    /// Traffic from a source listed by a count-only threat feed
+    ThreatIntel { confidence: u8 },
+    /// The source sent traffic to a honeypot port
+    Honeypot,
+}
+
+impl ReputationEvent {
+    /// Parse an event name as used by the HTTP API
+    pub fn parse(name: &str, confidence: Option<u8>) -> Option<Self> {
+        match name {
+            "blocked" => Some(ReputationEvent::Blocked),
+            "challenge_failed" => Some(ReputationEvent::ChallengeFailed),
+            "threat_intel" => Some(ReputationEvent::ThreatIntel {
+                confidence: confidence.unwrap_or(100).min(100),
+            }),
+            "honeypot" => Some(ReputationEvent::Honeypot),
+            _ => None,
+        }
+    }
+
+    /// Score added by the event
+    pub fn weight(&self) -> f64 {
+        match self {
+            ReputationEvent::Blocked => 40.0,
+            ReputationEvent::ChallengeFailed => 15.0,
+            ReputationEvent::ThreatIntel { confidence } => *confidence as f64 / 4.0,
+            ReputationEvent::Honeypot => 50.0,
+        }
+    }
+}
+
+/// Reputation configuration
+#[derive(Debug, Clone)]
+pub struct ReputationConfig {
+    pub half_life: Duration,
+    pub greylist_threshold: f64,
+    /// Limits of greylisted sources (0 = kernel default)
+    pub greylist_pps: u64,
+    pub greylist_burst: u64,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self {
+            half_life: Duration::from_secs(DEFAULT_HALF_LIFE_SECS),
+            greylist_threshold: DEFAULT_GREYLIST_THRESHOLD,
+            greylist_pps: 0,
+            greylist_burst: 0,
+        }
+    }
+}
+
+impl ReputationConfig {
+    /// Load reputation configuration from environment variables
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(secs) = std::env::var("PISTON_REPUTATION_HALF_LIFE_SECS") {
+            if let Ok(secs) = secs.parse::<u64>() {
+                config.half_life = Duration::from_secs(secs.max(1));
+            }
+        }
+
+        if let Ok(threshold) = std::env::var("PISTON_REPUTATION_GREYLIST_THRESHOLD") {
+            if let Ok(threshold) = threshold.parse::<f64>() {
+                config.greylist_threshold = threshold.clamp(1.0, MAX_SCORE);
+            }
+        }
+
+        if let Ok(pps) = std::env::var("PISTON_REPUTATION_GREYLIST_PPS") {
+            if let Ok(pps) = pps.parse::<u64>() {
+                config.greylist_pps = pps;
+            }
+        }
+
+        if let Ok(burst) = std::env::var("PISTON_REPUTATION_GREYLIST_BURST") {
+            if let Ok(burst) = burst.parse::<u64>() {
+                config.greylist_burst = burst;
+            }
+        }
+
+        config
+    }
+
+    fn half_life_secs(&self) -> f64 {
+        self.half_life.as_secs_f64().max(1.0)
+    }
+
+    /// Time for a maximal score to decay below 1, after which a record is
+    /// worthless
+    fn record_ttl_secs(&self) -> u64 {
+        (self.half_life_secs() * MAX_SCORE.log2()).ceil() as u64 + 1
+    }
+}
+
+/// Decaying score of one source
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub value: f64,
+    /// Unix time of the last update
+    pub updated: i64,
+}
+
+impl Score {
+    /// Value at `now`
+    pub fn decayed(&self, now: i64, half_life: f64) -> f64 {
+        let elapsed = (now - self.updated).max(0) as f64;
+        self.value * 0.5f64.powf(elapsed / half_life)
+    }
+
+    /// Decay to `now` and add `delta`, capped at `MAX_SCORE`
+    pub fn add(&mut self, delta: f64, now: i64, half_life: f64) {
+        self.value = (self.decayed(now, half_life) + delta).min(MAX_SCORE);
+        self.updated = now.max(self.updated);
+    }
+
+    /// Unix time at which the score decays below `threshold`, if it is at or
+    /// above it now
+    pub fn greylisted_until(&self, threshold: f64, half_life: f64) -> Option<f64> {
+        (self.value >= threshold)
+            .then(|| self.updated as f64 + half_life * (self.value / threshold).log2())
+    }
+}
+
+/// Greylisted source
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GreylistedSource {
+    pub ip: IpAddr,
+    /// When the score decays below the threshold
+    pub until: chrono::DateTime<chrono::Utc>,
+}
+
+/// Reputation status
+#[derive(Debug, Clone, Serialize)]
+pub struct ReputationStatus {
+    /// `redis` or `local`
+    pub store: &'static str,
+    pub half_life_secs: u64,
+    pub greylist_threshold: f64,
+    pub pending_sources: usize,
+    pub events_recorded: u64,
+    pub last_error: Option<String>,
+    pub greylisted: Vec<GreylistedSource>,
+}
+
+/// Where scores are kept
+enum ScoreStore {
+    Redis(RedisPool),
+    Local(Mutex<HashMap<IpAddr, Score>>),
+}
+
+/// Reputation scoring and greylist selection
+pub struct ReputationEngine {
+    config: ReputationConfig,
+    store: ScoreStore,
+    /// Score deltas per source waiting to be flushed
+    pending: Mutex<HashMap<IpAddr, f64>>,
+    greylist: RwLock<Vec<GreylistedSource>>,
+    events_recorded: Mutex<u64>,
+    last_error: Mutex<Option<String>>,
+}
+
+impl ReputationEngine {
+    /// Create an engine, scoring in Redis when a pool is given
+    pub fn new(config: ReputationConfig, redis: Option<RedisPool>) -> Self {
+        let store = match redis {
+            Some(pool) => ScoreStore::Redis(pool),
+            None => ScoreStore::Local(Mutex::new(HashMap::new())),
+        };
+
+        Self {
+            config,
+            store,
+            pending: Mutex::new(HashMap::new()),
+            greylist: RwLock::new(Vec::new()),
+            events_recorded: Mutex::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// Queue an event for the next flush
+    ///
+    /// Events of new sources are dropped while `MAX_PENDING_SOURCES` sources
+    /// are waiting.
+    pub fn record(&self, ip: IpAddr, event: ReputationEvent) {
+        let mut pending = self.pending.lock();
+        if pending.len() >= MAX_PENDING_SOURCES && !pending.contains_key(&ip) {
+            return;
+        }
+        let delta = pending.entry(ip).or_insert(0.0);
+        *delta = (*delta + event.weight()).min(MAX_SCORE);
+        *self.events_recorded.lock() += 1;
+    }
+
+    /// Apply the queued events to the stored scores
+    pub async fn flush(&self, now: i64) -> Result<usize> {
+        let pending: Vec<(IpAddr, f64)> = self.pending.lock().drain().collect();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        let half_life = self.config.half_life_secs();
+
+        let result = match &self.store {
+            ScoreStore::Local(scores) => {
+                let mut scores = scores.lock();
+                for (ip, delta) in &pending {
+                    scores
+                        .entry(*ip)
+                        .or_insert(Score {
+                            value: 0.0,
+                            updated: now,
+                        })
+                        .add(*delta, now, half_life);
+                }
+                Ok(())
+            }
+            ScoreStore::Redis(pool) => {
+                let mut conn = pool
+                    .get()
+                    .await
+                    .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
+
+                let mut pipe = redis::pipe();
+                for (ip, delta) in &pending {
+                    pipe.cmd("EVAL")
+                        .arg(UPDATE_SCRIPT)
+                        .arg(2)
+                        .arg(score_key(ip))
+                        .arg(greylist_key())
+                        .arg(*delta)
+                        .arg(now)
+                        .arg(half_life)
+                        .arg(self.config.greylist_threshold)
+                        .arg(MAX_SCORE)
+                        .arg(self.config.record_ttl_secs())
+                        .arg(ip.to_string())
+                        .ignore();
+                }
+                pipe.query_async::<()>(&mut conn).await.map_err(Error::from)
+            }
+        };
+
+        self.record_result(&result);
+        result.map(|_| pending.len())
+    }
+
+    /// Current score of a source
+    pub async fn score(&self, ip: &IpAddr, now: i64) -> Result<f64> {
+        let half_life = self.config.half_life_secs();
+        let score = match &self.store {
+            ScoreStore::Local(scores) => scores.lock().get(ip).copied(),
+            ScoreStore::Redis(pool) => {
+                let mut conn = pool
+                    .get()
+                    .await
+                    .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
+                let (value, updated): (Option<f64>, Option<i64>) = redis::cmd("HMGET")
+                    .arg(score_key(ip))
+                    .arg("score")
+                    .arg("updated")
+                    .query_async(&mut conn)
+                    .await?;
+                value
+                    .zip(updated)
+                    .map(|(value, updated)| Score { value, updated })
+            }
+        };
+
+        Ok(score.map_or(0.0, |score| score.decayed(now, half_life)))
+    }
+
+    /// Reload the sources currently above the greylist threshold
+    pub async fn refresh_greylist(&self, now: i64) -> Result<Vec<GreylistedSource>> {
+        let half_life = self.config.half_life_secs();
+        let threshold = self.config.greylist_threshold;
+
+        let result: Result<Vec<(IpAddr, f64)>> = match &self.store {
+            ScoreStore::Local(scores) => {
+                let mut scores = scores.lock();
+                // Forget sources whose score has decayed to nothing
+                scores.retain(|_, score| score.decayed(now, half_life) >= 1.0);
+                Ok(scores
+                    .iter()
+                    .filter_map(|(ip, score)| {
+                        let until = score.greylisted_until(threshold, half_life)?;
+                        (until > now as f64).then_some((*ip, until))
+                    })
+                    .collect())
+            }
+            ScoreStore::Redis(pool) => self.redis_greylist(pool, now).await,
+        };
+        self.record_result(&result);
+
+        let mut sources = result?;
+        sources.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        sources.truncate(MAX_GREYLISTED);
+
+        let greylist: Vec<GreylistedSource> = sources
+            .into_iter()
+            .filter_map(|(ip, until)| {
+                let until = chrono::DateTime::from_timestamp(until as i64, 0)?;
+                Some(GreylistedSource { ip, until })
+            })
+            .collect();
+        *self.greylist.write() = greylist.clone();
+        Ok(greylist)
+    }
+
+    async fn redis_greylist(&self, pool: &RedisPool, now: i64) -> Result<Vec<(IpAddr, f64)>> {
+        let mut conn = pool
+            .get()
+            .await
+            .map_err(|e| Error::Internal(format!("Redis connection error: {}", e)))?;
+
+        let _: usize = conn.zrembyscore(greylist_key(), "-inf", now).await?;
+        let members: Vec<(String, f64)> = conn
+            .zrangebyscore_limit_withscores(
+                greylist_key(),
+                format!("({}", now),
+                "+inf",
+                0,
+                MAX_GREYLISTED as isize,
+            )
+            .await?;
+
+        Ok(members
+            .into_iter()
+            .filter_map(|(ip, until)| Some((ip.parse().ok()?, until)))
+            .collect())
+    }
+
+    /// Local scores still above decay, highest first
+    ///
+    /// Scores kept in Redis are already shared with every worker, so nothing
+    /// is exported from them.
+    pub fn export_scores(&self, now: i64) -> Vec<(IpAddr, Score)> {
+        let ScoreStore::Local(scores) = &self.store else {
+            return Vec::new();
+        };
+        let half_life = self.config.half_life_secs();
+
+        let mut exported: Vec<(IpAddr, Score)> = scores
+            .lock()
+            .iter()
+            .filter_map(|(ip, score)| {
+                let value = score.decayed(now, half_life);
+                (value >= 1.0).then_some((
+                    *ip,
+                    Score {
+                        value,
+                        updated: now,
+                    },
+                ))
+            })
+            .collect();
+        exported.sort_by(|a, b| b.1.value.total_cmp(&a.1.value).then(a.0.cmp(&b.0)));
+        exported.truncate(MAX_EXPORTED_SCORES);
+        exported
+    }
+
+    /// Merge scores exported by another worker into the local scores
+    ///
+    /// A source keeps the higher of both scores. Returns the number of
+    /// sources imported; with Redis nothing is imported.
+    pub fn import_scores(&self, imported: &[(IpAddr, Score)], now: i64) -> usize {
+        let ScoreStore::Local(scores) = &self.store else {
+            return 0;
+        };
+        let half_life = self.config.half_life_secs();
+
+        let mut scores = scores.lock();
+        let mut merged = 0;
+        for (ip, score) in imported.iter().take(MAX_EXPORTED_SCORES) {
+            if !score.value.is_finite() {
+                continue;
+            }
+            let value = score.decayed(now, half_life).clamp(0.0, MAX_SCORE);
+            if value < 1.0 {
+                continue;
+            }
+            let current = scores.get(ip).map_or(0.0, |s| s.decayed(now, half_life));
+            if value > current {
+                scores.insert(
+                    *ip,
+                    Score {
+                        value,
+                        updated: now,
+                    },
+                );
+                merged += 1;
+            }
+        }
+        merged
+    }
+
+    fn record_result<T>(&self, result: &Result<T>) {
+        *self.last_error.lock() = result.as_ref().err().map(|e| e.to_string());
+    }
+
+    /// Sources greylisted at the last refresh
+    pub fn greylist(&self) -> Vec<GreylistedSource> {
+        self.greylist.read().clone()
+    }
+
+    /// Contents of the xdp_filter greylist maps
+    pub fn map_entries(&self) -> Vec<(IpAddr, GreylistEntry)> {
+        let entry = GreylistEntry {
+            pps_limit: self.config.greylist_pps,
+            burst: self.config.greylist_burst,
+        };
+        self.greylist
+            .read()
+            .iter()
+            .map(|source| (source.ip, entry))
+            .collect()
+    }
+
+    pub fn status(&self) -> ReputationStatus {
+        ReputationStatus {
+            store: match self.store {
+                ScoreStore::Redis(_) => "redis",
+                ScoreStore::Local(_) => "local",
+            },
+            half_life_secs: self.config.half_life.as_secs(),
+            greylist_threshold: self.config.greylist_threshold,
+            pending_sources: self.pending.lock().len(),
+            events_recorded: *self.events_recorded.lock(),
+            last_error: self.last_error.lock().clone(),
+            greylisted: self.greylist(),
+        }
+    }
+}
+
+fn score_key(ip: &IpAddr) -> String {
+    format!("{}:ip:{}", KEY_PREFIX, ip)
+}
+
+fn greylist_key() -> String {
+    format!("{}:greylist", KEY_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i64 = 3600;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_score_decay() {
+        let mut score = Score {
+            value: 0.0,
+            updated: 0,
+        };
+        score.add(80.0, 0, HOUR as f64);
+        assert_eq!(score.decayed(HOUR, HOUR as f64), 40.0);
+        assert_eq!(score.decayed(2 * HOUR, HOUR as f64), 20.0);
+
+        // Adding decays the old value first
+        score.add(10.0, HOUR, HOUR as f64);
+        assert_eq!(score.value, 50.0);
+        assert_eq!(score.updated, HOUR);
+
+        score.add(5000.0, HOUR, HOUR as f64);
+        assert_eq!(score.value, MAX_SCORE);
+    }
+
+    #[test]
+    fn test_greylisted_until() {
+        let score = Score {
+            value: 200.0,
+            updated: 1000,
+        };
+        // 200 -> 100 -> 50: two half-lives above a threshold of 50
+        assert_eq!(
+            score.greylisted_until(50.0, HOUR as f64),
+            Some((1000 + 2 * HOUR) as f64)
+        );
+        assert_eq!(score.greylisted_until(500.0, HOUR as f64), None);
+    }
+
+    #[test]
+    fn test_event_weights() {
+        assert_eq!(
+            ReputationEvent::parse("challenge_failed", None),
+            Some(ReputationEvent::ChallengeFailed)
+        );
+        assert_eq!(
+            ReputationEvent::parse("threat_intel", Some(250)),
+            Some(ReputationEvent::ThreatIntel { confidence: 100 })
+        );
+        assert_eq!(ReputationEvent::parse("other", None), None);
+
+        assert!(ReputationEvent::Blocked.weight() >= DEFAULT_GREYLIST_THRESHOLD * 0.8);
+        assert_eq!(
+            ReputationEvent::ThreatIntel { confidence: 80 }.weight(),
+            20.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_greylist() {
+        let engine = ReputationEngine::new(ReputationConfig::default(), None);
+        let offender = ip("198.51.100.7");
+        let bystander = ip("2001:db8::1");
+
+        engine.record(offender, ReputationEvent::Blocked);
+        engine.record(offender, ReputationEvent::ChallengeFailed);
+        engine.record(bystander, ReputationEvent::ChallengeFailed);
+        assert_eq!(engine.flush(0).await.unwrap(), 2);
+        assert_eq!(engine.flush(0).await.unwrap(), 0);
+        assert_eq!(engine.score(&offender, 0).await.unwrap(), 55.0);
+
+        let greylist = engine.refresh_greylist(0).await.unwrap();
+        assert_eq!(greylist.len(), 1);
+        assert_eq!(greylist[0].ip, offender);
+        assert_eq!(engine.map_entries(), [(offender, GreylistEntry::default())]);
+
+        // One half-life later the offender has decayed below the threshold
+        assert!(engine.refresh_greylist(HOUR).await.unwrap().is_empty());
+        assert!(engine.map_entries().is_empty());
+        assert_eq!(engine.status().store, "local");
+        assert_eq!(engine.status().events_recorded, 3);
+    }
+
+    #[tokio::test]
+    async fn test_score_export_import() {
+        let seasoned = ReputationEngine::new(ReputationConfig::default(), None);
+        let offender = ip("198.51.100.7");
+        seasoned.record(offender, ReputationEvent::Blocked);
+        seasoned.record(offender, ReputationEvent::ChallengeFailed);
+        seasoned.flush(0).await.unwrap();
+
+        let exported = seasoned.export_scores(HOUR);
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].1.value, 27.5);
+
+        // A new worker greylists the offender from its first refresh
+        let fresh = ReputationEngine::new(ReputationConfig::default(), None);
+        assert_eq!(fresh.import_scores(&exported, HOUR), 1);
+        assert_eq!(fresh.score(&offender, HOUR).await.unwrap(), 27.5);
+        assert_eq!(fresh.import_scores(&exported, 2 * HOUR), 0);
+    }
+
+    #[test]
+    fn test_pending_sources_bounded() {
+        let engine = ReputationEngine::new(ReputationConfig::default(), None);
+        for i in 0..MAX_PENDING_SOURCES as u32 + 10 {
+            engine.record(IpAddr::from(i.to_be_bytes()), ReputationEvent::Blocked);
+        }
+        assert_eq!(engine.status().pending_sources, MAX_PENDING_SOURCES);
+    }
+}
@@ -0,0 +1,211 @@
+//! Restart Modes
+//!
+//! Applies the restart modes published by the gateway (see
+//! `pistonprotection_common::restart_mode`). While a backend restarts, the
+//! per-source limits of its namespace in the xdp_filter tenant config map
+//! are multiplied and its protection level raised to at least high, and the
+//! multiplier is exported in the `restart_mode_active` metric.
+//!
+//! Restart modes past their end are dropped here even while the gateway
+//! still publishes them, so relaxed limits never outlive the restart
+//! because the gateway is unreachable.
+
+use parking_lot::RwLock;
+use pistonprotection_common::error::Result;
+use pistonprotection_common::metrics::RESTART_MODE_ACTIVE;
+use pistonprotection_common::restart_mode::RestartMode;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Restart mode of a backend started or ended on this worker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestartChange {
+    pub backend_id: String,
+    /// Multiplier now applied, unset once the restart mode ended
+    pub connection_multiplier: Option<u32>,
+    /// The restart mode reached its end
+    pub expired: bool,
+}
+
+/// Restart mode applied on this worker
+#[derive(Debug, Clone, Serialize)]
+pub struct RestartStatus {
+    #[serde(flatten)]
+    pub restart: RestartMode,
+    /// Seconds until the limits go back to normal
+    pub ends_in_secs: i64,
+}
+
+/// Restart modes applied to the XDP maps.
+#[derive(Default)]
+pub struct RestartModes {
+    /// Restart modes keyed by backend ID
+    applied: RwLock<HashMap<String, RestartMode>>,
+}
+
+impl RestartModes {
+    /// Create a tracker without restart modes: every backend uses its
+    /// normal limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the applied restart modes.
+    ///
+    /// Restart modes past their end at `now` are dropped. The remaining
+    /// ones are handed to `write_xdp`; if it fails the previous restart
+    /// modes stay in place and the error is returned.
+    pub fn apply<W>(
+        &self,
+        published: Vec<RestartMode>,
+        now: i64,
+        write_xdp: W,
+    ) -> Result<Vec<RestartChange>>
+    where
+        W: FnOnce(&HashMap<String, RestartMode>) -> Result<()>,
+    {
+        let mut next = HashMap::new();
+        let mut expired = Vec::new();
+        for restart in published {
+            if restart.expired(now) {
+                expired.push(restart.backend_id);
+                continue;
+            }
+            next.insert(restart.backend_id.clone(), restart);
+        }
+
+        let mut applied = self.applied.write();
+        write_xdp(&next)?;
+
+        let mut changes = Vec::new();
+        for backend_id in applied.keys() {
+            if !next.contains_key(backend_id) {
+                changes.push(RestartChange {
+                    backend_id: backend_id.clone(),
+                    connection_multiplier: None,
+                    expired: expired.contains(backend_id),
+                });
+            }
+        }
+        for (backend_id, restart) in &next {
+            let from = applied
+                .get(backend_id)
+                .map(|current| current.connection_multiplier);
+            if from != Some(restart.connection_multiplier) {
+                changes.push(RestartChange {
+                    backend_id: backend_id.clone(),
+                    connection_multiplier: Some(restart.connection_multiplier),
+                    expired: false,
+                });
+            }
+        }
+
+        RESTART_MODE_ACTIVE.reset();
+        for restart in next.values() {
+            RESTART_MODE_ACTIVE
+                .with_label_values(&[restart.backend_id.as_str()])
+                .set(f64::from(restart.connection_multiplier));
+        }
+
+        *applied = next;
+        Ok(changes)
+    }
+
+    /// Restart modes currently applied.
+    pub fn restarts(&self) -> Vec<RestartMode> {
+        self.applied.read().values().cloned().collect()
+    }
+
+    /// Restart modes applied on this worker.
+    pub fn status(&self, now: i64) -> Vec<RestartStatus> {
+        let mut status: Vec<RestartStatus> = self
+            .applied
+            .read()
+            .values()
+            .map(|restart| RestartStatus {
+                ends_in_secs: (restart.ends_at - now).max(0),
+                restart: restart.clone(),
+            })
+            .collect();
+        status.sort_by(|a, b| a.restart.backend_id.cmp(&b.restart.backend_id));
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pistonprotection_common::error::Error;
+
+    fn restart(backend_id: &str, connection_multiplier: u32, ends_at: i64) -> RestartMode {
+        RestartMode {
+            backend_id: backend_id.to_string(),
+            connection_multiplier,
+            reason: "Weekly restart".to_string(),
+            started_by: "user-1".to_string(),
+            started_at: 0,
+            ends_at,
+        }
+    }
+
+    #[test]
+    fn test_applies_restart_modes() {
+        let tracker = RestartModes::new();
+        let mut written = HashMap::new();
+
+        let changes = tracker
+            .apply(vec![restart("b1", 5, 600)], 100, |restarts| {
+                written = restarts.clone();
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(written["b1"].connection_multiplier, 5);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].connection_multiplier, Some(5));
+        assert_eq!(tracker.status(100)[0].ends_in_secs, 500);
+
+        // Republishing the same mode is not a change
+        let changes = tracker
+            .apply(vec![restart("b1", 5, 600)], 200, |_| Ok(()))
+            .unwrap();
+        assert!(changes.is_empty());
+
+        let changes = tracker.apply(vec![], 200, |_| Ok(())).unwrap();
+        assert_eq!(changes[0].connection_multiplier, None);
+        assert!(!changes[0].expired);
+    }
+
+    #[test]
+    fn test_ended_restart_mode_dropped() {
+        let tracker = RestartModes::new();
+        tracker
+            .apply(vec![restart("b1", 5, 600)], 100, |_| Ok(()))
+            .unwrap();
+
+        // The gateway still publishes it, but its time is up
+        let mut written = None;
+        let changes = tracker
+            .apply(vec![restart("b1", 5, 600)], 600, |restarts| {
+                written = Some(restarts.len());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(written, Some(0));
+        assert!(changes[0].expired);
+        assert!(tracker.restarts().is_empty());
+    }
+
+    #[test]
+    fn test_failed_write_keeps_restart_modes() {
+        let tracker = RestartModes::new();
+        tracker
+            .apply(vec![restart("b1", 5, 600)], 100, |_| Ok(()))
+            .unwrap();
+
+        let result = tracker.apply(vec![], 100, |_| {
+            Err(Error::Internal("map update failed".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(tracker.restarts().len(), 1);
+    }
+}
@@ -0,0 +1,341 @@
+//! UDP session affinity.
+//!
+//! UDP has no connection for the load balancer to follow, so every datagram
+//! of a game flow would be balanced on its own and responses could come from
+//! a different origin than the one the client talks to. The affinity table
+//! pins each client address (IP and port) of a backend to the origin picked
+//! for its first datagram until the flow goes idle. When the table is full,
+//! new flows are spread by a consistent hash of the client address instead,
+//! which keeps them on one origin as long as the origin set does not change.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use parking_lot::RwLock;
+use pistonprotection_common::metrics::{UDP_AFFINITY_ENTRIES, UDP_AFFINITY_LOOKUPS};
+use serde::Serialize;
+
+use super::load_balancer::{FnvHasher, OriginInfo};
+use super::origin_selector::{OriginSelector, SelectedOrigin, SelectionReason};
+
+/// Limits of the affinity table.
+#[derive(Debug, Clone)]
+pub struct AffinityConfig {
+    /// Idle time after which a session is forgotten
+    pub idle_timeout: Duration,
+    /// Sessions kept across all backends
+    pub max_entries: usize,
+}
+
+impl Default for AffinityConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(60),
+            max_entries: 262_144,
+        }
+    }
+}
+
+impl AffinityConfig {
+    /// Load the limits from `PISTON_UDP_AFFINITY_*` environment variables.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(secs) = env_u64("PISTON_UDP_AFFINITY_IDLE_SECS") {
+            config.idle_timeout = Duration::from_secs(secs.max(1));
+        }
+        if let Some(max) = env_u64("PISTON_UDP_AFFINITY_MAX_ENTRIES") {
+            config.max_entries = max as usize;
+        }
+
+        config
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Origin a client address is pinned to.
+#[derive(Debug, Clone)]
+struct AffinityEntry {
+    origin_id: String,
+    last_seen: Instant,
+}
+
+/// Occupancy of the affinity table.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AffinityStats {
+    pub entries: usize,
+    pub max_entries: usize,
+    /// Sessions per backend ID
+    pub backends: HashMap<String, usize>,
+}
+
+/// Client address to origin table of UDP backends.
+pub struct SessionAffinity {
+    config: AffinityConfig,
+    /// Sessions keyed by backend ID and client address
+    sessions: RwLock<HashMap<(String, SocketAddr), AffinityEntry>>,
+}
+
+impl SessionAffinity {
+    /// Create an empty affinity table.
+    pub fn new(config: AffinityConfig) -> Self {
+        Self {
+            config,
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Select the origin of a datagram from `client`.
+    ///
+    /// A live session keeps its origin as long as the origin is enabled and
+    /// healthy, including while it drains for an origin switch. Otherwise
+    /// the selector picks an origin for a new session.
+    pub fn select(
+        &self,
+        selector: &OriginSelector,
+        client: SocketAddr,
+        now: Instant,
+    ) -> Option<SelectedOrigin> {
+        let origins = selector.origins();
+        let key = (selector.backend_id().to_string(), client);
+
+        {
+            let mut sessions = self.sessions.write();
+            if let Some(entry) = sessions.get_mut(&key) {
+                if now.saturating_duration_since(entry.last_seen) < self.config.idle_timeout
+                    && usable(&origins, &entry.origin_id)
+                {
+                    entry.last_seen = now;
+                    UDP_AFFINITY_LOOKUPS.with_label_values(&["hit"]).inc();
+                    return Some(pinned(entry.origin_id.clone(), SelectionReason::Affinity));
+                }
+                sessions.remove(&key);
+            }
+
+            if sessions.len() >= self.config.max_entries {
+                UDP_AFFINITY_LOOKUPS
+                    .with_label_values(&["hash_fallback"])
+                    .inc();
+                return consistent_hash(&origins, client)
+                    .map(|origin_id| pinned(origin_id, SelectionReason::ConsistentHash));
+            }
+        }
+
+        let selected = selector.select(client.ip())?;
+        UDP_AFFINITY_LOOKUPS.with_label_values(&["miss"]).inc();
+
+        // Another datagram of the flow may have created the session meanwhile
+        let mut sessions = self.sessions.write();
+        let entry = sessions.entry(key).or_insert_with(|| AffinityEntry {
+            origin_id: selected.origin_id.clone(),
+            last_seen: now,
+        });
+        if entry.origin_id == selected.origin_id {
+            Some(selected)
+        } else {
+            Some(pinned(entry.origin_id.clone(), SelectionReason::Affinity))
+        }
+    }
+
+    /// Forget the sessions of a backend, e.g. once it is removed.
+    pub fn remove_backend(&self, backend_id: &str) {
+        self.sessions
+            .write()
+            .retain(|(backend, _), _| backend != backend_id);
+    }
+
+    /// Forget idle sessions, returning how many were removed.
+    pub fn prune(&self, now: Instant) -> usize {
+        let idle_timeout = self.config.idle_timeout;
+        let mut sessions = self.sessions.write();
+        let before = sessions.len();
+        sessions.retain(|_, entry| now.saturating_duration_since(entry.last_seen) < idle_timeout);
+        before - sessions.len()
+    }
+
+    /// Occupancy of the table.
+    pub fn stats(&self) -> AffinityStats {
+        let sessions = self.sessions.read();
+        let mut backends: HashMap<String, usize> = HashMap::new();
+        for (backend_id, _) in sessions.keys() {
+            *backends.entry(backend_id.clone()).or_default() += 1;
+        }
+
+        AffinityStats {
+            entries: sessions.len(),
+            max_entries: self.config.max_entries,
+            backends,
+        }
+    }
+
+    /// Export the occupancy per backend as metrics.
+    pub fn record_metrics(&self) {
+        // Reset first so backends without sessions drop out
+        UDP_AFFINITY_ENTRIES.reset();
+        for (backend_id, entries) in self.stats().backends {
+            UDP_AFFINITY_ENTRIES
+                .with_label_values(&[&backend_id])
+                .set(entries as f64);
+        }
+    }
+}
+
+fn pinned(origin_id: String, selection_reason: SelectionReason) -> SelectedOrigin {
+    SelectedOrigin {
+        origin_id,
+        selection_reason,
+        client_location: None,
+        distance_km: None,
+    }
+}
+
+/// Whether a session may stay on an origin.
+fn usable(origins: &[OriginInfo], origin_id: &str) -> bool {
+    origins
+        .iter()
+        .any(|o| o.id == origin_id && o.enabled && o.healthy)
+}
+
+/// Rendezvous hash of the client address over the origins taking new
+/// sessions, so only the flows of a removed origin move elsewhere.
+fn consistent_hash(origins: &[OriginInfo], client: SocketAddr) -> Option<String> {
+    origins
+        .iter()
+        .filter(|o| o.enabled && o.healthy && !o.draining)
+        .max_by_key(|o| {
+            let mut hasher = FnvHasher::default();
+            o.id.hash(&mut hasher);
+            client.hash(&mut hasher);
+            hasher.finish()
+        })
+        .map(|o| o.id.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routing::GeoDatabase;
+    use std::sync::Arc;
+
+    fn selector(origins: &[&str]) -> OriginSelector {
+        let selector = OriginSelector::new("backend-1", Arc::new(GeoDatabase::new()));
+        selector.update_origins(origins.iter().map(|id| OriginInfo::new(*id)).collect());
+        selector
+    }
+
+    fn client(port: u16) -> SocketAddr {
+        SocketAddr::from(([198, 51, 100, 7], port))
+    }
+
+    #[test]
+    fn test_session_sticks_to_origin() {
+        let affinity = SessionAffinity::new(AffinityConfig::default());
+        let selector = selector(&["a", "b", "c"]);
+        let now = Instant::now();
+
+        let first = affinity.select(&selector, client(19132), now).unwrap();
+        for i in 1..10 {
+            let selected = affinity
+                .select(&selector, client(19132), now + Duration::from_secs(i))
+                .unwrap();
+            assert_eq!(selected.origin_id, first.origin_id);
+            assert_eq!(selected.selection_reason, SelectionReason::Affinity);
+        }
+        assert_eq!(affinity.stats().entries, 1);
+        assert_eq!(affinity.stats().backends["backend-1"], 1);
+    }
+
+    #[test]
+    fn test_session_survives_draining_but_not_unhealthy() {
+        let affinity = SessionAffinity::new(AffinityConfig::default());
+        let selector = selector(&["a", "b"]);
+        let now = Instant::now();
+
+        let origin = affinity
+            .select(&selector, client(40000), now)
+            .unwrap()
+            .origin_id;
+        selector.set_origin_draining(&origin, true);
+        assert_eq!(
+            affinity
+                .select(&selector, client(40000), now)
+                .unwrap()
+                .origin_id,
+            origin
+        );
+
+        selector.set_origin_draining(&origin, false);
+        selector.update_origin_health(&origin, false);
+        let moved = affinity.select(&selector, client(40000), now).unwrap();
+        assert_ne!(moved.origin_id, origin);
+    }
+
+    #[test]
+    fn test_idle_expiry() {
+        let config = AffinityConfig {
+            idle_timeout: Duration::from_secs(30),
+            ..Default::default()
+        };
+        let affinity = SessionAffinity::new(config);
+        let selector = selector(&["a", "b"]);
+        let now = Instant::now();
+
+        affinity.select(&selector, client(1), now);
+        affinity.select(&selector, client(2), now + Duration::from_secs(20));
+        assert_eq!(affinity.prune(now + Duration::from_secs(40)), 1);
+        assert_eq!(affinity.stats().entries, 1);
+
+        affinity.remove_backend("backend-1");
+        assert_eq!(affinity.stats().entries, 0);
+    }
+
+    #[test]
+    fn test_full_table_falls_back_to_consistent_hash() {
+        let config = AffinityConfig {
+            max_entries: 1,
+            ..Default::default()
+        };
+        let affinity = SessionAffinity::new(config);
+        let selector = selector(&["a", "b", "c"]);
+        let now = Instant::now();
+
+        affinity.select(&selector, client(1), now);
+        let selected = affinity.select(&selector, client(2), now).unwrap();
+        assert_eq!(selected.selection_reason, SelectionReason::ConsistentHash);
+        for _ in 0..5 {
+            assert_eq!(
+                affinity
+                    .select(&selector, client(2), now)
+                    .unwrap()
+                    .origin_id,
+                selected.origin_id
+            );
+        }
+        assert_eq!(affinity.stats().entries, 1);
+
+        // Removing another origin keeps the flow where it is
+        let removed = ["a", "b", "c"]
+            .into_iter()
+            .find(|id| *id != selected.origin_id)
+            .unwrap();
+        selector.update_origins(
+            ["a", "b", "c"]
+                .into_iter()
+                .filter(|id| *id != removed)
+                .map(OriginInfo::new)
+                .collect(),
+        );
+        assert_eq!(
+            affinity
+                .select(&selector, client(2), now)
+                .unwrap()
+                .origin_id,
+            selected.origin_id
+        );
+    }
+}
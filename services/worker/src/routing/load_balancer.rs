@@ -3,7 +3,7 @@
 //! Implements various load balancing strategies including round-robin,
 //! weighted, least connections, IP hash, and random selection.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::net::IpAddr;
 use std::sync::Arc;
@@ -43,6 +43,8 @@ pub struct OriginInfo {
     pub enabled: bool,
     /// Current active connection count
     pub active_connections: u64,
+    /// Whether the origin is draining (keeps its connections, gets no new ones)
+    pub draining: bool,
 }
 
 impl OriginInfo {
@@ -55,6 +57,7 @@ impl OriginInfo {
             healthy: true,
             enabled: true,
             active_connections: 0,
+            draining: false,
         }
     }
 
@@ -83,6 +86,8 @@ pub struct LoadBalancer {
     weighted_state: Arc<RwLock<WeightedState>>,
     /// Connection counts per origin
     connection_counts: Arc<RwLock<HashMap<String, u64>>>,
+    /// Draining origins, kept across origin updates
+    draining: Arc<RwLock<HashSet<String>>>,
     /// Whether to route only to healthy origins
     route_to_healthy_only: bool,
 }
@@ -105,6 +110,7 @@ impl LoadBalancer {
             rr_counter: AtomicU64::new(0),
             weighted_state: Arc::new(RwLock::new(WeightedState::default())),
             connection_counts: Arc::new(RwLock::new(HashMap::new())),
+            draining: Arc::new(RwLock::new(HashSet::new())),
             route_to_healthy_only: true,
         }
     }
@@ -115,7 +121,13 @@ impl LoadBalancer {
     }
 
     /// Update the list of available origins.
-    pub fn update_origins(&self, origins: Vec<OriginInfo>) {
+    pub fn update_origins(&self, mut origins: Vec<OriginInfo>) {
+        let draining = self.draining.read();
+        for origin in &mut origins {
+            origin.draining = draining.contains(&origin.id);
+        }
+        drop(draining);
+
         let mut weighted_state = self.weighted_state.write();
 
         // Calculate GCD and max weight for weighted algorithm
@@ -137,6 +149,32 @@ impl LoadBalancer {
         }
     }
 
+    /// Start or stop draining an origin.
+    ///
+    /// A draining origin keeps its established connections but is not
+    /// selected for new ones.
+    pub fn set_origin_draining(&self, origin_id: &str, draining: bool) {
+        if draining {
+            self.draining.write().insert(origin_id.to_string());
+        } else {
+            self.draining.write().remove(origin_id);
+        }
+
+        let mut origins = self.origins.write();
+        if let Some(origin) = origins.iter_mut().find(|o| o.id == origin_id) {
+            origin.draining = draining;
+        }
+    }
+
+    /// Get the active connection count of an origin.
+    pub fn connections(&self, origin_id: &str) -> u64 {
+        self.connection_counts
+            .read()
+            .get(origin_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
     /// Update the connection count for an origin.
     pub fn update_connection_count(&self, origin_id: &str, count: u64) {
         let mut counts = self.connection_counts.write();
@@ -164,7 +202,7 @@ impl LoadBalancer {
         // Filter to available origins
         let available: Vec<&OriginInfo> = origins
             .iter()
-            .filter(|o| o.enabled && (!self.route_to_healthy_only || o.healthy))
+            .filter(|o| o.enabled && !o.draining && (!self.route_to_healthy_only || o.healthy))
             .collect();
 
         if available.is_empty() {
@@ -304,7 +342,7 @@ fn gcd(a: u32, b: u32) -> u32 {
 
 /// FNV-1a hasher for consistent hashing.
 #[derive(Default)]
-struct FnvHasher {
+pub(crate) struct FnvHasher {
     state: u64,
 }
 
@@ -427,4 +465,23 @@ mod tests {
         // No healthy origins available
         assert_eq!(lb.select(None), None);
     }
+
+    #[test]
+    fn test_draining_origin() {
+        let lb = LoadBalancer::new(LoadBalancerAlgorithm::RoundRobin);
+        lb.set_origin_draining("old", true);
+        lb.update_origins(vec![OriginInfo::new("old"), OriginInfo::new("new")]);
+        lb.increment_connections("old");
+
+        // New connections only go to the new origin
+        for _ in 0..5 {
+            assert_eq!(lb.select(None), Some("new".to_string()));
+        }
+        assert!(lb.get_origins()[0].draining);
+        assert_eq!(lb.connections("old"), 1);
+
+        lb.set_origin_draining("old", false);
+        let selections: Vec<String> = (0..2).filter_map(|_| lb.select(None)).collect();
+        assert!(selections.contains(&"old".to_string()));
+    }
 }
@@ -3,10 +3,16 @@
 //! This module provides GeoDNS-like functionality for selecting the best origin
 //! server based on client location, health status, and load balancing algorithms.
 
+pub mod affinity;
 pub mod geo;
 pub mod load_balancer;
 pub mod origin_selector;
+pub mod origin_switch;
+pub mod pool;
 
+pub use affinity::{AffinityConfig, SessionAffinity};
 pub use geo::{GeoDatabase, GeoLocation, GeoLookupResult};
 pub use load_balancer::{LoadBalancer, LoadBalancerAlgorithm};
 pub use origin_selector::{OriginSelector, SelectedOrigin};
+pub use origin_switch::OriginSwitches;
+pub use pool::{OriginPools, PoolConfig};
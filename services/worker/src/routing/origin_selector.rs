@@ -93,6 +93,10 @@ pub enum SelectionReason {
     Fallback,
     /// Only one origin available
     SingleOrigin,
+    /// Kept by the UDP session affinity table
+    Affinity,
+    /// Consistent hash of the client address (affinity table full)
+    ConsistentHash,
 }
 
 /// Origin selector combining geo routing and load balancing.
@@ -159,6 +163,21 @@ impl OriginSelector {
         *region_mappings = mappings;
     }
 
+    /// Start or stop draining an origin.
+    pub fn set_origin_draining(&self, origin_id: &str, draining: bool) {
+        self.load_balancer.set_origin_draining(origin_id, draining);
+    }
+
+    /// Get the origins of this backend.
+    pub fn origins(&self) -> Vec<OriginInfo> {
+        self.load_balancer.get_origins()
+    }
+
+    /// Get the active connection count of an origin.
+    pub fn connections(&self, origin_id: &str) -> u64 {
+        self.load_balancer.connections(origin_id)
+    }
+
     /// Update the health status of an origin.
     pub fn update_origin_health(&self, origin_id: &str, healthy: bool) {
         self.load_balancer.update_origin_health(origin_id, healthy);
@@ -177,8 +196,13 @@ impl OriginSelector {
             "Selecting origin for client"
         );
 
-        // Get all available origins
-        let origins = self.load_balancer.get_origins();
+        // Get all available origins, draining ones only keep their connections
+        let origins: Vec<OriginInfo> = self
+            .load_balancer
+            .get_origins()
+            .into_iter()
+            .filter(|o| !o.draining)
+            .collect();
         if origins.is_empty() {
             warn!(backend = %self.backend_id, "No origins available");
             return None;
@@ -0,0 +1,218 @@
+//! Zero-downtime origin switches.
+//!
+//! Applies the switch plans published by the gateway (see
+//! `pistonprotection_common::origin_switch`): the old origin of a backend is
+//! drained in its origin selector, so new connections go to the new origin
+//! while established ones stay where they are, and the connections still
+//! open to the old origin are counted for the drain reports.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use pistonprotection_common::origin_switch::OriginSwitchPlan;
+use serde::Serialize;
+use tracing::info;
+
+use super::OriginSelector;
+use crate::ebpf::maps::MapManager;
+
+/// Progress of a switch on this worker.
+#[derive(Debug, Clone, Serialize)]
+pub struct OriginSwitchStatus {
+    #[serde(flatten)]
+    pub plan: OriginSwitchPlan,
+    /// Connections still open to the old origin
+    pub remaining_connections: u64,
+}
+
+/// Switch plans in progress and the origin selectors they apply to.
+#[derive(Default)]
+pub struct OriginSwitches {
+    /// Plans keyed by backend ID
+    plans: RwLock<HashMap<String, OriginSwitchPlan>>,
+    /// Origin selectors keyed by backend ID
+    selectors: RwLock<HashMap<String, Arc<OriginSelector>>>,
+}
+
+impl OriginSwitches {
+    /// Create an empty switch tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the origin selector of a backend, draining the old origin
+    /// if a switch is in progress.
+    pub fn register_selector(&self, selector: Arc<OriginSelector>) {
+        if let Some(plan) = self.plans.read().get(selector.backend_id()) {
+            selector.set_origin_draining(&plan.from_origin_id, true);
+        }
+        self.selectors
+            .write()
+            .insert(selector.backend_id().to_string(), selector);
+    }
+
+    /// Replace the plans in progress.
+    ///
+    /// Old origins of new plans start draining. Once a plan is gone the
+    /// switch completed (the old origin is disabled with the next origin
+    /// update) or was cancelled, and its old origin stops draining.
+    pub fn update(&self, plans: Vec<OriginSwitchPlan>) {
+        let mut current = self.plans.write();
+        let selectors = self.selectors.read();
+        let next: HashMap<String, OriginSwitchPlan> = plans
+            .into_iter()
+            .map(|plan| (plan.backend_id.clone(), plan))
+            .collect();
+
+        for (backend_id, plan) in current.iter() {
+            if next.get(backend_id).map(|p| &p.id) != Some(&plan.id) {
+                if let Some(selector) = selectors.get(backend_id) {
+                    selector.set_origin_draining(&plan.from_origin_id, false);
+                }
+                info!(backend = %backend_id, switch = %plan.id, "Origin switch ended");
+            }
+        }
+        for (backend_id, plan) in next.iter() {
+            if current.get(backend_id).map(|p| &p.id) != Some(&plan.id) {
+                if let Some(selector) = selectors.get(backend_id) {
+                    selector.set_origin_draining(&plan.from_origin_id, true);
+                }
+                info!(
+                    backend = %backend_id,
+                    switch = %plan.id,
+                    from = %plan.from_origin_id,
+                    to = %plan.to_origin_id,
+                    "Draining origin for switch"
+                );
+            }
+        }
+
+        *current = next;
+    }
+
+    /// Plans in progress.
+    pub fn plans(&self) -> Vec<OriginSwitchPlan> {
+        self.plans.read().values().cloned().collect()
+    }
+
+    /// Progress of the plans in progress.
+    pub fn status(&self, maps: &MapManager) -> Vec<OriginSwitchStatus> {
+        self.plans()
+            .into_iter()
+            .map(|plan| OriginSwitchStatus {
+                remaining_connections: self.remaining_connections(&plan, maps),
+                plan,
+            })
+            .collect()
+    }
+
+    /// Connections still open to the old origin of a plan, from the origin
+    /// selector and connection tracking.
+    pub fn remaining_connections(&self, plan: &OriginSwitchPlan, maps: &MapManager) -> u64 {
+        let routed = self
+            .selectors
+            .read()
+            .get(&plan.backend_id)
+            .map(|selector| selector.connections(&plan.from_origin_id))
+            .unwrap_or(0);
+        let tracked = plan
+            .from_address
+            .parse::<IpAddr>()
+            .map(|ip| maps.count_connections(ip, plan.from_port))
+            .unwrap_or(0);
+
+        routed.max(tracked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ebpf::maps::{ConnTrackKey, ConnTrackState};
+    use crate::routing::GeoDatabase;
+    use crate::routing::load_balancer::OriginInfo;
+    use std::net::Ipv4Addr;
+
+    fn plan(id: &str) -> OriginSwitchPlan {
+        OriginSwitchPlan {
+            id: id.to_string(),
+            backend_id: "backend-1".to_string(),
+            from_origin_id: "old".to_string(),
+            from_address: "10.0.0.2".to_string(),
+            from_port: 25565,
+            to_origin_id: "new".to_string(),
+            drain_until: 0,
+        }
+    }
+
+    fn selector() -> Arc<OriginSelector> {
+        let selector = Arc::new(OriginSelector::new(
+            "backend-1",
+            Arc::new(GeoDatabase::new()),
+        ));
+        selector.update_origins(vec![OriginInfo::new("old"), OriginInfo::new("new")]);
+        selector
+    }
+
+    #[test]
+    fn test_switch_drains_old_origin() {
+        let switches = OriginSwitches::new();
+        let selector = selector();
+        switches.register_selector(Arc::clone(&selector));
+        let client = IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8));
+
+        switches.update(vec![plan("switch-1")]);
+        for _ in 0..5 {
+            assert_eq!(selector.select(client).unwrap().origin_id, "new");
+        }
+
+        // The plan is gone: the old origin is selectable again
+        switches.update(Vec::new());
+        let selected: Vec<String> = (0..4)
+            .filter_map(|_| selector.select(client))
+            .map(|s| s.origin_id)
+            .collect();
+        assert!(selected.contains(&"old".to_string()));
+    }
+
+    #[test]
+    fn test_selector_registered_during_switch() {
+        let switches = OriginSwitches::new();
+        switches.update(vec![plan("switch-1")]);
+
+        let selector = selector();
+        switches.register_selector(Arc::clone(&selector));
+        let client = IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8));
+        assert_eq!(selector.select(client).unwrap().origin_id, "new");
+    }
+
+    #[test]
+    fn test_remaining_connections() {
+        let switches = OriginSwitches::new();
+        let selector = selector();
+        switches.register_selector(Arc::clone(&selector));
+        let plan = plan("switch-1");
+        switches.update(vec![plan.clone()]);
+
+        let mut maps = MapManager::new();
+        assert_eq!(switches.remaining_connections(&plan, &maps), 0);
+
+        for src_port in [40000, 40001] {
+            maps.update_conntrack(
+                ConnTrackKey {
+                    src_ip: "192.0.2.1".parse().unwrap(),
+                    dst_ip: "10.0.0.2".parse().unwrap(),
+                    src_port,
+                    dst_port: 25565,
+                    protocol: 6,
+                },
+                ConnTrackState::Established,
+                10,
+                1000,
+            );
+        }
+        assert_eq!(switches.remaining_connections(&plan, &maps), 2);
+    }
+}
@@ -0,0 +1,400 @@
+//! Origin connection pools for proxied TCP backends.
+//!
+//! Every proxied client connection holds a slot in the pool of the origin
+//! it is forwarded to. A pool admits up to `max_connections` connections;
+//! further clients wait in a bounded pending queue for a slot to free up.
+//! When the queue is full, or a client waits longer than `queue_timeout`,
+//! the connection is shed right away (a 503 for HTTP backends, a disconnect
+//! otherwise) instead of piling more load onto a saturated origin.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use parking_lot::RwLock;
+use pistonprotection_common::metrics::{
+    ORIGIN_POOL_CONNECTIONS, ORIGIN_POOL_SHED, ORIGIN_POOL_UTILIZATION,
+};
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// Response sent to shed HTTP clients before closing the connection
+pub const SHED_HTTP_RESPONSE: &[u8] = b"HTTP/1.1 503 Service Unavailable\r\n\
+Content-Length: 0\r\n\
+Retry-After: 1\r\n\
+Connection: close\r\n\r\n";
+
+/// Limits of the origin connection pools.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Connections admitted per origin
+    pub max_connections: usize,
+    /// Connections waiting for a slot per origin
+    pub max_pending: usize,
+    /// Time a connection may wait for a slot
+    pub queue_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 4096,
+            max_pending: 256,
+            queue_timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Load the limits from `PISTON_ORIGIN_POOL_*` environment variables.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Some(max) = env_u64("PISTON_ORIGIN_POOL_MAX_CONNECTIONS") {
+            config.max_connections = (max as usize).max(1);
+        }
+        if let Some(max) = env_u64("PISTON_ORIGIN_POOL_MAX_PENDING") {
+            config.max_pending = max as usize;
+        }
+        if let Some(ms) = env_u64("PISTON_ORIGIN_POOL_QUEUE_TIMEOUT_MS") {
+            config.queue_timeout = Duration::from_millis(ms);
+        }
+
+        config
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Why a connection was shed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShedReason {
+    /// The pending queue of the origin is full
+    QueueFull,
+    /// No slot freed up within the queue timeout
+    QueueTimeout,
+}
+
+impl ShedReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShedReason::QueueFull => "queue_full",
+            ShedReason::QueueTimeout => "queue_timeout",
+        }
+    }
+
+    /// Bytes to send a shed client before closing: a 503 for HTTP backends,
+    /// nothing (a plain disconnect) otherwise.
+    pub fn response(&self, http: bool) -> Option<&'static [u8]> {
+        http.then_some(SHED_HTTP_RESPONSE)
+    }
+}
+
+impl std::fmt::Display for ShedReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Connection pool of one origin.
+struct OriginPool {
+    backend_id: String,
+    origin_id: String,
+    max_connections: usize,
+    slots: Arc<Semaphore>,
+    pending: AtomicUsize,
+    shed: AtomicU64,
+}
+
+impl OriginPool {
+    fn active(&self) -> usize {
+        self.max_connections - self.slots.available_permits()
+    }
+
+    fn record_gauges(&self) {
+        let labels = [self.backend_id.as_str(), self.origin_id.as_str()];
+        let active = self.active();
+        ORIGIN_POOL_CONNECTIONS
+            .with_label_values(&[labels[0], labels[1], "active"])
+            .set(active as f64);
+        ORIGIN_POOL_CONNECTIONS
+            .with_label_values(&[labels[0], labels[1], "pending"])
+            .set(self.pending.load(Ordering::Relaxed) as f64);
+        ORIGIN_POOL_UTILIZATION
+            .with_label_values(&labels)
+            .set(active as f64 / self.max_connections as f64);
+    }
+
+    fn remove_gauges(&self) {
+        let labels = [self.backend_id.as_str(), self.origin_id.as_str()];
+        for state in ["active", "pending"] {
+            let _ = ORIGIN_POOL_CONNECTIONS.remove_label_values(&[labels[0], labels[1], state]);
+        }
+        let _ = ORIGIN_POOL_UTILIZATION.remove_label_values(&labels);
+    }
+}
+
+/// Slot of a proxied connection in its origin pool, freed on drop.
+pub struct PoolPermit {
+    pool: Arc<OriginPool>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl PoolPermit {
+    /// Origin the connection is admitted to.
+    pub fn origin_id(&self) -> &str {
+        &self.pool.origin_id
+    }
+}
+
+impl Drop for PoolPermit {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        self.pool.record_gauges();
+    }
+}
+
+/// Counts a waiting connection for as long as it is queued, including when
+/// the waiting future is dropped.
+struct PendingGuard<'a>(&'a OriginPool);
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Utilization of an origin pool.
+#[derive(Debug, Clone, Serialize)]
+pub struct OriginPoolStats {
+    pub backend_id: String,
+    pub origin_id: String,
+    pub active: usize,
+    pub pending: usize,
+    pub max_connections: usize,
+    /// Connections shed since the pool was created
+    pub shed: u64,
+}
+
+/// Connection pools of the origins of proxied TCP backends.
+pub struct OriginPools {
+    config: PoolConfig,
+    /// Pools keyed by backend and origin ID
+    pools: RwLock<HashMap<(String, String), Arc<OriginPool>>>,
+}
+
+impl OriginPools {
+    /// Create the pool manager.
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            pools: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn pool(&self, backend_id: &str, origin_id: &str) -> Arc<OriginPool> {
+        let key = (backend_id.to_string(), origin_id.to_string());
+        if let Some(pool) = self.pools.read().get(&key) {
+            return Arc::clone(pool);
+        }
+
+        let max_connections = self.config.max_connections;
+        let mut pools = self.pools.write();
+        let pool = pools.entry(key).or_insert_with(|| {
+            Arc::new(OriginPool {
+                backend_id: backend_id.to_string(),
+                origin_id: origin_id.to_string(),
+                max_connections,
+                slots: Arc::new(Semaphore::new(max_connections)),
+                pending: AtomicUsize::new(0),
+                shed: AtomicU64::new(0),
+            })
+        });
+        Arc::clone(pool)
+    }
+
+    /// Admit a connection to an origin, waiting for a slot while the origin
+    /// is saturated.
+    pub async fn acquire(
+        &self,
+        backend_id: &str,
+        origin_id: &str,
+    ) -> Result<PoolPermit, ShedReason> {
+        let pool = self.pool(backend_id, origin_id);
+
+        let permit = match Arc::clone(&pool.slots).try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                if pool.pending.fetch_add(1, Ordering::Relaxed) >= self.config.max_pending {
+                    pool.pending.fetch_sub(1, Ordering::Relaxed);
+                    return Err(self.shed(&pool, ShedReason::QueueFull));
+                }
+                let pending = PendingGuard(&pool);
+                pool.record_gauges();
+
+                let acquired = tokio::time::timeout(
+                    self.config.queue_timeout,
+                    Arc::clone(&pool.slots).acquire_owned(),
+                )
+                .await;
+                drop(pending);
+
+                match acquired {
+                    Ok(Ok(permit)) => permit,
+                    // The semaphore is never closed; treat it like a timeout
+                    Ok(Err(_)) | Err(_) => {
+                        return Err(self.shed(&pool, ShedReason::QueueTimeout));
+                    }
+                }
+            }
+        };
+
+        pool.record_gauges();
+        Ok(PoolPermit {
+            pool,
+            permit: Some(permit),
+        })
+    }
+
+    fn shed(&self, pool: &OriginPool, reason: ShedReason) -> ShedReason {
+        pool.shed.fetch_add(1, Ordering::Relaxed);
+        ORIGIN_POOL_SHED
+            .with_label_values(&[pool.backend_id.as_str(), reason.as_str()])
+            .inc();
+        pool.record_gauges();
+        debug!(
+            backend = %pool.backend_id,
+            origin = %pool.origin_id,
+            reason = %reason,
+            "Shed connection to saturated origin"
+        );
+        reason
+    }
+
+    /// Whether an origin has no free slot, so the load balancer can prefer
+    /// other origins.
+    pub fn is_saturated(&self, backend_id: &str, origin_id: &str) -> bool {
+        self.pools
+            .read()
+            .get(&(backend_id.to_string(), origin_id.to_string()))
+            .is_some_and(|pool| pool.slots.available_permits() == 0)
+    }
+
+    /// Drop the pools without connections, e.g. of removed origins.
+    pub fn prune(&self) -> usize {
+        let mut pools = self.pools.write();
+        let before = pools.len();
+        pools.retain(|_, pool| {
+            let idle = pool.active() == 0 && pool.pending.load(Ordering::Relaxed) == 0;
+            if idle {
+                pool.remove_gauges();
+            }
+            !idle
+        });
+        before - pools.len()
+    }
+
+    /// Utilization of all pools.
+    pub fn stats(&self) -> Vec<OriginPoolStats> {
+        self.pools
+            .read()
+            .values()
+            .map(|pool| OriginPoolStats {
+                backend_id: pool.backend_id.clone(),
+                origin_id: pool.origin_id.clone(),
+                active: pool.active(),
+                pending: pool.pending.load(Ordering::Relaxed),
+                max_connections: pool.max_connections,
+                shed: pool.shed.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pools(max_connections: usize, max_pending: usize) -> OriginPools {
+        OriginPools::new(PoolConfig {
+            max_connections,
+            max_pending,
+            queue_timeout: Duration::from_millis(50),
+        })
+    }
+
+    fn stats(pools: &OriginPools) -> OriginPoolStats {
+        pools.stats().into_iter().next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_slots_are_released() {
+        let pools = pools(2, 0);
+        let first = pools.acquire("backend-1", "origin-1").await.unwrap();
+        let _second = pools.acquire("backend-1", "origin-1").await.unwrap();
+        assert!(pools.is_saturated("backend-1", "origin-1"));
+        assert_eq!(stats(&pools).active, 2);
+
+        // Other origins have their own pool
+        assert!(pools.acquire("backend-1", "origin-2").await.is_ok());
+
+        drop(first);
+        assert!(!pools.is_saturated("backend-1", "origin-1"));
+        assert!(pools.acquire("backend-1", "origin-1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_sheds() {
+        let pools = pools(1, 0);
+        let _held = pools.acquire("backend-1", "origin-1").await.unwrap();
+
+        let shed = pools.acquire("backend-1", "origin-1").await.err().unwrap();
+        assert_eq!(shed, ShedReason::QueueFull);
+        assert_eq!(shed.response(true), Some(SHED_HTTP_RESPONSE));
+        assert_eq!(shed.response(false), None);
+        assert_eq!(stats(&pools).shed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_queue_timeout_sheds() {
+        let pools = pools(1, 4);
+        let _held = pools.acquire("backend-1", "origin-1").await.unwrap();
+
+        let shed = pools.acquire("backend-1", "origin-1").await.err().unwrap();
+        assert_eq!(shed, ShedReason::QueueTimeout);
+        assert_eq!(stats(&pools).pending, 0);
+    }
+
+    #[tokio::test]
+    async fn test_queued_connection_gets_freed_slot() {
+        let pools = Arc::new(pools(1, 4));
+        let held = pools.acquire("backend-1", "origin-1").await.unwrap();
+
+        let waiter = {
+            let pools = Arc::clone(&pools);
+            tokio::spawn(async move { pools.acquire("backend-1", "origin-1").await.is_ok() })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(stats(&pools).pending, 1);
+
+        drop(held);
+        assert!(waiter.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_prune_idle_pools() {
+        let pools = pools(1, 0);
+        let held = pools.acquire("backend-1", "origin-1").await.unwrap();
+        drop(pools.acquire("backend-1", "origin-2").await.unwrap());
+
+        assert_eq!(pools.prune(), 1);
+        assert_eq!(stats(&pools).origin_id, "origin-1");
+        drop(held);
+        assert_eq!(pools.prune(), 1);
+        assert!(pools.stats().is_empty());
+    }
+}
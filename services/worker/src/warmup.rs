@@ -0,0 +1,365 @@
+//! Warm-up Snapshots
+//!
+//! A new worker starts without traffic baselines, allowlist learning or
+//! source reputation, so it is trigger-happy or blind until it has observed
+//! enough traffic of its own. Workers that have been serving for a while
+//! publish what they learned to a snapshot of their region in Redis; a
+//! worker joining the fleet imports the freshest snapshot of its region at
+//! startup.
+//!
+//! Imported state is merged with what the worker observes itself and never
+//! overrides it: learned handshakes and reputation scores keep the larger
+//! value, baselines only seed hosts without one. Snapshots can also be
+//! exported and imported by hand through the local API, e.g. to seed a node
+//! without access to the shared Redis.
+
+use crate::ebpf::learning::LearnedBackend;
+use crate::proxy::anomaly::OriginAnomalyDetector;
+use crate::reputation::{ReputationEngine, Score};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use pistonprotection_common::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Version of the snapshot format; snapshots of other versions are ignored
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Default interval between published snapshots
+pub const DEFAULT_PUBLISH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Default uptime before a worker publishes, so fresh workers do not
+/// replace the snapshot with what little they have seen
+pub const DEFAULT_MIN_UPTIME: Duration = Duration::from_secs(900);
+
+/// Default age from which a snapshot is too old to import
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// Region of workers that do not report one
+const DEFAULT_REGION: &str = "default";
+
+/// Warm-up settings
+#[derive(Debug, Clone)]
+pub struct WarmupConfig {
+    /// Region whose snapshot is imported and published
+    pub region: String,
+    pub publish_interval: Duration,
+    pub min_uptime: Duration,
+    pub max_age: Duration,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            region: DEFAULT_REGION.to_string(),
+            publish_interval: DEFAULT_PUBLISH_INTERVAL,
+            min_uptime: DEFAULT_MIN_UPTIME,
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+}
+
+impl WarmupConfig {
+    /// Load the settings from the worker region and `PISTON_WARMUP_*`
+    /// environment variables.
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+
+        if let Ok(region) = std::env::var("PISTON_WORKER_REGION")
+            && !region.is_empty()
+        {
+            config.region = region;
+        }
+        if let Some(secs) = env_parse::<u64>("PISTON_WARMUP_PUBLISH_INTERVAL_SECS") {
+            config.publish_interval = Duration::from_secs(secs.max(30));
+        }
+        if let Some(secs) = env_parse("PISTON_WARMUP_MIN_UPTIME_SECS") {
+            config.min_uptime = Duration::from_secs(secs);
+        }
+        if let Some(secs) = env_parse::<u64>("PISTON_WARMUP_MAX_AGE_SECS") {
+            config.max_age = Duration::from_secs(secs.max(60));
+        }
+
+        config
+    }
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+/// Redis key of the snapshot of a region
+pub fn snapshot_key(region: &str) -> String {
+    format!("warmup_snapshot:{}", region)
+}
+
+/// Learned state of a worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupSnapshot {
+    pub version: u32,
+    pub region: String,
+    /// Worker the snapshot was taken on
+    pub worker_id: Option<String>,
+    pub exported_at: DateTime<Utc>,
+    /// Allowlist learning of each backend
+    pub learning: Vec<LearnedBackend>,
+    /// Local reputation scores, empty when scores are shared through Redis
+    pub reputation: Vec<(IpAddr, Score)>,
+    /// Baseline inbound rate of each HTTP proxy host
+    pub baselines: Vec<(String, f64)>,
+}
+
+impl WarmupSnapshot {
+    /// Take a snapshot of the learned state.
+    pub fn collect(
+        region: &str,
+        worker_id: Option<String>,
+        learning: Vec<LearnedBackend>,
+        reputation: &ReputationEngine,
+        anomalies: &OriginAnomalyDetector,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            region: region.to_string(),
+            worker_id,
+            exported_at: now,
+            learning,
+            reputation: reputation.export_scores(now.timestamp()),
+            baselines: anomalies.baselines(),
+        }
+    }
+
+    /// Whether the snapshot holds nothing worth importing
+    pub fn is_empty(&self) -> bool {
+        self.learning
+            .iter()
+            .all(|backend| backend.sources.is_empty())
+            && self.reputation.is_empty()
+            && self.baselines.is_empty()
+    }
+
+    fn validate(&self, max_age: Duration, now: DateTime<Utc>) -> Result<()> {
+        if self.version != SNAPSHOT_VERSION {
+            return Err(Error::Validation(format!(
+                "Unsupported warm-up snapshot version {}",
+                self.version
+            )));
+        }
+        let age = (now - self.exported_at).num_seconds();
+        if age > max_age.as_secs() as i64 {
+            return Err(Error::Validation(format!(
+                "Warm-up snapshot is {}s old, older than {}s",
+                age,
+                max_age.as_secs()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a snapshot import
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WarmupReport {
+    pub imported_at: DateTime<Utc>,
+    pub source_worker_id: Option<String>,
+    pub exported_at: DateTime<Utc>,
+    /// Backends whose learning was imported
+    pub learning_backends: usize,
+    /// Sources whose reputation score was raised
+    pub reputation_sources: usize,
+    /// Hosts seeded with a baseline
+    pub baselines: usize,
+}
+
+/// Warm-up state of the worker
+#[derive(Debug, Clone, Serialize)]
+pub struct WarmupStatus {
+    pub region: String,
+    pub uptime_secs: i64,
+    /// Whether the worker publishes snapshots of its region
+    pub publishing: bool,
+    pub last_import: Option<WarmupReport>,
+    pub last_published: Option<DateTime<Utc>>,
+}
+
+/// Imports and publishes the warm-up snapshots of a worker.
+pub struct Warmup {
+    config: WarmupConfig,
+    started_at: DateTime<Utc>,
+    last_import: RwLock<Option<WarmupReport>>,
+    last_published: RwLock<Option<DateTime<Utc>>>,
+}
+
+impl Warmup {
+    /// Create the warm-up state of a worker started at `started_at`.
+    pub fn new(config: WarmupConfig, started_at: DateTime<Utc>) -> Self {
+        Self {
+            config,
+            started_at,
+            last_import: RwLock::new(None),
+            last_published: RwLock::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &WarmupConfig {
+        &self.config
+    }
+
+    /// Redis key of the snapshot of the worker's region.
+    pub fn key(&self) -> String {
+        snapshot_key(&self.config.region)
+    }
+
+    /// Whether the worker has served long enough to publish its state.
+    pub fn seasoned(&self, now: DateTime<Utc>) -> bool {
+        (now - self.started_at).num_seconds() >= self.config.min_uptime.as_secs() as i64
+    }
+
+    /// Whether a snapshot has been imported on this worker.
+    pub fn imported(&self) -> bool {
+        self.last_import.read().is_some()
+    }
+
+    /// Whether `snapshot` should replace the snapshot of the region.
+    pub fn should_publish(&self, snapshot: &WarmupSnapshot, now: DateTime<Utc>) -> bool {
+        self.seasoned(now) && !snapshot.is_empty()
+    }
+
+    /// Record a published snapshot.
+    pub fn published(&self, snapshot: &WarmupSnapshot) {
+        *self.last_published.write() = Some(snapshot.exported_at);
+    }
+
+    /// Merge a snapshot into the learned state.
+    ///
+    /// The learning is handed to `import_learning`, which returns the number
+    /// of backends imported. Snapshots of another format or older than the
+    /// maximum age are rejected.
+    pub fn import<L>(
+        &self,
+        snapshot: WarmupSnapshot,
+        reputation: &ReputationEngine,
+        anomalies: &OriginAnomalyDetector,
+        now: DateTime<Utc>,
+        import_learning: L,
+    ) -> Result<WarmupReport>
+    where
+        L: FnOnce(Vec<LearnedBackend>) -> usize,
+    {
+        snapshot.validate(self.config.max_age, now)?;
+
+        let report = WarmupReport {
+            imported_at: now,
+            source_worker_id: snapshot.worker_id,
+            exported_at: snapshot.exported_at,
+            reputation_sources: reputation.import_scores(&snapshot.reputation, now.timestamp()),
+            baselines: anomalies.seed_baselines(&snapshot.baselines),
+            learning_backends: import_learning(snapshot.learning),
+        };
+        *self.last_import.write() = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Warm-up state at `now`.
+    pub fn status(&self, now: DateTime<Utc>) -> WarmupStatus {
+        WarmupStatus {
+            region: self.config.region.clone(),
+            uptime_secs: (now - self.started_at).num_seconds(),
+            publishing: self.seasoned(now),
+            last_import: self.last_import.read().clone(),
+            last_published: *self.last_published.read(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::anomaly::AnomalyConfig;
+    use crate::reputation::ReputationConfig;
+
+    fn snapshot(exported_at: DateTime<Utc>) -> WarmupSnapshot {
+        WarmupSnapshot {
+            version: SNAPSHOT_VERSION,
+            region: "eu-west".to_string(),
+            worker_id: Some("worker-1".to_string()),
+            exported_at,
+            learning: vec![LearnedBackend {
+                backend_id: "b1".to_string(),
+                started_at: exported_at,
+                training_secs: 3600,
+                sources: vec![("198.51.100.1".parse().unwrap(), 4)],
+            }],
+            reputation: vec![(
+                "198.51.100.7".parse().unwrap(),
+                Score {
+                    value: 60.0,
+                    updated: exported_at.timestamp(),
+                },
+            )],
+            baselines: vec![("example.com".to_string(), 20.0)],
+        }
+    }
+
+    #[test]
+    fn test_import() {
+        let now = Utc::now();
+        let warmup = Warmup::new(WarmupConfig::default(), now);
+        let reputation = ReputationEngine::new(ReputationConfig::default(), None);
+        let anomalies = OriginAnomalyDetector::new(AnomalyConfig::default());
+
+        let mut learned = Vec::new();
+        let report = warmup
+            .import(snapshot(now), &reputation, &anomalies, now, |learning| {
+                learned = learning;
+                learned.len()
+            })
+            .unwrap();
+        assert_eq!(report.learning_backends, 1);
+        assert_eq!(report.reputation_sources, 1);
+        assert_eq!(report.baselines, 1);
+        assert_eq!(learned[0].backend_id, "b1");
+        assert!(warmup.imported());
+        assert_eq!(
+            anomalies.baselines(),
+            vec![("example.com".to_string(), 20.0)]
+        );
+    }
+
+    #[test]
+    fn test_rejects_stale_snapshot() {
+        let now = Utc::now();
+        let warmup = Warmup::new(WarmupConfig::default(), now);
+        let reputation = ReputationEngine::new(ReputationConfig::default(), None);
+        let anomalies = OriginAnomalyDetector::new(AnomalyConfig::default());
+
+        let stale = snapshot(now - chrono::Duration::seconds(7200));
+        let result = warmup.import(stale, &reputation, &anomalies, now, |_| 0);
+        assert!(matches!(result, Err(Error::Validation(_))));
+
+        let mut future = snapshot(now);
+        future.version = SNAPSHOT_VERSION + 1;
+        let result = warmup.import(future, &reputation, &anomalies, now, |_| 0);
+        assert!(matches!(result, Err(Error::Validation(_))));
+        assert!(!warmup.imported());
+        assert!(anomalies.baselines().is_empty());
+    }
+
+    #[test]
+    fn test_fresh_worker_does_not_publish() {
+        let started = Utc::now();
+        let warmup = Warmup::new(WarmupConfig::default(), started);
+
+        assert!(!warmup.should_publish(&snapshot(started), started));
+        let later = started + chrono::Duration::seconds(DEFAULT_MIN_UPTIME.as_secs() as i64);
+        assert!(warmup.should_publish(&snapshot(later), later));
+
+        let mut empty = snapshot(later);
+        empty.learning[0].sources.clear();
+        empty.reputation.clear();
+        empty.baselines.clear();
+        assert!(!warmup.should_publish(&empty, later));
+    }
+}